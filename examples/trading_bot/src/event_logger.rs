@@ -0,0 +1,125 @@
+//! # Wallet Event Logger
+//!
+//! This example subscribes to an `OrderWallet`'s events and writes each one
+//! to stdout as a line of JSON, the way a monitoring stack would ingest them.
+//!
+//! ## Behaviour
+//! - Funds a trading account, opens a market order, queries it, closes it and
+//!   rotates the settled account, so every kind of account and order event
+//!   shows up once
+//! - Logs events from a separate task while the orders run
+//! - Reports events skipped because the logger fell behind
+//!
+//! ## Usage
+//! ```bash
+//! cargo run --bin event_logger -- --amount 5000 --leverage 2
+//! ```
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use log::{info, warn};
+use nyks_wallet::relayer_module::events::WalletEvent;
+use nyks_wallet::relayer_module::order_wallet::OrderWallet;
+use nyks_wallet::relayer_module::relayer_types::{OrderType, PositionType};
+use std::time::Duration;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::time::sleep;
+
+/// Event logger command line arguments
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Sats to move into the trading account
+    #[arg(long, default_value = "5000")]
+    amount: u64,
+
+    /// Leverage of the market order
+    #[arg(long, default_value = "2")]
+    leverage: u64,
+
+    /// Seconds to hold the position before closing it
+    #[arg(long, default_value = "10")]
+    hold: u64,
+}
+
+/// Print every event as one line of JSON until the wallet is dropped.
+async fn log_events(mut events: Receiver<WalletEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => match serde_json::to_string(&event) {
+                Ok(line) => println!("{}", line),
+                Err(e) => warn!("Failed to serialize event: {}", e),
+            },
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Logger fell behind; skipped {} events", skipped)
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter_level(log::LevelFilter::Info)
+        .init();
+    dotenv::dotenv().ok();
+
+    let args = Args::parse();
+
+    let mut order_wallet =
+        OrderWallet::new(None).map_err(|e| anyhow!("Failed to create OrderWallet: {}", e))?;
+    let logger = tokio::spawn(log_events(order_wallet.subscribe_events()));
+
+    // Get test tokens
+    let _ = nyks_wallet::wallet::get_test_tokens(&mut order_wallet.wallet).await?;
+
+    let (_, index) = order_wallet
+        .funding_to_trading(args.amount)
+        .await
+        .map_err(|e| anyhow!("Failed to fund trading account: {}", e))?;
+    let price = order_wallet
+        .relayer_api_client
+        .btc_usd_price()
+        .await
+        .map_err(|e| anyhow!("Failed to fetch BTC/USD price: {}", e))?
+        .price as u64;
+    order_wallet
+        .open_trader_order(
+            index,
+            OrderType::MARKET,
+            PositionType::LONG,
+            price,
+            args.leverage,
+        )
+        .await
+        .map_err(|e| anyhow!("Failed to open trader order: {}", e))?;
+
+    sleep(Duration::from_secs(args.hold)).await;
+    // A query that sees a new status emits `order_status_changed`.
+    let order = order_wallet
+        .query_trader_order(index)
+        .await
+        .map_err(|e| anyhow!("Failed to query trader order: {}", e))?;
+    info!("Order on account {} is {:?}", index, order.order_status);
+
+    order_wallet
+        .close_trader_order(index, OrderType::MARKET, 0.0)
+        .await
+        .map_err(|e| anyhow!("Failed to close trader order: {}", e))?;
+    sleep(Duration::from_secs(5)).await;
+    order_wallet
+        .unlock_trader_order(index)
+        .await
+        .map_err(|e| anyhow!("Failed to unlock settled account: {}", e))?;
+    let new_index = order_wallet
+        .trading_to_trading(index)
+        .await
+        .map_err(|e| anyhow!("Failed to rotate account: {}", e))?;
+    info!("Rotated account {} -> {}", index, new_index);
+
+    // Dropping the wallet closes the channel, which ends the logger.
+    drop(order_wallet);
+    logger.await?;
+    Ok(())
+}
@@ -19,6 +19,7 @@ use clap::Parser;
 use log::{error, info, warn};
 use nyks_wallet::relayer_module::order_wallet::{AccountIndex, OrderWallet};
 use nyks_wallet::relayer_module::relayer_types::{LendOrder, OrderStatus};
+use nyks_wallet::relayer_module::status;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, time::Duration};
 use tokio::time::{interval, sleep};
@@ -143,38 +144,26 @@ impl LendingBot {
     async fn initialize_accounts(&mut self, order_wallet: &mut OrderWallet) -> Result<()> {
         info!("Initializing lending accounts using ZkOS pattern...");
 
-        // Step 1: Create a master trading account with the full capital
-        let (tx_result, master_account) = order_wallet
-            .funding_to_trading(self.config.initial_capital)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to create master lending account: {}", e))?;
-
-        if tx_result.code != 0 {
-            return Err(anyhow::anyhow!(
-                "Master account creation failed with code: {}",
-                tx_result.code
-            ));
-        }
-
-        info!(
-            "Created master lending account {} with {} sats (tx: {})",
-            master_account, self.config.initial_capital, tx_result.tx_hash
-        );
-
-        // Step 2: Split the master account into multiple smaller accounts for lending
+        // Fund one account per position straight from the wallet: the mints
+        // share one chain transaction and the UTXOs are fetched together,
+        // instead of funding a master account and splitting it.
         let account_count = self.config.max_positions;
         let capital_per_account = self.config.initial_capital / account_count as u64;
-        let splits = vec![capital_per_account; account_count as usize];
+        let amounts = vec![capital_per_account; account_count as usize];
 
         info!(
-            "Splitting master account into {} accounts with {} sats each",
+            "Funding {} lending accounts with {} sats each",
             account_count, capital_per_account
         );
 
-        let accounts = order_wallet
-            .trading_to_trading_multiple_accounts(master_account, splits)
+        let (txs, accounts) = order_wallet
+            .funding_to_trading_multiple(amounts)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to split accounts: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("Failed to fund lending accounts: {}", e))?;
+
+        for tx in &txs {
+            info!("Funding tx: {}", tx.tx_hash);
+        }
 
         // Store all accounts as available for lending
         self.available_accounts = accounts;
@@ -212,7 +201,7 @@ impl LendingBot {
             monitoring_timer.tick().await;
 
             // Update market data
-            if let Err(e) = self.update_market_data().await {
+            if let Err(e) = self.update_market_data(order_wallet).await {
                 error!("Error updating market data: {}", e);
                 continue;
             }
@@ -233,23 +222,24 @@ impl LendingBot {
 
             // Log status periodically
             if self.stats.total_positions_opened > 0 && self.stats.total_positions_opened % 5 == 0 {
-                self.log_status();
+                match order_wallet.status_snapshot().await {
+                    Ok(snapshot) => info!("{}", status::render_compact(&snapshot)),
+                    Err(e) => warn!("Wallet status unavailable: {}", e),
+                }
+                info!("{}", self.status_line());
             }
         }
     }
 
-    /// Update lending market data
-    async fn update_market_data(&mut self) -> Result<()> {
-        // In a real implementation, you would:
-        // 1. Query current lending rates from the protocol
-        // 2. Analyze market utilization and demand
-        // 3. Monitor competitor rates and strategies
-        // 4. Calculate optimal lending rates
-
-        // For demonstration, simulate market data
-        let base_rate = 0.08; // 8% base rate
-        let rate_variance = (rand::random::<f64>() - 0.5) * 0.02; // ±1% variance
-        let current_rate = base_rate + rate_variance;
+    /// Update lending market data from the relayer's lend pool
+    async fn update_market_data(&mut self, order_wallet: &OrderWallet) -> Result<()> {
+        // Yield a new deposit of the configured size would earn over a day,
+        // after diluting the pool
+        let estimate = order_wallet
+            .estimate_lend_yield(self.config.lending_amount, 24)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to estimate lend yield: {}", e))?;
+        let current_rate = estimate.estimated_apy_pct / 100.0;
 
         self.market_data.current_lending_rates.push(current_rate);
 
@@ -262,16 +252,17 @@ impl LendingBot {
         self.market_data.average_rate = self.market_data.current_lending_rates.iter().sum::<f64>()
             / self.market_data.current_lending_rates.len() as f64;
 
-        // Simulate market utilization
-        self.market_data.market_utilization = 0.6 + (rand::random::<f64>() - 0.5) * 0.3;
-        self.market_data.total_available_liquidity = 1_000_000 + rand::random::<u64>() % 500_000;
-        self.market_data.estimated_demand = 800_000 + rand::random::<u64>() % 400_000;
+        self.market_data.market_utilization = estimate.utilization;
+        self.market_data.total_available_liquidity = estimate.pool_value as u64;
+        // Open interest the pool is backing
+        self.market_data.estimated_demand = (estimate.pool_value * estimate.utilization) as u64;
 
         info!(
-            "Market update - Rate: {:.3}%, Utilization: {:.1}%, Avg Rate: {:.3}%",
+            "Market update - Rate: {:.3}%, Utilization: {:.1}%, Avg Rate: {:.3}%, Est. daily interest: {:.0} sats",
             current_rate * 100.0,
             self.market_data.market_utilization * 100.0,
-            self.market_data.average_rate * 100.0
+            self.market_data.average_rate * 100.0,
+            estimate.estimated_interest
         );
 
         Ok(())
@@ -284,7 +275,7 @@ impl LendingBot {
         account_index: AccountIndex,
     ) -> Result<u64> {
         // Try to get balance from ZkAccountDB
-        if let Some(balance) = order_wallet.zk_accounts.get_balance(&account_index) {
+        if let Ok(balance) = order_wallet.zk_accounts.get_balance(&account_index) {
             Ok(balance)
         } else {
             Err(anyhow::anyhow!(
@@ -339,8 +330,23 @@ impl LendingBot {
                     Ok(lend_order) => {
                         use nyks_wallet::relayer_module::relayer_types::OrderStatus;
 
-                        // Update position data
-                        position.current_value = lend_order.balance as u64;
+                        // Update position data; open positions are valued from the
+                        // pool state rather than waiting for settlement
+                        position.current_value = match lend_order.order_status {
+                            OrderStatus::FILLED => {
+                                match order_wallet.lend_position_value(account_index).await {
+                                    Ok(value) => value.current_value as u64,
+                                    Err(e) => {
+                                        warn!(
+                                            "Failed to value lend position on account {}: {}",
+                                            account_index, e
+                                        );
+                                        lend_order.balance as u64
+                                    }
+                                }
+                            }
+                            _ => lend_order.balance as u64,
+                        };
                         position.accrued_interest = position
                             .current_value
                             .saturating_sub(position.principal_amount);
@@ -762,40 +768,19 @@ impl LendingBot {
         }
     }
 
-    /// Log current lending bot status
-    fn log_status(&self) {
-        info!("=== Lending Bot Status ===");
-
-        // Account information
-        info!("Available accounts: {}", self.available_accounts.len());
-        let total_available_balance: u64 = self
-            .available_accounts
-            .iter()
-            .map(|(_, balance)| balance)
-            .sum();
-        info!("Total available balance: {} sats", total_available_balance);
-
-        info!("Active positions: {}", self.active_positions.len());
-        info!("Total lent: {} sats", self.stats.total_principal_lent);
-        info!(
-            "Total interest earned: {} sats",
-            self.stats.total_interest_earned
-        );
-        info!(
-            "Average lending rate: {:.3}%",
-            self.stats.average_lending_rate * 100.0
-        );
-        info!(
-            "Current market rate: {:.3}%",
-            self.market_data.average_rate * 100.0
-        );
-        info!("Estimated APY: {:.2}%", self.stats.current_apy * 100.0);
-        info!("Total yield: {:.2}%", self.stats.total_yield * 100.0);
-        info!(
-            "Market utilization: {:.1}%",
+    /// This bot's counters, logged next to the wallet's
+    /// [`status::render_compact`] line.
+    fn status_line(&self) -> String {
+        format!(
+            "lent {} sats | interest {} sats | avg rate {:.3}% | market rate {:.3}% | APY {:.2}% | yield {:.2}% | utilization {:.1}%",
+            self.stats.total_principal_lent,
+            self.stats.total_interest_earned,
+            self.stats.average_lending_rate * 100.0,
+            self.market_data.average_rate * 100.0,
+            self.stats.current_apy * 100.0,
+            self.stats.total_yield * 100.0,
             self.market_data.market_utilization * 100.0
-        );
-        info!("===========================");
+        )
     }
 
     /// Close all lending positions
@@ -816,7 +801,11 @@ impl LendingBot {
             }
         }
 
-        self.log_status();
+        match order_wallet.status_snapshot().await {
+            Ok(snapshot) => info!("Final status:\n{}", status::render(&snapshot)),
+            Err(e) => warn!("Wallet status unavailable: {}", e),
+        }
+        info!("{}", self.status_line());
         info!("Lending bot shutdown complete");
 
         Ok(())
@@ -851,8 +840,12 @@ async fn main() -> Result<()> {
     let wallet_exists = OrderWallet::get_wallet_id_from_db(&wallet_id, None)
         .map_err(|e| anyhow::anyhow!("Failed to check wallet ID existence: {}", e))?;
     if wallet_exists {
-        order_wallet = OrderWallet::load_from_db(wallet_id, None, None)
+        let (loaded, drift) = OrderWallet::load_from_db(wallet_id, None, None)
             .map_err(|e| anyhow::anyhow!("Failed to load wallet from database: {}", e))?;
+        if drift.is_significant() {
+            warn!("Config drift since the wallet was saved: {}", drift);
+        }
+        order_wallet = loaded;
     } else {
         order_wallet = OrderWallet::new(None)
             .map_err(|e| anyhow::anyhow!("Failed to create OrderWallet: {}", e))?;
@@ -23,6 +23,7 @@ use nyks_wallet::relayer_module::order_wallet::{AccountIndex, OrderWallet, Reque
 use nyks_wallet::relayer_module::relayer_types::{
     IOType, OrderStatus, OrderType, PositionType, TraderOrder,
 };
+use nyks_wallet::relayer_module::status;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, time::Duration};
 use tokio::time::{interval, sleep};
@@ -227,7 +228,11 @@ impl TradingBot {
 
             // Log current status periodically
             if trade_count % 10 == 0 {
-                self.log_status();
+                match order_wallet.status_snapshot().await {
+                    Ok(snapshot) => info!("{}", status::render_compact(&snapshot)),
+                    Err(e) => warn!("Wallet status unavailable: {}", e),
+                }
+                info!("{}", self.status_line());
             }
         }
 
@@ -491,18 +496,20 @@ impl TradingBot {
         Ok(())
     }
 
-    /// Log current bot status
-    fn log_status(&mut self) {
-        info!("=== Trading Bot Status ===");
-        info!("Strategy: {:?}", self.strategy);
-        info!("Total trades: {}", self.stats.total_trades);
-        info!("Active positions: {}", self.active_positions.len());
-        info!("Available capital: {} sats", self.available_capital);
+    /// This bot's counters, logged next to the wallet's
+    /// [`status::render_compact`] line.
+    fn status_line(&mut self) -> String {
         if self.stats.total_trades > 0 {
             self.stats.win_rate = self.stats.winning_trades as f64 / self.stats.total_trades as f64;
-            info!("Win rate: {:.2}%", self.stats.win_rate * 100.0);
         }
-        info!("=========================");
+        format!(
+            "{:?} | trades {} | win rate {:.2}% | active positions {} | capital {} sats",
+            self.strategy,
+            self.stats.total_trades,
+            self.stats.win_rate * 100.0,
+            self.active_positions.len(),
+            self.available_capital
+        )
     }
 }
 
@@ -562,7 +569,11 @@ async fn main() -> Result<()> {
         .context("Trading bot execution failed")?;
 
     info!("Trading bot finished");
-    bot.log_status();
+    match order_wallet.status_snapshot().await {
+        Ok(snapshot) => info!("Final status:\n{}", status::render(&snapshot)),
+        Err(e) => warn!("Wallet status unavailable: {}", e),
+    }
+    info!("{}", bot.status_line());
 
     Ok(())
 }
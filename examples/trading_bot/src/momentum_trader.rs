@@ -4,7 +4,7 @@
 //! and market analysis to identify trend-following opportunities.
 //!
 //! ## Strategy Overview
-//! - Uses moving averages and RSI for trend identification
+//! - Uses moving averages and RSI on the relayer's candles for trend identification
 //! - Implements position sizing based on signal strength
 //! - Includes stop-loss and take-profit management
 //! - Tracks multiple timeframes for confirmation
@@ -18,7 +18,11 @@ use anyhow::{Context, Result};
 use clap::Parser;
 use log::{error, info, warn};
 use nyks_wallet::relayer_module::order_wallet::{AccountIndex, OrderWallet};
-use nyks_wallet::relayer_module::relayer_types::{IOType, OrderStatus, OrderType, PositionType};
+use nyks_wallet::relayer_module::relayer_api::RelayerJsonRpcClient;
+use nyks_wallet::relayer_module::relayer_types::{
+    CandleInterval, IOType, OrderStatus, OrderType, PositionType,
+};
+use nyks_wallet::relayer_module::status;
 use serde::{Deserialize, Serialize};
 use std::{collections::VecDeque, time::Duration};
 use tokio::time::{interval, sleep};
@@ -55,6 +59,10 @@ struct Args {
     #[arg(short, long, default_value = "60")]
     analysis_interval: u64,
 
+    /// Candle interval the indicators run on: 1m, 5m, 15m, 1h, 4h, 1d
+    #[arg(long, default_value = "1m")]
+    candle_interval: CandleInterval,
+
     /// Stop loss percentage (0.05 = 5%)
     #[arg(long, default_value = "0.05")]
     stop_loss: f64,
@@ -97,6 +105,7 @@ struct MomentumConfig {
     max_leverage: u64,
     initial_capital: u64,
     analysis_interval: Duration,
+    candle_interval: CandleInterval,
     stop_loss_pct: f64,
     take_profit_pct: f64,
     paper_trading: bool,
@@ -129,6 +138,8 @@ struct TechnicalIndicators {
     slow_ma: Option<f64>,
     rsi: Option<f64>,
     momentum: Option<f64>,
+    /// Long share of the open interest, from the relayer's position sizes
+    long_share: Option<f64>,
     signal_strength: f64,
     trend_direction: TrendDirection,
 }
@@ -168,6 +179,7 @@ impl MomentumTrader {
                 max_leverage: args.max_leverage,
                 initial_capital: args.initial_capital,
                 analysis_interval: Duration::from_secs(args.analysis_interval),
+                candle_interval: args.candle_interval,
                 stop_loss_pct: args.stop_loss,
                 take_profit_pct: args.take_profit,
                 paper_trading: args.paper_trading,
@@ -185,39 +197,27 @@ impl MomentumTrader {
     async fn initialize_accounts(&mut self, order_wallet: &mut OrderWallet) -> Result<()> {
         info!("Initializing momentum trading accounts using ZkOS pattern...");
 
-        // Step 1: Create a master trading account with the full capital
-        let (tx_result, master_account) = order_wallet
-            .funding_to_trading(self.config.initial_capital)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to create master trading account: {}", e))?;
-
-        if tx_result.code != 0 {
-            return Err(anyhow::anyhow!(
-                "Master account creation failed with code: {}",
-                tx_result.code
-            ));
-        }
-
-        info!(
-            "Created master trading account {} with {} sats (tx: {})",
-            master_account, self.config.initial_capital, tx_result.tx_hash
-        );
-
-        // Step 2: Split the master account into multiple accounts for position rotation
+        // Fund one account per position straight from the wallet: the mints
+        // share one chain transaction and the UTXOs are fetched together,
+        // instead of funding a master account and splitting it.
         // Create 3 accounts to allow for position rotation
         let account_count = 3;
         let capital_per_account = self.config.initial_capital / account_count;
-        let splits = vec![capital_per_account; account_count as usize];
+        let amounts = vec![capital_per_account; account_count as usize];
 
         info!(
-            "Splitting master account into {} accounts with {} sats each",
+            "Funding {} trading accounts with {} sats each",
             account_count, capital_per_account
         );
 
-        let accounts = order_wallet
-            .trading_to_trading_multiple_accounts(master_account, splits)
+        let (txs, accounts) = order_wallet
+            .funding_to_trading_multiple(amounts)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to split accounts: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("Failed to fund trading accounts: {}", e))?;
+
+        for tx in &txs {
+            info!("Funding tx: {}", tx.tx_hash);
+        }
 
         // Store all accounts as available for trading
         self.available_accounts = accounts;
@@ -254,7 +254,10 @@ impl MomentumTrader {
             analysis_timer.tick().await;
 
             // Update market data
-            if let Err(e) = self.update_market_data().await {
+            if let Err(e) = self
+                .update_market_data(&order_wallet.relayer_api_client)
+                .await
+            {
                 error!("Error updating market data: {}", e);
                 continue;
             }
@@ -275,53 +278,55 @@ impl MomentumTrader {
 
             // Log status periodically
             if self.stats.total_trades > 0 && self.stats.total_trades % 10 == 0 {
-                self.log_status();
+                match order_wallet.status_snapshot().await {
+                    Ok(snapshot) => info!("{}", status::render_compact(&snapshot)),
+                    Err(e) => warn!("Wallet status unavailable: {}", e),
+                }
+                info!("{}", self.status_line());
             }
         }
     }
 
-    /// Update market data (simulated for demonstration)
-    async fn update_market_data(&mut self) -> Result<()> {
-        // In a real implementation, you would:
-        // 1. Fetch data from multiple exchanges
-        // 2. Calculate VWAP and other price metrics
-        // 3. Get order book data for volume analysis
-        // 4. Handle websocket feeds for real-time data
-
-        // For demonstration, simulate price data with some realistic movement
-        let current_time = chrono::Utc::now();
-
-        let base_price = if let Some(last_point) = self.price_history.back() {
-            last_point.price
-        } else {
-            50000.0 // Starting price
-        };
-
-        // Simulate price movement with some trending behavior
-        let trend_factor = match self.indicators.trend_direction {
-            TrendDirection::Bullish => 0.0002,
-            TrendDirection::Bearish => -0.0002,
-            TrendDirection::Sideways => 0.0,
-        };
-
-        let random_factor = (rand::random::<f64>() - 0.5) * 0.002; // ±0.1% random
-        let price_change = trend_factor + random_factor;
-        let new_price = base_price * (1.0 + price_change);
-
-        let volume = 1000.0 + rand::random::<f64>() * 500.0; // Random volume
-
-        let price_point = PricePoint {
-            timestamp: current_time,
-            price: new_price,
-            volume,
-        };
-
-        self.price_history.push_back(price_point);
-
+    /// Update market data from the relayer's candles
+    async fn update_market_data(&mut self, client: &RelayerJsonRpcClient) -> Result<()> {
         // Keep only necessary history
         let max_history = self.config.slow_ma_period.max(self.config.rsi_period) * 2;
-        while self.price_history.len() > max_history {
-            self.price_history.pop_front();
+        let interval = self.config.candle_interval;
+        let since = chrono::Utc::now() - interval.duration() * max_history as i32;
+
+        let candles = client
+            .candles(interval, since, max_history)
+            .await
+            .context("Failed to fetch candles")?;
+        if candles.is_empty() {
+            return Err(anyhow::anyhow!("No candles since {}", since));
+        }
+
+        // Candles come oldest first; intervals without trades are missing,
+        // so indicators run on the candles that exist.
+        self.price_history = candles
+            .into_iter()
+            .map(|candle| PricePoint {
+                timestamp: candle.started_at,
+                price: candle.close,
+                volume: candle.btc_volume,
+            })
+            .collect();
+
+        // Open interest sentiment from the market snapshot; a failed part
+        // leaves the previous value in place
+        let snapshot = client.market_snapshot().await;
+        if let Some(size) = &snapshot.position_size {
+            self.indicators.long_share = size.long_share();
+        }
+        if let Some(price) = &snapshot.price {
+            info!(
+                "Live BTC price: {:.2}, open interest long share: {}",
+                price.price,
+                self.indicators
+                    .long_share
+                    .map_or("n/a".to_string(), |share| format!("{:.1}%", share * 100.0)),
+            );
         }
 
         Ok(())
@@ -579,7 +584,7 @@ impl MomentumTrader {
         account_index: AccountIndex,
     ) -> Result<u64> {
         // Try to get balance from ZkAccountDB
-        if let Some(balance) = order_wallet.zk_accounts.get_balance(&account_index) {
+        if let Ok(balance) = order_wallet.zk_accounts.get_balance(&account_index) {
             Ok(balance)
         } else {
             Err(anyhow::anyhow!(
@@ -919,55 +924,48 @@ impl MomentumTrader {
         Ok(())
     }
 
-    /// Log current trader status
-    fn log_status(&mut self) {
-        info!("=== Momentum Trader Status ===");
-
-        // Account information
-        info!("Available accounts: {}", self.available_accounts.len());
-        let total_available_balance: u64 = self
-            .available_accounts
-            .iter()
-            .map(|(_, balance)| balance)
-            .sum();
-        info!("Total available balance: {} sats", total_available_balance);
-
-        // Position information
-        if let Some(position) = &self.current_position {
-            info!(
-                "Current position: {:?} at {:.2} using {} sats on account {}",
-                position.position_type, position.entry_price, position.size, position.account_index
-            );
-        } else {
-            info!("No current position");
+    /// This bot's counters, logged next to the wallet's
+    /// [`status::render_compact`] line.
+    fn status_line(&mut self) -> String {
+        if self.stats.total_trades > 0 {
+            self.stats.win_rate = self.stats.winning_trades as f64 / self.stats.total_trades as f64;
         }
-
-        // Technical analysis
-        info!("Signal strength: {:.3}", self.indicators.signal_strength);
-        info!("Trend: {:?}", self.indicators.trend_direction);
-        if let (Some(fast_ma), Some(slow_ma), Some(rsi)) = (
+        let position = match &self.current_position {
+            Some(position) => format!(
+                "{:?} at {:.2} with {} sats on account {}",
+                position.position_type, position.entry_price, position.size, position.account_index
+            ),
+            None => "none".to_string(),
+        };
+        let averages = match (
             self.indicators.fast_ma,
             self.indicators.slow_ma,
             self.indicators.rsi,
         ) {
-            info!(
-                "Fast MA: {:.2}, Slow MA: {:.2}, RSI: {:.2}",
+            (Some(fast_ma), Some(slow_ma), Some(rsi)) => format!(
+                " | fast MA {:.2} slow MA {:.2} RSI {:.2}",
                 fast_ma, slow_ma, rsi
-            );
-        }
-
-        // Trading statistics
-        info!("Total trades: {}", self.stats.total_trades);
-        if self.stats.total_trades > 0 {
-            self.stats.win_rate = self.stats.winning_trades as f64 / self.stats.total_trades as f64;
-            info!("Win rate: {:.2}%", self.stats.win_rate * 100.0);
-        }
-        info!("Total P&L: {:.2}", self.stats.total_pnl);
-        info!(
-            "Max profit: {:.2}, Max drawdown: {:.2}",
-            self.stats.max_profit, self.stats.max_drawdown
-        );
-        info!("===============================");
+            ),
+            _ => String::new(),
+        };
+        let long_share = self
+            .indicators
+            .long_share
+            .map(|share| format!(" | OI long {:.1}%", share * 100.0))
+            .unwrap_or_default();
+        format!(
+            "position {} | signal {:.3} {:?}{}{} | trades {} | win rate {:.2}% | P&L {:.2} | max profit {:.2} | max drawdown {:.2}",
+            position,
+            self.indicators.signal_strength,
+            self.indicators.trend_direction,
+            long_share,
+            averages,
+            self.stats.total_trades,
+            self.stats.win_rate * 100.0,
+            self.stats.total_pnl,
+            self.stats.max_profit,
+            self.stats.max_drawdown
+        )
     }
 }
 
@@ -1042,7 +1040,11 @@ async fn main() -> Result<()> {
 
     match shutdown_result {
         Ok(_) => {
-            trader.log_status();
+            match order_wallet.status_snapshot().await {
+                Ok(snapshot) => info!("Final status:\n{}", status::render(&snapshot)),
+                Err(e) => warn!("Wallet status unavailable: {}", e),
+            }
+            info!("{}", trader.status_line());
             info!("Momentum trader finished successfully");
         }
         Err(e) => error!("Momentum trader error: {}", e),
@@ -0,0 +1,92 @@
+//! # Live Price Stream
+//!
+//! This example subscribes to the relayer's live BTC/USD price feed over
+//! WebSocket and shows how a bot pauses while the feed is stale.
+//!
+//! ## Behaviour
+//! - Prints every price update as it arrives
+//! - Reconnects with exponential backoff when the connection drops
+//! - Logs a pause when no fresh price arrived within `--max-age` seconds,
+//!   and a resume once prices flow again
+//!
+//! ## Usage
+//! ```bash
+//! cargo run --bin price_stream -- --max-age 5
+//! ```
+
+use anyhow::Result;
+use clap::Parser;
+use log::{info, warn};
+use nyks_wallet::config::RELAYER_API_RPC_SERVER_URL;
+use nyks_wallet::relayer_module::relayer_api::{FeedState, RelayerWsClient};
+use std::time::Duration;
+use tokio::time::interval;
+
+/// Price stream command line arguments
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// WebSocket endpoint; defaults to RELAYER_WS_URL, then the relayer API URL
+    #[arg(long)]
+    endpoint: Option<String>,
+
+    /// Seconds without a price before the feed counts as stale
+    #[arg(long, default_value = "5")]
+    max_age: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter_level(log::LevelFilter::Info)
+        .init();
+    dotenv::dotenv().ok();
+
+    let args = Args::parse();
+    let endpoint = args
+        .endpoint
+        .or_else(|| std::env::var("RELAYER_WS_URL").ok())
+        .unwrap_or_else(|| RELAYER_API_RPC_SERVER_URL.to_string());
+    let max_age = Duration::from_secs(args.max_age);
+
+    let client = RelayerWsClient::new(&endpoint);
+    info!(
+        "Subscribing to live BTC/USD prices at {}",
+        client.endpoint()
+    );
+    let mut prices = client.subscribe_btc_price();
+
+    let mut check = interval(Duration::from_secs(1));
+    let mut paused = true;
+
+    loop {
+        tokio::select! {
+            price = prices.next() => {
+                let Some(price) = price else {
+                    warn!("Price feed stopped");
+                    break;
+                };
+                info!("BTC/USD ${:.2} at {}", price.price, price.timestamp);
+            }
+            _ = check.tick() => {
+                let status = prices.status();
+                let stale = status.is_stale(max_age);
+                if stale && !paused {
+                    match status.state {
+                        FeedState::Reconnecting { attempt, last_error } => warn!(
+                            "⏸️  Pausing trading: reconnecting (attempt {}): {}",
+                            attempt, last_error
+                        ),
+                        _ => warn!("⏸️  Pausing trading: no price for over {:?}", max_age),
+                    }
+                    paused = true;
+                } else if !stale && paused {
+                    info!("▶️  Price feed is live; trading resumed");
+                    paused = false;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -164,7 +164,7 @@ impl RandomOrderBot {
                     batch_end
                 );
 
-                let batch_accounts = order_wallet
+                let outcome = order_wallet
                     .trading_to_trading_multiple_accounts(current_master, batch_splits)
                     .await
                     .map_err(|e| {
@@ -174,8 +174,11 @@ impl RandomOrderBot {
                             e
                         )
                     })?;
+                for (account_index, e) in &outcome.unconfirmed {
+                    warn!("Account {} is unconfirmed: {}", account_index, e);
+                }
 
-                all_accounts.extend(batch_accounts);
+                all_accounts.extend(outcome.confirmed);
             }
             // For subsequent batches, use the first account from the previous batch as the source
             // if batch_end < total_orders {
@@ -679,8 +682,12 @@ async fn main() -> Result<(), anyhow::Error> {
         .map_err(|e| anyhow::anyhow!("Failed to check wallet ID existence: {}", e))?;
 
     if wallet_exists {
-        order_wallet = OrderWallet::load_from_db(wallet_id, None, None)
+        let (loaded, drift) = OrderWallet::load_from_db(wallet_id, None, None)
             .map_err(|e| anyhow::anyhow!("Failed to load wallet from database: {}", e))?;
+        if drift.is_significant() {
+            warn!("Config drift since the wallet was saved: {}", drift);
+        }
+        order_wallet = loaded;
     } else {
         order_wallet = OrderWallet::new(None)
             .map_err(|e| anyhow::anyhow!("Failed to create OrderWallet: {}", e))?;
@@ -15,10 +15,12 @@
 //! ```
 
 use anyhow::{Context, Result};
+use chrono::{TimeDelta, Utc};
 use clap::Parser;
 use log::{error, info, warn};
 use nyks_wallet::relayer_module::order_wallet::{AccountIndex, OrderWallet};
 use nyks_wallet::relayer_module::relayer_types::{IOType, OrderStatus, OrderType, PositionType};
+use nyks_wallet::relayer_module::status;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, time::Duration};
 use tokio::time::{interval, sleep};
@@ -132,39 +134,27 @@ impl MarketMaker {
     async fn initialize_accounts(&mut self, order_wallet: &mut OrderWallet) -> Result<()> {
         info!("Initializing market maker accounts using ZkOS pattern...");
 
-        // Step 1: Create a master trading account with the full capital
-        let (tx_result, master_account) = order_wallet
-            .funding_to_trading(self.config.initial_capital)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to create master trading account: {}", e))?;
-
-        if tx_result.code != 0 {
-            return Err(anyhow::anyhow!(
-                "Master account creation failed with code: {}",
-                tx_result.code
-            ));
-        }
-
-        info!(
-            "Created master trading account {} with {} sats (tx: {})",
-            master_account, self.config.initial_capital, tx_result.tx_hash
-        );
-
-        // Step 2: Split the master account into multiple smaller accounts for market making
+        // Fund one account per position straight from the wallet: the mints
+        // share one chain transaction and the UTXOs are fetched together,
+        // instead of funding a master account and splitting it.
         // Each account gets a portion of capital for individual orders
         let account_count = 6; // Create 6 accounts for buy/sell rotation
         let capital_per_account = self.config.initial_capital / account_count;
-        let splits = vec![capital_per_account; account_count as usize];
+        let amounts = vec![capital_per_account; account_count as usize];
 
         info!(
-            "Splitting master account into {} accounts with {} sats each",
+            "Funding {} trading accounts with {} sats each",
             account_count, capital_per_account
         );
 
-        let accounts = order_wallet
-            .trading_to_trading_multiple_accounts(master_account, splits)
+        let (txs, accounts) = order_wallet
+            .funding_to_trading_multiple(amounts)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to split accounts: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("Failed to fund trading accounts: {}", e))?;
+
+        for tx in &txs {
+            info!("Funding tx: {}", tx.tx_hash);
+        }
 
         // Store all accounts as available for trading
         self.available_accounts = accounts;
@@ -233,7 +223,11 @@ impl MarketMaker {
             // Log status periodically
             if self.stats.uptime_seconds % 300 == 0 {
                 // Every 5 minutes
-                self.log_status();
+                match order_wallet.status_snapshot().await {
+                    Ok(snapshot) => info!("{}", status::render_compact(&snapshot)),
+                    Err(e) => warn!("Wallet status unavailable: {}", e),
+                }
+                info!("{}", self.status_line());
             }
         }
     }
@@ -377,7 +371,7 @@ impl MarketMaker {
         account_index: AccountIndex,
     ) -> Result<u64> {
         // Try to get balance from ZkAccountDB
-        if let Some(balance) = order_wallet.zk_accounts.get_balance(&account_index) {
+        if let Ok(balance) = order_wallet.zk_accounts.get_balance(&account_index) {
             Ok(balance)
         } else {
             Err(anyhow::anyhow!(
@@ -470,33 +464,27 @@ impl MarketMaker {
 
     /// Enhanced market data update with additional sources (optional)
     async fn update_enhanced_market_data(&mut self, order_wallet: &OrderWallet) -> Result<()> {
-        // Fetch multiple data sources for more informed pricing
-        let price_future = order_wallet.relayer_api_client.btc_usd_price();
-        let order_book_future = order_wallet.relayer_api_client.open_limit_orders();
-        let recent_trades_future = order_wallet.relayer_api_client.recent_trade_orders();
-
-        // Execute all API calls concurrently
-        let (price_result, order_book_result, recent_trades_result) =
-            tokio::join!(price_future, order_book_future, recent_trades_future);
+        // Fetch all market data sources concurrently in one snapshot
+        let snapshot = order_wallet.market_snapshot().await;
+        for (part, e) in &snapshot.errors {
+            warn!("Failed to fetch {}: {}", part, e);
+        }
 
         // Process price data
-        let base_price = match price_result {
-            Ok(btc_price) => {
+        let base_price = match &snapshot.price {
+            Some(btc_price) => {
                 info!(
                     "Latest BTC price: {} (timestamp: {})",
                     btc_price.price, btc_price.timestamp
                 );
                 btc_price.price as u64
             }
-            Err(e) => {
-                warn!("Failed to fetch BTC price: {}", e);
-                self.estimated_market_price // Use current estimate
-            }
+            None => self.estimated_market_price, // Use current estimate
         };
 
         // Analyze order book for better price estimation
-        let adjusted_price = match order_book_result {
-            Ok(order_book) => {
+        let adjusted_price = match &snapshot.order_book {
+            Some(order_book) => {
                 // Calculate mid-market price from order book
                 let best_bid = order_book
                     .bid
@@ -518,23 +506,41 @@ impl MarketMaker {
                 // Weight between base price and mid-market (70% base, 30% order book)
                 (base_price as f64 * 0.7 + mid_market * 0.3) as u64
             }
-            Err(e) => {
-                warn!("Failed to fetch order book: {}", e);
-                base_price
-            }
+            None => base_price,
         };
 
         // Log recent trading activity for context
-        if let Ok(recent_trades) = recent_trades_result {
-            let total_volume: f64 = recent_trades
-                .orders
-                .iter()
-                .map(|order| order.positionsize)
-                .sum();
+        if let Some(recent_trades) = &snapshot.recent_trades {
+            let flow = recent_trades.flow();
+            let since = Utc::now() - TimeDelta::minutes(5);
+            let sides = flow.flow_since(since);
             info!(
-                "Recent trading volume: {:.0} sats from {} orders",
-                total_volume,
-                recent_trades.orders.len()
+                "Recent trades (5m): volume {:.0} from {} buys / {} sells, VWAP {}, buy/sell ratio {}",
+                flow.volume_since(since),
+                sides.buys,
+                sides.sells,
+                flow.vwap_since(since)
+                    .map_or("n/a".to_string(), |vwap| format!("{:.2}", vwap)),
+                sides
+                    .ratio()
+                    .map_or("n/a".to_string(), |ratio| format!("{:.2}", ratio)),
+            );
+            if let Some(largest) = flow.largest_trade_since(since) {
+                info!(
+                    "Largest recent trade: {:.0} {:?} at {:.2}",
+                    largest.positionsize, largest.side, largest.price
+                );
+            }
+        }
+
+        // Log open interest for context
+        if let Some(size) = &snapshot.position_size {
+            info!(
+                "Open interest - Long: {:.0}, Short: {:.0}, long share {}",
+                size.long,
+                size.short,
+                size.long_share()
+                    .map_or("n/a".to_string(), |share| format!("{:.1}%", share * 100.0)),
             );
         }
 
@@ -801,39 +807,26 @@ impl MarketMaker {
         Ok(())
     }
 
-    /// Log current market maker status
-    fn log_status(&self) {
-        info!("=== Market Maker Status ===");
-        info!("Estimated market price: {}", self.estimated_market_price);
-        info!("Current inventory: {} sats", self.inventory);
-        info!("Available accounts: {}", self.available_accounts.len());
-        info!("Active orders: {}", self.active_orders.len());
-
-        // Show account balances
-        let total_available_balance: u64 = self
-            .available_accounts
-            .iter()
-            .map(|(_, balance)| balance)
-            .sum();
-        info!("Total available balance: {} sats", total_available_balance);
-
-        info!("Orders placed: {}", self.stats.orders_placed);
-        info!("Orders filled: {}", self.stats.orders_filled);
-        info!("Total volume: {} sats", self.stats.total_volume);
-        info!(
-            "Fill ratio: {:.2}%",
-            if self.stats.orders_placed > 0 {
-                (self.stats.orders_filled as f64 / self.stats.orders_placed as f64) * 100.0
-            } else {
-                0.0
-            }
-        );
-        info!(
-            "Max inventory reached: {} sats",
-            self.stats.max_inventory_reached
-        );
-        info!("Uptime: {}s", self.stats.uptime_seconds);
-        info!("============================");
+    /// This bot's counters, logged next to the wallet's
+    /// [`status::render_compact`] line.
+    fn status_line(&self) -> String {
+        let fill_ratio = if self.stats.orders_placed > 0 {
+            (self.stats.orders_filled as f64 / self.stats.orders_placed as f64) * 100.0
+        } else {
+            0.0
+        };
+        format!(
+            "price {} | inventory {} sats (max {}) | active orders {} | placed {} | filled {} ({:.2}%) | volume {} sats | uptime {}s",
+            self.estimated_market_price,
+            self.inventory,
+            self.stats.max_inventory_reached,
+            self.active_orders.len(),
+            self.stats.orders_placed,
+            self.stats.orders_filled,
+            fill_ratio,
+            self.stats.total_volume,
+            self.stats.uptime_seconds
+        )
     }
 
     /// Close all active orders and positions
@@ -862,7 +855,11 @@ impl MarketMaker {
             }
         }
 
-        self.log_status();
+        match order_wallet.status_snapshot().await {
+            Ok(snapshot) => info!("Final status:\n{}", status::render(&snapshot)),
+            Err(e) => warn!("Wallet status unavailable: {}", e),
+        }
+        info!("{}", self.status_line());
         info!("Market maker shutdown complete");
         info!(
             "Total accounts managed: {} available + {} active",
@@ -901,8 +898,12 @@ async fn main() -> Result<()> {
     let wallet_exists = OrderWallet::get_wallet_id_from_db(&wallet_id, None)
         .map_err(|e| anyhow::anyhow!("Failed to check wallet ID existence: {}", e))?;
     if wallet_exists {
-        order_wallet = OrderWallet::load_from_db(wallet_id, None, None)
+        let (loaded, drift) = OrderWallet::load_from_db(wallet_id, None, None)
             .map_err(|e| anyhow::anyhow!("Failed to load wallet from database: {}", e))?;
+        if drift.is_significant() {
+            warn!("Config drift since the wallet was saved: {}", drift);
+        }
+        order_wallet = loaded;
     } else {
         order_wallet = OrderWallet::new(None)
             .map_err(|e| anyhow::anyhow!("Failed to create OrderWallet: {}", e))?;
@@ -49,23 +49,22 @@ async fn main() -> Result<()> {
         Ok(order_book) => {
             info!("✅ Successfully fetched order book:");
 
-            if let Some(best_bid) = order_book.bid.first() {
+            if let Some(best_bid) = order_book.best_bid() {
                 info!(
                     "   Best Bid: ${:.2} (size: {:.2})",
                     best_bid.price, best_bid.positionsize
                 );
             }
 
-            if let Some(best_ask) = order_book.ask.first() {
+            if let Some(best_ask) = order_book.best_ask() {
                 info!(
                     "   Best Ask: ${:.2} (size: {:.2})",
                     best_ask.price, best_ask.positionsize
                 );
 
                 // Calculate spread
-                if let Some(best_bid) = order_book.bid.first() {
-                    let spread = best_ask.price - best_bid.price;
-                    let spread_pct = (spread / best_bid.price) * 100.0;
+                if let (Some(spread), Some(mid)) = (order_book.spread(), order_book.mid_price()) {
+                    let spread_pct = (spread / mid) * 100.0;
                     info!("   Spread: ${:.2} ({:.3}%)", spread, spread_pct);
                 }
             }
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nyks_wallet::wallet::faucet::parse_account_response;
+use nyks_wallet::wallet::wallet::parse_balance_response;
+
+// Raw LCD bodies. reqwest hands these over as lossily decoded text.
+fuzz_target!(|data: &[u8]| {
+    let body = String::from_utf8_lossy(data);
+    let _ = parse_account_response(&body);
+    let _ = parse_balance_response(&body);
+});
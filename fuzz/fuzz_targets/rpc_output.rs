@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nyks_wallet::nyks_rpc::rpcclient::method::Method;
+use nyks_wallet::nyks_rpc::rpcclient::txrequest::parse_rpc_output;
+use nyks_wallet::nyks_rpc::rpcclient::txresult::parse_tx_response;
+
+// Raw chain RPC body -> JSON-RPC output -> broadcast result.
+fuzz_target!(|data: &[u8]| {
+    for method in [Method::broadcast_tx_sync, Method::broadcast_tx_commit] {
+        let _ = parse_tx_response(&method, parse_rpc_output(data));
+    }
+});
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_pending_operations_wallet_status;
+DROP TABLE IF EXISTS pending_operations;
@@ -0,0 +1,2 @@
+-- Most recent failure recorded against an account (JSON-encoded StoredError), NULL when none
+ALTER TABLE zk_accounts ADD COLUMN last_error TEXT DEFAULT NULL;
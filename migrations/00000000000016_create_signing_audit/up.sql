@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS signing_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    seq BIGINT NOT NULL,
+    account_index BIGINT NOT NULL,
+    purpose TEXT NOT NULL,
+    payload_hash TEXT NOT NULL,
+    prev_hash TEXT NOT NULL,
+    entry_hash TEXT NOT NULL,
+    signed_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, seq)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS activity_buckets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    hour TIMESTAMP NOT NULL,
+    category TEXT NOT NULL,
+    count BIGINT NOT NULL,
+    UNIQUE (wallet_id, network_type, hour, category)
+);
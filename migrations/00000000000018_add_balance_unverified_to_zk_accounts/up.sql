@@ -0,0 +1,2 @@
+-- Balance was taken from the requested funding amount because the committed amount could not be derived
+ALTER TABLE zk_accounts ADD COLUMN balance_unverified BOOLEAN NOT NULL DEFAULT FALSE;
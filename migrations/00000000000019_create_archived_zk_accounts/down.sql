@@ -0,0 +1 @@
+DROP TABLE IF EXISTS archived_zk_accounts;
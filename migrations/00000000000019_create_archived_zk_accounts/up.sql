@@ -0,0 +1,10 @@
+-- Accounts archived out of the active set. The zk_accounts row is kept so
+-- order and transfer history referencing the account index still resolves.
+CREATE TABLE IF NOT EXISTS archived_zk_accounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    account_index BIGINT NOT NULL,
+    archived_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, account_index)
+);
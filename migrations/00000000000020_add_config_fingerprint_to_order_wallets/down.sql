@@ -0,0 +1,21 @@
+-- SQLite does not support DROP COLUMN directly, so we recreate the table
+CREATE TABLE order_wallets_backup (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    chain_id TEXT NOT NULL,
+    seed_encrypted BLOB NOT NULL,
+    seed_salt BLOB NOT NULL,
+    seed_nonce BLOB NOT NULL,
+    relayer_api_endpoint TEXT NOT NULL,
+    zkos_server_endpoint TEXT NOT NULL,
+    relayer_program_json_path TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(wallet_id, network_type)
+);
+INSERT INTO order_wallets_backup (id, wallet_id, network_type, chain_id, seed_encrypted, seed_salt, seed_nonce, relayer_api_endpoint, zkos_server_endpoint, relayer_program_json_path, is_active, created_at, updated_at)
+    SELECT id, wallet_id, network_type, chain_id, seed_encrypted, seed_salt, seed_nonce, relayer_api_endpoint, zkos_server_endpoint, relayer_program_json_path, is_active, created_at, updated_at FROM order_wallets;
+DROP TABLE order_wallets;
+ALTER TABLE order_wallets_backup RENAME TO order_wallets;
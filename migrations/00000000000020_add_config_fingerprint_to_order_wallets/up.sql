@@ -0,0 +1,2 @@
+-- JSON-encoded ConfigFingerprint of the configuration the wallet was saved under, NULL for older rows
+ALTER TABLE order_wallets ADD COLUMN config_fingerprint TEXT DEFAULT NULL;
@@ -0,0 +1,16 @@
+-- Every order request made from an account, in submission order. request_ids
+-- keeps only the latest per account; seq orders the rows within an account.
+-- Keyed on its columns rather than an AUTOINCREMENT id, which PostgreSQL
+-- does not have.
+CREATE TABLE IF NOT EXISTS order_records (
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    account_index BIGINT NOT NULL,
+    seq BIGINT NOT NULL,
+    request_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL,
+    recorded_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (wallet_id, network_type, account_index, seq)
+);
@@ -0,0 +1,17 @@
+DROP TABLE IF EXISTS pending_submissions;
+
+-- SQLite does not support DROP COLUMN directly, so we recreate the table
+CREATE TABLE request_ids_backup (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    account_index BIGINT NOT NULL,
+    request_id TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(wallet_id, network_type, account_index)
+);
+INSERT INTO request_ids_backup (id, wallet_id, network_type, account_index, request_id, created_at, updated_at)
+    SELECT id, wallet_id, network_type, account_index, request_id, created_at, updated_at FROM request_ids;
+DROP TABLE request_ids;
+ALTER TABLE request_ids_backup RENAME TO request_ids;
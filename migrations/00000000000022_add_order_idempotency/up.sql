@@ -0,0 +1,16 @@
+-- Idempotency key the latest request of each account was submitted with, NULL for older rows
+ALTER TABLE request_ids ADD COLUMN idempotency_key TEXT DEFAULT NULL;
+
+-- Order submits whose outcome is not known yet; a row is removed once the
+-- relayer answers or reconciliation resolves it.
+CREATE TABLE IF NOT EXISTS pending_submissions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    account_index BIGINT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    account_address TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, account_index)
+);
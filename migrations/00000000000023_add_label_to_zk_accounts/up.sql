@@ -0,0 +1,2 @@
+-- Caller-chosen account name; uniqueness among active accounts is enforced by the wallet
+ALTER TABLE zk_accounts ADD COLUMN label TEXT DEFAULT NULL;
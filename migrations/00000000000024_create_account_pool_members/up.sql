@@ -0,0 +1,12 @@
+-- Members of each named AccountPool of a wallet. A rotated account's row is
+-- replaced by its successor's; state is 'available' or 'in_use'.
+CREATE TABLE IF NOT EXISTS account_pool_members (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    pool_name TEXT NOT NULL,
+    account_index BIGINT NOT NULL,
+    state TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, pool_name, account_index)
+);
@@ -0,0 +1,13 @@
+-- Client-side stop-loss and take-profit triggers of a ConditionalOrderManager,
+-- one per account and kind. payload is the serialized ConditionalTrigger;
+-- a trigger's row is deleted once its close has been submitted.
+CREATE TABLE IF NOT EXISTS conditional_triggers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    account_index BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, account_index, kind)
+);
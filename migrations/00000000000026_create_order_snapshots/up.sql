@@ -0,0 +1,24 @@
+-- Prices and margins of an order when it was closed or cancelled, for
+-- accounting. One row per request; a close is written when it is submitted
+-- and updated with the settled margin and realized P&L once it settles.
+CREATE TABLE IF NOT EXISTS order_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    account_index BIGINT NOT NULL,
+    request_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    order_status TEXT NOT NULL,
+    position_type TEXT,
+    entry_price DOUBLE PRECISION,
+    exit_price DOUBLE PRECISION,
+    leverage DOUBLE PRECISION,
+    initial_margin DOUBLE PRECISION NOT NULL,
+    settled_margin DOUBLE PRECISION,
+    realized_pnl DOUBLE PRECISION,
+    fees DOUBLE PRECISION,
+    opened_at TIMESTAMP,
+    recorded_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, account_index, request_id)
+);
@@ -0,0 +1,6 @@
+-- AES-256-GCM ciphertext of the account's scalar and account, with the PBKDF2
+-- salt of the key and the row's nonce; NULL for rows not yet encrypted, whose
+-- scalar and account columns still hold the plaintext
+ALTER TABLE zk_accounts ADD COLUMN secrets_encrypted BLOB DEFAULT NULL;
+ALTER TABLE zk_accounts ADD COLUMN secrets_salt BLOB DEFAULT NULL;
+ALTER TABLE zk_accounts ADD COLUMN secrets_nonce BLOB DEFAULT NULL;
@@ -0,0 +1,17 @@
+-- SQLite does not support DROP COLUMN directly, so we recreate the table
+CREATE TABLE order_records_backup (
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    account_index BIGINT NOT NULL,
+    seq BIGINT NOT NULL,
+    request_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL,
+    recorded_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (wallet_id, network_type, account_index, seq)
+);
+INSERT INTO order_records_backup (wallet_id, network_type, account_index, seq, request_id, kind, status, recorded_at, updated_at)
+    SELECT wallet_id, network_type, account_index, seq, request_id, kind, status, recorded_at, updated_at FROM order_records;
+DROP TABLE order_records;
+ALTER TABLE order_records_backup RENAME TO order_records;
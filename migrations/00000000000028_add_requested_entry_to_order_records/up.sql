@@ -0,0 +1,4 @@
+-- Order type and entry price a trader open was requested with, for execution
+-- reports; NULL for other kinds and for opens recorded before this migration
+ALTER TABLE order_records ADD COLUMN order_type TEXT DEFAULT NULL;
+ALTER TABLE order_records ADD COLUMN requested_price BIGINT DEFAULT NULL;
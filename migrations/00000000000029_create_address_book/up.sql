@@ -0,0 +1,12 @@
+-- Labelled addresses of a wallet's AddressBook, one per label. kind is
+-- twilight, btc or zkos; the address was format-checked for it when added.
+CREATE TABLE IF NOT EXISTS address_book (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    label TEXT NOT NULL,
+    address TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    added_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, label)
+);
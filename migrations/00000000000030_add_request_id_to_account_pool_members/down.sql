@@ -0,0 +1,15 @@
+-- SQLite does not support DROP COLUMN directly, so we recreate the table
+CREATE TABLE account_pool_members_backup (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    wallet_id TEXT NOT NULL,
+    network_type TEXT NOT NULL DEFAULT 'mainnet',
+    pool_name TEXT NOT NULL,
+    account_index BIGINT NOT NULL,
+    state TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    UNIQUE (wallet_id, network_type, pool_name, account_index)
+);
+INSERT INTO account_pool_members_backup (id, wallet_id, network_type, pool_name, account_index, state, updated_at)
+    SELECT id, wallet_id, network_type, pool_name, account_index, state, updated_at FROM account_pool_members;
+DROP TABLE account_pool_members;
+ALTER TABLE account_pool_members_backup RENAME TO account_pool_members;
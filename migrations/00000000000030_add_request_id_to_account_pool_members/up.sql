@@ -0,0 +1,2 @@
+-- Request ID of the order a pool member carries; NULL while it carries none
+ALTER TABLE account_pool_members ADD COLUMN request_id TEXT DEFAULT NULL;
@@ -0,0 +1,37 @@
+#!/usr/bin/env bash
+# Build the library under each supported feature combination.
+# Usage: scripts/check-features.sh [extra cargo args]
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+
+combos=(
+    ""
+    "market-data"
+    "wallet-core"
+    "zk-accounts"
+    "order-wallet"
+    "validator-wallet"
+    "db-sqlite"
+    "db-postgres"
+    "health-endpoint"
+    "blocking"
+    "webhooks"
+    "test-utils"
+    "ws"
+)
+
+for features in "${combos[@]}"; do
+    echo "==> cargo build --lib --no-default-features --features '${features}'"
+    cargo build --lib --no-default-features --features "${features}" "$@"
+done
+
+echo "==> cargo build --all-targets (default features)"
+cargo build --all-targets "$@"
+
+# OrderWallet without a database: tests and binaries too, not just the library.
+echo "==> cargo build --all-targets --no-default-features --features order-wallet"
+cargo build --all-targets --no-default-features --features order-wallet "$@"
+
+echo "==> cargo test --lib --no-default-features --features market-data"
+cargo test --lib --no-default-features --features market-data "$@"
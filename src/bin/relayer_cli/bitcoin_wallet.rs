@@ -315,13 +315,7 @@ pub(crate) async fn handle_bitcoin_wallet(
             ow.wallet.btc_wallet = Some(btc_wallet);
 
             // Persist to DB
-            let db = ow
-                .get_db_manager()
-                .ok_or("Database not available. Rebuild with --features sqlite".to_string())?;
-            let wallet_password = ow
-                .get_wallet_password()
-                .ok_or("No wallet password available for re-encryption".to_string())?;
-            db.save_encrypted_wallet(&ow.wallet, wallet_password)
+            ow.save_encrypted_wallet_to_db()
                 .map_err(|e| format!("Failed to save updated wallet: {}", e))?;
 
             let network = if nyks_wallet::config::is_btc_mainnet() {
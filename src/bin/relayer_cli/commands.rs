@@ -1,4 +1,5 @@
 use clap::Subcommand;
+use nyks_wallet::relayer_module::leverage::Leverage;
 
 // ---------------------------------------------------------------------------
 // Wallet sub-commands
@@ -38,6 +39,10 @@ pub(crate) enum WalletCmd {
         /// Optional BTC native SegWit address (bc1q...) to use instead of deriving from mnemonic
         #[arg(long)]
         btc_address: Option<String>,
+
+        /// Prompt for the mnemonic's BIP-39 passphrase (always asked when the mnemonic is prompted)
+        #[arg(long)]
+        mnemonic_passphrase: bool,
     },
 
     /// Load a wallet from the database
@@ -73,7 +78,7 @@ pub(crate) enum WalletCmd {
         db_url: Option<String>,
     },
 
-    /// Export wallet to a JSON file
+    /// Export wallet to a passphrase-encrypted JSON file
     Export {
         /// Output file path
         #[arg(long, default_value = "wallet.json")]
@@ -86,6 +91,10 @@ pub(crate) enum WalletCmd {
         /// Database encryption password (falls back to NYKS_WALLET_PASSPHRASE env var)
         #[arg(long)]
         password: Option<String>,
+
+        /// Write the private key unencrypted instead
+        #[arg(long)]
+        plaintext: bool,
     },
 
     /// List all ZkOS accounts for a wallet
@@ -266,9 +275,10 @@ pub(crate) enum WalletCmd {
 
     /// Submit a BTC withdrawal request to your registered BTC address (mainnet only)
     WithdrawBtc {
-        /// Reserve ID to withdraw from (see `wallet reserves`)
+        /// Reserve ID to withdraw from (see `wallet reserves`); defaults to
+        /// the reserve holding the most BTC that can pay out the amount
         #[arg(long)]
-        reserve_id: u64,
+        reserve_id: Option<u64>,
 
         /// Amount in satoshis to withdraw
         #[arg(long)]
@@ -446,9 +456,9 @@ pub(crate) enum OrderCmd {
         #[arg(long)]
         entry_price: u64,
 
-        /// Leverage multiplier (1-50)
+        /// Leverage multiplier, whole or fractional (e.g. 5 or 2.5); capped by the market's max leverage
         #[arg(long)]
-        leverage: u64,
+        leverage: Leverage,
 
         /// Wallet ID to load from DB (falls back to NYKS_WALLET_ID env var)
         #[arg(long)]
@@ -779,6 +789,40 @@ pub(crate) enum HistoryCmd {
     },
 }
 
+// ---------------------------------------------------------------------------
+// Pending operation sub-commands
+// ---------------------------------------------------------------------------
+
+#[derive(Subcommand)]
+pub(crate) enum OpsCmd {
+    /// List multi-step operations that have not finished
+    List {
+        /// Wallet ID (falls back to NYKS_WALLET_ID env var)
+        #[arg(long)]
+        wallet_id: Option<String>,
+
+        /// Database encryption password (falls back to NYKS_WALLET_PASSPHRASE env var)
+        #[arg(long)]
+        password: Option<String>,
+    },
+
+    /// Re-run the remaining steps of a pending operation, or of all of them
+    /// (operations that cannot be finished are flagged for manual resolution)
+    Resume {
+        /// Wallet ID (falls back to NYKS_WALLET_ID env var)
+        #[arg(long)]
+        wallet_id: Option<String>,
+
+        /// Database encryption password (falls back to NYKS_WALLET_PASSPHRASE env var)
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Operation ID (from `ops list`); resumes every pending operation when omitted
+        #[arg(long)]
+        id: Option<String>,
+    },
+}
+
 // ---------------------------------------------------------------------------
 // Portfolio sub-commands
 // ---------------------------------------------------------------------------
@@ -809,6 +853,10 @@ pub(crate) enum PortfolioCmd {
         /// Display unit: sats (default), mbtc, or btc
         #[arg(long, default_value = "sats")]
         unit: String,
+
+        /// Also list accounts archived as spent
+        #[arg(long, default_value_t = false)]
+        include_archived: bool,
     },
 
     /// Show liquidation risk for open positions
@@ -821,6 +869,21 @@ pub(crate) enum PortfolioCmd {
         #[arg(long)]
         password: Option<String>,
     },
+
+    /// Show wallet status: balances, accounts, open orders and endpoint health
+    Status {
+        /// Wallet ID to load from DB (falls back to NYKS_WALLET_ID env var)
+        #[arg(long)]
+        wallet_id: Option<String>,
+
+        /// Database encryption password (falls back to NYKS_WALLET_PASSPHRASE env var)
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Print a single summary line instead of the full report
+        #[arg(long, default_value_t = false)]
+        compact: bool,
+    },
 }
 
 // ---------------------------------------------------------------------------
@@ -24,7 +24,8 @@ COMMANDS:
                     open-interest, market-stats, server-time, history-price,
                     candles, history-funding, history-fees, apy-chart)
     history         Local DB history (orders, transfers)
-    portfolio       Portfolio tracking (summary, balances, risks)
+    ops             Pending multi-step operations (list, resume)
+    portfolio       Portfolio tracking (summary, balances, risks, status)
     repl            Interactive REPL mode — enter wallet ID and password once,
                     then run commands without the `relayer-cli` prefix
     verify-test     Run verification tests against testnet (testnet only)
@@ -68,7 +69,7 @@ SUBCOMMANDS:
     balance             Show wallet balance (on-chain query)
     info                Show wallet info (no chain calls)
     accounts            List all ZkOS accounts for a wallet (INDEX, BALANCE, ON-CHAIN, IO-TYPE, TX-TYPE, ACCOUNT)
-    export              Export wallet to a JSON file
+    export              Export wallet to an encrypted JSON file (--plaintext to skip)
     backup              Full database backup to JSON
     restore             Restore wallet from backup JSON
     unlock              Cache wallet-id + password for this terminal session
@@ -95,7 +96,7 @@ EXAMPLES:
     relayer-cli wallet deposit-btc --amount 50000 --reserve-address bc1q...
     relayer-cli wallet reserves                       # see where to send BTC
     relayer-cli wallet deposit-status                 # check if confirmed by validators
-    relayer-cli wallet withdraw-btc --reserve-id 1 --amount 50000
+    relayer-cli wallet withdraw-btc --amount 50000
     relayer-cli wallet withdraw-status                # check pending withdrawals
     relayer-cli wallet faucet                         # testnet only: get test tokens"#
     );
@@ -244,6 +245,27 @@ EXAMPLES:
     );
 }
 
+pub(crate) fn print_ops_help() {
+    println!(
+        r#"Pending multi-step operations (requires DB feature).
+
+A split (`zkaccount split`) that broadcast successfully but could not finish
+updating every account is recorded as a pending operation. Resuming re-runs
+only the steps that are still outstanding.
+
+USAGE:
+    relayer-cli ops <SUBCOMMAND>
+
+SUBCOMMANDS:
+    list        List operations that have not finished
+    resume      Re-run the remaining steps of an operation
+
+EXAMPLES:
+    relayer-cli ops list
+    relayer-cli ops resume --id <OPERATION_ID>"#
+    );
+}
+
 pub(crate) fn print_portfolio_help() {
     println!(
         r#"Portfolio and position tracking.
@@ -255,11 +277,13 @@ SUBCOMMANDS:
     summary     Full portfolio summary (balances, positions, PnL)
     balances    Per-account balance breakdown (--unit sats|mbtc|btc)
     risks       Liquidation risk for open positions
+    status      Wallet status report: balances, accounts, orders, endpoints (--compact)
 
 EXAMPLES:
     relayer-cli portfolio summary
     relayer-cli portfolio balances --unit btc
-    relayer-cli portfolio risks"#
+    relayer-cli portfolio risks
+    relayer-cli portfolio status --compact"#
     );
 }
 
@@ -346,6 +370,7 @@ pub(crate) fn print_subcommand_help(group: &str) {
         "order" => print_order_help(),
         "market" => print_market_help(),
         "history" => print_history_help(),
+        "ops" => print_ops_help(),
         "portfolio" => print_portfolio_help(),
         "verifytest" => print_verify_test_help(),
         "update" => print_update_help(),
@@ -64,11 +64,11 @@ pub(crate) async fn get_or_resolve_wallet<'a>(
 // Helpers
 // ---------------------------------------------------------------------------
 
-pub(crate) fn parse_order_type(s: &str) -> Result<twilight_client_sdk::relayer_types::OrderType, String> {
+pub(crate) fn parse_order_type(s: &str) -> Result<nyks_wallet::compat::relayer_types::OrderType, String> {
     match s.to_uppercase().as_str() {
-        "MARKET" => Ok(twilight_client_sdk::relayer_types::OrderType::MARKET),
-        "LIMIT" => Ok(twilight_client_sdk::relayer_types::OrderType::LIMIT),
-        "SLTP" => Ok(twilight_client_sdk::relayer_types::OrderType::SLTP),
+        "MARKET" => Ok(nyks_wallet::compat::relayer_types::OrderType::MARKET),
+        "LIMIT" => Ok(nyks_wallet::compat::relayer_types::OrderType::LIMIT),
+        "SLTP" => Ok(nyks_wallet::compat::relayer_types::OrderType::SLTP),
         other => Err(format!(
             "Unknown order type: {other}. Use MARKET, LIMIT, or SLTP"
         )),
@@ -77,10 +77,10 @@ pub(crate) fn parse_order_type(s: &str) -> Result<twilight_client_sdk::relayer_t
 
 pub(crate) fn parse_position_type(
     s: &str,
-) -> Result<twilight_client_sdk::relayer_types::PositionType, String> {
+) -> Result<nyks_wallet::compat::relayer_types::PositionType, String> {
     match s.to_uppercase().as_str() {
-        "LONG" => Ok(twilight_client_sdk::relayer_types::PositionType::LONG),
-        "SHORT" => Ok(twilight_client_sdk::relayer_types::PositionType::SHORT),
+        "LONG" => Ok(nyks_wallet::compat::relayer_types::PositionType::LONG),
+        "SHORT" => Ok(nyks_wallet::compat::relayer_types::PositionType::SHORT),
         other => Err(format!("Unknown position side: {other}. Use LONG or SHORT")),
     }
 }
@@ -146,7 +146,11 @@ pub(crate) fn load_order_wallet_from_db(
     db_url: Option<String>,
 ) -> Result<OrderWallet, String> {
     let pwd = resolve_password(password).map(|p| SecretString::new(p.into()));
-    OrderWallet::load_from_db(wallet_id.to_string(), pwd, db_url)
+    let (ow, drift) = OrderWallet::load_from_db(wallet_id.to_string(), pwd, db_url)?;
+    if drift.is_significant() {
+        eprintln!("Warning: Configuration changed since wallet '{wallet_id}' was saved: {drift}");
+    }
+    Ok(ow)
 }
 
 // ---------------------------------------------------------------------------
@@ -6,6 +6,7 @@ mod help;
 mod helpers;
 mod history;
 mod market;
+mod ops;
 mod order;
 mod portfolio;
 mod repl;
@@ -39,6 +40,10 @@ struct Cli {
     /// Output results as JSON instead of formatted tables (useful for scripting)
     #[arg(long, global = true, default_value_t = false)]
     json: bool,
+
+    /// TOML config file; environment variables override its values
+    #[arg(long, global = true)]
+    config: Option<std::path::PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -64,6 +69,10 @@ enum Commands {
     #[command(subcommand)]
     History(HistoryCmd),
 
+    /// Pending multi-step operations (list, resume)
+    #[command(subcommand)]
+    Ops(OpsCmd),
+
     /// Portfolio and position tracking
     #[command(subcommand)]
     Portfolio(PortfolioCmd),
@@ -97,7 +106,7 @@ enum Commands {
 
     /// Show help for a command group (e.g. `help wallet`)
     Help {
-        /// Command group to get help for (wallet, zkaccount, order, market, history, portfolio)
+        /// Command group to get help for (wallet, zkaccount, order, market, history, ops, portfolio)
         command: Option<String>,
     },
 }
@@ -106,13 +115,55 @@ enum Commands {
 // Main
 // ---------------------------------------------------------------------------
 
-#[tokio::main]
-async fn main() {
+fn main() {
     dotenv::dotenv().ok();
     env_logger::init();
 
     let cli = Cli::parse();
 
+    if let Some(path) = &cli.config {
+        let loaded = nyks_wallet::config::Config::from_file(path).and_then(|config| {
+            config.validate()?;
+            Ok(config)
+        });
+        match loaded {
+            Ok(config) => config.apply_to_env(),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Release binaries ship without relayerprogram.json; use the program
+    // compiled into the wallet when none is configured or next to the binary.
+    if std::env::var_os("RELAYER_PROGRAM_JSON_PATH").is_none()
+        && !std::path::Path::new("./relayerprogram.json").exists()
+    {
+        unsafe {
+            std::env::set_var(
+                "RELAYER_PROGRAM_JSON_PATH",
+                nyks_wallet::relayer_module::relayer_program::BUILTIN_RELAYER_PROGRAM,
+            );
+        }
+    }
+
+    // The environment is only written above, before the runtime starts its
+    // worker threads.
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: failed to start the async runtime: {e}");
+            std::process::exit(1);
+        }
+    };
+    runtime.block_on(run(cli));
+}
+
+async fn run(cli: Cli) {
     let json_output = cli.json;
 
     let result = match cli.command {
@@ -121,6 +172,7 @@ async fn main() {
         Commands::Order(cmd) => order::handle_order(cmd, json_output, None).await,
         Commands::Market(cmd) => market::handle_market(cmd, json_output).await,
         Commands::History(cmd) => history::handle_history(cmd, json_output, None).await,
+        Commands::Ops(cmd) => ops::handle_ops(cmd, json_output, None).await,
         Commands::Portfolio(cmd) => portfolio::handle_portfolio(cmd, json_output, None).await,
         Commands::BitcoinWallet(cmd) => bitcoin_wallet::handle_bitcoin_wallet(cmd, None).await,
         Commands::VerifyTest(cmd) => verify_test::handle_verify_test(cmd).await,
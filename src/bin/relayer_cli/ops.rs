@@ -0,0 +1,132 @@
+use nyks_wallet::relayer_module::order_wallet::OrderWallet;
+
+use crate::commands::OpsCmd;
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::helpers::{load_order_wallet_from_db, resolve_wallet_id, MaybeOwnedWallet};
+
+pub(crate) async fn handle_ops(
+    cmd: OpsCmd,
+    json_output: bool,
+    repl_wallet: Option<&mut OrderWallet>,
+) -> Result<(), String> {
+    #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+    {
+        let _ = (cmd, json_output, repl_wallet);
+        return Err(
+            "Database features (sqlite/postgresql) not enabled. Rebuild with --features sqlite"
+                .to_string(),
+        );
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    match cmd {
+        OpsCmd::List {
+            wallet_id,
+            password,
+        } => {
+            let ow: MaybeOwnedWallet<'_> = match repl_wallet {
+                Some(w) => MaybeOwnedWallet::Borrowed(w),
+                None => {
+                    let wallet_id = resolve_wallet_id(wallet_id)
+                        .ok_or("wallet_id is required (pass --wallet-id, set NYKS_WALLET_ID env var, or run `wallet unlock`)")?;
+                    MaybeOwnedWallet::Owned(load_order_wallet_from_db(&wallet_id, password, None)?)
+                }
+            };
+            let ops = ow.pending_operations();
+
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&ops).map_err(|e| e.to_string())?
+                );
+                return Ok(());
+            }
+
+            if ops.is_empty() {
+                println!("No pending operations");
+            } else {
+                println!(
+                    "{:<38} {:<14} {:<16} {:<6} {:<20} {}",
+                    "ID", "KIND", "STATUS", "STEPS", "UPDATED", "LAST ERROR"
+                );
+                println!("{}", "-".repeat(117));
+                for op in &ops {
+                    println!(
+                        "{:<38} {:<14} {:<16} {:<6} {:<20} {}",
+                        op.id,
+                        op.kind,
+                        op.status.as_str(),
+                        format!(
+                            "{}/{}",
+                            op.completed_steps.len(),
+                            op.completed_steps.len() + op.remaining_steps.len()
+                        ),
+                        op.updated_at.format("%Y-%m-%d %H:%M:%S"),
+                        op.last_error.as_deref().unwrap_or("-"),
+                    );
+                }
+                println!("\nShowing {} operations", ops.len());
+            }
+            Ok(())
+        }
+
+        OpsCmd::Resume {
+            wallet_id,
+            password,
+            id,
+        } => {
+            let mut ow: MaybeOwnedWallet<'_> = match repl_wallet {
+                Some(w) => MaybeOwnedWallet::Borrowed(w),
+                None => {
+                    let wallet_id = resolve_wallet_id(wallet_id)
+                        .ok_or("wallet_id is required (pass --wallet-id, set NYKS_WALLET_ID env var, or run `wallet unlock`)")?;
+                    MaybeOwnedWallet::Owned(load_order_wallet_from_db(&wallet_id, password, None)?)
+                }
+            };
+            let Some(id) = id else {
+                let ops = ow.resume_pending().await?;
+                if json_output {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&ops).map_err(|e| e.to_string())?
+                    );
+                    return Ok(());
+                }
+                if ops.is_empty() {
+                    println!("No pending operations to resume");
+                }
+                for op in &ops {
+                    println!(
+                        "Operation {} ({}): {}{}",
+                        op.id,
+                        op.kind,
+                        op.status.as_str(),
+                        op.last_error
+                            .as_deref()
+                            .map(|e| format!(" ({})", e))
+                            .unwrap_or_default()
+                    );
+                }
+                return Ok(());
+            };
+            let op = ow.resume_operation(&id).await?;
+
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&op).map_err(|e| e.to_string())?
+                );
+                return Ok(());
+            }
+
+            println!(
+                "Operation {} ({}) completed: {} steps",
+                op.id,
+                op.kind,
+                op.completed_steps.len()
+            );
+            Ok(())
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use nyks_wallet::error::OrderWalletError;
 use nyks_wallet::relayer_module::order_wallet::OrderWallet;
 // use nyks_wallet::relayer_module::relayer_types::OrderStatus;
 
@@ -34,7 +35,7 @@ pub(crate) async fn handle_order(
                 .await
             {
                 Ok(id) => id,
-                Err(e) if e.contains("Value Witness Verification Failed") => {
+                Err(e @ OrderWalletError::WitnessRejected(_)) => {
                     return Err(format!(
                         "{e}\n\nHint: If a previous order on this account was closed, you need to \
                          create a transfer first before placing a new order.\n\
@@ -42,7 +43,7 @@ pub(crate) async fn handle_order(
                          Use: relayer-cli zkaccount transfer --account-index {account_index}"
                     ));
                 }
-                Err(e) => return Err(e),
+                Err(e) => return Err(e.into()),
             };
             if json_output {
                 println!("{}", serde_json::json!({"request_id": request_id}));
@@ -170,7 +171,7 @@ pub(crate) async fn handle_order(
             }
             let request_id = match ow.open_lend_order(account_index).await {
                 Ok(id) => id,
-                Err(e) if e.contains("Value Witness Verification Failed") => {
+                Err(e @ OrderWalletError::WitnessRejected(_)) => {
                     return Err(format!(
                         "{e}\n\nHint: If the account was previously used for an open/closed order, \
                          you must transfer the account first before placing a new order.\n\
@@ -178,7 +179,7 @@ pub(crate) async fn handle_order(
                          Use: relayer-cli zkaccount transfer --account-index {account_index}"
                     ));
                 }
-                Err(e) => return Err(e),
+                Err(e) => return Err(e.into()),
             };
             if json_output {
                 println!("{}", serde_json::json!({"request_id": request_id}));
@@ -216,21 +217,27 @@ pub(crate) async fn handle_order(
         } => {
             let mut ow = get_or_resolve_wallet(repl_wallet, wallet_id, password).await?;
 
+            // Accounts saved before the order kind was kept have none.
+            ow.identify_memo_orders().await;
             let tx_type = ow.zk_accounts.get_account(&account_index)?.tx_type.clone();
 
             let result = match tx_type {
-                Some(twilight_client_sdk::relayer_types::TXType::LENDTX) => {
+                Some(nyks_wallet::compat::relayer_types::TXType::LENDTX) => {
                     if !json_output {
                         println!("Unlocking settled lend order on account {account_index}...");
                     }
                     ow.unlock_lend_order(account_index).await
                 }
-                Some(twilight_client_sdk::relayer_types::TXType::ORDERTX) | None => {
+                Some(nyks_wallet::compat::relayer_types::TXType::ORDERTX) => {
                     if !json_output {
                         println!("Unlocking settled trader order on account {account_index}...");
                     }
                     ow.unlock_trader_order(account_index).await
                 }
+                None => Err(format!(
+                    "Order kind of account {account_index} is unknown; the relayer has no order for it"
+                )
+                .into()),
             };
 
             match result {
@@ -251,7 +258,7 @@ pub(crate) async fn handle_order(
                     if json_output {
                         println!(
                             "{}",
-                            serde_json::json!({"account_index": account_index, "error": e})
+                            serde_json::json!({"account_index": account_index, "error": e.to_string()})
                         );
                     } else {
                         println!("Error: {}", e);
@@ -289,7 +296,7 @@ pub(crate) async fn handle_order(
                     if json_output {
                         println!(
                             "{}",
-                            serde_json::json!({"account_index": account_index, "error": e})
+                            serde_json::json!({"account_index": account_index, "error": e.to_string()})
                         );
                     } else {
                         println!("Error: {}", e);
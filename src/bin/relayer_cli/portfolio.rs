@@ -1,5 +1,6 @@
 use nyks_wallet::relayer_module::order_wallet::OrderWallet;
 use nyks_wallet::relayer_module::relayer_types::OrderStatus;
+use nyks_wallet::relayer_module::status;
 
 use crate::commands::PortfolioCmd;
 use crate::helpers::get_or_resolve_wallet;
@@ -246,10 +247,11 @@ pub(crate) async fn handle_portfolio(
             wallet_id,
             password,
             unit,
+            include_archived,
         } => {
             let ow = get_or_resolve_wallet(repl_wallet, wallet_id, password).await?;
 
-            let balances = ow.get_account_balances();
+            let balances = ow.get_account_balances(include_archived);
 
             if json_output {
                 println!(
@@ -293,6 +295,17 @@ pub(crate) async fn handle_portfolio(
                             b.on_chain,
                         );
                     }
+                    if b.balance_unverified {
+                        println!("         balance not verified against the chain output");
+                    }
+                    if let Some(err) = &b.last_error {
+                        println!(
+                            "         last error: {} at {}: {}",
+                            err.operation,
+                            err.when.format("%Y-%m-%d %H:%M:%S"),
+                            err.message
+                        );
+                    }
                     total += b.balance;
                 }
                 println!("{}", "-".repeat(46));
@@ -361,5 +374,27 @@ pub(crate) async fn handle_portfolio(
             }
             Ok(())
         }
+
+        PortfolioCmd::Status {
+            wallet_id,
+            password,
+            compact,
+        } => {
+            let ow = get_or_resolve_wallet(repl_wallet, wallet_id, password).await?;
+
+            let snapshot = ow.status_snapshot().await?;
+
+            if json_output {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?
+                );
+            } else if compact {
+                println!("{}", status::render_compact(&snapshot));
+            } else {
+                print!("{}", status::render(&snapshot));
+            }
+            Ok(())
+        }
     }
 }
@@ -42,6 +42,8 @@ enum ReplCommands {
     #[command(subcommand)]
     History(HistoryCmd),
     #[command(subcommand)]
+    Ops(OpsCmd),
+    #[command(subcommand)]
     Portfolio(PortfolioCmd),
     #[command(subcommand)]
     BitcoinWallet(BitcoinWalletCmd),
@@ -153,7 +155,7 @@ pub(crate) async fn run_repl(
         println!("Relayer REPL — interactive mode");
         println!("  Wallet ID: {}", wallet_id);
         println!("  Address:   {}", ow.wallet.twilightaddress);
-        println!("  Accounts:  {}", ow.zk_accounts.accounts.len());
+        println!("  Accounts:  {}", ow.zk_accounts.len());
         println!();
         println!("Type commands without the `relayer-cli` prefix.");
         println!("Examples: wallet balance, order query-trade --account-index 0, market price");
@@ -239,6 +241,9 @@ pub(crate) async fn run_repl(
                 ReplCommands::History(cmd) => {
                     crate::history::handle_history(cmd, json_output, Some(&mut ow)).await
                 }
+                ReplCommands::Ops(cmd) => {
+                    crate::ops::handle_ops(cmd, json_output, Some(&mut ow)).await
+                }
                 ReplCommands::Portfolio(cmd) => {
                     crate::portfolio::handle_portfolio(cmd, json_output, Some(&mut ow)).await
                 }
@@ -264,7 +269,7 @@ pub(crate) async fn run_repl(
                         Ok(new_ow) => {
                             ow = new_ow;
                             println!("Wallet reloaded from database.");
-                            println!("  Accounts: {}", ow.zk_accounts.accounts.len());
+                            println!("  Accounts: {}", ow.zk_accounts.len());
                         }
                         Err(e) => eprintln!("Error reloading wallet: {e}"),
                     }
@@ -316,6 +321,7 @@ COMMANDS (same as CLI, without the `relayer-cli` prefix):
     order <sub>           Trading and lending orders
     market <sub>          Market data queries
     history <sub>         Local DB history
+    ops <sub>             Pending multi-step operations
     portfolio <sub>       Portfolio tracking
     bitcoin-wallet <sub>  On-chain BTC operations
     verify-test <sub>     Testnet verification
@@ -127,9 +127,7 @@ async fn verify_wallet(passed: &mut u32, failed: &mut u32, _skipped: &mut u32) {
 
     // 3. Faucet (get test tokens)
     vt_step(3, total_steps, "wallet faucet (get test tokens)");
-    let faucet_result = nyks_wallet::wallet::wallet::get_test_tokens(&mut ow.wallet)
-        .await
-        .map_err(|e| format!("{e}"));
+    let faucet_result = ow.request_test_tokens().await.map(|_| ());
     vt_result("wallet faucet", &faucet_result);
     if faucet_result.is_ok() {
         *passed += 1;
@@ -158,7 +156,7 @@ async fn verify_wallet(passed: &mut u32, failed: &mut u32, _skipped: &mut u32) {
     let export_path = format!("/tmp/verify-test-{}.json", chrono::Utc::now().timestamp());
     let export_result = ow
         .wallet
-        .export_to_json(&export_path)
+        .export_to_encrypted_json(&export_path, &SecretString::new("verify-test".into()))
         .map(|_| {
             println!("    Exported to: {export_path}");
             let _ = std::fs::remove_file(&export_path); // cleanup
@@ -174,7 +172,7 @@ async fn verify_wallet(passed: &mut u32, failed: &mut u32, _skipped: &mut u32) {
     // 6. Wallet accounts (ZkOS account list)
     vt_step(6, total_steps, "wallet accounts");
     {
-        let count = ow.zk_accounts.accounts.len();
+        let count = ow.zk_accounts.len();
         println!("  PASS: wallet accounts ({count} ZkOS accounts)");
         *passed += 1;
     }
@@ -387,9 +385,7 @@ async fn verify_zkaccount(passed: &mut u32, failed: &mut u32, skipped: &mut u32)
         let mut ow = OrderWallet::new(None).map_err(|e| e.to_string())?;
         let pwd = Some(SecretString::new(test_password.into()));
         ow.with_db(pwd, Some(test_wallet_id.clone()))?;
-        nyks_wallet::wallet::wallet::get_test_tokens(&mut ow.wallet)
-            .await
-            .map_err(|e| format!("{e}"))?;
+        ow.request_test_tokens().await?;
         let bal = ow
             .wallet
             .update_balance()
@@ -436,8 +432,8 @@ async fn verify_zkaccount(passed: &mut u32, failed: &mut u32, skipped: &mut u32)
     // 2. Query ZkOS account
     vt_step(2, total_steps, "zkaccount query");
     let query_result: Result<(), String> = {
-        match ow.zk_accounts.accounts.get(&account_index) {
-            Some(acct) => {
+        match ow.zk_accounts.get_account(&account_index) {
+            Ok(acct) => {
                 println!("  PASS: zkaccount query");
                 println!("    Index: {}", acct.index);
                 println!("    Balance: {}", acct.balance);
@@ -445,7 +441,7 @@ async fn verify_zkaccount(passed: &mut u32, failed: &mut u32, skipped: &mut u32)
                 *passed += 1;
                 Ok(())
             }
-            None => {
+            Err(_) => {
                 let err = format!("Account index {account_index} not found");
                 println!("  FAIL: zkaccount query -> {err}");
                 *failed += 1;
@@ -459,7 +455,7 @@ async fn verify_zkaccount(passed: &mut u32, failed: &mut u32, skipped: &mut u32)
     if query_result.is_ok() {
         let withdraw_result = ow.trading_to_funding(account_index).await;
         match &withdraw_result {
-            Ok(()) => {
+            Ok(_) => {
                 println!("  PASS: zkaccount withdraw (trading_to_funding)");
                 *passed += 1;
             }
@@ -488,7 +484,7 @@ async fn verify_zkaccount(_passed: &mut u32, failed: &mut u32, _skipped: &mut u3
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 async fn verify_order(passed: &mut u32, failed: &mut u32, skipped: &mut u32) {
     use secrecy::SecretString;
-    use twilight_client_sdk::relayer_types::{OrderType, PositionType};
+    use nyks_wallet::compat::relayer_types::{OrderType, PositionType};
 
     let total_steps = 3;
     let test_wallet_id = format!("verify-order-{}", chrono::Utc::now().timestamp());
@@ -503,9 +499,7 @@ async fn verify_order(passed: &mut u32, failed: &mut u32, skipped: &mut u32) {
         let mut ow = OrderWallet::new(None).map_err(|e| e.to_string())?;
         let pwd = Some(SecretString::new(test_password.into()));
         ow.with_db(pwd, Some(test_wallet_id.clone()))?;
-        nyks_wallet::wallet::wallet::get_test_tokens(&mut ow.wallet)
-            .await
-            .map_err(|e| format!("{e}"))?;
+        ow.request_test_tokens().await?;
         let bal = ow
             .wallet
             .update_balance()
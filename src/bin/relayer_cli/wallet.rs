@@ -1,6 +1,7 @@
 use nyks_wallet::relayer_module::order_wallet::OrderWallet;
 use nyks_wallet::wallet::btc_wallet::validation::validate_btc_segwit_address;
 use nyks_wallet::wallet::btc_wallet::BtcProposedReserve;
+use nyks_wallet::wallet::btc_withdrawal::{BtcWithdrawalId, WithdrawBtcRequestBuilder};
 use secrecy::{ExposeSecret, SecretString};
 
 use crate::commands::WalletCmd;
@@ -118,10 +119,12 @@ pub(crate) async fn handle_wallet(
             wallet_id,
             password,
             btc_address,
+            mnemonic_passphrase,
         } => {
             if let Some(ref addr) = btc_address {
                 validate_btc_segwit_address(addr)?;
             }
+            let ask_passphrase = mnemonic_passphrase || mnemonic.is_none();
             let mnemonic = match mnemonic {
                 Some(m) => m.trim().to_string(),
                 None => {
@@ -133,8 +136,20 @@ pub(crate) async fn handle_wallet(
                     m.trim().to_string()
                 }
             };
-            let mut ow =
-                OrderWallet::import_from_mnemonic(&mnemonic, None).map_err(|e| e.to_string())?;
+            let passphrase = if ask_passphrase {
+                let p = rpassword::prompt_password("Mnemonic passphrase (Enter for none): ")
+                    .map_err(|e| e.to_string())?;
+                (!p.is_empty()).then(|| SecretString::new(p))
+            } else {
+                None
+            };
+            let mut ow = match passphrase {
+                Some(passphrase) => {
+                    OrderWallet::import_from_mnemonic_with_passphrase(&mnemonic, passphrase, None)
+                }
+                None => OrderWallet::import_from_mnemonic(&mnemonic, None),
+            }
+            .map_err(|e| e.to_string())?;
 
             // Check BTC address registration status on-chain
             if let Some(addr) = btc_address {
@@ -233,7 +248,7 @@ pub(crate) async fn handle_wallet(
             println!("  Address: {}", ow.wallet.twilightaddress);
             println!("  BTC address: {}", ow.wallet.btc_address);
             println!("  Chain ID: {}", ow.chain_id);
-            println!("  ZkOS accounts: {}", ow.zk_accounts.accounts.len());
+            println!("  ZkOS accounts: {}", ow.zk_accounts.len());
             Ok(())
         }
 
@@ -287,13 +302,37 @@ pub(crate) async fn handle_wallet(
             output,
             wallet_id,
             password,
+            plaintext,
         } => {
             let ow = get_or_resolve_wallet(repl_wallet, wallet_id, password).await?;
 
+            if plaintext {
+                ow.wallet
+                    .export_to_json(&output, true)
+                    .map_err(|e| e.to_string())?;
+                println!("Wallet exported UNENCRYPTED to {output}");
+                return Ok(());
+            }
+            let export_password = match std::env::var("NYKS_EXPORT_PASSPHRASE") {
+                Ok(p) if !p.is_empty() => p,
+                _ => {
+                    let p = rpassword::prompt_password("Export file password: ")
+                        .map_err(|e| e.to_string())?;
+                    let confirm = rpassword::prompt_password("Confirm export file password: ")
+                        .map_err(|e| e.to_string())?;
+                    if p != confirm {
+                        return Err("passwords do not match".to_string());
+                    }
+                    p
+                }
+            };
+            if export_password.is_empty() {
+                return Err("export password must not be empty".to_string());
+            }
             ow.wallet
-                .export_to_json(&output)
+                .export_to_encrypted_json(&output, &SecretString::new(export_password.into()))
                 .map_err(|e| e.to_string())?;
-            println!("Wallet exported to {output}");
+            println!("Wallet exported (encrypted) to {output}");
             Ok(())
         }
 
@@ -348,7 +387,7 @@ pub(crate) async fn handle_wallet(
             println!("  BTC address:     {}", ow.wallet.btc_address);
             println!("  BTC registered:  {}", ow.wallet.btc_address_registered);
             println!("  Chain ID:        {}", ow.chain_id);
-            println!("  ZkOS accounts:   {}", ow.zk_accounts.accounts.len());
+            println!("  ZkOS accounts:   {}", ow.zk_accounts.len());
             println!("  Next nonce:      {}", ow.nonce_manager.peek_next());
             println!("  Account number:  {}", ow.nonce_manager.account_number());
             Ok(())
@@ -367,10 +406,7 @@ pub(crate) async fn handle_wallet(
             password,
         } => {
             let ow = load_order_wallet_from_db(&wallet_id, password, None)?;
-            let db_manager = ow
-                .get_db_manager()
-                .ok_or("Database not enabled on this wallet")?;
-            db_manager.export_backup_to_file(&output)?;
+            ow.export_backup_to_file(&output)?;
             println!("Backup exported to {output}");
             Ok(())
         }
@@ -393,6 +429,8 @@ pub(crate) async fn handle_wallet(
                 .get_db_manager()
                 .ok_or("Database not enabled on this wallet")?;
             db_manager.import_backup_from_file(&input, force)?;
+            // Re-encrypt the restored account secrets under this wallet's key
+            ow.with_wallet_password(|password| db_manager.unlock_zk_accounts(password))??;
             println!("Backup restored from {input}");
             Ok(())
         }
@@ -514,7 +552,7 @@ pub(crate) async fn handle_wallet(
             }
 
             // Load wallet with old password to verify it's correct
-            let ow = load_order_wallet_from_db(&wid, Some(old_password), None)?;
+            let mut ow = load_order_wallet_from_db(&wid, Some(old_password), None)?;
 
             let new_password =
                 rpassword::prompt_password("New password: ").map_err(|e| e.to_string())?;
@@ -527,11 +565,8 @@ pub(crate) async fn handle_wallet(
                 return Err("passwords do not match".to_string());
             }
 
-            let db_manager = ow
-                .get_db_manager()
-                .ok_or("database manager not available")?;
             let new_secret = SecretString::new(new_password.into());
-            db_manager.save_encrypted_wallet(&ow.wallet, &new_secret)?;
+            ow.change_wallet_password(new_secret.clone())?;
 
             // Update session cache if one exists
             if session_load().is_some() {
@@ -597,13 +632,7 @@ pub(crate) async fn handle_wallet(
             // Invalidate the old BTC wallet since the address has changed
             ow.wallet.btc_wallet = None;
 
-            let db_manager = ow
-                .get_db_manager()
-                .ok_or("database manager not available")?;
-            let wallet_password = ow
-                .get_wallet_password()
-                .ok_or("wallet password not available — cannot persist changes")?;
-            db_manager.save_encrypted_wallet(&ow.wallet, wallet_password)?;
+            ow.save_encrypted_wallet_to_db()?;
 
             println!("BTC address updated for wallet.");
             println!("  Old: {}", old_address);
@@ -825,11 +854,7 @@ pub(crate) async fn handle_wallet(
             println!("  TX Hash: {tx_hash}");
 
             // Persist the updated btc_address_registered flag
-            if let Some(db_manager) = ow.get_db_manager() {
-                if let Some(wallet_password) = ow.get_wallet_password() {
-                    let _ = db_manager.save_encrypted_wallet(&ow.wallet, wallet_password);
-                }
-            }
+            let _ = ow.save_encrypted_wallet_to_db();
 
             // 6. Pay to reserve — auto if btc_wallet available, manual otherwise
             if let (Some(btc_wallet), Some((target_reserve, blocks_left))) =
@@ -1383,33 +1408,30 @@ pub(crate) async fn handle_wallet(
             println!("Submitting BTC withdrawal request");
             println!("  From:       {tw_addr}");
             println!("  To (BTC):   {btc_addr}");
-            println!("  Reserve ID: {reserve_id}");
+            match reserve_id {
+                Some(reserve_id) => println!("  Reserve ID: {reserve_id}"),
+                None => println!("  Reserve ID: (largest reserve that can pay out)"),
+            }
             println!("  Amount:     {amount} sats");
 
-            match ow.wallet.withdraw_btc(&btc_addr, reserve_id, amount).await {
-                Ok(tx_hash) => {
-                    println!("\nWithdrawal request submitted successfully");
-                    println!("  TX Hash: {tx_hash}");
+            let result = match reserve_id {
+                Some(reserve_id) => {
+                    let msg = WithdrawBtcRequestBuilder::new(&tw_addr)
+                        .to(&btc_addr)
+                        .reserve(reserve_id)
+                        .amount(amount)
+                        .build()
+                        .map_err(|e| e.to_string())?;
+                    ow.submit_btc_withdrawal(msg).await
+                }
+                None => ow.request_btc_withdrawal(&btc_addr, amount, true).await,
+            };
 
-                    // Save withdrawal record to database
-                    if let Some(db_manager) = ow.get_db_manager() {
-                        let now = chrono::Utc::now().naive_utc();
-                        let withdrawal_entry = nyks_wallet::database::models::NewDbBtcWithdrawal {
-                            wallet_id: db_manager.get_wallet_id().to_string(),
-                            network_type: nyks_wallet::config::NETWORK_TYPE.to_string(),
-                            withdraw_address: btc_addr.clone(),
-                            twilight_address: tw_addr.clone(),
-                            reserve_id: reserve_id as i64,
-                            amount: amount as i64,
-                            tx_hash: Some(tx_hash.clone()),
-                            status: "submitted".to_string(),
-                            created_at: now,
-                            updated_at: now,
-                        };
-                        if let Err(e) = db_manager.save_btc_withdrawal(withdrawal_entry) {
-                            eprintln!("Warning: Could not save withdrawal to database: {e}");
-                        }
-                    }
+            match result {
+                Ok(submission) => {
+                    println!("\nWithdrawal request submitted successfully");
+                    println!("  Reserve ID: {}", submission.id.reserve_id);
+                    println!("  TX Hash: {}", submission.tx.hash);
 
                     println!("\nThe withdrawal will be processed by validators.");
                     println!("Check status with: wallet withdraw-status");
@@ -1461,28 +1483,28 @@ pub(crate) async fn handle_wallet(
                 );
                 let mut updated_count = 0;
                 for w in &pending {
-                    let amount: u64 = w.amount as u64;
-                    match ow
-                        .wallet
-                        .fetch_withdrawal_status(w.reserve_id as u64, &w.withdraw_address, amount)
-                        .await
-                    {
-                        Ok(Some(status)) => {
-                            if status.is_confirmed {
-                                // Update DB to confirmed
-                                if let Some(id) = w.id {
-                                    let _ =
-                                        db_manager.update_btc_withdrawal_status(id, "confirmed");
-                                    updated_count += 1;
-                                    println!(
-                                        "  Updated withdrawal #{} ({} sats to {}) -> CONFIRMED",
-                                        status.withdraw_identifier, w.amount, w.withdraw_address
-                                    );
-                                }
+                    let id = BtcWithdrawalId {
+                        reserve_id: w.reserve_id as u64,
+                        btc_address: w.withdraw_address.clone(),
+                        amount_sats: w.amount as u64,
+                    };
+                    match ow.wallet.withdrawal_status(&id).await {
+                        Ok(state) if state.is_confirmed() => {
+                            if let Some(row_id) = w.id {
+                                let _ = db_manager
+                                    .update_btc_withdrawal_status(row_id, state.db_status());
+                                updated_count += 1;
+                                println!(
+                                    "  Updated withdrawal ({} sats to {}) -> {}",
+                                    w.amount, w.withdraw_address, state
+                                );
                             }
                         }
-                        Ok(None) => {
-                            // Not found on chain yet — might still be processing
+                        Ok(state) => {
+                            println!(
+                                "  Withdrawal ({} sats to {}): {}",
+                                w.amount, w.withdraw_address, state
+                            );
                         }
                         Err(e) => {
                             eprintln!(
@@ -1853,15 +1875,18 @@ pub(crate) async fn handle_wallet(
             let tw_addr = ow.wallet.twilightaddress.clone();
 
             println!("Requesting test tokens for {tw_addr}...");
-            nyks_wallet::wallet::wallet::get_test_tokens(&mut ow.wallet)
+            let (report, _) = ow
+                .request_test_tokens()
                 .await
                 .map_err(|e| format!("Failed to get test tokens: {e}"))?;
+            for step in &report.steps {
+                println!(
+                    "  {:?}: {:?} ({:.1?})",
+                    step.kind, step.status, step.elapsed
+                );
+            }
 
-            let balance = ow
-                .wallet
-                .update_balance()
-                .await
-                .map_err(|e| e.to_string())?;
+            let balance = report.balance;
             println!("\nUpdated balance:");
             println!("  NYKS: {}", balance.nyks);
             println!("  SATS: {}", balance.sats);
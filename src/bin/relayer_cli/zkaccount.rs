@@ -70,8 +70,8 @@ pub(crate) async fn handle_zkaccount(
             let mut ow = get_or_resolve_wallet(repl_wallet, wallet_id, password).await?;
 
             println!("Withdrawing from ZkOS account {account_index} back to on-chain wallet...");
-            ow.trading_to_funding(account_index).await?;
-            println!("Withdrawal successful");
+            let result = ow.trading_to_funding(account_index).await?;
+            println!("Withdrawal successful: tx {}", result.tx_hash);
             Ok(())
         }
 
@@ -167,13 +167,20 @@ pub(crate) async fn handle_zkaccount(
                 balance_vec.len(),
                 total
             );
-            let results = ow
+            let outcome = ow
                 .trading_to_trading_multiple_accounts(account_index, balance_vec)
                 .await?;
-            println!("Split successful");
-            for (idx, bal) in &results {
+            if outcome.is_complete() {
+                println!("Split successful");
+            } else {
+                println!("Split transaction sent; some accounts are unconfirmed");
+            }
+            for (idx, bal) in &outcome.confirmed {
                 println!("  Account {}: {} sats", idx, bal);
             }
+            for (idx, e) in &outcome.unconfirmed {
+                println!("  Account {}: unconfirmed ({})", idx, e);
+            }
             Ok(())
         }
     }
@@ -14,7 +14,7 @@ use nyks_wallet::{
 
 use secrecy::ExposeSecret;
 use tokio::time::{Duration, sleep};
-use twilight_client_sdk::{
+use nyks_wallet::compat::{
     script,
     transaction::Transaction,
     util,
@@ -161,7 +161,7 @@ async fn fetch_utxo_details_with_retry(
     loop {
         let account_id_clone = account_id.clone();
         match tokio::task::spawn_blocking(move || {
-            twilight_client_sdk::chain::get_utxo_details_by_address(account_id_clone, IOType::Coin)
+            nyks_wallet::compat::chain::get_utxo_details_by_address(account_id_clone, IOType::Coin)
         })
         .await
         {
@@ -288,7 +288,7 @@ async fn main() -> Result<(), String> {
     info!("    Broadcasting deployment transaction...");
     let tx_clone = tx.clone();
     let broadcast_result = match tokio::task::spawn_blocking(move || {
-        twilight_client_sdk::chain::tx_commit_broadcast_transaction(tx_clone)
+        nyks_wallet::compat::chain::tx_commit_broadcast_transaction(tx_clone)
     })
     .await
     {
@@ -314,7 +314,7 @@ async fn main() -> Result<(), String> {
     zk_accounts.update_on_chain(&index, true)?;
     zk_accounts.export_to_json("ZkAccounts.json")?;
 
-    let updated_zk_account = zk_accounts.get_account(&index).unwrap();
+    let updated_zk_account = zk_accounts.get_account(&index)?;
     info!("    Exporting relayer data to file...");
     export_relayer_data(
         &updated_zk_account,
@@ -344,7 +344,7 @@ pub fn deploy_relayer_initial_state(
     };
     let program_json_path: &str = &std::env::var("RELAYER_PROGRAM_JSON_PATH")
         .unwrap_or_else(|_| "./relayerprogram.json".to_string());
-    let chain_net = twilight_client_sdk::address::Network::default();
+    let chain_net = nyks_wallet::compat::address::Network::default();
     let state_variables: Vec<u64> = vec![balance.clone() / 100];
     let program_tag: String = "RelayerInitializer".to_string();
     let pool_share = balance.clone() / 100;
@@ -0,0 +1,382 @@
+//! Synchronous [`OrderWallet`] for hosts without an async runtime.
+//!
+//! [`OrderWallet`] here wraps the async
+//! [`relayer_module::order_wallet::OrderWallet`](AsyncOrderWallet) together
+//! with a multi-thread tokio runtime it owns, and runs each call to
+//! completion on that runtime. Every method forwards to the async method of
+//! the same name, so validation, state and persistence are exactly those of
+//! the async wallet. Background tasks the wallet spawns keep running on the
+//! runtime's workers between calls.
+//!
+//! Blocking inside another async runtime would stall the thread driving it,
+//! so a call made from async code fails with [`NESTED_RUNTIME`] instead of
+//! panicking; use the async wallet there. Calls that are synchronous in the
+//! async API go through [`inner`](OrderWallet::inner) and
+//! [`inner_mut`](OrderWallet::inner_mut).
+//!
+//! ```no_run
+//! use nyks_wallet::blocking::OrderWallet;
+//! use nyks_wallet::compat::relayer_types::{OrderType, PositionType};
+//!
+//! fn main() -> Result<(), String> {
+//!     let mut order_wallet = OrderWallet::new(None).map_err(|e| e.to_string())?;
+//!     let (_, account_index) = order_wallet.funding_to_trading(10_000)?;
+//!     order_wallet.open_trader_order(
+//!         account_index,
+//!         OrderType::MARKET,
+//!         PositionType::LONG,
+//!         50_000,
+//!         10,
+//!     )?;
+//!     let order = order_wallet.query_trader_order(account_index)?;
+//!     println!("Order status: {:?}", order.order_status);
+//!     Ok(())
+//! }
+//! ```
+
+use std::future::Future;
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::compat::relayer_types::{LendOrder, OrderType, PositionType, TraderOrder};
+use crate::config::EndpointConfig;
+use crate::error::{OrderWalletResult, Result as WalletResult, WalletError};
+use crate::relayer_module::TxResult;
+use crate::relayer_module::leverage::Leverage;
+use crate::relayer_module::order_wallet::{AccountIndex, OrderWallet as AsyncOrderWallet};
+use crate::relayer_module::portfolio::Portfolio;
+use crate::wallet::Balance;
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::config::ConfigDrift;
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use secrecy::SecretString;
+
+/// Error of a blocking call made from within an async runtime.
+pub const NESTED_RUNTIME: &str = "nyks_wallet::blocking::OrderWallet was called from within an async runtime; use relayer_module::order_wallet::OrderWallet there";
+
+/// The runtime the wallet's calls run on.
+#[derive(Debug)]
+struct BlockingRuntime {
+    /// Only `None` while dropping.
+    runtime: Option<Runtime>,
+}
+
+impl BlockingRuntime {
+    fn new() -> Result<Self, String> {
+        ensure_not_in_runtime()?;
+        let runtime = Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("nyks-wallet-blocking")
+            .build()
+            .map_err(|e| format!("Failed to start the wallet runtime: {}", e))?;
+        Ok(Self {
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Run `future` on the runtime, or fail with [`NESTED_RUNTIME`] when
+    /// called from async code.
+    fn block_on<T, E: From<String>>(
+        &self,
+        future: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        ensure_not_in_runtime()?;
+        match self.runtime.as_ref() {
+            Some(runtime) => runtime.block_on(future),
+            None => Err("The wallet runtime has shut down".to_string().into()),
+        }
+    }
+}
+
+impl Drop for BlockingRuntime {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks on its workers, which panics in async code.
+        if let Some(runtime) = self.runtime.take() {
+            if Handle::try_current().is_ok() {
+                runtime.shutdown_background();
+            }
+        }
+    }
+}
+
+fn ensure_not_in_runtime() -> Result<(), String> {
+    match Handle::try_current() {
+        Ok(_) => Err(NESTED_RUNTIME.to_string()),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Synchronous wrapper of the async `OrderWallet`; see the [module docs](self).
+#[derive(Debug)]
+pub struct OrderWallet {
+    // Declared first so the wallet is dropped while the runtime still runs.
+    inner: AsyncOrderWallet,
+    runtime: BlockingRuntime,
+}
+
+impl OrderWallet {
+    /// Wrap an async wallet, e.g. one configured with builder methods that
+    /// are not mirrored here. Fails inside an async runtime.
+    pub fn from_async(inner: AsyncOrderWallet) -> Result<Self, String> {
+        Ok(Self {
+            inner,
+            runtime: BlockingRuntime::new()?,
+        })
+    }
+
+    /// See [`OrderWallet::new`](AsyncOrderWallet::new).
+    pub fn new(endpoint_config: Option<EndpointConfig>) -> WalletResult<Self> {
+        let runtime = BlockingRuntime::new().map_err(WalletError::WalletCreation)?;
+        Ok(Self {
+            inner: AsyncOrderWallet::new(endpoint_config)?,
+            runtime,
+        })
+    }
+
+    /// See [`OrderWallet::import_from_mnemonic`](AsyncOrderWallet::import_from_mnemonic).
+    pub fn import_from_mnemonic(
+        mnemonic: &str,
+        endpoint_config: Option<EndpointConfig>,
+    ) -> Result<Self, String> {
+        let runtime = BlockingRuntime::new()?;
+        Ok(Self {
+            inner: AsyncOrderWallet::import_from_mnemonic(mnemonic, endpoint_config)?,
+            runtime,
+        })
+    }
+
+    /// See [`OrderWallet::import_from_private_key`](AsyncOrderWallet::import_from_private_key).
+    pub fn import_from_private_key(
+        private_key_hex: &str,
+        btc_address: Option<&str>,
+        endpoint_config: Option<EndpointConfig>,
+    ) -> WalletResult<Self> {
+        let runtime = BlockingRuntime::new().map_err(WalletError::WalletCreation)?;
+        Ok(Self {
+            inner: AsyncOrderWallet::import_from_private_key(
+                private_key_hex,
+                btc_address,
+                endpoint_config,
+            )?,
+            runtime,
+        })
+    }
+
+    /// See [`OrderWallet::load_from_db`](AsyncOrderWallet::load_from_db).
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn load_from_db(
+        wallet_id: String,
+        password: Option<SecretString>,
+        db_url: Option<String>,
+    ) -> Result<(Self, ConfigDrift), String> {
+        let runtime = BlockingRuntime::new()?;
+        let (inner, drift) = AsyncOrderWallet::load_from_db(wallet_id, password, db_url)?;
+        Ok((Self { inner, runtime }, drift))
+    }
+
+    /// See [`OrderWallet::with_db`](AsyncOrderWallet::with_db).
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn with_db(
+        &mut self,
+        wallet_password: Option<SecretString>,
+        wallet_id: Option<String>,
+    ) -> Result<(), String> {
+        self.inner.with_db(wallet_password, wallet_id).map(drop)
+    }
+
+    pub fn inner(&self) -> &AsyncOrderWallet {
+        &self.inner
+    }
+
+    /// The async wallet, for its synchronous methods. Those that spawn
+    /// background tasks need the runtime entered; call them through
+    /// [`enter`](Self::enter).
+    pub fn inner_mut(&mut self) -> &mut AsyncOrderWallet {
+        &mut self.inner
+    }
+
+    /// Run `f` on the async wallet inside the runtime's context, so tasks it
+    /// spawns land on the runtime.
+    pub fn enter<T>(&mut self, f: impl FnOnce(&mut AsyncOrderWallet) -> T) -> Result<T, String> {
+        ensure_not_in_runtime()?;
+        let runtime = self
+            .runtime
+            .runtime
+            .as_ref()
+            .ok_or("The wallet runtime has shut down")?;
+        let _guard = runtime.enter();
+        Ok(f(&mut self.inner))
+    }
+
+    // -------------------------
+    // Funding and transfers
+    // -------------------------
+
+    /// Refresh and return the on-chain balance of the funding wallet.
+    pub fn update_balance(&mut self) -> Result<Balance, String> {
+        let wallet = &mut self.inner.wallet;
+        self.runtime
+            .block_on(async move { wallet.update_balance().await.map_err(|e| e.to_string()) })
+    }
+
+    pub fn funding_to_trading(&mut self, amount: u64) -> OrderWalletResult<(TxResult, u64)> {
+        self.runtime.block_on(self.inner.funding_to_trading(amount))
+    }
+
+    pub fn trading_to_trading(&mut self, index: AccountIndex) -> OrderWalletResult<AccountIndex> {
+        self.runtime.block_on(self.inner.trading_to_trading(index))
+    }
+
+    pub fn trading_to_funding(&mut self, index: AccountIndex) -> OrderWalletResult<TxResult> {
+        self.runtime.block_on(self.inner.trading_to_funding(index))
+    }
+
+    pub fn sync_account_state(&mut self, index: AccountIndex) -> OrderWalletResult<()> {
+        self.runtime.block_on(self.inner.sync_account_state(index))
+    }
+
+    // -------------------------
+    // Trader orders
+    // -------------------------
+
+    pub fn open_trader_order(
+        &mut self,
+        index: AccountIndex,
+        order_type: OrderType,
+        order_side: PositionType,
+        entry_price: u64,
+        leverage: impl Into<Leverage>,
+    ) -> OrderWalletResult<String> {
+        self.runtime.block_on(self.inner.open_trader_order(
+            index,
+            order_type,
+            order_side,
+            entry_price,
+            leverage,
+        ))
+    }
+
+    pub fn close_trader_order(
+        &mut self,
+        index: AccountIndex,
+        order_type: OrderType,
+        execution_price: f64,
+    ) -> OrderWalletResult<String> {
+        self.runtime.block_on(
+            self.inner
+                .close_trader_order(index, order_type, execution_price),
+        )
+    }
+
+    pub fn close_trader_order_sltp(
+        &mut self,
+        index: AccountIndex,
+        order_type: OrderType,
+        execution_price: f64,
+        stop_loss_price: Option<f64>,
+        take_profit_price: Option<f64>,
+    ) -> OrderWalletResult<String> {
+        self.runtime.block_on(self.inner.close_trader_order_sltp(
+            index,
+            order_type,
+            execution_price,
+            stop_loss_price,
+            take_profit_price,
+        ))
+    }
+
+    pub fn cancel_trader_order(&mut self, index: AccountIndex) -> OrderWalletResult<String> {
+        self.runtime.block_on(self.inner.cancel_trader_order(index))
+    }
+
+    pub fn query_trader_order(&self, index: AccountIndex) -> OrderWalletResult<TraderOrder> {
+        self.runtime.block_on(self.inner.query_trader_order(index))
+    }
+
+    pub fn query_trader_order_by_id(&self, order_id: &str) -> OrderWalletResult<TraderOrder> {
+        self.runtime
+            .block_on(self.inner.query_trader_order_by_id(order_id))
+    }
+
+    // -------------------------
+    // Lend orders
+    // -------------------------
+
+    pub fn open_lend_order(&mut self, index: AccountIndex) -> OrderWalletResult<String> {
+        self.runtime.block_on(self.inner.open_lend_order(index))
+    }
+
+    pub fn close_lend_order(&mut self, index: AccountIndex) -> OrderWalletResult<String> {
+        self.runtime.block_on(self.inner.close_lend_order(index))
+    }
+
+    pub fn query_lend_order(&self, index: AccountIndex) -> OrderWalletResult<LendOrder> {
+        self.runtime.block_on(self.inner.query_lend_order(index))
+    }
+
+    pub fn query_lend_order_by_id(&self, order_id: &str) -> OrderWalletResult<LendOrder> {
+        self.runtime
+            .block_on(self.inner.query_lend_order_by_id(order_id))
+    }
+
+    // -------------------------
+    // Portfolio
+    // -------------------------
+
+    pub fn get_portfolio_summary(&mut self) -> Result<Portfolio, String> {
+        self.runtime.block_on(self.inner.get_portfolio_summary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calls_from_async_code_fail_instead_of_panicking() {
+        let runtime = BlockingRuntime::new().unwrap();
+        assert_eq!(runtime.block_on(async { Ok::<_, String>(7) }), Ok(7));
+
+        let outer = Builder::new_current_thread().build().unwrap();
+        let nested = outer.block_on(async { runtime.block_on(async { Ok::<_, String>(7) }) });
+        assert_eq!(nested, Err(NESTED_RUNTIME.to_string()));
+        assert!(outer.block_on(async { BlockingRuntime::new() }).is_err());
+        // Dropping inside async code shuts the runtime down in the background.
+        outer.block_on(async move { drop(runtime) });
+    }
+
+    #[test]
+    fn test_wrapped_calls_return_the_async_error() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::zkos_accounts::zkaccount::ZkAccount;
+        use std::sync::Arc;
+
+        const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        // A Coin account with no order, on a scripted relayer.
+        let wallet = || -> Result<AsyncOrderWallet, String> {
+            let mut wallet = AsyncOrderWallet::import_from_mnemonic(MNEMONIC, None)?
+                .with_relayer(Arc::new(MockRelayer::new()));
+            let account = ZkAccount::new(
+                "qq".to_string(),
+                1_000,
+                "acct".to_string(),
+                "scalar".to_string(),
+                0,
+            );
+            wallet.zk_accounts.add_account(account)?;
+            Ok(wallet)
+        };
+        let expected = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?
+            .block_on(wallet()?.cancel_trader_order(0))
+            .unwrap_err();
+
+        let mut blocking = OrderWallet::from_async(wallet()?)?;
+        let err = blocking.cancel_trader_order(0).unwrap_err();
+        assert_eq!(err.to_string(), expected.to_string());
+        Ok(())
+    }
+}
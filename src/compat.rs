@@ -0,0 +1,691 @@
+//! Pinned adapter layer over `twilight_client_sdk`.
+//!
+//! This is the only module that names the SDK crate. Everything else in the
+//! crate, binaries included, imports SDK types through the re-exports below,
+//! so an SDK upgrade that moves or renames a type is fixed here once (for
+//! example with `pub use new_name as old_name;`). A unit test fails the build
+//! if any other file under `src/` refers to the SDK directly.
+//!
+//! SDK functions are not re-exported. [`chain`] and [`relayer`], and with
+//! `zk-accounts` also `script` and `util`, hold one wrapper per function the
+//! crate calls, with the signature
+//! the crate uses: order types and statuses are passed as enums rather than
+//! the SDK's strings, arguments this crate always sets the same way (zero
+//! fees, the initial `PENDING` status) are filled in by the wrapper, and
+//! errors come back as `String`. When an SDK upgrade renames a function,
+//! reorders its parameters or changes its error type, only the wrapper body
+//! changes.
+//!
+//! The SDK calls behind [`OrderWallet`](crate::relayer_module::order_wallet::OrderWallet)
+//! transfers also sit behind traits: building transfer transactions (and
+//! their proofs) with [`TransferBuilder`], and broadcasting them with
+//! [`ChainBroadcaster`]. The default implementations forward to the SDK
+//! unchanged; tests swap in fixtures through
+//! [`OrderWallet::with_transfer_builder`](crate::relayer_module::order_wallet::OrderWallet::with_transfer_builder)
+//! and [`OrderWallet::with_chain_broadcaster`](crate::relayer_module::order_wallet::OrderWallet::with_chain_broadcaster).
+//!
+//! SDK calls can panic (proof construction in particular). [`guard`],
+//! [`guard_result`] and [`guard_async`] run a call under `catch_unwind` and
+//! report panics and errors as [`WalletError::ClientSdk`], with the
+//! operation and a short context (account index, input sizes);
+//! [`join_error`] does the same for calls already on a `spawn_blocking`
+//! thread.
+
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::error::{Result as WalletResult, WalletError};
+
+pub use twilight_client_sdk::{
+    address, programcontroller, quisquislib, relayer_rpcclient, relayer_types, transaction, zkvm,
+};
+
+// -------------------------
+// Pinned SDK functions
+// -------------------------
+
+/// Chain queries and broadcasts.
+pub mod chain {
+    use super::relayer_rpcclient::method::UtxoDetailResponse;
+    use super::transaction::Transaction;
+    use super::zkvm::{IOType, Input};
+
+    /// Coin input of the account at `address`, as the chain has it now.
+    pub fn get_transaction_coin_input_from_address_fast(address: String) -> Result<Input, String> {
+        twilight_client_sdk::chain::get_transaction_coin_input_from_address_fast(address)
+            .map_err(|e| e.to_string())
+    }
+
+    /// UTXO of `io_type` held by `address`. The error keeps the chain's
+    /// message, e.g. `UTXO not found`.
+    pub fn get_utxo_details_by_address(
+        address: String,
+        io_type: IOType,
+    ) -> Result<UtxoDetailResponse, String> {
+        twilight_client_sdk::chain::get_utxo_details_by_address(address, io_type)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Broadcast `tx` and return its hash.
+    pub fn tx_commit_broadcast_transaction(tx: Transaction) -> Result<String, String> {
+        twilight_client_sdk::chain::tx_commit_broadcast_transaction(tx).map_err(|e| e.to_string())
+    }
+}
+
+/// Signed relayer requests, hex-encoded as the relayer expects them.
+pub mod relayer {
+    use uuid::Uuid;
+
+    use super::quisquislib::RistrettoSecretKey;
+    use super::relayer_types::{OrderStatus, OrderType, SlTpOrder, SlTpOrderCancel, TXType};
+    use super::zkvm::{Input, Output};
+
+    /// New trader order on `input_coin`, `PENDING` at `entry_price`.
+    #[cfg(feature = "zk-accounts")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_trader_order_zkos(
+        input_coin: Input,
+        secret_key: RistrettoSecretKey,
+        rscalar: curve25519_dalek::scalar::Scalar,
+        value: u64,
+        position_type: super::relayer_types::PositionType,
+        order_type: OrderType,
+        leverage: f64,
+        entry_price: u64,
+        position_value: u64,
+        position_size: u64,
+        programs: &super::programcontroller::ContractManager,
+    ) -> Result<String, String> {
+        twilight_client_sdk::relayer::create_trader_order_zkos(
+            input_coin,
+            secret_key,
+            rscalar,
+            value,
+            position_type.to_str(),
+            order_type.to_str(),
+            leverage,
+            value as f64,
+            value as f64,
+            "PENDING".to_string(),
+            entry_price as f64,
+            entry_price as f64,
+            position_value,
+            position_size,
+            position_type.clone(),
+            programs,
+            0u32,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// New `PENDING` lend order of `amount` on `input_coin`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_lend_order_zkos(
+        input_coin: Input,
+        output_memo: Output,
+        secret_key: RistrettoSecretKey,
+        scalar_hex: String,
+        amount: u64,
+        account_address: String,
+    ) -> Result<String, String> {
+        twilight_client_sdk::relayer::create_lend_order_zkos(
+            input_coin,
+            output_memo,
+            secret_key,
+            scalar_hex,
+            amount,
+            account_address,
+            amount as f64,
+            OrderType::LEND.to_str(),
+            OrderStatus::PENDING.to_str(),
+            amount as f64,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Settle the order behind `output_memo` as `FILLED`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_order_zkos(
+        output_memo: Output,
+        secret_key: &RistrettoSecretKey,
+        account_id: String,
+        uuid: Uuid,
+        order_type: OrderType,
+        execution_price: f64,
+        tx_type: TXType,
+    ) -> String {
+        twilight_client_sdk::relayer::execute_order_zkos(
+            output_memo,
+            secret_key,
+            account_id,
+            uuid,
+            order_type.to_str(),
+            0.0,
+            OrderStatus::FILLED.to_str(),
+            execution_price,
+            tx_type,
+        )
+    }
+
+    /// [`execute_order_zkos`] for a trader order, with stop-loss and
+    /// take-profit.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_order_zkos_sltp(
+        output_memo: Output,
+        secret_key: &RistrettoSecretKey,
+        account_id: String,
+        uuid: Uuid,
+        order_type: OrderType,
+        execution_price: f64,
+        sltp: SlTpOrder,
+    ) -> String {
+        twilight_client_sdk::relayer::execute_order_zkos_sltp(
+            output_memo,
+            secret_key,
+            account_id,
+            uuid,
+            order_type.to_str(),
+            0.0,
+            OrderStatus::FILLED.to_str(),
+            execution_price,
+            TXType::ORDERTX,
+            Some(sltp),
+        )
+    }
+
+    /// Cancel the pending `order_type` order of `account_address`.
+    pub fn cancel_trader_order_zkos(
+        account_address: String,
+        secret_key: &RistrettoSecretKey,
+        account_id: String,
+        uuid: Uuid,
+        order_type: OrderType,
+    ) -> String {
+        twilight_client_sdk::relayer::cancel_trader_order_zkos(
+            account_address,
+            secret_key,
+            account_id,
+            uuid,
+            order_type.to_str(),
+            OrderStatus::CANCELLED.to_str(),
+        )
+    }
+
+    /// Cancel the stop-loss and/or take-profit in `sltp_cancel`.
+    pub fn cancel_trader_order_zkos_sltp(
+        account_address: String,
+        secret_key: &RistrettoSecretKey,
+        account_id: String,
+        uuid: Uuid,
+        sltp_cancel: SlTpOrderCancel,
+    ) -> String {
+        twilight_client_sdk::relayer::cancel_trader_order_zkos_sltp(
+            account_address,
+            secret_key,
+            account_id,
+            uuid,
+            OrderType::SLTP.to_str(),
+            OrderStatus::CANCELLED.to_str(),
+            sltp_cancel,
+        )
+    }
+
+    /// Query the trader order of `account_address` in `status`.
+    pub fn query_trader_order_zkos(
+        account_address: String,
+        secret_key: &RistrettoSecretKey,
+        account_id: String,
+        status: OrderStatus,
+    ) -> String {
+        twilight_client_sdk::relayer::query_trader_order_zkos(
+            account_address,
+            secret_key,
+            account_id,
+            status.to_str(),
+        )
+    }
+
+    /// Query the lend order of `account_address` in `status`.
+    pub fn query_lend_order_zkos(
+        account_address: String,
+        secret_key: &RistrettoSecretKey,
+        account_id: String,
+        status: OrderStatus,
+    ) -> String {
+        twilight_client_sdk::relayer::query_lend_order_zkos(
+            account_address,
+            secret_key,
+            account_id,
+            status.to_str(),
+        )
+    }
+}
+
+/// Contract deployment.
+#[cfg(feature = "zk-accounts")]
+pub mod script {
+    use curve25519_dalek::scalar::Scalar;
+
+    use super::address::Network;
+    use super::quisquislib::RistrettoSecretKey;
+    use super::transaction::Transaction;
+    use super::zkvm::Output;
+
+    /// Deploy the program tagged `program_tag` from `program_json_path`,
+    /// returning the transaction and the contract's initial state output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_contract_deploy_transaction(
+        secret_key: RistrettoSecretKey,
+        balance: u64,
+        pool_share: u64,
+        account: String,
+        scalar: Scalar,
+        program_json_path: &str,
+        network: Network,
+        state_variables: Vec<u64>,
+        program_tag: String,
+        fee: u64,
+    ) -> Result<(Transaction, Output), String> {
+        twilight_client_sdk::script::create_contract_deploy_transaction(
+            secret_key,
+            balance,
+            pool_share,
+            account,
+            scalar,
+            program_json_path,
+            network,
+            state_variables,
+            program_tag,
+            fee,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Scalars and output memos.
+#[cfg(feature = "zk-accounts")]
+pub mod util {
+    use curve25519_dalek::scalar::Scalar;
+
+    use super::address::Network;
+    use super::programcontroller::ContractManager;
+    use super::zkvm::Output;
+
+    /// Parse a hex-encoded scalar; `None` if it is not one.
+    pub fn hex_to_scalar(hex: String) -> Option<Scalar> {
+        twilight_client_sdk::util::hex_to_scalar(hex)
+    }
+
+    /// Output memo of a lend order of `amount` from `account_address` to the
+    /// lend pool contract of `programs`.
+    pub fn create_output_memo_for_lender(
+        programs: &ContractManager,
+        account_address: String,
+        amount: u64,
+        scalar: Scalar,
+    ) -> Result<Output, String> {
+        let script_address = programs.create_contract_address(Network::default())?;
+        Ok(twilight_client_sdk::util::create_output_memo_for_lender(
+            script_address,
+            account_address,
+            amount,
+            0,
+            scalar,
+            0,
+        ))
+    }
+}
+
+// -------------------------
+// Panic containment
+// -------------------------
+
+/// Run the SDK call `f`, reporting a panic as [`WalletError::ClientSdk`].
+pub fn guard<T>(operation: &str, context: &str, f: impl FnOnce() -> T) -> WalletResult<T> {
+    catch_unwind(AssertUnwindSafe(f)).map_err(|payload| panicked(operation, context, payload))
+}
+
+/// [`guard`] for SDK calls that return a `Result`; their errors get the same context.
+pub fn guard_result<T, E: std::fmt::Display>(
+    operation: &str,
+    context: &str,
+    f: impl FnOnce() -> Result<T, E>,
+) -> WalletResult<T> {
+    guard(operation, context, f)?.map_err(|e| client_sdk(operation, context, e))
+}
+
+/// [`guard`] for async calls that run SDK code while polled.
+pub async fn guard_async<T>(
+    operation: &str,
+    context: &str,
+    future: impl Future<Output = T>,
+) -> WalletResult<T> {
+    CatchUnwind(Box::pin(future))
+        .await
+        .map_err(|payload| panicked(operation, context, payload))
+}
+
+/// Map the join error of a `spawn_blocking` task running an SDK call.
+pub fn join_error(operation: &str, context: &str, error: tokio::task::JoinError) -> WalletError {
+    if error.is_panic() {
+        panicked(operation, context, error.into_panic())
+    } else {
+        client_sdk(operation, context, error)
+    }
+}
+
+fn client_sdk(operation: &str, context: &str, error: impl std::fmt::Display) -> WalletError {
+    WalletError::ClientSdk {
+        operation: operation.to_string(),
+        detail: format!("{}: {}", context, error),
+    }
+}
+
+fn panicked(operation: &str, context: &str, payload: Box<dyn std::any::Any + Send>) -> WalletError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string());
+    client_sdk(operation, context, format_args!("panicked: {}", message))
+}
+
+/// Polls the wrapped future under `catch_unwind`.
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = self.0.as_mut();
+        match catch_unwind(AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(value)) => Poll::Ready(Ok(value)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+#[cfg(feature = "order-wallet")]
+pub use seams::*;
+
+#[cfg(feature = "order-wallet")]
+mod seams {
+    use curve25519_dalek::scalar::Scalar;
+
+    use super::quisquislib::RistrettoSecretKey;
+    use super::transaction::{Sender, Transaction};
+    use super::zkvm::Input;
+
+    /// A single-receiver transfer built by [`TransferBuilder::single_receiver`].
+    #[derive(Debug, Clone)]
+    pub struct SingleTransfer {
+        /// `None` when the SDK could not assemble the transaction.
+        pub tx: Option<Transaction>,
+        /// Hex encryption scalar of the receiver's new output.
+        pub encrypt_scalar_hex: String,
+    }
+
+    /// A one-sender, many-receiver transfer built by
+    /// [`TransferBuilder::multiple_receivers`].
+    #[derive(Debug, Clone)]
+    pub struct MultiTransfer {
+        pub tx: Option<Transaction>,
+        /// Encryption scalar of each receiver output, in receiver order.
+        pub encrypt_scalars: Vec<String>,
+    }
+
+    /// Builds ZkOS transfer transactions, including their proofs.
+    pub trait TransferBuilder: std::fmt::Debug + Send + Sync {
+        /// Move `amount` from the account behind `input` to `receiver`.
+        #[allow(clippy::too_many_arguments)]
+        fn single_receiver(
+            &self,
+            sender_key: RistrettoSecretKey,
+            input: Input,
+            receiver: String,
+            amount: u64,
+            address_input: bool,
+            updated_sender_balance: u64,
+            fee: u64,
+        ) -> SingleTransfer;
+
+        /// Split one sender across several new receivers.
+        #[allow(clippy::too_many_arguments)]
+        fn multiple_receivers(
+            &self,
+            senders: Vec<Sender>,
+            input: Input,
+            sender_key: RistrettoSecretKey,
+            updated_sender_balances: Vec<u64>,
+            updated_receiver_balances: Vec<u64>,
+            receiver_scalars: Option<&[Scalar]>,
+            fee: u64,
+        ) -> Result<MultiTransfer, String>;
+
+        /// Hex-encoded transaction that burns `amount` from the account behind `input`.
+        fn burn_message(
+            &self,
+            input: Input,
+            amount: u64,
+            encrypt_scalar_hex: String,
+            sender_key: RistrettoSecretKey,
+            account: String,
+        ) -> String;
+    }
+
+    /// Submits a finished transaction to the ZkOS chain. Blocking; callers run
+    /// it on a blocking thread.
+    pub trait ChainBroadcaster: std::fmt::Debug + Send + Sync {
+        /// Broadcast `tx` and return its hash.
+        fn broadcast(&self, tx: Transaction) -> Result<String, String>;
+    }
+
+    /// [`TransferBuilder`] backed by the SDK's `transfer` module.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SdkTransferBuilder;
+
+    impl TransferBuilder for SdkTransferBuilder {
+        fn single_receiver(
+            &self,
+            sender_key: RistrettoSecretKey,
+            input: Input,
+            receiver: String,
+            amount: u64,
+            address_input: bool,
+            updated_sender_balance: u64,
+            fee: u64,
+        ) -> SingleTransfer {
+            let tx_wallet = twilight_client_sdk::transfer::create_private_transfer_tx_single(
+                sender_key,
+                input,
+                receiver,
+                amount,
+                address_input,
+                updated_sender_balance,
+                fee,
+            );
+            SingleTransfer {
+                tx: tx_wallet.get_tx(),
+                encrypt_scalar_hex: tx_wallet.get_encrypt_scalar_hex(),
+            }
+        }
+
+        fn multiple_receivers(
+            &self,
+            senders: Vec<Sender>,
+            input: Input,
+            sender_key: RistrettoSecretKey,
+            updated_sender_balances: Vec<u64>,
+            updated_receiver_balances: Vec<u64>,
+            receiver_scalars: Option<&[Scalar]>,
+            fee: u64,
+        ) -> Result<MultiTransfer, String> {
+            let receiver_scalars = receiver_scalars.map(<[Scalar]>::to_vec);
+            let tx_wallet =
+                twilight_client_sdk::transfer::create_private_transfer_transaction_single_source_multiple_recievers(
+                    senders,
+                    input,
+                    sender_key,
+                    updated_sender_balances,
+                    updated_receiver_balances,
+                    receiver_scalars.as_ref(),
+                    fee,
+                )?;
+            Ok(MultiTransfer {
+                tx: tx_wallet.get_tx(),
+                encrypt_scalars: tx_wallet
+                    .get_encrypt_scalar()
+                    .iter()
+                    .map(|scalar| scalar.to_string())
+                    .collect(),
+            })
+        }
+
+        fn burn_message(
+            &self,
+            input: Input,
+            amount: u64,
+            encrypt_scalar_hex: String,
+            sender_key: RistrettoSecretKey,
+            account: String,
+        ) -> String {
+            twilight_client_sdk::transfer::create_burn_message_transaction(
+                input,
+                amount,
+                encrypt_scalar_hex,
+                sender_key,
+                account,
+            )
+        }
+    }
+
+    /// [`ChainBroadcaster`] backed by [`chain::tx_commit_broadcast_transaction`](super::chain::tx_commit_broadcast_transaction).
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SdkChainBroadcaster;
+
+    impl ChainBroadcaster for SdkChainBroadcaster {
+        fn broadcast(&self, tx: Transaction) -> Result<String, String> {
+            super::chain::tx_commit_broadcast_transaction(tx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    /// Files under `src/` other than this one that name the SDK crate.
+    fn direct_sdk_references(dir: &Path, found: &mut Vec<String>) {
+        let this_file = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/compat.rs");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path: PathBuf = entry.unwrap().path();
+            if path.is_dir() {
+                direct_sdk_references(&path, found);
+            } else if path.extension().is_some_and(|ext| ext == "rs") && path != this_file {
+                let source = std::fs::read_to_string(&path).unwrap();
+                for (line_no, line) in source.lines().enumerate() {
+                    if line.contains("twilight_client_sdk") {
+                        found.push(format!("{}:{}", path.display(), line_no + 1));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sdk_only_referenced_from_compat() {
+        let mut found = Vec::new();
+        direct_sdk_references(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut found,
+        );
+        assert!(
+            found.is_empty(),
+            "import SDK items through crate::compat instead: {:?}",
+            found
+        );
+    }
+
+    #[tokio::test]
+    async fn test_guards_report_panics_and_errors() {
+        use crate::error::WalletError;
+
+        let err =
+            super::guard("single_receiver", "account 2", || -> u64 { panic!("boom") }).unwrap_err();
+        assert!(matches!(
+            &err,
+            WalletError::ClientSdk { operation, detail }
+                if operation == "single_receiver" && detail == "account 2: panicked: boom"
+        ));
+
+        let err =
+            super::guard_result("get_input", "account 2", || Err::<(), _>("bad utxo")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "client SDK get_input failed: account 2: bad utxo"
+        );
+
+        async fn exploding_proof(inputs: usize) -> u64 {
+            panic!("{} inputs", inputs)
+        }
+        let err = super::guard_async("create_trader_order", "account 2", exploding_proof(3))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with("panicked: 3 inputs"));
+        assert_eq!(
+            super::guard_async("close_lend_order", "account 2", async { 7 })
+                .await
+                .unwrap(),
+            7
+        );
+    }
+
+    #[cfg(feature = "zk-accounts")]
+    #[test]
+    fn test_pinned_wrappers_build_decodable_requests() {
+        use super::relayer::{cancel_trader_order_zkos, query_trader_order_zkos};
+        use super::relayer_types::{
+            CancelTraderOrderZkos, OrderStatus, OrderType, QueryTraderOrderZkos,
+        };
+        use crate::zkos_accounts::{encrypted_account::KeyManager, zkaccount::ZkAccount};
+        use curve25519_dalek::scalar::Scalar;
+        use secrecy::{ExposeSecret, SecretString};
+
+        let scalar = Scalar::from(42u64);
+        assert_eq!(
+            super::util::hex_to_scalar(hex::encode(scalar.to_bytes())),
+            Some(scalar)
+        );
+
+        let seed = SecretString::new("compat-fixture-seed".to_string());
+        let key =
+            KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes()).derive_child_key(0);
+        let address = ZkAccount::from_seed(0, &seed, 1_000).unwrap().account;
+        let query =
+            query_trader_order_zkos(address.clone(), &key, address.clone(), OrderStatus::PENDING);
+        assert!(QueryTraderOrderZkos::decode_from_hex_string(query).is_ok());
+        let cancel = cancel_trader_order_zkos(
+            address.clone(),
+            &key,
+            address,
+            uuid::Uuid::new_v4(),
+            OrderType::LIMIT,
+        );
+        assert!(CancelTraderOrderZkos::decode_from_hex_string(cancel).is_ok());
+    }
+
+    #[test]
+    fn test_reexported_relayer_types_round_trip() {
+        use super::relayer_types::{OrderStatus, OrderType, PositionType};
+
+        // Names and wire forms this crate relies on; a rename in the SDK fails here.
+        assert_eq!(OrderType::MARKET.to_str(), "MARKET");
+        assert_eq!(PositionType::LONG.to_str(), "LONG");
+        let status: OrderStatus = serde_json::from_str("\"FILLED\"").unwrap();
+        assert_eq!(status, OrderStatus::FILLED);
+        assert_eq!(serde_json::to_string(&status).unwrap(), "\"FILLED\"");
+    }
+}
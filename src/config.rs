@@ -1,15 +1,51 @@
 use serde::{Deserialize, Serialize};
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
+
+pub mod builder;
+pub mod failover;
+pub mod fee;
+pub mod file;
+pub mod fingerprint;
+pub mod rate_limit;
+pub mod retry;
+pub use builder::{EndpointConfigBuilder, Profile, ProfileEndpoints};
+pub use failover::{FailoverPolicy, FailoverStrategy};
+pub use fee::TxFeeConfig;
+pub use file::{Config, ConfigError, RiskLimits};
+pub use fingerprint::{ConfigDrift, ConfigFingerprint};
+pub use rate_limit::RateLimitPolicy;
+pub use retry::{Backoff, LcdRetryPolicy, RetryPolicy};
+
+static NETWORK_TYPE_OVERRIDE: OnceLock<String> = OnceLock::new();
 
 /// Network type: "testnet" or "mainnet".
-/// and default endpoint URLs.
-pub static NETWORK_TYPE: LazyLock<String> =
-    LazyLock::new(|| std::env::var("NETWORK_TYPE").unwrap_or("mainnet".to_string()));
+/// and default endpoint URLs. Set by [`set_network_type`] if that ran first.
+pub static NETWORK_TYPE: LazyLock<String> = LazyLock::new(|| {
+    NETWORK_TYPE_OVERRIDE
+        .get()
+        .cloned()
+        .unwrap_or_else(|| std::env::var("NETWORK_TYPE").unwrap_or("mainnet".to_string()))
+});
 
 pub fn is_mainnet() -> bool {
     *NETWORK_TYPE == "mainnet"
 }
 
+/// Make `network_type` the process's [`NETWORK_TYPE`], ahead of the
+/// environment. `NETWORK_TYPE` is fixed once read, so this fails if it has
+/// already been read, or set, as another network.
+pub fn set_network_type(network_type: &str) -> Result<(), String> {
+    let _ = NETWORK_TYPE_OVERRIDE.set(network_type.to_string());
+    if *NETWORK_TYPE == network_type {
+        Ok(())
+    } else {
+        Err(format!(
+            "NETWORK_TYPE is already {}, not {}",
+            *NETWORK_TYPE, network_type
+        ))
+    }
+}
+
 /// Bitcoin network type: "mainnet" or "testnet".
 /// Falls back to `mainnet` if `BTC_NETWORK_TYPE` is not set.
 pub static BTC_NETWORK_TYPE: LazyLock<String> =
@@ -47,54 +83,36 @@ pub fn esplora_endpoints() -> (&'static str, &'static str) {
 }
 
 pub static FAUCET_BASE_URL: LazyLock<String> = LazyLock::new(|| {
-    let default = if is_mainnet() {
-        "".to_string()
-    } else {
-        "https://faucet-rpc.twilight.rest".to_string()
-    };
-    std::env::var("FAUCET_BASE_URL").unwrap_or(default)
+    std::env::var("FAUCET_BASE_URL")
+        .unwrap_or_else(|_| Profile::current().endpoints().faucet.to_string())
 });
 pub static NYKS_LCD_BASE_URL: LazyLock<String> = LazyLock::new(|| {
-    let default = if is_mainnet() {
-        "https://lcd.twilight.org".to_string()
-    } else {
-        "https://lcd.twilight.rest".to_string()
-    };
-    std::env::var("NYKS_LCD_BASE_URL").unwrap_or(default)
+    std::env::var("NYKS_LCD_BASE_URL")
+        .unwrap_or_else(|_| Profile::current().endpoints().nyks_lcd.to_string())
 });
 pub static NYKS_RPC_BASE_URL: LazyLock<String> = LazyLock::new(|| {
-    let default = if is_mainnet() {
-        "https://rpc.twilight.org".to_string()
-    } else {
-        "https://rpc.twilight.rest".to_string()
-    };
-    std::env::var("NYKS_RPC_BASE_URL").unwrap_or(default)
+    std::env::var("NYKS_RPC_BASE_URL")
+        .unwrap_or_else(|_| Profile::current().endpoints().nyks_rpc.to_string())
 });
 pub static VALIDATOR_WALLET_PATH: LazyLock<String> = LazyLock::new(|| {
-    std::env::var("VALIDATOR_WALLET_PATH").unwrap_or("validator.mnemonic".to_string())
+    std::env::var("VALIDATOR_WALLET_PATH")
+        .unwrap_or_else(|_| builder::DEFAULT_VALIDATOR_WALLET_PATH.to_string())
 });
 pub static RELAYER_PROGRAM_JSON_PATH: LazyLock<String> = LazyLock::new(|| {
     std::env::var("RELAYER_PROGRAM_JSON_PATH")
-        .unwrap_or_else(|_| "./relayerprogram.json".to_string())
+        .unwrap_or_else(|_| builder::DEFAULT_RELAYER_PROGRAM_JSON_PATH.to_string())
 });
 pub static ZKOS_SERVER_URL: LazyLock<String> = LazyLock::new(|| {
-    let default = if is_mainnet() {
-        "https://zkserver.twilight.org".to_string()
-    } else {
-        "https://nykschain.twilight.rest/zkos".to_string()
-    };
-    std::env::var("ZKOS_SERVER_URL").unwrap_or(default)
+    std::env::var("ZKOS_SERVER_URL")
+        .unwrap_or_else(|_| Profile::current().endpoints().zkos_server.to_string())
 });
 pub static RELAYER_API_RPC_SERVER_URL: LazyLock<String> = LazyLock::new(|| {
-    let default = if is_mainnet() {
-        "https://api.ephemeral.fi/api".to_string()
-    } else {
-        "https://relayer.twilight.rest/api".to_string()
-    };
-    std::env::var("RELAYER_API_RPC_SERVER_URL").unwrap_or(default)
+    std::env::var("RELAYER_API_RPC_SERVER_URL")
+        .unwrap_or_else(|_| Profile::current().endpoints().relayer_api.to_string())
+});
+pub static CHAIN_ID: LazyLock<String> = LazyLock::new(|| {
+    std::env::var("CHAIN_ID").unwrap_or_else(|_| builder::DEFAULT_CHAIN_ID.to_string())
 });
-pub static CHAIN_ID: LazyLock<String> =
-    LazyLock::new(|| std::env::var("CHAIN_ID").unwrap_or("nyks".to_string()));
 pub static TWILIGHT_INDEXER_URL: LazyLock<String> = LazyLock::new(|| {
     let default = if is_mainnet() {
         "https://indexer.twilight.org".to_string()
@@ -104,6 +122,39 @@ pub static TWILIGHT_INDEXER_URL: LazyLock<String> = LazyLock::new(|| {
     std::env::var("TWILIGHT_INDEXER_URL").unwrap_or(default)
 });
 
+pub const PROGRAM_LOADING_VAR: &str = "RELAYER_PROGRAM_LOADING";
+
+/// When an `OrderWallet` loads its relayer program (`relayer_program_json_path`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramLoading {
+    /// While the wallet is constructed; a missing or invalid program fails
+    /// construction.
+    #[default]
+    Eager,
+    /// On the first order, for embedders that never trade.
+    Lazy,
+}
+
+impl std::str::FromStr for ProgramLoading {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "eager" => Ok(ProgramLoading::Eager),
+            "lazy" => Ok(ProgramLoading::Lazy),
+            other => Err(format!("Unknown relayer program loading: {}", other)),
+        }
+    }
+}
+
+impl ProgramLoading {
+    /// `RELAYER_PROGRAM_LOADING` (`eager` or `lazy`), eager when unset.
+    pub fn from_env() -> Self {
+        retry::parse(&|var: &str| std::env::var(var).ok(), PROGRAM_LOADING_VAR).unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EndpointConfig {
     pub validator_wallet_path: String,
@@ -114,6 +165,18 @@ pub struct EndpointConfig {
     pub nyks_rpc_endpoint: String,
     pub faucet_endpoint: String,
     pub chain_id: String,
+    /// Relayer retry and timeout policy; see [`RetryPolicy`].
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    /// Fee and gas of chain transactions; see [`TxFeeConfig`].
+    #[serde(default)]
+    pub tx_fee: TxFeeConfig,
+    /// When the relayer program is loaded; see [`ProgramLoading`].
+    #[serde(default)]
+    pub program_loading: ProgramLoading,
+    /// Relayer request rate limit; see [`RateLimitPolicy`].
+    #[serde(default)]
+    pub rate_limit: RateLimitPolicy,
 }
 
 impl Default for EndpointConfig {
@@ -130,6 +193,10 @@ impl Default for EndpointConfig {
             nyks_rpc_endpoint: NYKS_RPC_BASE_URL.to_string(),
             faucet_endpoint: FAUCET_BASE_URL.to_string(),
             chain_id: CHAIN_ID.to_string(),
+            retry_policy: RetryPolicy::from_env(),
+            tx_fee: TxFeeConfig::default(),
+            program_loading: ProgramLoading::from_env(),
+            rate_limit: RateLimitPolicy::from_env(),
         }
     }
 }
@@ -154,9 +221,33 @@ impl EndpointConfig {
             nyks_rpc_endpoint,
             faucet_endpoint,
             chain_id,
+            retry_policy: RetryPolicy::from_env(),
+            tx_fee: TxFeeConfig::default(),
+            program_loading: ProgramLoading::from_env(),
+            rate_limit: RateLimitPolicy::from_env(),
         }
     }
 
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_tx_fee(mut self, tx_fee: TxFeeConfig) -> Self {
+        self.tx_fee = tx_fee;
+        self
+    }
+
+    pub fn with_program_loading(mut self, program_loading: ProgramLoading) -> Self {
+        self.program_loading = program_loading;
+        self
+    }
+
+    pub fn with_rate_limit(mut self, rate_limit: RateLimitPolicy) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
     pub fn from_env() -> Self {
         Self::default()
     }
@@ -168,6 +259,7 @@ impl EndpointConfig {
             self.nyks_rpc_endpoint.clone(),
             self.chain_id.clone(),
         )
+        .with_tx_fee(self.tx_fee.clone())
     }
     pub fn to_relayer_endpoint_config(&self) -> RelayerEndPointConfig {
         RelayerEndPointConfig::new(
@@ -175,6 +267,8 @@ impl EndpointConfig {
             self.zkos_server_endpoint.clone(),
             self.relayer_program_json_path.clone(),
         )
+        .with_retry_policy(self.retry_policy)
+        .with_rate_limit(self.rate_limit)
     }
 }
 
@@ -184,6 +278,9 @@ pub struct WalletEndPointConfig {
     pub faucet_endpoint: String,
     pub rpc_endpoint: String,
     pub chain_id: String,
+    /// Fee and gas of the transactions this wallet signs.
+    #[serde(default)]
+    pub tx_fee: TxFeeConfig,
 }
 
 impl Default for WalletEndPointConfig {
@@ -193,6 +290,7 @@ impl Default for WalletEndPointConfig {
             faucet_endpoint: FAUCET_BASE_URL.to_string(),
             rpc_endpoint: NYKS_RPC_BASE_URL.to_string(),
             chain_id: CHAIN_ID.to_string(),
+            tx_fee: TxFeeConfig::default(),
         }
     }
 }
@@ -209,9 +307,15 @@ impl WalletEndPointConfig {
             faucet_endpoint,
             rpc_endpoint,
             chain_id,
+            tx_fee: TxFeeConfig::default(),
         }
     }
 
+    pub fn with_tx_fee(mut self, tx_fee: TxFeeConfig) -> Self {
+        self.tx_fee = tx_fee;
+        self
+    }
+
     pub fn from_env() -> Self {
         Self::default()
     }
@@ -219,9 +323,19 @@ impl WalletEndPointConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RelayerEndPointConfig {
+    /// One relayer URL, or a comma-separated list failed over with `failover`.
     pub relayer_api_endpoint: String,
     pub zkos_server_endpoint: String,
     pub relayer_program_json_path: String,
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    #[serde(default)]
+    pub failover: FailoverPolicy,
+    #[serde(default)]
+    pub rate_limit: RateLimitPolicy,
+    /// How often the client logs its request metrics; `None` never does.
+    #[serde(default)]
+    pub metrics_log_interval: Option<std::time::Duration>,
 }
 
 impl Default for RelayerEndPointConfig {
@@ -230,6 +344,10 @@ impl Default for RelayerEndPointConfig {
             relayer_api_endpoint: RELAYER_API_RPC_SERVER_URL.to_string(),
             zkos_server_endpoint: ZKOS_SERVER_URL.to_string(),
             relayer_program_json_path: RELAYER_PROGRAM_JSON_PATH.to_string(),
+            retry_policy: RetryPolicy::from_env(),
+            failover: FailoverPolicy::from_env(),
+            rate_limit: RateLimitPolicy::from_env(),
+            metrics_log_interval: rate_limit::metrics_log_interval_from_env(),
         }
     }
 }
@@ -244,9 +362,38 @@ impl RelayerEndPointConfig {
             relayer_api_endpoint,
             zkos_server_endpoint,
             relayer_program_json_path,
+            retry_policy: RetryPolicy::from_env(),
+            failover: FailoverPolicy::from_env(),
+            rate_limit: RateLimitPolicy::from_env(),
+            metrics_log_interval: rate_limit::metrics_log_interval_from_env(),
         }
     }
 
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_failover(mut self, failover: FailoverPolicy) -> Self {
+        self.failover = failover;
+        self
+    }
+
+    pub fn with_rate_limit(mut self, rate_limit: RateLimitPolicy) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    pub fn with_metrics_log_interval(mut self, interval: std::time::Duration) -> Self {
+        self.metrics_log_interval = Some(interval);
+        self
+    }
+
+    /// The relayer URLs in `relayer_api_endpoint`, in priority order.
+    pub fn relayer_api_endpoints(&self) -> Vec<String> {
+        failover::split_endpoints(&self.relayer_api_endpoint)
+    }
+
     pub fn from_env() -> Self {
         Self::default()
     }
@@ -0,0 +1,476 @@
+//! Typed construction and validation of an [`EndpointConfig`].
+//!
+//! [`EndpointConfig::default`] reads every endpoint from the environment and
+//! quietly uses the built-in defaults for anything unset. The builder reads
+//! nothing from the environment: each endpoint comes from a setter or from
+//! the chosen [`Profile`], and [`EndpointConfigBuilder::validate`] refuses a
+//! config with a missing or malformed field, naming it.
+//!
+//! ```no_run
+//! use nyks_wallet::config::{EndpointConfig, Profile};
+//!
+//! let config = EndpointConfig::builder()
+//!     .profile(Profile::Testnet)
+//!     .relayer_api("https://relayer.example/api")
+//!     .validate()?;
+//! # Ok::<(), nyks_wallet::config::ConfigError>(())
+//! ```
+//!
+//! [`EndpointConfig::validate`] only parses values;
+//! [`EndpointConfig::validate_connectivity`] also sends one request to each
+//! endpoint.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ConfigError, EndpointConfig, ProgramLoading, RateLimitPolicy, RetryPolicy, TxFeeConfig,
+    failover,
+};
+
+/// How long [`EndpointConfig::validate_connectivity`] waits for each endpoint.
+pub const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) const DEFAULT_CHAIN_ID: &str = "nyks";
+pub(crate) const DEFAULT_VALIDATOR_WALLET_PATH: &str = "validator.mnemonic";
+pub(crate) const DEFAULT_RELAYER_PROGRAM_JSON_PATH: &str = "./relayerprogram.json";
+
+/// A named network with its canonical public endpoints, which are also the
+/// defaults of the endpoint environment variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    Mainnet,
+    Testnet,
+    /// A chain, relayer and ZkOS server on this machine, on their default ports.
+    Local,
+}
+
+impl std::str::FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mainnet" => Ok(Profile::Mainnet),
+            "testnet" => Ok(Profile::Testnet),
+            "local" => Ok(Profile::Local),
+            other => Err(format!("Unknown profile: {}", other)),
+        }
+    }
+}
+
+/// The endpoints a [`Profile`] fills in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileEndpoints {
+    pub nyks_rpc: &'static str,
+    pub nyks_lcd: &'static str,
+    pub relayer_api: &'static str,
+    pub zkos_server: &'static str,
+    /// Empty when the network has no faucet.
+    pub faucet: &'static str,
+}
+
+impl Profile {
+    /// The network of [`NETWORK_TYPE`](super::NETWORK_TYPE).
+    pub fn current() -> Self {
+        if super::is_mainnet() {
+            Profile::Mainnet
+        } else {
+            Profile::Testnet
+        }
+    }
+
+    /// The `NETWORK_TYPE` of the profile's network; `None` for `Local`, which
+    /// runs as either.
+    pub fn network_type(self) -> Option<&'static str> {
+        match self {
+            Profile::Mainnet => Some("mainnet"),
+            Profile::Testnet => Some("testnet"),
+            Profile::Local => None,
+        }
+    }
+
+    pub fn endpoints(self) -> ProfileEndpoints {
+        match self {
+            Profile::Mainnet => ProfileEndpoints {
+                nyks_rpc: "https://rpc.twilight.org",
+                nyks_lcd: "https://lcd.twilight.org",
+                relayer_api: "https://api.ephemeral.fi/api",
+                zkos_server: "https://zkserver.twilight.org",
+                faucet: "",
+            },
+            Profile::Testnet => ProfileEndpoints {
+                nyks_rpc: "https://rpc.twilight.rest",
+                nyks_lcd: "https://lcd.twilight.rest",
+                relayer_api: "https://relayer.twilight.rest/api",
+                zkos_server: "https://nykschain.twilight.rest/zkos",
+                faucet: "https://faucet-rpc.twilight.rest",
+            },
+            Profile::Local => ProfileEndpoints {
+                nyks_rpc: "http://127.0.0.1:26657",
+                nyks_lcd: "http://127.0.0.1:1317",
+                relayer_api: "http://127.0.0.1:8088/api",
+                zkos_server: "http://127.0.0.1:3030",
+                faucet: "http://127.0.0.1:6969",
+            },
+        }
+    }
+}
+
+/// Builder returned by [`EndpointConfig::builder`]. Setters override the
+/// profile's endpoints; retry, fee, program loading and rate limit default to
+/// their built-in values, not the environment.
+#[derive(Debug, Clone, Default)]
+pub struct EndpointConfigBuilder {
+    profile: Option<Profile>,
+    chain_id: Option<String>,
+    nyks_rpc: Option<String>,
+    nyks_lcd: Option<String>,
+    relayer_api: Option<String>,
+    zkos_server: Option<String>,
+    faucet: Option<String>,
+    relayer_program_json_path: Option<String>,
+    validator_wallet_path: Option<String>,
+    retry_policy: Option<RetryPolicy>,
+    tx_fee: Option<TxFeeConfig>,
+    program_loading: Option<ProgramLoading>,
+    rate_limit: Option<RateLimitPolicy>,
+}
+
+impl EndpointConfigBuilder {
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Defaults to `nyks`.
+    pub fn chain_id(mut self, chain_id: impl Into<String>) -> Self {
+        self.chain_id = Some(chain_id.into());
+        self
+    }
+
+    pub fn rpc(mut self, url: impl Into<String>) -> Self {
+        self.nyks_rpc = Some(url.into());
+        self
+    }
+
+    pub fn lcd(mut self, url: impl Into<String>) -> Self {
+        self.nyks_lcd = Some(url.into());
+        self
+    }
+
+    /// One relayer URL, or a comma-separated list to fail over between.
+    pub fn relayer_api(mut self, url: impl Into<String>) -> Self {
+        self.relayer_api = Some(url.into());
+        self
+    }
+
+    pub fn zkos_server(mut self, url: impl Into<String>) -> Self {
+        self.zkos_server = Some(url.into());
+        self
+    }
+
+    /// An empty URL disables the faucet.
+    pub fn faucet(mut self, url: impl Into<String>) -> Self {
+        self.faucet = Some(url.into());
+        self
+    }
+
+    pub fn relayer_program_json_path(mut self, path: impl Into<String>) -> Self {
+        self.relayer_program_json_path = Some(path.into());
+        self
+    }
+
+    pub fn validator_wallet_path(mut self, path: impl Into<String>) -> Self {
+        self.validator_wallet_path = Some(path.into());
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn tx_fee(mut self, tx_fee: TxFeeConfig) -> Self {
+        self.tx_fee = Some(tx_fee);
+        self
+    }
+
+    pub fn program_loading(mut self, program_loading: ProgramLoading) -> Self {
+        self.program_loading = Some(program_loading);
+        self
+    }
+
+    pub fn rate_limit(mut self, rate_limit: RateLimitPolicy) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// The config, once every endpoint is set and passes
+    /// [`EndpointConfig::validate`]. Without a profile, the RPC, LCD, relayer
+    /// and ZkOS endpoints must all be given; the faucet is then disabled.
+    ///
+    /// A `Mainnet` or `Testnet` profile also sets the process's
+    /// [`NETWORK_TYPE`](super::NETWORK_TYPE) (see
+    /// [`set_network_type`](super::set_network_type)), which keys database
+    /// rows and selects address formats; a profile of another network than
+    /// the one already in use is refused, as field `profile`.
+    pub fn validate(self) -> Result<EndpointConfig, ConfigError> {
+        if let Some(network_type) = self.profile.and_then(Profile::network_type) {
+            super::set_network_type(network_type).map_err(|reason| invalid("profile", &reason))?;
+        }
+        let defaults = self.profile.map(Profile::endpoints);
+        let pick =
+            |field: &str, value: Option<String>, default: fn(&ProfileEndpoints) -> &'static str| {
+                value
+                    .or_else(|| defaults.as_ref().map(|d| default(d).to_string()))
+                    .ok_or_else(|| ConfigError::Invalid {
+                        field: field.to_string(),
+                        reason: "not set; set it or choose a profile".to_string(),
+                    })
+            };
+        let config = EndpointConfig {
+            validator_wallet_path: self
+                .validator_wallet_path
+                .unwrap_or_else(|| DEFAULT_VALIDATOR_WALLET_PATH.to_string()),
+            relayer_program_json_path: self
+                .relayer_program_json_path
+                .unwrap_or_else(|| DEFAULT_RELAYER_PROGRAM_JSON_PATH.to_string()),
+            zkos_server_endpoint: pick("zkos_server_endpoint", self.zkos_server, |d| {
+                d.zkos_server
+            })?,
+            relayer_api_endpoint: pick("relayer_api_endpoint", self.relayer_api, |d| {
+                d.relayer_api
+            })?,
+            nyks_lcd_endpoint: pick("nyks_lcd_endpoint", self.nyks_lcd, |d| d.nyks_lcd)?,
+            nyks_rpc_endpoint: pick("nyks_rpc_endpoint", self.nyks_rpc, |d| d.nyks_rpc)?,
+            faucet_endpoint: self
+                .faucet
+                .or_else(|| defaults.map(|d| d.faucet.to_string()))
+                .unwrap_or_default(),
+            chain_id: self
+                .chain_id
+                .unwrap_or_else(|| DEFAULT_CHAIN_ID.to_string()),
+            retry_policy: self.retry_policy.unwrap_or_default(),
+            tx_fee: self.tx_fee.unwrap_or_default(),
+            program_loading: self.program_loading.unwrap_or_default(),
+            rate_limit: self.rate_limit.unwrap_or_default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl EndpointConfig {
+    pub fn builder() -> EndpointConfigBuilder {
+        EndpointConfigBuilder::default()
+    }
+
+    /// Check every field without contacting any endpoint: URLs must parse as
+    /// http(s) with a host, the relayer list must not be empty, and the chain
+    /// id and relayer program path must be set. An empty faucet URL is
+    /// allowed.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.chain_id.trim().is_empty() {
+            return Err(invalid("chain_id", "must not be empty"));
+        }
+        if self.chain_id.chars().any(char::is_whitespace) {
+            return Err(invalid("chain_id", "must not contain whitespace"));
+        }
+        for (field, url) in self.urls() {
+            check_http_url(field, url)?;
+        }
+        if failover::split_endpoints(&self.relayer_api_endpoint).is_empty() {
+            return Err(invalid("relayer_api_endpoint", "must not be empty"));
+        }
+        if self.relayer_program_json_path.trim().is_empty() {
+            return Err(invalid("relayer_program_json_path", "must not be empty"));
+        }
+        Ok(())
+    }
+
+    /// [`validate`](EndpointConfig::validate), then one HEAD request to each
+    /// endpoint. Any HTTP response counts as reachable; the first endpoint
+    /// that refuses the connection or does not answer within
+    /// [`CONNECTIVITY_TIMEOUT`] is returned as [`ConfigError::Unreachable`].
+    pub async fn validate_connectivity(&self) -> Result<(), ConfigError> {
+        self.validate()?;
+        let client = reqwest::Client::builder()
+            .timeout(CONNECTIVITY_TIMEOUT)
+            .build()
+            .map_err(|e| invalid("http", &e.to_string()))?;
+        for (field, url) in self.urls() {
+            if let Err(e) = client.head(url).send().await {
+                return Err(ConfigError::Unreachable {
+                    field: field.to_string(),
+                    url: url.to_string(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Every configured URL with the field it came from; relayer lists are
+    /// split and a disabled faucet is left out.
+    fn urls(&self) -> Vec<(&'static str, &str)> {
+        let mut urls = vec![
+            ("nyks_rpc_endpoint", self.nyks_rpc_endpoint.as_str()),
+            ("nyks_lcd_endpoint", self.nyks_lcd_endpoint.as_str()),
+            ("zkos_server_endpoint", self.zkos_server_endpoint.as_str()),
+        ];
+        urls.extend(
+            self.relayer_api_endpoint
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(|url| ("relayer_api_endpoint", url)),
+        );
+        if !self.faucet_endpoint.is_empty() {
+            urls.push(("faucet_endpoint", self.faucet_endpoint.as_str()));
+        }
+        urls
+    }
+}
+
+fn invalid(field: &str, reason: &str) -> ConfigError {
+    ConfigError::Invalid {
+        field: field.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// `url` must be an http(s) URL with a host; `field` names it in the error.
+pub(crate) fn check_http_url(field: &str, url: &str) -> Result<(), ConfigError> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| invalid(field, &e.to_string()))?;
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(invalid(field, "expected an http(s) URL"));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(invalid(field, "URL has no host"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_of(err: ConfigError) -> String {
+        match err {
+            ConfigError::Invalid { field, .. } | ConfigError::Unreachable { field, .. } => field,
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_profiles_fill_endpoints_and_setters_override() {
+        let config = EndpointConfig::builder()
+            .profile(Profile::Local)
+            .relayer_api("https://relayer.example/api, https://backup.example/api")
+            .validate()
+            .unwrap();
+        assert_eq!(config.nyks_rpc_endpoint, "http://127.0.0.1:26657");
+        assert_eq!(config.faucet_endpoint, "http://127.0.0.1:6969");
+        assert_eq!(
+            config.to_relayer_endpoint_config().relayer_api_endpoints(),
+            vec!["https://relayer.example/api", "https://backup.example/api"]
+        );
+        assert_eq!(config.chain_id, "nyks");
+        assert_eq!(config.retry_policy, RetryPolicy::default());
+        assert_eq!(
+            Profile::Testnet.endpoints().nyks_rpc,
+            "https://rpc.twilight.rest"
+        );
+        assert_eq!("local".parse::<Profile>(), Ok(Profile::Local));
+    }
+
+    // Tests run as mainnet, the NETWORK_TYPE default.
+    #[test]
+    fn test_a_network_profile_sets_the_network_type() {
+        let mainnet = EndpointConfig::builder()
+            .profile(Profile::Mainnet)
+            .validate()
+            .unwrap();
+        assert_eq!(mainnet.faucet_endpoint, "");
+        assert_eq!(super::super::NETWORK_TYPE.as_str(), "mainnet");
+        assert_eq!(Profile::current(), Profile::Mainnet);
+        if std::env::var("NYKS_LCD_BASE_URL").is_err() {
+            assert_eq!(
+                super::super::NYKS_LCD_BASE_URL.as_str(),
+                Profile::Mainnet.endpoints().nyks_lcd
+            );
+        }
+
+        // Once set, the network type stays.
+        let err = EndpointConfig::builder()
+            .profile(Profile::Testnet)
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid config value `profile`: NETWORK_TYPE is already mainnet, not testnet"
+        );
+        assert_eq!(field_of(err), "profile");
+    }
+
+    #[test]
+    fn test_invalid_fields_are_named() {
+        // Without a profile nothing falls back to a default endpoint.
+        let err = EndpointConfig::builder()
+            .rpc("https://rpc.example")
+            .lcd("https://lcd.example")
+            .zkos_server("https://zkos.example")
+            .validate()
+            .unwrap_err();
+        assert_eq!(field_of(err), "relayer_api_endpoint");
+
+        let local = || EndpointConfig::builder().profile(Profile::Local);
+        for (builder, field) in [
+            (local().lcd("0.0.0.0:1317"), "nyks_lcd_endpoint"),
+            (local().rpc("ftp://rpc.example"), "nyks_rpc_endpoint"),
+            (local().relayer_api(" , "), "relayer_api_endpoint"),
+            (local().faucet("faucet"), "faucet_endpoint"),
+            (local().chain_id(""), "chain_id"),
+            (
+                local().relayer_program_json_path(""),
+                "relayer_program_json_path",
+            ),
+        ] {
+            assert_eq!(field_of(builder.validate().unwrap_err()), field);
+        }
+        assert!(local().faucet("").validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_connectivity_names_unreachable_endpoint() {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let up = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let _ = stream.read(&mut [0u8; 1024]);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let down = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+
+        let builder = EndpointConfig::builder()
+            .rpc(up.as_str())
+            .lcd(up.as_str())
+            .zkos_server(up.as_str());
+        let reachable = builder.clone().relayer_api(up.as_str()).validate().unwrap();
+        reachable.validate_connectivity().await.unwrap();
+
+        let unreachable = builder.relayer_api(down.as_str()).validate().unwrap();
+        let err = unreachable.validate_connectivity().await.unwrap_err();
+        assert!(matches!(err, ConfigError::Unreachable { ref url, .. } if *url == down));
+        assert_eq!(field_of(err), "relayer_api_endpoint");
+    }
+}
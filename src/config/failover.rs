@@ -0,0 +1,142 @@
+//! Failover between several relayer endpoints.
+//!
+//! A [`FailoverPolicy`] decides which of a `RelayerJsonRpcClient`'s endpoints
+//! a request goes to first and when an endpoint is set aside. Endpoints come
+//! from `RelayerJsonRpcClient::new_with_failover` or a comma-separated
+//! `RELAYER_API_RPC_SERVER_URL`; with a single endpoint the policy has no
+//! effect.
+//!
+//! [`FailoverPolicy::from_env`] applies these overrides to the defaults:
+//!
+//! | Variable | Field |
+//! |----------|-------|
+//! | `RELAYER_FAILOVER_STRATEGY` | `strategy` (`priority` or `round_robin`) |
+//! | `RELAYER_FAILOVER_THRESHOLD` | `failure_threshold` |
+//! | `RELAYER_FAILOVER_COOLDOWN_SECS` | `cooldown` |
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::retry::parse;
+
+pub const STRATEGY_VAR: &str = "RELAYER_FAILOVER_STRATEGY";
+pub const THRESHOLD_VAR: &str = "RELAYER_FAILOVER_THRESHOLD";
+pub const COOLDOWN_SECS_VAR: &str = "RELAYER_FAILOVER_COOLDOWN_SECS";
+
+/// Order in which healthy endpoints are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverStrategy {
+    /// Always start with the first healthy endpoint in the configured order.
+    #[default]
+    Priority,
+    /// Start each request with the next healthy endpoint in turn.
+    RoundRobin,
+}
+
+impl FromStr for FailoverStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "priority" => Ok(FailoverStrategy::Priority),
+            "round_robin" | "round-robin" | "roundrobin" => Ok(FailoverStrategy::RoundRobin),
+            other => Err(format!("Unknown failover strategy: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailoverPolicy {
+    pub strategy: FailoverStrategy,
+    /// Consecutive connection failures or timeouts that mark an endpoint unhealthy.
+    pub failure_threshold: u32,
+    /// How long an unhealthy endpoint is only tried after every healthy one.
+    pub cooldown: Duration,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            strategy: FailoverStrategy::Priority,
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl FailoverPolicy {
+    /// The defaults with `strategy`.
+    pub fn new(strategy: FailoverStrategy) -> Self {
+        Self {
+            strategy,
+            ..Self::default()
+        }
+    }
+
+    /// The defaults with the `RELAYER_FAILOVER_*` environment overrides applied.
+    pub fn from_env() -> Self {
+        Self::from_env_with(|var| std::env::var(var).ok())
+    }
+
+    /// [`from_env`](Self::from_env) reading variables through `env`. Values
+    /// that do not parse are ignored with a warning.
+    pub(crate) fn from_env_with(env: impl Fn(&str) -> Option<String>) -> Self {
+        let mut policy = Self::default();
+        if let Some(strategy) = parse(&env, STRATEGY_VAR) {
+            policy.strategy = strategy;
+        }
+        if let Some(n) = parse(&env, THRESHOLD_VAR) {
+            policy.failure_threshold = n;
+        }
+        if let Some(secs) = parse(&env, COOLDOWN_SECS_VAR) {
+            policy.cooldown = Duration::from_secs(secs);
+        }
+        policy
+    }
+}
+
+/// The endpoints of a comma-separated endpoint list, trimmed, without empty entries.
+pub fn split_endpoints(endpoints: &str) -> Vec<String> {
+    endpoints
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_env_overrides_map_onto_policy() {
+        let env: HashMap<&str, &str> = [
+            (STRATEGY_VAR, "round_robin"),
+            (COOLDOWN_SECS_VAR, "5"),
+            (THRESHOLD_VAR, "many"),
+        ]
+        .into();
+        let policy = FailoverPolicy::from_env_with(|var| env.get(var).map(|v| v.to_string()));
+        assert_eq!(policy.strategy, FailoverStrategy::RoundRobin);
+        assert_eq!(policy.cooldown, Duration::from_secs(5));
+        assert_eq!(policy.failure_threshold, 3);
+    }
+
+    #[test]
+    fn test_split_endpoints() {
+        assert_eq!(
+            split_endpoints(" https://a.example/api , ,https://b.example/api"),
+            vec!["https://a.example/api", "https://b.example/api"]
+        );
+        assert_eq!(
+            split_endpoints("https://a.example/api"),
+            vec!["https://a.example/api"]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Fee and gas for Cosmos transactions.
+//!
+//! A [`TxFeeConfig`] sets the fee coin and gas limit every signed chain
+//! transaction carries. The defaults match what the wallet has always sent:
+//! 1000 nyks for a 2,000,000 gas limit.
+//!
+//! With [`TxFeeConfig::gas_adjustment`] set, the signer first simulates the
+//! transaction against the LCD and signs with the simulated gas times the
+//! adjustment instead of the fixed limit; see [`TxFeeConfig::adjusted`].
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_GAS_LIMIT: u64 = 2_000_000;
+pub const DEFAULT_FEE_AMOUNT: u64 = 1_000;
+pub const DEFAULT_FEE_DENOM: &str = "nyks";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TxFeeConfig {
+    /// Gas limit signed into the transaction.
+    pub gas_limit: u64,
+    /// Fee paid for `gas_limit`, in `fee_denom`.
+    pub fee_amount: u64,
+    pub fee_denom: String,
+    /// When set, simulate first and sign with the gas used times this factor.
+    pub gas_adjustment: Option<f64>,
+}
+
+impl Default for TxFeeConfig {
+    fn default() -> Self {
+        Self {
+            gas_limit: DEFAULT_GAS_LIMIT,
+            fee_amount: DEFAULT_FEE_AMOUNT,
+            fee_denom: DEFAULT_FEE_DENOM.to_string(),
+            gas_adjustment: None,
+        }
+    }
+}
+
+impl TxFeeConfig {
+    pub fn with_fee_amount(mut self, fee_amount: u64) -> Self {
+        self.fee_amount = fee_amount;
+        self
+    }
+
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    pub fn with_gas_adjustment(mut self, gas_adjustment: Option<f64>) -> Self {
+        self.gas_adjustment = gas_adjustment;
+        self
+    }
+
+    /// Whether the signer should simulate before signing.
+    pub fn simulates(&self) -> bool {
+        self.gas_adjustment.is_some()
+    }
+
+    /// The fee to sign a transaction with after a simulation reported
+    /// `gas_used`: a gas limit of `gas_used * gas_adjustment`, rounded up,
+    /// and the fee amount scaled to keep the configured price per gas when
+    /// that limit is above `gas_limit`. A smaller limit keeps the configured
+    /// amount, so the fee never drops below what the node accepted before.
+    /// Without an adjustment the configuration is returned unchanged.
+    pub fn adjusted(&self, gas_used: u64) -> Self {
+        let Some(adjustment) = self.gas_adjustment else {
+            return self.clone();
+        };
+        let gas_limit = ((gas_used as f64 * adjustment).ceil() as u64).max(1);
+        let fee_amount = if gas_limit > self.gas_limit && self.gas_limit > 0 {
+            (self.fee_amount as u128 * gas_limit as u128).div_ceil(self.gas_limit as u128) as u64
+        } else {
+            self.fee_amount
+        };
+        Self {
+            gas_limit,
+            fee_amount,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_legacy_fee() {
+        let fee = TxFeeConfig::default();
+        assert_eq!(fee.gas_limit, 2_000_000);
+        assert_eq!(fee.fee_amount, 1_000);
+        assert_eq!(fee.fee_denom, "nyks");
+        assert!(!fee.simulates());
+
+        let parsed: TxFeeConfig = serde_json::from_str(r#"{"fee_amount":5000}"#).unwrap();
+        assert_eq!(parsed, TxFeeConfig::default().with_fee_amount(5_000));
+    }
+
+    #[test]
+    fn test_adjusted_scales_gas_and_fee() {
+        let fee = TxFeeConfig::default().with_gas_adjustment(Some(1.5));
+        // Below the configured limit: tighter gas, same fee.
+        let small = fee.adjusted(100_000);
+        assert_eq!(small.gas_limit, 150_000);
+        assert_eq!(small.fee_amount, 1_000);
+        // Above it: the fee follows at 1000 nyks per 2M gas, rounded up.
+        let large = fee.adjusted(2_000_001);
+        assert_eq!(large.gas_limit, 3_000_002);
+        assert_eq!(large.fee_amount, 1_501);
+        assert_eq!(large.fee_denom, "nyks");
+
+        let fixed = TxFeeConfig::default();
+        assert_eq!(fixed.adjusted(100_000), fixed);
+    }
+}
@@ -0,0 +1,743 @@
+//! TOML configuration file.
+//!
+//! One file can hold everything the env vars in [`crate::config`] cover, plus
+//! gas, retry, HTTP, database, traffic and risk settings. Precedence, highest
+//! first: process environment (including `.env`), the config file, built-in
+//! defaults. Every section and field is optional.
+//!
+//! Secrets never belong in the file. Any key that looks like a password,
+//! passphrase, mnemonic, seed, secret or private key is rejected, and so
+//! is a database URL with a password in it. Pass those through the
+//! environment or a prompt as before.
+//!
+//! The endpoint statics in [`crate::config`] read the environment once, on
+//! first use. Binaries should therefore call [`Config::apply_to_env`] before
+//! anything else, as `relayer-cli --config` does.
+//! [`EndpointConfig::from_file`] does not touch the environment.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    CHAIN_ID, EndpointConfig, FAUCET_BASE_URL, NYKS_LCD_BASE_URL, NYKS_RPC_BASE_URL,
+    RELAYER_API_RPC_SERVER_URL, RELAYER_PROGRAM_JSON_PATH, RateLimitPolicy, TxFeeConfig,
+    VALIDATOR_WALLET_PATH, ZKOS_SERVER_URL, builder::check_http_url, rate_limit, retry,
+};
+
+/// Key fragments that mark a value as a secret.
+const SECRET_KEY_PATTERNS: [&str; 6] = [
+    "password",
+    "passphrase",
+    "mnemonic",
+    "seed",
+    "secret",
+    "private_key",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid config file: {0}")]
+    Parse(String),
+    #[error("config file must not contain secrets; remove `{0}` and pass it via the environment")]
+    SecretInFile(String),
+    #[error("invalid config value `{field}`: {reason}")]
+    Invalid { field: String, reason: String },
+    #[error("endpoint `{field}` ({url}) is unreachable: {reason}")]
+    Unreachable {
+        field: String,
+        url: String,
+        reason: String,
+    },
+}
+
+/// Parsed config file. See [`Config::example`] for every field.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub chain: ChainProfile,
+    #[serde(default)]
+    pub endpoints: Endpoints,
+    #[serde(default)]
+    pub gas: GasConfig,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub http: HttpClientConfig,
+    #[serde(default)]
+    pub database: DatabaseOptions,
+    #[serde(default)]
+    pub traffic: TrafficShaping,
+    #[serde(default)]
+    pub risk: RiskLimits,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainProfile {
+    /// `mainnet` or `testnet` (`NETWORK_TYPE`).
+    pub network_type: Option<String>,
+    /// `BTC_NETWORK_TYPE`.
+    pub btc_network_type: Option<String>,
+    /// `CHAIN_ID`.
+    pub chain_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Endpoints {
+    pub nyks_rpc: Option<String>,
+    pub nyks_lcd: Option<String>,
+    pub relayer_api: Option<String>,
+    pub zkos_server: Option<String>,
+    pub faucet: Option<String>,
+    pub indexer: Option<String>,
+    pub esplora_primary: Option<String>,
+    pub esplora_fallback: Option<String>,
+    pub relayer_program_json_path: Option<String>,
+    pub validator_wallet_path: Option<String>,
+}
+
+/// Fee and gas for Cosmos transactions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GasConfig {
+    pub denom: Option<String>,
+    pub fee_amount: Option<u64>,
+    pub gas_limit: Option<u64>,
+    /// Simulate transactions and sign with the gas used times this factor.
+    pub gas_adjustment: Option<f64>,
+}
+
+impl GasConfig {
+    /// The `[gas]` values over the [`TxFeeConfig`] defaults.
+    pub fn tx_fee(&self) -> TxFeeConfig {
+        let default = TxFeeConfig::default();
+        TxFeeConfig {
+            gas_limit: self.gas_limit.unwrap_or(default.gas_limit),
+            fee_amount: self.fee_amount.unwrap_or(default.fee_amount),
+            fee_denom: self.denom.clone().unwrap_or(default.fee_denom),
+            gas_adjustment: self.gas_adjustment,
+        }
+    }
+}
+
+/// Retry policy for chain and relayer polling.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Polls for a submitted tx's hash.
+    pub max_attempts: Option<u32>,
+    /// Polls for an account's UTXO.
+    pub utxo_max_attempts: Option<u32>,
+    pub initial_delay_ms: Option<u64>,
+    pub max_delay_ms: Option<u64>,
+    pub backoff_factor: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpClientConfig {
+    /// Timeout of a single relayer request (`RELAYER_REQUEST_TIMEOUT_SECS`).
+    pub timeout_secs: Option<u64>,
+    /// Proxy for outgoing HTTP(S) requests; exported as `HTTPS_PROXY` / `HTTP_PROXY`.
+    pub proxy: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatabaseOptions {
+    /// `DATABASE_URL_SQLITE`.
+    pub sqlite_path: Option<String>,
+    /// `DATABASE_URL_POSTGRESQL`; must not embed a password.
+    pub postgresql_url: Option<String>,
+    /// `NYKS_WALLET_ID`.
+    pub wallet_id: Option<String>,
+}
+
+/// Client-side limit on the relayer request rate; see
+/// [`RateLimitPolicy`](super::RateLimitPolicy).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrafficShaping {
+    /// `RELAYER_RATE_LIMIT_RPS`.
+    pub max_requests_per_second: Option<u32>,
+    /// `RELAYER_RATE_LIMIT_BURST`.
+    pub burst: Option<u32>,
+}
+
+pub const MAX_LEVERAGE_VAR: &str = "NYKS_RISK_MAX_LEVERAGE";
+pub const MAX_POSITION_SATS_VAR: &str = "NYKS_RISK_MAX_POSITION_SATS";
+pub const MAX_OPEN_ORDERS_VAR: &str = "NYKS_RISK_MAX_OPEN_ORDERS";
+
+/// Local limits an `OrderWallet` checks new trader orders against, on top of
+/// the relayer's risk engine. `None` leaves that bound to the relayer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RiskLimits {
+    /// `NYKS_RISK_MAX_LEVERAGE`.
+    pub max_leverage: Option<f64>,
+    /// Largest position value (margin times leverage), `NYKS_RISK_MAX_POSITION_SATS`.
+    pub max_position_sats: Option<u64>,
+    /// Trader orders open at once, `NYKS_RISK_MAX_OPEN_ORDERS`.
+    pub max_open_orders: Option<u32>,
+}
+
+impl RiskLimits {
+    /// The `NYKS_RISK_*` limits set in the environment.
+    pub fn from_env() -> Self {
+        Self::from_env_with(|var| std::env::var(var).ok())
+    }
+
+    /// [`from_env`](Self::from_env) reading variables through `env`. Values
+    /// that do not parse are ignored with a warning.
+    pub(crate) fn from_env_with(env: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            max_leverage: retry::parse(&env, MAX_LEVERAGE_VAR),
+            max_position_sats: retry::parse(&env, MAX_POSITION_SATS_VAR),
+            max_open_orders: retry::parse(&env, MAX_OPEN_ORDERS_VAR),
+        }
+    }
+
+    /// Check a new trader order worth `position_value` sats at `leverage`,
+    /// with `open_orders` trader orders already open.
+    pub fn check(
+        &self,
+        position_value: u64,
+        leverage: f64,
+        open_orders: usize,
+    ) -> Result<(), String> {
+        if let Some(max) = self.max_leverage.filter(|max| leverage > *max) {
+            return Err(format!(
+                "Leverage {}x above the configured maximum {}x",
+                leverage, max
+            ));
+        }
+        if let Some(max) = self.max_position_sats.filter(|max| position_value > *max) {
+            return Err(format!(
+                "Position value {} sats above the configured maximum {} sats",
+                position_value, max
+            ));
+        }
+        if let Some(max) = self
+            .max_open_orders
+            .filter(|max| open_orders >= *max as usize)
+        {
+            return Err(format!(
+                "{} trader orders already open, the configured maximum",
+                max
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::from_toml(&text)
+    }
+
+    /// Parse TOML text, rejecting secrets and unknown fields.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let value: toml::Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()))?;
+        reject_secrets(&value, "")?;
+        let config: Config = toml::Value::Table(value)
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()))?;
+        if let Some(url) = &config.database.postgresql_url {
+            let has_password = reqwest::Url::parse(url)
+                .map(|url| url.password().is_some())
+                .unwrap_or(false);
+            if has_password {
+                return Err(ConfigError::SecretInFile(
+                    "database.postgresql_url (password in URL)".to_string(),
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Check values without contacting any endpoint.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field: &str, reason: &str| ConfigError::Invalid {
+            field: field.to_string(),
+            reason: reason.to_string(),
+        };
+        for (field, value) in [
+            ("chain.network_type", &self.chain.network_type),
+            ("chain.btc_network_type", &self.chain.btc_network_type),
+        ] {
+            if value
+                .as_deref()
+                .is_some_and(|v| v != "mainnet" && v != "testnet")
+            {
+                return Err(invalid(field, "expected `mainnet` or `testnet`"));
+            }
+        }
+        let e = &self.endpoints;
+        for (field, value) in [
+            ("endpoints.nyks_rpc", &e.nyks_rpc),
+            ("endpoints.nyks_lcd", &e.nyks_lcd),
+            ("endpoints.relayer_api", &e.relayer_api),
+            ("endpoints.zkos_server", &e.zkos_server),
+            ("endpoints.faucet", &e.faucet),
+            ("endpoints.indexer", &e.indexer),
+            ("endpoints.esplora_primary", &e.esplora_primary),
+            ("endpoints.esplora_fallback", &e.esplora_fallback),
+            ("http.proxy", &self.http.proxy),
+        ] {
+            // An empty faucet URL is how mainnet disables the faucet.
+            match value.as_deref() {
+                None | Some("") => {}
+                Some(url) => check_http_url(field, url)?,
+            }
+        }
+        let r = &self.retry;
+        if r.max_attempts == Some(0) {
+            return Err(invalid("retry.max_attempts", "must be at least 1"));
+        }
+        if r.utxo_max_attempts == Some(0) {
+            return Err(invalid("retry.utxo_max_attempts", "must be at least 1"));
+        }
+        if r.backoff_factor.is_some_and(|f| f < 1.0) {
+            return Err(invalid("retry.backoff_factor", "must be at least 1.0"));
+        }
+        if matches!((r.initial_delay_ms, r.max_delay_ms), (Some(initial), Some(max)) if initial > max)
+        {
+            return Err(invalid(
+                "retry.initial_delay_ms",
+                "exceeds retry.max_delay_ms",
+            ));
+        }
+        if self.gas.gas_limit == Some(0) {
+            return Err(invalid("gas.gas_limit", "must be positive"));
+        }
+        if self.gas.gas_adjustment.is_some_and(|a| a < 1.0) {
+            return Err(invalid("gas.gas_adjustment", "must be at least 1.0"));
+        }
+        if self.http.timeout_secs == Some(0) {
+            return Err(invalid("http.timeout_secs", "must be positive"));
+        }
+        if self.traffic.max_requests_per_second == Some(0) || self.traffic.burst == Some(0) {
+            return Err(invalid("traffic", "limits must be positive"));
+        }
+        if self.risk.max_leverage.is_some_and(|l| l < 1.0) {
+            return Err(invalid("risk.max_leverage", "must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Environment variables this file sets, with the file's values.
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let c = &self.chain;
+        let e = &self.endpoints;
+        let d = &self.database;
+        let p = &self.http.proxy;
+        [
+            ("NETWORK_TYPE", &c.network_type),
+            ("BTC_NETWORK_TYPE", &c.btc_network_type),
+            ("CHAIN_ID", &c.chain_id),
+            ("NYKS_RPC_BASE_URL", &e.nyks_rpc),
+            ("NYKS_LCD_BASE_URL", &e.nyks_lcd),
+            ("RELAYER_API_RPC_SERVER_URL", &e.relayer_api),
+            ("ZKOS_SERVER_URL", &e.zkos_server),
+            ("FAUCET_BASE_URL", &e.faucet),
+            ("TWILIGHT_INDEXER_URL", &e.indexer),
+            ("BTC_ESPLORA_PRIMARY_URL", &e.esplora_primary),
+            ("BTC_ESPLORA_FALLBACK_URL", &e.esplora_fallback),
+            ("RELAYER_PROGRAM_JSON_PATH", &e.relayer_program_json_path),
+            ("VALIDATOR_WALLET_PATH", &e.validator_wallet_path),
+            ("DATABASE_URL_SQLITE", &d.sqlite_path),
+            ("DATABASE_URL_POSTGRESQL", &d.postgresql_url),
+            ("NYKS_WALLET_ID", &d.wallet_id),
+            ("HTTPS_PROXY", p),
+            ("HTTP_PROXY", p),
+        ]
+        .into_iter()
+        .filter_map(|(var, value)| value.clone().map(|value| (var, value)))
+        .chain(self.policy_env_vars())
+        .collect()
+    }
+
+    /// `[retry]`, `[http]` timeout, `[traffic]` and `[risk]` values under the
+    /// variables read by [`retry::RetryPolicy::from_env`],
+    /// [`RateLimitPolicy::from_env`](super::RateLimitPolicy::from_env) and
+    /// [`RiskLimits::from_env`].
+    fn policy_env_vars(&self) -> Vec<(&'static str, String)> {
+        let r = &self.retry;
+        let t = &self.traffic;
+        let k = &self.risk;
+        [
+            (
+                retry::MAX_RETRIES_VAR,
+                r.max_attempts.map(|n| n.to_string()),
+            ),
+            (
+                retry::MAX_UTXO_RETRIES_VAR,
+                r.utxo_max_attempts.map(|n| n.to_string()),
+            ),
+            (
+                retry::INITIAL_DELAY_MS_VAR,
+                r.initial_delay_ms.map(|ms| ms.to_string()),
+            ),
+            (
+                retry::MAX_DELAY_MS_VAR,
+                r.max_delay_ms.map(|ms| ms.to_string()),
+            ),
+            (retry::BACKOFF_VAR, r.backoff_factor.map(|f| f.to_string())),
+            (
+                retry::REQUEST_TIMEOUT_SECS_VAR,
+                self.http.timeout_secs.map(|secs| secs.to_string()),
+            ),
+            (
+                rate_limit::RPS_VAR,
+                t.max_requests_per_second.map(|n| n.to_string()),
+            ),
+            (rate_limit::BURST_VAR, t.burst.map(|n| n.to_string())),
+            (MAX_LEVERAGE_VAR, k.max_leverage.map(|l| l.to_string())),
+            (
+                MAX_POSITION_SATS_VAR,
+                k.max_position_sats.map(|n| n.to_string()),
+            ),
+            (
+                MAX_OPEN_ORDERS_VAR,
+                k.max_open_orders.map(|n| n.to_string()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(var, value)| value.map(|value| (var, value)))
+        .collect()
+    }
+
+    /// Export file values for every variable not already set, so the rest of
+    /// the crate sees them. Call at startup, before other threads exist and
+    /// before any endpoint static is read.
+    pub fn apply_to_env(&self) {
+        for (var, value) in self.env_vars() {
+            if std::env::var_os(var).is_none() {
+                unsafe {
+                    std::env::set_var(var, value);
+                }
+            }
+        }
+    }
+
+    /// Endpoint configuration with environment overrides applied.
+    pub fn endpoint_config(&self) -> EndpointConfig {
+        self.endpoint_config_with(|var| std::env::var(var).ok())
+    }
+
+    fn endpoint_config_with(&self, env: impl Fn(&str) -> Option<String>) -> EndpointConfig {
+        let pick = |var: &str, file: &Option<String>, default: &str| {
+            env(var)
+                .or_else(|| file.clone())
+                .unwrap_or_else(|| default.to_string())
+        };
+        let e = &self.endpoints;
+        EndpointConfig::new(
+            pick(
+                "VALIDATOR_WALLET_PATH",
+                &e.validator_wallet_path,
+                VALIDATOR_WALLET_PATH.as_str(),
+            ),
+            pick(
+                "RELAYER_PROGRAM_JSON_PATH",
+                &e.relayer_program_json_path,
+                RELAYER_PROGRAM_JSON_PATH.as_str(),
+            ),
+            pick("ZKOS_SERVER_URL", &e.zkos_server, ZKOS_SERVER_URL.as_str()),
+            pick(
+                "RELAYER_API_RPC_SERVER_URL",
+                &e.relayer_api,
+                RELAYER_API_RPC_SERVER_URL.as_str(),
+            ),
+            pick("NYKS_LCD_BASE_URL", &e.nyks_lcd, NYKS_LCD_BASE_URL.as_str()),
+            pick("NYKS_RPC_BASE_URL", &e.nyks_rpc, NYKS_RPC_BASE_URL.as_str()),
+            pick("FAUCET_BASE_URL", &e.faucet, FAUCET_BASE_URL.as_str()),
+            pick("CHAIN_ID", &self.chain.chain_id, CHAIN_ID.as_str()),
+        )
+        .with_retry_policy(retry::RetryPolicy::from_env_with(self.over_file(&env)))
+        .with_rate_limit(RateLimitPolicy::from_env_with(self.over_file(&env)))
+        .with_tx_fee(self.gas.tx_fee())
+    }
+
+    /// Risk limits for an `OrderWallet`, with environment overrides applied.
+    pub fn risk_limits(&self) -> RiskLimits {
+        RiskLimits::from_env_with(self.over_file(|var: &str| std::env::var(var).ok()))
+    }
+
+    /// `env`, falling back to this file's value of each variable.
+    fn over_file(&self, env: impl Fn(&str) -> Option<String>) -> impl Fn(&str) -> Option<String> {
+        let file = self.policy_env_vars();
+        move |var| {
+            env(var).or_else(|| {
+                file.iter()
+                    .find(|(name, _)| *name == var)
+                    .map(|(_, value)| value.clone())
+            })
+        }
+    }
+
+    /// Commented template listing every field with its mainnet default.
+    pub fn example() -> String {
+        r#"# nyks-wallet configuration.
+# Environment variables (and .env) override values here. Never put
+# passwords, passphrases, mnemonics or keys in this file.
+
+[chain]
+# mainnet or testnet; selects the endpoint defaults (NETWORK_TYPE)
+network_type = "mainnet"
+# BTC network for Esplora (BTC_NETWORK_TYPE)
+btc_network_type = "mainnet"
+chain_id = "nyks"
+
+[endpoints]
+nyks_rpc = "https://rpc.twilight.org"
+nyks_lcd = "https://lcd.twilight.org"
+relayer_api = "https://api.ephemeral.fi/api"
+zkos_server = "https://zkserver.twilight.org"
+# Empty on mainnet (no faucet)
+faucet = ""
+indexer = "https://indexer.twilight.org"
+esplora_primary = "https://blockstream.info/api"
+esplora_fallback = "https://mempool.space/api"
+relayer_program_json_path = "./relayerprogram.json"
+validator_wallet_path = "validator.mnemonic"
+
+[gas]
+denom = "nyks"
+fee_amount = 1000
+gas_limit = 2000000
+# Simulate each tx and sign with the gas used times this factor
+# gas_adjustment = 1.3
+
+# Relayer polling; RELAYER_MAX_RETRIES and friends override these
+[retry]
+# Tx hash lookups
+max_attempts = 60
+# UTXO lookups
+utxo_max_attempts = 30
+initial_delay_ms = 200
+max_delay_ms = 1000
+backoff_factor = 1.5
+
+[http]
+# Per relayer request (RELAYER_REQUEST_TIMEOUT_SECS)
+timeout_secs = 30
+# Exported as HTTPS_PROXY / HTTP_PROXY
+proxy = "http://127.0.0.1:3128"
+
+[database]
+sqlite_path = "./wallet_data.db"
+# No password in the URL; use PGPASSWORD or a .pgpass file
+postgresql_url = "postgres://nyks@localhost/nyks_wallet"
+wallet_id = "my_trading_wallet"
+
+# Relayer request rate (RELAYER_RATE_LIMIT_RPS / RELAYER_RATE_LIMIT_BURST)
+[traffic]
+max_requests_per_second = 10
+burst = 20
+
+# Checked by OrderWallet before each trader order (NYKS_RISK_*)
+[risk]
+max_leverage = 10.0
+max_position_sats = 10000000
+max_open_orders = 20
+"#
+        .to_string()
+    }
+}
+
+impl EndpointConfig {
+    /// Endpoints from a TOML config file, with environment variables taking
+    /// precedence over the file. Validates the file first.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let config = Config::from_file(path)?;
+        config.validate()?;
+        Ok(config.endpoint_config())
+    }
+}
+
+fn reject_secrets(table: &toml::Table, prefix: &str) -> Result<(), ConfigError> {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        let lower = key.to_ascii_lowercase();
+        if SECRET_KEY_PATTERNS.iter().any(|p| lower.contains(p)) {
+            return Err(ConfigError::SecretInFile(path));
+        }
+        if let toml::Value::Table(inner) = value {
+            reject_secrets(inner, &path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_full_example_parses_and_validates() {
+        let config = Config::from_toml(&Config::example()).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.chain.network_type.as_deref(), Some("mainnet"));
+        assert_eq!(config.gas.gas_limit, Some(2_000_000));
+        assert_eq!(config.endpoint_config().tx_fee, TxFeeConfig::default());
+        assert_eq!(config.retry.backoff_factor, Some(1.5));
+        assert_eq!(config.retry.utxo_max_attempts, Some(30));
+        assert_eq!(config.risk.max_open_orders, Some(20));
+        assert_eq!(config.traffic.burst, Some(20));
+        assert!(
+            config
+                .env_vars()
+                .contains(&("HTTPS_PROXY", "http://127.0.0.1:3128".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let config = Config::from_toml(
+            r#"
+            [chain]
+            chain_id = "nyks-file"
+            [endpoints]
+            relayer_api = "https://relayer.file/api"
+            nyks_lcd = "https://lcd.file"
+            "#,
+        )
+        .unwrap();
+        let env: HashMap<&str, &str> = [("NYKS_LCD_BASE_URL", "https://lcd.env")].into();
+        let endpoints = config.endpoint_config_with(|var| env.get(var).map(|v| v.to_string()));
+        assert_eq!(endpoints.relayer_api_endpoint, "https://relayer.file/api");
+        assert_eq!(endpoints.chain_id, "nyks-file");
+        assert_eq!(endpoints.nyks_lcd_endpoint, "https://lcd.env");
+        // Unset in both: built-in default.
+        assert_eq!(endpoints.nyks_rpc_endpoint, NYKS_RPC_BASE_URL.as_str());
+    }
+
+    #[test]
+    fn test_retry_section_maps_onto_policy() {
+        let config =
+            Config::from_toml("[retry]\nmax_attempts = 5\ninitial_delay_ms = 50\n").unwrap();
+        let env: HashMap<&str, &str> = [(retry::MAX_RETRIES_VAR, "3")].into();
+        let endpoints = config.endpoint_config_with(|var| env.get(var).map(|v| v.to_string()));
+        let policy = endpoints.retry_policy;
+        assert_eq!(policy.max_attempts, 3);
+        assert_eq!(policy.initial_delay, std::time::Duration::from_millis(50));
+        assert_eq!(
+            policy.request_timeout,
+            retry::RetryPolicy::default().request_timeout
+        );
+        assert!(
+            config
+                .env_vars()
+                .contains(&(retry::INITIAL_DELAY_MS_VAR, "50".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_http_traffic_and_risk_sections_are_applied() {
+        let config = Config::from_toml(
+            r#"
+            [http]
+            timeout_secs = 5
+            [traffic]
+            max_requests_per_second = 10
+            burst = 20
+            [risk]
+            max_leverage = 5.0
+            max_open_orders = 2
+            "#,
+        )
+        .unwrap();
+        let env: HashMap<&str, &str> = [(rate_limit::BURST_VAR, "40")].into();
+        let endpoints = config.endpoint_config_with(|var| env.get(var).map(|v| v.to_string()));
+        assert_eq!(
+            endpoints.retry_policy.request_timeout,
+            std::time::Duration::from_secs(5)
+        );
+        assert_eq!(endpoints.rate_limit, RateLimitPolicy::new(10.0, 40));
+        assert_eq!(
+            endpoints.to_relayer_endpoint_config().rate_limit,
+            endpoints.rate_limit
+        );
+
+        let limits = RiskLimits::from_env_with(config.over_file(|_: &str| None));
+        assert_eq!(limits.max_leverage, Some(5.0));
+        assert_eq!(limits.max_position_sats, None);
+        assert!(limits.check(1_000_000, 5.0, 1).is_ok());
+        assert_eq!(
+            limits.check(1_000, 10.0, 0).unwrap_err(),
+            "Leverage 10x above the configured maximum 5x"
+        );
+        assert_eq!(
+            limits.check(1_000, 2.0, 2).unwrap_err(),
+            "2 trader orders already open, the configured maximum"
+        );
+    }
+
+    #[test]
+    fn test_gas_section_maps_onto_tx_fee() {
+        let config = Config::from_toml("[gas]\nfee_amount = 3000\ngas_adjustment = 1.2\n").unwrap();
+        config.validate().unwrap();
+        let tx_fee = config.endpoint_config().tx_fee;
+        assert_eq!(tx_fee.fee_amount, 3_000);
+        assert_eq!(tx_fee.gas_adjustment, Some(1.2));
+        assert_eq!(tx_fee.gas_limit, TxFeeConfig::default().gas_limit);
+        assert_eq!(tx_fee.fee_denom, "nyks");
+        assert_eq!(
+            config.endpoint_config().to_wallet_endpoint_config().tx_fee,
+            tx_fee
+        );
+
+        let config = Config::from_toml("[gas]\ngas_adjustment = 0.5\n").unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { ref field, .. }) if field == "gas.gas_adjustment"
+        ));
+    }
+
+    #[test]
+    fn test_secrets_and_bad_values_rejected() {
+        let err = Config::from_toml("[database]\nwallet_id = \"w\"\npassphrase = \"hunter2\"\n")
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::SecretInFile(ref key) if key == "database.passphrase"));
+        assert!(matches!(
+            Config::from_toml("mnemonic = \"abandon abandon\"").unwrap_err(),
+            ConfigError::SecretInFile(_)
+        ));
+        assert!(matches!(
+            Config::from_toml("[database]\npostgresql_url = \"postgres://u:pw@db/nyks\"")
+                .unwrap_err(),
+            ConfigError::SecretInFile(_)
+        ));
+        assert!(matches!(
+            Config::from_toml("[endpoints]\nrelayer = \"typo\"").unwrap_err(),
+            ConfigError::Parse(_)
+        ));
+        let config = Config::from_toml("[chain]\nnetwork_type = \"devnet\"").unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid { ref field, .. }) if field == "chain.network_type"
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+//! Fingerprint of the effective configuration a wallet was saved under.
+//!
+//! A wallet restored on another machine with a different relayer, chain id
+//! or network fails far from the cause. [`ConfigFingerprint`] is stored with
+//! the saved order wallet; on load it is compared with the current
+//! configuration and every differing field is listed in a [`ConfigDrift`].
+
+use serde::{Deserialize, Serialize};
+
+use super::{BTC_NETWORK_TYPE, NETWORK_TYPE, RelayerEndPointConfig, WalletEndPointConfig};
+
+/// Version of the key derivation scheme (BIP-44 path and ZkOS child keys).
+/// Bump when either changes so old wallets are flagged on load.
+pub const DERIVATION_VERSION: u32 = 1;
+
+/// Fields that are reported but never make a drift fatal.
+const INFORMATIONAL_FIELDS: [&str; 1] = ["crate_version"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigFingerprint {
+    pub crate_version: String,
+    pub derivation_version: u32,
+    /// Database namespace the wallet's rows are keyed under (`NETWORK_TYPE`).
+    pub namespace: String,
+    pub btc_network_type: String,
+    pub chain_id: String,
+    pub nyks_lcd_endpoint: String,
+    pub nyks_rpc_endpoint: String,
+    pub faucet_endpoint: String,
+    pub relayer_api_endpoint: String,
+    pub zkos_server_endpoint: String,
+    pub relayer_program_json_path: String,
+}
+
+impl ConfigFingerprint {
+    /// Fingerprint of the given endpoints under the current environment.
+    pub fn capture(
+        chain_id: &str,
+        wallet: &WalletEndPointConfig,
+        relayer: &RelayerEndPointConfig,
+    ) -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            derivation_version: DERIVATION_VERSION,
+            namespace: NETWORK_TYPE.to_string(),
+            btc_network_type: BTC_NETWORK_TYPE.to_string(),
+            chain_id: chain_id.to_string(),
+            nyks_lcd_endpoint: wallet.lcd_endpoint.clone(),
+            nyks_rpc_endpoint: wallet.rpc_endpoint.clone(),
+            faucet_endpoint: wallet.faucet_endpoint.clone(),
+            relayer_api_endpoint: relayer.relayer_api_endpoint.clone(),
+            zkos_server_endpoint: relayer.zkos_server_endpoint.clone(),
+            relayer_program_json_path: relayer.relayer_program_json_path.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid config fingerprint: {}", e))
+    }
+
+    fn fields(&self) -> [(&'static str, String); 11] {
+        [
+            ("crate_version", self.crate_version.clone()),
+            ("derivation_version", self.derivation_version.to_string()),
+            ("namespace", self.namespace.clone()),
+            ("btc_network_type", self.btc_network_type.clone()),
+            ("chain_id", self.chain_id.clone()),
+            ("nyks_lcd_endpoint", self.nyks_lcd_endpoint.clone()),
+            ("nyks_rpc_endpoint", self.nyks_rpc_endpoint.clone()),
+            ("faucet_endpoint", self.faucet_endpoint.clone()),
+            ("relayer_api_endpoint", self.relayer_api_endpoint.clone()),
+            ("zkos_server_endpoint", self.zkos_server_endpoint.clone()),
+            (
+                "relayer_program_json_path",
+                self.relayer_program_json_path.clone(),
+            ),
+        ]
+    }
+}
+
+/// One field that differs between the saved and the current configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDrift {
+    pub field: &'static str,
+    pub saved: String,
+    pub current: String,
+}
+
+/// Every field that differs between a saved and the current fingerprint.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConfigDrift {
+    pub fields: Vec<FieldDrift>,
+}
+
+impl ConfigDrift {
+    pub fn between(saved: &ConfigFingerprint, current: &ConfigFingerprint) -> Self {
+        let fields = saved
+            .fields()
+            .into_iter()
+            .zip(current.fields())
+            .filter(|((_, saved), (_, current))| saved != current)
+            .map(|((field, saved), (_, current))| FieldDrift {
+                field,
+                saved,
+                current,
+            })
+            .collect();
+        Self { fields }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&FieldDrift> {
+        self.fields.iter().find(|f| f.field == field)
+    }
+
+    /// Whether any field other than the crate version differs. This is what
+    /// `strict_config` refuses to load.
+    pub fn is_significant(&self) -> bool {
+        self.fields
+            .iter()
+            .any(|f| !INFORMATIONAL_FIELDS.contains(&f.field))
+    }
+}
+
+impl std::fmt::Display for ConfigDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.fields.is_empty() {
+            return write!(f, "no configuration drift");
+        }
+        for (i, drift) in self.fields.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(
+                f,
+                "{}: saved {:?}, current {:?}",
+                drift.field, drift.saved, drift.current
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint() -> ConfigFingerprint {
+        ConfigFingerprint::capture(
+            "nyks",
+            &WalletEndPointConfig::new(
+                "http://lcd".to_string(),
+                "http://faucet".to_string(),
+                "http://rpc".to_string(),
+                "nyks".to_string(),
+            ),
+            &RelayerEndPointConfig::new(
+                "http://relayer".to_string(),
+                "http://zkos".to_string(),
+                "./relayerprogram.json".to_string(),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_identical_fingerprints_have_no_drift() {
+        let drift = ConfigDrift::between(&fingerprint(), &fingerprint());
+        assert!(drift.is_empty());
+        assert!(!drift.is_significant());
+        assert_eq!(drift.to_string(), "no configuration drift");
+    }
+
+    #[test]
+    fn test_every_differing_field_is_listed() {
+        let saved = fingerprint();
+        let mut current = fingerprint();
+        current.relayer_api_endpoint = "http://other-relayer".to_string();
+        current.chain_id = "nyks-2".to_string();
+        let drift = ConfigDrift::between(&saved, &current);
+        assert_eq!(drift.fields.len(), 2);
+        assert_eq!(
+            drift.get("relayer_api_endpoint"),
+            Some(&FieldDrift {
+                field: "relayer_api_endpoint",
+                saved: "http://relayer".to_string(),
+                current: "http://other-relayer".to_string(),
+            })
+        );
+        assert_eq!(drift.get("chain_id").unwrap().saved, "nyks");
+        assert!(drift.is_significant());
+        assert!(
+            drift
+                .to_string()
+                .contains("chain_id: saved \"nyks\", current \"nyks-2\"")
+        );
+    }
+
+    #[test]
+    fn test_crate_version_alone_is_not_significant() {
+        let mut saved = fingerprint();
+        saved.crate_version = "0.0.1".to_string();
+        let drift = ConfigDrift::between(&saved, &fingerprint());
+        assert_eq!(drift.fields.len(), 1);
+        assert!(!drift.is_significant());
+    }
+
+    #[test]
+    fn test_json_round_trip_tolerates_missing_fields() {
+        let saved = fingerprint();
+        assert_eq!(
+            ConfigFingerprint::from_json(&saved.to_json()).unwrap(),
+            saved
+        );
+        let partial = ConfigFingerprint::from_json(r#"{"chain_id":"nyks"}"#).unwrap();
+        assert_eq!(partial.chain_id, "nyks");
+        assert_eq!(partial.derivation_version, 0);
+        assert!(ConfigFingerprint::from_json("not json").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Request rate limit of a relayer client.
+//!
+//! A [`RateLimitPolicy`] sizes the token bucket a `RelayerJsonRpcClient` and
+//! its clones draw from before each request: `burst` requests can go out at
+//! once, after which requests are spaced to `requests_per_sec`. A request
+//! over the limit waits for its turn; it is never rejected. The defaults are
+//! well above what one wallet sends, so they only hold back a burst of
+//! concurrent tasks polling the same relayer.
+//!
+//! [`RateLimitPolicy::from_env`] applies these overrides to the defaults:
+//!
+//! | Variable | Field |
+//! |----------|-------|
+//! | `RELAYER_RATE_LIMIT_RPS` | `requests_per_sec` (0 disables the limit; negative, infinite or NaN values are ignored) |
+//! | `RELAYER_RATE_LIMIT_BURST` | `burst` |
+//!
+//! `RELAYER_METRICS_LOG_SECS` sets how often a client built from a
+//! `RelayerEndPointConfig` logs its request metrics; see
+//! [`metrics_log_interval_from_env`].
+
+use std::time::Duration;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::retry::parse;
+
+pub const RPS_VAR: &str = "RELAYER_RATE_LIMIT_RPS";
+pub const BURST_VAR: &str = "RELAYER_RATE_LIMIT_BURST";
+pub const METRICS_LOG_SECS_VAR: &str = "RELAYER_METRICS_LOG_SECS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitPolicy {
+    /// Sustained request rate; 0 or less sends every request at once.
+    pub requests_per_sec: f64,
+    /// Requests that can go out back to back before the rate applies.
+    pub burst: u32,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        Self {
+            requests_per_sec: 50.0,
+            burst: 100,
+        }
+    }
+}
+
+impl RateLimitPolicy {
+    /// A `requests_per_sec` that is negative, infinite or NaN is replaced
+    /// by 0, with a warning: the policy is unlimited.
+    pub fn new(requests_per_sec: f64, burst: u32) -> Self {
+        let requests_per_sec = if is_valid_rate(requests_per_sec) {
+            requests_per_sec
+        } else {
+            warn!(
+                "Rate limit of {} requests/s is not a finite, non-negative rate; not limiting",
+                requests_per_sec
+            );
+            0.0
+        };
+        Self {
+            requests_per_sec,
+            burst,
+        }
+    }
+
+    /// No limit.
+    pub fn unlimited() -> Self {
+        Self::new(0.0, 0)
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        !(is_valid_rate(self.requests_per_sec) && self.requests_per_sec > 0.0)
+    }
+
+    /// The defaults with the `RELAYER_RATE_LIMIT_*` environment overrides applied.
+    pub fn from_env() -> Self {
+        Self::from_env_with(|var| std::env::var(var).ok())
+    }
+
+    /// [`from_env`](Self::from_env) reading variables through `env`. Values
+    /// that do not parse, and rates that are negative, infinite or NaN, are
+    /// ignored with a warning.
+    pub(crate) fn from_env_with(env: impl Fn(&str) -> Option<String>) -> Self {
+        let mut policy = Self::default();
+        match parse(&env, RPS_VAR) {
+            Some(rps) if is_valid_rate(rps) => policy.requests_per_sec = rps,
+            Some(rps) => warn!(
+                "Ignoring {}={}: not a finite, non-negative rate",
+                RPS_VAR, rps
+            ),
+            None => {}
+        }
+        if let Some(burst) = parse(&env, BURST_VAR) {
+            policy.burst = burst;
+        }
+        policy
+    }
+}
+
+/// 0 turns the limit off; anything else must be a positive number.
+fn is_valid_rate(requests_per_sec: f64) -> bool {
+    requests_per_sec.is_finite() && requests_per_sec >= 0.0
+}
+
+/// `RELAYER_METRICS_LOG_SECS` as an interval; `None` when unset or 0.
+pub fn metrics_log_interval_from_env() -> Option<Duration> {
+    metrics_log_interval_with(|var| std::env::var(var).ok())
+}
+
+pub(crate) fn metrics_log_interval_with(env: impl Fn(&str) -> Option<String>) -> Option<Duration> {
+    parse(&env, METRICS_LOG_SECS_VAR)
+        .filter(|secs: &u64| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_env_overrides_map_onto_policy() {
+        let env: HashMap<&str, &str> = [
+            (RPS_VAR, "2.5"),
+            (BURST_VAR, "lots"),
+            (METRICS_LOG_SECS_VAR, "60"),
+        ]
+        .into();
+        let get = |var: &str| env.get(var).map(|v| v.to_string());
+        let policy = RateLimitPolicy::from_env_with(get);
+        assert_eq!(policy.requests_per_sec, 2.5);
+        assert_eq!(policy.burst, RateLimitPolicy::default().burst);
+        assert_eq!(
+            metrics_log_interval_with(get),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(metrics_log_interval_with(|_| Some("0".to_string())), None);
+        assert!(RateLimitPolicy::unlimited().is_unlimited());
+    }
+
+    #[test]
+    fn test_rates_that_are_not_finite_or_negative_are_rejected() {
+        for rps in ["NaN", "inf", "-inf", "-2"] {
+            let policy =
+                RateLimitPolicy::from_env_with(|var| (var == RPS_VAR).then(|| rps.to_string()));
+            assert_eq!(policy, RateLimitPolicy::default(), "{}", rps);
+        }
+        let tiny = RateLimitPolicy::from_env_with(|var| (var == RPS_VAR).then(|| "1e-300".into()));
+        assert_eq!(tiny.requests_per_sec, 1e-300);
+        assert!(!tiny.is_unlimited());
+
+        assert!(RateLimitPolicy::new(f64::NAN, 10).is_unlimited());
+        assert!(RateLimitPolicy::new(f64::INFINITY, 10).is_unlimited());
+        assert_eq!(RateLimitPolicy::new(f64::NAN, 10).requests_per_sec, 0.0);
+        // A policy deserialized with a NaN rate does not limit either.
+        let nan = RateLimitPolicy {
+            requests_per_sec: f64::NAN,
+            burst: 10,
+        };
+        assert!(nan.is_unlimited());
+    }
+}
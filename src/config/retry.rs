@@ -0,0 +1,286 @@
+//! Retry and timeout policy for relayer requests.
+//!
+//! A [`RetryPolicy`] sets the per-request timeout of a
+//! `RelayerJsonRpcClient` and bounds the helpers that poll until the relayer
+//! or the chain has indexed a submission (`fetch_tx_hash_with_retry`,
+//! `fetch_utxo_details_with_retry` and the like). The defaults suit the
+//! public relayers. A slow testnet relayer may need more attempts; a local
+//! devnet can fail fast with fewer.
+//!
+//! [`RetryPolicy::from_env`] applies these overrides to the defaults:
+//!
+//! | Variable | Field |
+//! |----------|-------|
+//! | `RELAYER_MAX_RETRIES` | `max_attempts` |
+//! | `RELAYER_MAX_UTXO_RETRIES` | `utxo_max_attempts` |
+//! | `RELAYER_RETRY_INITIAL_DELAY_MS` | `initial_delay` |
+//! | `RELAYER_RETRY_MAX_DELAY_MS` | `max_delay` |
+//! | `RELAYER_RETRY_BACKOFF` | `backoff_multiplier` |
+//! | `RELAYER_REQUEST_TIMEOUT_SECS` | `request_timeout` |
+//!
+//! The delays follow a [`Backoff`], the schedule the LCD retries and the
+//! relayer feed reconnects use too.
+//!
+//! [`LcdRetryPolicy`] bounds the retries of chain LCD queries
+//! (`nyks_rpc::lcd::get`). [`LcdRetryPolicy::from_env`] applies:
+//!
+//! | Variable | Field |
+//! |----------|-------|
+//! | `LCD_MAX_RETRIES` | `max_attempts` |
+//! | `LCD_RETRY_INITIAL_DELAY_MS` | `backoff.initial_delay` |
+//! | `LCD_RETRY_MAX_DELAY_MS` | `backoff.max_delay` |
+//! | `LCD_MAX_RETRY_AFTER_SECS` | `max_retry_after` |
+
+use std::time::Duration;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_RETRIES_VAR: &str = "RELAYER_MAX_RETRIES";
+pub const MAX_UTXO_RETRIES_VAR: &str = "RELAYER_MAX_UTXO_RETRIES";
+pub const INITIAL_DELAY_MS_VAR: &str = "RELAYER_RETRY_INITIAL_DELAY_MS";
+pub const MAX_DELAY_MS_VAR: &str = "RELAYER_RETRY_MAX_DELAY_MS";
+pub const BACKOFF_VAR: &str = "RELAYER_RETRY_BACKOFF";
+pub const REQUEST_TIMEOUT_SECS_VAR: &str = "RELAYER_REQUEST_TIMEOUT_SECS";
+pub const LCD_MAX_RETRIES_VAR: &str = "LCD_MAX_RETRIES";
+pub const LCD_INITIAL_DELAY_MS_VAR: &str = "LCD_RETRY_INITIAL_DELAY_MS";
+pub const LCD_MAX_DELAY_MS_VAR: &str = "LCD_RETRY_MAX_DELAY_MS";
+pub const LCD_MAX_RETRY_AFTER_SECS_VAR: &str = "LCD_MAX_RETRY_AFTER_SECS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Polls before a tx hash lookup gives up.
+    pub max_attempts: u32,
+    /// Polls before a UTXO lookup gives up. Each poll is a full UTXO query,
+    /// so these give up sooner than tx hash lookups.
+    pub utxo_max_attempts: u32,
+    /// Delay after the first failed poll.
+    pub initial_delay: Duration,
+    /// Cap on the delay between polls, before jitter.
+    pub max_delay: Duration,
+    /// Factor the delay grows by after each failed poll.
+    pub backoff_multiplier: f64,
+    /// Timeout of a single relayer request.
+    pub request_timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 60,
+            utxo_max_attempts: 30,
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(1),
+            backoff_multiplier: 1.5,
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The defaults with the `RELAYER_*` environment overrides applied.
+    pub fn from_env() -> Self {
+        Self::from_env_with(|var| std::env::var(var).ok())
+    }
+
+    /// [`from_env`](Self::from_env) reading variables through `env`. Values
+    /// that do not parse are ignored with a warning.
+    pub(crate) fn from_env_with(env: impl Fn(&str) -> Option<String>) -> Self {
+        let mut policy = Self::default();
+        if let Some(n) = parse(&env, MAX_RETRIES_VAR) {
+            policy.max_attempts = n;
+        }
+        if let Some(n) = parse(&env, MAX_UTXO_RETRIES_VAR) {
+            policy.utxo_max_attempts = n;
+        }
+        if let Some(ms) = parse(&env, INITIAL_DELAY_MS_VAR) {
+            policy.initial_delay = Duration::from_millis(ms);
+        }
+        if let Some(ms) = parse(&env, MAX_DELAY_MS_VAR) {
+            policy.max_delay = Duration::from_millis(ms);
+        }
+        if let Some(factor) = parse(&env, BACKOFF_VAR) {
+            policy.backoff_multiplier = factor;
+        }
+        if let Some(secs) = parse(&env, REQUEST_TIMEOUT_SECS_VAR) {
+            policy.request_timeout = Duration::from_secs(secs);
+        }
+        policy
+    }
+
+    /// The policy's delays, with up to 10% jitter.
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            initial_delay: self.initial_delay,
+            max_delay: self.max_delay,
+            factor: self.backoff_multiplier,
+            jitter: 0.1,
+        }
+    }
+
+    /// Delay before poll `attempt + 1`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff().delay(attempt.saturating_add(1))
+    }
+}
+
+/// Exponential backoff with jitter between the attempts of a retried call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+    /// Cap on the delay, before jitter.
+    pub max_delay: Duration,
+    /// Factor the delay grows by after each retry.
+    pub factor: f64,
+    /// Most jitter added, as a fraction of the delay.
+    pub jitter: f64,
+}
+
+impl Backoff {
+    /// Delay before retry `retry` (1 for the first retry).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let base = self.initial_delay.as_secs_f64() * self.factor.powi(exponent);
+        let capped = base.min(self.max_delay.as_secs_f64());
+        Duration::from_secs_f64(capped + fastrand::f64() * capped * self.jitter)
+    }
+}
+
+/// How LCD queries retry an LCD that is answering `5xx` or not answering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LcdRetryPolicy {
+    /// Attempts per request, including the first.
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    /// Upper bound on a server-supplied `Retry-After`.
+    pub max_retry_after: Duration,
+}
+
+impl Default for LcdRetryPolicy {
+    /// Six attempts spread over roughly eight seconds.
+    fn default() -> Self {
+        Self {
+            max_attempts: 6,
+            backoff: Backoff {
+                initial_delay: Duration::from_millis(250),
+                max_delay: Duration::from_secs(4),
+                factor: 2.0,
+                jitter: 0.2,
+            },
+            max_retry_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl LcdRetryPolicy {
+    /// The defaults with the `LCD_*` environment overrides applied.
+    pub fn from_env() -> Self {
+        Self::from_env_with(|var| std::env::var(var).ok())
+    }
+
+    /// [`from_env`](Self::from_env) reading variables through `env`. Values
+    /// that do not parse are ignored with a warning.
+    pub(crate) fn from_env_with(env: impl Fn(&str) -> Option<String>) -> Self {
+        let mut policy = Self::default();
+        if let Some(n) = parse(&env, LCD_MAX_RETRIES_VAR) {
+            policy.max_attempts = n;
+        }
+        if let Some(ms) = parse(&env, LCD_INITIAL_DELAY_MS_VAR) {
+            policy.backoff.initial_delay = Duration::from_millis(ms);
+        }
+        if let Some(ms) = parse(&env, LCD_MAX_DELAY_MS_VAR) {
+            policy.backoff.max_delay = Duration::from_millis(ms);
+        }
+        if let Some(secs) = parse(&env, LCD_MAX_RETRY_AFTER_SECS_VAR) {
+            policy.max_retry_after = Duration::from_secs(secs);
+        }
+        policy
+    }
+}
+
+pub(super) fn parse<T: std::str::FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    var: &str,
+) -> Option<T> {
+    let value = env(var)?;
+    match value.trim().parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            warn!("Ignoring {}={:?}: not a valid value", var, value);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_env_overrides_map_onto_policy() {
+        let env: HashMap<&str, &str> = [
+            (MAX_RETRIES_VAR, "5"),
+            (MAX_UTXO_RETRIES_VAR, "4"),
+            (INITIAL_DELAY_MS_VAR, "10"),
+            (REQUEST_TIMEOUT_SECS_VAR, "2"),
+            (BACKOFF_VAR, "fast"),
+        ]
+        .into();
+        let policy = RetryPolicy::from_env_with(|var| env.get(var).map(|v| v.to_string()));
+        assert_eq!(policy.max_attempts, 5);
+        assert_eq!(policy.utxo_max_attempts, 4);
+        assert_eq!(policy.initial_delay, Duration::from_millis(10));
+        assert_eq!(policy.request_timeout, Duration::from_secs(2));
+        // Unparseable or unset: default.
+        assert_eq!(policy.backoff_multiplier, 1.5);
+        assert_eq!(policy.max_delay, Duration::from_secs(1));
+        // The UTXO lookups keep their own, smaller budget.
+        assert_eq!(RetryPolicy::default().utxo_max_attempts, 30);
+    }
+
+    #[test]
+    fn test_delay_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(400),
+            backoff_multiplier: 2.0,
+            ..Default::default()
+        };
+        let first = policy.delay(0);
+        assert!(first >= Duration::from_millis(100) && first <= Duration::from_millis(110));
+        let second = policy.delay(1);
+        assert!(second >= Duration::from_millis(200) && second <= Duration::from_millis(220));
+        let capped = policy.delay(10);
+        assert!(capped >= Duration::from_millis(400) && capped <= Duration::from_millis(440));
+    }
+
+    #[test]
+    fn test_lcd_env_overrides_map_onto_policy() {
+        let env: HashMap<&str, &str> = [
+            (LCD_MAX_RETRIES_VAR, "2"),
+            (LCD_MAX_DELAY_MS_VAR, "500"),
+            (LCD_MAX_RETRY_AFTER_SECS_VAR, "soon"),
+        ]
+        .into();
+        let policy = LcdRetryPolicy::from_env_with(|var| env.get(var).map(|v| v.to_string()));
+        let default = LcdRetryPolicy::default();
+        assert_eq!(policy.max_attempts, 2);
+        assert_eq!(policy.backoff.max_delay, Duration::from_millis(500));
+        assert_eq!(policy.backoff.initial_delay, default.backoff.initial_delay);
+        assert_eq!(policy.max_retry_after, default.max_retry_after);
+    }
+
+    #[test]
+    fn test_lcd_backoff_grows_and_is_capped() {
+        let policy = LcdRetryPolicy::default();
+        let first = policy.backoff.delay(1);
+        assert!(first >= Duration::from_millis(250) && first <= Duration::from_millis(300));
+        let third = policy.backoff.delay(3);
+        assert!(third >= Duration::from_secs(1) && third <= Duration::from_millis(1200));
+        let late = policy.backoff.delay(30);
+        assert!(late >= Duration::from_secs(4) && late <= Duration::from_millis(4800));
+    }
+}
@@ -0,0 +1,193 @@
+//! Encryption of the ZkOS account secrets in the `zk_accounts` table.
+//!
+//! An account's `scalar` and `account` are stored as one AES-256-GCM
+//! ciphertext in `secrets_encrypted`, under a key derived from the wallet
+//! password with PBKDF2, the scheme of the encrypted wallet blob. Every write
+//! draws a fresh 12-byte nonce (`secrets_nonce`). The salt (`secrets_salt`) is
+//! shared by all rows of a wallet so the key is derived once per load rather
+//! than once per account. The account index is authenticated along with the
+//! ciphertext, so one row's secrets cannot be passed off as another's.
+//!
+//! The plaintext `scalar` and `account` columns of an encrypted row are empty.
+//! Index, balance, IO type, on-chain flag and the other columns stay plaintext,
+//! so accounts can be listed without the password. Rows written before
+//! encryption keep their plaintext until
+//! [`DatabaseManager::unlock_zk_accounts`](super::DatabaseManager::unlock_zk_accounts)
+//! re-encrypts them.
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::security::{entropy::EntropySource, SecurePassword};
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use secrecy::SecretString;
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use zeroize::Zeroize;
+
+/// Bytes of the PBKDF2 salt shared by a wallet's rows.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+pub const ACCOUNT_SECRETS_SALT_BYTES: usize = 32;
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Serialize, Deserialize)]
+struct AccountSecrets {
+    scalar: String,
+    account: String,
+}
+
+/// Ciphertext and nonce of one row's secrets.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SealedSecrets {
+    pub ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+}
+
+/// Key for the account secrets of one wallet, derived from its password.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Clone)]
+pub struct AccountSecretsCipher {
+    salt: Vec<u8>,
+    key: [u8; 32],
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl std::fmt::Debug for AccountSecretsCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountSecretsCipher")
+            .field("salt", &self.salt)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl Drop for AccountSecretsCipher {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl AccountSecretsCipher {
+    pub fn derive(password: &SecretString, salt: &[u8]) -> Result<Self, String> {
+        let key = SecurePassword::derive_key_from_passphrase(password, salt)
+            .map_err(|e| format!("Key derivation failed: {}", e))?;
+        Ok(Self {
+            salt: salt.to_vec(),
+            key,
+        })
+    }
+
+    /// Key under a fresh salt drawn from `source`.
+    pub fn generate(password: &SecretString, source: &dyn EntropySource) -> Result<Self, String> {
+        let mut salt = [0u8; ACCOUNT_SECRETS_SALT_BYTES];
+        source.fill_bytes(&mut salt);
+        Self::derive(password, &salt)
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+    }
+
+    /// Encrypt the secrets of account `index` under a nonce from `source`.
+    pub fn seal(
+        &self,
+        index: u64,
+        scalar: &str,
+        account: &str,
+        source: &dyn EntropySource,
+    ) -> Result<SealedSecrets, String> {
+        let mut nonce = [0u8; 12];
+        source.fill_bytes(&mut nonce);
+        let mut plaintext = serde_json::to_vec(&AccountSecrets {
+            scalar: scalar.to_string(),
+            account: account.to_string(),
+        })
+        .map_err(|e| format!("Failed to serialize zk_account secrets: {}", e))?;
+        let sealed = self.cipher().encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &index.to_be_bytes(),
+            },
+        );
+        plaintext.zeroize();
+        Ok(SealedSecrets {
+            ciphertext: sealed.map_err(|e| format!("Encryption failed: {}", e))?,
+            nonce: nonce.to_vec(),
+        })
+    }
+
+    /// Decrypt the secrets of account `index` into `(scalar, account)`.
+    /// Fails for a wrong password, a row of another index or a changed
+    /// ciphertext alike.
+    pub fn open(&self, index: u64, sealed: &SealedSecrets) -> Result<(String, String), String> {
+        if sealed.nonce.len() != 12 {
+            return Err(format!("zk_account {} has a malformed nonce", index));
+        }
+        let mut plaintext = self
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(&sealed.nonce),
+                Payload {
+                    msg: &sealed.ciphertext,
+                    aad: &index.to_be_bytes(),
+                },
+            )
+            .map_err(|_| {
+                format!(
+                    "Failed to decrypt zk_account {}: wrong password or corrupted row",
+                    index
+                )
+            })?;
+        let secrets: Result<AccountSecrets, _> = serde_json::from_slice(&plaintext);
+        plaintext.zeroize();
+        let secrets =
+            secrets.map_err(|e| format!("Failed to deserialize zk_account {}: {}", index, e))?;
+        Ok((secrets.scalar, secrets.account))
+    }
+}
+
+#[cfg(all(test, any(feature = "sqlite", feature = "postgresql")))]
+mod tests {
+    use super::*;
+    use crate::security::entropy::testing::CountingEntropy;
+
+    #[test]
+    fn test_secrets_open_only_with_the_same_key_and_index() {
+        let password = SecretString::new("correct horse".to_string());
+        let source = CountingEntropy::starting_at(0);
+        let cipher = AccountSecretsCipher::generate(&password, &source).unwrap();
+
+        let first = cipher
+            .seal(3, "scalar-hex", "account-hex", &source)
+            .unwrap();
+        let second = cipher
+            .seal(3, "scalar-hex", "account-hex", &source)
+            .unwrap();
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.ciphertext, second.ciphertext);
+        assert_eq!(
+            cipher.open(3, &first).unwrap(),
+            ("scalar-hex".to_string(), "account-hex".to_string())
+        );
+
+        let again = AccountSecretsCipher::derive(&password, cipher.salt()).unwrap();
+        assert!(again.open(3, &second).is_ok());
+
+        let wrong_password = SecretString::new("wrong".to_string());
+        let wrong = AccountSecretsCipher::derive(&wrong_password, cipher.salt()).unwrap();
+        let err = wrong.open(3, &first).unwrap_err();
+        assert!(err.contains("wrong password"));
+        assert!(cipher.open(4, &first).is_err());
+    }
+}
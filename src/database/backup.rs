@@ -14,13 +14,15 @@ use crate::database::{
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use diesel::prelude::*;
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::log_privacy::LoggedAddress;
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use log::debug;
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use serde::{Deserialize, Serialize};
 
 /// Version of the backup format. Increment when the schema changes.
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-const BACKUP_FORMAT_VERSION: u32 = 3;
+const BACKUP_FORMAT_VERSION: u32 = 4;
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 fn current_network_type() -> String {
@@ -145,7 +147,7 @@ impl DatabaseManager {
 
         debug!(
             "Exported backup for wallet {}: {} zk_accounts, {} utxos, {} order_history, {} transfer_history",
-            self.get_wallet_id(),
+            LoggedAddress(self.get_wallet_id()),
             backup.zk_accounts.len(),
             backup.utxo_details.len(),
             backup.order_history.len(),
@@ -166,6 +168,11 @@ impl DatabaseManager {
     ///
     /// The backup's `wallet_id` must match this DatabaseManager's wallet_id
     /// (or `force` must be true to re-map data to the current wallet_id).
+    ///
+    /// Zk account secrets are imported as they were exported, sealed under
+    /// the exporting wallet's key or, before format 4, in plaintext. Call
+    /// [`Self::unlock_zk_accounts`] afterwards to re-encrypt them under this
+    /// manager's key; until then sealed rows of another key fail to load.
     pub fn import_backup(&self, backup: &WalletBackup, force: bool) -> Result<(), String> {
         // Accept both v1 and v2 backups
         if backup.format_version > BACKUP_FORMAT_VERSION {
@@ -368,7 +375,7 @@ impl DatabaseManager {
 
         debug!(
             "Imported backup for wallet {}: {} zk_accounts, {} utxos, {} order_history, {} transfer_history",
-            wallet_id,
+            LoggedAddress(&wallet_id),
             backup.zk_accounts.len(),
             backup.utxo_details.len(),
             backup.order_history.len(),
@@ -1,9 +1,11 @@
+pub mod account_secrets;
 pub mod backup;
 pub mod connection;
 pub mod models;
 pub mod operations;
 pub mod schema;
 
+pub use account_secrets::*;
 pub use backup::*;
 pub use connection::*;
 pub use models::*;
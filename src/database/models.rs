@@ -11,10 +11,16 @@ use diesel::prelude::*;
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use serde::{Deserialize, Serialize};
 
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::database::account_secrets::{AccountSecretsCipher, SealedSecrets};
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::security::entropy::EntropySource;
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::relayer_module::pending_operations::{OperationStep, PendingOperation};
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use crate::security::SecurePassword;
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-use twilight_client_sdk::{relayer_rpcclient::method::UtxoDetailResponse, relayer_types::TXType, zkvm::IOType};
+use crate::compat::{relayer_rpcclient::method::UtxoDetailResponse, relayer_types::TXType, zkvm::IOType};
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 fn current_network_type() -> String {
@@ -40,6 +46,17 @@ pub struct DbZkAccount {
     pub tx_type: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub balance_unverified: bool,
+    pub label: Option<String>,
+    /// Sealed `scalar` and `account`; see [`account_secrets`](super::account_secrets).
+    /// `None` for rows written before encryption.
+    #[serde(default)]
+    pub secrets_encrypted: Option<Vec<u8>>,
+    #[serde(default)]
+    pub secrets_salt: Option<Vec<u8>>,
+    #[serde(default)]
+    pub secrets_nonce: Option<Vec<u8>>,
 }
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -58,6 +75,12 @@ pub struct NewDbZkAccount {
     pub tx_type: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub balance_unverified: bool,
+    pub label: Option<String>,
+    pub secrets_encrypted: Option<Vec<u8>>,
+    pub secrets_salt: Option<Vec<u8>>,
+    pub secrets_nonce: Option<Vec<u8>>,
 }
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -89,6 +112,7 @@ pub struct NewEncryptedWallet {
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 impl DbZkAccount {
+    /// Row for `zk_account` with its secrets in plaintext.
     pub fn from_zk_account(zk_account: &ZkAccount, wallet_id: String) -> NewDbZkAccount {
         let now = chrono::Utc::now().naive_utc();
         NewDbZkAccount {
@@ -104,10 +128,78 @@ impl DbZkAccount {
             tx_type: zk_account.tx_type.as_ref().map(|t| format!("{:?}", t)),
             created_at: now,
             updated_at: now,
+            last_error: encode_last_error(zk_account),
+            balance_unverified: zk_account.balance_unverified,
+            label: zk_account.label.clone(),
+            secrets_encrypted: None,
+            secrets_salt: None,
+            secrets_nonce: None,
         }
     }
 
-    pub fn to_zk_account(&self) -> Result<ZkAccount, String> {
+    /// Row for `zk_account` with `scalar` and `account` sealed by `cipher`
+    /// and their plaintext columns left empty.
+    pub fn from_zk_account_sealed(
+        zk_account: &ZkAccount,
+        wallet_id: String,
+        cipher: &AccountSecretsCipher,
+        source: &dyn EntropySource,
+    ) -> Result<NewDbZkAccount, String> {
+        let sealed = cipher.seal(
+            zk_account.index,
+            &zk_account.scalar,
+            &zk_account.account,
+            source,
+        )?;
+        Ok(NewDbZkAccount {
+            account: String::new(),
+            scalar: String::new(),
+            secrets_encrypted: Some(sealed.ciphertext),
+            secrets_salt: Some(cipher.salt().to_vec()),
+            secrets_nonce: Some(sealed.nonce),
+            ..Self::from_zk_account(zk_account, wallet_id)
+        })
+    }
+
+    /// The sealed secrets and their salt; `None` for a plaintext row.
+    pub fn sealed_secrets(&self) -> Option<(SealedSecrets, &[u8])> {
+        match (
+            &self.secrets_encrypted,
+            &self.secrets_salt,
+            &self.secrets_nonce,
+        ) {
+            (Some(ciphertext), Some(salt), Some(nonce)) => Some((
+                SealedSecrets {
+                    ciphertext: ciphertext.clone(),
+                    nonce: nonce.clone(),
+                },
+                salt.as_slice(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The account, its secrets decrypted with `cipher` if the row is
+    /// sealed. A sealed row fails without a cipher, or with one of another
+    /// salt, rather than loading with empty secrets.
+    pub fn to_zk_account(
+        &self,
+        cipher: Option<&AccountSecretsCipher>,
+    ) -> Result<ZkAccount, String> {
+        let index = self.account_index as u64;
+        let (scalar, account) = match self.sealed_secrets() {
+            None => (self.scalar.clone(), self.account.clone()),
+            Some((sealed, salt)) => match cipher {
+                Some(cipher) if cipher.salt() == salt => cipher.open(index, &sealed)?,
+                _ => {
+                    return Err(format!(
+                        "zk_account {} is encrypted; unlock the accounts with the wallet password first",
+                        index
+                    ));
+                }
+            },
+        };
+
         let io_type = match self.io_type_value {
             0 => IOType::Coin,
             1 => IOType::Memo,
@@ -119,12 +211,20 @@ impl DbZkAccount {
         Ok(ZkAccount {
             qq_address: self.qq_address.clone(),
             balance: self.balance as u64,
-            account: self.account.clone(),
-            scalar: self.scalar.clone(),
-            index: self.account_index as u64,
+            account,
+            scalar,
+            index,
             io_type,
             on_chain: self.on_chain,
             tx_type,
+            // An unreadable note is dropped rather than failing the whole account load.
+            last_error: self
+                .last_error
+                .as_deref()
+                .and_then(|json| serde_json::from_str(json).ok()),
+            balance_unverified: self.balance_unverified,
+            simulated: false,
+            label: self.label.clone(),
         })
     }
 
@@ -133,10 +233,22 @@ impl DbZkAccount {
         self.io_type_value = zk_account.io_type.clone() as i32;
         self.on_chain = zk_account.on_chain;
         self.tx_type = zk_account.tx_type.as_ref().map(|t| format!("{:?}", t));
+        self.last_error = encode_last_error(zk_account);
+        self.balance_unverified = zk_account.balance_unverified;
+        self.label = zk_account.label.clone();
         self.updated_at = chrono::Utc::now().naive_utc();
     }
 }
 
+/// JSON encoding of `ZkAccount::last_error` for the `zk_accounts.last_error` column.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+pub(crate) fn encode_last_error(zk_account: &ZkAccount) -> Option<String> {
+    zk_account
+        .last_error
+        .as_ref()
+        .and_then(|e| serde_json::to_string(e).ok())
+}
+
 // OrderWallet related models
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 #[derive(Queryable, Selectable, Insertable, AsChangeset, Debug, Clone, Serialize, Deserialize)]
@@ -157,6 +269,9 @@ pub struct DbOrderWallet {
     pub is_active: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    /// JSON `ConfigFingerprint`; `None` for rows saved before it existed.
+    #[serde(default)]
+    pub config_fingerprint: Option<String>,
 }
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -175,6 +290,7 @@ pub struct NewDbOrderWallet {
     pub is_active: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub config_fingerprint: Option<String>,
 }
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -217,6 +333,7 @@ pub struct DbRequestId {
     pub request_id: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub idempotency_key: Option<String>,
 }
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -229,6 +346,7 @@ pub struct NewDbRequestId {
     pub request_id: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub idempotency_key: Option<String>,
 }
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -238,6 +356,7 @@ impl DbOrderWallet {
         chain_id: String,
         seed: &str,
         relayer_config: &RelayerEndPointConfig,
+        fingerprint: &crate::config::ConfigFingerprint,
         password: &str,
     ) -> Result<NewDbOrderWallet, String> {
         use secrecy::SecretString;
@@ -261,6 +380,7 @@ impl DbOrderWallet {
             is_active: true,
             created_at: now,
             updated_at: now,
+            config_fingerprint: Some(fingerprint.to_json()),
         })
     }
 
@@ -287,18 +407,22 @@ impl DbOrderWallet {
     fn encrypt_seed(
         seed: &str,
         password: &secrecy::SecretString,
+    ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), String> {
+        Self::encrypt_seed_with(seed, password, &*crate::security::entropy::default_source())
+    }
+
+    fn encrypt_seed_with(
+        seed: &str,
+        password: &secrecy::SecretString,
+        source: &dyn crate::security::entropy::EntropySource,
     ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), String> {
         use aes_gcm::{
             Aes256Gcm, Key, Nonce,
-            aead::{Aead, KeyInit, OsRng},
+            aead::{Aead, KeyInit},
         };
-        use rand_core::RngCore;
 
         // Generate salt and nonce
-        let mut salt = [0u8; 32];
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut salt);
-        OsRng.fill_bytes(&mut nonce_bytes);
+        let (salt, nonce_bytes) = crate::security::entropy::salt_and_nonce(source);
 
         // Derive key
         let key_bytes = SecurePassword::derive_key_from_passphrase(password, &salt)
@@ -557,9 +681,220 @@ pub struct NewDbBtcTransfer {
     pub updated_at: NaiveDateTime,
 }
 
+// Pending (resumable) operation model
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = pending_operations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbPendingOperation {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub operation_id: String,
+    pub kind: String,
+    pub status: String,
+    pub payload: String,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = pending_operations)]
+pub struct NewDbPendingOperation {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub operation_id: String,
+    pub kind: String,
+    pub status: String,
+    pub payload: String,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbPendingOperation {
+    /// Row for `op`. The zk secrets of its steps are sealed with `cipher`,
+    /// which is needed only if [`PendingOperation::carries_secrets`].
+    pub fn from_pending_operation(
+        wallet_id: String,
+        op: &PendingOperation,
+        cipher: Option<&AccountSecretsCipher>,
+        source: &dyn EntropySource,
+    ) -> Result<Self, String> {
+        let payload = match cipher {
+            _ if !op.carries_secrets() => serde_json::to_string(op),
+            Some(cipher) => {
+                serde_json::to_string(&SealedPendingOperation::seal(op, cipher, source)?)
+            }
+            None => {
+                return Err(format!(
+                    "Pending operation {} holds zk secrets; unlock the zk accounts to save it",
+                    op.id
+                ));
+            }
+        }
+        .map_err(|e| format!("Failed to serialize pending operation: {}", e))?;
+        Ok(Self {
+            wallet_id,
+            network_type: current_network_type(),
+            operation_id: op.id.clone(),
+            kind: op.kind.as_str().to_string(),
+            status: op.status.as_str().to_string(),
+            payload,
+            last_error: op.last_error.clone(),
+            created_at: op.created_at.naive_utc(),
+            updated_at: op.updated_at.naive_utc(),
+        })
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl DbPendingOperation {
+    /// The stored operation; a sealed payload needs `cipher`. Rows written
+    /// before journals were sealed are read as plaintext.
+    pub fn to_pending_operation(
+        &self,
+        cipher: Option<&AccountSecretsCipher>,
+    ) -> Result<PendingOperation, String> {
+        let payload: serde_json::Value = serde_json::from_str(&self.payload)
+            .map_err(|e| format!("Failed to deserialize pending operation: {}", e))?;
+        if payload.get("sealed_steps").is_none() {
+            return serde_json::from_value(payload)
+                .map_err(|e| format!("Failed to deserialize pending operation: {}", e));
+        }
+        let sealed: SealedPendingOperation = serde_json::from_value(payload)
+            .map_err(|e| format!("Failed to deserialize pending operation: {}", e))?;
+        let cipher = cipher.ok_or_else(|| {
+            format!(
+                "Pending operation {} is sealed; unlock the zk accounts to resume it",
+                self.operation_id
+            )
+        })?;
+        sealed.open(cipher)
+    }
+
+    /// Whether the payload holds zk secrets in plaintext.
+    pub fn has_plaintext_secrets(&self) -> Result<bool, String> {
+        let payload: serde_json::Value = serde_json::from_str(&self.payload)
+            .map_err(|e| format!("Failed to deserialize pending operation: {}", e))?;
+        if payload.get("sealed_steps").is_some() {
+            return Ok(false);
+        }
+        Ok(self.to_pending_operation(None)?.carries_secrets())
+    }
+}
+
+/// Sealed `(encrypt_scalar, account_key)` of one step, hex encoded; a
+/// rotation has no account key and seals it empty.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Serialize, Deserialize)]
+struct SealedStepSecrets {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// `payload` of a pending operation that [carries
+/// secrets](PendingOperation::carries_secrets): the operation with the
+/// secrets of its steps emptied, and those secrets sealed as the
+/// `zk_accounts` secrets are, one entry per step that held them, completed
+/// steps first.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Serialize, Deserialize)]
+struct SealedPendingOperation {
+    operation: PendingOperation,
+    sealed_steps: Vec<SealedStepSecrets>,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl SealedPendingOperation {
+    fn seal(
+        op: &PendingOperation,
+        cipher: &AccountSecretsCipher,
+        source: &dyn EntropySource,
+    ) -> Result<Self, String> {
+        let mut operation = op.clone();
+        let mut sealed_steps = Vec::new();
+        for (index, encrypt_scalar, account_key) in step_secrets(&mut operation) {
+            let encrypt_scalar = std::mem::take(encrypt_scalar);
+            let account_key = account_key.map(std::mem::take).unwrap_or_default();
+            let sealed = cipher.seal(index, &encrypt_scalar, &account_key, source)?;
+            sealed_steps.push(SealedStepSecrets {
+                nonce: hex::encode(sealed.nonce),
+                ciphertext: hex::encode(sealed.ciphertext),
+            });
+        }
+        Ok(Self {
+            operation,
+            sealed_steps,
+        })
+    }
+
+    fn open(self, cipher: &AccountSecretsCipher) -> Result<PendingOperation, String> {
+        let Self {
+            mut operation,
+            sealed_steps,
+        } = self;
+        let id = operation.id.clone();
+        let mut sealed_steps = sealed_steps.into_iter();
+        for (index, encrypt_scalar, account_key) in step_secrets(&mut operation) {
+            let step = sealed_steps
+                .next()
+                .ok_or_else(|| format!("Pending operation {} is missing sealed secrets", id))?;
+            let sealed = SealedSecrets {
+                ciphertext: hex::decode(&step.ciphertext).map_err(|e| {
+                    format!("Pending operation {} has a malformed ciphertext: {}", id, e)
+                })?,
+                nonce: hex::decode(&step.nonce).map_err(|e| {
+                    format!("Pending operation {} has a malformed nonce: {}", id, e)
+                })?,
+            };
+            let (scalar, key) = cipher.open(index, &sealed)?;
+            *encrypt_scalar = scalar;
+            if let Some(account_key) = account_key {
+                *account_key = key;
+            }
+        }
+        Ok(operation)
+    }
+}
+
+/// The account, encrypt scalar and account key (for a receiver) of each
+/// step holding zk secrets.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+fn step_secrets(
+    op: &mut PendingOperation,
+) -> impl Iterator<Item = (u64, &mut String, Option<&mut String>)> {
+    op.completed_steps
+        .iter_mut()
+        .chain(op.remaining_steps.iter_mut())
+        .filter_map(|step| match step {
+            OperationStep::FinalizeReceiver {
+                account_index,
+                encrypt_scalar,
+                account_key,
+                ..
+            } => Some((*account_index, encrypt_scalar, Some(account_key))),
+            OperationStep::FinalizeRotation {
+                account_index,
+                encrypt_scalar,
+                ..
+            } => Some((*account_index, encrypt_scalar, None)),
+            _ => None,
+        })
+}
+
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 impl DbRequestId {
-    pub fn new(wallet_id: String, account_index: u64, request_id: String) -> NewDbRequestId {
+    pub fn new(
+        wallet_id: String,
+        account_index: u64,
+        request_id: String,
+        idempotency_key: Option<String>,
+    ) -> NewDbRequestId {
         let now = chrono::Utc::now().naive_utc();
 
         NewDbRequestId {
@@ -569,6 +904,541 @@ impl DbRequestId {
             request_id,
             created_at: now,
             updated_at: now,
+            idempotency_key,
+        }
+    }
+}
+
+// Signing audit model
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = signing_audit)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbSigningAuditEntry {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub seq: i64,
+    pub account_index: i64,
+    pub purpose: String,
+    pub payload_hash: String,
+    pub prev_hash: String,
+    pub entry_hash: String,
+    pub signed_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = signing_audit)]
+pub struct NewDbSigningAuditEntry {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub seq: i64,
+    pub account_index: i64,
+    pub purpose: String,
+    pub payload_hash: String,
+    pub prev_hash: String,
+    pub entry_hash: String,
+    pub signed_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbSigningAuditEntry {
+    pub fn from_entry(
+        wallet_id: String,
+        entry: &crate::relayer_module::signing_audit::SigningAuditEntry,
+    ) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            seq: entry.seq as i64,
+            account_index: entry.account_index as i64,
+            purpose: entry.purpose.as_str().to_string(),
+            payload_hash: entry.payload_hash.clone(),
+            prev_hash: entry.prev_hash.clone(),
+            entry_hash: entry.entry_hash.clone(),
+            signed_at: entry.signed_at.naive_utc(),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl DbSigningAuditEntry {
+    pub fn to_entry(
+        &self,
+    ) -> Result<crate::relayer_module::signing_audit::SigningAuditEntry, String> {
+        use crate::relayer_module::signing_audit::{SigningAuditEntry, SigningPurpose};
+        let purpose = SigningPurpose::parse(&self.purpose)
+            .ok_or_else(|| format!("Unknown signing purpose: {}", self.purpose))?;
+        Ok(SigningAuditEntry {
+            seq: self.seq as u64,
+            account_index: self.account_index as u64,
+            purpose,
+            payload_hash: self.payload_hash.clone(),
+            signed_at: self.signed_at.and_utc(),
+            prev_hash: self.prev_hash.clone(),
+            entry_hash: self.entry_hash.clone(),
+        })
+    }
+}
+
+// Activity histogram model
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = activity_buckets)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbActivityBucket {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub hour: NaiveDateTime,
+    pub category: String,
+    pub count: i64,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = activity_buckets)]
+pub struct NewDbActivityBucket {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub hour: NaiveDateTime,
+    pub category: String,
+    pub count: i64,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbActivityBucket {
+    pub fn from_count(
+        wallet_id: String,
+        count: &crate::relayer_module::activity::ActivityCount,
+    ) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            hour: count.hour.naive_utc(),
+            category: count.category.as_str().to_string(),
+            count: count.count as i64,
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl DbActivityBucket {
+    pub fn to_count(&self) -> Result<crate::relayer_module::activity::ActivityCount, String> {
+        use crate::relayer_module::activity::{ActivityCategory, ActivityCount};
+        let category = ActivityCategory::parse(&self.category)
+            .ok_or_else(|| format!("Unknown activity category: {}", self.category))?;
+        Ok(ActivityCount {
+            hour: self.hour.and_utc(),
+            category,
+            count: self.count.max(0) as u64,
+        })
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = archived_zk_accounts)]
+pub struct NewDbArchivedZkAccount {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub archived_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbArchivedZkAccount {
+    pub fn new(wallet_id: String, account_index: u64, archived_at: NaiveDateTime) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            account_index: account_index as i64,
+            archived_at,
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = order_records)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbOrderRecord {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub seq: i64,
+    pub request_id: String,
+    pub kind: String,
+    pub status: String,
+    pub recorded_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    #[serde(default)]
+    pub order_type: Option<String>,
+    #[serde(default)]
+    pub requested_price: Option<i64>,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = order_records)]
+pub struct NewDbOrderRecord {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub seq: i64,
+    pub request_id: String,
+    pub kind: String,
+    pub status: String,
+    pub recorded_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub order_type: Option<String>,
+    pub requested_price: Option<i64>,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbOrderRecord {
+    pub fn new(
+        wallet_id: String,
+        account_index: u64,
+        seq: usize,
+        record: &crate::relayer_module::transaction_history::OrderRecord,
+        updated_at: chrono::DateTime<chrono::Utc>,
+    ) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            account_index: account_index as i64,
+            seq: seq as i64,
+            request_id: record.request_id.clone(),
+            kind: record.kind.as_str().to_string(),
+            status: record.status.clone(),
+            recorded_at: record.recorded_at.naive_utc(),
+            updated_at: updated_at.naive_utc(),
+            order_type: record.order_type.clone(),
+            requested_price: record.requested_price.map(|p| p as i64),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = pending_submissions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbPendingSubmission {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub idempotency_key: String,
+    pub kind: String,
+    pub account_address: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = pending_submissions)]
+pub struct NewDbPendingSubmission {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub idempotency_key: String,
+    pub kind: String,
+    pub account_address: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbPendingSubmission {
+    pub fn new(
+        wallet_id: String,
+        submission: &crate::relayer_module::idempotency::PendingSubmission,
+    ) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            account_index: submission.account_index as i64,
+            idempotency_key: submission.idempotency_key.clone(),
+            kind: submission.kind.as_str().to_string(),
+            account_address: submission.account_address.clone(),
+            created_at: submission.created_at.naive_utc(),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = account_pool_members)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbAccountPoolMember {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub pool_name: String,
+    pub account_index: i64,
+    pub state: String,
+    pub updated_at: NaiveDateTime,
+    pub request_id: Option<String>,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = account_pool_members)]
+pub struct NewDbAccountPoolMember {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub pool_name: String,
+    pub account_index: i64,
+    pub state: String,
+    pub updated_at: NaiveDateTime,
+    pub request_id: Option<String>,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbAccountPoolMember {
+    pub fn new(
+        wallet_id: String,
+        pool_name: &str,
+        account_index: u64,
+        state: &str,
+        request_id: Option<&str>,
+    ) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            pool_name: pool_name.to_string(),
+            account_index: account_index as i64,
+            state: state.to_string(),
+            updated_at: chrono::Utc::now().naive_utc(),
+            request_id: request_id.map(str::to_string),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = conditional_triggers)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbConditionalTrigger {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub kind: String,
+    pub payload: String,
+    pub updated_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = conditional_triggers)]
+pub struct NewDbConditionalTrigger {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub kind: String,
+    pub payload: String,
+    pub updated_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbConditionalTrigger {
+    pub fn from_trigger(
+        wallet_id: String,
+        trigger: &crate::relayer_module::conditional_orders::ConditionalTrigger,
+    ) -> Result<Self, String> {
+        let payload = serde_json::to_string(trigger)
+            .map_err(|e| format!("Failed to serialize conditional trigger: {}", e))?;
+        Ok(Self {
+            wallet_id,
+            network_type: current_network_type(),
+            account_index: trigger.account_index as i64,
+            kind: trigger.kind.as_str().to_string(),
+            payload,
+            updated_at: chrono::Utc::now().naive_utc(),
+        })
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl DbConditionalTrigger {
+    pub fn to_trigger(
+        &self,
+    ) -> Result<crate::relayer_module::conditional_orders::ConditionalTrigger, String> {
+        serde_json::from_str(&self.payload)
+            .map_err(|e| format!("Failed to deserialize conditional trigger: {}", e))
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = order_snapshots)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbOrderSnapshot {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub request_id: String,
+    pub kind: String,
+    pub order_status: String,
+    pub position_type: Option<String>,
+    pub entry_price: Option<f64>,
+    pub exit_price: Option<f64>,
+    pub leverage: Option<f64>,
+    pub initial_margin: f64,
+    pub settled_margin: Option<f64>,
+    pub realized_pnl: Option<f64>,
+    pub fees: Option<f64>,
+    pub opened_at: Option<NaiveDateTime>,
+    pub recorded_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = order_snapshots)]
+pub struct NewDbOrderSnapshot {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub account_index: i64,
+    pub request_id: String,
+    pub kind: String,
+    pub order_status: String,
+    pub position_type: Option<String>,
+    pub entry_price: Option<f64>,
+    pub exit_price: Option<f64>,
+    pub leverage: Option<f64>,
+    pub initial_margin: f64,
+    pub settled_margin: Option<f64>,
+    pub realized_pnl: Option<f64>,
+    pub fees: Option<f64>,
+    pub opened_at: Option<NaiveDateTime>,
+    pub recorded_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbOrderSnapshot {
+    pub fn new(
+        wallet_id: String,
+        snapshot: &crate::relayer_module::transaction_history::OrderSnapshot,
+    ) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            account_index: snapshot.account_index as i64,
+            request_id: snapshot.request_id.clone(),
+            kind: snapshot.kind.as_str().to_string(),
+            order_status: snapshot.order_status.clone(),
+            position_type: snapshot.position_type.clone(),
+            entry_price: snapshot.entry_price,
+            exit_price: snapshot.exit_price,
+            leverage: snapshot.leverage,
+            initial_margin: snapshot.initial_margin,
+            settled_margin: snapshot.settled_margin,
+            realized_pnl: snapshot.realized_pnl,
+            fees: snapshot.fees,
+            opened_at: snapshot.opened_at.map(|t| t.naive_utc()),
+            recorded_at: snapshot.recorded_at.naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Queryable, Selectable, Debug, Clone, Serialize, Deserialize)]
+#[diesel(table_name = address_book)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DbAddressBookEntry {
+    pub id: Option<i32>,
+    pub wallet_id: String,
+    pub network_type: String,
+    pub label: String,
+    pub address: String,
+    pub kind: String,
+    pub added_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+#[derive(Insertable, Debug)]
+#[diesel(table_name = address_book)]
+pub struct NewDbAddressBookEntry {
+    pub wallet_id: String,
+    pub network_type: String,
+    pub label: String,
+    pub address: String,
+    pub kind: String,
+    pub added_at: NaiveDateTime,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl NewDbAddressBookEntry {
+    pub fn new(
+        wallet_id: String,
+        entry: &crate::relayer_module::address_book::AddressBookEntry,
+    ) -> Self {
+        Self {
+            wallet_id,
+            network_type: current_network_type(),
+            label: entry.label.clone(),
+            address: entry.address.clone(),
+            kind: entry.kind.as_str().to_string(),
+            added_at: entry.added_at.naive_utc(),
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "sqlite", feature = "postgresql")))]
+mod tests {
+    use super::*;
+    use crate::response_mutations::arbitrary_json;
+    use proptest::prelude::*;
+
+    fn stored_utxo(utxo_data: String) -> DbUtxoDetail {
+        let now = chrono::Utc::now().naive_utc();
+        DbUtxoDetail {
+            id: None,
+            wallet_id: "wallet".to_string(),
+            network_type: current_network_type(),
+            account_index: 0,
+            utxo_data,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_seed_salt_and_nonce_come_from_entropy_source() {
+        use crate::security::entropy::testing::CountingEntropy;
+
+        let password = secrecy::SecretString::new("correct horse".to_string());
+        let source = CountingEntropy::starting_at(100);
+        let (data, salt, nonce) =
+            DbOrderWallet::encrypt_seed_with("seed words", &password, &source).unwrap();
+        assert_eq!(source.calls(), 2);
+        assert_eq!(salt[0], 100);
+        assert_eq!(nonce[0], 132);
+        let seed = DbOrderWallet::decrypt_seed_internal(&data, &salt, &nonce, &password).unwrap();
+        assert_eq!(seed, "seed words");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_to_utxo_detail_never_panics(value in arbitrary_json(), cut in any::<usize>()) {
+            let json = value.to_string();
+            let truncated = json.get(..cut % (json.len() + 1)).unwrap_or("").to_string();
+            let _ = stored_utxo(json).to_utxo_detail();
+            let _ = stored_utxo(truncated).to_utxo_detail();
         }
     }
 }
@@ -1,15 +1,19 @@
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use crate::database::{
+    account_secrets::{AccountSecretsCipher, SealedSecrets},
     models::{
         DbBtcDeposit, DbBtcTransfer, DbBtcWithdrawal, DbOrderWallet, DbRequestId, DbUtxoDetail,
-        DbZkAccount, EncryptedWallet, NewDbBtcTransfer, NewEncryptedWallet,
+        DbZkAccount, EncryptedWallet, NewDbArchivedZkAccount, NewDbBtcTransfer,
+        NewEncryptedWallet, encode_last_error,
     },
     schema::{
-        btc_deposits, btc_transfers, btc_withdrawals, encrypted_wallets, order_wallets,
-        request_ids, utxo_details, zk_accounts,
+        archived_zk_accounts, btc_deposits, btc_transfers, btc_withdrawals, encrypted_wallets,
+        order_wallets, request_ids, utxo_details, zk_accounts,
     },
 };
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::security::entropy::{self, EntropySource};
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use crate::security::SecurePassword;
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use crate::wallet::Wallet;
@@ -17,26 +21,28 @@ use crate::wallet::Wallet;
 use crate::zkos_accounts::zkaccount::ZkAccount;
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use aes_gcm::{
-    aead::{Aead, KeyInit, OsRng},
+    aead::{Aead, KeyInit},
     Aes256Gcm, Key, Nonce,
 };
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use diesel::prelude::*;
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-use rand_core::RngCore;
-#[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use secrecy::{ExposeSecret, SecretString};
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use serde::{Deserialize, Serialize};
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use crate::database::connection::{get_conn, DbPool};
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use crate::log_privacy::LoggedAddress;
 use log::debug;
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -50,6 +56,10 @@ pub struct DatabaseManager {
     wallet_id: String,
     #[serde(skip)]
     pool: Arc<DbPool>,
+    /// Key for the zk account secrets, set by [`Self::unlock_zk_accounts`]
+    /// and shared by clones.
+    #[serde(skip)]
+    account_cipher: Arc<RwLock<Option<AccountSecretsCipher>>>,
 }
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -65,6 +75,7 @@ impl DatabaseManager {
         Self {
             wallet_id,
             pool: Arc::new(pool),
+            account_cipher: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -76,8 +87,146 @@ impl DatabaseManager {
     }
 
     // ZkAccount operations
+
+    /// Derive the key for this wallet's zk account secrets from `password`
+    /// and keep it for the account reads and writes that follow. Rows still
+    /// in plaintext, and rows sealed under another salt (for example from an
+    /// imported backup), are re-encrypted under the key, as are the secrets of
+    /// pending operations saved in plaintext. A password that does not
+    /// decrypt the stored secrets fails and leaves the state unchanged.
+    ///
+    /// The key stays with the manager, and its clones, while the wallet is
+    /// locked, so accounts are still saved then.
+    pub fn unlock_zk_accounts(&self, password: &SecretString) -> Result<(), String> {
+        let rows = self.load_db_zk_accounts()?;
+        let cipher = match rows.iter().find_map(|row| row.sealed_secrets()) {
+            Some((_, salt)) => AccountSecretsCipher::derive(password, salt)?,
+            None => AccountSecretsCipher::generate(password, &*entropy::default_source())?,
+        };
+
+        // Decrypt and re-seal everything before the first write, so a wrong
+        // password fails without touching a row.
+        let wrong_password = |_: String| {
+            format!(
+                "Wrong password for the zk accounts of wallet {}",
+                self.wallet_id
+            )
+        };
+        let source = entropy::default_source();
+        let mut keys: HashMap<Vec<u8>, AccountSecretsCipher> = HashMap::new();
+        let mut migrate = Vec::new();
+        for row in &rows {
+            let index = row.account_index as u64;
+            let (scalar, account) = match row.sealed_secrets() {
+                None => (row.scalar.clone(), row.account.clone()),
+                Some((sealed, salt)) if salt == cipher.salt() => {
+                    cipher.open(index, &sealed).map_err(wrong_password)?;
+                    continue;
+                }
+                Some((sealed, salt)) => {
+                    if !keys.contains_key(salt) {
+                        let key = AccountSecretsCipher::derive(password, salt)?;
+                        keys.insert(salt.to_vec(), key);
+                    }
+                    keys[salt].open(index, &sealed).map_err(wrong_password)?
+                }
+            };
+            migrate.push((index, cipher.seal(index, &scalar, &account, &*source)?));
+        }
+
+        self.write_sealed_secrets(&cipher, &migrate)?;
+        if !migrate.is_empty() {
+            debug!("Encrypted the secrets of {} zk_accounts", migrate.len());
+        }
+        self.reseal_pending_operations(None, &cipher)?;
+        *self
+            .account_cipher
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(cipher);
+        Ok(())
+    }
+
+    /// Re-encrypt every zk account's and pending operation's secrets under a
+    /// key derived from `new_password` with a fresh salt, and keep that key,
+    /// for a password change. Needs the current key from
+    /// [`Self::unlock_zk_accounts`].
+    pub fn rekey_zk_accounts(&self, new_password: &SecretString) -> Result<(), String> {
+        let current = self.account_cipher()?;
+        let source = entropy::default_source();
+        let cipher = AccountSecretsCipher::generate(new_password, &*source)?;
+        let mut resealed = Vec::new();
+        for row in self.load_db_zk_accounts()? {
+            let index = row.account_index as u64;
+            let (scalar, account) = match row.sealed_secrets() {
+                Some((sealed, _)) => current.open(index, &sealed)?,
+                None => (row.scalar.clone(), row.account.clone()),
+            };
+            resealed.push((index, cipher.seal(index, &scalar, &account, &*source)?));
+        }
+        self.write_sealed_secrets(&cipher, &resealed)?;
+        self.reseal_pending_operations(Some(&current), &cipher)?;
+        *self
+            .account_cipher
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(cipher);
+        Ok(())
+    }
+
+    /// Store the sealed secrets of each `(index, sealed)` account, under the
+    /// salt of `cipher`, in one transaction.
+    fn write_sealed_secrets(
+        &self,
+        cipher: &AccountSecretsCipher,
+        sealed_accounts: &[(u64, SealedSecrets)],
+    ) -> Result<(), String> {
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            for (index, sealed) in sealed_accounts {
+                diesel::update(
+                    zk_accounts::table.filter(
+                        zk_accounts::wallet_id
+                            .eq(&self.wallet_id)
+                            .and(zk_accounts::network_type.eq(&net))
+                            .and(zk_accounts::account_index.eq(*index as i64)),
+                    ),
+                )
+                .set((
+                    zk_accounts::scalar.eq(""),
+                    zk_accounts::account.eq(""),
+                    zk_accounts::secrets_encrypted.eq(Some(sealed.ciphertext.clone())),
+                    zk_accounts::secrets_salt.eq(Some(cipher.salt().to_vec())),
+                    zk_accounts::secrets_nonce.eq(Some(sealed.nonce.clone())),
+                ))
+                .execute(conn)?;
+            }
+            Ok(())
+        })
+        .map_err(|e| format!("Failed to encrypt zk_accounts: {}", e))
+    }
+
+    /// The key set by [`Self::unlock_zk_accounts`].
+    fn account_cipher(&self) -> Result<AccountSecretsCipher, String> {
+        self.account_cipher
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| {
+                "zk account secrets are locked; call unlock_zk_accounts with the wallet password first"
+                    .to_string()
+            })
+    }
+
+    /// Upsert the account, its secrets sealed with the key set by
+    /// [`Self::unlock_zk_accounts`].
     pub fn save_zk_account(&self, zk_account: &ZkAccount) -> Result<(), String> {
-        let new_account = DbZkAccount::from_zk_account(zk_account, self.wallet_id.clone());
+        let cipher = self.account_cipher()?;
+        let new_account = DbZkAccount::from_zk_account_sealed(
+            zk_account,
+            self.wallet_id.clone(),
+            &cipher,
+            &*entropy::default_source(),
+        )?;
         let mut conn = get_conn(self.pool())?;
         let n = diesel::insert_into(zk_accounts::table)
             .values(&new_account)
@@ -89,9 +238,15 @@ impl DatabaseManager {
                 zk_accounts::on_chain.eq(new_account.on_chain),
                 zk_accounts::tx_type.eq(new_account.tx_type.clone()),
                 zk_accounts::updated_at.eq(new_account.updated_at),
-                zk_accounts::scalar.eq(zk_account.scalar.clone()),
-                zk_accounts::account.eq(zk_account.account.clone()),
+                zk_accounts::scalar.eq(new_account.scalar.clone()),
+                zk_accounts::account.eq(new_account.account.clone()),
                 zk_accounts::qq_address.eq(zk_account.qq_address.clone()),
+                zk_accounts::last_error.eq(new_account.last_error.clone()),
+                zk_accounts::balance_unverified.eq(new_account.balance_unverified),
+                zk_accounts::label.eq(new_account.label.clone()),
+                zk_accounts::secrets_encrypted.eq(new_account.secrets_encrypted.clone()),
+                zk_accounts::secrets_salt.eq(new_account.secrets_salt.clone()),
+                zk_accounts::secrets_nonce.eq(new_account.secrets_nonce.clone()),
             ))
             .execute(&mut conn)
             .map_err(|e| format!("Failed to save zk_account: {}", e))?;
@@ -103,6 +258,13 @@ impl DatabaseManager {
     }
 
     pub fn update_zk_account(&self, zk_account: &ZkAccount) -> Result<(), String> {
+        let cipher = self.account_cipher()?;
+        let sealed = cipher.seal(
+            zk_account.index,
+            &zk_account.scalar,
+            &zk_account.account,
+            &*entropy::default_source(),
+        )?;
         let now = chrono::Utc::now().naive_utc();
         let net = current_network_type();
         let mut conn = get_conn(self.pool())?;
@@ -120,9 +282,15 @@ impl DatabaseManager {
             zk_accounts::on_chain.eq(zk_account.on_chain),
             zk_accounts::tx_type.eq(zk_account.tx_type.as_ref().map(|t| format!("{:?}", t))),
             zk_accounts::updated_at.eq(now),
-            zk_accounts::scalar.eq(zk_account.scalar.clone()),
-            zk_accounts::account.eq(zk_account.account.clone()),
+            zk_accounts::scalar.eq(""),
+            zk_accounts::account.eq(""),
             zk_accounts::qq_address.eq(zk_account.qq_address.clone()),
+            zk_accounts::last_error.eq(encode_last_error(zk_account)),
+            zk_accounts::balance_unverified.eq(zk_account.balance_unverified),
+            zk_accounts::label.eq(zk_account.label.clone()),
+            zk_accounts::secrets_encrypted.eq(Some(sealed.ciphertext)),
+            zk_accounts::secrets_salt.eq(Some(cipher.salt().to_vec())),
+            zk_accounts::secrets_nonce.eq(Some(sealed.nonce)),
         ))
         .execute(&mut conn)
         .map_err(|e| format!("Failed to update zk_account: {}", e))?;
@@ -153,22 +321,104 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Load the active (not archived) zk accounts.
     pub fn load_all_zk_accounts(&self) -> Result<HashMap<u64, ZkAccount>, String> {
+        self.load_zk_accounts_where(false)
+    }
+
+    /// Load the accounts moved out of the active set by [`Self::archive_zk_account`].
+    pub fn load_archived_zk_accounts(&self) -> Result<HashMap<u64, ZkAccount>, String> {
+        self.load_zk_accounts_where(true)
+    }
+
+    /// Sealed rows need the key set by [`Self::unlock_zk_accounts`].
+    fn load_zk_accounts_where(&self, archived: bool) -> Result<HashMap<u64, ZkAccount>, String> {
+        let archived_indices = self.load_archived_account_indices()?;
+        let cipher = self.account_cipher().ok();
+        let mut accounts = HashMap::new();
+        for db_account in self.load_db_zk_accounts()? {
+            if archived_indices.contains(&db_account.account_index) == archived {
+                let zk_account = db_account.to_zk_account(cipher.as_ref())?;
+                accounts.insert(zk_account.index, zk_account);
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn load_db_zk_accounts(&self) -> Result<Vec<DbZkAccount>, String> {
         let net = current_network_type();
         let mut conn = get_conn(self.pool())?;
-        let db_accounts: Vec<DbZkAccount> = zk_accounts::table
+        zk_accounts::table
             .filter(zk_accounts::wallet_id.eq(&self.wallet_id))
             .filter(zk_accounts::network_type.eq(&net))
             .load(&mut conn)
-            .map_err(|e| format!("Failed to load zk_accounts: {}", e))?;
+            .map_err(|e| format!("Failed to load zk_accounts: {}", e))
+    }
 
-        let mut accounts = HashMap::new();
-        for db_account in db_accounts {
-            let zk_account = db_account.to_zk_account()?;
-            accounts.insert(zk_account.index, zk_account);
-        }
+    fn load_archived_account_indices(&self) -> Result<HashSet<i64>, String> {
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let indices: Vec<i64> = archived_zk_accounts::table
+            .filter(archived_zk_accounts::wallet_id.eq(&self.wallet_id))
+            .filter(archived_zk_accounts::network_type.eq(&net))
+            .select(archived_zk_accounts::account_index)
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load archived zk_accounts: {}", e))?;
+        Ok(indices.into_iter().collect())
+    }
 
-        Ok(accounts)
+    /// Last time each active account's row was written.
+    pub fn load_zk_account_update_times(&self) -> Result<HashMap<u64, DateTime<Utc>>, String> {
+        let archived_indices = self.load_archived_account_indices()?;
+        Ok(self
+            .load_db_zk_accounts()?
+            .into_iter()
+            .filter(|a| !archived_indices.contains(&a.account_index))
+            .map(|a| (a.account_index as u64, a.updated_at.and_utc()))
+            .collect())
+    }
+
+    /// Save the account's final state and mark it archived. The row itself is
+    /// kept so history referencing the index still resolves, and still counts
+    /// towards [`Self::get_max_account_index`].
+    pub fn archive_zk_account(
+        &self,
+        zk_account: &ZkAccount,
+        archived_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        self.save_zk_account(zk_account)?;
+        let marker = NewDbArchivedZkAccount::new(
+            self.wallet_id.clone(),
+            zk_account.index,
+            archived_at.naive_utc(),
+        );
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(archived_zk_accounts::table)
+            .values(&marker)
+            .on_conflict((
+                archived_zk_accounts::wallet_id,
+                archived_zk_accounts::network_type,
+                archived_zk_accounts::account_index,
+            ))
+            .do_nothing()
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to archive zk_account: {}", e))?;
+        Ok(())
+    }
+
+    /// Return an archived account to the active set.
+    pub fn unarchive_zk_account(&self, account_index: u64) -> Result<(), String> {
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        diesel::delete(
+            archived_zk_accounts::table
+                .filter(archived_zk_accounts::wallet_id.eq(&self.wallet_id))
+                .filter(archived_zk_accounts::network_type.eq(&net))
+                .filter(archived_zk_accounts::account_index.eq(account_index as i64)),
+        )
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to unarchive zk_account: {}", e))?;
+        Ok(())
     }
     pub fn get_max_account_index(&self) -> Result<u64, String> {
         let net = current_network_type();
@@ -212,6 +462,124 @@ impl DatabaseManager {
         Ok(exists.is_some())
     }
 
+    /// Delete `wallet_id` and every row stored under it, on all networks:
+    /// the encrypted wallet, order wallet, ZkOS accounts, UTXO details,
+    /// request IDs, order records, account pools, conditional triggers,
+    /// order snapshots, history and audit entries. `password` must decrypt the stored wallet,
+    /// so an entry cannot be deleted without its passphrase.
+    ///
+    /// An `OrderWallet` still open on this entry writes it back when saved or
+    /// dropped; close it first.
+    pub fn delete_wallet(
+        pool: &DbPool,
+        wallet_id: &str,
+        password: &SecretString,
+    ) -> Result<(), String> {
+        let manager = DatabaseManager::new(wallet_id.to_string(), pool.clone());
+        manager
+            .load_encrypted_wallet(password)
+            .map_err(|e| format!("Not deleting wallet {}: {}", wallet_id, e))?;
+
+        let mut conn = get_conn(pool)?;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            use crate::database::schema::{
+                account_pool_members, activity_buckets, address_book, conditional_triggers,
+                order_history, order_records, order_snapshots, pending_operations,
+                pending_submissions, signing_audit, transfer_history,
+            };
+            macro_rules! delete_from {
+                ($($table:ident),+ $(,)?) => {
+                    $(
+                        diesel::delete($table::table.filter($table::wallet_id.eq(wallet_id)))
+                            .execute(conn)?;
+                    )+
+                };
+            }
+            delete_from!(
+                zk_accounts,
+                archived_zk_accounts,
+                utxo_details,
+                request_ids,
+                order_records,
+                pending_operations,
+                pending_submissions,
+                account_pool_members,
+                conditional_triggers,
+                order_snapshots,
+                address_book,
+                order_history,
+                transfer_history,
+                btc_deposits,
+                btc_withdrawals,
+                btc_transfers,
+                signing_audit,
+                activity_buckets,
+                order_wallets,
+                encrypted_wallets,
+            );
+            Ok(())
+        })
+        .map_err(|e| format!("Failed to delete wallet {}: {}", wallet_id, e))?;
+        debug!("Deleted wallet_id: {}", LoggedAddress(wallet_id));
+        Ok(())
+    }
+
+    /// Move every row stored under `old_id` to `new_id`. Fails if `old_id`
+    /// has no stored wallet or `new_id` already has one.
+    pub fn rename_wallet(pool: &DbPool, old_id: &str, new_id: &str) -> Result<(), String> {
+        if !Self::check_wallet_id_exists(pool, old_id)? {
+            return Err(format!(
+                "No encrypted wallet found for wallet_id: {}",
+                old_id
+            ));
+        }
+        if Self::check_wallet_id_exists(pool, new_id)? {
+            return Err(format!("Wallet ID already exists: {}", new_id));
+        }
+
+        let mut conn = get_conn(pool)?;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            use crate::database::schema::{
+                account_pool_members, activity_buckets, address_book, conditional_triggers,
+                order_history, order_records, order_snapshots, pending_operations,
+                pending_submissions, signing_audit, transfer_history,
+            };
+            macro_rules! rename_in {
+                ($($table:ident),+ $(,)?) => {
+                    $(
+                        diesel::update($table::table.filter($table::wallet_id.eq(old_id)))
+                            .set($table::wallet_id.eq(new_id))
+                            .execute(conn)?;
+                    )+
+                };
+            }
+            rename_in!(
+                encrypted_wallets,
+                order_wallets,
+                zk_accounts,
+                archived_zk_accounts,
+                utxo_details,
+                request_ids,
+                order_records,
+                pending_operations,
+                pending_submissions,
+                account_pool_members,
+                conditional_triggers,
+                order_snapshots,
+                address_book,
+                order_history,
+                transfer_history,
+                btc_deposits,
+                btc_withdrawals,
+                btc_transfers,
+                signing_audit,
+                activity_buckets,
+            );
+            Ok(())
+        })
+        .map_err(|e| format!("Failed to rename wallet {} to {}: {}", old_id, new_id, e))
+    }
+
     // Wallet encryption operations
     pub fn save_encrypted_wallet(
         &self,
@@ -243,7 +611,11 @@ impl DatabaseManager {
             ))
             .execute(&mut conn)
             .map_err(|e| format!("Failed to save encrypted wallet: {}", e))?;
-        debug!("The upserted row: {} for wallet_id: {}", n, self.wallet_id);
+        debug!(
+            "The upserted row: {} for wallet_id: {}",
+            n,
+            LoggedAddress(&self.wallet_id)
+        );
         Ok(())
     }
 
@@ -278,6 +650,7 @@ impl DatabaseManager {
         chain_id: &str,
         seed: &str,
         relayer_config: &crate::config::RelayerEndPointConfig,
+        fingerprint: &crate::config::ConfigFingerprint,
         password: &str,
     ) -> Result<(), String> {
         let new_order_wallet = DbOrderWallet::new_from_order_wallet(
@@ -285,6 +658,7 @@ impl DatabaseManager {
             chain_id.to_string(),
             seed,
             relayer_config,
+            fingerprint,
             password,
         )?;
         let mut conn = get_conn(self.pool())?;
@@ -302,10 +676,15 @@ impl DatabaseManager {
                 order_wallets::relayer_program_json_path
                     .eq(&new_order_wallet.relayer_program_json_path),
                 order_wallets::updated_at.eq(new_order_wallet.updated_at),
+                order_wallets::config_fingerprint.eq(&new_order_wallet.config_fingerprint),
             ))
             .execute(&mut conn)
             .map_err(|e| format!("Failed to save order wallet: {}", e))?;
-        debug!("The upserted row: {} for wallet_id: {}", n, self.wallet_id);
+        debug!(
+            "The upserted row: {} for wallet_id: {}",
+            n,
+            LoggedAddress(&self.wallet_id)
+        );
         Ok(())
     }
 
@@ -333,6 +712,27 @@ impl DatabaseManager {
         }
     }
 
+    /// Configuration fingerprint stored with the active order wallet. `None`
+    /// when there is no row or it predates fingerprints.
+    pub fn load_config_fingerprint(
+        &self,
+    ) -> Result<Option<crate::config::ConfigFingerprint>, String> {
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let fingerprint: Option<Option<String>> = order_wallets::table
+            .filter(order_wallets::wallet_id.eq(&self.wallet_id))
+            .filter(order_wallets::network_type.eq(&net))
+            .filter(order_wallets::is_active.eq(true))
+            .select(order_wallets::config_fingerprint)
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| format!("Failed to load config fingerprint: {}", e))?;
+        fingerprint
+            .flatten()
+            .map(|json| crate::config::ConfigFingerprint::from_json(&json))
+            .transpose()
+    }
+
     pub fn deactivate_order_wallet(&self) -> Result<(), String> {
         let net = current_network_type();
         let mut conn = get_conn(self.pool())?;
@@ -355,7 +755,7 @@ impl DatabaseManager {
     pub fn save_utxo_detail(
         &self,
         account_index: u64,
-        utxo_detail: &twilight_client_sdk::relayer_rpcclient::method::UtxoDetailResponse,
+        utxo_detail: &crate::compat::relayer_rpcclient::method::UtxoDetailResponse,
     ) -> Result<(), String> {
         let new_utxo_detail =
             DbUtxoDetail::from_utxo_detail(self.wallet_id.clone(), account_index, utxo_detail)?;
@@ -380,7 +780,7 @@ impl DatabaseManager {
     pub fn load_utxo_detail(
         &self,
         account_index: u64,
-    ) -> Result<Option<twilight_client_sdk::relayer_rpcclient::method::UtxoDetailResponse>, String>
+    ) -> Result<Option<crate::compat::relayer_rpcclient::method::UtxoDetailResponse>, String>
     {
         let net = current_network_type();
         let mut conn = get_conn(self.pool())?;
@@ -401,7 +801,7 @@ impl DatabaseManager {
     pub fn load_all_utxo_details(
         &self,
     ) -> Result<
-        HashMap<u64, twilight_client_sdk::relayer_rpcclient::method::UtxoDetailResponse>,
+        HashMap<u64, crate::compat::relayer_rpcclient::method::UtxoDetailResponse>,
         String,
     > {
         let net = current_network_type();
@@ -442,11 +842,13 @@ impl DatabaseManager {
     }
 
     // Request ID operations
+    /// Upsert the request id of `account_index`, keeping a stored idempotency key.
     pub fn save_request_id(&self, account_index: u64, request_id: &str) -> Result<(), String> {
         let new_request_id = DbRequestId::new(
             self.wallet_id.clone(),
             account_index,
             request_id.to_string(),
+            None,
         );
         let mut conn = get_conn(self.pool())?;
         let n = diesel::insert_into(request_ids::table)
@@ -466,6 +868,56 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Upsert the request id of `account_index` together with the idempotency
+    /// key it was submitted with (`None` clears a stored key).
+    pub fn save_request_id_with_key(
+        &self,
+        account_index: u64,
+        request_id: &str,
+        idempotency_key: Option<&str>,
+    ) -> Result<(), String> {
+        let new_request_id = DbRequestId::new(
+            self.wallet_id.clone(),
+            account_index,
+            request_id.to_string(),
+            idempotency_key.map(str::to_string),
+        );
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(request_ids::table)
+            .values(&new_request_id)
+            .on_conflict((
+                request_ids::wallet_id,
+                request_ids::network_type,
+                request_ids::account_index,
+            ))
+            .do_update()
+            .set((
+                request_ids::request_id.eq(&new_request_id.request_id),
+                request_ids::idempotency_key.eq(&new_request_id.idempotency_key),
+                request_ids::updated_at.eq(new_request_id.updated_at),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save request ID: {}", e))?;
+        Ok(())
+    }
+
+    /// Idempotency keys stored with the request ids, by account.
+    pub fn load_idempotency_keys(&self) -> Result<HashMap<u64, String>, String> {
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let db_request_ids: Vec<DbRequestId> = request_ids::table
+            .filter(request_ids::wallet_id.eq(&self.wallet_id))
+            .filter(request_ids::network_type.eq(&net))
+            .filter(request_ids::idempotency_key.is_not_null())
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load idempotency keys: {}", e))?;
+
+        Ok(db_request_ids
+            .into_iter()
+            .filter_map(|r| Some((r.account_index as u64, r.idempotency_key?)))
+            .collect())
+    }
+
     pub fn load_request_id(&self, account_index: u64) -> Result<Option<String>, String> {
         let net = current_network_type();
         let mut conn = get_conn(self.pool())?;
@@ -517,6 +969,412 @@ impl DatabaseManager {
         Ok(())
     }
 
+    // -------------------------
+    // Order record operations
+    // -------------------------
+
+    /// Upsert the `seq`-th order record of `account_index` as of
+    /// `updated_at`; an existing row only has its status and `updated_at`
+    /// updated.
+    pub fn save_order_record(
+        &self,
+        account_index: u64,
+        seq: usize,
+        record: &crate::relayer_module::transaction_history::OrderRecord,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        use crate::database::{models::NewDbOrderRecord, schema::order_records};
+        let row = NewDbOrderRecord::new(
+            self.wallet_id.clone(),
+            account_index,
+            seq,
+            record,
+            updated_at,
+        );
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(order_records::table)
+            .values(&row)
+            .on_conflict((
+                order_records::wallet_id,
+                order_records::network_type,
+                order_records::account_index,
+                order_records::seq,
+            ))
+            .do_update()
+            .set((
+                order_records::status.eq(&row.status),
+                order_records::updated_at.eq(row.updated_at),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save order record: {}", e))?;
+        Ok(())
+    }
+
+    /// Every stored order record by account, oldest first.
+    pub fn load_order_records(
+        &self,
+    ) -> Result<HashMap<u64, Vec<crate::relayer_module::transaction_history::OrderRecord>>, String>
+    {
+        use crate::database::{models::DbOrderRecord, schema::order_records};
+        use crate::relayer_module::transaction_history::OrderRecord;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows: Vec<DbOrderRecord> = order_records::table
+            .filter(order_records::wallet_id.eq(&self.wallet_id))
+            .filter(order_records::network_type.eq(&net))
+            .order((order_records::account_index.asc(), order_records::seq.asc()))
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load order records: {}", e))?;
+
+        let mut records: HashMap<u64, Vec<OrderRecord>> = HashMap::new();
+        for row in &rows {
+            records
+                .entry(row.account_index as u64)
+                .or_default()
+                .push(OrderRecord::from_db(row)?);
+        }
+        Ok(records)
+    }
+
+    // -------------------------
+    // Pending submission operations
+    // -------------------------
+
+    /// Insert or replace the pending submission of its account.
+    pub fn save_pending_submission(
+        &self,
+        submission: &crate::relayer_module::idempotency::PendingSubmission,
+    ) -> Result<(), String> {
+        use crate::database::{models::NewDbPendingSubmission, schema::pending_submissions};
+        let row = NewDbPendingSubmission::new(self.wallet_id.clone(), submission);
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(pending_submissions::table)
+            .values(&row)
+            .on_conflict((
+                pending_submissions::wallet_id,
+                pending_submissions::network_type,
+                pending_submissions::account_index,
+            ))
+            .do_update()
+            .set((
+                pending_submissions::idempotency_key.eq(&row.idempotency_key),
+                pending_submissions::kind.eq(&row.kind),
+                pending_submissions::account_address.eq(&row.account_address),
+                pending_submissions::created_at.eq(row.created_at),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save pending submission: {}", e))?;
+        Ok(())
+    }
+
+    /// Every stored pending submission by account.
+    pub fn load_pending_submissions(
+        &self,
+    ) -> Result<HashMap<u64, crate::relayer_module::idempotency::PendingSubmission>, String> {
+        use crate::database::{models::DbPendingSubmission, schema::pending_submissions};
+        use crate::relayer_module::idempotency::PendingSubmission;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows: Vec<DbPendingSubmission> = pending_submissions::table
+            .filter(pending_submissions::wallet_id.eq(&self.wallet_id))
+            .filter(pending_submissions::network_type.eq(&net))
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load pending submissions: {}", e))?;
+
+        rows.iter()
+            .map(|row| Ok((row.account_index as u64, PendingSubmission::from_db(row)?)))
+            .collect()
+    }
+
+    pub fn remove_pending_submission(&self, account_index: u64) -> Result<(), String> {
+        use crate::database::schema::pending_submissions;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        diesel::delete(
+            pending_submissions::table.filter(
+                pending_submissions::wallet_id
+                    .eq(&self.wallet_id)
+                    .and(pending_submissions::network_type.eq(&net))
+                    .and(pending_submissions::account_index.eq(account_index as i64)),
+            ),
+        )
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to remove pending submission: {}", e))?;
+        Ok(())
+    }
+
+    // -------------------------
+    // Account pool operations
+    // -------------------------
+
+    /// Insert or update the membership of `account_index` in pool `pool_name`,
+    /// with the request ID of the order it carries.
+    pub fn save_pool_member(
+        &self,
+        pool_name: &str,
+        account_index: u64,
+        state: &str,
+        request_id: Option<&str>,
+    ) -> Result<(), String> {
+        use crate::database::{models::NewDbAccountPoolMember, schema::account_pool_members};
+        let row = NewDbAccountPoolMember::new(
+            self.wallet_id.clone(),
+            pool_name,
+            account_index,
+            state,
+            request_id,
+        );
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(account_pool_members::table)
+            .values(&row)
+            .on_conflict((
+                account_pool_members::wallet_id,
+                account_pool_members::network_type,
+                account_pool_members::pool_name,
+                account_pool_members::account_index,
+            ))
+            .do_update()
+            .set((
+                account_pool_members::state.eq(&row.state),
+                account_pool_members::updated_at.eq(row.updated_at),
+                account_pool_members::request_id.eq(&row.request_id),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save pool member: {}", e))?;
+        Ok(())
+    }
+
+    /// Members of pool `pool_name` with their stored state and request ID, by
+    /// account index.
+    pub fn load_pool_members(
+        &self,
+        pool_name: &str,
+    ) -> Result<Vec<(u64, String, Option<String>)>, String> {
+        use crate::database::{models::DbAccountPoolMember, schema::account_pool_members};
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows: Vec<DbAccountPoolMember> = account_pool_members::table
+            .filter(account_pool_members::wallet_id.eq(&self.wallet_id))
+            .filter(account_pool_members::network_type.eq(&net))
+            .filter(account_pool_members::pool_name.eq(pool_name))
+            .order(account_pool_members::account_index.asc())
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load pool members: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.account_index as u64, row.state, row.request_id))
+            .collect())
+    }
+
+    pub fn remove_pool_member(&self, pool_name: &str, account_index: u64) -> Result<(), String> {
+        use crate::database::schema::account_pool_members;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        diesel::delete(
+            account_pool_members::table.filter(
+                account_pool_members::wallet_id
+                    .eq(&self.wallet_id)
+                    .and(account_pool_members::network_type.eq(&net))
+                    .and(account_pool_members::pool_name.eq(pool_name))
+                    .and(account_pool_members::account_index.eq(account_index as i64)),
+            ),
+        )
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to remove pool member: {}", e))?;
+        Ok(())
+    }
+
+    // -------------------------
+    // Conditional trigger operations
+    // -------------------------
+
+    /// Insert or update the trigger of its account and kind.
+    pub fn save_conditional_trigger(
+        &self,
+        trigger: &crate::relayer_module::conditional_orders::ConditionalTrigger,
+    ) -> Result<(), String> {
+        use crate::database::{models::NewDbConditionalTrigger, schema::conditional_triggers};
+        let row = NewDbConditionalTrigger::from_trigger(self.wallet_id.clone(), trigger)?;
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(conditional_triggers::table)
+            .values(&row)
+            .on_conflict((
+                conditional_triggers::wallet_id,
+                conditional_triggers::network_type,
+                conditional_triggers::account_index,
+                conditional_triggers::kind,
+            ))
+            .do_update()
+            .set((
+                conditional_triggers::payload.eq(&row.payload),
+                conditional_triggers::updated_at.eq(row.updated_at),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save conditional trigger: {}", e))?;
+        Ok(())
+    }
+
+    /// This wallet's conditional triggers, by account index.
+    pub fn load_conditional_triggers(
+        &self,
+    ) -> Result<Vec<crate::relayer_module::conditional_orders::ConditionalTrigger>, String> {
+        use crate::database::{models::DbConditionalTrigger, schema::conditional_triggers};
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows: Vec<DbConditionalTrigger> = conditional_triggers::table
+            .filter(conditional_triggers::wallet_id.eq(&self.wallet_id))
+            .filter(conditional_triggers::network_type.eq(&net))
+            .order(conditional_triggers::account_index.asc())
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load conditional triggers: {}", e))?;
+        rows.iter().map(|r| r.to_trigger()).collect()
+    }
+
+    pub fn remove_conditional_trigger(&self, account_index: u64, kind: &str) -> Result<(), String> {
+        use crate::database::schema::conditional_triggers;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        diesel::delete(
+            conditional_triggers::table.filter(
+                conditional_triggers::wallet_id
+                    .eq(&self.wallet_id)
+                    .and(conditional_triggers::network_type.eq(&net))
+                    .and(conditional_triggers::account_index.eq(account_index as i64))
+                    .and(conditional_triggers::kind.eq(kind)),
+            ),
+        )
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to remove conditional trigger: {}", e))?;
+        Ok(())
+    }
+
+    // -------------------------
+    // Order snapshot operations
+    // -------------------------
+
+    /// Insert the snapshot of its request, or replace the stored one.
+    pub fn save_order_snapshot(
+        &self,
+        snapshot: &crate::relayer_module::transaction_history::OrderSnapshot,
+    ) -> Result<(), String> {
+        use crate::database::{models::NewDbOrderSnapshot, schema::order_snapshots};
+        let row = NewDbOrderSnapshot::new(self.wallet_id.clone(), snapshot);
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(order_snapshots::table)
+            .values(&row)
+            .on_conflict((
+                order_snapshots::wallet_id,
+                order_snapshots::network_type,
+                order_snapshots::account_index,
+                order_snapshots::request_id,
+            ))
+            .do_update()
+            .set((
+                order_snapshots::kind.eq(&row.kind),
+                order_snapshots::order_status.eq(&row.order_status),
+                order_snapshots::position_type.eq(&row.position_type),
+                order_snapshots::entry_price.eq(row.entry_price),
+                order_snapshots::exit_price.eq(row.exit_price),
+                order_snapshots::leverage.eq(row.leverage),
+                order_snapshots::initial_margin.eq(row.initial_margin),
+                order_snapshots::settled_margin.eq(row.settled_margin),
+                order_snapshots::realized_pnl.eq(row.realized_pnl),
+                order_snapshots::fees.eq(row.fees),
+                order_snapshots::opened_at.eq(row.opened_at),
+                order_snapshots::recorded_at.eq(row.recorded_at),
+                order_snapshots::updated_at.eq(row.updated_at),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save order snapshot: {}", e))?;
+        Ok(())
+    }
+
+    /// Every order snapshot of wallet `wallet_id` on the current network,
+    /// oldest first. Any wallet in the manager's database can be read.
+    pub fn load_order_snapshots(
+        &self,
+        wallet_id: &str,
+    ) -> Result<Vec<crate::relayer_module::transaction_history::OrderSnapshot>, String> {
+        use crate::database::{models::DbOrderSnapshot, schema::order_snapshots};
+        use crate::relayer_module::transaction_history::OrderSnapshot;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows: Vec<DbOrderSnapshot> = order_snapshots::table
+            .filter(order_snapshots::wallet_id.eq(wallet_id))
+            .filter(order_snapshots::network_type.eq(&net))
+            .order((
+                order_snapshots::recorded_at.asc(),
+                order_snapshots::id.asc(),
+            ))
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load order snapshots: {}", e))?;
+        rows.iter().map(OrderSnapshot::from_db).collect()
+    }
+
+    // -------------------------
+    // Address book operations
+    // -------------------------
+
+    /// Insert the entry of its label, or replace the stored one.
+    pub fn save_address_book_entry(
+        &self,
+        entry: &crate::relayer_module::address_book::AddressBookEntry,
+    ) -> Result<(), String> {
+        use crate::database::{models::NewDbAddressBookEntry, schema::address_book};
+        let row = NewDbAddressBookEntry::new(self.wallet_id.clone(), entry);
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(address_book::table)
+            .values(&row)
+            .on_conflict((
+                address_book::wallet_id,
+                address_book::network_type,
+                address_book::label,
+            ))
+            .do_update()
+            .set((
+                address_book::address.eq(&row.address),
+                address_book::kind.eq(&row.kind),
+                address_book::added_at.eq(row.added_at),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save address book entry: {}", e))?;
+        Ok(())
+    }
+
+    /// This wallet's address book entries, by label.
+    pub fn load_address_book(
+        &self,
+    ) -> Result<Vec<crate::relayer_module::address_book::AddressBookEntry>, String> {
+        use crate::database::{models::DbAddressBookEntry, schema::address_book};
+        use crate::relayer_module::address_book::AddressBookEntry;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows: Vec<DbAddressBookEntry> = address_book::table
+            .filter(address_book::wallet_id.eq(&self.wallet_id))
+            .filter(address_book::network_type.eq(&net))
+            .order(address_book::label.asc())
+            .load(&mut conn)
+            .map_err(|e| format!("Failed to load address book: {}", e))?;
+        rows.iter().map(AddressBookEntry::from_db).collect()
+    }
+
+    pub fn remove_address_book_entry(&self, label: &str) -> Result<(), String> {
+        use crate::database::schema::address_book;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        diesel::delete(
+            address_book::table.filter(
+                address_book::wallet_id
+                    .eq(&self.wallet_id)
+                    .and(address_book::network_type.eq(&net))
+                    .and(address_book::label.eq(label)),
+            ),
+        )
+        .execute(&mut conn)
+        .map_err(|e| format!("Failed to remove address book entry: {}", e))?;
+        Ok(())
+    }
+
     // -------------------------
     // Order History operations
     // -------------------------
@@ -577,6 +1435,26 @@ impl DatabaseManager {
         Ok(rows)
     }
 
+    /// Order history rows created in `[from, to)`, oldest first.
+    pub fn load_order_history_between(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Result<Vec<crate::database::models::DbOrderHistory>, String> {
+        use crate::database::schema::order_history;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows = order_history::table
+            .filter(order_history::wallet_id.eq(&self.wallet_id))
+            .filter(order_history::network_type.eq(&net))
+            .filter(order_history::created_at.ge(from))
+            .filter(order_history::created_at.lt(to))
+            .order(order_history::created_at.asc())
+            .load::<crate::database::models::DbOrderHistory>(&mut conn)
+            .map_err(|e| format!("Failed to load order history: {}", e))?;
+        Ok(rows)
+    }
+
     // -------------------------
     // Transfer History operations
     // -------------------------
@@ -613,6 +1491,26 @@ impl DatabaseManager {
         Ok(rows)
     }
 
+    /// Transfer history rows created in `[from, to)`, oldest first.
+    pub fn load_transfer_history_between(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Result<Vec<crate::database::models::DbTransferHistory>, String> {
+        use crate::database::schema::transfer_history;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows = transfer_history::table
+            .filter(transfer_history::wallet_id.eq(&self.wallet_id))
+            .filter(transfer_history::network_type.eq(&net))
+            .filter(transfer_history::created_at.ge(from))
+            .filter(transfer_history::created_at.lt(to))
+            .order(transfer_history::created_at.asc())
+            .load::<crate::database::models::DbTransferHistory>(&mut conn)
+            .map_err(|e| format!("Failed to load transfer history: {}", e))?;
+        Ok(rows)
+    }
+
     // -------------------------
     // BTC Deposit operations
     // -------------------------
@@ -751,6 +1649,209 @@ impl DatabaseManager {
         Ok(rows)
     }
 
+    // -------------------------
+    // Pending operation records
+    // -------------------------
+
+    /// Upsert `op`. The zk secrets its steps hold are sealed with the key
+    /// set by [`Self::unlock_zk_accounts`]; such an operation is refused
+    /// while the secrets are locked.
+    pub fn save_pending_operation(
+        &self,
+        op: &crate::relayer_module::pending_operations::PendingOperation,
+    ) -> Result<(), String> {
+        let cipher = match op.carries_secrets() {
+            true => Some(self.account_cipher()?),
+            false => None,
+        };
+        self.save_pending_operation_with(op, cipher.as_ref())
+    }
+
+    fn save_pending_operation_with(
+        &self,
+        op: &crate::relayer_module::pending_operations::PendingOperation,
+        cipher: Option<&AccountSecretsCipher>,
+    ) -> Result<(), String> {
+        use crate::database::models::NewDbPendingOperation;
+        use crate::database::schema::pending_operations;
+        let row = NewDbPendingOperation::from_pending_operation(
+            self.wallet_id.clone(),
+            op,
+            cipher,
+            &*entropy::default_source(),
+        )?;
+        let mut conn = get_conn(self.pool())?;
+        let n = diesel::insert_into(pending_operations::table)
+            .values(&row)
+            .on_conflict(pending_operations::operation_id)
+            .do_update()
+            .set((
+                pending_operations::status.eq(&row.status),
+                pending_operations::payload.eq(&row.payload),
+                pending_operations::last_error.eq(&row.last_error),
+                pending_operations::updated_at.eq(row.updated_at),
+            ))
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save pending operation: {}", e))?;
+        debug!("The upserted row: {} for operation_id: {}", n, op.id);
+        Ok(())
+    }
+
+    /// Load pending-operation records for this wallet, optionally filtered by status
+    /// (`"pending"` / `"done"`), newest first. Records with sealed secrets
+    /// need the key set by [`Self::unlock_zk_accounts`].
+    pub fn load_pending_operations(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<crate::relayer_module::pending_operations::PendingOperation>, String> {
+        let cipher = self.account_cipher().ok();
+        self.load_db_pending_operations(status)?
+            .iter()
+            .map(|r| r.to_pending_operation(cipher.as_ref()))
+            .collect()
+    }
+
+    /// Seal the operations still holding plaintext secrets (`current` is
+    /// `None`), or re-seal every sealed one from `current` to `cipher`.
+    fn reseal_pending_operations(
+        &self,
+        current: Option<&AccountSecretsCipher>,
+        cipher: &AccountSecretsCipher,
+    ) -> Result<(), String> {
+        for row in self.load_db_pending_operations(None)? {
+            let op = match current {
+                None if row.has_plaintext_secrets()? => row.to_pending_operation(None)?,
+                None => continue,
+                Some(current) => {
+                    let op = row.to_pending_operation(Some(current))?;
+                    if !op.carries_secrets() {
+                        continue;
+                    }
+                    op
+                }
+            };
+            self.save_pending_operation_with(&op, Some(cipher))?;
+        }
+        Ok(())
+    }
+
+    fn load_db_pending_operations(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<crate::database::models::DbPendingOperation>, String> {
+        use crate::database::models::DbPendingOperation;
+        use crate::database::schema::pending_operations;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let base = pending_operations::table
+            .filter(pending_operations::wallet_id.eq(&self.wallet_id))
+            .filter(pending_operations::network_type.eq(&net));
+        let rows = match status {
+            Some(status) => base
+                .filter(pending_operations::status.eq(status))
+                .order(pending_operations::created_at.desc())
+                .load::<DbPendingOperation>(&mut conn),
+            None => base
+                .order(pending_operations::created_at.desc())
+                .load::<DbPendingOperation>(&mut conn),
+        }
+        .map_err(|e| format!("Failed to load pending operations: {}", e))?;
+        Ok(rows)
+    }
+
+    // ---- Signing audit operations ----
+
+    /// Append one signing audit entry. Entries are never updated.
+    pub fn save_signing_audit_entry(
+        &self,
+        entry: &crate::relayer_module::signing_audit::SigningAuditEntry,
+    ) -> Result<(), String> {
+        use crate::database::models::NewDbSigningAuditEntry;
+        use crate::database::schema::signing_audit;
+        let row = NewDbSigningAuditEntry::from_entry(self.wallet_id.clone(), entry);
+        let mut conn = get_conn(self.pool())?;
+        diesel::insert_into(signing_audit::table)
+            .values(&row)
+            .execute(&mut conn)
+            .map_err(|e| format!("Failed to save signing audit entry: {}", e))?;
+        Ok(())
+    }
+
+    /// Load this wallet's signing audit chain, oldest first.
+    pub fn load_signing_audit_entries(
+        &self,
+    ) -> Result<Vec<crate::relayer_module::signing_audit::SigningAuditEntry>, String> {
+        use crate::database::models::DbSigningAuditEntry;
+        use crate::database::schema::signing_audit;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows = signing_audit::table
+            .filter(signing_audit::wallet_id.eq(&self.wallet_id))
+            .filter(signing_audit::network_type.eq(&net))
+            .order(signing_audit::seq.asc())
+            .load::<DbSigningAuditEntry>(&mut conn)
+            .map_err(|e| format!("Failed to load signing audit entries: {}", e))?;
+        rows.iter().map(|r| r.to_entry()).collect()
+    }
+
+    // ---- Activity histogram operations ----
+
+    /// Upsert hourly activity counts and delete rows older than `cutoff`.
+    pub fn save_activity_counts(
+        &self,
+        counts: &[crate::relayer_module::activity::ActivityCount],
+        cutoff: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), String> {
+        use crate::database::models::NewDbActivityBucket;
+        use crate::database::schema::activity_buckets;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            for count in counts {
+                let row = NewDbActivityBucket::from_count(self.wallet_id.clone(), count);
+                diesel::insert_into(activity_buckets::table)
+                    .values(&row)
+                    .on_conflict((
+                        activity_buckets::wallet_id,
+                        activity_buckets::network_type,
+                        activity_buckets::hour,
+                        activity_buckets::category,
+                    ))
+                    .do_update()
+                    .set(activity_buckets::count.eq(row.count))
+                    .execute(conn)?;
+            }
+            diesel::delete(
+                activity_buckets::table
+                    .filter(activity_buckets::wallet_id.eq(&self.wallet_id))
+                    .filter(activity_buckets::network_type.eq(&net))
+                    .filter(activity_buckets::hour.lt(cutoff.naive_utc())),
+            )
+            .execute(conn)?;
+            Ok(())
+        })
+        .map_err(|e| format!("Failed to save activity counts: {}", e))
+    }
+
+    /// Load this wallet's hourly activity counts from `since` onwards.
+    pub fn load_activity_counts(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<crate::relayer_module::activity::ActivityCount>, String> {
+        use crate::database::models::DbActivityBucket;
+        use crate::database::schema::activity_buckets;
+        let net = current_network_type();
+        let mut conn = get_conn(self.pool())?;
+        let rows = activity_buckets::table
+            .filter(activity_buckets::wallet_id.eq(&self.wallet_id))
+            .filter(activity_buckets::network_type.eq(&net))
+            .filter(activity_buckets::hour.ge(since.naive_utc()))
+            .order(activity_buckets::hour.asc())
+            .load::<DbActivityBucket>(&mut conn)
+            .map_err(|e| format!("Failed to load activity counts: {}", e))?;
+        rows.iter().map(|r| r.to_count()).collect()
+    }
+
     // ---- BTC Transfer operations ----
 
     pub fn save_btc_transfer(&self, record: NewDbBtcTransfer) -> Result<(), String> {
@@ -809,16 +1910,32 @@ impl DatabaseManager {
     }
 }
 
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl crate::relayer_module::signing_audit::SigningAuditSink for DatabaseManager {
+    fn append(
+        &self,
+        entry: &crate::relayer_module::signing_audit::SigningAuditEntry,
+    ) -> Result<(), String> {
+        self.save_signing_audit_entry(entry)
+    }
+}
+
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 fn encrypt_wallet(
     wallet: &Wallet,
     password: &SecretString,
+) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), String> {
+    encrypt_wallet_with(wallet, password, &*entropy::default_source())
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+fn encrypt_wallet_with(
+    wallet: &Wallet,
+    password: &SecretString,
+    source: &dyn EntropySource,
 ) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), String> {
     // Generate salt and nonce
-    let mut salt = [0u8; 32];
-    let mut nonce_bytes = [0u8; 12];
-    OsRng.fill_bytes(&mut salt);
-    OsRng.fill_bytes(&mut nonce_bytes);
+    let (salt, nonce_bytes) = entropy::salt_and_nonce(source);
 
     // Derive key from password using PBKDF2
     let key_bytes = derive_key(password, &salt)?;
@@ -826,9 +1943,10 @@ fn encrypt_wallet(
     let cipher = Aes256Gcm::new(key);
     let nonce = Nonce::from_slice(&nonce_bytes);
 
-    // Serialize wallet
-    let wallet_json =
-        serde_json::to_vec(wallet).map_err(|e| format!("Failed to serialize wallet: {}", e))?;
+    // Serialize wallet, key material included
+    let wallet_json = wallet
+        .dangerous_serialize_with_secrets()
+        .map_err(|e| format!("Failed to serialize wallet: {}", e))?;
 
     // Encrypt
     let encrypted_data = cipher
@@ -865,3 +1983,457 @@ fn derive_key(password: &SecretString, salt: &[u8]) -> Result<[u8; 32], String>
     SecurePassword::derive_key_from_passphrase(password, salt)
         .map_err(|e| format!("Key derivation failed: {}", e))
 }
+
+#[cfg(all(test, any(feature = "sqlite", feature = "postgresql")))]
+mod tests {
+    use super::*;
+    use crate::security::entropy::testing::CountingEntropy;
+
+    #[test]
+    fn test_wallet_salt_and_nonce_come_from_entropy_source() {
+        let wallet = Wallet::from_mnemonic(
+            "test test test test test test test test test test test junk",
+            None,
+        )
+        .unwrap();
+        let password = SecretString::new("correct horse".to_string());
+
+        let source = CountingEntropy::starting_at(0);
+        let (data_a, salt, nonce) = encrypt_wallet_with(&wallet, &password, &source).unwrap();
+        assert_eq!(source.calls(), 2);
+        assert_eq!(salt, (0u8..32).collect::<Vec<_>>());
+        assert_eq!(nonce, (32u8..44).collect::<Vec<_>>());
+
+        let (data_b, _, _) =
+            encrypt_wallet_with(&wallet, &password, &CountingEntropy::starting_at(0)).unwrap();
+        assert_eq!(data_a, data_b);
+
+        let decrypted = decrypt_wallet(&data_a, &salt, &nonce, &password).unwrap();
+        assert_eq!(decrypted.twilightaddress, wallet.twilightaddress);
+        assert_eq!(decrypted.private_key_bytes(), wallet.private_key_bytes());
+        let wif = |w: &Wallet| w.btc_wallet.as_ref().map(|btc| btc.wif().to_string());
+        assert_eq!(wif(&decrypted), wif(&wallet));
+    }
+
+    /// Pool on a fresh SQLite file under the temp dir, migrated.
+    #[cfg(feature = "sqlite")]
+    fn temp_pool(name: &str) -> (DbPool, String) {
+        let path = std::env::temp_dir().join(format!("nyks-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = path.to_str().unwrap().to_string();
+        let pool = crate::database::connection::init_pool(Some(url.clone())).unwrap();
+        crate::database::connection::run_migrations(&mut get_conn(&pool).unwrap()).unwrap();
+        (pool, url)
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn stored_wallet(pool: &DbPool, wallet_id: &str, password: &SecretString) -> DatabaseManager {
+        let wallet = Wallet::from_mnemonic(
+            "test test test test test test test test test test test junk",
+            None,
+        )
+        .unwrap();
+        let manager = DatabaseManager::new(wallet_id.to_string(), pool.clone());
+        manager.save_encrypted_wallet(&wallet, password).unwrap();
+        manager.save_request_id(1, "req-1").unwrap();
+        manager
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_pool_members_are_scoped_by_pool_name() {
+        let (pool, url) = temp_pool("pool-members");
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        manager
+            .save_pool_member("grid", 4, "available", None)
+            .unwrap();
+        manager
+            .save_pool_member("grid", 2, "available", None)
+            .unwrap();
+        manager
+            .save_pool_member("hedge", 9, "in_use", None)
+            .unwrap();
+        manager
+            .save_pool_member("grid", 4, "in_use", Some("REQ-4"))
+            .unwrap();
+
+        assert_eq!(
+            manager.load_pool_members("grid").unwrap(),
+            vec![
+                (2, "available".to_string(), None),
+                (4, "in_use".to_string(), Some("REQ-4".to_string()))
+            ]
+        );
+        manager.remove_pool_member("grid", 2).unwrap();
+        assert_eq!(manager.load_pool_members("grid").unwrap().len(), 1);
+        assert_eq!(manager.load_pool_members("hedge").unwrap().len(), 1);
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_conditional_triggers_upsert_per_account_and_kind() {
+        use crate::compat::relayer_types::{OrderType, PositionType};
+        use crate::relayer_module::conditional_orders::{ConditionalTrigger, TriggerKind};
+
+        let (pool, url) = temp_pool("conditional-triggers");
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        let trigger = |kind, price| {
+            ConditionalTrigger::new(
+                3,
+                kind,
+                price,
+                PositionType::LONG,
+                OrderType::MARKET,
+                DateTime::<Utc>::UNIX_EPOCH,
+            )
+        };
+        let stop_loss = trigger(TriggerKind::StopLoss, 58_000.0);
+        let take_profit = trigger(TriggerKind::TakeProfit, 70_000.0);
+        manager
+            .save_conditional_trigger(&trigger(TriggerKind::StopLoss, 60_000.0))
+            .unwrap();
+        manager.save_conditional_trigger(&stop_loss).unwrap();
+        manager.save_conditional_trigger(&take_profit).unwrap();
+
+        let loaded = manager.load_conditional_triggers().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded.contains(&stop_loss));
+        manager
+            .remove_conditional_trigger(3, TriggerKind::StopLoss.as_str())
+            .unwrap();
+        assert_eq!(
+            manager.load_conditional_triggers().unwrap(),
+            vec![take_profit]
+        );
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_address_book_entries_round_trip_per_wallet() {
+        use crate::relayer_module::address_book::{AddressBook, AddressKind};
+
+        let (pool, url) = temp_pool("address-book");
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        let other = DatabaseManager::new("other".to_string(), pool.clone());
+        let added_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let address = |byte| {
+            cosmrs::AccountId::new(crate::wallet::BECH_PREFIX, &[byte; 20])
+                .unwrap()
+                .to_string()
+        };
+        let mut book = AddressBook::new();
+        let bob = book
+            .add_entry("bob", &address(2), AddressKind::Twilight, added_at)
+            .unwrap();
+        let alice = book
+            .add_entry("alice", &address(1), AddressKind::Twilight, added_at)
+            .unwrap();
+        manager.save_address_book_entry(&bob).unwrap();
+        manager.save_address_book_entry(&alice).unwrap();
+        other.save_address_book_entry(&alice).unwrap();
+
+        assert_eq!(
+            manager.load_address_book().unwrap(),
+            vec![alice.clone(), bob]
+        );
+        manager.remove_address_book_entry("bob").unwrap();
+        assert_eq!(manager.load_address_book().unwrap(), vec![alice.clone()]);
+        assert_eq!(other.load_address_book().unwrap(), vec![alice]);
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_order_snapshots_upsert_per_request() {
+        use crate::relayer_module::transaction_history::{OrderRecordKind, OrderSnapshot};
+
+        let (pool, url) = temp_pool("order-snapshots");
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        let recorded_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let submitted = OrderSnapshot {
+            account_index: 2,
+            request_id: "req-close".to_string(),
+            kind: OrderRecordKind::TraderClose,
+            order_status: "FILLED".to_string(),
+            position_type: Some("LONG".to_string()),
+            entry_price: Some(50_000.0),
+            exit_price: Some(52_000.0),
+            leverage: Some(5.0),
+            initial_margin: 1_000.0,
+            settled_margin: None,
+            realized_pnl: None,
+            fees: Some(1.5),
+            opened_at: Some(recorded_at - chrono::Duration::hours(1)),
+            recorded_at,
+        };
+        manager.save_order_snapshot(&submitted).unwrap();
+        let settled = OrderSnapshot {
+            order_status: "SETTLED".to_string(),
+            exit_price: Some(52_100.0),
+            settled_margin: Some(1_200.0),
+            realized_pnl: Some(200.0),
+            ..submitted.clone()
+        };
+        manager.save_order_snapshot(&settled).unwrap();
+        let other_wallet = DatabaseManager::new("other".to_string(), pool.clone());
+        other_wallet.save_order_snapshot(&submitted).unwrap();
+
+        assert_eq!(manager.load_order_snapshots("bot").unwrap(), vec![settled]);
+        // Either manager reads both wallets.
+        assert_eq!(
+            manager.load_order_snapshots("other").unwrap(),
+            vec![submitted]
+        );
+        assert_eq!(
+            other_wallet.load_order_snapshots("bot").unwrap(),
+            manager.load_order_snapshots("bot").unwrap()
+        );
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_delete_wallet_requires_its_password() {
+        let (pool, url) = temp_pool("delete-wallet");
+        let password = SecretString::new("correct horse".to_string());
+        let manager = stored_wallet(&pool, "bot-1", &password);
+        stored_wallet(&pool, "bot-2", &password);
+
+        let wrong = SecretString::new("battery staple".to_string());
+        assert!(DatabaseManager::delete_wallet(&pool, "bot-1", &wrong).is_err());
+        assert!(DatabaseManager::check_wallet_id_exists(&pool, "bot-1").unwrap());
+        assert_eq!(manager.load_all_request_ids().unwrap().len(), 1);
+
+        DatabaseManager::delete_wallet(&pool, "bot-1", &password).unwrap();
+        assert!(!DatabaseManager::check_wallet_id_exists(&pool, "bot-1").unwrap());
+        assert!(manager.load_all_request_ids().unwrap().is_empty());
+        // Other wallets are untouched.
+        assert!(DatabaseManager::check_wallet_id_exists(&pool, "bot-2").unwrap());
+
+        let loaded = crate::relayer_module::order_wallet::OrderWallet::load_from_db(
+            "bot-1".to_string(),
+            Some(password),
+            Some(url.clone()),
+        );
+        assert!(loaded.is_err());
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_rename_wallet_moves_rows_and_refuses_existing_target() {
+        let (pool, url) = temp_pool("rename-wallet");
+        let password = SecretString::new("correct horse".to_string());
+        stored_wallet(&pool, "old", &password);
+        stored_wallet(&pool, "taken", &password);
+
+        assert!(DatabaseManager::rename_wallet(&pool, "old", "taken").is_err());
+        assert!(DatabaseManager::rename_wallet(&pool, "missing", "new").is_err());
+
+        DatabaseManager::rename_wallet(&pool, "old", "new").unwrap();
+        assert!(!DatabaseManager::check_wallet_id_exists(&pool, "old").unwrap());
+        let renamed = DatabaseManager::new("new".to_string(), pool.clone());
+        assert!(renamed.load_encrypted_wallet(&password).is_ok());
+        assert_eq!(
+            renamed.load_request_id(1).unwrap(),
+            Some("req-1".to_string())
+        );
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_order_records_round_trip_in_order() {
+        use crate::relayer_module::transaction_history::{OrderRecord, OrderRecordKind};
+        let (pool, url) = temp_pool("order-records");
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        let at = chrono::Utc::now();
+        let open = OrderRecord::new("req-open".to_string(), OrderRecordKind::TraderOpen, at)
+            .with_requested_entry("LIMIT".to_string(), 64_000);
+        let close = OrderRecord::new("req-close".to_string(), OrderRecordKind::TraderClose, at);
+        manager.save_order_record(1, 1, &close, at).unwrap();
+        manager.save_order_record(1, 0, &open, at).unwrap();
+        let mut settled = close.clone();
+        settled.status = "SETTLED".to_string();
+        manager.save_order_record(1, 1, &settled, at).unwrap();
+
+        let records = manager.load_order_records().unwrap();
+        let loaded = &records[&1];
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].request_id, "req-open");
+        assert_eq!(loaded[0].requested_price, Some(64_000));
+        assert_eq!(loaded[1].order_type, None);
+        assert_eq!(loaded[1].kind, OrderRecordKind::TraderClose);
+        assert_eq!(loaded[1].status, "SETTLED");
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_idempotency_keys_and_pending_submissions_round_trip() {
+        use crate::relayer_module::events::OrderKind;
+        use crate::relayer_module::idempotency::PendingSubmission;
+        let (pool, url) = temp_pool("pending-submissions");
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+
+        manager
+            .save_request_id_with_key(1, "req-1", Some("key-1"))
+            .unwrap();
+        // A plain save keeps the key; a keyed save replaces it.
+        manager.save_request_id(1, "req-1").unwrap();
+        manager.save_request_id_with_key(2, "req-2", None).unwrap();
+        let keys = manager.load_idempotency_keys().unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[&1], "key-1");
+
+        let at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let submission = PendingSubmission::new(3, OrderKind::Lend, "acct-3", at);
+        manager.save_pending_submission(&submission).unwrap();
+        let loaded = manager.load_pending_submissions().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[&3], submission);
+
+        manager.remove_pending_submission(3).unwrap();
+        assert!(manager.load_pending_submissions().unwrap().is_empty());
+        let _ = std::fs::remove_file(url);
+    }
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_account_labels_round_trip() {
+        let (pool, url) = temp_pool("account-labels");
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        manager
+            .unlock_zk_accounts(&SecretString::new("pw".to_string()))
+            .unwrap();
+        let mut account = ZkAccount::new(
+            "qq".to_string(),
+            10,
+            "acct".to_string(),
+            "scalar".to_string(),
+            0,
+        );
+        account.label = Some("hedge".to_string());
+        manager.save_zk_account(&account).unwrap();
+        assert_eq!(
+            manager.load_all_zk_accounts().unwrap()[&0].label.as_deref(),
+            Some("hedge")
+        );
+
+        account.label = None;
+        manager.update_zk_account(&account).unwrap();
+        assert_eq!(manager.load_all_zk_accounts().unwrap()[&0].label, None);
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_zk_account_secrets_are_sealed_and_plaintext_rows_migrated() {
+        let (pool, url) = temp_pool("account-secrets");
+        let account = |index| {
+            ZkAccount::new(
+                "qq".to_string(),
+                10,
+                "acct".to_string(),
+                "scalar".to_string(),
+                index,
+            )
+        };
+        // A row written before the secrets were encrypted.
+        let legacy = DbZkAccount::from_zk_account(&account(0), "bot".to_string());
+        diesel::insert_into(zk_accounts::table)
+            .values(&legacy)
+            .execute(&mut get_conn(&pool).unwrap())
+            .unwrap();
+
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        assert!(manager.save_zk_account(&account(1)).is_err());
+        manager
+            .unlock_zk_accounts(&SecretString::new("pw".to_string()))
+            .unwrap();
+        manager.save_zk_account(&account(1)).unwrap();
+
+        let rows = manager.load_db_zk_accounts().unwrap();
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            assert!(row.scalar.is_empty() && row.account.is_empty());
+            assert!(row.sealed_secrets().is_some());
+            assert_eq!(row.secrets_salt, rows[0].secrets_salt);
+        }
+        assert_ne!(rows[0].secrets_nonce, rows[1].secrets_nonce);
+        let loaded = manager.load_all_zk_accounts().unwrap();
+        assert_eq!(loaded[&0].scalar, "scalar");
+        assert_eq!(loaded[&1].account, "acct");
+
+        let other = DatabaseManager::new("bot".to_string(), pool.clone());
+        assert!(other.load_all_zk_accounts().unwrap_err().contains("unlock"));
+        let wrong = other.unlock_zk_accounts(&SecretString::new("wrong".to_string()));
+        assert!(wrong.unwrap_err().contains("Wrong password"));
+        assert!(other.load_all_zk_accounts().is_err());
+        let _ = std::fs::remove_file(url);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_pending_operation_secrets_are_sealed() {
+        use crate::database::schema::pending_operations;
+        use crate::relayer_module::pending_operations::{
+            OperationInputs, OperationStep, PendingOperation, PendingOperationKind,
+        };
+
+        let (pool, url) = temp_pool("pending-secrets");
+        let scalar = "5ca1a75ca1a75ca1a75ca1a75ca1a75ca1a75ca1a75ca1a75ca1a75ca1a70001";
+        let account_key = "acc0acc0acc0acc0acc0acc0acc0acc0acc0acc0acc0acc0acc0acc0acc00002";
+        let op = PendingOperation::new(
+            PendingOperationKind::SplitAccount,
+            OperationInputs::SplitAccount {
+                sender_account_index: 1,
+                balances: vec![100],
+            },
+            vec![OperationStep::FinalizeReceiver {
+                account_index: 2,
+                balance: 100,
+                encrypt_scalar: scalar.to_string(),
+                account_key: account_key.to_string(),
+            }],
+            DateTime::<Utc>::UNIX_EPOCH,
+        );
+        let manager = DatabaseManager::new("bot".to_string(), pool.clone());
+        assert!(
+            manager
+                .save_pending_operation(&op)
+                .unwrap_err()
+                .contains("unlock")
+        );
+        manager
+            .unlock_zk_accounts(&SecretString::new("pw".to_string()))
+            .unwrap();
+        manager.save_pending_operation(&op).unwrap();
+
+        let payloads: Vec<String> = pending_operations::table
+            .select(pending_operations::payload)
+            .load(&mut get_conn(&pool).unwrap())
+            .unwrap();
+        assert_eq!(payloads.len(), 1);
+        assert!(!payloads[0].contains(scalar) && !payloads[0].contains(account_key));
+        assert_eq!(
+            manager.load_pending_operations(None).unwrap(),
+            vec![op.clone()]
+        );
+
+        // The key is needed to resume, and follows a password change.
+        let locked = DatabaseManager::new("bot".to_string(), pool.clone());
+        assert!(
+            locked
+                .load_pending_operations(None)
+                .unwrap_err()
+                .contains("unlock")
+        );
+        manager
+            .rekey_zk_accounts(&SecretString::new("new pw".to_string()))
+            .unwrap();
+        assert_eq!(manager.load_pending_operations(None).unwrap(), vec![op]);
+        let _ = std::fs::remove_file(url);
+    }
+}
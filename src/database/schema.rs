@@ -17,6 +17,12 @@ diesel::table! {
         tx_type -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        balance_unverified -> Bool,
+        label -> Nullable<Text>,
+        secrets_encrypted -> Nullable<Binary>,
+        secrets_salt -> Nullable<Binary>,
+        secrets_nonce -> Nullable<Binary>,
     }
 }
 
@@ -49,6 +55,7 @@ diesel::table! {
         is_active -> Bool,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        config_fingerprint -> Nullable<Text>,
     }
 }
 
@@ -75,6 +82,7 @@ diesel::table! {
         request_id -> Text,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        idempotency_key -> Nullable<Text>,
     }
 }
 
@@ -169,6 +177,156 @@ diesel::table! {
     }
 }
 
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    pending_operations (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        operation_id -> Text,
+        kind -> Text,
+        status -> Text,
+        payload -> Text,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    signing_audit (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        seq -> BigInt,
+        account_index -> BigInt,
+        purpose -> Text,
+        payload_hash -> Text,
+        prev_hash -> Text,
+        entry_hash -> Text,
+        signed_at -> Timestamp,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    activity_buckets (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        hour -> Timestamp,
+        category -> Text,
+        count -> BigInt,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    archived_zk_accounts (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        account_index -> BigInt,
+        archived_at -> Timestamp,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    order_records (wallet_id, network_type, account_index, seq) {
+        wallet_id -> Text,
+        network_type -> Text,
+        account_index -> BigInt,
+        seq -> BigInt,
+        request_id -> Text,
+        kind -> Text,
+        status -> Text,
+        recorded_at -> Timestamp,
+        updated_at -> Timestamp,
+        order_type -> Nullable<Text>,
+        requested_price -> Nullable<BigInt>,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    pending_submissions (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        account_index -> BigInt,
+        idempotency_key -> Text,
+        kind -> Text,
+        account_address -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    account_pool_members (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        pool_name -> Text,
+        account_index -> BigInt,
+        state -> Text,
+        updated_at -> Timestamp,
+        request_id -> Nullable<Text>,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    conditional_triggers (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        account_index -> BigInt,
+        kind -> Text,
+        payload -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    order_snapshots (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        account_index -> BigInt,
+        request_id -> Text,
+        kind -> Text,
+        order_status -> Text,
+        position_type -> Nullable<Text>,
+        entry_price -> Nullable<Double>,
+        exit_price -> Nullable<Double>,
+        leverage -> Nullable<Double>,
+        initial_margin -> Double,
+        settled_margin -> Nullable<Double>,
+        realized_pnl -> Nullable<Double>,
+        fees -> Nullable<Double>,
+        opened_at -> Nullable<Timestamp>,
+        recorded_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+diesel::table! {
+    address_book (id) {
+        id -> Nullable<Integer>,
+        wallet_id -> Text,
+        network_type -> Text,
+        label -> Text,
+        address -> Text,
+        kind -> Text,
+        added_at -> Timestamp,
+    }
+}
+
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 diesel::allow_tables_to_appear_in_same_query!(
     zk_accounts,
@@ -181,4 +339,14 @@ diesel::allow_tables_to_appear_in_same_query!(
     btc_deposits,
     btc_withdrawals,
     btc_transfers,
+    pending_operations,
+    signing_audit,
+    activity_buckets,
+    archived_zk_accounts,
+    order_records,
+    pending_submissions,
+    account_pool_members,
+    conditional_triggers,
+    order_snapshots,
+    address_book,
 );
@@ -25,6 +25,17 @@ pub enum WalletError {
     TxBuild(String),
     #[error("transaction broadcast failed (code {code}) for tx {tx_hash}")]
     TxBroadcastFailed { tx_hash: String, code: u32 },
+    /// The chain rejected the tx for its sequence again after it was re-signed
+    /// with the sequence the chain asked for; `expected` is from the last
+    /// rejection's log, when it names one.
+    #[error(
+        "sequence mismatch for tx {tx_hash}: signed with {sequence}, chain expects {expected:?}"
+    )]
+    SequenceMismatch {
+        tx_hash: String,
+        sequence: u64,
+        expected: Option<u64>,
+    },
     #[error(
         "failed to fetch UTXO details after {attempts} attempts for IO type {io_type}: {source}"
     )]
@@ -34,6 +45,8 @@ pub enum WalletError {
         #[source]
         source: AnyhowError,
     },
+    #[error("account {address} not found on chain after {attempts} attempts (not indexed yet?)")]
+    AccountNotOnChain { address: String, attempts: u32 },
     #[error("RPC request failed: {0}")]
     RpcRequest(String),
     #[error("failed to create trader order: {0}")]
@@ -64,12 +77,177 @@ pub enum WalletError {
     SigningKey(String),
     #[error("transfer transaction failed: {0}")]
     Transfer(String),
+    /// A call into the client SDK failed or panicked; `detail` carries the
+    /// account and input sizes involved.
+    #[error("client SDK {operation} failed: {detail}")]
+    ClientSdk { operation: String, detail: String },
     #[error("import failed: {0}")]
     Import(String),
+    #[error("export failed: {0}")]
+    Export(String),
+    /// A wallet file field is missing, malformed or inconsistent with the
+    /// private key or the configuration.
+    #[error("invalid wallet file: {field}: {reason}")]
+    InvalidWalletFile { field: String, reason: String },
+    #[error("private key is not valid hex: {0}")]
+    InvalidPrivateKeyHex(String),
+    #[error("private key must be 32 bytes, got {0}")]
+    InvalidPrivateKeyLength(usize),
+    /// 32 bytes, but not a valid secp256k1 secret (zero or above the curve order).
+    #[error("invalid private key: {0}")]
+    InvalidPrivateKey(String),
+    #[error("invalid BTC address: {0}")]
+    InvalidBtcAddress(String),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
     #[error("zk account seed not found: {0}")]
     ZkAccountSeedNotFound(String),
+    #[error(transparent)]
+    InvalidConfig(#[from] crate::config::ConfigError),
+    #[cfg(feature = "order-wallet")]
+    #[error(transparent)]
+    RelayerProgram(#[from] crate::relayer_module::relayer_program::RelayerProgramError),
 }
 
 pub type Result<T> = std::result::Result<T, WalletError>;
+
+/// Errors from the trading and funding operations of
+/// [`OrderWallet`](crate::relayer_module::order_wallet::OrderWallet).
+///
+/// Layers below the wallet still report plain strings. Those are typed where
+/// the message is known: the chain's "UTXO not found" becomes
+/// [`UtxoNotFound`](OrderWalletError::UtxoNotFound), a failure to reach the
+/// LCD, the RPC or the UTXO endpoint
+/// [`ChainUnreachable`](OrderWalletError::ChainUnreachable), and the
+/// relayer's value witness failure
+/// [`WitnessRejected`](OrderWalletError::WitnessRejected). Anything else
+/// arrives as [`Other`](OrderWalletError::Other).
+#[cfg(feature = "order-wallet")]
+#[derive(Debug, Error)]
+pub enum OrderWalletError {
+    #[error("insufficient balance: {required} sats required, {available} available")]
+    InsufficientBalance { required: u64, available: u64 },
+    #[error("account {0} does not exist on chain")]
+    AccountNotOnChain(u64),
+    #[error("account {0} has no balance")]
+    EmptyAccount(u64),
+    #[error("invalid order state: expected {expected}, found {actual}")]
+    InvalidOrderState { expected: String, actual: String },
+    #[error(transparent)]
+    Account(#[from] crate::zkos_accounts::zkaccount::ZkAccountError),
+    #[error("relayer request failed: {0}")]
+    RelayerRpc(#[from] jsonrpsee::core::client::Error),
+    /// The chain rejected tx `tx_hash`; `log` is the chain's log for it,
+    /// empty when it gave none.
+    #[error("chain rejected tx {tx_hash} with code {code}: {log}")]
+    ChainTx {
+        tx_hash: String,
+        code: u32,
+        log: String,
+    },
+    /// The chain still rejected the tx for its sequence (code 32) after it
+    /// was re-signed at the sequence the chain asked for.
+    #[error("chain rejected tx {tx_hash} for a stale signer sequence (code {code})")]
+    SequenceMismatch { tx_hash: String, code: u32 },
+    #[error("UTXO not found")]
+    UtxoNotFound,
+    /// The LCD, the Tendermint RPC or the UTXO endpoint could not be reached
+    /// or timed out.
+    #[error("{0}")]
+    ChainUnreachable(String),
+    /// The relayer failed the order's value witness. This is usually an
+    /// account whose address already carried an order; rotate it with
+    /// `trading_to_trading` before ordering again.
+    #[error("{0}")]
+    WitnessRejected(String),
+    /// A multi-account funding failed after some of its chunks reached the
+    /// chain: `txs` are those chunks' transactions and `funded` the accounts
+    /// they funded, with their balances.
+    #[error("funded accounts {:?}, then failed: {source}", .funded.iter().map(|(index, _)| index).collect::<Vec<_>>())]
+    PartialFunding {
+        txs: Vec<crate::relayer_module::TxResult>,
+        funded: Vec<(u64, u64)>,
+        source: Box<OrderWalletError>,
+    },
+    #[error("database error: {0}")]
+    Database(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+#[cfg(feature = "order-wallet")]
+impl OrderWalletError {
+    /// How a repeat of the same call may fare; see
+    /// [`RetryClass`](crate::relayer_module::RetryClass). The relayer or
+    /// the chain could not be reached or timed out, or the chain rejected a
+    /// stale signer sequence: transient. An output not indexed yet: not
+    /// found. Rejections by the relayer and invalid account or order states:
+    /// permanent.
+    pub fn retry_class(&self) -> crate::relayer_module::RetryClass {
+        use crate::relayer_module::RetryClass;
+        match self {
+            OrderWalletError::RelayerRpc(e) => RetryClass::of_rpc_error(e),
+            OrderWalletError::ChainTx { code, .. }
+                if crate::relayer_module::is_stale_signer_code(*code) =>
+            {
+                RetryClass::Transient
+            }
+            OrderWalletError::SequenceMismatch { .. } | OrderWalletError::ChainUnreachable(_) => {
+                RetryClass::Transient
+            }
+            OrderWalletError::UtxoNotFound => RetryClass::NotFound,
+            OrderWalletError::InsufficientBalance { .. }
+            | OrderWalletError::AccountNotOnChain(_)
+            | OrderWalletError::EmptyAccount(_)
+            | OrderWalletError::InvalidOrderState { .. }
+            | OrderWalletError::Account(_)
+            | OrderWalletError::ChainTx { .. }
+            | OrderWalletError::WitnessRejected(_)
+            | OrderWalletError::PartialFunding { .. }
+            | OrderWalletError::Database(_)
+            | OrderWalletError::Other(_) => RetryClass::Permanent,
+        }
+    }
+
+    /// Whether the same call may succeed if repeated unchanged, i.e. its
+    /// [`retry_class`](Self::retry_class) is not permanent.
+    pub fn is_retryable(&self) -> bool {
+        self.retry_class().is_retryable()
+    }
+}
+
+/// The relayer's error for an order whose value witness does not verify.
+#[cfg(feature = "order-wallet")]
+const WITNESS_REJECTED: &str = "Value Witness Verification Failed";
+
+#[cfg(feature = "order-wallet")]
+impl From<String> for OrderWalletError {
+    fn from(e: String) -> Self {
+        if crate::relayer_module::is_utxo_not_found(&e) {
+            OrderWalletError::UtxoNotFound
+        } else if e.contains(WITNESS_REJECTED) {
+            OrderWalletError::WitnessRejected(e)
+        } else if crate::relayer_module::is_unreachable_error(&e) {
+            OrderWalletError::ChainUnreachable(e)
+        } else {
+            OrderWalletError::Other(e)
+        }
+    }
+}
+
+#[cfg(feature = "order-wallet")]
+impl From<&str> for OrderWalletError {
+    fn from(e: &str) -> Self {
+        e.to_string().into()
+    }
+}
+
+#[cfg(feature = "order-wallet")]
+impl From<OrderWalletError> for String {
+    fn from(e: OrderWalletError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(feature = "order-wallet")]
+pub type OrderWalletResult<T> = std::result::Result<T, OrderWalletError>;
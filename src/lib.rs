@@ -35,7 +35,7 @@
 //!
 //! ```no_run
 //! use nyks_wallet::relayer_module::order_wallet::OrderWallet;
-//! use twilight_client_sdk::relayer_types::{OrderType, PositionType};
+//! use nyks_wallet::compat::relayer_types::{OrderType, PositionType};
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), String> {
@@ -138,19 +138,43 @@
 //! | `TWILIGHT_INDEXER_URL` | Twilight indexer | mainnet: `https://indexer.twilight.org`; testnet: `https://indexer.twilight.rest` |
 //! | `BTC_ESPLORA_PRIMARY_URL` | Primary Esplora API (driven by `BTC_NETWORK_TYPE`) | `https://blockstream.info/api` (mainnet) |
 //! | `BTC_ESPLORA_FALLBACK_URL` | Fallback Esplora API (driven by `BTC_NETWORK_TYPE`) | `https://mempool.space/api` (mainnet) |
-//! | `RELAYER_PROGRAM_JSON_PATH` | Path to relayer program JSON | `./relayerprogram.json` |
+//! | `RELAYER_PROGRAM_JSON_PATH` | Path to relayer program JSON (`builtin` for the compiled-in program) | `./relayerprogram.json` |
+//! | `RELAYER_PROGRAM_LOADING` | `eager` (at construction) or `lazy` (first order) | `eager` |
 //! | `VALIDATOR_WALLET_PATH` | Validator mnemonic file (`validator-wallet` feature) | `validator.mnemonic` |
 //! | `NYKS_WALLET_PASSPHRASE` | DB encryption passphrase | – (prompt) |
 //! | `WALLET_ID` | DB wallet ID (defaults to Twilight address) | – |
 //! | `DATABASE_URL_SQLITE` | SQLite path (`sqlite` feature) | `./wallet_data.db` |
 //! | `DATABASE_URL_POSTGRESQL` | PostgreSQL DSN (`postgresql` feature) | – |
 //! | `RUST_LOG` | Logging level | – |
+//! | `NYKS_LOG_PRIVACY` | Log redaction: `full`, `redact-amounts`, `redact-addresses` or `minimal` (see [`log_privacy`]) | `full` |
+//!
+//! The chain, endpoint and database variables can also come from a TOML file
+//! (see [`config::Config`] and `relayer-cli --config <path>`); variables set in
+//! the environment take precedence over the file.
 //!
 //! ## Feature Flags
 //!
-//! - `sqlite`: Enable SQLite database persistence
-//! - `postgresql`: Enable PostgreSQL database persistence  
-//! - `validator-wallet`: Enable validator-specific functionality
+//! | Feature | Enables | Implies |
+//! |---------|---------|---------|
+//! | `market-data` | [`compat`], [`relayer_module::relayer_api`], [`relayer_module::relayer_types`], [`relayer_module::order_query`], [`relayer_module::capabilities`], [`relayer_module::clock`] | – |
+//! | `ws` | `RelayerWsClient` live price, order book and trade subscriptions | `market-data` |
+//! | `wallet-core` | [`wallet`], [`nyks_rpc`], [`security`] | – |
+//! | `zk-accounts` | [`zkos_accounts`] | `market-data`, `wallet-core` |
+//! | `order-wallet` | Full trading stack ([`relayer_module::order_wallet`] and friends) | `zk-accounts` |
+//! | `sqlite` / `db-sqlite` | SQLite database persistence | `order-wallet` |
+//! | `postgresql` / `db-postgres` | PostgreSQL database persistence | `order-wallet` |
+//! | `validator-wallet` | Validator-specific functionality | `wallet-core` |
+//! | `otel` | W3C trace-context propagation on HTTP calls (see [`telemetry`]) | – |
+//! | `health-endpoint` | `/healthz` + `/readyz` listener via `OrderWallet::serve_health` | `order-wallet` |
+//! | `webhooks` | Signed order lifecycle webhooks via `OrderWallet::add_webhook` | `order-wallet` |
+//! | `test-utils` / `testing` | Builders and ready-made relayer values for tests (`relayer_module::test_fixtures`), scripted `relayer_module::mock_relayer::MockRelayer` | `market-data` |
+//!
+//! The default feature set is `sqlite` + `order-wallet`. A service that only needs
+//! relayer market data can depend on `default-features = false, features = ["market-data"]`.
+//! Note that `market-data` still depends on `twilight-client-sdk`, which owns the shared
+//! relayer type definitions.
+//!
+//! `scripts/check-features.sh` builds every supported feature combination.
 //!
 //! **Note**: If both `sqlite` and `postgresql` are enabled, SQLite takes precedence.
 //!
@@ -167,21 +191,34 @@
 //! - [`relayer_module`]: Trading operations via the Twilight relayer
 //!   - [`relayer_module::order_wallet`]: High-level trading interface with OrderWallet
 //!   - [`relayer_module::relayer_api`]: Low-level JSON-RPC client for relayer endpoints
+//!   - [`relayer_module::order_query`]: Signed order queries from per-account keys, without a wallet
+//!   - [`relayer_module::capabilities`]: What the connected relayer supports, from a version handshake or probing
+//! - [`compat`]: Pinned re-exports of `twilight-client-sdk` and swappable transfer/broadcast adapters
 //! - [`zkos_accounts`]: Privacy-preserving account management
 //! - [`database`]: Optional persistence layer (requires feature flags)
 //! - [`security`]: Secure password and key management utilities
 //! - [`config`]: Configuration management and endpoint settings
+//! - [`log_privacy`]: Redaction of addresses and amounts in log lines
+//! - [`telemetry`]: Optional trace-context propagation for outgoing requests
 //! - [`error`]: Error types and handling
 //!
 //! For detailed usage examples and API documentation, see the individual module documentation
 //! and the [`OrderWallet.md`](../../OrderWallet.md) guide in the repository.
 
+#[cfg(feature = "wallet-core")]
 pub mod nyks_rpc;
+#[cfg(feature = "wallet-core")]
 pub mod wallet;
+#[cfg(feature = "wallet-core")]
 pub use wallet::*;
 pub mod config;
 pub mod error;
+pub mod log_privacy;
+pub mod telemetry;
+#[cfg(feature = "order-wallet")]
 pub mod test;
+#[cfg(test)]
+mod response_mutations;
 // ----------------------------------------------------------------------------
 // Generated protobuf module (prost-build)
 // ----------------------------------------------------------------------------
@@ -212,19 +249,23 @@ pub mod validator_wallet;
 pub use validator_wallet::*;
 
 // -------------------------------------------------------------
-// Optional order-wallet feature
+// Relayer client (`market-data`) and trading stack (`order-wallet`)
 // -------------------------------------------------------------
-#[cfg(feature = "order-wallet")]
+#[cfg(feature = "market-data")]
+pub mod compat;
+#[cfg(feature = "market-data")]
 pub mod relayer_module;
-#[cfg(feature = "order-wallet")]
+#[cfg(feature = "zk-accounts")]
 pub mod zkos_accounts;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 
 // Database module (optional, based on features)
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 pub mod database;
 
 // Security module for secure password and wallet management
-// #[cfg(feature = "order-wallet")]
+#[cfg(feature = "wallet-core")]
 pub mod security;
 
 #[cfg(all(feature = "sqlite", feature = "postgresql"))]
@@ -0,0 +1,358 @@
+//! Redaction of addresses and amounts in log output.
+//!
+//! Log call sites wrap sensitive values in [`LoggedAddress`] or
+//! [`LoggedAmount`], and dumps of values that embed addresses, such as a
+//! UTXO, in [`LoggedDebug`]; their `Display` consults the process-wide
+//! [`LogPrivacy`] policy and prints the full value, a short fingerprint or
+//! `[redacted]`. The policy is read from `NYKS_LOG_PRIVACY` on first use and
+//! can be changed at runtime with [`LogPrivacy::set`]; the change applies to
+//! every log line formatted afterwards.
+//!
+//! | `NYKS_LOG_PRIVACY` | Addresses | Amounts |
+//! |--------------------|-----------|---------|
+//! | `full` (default) | full | full |
+//! | `redact-amounts` | full | `[redacted]` |
+//! | `redact-addresses` | fingerprint | full |
+//! | `minimal` | `[redacted]` | `[redacted]` |
+//!
+//! A fingerprint is `#` followed by the first 8 hex digits of the SHA-256 of
+//! the address, so the same address can still be followed across lines. A
+//! [`LoggedDebug`] dump is printed whole or, when addresses are redacted, not
+//! at all.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use log::warn;
+use sha2::{Digest, Sha256};
+
+/// Environment variable holding the initial policy.
+pub const LOG_PRIVACY_ENV: &str = "NYKS_LOG_PRIVACY";
+
+const REDACTED: &str = "[redacted]";
+const UNSET: u8 = u8::MAX;
+
+static POLICY: AtomicU8 = AtomicU8::new(UNSET);
+
+/// How much of an address or amount reaches the logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogPrivacy {
+    #[default]
+    Full,
+    RedactAmounts,
+    RedactAddresses,
+    Minimal,
+}
+
+impl LogPrivacy {
+    /// The active policy, initialised from `NYKS_LOG_PRIVACY` on first call.
+    pub fn current() -> Self {
+        match POLICY.load(Ordering::Relaxed) {
+            UNSET => {
+                let policy = Self::from_env();
+                // Keep a policy set concurrently with this first read.
+                match POLICY.compare_exchange(
+                    UNSET,
+                    policy as u8,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => policy,
+                    Err(set) => Self::from_u8(set),
+                }
+            }
+            value => Self::from_u8(value),
+        }
+    }
+
+    /// Replace the active policy for every log line formatted from now on.
+    pub fn set(policy: LogPrivacy) {
+        POLICY.store(policy as u8, Ordering::Relaxed);
+    }
+
+    /// Policy from `NYKS_LOG_PRIVACY`; unset or unknown values mean [`LogPrivacy::Full`].
+    pub fn from_env() -> Self {
+        match std::env::var(LOG_PRIVACY_ENV) {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                warn!("{}; logging without redaction", e);
+                LogPrivacy::Full
+            }),
+            Err(_) => LogPrivacy::Full,
+        }
+    }
+
+    pub fn redacts_addresses(self) -> bool {
+        matches!(self, LogPrivacy::RedactAddresses | LogPrivacy::Minimal)
+    }
+
+    pub fn redacts_amounts(self) -> bool {
+        matches!(self, LogPrivacy::RedactAmounts | LogPrivacy::Minimal)
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => LogPrivacy::RedactAmounts,
+            2 => LogPrivacy::RedactAddresses,
+            3 => LogPrivacy::Minimal,
+            _ => LogPrivacy::Full,
+        }
+    }
+}
+
+impl FromStr for LogPrivacy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "full" => Ok(LogPrivacy::Full),
+            "redact-amounts" => Ok(LogPrivacy::RedactAmounts),
+            "redact-addresses" => Ok(LogPrivacy::RedactAddresses),
+            "minimal" => Ok(LogPrivacy::Minimal),
+            other => Err(format!(
+                "Invalid {} '{}': expected full, redact-amounts, redact-addresses or minimal",
+                LOG_PRIVACY_ENV, other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogPrivacy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogPrivacy::Full => "full",
+            LogPrivacy::RedactAmounts => "redact-amounts",
+            LogPrivacy::RedactAddresses => "redact-addresses",
+            LogPrivacy::Minimal => "minimal",
+        })
+    }
+}
+
+/// First 8 hex digits of the SHA-256 of `value`, prefixed with `#`.
+pub fn fingerprint(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    format!("#{}", hex::encode(&digest[..4]))
+}
+
+/// An address, account id or public key as it should appear in a log line.
+#[derive(Debug, Clone, Copy)]
+pub struct LoggedAddress<'a>(pub &'a str);
+
+impl fmt::Display for LoggedAddress<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match LogPrivacy::current() {
+            LogPrivacy::Full | LogPrivacy::RedactAmounts => f.write_str(self.0),
+            LogPrivacy::RedactAddresses => f.write_str(&fingerprint(self.0)),
+            LogPrivacy::Minimal => f.write_str(REDACTED),
+        }
+    }
+}
+
+/// The `Debug` dump of a value that embeds addresses, such as a UTXO. A
+/// fingerprint of the whole dump would not identify anything, so it is
+/// redacted entirely.
+#[derive(Debug, Clone, Copy)]
+pub struct LoggedDebug<'a, T: ?Sized>(pub &'a T);
+
+impl<T: fmt::Debug + ?Sized> fmt::Display for LoggedDebug<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if LogPrivacy::current().redacts_addresses() {
+            f.write_str(REDACTED)
+        } else {
+            write!(f, "{:?}", self.0)
+        }
+    }
+}
+
+/// A balance, order size or price as it should appear in a log line.
+#[derive(Debug, Clone, Copy)]
+pub struct LoggedAmount<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for LoggedAmount<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if LogPrivacy::current().redacts_amounts() {
+            f.write_str(REDACTED)
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+/// The test binary's global logger: `env_logger` output as usual, plus a
+/// capture of the formatted records while [`start`](test_logger::start) is
+/// in effect, so a test can check what actually reached the logger.
+#[cfg(test)]
+pub(crate) mod test_logger {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Mutex, OnceLock};
+
+    struct TestLogger {
+        inner: env_logger::Logger,
+        capturing: AtomicBool,
+        lines: Mutex<Vec<String>>,
+    }
+
+    impl log::Log for TestLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.capturing.load(Ordering::Relaxed) {
+                self.lines.lock().unwrap().push(record.args().to_string());
+            }
+            self.inner.log(record);
+        }
+
+        fn flush(&self) {
+            self.inner.flush();
+        }
+    }
+
+    static LOGGER: OnceLock<TestLogger> = OnceLock::new();
+
+    fn logger() -> &'static TestLogger {
+        LOGGER.get_or_init(|| TestLogger {
+            inner: env_logger::builder().is_test(true).build(),
+            capturing: AtomicBool::new(false),
+            lines: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Install the logger; later calls do nothing.
+    pub(crate) fn init() {
+        if log::set_logger(logger()).is_ok() {
+            log::set_max_level(log::LevelFilter::Trace);
+        }
+    }
+
+    /// Install the logger and capture from now on, dropping earlier lines.
+    pub(crate) fn start() {
+        init();
+        let logger = logger();
+        logger.lines.lock().unwrap().clear();
+        logger.capturing.store(true, Ordering::Relaxed);
+    }
+
+    /// Stop capturing and return the lines captured since [`start`].
+    pub(crate) fn finish() -> Vec<String> {
+        let logger = logger();
+        logger.capturing.store(false, Ordering::Relaxed);
+        std::mem::take(&mut *logger.lines.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::sync::Mutex;
+
+    /// Collects formatted records instead of printing them.
+    #[derive(Default)]
+    struct CaptureLogger {
+        lines: Mutex<Vec<String>>,
+    }
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.lines.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    const ADDRESS: &str = "twilight1qyqszqgpqyqszqgpqyqszqgpqyqszqgpvxwz6g";
+
+    fn capture(policy: LogPrivacy) -> String {
+        LogPrivacy::set(policy);
+        let logger = CaptureLogger::default();
+        log::Log::log(
+            &logger,
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .args(format_args!(
+                    "Deposit of {} sats to {}",
+                    LoggedAmount(50_000u64),
+                    LoggedAddress(ADDRESS)
+                ))
+                .build(),
+        );
+        let lines = logger.lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        lines[0].clone()
+    }
+
+    #[test]
+    #[serial]
+    fn test_log_output_under_each_policy() {
+        let fp = fingerprint(ADDRESS);
+        assert_eq!(fp.len(), 9);
+
+        assert_eq!(
+            capture(LogPrivacy::Full),
+            format!("Deposit of 50000 sats to {}", ADDRESS)
+        );
+        assert_eq!(
+            capture(LogPrivacy::RedactAmounts),
+            format!("Deposit of [redacted] sats to {}", ADDRESS)
+        );
+        assert_eq!(
+            capture(LogPrivacy::RedactAddresses),
+            format!("Deposit of 50000 sats to {}", fp)
+        );
+        assert_eq!(
+            capture(LogPrivacy::Minimal),
+            "Deposit of [redacted] sats to [redacted]"
+        );
+        LogPrivacy::set(LogPrivacy::Full);
+    }
+
+    #[test]
+    #[serial]
+    fn test_policy_change_applies_to_existing_wrappers() {
+        let address = LoggedAddress(ADDRESS);
+        LogPrivacy::set(LogPrivacy::Full);
+        assert_eq!(address.to_string(), ADDRESS);
+        LogPrivacy::set(LogPrivacy::Minimal);
+        assert_eq!(address.to_string(), "[redacted]");
+        LogPrivacy::set(LogPrivacy::Full);
+    }
+
+    #[test]
+    #[serial]
+    fn test_debug_dumps_are_redacted_whole() {
+        let utxo = ("output", ADDRESS, 50_000u64);
+        LogPrivacy::set(LogPrivacy::RedactAmounts);
+        assert_eq!(LoggedDebug(&utxo).to_string(), format!("{:?}", utxo));
+        LogPrivacy::set(LogPrivacy::RedactAddresses);
+        assert_eq!(LoggedDebug(&utxo).to_string(), "[redacted]");
+        LogPrivacy::set(LogPrivacy::Full);
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            "redact-addresses".parse::<LogPrivacy>(),
+            Ok(LogPrivacy::RedactAddresses)
+        );
+        assert_eq!(
+            "REDACT_AMOUNTS".parse::<LogPrivacy>(),
+            Ok(LogPrivacy::RedactAmounts)
+        );
+        assert!("everything".parse::<LogPrivacy>().is_err());
+        for policy in [
+            LogPrivacy::Full,
+            LogPrivacy::RedactAmounts,
+            LogPrivacy::RedactAddresses,
+            LogPrivacy::Minimal,
+        ] {
+            assert_eq!(policy.to_string().parse::<LogPrivacy>(), Ok(policy));
+            assert_eq!(LogPrivacy::from_u8(policy as u8), policy);
+        }
+    }
+}
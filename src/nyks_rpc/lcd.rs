@@ -0,0 +1,485 @@
+//! Retrying GET requests against the chain LCD.
+//!
+//! Around chain upgrades and short halts the LCD answers `5xx` or drops
+//! connections for a few seconds. [`get`] retries those failures with
+//! exponential backoff and jitter, or waits for the server's `Retry-After`
+//! when one is sent. Any other answer is returned on the first attempt:
+//! `404` because "account/tx not indexed yet" has its own handling in the
+//! callers, any other `4xx` because repeating a bad request cannot help.
+//!
+//! Each LCD origin (scheme, host and port) has a degraded flag. The first
+//! failed attempt sets it, logs one warning and emits [`LcdEvent::Degraded`];
+//! further failures are only logged at debug level. The next answered request
+//! clears it, logs the recovery and emits [`LcdEvent::Recovered`]. [`status`]
+//! and [`status_for`] report the current state, e.g. for `/readyz`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use reqwest::{Client, Response, header::RETRY_AFTER};
+use serde::Serialize;
+
+pub use crate::config::LcdRetryPolicy;
+use crate::telemetry::WithTraceContext;
+
+/// Longest response body kept in a failure reason.
+const MAX_REASON_BODY: usize = 200;
+
+static POLICY: RwLock<Option<LcdRetryPolicy>> = RwLock::new(None);
+static ENV_POLICY: LazyLock<LcdRetryPolicy> = LazyLock::new(LcdRetryPolicy::from_env);
+static STATES: LazyLock<Mutex<HashMap<String, LcdStatus>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static LISTENERS: RwLock<Vec<Listener>> = RwLock::new(Vec::new());
+
+type Listener = Arc<dyn Fn(&LcdEvent) + Send + Sync>;
+
+impl LcdRetryPolicy {
+    /// Policy used by [`get`]: the last one passed to [`set`](Self::set), or
+    /// [`from_env`](Self::from_env) as read on first use.
+    pub fn current() -> Self {
+        POLICY
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_else(|| ENV_POLICY.clone())
+    }
+
+    /// Replace the policy for every LCD request started from now on.
+    pub fn set(policy: LcdRetryPolicy) {
+        *POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(policy);
+    }
+}
+
+/// Why [`get`] gave up.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LcdError {
+    #[error("Invalid LCD request to {endpoint}: {message}")]
+    Request { endpoint: String, message: String },
+    #[error("LCD {endpoint} unavailable after {attempts} attempts: {last_error}")]
+    Unavailable {
+        endpoint: String,
+        attempts: u32,
+        last_error: String,
+    },
+}
+
+/// Degraded/recovered state of one LCD origin.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LcdStatus {
+    pub endpoint: String,
+    pub degraded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub degraded_since: Option<DateTime<Utc>>,
+    /// Failed attempts since the endpoint became degraded.
+    pub failed_attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success: Option<DateTime<Utc>>,
+}
+
+impl LcdStatus {
+    fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            degraded: false,
+            degraded_since: None,
+            failed_attempts: 0,
+            last_error: None,
+            last_success: None,
+        }
+    }
+}
+
+/// Transition of an LCD origin into or out of degraded mode.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LcdEvent {
+    Degraded {
+        endpoint: String,
+        reason: String,
+        at: DateTime<Utc>,
+    },
+    Recovered {
+        endpoint: String,
+        degraded_since: DateTime<Utc>,
+        failed_attempts: u32,
+        at: DateTime<Utc>,
+    },
+}
+
+impl LcdEvent {
+    pub fn endpoint(&self) -> &str {
+        match self {
+            LcdEvent::Degraded { endpoint, .. } | LcdEvent::Recovered { endpoint, .. } => endpoint,
+        }
+    }
+}
+
+/// Call `listener` on every [`LcdEvent`] for the rest of the process.
+pub fn on_event(listener: impl Fn(&LcdEvent) + Send + Sync + 'static) {
+    LISTENERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::new(listener));
+}
+
+/// State of every LCD origin queried so far, sorted by endpoint.
+pub fn status() -> Vec<LcdStatus> {
+    let mut all: Vec<LcdStatus> = STATES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .cloned()
+        .collect();
+    all.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+    all
+}
+
+/// State of the origin of `lcd_endpoint`, or `None` if it was never queried.
+pub fn status_for(lcd_endpoint: &str) -> Option<LcdStatus> {
+    STATES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&origin(lcd_endpoint))
+        .cloned()
+}
+
+/// GET `url` from the LCD with the [current](LcdRetryPolicy::current) policy.
+pub async fn get(url: &str) -> Result<Response, LcdError> {
+    get_with(&Client::new(), url, &LcdRetryPolicy::current()).await
+}
+
+/// [`get`] with an explicit client and policy.
+pub async fn get_with(
+    client: &Client,
+    url: &str,
+    policy: &LcdRetryPolicy,
+) -> Result<Response, LcdError> {
+    let endpoint = origin(url);
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let sent = client
+            .get(url)
+            .header("accept", "application/json")
+            .with_trace_context()
+            .send()
+            .await;
+        let (reason, retry_after) = match sent {
+            Ok(response) if !response.status().is_server_error() => {
+                record_success(&endpoint);
+                return Ok(response);
+            }
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, Utc::now()));
+                let body = response.text().await.unwrap_or_default();
+                (format!("{}: {}", status, truncate(&body)), retry_after)
+            }
+            Err(e) if e.is_builder() => {
+                return Err(LcdError::Request {
+                    endpoint,
+                    message: e.to_string(),
+                });
+            }
+            Err(e) => (e.to_string(), None),
+        };
+        record_failure(&endpoint, &reason);
+        if attempt >= max_attempts {
+            return Err(LcdError::Unavailable {
+                endpoint,
+                attempts: attempt,
+                last_error: reason,
+            });
+        }
+        let delay = match retry_after {
+            Some(wait) => wait.min(policy.max_retry_after),
+            None => policy.backoff.delay(attempt),
+        };
+        debug!(
+            "LCD {} attempt {}/{} failed ({}); retrying in {:?}",
+            endpoint, attempt, max_attempts, reason, delay
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// `Retry-After` as delay-seconds or an HTTP date relative to `now`.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value)
+        .ok()?
+        .with_timezone(&Utc);
+    Some((at - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Scheme, host and port of `url`; the whole string if it does not parse.
+fn origin(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .map(|url| url.origin().ascii_serialization())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+fn truncate(body: &str) -> &str {
+    let mut end = body.len().min(MAX_REASON_BODY);
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    body[..end].trim()
+}
+
+fn record_failure(endpoint: &str, reason: &str) {
+    let now = Utc::now();
+    let event = {
+        let mut states = STATES.lock().unwrap_or_else(|e| e.into_inner());
+        let state = states
+            .entry(endpoint.to_string())
+            .or_insert_with(|| LcdStatus::new(endpoint));
+        state.failed_attempts += 1;
+        state.last_error = Some(reason.to_string());
+        if state.degraded {
+            None
+        } else {
+            state.degraded = true;
+            state.degraded_since = Some(now);
+            Some(LcdEvent::Degraded {
+                endpoint: endpoint.to_string(),
+                reason: reason.to_string(),
+                at: now,
+            })
+        }
+    };
+    if let Some(event) = event {
+        warn!(
+            "LCD {} degraded ({}); retrying with backoff",
+            endpoint, reason
+        );
+        emit(&event);
+    }
+}
+
+fn record_success(endpoint: &str) {
+    let now = Utc::now();
+    let event = {
+        let mut states = STATES.lock().unwrap_or_else(|e| e.into_inner());
+        let state = states
+            .entry(endpoint.to_string())
+            .or_insert_with(|| LcdStatus::new(endpoint));
+        state.last_success = Some(now);
+        let event = match (state.degraded, state.degraded_since) {
+            (true, Some(since)) => Some(LcdEvent::Recovered {
+                endpoint: endpoint.to_string(),
+                degraded_since: since,
+                failed_attempts: state.failed_attempts,
+                at: now,
+            }),
+            _ => None,
+        };
+        state.degraded = false;
+        state.degraded_since = None;
+        state.failed_attempts = 0;
+        state.last_error = None;
+        event
+    };
+    if let Some(LcdEvent::Recovered {
+        degraded_since,
+        failed_attempts,
+        ..
+    }) = &event
+    {
+        info!(
+            "LCD {} recovered after {} failed attempts over {}s",
+            endpoint,
+            failed_attempts,
+            (now - *degraded_since).num_seconds()
+        );
+    }
+    if let Some(event) = event {
+        emit(&event);
+    }
+}
+
+fn emit(event: &LcdEvent) {
+    let listeners: Vec<Listener> = LISTENERS.read().unwrap_or_else(|e| e.into_inner()).clone();
+    for listener in listeners {
+        listener(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Backoff;
+    use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+
+    /// A chain answering every path with `responses` in order, the last
+    /// repeating.
+    fn lcd(responses: Vec<MockResponse>) -> (MockChain, String) {
+        let chain = MockChain::spawn();
+        chain.route("/", responses);
+        let base = chain.url().to_string();
+        (chain, base)
+    }
+
+    fn fast_policy(max_attempts: u32) -> LcdRetryPolicy {
+        LcdRetryPolicy {
+            max_attempts,
+            backoff: Backoff {
+                initial_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(5),
+                factor: 2.0,
+                jitter: 0.2,
+            },
+            max_retry_after: Duration::from_secs(2),
+        }
+    }
+
+    /// Collect events for `endpoint` only; other tests share the listener list.
+    fn capture_events(endpoint: &str) -> Arc<Mutex<Vec<LcdEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let endpoint = endpoint.to_string();
+        on_event(move |event| {
+            if event.endpoint() == endpoint {
+                sink.lock().unwrap().push(event.clone());
+            }
+        });
+        events
+    }
+
+    #[tokio::test]
+    async fn test_503_burst_then_recovery() {
+        let (chain, base) = lcd(vec![
+            MockResponse::status(503, "upgrading"),
+            MockResponse::status(503, "upgrading"),
+            MockResponse::status(503, "upgrading"),
+            MockResponse::ok(r#"{"balances":[]}"#),
+        ]);
+        let events = capture_events(&base);
+        let url = format!("{}/cosmos/bank/v1beta1/balances/x", base);
+
+        let response = get_with(&Client::new(), &url, &fast_policy(6))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(chain.count("/"), 4);
+
+        let events = events.lock().unwrap().clone();
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(matches!(events[0], LcdEvent::Degraded { .. }));
+        match &events[1] {
+            LcdEvent::Recovered {
+                failed_attempts, ..
+            } => assert_eq!(*failed_attempts, 3),
+            other => panic!("unexpected event: {:?}", other),
+        }
+        let status = status_for(&base).unwrap();
+        assert!(!status.degraded);
+        assert_eq!(status.failed_attempts, 0);
+        assert!(status.last_success.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_attempts_are_bounded_and_state_stays_degraded() {
+        let (chain, base) = lcd(vec![MockResponse::status(502, "bad gateway")]);
+        let events = capture_events(&base);
+
+        let err = get_with(&Client::new(), &format!("{}/status", base), &fast_policy(3))
+            .await
+            .unwrap_err();
+        match err {
+            LcdError::Unavailable {
+                attempts,
+                last_error,
+                ..
+            } => {
+                assert_eq!(attempts, 3);
+                assert!(last_error.contains("502"), "{}", last_error);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(chain.count("/"), 3);
+        assert_eq!(events.lock().unwrap().len(), 1);
+        let status = status_for(&base).unwrap();
+        assert!(status.degraded);
+        assert_eq!(status.failed_attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_and_404_are_not_retried() {
+        let (chain, base) = lcd(vec![MockResponse::status(400, r#"{"code":3}"#)]);
+        let events = capture_events(&base);
+        let response = get_with(&Client::new(), &format!("{}/bad", base), &fast_policy(5))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(chain.count("/"), 1);
+
+        let (chain, missing) = lcd(vec![MockResponse::status(404, r#"{"code":5}"#)]);
+        let response = get_with(
+            &Client::new(),
+            &format!("{}/acct", missing),
+            &fast_policy(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(chain.count("/"), 1);
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_respected() {
+        let (chain, base) = lcd(vec![
+            MockResponse::status(503, "halted").header("Retry-After", "1"),
+            MockResponse::ok("{}"),
+        ]);
+        let started = std::time::Instant::now();
+        let response = get_with(&Client::new(), &format!("{}/status", base), &fast_policy(3))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(chain.count("/"), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_connection_errors_are_retried() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let err = get_with(&Client::new(), &format!("{}/status", base), &fast_policy(2))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, LcdError::Unavailable { attempts: 2, .. }));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:27:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_retry_after(" 5 ", now), Some(Duration::from_secs(5)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:26:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Local HTTP server standing in for the chain in offline tests.
+//!
+//! A [`MockChain`] listens on a free local port and answers each request
+//! from the responses routed to the longest path prefix it starts with, in
+//! order, the last one repeating. Unrouted paths get a `404` with the LCD's
+//! "not found" body, which is also how the LCD answers for a tx or account
+//! it has not indexed yet. Every request path is logged. A response can carry
+//! extra headers, e.g. `Retry-After`.
+//!
+//! One server can be both the LCD and, through a `/rpc` route and
+//! [`rpc_url`](MockChain::rpc_url), the Tendermint RPC.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// Body of an LCD `404` for something it has not indexed.
+pub(crate) const NOT_FOUND: &str = r#"{"code":5,"message":"not found","details":[]}"#;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub body: String,
+    /// Sent after `Content-Type` and `Content-Length`.
+    pub headers: Vec<(String, String)>,
+}
+
+impl MockResponse {
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self::status(200, body)
+    }
+
+    pub fn status(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Also send header `name: value`.
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct Routes {
+    /// Responses by path prefix, with the number already served.
+    responses: HashMap<String, (Vec<MockResponse>, usize)>,
+    requests: Vec<String>,
+}
+
+impl Routes {
+    fn next(&mut self, path: &str) -> MockResponse {
+        self.requests.push(path.to_string());
+        let route = self
+            .responses
+            .iter_mut()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len());
+        match route {
+            Some((_, (responses, served))) if !responses.is_empty() => {
+                let response = responses[(*served).min(responses.len() - 1)].clone();
+                *served += 1;
+                response
+            }
+            _ => MockResponse::status(404, NOT_FOUND),
+        }
+    }
+}
+
+/// Scripted chain endpoints; see the [module docs](self). Clones share the
+/// routes and the request log.
+#[derive(Debug, Clone)]
+pub(crate) struct MockChain {
+    url: String,
+    routes: Arc<Mutex<Routes>>,
+}
+
+impl MockChain {
+    pub fn spawn() -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes = Arc::new(Mutex::new(Routes::default()));
+        let served = routes.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim_end().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                let _ = reader.read_exact(&mut body);
+                let path = request_line.split_whitespace().nth(1).unwrap_or("");
+                let response = served.lock().unwrap().next(path);
+                let headers: String = response
+                    .headers
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                    .collect();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+                    response.status,
+                    response.body.len(),
+                    headers,
+                    response.body
+                );
+            }
+        });
+        Self { url, routes }
+    }
+
+    /// Base URL, the LCD endpoint.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// RPC endpoint, answered by the `/rpc` route.
+    pub fn rpc_url(&self) -> String {
+        format!("{}/rpc", self.url)
+    }
+
+    /// Answer paths starting with `prefix` with `responses`, replacing the
+    /// route's earlier responses.
+    pub fn route(&self, prefix: &str, responses: Vec<MockResponse>) {
+        self.lock()
+            .responses
+            .insert(prefix.to_string(), (responses, 0));
+    }
+
+    /// Requests so far to paths starting with `prefix`.
+    pub fn count(&self, prefix: &str) -> usize {
+        self.lock()
+            .requests
+            .iter()
+            .filter(|path| path.starts_with(prefix))
+            .count()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Routes> {
+        self.routes.lock().unwrap()
+    }
+}
@@ -1 +1,4 @@
+pub mod lcd;
+#[cfg(test)]
+pub(crate) mod mock_chain;
 pub mod rpcclient;
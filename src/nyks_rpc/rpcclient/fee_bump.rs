@@ -0,0 +1,602 @@
+//! Fee bumping for chain transactions that stay unconfirmed.
+//!
+//! [`submit_with_fee_bump`] broadcasts a transaction, polls the LCD until it
+//! lands in a block, and when the wait runs out re-signs the same messages
+//! with the fee raised by [`FeeBumpPolicy::multiplier`] and broadcasts again,
+//! up to [`FeeBumpPolicy::max_bumps`] times. Every attempt reuses the sequence
+//! of the first one, so at most one of them can ever be included; before each
+//! resubmission all earlier attempts are checked again and a confirmed one is
+//! returned instead of broadcasting a replacement.
+//!
+//! A node only accepts a replacement once it has dropped the original from
+//! its mempool. Until then CheckTx answers with a sequence mismatch, which is
+//! recorded as an attempt and treated as still pending.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::sleep;
+
+use super::method::{Method, TX_FEE_NYKS};
+use super::txrequest::{RpcBody, RpcRequest, TxParams};
+use super::txresult::{parse_tx_response, TxResponse, TxResult};
+use crate::nyks_rpc::lcd;
+
+/// CheckTx code for a fee below the node's minimum gas price (ErrInsufficientFee).
+const CODE_INSUFFICIENT_FEE: u32 = 13;
+/// CheckTx code for a sequence already used by a tx in the mempool (ErrWrongSequence).
+const CODE_WRONG_SEQUENCE: u32 = 32;
+
+/// How a stuck transaction is re-submitted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeBumpPolicy {
+    /// Fee of the first attempt, in nyks.
+    pub initial_fee_nyks: u64,
+    /// Factor applied to the fee on every bump; the result is rounded up.
+    pub multiplier: f64,
+    /// No attempt pays more than this, in nyks.
+    pub max_fee_nyks: u64,
+    /// Re-submissions after the first attempt.
+    pub max_bumps: u32,
+    /// Status polls per attempt before it counts as stuck.
+    pub confirm_polls: u32,
+    pub poll_interval: Duration,
+}
+
+impl Default for FeeBumpPolicy {
+    fn default() -> Self {
+        Self {
+            initial_fee_nyks: TX_FEE_NYKS,
+            multiplier: 1.5,
+            max_fee_nyks: TX_FEE_NYKS * 10,
+            max_bumps: 3,
+            confirm_polls: 20,
+            poll_interval: Duration::from_secs(3),
+        }
+    }
+}
+
+impl FeeBumpPolicy {
+    /// Fee of the attempt after one paying `fee`, or `None` once `fee` has
+    /// reached [`max_fee_nyks`](Self::max_fee_nyks). Always at least one nyks
+    /// more than `fee`.
+    pub fn next_fee(&self, fee: u64) -> Option<u64> {
+        if fee >= self.max_fee_nyks {
+            return None;
+        }
+        let bumped = (fee as f64 * self.multiplier).ceil() as u64;
+        Some(bumped.max(fee + 1).min(self.max_fee_nyks))
+    }
+
+    /// Fees of every attempt the policy allows, first attempt included.
+    pub fn fee_schedule(&self) -> Vec<u64> {
+        let mut fees = vec![self.initial_fee_nyks];
+        while fees.len() <= self.max_bumps as usize {
+            match self.next_fee(*fees.last().unwrap()) {
+                Some(fee) => fees.push(fee),
+                None => break,
+            }
+        }
+        fees
+    }
+}
+
+/// One broadcast of the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TxAttempt {
+    pub tx_hash: String,
+    pub fee_nyks: u64,
+    /// CheckTx code returned by the broadcast.
+    pub code: u32,
+}
+
+impl TxAttempt {
+    /// Whether the node took the attempt into its mempool.
+    pub fn accepted(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// A transaction that made it into a block, with every attempt it took.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeBumpReceipt {
+    /// Hash of the attempt that confirmed.
+    pub tx_hash: String,
+    pub fee_nyks: u64,
+    /// All attempts in broadcast order, the confirmed one included.
+    pub attempts: Vec<TxAttempt>,
+}
+
+impl FeeBumpReceipt {
+    pub fn bumps(&self) -> usize {
+        self.attempts.len().saturating_sub(1)
+    }
+}
+
+/// Where a broadcast transaction stands on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Not in a block yet.
+    Pending,
+    /// Included in a block; `code` 0 means it succeeded.
+    Committed { code: u32, raw_log: String },
+}
+
+/// Hashes of `attempts`, comma separated.
+struct AttemptHashes<'a>(&'a [TxAttempt]);
+
+impl fmt::Display for AttemptHashes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, attempt) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(&attempt.tx_hash)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FeeBumpError {
+    /// A broadcast failed; earlier `attempts` may still confirm.
+    #[error("failed to broadcast tx: {error}")]
+    Broadcast {
+        error: String,
+        attempts: Vec<TxAttempt>,
+    },
+    /// CheckTx refused an attempt for a reason a higher fee does not fix.
+    /// Earlier `attempts` may still confirm.
+    #[error("tx {tx_hash} rejected with code {code}")]
+    Rejected {
+        tx_hash: String,
+        code: u32,
+        attempts: Vec<TxAttempt>,
+    },
+    /// An attempt was included in a block but failed; nothing was resubmitted.
+    #[error("tx {tx_hash} failed with code {code}: {raw_log}")]
+    Failed {
+        tx_hash: String,
+        code: u32,
+        raw_log: String,
+        attempts: Vec<TxAttempt>,
+    },
+    /// No attempt confirmed within the policy's bumps and fee bound.
+    #[error(
+        "tx still unconfirmed after {} attempts: {}",
+        .attempts.len(),
+        AttemptHashes(.attempts)
+    )]
+    StuckTx { attempts: Vec<TxAttempt> },
+}
+
+impl FeeBumpError {
+    /// Every attempt broadcast before the error.
+    pub fn attempts(&self) -> &[TxAttempt] {
+        match self {
+            FeeBumpError::Broadcast { attempts, .. }
+            | FeeBumpError::Rejected { attempts, .. }
+            | FeeBumpError::Failed { attempts, .. }
+            | FeeBumpError::StuckTx { attempts } => attempts,
+        }
+    }
+}
+
+/// Broadcast with a fee bump whenever the confirmation wait runs out.
+///
+/// `broadcast(fee_nyks)` signs the transaction with that fee, always with the
+/// same sequence, broadcasts it and returns its hash and CheckTx code.
+/// `status(tx_hash)` looks the hash up on chain; lookup errors count as
+/// pending.
+pub async fn submit_with_fee_bump<B, BFut, S, SFut>(
+    policy: &FeeBumpPolicy,
+    mut broadcast: B,
+    mut status: S,
+) -> Result<FeeBumpReceipt, FeeBumpError>
+where
+    B: FnMut(u64) -> BFut,
+    BFut: Future<Output = Result<(String, u32), String>>,
+    S: FnMut(String) -> SFut,
+    SFut: Future<Output = Result<TxStatus, String>>,
+{
+    let mut attempts: Vec<TxAttempt> = Vec::new();
+    let mut fee = policy.initial_fee_nyks;
+    loop {
+        // An earlier attempt may have landed after its wait ran out.
+        if let Some(done) = find_committed(&attempts, &mut status).await {
+            return done;
+        }
+
+        let (tx_hash, code) = match broadcast(fee).await {
+            Ok(sent) => sent,
+            Err(error) => return Err(FeeBumpError::Broadcast { error, attempts }),
+        };
+        let attempt = TxAttempt {
+            tx_hash,
+            fee_nyks: fee,
+            code,
+        };
+        debug!(
+            "fee bump attempt {}: {} with fee {} nyks, code {}",
+            attempts.len() + 1,
+            attempt.tx_hash,
+            fee,
+            code
+        );
+        let retry_immediately = match code {
+            0 => false,
+            CODE_INSUFFICIENT_FEE => true,
+            CODE_WRONG_SEQUENCE if !attempts.is_empty() => false,
+            _ => {
+                return Err(FeeBumpError::Rejected {
+                    tx_hash: attempt.tx_hash,
+                    code,
+                    attempts,
+                });
+            }
+        };
+        attempts.push(attempt);
+
+        if !retry_immediately {
+            for _ in 0..policy.confirm_polls {
+                sleep(policy.poll_interval).await;
+                if let Some(done) = find_committed(&attempts, &mut status).await {
+                    return done;
+                }
+            }
+        }
+
+        let bumps = attempts.len() as u32 - 1;
+        match policy.next_fee(fee) {
+            Some(next) if bumps < policy.max_bumps => {
+                warn!(
+                    "tx {} not confirmed, resubmitting with fee {} nyks (bump {}/{})",
+                    attempts.last().unwrap().tx_hash,
+                    next,
+                    bumps + 1,
+                    policy.max_bumps
+                );
+                fee = next;
+            }
+            _ => {
+                if let Some(done) = find_committed(&attempts, &mut status).await {
+                    return done;
+                }
+                return Err(FeeBumpError::StuckTx { attempts });
+            }
+        }
+    }
+}
+
+/// Outcome of the first accepted attempt found in a block, if any.
+async fn find_committed<S, SFut>(
+    attempts: &[TxAttempt],
+    status: &mut S,
+) -> Option<Result<FeeBumpReceipt, FeeBumpError>>
+where
+    S: FnMut(String) -> SFut,
+    SFut: Future<Output = Result<TxStatus, String>>,
+{
+    for attempt in attempts.iter().filter(|a| a.accepted()) {
+        match status(attempt.tx_hash.clone()).await {
+            Ok(TxStatus::Pending) => {}
+            Ok(TxStatus::Committed { code: 0, .. }) => {
+                if attempts.len() > 1 {
+                    info!(
+                        "tx {} confirmed after {} attempts: {}",
+                        attempt.tx_hash,
+                        attempts.len(),
+                        AttemptHashes(attempts)
+                    );
+                }
+                return Some(Ok(FeeBumpReceipt {
+                    tx_hash: attempt.tx_hash.clone(),
+                    fee_nyks: attempt.fee_nyks,
+                    attempts: attempts.to_vec(),
+                }));
+            }
+            Ok(TxStatus::Committed { code, raw_log }) => {
+                return Some(Err(FeeBumpError::Failed {
+                    tx_hash: attempt.tx_hash.clone(),
+                    code,
+                    raw_log,
+                    attempts: attempts.to_vec(),
+                }));
+            }
+            Err(e) => debug!("status of tx {} unavailable: {}", attempt.tx_hash, e),
+        }
+    }
+    None
+}
+
+/// Broadcast a signed, base64-encoded tx with `broadcast_tx_sync` and return
+/// its hash and CheckTx code.
+pub async fn broadcast_signed_tx(
+    signed_tx: String,
+    rpc_endpoint: &str,
+) -> Result<(String, u32), String> {
+    let result = broadcast_tx_sync(signed_tx, rpc_endpoint).await?;
+    Ok((result.hash, result.code))
+}
+
+/// Broadcast `signed_tx` with `broadcast_tx_sync` and return its CheckTx result.
+pub async fn broadcast_tx_sync(signed_tx: String, rpc_endpoint: &str) -> Result<TxResult, String> {
+    let method = Method::broadcast_tx_sync;
+    let (tx_send, _): (RpcBody<TxParams>, String) =
+        RpcRequest::new_with_data(TxParams::new(signed_tx.clone()), method, signed_tx);
+    let rpc_endpoint = rpc_endpoint.to_string();
+    let response = tokio::task::spawn_blocking(crate::telemetry::in_current_context(move || {
+        tx_send.send(rpc_endpoint)
+    }))
+    .await
+    .map_err(|e| format!("RPC send failed: {}", e))?
+    .map_err(|e| format!("RPC error: {}", e))?;
+    match parse_tx_response(&method, response).map_err(|e| e.to_string())? {
+        TxResponse::BroadcastTxSync(result) => Ok(result),
+        other => Err(format!("unexpected broadcast response: {:?}", other)),
+    }
+}
+
+/// Look `tx_hash` up on the LCD once.
+pub async fn query_tx_status(tx_hash: &str, lcd_endpoint: &str) -> Result<TxStatus, String> {
+    let url = format!("{}/cosmos/tx/v1beta1/txs/{}", lcd_endpoint, tx_hash);
+    let body: Value = lcd::get(&url)
+        .await
+        .map_err(|e| format!("Failed to query tx status: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse tx status response: {}", e))?;
+    parse_tx_status(&body)
+}
+
+fn parse_tx_status(body: &Value) -> Result<TxStatus, String> {
+    let Some(tx_response) = body.get("tx_response") else {
+        return Ok(TxStatus::Pending);
+    };
+    let code = tx_response
+        .get("code")
+        .and_then(|c| c.as_u64())
+        .and_then(|c| u32::try_from(c).ok())
+        .ok_or_else(|| "Missing code in tx_response".to_string())?;
+    let raw_log = tx_response
+        .get("raw_log")
+        .and_then(|l| l.as_str())
+        .unwrap_or_default()
+        .to_string();
+    Ok(TxStatus::Committed { code, raw_log })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nyks_rpc::mock_chain::{MockChain, MockResponse, NOT_FOUND};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn fast_policy() -> FeeBumpPolicy {
+        FeeBumpPolicy {
+            confirm_polls: 2,
+            poll_interval: Duration::ZERO,
+            ..FeeBumpPolicy::default()
+        }
+    }
+
+    fn sync_response(hash: &str, code: u32) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":"1","result":{{"code":{},"data":"","log":"[]","codespace":"","hash":"{}"}}}}"#,
+            code, hash
+        )
+    }
+
+    fn committed_response(code: u32) -> String {
+        format!(
+            r#"{{"tx_response":{{"code":{},"raw_log":"","txhash":"X"}}}}"#,
+            code
+        )
+    }
+
+    /// Chain whose RPC answers broadcasts with `broadcasts` and whose LCD
+    /// answers tx lookups with `statuses[hash]`; see [`MockChain`].
+    fn spawn_mock_chain(
+        broadcasts: Vec<String>,
+        statuses: HashMap<String, Vec<String>>,
+    ) -> MockChain {
+        let chain = MockChain::spawn();
+        let broadcasts = broadcasts.into_iter().map(MockResponse::ok).collect();
+        chain.route("/rpc", broadcasts);
+        for (hash, bodies) in statuses {
+            let path = format!("/cosmos/tx/v1beta1/txs/{}", hash);
+            chain.route(&path, bodies.into_iter().map(MockResponse::ok).collect());
+        }
+        chain
+    }
+
+    async fn submit_to(
+        chain: &MockChain,
+        policy: &FeeBumpPolicy,
+        fees: Arc<Mutex<Vec<u64>>>,
+    ) -> Result<FeeBumpReceipt, FeeBumpError> {
+        let rpc = chain.rpc_url();
+        let base = chain.url();
+        submit_with_fee_bump(
+            policy,
+            |fee| {
+                fees.lock().unwrap().push(fee);
+                let rpc = rpc.clone();
+                async move { broadcast_signed_tx(format!("signed-with-{}", fee), &rpc).await }
+            },
+            |hash| {
+                let base = base.to_string();
+                async move { query_tx_status(&hash, &base).await }
+            },
+        )
+        .await
+    }
+
+    #[test]
+    fn test_fee_schedule_is_deterministic_and_bounded() {
+        let policy = FeeBumpPolicy::default();
+        assert_eq!(policy.fee_schedule(), vec![1_000, 1_500, 2_250, 3_375]);
+
+        let capped = FeeBumpPolicy {
+            max_fee_nyks: 2_000,
+            max_bumps: 10,
+            ..FeeBumpPolicy::default()
+        };
+        assert_eq!(capped.fee_schedule(), vec![1_000, 1_500, 2_000]);
+        assert_eq!(capped.next_fee(2_000), None);
+
+        let flat = FeeBumpPolicy {
+            initial_fee_nyks: 1,
+            multiplier: 1.0,
+            ..FeeBumpPolicy::default()
+        };
+        assert_eq!(flat.fee_schedule(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_parse_tx_status() {
+        let pending: Value = serde_json::from_str(NOT_FOUND).unwrap();
+        assert_eq!(parse_tx_status(&pending), Ok(TxStatus::Pending));
+        let ok: Value = serde_json::from_str(&committed_response(0)).unwrap();
+        assert_eq!(
+            parse_tx_status(&ok),
+            Ok(TxStatus::Committed {
+                code: 0,
+                raw_log: String::new()
+            })
+        );
+        let bad = serde_json::json!({ "tx_response": { "code": "zero" } });
+        assert!(parse_tx_status(&bad).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bump_then_confirm() {
+        // The first attempt never shows up; the bumped one confirms on its
+        // second poll.
+        let statuses = HashMap::from([(
+            "BBBB".to_string(),
+            vec![NOT_FOUND.to_string(), committed_response(0)],
+        )]);
+        let chain = spawn_mock_chain(
+            vec![sync_response("AAAA", 0), sync_response("BBBB", 0)],
+            statuses,
+        );
+        let fees = Arc::new(Mutex::new(Vec::new()));
+        let receipt = submit_to(&chain, &fast_policy(), fees.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(receipt.tx_hash, "BBBB");
+        assert_eq!(receipt.fee_nyks, 1_500);
+        assert_eq!(receipt.bumps(), 1);
+        let hashes: Vec<_> = receipt
+            .attempts
+            .iter()
+            .map(|a| a.tx_hash.as_str())
+            .collect();
+        assert_eq!(hashes, ["AAAA", "BBBB"]);
+        assert_eq!(*fees.lock().unwrap(), vec![1_000, 1_500]);
+    }
+
+    #[tokio::test]
+    async fn test_max_bumps_reached_is_stuck_tx_with_every_hash() {
+        let chain = spawn_mock_chain(
+            vec![
+                sync_response("AAAA", 0),
+                // The node still holds AAAA, so the replacements bounce.
+                sync_response("BBBB", CODE_WRONG_SEQUENCE),
+                sync_response("CCCC", CODE_WRONG_SEQUENCE),
+                sync_response("DDDD", CODE_WRONG_SEQUENCE),
+            ],
+            HashMap::new(),
+        );
+        let fees = Arc::new(Mutex::new(Vec::new()));
+        let err = submit_to(&chain, &fast_policy(), fees.clone())
+            .await
+            .unwrap_err();
+
+        let FeeBumpError::StuckTx { attempts } = &err else {
+            panic!("expected StuckTx, got {:?}", err);
+        };
+        let hashes: Vec<_> = attempts.iter().map(|a| a.tx_hash.as_str()).collect();
+        assert_eq!(hashes, ["AAAA", "BBBB", "CCCC", "DDDD"]);
+        assert_eq!(
+            err.to_string(),
+            "tx still unconfirmed after 4 attempts: AAAA, BBBB, CCCC, DDDD"
+        );
+        assert_eq!(*fees.lock().unwrap(), vec![1_000, 1_500, 2_250, 3_375]);
+    }
+
+    #[tokio::test]
+    async fn test_original_confirming_before_resubmit_is_not_rebroadcast() {
+        // AAAA stays unconfirmed for the whole wait (two polls), then shows up
+        // in the check made right before a replacement would go out.
+        let statuses = HashMap::from([(
+            "AAAA".to_string(),
+            vec![
+                NOT_FOUND.to_string(),
+                NOT_FOUND.to_string(),
+                committed_response(0),
+            ],
+        )]);
+        let chain = spawn_mock_chain(vec![sync_response("AAAA", 0)], statuses);
+        let fees = Arc::new(Mutex::new(Vec::new()));
+        let receipt = submit_to(&chain, &fast_policy(), fees.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(receipt.tx_hash, "AAAA");
+        assert_eq!(receipt.bumps(), 0);
+        assert_eq!(*fees.lock().unwrap(), vec![1_000]);
+        assert_eq!(chain.count("/rpc"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_in_block_and_rejected_are_not_bumped() {
+        let statuses = HashMap::from([("AAAA".to_string(), vec![committed_response(11)])]);
+        let chain = spawn_mock_chain(vec![sync_response("AAAA", 0)], statuses);
+        let fees = Arc::new(Mutex::new(Vec::new()));
+        let err = submit_to(&chain, &fast_policy(), fees.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FeeBumpError::Failed { code: 11, .. }));
+        assert_eq!(err.attempts().len(), 1);
+
+        let chain = spawn_mock_chain(vec![sync_response("AAAA", 5)], HashMap::new());
+        let err = submit_to(&chain, &fast_policy(), Arc::new(Mutex::new(Vec::new())))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            FeeBumpError::Rejected {
+                tx_hash: "AAAA".to_string(),
+                code: 5,
+                attempts: Vec::new(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_fee_bumps_without_waiting() {
+        let statuses = HashMap::from([("BBBB".to_string(), vec![committed_response(0)])]);
+        let chain = spawn_mock_chain(
+            vec![
+                sync_response("AAAA", CODE_INSUFFICIENT_FEE),
+                sync_response("BBBB", 0),
+            ],
+            statuses,
+        );
+        let receipt = submit_to(&chain, &fast_policy(), Arc::new(Mutex::new(Vec::new())))
+            .await
+            .unwrap();
+        assert_eq!(receipt.tx_hash, "BBBB");
+        assert!(!receipt.attempts[0].accepted());
+        // AAAA never entered the mempool, so it is never looked up.
+        assert_eq!(chain.count("/cosmos/tx/v1beta1/txs/AAAA"), 0);
+    }
+}
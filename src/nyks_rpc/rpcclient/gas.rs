@@ -0,0 +1,100 @@
+//! Gas estimation through the LCD `simulate` endpoint.
+//!
+//! With [`TxFeeConfig::gas_adjustment`] set, [`estimate_tx_fee`] signs a
+//! draft of the transaction with the configured fee, posts it to
+//! `/cosmos/tx/v1beta1/simulate` and returns the fee to sign the real
+//! transaction with ([`TxFeeConfig::adjusted`]). The draft must carry the
+//! sequence of the real transaction: the node checks it during simulation.
+
+use log::debug;
+use reqwest::Client;
+use serde_json::{Value, json};
+
+use crate::config::TxFeeConfig;
+use crate::telemetry::WithTraceContext;
+
+/// Gas used by `tx_base64`, as simulated by the LCD at `lcd_endpoint`.
+pub async fn simulate_gas(lcd_endpoint: &str, tx_base64: &str) -> Result<u64, String> {
+    let response = Client::new()
+        .post(format!("{}/cosmos/tx/v1beta1/simulate", lcd_endpoint))
+        .with_trace_context()
+        .json(&json!({ "tx_bytes": tx_base64 }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to simulate tx: {}", e))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read simulate response: {}", e))?;
+    if !status.is_success() {
+        return Err(format!(
+            "Tx simulation failed with status {}: {}",
+            status, body
+        ));
+    }
+    parse_simulate_response(&body)
+}
+
+/// `gas_info.gas_used` of a simulate response. The LCD encodes it as a
+/// string, older nodes as a number.
+pub fn parse_simulate_response(body: &str) -> Result<u64, String> {
+    let value: Value =
+        serde_json::from_str(body).map_err(|e| format!("Invalid simulate response: {}", e))?;
+    let gas_used = &value["gas_info"]["gas_used"];
+    gas_used
+        .as_str()
+        .and_then(|gas| gas.parse().ok())
+        .or_else(|| gas_used.as_u64())
+        .ok_or_else(|| format!("Simulate response has no gas_info.gas_used: {}", body))
+}
+
+/// The fee to sign with: `fee` itself without a gas adjustment, otherwise
+/// `fee` adjusted to the gas a draft signed by `sign` uses in simulation.
+pub async fn estimate_tx_fee<F>(
+    fee: &TxFeeConfig,
+    lcd_endpoint: &str,
+    sign: F,
+) -> Result<TxFeeConfig, String>
+where
+    F: FnOnce(&TxFeeConfig) -> Result<String, String>,
+{
+    if !fee.simulates() {
+        return Ok(fee.clone());
+    }
+    let gas_used = simulate_gas(lcd_endpoint, &sign(fee)?).await?;
+    let adjusted = fee.adjusted(gas_used);
+    debug!(
+        "simulated gas {} -> gas limit {}, fee {}{}",
+        gas_used, adjusted.gas_limit, adjusted.fee_amount, adjusted.fee_denom
+    );
+    Ok(adjusted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simulate_response() {
+        let body = r#"{"gas_info":{"gas_wanted":"2000000","gas_used":"81234"},"result":{}}"#;
+        assert_eq!(parse_simulate_response(body), Ok(81_234));
+        assert_eq!(
+            parse_simulate_response(r#"{"gas_info":{"gas_used":500}}"#),
+            Ok(500)
+        );
+        assert!(parse_simulate_response(r#"{"code":3,"message":"out of gas"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_estimate_without_adjustment_skips_simulation() {
+        let fee = TxFeeConfig::default();
+        // The endpoint is never contacted and the draft never signed.
+        let estimated = estimate_tx_fee(&fee, "http://127.0.0.1:1", |_| {
+            Err("signed a draft".to_string())
+        })
+        .await
+        .unwrap();
+        assert_eq!(estimated, fee);
+    }
+}
@@ -77,6 +77,14 @@ pub enum MethodTypeURL {
     // ---- bank module ----
     MsgSend,
 
+    // ---- staking module ----
+    MsgDelegate,
+    MsgUndelegate,
+    MsgBeginRedelegate,
+
+    // ---- distribution module ----
+    MsgWithdrawDelegatorReward,
+
     // ---- bridge module ----
     MsgConfirmBtcDeposit,
     MsgRegisterBtcDepositAddress,
@@ -104,6 +112,12 @@ use cosmrs::{
     tx::{Body, Fee, SignDoc, SignerInfo},
 };
 use std::str::FromStr;
+
+use crate::config::TxFeeConfig;
+
+/// Fee, in nyks, that [`MethodTypeURL::sign_msg`] attaches to every transaction.
+pub const TX_FEE_NYKS: u64 = crate::config::fee::DEFAULT_FEE_AMOUNT;
+
 impl MethodTypeURL {
     pub fn type_url<T>(&self, msg: T) -> cosmrs::Any
     where
@@ -140,6 +154,42 @@ impl MethodTypeURL {
                 }
             }
 
+            // ---- staking module ----
+            MethodTypeURL::MsgDelegate => {
+                let mut buf = Vec::new();
+                msg.encode(&mut buf).expect("msg encoding failed");
+                cosmrs::Any {
+                    type_url: "/cosmos.staking.v1beta1.MsgDelegate".to_string(),
+                    value: buf,
+                }
+            }
+            MethodTypeURL::MsgUndelegate => {
+                let mut buf = Vec::new();
+                msg.encode(&mut buf).expect("msg encoding failed");
+                cosmrs::Any {
+                    type_url: "/cosmos.staking.v1beta1.MsgUndelegate".to_string(),
+                    value: buf,
+                }
+            }
+            MethodTypeURL::MsgBeginRedelegate => {
+                let mut buf = Vec::new();
+                msg.encode(&mut buf).expect("msg encoding failed");
+                cosmrs::Any {
+                    type_url: "/cosmos.staking.v1beta1.MsgBeginRedelegate".to_string(),
+                    value: buf,
+                }
+            }
+
+            // ---- distribution module ----
+            MethodTypeURL::MsgWithdrawDelegatorReward => {
+                let mut buf = Vec::new();
+                msg.encode(&mut buf).expect("msg encoding failed");
+                cosmrs::Any {
+                    type_url: "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward".to_string(),
+                    value: buf,
+                }
+            }
+
             // ---- bridge module ----
             MethodTypeURL::MsgRegisterBtcDepositAddress => {
                 let mut buf = Vec::new();
@@ -306,24 +356,100 @@ impl MethodTypeURL {
         account_number: u64,
         sk: SigningKey,
     ) -> Result<String, anyhow::Error> {
-        let body = Body::new(vec![any], "", 0u16);
+        self.sign_msg_with_fee::<T>(any, pk, sequence, account_number, sk, TX_FEE_NYKS)
+    }
+
+    /// [`sign_msg`](Self::sign_msg) with a fee of `fee_nyks` instead of [`TX_FEE_NYKS`].
+    pub fn sign_msg_with_fee<T>(
+        &self,
+        any: cosmrs::Any,
+        pk: PublicKey,
+        sequence: u64,
+        account_number: u64,
+        sk: SigningKey,
+        fee_nyks: u64,
+    ) -> Result<String, anyhow::Error> {
+        let fee = TxFeeConfig::default().with_fee_amount(fee_nyks);
+        self.sign_msg_with_fee_config::<T>(any, pk, sequence, account_number, sk, &fee)
+    }
+
+    /// [`sign_msg`](Self::sign_msg) with the fee coin and gas limit of `fee`.
+    pub fn sign_msg_with_fee_config<T>(
+        &self,
+        any: cosmrs::Any,
+        pk: PublicKey,
+        sequence: u64,
+        account_number: u64,
+        sk: SigningKey,
+        fee: &TxFeeConfig,
+    ) -> Result<String, anyhow::Error> {
+        sign_msgs_with_fee_config(vec![any], pk, sequence, account_number, sk, fee)
+    }
+}
+
+/// Sign a transaction carrying all of `msgs`, in order, with the fee coin and
+/// gas limit of `fee`. Returns the base64-encoded tx bytes.
+pub fn sign_msgs_with_fee_config(
+    msgs: Vec<cosmrs::Any>,
+    pk: PublicKey,
+    sequence: u64,
+    account_number: u64,
+    sk: SigningKey,
+    fee: &TxFeeConfig,
+) -> Result<String, anyhow::Error> {
+    let body = Body::new(msgs, "", 0u16);
+
+    let auth_info =
+        SignerInfo::single_direct(Some(pk.into()), sequence).auth_info(cosmos_fee(fee)?);
+    let chain_id = ChainId::try_from("nyks").map_err(|e| anyhow!("{}", e))?;
+
+    let sign_doc =
+        SignDoc::new(&body, &auth_info, &chain_id, account_number).map_err(|e| anyhow!("{}", e))?;
+
+    let raw_tx = sign_doc.sign(&sk).map_err(|e| anyhow!("{}", e))?;
+    let tx_bytes = raw_tx.to_bytes().map_err(|e| anyhow!("{}", e))?;
+    let tx_base64 = general_purpose::STANDARD.encode(&tx_bytes);
+    Ok(tx_base64)
+}
+
+/// The cosmrs [`Fee`] for `fee`.
+pub fn cosmos_fee(fee: &TxFeeConfig) -> Result<Fee, anyhow::Error> {
+    let amount = cosmrs::Coin {
+        denom: cosmrs::Denom::from_str(&fee.fee_denom).map_err(|e| anyhow!("{}", e))?,
+        amount: fee.fee_amount.into(),
+    };
+    Ok(Fee::from_amount_and_gas(amount, fee.gas_limit))
+}
 
-        let fee = Fee::from_amount_and_gas(
-            cosmrs::Coin {
-                denom: cosmrs::Denom::from_str("nyks").map_err(|e| anyhow!("{}", e))?,
-                amount: 1_000u64.into(),
-            },
-            200_0000u64,
-        );
-        let auth_info = SignerInfo::single_direct(Some(pk.into()), sequence).auth_info(fee);
-        let chain_id = ChainId::try_from("nyks").map_err(|e| anyhow!("{}", e))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmrs::tx::Tx;
 
-        let sign_doc = SignDoc::new(&body, &auth_info, &chain_id, account_number)
-            .map_err(|e| anyhow!("{}", e))?;
+    #[test]
+    fn test_signed_tx_carries_configured_fee() {
+        let sk = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let pk = sk.public_key();
+        let fee = TxFeeConfig {
+            gas_limit: 150_000,
+            fee_amount: 2_500,
+            fee_denom: "unyks".to_string(),
+            gas_adjustment: None,
+        };
+        let any = cosmrs::Any {
+            type_url: "/cosmos.bank.v1beta1.MsgSend".to_string(),
+            value: Vec::new(),
+        };
+        let signed = MethodTypeURL::MsgSend
+            .sign_msg_with_fee_config::<()>(any, pk, 4, 12, sk, &fee)
+            .unwrap();
 
-        let raw_tx = sign_doc.sign(&sk).map_err(|e| anyhow!("{}", e))?;
-        let tx_bytes = raw_tx.to_bytes().map_err(|e| anyhow!("{}", e))?;
-        let tx_base64 = general_purpose::STANDARD.encode(&tx_bytes);
-        Ok(tx_base64)
+        let tx = Tx::from_bytes(&general_purpose::STANDARD.decode(signed).unwrap()).unwrap();
+        let signed_fee = &tx.auth_info.fee;
+        assert_eq!(signed_fee.gas_limit, 150_000);
+        assert_eq!(signed_fee.amount.len(), 1);
+        assert_eq!(signed_fee.amount[0].amount, 2_500);
+        assert_eq!(signed_fee.amount[0].denom.as_ref(), "unyks");
+        assert_eq!(tx.auth_info.signer_infos[0].sequence, 4);
     }
 }
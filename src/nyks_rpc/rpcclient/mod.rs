@@ -1,3 +1,5 @@
+pub mod fee_bump;
+pub mod gas;
 pub mod method;
 pub mod txrequest;
 pub mod txresult;
@@ -7,10 +7,10 @@ use jsonrpc_core::response::Output;
 use jsonrpc_core::{Id, Version};
 use serde::{Deserialize, Serialize};
 // use super::method::Method;
+use crate::telemetry::WithTraceContext;
 use reqwest::blocking::Response;
 use reqwest::header::{ACCEPT, ACCEPT_ENCODING, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
-use std::fs::File;
-use std::io::prelude::*;
+use log::debug;
 // pub type TransactionStatusId = String;
 // use crate::nyks_rpc::rpcclient::method::ByteRec;
 
@@ -99,27 +99,33 @@ pub struct Resp {
 pub fn rpc_response(
     resp: Result<Response, reqwest::Error>,
 ) -> Result<RpcResponse<serde_json::Value>, reqwest::Error> {
-    match resp {
-        Ok(response) => {
-            // if response.status().is_success() {
-            let output: Output = serde_json::from_slice(&response.bytes().unwrap()).unwrap();
-            let rpc_response = match output {
-                Output::Success(s) => RpcResponse {
-                    jsonrpc: s.jsonrpc.unwrap(),
-                    id: s.id,
-                    result: Ok(s.result),
-                },
-                Output::Failure(f) => RpcResponse {
-                    jsonrpc: f.jsonrpc.unwrap(),
-                    id: f.id,
-                    result: Err(f.error),
-                },
-            };
-            return Ok(rpc_response);
-
-            // } else { };
-        }
-        Err(arg) => Err(arg),
+    let body = resp?.bytes()?;
+    Ok(parse_rpc_output(&body))
+}
+
+/// Parse a JSON-RPC response body. A body that is not a JSON-RPC 2.0 output
+/// becomes a `ParseError` result rather than an error of the transport.
+pub fn parse_rpc_output(body: &[u8]) -> RpcResponse<serde_json::Value> {
+    match serde_json::from_slice::<Output>(body) {
+        Ok(Output::Success(s)) => RpcResponse {
+            jsonrpc: s.jsonrpc.unwrap_or(Version::V2),
+            id: s.id,
+            result: Ok(s.result),
+        },
+        Ok(Output::Failure(f)) => RpcResponse {
+            jsonrpc: f.jsonrpc.unwrap_or(Version::V2),
+            id: f.id,
+            result: Err(f.error),
+        },
+        Err(e) => RpcResponse {
+            jsonrpc: Version::V2,
+            id: Id::Null,
+            result: Err(jsonrpc_core::Error {
+                code: jsonrpc_core::ErrorCode::ParseError,
+                message: format!("invalid JSON-RPC response: {e}"),
+                data: None,
+            }),
+        },
     }
 }
 
@@ -156,10 +162,9 @@ impl RpcRequest<TxParams> for RpcBody<TxParams> {
         &self.params
     }
     fn into_json(self) -> String {
-        let tx = serde_json::to_string(&self).unwrap();
-        let mut file = File::create("foo.txt").unwrap();
-        file.write_all(&serde_json::to_vec_pretty(&tx.clone()).unwrap())
-            .unwrap();
+        // Plain strings and ids only; serializing them cannot fail.
+        let tx = serde_json::to_string(&self).unwrap_or_default();
+        debug!("rpc request: {}", tx);
         tx
     }
 
@@ -178,6 +183,7 @@ impl RpcRequest<TxParams> for RpcBody<TxParams> {
                 let res = clint_clone
                     .post(url)
                     .headers(construct_headers())
+                    .with_trace_context()
                     .body(self.into_json())
                     .send();
 
@@ -189,6 +195,7 @@ impl RpcRequest<TxParams> for RpcBody<TxParams> {
                 let res = clint_clone
                     .post(url)
                     .headers(construct_headers())
+                    .with_trace_context()
                     .body(self.into_json())
                     .send();
 
@@ -197,3 +204,39 @@ impl RpcRequest<TxParams> for RpcBody<TxParams> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response_mutations::{fixture, mutated_fixture};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse_rpc_output_fixtures() {
+        let ok = parse_rpc_output(fixture("rpc_output/broadcast_tx_sync.json").as_bytes());
+        assert_eq!(ok.id, Id::Num(0));
+        assert_eq!(ok.result.unwrap()["code"], 0);
+
+        let failed = parse_rpc_output(fixture("rpc_output/failure.json").as_bytes());
+        assert_eq!(
+            failed.result.unwrap_err().code,
+            jsonrpc_core::ErrorCode::InvalidParams
+        );
+
+        let garbage = parse_rpc_output(b"<html>502 Bad Gateway</html>");
+        assert_eq!(garbage.id, Id::Null);
+        assert_eq!(
+            garbage.result.unwrap_err().code,
+            jsonrpc_core::ErrorCode::ParseError
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_rpc_output_never_panics(
+            body in mutated_fixture("rpc_output/broadcast_tx_sync.json"),
+        ) {
+            let _ = parse_rpc_output(&body);
+        }
+    }
+}
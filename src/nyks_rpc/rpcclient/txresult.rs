@@ -30,6 +30,16 @@ impl TxResponse {
             TxResponse::BroadcastTxCommit(tx) => tx.deliver_tx.code.clone(),
         }
     }
+    /// The node's log for the tx, as it sent it. A commit result only has
+    /// structured logs, so it has none.
+    pub fn get_log(&self) -> Option<String> {
+        match self {
+            TxResponse::BroadcastTxSync(tx) | TxResponse::BroadcastTxAsync(tx) => {
+                tx.log.clone().or_else(|| tx.raw_log.clone())
+            }
+            TxResponse::BroadcastTxCommit(_) => None,
+        }
+    }
 }
 
 // use crate::nyks_rpc::rpcclient::{
@@ -93,6 +103,8 @@ pub struct TxResult {
     pub hash: String,
     pub txhash: Option<String>,
     pub logs: Option<Vec<LogEntry>>,
+    /// CheckTx log of a `broadcast_tx_sync` result, e.g. why it was rejected.
+    pub log: Option<String>,
     pub raw_log: Option<String>,
     pub info: Option<String>,
     pub gas_wanted: Option<String>,
@@ -305,3 +317,58 @@ where
     }
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nyks_rpc::rpcclient::txrequest::parse_rpc_output;
+    use crate::response_mutations::{fixture, fixture_value, mutated};
+    use jsonrpc_core::{Id, Version};
+    use proptest::prelude::*;
+
+    fn ok_response(result: Value) -> RpcResponse<Value> {
+        RpcResponse {
+            jsonrpc: Version::V2,
+            id: Id::Num(0),
+            result: Ok(result),
+        }
+    }
+
+    #[test]
+    fn test_parse_tx_response_fixtures() {
+        let sync = parse_rpc_output(fixture("rpc_output/broadcast_tx_sync.json").as_bytes());
+        let sync = parse_tx_response(&Method::broadcast_tx_sync, sync).unwrap();
+        assert_eq!(sync.get_code(), 0);
+        assert_eq!(
+            sync.get_tx_hash(),
+            "ABE2D106BA6A2986E8E4EA0272D101507E0399FCFFA41A05748BBACB421BE356"
+        );
+
+        let commit = fixture_value("rpc_output/broadcast_tx_commit.json")["result"].clone();
+        let commit = from_rpc_response_tx_commit(ok_response(commit)).unwrap();
+        assert_eq!(commit.height, 1234);
+        assert_eq!(
+            commit.deliver_tx.log.unwrap()[0].events[0].event_type,
+            "message"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_tx_response_never_panics(
+            sync in mutated(fixture_value("rpc_output/broadcast_tx_sync.json")["result"].clone()),
+            commit in mutated(fixture_value("rpc_output/broadcast_tx_commit.json")["result"].clone()),
+        ) {
+            let results = [
+                parse_tx_response(&Method::broadcast_tx_sync, ok_response(sync.clone())).map(drop),
+                from_rpc_response(ok_response(sync)).map(drop),
+                parse_tx_response(&Method::broadcast_tx_commit, ok_response(commit)).map(drop),
+            ];
+            for result in results {
+                if let Err(e) = result {
+                    prop_assert_eq!(e.code, ErrorCode::ParseError);
+                }
+            }
+        }
+    }
+}
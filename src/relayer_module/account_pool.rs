@@ -0,0 +1,563 @@
+//! A pool of funded accounts for running several positions at once.
+//!
+//! A ZkOS account carries one order at a time, and an account whose order
+//! has settled moves its balance to a fresh address before it trades again.
+//! Running N positions therefore takes N accounts and some bookkeeping about
+//! which of them are free. [`AccountPool`] keeps that bookkeeping around an
+//! [`OrderWallet`]:
+//!
+//! - [`AccountPool::initialize`] funds one account from the wallet's on-chain
+//!   balance and splits it into equal accounts, at most
+//!   [`MAX_SPLITS_PER_TRANSFER`] new accounts per transfer.
+//! - [`AccountPool::acquire`] hands out a member that is free, on chain in
+//!   Coin state, funded, and has never carried an order;
+//!   [`AccountPool::release`] gives it back.
+//! - [`AccountPool::open_trader_order`] opens an order on an acquired member
+//!   and returns its request ID. Positions are tracked by request ID
+//!   ([`AccountPool::position`]), each member carrying at most one; an order
+//!   opened on a member through the wallet is tracked with
+//!   [`AccountPool::track`].
+//! - [`AccountPool::rotate_settled`] unlocks members whose order has
+//!   settled, moves each balance to a fresh account with
+//!   `trading_to_trading` and puts the fresh account in the pool instead.
+//!   Members left with nothing, e.g. after a liquidation, leave the pool.
+//!
+//! With DB persistence every membership change is written to the
+//! `account_pool_members` table under the pool's name, with the request ID
+//! of the member's order, and [`AccountPool::new`] reads it back, so a
+//! restarted bot recovers its pool and its positions.
+
+use std::collections::BTreeMap;
+
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::compat::{
+    relayer_types::{OrderType, PositionType, TXType},
+    zkvm::IOType,
+};
+use crate::error::{OrderWalletError, OrderWalletResult};
+
+use super::leverage::Leverage;
+use super::order_wallet::{AccountIndex, OrderWallet, RequestId};
+
+/// Name of the pool a bot gets when it only runs one.
+pub const DEFAULT_POOL_NAME: &str = "default";
+
+/// New accounts created by one split transfer; larger transfers exceed the
+/// transaction size limit.
+pub const MAX_SPLITS_PER_TRANSFER: usize = 8;
+
+/// Whether a pool member is handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolSlot {
+    Available,
+    InUse,
+}
+
+impl PoolSlot {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PoolSlot::Available => "available",
+            PoolSlot::InUse => "in_use",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "available" => Some(PoolSlot::Available),
+            "in_use" => Some(PoolSlot::InUse),
+            _ => None,
+        }
+    }
+}
+
+/// A member of an [`AccountPool`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PoolMember {
+    pub slot: PoolSlot,
+    /// Request ID of the order the member carries, until it is rotated.
+    pub request_id: Option<RequestId>,
+}
+
+impl PoolMember {
+    fn available() -> Self {
+        Self {
+            slot: PoolSlot::Available,
+            request_id: None,
+        }
+    }
+}
+
+/// Accounts of one [`OrderWallet`] shared out to concurrent positions.
+pub struct AccountPool {
+    wallet: OrderWallet,
+    name: String,
+    members: BTreeMap<AccountIndex, PoolMember>,
+}
+
+impl AccountPool {
+    /// Pool `name` over `wallet`, with the members stored under that name
+    /// when the wallet persists to a database.
+    pub fn new(wallet: OrderWallet, name: &str) -> OrderWalletResult<Self> {
+        #[allow(unused_mut)]
+        let mut members = BTreeMap::new();
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(db_manager) = wallet.get_db_manager() {
+            let stored = db_manager
+                .load_pool_members(name)
+                .map_err(OrderWalletError::Database)?;
+            for (index, state, request_id) in stored {
+                let slot = PoolSlot::parse(&state).ok_or_else(|| {
+                    OrderWalletError::Database(format!("Unknown pool member state: {}", state))
+                })?;
+                members.insert(index, PoolMember { slot, request_id });
+            }
+        }
+        Ok(Self {
+            wallet,
+            name: name.to_string(),
+            members,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn wallet(&self) -> &OrderWallet {
+        &self.wallet
+    }
+
+    /// The wallet, e.g. to open an order on an acquired account.
+    pub fn wallet_mut(&mut self) -> &mut OrderWallet {
+        &mut self.wallet
+    }
+
+    pub fn into_wallet(self) -> OrderWallet {
+        self.wallet
+    }
+
+    /// Members by account index.
+    pub fn members(&self) -> &BTreeMap<AccountIndex, PoolMember> {
+        &self.members
+    }
+
+    /// The member carrying the order of `request_id`.
+    pub fn position(&self, request_id: &str) -> Option<AccountIndex> {
+        self.positions()
+            .find(|(tracked, _)| *tracked == request_id)
+            .map(|(_, index)| index)
+    }
+
+    /// Request ID and member of every tracked order, by account index.
+    pub fn positions(&self) -> impl Iterator<Item = (&str, AccountIndex)> {
+        self.members.iter().filter_map(|(index, member)| {
+            member
+                .request_id
+                .as_deref()
+                .map(|request_id| (request_id, *index))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Fund `splits` accounts of `total_capital / splits` sats each and add
+    /// them to the pool: one `funding_to_trading` mint, then
+    /// `trading_to_trading_multiple_accounts` from the minted account, which
+    /// keeps the last share itself. Any remainder of the division stays in
+    /// the on-chain wallet. On failure the accounts created so far remain
+    /// members. Receivers left unconfirmed by a split are members too and
+    /// become ready once `confirm_pending_receivers` finalizes them. Returns
+    /// the new members.
+    pub async fn initialize(
+        &mut self,
+        total_capital: u64,
+        splits: usize,
+    ) -> OrderWalletResult<Vec<AccountIndex>> {
+        if splits == 0 {
+            return Err("An account pool needs at least one split".into());
+        }
+        let share = total_capital / splits as u64;
+        if share == 0 {
+            return Err(format!("{} sats cannot fund {} accounts", total_capital, splits).into());
+        }
+
+        let (_, master) = self
+            .wallet
+            .funding_to_trading(share * splits as u64)
+            .await?;
+        let mut added = Vec::with_capacity(splits);
+        let mut remaining = splits - 1;
+        while remaining > 0 {
+            let batch = remaining.min(MAX_SPLITS_PER_TRANSFER);
+            let outcome = self
+                .wallet
+                .trading_to_trading_multiple_accounts(master, vec![share; batch])
+                .await?;
+            let unconfirmed = outcome
+                .unconfirmed
+                .into_iter()
+                .map(|(index, _)| index)
+                .filter(|index| *index != master);
+            for index in outcome
+                .confirmed
+                .into_iter()
+                .map(|(index, _)| index)
+                .chain(unconfirmed)
+            {
+                self.insert(index, PoolMember::available());
+                added.push(index);
+            }
+            remaining -= batch;
+        }
+        self.insert(master, PoolMember::available());
+        added.push(master);
+        info!(
+            "Account pool {}: {} accounts of {} sats",
+            self.name, splits, share
+        );
+        Ok(added)
+    }
+
+    /// Add an existing account of the wallet as an available member.
+    pub fn add(&mut self, index: AccountIndex) {
+        self.insert(index, PoolMember::available());
+    }
+
+    /// A free member ready for a new order, now marked in use: on chain in
+    /// Coin state, funded, and without an order. `None` when every such
+    /// member is handed out.
+    pub fn acquire(&mut self) -> Option<AccountIndex> {
+        let index = self
+            .members
+            .iter()
+            .filter(|(_, member)| member.slot == PoolSlot::Available)
+            .map(|(index, _)| *index)
+            .find(|index| self.is_ready(*index))?;
+        self.update(index, |member| member.slot = PoolSlot::InUse);
+        Some(index)
+    }
+
+    /// Open a trader order on an acquired member and track it. The member
+    /// is handed back if the order is not submitted. Returns the member and
+    /// the order's request ID.
+    pub async fn open_trader_order(
+        &mut self,
+        order_type: OrderType,
+        order_side: PositionType,
+        entry_price: u64,
+        leverage: impl Into<Leverage>,
+    ) -> OrderWalletResult<(AccountIndex, RequestId)> {
+        let index = self.acquire().ok_or_else(|| {
+            OrderWalletError::Other(format!("Account pool {} has no ready account", self.name))
+        })?;
+        let opened = self
+            .wallet
+            .open_trader_order(index, order_type, order_side, entry_price, leverage)
+            .await;
+        match opened {
+            Ok(request_id) => {
+                self.track(index, &request_id);
+                Ok((index, request_id))
+            }
+            Err(e) => {
+                self.release(index);
+                Err(e)
+            }
+        }
+    }
+
+    /// Track `request_id` as the order carried by member `index`, for orders
+    /// opened through [`wallet_mut`](Self::wallet_mut). Returns whether
+    /// `index` is a member.
+    pub fn track(&mut self, index: AccountIndex, request_id: &str) -> bool {
+        self.update(index, |member| {
+            member.slot = PoolSlot::InUse;
+            member.request_id = Some(request_id.to_string());
+        })
+    }
+
+    /// Hand `index` back to the pool. Returns whether it was an in-use member.
+    /// A member that carried an order stays out of [`acquire`](Self::acquire)
+    /// until [`rotate_settled`](Self::rotate_settled) replaces it.
+    pub fn release(&mut self, index: AccountIndex) -> bool {
+        if self.members.get(&index).map(|member| member.slot) != Some(PoolSlot::InUse) {
+            return false;
+        }
+        self.update(index, |member| member.slot = PoolSlot::Available)
+    }
+
+    /// Replace every member whose order has settled with a fresh account.
+    ///
+    /// Members still holding an order (Memo state) are unlocked first when
+    /// the relayer reports it settled, which costs one relayer query each. A
+    /// settled member with a balance is rotated with `trading_to_trading` and
+    /// the fresh account joins the pool as available; one left empty is
+    /// dropped. A failure is logged and the member kept for the next call.
+    /// An order the wallet opened on a member without [`track`](Self::track)
+    /// is tracked first. Returns the fresh accounts.
+    pub async fn rotate_settled(&mut self) -> Vec<AccountIndex> {
+        let untracked: Vec<(AccountIndex, RequestId)> = self
+            .members
+            .iter()
+            .filter(|(_, member)| member.request_id.is_none())
+            .filter_map(|(index, _)| Some((*index, self.wallet.request_ids.get(index)?)))
+            .collect();
+        for (index, request_id) in untracked {
+            self.track(index, &request_id);
+        }
+        let used: Vec<AccountIndex> = self.positions().map(|(_, index)| index).collect();
+        let mut fresh = Vec::new();
+        for index in used {
+            let account = match self.wallet.zk_accounts.get_account(&index) {
+                Ok(account) => account,
+                Err(e) => {
+                    warn!(
+                        "Account pool {}: dropping account {}: {}",
+                        self.name, index, e
+                    );
+                    self.remove(index);
+                    continue;
+                }
+            };
+            if account.io_type == IOType::Memo {
+                let unlocked = match account.tx_type {
+                    Some(TXType::ORDERTX) => self.wallet.unlock_trader_order(index).await,
+                    Some(TXType::LENDTX) => self.wallet.unlock_lend_order(index).await,
+                    _ => continue,
+                };
+                if let Err(e) = unlocked {
+                    debug!("Account {} not settled yet: {}", index, e);
+                    continue;
+                }
+            }
+
+            let settled = match self.wallet.zk_accounts.get_account(&index) {
+                Ok(account) => account,
+                Err(_) => continue,
+            };
+            if !settled.on_chain || settled.balance == 0 {
+                info!(
+                    "Account pool {}: account {} settled empty, leaving the pool",
+                    self.name, index
+                );
+                self.remove(index);
+                continue;
+            }
+            match self.wallet.trading_to_trading(index).await {
+                Ok(new_index) => {
+                    info!(
+                        "Account pool {}: rotated account {} -> {}",
+                        self.name, index, new_index
+                    );
+                    self.remove(index);
+                    self.insert(new_index, PoolMember::available());
+                    fresh.push(new_index);
+                }
+                Err(e) => warn!(
+                    "Account pool {}: failed to rotate account {}: {}",
+                    self.name, index, e
+                ),
+            }
+        }
+        fresh
+    }
+
+    fn is_ready(&self, index: AccountIndex) -> bool {
+        self.members[&index].request_id.is_none()
+            && !self.wallet.request_ids.contains_key(&index)
+            && self
+                .wallet
+                .zk_accounts
+                .get_account(&index)
+                .is_ok_and(|account| {
+                    account.on_chain && account.io_type == IOType::Coin && account.balance > 0
+                })
+    }
+
+    /// Change member `index` in place and save it. Returns whether it is a
+    /// member.
+    fn update(&mut self, index: AccountIndex, f: impl FnOnce(&mut PoolMember)) -> bool {
+        let Some(mut member) = self.members.get(&index).cloned() else {
+            return false;
+        };
+        f(&mut member);
+        self.insert(index, member);
+        true
+    }
+
+    fn insert(&mut self, index: AccountIndex, member: PoolMember) {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(db_manager) = self.wallet.get_db_manager() {
+            let state = member.slot.as_str();
+            let request_id = member.request_id.as_deref();
+            if let Err(e) = db_manager.save_pool_member(&self.name, index, state, request_id) {
+                warn!("Failed to save pool member {}: {}", index, e);
+            }
+        }
+        self.members.insert(index, member);
+    }
+
+    fn remove(&mut self, index: AccountIndex) {
+        self.members.remove(&index);
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(db_manager) = self.wallet.get_db_manager() {
+            if let Err(e) = db_manager.remove_pool_member(&self.name, index) {
+                warn!("Failed to remove pool member {}: {}", index, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zkos_accounts::zkaccount::ZkAccount;
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn pool() -> AccountPool {
+        let wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None).unwrap();
+        AccountPool::new(wallet, DEFAULT_POOL_NAME).unwrap()
+    }
+
+    /// Add an account with `balance` to the pool's wallet and the pool.
+    fn member(pool: &mut AccountPool, balance: u64, on_chain: bool, io_type: IOType) -> u64 {
+        let account = ZkAccount::new(
+            format!("qq-{}", pool.len()),
+            balance,
+            format!("acct-{}", pool.len()),
+            "scalar".to_string(),
+            0,
+        );
+        let zk_accounts = &pool.wallet.zk_accounts;
+        let index = zk_accounts.add_account(account).unwrap();
+        zk_accounts.update_on_chain(&index, on_chain).unwrap();
+        zk_accounts.update_io_type(&index, io_type, None).unwrap();
+        pool.add(index);
+        index
+    }
+
+    #[test]
+    fn test_acquire_hands_out_only_ready_accounts() {
+        let mut pool = pool();
+        member(&mut pool, 1_000, false, IOType::Coin);
+        member(&mut pool, 0, true, IOType::Coin);
+        member(&mut pool, 1_000, true, IOType::Memo);
+        let used = member(&mut pool, 1_000, true, IOType::Coin);
+        pool.wallet.request_ids.insert(used, "REQ-1".to_string());
+        let ready = member(&mut pool, 1_000, true, IOType::Coin);
+        assert_eq!(pool.len(), 5);
+
+        assert_eq!(pool.acquire(), Some(ready));
+        assert_eq!(pool.members()[&ready].slot, PoolSlot::InUse);
+        assert_eq!(pool.acquire(), None);
+
+        assert!(pool.release(ready));
+        assert!(!pool.release(ready));
+        assert!(!pool.release(used));
+        assert_eq!(pool.acquire(), Some(ready));
+    }
+
+    #[tokio::test]
+    async fn test_rotate_drops_members_settled_empty() {
+        let mut pool = pool();
+        let liquidated = member(&mut pool, 0, true, IOType::Coin);
+        pool.wallet
+            .request_ids
+            .insert(liquidated, "REQ-1".to_string());
+        let fresh = member(&mut pool, 1_000, true, IOType::Coin);
+
+        assert!(pool.rotate_settled().await.is_empty());
+        assert!(!pool.members().contains_key(&liquidated));
+        // Never traded: nothing to rotate.
+        assert_eq!(pool.members()[&fresh], PoolMember::available());
+    }
+
+    #[test]
+    fn test_orders_are_tracked_by_request_id() {
+        let mut pool = pool();
+        let first = member(&mut pool, 1_000, true, IOType::Coin);
+        let second = member(&mut pool, 1_000, true, IOType::Coin);
+        assert!(pool.track(first, "REQ-1"));
+        assert!(!pool.track(99, "REQ-99"));
+        assert_eq!(pool.position("REQ-1"), Some(first));
+        assert_eq!(pool.position("REQ-99"), None);
+
+        // A member carrying an order is not handed out again, even released.
+        assert!(pool.release(first));
+        assert_eq!(pool.members()[&first].request_id.as_deref(), Some("REQ-1"));
+        assert_eq!(pool.acquire(), Some(second));
+        assert_eq!(pool.acquire(), None);
+        assert!(pool.track(second, "REQ-2"));
+        let positions: Vec<(&str, u64)> = pool.positions().collect();
+        assert_eq!(positions, vec![("REQ-1", first), ("REQ-2", second)]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_restarted_pool_recovers_members_and_orders() {
+        use secrecy::SecretString;
+
+        let path =
+            std::env::temp_dir().join(format!("nyks-account-pool-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = path.to_str().unwrap().to_string();
+        let db_pool = crate::database::connection::init_pool(Some(url.clone())).unwrap();
+        let mut conn = crate::database::connection::get_conn(&db_pool).unwrap();
+        crate::database::connection::run_migrations(&mut conn).unwrap();
+        drop(conn);
+        let password = SecretString::new("account_pool_password".into());
+        let wallet_id = format!("account-pool-{}", uuid::Uuid::new_v4());
+        let mut wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None).unwrap();
+        wallet
+            .attach_database(password.clone(), wallet_id.clone(), db_pool)
+            .unwrap();
+        wallet.save_order_wallet_to_db().unwrap();
+
+        let mut pool = AccountPool::new(wallet, "grid").unwrap();
+        let carrying = member(&mut pool, 1_000, true, IOType::Memo);
+        assert!(pool.track(carrying, "REQ-OPEN"));
+        let in_use = member(&mut pool, 1_000, true, IOType::Coin);
+        let available = member(&mut pool, 1_000, true, IOType::Coin);
+        assert_eq!(pool.acquire(), Some(in_use));
+        let members = pool.members().clone();
+        drop(pool);
+
+        let (wallet, _) = OrderWallet::load_from_db(wallet_id, Some(password), Some(url)).unwrap();
+        let restarted = AccountPool::new(wallet, "grid").unwrap();
+        assert_eq!(restarted.members(), &members);
+        assert_eq!(restarted.members()[&available], PoolMember::available());
+        assert_eq!(restarted.members()[&in_use].slot, PoolSlot::InUse);
+        assert_eq!(restarted.position("REQ-OPEN"), Some(carrying));
+        // Other pools of the wallet are stored apart.
+        let other = AccountPool::new(restarted.into_wallet(), DEFAULT_POOL_NAME).unwrap();
+        assert!(other.is_empty());
+        drop(other);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_rejects_empty_splits() {
+        let mut pool = pool();
+        assert!(pool.initialize(10_000, 0).await.is_err());
+        assert!(pool.initialize(3, 4).await.is_err());
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_slot_round_trips() {
+        for slot in [PoolSlot::Available, PoolSlot::InUse] {
+            assert_eq!(PoolSlot::parse(slot.as_str()), Some(slot));
+        }
+        assert_eq!(PoolSlot::parse("spent"), None);
+    }
+}
@@ -0,0 +1,539 @@
+//! Per-account wallet state that concurrent operations can share.
+//!
+//! [`OrderWallet`](super::order_wallet::OrderWallet) keeps its ZkOS accounts,
+//! cached UTXOs, request IDs and order records in these containers so that
+//! operations on different accounts can run through a shared `&OrderWallet`
+//! (e.g. an `Arc<OrderWallet>` handed to several tokio tasks).
+//!
+//! Every method takes the lock for the duration of the call only, so no lock
+//! is ever held across an `.await`. Values are returned by clone. Cloning a
+//! container copies its contents; it does not share them.
+//!
+//! Wallet-wide state those operations update (the UTXO cache, the pending
+//! operations journal, the trading limits) sits in a [`Guarded`] value on the
+//! same terms. [`AccountLocks`] is the one lock that is held across awaits:
+//! it serializes the order operations on one account, so two tasks never
+//! build orders on the same UTXO, while other accounts proceed.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use secrecy::SecretString;
+use serde::{Serialize, Serializer};
+
+use crate::compat::{quisquislib::Account, relayer_types::TXType, zkvm::IOType};
+use crate::zkos_accounts::zkaccount::{StoredError, ZkAccount, ZkAccountDB, ZkAccountError};
+
+use super::order_wallet::AccountIndex;
+
+// -------------------------
+// AccountMap
+// -------------------------
+
+/// A map keyed by account index behind a read-write lock.
+pub struct AccountMap<V> {
+    inner: RwLock<HashMap<AccountIndex, V>>,
+}
+
+impl<V> AccountMap<V> {
+    pub fn new() -> Self {
+        Self::from(HashMap::new())
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<AccountIndex, V>> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<AccountIndex, V>> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn contains_key(&self, index: &AccountIndex) -> bool {
+        self.read().contains_key(index)
+    }
+
+    pub fn insert(&self, index: AccountIndex, value: V) -> Option<V> {
+        self.write().insert(index, value)
+    }
+
+    pub fn remove(&self, index: &AccountIndex) -> Option<V> {
+        self.write().remove(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Account indices present, in no particular order.
+    pub fn keys(&self) -> Vec<AccountIndex> {
+        self.read().keys().copied().collect()
+    }
+
+    /// Replace the whole map, e.g. with state loaded from the database.
+    pub fn replace(&self, map: HashMap<AccountIndex, V>) {
+        *self.write() = map;
+    }
+
+    /// Run `f` on the entry for `index`, inserting `V::default()` first if
+    /// there is none.
+    pub fn update<R>(&self, index: AccountIndex, f: impl FnOnce(&mut V) -> R) -> R
+    where
+        V: Default,
+    {
+        f(self.write().entry(index).or_default())
+    }
+
+    /// Run `f` on the entry for `index` if there is one.
+    pub fn update_existing<R>(
+        &self,
+        index: &AccountIndex,
+        f: impl FnOnce(&mut V) -> R,
+    ) -> Option<R> {
+        self.write().get_mut(index).map(f)
+    }
+}
+
+impl<V: Clone> AccountMap<V> {
+    pub fn get(&self, index: &AccountIndex) -> Option<V> {
+        self.read().get(index).cloned()
+    }
+
+    /// Copy of the whole map.
+    pub fn snapshot(&self) -> HashMap<AccountIndex, V> {
+        self.read().clone()
+    }
+}
+
+impl<V> Default for AccountMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> From<HashMap<AccountIndex, V>> for AccountMap<V> {
+    fn from(map: HashMap<AccountIndex, V>) -> Self {
+        Self {
+            inner: RwLock::new(map),
+        }
+    }
+}
+
+impl<V: Clone> Clone for AccountMap<V> {
+    fn clone(&self) -> Self {
+        Self::from(self.snapshot())
+    }
+}
+
+impl<V: std::fmt::Debug> std::fmt::Debug for AccountMap<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.read().fmt(f)
+    }
+}
+
+impl<V: Serialize> Serialize for AccountMap<V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.read().serialize(serializer)
+    }
+}
+
+// -------------------------
+// Guarded
+// -------------------------
+
+/// A value behind a read-write lock, updated through `&self`.
+pub struct Guarded<T> {
+    inner: RwLock<T>,
+}
+
+impl<T> Guarded<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: RwLock::new(value),
+        }
+    }
+
+    /// Run `f` on the value.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.inner.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Run `f` on the value mutably.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.inner.write().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// The value, without locking; exclusive access makes that safe.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn replace(&self, value: T) -> T {
+        self.update(|current| std::mem::replace(current, value))
+    }
+}
+
+impl<T: Clone> Guarded<T> {
+    pub fn get(&self) -> T {
+        self.with(T::clone)
+    }
+}
+
+impl<T: Default> Default for Guarded<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Clone> Clone for Guarded<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.get())
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Guarded<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.with(|value| value.fmt(f))
+    }
+}
+
+// -------------------------
+// AccountLocks
+// -------------------------
+
+/// One async lock per account index, held for a whole order operation on
+/// that account. A clone has its own, unlocked, locks.
+#[derive(Default)]
+pub struct AccountLocks {
+    locks: Mutex<HashMap<AccountIndex, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl AccountLocks {
+    /// Wait until no other operation holds `index`, and hold it until the
+    /// guard is dropped. Not reentrant: an operation holding `index` must not
+    /// lock it again.
+    pub async fn lock(&self, index: AccountIndex) -> tokio::sync::OwnedMutexGuard<()> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(index)
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+}
+
+impl Clone for AccountLocks {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl std::fmt::Debug for AccountLocks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("AccountLocks")
+            .field("accounts", &locks.len())
+            .finish()
+    }
+}
+
+// -------------------------
+// ZkAccountStore
+// -------------------------
+
+/// A [`ZkAccountDB`] behind a read-write lock, with the same lookups and
+/// updates taking `&self`. Lookups return owned accounts.
+pub struct ZkAccountStore {
+    inner: RwLock<ZkAccountDB>,
+}
+
+impl ZkAccountStore {
+    pub fn new(db: ZkAccountDB) -> Self {
+        Self {
+            inner: RwLock::new(db),
+        }
+    }
+
+    /// Lock the database for reading. Drop the guard before any `.await` and
+    /// before calling back into the store.
+    pub fn read(&self) -> RwLockReadGuard<'_, ZkAccountDB> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Lock the database for writing. Drop the guard before any `.await` and
+    /// before calling back into the store.
+    pub fn write(&self) -> RwLockWriteGuard<'_, ZkAccountDB> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Copy of the whole database.
+    pub fn snapshot(&self) -> ZkAccountDB {
+        self.read().clone()
+    }
+
+    /// Replace the whole database, e.g. with accounts loaded from storage.
+    pub fn replace(&self, db: ZkAccountDB) {
+        *self.write() = db;
+    }
+
+    /// Number of active accounts.
+    pub fn len(&self) -> usize {
+        self.read().accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().accounts.is_empty()
+    }
+
+    /// Number of archived accounts.
+    pub fn archived_len(&self) -> usize {
+        self.read().archived.len()
+    }
+
+    pub fn add_account(&self, account: ZkAccount) -> Result<u64, ZkAccountError> {
+        self.write().add_account(account)
+    }
+
+    pub fn generate_new_account(
+        &self,
+        balance: u64,
+        seed: &SecretString,
+    ) -> Result<u64, ZkAccountError> {
+        self.write().generate_new_account(balance, seed)
+    }
+
+    pub fn try_add_account(&self, account: ZkAccount) -> Result<u64, ZkAccountError> {
+        self.write().try_add_account(account)
+    }
+
+    pub fn restore_account(&self, account: ZkAccount) -> Result<(), ZkAccountError> {
+        self.write().restore_account(account)
+    }
+
+    pub fn get_account_address(&self, index: &u64) -> Result<String, ZkAccountError> {
+        self.read().get_account_address(index)
+    }
+
+    pub fn get_account(&self, index: &u64) -> Result<ZkAccount, ZkAccountError> {
+        self.read().get_account(index)
+    }
+
+    pub fn remove_account(&self, index: &u64) -> Result<ZkAccount, ZkAccountError> {
+        self.write().remove_account(index)
+    }
+
+    /// Copies of the active accounts, in no particular order.
+    pub fn get_all_accounts(&self) -> Vec<ZkAccount> {
+        self.read().accounts.values().cloned().collect()
+    }
+
+    /// Copies of the archived accounts, in no particular order.
+    pub fn get_archived_accounts(&self) -> Vec<ZkAccount> {
+        self.read().archived.values().cloned().collect()
+    }
+
+    pub fn get_all_accounts_as_json(&self) -> Result<String, String> {
+        self.read().get_all_accounts_as_json()
+    }
+
+    pub fn get_address(&self, index: &u64) -> Result<String, ZkAccountError> {
+        self.read().get_address(index)
+    }
+
+    pub fn get_balance(&self, index: &u64) -> Result<u64, ZkAccountError> {
+        self.read().get_balance(index)
+    }
+
+    pub fn get_io_type(&self, index: &u64) -> Result<IOType, ZkAccountError> {
+        self.read().get_io_type(index)
+    }
+
+    pub fn is_on_chain(&self, index: &u64) -> Result<bool, ZkAccountError> {
+        self.read().is_on_chain(index)
+    }
+
+    pub fn update_balance(&self, index: &u64, balance: u64) -> Result<(), ZkAccountError> {
+        self.write().update_balance(index, balance)
+    }
+
+    pub fn export_to_json(&self, path: &str) -> Result<(), String> {
+        self.read().export_to_json(path)
+    }
+
+    pub fn try_export_to_json(&self, path: &str) -> Result<(), String> {
+        self.read().try_export_to_json(path)
+    }
+
+    pub fn update_io_type(
+        &self,
+        index: &u64,
+        io_type: IOType,
+        tx_type: Option<TXType>,
+    ) -> Result<(), ZkAccountError> {
+        self.write().update_io_type(index, io_type, tx_type)
+    }
+
+    pub fn update_scalar(&self, index: &u64, scalar: &str) -> Result<(), ZkAccountError> {
+        self.write().update_scalar(index, scalar)
+    }
+
+    pub fn update_account_key(&self, index: &u64, account_key: &str) -> Result<(), ZkAccountError> {
+        self.write().update_account_key(index, account_key)
+    }
+
+    pub fn update_on_chain(&self, index: &u64, on_chain: bool) -> Result<(), ZkAccountError> {
+        self.write().update_on_chain(index, on_chain)
+    }
+
+    pub fn update_qq_account(&self, index: &u64, account: Account) -> Result<(), ZkAccountError> {
+        self.write().update_qq_account(index, account)
+    }
+
+    pub fn set_last_error(&self, index: &u64, error: StoredError) -> Result<(), ZkAccountError> {
+        self.write().set_last_error(index, error)
+    }
+
+    pub fn set_balance_unverified(
+        &self,
+        index: &u64,
+        unverified: bool,
+    ) -> Result<(), ZkAccountError> {
+        self.write().set_balance_unverified(index, unverified)
+    }
+
+    pub fn mark_simulated(&self, index: &u64) -> Result<(), ZkAccountError> {
+        self.write().mark_simulated(index)
+    }
+
+    pub fn clear_last_error(&self, index: &u64) -> Result<bool, ZkAccountError> {
+        self.write().clear_last_error(index)
+    }
+
+    pub fn archive_account(&self, index: &u64) -> Result<(), ZkAccountError> {
+        self.write().archive_account(index)
+    }
+
+    pub fn unarchive_account(&self, index: &u64) -> Result<(), ZkAccountError> {
+        self.write().unarchive_account(index)
+    }
+
+    pub fn set_label(&self, index: &u64, label: Option<String>) -> Result<(), ZkAccountError> {
+        self.write().set_label(index, label)
+    }
+
+    pub fn get_by_label(&self, label: &str) -> Option<ZkAccount> {
+        self.read().get_by_label(label).cloned()
+    }
+
+    /// Copies of the active accounts whose label starts with `prefix`, by index.
+    pub fn accounts_with_tag(&self, prefix: &str) -> Vec<ZkAccount> {
+        self.read()
+            .accounts_with_tag(prefix)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn check_label_free(&self, index: &u64, label: &str) -> Result<(), ZkAccountError> {
+        self.read().check_label_free(index, label)
+    }
+
+    pub fn is_archived(&self, index: &u64) -> bool {
+        self.read().is_archived(index)
+    }
+
+    pub fn resolve_account(&self, index: &u64) -> Result<ZkAccount, ZkAccountError> {
+        self.read().resolve_account(index)
+    }
+
+    pub fn remove_account_by_index(&self, index: &u64) -> Result<(), ZkAccountError> {
+        self.write().remove_account_by_index(index)
+    }
+}
+
+impl Default for ZkAccountStore {
+    fn default() -> Self {
+        Self::new(ZkAccountDB::new())
+    }
+}
+
+impl From<ZkAccountDB> for ZkAccountStore {
+    fn from(db: ZkAccountDB) -> Self {
+        Self::new(db)
+    }
+}
+
+impl Clone for ZkAccountStore {
+    fn clone(&self) -> Self {
+        Self::new(self.snapshot())
+    }
+}
+
+impl std::fmt::Debug for ZkAccountStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.read().fmt(f)
+    }
+}
+
+impl Serialize for ZkAccountStore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.read().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_map_clone_copies_contents() {
+        let map = AccountMap::new();
+        map.insert(1, "a".to_string());
+        let copy = map.clone();
+        map.insert(2, "b".to_string());
+        assert_eq!(copy.len(), 1);
+        assert_eq!(map.get(&2).as_deref(), Some("b"));
+        map.update(3, |v: &mut String| v.push('c'));
+        assert_eq!(map.get(&3).as_deref(), Some("c"));
+        assert_eq!(map.update_existing(&4, |v| v.clone()), None);
+    }
+
+    #[tokio::test]
+    async fn test_account_locks_serialize_one_account_only() {
+        let locks = AccountLocks::default();
+        let first = locks.lock(1).await;
+        // Another account is not held up.
+        drop(locks.lock(2).await);
+        let second = tokio::time::timeout(std::time::Duration::from_millis(20), locks.lock(1));
+        assert!(second.await.is_err());
+        drop(first);
+        drop(locks.lock(1).await);
+    }
+
+    #[test]
+    fn test_account_map_shared_across_threads() {
+        let map = std::sync::Arc::new(AccountMap::<u64>::new());
+        let handles: Vec<_> = (0..4)
+            .map(|index| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    for n in 0..100 {
+                        map.update(index, |v| *v += n);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(
+            map.snapshot(),
+            (0..4).map(|i| (i, 4950)).collect::<HashMap<_, _>>()
+        );
+    }
+}
@@ -0,0 +1,571 @@
+//! Repairing local account state that diverged from the chain.
+//!
+//! A crash between broadcasting a transaction and saving its effect leaves a
+//! [`ZkAccount`] claiming a state the chain does not have: an account still
+//! marked as an on-chain Coin after its output was spent, or a Coin that is
+//! in fact locked in an order. Every later operation then fails in
+//! `ensure_coin_onchain` or on a stale input.
+//!
+//! [`OrderWallet::resync_account`](super::order_wallet::OrderWallet::resync_account)
+//! looks the account address up as both a Coin and a Memo output
+//! ([`probe_chain_utxo`]), [`plan_fixes`] compares the answer with the local
+//! fields, and the wallet applies the fixes to the account, the
+//! `utxo_details` cache and the database. For a Coin output the balance is
+//! re-derived from the output's commitment. The [`AccountSyncReport`] lists
+//! every change for operators to audit.
+//!
+//! [`OrderWallet::scan_derived_accounts`](super::order_wallet::OrderWallet::scan_derived_accounts)
+//! recovers accounts missing from local state, e.g. after the database was
+//! lost: account addresses are derived from the ZkOS seed and index, so each
+//! index can be probed and any output found rebuilt with
+//! [`restore_from_chain`] into a [`RecoveredAccount`]. Accounts created
+//! before addresses were derived have randomized addresses;
+//! [`OrderWallet::scan_minted_accounts`](super::order_wallet::OrderWallet::scan_minted_accounts)
+//! finds those among the accounts the wallet funded ([`fetch_minted_accounts`]),
+//! by the derived key that owns them.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::compat::{
+    relayer_rpcclient::method::UtxoDetailResponse,
+    relayer_types::{OrderType, TXType, TxHash},
+    zkvm::IOType,
+};
+use crate::nyks_rpc::lcd;
+use crate::zkos_accounts::zkaccount::ZkAccount;
+
+use super::is_utxo_not_found;
+use super::order_wallet::AccountIndex;
+use super::utxo_cache::UtxoFetcher;
+
+/// Accounts probed at once by
+/// [`OrderWallet::resync_all`](super::order_wallet::OrderWallet::resync_all).
+pub const RESYNC_CONCURRENCY: usize = 8;
+
+/// What the chain holds at an account address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainUtxoState {
+    Coin,
+    Memo,
+    /// No output: never funded, not confirmed yet, or spent.
+    Absent,
+}
+
+/// The output found by [`probe_chain_utxo`].
+#[derive(Debug, Clone)]
+pub enum ChainUtxo {
+    Coin(UtxoDetailResponse),
+    Memo(UtxoDetailResponse),
+    Absent,
+}
+
+impl ChainUtxo {
+    pub fn state(&self) -> ChainUtxoState {
+        match self {
+            ChainUtxo::Coin(_) => ChainUtxoState::Coin,
+            ChainUtxo::Memo(_) => ChainUtxoState::Memo,
+            ChainUtxo::Absent => ChainUtxoState::Absent,
+        }
+    }
+
+    pub fn into_detail(self) -> Option<UtxoDetailResponse> {
+        match self {
+            ChainUtxo::Coin(detail) | ChainUtxo::Memo(detail) => Some(detail),
+            ChainUtxo::Absent => None,
+        }
+    }
+}
+
+/// Look `address` up as a Coin and as a Memo output, one attempt each. A
+/// "not found" answer counts as absent; any other failure is returned, since
+/// the state cannot be decided without both answers.
+pub async fn probe_chain_utxo(
+    fetcher: Arc<dyn UtxoFetcher>,
+    address: String,
+) -> Result<ChainUtxo, String> {
+    let (coin, memo) = tokio::join!(
+        fetcher.fetch_once(address.clone(), IOType::Coin),
+        fetcher.fetch_once(address, IOType::Memo)
+    );
+    match (found(coin)?, found(memo)?) {
+        (Some(detail), None) => Ok(ChainUtxo::Coin(detail)),
+        (None, Some(detail)) => Ok(ChainUtxo::Memo(detail)),
+        (None, None) => Ok(ChainUtxo::Absent),
+        (Some(_), Some(_)) => Err("address holds both a Coin and a Memo output".to_string()),
+    }
+}
+
+fn found(
+    fetched: Result<UtxoDetailResponse, String>,
+) -> Result<Option<UtxoDetailResponse>, String> {
+    match fetched {
+        Ok(detail) => Ok(Some(detail)),
+        Err(e) if is_utxo_not_found(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// One change made to bring an account in line with the chain.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "field", rename_all = "snake_case")]
+pub enum AccountFix {
+    OnChain {
+        from: bool,
+        to: bool,
+    },
+    IoType {
+        from: IOType,
+        to: IOType,
+    },
+    /// The order kind of a Memo cannot be read from the chain, so it is
+    /// cleared when the account turns out to be a Coin or spent, and read
+    /// from the relayer's records ([`memo_order`]) for a Memo without one.
+    TxTypeCleared {
+        from: TXType,
+    },
+    /// A Memo without an order kind was matched to the relayer's record of
+    /// its order.
+    OrderIdentified {
+        tx_type: TXType,
+        request_id: String,
+    },
+    Balance {
+        from: u64,
+        to: u64,
+    },
+    /// The cached UTXO detail was added or replaced by the chain's.
+    UtxoDetailUpdated,
+    /// The cached UTXO detail was dropped; the chain has no output.
+    UtxoDetailRemoved,
+}
+
+impl AccountFix {
+    /// Apply a field fix to `account`. Cache fixes do nothing here.
+    pub fn apply(&self, account: &mut ZkAccount) {
+        match self {
+            AccountFix::OnChain { to, .. } => account.on_chain = *to,
+            AccountFix::IoType { to, .. } => account.io_type = *to,
+            AccountFix::TxTypeCleared { .. } => account.tx_type = None,
+            AccountFix::OrderIdentified { tx_type, .. } => account.tx_type = Some(tx_type.clone()),
+            AccountFix::Balance { to, .. } => account.balance = *to,
+            AccountFix::UtxoDetailUpdated | AccountFix::UtxoDetailRemoved => {}
+        }
+    }
+}
+
+/// Field fixes that make `local` agree with `chain`. An account with no
+/// output that is not marked on-chain is left alone: its funding may not
+/// have confirmed yet. Balances behind a Coin output are derived by the
+/// wallet, which holds the key.
+pub fn plan_fixes(local: &ZkAccount, chain: ChainUtxoState) -> Vec<AccountFix> {
+    let mut fixes = Vec::new();
+    let (on_chain, io_type) = match chain {
+        ChainUtxoState::Coin => (true, IOType::Coin),
+        ChainUtxoState::Memo => (true, IOType::Memo),
+        ChainUtxoState::Absent if local.on_chain => (false, IOType::Coin),
+        ChainUtxoState::Absent => return fixes,
+    };
+    if local.on_chain != on_chain {
+        fixes.push(AccountFix::OnChain {
+            from: local.on_chain,
+            to: on_chain,
+        });
+    }
+    if local.io_type != io_type {
+        fixes.push(AccountFix::IoType {
+            from: local.io_type,
+            to: io_type,
+        });
+    }
+    if chain != ChainUtxoState::Memo {
+        if let Some(tx_type) = &local.tx_type {
+            fixes.push(AccountFix::TxTypeCleared {
+                from: tx_type.clone(),
+            });
+        }
+    }
+    if chain == ChainUtxoState::Absent && local.balance != 0 {
+        fixes.push(AccountFix::Balance {
+            from: local.balance,
+            to: 0,
+        });
+    }
+    fixes
+}
+
+/// Order kind and request ID of the newest order in an account's relayer
+/// records `txs`, the one its Memo output holds. Records without a request
+/// ID are skipped.
+pub fn memo_order(txs: &[TxHash]) -> Option<(TXType, String)> {
+    txs.iter()
+        .filter_map(|tx| {
+            let request_id = tx.request_id.as_deref()?.trim();
+            (!request_id.is_empty()).then_some((tx, request_id))
+        })
+        .max_by_key(|(tx, _)| tx.id)
+        .map(|(tx, request_id)| {
+            let tx_type = match tx.order_type {
+                OrderType::LEND => TXType::LENDTX,
+                _ => TXType::ORDERTX,
+            };
+            (tx_type, request_id.to_string())
+        })
+}
+
+/// What [`OrderWallet::resync_account`](super::order_wallet::OrderWallet::resync_account)
+/// found and changed for one account.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountSyncReport {
+    pub account_index: AccountIndex,
+    pub chain_state: ChainUtxoState,
+    /// Empty when local state already matched the chain.
+    pub fixes: Vec<AccountFix>,
+}
+
+impl AccountSyncReport {
+    pub fn is_clean(&self) -> bool {
+        self.fixes.is_empty()
+    }
+}
+
+/// Result of [`OrderWallet::resync_all`](super::order_wallet::OrderWallet::resync_all).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResyncSummary {
+    /// One report per account that could be probed, by index.
+    pub accounts: Vec<AccountSyncReport>,
+    /// Accounts whose state could not be determined, with the reason; left
+    /// unchanged.
+    pub failed: Vec<(AccountIndex, String)>,
+}
+
+impl ResyncSummary {
+    /// Reports with at least one fix.
+    pub fn repaired(&self) -> impl Iterator<Item = &AccountSyncReport> {
+        self.accounts.iter().filter(|report| !report.is_clean())
+    }
+}
+
+/// An account rebuilt by
+/// [`OrderWallet::scan_derived_accounts`](super::order_wallet::OrderWallet::scan_derived_accounts).
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveredAccount {
+    pub account_index: AccountIndex,
+    pub address: String,
+    /// `Coin` or `Memo`; absent addresses are not recovered.
+    pub chain_state: ChainUtxoState,
+    /// Amount decrypted from a Coin output. `None` for a Memo, whose order has
+    /// to be queried from the relayer, or when the amount could not be derived.
+    pub balance: Option<u64>,
+}
+
+/// Transactions requested per page by [`fetch_minted_accounts`].
+const MINT_SEARCH_PAGE: usize = 100;
+
+/// A ZkOS account funded by a `MsgMintBurnTradingBtc` mint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MintedAccount {
+    /// Hex `EncryptedAccount` the mint funded.
+    pub qq_account: String,
+    pub encrypt_scalar: String,
+}
+
+/// Mints signed by `twilight_address` in one LCD `/cosmos/tx/v1beta1/txs`
+/// search page, in page order. Burns, other messages and mints of an
+/// account too short to be an `EncryptedAccount` are skipped.
+pub fn minted_accounts(page: &Value, twilight_address: &str) -> Vec<MintedAccount> {
+    let txs = page.get("txs").and_then(Value::as_array);
+    let messages = txs
+        .into_iter()
+        .flatten()
+        .filter_map(|tx| tx.pointer("/body/messages").and_then(Value::as_array))
+        .flatten();
+    let field = |message: &Value, name: &str| {
+        message
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    messages
+        .filter(|message| {
+            message.get("@type").and_then(Value::as_str)
+                == Some("/twilightproject.nyks.zkos.MsgMintBurnTradingBtc")
+                && message.get("mint_or_burn").and_then(Value::as_bool) == Some(true)
+                && message.get("twilight_address").and_then(Value::as_str) == Some(twilight_address)
+        })
+        .filter_map(|message| {
+            Some(MintedAccount {
+                qq_account: field(message, "qq_account")?,
+                encrypt_scalar: field(message, "encrypt_scalar").unwrap_or_default(),
+            })
+        })
+        .filter(|minted| {
+            // `EncryptedAccount::from_hex_str` panics on anything shorter
+            // than an address and a commitment.
+            hex::decode(&minted.qq_account).is_ok_and(|bytes| bytes.len() > 69)
+        })
+        .collect()
+}
+
+/// Every account `twilight_address` funded, oldest first, searched on the
+/// LCD by message sender. Pages are requested until the reported total has
+/// been read or a page comes back empty.
+pub async fn fetch_minted_accounts(
+    lcd_endpoint: &str,
+    twilight_address: &str,
+) -> Result<Vec<MintedAccount>, String> {
+    let mut minted = Vec::new();
+    let mut offset = 0;
+    loop {
+        let url = format!(
+            "{}/cosmos/tx/v1beta1/txs?events=message.sender%3D%27{}%27&order_by=ORDER_BY_ASC&pagination.limit={}&pagination.offset={}&pagination.count_total=true",
+            lcd_endpoint, twilight_address, MINT_SEARCH_PAGE, offset
+        );
+        let page: Value = lcd::get(&url)
+            .await
+            .map_err(|e| format!("Failed to search the wallet's transactions: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse transaction search response: {}", e))?;
+        let read = page
+            .get("txs")
+            .and_then(Value::as_array)
+            .map_or(0, Vec::len);
+        minted.extend(minted_accounts(&page, twilight_address));
+        offset += read;
+        let total = page
+            .pointer("/pagination/total")
+            .and_then(Value::as_str)
+            .and_then(|total| total.parse::<usize>().ok())
+            .unwrap_or(offset);
+        if read == 0 || offset >= total {
+            return Ok(minted);
+        }
+    }
+}
+
+/// `account`, a fresh zero-balance account for the derived key, marked as
+/// holding the `chain` output with the decrypted `balance`. An amount that
+/// could not be derived leaves the account flagged `balance_unverified`.
+pub fn restore_from_chain(
+    mut account: ZkAccount,
+    chain: ChainUtxoState,
+    balance: Option<u64>,
+) -> ZkAccount {
+    account.on_chain = chain != ChainUtxoState::Absent;
+    account.io_type = match chain {
+        ChainUtxoState::Memo => IOType::Memo,
+        _ => IOType::Coin,
+    };
+    account.tx_type = None;
+    account.balance = balance.unwrap_or(0);
+    account.balance_unverified = account.on_chain && balance.is_none();
+    account
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::utxo_cache::UtxoFuture;
+
+    fn account(on_chain: bool, io_type: IOType, tx_type: Option<TXType>) -> ZkAccount {
+        let mut account = ZkAccount::new(
+            "qq".to_string(),
+            1_000,
+            "acct".to_string(),
+            "scalar".to_string(),
+            4,
+        );
+        account.on_chain = on_chain;
+        account.io_type = io_type;
+        account.tx_type = tx_type;
+        account
+    }
+
+    fn apply(mut local: ZkAccount, chain: ChainUtxoState) -> (ZkAccount, Vec<AccountFix>) {
+        let fixes = plan_fixes(&local, chain);
+        for fix in &fixes {
+            fix.apply(&mut local);
+        }
+        (local, fixes)
+    }
+
+    #[test]
+    fn test_matching_state_needs_no_fix() {
+        assert!(plan_fixes(&account(true, IOType::Coin, None), ChainUtxoState::Coin).is_empty());
+        let memo = account(true, IOType::Memo, Some(TXType::ORDERTX));
+        assert!(plan_fixes(&memo, ChainUtxoState::Memo).is_empty());
+        // Unconfirmed funding: nothing on chain yet, nothing claimed locally.
+        assert!(plan_fixes(&account(false, IOType::Coin, None), ChainUtxoState::Absent).is_empty());
+    }
+
+    #[test]
+    fn test_memo_that_settled_back_to_coin() {
+        let local = account(true, IOType::Memo, Some(TXType::LENDTX));
+        let (fixed, fixes) = apply(local, ChainUtxoState::Coin);
+        assert_eq!(fixed.io_type, IOType::Coin);
+        assert!(fixed.tx_type.is_none());
+        assert_eq!(fixes.len(), 2);
+    }
+
+    #[test]
+    fn test_coin_found_locked_in_an_order() {
+        let (fixed, fixes) = apply(account(true, IOType::Coin, None), ChainUtxoState::Memo);
+        assert_eq!(fixed.io_type, IOType::Memo);
+        assert!(matches!(
+            fixes.as_slice(),
+            [AccountFix::IoType {
+                from: IOType::Coin,
+                to: IOType::Memo
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_confirmed_funding_is_marked_on_chain() {
+        let (fixed, fixes) = apply(account(false, IOType::Coin, None), ChainUtxoState::Coin);
+        assert!(fixed.on_chain);
+        assert!(matches!(
+            fixes.as_slice(),
+            [AccountFix::OnChain {
+                from: false,
+                to: true
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_spent_account_is_marked_off_chain_and_empty() {
+        let local = account(true, IOType::Memo, Some(TXType::ORDERTX));
+        let (fixed, fixes) = apply(local, ChainUtxoState::Absent);
+        assert!(!fixed.on_chain);
+        assert_eq!(fixed.io_type, IOType::Coin);
+        assert!(fixed.tx_type.is_none());
+        assert_eq!(fixed.balance, 0);
+        assert_eq!(fixes.len(), 4);
+    }
+
+    #[test]
+    fn test_restore_from_chain() {
+        let fresh = account(false, IOType::Coin, None);
+        let coin = restore_from_chain(fresh.clone(), ChainUtxoState::Coin, Some(750));
+        assert!(coin.on_chain);
+        assert_eq!(coin.balance, 750);
+        assert!(!coin.balance_unverified);
+
+        // The margin behind a Memo is only known once its order is queried.
+        let memo = restore_from_chain(fresh, ChainUtxoState::Memo, None);
+        assert!(memo.on_chain);
+        assert_eq!(memo.io_type, IOType::Memo);
+        assert_eq!(memo.balance, 0);
+        assert!(memo.balance_unverified);
+    }
+
+    #[derive(Debug)]
+    struct Chain {
+        coin: Result<(), String>,
+        memo: Result<(), String>,
+    }
+
+    impl UtxoFetcher for Chain {
+        fn fetch(&self, _account_address: String, io_type: IOType) -> UtxoFuture {
+            let answer = match io_type {
+                IOType::Coin => self.coin.clone(),
+                _ => self.memo.clone(),
+            };
+            Box::pin(async move {
+                match answer {
+                    Err(e) => Err(e),
+                    Ok(()) => Err("unexpected fetch of a present output".to_string()),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_treats_not_found_as_absent() {
+        let not_found = || Err("Failed to get utxo details: UTXO not found".to_string());
+        let chain = Arc::new(Chain {
+            coin: not_found(),
+            memo: not_found(),
+        });
+        let probed = probe_chain_utxo(chain, "acct".to_string()).await.unwrap();
+        assert_eq!(probed.state(), ChainUtxoState::Absent);
+
+        // An unreachable indexer says nothing about the account.
+        let chain = Arc::new(Chain {
+            coin: not_found(),
+            memo: Err("connection refused".to_string()),
+        });
+        let err = probe_chain_utxo(chain, "acct".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(err, "connection refused");
+    }
+
+    #[test]
+    fn test_minted_accounts_keeps_the_wallets_well_formed_mints() {
+        let qq = "ab".repeat(70);
+        let message = |mint_or_burn: bool, address: &str, qq_account: &str| {
+            serde_json::json!({
+                "@type": "/twilightproject.nyks.zkos.MsgMintBurnTradingBtc",
+                "mint_or_burn": mint_or_burn,
+                "btc_value": "1000",
+                "qq_account": qq_account,
+                "encrypt_scalar": "05",
+                "twilight_address": address,
+            })
+        };
+        let page = serde_json::json!({
+            "txs": [
+                {"body": {"messages": [
+                    message(true, "twilight1me", &qq),
+                    message(false, "twilight1me", &qq),
+                ]}},
+                {"body": {"messages": [
+                    {"@type": "/cosmos.bank.v1beta1.MsgSend"},
+                    message(true, "twilight1other", &qq),
+                    message(true, "twilight1me", "abcd"),
+                ]}},
+            ],
+            "pagination": {"total": "2"},
+        });
+        assert_eq!(
+            minted_accounts(&page, "twilight1me"),
+            vec![MintedAccount {
+                qq_account: qq,
+                encrypt_scalar: "05".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_memo_order_reads_the_newest_record() {
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
+
+        let tx = |id: i64, order_type: OrderType, request_id: Option<&str>| {
+            let builder = TxHashBuilder::new().field("id", id).order_type(order_type);
+            match request_id {
+                Some(request_id) => builder.request_id(request_id).build(),
+                None => builder.field("request_id", None::<String>).build(),
+            }
+        };
+        assert!(memo_order(&[]).is_none());
+        let txs = [
+            tx(1, OrderType::MARKET, Some("REQ1")),
+            tx(3, OrderType::LEND, None),
+            tx(2, OrderType::LEND, Some("REQ2")),
+        ];
+        assert!(matches!(
+            memo_order(&txs),
+            Some((TXType::LENDTX, request_id)) if request_id == "REQ2"
+        ));
+        assert!(matches!(
+            memo_order(&txs[..1]),
+            Some((TXType::ORDERTX, request_id)) if request_id == "REQ1"
+        ));
+    }
+}
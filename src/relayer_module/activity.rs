@@ -0,0 +1,369 @@
+//! Hourly operation counts for capacity planning.
+//!
+//! [`ActivityTracker`] counts operations per UTC hour and per
+//! [`ActivityCategory`], keeping a bounded retention window (14 days by
+//! default). It is cheap to clone and shared between an
+//! [`OrderWallet`](super::order_wallet::OrderWallet) and its
+//! [`RelayerJsonRpcClient`](super::relayer_api::RelayerJsonRpcClient), which
+//! counts every relayer call.
+//!
+//! Buckets touched since the last save are reported by
+//! [`take_dirty`](ActivityTracker::take_dirty) so the wallet can upsert them
+//! to the database every [`PERSIST_INTERVAL`], and reloaded with
+//! [`merge`](ActivityTracker::merge) after a restart.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::clock::{Clock, system_clock};
+
+/// Default retention window for hourly buckets.
+pub const DEFAULT_RETENTION: TimeDelta = TimeDelta::days(14);
+/// Minimum time between automatic saves of dirty buckets.
+pub const PERSIST_INTERVAL: TimeDelta = TimeDelta::minutes(5);
+
+/// What was counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityCategory {
+    OrdersOpened,
+    OrdersSettled,
+    /// ZkOS transfers between funding and trading accounts.
+    Transfers,
+    /// Transactions broadcast to the chain.
+    ChainTxs,
+    RelayerCalls,
+    /// Failed account operations.
+    Errors,
+}
+
+impl ActivityCategory {
+    pub const ALL: [ActivityCategory; 6] = [
+        ActivityCategory::OrdersOpened,
+        ActivityCategory::OrdersSettled,
+        ActivityCategory::Transfers,
+        ActivityCategory::ChainTxs,
+        ActivityCategory::RelayerCalls,
+        ActivityCategory::Errors,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivityCategory::OrdersOpened => "orders_opened",
+            ActivityCategory::OrdersSettled => "orders_settled",
+            ActivityCategory::Transfers => "transfers",
+            ActivityCategory::ChainTxs => "chain_txs",
+            ActivityCategory::RelayerCalls => "relayer_calls",
+            ActivityCategory::Errors => "errors",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+
+    /// Category counted when the account operation `operation` succeeds.
+    pub fn for_operation(operation: &str) -> Option<Self> {
+        match operation {
+            "open_trader_order" | "open_lend_order" => Some(ActivityCategory::OrdersOpened),
+            "close_trader_order" | "close_trader_order_sltp" | "close_lend_order" => {
+                Some(ActivityCategory::OrdersSettled)
+            }
+            "funding_to_trading"
+            | "trading_to_trading"
+            | "trading_to_funding"
+            | "transfer_to_address" => Some(ActivityCategory::Transfers),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ActivityCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One stored count: `count` operations of `category` during the hour starting at `hour`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityCount {
+    pub hour: DateTime<Utc>,
+    pub category: ActivityCategory,
+    pub count: u64,
+}
+
+/// Counts for one UTC hour. Categories with no activity are omitted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActivityBucket {
+    pub hour: DateTime<Utc>,
+    pub counts: BTreeMap<ActivityCategory, u64>,
+}
+
+/// Hourly buckets within a time range, oldest first, plus per-category totals.
+/// Hours without any activity are omitted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActivityHistogram {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub buckets: Vec<ActivityBucket>,
+    pub totals: BTreeMap<ActivityCategory, u64>,
+}
+
+/// Start of the UTC hour containing `t`.
+pub fn hour_of(t: DateTime<Utc>) -> DateTime<Utc> {
+    t.duration_trunc(TimeDelta::hours(1)).unwrap_or(t)
+}
+
+#[derive(Debug)]
+struct TrackerState {
+    clock: Arc<dyn Clock>,
+    retention: TimeDelta,
+    buckets: BTreeMap<DateTime<Utc>, BTreeMap<ActivityCategory, u64>>,
+    dirty: BTreeSet<DateTime<Utc>>,
+    last_persisted: Option<DateTime<Utc>>,
+}
+
+impl TrackerState {
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = hour_of(now) - self.retention;
+        self.buckets = self.buckets.split_off(&cutoff);
+        self.dirty = self.dirty.split_off(&cutoff);
+    }
+}
+
+/// Shared hourly activity counter. Clones share the same counts.
+#[derive(Debug, Clone)]
+pub struct ActivityTracker {
+    state: Arc<Mutex<TrackerState>>,
+}
+
+impl Default for ActivityTracker {
+    fn default() -> Self {
+        Self::new(system_clock(), DEFAULT_RETENTION)
+    }
+}
+
+impl ActivityTracker {
+    /// Tracker timestamped by `clock`, keeping `retention` worth of hours.
+    pub fn new(clock: Arc<dyn Clock>, retention: TimeDelta) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TrackerState {
+                clock,
+                retention,
+                buckets: BTreeMap::new(),
+                dirty: BTreeSet::new(),
+                last_persisted: None,
+            })),
+        }
+    }
+
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.lock().clock = clock;
+    }
+
+    /// Oldest hour still retained at `now`.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        hour_of(now) - self.lock().retention
+    }
+
+    /// Count one `category` operation now.
+    pub fn record(&self, category: ActivityCategory) {
+        let mut state = self.lock();
+        let now = state.clock.now();
+        let hour = hour_of(now);
+        *state
+            .buckets
+            .entry(hour)
+            .or_default()
+            .entry(category)
+            .or_insert(0) += 1;
+        state.dirty.insert(hour);
+        state.prune(now);
+    }
+
+    /// Add previously stored counts (e.g. loaded from the database after a
+    /// restart). Counts outside the retention window are dropped.
+    pub fn merge(&self, counts: impl IntoIterator<Item = ActivityCount>) {
+        let mut state = self.lock();
+        let now = state.clock.now();
+        for c in counts {
+            *state
+                .buckets
+                .entry(hour_of(c.hour))
+                .or_default()
+                .entry(c.category)
+                .or_insert(0) += c.count;
+        }
+        state.prune(now);
+    }
+
+    /// Hourly buckets whose hour starts within `range`.
+    pub fn histogram(&self, range: Range<DateTime<Utc>>) -> ActivityHistogram {
+        let state = self.lock();
+        let mut totals = BTreeMap::new();
+        let hours = if range.start < range.end {
+            state.buckets.range(range.clone())
+        } else {
+            // `BTreeMap::range` panics on inverted bounds.
+            state.buckets.range(range.start..range.start)
+        };
+        let buckets: Vec<ActivityBucket> = hours
+            .map(|(hour, counts)| {
+                for (category, count) in counts {
+                    *totals.entry(*category).or_insert(0) += count;
+                }
+                ActivityBucket {
+                    hour: *hour,
+                    counts: counts.clone(),
+                }
+            })
+            .collect();
+        ActivityHistogram {
+            from: range.start,
+            to: range.end,
+            buckets,
+            totals,
+        }
+    }
+
+    /// Whether dirty buckets exist and [`PERSIST_INTERVAL`] has passed since the last save.
+    pub fn persist_due(&self) -> bool {
+        let state = self.lock();
+        let now = state.clock.now();
+        !state.dirty.is_empty()
+            && state
+                .last_persisted
+                .is_none_or(|last| now - last >= PERSIST_INTERVAL)
+    }
+
+    /// Current counts of every bucket touched since the last call, marking
+    /// them clean. Call [`mark_dirty`](Self::mark_dirty) if saving them fails.
+    pub fn take_dirty(&self) -> Vec<ActivityCount> {
+        let mut state = self.lock();
+        state.last_persisted = Some(state.clock.now());
+        let dirty = std::mem::take(&mut state.dirty);
+        dirty
+            .iter()
+            .filter_map(|hour| state.buckets.get(hour).map(|counts| (hour, counts)))
+            .flat_map(|(hour, counts)| {
+                counts.iter().map(|(category, count)| ActivityCount {
+                    hour: *hour,
+                    category: *category,
+                    count: *count,
+                })
+            })
+            .collect()
+    }
+
+    /// Mark every retained bucket as needing a save.
+    pub fn mark_all_dirty(&self) {
+        let mut state = self.lock();
+        state.dirty = state.buckets.keys().copied().collect();
+    }
+
+    /// Mark the hours of `counts` as needing a save again.
+    pub fn mark_dirty(&self, counts: &[ActivityCount]) {
+        let mut state = self.lock();
+        for c in counts {
+            state.dirty.insert(c.hour);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TrackerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::clock::ManualClock;
+    use std::time::Duration;
+
+    fn start() -> DateTime<Utc> {
+        "2025-03-01T00:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_mocked_day_of_activity() {
+        let clock = ManualClock::new(start());
+        let tracker = ActivityTracker::new(Arc::new(clock.clone()), DEFAULT_RETENTION);
+
+        // Busy 09:00-12:00 UTC: 3 opens and a relayer call per open each hour.
+        for hour in 0..24 {
+            if (9..12).contains(&hour) {
+                for _ in 0..3 {
+                    tracker.record(ActivityCategory::OrdersOpened);
+                    tracker.record(ActivityCategory::RelayerCalls);
+                }
+            }
+            tracker.record(ActivityCategory::ChainTxs);
+            clock.advance(Duration::from_secs(3600));
+        }
+        // 23:59:59 still lands in the 23:00 bucket.
+        clock.set(start() + TimeDelta::hours(23) + TimeDelta::seconds(3599));
+        tracker.record(ActivityCategory::Errors);
+
+        let day = tracker.histogram(start()..start() + TimeDelta::days(1));
+        assert_eq!(day.buckets.len(), 24);
+        assert_eq!(day.totals[&ActivityCategory::OrdersOpened], 9);
+        assert_eq!(day.totals[&ActivityCategory::ChainTxs], 24);
+        let ten = &day.buckets[10];
+        assert_eq!(ten.hour, start() + TimeDelta::hours(10));
+        assert_eq!(ten.counts[&ActivityCategory::RelayerCalls], 3);
+        assert_eq!(day.buckets[23].counts[&ActivityCategory::Errors], 1);
+
+        let morning =
+            tracker.histogram(start() + TimeDelta::hours(9)..start() + TimeDelta::hours(12));
+        assert_eq!(morning.buckets.len(), 3);
+        assert_eq!(morning.totals[&ActivityCategory::OrdersOpened], 9);
+    }
+
+    #[test]
+    fn test_retention_pruning_and_restore() {
+        let clock = ManualClock::new(start());
+        let tracker = ActivityTracker::new(Arc::new(clock.clone()), TimeDelta::days(2));
+        tracker.record(ActivityCategory::Transfers);
+        let saved = tracker.take_dirty();
+        assert_eq!(saved.len(), 1);
+        assert!(tracker.take_dirty().is_empty());
+
+        clock.set(start() + TimeDelta::days(2) + TimeDelta::minutes(90));
+        tracker.record(ActivityCategory::Transfers);
+        let all = tracker.histogram(DateTime::<Utc>::MIN_UTC..DateTime::<Utc>::MAX_UTC);
+        assert_eq!(all.buckets.len(), 1);
+        assert_eq!(
+            all.buckets[0].hour,
+            start() + TimeDelta::days(2) + TimeDelta::hours(1)
+        );
+
+        // A restarted tracker picks up saved counts inside the retention window only.
+        let restarted = ActivityTracker::new(Arc::new(clock.clone()), TimeDelta::days(2));
+        restarted.merge(saved);
+        restarted.merge(tracker.take_dirty());
+        let all = restarted.histogram(DateTime::<Utc>::MIN_UTC..DateTime::<Utc>::MAX_UTC);
+        assert_eq!(all.totals[&ActivityCategory::Transfers], 1);
+    }
+
+    #[test]
+    fn test_persist_due_after_interval() {
+        let clock = ManualClock::new(start());
+        let tracker = ActivityTracker::new(Arc::new(clock.clone()), DEFAULT_RETENTION);
+        assert!(!tracker.persist_due());
+        tracker.record(ActivityCategory::Errors);
+        assert!(tracker.persist_due());
+        tracker.take_dirty();
+        tracker.record(ActivityCategory::Errors);
+        assert!(!tracker.persist_due());
+        clock.advance(Duration::from_secs(300));
+        assert!(tracker.persist_due());
+        assert_eq!(
+            ActivityCategory::parse("chain_txs"),
+            Some(ActivityCategory::ChainTxs)
+        );
+    }
+}
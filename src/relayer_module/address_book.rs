@@ -0,0 +1,470 @@
+//! Labelled Twilight, BTC and ZkOS addresses a wallet sends funds to.
+//!
+//! Transfers cannot be undone, and ZkOS transfers are private as well, so a
+//! mistyped address loses the funds. [`AddressBook::add_entry`] checks an
+//! address against the format of its [`AddressKind`] before storing it under
+//! a label:
+//!
+//! - Twilight: bech32 with the `twilight` prefix and a valid checksum.
+//! - BTC: a native SegWit address of the configured BTC network (see
+//!   [`validate_btc_segwit_address`]).
+//! - ZkOS: a standard address (see [`receiver_check::validate_address`]).
+//!
+//! The OrderWallet send paths (`transfer_to_address`, `send_tokens` and
+//! `request_btc_withdrawal`) take a label wherever they take an address. In
+//! strict mode (`OrderWallet::set_strict_address_book`) they refuse raw
+//! addresses that are not in the book, or the wallet's own, unless the call
+//! passes `allow_unknown`.
+//!
+//! An OrderWallet with a database keeps its book in the `address_book` table;
+//! without one, `OrderWallet::with_address_book_file` keeps it in a JSON file.
+//! [`AddressBook::to_json`] and [`AddressBook::from_json`] carry a book
+//! between deployments.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::receiver_check;
+use crate::wallet::BECH_PREFIX;
+use crate::wallet::btc_wallet::validation::validate_btc_segwit_address;
+
+/// What an address receives funds on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressKind {
+    /// A Twilight chain account (nyks and sats).
+    Twilight,
+    /// A Bitcoin address, for withdrawals.
+    Btc,
+    /// A ZkOS trading account address.
+    ZkOs,
+}
+
+impl AddressKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddressKind::Twilight => "twilight",
+            AddressKind::Btc => "btc",
+            AddressKind::ZkOs => "zkos",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "twilight" => Some(AddressKind::Twilight),
+            "btc" => Some(AddressKind::Btc),
+            "zkos" => Some(AddressKind::ZkOs),
+            _ => None,
+        }
+    }
+
+    /// Check that `address` has the format of this kind.
+    pub fn validate(&self, address: &str) -> Result<(), String> {
+        match self {
+            AddressKind::Twilight => validate_twilight_address(address),
+            AddressKind::Btc => validate_btc_segwit_address(address),
+            AddressKind::ZkOs => {
+                receiver_check::validate_address(address, None).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Parses as bech32 (checksum included) with the Twilight account prefix.
+fn validate_twilight_address(address: &str) -> Result<(), String> {
+    let account_id: cosmrs::AccountId = address
+        .parse()
+        .map_err(|e| format!("Invalid Twilight address: {}", e))?;
+    if account_id.prefix() != BECH_PREFIX {
+        return Err(format!(
+            "Twilight address must start with {}1, got prefix {}",
+            BECH_PREFIX,
+            account_id.prefix()
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AddressBookError {
+    #[error("invalid address book label {0:?}: {1}")]
+    InvalidLabel(String, &'static str),
+    #[error("invalid {kind} address for {label:?}: {reason}")]
+    InvalidAddress {
+        label: String,
+        kind: &'static str,
+        reason: String,
+    },
+    #[error("address book label {0:?} is already in use")]
+    DuplicateLabel(String),
+    #[error("{address} is already in the address book as {label:?}")]
+    DuplicateAddress { address: String, label: String },
+    #[error("address book entry {label:?} is a {found} address, expected {expected}")]
+    WrongKind {
+        label: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("{0} is not in the address book; pass allow_unknown to send to it")]
+    UnknownAddress(String),
+    #[error("invalid address book JSON: {0}")]
+    Json(String),
+    #[error("failed to store the address book: {0}")]
+    Storage(String),
+}
+
+/// One labelled address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBookEntry {
+    pub label: String,
+    pub address: String,
+    pub kind: AddressKind,
+    pub added_at: DateTime<Utc>,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl AddressBookEntry {
+    pub fn from_db(row: &crate::database::models::DbAddressBookEntry) -> Result<Self, String> {
+        Ok(Self {
+            label: row.label.clone(),
+            address: row.address.clone(),
+            kind: AddressKind::parse(&row.kind)
+                .ok_or_else(|| format!("Unknown address kind {:?}", row.kind))?,
+            added_at: row.added_at.and_utc(),
+        })
+    }
+}
+
+/// Addresses by label; see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressBook {
+    entries: BTreeMap<String, AddressBookEntry>,
+}
+
+/// Document written by [`AddressBook::to_json`].
+#[derive(Serialize, Deserialize)]
+struct AddressBookJson {
+    entries: Vec<AddressBookEntry>,
+}
+
+impl AddressBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Book of entries already checked, such as those read back from the
+    /// database. Later entries replace earlier ones of the same label.
+    pub fn from_entries(entries: impl IntoIterator<Item = AddressBookEntry>) -> Self {
+        Self {
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.label.clone(), entry))
+                .collect(),
+        }
+    }
+
+    /// Store `address` under `label` after checking both. A label must be
+    /// new, must not contain whitespace and must not itself be an address;
+    /// an address may be in the book only once per kind.
+    pub fn add_entry(
+        &mut self,
+        label: &str,
+        address: &str,
+        kind: AddressKind,
+        added_at: DateTime<Utc>,
+    ) -> Result<AddressBookEntry, AddressBookError> {
+        let entry = AddressBookEntry {
+            label: label.to_string(),
+            address: address.to_string(),
+            kind,
+            added_at,
+        };
+        self.check_entry(&entry)?;
+        self.entries.insert(entry.label.clone(), entry.clone());
+        Ok(entry)
+    }
+
+    fn check_entry(&self, entry: &AddressBookEntry) -> Result<(), AddressBookError> {
+        check_label(&entry.label)?;
+        entry
+            .kind
+            .validate(&entry.address)
+            .map_err(|reason| AddressBookError::InvalidAddress {
+                label: entry.label.clone(),
+                kind: entry.kind.as_str(),
+                reason,
+            })?;
+        if self.entries.contains_key(&entry.label) {
+            return Err(AddressBookError::DuplicateLabel(entry.label.clone()));
+        }
+        if let Some(existing) = self
+            .entries
+            .values()
+            .find(|e| e.kind == entry.kind && e.address == entry.address)
+        {
+            return Err(AddressBookError::DuplicateAddress {
+                address: entry.address.clone(),
+                label: existing.label.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn remove_entry(&mut self, label: &str) -> Option<AddressBookEntry> {
+        self.entries.remove(label)
+    }
+
+    pub fn get(&self, label: &str) -> Option<&AddressBookEntry> {
+        self.entries.get(label)
+    }
+
+    /// The entry holding `address`, if it is in the book.
+    pub fn verify(&self, address: &str) -> Option<&AddressBookEntry> {
+        self.entries.values().find(|entry| entry.address == address)
+    }
+
+    /// Entries by label.
+    pub fn entries(&self) -> impl Iterator<Item = &AddressBookEntry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Address to send `kind` funds to for `recipient`, a label or a raw
+    /// address. A label must be of `kind`. A raw address is returned as is,
+    /// its format left to the send path; with `strict` set it must be in the
+    /// book as a `kind` address.
+    pub fn resolve(
+        &self,
+        recipient: &str,
+        kind: AddressKind,
+        strict: bool,
+    ) -> Result<String, AddressBookError> {
+        if let Some(entry) = self.entries.get(recipient) {
+            if entry.kind != kind {
+                return Err(AddressBookError::WrongKind {
+                    label: entry.label.clone(),
+                    expected: kind.as_str(),
+                    found: entry.kind.as_str(),
+                });
+            }
+            return Ok(entry.address.clone());
+        }
+        let known = self
+            .entries
+            .values()
+            .any(|entry| entry.kind == kind && entry.address == recipient);
+        if strict && !known {
+            return Err(AddressBookError::UnknownAddress(recipient.to_string()));
+        }
+        Ok(recipient.to_string())
+    }
+
+    /// Add every entry of `other` not already here and return those added.
+    /// Entries identical to one here are skipped; an entry whose label or
+    /// address is taken by a different one fails the merge, and nothing is
+    /// added.
+    pub fn merge(&mut self, other: AddressBook) -> Result<Vec<AddressBookEntry>, AddressBookError> {
+        let mut merged = self.clone();
+        let mut added = Vec::new();
+        for entry in other.entries.into_values() {
+            let same = merged.entries.get(&entry.label).is_some_and(|existing| {
+                existing.address == entry.address && existing.kind == entry.kind
+            });
+            if same {
+                continue;
+            }
+            merged.check_entry(&entry)?;
+            merged.entries.insert(entry.label.clone(), entry.clone());
+            added.push(entry);
+        }
+        *self = merged;
+        Ok(added)
+    }
+
+    /// The book as a JSON document for [`from_json`](Self::from_json).
+    pub fn to_json(&self) -> Result<String, AddressBookError> {
+        serde_json::to_string_pretty(&AddressBookJson {
+            entries: self.entries.values().cloned().collect(),
+        })
+        .map_err(|e| AddressBookError::Json(e.to_string()))
+    }
+
+    /// Read a document written by [`to_json`](Self::to_json). Every entry is
+    /// checked as by [`add_entry`](Self::add_entry).
+    pub fn from_json(json: &str) -> Result<Self, AddressBookError> {
+        let document: AddressBookJson =
+            serde_json::from_str(json).map_err(|e| AddressBookError::Json(e.to_string()))?;
+        let mut book = Self::new();
+        for entry in document.entries {
+            book.check_entry(&entry)?;
+            book.entries.insert(entry.label.clone(), entry);
+        }
+        Ok(book)
+    }
+}
+
+fn check_label(label: &str) -> Result<(), AddressBookError> {
+    let invalid = |reason| Err(AddressBookError::InvalidLabel(label.to_string(), reason));
+    if label.is_empty() {
+        return invalid("labels cannot be empty");
+    }
+    if label.chars().any(char::is_whitespace) {
+        return invalid("labels cannot contain whitespace");
+    }
+    // A label is looked up before it is taken as an address, so one that is
+    // an address would shadow it.
+    let kinds = [AddressKind::Twilight, AddressKind::Btc, AddressKind::ZkOs];
+    if kinds.iter().any(|kind| kind.validate(label).is_ok()) {
+        return invalid("labels cannot be addresses");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn twilight_address(byte: u8) -> String {
+        cosmrs::AccountId::new(BECH_PREFIX, &[byte; 20])
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_entries_are_checked_when_added() {
+        let mut book = AddressBook::new();
+        let now = Utc::now();
+        let alice = twilight_address(1);
+        book.add_entry("alice", &alice, AddressKind::Twilight, now)
+            .unwrap();
+        assert_eq!(book.verify(&alice).unwrap().label, "alice");
+
+        // One changed character breaks the bech32 checksum.
+        let mut typo = alice.clone();
+        let last = if typo.ends_with('q') { 'p' } else { 'q' };
+        typo.pop();
+        typo.push(last);
+        assert!(matches!(
+            book.add_entry("bob", &typo, AddressKind::Twilight, now),
+            Err(AddressBookError::InvalidAddress { .. })
+        ));
+        let valoper = cosmrs::AccountId::new("twilightvaloper", &[2; 20])
+            .unwrap()
+            .to_string();
+        assert!(
+            book.add_entry("bob", &valoper, AddressKind::Twilight, now)
+                .is_err()
+        );
+        assert!(
+            book.add_entry("bob", &alice, AddressKind::ZkOs, now)
+                .is_err()
+        );
+
+        assert_eq!(
+            book.add_entry("alice", &twilight_address(3), AddressKind::Twilight, now),
+            Err(AddressBookError::DuplicateLabel("alice".to_string()))
+        );
+        assert!(matches!(
+            book.add_entry("alice2", &alice, AddressKind::Twilight, now),
+            Err(AddressBookError::DuplicateAddress { .. })
+        ));
+        assert!(matches!(
+            book.add_entry(
+                "two words",
+                &twilight_address(4),
+                AddressKind::Twilight,
+                now
+            ),
+            Err(AddressBookError::InvalidLabel(..))
+        ));
+        assert!(matches!(
+            book.add_entry(
+                &twilight_address(5),
+                &twilight_address(6),
+                AddressKind::Twilight,
+                now
+            ),
+            Err(AddressBookError::InvalidLabel(..))
+        ));
+        assert_eq!(book.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_labels_and_strict_mode() {
+        let mut book = AddressBook::new();
+        let alice = twilight_address(1);
+        book.add_entry("alice", &alice, AddressKind::Twilight, Utc::now())
+            .unwrap();
+
+        assert_eq!(
+            book.resolve("alice", AddressKind::Twilight, true),
+            Ok(alice.clone())
+        );
+        assert!(matches!(
+            book.resolve("alice", AddressKind::ZkOs, false),
+            Err(AddressBookError::WrongKind { .. })
+        ));
+        assert_eq!(book.resolve(&alice, AddressKind::Twilight, true), Ok(alice));
+        let stranger = twilight_address(9);
+        assert_eq!(
+            book.resolve(&stranger, AddressKind::Twilight, false),
+            Ok(stranger.clone())
+        );
+        assert_eq!(
+            book.resolve(&stranger, AddressKind::Twilight, true),
+            Err(AddressBookError::UnknownAddress(stranger))
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip_and_merge() {
+        let mut book = AddressBook::new();
+        let now = Utc::now();
+        book.add_entry("alice", &twilight_address(1), AddressKind::Twilight, now)
+            .unwrap();
+        book.add_entry("bob", &twilight_address(2), AddressKind::Twilight, now)
+            .unwrap();
+        let json = book.to_json().unwrap();
+        assert_eq!(AddressBook::from_json(&json).unwrap(), book);
+
+        let mut other = AddressBook::new();
+        other
+            .add_entry("alice", &twilight_address(1), AddressKind::Twilight, now)
+            .unwrap();
+        other
+            .add_entry("carol", &twilight_address(3), AddressKind::Twilight, now)
+            .unwrap();
+        let added = book.merge(other).unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].label, "carol");
+        assert_eq!(book.len(), 3);
+
+        let mut conflicting = AddressBook::new();
+        conflicting
+            .add_entry("dave", &twilight_address(4), AddressKind::Twilight, now)
+            .unwrap();
+        conflicting
+            .add_entry("bob", &twilight_address(5), AddressKind::Twilight, now)
+            .unwrap();
+        assert_eq!(
+            book.merge(conflicting),
+            Err(AddressBookError::DuplicateLabel("bob".to_string()))
+        );
+        assert!(book.get("dave").is_none());
+
+        let tampered = json.replace(&twilight_address(2), "twilight1notachecksum");
+        assert!(matches!(
+            AddressBook::from_json(&tampered),
+            Err(AddressBookError::InvalidAddress { .. })
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+//! What the connected relayer supports.
+//!
+//! Relayers are upgraded independently of this crate, so optional endpoints
+//! may or may not exist on the server a client talks to.
+//! [`RelayerJsonRpcClient::capabilities`](super::relayer_api::RelayerJsonRpcClient::capabilities)
+//! asks the server once via [`SERVER_INFO_METHOD`]. Servers that predate it
+//! are probed instead: each optional endpoint is called with an empty
+//! payload, and only a "method not found" reply marks it missing.
+//! Capabilities that cannot be probed (post-only orders, WebSocket
+//! subscriptions, client order ids) are reported as unsupported on such
+//! servers.
+
+use serde::{Deserialize, Serialize};
+
+/// JSON-RPC method returning [`ServerInfo`].
+pub const SERVER_INFO_METHOD: &str = "server_info";
+
+/// JSON-RPC method settling part of a trader position, whose presence
+/// implies [`Capability::PartialClose`].
+pub const PARTIAL_SETTLE_METHOD: &str = "settle_trade_order_partial";
+
+/// JSON-RPC "method not found" error code.
+pub(crate) const METHOD_NOT_FOUND: i32 = -32601;
+
+/// An optional relayer feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Limit orders that are rejected instead of filling on arrival.
+    PostOnly,
+    /// Multi-account queries such as `all_account_summaries`.
+    BulkQuery,
+    /// WebSocket subscriptions.
+    Ws,
+    /// `trader_order_info_v1` / `lend_order_info_v1`.
+    OrderInfoV1,
+    /// `order_funding_history`.
+    FundingHistory,
+    /// Order submits carrying a client-chosen `client_order_id`, which the
+    /// relayer uses to recognise a resubmitted order.
+    ClientOrderId,
+    /// Settlements of part of a trader position via [`PARTIAL_SETTLE_METHOD`].
+    PartialClose,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 7] = [
+        Capability::PostOnly,
+        Capability::BulkQuery,
+        Capability::Ws,
+        Capability::OrderInfoV1,
+        Capability::FundingHistory,
+        Capability::ClientOrderId,
+        Capability::PartialClose,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::PostOnly => "post_only",
+            Capability::BulkQuery => "bulk_query",
+            Capability::Ws => "ws",
+            Capability::OrderInfoV1 => "order_info_v1",
+            Capability::FundingHistory => "funding_history",
+            Capability::ClientOrderId => "client_order_id",
+            Capability::PartialClose => "partial_close",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+
+    /// Relayer method whose presence implies this capability, if it can be probed.
+    pub fn probe_method(&self) -> Option<&'static str> {
+        match self {
+            Capability::BulkQuery => Some("all_account_summaries"),
+            Capability::OrderInfoV1 => Some("trader_order_info_v1"),
+            Capability::FundingHistory => Some("order_funding_history"),
+            Capability::PartialClose => Some(PARTIAL_SETTLE_METHOD),
+            Capability::PostOnly | Capability::Ws | Capability::ClientOrderId => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Response of [`SERVER_INFO_METHOD`]. Unknown capability names are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServerInfo {
+    pub version: String,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+/// Capabilities of one relayer, from its handshake or from probing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RelayerCapabilities {
+    /// Server version; `None` when the server has no version endpoint.
+    pub version: Option<String>,
+    pub supports_post_only: bool,
+    pub supports_bulk_query: bool,
+    pub supports_ws: bool,
+    pub supports_order_info_v1: bool,
+    pub supports_funding_history: bool,
+    pub supports_client_order_id: bool,
+    pub supports_partial_close: bool,
+    /// `true` when the server advertised these itself, `false` when probed.
+    pub advertised: bool,
+}
+
+impl RelayerCapabilities {
+    /// Capabilities advertised by `info`.
+    pub fn from_server_info(info: &ServerInfo) -> Self {
+        let mut capabilities = Self {
+            version: Some(info.version.clone()),
+            advertised: true,
+            ..Self::default()
+        };
+        for capability in info.capabilities.iter().filter_map(|c| Capability::parse(c)) {
+            capabilities.set(capability, true);
+        }
+        capabilities
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::PostOnly => self.supports_post_only,
+            Capability::BulkQuery => self.supports_bulk_query,
+            Capability::Ws => self.supports_ws,
+            Capability::OrderInfoV1 => self.supports_order_info_v1,
+            Capability::FundingHistory => self.supports_funding_history,
+            Capability::ClientOrderId => self.supports_client_order_id,
+            Capability::PartialClose => self.supports_partial_close,
+        }
+    }
+
+    pub fn set(&mut self, capability: Capability, supported: bool) {
+        let flag = match capability {
+            Capability::PostOnly => &mut self.supports_post_only,
+            Capability::BulkQuery => &mut self.supports_bulk_query,
+            Capability::Ws => &mut self.supports_ws,
+            Capability::OrderInfoV1 => &mut self.supports_order_info_v1,
+            Capability::FundingHistory => &mut self.supports_funding_history,
+            Capability::ClientOrderId => &mut self.supports_client_order_id,
+            Capability::PartialClose => &mut self.supports_partial_close,
+        };
+        *flag = supported;
+    }
+
+    /// `Ok` if `capability` is supported, otherwise an error naming it and the server version.
+    pub fn require(&self, capability: Capability) -> Result<(), UnsupportedCapability> {
+        if self.supports(capability) {
+            Ok(())
+        } else {
+            Err(UnsupportedCapability {
+                capability,
+                server_version: self.version.clone(),
+            })
+        }
+    }
+}
+
+/// The connected relayer lacks a capability an operation needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedCapability {
+    pub capability: Capability,
+    pub server_version: Option<String>,
+}
+
+impl std::fmt::Display for UnsupportedCapability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.server_version {
+            Some(version) => write!(
+                f,
+                "Relayer {} does not support {}",
+                version, self.capability
+            ),
+            None => write!(
+                f,
+                "Relayer (version unknown) does not support {}",
+                self.capability
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UnsupportedCapability {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_info_maps_known_capabilities() {
+        let info = ServerInfo {
+            version: "0.9.1".to_string(),
+            capabilities: vec!["post_only".into(), "ws".into(), "teleport".into()],
+        };
+        let capabilities = RelayerCapabilities::from_server_info(&info);
+        assert!(capabilities.supports(Capability::PostOnly));
+        assert!(capabilities.supports(Capability::Ws));
+        assert!(!capabilities.supports(Capability::FundingHistory));
+        assert!(capabilities.advertised);
+
+        let err = capabilities
+            .require(Capability::FundingHistory)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Relayer 0.9.1 does not support funding_history"
+        );
+    }
+}
@@ -0,0 +1,462 @@
+//! Serialized signing and broadcast for transactions from one twilight address.
+//!
+//! Several `OrderWallet`s built from the same mnemonic share one Cosmos
+//! account, and each one's `NonceManager` only knows about its own
+//! transactions, so concurrent funding calls race on the account sequence.
+//! A [`ChainTxSerializer`] holds one `NonceManager` for its address and runs
+//! sign-and-broadcast for each submitted transaction in turn. Wallets that
+//! join it adopt that manager as their `Wallet::nonce_manager`, so their
+//! staking, withdrawal and other `Wallet::sign_and_broadcast` transactions
+//! count on the same sequence as the serialized ones. Callers are served
+//! first come, first served. Each transaction has a timeout. A sequence
+//! mismatch (code 32) resets the sequence to the one the chain expects and
+//! re-signs once, handled here for every caller.
+//!
+//! Wallets that should share a serializer look it up in the same
+//! [`ChainTxRegistry`], either one owned by the caller or the process-wide
+//! [`ChainTxRegistry::global`]:
+//!
+//! ```no_run
+//! # use nyks_wallet::relayer_module::{chain_tx::ChainTxRegistry, order_wallet::OrderWallet};
+//! # fn run(mnemonic: &str) -> Result<(), String> {
+//! let a = OrderWallet::import_from_mnemonic(mnemonic, None)?
+//!     .with_chain_tx_registry(ChainTxRegistry::global());
+//! let b = OrderWallet::import_from_mnemonic(mnemonic, None)?
+//!     .with_chain_tx_registry(ChainTxRegistry::global());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::log_privacy::LoggedAddress;
+use crate::wallet::nonce_manager::{NonceManager, SEQUENCE_MISMATCH_CODE, expected_sequence};
+
+use super::utils::{TxResult, fetch_account_details_with_retry};
+
+/// Per-transaction timeout used unless the registry is configured otherwise.
+pub const DEFAULT_TX_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Boxed future returned by [`SequenceSource::fetch`], resolving to
+/// `(sequence, account_number)`.
+pub type SequenceFuture = Pin<Box<dyn Future<Output = Result<(u64, u64), String>> + Send>>;
+
+/// Source of the on-chain sequence and account number of an address.
+/// The default [`LcdSequenceSource`] queries the LCD; tests substitute a mock.
+pub trait SequenceSource: std::fmt::Debug + Send + Sync {
+    fn fetch(&self, address: &str) -> SequenceFuture;
+}
+
+/// Fetches via [`fetch_account_details_with_retry`], waiting for a new
+/// account to be indexed.
+#[derive(Debug, Clone)]
+pub struct LcdSequenceSource {
+    pub lcd_endpoint: String,
+}
+
+impl SequenceSource for LcdSequenceSource {
+    fn fetch(&self, address: &str) -> SequenceFuture {
+        let address = address.to_string();
+        let lcd_endpoint = self.lcd_endpoint.clone();
+        Box::pin(async move {
+            fetch_account_details_with_retry(&address, &lcd_endpoint)
+                .await
+                .map(|account| (account.sequence, account.account_number))
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Counters for one serializer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChainTxStats {
+    /// Transactions handed to [`ChainTxSerializer::submit`].
+    pub submitted: u64,
+    /// Transactions the chain accepted (code 0).
+    pub accepted: u64,
+    /// Sequence fetches from the chain, including the initial sync.
+    pub refreshes: u64,
+    /// Re-signs after a sequence mismatch rejection.
+    pub mismatch_retries: u64,
+    pub timeouts: u64,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Set after a timeout or transport error, when it is unknown whether the
+    /// last sequence was consumed.
+    needs_refresh: bool,
+}
+
+/// Queue and sequence counter for one twilight address.
+#[derive(Debug)]
+pub struct ChainTxSerializer {
+    address: String,
+    nonce_manager: Arc<NonceManager>,
+    source: Arc<dyn SequenceSource>,
+    tx_timeout: Duration,
+    /// Held for the whole sign-and-broadcast of one transaction. Tokio's
+    /// mutex grants the lock in request order, which gives the fairness.
+    state: tokio::sync::Mutex<QueueState>,
+    stats: Mutex<ChainTxStats>,
+}
+
+impl ChainTxSerializer {
+    /// Serializer counting sequences on `nonce_manager`, synced from
+    /// `source` until it is.
+    pub fn new(
+        address: &str,
+        nonce_manager: Arc<NonceManager>,
+        source: Arc<dyn SequenceSource>,
+        tx_timeout: Duration,
+    ) -> Self {
+        Self {
+            address: address.to_string(),
+            nonce_manager,
+            source,
+            tx_timeout,
+            state: tokio::sync::Mutex::new(QueueState::default()),
+            stats: Mutex::new(ChainTxStats::default()),
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// The sequence counter of the address, for wallets sharing it.
+    pub fn nonce_manager(&self) -> Arc<NonceManager> {
+        self.nonce_manager.clone()
+    }
+
+    pub fn tx_timeout(&self) -> Duration {
+        self.tx_timeout
+    }
+
+    pub fn stats(&self) -> ChainTxStats {
+        *self.lock_stats()
+    }
+
+    /// Sign and broadcast one transaction once every earlier submission for
+    /// this address has finished.
+    ///
+    /// `sign_and_send` receives `(sequence, account_number)` and returns the
+    /// broadcast result. If the chain rejects the transaction for its
+    /// sequence, the counter is reset to the sequence the chain expects and
+    /// `sign_and_send` is called once more. Other non-zero codes are
+    /// returned to the caller unchanged.
+    pub async fn submit<F, Fut>(&self, mut sign_and_send: F) -> Result<TxResult, String>
+    where
+        F: FnMut(u64, u64) -> Fut,
+        Fut: Future<Output = Result<TxResult, String>>,
+    {
+        let mut state = self.state.lock().await;
+        self.lock_stats().submitted += 1;
+        if !self.nonce_manager.is_synced() || state.needs_refresh {
+            self.refresh().await?;
+            state.needs_refresh = false;
+        }
+
+        let mut retried = false;
+        loop {
+            let (sequence, account_number) = self.nonce_manager.acquire_next()?;
+            let outcome =
+                tokio::time::timeout(self.tx_timeout, sign_and_send(sequence, account_number))
+                    .await;
+            let result = match outcome {
+                Err(_) => {
+                    self.nonce_manager.release(sequence);
+                    state.needs_refresh = true;
+                    self.lock_stats().timeouts += 1;
+                    return Err(format!(
+                        "Transaction from {} with sequence {} timed out after {:?}",
+                        self.address, sequence, self.tx_timeout
+                    ));
+                }
+                Ok(Err(e)) => {
+                    self.nonce_manager.release(sequence);
+                    state.needs_refresh = true;
+                    return Err(e);
+                }
+                Ok(Ok(result)) => result,
+            };
+            if result.code == 0 {
+                self.lock_stats().accepted += 1;
+                debug!(
+                    "ChainTxSerializer: {} accepted sequence {}",
+                    LoggedAddress(&self.address),
+                    sequence
+                );
+                return Ok(result);
+            }
+            self.nonce_manager.release(sequence);
+            if result.code != SEQUENCE_MISMATCH_CODE || retried {
+                return Ok(result);
+            }
+            let expected = result.log.as_deref().and_then(expected_sequence);
+            warn!(
+                "ChainTxSerializer: {} sequence {} rejected for a sequence mismatch (chain expects {:?}), re-signing",
+                LoggedAddress(&self.address),
+                sequence,
+                expected
+            );
+            retried = true;
+            self.lock_stats().mismatch_retries += 1;
+            match expected {
+                Some(expected) => self.nonce_manager.reset_to(expected),
+                None => {
+                    let chain_sequence = self.refresh().await?;
+                    self.nonce_manager.reset_to(chain_sequence);
+                }
+            }
+        }
+    }
+
+    /// Re-anchor the counter to the chain, keeping a local count that is
+    /// ahead of it: the LCD may lag behind transactions already accepted.
+    /// Returns the chain's sequence.
+    async fn refresh(&self) -> Result<u64, String> {
+        self.lock_stats().refreshes += 1;
+        let (chain_sequence, account_number) = self.source.fetch(&self.address).await?;
+        self.nonce_manager.sync_to(chain_sequence, account_number)?;
+        debug!(
+            "ChainTxSerializer: {} synced, chain_seq={}, next={}",
+            LoggedAddress(&self.address),
+            chain_sequence,
+            self.nonce_manager.peek_next()
+        );
+        Ok(chain_sequence)
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, ChainTxStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Serializers by twilight address. Clones share the same map.
+#[derive(Debug, Clone)]
+pub struct ChainTxRegistry {
+    serializers: Arc<Mutex<HashMap<String, Arc<ChainTxSerializer>>>>,
+    /// Overrides the LCD for every serializer created here.
+    source: Option<Arc<dyn SequenceSource>>,
+    tx_timeout: Duration,
+}
+
+impl Default for ChainTxRegistry {
+    fn default() -> Self {
+        Self {
+            serializers: Arc::new(Mutex::new(HashMap::new())),
+            source: None,
+            tx_timeout: DEFAULT_TX_TIMEOUT,
+        }
+    }
+}
+
+static GLOBAL_REGISTRY: LazyLock<ChainTxRegistry> = LazyLock::new(ChainTxRegistry::default);
+
+impl ChainTxRegistry {
+    /// Registry shared by the whole process.
+    pub fn global() -> &'static ChainTxRegistry {
+        &GLOBAL_REGISTRY
+    }
+
+    /// Fetch sequences from `source` instead of each wallet's LCD endpoint.
+    pub fn with_source(mut self, source: Arc<dyn SequenceSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Timeout for each transaction of serializers created after this call.
+    pub fn with_tx_timeout(mut self, tx_timeout: Duration) -> Self {
+        self.tx_timeout = tx_timeout;
+        self
+    }
+
+    /// Serializer for `address`, created on first use with `nonce_manager`
+    /// and an [`LcdSequenceSource`] for `lcd_endpoint`. Later callers get
+    /// the first one's counter from
+    /// [`ChainTxSerializer::nonce_manager`].
+    pub fn serializer(
+        &self,
+        address: &str,
+        lcd_endpoint: &str,
+        nonce_manager: &Arc<NonceManager>,
+    ) -> Arc<ChainTxSerializer> {
+        self.lock()
+            .entry(address.to_string())
+            .or_insert_with(|| {
+                let source = self.source.clone().unwrap_or_else(|| {
+                    Arc::new(LcdSequenceSource {
+                        lcd_endpoint: lcd_endpoint.to_string(),
+                    })
+                });
+                Arc::new(ChainTxSerializer::new(
+                    address,
+                    nonce_manager.clone(),
+                    source,
+                    self.tx_timeout,
+                ))
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<ChainTxSerializer>>> {
+        self.serializers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Chain stand-in: reports `sequence` and accepts only that sequence.
+    #[derive(Debug, Default)]
+    struct MockChain {
+        sequence: AtomicU64,
+        fetches: AtomicU64,
+        sent: Mutex<Vec<(String, u64)>>,
+    }
+
+    impl MockChain {
+        fn broadcast(&self, caller: &str, sequence: u64) -> TxResult {
+            let expected = self.sequence.load(Ordering::SeqCst);
+            if sequence != expected {
+                return TxResult {
+                    tx_hash: String::new(),
+                    code: 32,
+                    simulated: false,
+                    attempts: Vec::new(),
+                    log: None,
+                };
+            }
+            self.sequence.store(expected + 1, Ordering::SeqCst);
+            self.sent
+                .lock()
+                .unwrap()
+                .push((caller.to_string(), sequence));
+            TxResult {
+                tx_hash: format!("tx-{}", sequence),
+                code: 0,
+                simulated: false,
+                attempts: Vec::new(),
+                log: None,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct MockSource(Arc<MockChain>);
+
+    impl SequenceSource for MockSource {
+        fn fetch(&self, _address: &str) -> SequenceFuture {
+            self.0.fetches.fetch_add(1, Ordering::SeqCst);
+            let sequence = self.0.sequence.load(Ordering::SeqCst);
+            Box::pin(async move { Ok((sequence, 7)) })
+        }
+    }
+
+    async fn send(
+        serializer: &ChainTxSerializer,
+        chain: &MockChain,
+        caller: &str,
+    ) -> Result<TxResult, String> {
+        serializer
+            .submit(|sequence, account_number| {
+                assert_eq!(account_number, 7);
+                let result = chain.broadcast(caller, sequence);
+                async move {
+                    // Yield so queued callers get a chance to interleave.
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    Ok(result)
+                }
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_mismatch_refreshes_once() {
+        let chain = Arc::new(MockChain::default());
+        chain.sequence.store(3, Ordering::SeqCst);
+        let serializer = ChainTxSerializer::new(
+            "twilight1a",
+            Arc::default(),
+            Arc::new(MockSource(chain.clone())),
+            DEFAULT_TX_TIMEOUT,
+        );
+        assert_eq!(send(&serializer, &chain, "a").await.unwrap().tx_hash, "tx-3");
+
+        // Another process spends sequence 4 behind our back.
+        chain.sequence.store(5, Ordering::SeqCst);
+        let result = send(&serializer, &chain, "a").await.unwrap();
+        assert_eq!(result.tx_hash, "tx-5");
+        let stats = serializer.stats();
+        assert_eq!(stats.refreshes, 2);
+        assert_eq!(stats.mismatch_retries, 1);
+        assert_eq!(stats.accepted, 2);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_forces_refresh() {
+        let chain = Arc::new(MockChain::default());
+        let serializer = ChainTxSerializer::new(
+            "twilight1a",
+            Arc::default(),
+            Arc::new(MockSource(chain.clone())),
+            Duration::from_millis(20),
+        );
+        let err = serializer
+            .submit(|_, _| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(TxResult {
+                    tx_hash: String::new(),
+                    code: 0,
+                    simulated: false,
+                    attempts: Vec::new(),
+                    log: None,
+                })
+            })
+            .await
+            .unwrap_err();
+        assert!(err.contains("timed out"));
+
+        // The timed-out tx never landed; the next one re-syncs and reuses sequence 0.
+        let result = send(&serializer, &chain, "a").await.unwrap();
+        assert_eq!(result.tx_hash, "tx-0");
+        assert_eq!(serializer.stats().timeouts, 1);
+        assert_eq!(serializer.stats().refreshes, 2);
+    }
+
+    #[test]
+    fn test_registry_shares_by_address() {
+        let registry = ChainTxRegistry::default();
+        let first = Arc::new(NonceManager::new());
+        let a = registry.serializer("twilight1a", "http://lcd", &first);
+        let b = registry.serializer("twilight1a", "http://other-lcd", &Arc::default());
+        let c = registry.serializer("twilight1b", "http://lcd", &Arc::default());
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        // The address counts on the first wallet's sequence.
+        assert!(Arc::ptr_eq(&b.nonce_manager(), &first));
+        // Clones share the map.
+        registry
+            .clone()
+            .serializer("twilight1b", "http://lcd", &Arc::default());
+        assert_eq!(registry.len(), 2);
+    }
+}
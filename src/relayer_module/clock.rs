@@ -0,0 +1,161 @@
+//! Clock abstraction for time-dependent relayer logic.
+//!
+//! Production code uses [`SystemClock`], which reads the wall clock and sleeps
+//! on the tokio timer. Tests and backtests can swap in a [`ManualClock`] whose
+//! time only moves when told to, so time-dependent flows run deterministically
+//! and without real waiting.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Boxed future returned by [`Clock::sleep_until`].
+pub type SleepFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Source of "now" and of waiting, injectable into [`OrderWallet`](super::order_wallet::OrderWallet).
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Current time according to this clock.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Wait until `deadline` has been reached on this clock.
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> SleepFuture<'_>;
+
+    /// Wait for `duration` on this clock.
+    fn sleep(&self, duration: Duration) -> SleepFuture<'_> {
+        let step = ChronoDuration::from_std(duration).unwrap_or(ChronoDuration::MAX);
+        let deadline = self.now().checked_add_signed(step).unwrap_or(DateTime::<Utc>::MAX_UTC);
+        self.sleep_until(deadline)
+    }
+}
+
+/// Wall-clock implementation backed by `chrono::Utc::now` and `tokio::time::sleep`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> SleepFuture<'_> {
+        Box::pin(async move {
+            let remaining = (deadline - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+            if !remaining.is_zero() {
+                tokio::time::sleep(remaining).await;
+            }
+        })
+    }
+}
+
+/// Deterministic clock for tests and backtesting.
+///
+/// Time only changes via [`set`](ManualClock::set) / [`advance`](ManualClock::advance),
+/// or when a sleep is requested: `sleep_until` jumps the clock forward to the
+/// deadline and returns immediately. Clones share the same underlying time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl ManualClock {
+    /// Create a manual clock starting at `start`.
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    /// Set the current time. Moving backwards is allowed.
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.lock() = time;
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let step = ChronoDuration::from_std(duration).unwrap_or(ChronoDuration::MAX);
+        let mut now = self.lock();
+        *now = now.checked_add_signed(step).unwrap_or(DateTime::<Utc>::MAX_UTC);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DateTime<Utc>> {
+        // A poisoned lock only means another holder panicked; the timestamp itself is still valid.
+        self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(DateTime::<Utc>::UNIX_EPOCH)
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.lock()
+    }
+
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> SleepFuture<'_> {
+        let mut now = self.lock();
+        if deadline > *now {
+            *now = deadline;
+        }
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// Default clock used when none is supplied.
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_set_and_advance() {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now(), start + ChronoDuration::seconds(90));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+
+    #[test]
+    fn test_manual_clock_clones_share_time() {
+        let clock = ManualClock::default();
+        let other = clock.clone();
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(other.now(), clock.now());
+    }
+
+    #[tokio::test]
+    async fn test_manual_clock_sleep_jumps_forward() {
+        let clock = ManualClock::default();
+        let start = clock.now();
+        let wall = std::time::Instant::now();
+
+        clock.sleep(Duration::from_secs(3600)).await;
+        assert_eq!(clock.now(), start + ChronoDuration::hours(1));
+
+        // Sleeping until a past deadline never moves time backwards.
+        clock.sleep_until(start).await;
+        assert_eq!(clock.now(), start + ChronoDuration::hours(1));
+
+        assert!(wall.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_system_clock_past_deadline_returns() {
+        let clock = SystemClock;
+        let before = clock.now();
+        clock.sleep_until(before - ChronoDuration::seconds(10)).await;
+        assert!(clock.now() >= before);
+    }
+}
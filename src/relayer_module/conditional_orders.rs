@@ -0,0 +1,817 @@
+//! Client-side stop-loss and take-profit triggers.
+//!
+//! The relayer executes MARKET and LIMIT orders only. A
+//! [`ConditionalOrderManager`] keeps price triggers per account and closes
+//! the position with `close_trader_order` once the BTC/USD price crosses
+//! one: a stop-loss when the price moves against the position (down to the
+//! trigger for a LONG, up to it for a SHORT), a take-profit when it moves
+//! the other way. The two triggers of an account are a pair: when one
+//! fires, both are removed.
+//!
+//! A trigger fires once. If the close fails, the trigger stays armed and
+//! the close is submitted again, with backoff
+//! ([`ConditionalOrderManager::with_close_retry`]), on the first price past
+//! the trigger after the delay. Once the attempts run out the trigger is
+//! disarmed until the price has moved back past it by the hysteresis band
+//! ([`ConditionalOrderManager::with_hysteresis_pct`]). A price that recovers
+//! past the band re-arms a retrying trigger as well, with its attempts reset.
+//!
+//! [`ConditionalOrderManager::start`] polls `btc_usd_price` in a background
+//! task; with the `ws` feature, `start_with_feed` follows the live price
+//! stream instead. What happened is broadcast as [`TriggerEvent`]s. With
+//! DB persistence every trigger change is written to the
+//! `conditional_triggers` table and [`ConditionalOrderManager::new`] reads
+//! the triggers back, so they survive a restart. A trigger read back while
+//! firing was cut off mid-close: its account's order is queried, and the
+//! triggers are dropped if the order is no longer filled, or the close is
+//! retried otherwise.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::compat::relayer_types::{OrderStatus, OrderType, PositionType};
+use crate::config::RetryPolicy;
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::database::DatabaseManager;
+
+use super::clock::Clock;
+use super::order_wallet::{AccountIndex, OrderWallet};
+#[cfg(feature = "ws")]
+use super::{relayer_types::BtcUsdPrice, relayer_ws::FeedStream};
+
+/// Default interval between `btc_usd_price` polls.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default hysteresis band, in percent of the trigger price.
+pub const DEFAULT_HYSTERESIS_PCT: f64 = 0.1;
+
+/// Default backoff between closes of a crossed trigger: 5 s, doubling up to
+/// a minute, for at most ten closes.
+pub const DEFAULT_CLOSE_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 10,
+    utxo_max_attempts: 10,
+    initial_delay: Duration::from_secs(5),
+    max_delay: Duration::from_secs(60),
+    backoff_multiplier: 2.0,
+    request_timeout: Duration::from_secs(30),
+};
+
+/// Events buffered for each [`ConditionalOrderManager::subscribe`] receiver.
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerKind {
+    StopLoss,
+    TakeProfit,
+}
+
+impl TriggerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TriggerKind::StopLoss => "stop_loss",
+            TriggerKind::TakeProfit => "take_profit",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "stop_loss" => Some(TriggerKind::StopLoss),
+            "take_profit" => Some(TriggerKind::TakeProfit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TriggerState {
+    Armed,
+    /// The close is being submitted, after `failures` failed ones.
+    Firing {
+        #[serde(default)]
+        failures: u32,
+    },
+    /// The last close failed; it is submitted again on a price past the
+    /// trigger from `retry_at` on.
+    Retrying {
+        error: String,
+        failures: u32,
+        retry_at: DateTime<Utc>,
+    },
+    /// The closes ran out; re-armed once the price recovers past the band.
+    Disarmed {
+        error: String,
+    },
+}
+
+/// A price trigger that closes the trader order of `account_index`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalTrigger {
+    pub account_index: AccountIndex,
+    pub kind: TriggerKind,
+    pub trigger_price: f64,
+    /// Side of the position, which decides the direction of the trigger.
+    pub position_type: PositionType,
+    /// A LIMIT close is placed at `trigger_price`.
+    pub close_order_type: OrderType,
+    pub state: TriggerState,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ConditionalTrigger {
+    pub fn new(
+        account_index: AccountIndex,
+        kind: TriggerKind,
+        trigger_price: f64,
+        position_type: PositionType,
+        close_order_type: OrderType,
+        created_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            account_index,
+            kind,
+            trigger_price,
+            position_type,
+            close_order_type,
+            state: TriggerState::Armed,
+            created_at,
+        }
+    }
+
+    /// Whether the trigger fires on a falling price.
+    fn fires_below(&self) -> bool {
+        matches!(
+            (self.kind, &self.position_type),
+            (TriggerKind::StopLoss, PositionType::LONG)
+                | (TriggerKind::TakeProfit, PositionType::SHORT)
+        )
+    }
+
+    /// Whether `price` has reached the trigger.
+    pub fn crossed(&self, price: f64) -> bool {
+        if self.fires_below() {
+            price <= self.trigger_price
+        } else {
+            price >= self.trigger_price
+        }
+    }
+
+    /// Whether `price` is back on the untriggered side by more than
+    /// `hysteresis_pct` percent of the trigger price.
+    pub fn recovered(&self, price: f64, hysteresis_pct: f64) -> bool {
+        let band = self.trigger_price * hysteresis_pct / 100.0;
+        if self.fires_below() {
+            price > self.trigger_price + band
+        } else {
+            price < self.trigger_price - band
+        }
+    }
+
+    /// Execution price passed to `close_trader_order`.
+    fn execution_price(&self) -> f64 {
+        match self.close_order_type {
+            OrderType::LIMIT => self.trigger_price,
+            _ => 0.0,
+        }
+    }
+}
+
+/// What a [`ConditionalOrderManager`] did on a price update.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerEvent {
+    /// The close was submitted; both triggers of the account are removed.
+    Fired {
+        account_index: AccountIndex,
+        kind: TriggerKind,
+        trigger_price: f64,
+        price: f64,
+        request_id: String,
+    },
+    /// The close failed. It is retried from `retry_at` on; without one the
+    /// attempts ran out and the trigger is disarmed.
+    Failed {
+        account_index: AccountIndex,
+        kind: TriggerKind,
+        trigger_price: f64,
+        price: f64,
+        error: String,
+        retry_at: Option<DateTime<Utc>>,
+    },
+    Rearmed {
+        account_index: AccountIndex,
+        kind: TriggerKind,
+        price: f64,
+    },
+}
+
+// -------------------------
+// Trigger book
+// -------------------------
+
+/// The triggers of a manager, at most one per account and kind.
+#[derive(Debug, Clone, Default)]
+pub struct TriggerBook {
+    triggers: BTreeMap<(AccountIndex, TriggerKind), ConditionalTrigger>,
+}
+
+impl TriggerBook {
+    /// Add `trigger`, replacing the one of the same account and kind.
+    pub fn insert(&mut self, trigger: ConditionalTrigger) {
+        self.triggers
+            .insert((trigger.account_index, trigger.kind), trigger);
+    }
+
+    pub fn remove(&mut self, index: AccountIndex, kind: TriggerKind) -> Option<ConditionalTrigger> {
+        self.triggers.remove(&(index, kind))
+    }
+
+    pub fn get(&self, index: AccountIndex, kind: TriggerKind) -> Option<&ConditionalTrigger> {
+        self.triggers.get(&(index, kind))
+    }
+
+    pub fn triggers(&self) -> impl Iterator<Item = &ConditionalTrigger> {
+        self.triggers.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.triggers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triggers.is_empty()
+    }
+
+    /// Set the state of a trigger that is still in the book.
+    fn set_state(
+        &mut self,
+        index: AccountIndex,
+        kind: TriggerKind,
+        state: TriggerState,
+    ) -> Option<ConditionalTrigger> {
+        let trigger = self.triggers.get_mut(&(index, kind))?;
+        trigger.state = state;
+        Some(trigger.clone())
+    }
+
+    /// Move every trigger to `price` at `now`. Disarmed and retrying
+    /// triggers the price has recovered from are re-armed; armed triggers
+    /// the price has crossed, and retrying ones past their `retry_at`, are
+    /// marked firing, one per account and none for an account that is
+    /// already firing. Returns the triggers to fire and the re-armed ones.
+    pub fn advance(
+        &mut self,
+        price: f64,
+        hysteresis_pct: f64,
+        now: DateTime<Utc>,
+    ) -> (Vec<ConditionalTrigger>, Vec<ConditionalTrigger>) {
+        let mut busy: BTreeSet<AccountIndex> = self
+            .triggers
+            .values()
+            .filter(|t| matches!(t.state, TriggerState::Firing { .. }))
+            .map(|t| t.account_index)
+            .collect();
+        let mut firing = Vec::new();
+        let mut rearmed = Vec::new();
+        for trigger in self.triggers.values_mut() {
+            let failures = match trigger.state {
+                TriggerState::Disarmed { .. } | TriggerState::Retrying { .. }
+                    if trigger.recovered(price, hysteresis_pct) =>
+                {
+                    trigger.state = TriggerState::Armed;
+                    rearmed.push(trigger.clone());
+                    continue;
+                }
+                TriggerState::Armed => 0,
+                TriggerState::Retrying {
+                    failures, retry_at, ..
+                } if now >= retry_at => failures,
+                _ => continue,
+            };
+            if trigger.crossed(price) && busy.insert(trigger.account_index) {
+                trigger.state = TriggerState::Firing { failures };
+                firing.push(trigger.clone());
+            }
+        }
+        (firing, rearmed)
+    }
+}
+
+// -------------------------
+// Manager
+// -------------------------
+
+/// Stop-loss and take-profit triggers over a shared [`OrderWallet`].
+///
+/// Clones share the triggers and the event channel.
+#[derive(Clone)]
+pub struct ConditionalOrderManager {
+    wallet: Arc<tokio::sync::Mutex<OrderWallet>>,
+    book: Arc<Mutex<TriggerBook>>,
+    /// The wallet's clock, which times close retries.
+    clock: Arc<dyn Clock>,
+    poll_interval: Duration,
+    hysteresis_pct: f64,
+    close_retry: RetryPolicy,
+    events: broadcast::Sender<TriggerEvent>,
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    db_manager: Option<DatabaseManager>,
+}
+
+impl ConditionalOrderManager {
+    /// Manager over `wallet`, with the triggers stored for it when the
+    /// wallet persists to a database.
+    pub async fn new(wallet: Arc<tokio::sync::Mutex<OrderWallet>>) -> Result<Self, String> {
+        #[allow(unused_mut)]
+        let mut triggers = Vec::new();
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(db_manager) = wallet.lock().await.get_db_manager() {
+            triggers = db_manager.load_conditional_triggers()?;
+        }
+        Ok(Self::from_triggers(wallet, triggers).await)
+    }
+
+    /// Manager over `wallet` with `triggers`, e.g. kept by the host rather
+    /// than in the wallet's database. Triggers that were firing are settled
+    /// against the relayer first; see the [module docs](self).
+    pub async fn from_triggers(
+        wallet: Arc<tokio::sync::Mutex<OrderWallet>>,
+        triggers: Vec<ConditionalTrigger>,
+    ) -> Self {
+        let mut book = TriggerBook::default();
+        for trigger in triggers {
+            book.insert(trigger);
+        }
+        let clock = wallet.lock().await.clock();
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let db_manager = wallet.lock().await.get_db_manager().cloned();
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        let manager = Self {
+            wallet,
+            book: Arc::new(Mutex::new(book)),
+            clock,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            hysteresis_pct: DEFAULT_HYSTERESIS_PCT,
+            close_retry: DEFAULT_CLOSE_RETRY,
+            events,
+            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+            db_manager,
+        };
+        manager.recover_interrupted().await;
+        manager
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Band, in percent of the trigger price, the price must move back past
+    /// to re-arm a trigger whose closes failed.
+    pub fn with_hysteresis_pct(mut self, hysteresis_pct: f64) -> Self {
+        self.hysteresis_pct = hysteresis_pct.max(0.0);
+        self
+    }
+
+    /// Delays between closes of a crossed trigger after a failed one, and
+    /// `max_attempts`, the closes made before the trigger is disarmed.
+    /// `request_timeout` is not used.
+    pub fn with_close_retry(mut self, close_retry: RetryPolicy) -> Self {
+        self.close_retry = close_retry;
+        self
+    }
+
+    pub fn wallet(&self) -> &Arc<tokio::sync::Mutex<OrderWallet>> {
+        &self.wallet
+    }
+
+    /// Events from now on. A receiver that falls more than 64 events behind
+    /// skips the oldest.
+    pub fn subscribe(&self) -> broadcast::Receiver<TriggerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Current triggers, by account index.
+    pub fn triggers(&self) -> Vec<ConditionalTrigger> {
+        self.book.lock().unwrap().triggers().cloned().collect()
+    }
+
+    /// Close the position of `index` with a `close_order_type` order once
+    /// the price reaches `trigger_price` against it.
+    pub async fn add_stop_loss(
+        &self,
+        index: AccountIndex,
+        trigger_price: f64,
+        close_order_type: OrderType,
+    ) -> Result<(), String> {
+        self.add(
+            index,
+            TriggerKind::StopLoss,
+            trigger_price,
+            close_order_type,
+        )
+        .await
+    }
+
+    /// Close the position of `index` at market once the price reaches
+    /// `trigger_price` in its favour.
+    pub async fn add_take_profit(
+        &self,
+        index: AccountIndex,
+        trigger_price: f64,
+    ) -> Result<(), String> {
+        self.add(
+            index,
+            TriggerKind::TakeProfit,
+            trigger_price,
+            OrderType::MARKET,
+        )
+        .await
+    }
+
+    /// Look up the side of the filled order on `index` and register the trigger.
+    async fn add(
+        &self,
+        index: AccountIndex,
+        kind: TriggerKind,
+        trigger_price: f64,
+        close_order_type: OrderType,
+    ) -> Result<(), String> {
+        if !trigger_price.is_finite() || trigger_price <= 0.0 {
+            return Err(format!("Invalid trigger price: {}", trigger_price));
+        }
+        let order = self
+            .wallet
+            .lock()
+            .await
+            .query_trader_order(index)
+            .await
+            .map_err(|e| e.to_string())?;
+        if order.order_status != OrderStatus::FILLED {
+            return Err(format!(
+                "Account {} has no filled trader order, status: {}",
+                index,
+                order.order_status.to_str()
+            ));
+        }
+        let trigger = ConditionalTrigger::new(
+            index,
+            kind,
+            trigger_price,
+            order.position_type,
+            close_order_type,
+            self.clock.now(),
+        );
+        info!(
+            "Added {} for account {} at {}",
+            kind.as_str(),
+            index,
+            trigger_price
+        );
+        self.persist(&trigger);
+        self.book.lock().unwrap().insert(trigger);
+        Ok(())
+    }
+
+    /// Drop the `kind` trigger of `index`. Returns whether there was one.
+    pub fn remove(&self, index: AccountIndex, kind: TriggerKind) -> bool {
+        let removed = self.book.lock().unwrap().remove(index, kind).is_some();
+        if removed {
+            self.unpersist(index, kind);
+        }
+        removed
+    }
+
+    /// Drop both triggers of `index`, e.g. after closing the position by hand.
+    pub fn remove_all(&self, index: AccountIndex) {
+        for kind in [TriggerKind::StopLoss, TriggerKind::TakeProfit] {
+            self.remove(index, kind);
+        }
+    }
+
+    /// Act on a new BTC/USD price: re-arm recovered triggers and close the
+    /// positions whose trigger the price crossed. The returned events are
+    /// also broadcast to subscribers.
+    pub async fn on_price(&self, price: f64) -> Vec<TriggerEvent> {
+        let now = self.clock.now();
+        let (firing, rearmed) = self
+            .book
+            .lock()
+            .unwrap()
+            .advance(price, self.hysteresis_pct, now);
+        let mut events = Vec::new();
+        for trigger in rearmed {
+            self.persist(&trigger);
+            events.push(TriggerEvent::Rearmed {
+                account_index: trigger.account_index,
+                kind: trigger.kind,
+                price,
+            });
+        }
+        for trigger in firing {
+            self.persist(&trigger);
+            events.push(self.fire(&trigger, price).await);
+        }
+        for event in &events {
+            // No receivers is not an error.
+            let _ = self.events.send(event.clone());
+        }
+        events
+    }
+
+    async fn fire(&self, trigger: &ConditionalTrigger, price: f64) -> TriggerEvent {
+        let index = trigger.account_index;
+        info!(
+            "{} of account {} at {} triggered by price {}",
+            trigger.kind.as_str(),
+            index,
+            trigger.trigger_price,
+            price
+        );
+        let result = self
+            .wallet
+            .lock()
+            .await
+            .close_trader_order(
+                index,
+                trigger.close_order_type.clone(),
+                trigger.execution_price(),
+            )
+            .await;
+        match result {
+            Ok(request_id) => {
+                self.remove_all(index);
+                TriggerEvent::Fired {
+                    account_index: index,
+                    kind: trigger.kind,
+                    trigger_price: trigger.trigger_price,
+                    price,
+                    request_id,
+                }
+            }
+            Err(e) => {
+                let error = e.to_string();
+                warn!(
+                    "Closing account {} on {} failed: {}",
+                    index,
+                    trigger.kind.as_str(),
+                    error
+                );
+                let retry_at = self.retry_later(trigger, error.clone());
+                TriggerEvent::Failed {
+                    account_index: index,
+                    kind: trigger.kind,
+                    trigger_price: trigger.trigger_price,
+                    price,
+                    error,
+                    retry_at,
+                }
+            }
+        }
+    }
+
+    /// Schedule the next close of the firing `trigger` after one failed
+    /// with `error`, or disarm it when the closes ran out. Returns when the
+    /// close is retried.
+    fn retry_later(&self, trigger: &ConditionalTrigger, error: String) -> Option<DateTime<Utc>> {
+        let failures = match trigger.state {
+            TriggerState::Firing { failures } => failures + 1,
+            _ => 1,
+        };
+        let (state, retry_at) = if failures >= self.close_retry.max_attempts {
+            (TriggerState::Disarmed { error }, None)
+        } else {
+            let delay = chrono::Duration::from_std(self.close_retry.delay(failures - 1))
+                .unwrap_or(chrono::Duration::MAX);
+            let retry_at = self
+                .clock
+                .now()
+                .checked_add_signed(delay)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC);
+            let state = TriggerState::Retrying {
+                error,
+                failures,
+                retry_at,
+            };
+            (state, Some(retry_at))
+        };
+        let index = trigger.account_index;
+        let updated = self
+            .book
+            .lock()
+            .unwrap()
+            .set_state(index, trigger.kind, state);
+        if let Some(updated) = updated {
+            self.persist(&updated);
+        }
+        retry_at
+    }
+
+    /// Settle the triggers that were firing when the manager last stopped.
+    /// An account whose order is no longer filled was closed, and both its
+    /// triggers are dropped; otherwise the close is retried.
+    async fn recover_interrupted(&self) {
+        let interrupted: Vec<ConditionalTrigger> = self
+            .book
+            .lock()
+            .unwrap()
+            .triggers()
+            .filter(|t| matches!(t.state, TriggerState::Firing { .. }))
+            .cloned()
+            .collect();
+        for trigger in interrupted {
+            let index = trigger.account_index;
+            let order = self.wallet.lock().await.query_trader_order(index).await;
+            let error = match order {
+                Ok(order) if order.order_status != OrderStatus::FILLED => {
+                    info!(
+                        "Account {} was closed while its {} was firing, status: {}",
+                        index,
+                        trigger.kind.as_str(),
+                        order.order_status.to_str()
+                    );
+                    self.remove_all(index);
+                    continue;
+                }
+                Ok(_) => "interrupted while closing".to_string(),
+                Err(e) => format!("interrupted while closing: {}", e),
+            };
+            self.retry_later(&trigger, error);
+        }
+    }
+
+    /// Poll `btc_usd_price` every poll interval and act on each price until
+    /// the task is aborted. Failed polls are logged and skipped.
+    pub fn start(&self) -> JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let client = manager.wallet.lock().await.relayer_api_client.clone();
+            let mut interval = tokio::time::interval(manager.poll_interval);
+            loop {
+                interval.tick().await;
+                match client.btc_usd_price().await {
+                    Ok(price) => {
+                        manager.on_price(price.price).await;
+                    }
+                    Err(e) => debug!("Conditional orders: price poll failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Act on every price of `feed` until it ends or the task is aborted.
+    #[cfg(feature = "ws")]
+    pub fn start_with_feed(&self, mut feed: FeedStream<BtcUsdPrice>) -> JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            while let Some(price) = feed.next().await {
+                manager.on_price(price.price).await;
+            }
+        })
+    }
+
+    fn persist(&self, trigger: &ConditionalTrigger) {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(ref db_manager) = self.db_manager {
+            if let Err(e) = db_manager.save_conditional_trigger(trigger) {
+                warn!(
+                    "Failed to save {} of account {}: {}",
+                    trigger.kind.as_str(),
+                    trigger.account_index,
+                    e
+                );
+            }
+        }
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let _ = trigger;
+    }
+
+    fn unpersist(&self, index: AccountIndex, kind: TriggerKind) {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(ref db_manager) = self.db_manager {
+            if let Err(e) = db_manager.remove_conditional_trigger(index, kind.as_str()) {
+                warn!(
+                    "Failed to remove {} of account {}: {}",
+                    kind.as_str(),
+                    index,
+                    e
+                );
+            }
+        }
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let _ = (index, kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: DateTime<Utc> = DateTime::<Utc>::UNIX_EPOCH;
+
+    fn trigger(kind: TriggerKind, price: f64, position_type: PositionType) -> ConditionalTrigger {
+        ConditionalTrigger::new(1, kind, price, position_type, OrderType::MARKET, NOW)
+    }
+
+    #[test]
+    fn test_trigger_direction_follows_position_side() {
+        let long_stop = trigger(TriggerKind::StopLoss, 60_000.0, PositionType::LONG);
+        assert!(long_stop.crossed(59_999.0));
+        assert!(!long_stop.crossed(60_001.0));
+        let short_stop = trigger(TriggerKind::StopLoss, 60_000.0, PositionType::SHORT);
+        assert!(short_stop.crossed(60_001.0));
+        let long_take = trigger(TriggerKind::TakeProfit, 70_000.0, PositionType::LONG);
+        assert!(long_take.crossed(70_000.0));
+        assert!(!long_take.crossed(69_000.0));
+        let short_take = trigger(TriggerKind::TakeProfit, 50_000.0, PositionType::SHORT);
+        assert!(short_take.crossed(49_000.0));
+    }
+
+    #[test]
+    fn test_advance_fires_once_per_account() {
+        let mut book = TriggerBook::default();
+        book.insert(trigger(TriggerKind::StopLoss, 60_000.0, PositionType::LONG));
+        book.insert(trigger(
+            TriggerKind::TakeProfit,
+            70_000.0,
+            PositionType::LONG,
+        ));
+
+        let (firing, rearmed) = book.advance(59_000.0, 0.1, NOW);
+        assert_eq!(firing.len(), 1);
+        assert_eq!(firing[0].kind, TriggerKind::StopLoss);
+        assert!(rearmed.is_empty());
+        // Still firing: the next tick does not submit a second close.
+        assert!(book.advance(58_000.0, 0.1, NOW).0.is_empty());
+    }
+
+    #[test]
+    fn test_failed_trigger_rearms_past_the_band() {
+        let mut book = TriggerBook::default();
+        book.insert(trigger(TriggerKind::StopLoss, 60_000.0, PositionType::LONG));
+        let failed = TriggerState::Disarmed {
+            error: "relayer down".to_string(),
+        };
+        book.set_state(1, TriggerKind::StopLoss, failed);
+
+        // Hovering at the trigger, inside the 0.1% band (60 USD): nothing.
+        assert_eq!(book.advance(59_990.0, 0.1, NOW), (vec![], vec![]));
+        assert_eq!(book.advance(60_050.0, 0.1, NOW), (vec![], vec![]));
+        let (_, rearmed) = book.advance(60_061.0, 0.1, NOW);
+        assert_eq!(rearmed.len(), 1);
+        let (firing, _) = book.advance(59_900.0, 0.1, NOW);
+        assert_eq!(firing.len(), 1);
+    }
+
+    #[test]
+    fn test_retrying_trigger_fires_again_only_while_crossed() {
+        let mut book = TriggerBook::default();
+        book.insert(trigger(TriggerKind::StopLoss, 60_000.0, PositionType::LONG));
+        let retry_at = NOW + chrono::Duration::seconds(5);
+        let retrying = TriggerState::Retrying {
+            error: "relayer down".to_string(),
+            failures: 2,
+            retry_at,
+        };
+        book.set_state(1, TriggerKind::StopLoss, retrying.clone());
+
+        // Still crossed, but before the delay is up.
+        assert_eq!(book.advance(59_000.0, 0.1, NOW), (vec![], vec![]));
+        // Past the delay but back above the trigger, inside the band.
+        assert_eq!(book.advance(60_030.0, 0.1, retry_at), (vec![], vec![]));
+        let (firing, _) = book.advance(59_500.0, 0.1, retry_at);
+        assert_eq!(firing[0].state, TriggerState::Firing { failures: 2 });
+
+        // A recovery past the band re-arms it with the count reset.
+        book.set_state(1, TriggerKind::StopLoss, retrying);
+        let (_, rearmed) = book.advance(60_100.0, 0.1, NOW);
+        assert_eq!(rearmed[0].state, TriggerState::Armed);
+        let (firing, _) = book.advance(59_000.0, 0.1, NOW);
+        assert_eq!(firing[0].state, TriggerState::Firing { failures: 0 });
+    }
+
+    #[test]
+    fn test_trigger_round_trips_through_json() {
+        let mut stop = trigger(TriggerKind::StopLoss, 60_000.0, PositionType::SHORT);
+        stop.state = TriggerState::Disarmed {
+            error: "timeout".to_string(),
+        };
+        let json = serde_json::to_value(&stop).unwrap();
+        assert_eq!(json["kind"], "stop_loss");
+        assert_eq!(json["state"]["state"], "disarmed");
+        let parsed: ConditionalTrigger = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, stop);
+        assert_eq!(
+            TriggerKind::parse("take_profit"),
+            Some(TriggerKind::TakeProfit)
+        );
+    }
+}
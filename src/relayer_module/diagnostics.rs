@@ -0,0 +1,37 @@
+//! Point-in-time diagnostic summary of an [`OrderWallet`](super::order_wallet::OrderWallet).
+//!
+//! [`OrderWallet::diagnostic_snapshot`](super::order_wallet::OrderWallet::diagnostic_snapshot)
+//! gathers what an operator needs to triage a wallet without reading logs:
+//! account and pending-operation counts, which optional subsystems are on,
+//! the last day of hourly activity, the most recent risk report and any
+//! configuration drift found when the wallet was loaded.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::ConfigDrift;
+
+use super::activity::ActivityHistogram;
+use super::risk::RiskReport;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiagnosticSnapshot {
+    pub generated_at: DateTime<Utc>,
+    pub chain_id: String,
+    pub accounts: usize,
+    /// Accounts moved out of the active set as spent.
+    pub archived_accounts: usize,
+    /// Accounts whose most recent operation failed.
+    pub accounts_with_errors: usize,
+    /// Multi-step operations not yet completed.
+    pub pending_operations: usize,
+    pub database_enabled: bool,
+    pub signing_audit_enabled: bool,
+    pub shut_down: bool,
+    /// Hourly activity over the 24 hours before `generated_at`.
+    pub activity: ActivityHistogram,
+    /// Last report from `OrderWallet::risk_report`, if one was computed.
+    pub risk: Option<RiskReport>,
+    /// Configuration changes since the wallet was saved (set on DB load).
+    pub config_drift: ConfigDrift,
+}
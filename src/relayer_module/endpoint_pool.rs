@@ -0,0 +1,253 @@
+//! Health tracking and attempt order for a relayer client's endpoints.
+//!
+//! A [`RelayerJsonRpcClient`](super::relayer_api::RelayerJsonRpcClient) built
+//! with several endpoints keeps one HTTP client per endpoint in an
+//! [`EndpointPool`]. Each request walks [`EndpointPool::attempt_order`]: the
+//! healthy endpoints in the [`FailoverStrategy`]'s order, then the unhealthy
+//! ones by how soon their cooldown ends, so a request still goes out when
+//! every endpoint is cooling down.
+//!
+//! Only errors where the endpoint itself did not answer move a request on
+//! ([`is_failover_error`]); an error returned by the relayer is the answer.
+//! `failure_threshold` such failures in a row mark an endpoint unhealthy for
+//! the policy's `cooldown`, and one answer makes it healthy again.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use jsonrpsee::core::client::Error as RpcError;
+use jsonrpsee::http_client::HttpClient;
+use serde::Serialize;
+
+use crate::config::{FailoverPolicy, FailoverStrategy};
+
+/// One relayer URL and the HTTP client built for it.
+#[derive(Debug, Clone)]
+pub(crate) struct Endpoint {
+    pub(crate) url: String,
+    pub(crate) client: HttpClient,
+}
+
+/// Health of one endpoint, as reported by
+/// [`RelayerJsonRpcClient::endpoint_health`](super::relayer_api::RelayerJsonRpcClient::endpoint_health).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// `false` while the endpoint cools down after too many failures in a row.
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub total_requests: u64,
+    pub total_failures: u64,
+    pub last_error: Option<String>,
+    /// Time left before the endpoint is tried in its normal turn again.
+    pub cooldown_remaining: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    consecutive_failures: u32,
+    total_requests: u64,
+    total_failures: u64,
+    last_error: Option<String>,
+    unhealthy_until: Option<Instant>,
+}
+
+impl HealthState {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until.is_none_or(|until| now >= until)
+    }
+}
+
+/// Whether `error` means the endpoint did not answer, so the request may be
+/// sent to the next endpoint.
+pub fn is_failover_error(error: &RpcError) -> bool {
+    matches!(
+        error,
+        RpcError::Transport(_) | RpcError::RestartNeeded(_) | RpcError::RequestTimeout
+    )
+}
+
+/// Whether a submit that failed with `error` may be sent to the next endpoint.
+/// A timed out submit may still have reached the relayer, so only transport
+/// errors move it on.
+pub fn is_submit_failover_error(error: &RpcError) -> bool {
+    matches!(error, RpcError::Transport(_))
+}
+
+#[derive(Debug)]
+pub(crate) struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    policy: FailoverPolicy,
+    health: Mutex<Vec<HealthState>>,
+    next: AtomicUsize,
+}
+
+impl EndpointPool {
+    /// `endpoints` must not be empty.
+    pub(crate) fn new(endpoints: Vec<Endpoint>, policy: FailoverPolicy) -> Self {
+        debug_assert!(!endpoints.is_empty());
+        let health = endpoints.iter().map(|_| HealthState::default()).collect();
+        Self {
+            endpoints,
+            policy,
+            health: Mutex::new(health),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> &FailoverPolicy {
+        &self.policy
+    }
+
+    /// The first configured endpoint.
+    pub(crate) fn primary(&self) -> &Endpoint {
+        &self.endpoints[0]
+    }
+
+    pub(crate) fn endpoint(&self, index: usize) -> &Endpoint {
+        &self.endpoints[index]
+    }
+
+    /// Indices of the endpoints to try for the next request, in order.
+    pub(crate) fn attempt_order(&self) -> Vec<usize> {
+        let n = self.endpoints.len();
+        let start = match self.policy.strategy {
+            FailoverStrategy::Priority => 0,
+            FailoverStrategy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % n,
+        };
+        let now = Instant::now();
+        let health = self.lock();
+        let (mut order, mut cooling): (Vec<usize>, Vec<usize>) = (0..n)
+            .map(|i| (start + i) % n)
+            .partition(|&i| health[i].is_healthy(now));
+        cooling.sort_by_key(|&i| health[i].unhealthy_until);
+        order.extend(cooling);
+        order
+    }
+
+    /// The endpoint at `index` answered.
+    pub(crate) fn record_success(&self, index: usize) {
+        let mut health = self.lock();
+        let state = &mut health[index];
+        state.total_requests += 1;
+        state.consecutive_failures = 0;
+        state.unhealthy_until = None;
+    }
+
+    /// The endpoint at `index` did not answer; see [`is_failover_error`].
+    pub(crate) fn record_failure(&self, index: usize, error: &RpcError) {
+        let mut health = self.lock();
+        let state = &mut health[index];
+        state.total_requests += 1;
+        state.total_failures += 1;
+        state.consecutive_failures += 1;
+        state.last_error = Some(error.to_string());
+        if state.consecutive_failures >= self.policy.failure_threshold.max(1) {
+            state.unhealthy_until = Some(Instant::now() + self.policy.cooldown);
+        }
+    }
+
+    pub(crate) fn health(&self) -> Vec<EndpointHealth> {
+        let now = Instant::now();
+        let health = self.lock();
+        self.endpoints
+            .iter()
+            .zip(health.iter())
+            .map(|(endpoint, state)| EndpointHealth {
+                url: endpoint.url.clone(),
+                healthy: state.is_healthy(now),
+                consecutive_failures: state.consecutive_failures,
+                total_requests: state.total_requests,
+                total_failures: state.total_failures,
+                last_error: state.last_error.clone(),
+                cooldown_remaining: state
+                    .unhealthy_until
+                    .filter(|until| *until > now)
+                    .map(|until| until - now),
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<HealthState>> {
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::http_client::HttpClientBuilder;
+
+    fn pool(n: usize, policy: FailoverPolicy) -> EndpointPool {
+        let endpoints = (0..n)
+            .map(|i| {
+                let url = format!("http://127.0.0.1:{}", 1 + i);
+                Endpoint {
+                    client: HttpClientBuilder::default().build(&url).unwrap(),
+                    url,
+                }
+            })
+            .collect();
+        EndpointPool::new(endpoints, policy)
+    }
+
+    #[test]
+    fn test_priority_skips_unhealthy_until_success() {
+        let pool = pool(3, FailoverPolicy::new(FailoverStrategy::Priority));
+        assert_eq!(pool.attempt_order(), vec![0, 1, 2]);
+
+        for _ in 0..2 {
+            pool.record_failure(0, &RpcError::RequestTimeout);
+        }
+        // Below the threshold the endpoint keeps its place.
+        assert_eq!(pool.attempt_order(), vec![0, 1, 2]);
+        pool.record_failure(0, &RpcError::RequestTimeout);
+        assert_eq!(pool.attempt_order(), vec![1, 2, 0]);
+
+        let health = pool.health();
+        assert!(!health[0].healthy);
+        assert_eq!(health[0].consecutive_failures, 3);
+        assert_eq!(health[0].total_failures, 3);
+        assert!(health[0].cooldown_remaining.is_some());
+        assert!(health[1].healthy);
+
+        pool.record_success(0);
+        assert_eq!(pool.attempt_order(), vec![0, 1, 2]);
+        let health = pool.health();
+        assert!(health[0].healthy);
+        assert_eq!(health[0].consecutive_failures, 0);
+        assert_eq!(health[0].total_requests, 4);
+    }
+
+    #[test]
+    fn test_round_robin_rotates_and_cooldown_expires() {
+        let policy = FailoverPolicy {
+            failure_threshold: 1,
+            cooldown: Duration::ZERO,
+            ..FailoverPolicy::new(FailoverStrategy::RoundRobin)
+        };
+        let pool = pool(3, policy);
+        assert_eq!(pool.attempt_order(), vec![0, 1, 2]);
+        assert_eq!(pool.attempt_order(), vec![1, 2, 0]);
+        assert_eq!(pool.attempt_order(), vec![2, 0, 1]);
+
+        // With no cooldown a failed endpoint is back in turn straight away.
+        pool.record_failure(0, &RpcError::RequestTimeout);
+        assert_eq!(pool.attempt_order(), vec![0, 1, 2]);
+        assert!(pool.health()[0].healthy);
+    }
+
+    #[test]
+    fn test_failover_error_classes() {
+        assert!(is_failover_error(&RpcError::RequestTimeout));
+        assert!(!is_submit_failover_error(&RpcError::RequestTimeout));
+        let call = RpcError::Call(jsonrpsee::types::ErrorObjectOwned::owned(
+            -32602,
+            "invalid params",
+            None::<()>,
+        ));
+        assert!(!is_failover_error(&call));
+        assert!(!is_submit_failover_error(&call));
+    }
+}
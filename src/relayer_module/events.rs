@@ -0,0 +1,327 @@
+//! Lifecycle events emitted by [`OrderWallet`](super::order_wallet::OrderWallet).
+//!
+//! Order events are derived from the outcome of each public order operation,
+//! after the operation has finished, so consumers never sit on a trading path.
+//! Account events (funding, rotation, balance changes, failed database saves)
+//! come from the points where `OrderWallet` changes that state.
+//!
+//! Events go to the wallet's webhooks and to every receiver of
+//! [`OrderWallet::subscribe_events`](super::order_wallet::OrderWallet::subscribe_events).
+//! Events other than order outcomes are only built while someone listens.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::order_wallet::AccountIndex;
+
+/// Events buffered for each [`EventBus`] receiver. A receiver further behind
+/// skips the oldest ones and gets `RecvError::Lagged`.
+pub const EVENT_BUFFER: usize = 256;
+
+/// Which book an order belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderKind {
+    Trader,
+    Lend,
+}
+
+impl OrderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderKind::Trader => "trader",
+            OrderKind::Lend => "lend",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "trader" => Some(OrderKind::Trader),
+            "lend" => Some(OrderKind::Lend),
+            _ => None,
+        }
+    }
+}
+
+/// Discriminant of a [`WalletEvent`], used for filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletEventKind {
+    OrderOpened,
+    OrderClosed,
+    OrderCancelled,
+    OrderFailed,
+    OrderStatusChanged,
+    AccountFunded,
+    AccountRotated,
+    BalanceUpdated,
+    DbSyncFailed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WalletEvent {
+    /// An order was submitted to the relayer.
+    OrderOpened {
+        account_index: AccountIndex,
+        request_id: String,
+        order: OrderKind,
+        occurred_at: DateTime<Utc>,
+    },
+    /// A trader order was settled or a lend order withdrawn.
+    OrderClosed {
+        account_index: AccountIndex,
+        request_id: String,
+        order: OrderKind,
+        occurred_at: DateTime<Utc>,
+    },
+    OrderCancelled {
+        account_index: AccountIndex,
+        request_id: String,
+        occurred_at: DateTime<Utc>,
+    },
+    /// An open, close or cancel failed; `operation` is the method name.
+    OrderFailed {
+        account_index: AccountIndex,
+        operation: String,
+        error: String,
+        occurred_at: DateTime<Utc>,
+    },
+    /// A query returned a new status for the account's latest order of
+    /// `order`'s book. `from` is the status last seen, `SUBMITTED` if none.
+    OrderStatusChanged {
+        account_index: AccountIndex,
+        request_id: String,
+        order: OrderKind,
+        from: String,
+        to: String,
+        occurred_at: DateTime<Utc>,
+    },
+    /// `amount` sats were minted from the on-chain wallet into a new account.
+    AccountFunded {
+        account_index: AccountIndex,
+        amount: u64,
+        tx_hash: String,
+        occurred_at: DateTime<Utc>,
+    },
+    /// The whole balance of `account_index` moved to the fresh
+    /// `new_account_index` (`trading_to_trading`).
+    AccountRotated {
+        account_index: AccountIndex,
+        new_account_index: AccountIndex,
+        balance: u64,
+        occurred_at: DateTime<Utc>,
+    },
+    /// The locally tracked balance of an account changed.
+    BalanceUpdated {
+        account_index: AccountIndex,
+        previous: u64,
+        balance: u64,
+        occurred_at: DateTime<Utc>,
+    },
+    /// Saving account state to the database failed; the in-memory state is
+    /// ahead of the database until the next successful save. `operation`
+    /// names what was being saved.
+    DbSyncFailed {
+        account_index: AccountIndex,
+        operation: String,
+        error: String,
+        occurred_at: DateTime<Utc>,
+    },
+}
+
+impl WalletEvent {
+    pub fn kind(&self) -> WalletEventKind {
+        match self {
+            WalletEvent::OrderOpened { .. } => WalletEventKind::OrderOpened,
+            WalletEvent::OrderClosed { .. } => WalletEventKind::OrderClosed,
+            WalletEvent::OrderCancelled { .. } => WalletEventKind::OrderCancelled,
+            WalletEvent::OrderFailed { .. } => WalletEventKind::OrderFailed,
+            WalletEvent::OrderStatusChanged { .. } => WalletEventKind::OrderStatusChanged,
+            WalletEvent::AccountFunded { .. } => WalletEventKind::AccountFunded,
+            WalletEvent::AccountRotated { .. } => WalletEventKind::AccountRotated,
+            WalletEvent::BalanceUpdated { .. } => WalletEventKind::BalanceUpdated,
+            WalletEvent::DbSyncFailed { .. } => WalletEventKind::DbSyncFailed,
+        }
+    }
+
+    pub fn account_index(&self) -> AccountIndex {
+        match self {
+            WalletEvent::OrderOpened { account_index, .. }
+            | WalletEvent::OrderClosed { account_index, .. }
+            | WalletEvent::OrderCancelled { account_index, .. }
+            | WalletEvent::OrderFailed { account_index, .. }
+            | WalletEvent::OrderStatusChanged { account_index, .. }
+            | WalletEvent::AccountFunded { account_index, .. }
+            | WalletEvent::AccountRotated { account_index, .. }
+            | WalletEvent::BalanceUpdated { account_index, .. }
+            | WalletEvent::DbSyncFailed { account_index, .. } => *account_index,
+        }
+    }
+
+    pub fn occurred_at(&self) -> DateTime<Utc> {
+        match self {
+            WalletEvent::OrderOpened { occurred_at, .. }
+            | WalletEvent::OrderClosed { occurred_at, .. }
+            | WalletEvent::OrderCancelled { occurred_at, .. }
+            | WalletEvent::OrderFailed { occurred_at, .. }
+            | WalletEvent::OrderStatusChanged { occurred_at, .. }
+            | WalletEvent::AccountFunded { occurred_at, .. }
+            | WalletEvent::AccountRotated { occurred_at, .. }
+            | WalletEvent::BalanceUpdated { occurred_at, .. }
+            | WalletEvent::DbSyncFailed { occurred_at, .. } => *occurred_at,
+        }
+    }
+
+    /// Request ID of the order behind the event, if it has one.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            WalletEvent::OrderOpened { request_id, .. }
+            | WalletEvent::OrderClosed { request_id, .. }
+            | WalletEvent::OrderCancelled { request_id, .. }
+            | WalletEvent::OrderStatusChanged { request_id, .. } => Some(request_id),
+            _ => None,
+        }
+    }
+
+    /// Event for the outcome of the `OrderWallet` method `operation`, or
+    /// `None` if that method is not an order lifecycle operation.
+    pub fn from_outcome(
+        account_index: AccountIndex,
+        operation: &str,
+        result: &Result<String, String>,
+        occurred_at: DateTime<Utc>,
+    ) -> Option<Self> {
+        let (kind, order) = match operation {
+            "open_trader_order" => (WalletEventKind::OrderOpened, OrderKind::Trader),
+            "open_lend_order" => (WalletEventKind::OrderOpened, OrderKind::Lend),
+            "close_trader_order" | "close_trader_order_sltp" | "close_trader_order_partial" => {
+                (WalletEventKind::OrderClosed, OrderKind::Trader)
+            }
+            "close_lend_order" => (WalletEventKind::OrderClosed, OrderKind::Lend),
+            "cancel_trader_order" | "cancel_trader_order_sltp" => {
+                (WalletEventKind::OrderCancelled, OrderKind::Trader)
+            }
+            _ => return None,
+        };
+        let request_id = match result {
+            Ok(request_id) => request_id.clone(),
+            Err(error) => {
+                return Some(WalletEvent::OrderFailed {
+                    account_index,
+                    operation: operation.to_string(),
+                    error: error.clone(),
+                    occurred_at,
+                });
+            }
+        };
+        Some(match kind {
+            WalletEventKind::OrderOpened => WalletEvent::OrderOpened {
+                account_index,
+                request_id,
+                order,
+                occurred_at,
+            },
+            WalletEventKind::OrderClosed => WalletEvent::OrderClosed {
+                account_index,
+                request_id,
+                order,
+                occurred_at,
+            },
+            _ => WalletEvent::OrderCancelled {
+                account_index,
+                request_id,
+                occurred_at,
+            },
+        })
+    }
+}
+
+/// Broadcasts events to
+/// [`OrderWallet::subscribe_events`](super::order_wallet::OrderWallet::subscribe_events)
+/// receivers. With no receiver, nothing is sent.
+#[derive(Debug, Clone)]
+pub(crate) struct EventBus {
+    sender: broadcast::Sender<WalletEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+}
+
+impl EventBus {
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<WalletEvent> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub(crate) fn send(&self, event: WalletEvent) {
+        // Only fails when every receiver has been dropped.
+        let _ = self.sender.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_from_outcomes() {
+        let now = Utc::now();
+        let opened = WalletEvent::from_outcome(3, "open_lend_order", &Ok("req-1".to_string()), now);
+        assert_eq!(
+            opened,
+            Some(WalletEvent::OrderOpened {
+                account_index: 3,
+                request_id: "req-1".to_string(),
+                order: OrderKind::Lend,
+                occurred_at: now,
+            })
+        );
+        let failed = WalletEvent::from_outcome(
+            3,
+            "cancel_trader_order",
+            &Err("not pending".to_string()),
+            now,
+        )
+        .unwrap();
+        assert_eq!(failed.kind(), WalletEventKind::OrderFailed);
+        assert_eq!(failed.request_id(), None);
+        assert_eq!(
+            serde_json::to_value(&failed).unwrap()["type"],
+            "order_failed"
+        );
+        assert_eq!(
+            WalletEvent::from_outcome(3, "trading_to_trading", &Ok(String::new()), now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_event_bus_only_sends_to_live_receivers() {
+        let bus = EventBus::default();
+        assert!(!bus.has_subscribers());
+        let event = WalletEvent::BalanceUpdated {
+            account_index: 1,
+            previous: 0,
+            balance: 500,
+            occurred_at: Utc::now(),
+        };
+        bus.send(event.clone());
+
+        let mut receiver = bus.subscribe();
+        assert!(bus.has_subscribers());
+        bus.send(event.clone());
+        assert_eq!(receiver.try_recv().unwrap(), event);
+        assert!(receiver.try_recv().is_err());
+        drop(receiver);
+        assert!(!bus.has_subscribers());
+    }
+}
@@ -0,0 +1,241 @@
+//! Execution quality of trader orders: requested versus filled entry price.
+//!
+//! `OrderWallet::open_trader_order` notes the order type and entry price it
+//! was asked for on the open's [`OrderRecord`], whose `recorded_at` is the
+//! submission time. [`ExecutionReport::from_trader_order`] sets them against
+//! the order the relayer filled, and [`ExecutionQuality::from_reports`]
+//! averages reports over a window to compare relayer endpoints.
+//!
+//! ## Formulas
+//!
+//! With `R` the requested entry price, `A` the entry price filled at and `S`
+//! the order's `positionsize`:
+//!
+//! - **slippage** = `A - R` for a long and `R - A` for a short, so a fill
+//!   worse than requested is positive.
+//! - **slippage bps** = `slippage / R * 10_000`.
+//! - **slippage sats** = the PnL the position would show at `A` had it been
+//!   entered at `R`: `S * (A - R) / (R * A)` for a long and
+//!   `S * (R - A) / (R * A)` for a short. It is what the fill costs at any
+//!   exit price.
+//! - **time to fill** = the relayer's order timestamp minus `recorded_at`,
+//!   `0` when the relayer clock is behind. For a MARKET order the timestamp
+//!   is its fill.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::order_wallet::AccountIndex;
+use super::portfolio::unrealized_pnl;
+use super::relayer_types::{OrderStatus, PositionType, TraderOrder};
+use super::transaction_history::{OrderRecord, OrderRecordKind};
+
+/// Requested and filled entry of one trader order; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExecutionReport {
+    pub account_index: AccountIndex,
+    pub request_id: String,
+    /// Order type the open was requested with.
+    pub order_type: String,
+    pub position_type: PositionType,
+    pub order_status: OrderStatus,
+    pub requested_price: f64,
+    pub entry_price: f64,
+    /// Positive when the fill was worse than requested.
+    pub slippage: f64,
+    pub slippage_bps: f64,
+    pub slippage_sats: f64,
+    pub submitted_at: DateTime<Utc>,
+    /// The relayer's order timestamp, if it parses.
+    pub filled_at: Option<DateTime<Utc>>,
+    pub time_to_fill: Option<Duration>,
+    /// Fee charged on the fill.
+    pub fee: Option<f64>,
+}
+
+impl ExecutionReport {
+    /// Report on `order`, the fill of the open request `record`. Fails for a
+    /// record that is not a trader open, has no requested price (opened
+    /// before they were recorded) or whose order has not filled.
+    pub fn from_trader_order(
+        account_index: AccountIndex,
+        record: &OrderRecord,
+        order: &TraderOrder,
+    ) -> Result<Self, String> {
+        if record.kind != OrderRecordKind::TraderOpen {
+            return Err(format!("{} is not a trader order open", record.request_id));
+        }
+        let (Some(order_type), Some(requested)) = (&record.order_type, record.requested_price)
+        else {
+            return Err(format!(
+                "No requested entry price recorded for {}",
+                record.request_id
+            ));
+        };
+        if !matches!(
+            order.order_status,
+            OrderStatus::FILLED | OrderStatus::SETTLED | OrderStatus::LIQUIDATE
+        ) {
+            return Err(format!(
+                "Order {} on account {} has not filled ({})",
+                record.request_id,
+                account_index,
+                order.order_status.to_str()
+            ));
+        }
+        let requested = requested as f64;
+        let actual = order.entryprice;
+        let slippage = match order.position_type {
+            PositionType::LONG => actual - requested,
+            PositionType::SHORT => requested - actual,
+        };
+        let slippage_bps = if requested > 0.0 {
+            slippage / requested * 10_000.0
+        } else {
+            0.0
+        };
+        let filled_at = DateTime::parse_from_rfc3339(&order.timestamp)
+            .ok()
+            .map(|t| t.with_timezone(&Utc));
+        let time_to_fill = filled_at.map(|filled_at| {
+            (filled_at - record.recorded_at)
+                .to_std()
+                .unwrap_or(Duration::ZERO)
+        });
+        Ok(Self {
+            account_index,
+            request_id: record.request_id.clone(),
+            order_type: order_type.clone(),
+            position_type: order.position_type.clone(),
+            order_status: order.order_status.clone(),
+            requested_price: requested,
+            entry_price: actual,
+            slippage,
+            slippage_bps,
+            slippage_sats: unrealized_pnl(
+                &order.position_type,
+                order.positionsize,
+                requested,
+                actual,
+            ),
+            submitted_at: record.recorded_at,
+            filled_at,
+            time_to_fill,
+            fee: Some(order.fee_filled),
+        })
+    }
+}
+
+/// Averages over the execution reports of a window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExecutionQuality {
+    /// Orders with a report.
+    pub orders: usize,
+    /// Trader opens in the window without a report: not filled, opened
+    /// without a requested price, or no longer queryable.
+    pub unavailable: usize,
+    pub mean_slippage_bps: Option<f64>,
+    /// Largest `slippage_bps`, i.e. the worst fill.
+    pub worst_slippage_bps: Option<f64>,
+    pub mean_slippage_sats: Option<f64>,
+    pub total_slippage_sats: f64,
+    pub mean_time_to_fill: Option<Duration>,
+    pub total_fees: f64,
+}
+
+impl ExecutionQuality {
+    pub fn from_reports(reports: &[ExecutionReport], unavailable: usize) -> Self {
+        let orders = reports.len();
+        let mean = |total: f64| (orders > 0).then(|| total / orders as f64);
+        let total_slippage_sats = reports.iter().map(|r| r.slippage_sats).sum();
+        let fill_times: Vec<Duration> = reports.iter().filter_map(|r| r.time_to_fill).collect();
+        Self {
+            orders,
+            unavailable,
+            mean_slippage_bps: mean(reports.iter().map(|r| r.slippage_bps).sum()),
+            worst_slippage_bps: reports.iter().map(|r| r.slippage_bps).reduce(f64::max),
+            mean_slippage_sats: mean(total_slippage_sats),
+            total_slippage_sats,
+            mean_time_to_fill: (!fill_times.is_empty())
+                .then(|| fill_times.iter().sum::<Duration>() / fill_times.len() as u32),
+            total_fees: reports.iter().filter_map(|r| r.fee).sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+
+    fn open_record(requested_price: u64) -> OrderRecord {
+        let submitted_at = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            - chrono::Duration::seconds(2);
+        OrderRecord::new(
+            "req-1".to_string(),
+            OrderRecordKind::TraderOpen,
+            submitted_at,
+        )
+        .with_requested_entry("MARKET".to_string(), requested_price)
+    }
+
+    #[test]
+    fn test_slippage_is_positive_for_worse_fills() {
+        let long = TraderOrderBuilder::new()
+            .position_type(PositionType::LONG)
+            .position(1_000.0, 2.0, 50_050.0)
+            .field("fee_filled", 3.0)
+            .build();
+        let report = ExecutionReport::from_trader_order(4, &open_record(50_000), &long).unwrap();
+        assert_eq!(report.slippage, 50.0);
+        assert!((report.slippage_bps - 10.0).abs() < 1e-9);
+        assert!(report.slippage_sats > 0.0);
+        assert_eq!(report.time_to_fill, Some(Duration::from_secs(2)));
+        assert_eq!(report.fee, Some(3.0));
+
+        let short = TraderOrderBuilder::new()
+            .position_type(PositionType::SHORT)
+            .position(1_000.0, 2.0, 50_050.0)
+            .build();
+        let report = ExecutionReport::from_trader_order(4, &open_record(50_000), &short).unwrap();
+        assert_eq!(report.slippage, -50.0);
+        assert!(report.slippage_sats < 0.0);
+
+        let pending = TraderOrderBuilder::new()
+            .order_status(OrderStatus::PENDING)
+            .build();
+        assert!(ExecutionReport::from_trader_order(4, &open_record(50_000), &pending).is_err());
+        let unrecorded =
+            OrderRecord::new("req-0".to_string(), OrderRecordKind::TraderOpen, Utc::now());
+        assert!(ExecutionReport::from_trader_order(4, &unrecorded, &long).is_err());
+    }
+
+    #[test]
+    fn test_quality_averages_reports() {
+        let order = |price| {
+            TraderOrderBuilder::new()
+                .position(1_000.0, 2.0, price)
+                .field("fee_filled", 2.0)
+                .build()
+        };
+        let reports: Vec<_> = [50_010.0, 50_030.0]
+            .into_iter()
+            .map(|price| ExecutionReport::from_trader_order(1, &open_record(50_000), &order(price)))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let quality = ExecutionQuality::from_reports(&reports, 1);
+        assert_eq!((quality.orders, quality.unavailable), (2, 1));
+        assert!((quality.mean_slippage_bps.unwrap() - 4.0).abs() < 1e-9);
+        assert!((quality.worst_slippage_bps.unwrap() - 6.0).abs() < 1e-9);
+        assert_eq!(quality.mean_time_to_fill, Some(Duration::from_secs(2)));
+        assert_eq!(quality.total_fees, 4.0);
+        assert_eq!(
+            ExecutionQuality::from_reports(&[], 0).mean_slippage_bps,
+            None
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Relayer trading fees: the current schedule and what an order will pay.
+//!
+//! The relayer's `get_fee_rate` reports four rates in percent of the order
+//! value: a fill on market (taker) or on limit (maker), and a settlement on
+//! market or on limit. A [`FeeSchedule`] holds them in basis points, and
+//! [`FeeEstimate::new`] turns a schedule into sats for opening and closing
+//! an order of a given value (`initial margin * leverage`).
+//!
+//! An estimate prices both legs at the order's value when it opens and with
+//! the same order type. The close fee the relayer charges follows the
+//! position value at settlement, so it moves with the price; take the
+//! estimate as a sizing guide, not a quote.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::relayer_types::FeeHistory;
+use crate::compat::relayer_types::OrderType;
+
+/// Fee history entries requested per `historical_fee_rate` page by
+/// [`RelayerJsonRpcClient::fee_history`](super::relayer_api::RelayerJsonRpcClient::fee_history).
+pub const FEE_HISTORY_PAGE_SIZE: usize = 500;
+
+/// Fee rates advertised by the relayer, in basis points of the order value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    /// Fill of a limit order.
+    pub maker_bps: f64,
+    /// Fill of a market order.
+    pub taker_bps: f64,
+    pub settle_market_bps: f64,
+    pub settle_limit_bps: f64,
+    /// When the relayer set these rates.
+    pub timestamp: DateTime<Utc>,
+}
+
+impl FeeSchedule {
+    /// Rates of a relayer fee entry, which are in percent.
+    pub fn from_fee_history(fee: &FeeHistory) -> Self {
+        Self {
+            maker_bps: fee.order_filled_on_limit * 100.0,
+            taker_bps: fee.order_filled_on_market * 100.0,
+            settle_market_bps: fee.order_settled_on_market * 100.0,
+            settle_limit_bps: fee.order_settled_on_limit * 100.0,
+            timestamp: fee.timestamp,
+        }
+    }
+
+    /// Rate charged when an order of `order_type` fills. Only limit orders
+    /// fill as maker.
+    pub fn open_bps(&self, order_type: &OrderType) -> f64 {
+        match order_type {
+            OrderType::LIMIT => self.maker_bps,
+            _ => self.taker_bps,
+        }
+    }
+
+    /// Rate charged when a position is closed with an order of `order_type`.
+    pub fn close_bps(&self, order_type: &OrderType) -> f64 {
+        match order_type {
+            OrderType::LIMIT => self.settle_limit_bps,
+            _ => self.settle_market_bps,
+        }
+    }
+}
+
+/// Fees, in sats, of opening and closing an order; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    /// Position value the fees are charged on, `initial margin * leverage`.
+    pub order_value: u64,
+    pub order_type: OrderType,
+    pub open_bps: f64,
+    pub close_bps: f64,
+    pub open_fee: u64,
+    pub close_fee: u64,
+    /// `open_fee + close_fee`.
+    pub total_fee: u64,
+}
+
+impl FeeEstimate {
+    /// Fees of an order worth `order_value` sats under `schedule`, each
+    /// rounded up to a whole sat.
+    pub fn new(schedule: &FeeSchedule, order_value: u64, order_type: OrderType) -> Self {
+        let open_bps = schedule.open_bps(&order_type);
+        let close_bps = schedule.close_bps(&order_type);
+        let open_fee = fee_sats(order_value, open_bps);
+        let close_fee = fee_sats(order_value, close_bps);
+        Self {
+            order_value,
+            order_type,
+            open_bps,
+            close_bps,
+            open_fee,
+            close_fee,
+            total_fee: open_fee.saturating_add(close_fee),
+        }
+    }
+}
+
+/// `bps` basis points of `value` sats, rounded up.
+pub fn fee_sats(value: u64, bps: f64) -> u64 {
+    if bps <= 0.0 {
+        return 0;
+    }
+    (value as f64 * bps / 10_000.0).ceil() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> FeeSchedule {
+        FeeSchedule::from_fee_history(&FeeHistory {
+            order_filled_on_market: 0.05,
+            order_filled_on_limit: 0.02,
+            order_settled_on_market: 0.04,
+            order_settled_on_limit: 0.025,
+            timestamp: "2025-01-01T00:00:00Z".parse().unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_schedule_converts_percent_to_bps() {
+        let schedule = schedule();
+        assert!((schedule.taker_bps - 5.0).abs() < 1e-9);
+        assert!((schedule.maker_bps - 2.0).abs() < 1e-9);
+        assert!((schedule.settle_market_bps - 4.0).abs() < 1e-9);
+        assert!((schedule.settle_limit_bps - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimate_picks_rates_by_order_type_and_rounds_up() {
+        // 10x on 100_000 sats of margin.
+        let market = FeeEstimate::new(&schedule(), 1_000_000, OrderType::MARKET);
+        assert_eq!(market.open_fee, 500);
+        assert_eq!(market.close_fee, 400);
+        assert_eq!(market.total_fee, 900);
+
+        let limit = FeeEstimate::new(&schedule(), 1_000_000, OrderType::LIMIT);
+        assert_eq!((limit.open_fee, limit.close_fee), (200, 250));
+
+        // 0.05% of 1_001 sats is 0.5005 sats.
+        assert_eq!(fee_sats(1_001, 5.0), 1);
+        assert_eq!(fee_sats(1_000_000, 0.0), 0);
+    }
+}
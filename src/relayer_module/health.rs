@@ -0,0 +1,385 @@
+//! Liveness/readiness HTTP endpoints for orchestration probes.
+//!
+//! Enabled with the `health-endpoint` feature. [`HealthRegistry`] holds the
+//! latest result of each named check; background tasks update it and the
+//! HTTP listener only reads it, so a probe never triggers a relayer or DB call.
+//!
+//! Routes served by [`serve`]:
+//! - `GET /healthz` — always `200` while the process is serving.
+//! - `GET /readyz` — `200` when every check is healthy and fresh, `503`
+//!   otherwise; the body lists each check's status.
+//!
+//! [`OrderWallet::serve_health`](super::order_wallet::OrderWallet::serve_health)
+//! wires the standard checks (wallet, database, relayer, lcd, accounts, config)
+//! and starts the listener.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use log::{debug, warn};
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use super::clock::{Clock, system_clock};
+
+/// The wallet was constructed/loaded.
+pub const CHECK_WALLET: &str = "wallet_loaded";
+/// The wallet database answers connection checkouts.
+pub const CHECK_DATABASE: &str = "database";
+/// The relayer answered the last background poll.
+pub const CHECK_RELAYER: &str = "relayer";
+/// The chain LCD is not in degraded mode (see [`crate::nyks_rpc::lcd`]).
+pub const CHECK_LCD: &str = "lcd";
+/// No multi-step operation is left half-finished.
+pub const CHECK_ACCOUNTS: &str = "accounts";
+/// The configuration matches the one the wallet was saved under, apart from
+/// the crate version.
+pub const CHECK_CONFIG: &str = "config";
+
+/// Latest result of one named check.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub checked_at: DateTime<Utc>,
+    /// Set when the result is older than its `max_age`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    #[serde(skip)]
+    pub max_age: Option<Duration>,
+}
+
+/// Aggregated readiness, as returned by `/readyz`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub ready: bool,
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+/// Shared store of check results. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct HealthRegistry {
+    checks: Arc<RwLock<BTreeMap<String, CheckResult>>>,
+    max_age: Option<Duration>,
+    /// Stamps results and ages them in [`report`](HealthRegistry::report).
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl HealthRegistry {
+    /// Create a registry. Results recorded with [`set`](HealthRegistry::set)
+    /// that are older than `max_age` count as unhealthy (e.g. when the task
+    /// polling them has died); `None` disables this.
+    pub fn new(max_age: Option<Duration>) -> Self {
+        Self {
+            checks: Arc::new(RwLock::new(BTreeMap::new())),
+            max_age,
+            clock: system_clock(),
+        }
+    }
+
+    /// Take check times from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record the result of a polled check `name`; it goes stale after `max_age`.
+    pub fn set(&self, name: &str, healthy: bool, detail: Option<String>) {
+        self.insert(name, healthy, detail, self.max_age);
+    }
+
+    /// Record the result of an event-driven check `name`, which stays valid
+    /// until it is set again.
+    pub fn set_persistent(&self, name: &str, healthy: bool, detail: Option<String>) {
+        self.insert(name, healthy, detail, None);
+    }
+
+    fn insert(&self, name: &str, healthy: bool, detail: Option<String>, max_age: Option<Duration>) {
+        let result = CheckResult {
+            healthy,
+            detail,
+            checked_at: self.clock.now(),
+            stale: false,
+            max_age,
+        };
+        self.checks
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), result);
+    }
+
+    /// Record a `Result`-shaped check outcome; the error becomes the detail.
+    pub fn set_result<E: std::fmt::Display>(&self, name: &str, result: &Result<(), E>) {
+        match result {
+            Ok(()) => self.set(name, true, None),
+            Err(e) => self.set(name, false, Some(e.to_string())),
+        }
+    }
+
+    /// Snapshot of all checks. Ready only if there is at least one check and
+    /// every check is healthy and fresh.
+    pub fn report(&self) -> HealthReport {
+        let now = self.clock.now();
+        let mut checks = self
+            .checks
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for result in checks.values_mut() {
+            if let Some(max_age) = result.max_age {
+                let age = (now - result.checked_at).to_std().unwrap_or(Duration::ZERO);
+                result.stale = age > max_age;
+            }
+        }
+        let ready = !checks.is_empty() && checks.values().all(|c| c.healthy && !c.stale);
+        HealthReport { ready, checks }
+    }
+}
+
+/// Running health listener. Call [`shutdown`](HealthServerHandle::shutdown) to
+/// stop accepting connections and let in-flight requests finish; dropping the
+/// handle also stops the listener.
+#[derive(Debug)]
+pub struct HealthServerHandle {
+    local_addr: SocketAddr,
+    registry: HealthRegistry,
+    shutdown: watch::Sender<bool>,
+    server: JoinHandle<()>,
+    background: Vec<JoinHandle<()>>,
+}
+
+impl HealthServerHandle {
+    /// Address the listener is bound to (useful when binding port 0).
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Registry backing `/readyz`.
+    pub fn registry(&self) -> &HealthRegistry {
+        &self.registry
+    }
+
+    /// Attach a background task (e.g. a check refresher) that is aborted on shutdown.
+    pub fn attach_task(&mut self, task: JoinHandle<()>) {
+        self.background.push(task);
+    }
+
+    /// Stop the listener, wait for open connections to close, and abort background tasks.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        for task in &self.background {
+            task.abort();
+        }
+        if let Err(e) = self.server.await {
+            warn!("Health server task ended abnormally: {}", e);
+        }
+    }
+}
+
+/// Bind `addr` and serve `/healthz` and `/readyz` from `registry`.
+pub async fn serve(
+    addr: SocketAddr,
+    registry: HealthRegistry,
+) -> Result<HealthServerHandle, String> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("Failed to bind health endpoint on {}: {}", addr, e))?;
+    let local_addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read health endpoint address: {}", e))?;
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let server_registry = registry.clone();
+    let server = tokio::spawn(async move {
+        let mut shutdown_rx = shutdown_rx;
+        let mut connections = Vec::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let stream = match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(e) => {
+                            warn!("Health endpoint accept failed: {}", e);
+                            continue;
+                        }
+                    };
+                    connections.retain(|c: &JoinHandle<()>| !c.is_finished());
+                    connections.push(tokio::spawn(serve_connection(
+                        stream,
+                        server_registry.clone(),
+                        shutdown_rx.clone(),
+                    )));
+                }
+                _ = shutdown_rx.changed() => break,
+            }
+        }
+        for connection in connections {
+            let _ = connection.await;
+        }
+    });
+
+    Ok(HealthServerHandle {
+        local_addr,
+        registry,
+        shutdown: shutdown_tx,
+        server,
+        background: Vec::new(),
+    })
+}
+
+async fn serve_connection(
+    stream: tokio::net::TcpStream,
+    registry: HealthRegistry,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+        let registry = registry.clone();
+        async move { Ok::<_, Infallible>(route(&req, &registry)) }
+    });
+    let conn = http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+    tokio::pin!(conn);
+    tokio::select! {
+        res = conn.as_mut() => {
+            if let Err(e) = res {
+                debug!("Health endpoint connection error: {}", e);
+            }
+        }
+        _ = shutdown_rx.changed() => {
+            conn.as_mut().graceful_shutdown();
+            let _ = conn.await;
+        }
+    }
+}
+
+fn route<B>(req: &Request<B>, registry: &HealthRegistry) -> Response<Full<Bytes>> {
+    if req.method() != Method::GET {
+        return json_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            &serde_json::json!({ "error": "method not allowed" }),
+        );
+    }
+    match req.uri().path() {
+        "/healthz" => json_response(StatusCode::OK, &serde_json::json!({ "status": "ok" })),
+        "/readyz" => {
+            let report = registry.report();
+            let status = if report.ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            json_response(status, &report)
+        }
+        _ => json_response(
+            StatusCode::NOT_FOUND,
+            &serde_json::json!({ "error": "not found" }),
+        ),
+    }
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Full<Bytes>> {
+    let body = serde_json::to_vec(body).unwrap_or_else(|_| b"{}".to_vec());
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::clock::ManualClock;
+
+    async fn get(addr: SocketAddr, path: &str) -> (u16, serde_json::Value) {
+        let response = reqwest::get(format!("http://{}{}", addr, path))
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        (status, response.json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_readyz_reports_unhealthy_check() {
+        let registry = HealthRegistry::default();
+        registry.set(CHECK_WALLET, true, None);
+        registry.set(CHECK_DATABASE, true, None);
+        registry.set(CHECK_RELAYER, false, Some("connection refused".to_string()));
+        let handle = serve("127.0.0.1:0".parse().unwrap(), registry.clone())
+            .await
+            .unwrap();
+        let addr = handle.local_addr();
+
+        let (status, body) = get(addr, "/healthz").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "ok");
+
+        let (status, body) = get(addr, "/readyz").await;
+        assert_eq!(status, 503);
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["checks"][CHECK_RELAYER]["healthy"], false);
+        assert_eq!(
+            body["checks"][CHECK_RELAYER]["detail"],
+            "connection refused"
+        );
+        assert_eq!(body["checks"][CHECK_DATABASE]["healthy"], true);
+
+        registry.set(CHECK_RELAYER, true, None);
+        let (status, body) = get(addr, "/readyz").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["ready"], true);
+
+        let (status, _) = get(addr, "/metricsz").await;
+        assert_eq!(status, 404);
+
+        handle.shutdown().await;
+        assert!(
+            reqwest::get(format!("http://{}/healthz", addr))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_stale_and_empty_reports_are_not_ready() {
+        assert!(!HealthRegistry::default().report().ready);
+
+        let clock = ManualClock::default();
+        let registry =
+            HealthRegistry::new(Some(Duration::from_secs(30))).with_clock(Arc::new(clock.clone()));
+        registry.set_persistent(CHECK_WALLET, true, None);
+        registry.set(CHECK_RELAYER, true, None);
+        assert_eq!(
+            registry.report().checks[CHECK_RELAYER].checked_at,
+            DateTime::<Utc>::UNIX_EPOCH
+        );
+        clock.advance(Duration::from_secs(30));
+        assert!(registry.report().ready);
+
+        clock.advance(Duration::from_secs(1));
+        let report = registry.report();
+        assert!(!report.ready);
+        assert!(!report.checks[CHECK_WALLET].stale);
+        assert!(report.checks[CHECK_RELAYER].stale);
+    }
+}
@@ -0,0 +1,190 @@
+//! Idempotency keys for order submission.
+//!
+//! A submit that times out may still have reached the relayer. Before
+//! [`OrderWallet`](super::order_wallet::OrderWallet) sends a trader or lend
+//! order it records a [`PendingSubmission`] carrying a fresh idempotency key
+//! (a UUID), in memory and, with DB persistence, in the `pending_submissions`
+//! table. The record is cleared once the relayer answers. While it exists the
+//! outcome is unknown, so the next attempt on that account first asks the
+//! relayer for an order on the account and adopts its request id instead of
+//! submitting again.
+//! [`OrderWallet::reconcile_pending_orders`](super::order_wallet::OrderWallet::reconcile_pending_orders)
+//! does the same for every outstanding record, e.g. after a restart.
+//!
+//! Relayers with [`Capability::ClientOrderId`](super::capabilities::Capability::ClientOrderId)
+//! also receive the key with the submit and recognise a resubmitted order
+//! themselves. Other relayers are matched by account address: a ZkOS account
+//! carries at most one order, so an order on the account submitted after the
+//! record was created is the one the record stands for.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::events::OrderKind;
+use super::order_wallet::{AccountIndex, RequestId};
+use super::relayer_types::parse_relayer_timestamp;
+use crate::compat::relayer_types::TxHash;
+
+/// How far, in seconds, the relayer's order timestamps may trail the local
+/// clock and still match a submission.
+pub const SUBMISSION_CLOCK_SKEW_SECS: i64 = 300;
+
+/// An order submit whose outcome is not known yet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingSubmission {
+    /// Client-side idempotency key, reused by every attempt at this order.
+    pub idempotency_key: String,
+    pub account_index: AccountIndex,
+    pub kind: OrderKind,
+    /// ZkOS address of the account at submit time.
+    pub account_address: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PendingSubmission {
+    pub fn new(
+        account_index: AccountIndex,
+        kind: OrderKind,
+        account_address: impl Into<String>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            idempotency_key: uuid::Uuid::new_v4().to_string(),
+            account_index,
+            kind,
+            account_address: account_address.into(),
+            created_at: now,
+        }
+    }
+
+    /// Whether `tx` may be the relayer's record of this submission: an order
+    /// on the same account, not timestamped before the submission started
+    /// (allowing [`SUBMISSION_CLOCK_SKEW_SECS`]). The relayer sends the
+    /// timestamp as a Unix integer; one that does not parse is no match, as
+    /// it may belong to any earlier order on the address.
+    pub fn matches(&self, tx: &TxHash) -> bool {
+        if tx.account_id != self.account_address {
+            return false;
+        }
+        match parse_relayer_timestamp(&tx.datetime) {
+            Some(at) => at + Duration::seconds(SUBMISSION_CLOCK_SKEW_SECS) >= self.created_at,
+            None => false,
+        }
+    }
+
+    /// Request id of the newest order in `txs` matching this submission.
+    pub fn find_request_id(&self, txs: &[TxHash]) -> Option<RequestId> {
+        txs.iter()
+            .filter(|tx| self.matches(tx))
+            .filter_map(|tx| {
+                let request_id = tx.request_id.as_deref()?.trim();
+                (!request_id.is_empty()).then(|| (tx.id, request_id.to_string()))
+            })
+            .max_by_key(|(id, _)| *id)
+            .map(|(_, request_id)| request_id)
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl PendingSubmission {
+    pub fn from_db(row: &crate::database::models::DbPendingSubmission) -> Result<Self, String> {
+        let kind = OrderKind::parse(&row.kind)
+            .ok_or_else(|| format!("Unknown pending submission kind: {}", row.kind))?;
+        Ok(Self {
+            idempotency_key: row.idempotency_key.clone(),
+            account_index: row.account_index as AccountIndex,
+            kind,
+            account_address: row.account_address.clone(),
+            created_at: row.created_at.and_utc(),
+        })
+    }
+}
+
+/// What [`OrderWallet::reconcile_pending_orders`](super::order_wallet::OrderWallet::reconcile_pending_orders)
+/// found for one submission. Either way the record is cleared.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Reconciliation {
+    /// The relayer holds the order; its request id is now tracked for the account.
+    Accepted { request_id: RequestId },
+    /// The relayer has no such order; the account is free for a new one.
+    NotFound,
+}
+
+/// One reconciled [`PendingSubmission`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReconciledSubmission {
+    pub submission: PendingSubmission,
+    pub reconciliation: Reconciliation,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::test_fixtures::TxHashBuilder;
+
+    fn submission() -> PendingSubmission {
+        PendingSubmission::new(
+            3,
+            OrderKind::Trader,
+            "acct-3",
+            "2024-01-01T00:10:00Z".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_keys_are_unique() {
+        assert_ne!(submission().idempotency_key, submission().idempotency_key);
+    }
+
+    /// `TxHash.datetime` as the relayer sends it: Unix milliseconds.
+    fn millis(at: &str) -> String {
+        at.parse::<DateTime<Utc>>()
+            .unwrap()
+            .timestamp_millis()
+            .to_string()
+    }
+
+    #[test]
+    fn test_find_request_id_matches_account_and_time() {
+        let submission = submission();
+        let tx = |id: i64, account: &str, at: String, request_id: &str| {
+            TxHashBuilder::new()
+                .field("id", id)
+                .account_id(account)
+                .field("datetime", at)
+                .request_id(request_id)
+                .build()
+        };
+
+        // Another account, and an order from before the submission.
+        let txs = vec![
+            tx(1, "acct-4", millis("2024-01-01T00:10:01Z"), "REQ-OTHER"),
+            tx(2, "acct-3", millis("2024-01-01T00:01:00Z"), "REQ-OLD"),
+        ];
+        assert_eq!(submission.find_request_id(&txs), None);
+
+        // Within the allowed skew, newest wins.
+        let txs = vec![
+            tx(3, "acct-3", millis("2024-01-01T00:06:00Z"), "REQ-A"),
+            tx(4, "acct-3", millis("2024-01-01T00:10:02Z"), "REQ-B"),
+        ];
+        assert_eq!(submission.find_request_id(&txs).as_deref(), Some("REQ-B"));
+
+        // Seconds are accepted too.
+        let seconds = "2024-01-01T00:10:03Z"
+            .parse::<DateTime<Utc>>()
+            .unwrap()
+            .timestamp()
+            .to_string();
+        let txs = vec![tx(5, "acct-3", seconds, "REQ-C")];
+        assert_eq!(submission.find_request_id(&txs).as_deref(), Some("REQ-C"));
+
+        // A timestamp that does not parse could be any earlier order.
+        let txs = vec![
+            tx(6, "acct-3", "yesterday".to_string(), "REQ-STALE"),
+            tx(7, "acct-3", String::new(), "REQ-STALE"),
+        ];
+        assert_eq!(submission.find_request_id(&txs), None);
+    }
+}
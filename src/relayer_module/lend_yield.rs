@@ -0,0 +1,210 @@
+//! Lend pool valuation and yield estimates.
+//!
+//! The relayer's lend pool is a share pool: a deposit `d` buys
+//! `d * TPS / TLV` shares, where `TLV` is the pool's total locked value in
+//! sats and `TPS` its total pool shares, both from `lend_pool_info`. A
+//! position is worth `shares * TLV / TPS` at any time, so earnings can be
+//! read off the pool before the order settles ([`LendPositionValue`]).
+//!
+//! [`LendYieldEstimate`] projects a new deposit from the pool's last-24h APY
+//! (`last_day_apy`, in percent). Pool income comes from trader positions, so
+//! it is taken as fixed for the estimate and spread over the larger pool: the
+//! deposit dilutes both the APY and the utilization (open interest over pool
+//! equity) by `TLV / (TLV + amount)`. The APY compounds over the duration.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::compat::relayer_types::LendOrder;
+
+use super::order_wallet::AccountIndex;
+use super::relayer_types::LendPoolInfo;
+
+pub const HOURS_PER_YEAR: f64 = 365.0 * 24.0;
+
+/// Sats one pool share is worth, or `None` for an empty pool.
+pub fn share_price(pool: &LendPoolInfo) -> Option<f64> {
+    (pool.total_pool_share > 0.0 && pool.total_locked_value > 0.0)
+        .then(|| pool.total_locked_value / pool.total_pool_share)
+}
+
+/// Projected return of depositing `amount` sats for `duration_hours`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LendYieldEstimate {
+    pub amount: u64,
+    pub duration_hours: u64,
+    /// Total locked value of the pool before the deposit, in sats.
+    pub pool_value: f64,
+    /// Open interest over pool equity, before the deposit.
+    pub utilization: f64,
+    /// Utilization once the deposit has joined the pool.
+    pub utilization_after: f64,
+    /// The pool's last-24h APY, in percent.
+    pub current_apy_pct: f64,
+    /// APY left for the pool after the deposit, in percent.
+    pub estimated_apy_pct: f64,
+    /// Interest earned over the duration, in sats.
+    pub estimated_interest: f64,
+    /// `amount + estimated_interest`.
+    pub estimated_value: f64,
+}
+
+impl LendYieldEstimate {
+    pub fn new(
+        amount: u64,
+        duration_hours: u64,
+        pool: &LendPoolInfo,
+        current_apy_pct: f64,
+        utilization: f64,
+    ) -> Self {
+        let pool_value = pool.total_locked_value.max(0.0);
+        let dilution = if pool_value + amount as f64 > 0.0 {
+            pool_value / (pool_value + amount as f64)
+        } else {
+            0.0
+        };
+        let estimated_apy_pct = current_apy_pct * dilution;
+        let growth = (1.0 + estimated_apy_pct / 100.0).powf(duration_hours as f64 / HOURS_PER_YEAR);
+        let estimated_interest = amount as f64 * (growth - 1.0);
+        Self {
+            amount,
+            duration_hours,
+            pool_value,
+            utilization,
+            utilization_after: utilization * dilution,
+            current_apy_pct,
+            estimated_apy_pct,
+            estimated_interest,
+            estimated_value: amount as f64 + estimated_interest,
+        }
+    }
+}
+
+/// Current value of an open lend order, from the pool state.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LendPositionValue {
+    pub account_index: AccountIndex,
+    pub deposit: f64,
+    pub pool_shares: f64,
+    /// Sats per pool share now.
+    pub share_price: f64,
+    /// `pool_shares * share_price`.
+    pub current_value: f64,
+    /// `current_value - deposit`; negative after trader profits.
+    pub accrued_interest: f64,
+    /// When the order was opened, if the relayer's timestamp parses.
+    pub opened_at: Option<DateTime<Utc>>,
+    pub elapsed_hours: Option<f64>,
+    /// Accrued interest annualized over the elapsed time, in percent.
+    /// `None` without an open time or before any time has passed.
+    pub annualized_return_pct: Option<f64>,
+}
+
+impl LendPositionValue {
+    pub fn from_lend_order(
+        account_index: AccountIndex,
+        order: &LendOrder,
+        pool: &LendPoolInfo,
+        now: DateTime<Utc>,
+    ) -> Result<Self, String> {
+        let share_price = share_price(pool).ok_or("Lend pool holds no shares")?;
+        let current_value = order.npoolshare * share_price;
+        let accrued_interest = current_value - order.deposit;
+        let opened_at = DateTime::parse_from_rfc3339(&order.timestamp)
+            .ok()
+            .map(|at| at.with_timezone(&Utc));
+        let elapsed_hours = opened_at.map(|at| (now - at).num_seconds().max(0) as f64 / 3600.0);
+        let annualized_return_pct = match elapsed_hours {
+            Some(hours) if hours > 0.0 && order.deposit > 0.0 => {
+                Some(accrued_interest / order.deposit * HOURS_PER_YEAR / hours * 100.0)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            account_index,
+            deposit: order.deposit,
+            pool_shares: order.npoolshare,
+            share_price,
+            current_value,
+            accrued_interest,
+            opened_at,
+            elapsed_hours,
+            annualized_return_pct,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::test_fixtures::LendOrderBuilder;
+
+    fn pool(total_locked_value: f64, total_pool_share: f64) -> LendPoolInfo {
+        LendPoolInfo {
+            id: 1,
+            sequence: 10,
+            nonce: 12,
+            total_pool_share,
+            total_locked_value,
+            pending_orders: 0,
+            aggregate_log_sequence: 40,
+            last_snapshot_id: None,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_estimate_dilutes_apy_and_utilization() {
+        // A deposit of a quarter of the pool leaves 80% of the APY.
+        let estimate = LendYieldEstimate::new(500_000, 8_760, &pool(2_000_000.0, 1e8), 10.0, 0.5);
+        assert_close(estimate.estimated_apy_pct, 8.0);
+        assert_close(estimate.utilization_after, 0.4);
+        // A full year at 8%.
+        assert_close(estimate.estimated_interest, 40_000.0);
+        assert_close(estimate.estimated_value, 540_000.0);
+
+        let day = LendYieldEstimate::new(500_000, 24, &pool(2_000_000.0, 1e8), 10.0, 0.5);
+        assert_close(
+            day.estimated_interest,
+            500_000.0 * (1.08f64.powf(1.0 / 365.0) - 1.0),
+        );
+        assert!(day.estimated_interest < estimate.estimated_interest / 365.0);
+    }
+
+    #[test]
+    fn test_estimate_for_empty_pool() {
+        let estimate = LendYieldEstimate::new(10_000, 24, &pool(0.0, 0.0), 10.0, 0.0);
+        assert_eq!(estimate.estimated_apy_pct, 0.0);
+        assert_eq!(estimate.estimated_interest, 0.0);
+    }
+
+    #[test]
+    fn test_position_value_from_pool_state() {
+        // Bought 3,000,000 shares at 100 shares per sat; the pool grew 5% since.
+        let order = LendOrderBuilder::new()
+            .amounts(30_000.0, 30_000.0)
+            .npoolshare(3_000_000.0)
+            .field("timestamp", "2024-01-01T00:00:00Z")
+            .build();
+        let now = "2024-01-11T00:00:00Z".parse().unwrap();
+        let value =
+            LendPositionValue::from_lend_order(2, &order, &pool(1_575_000.0, 1.5e8), now).unwrap();
+        assert_close(value.share_price, 0.0105);
+        assert_close(value.current_value, 31_500.0);
+        assert_close(value.accrued_interest, 1_500.0);
+        assert_eq!(value.elapsed_hours, Some(240.0));
+        // 5% in 10 days.
+        assert_close(value.annualized_return_pct.unwrap(), 5.0 * 36.5);
+
+        let err = LendPositionValue::from_lend_order(2, &order, &pool(0.0, 0.0), now).unwrap_err();
+        assert_eq!(err, "Lend pool holds no shares");
+    }
+}
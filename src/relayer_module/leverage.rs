@@ -0,0 +1,387 @@
+//! Fixed-precision leverage type.
+//!
+//! The relayer takes leverage as a floating-point multiplier (the SDK's
+//! `create_trader_order_zkos` encodes it as `f64`), so fractional values such
+//! as `2.5x` are accepted. [`Leverage`] stores the multiplier in units of
+//! `1 / LEVERAGE_SCALE` (0.0001x) so comparisons and margin math stay exact.
+//!
+//! Bounds come from the market, not a hard-coded cap: build a
+//! [`LeverageLimits`] from the relayer's [`RiskParams`] and validate with
+//! [`LeverageLimits::check`]. Markets that only take whole multipliers set
+//! `integer_only`, in which case [`Leverage::fractional`] returns
+//! [`LeverageError::Unsupported`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::relayer_types::RiskParams;
+
+/// Number of leverage units per 1x.
+pub const LEVERAGE_SCALE: u64 = 10_000;
+const SCALE_DIGITS: usize = 4;
+
+/// Errors from constructing or validating a [`Leverage`].
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LeverageError {
+    #[error("Leverage must be greater than 0")]
+    Zero,
+    #[error("Invalid leverage {0:?}")]
+    Invalid(String),
+    #[error("Leverage {0} has more than 4 decimal places")]
+    TooPrecise(String),
+    #[error("Leverage {value} exceeds maximum allowed {max}")]
+    AboveMax { value: Leverage, max: Leverage },
+    #[error("Fractional leverage {0} is not supported by this market; use a whole multiplier")]
+    Unsupported(Leverage),
+}
+
+/// Leverage multiplier with 0.0001x precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Leverage {
+    units: u64,
+}
+
+impl Leverage {
+    /// Leverage from raw units of `1 / LEVERAGE_SCALE`.
+    pub const fn from_units(units: u64) -> Self {
+        Self { units }
+    }
+
+    /// Whole-number leverage, or `None` if it does not fit.
+    pub fn whole(multiplier: u64) -> Option<Self> {
+        multiplier.checked_mul(LEVERAGE_SCALE).map(Self::from_units)
+    }
+
+    /// Fractional leverage (e.g. `2.5`), checked against `limits`.
+    ///
+    /// Returns [`LeverageError::Unsupported`] for a non-whole value when the
+    /// market is integer-only.
+    pub fn fractional(value: f64, limits: &LeverageLimits) -> Result<Self, LeverageError> {
+        let leverage = Self::try_from_f64(value)?;
+        limits.check(leverage)?;
+        Ok(leverage)
+    }
+
+    /// Convert a float, rejecting non-finite, non-positive or over-precise values.
+    /// No market bounds are applied.
+    pub fn try_from_f64(value: f64) -> Result<Self, LeverageError> {
+        if !value.is_finite() || value < 0.0 {
+            return Err(LeverageError::Invalid(value.to_string()));
+        }
+        let scaled = value * LEVERAGE_SCALE as f64;
+        if scaled >= u64::MAX as f64 {
+            return Err(LeverageError::Invalid(value.to_string()));
+        }
+        let units = scaled.round();
+        if (scaled - units).abs() > 1e-6 {
+            return Err(LeverageError::TooPrecise(value.to_string()));
+        }
+        if units == 0.0 {
+            return Err(LeverageError::Zero);
+        }
+        Ok(Self::from_units(units as u64))
+    }
+
+    /// Raw units of `1 / LEVERAGE_SCALE`.
+    pub fn units(&self) -> u64 {
+        self.units
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.units == 0
+    }
+
+    /// Whether the multiplier has no fractional part.
+    pub fn is_whole(&self) -> bool {
+        self.units % LEVERAGE_SCALE == 0
+    }
+
+    /// Integer part of the multiplier (`2.5x` → `2`).
+    pub fn whole_part(&self) -> u64 {
+        self.units / LEVERAGE_SCALE
+    }
+
+    /// Multiplier as `f64`, as sent to the relayer.
+    pub fn as_f64(&self) -> f64 {
+        self.units as f64 / LEVERAGE_SCALE as f64
+    }
+
+    /// `amount * leverage`, rounded down, or `None` on overflow.
+    /// Uses a `u128` intermediate so large margins do not overflow early.
+    pub fn apply(&self, amount: u64) -> Option<u64> {
+        let product = amount as u128 * self.units as u128 / LEVERAGE_SCALE as u128;
+        u64::try_from(product).ok()
+    }
+}
+
+impl From<u64> for Leverage {
+    /// Whole-number leverage; saturates at the largest representable value.
+    fn from(multiplier: u64) -> Self {
+        Self::whole(multiplier).unwrap_or(Self::from_units(u64::MAX))
+    }
+}
+
+impl fmt::Display for Leverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.whole_part();
+        let frac = self.units % LEVERAGE_SCALE;
+        if frac == 0 {
+            write!(f, "{}", whole)
+        } else {
+            let digits = format!("{:0width$}", frac, width = SCALE_DIGITS);
+            write!(f, "{}.{}", whole, digits.trim_end_matches('0'))
+        }
+    }
+}
+
+impl FromStr for Leverage {
+    type Err = LeverageError;
+
+    /// Parses `"10"`, `"2.5"` or `"2.5x"` without going through `f64`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let number = trimmed
+            .strip_suffix('x')
+            .or_else(|| trimmed.strip_suffix('X'))
+            .unwrap_or(trimmed);
+        let invalid = || LeverageError::Invalid(s.to_string());
+        let (whole, frac) = match number.split_once('.') {
+            Some((whole, frac)) => (whole, frac),
+            None => (number, ""),
+        };
+        if whole.is_empty() && frac.is_empty() {
+            return Err(invalid());
+        }
+        if !whole.chars().all(|c| c.is_ascii_digit()) || !frac.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let frac = frac.trim_end_matches('0');
+        if frac.len() > SCALE_DIGITS {
+            return Err(LeverageError::TooPrecise(s.to_string()));
+        }
+        let whole: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        let frac_units: u64 = if frac.is_empty() {
+            0
+        } else {
+            format!("{:0<width$}", frac, width = SCALE_DIGITS)
+                .parse()
+                .map_err(|_| invalid())?
+        };
+        let units = whole
+            .checked_mul(LEVERAGE_SCALE)
+            .and_then(|u| u.checked_add(frac_units))
+            .ok_or_else(invalid)?;
+        if units == 0 {
+            return Err(LeverageError::Zero);
+        }
+        Ok(Self::from_units(units))
+    }
+}
+
+impl Serialize for Leverage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_whole() {
+            serializer.serialize_u64(self.whole_part())
+        } else {
+            serializer.serialize_f64(self.as_f64())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Leverage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Int(u64),
+            Float(f64),
+            Str(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Int(v) => Leverage::whole(v)
+                .filter(|l| !l.is_zero())
+                .ok_or_else(|| serde::de::Error::custom(LeverageError::Invalid(v.to_string()))),
+            Raw::Float(v) => Leverage::try_from_f64(v).map_err(serde::de::Error::custom),
+            Raw::Str(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Market bounds for leverage.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct LeverageLimits {
+    /// Maximum leverage; `None` means the market does not publish a cap.
+    pub max: Option<Leverage>,
+    /// Only whole multipliers are accepted.
+    pub integer_only: bool,
+}
+
+impl LeverageLimits {
+    /// Limits from the relayer's risk parameters. The relayer takes leverage
+    /// as a float, so fractional values are allowed.
+    pub fn from_risk_params(params: &RiskParams) -> Self {
+        Self::with_max(params.max_leverage)
+    }
+
+    /// Limits capped at a published `max_leverage`; 0 or less means no cap.
+    pub fn with_max(max_leverage: f64) -> Self {
+        let max = if max_leverage > 0.0 {
+            // Round the published cap down to our precision so it is never exceeded.
+            let units = (max_leverage * LEVERAGE_SCALE as f64).floor();
+            Some(Leverage::from_units(units.min(u64::MAX as f64) as u64))
+        } else {
+            None
+        };
+        Self {
+            max,
+            integer_only: false,
+        }
+    }
+
+    /// Check `leverage` against these limits.
+    pub fn check(&self, leverage: Leverage) -> Result<(), LeverageError> {
+        if leverage.is_zero() {
+            return Err(LeverageError::Zero);
+        }
+        if self.integer_only && !leverage.is_whole() {
+            return Err(LeverageError::Unsupported(leverage));
+        }
+        if let Some(max) = self.max {
+            if leverage > max {
+                return Err(LeverageError::AboveMax {
+                    value: leverage,
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max: u64) -> LeverageLimits {
+        LeverageLimits {
+            max: Leverage::whole(max),
+            integer_only: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!("10".parse::<Leverage>().unwrap(), Leverage::from(10));
+        assert_eq!("2.5x".parse::<Leverage>().unwrap().units(), 25_000);
+        assert_eq!(" 0.0001 ".parse::<Leverage>().unwrap().units(), 1);
+        assert_eq!("2.50".parse::<Leverage>().unwrap().to_string(), "2.5");
+        assert_eq!(Leverage::from(10).to_string(), "10");
+        assert_eq!(Leverage::from_units(1_250).to_string(), "0.125");
+
+        assert_eq!("0".parse::<Leverage>(), Err(LeverageError::Zero));
+        assert!(matches!(
+            "1.00001".parse::<Leverage>(),
+            Err(LeverageError::TooPrecise(_))
+        ));
+        for bad in ["", "x", ".", "-1", "1e3", "abc", "1.2.3"] {
+            assert!(
+                matches!(bad.parse::<Leverage>(), Err(LeverageError::Invalid(_))),
+                "{bad:?} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_conversion() {
+        assert_eq!(Leverage::try_from_f64(2.5).unwrap().units(), 25_000);
+        assert_eq!(Leverage::try_from_f64(0.0), Err(LeverageError::Zero));
+        assert!(Leverage::try_from_f64(f64::NAN).is_err());
+        assert!(Leverage::try_from_f64(-1.0).is_err());
+        assert!(matches!(
+            Leverage::try_from_f64(1.23456),
+            Err(LeverageError::TooPrecise(_))
+        ));
+    }
+
+    #[test]
+    fn test_bounds_from_risk_params() {
+        let params = RiskParams {
+            max_oi_mult: 0.0,
+            max_net_mult: 0.0,
+            max_position_pct: 0.0,
+            min_position_btc: 0.0,
+            max_leverage: 20.0,
+            mm_ratio: 0.0,
+        };
+        let limits = LeverageLimits::from_risk_params(&params);
+        assert!(limits.check(Leverage::from(20)).is_ok());
+        assert!(Leverage::fractional(19.5, &limits).is_ok());
+        assert_eq!(
+            Leverage::fractional(20.5, &limits),
+            Err(LeverageError::AboveMax {
+                value: Leverage::from_units(205_000),
+                max: Leverage::from(20),
+            })
+        );
+        assert_eq!(
+            limits.check(Leverage::from_units(0)),
+            Err(LeverageError::Zero)
+        );
+
+        // No published cap.
+        let uncapped = LeverageLimits::from_risk_params(&RiskParams {
+            max_leverage: 0.0,
+            ..params
+        });
+        assert!(uncapped.check(Leverage::from(1_000)).is_ok());
+    }
+
+    #[test]
+    fn test_integer_only_market_rejects_fractional() {
+        let integer_only = LeverageLimits {
+            integer_only: true,
+            ..limits(50)
+        };
+        assert!(Leverage::fractional(5.0, &integer_only).is_ok());
+        assert_eq!(
+            Leverage::fractional(2.5, &integer_only),
+            Err(LeverageError::Unsupported(Leverage::from_units(25_000)))
+        );
+    }
+
+    #[test]
+    fn test_apply_is_overflow_safe() {
+        assert_eq!(Leverage::from(10).apply(1_000), Some(10_000));
+        assert_eq!(Leverage::from_units(25_000).apply(3), Some(7)); // 7.5 rounds down
+        // u64::MAX * 1x must not overflow in the intermediate.
+        assert_eq!(Leverage::from(1).apply(u64::MAX), Some(u64::MAX));
+        assert_eq!(
+            Leverage::from_units(5_000).apply(u64::MAX),
+            Some(u64::MAX / 2)
+        );
+        assert_eq!(Leverage::from(2).apply(u64::MAX), None);
+        // From<u64> saturates instead of wrapping.
+        assert_eq!(Leverage::from(u64::MAX).units(), u64::MAX);
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        assert_eq!(serde_json::to_string(&Leverage::from(10)).unwrap(), "10");
+        assert_eq!(
+            serde_json::to_string(&Leverage::from_units(25_000)).unwrap(),
+            "2.5"
+        );
+        let from_int: Leverage = serde_json::from_str("10").unwrap();
+        let from_float: Leverage = serde_json::from_str("2.5").unwrap();
+        let from_str: Leverage = serde_json::from_str("\"2.5x\"").unwrap();
+        assert_eq!(from_int, Leverage::from(10));
+        assert_eq!(from_float, from_str);
+        assert!(serde_json::from_str::<Leverage>("0").is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! One-call view of the relayer's market data.
+//!
+//! [`RelayerJsonRpcClient::market_snapshot`](super::relayer_api::RelayerJsonRpcClient::market_snapshot)
+//! fetches the BTC/USD price, the funding rate, the order book, the recent
+//! trades and the open position sizes concurrently and returns them as one
+//! [`MarketSnapshot`]. Each part is an `Option`: a part whose request failed
+//! is `None` and its error is kept in [`MarketSnapshot::errors`], so one
+//! failing endpoint leaves the rest of the snapshot usable.
+//!
+//! [`PositionSizeInfo`] is the relayer's `position_size` with the long/short
+//! shares worked out, for use as an open interest sentiment input.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::relayer_types::{BtcUsdPrice, FundingRate, OrderBook, PositionSize, RecentOrders};
+
+/// Open long and short position sizes, in the unit the relayer reports them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionSizeInfo {
+    pub long: f64,
+    pub short: f64,
+    pub total: f64,
+}
+
+impl PositionSizeInfo {
+    pub fn from_position_size(size: &PositionSize) -> Self {
+        Self {
+            long: size.total_long_position_size,
+            short: size.total_short_position_size,
+            total: size.total_position_size,
+        }
+    }
+
+    /// `long - short`; positive when longs dominate.
+    pub fn net(&self) -> f64 {
+        self.long - self.short
+    }
+
+    /// Long share of the open size, in `[0, 1]`; `None` without open positions.
+    pub fn long_share(&self) -> Option<f64> {
+        let open = self.long + self.short;
+        (open > 0.0).then(|| self.long / open)
+    }
+
+    /// Long over short size; `None` without shorts.
+    pub fn long_short_ratio(&self) -> Option<f64> {
+        (self.short > 0.0).then(|| self.long / self.short)
+    }
+}
+
+impl From<PositionSize> for PositionSizeInfo {
+    fn from(size: PositionSize) -> Self {
+        Self::from_position_size(&size)
+    }
+}
+
+/// The relayer's market data at `captured_at`; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarketSnapshot {
+    /// When the requests were sent.
+    pub captured_at: DateTime<Utc>,
+    pub price: Option<BtcUsdPrice>,
+    pub funding_rate: Option<FundingRate>,
+    pub order_book: Option<OrderBook>,
+    pub recent_trades: Option<RecentOrders>,
+    pub position_size: Option<PositionSizeInfo>,
+    /// Error of each part that could not be fetched, keyed by field name.
+    pub errors: BTreeMap<&'static str, String>,
+}
+
+impl MarketSnapshot {
+    /// Snapshot captured at `captured_at` from the result of each request.
+    pub fn from_results<E: std::fmt::Display>(
+        captured_at: DateTime<Utc>,
+        price: Result<BtcUsdPrice, E>,
+        funding_rate: Result<FundingRate, E>,
+        order_book: Result<OrderBook, E>,
+        recent_trades: Result<RecentOrders, E>,
+        position_size: Result<PositionSizeInfo, E>,
+    ) -> Self {
+        let mut errors = BTreeMap::new();
+        let mut keep = |field: &'static str, error: E| {
+            errors.insert(field, error.to_string());
+        };
+        let price = price.map_err(|e| keep("price", e)).ok();
+        let funding_rate = funding_rate.map_err(|e| keep("funding_rate", e)).ok();
+        let order_book = order_book.map_err(|e| keep("order_book", e)).ok();
+        let recent_trades = recent_trades.map_err(|e| keep("recent_trades", e)).ok();
+        let position_size = position_size.map_err(|e| keep("position_size", e)).ok();
+        Self {
+            captured_at,
+            price,
+            funding_rate,
+            order_book,
+            recent_trades,
+            position_size,
+            errors,
+        }
+    }
+
+    /// Whether every part was fetched.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Midpoint of the best bid and ask; `None` without both sides.
+    pub fn mid_price(&self) -> Option<f64> {
+        let book = self.order_book.as_ref()?;
+        let bid = book.bid.first()?.price;
+        let ask = book.ask.first()?.price;
+        Some((bid + ask) / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::relayer_types::{Ask, Bid};
+
+    #[test]
+    fn test_position_size_shares() {
+        let info = PositionSizeInfo::from(PositionSize {
+            total_short_position_size: 100.0,
+            total_long_position_size: 300.0,
+            total_position_size: 400.0,
+        });
+        assert_eq!(info.net(), 200.0);
+        assert_eq!(info.long_share(), Some(0.75));
+        assert_eq!(info.long_short_ratio(), Some(3.0));
+
+        let empty = PositionSizeInfo {
+            long: 0.0,
+            short: 0.0,
+            total: 0.0,
+        };
+        assert_eq!(empty.long_share(), None);
+        assert_eq!(empty.long_short_ratio(), None);
+    }
+
+    #[test]
+    fn test_failed_parts_are_none_with_their_error() {
+        let book = OrderBook {
+            bid: vec![Bid {
+                positionsize: 1.0,
+                price: 64_990.0,
+            }],
+            ask: vec![Ask {
+                positionsize: 1.0,
+                price: 65_010.0,
+            }],
+        };
+        let snapshot = MarketSnapshot::from_results(
+            DateTime::<Utc>::UNIX_EPOCH,
+            Err("price down"),
+            Err("no funding"),
+            Ok(book),
+            Ok(RecentOrders { orders: Vec::new() }),
+            Err("no position size"),
+        );
+        assert!(snapshot.price.is_none());
+        assert_eq!(snapshot.mid_price(), Some(65_000.0));
+        assert!(snapshot.recent_trades.is_some());
+        assert!(!snapshot.is_complete());
+        assert_eq!(
+            snapshot.errors.keys().copied().collect::<Vec<_>>(),
+            vec!["funding_rate", "position_size", "price"]
+        );
+        assert_eq!(snapshot.errors["price"], "price down");
+    }
+}
@@ -0,0 +1,393 @@
+//! Scripted in-memory relayer for offline tests (`test-utils` feature).
+//!
+//! A [`MockRelayer`] implements [`RelayerApi`], so an `OrderWallet` built
+//! `with_relayer` trades against it without a relayer. Answers are scripted
+//! per JSON-RPC method name (`trader_order_info`, `cancel_trader_order`,
+//! ...) as JSON, the form the [`test_fixtures`](super::test_fixtures)
+//! builders return from `to_json()`:
+//!
+//! ```ignore
+//! use nyks_wallet::relayer_module::mock_relayer::MockRelayer;
+//! use nyks_wallet::relayer_module::test_fixtures::TraderOrderBuilder;
+//!
+//! let relayer = MockRelayer::new();
+//! relayer.respond("trader_order_info", TraderOrderBuilder::new().to_json());
+//! relayer.fail_once("settle_trade_order", "Order is being liquidated");
+//! let order_wallet = order_wallet.with_relayer(Arc::new(relayer.clone()));
+//! // ...
+//! assert_eq!(relayer.call_count("settle_trade_order"), 1);
+//! ```
+//!
+//! Answers queued with [`respond_once`](MockRelayer::respond_once) and
+//! [`fail_once`](MockRelayer::fail_once) are used first, in order; then the
+//! standing answer set with [`respond`](MockRelayer::respond) or
+//! [`fail`](MockRelayer::fail). A method without either fails with a
+//! "no response scripted" error. `get_market_stats` starts out answering an
+//! open market. Both settle and both cancel calls share a method name, as on
+//! the wire.
+//!
+//! Clones share their script and call log, so a test keeps one clone to
+//! assert on. Capabilities are not scripted by method but set with
+//! [`with_capabilities`](MockRelayer::with_capabilities), and the
+//! handshake is not logged.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use jsonrpsee::core::client::Error as RpcError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::capabilities::RelayerCapabilities;
+use super::relayer_api::{RelayerApi, RelayerFuture};
+use super::relayer_types::{
+    BtcUsdPrice, LendOrder, LendOrderV1, MarketStats, RequestResponse, TraderOrder, TraderOrderV1,
+    TransactionHashArgs, TxHash,
+};
+use crate::compat::relayer_types::{
+    CancelTraderOrderZkos, CancelTraderOrderZkosSlTp, CreateLendOrderZkos,
+    CreateTraderOrderClientZkos, ExecuteLendOrderZkos, ExecuteTraderOrderZkos,
+    ExecuteTraderOrderZkosSlTp, QueryLendOrderZkos, QueryTraderOrderZkos,
+};
+use crate::config::RetryPolicy;
+
+#[derive(Debug, Default)]
+struct Script {
+    queued: HashMap<String, VecDeque<Result<Value, String>>>,
+    standing: HashMap<String, Result<Value, String>>,
+    calls: Vec<String>,
+}
+
+/// Scripted [`RelayerApi`]; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct MockRelayer {
+    script: Arc<Mutex<Script>>,
+    capabilities: RelayerCapabilities,
+    retry_policy: RetryPolicy,
+}
+
+impl Default for MockRelayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockRelayer {
+    /// A relayer with an open market, no optional capabilities, and a retry
+    /// policy of three polls 1 ms apart.
+    pub fn new() -> Self {
+        let relayer = Self {
+            script: Arc::new(Mutex::new(Script::default())),
+            capabilities: RelayerCapabilities::default(),
+            retry_policy: RetryPolicy {
+                max_attempts: 3,
+                utxo_max_attempts: 3,
+                initial_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+                backoff_multiplier: 1.0,
+                request_timeout: Duration::from_secs(1),
+            },
+        };
+        relayer.respond("get_market_stats", open_market());
+        relayer
+    }
+
+    pub fn with_capabilities(mut self, capabilities: RelayerCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Answer every later `method` call with `response`.
+    pub fn respond(&self, method: &str, response: impl Serialize) {
+        let response = to_value(method, response);
+        self.lock()
+            .standing
+            .insert(method.to_string(), Ok(response));
+    }
+
+    /// Answer the next `method` call with `response`.
+    pub fn respond_once(&self, method: &str, response: impl Serialize) {
+        let response = to_value(method, response);
+        self.queue(method, Ok(response));
+    }
+
+    /// Fail every later `method` call with `message`.
+    pub fn fail(&self, method: &str, message: impl Into<String>) {
+        self.lock()
+            .standing
+            .insert(method.to_string(), Err(message.into()));
+    }
+
+    /// Fail the next `method` call with `message`.
+    pub fn fail_once(&self, method: &str, message: impl Into<String>) {
+        self.queue(method, Err(message.into()));
+    }
+
+    /// Answer `method` calls with a [`RequestResponse`] for `request_id`.
+    pub fn accept(&self, method: &str, request_id: &str) {
+        self.respond(
+            method,
+            RequestResponse {
+                msg: "Order request submitted successfully".to_string(),
+                id_key: request_id.to_string(),
+            },
+        );
+    }
+
+    /// Methods called so far, oldest first.
+    pub fn calls(&self) -> Vec<String> {
+        self.lock().calls.clone()
+    }
+
+    pub fn call_count(&self, method: &str) -> usize {
+        self.lock()
+            .calls
+            .iter()
+            .filter(|call| *call == method)
+            .count()
+    }
+
+    pub fn clear_calls(&self) {
+        self.lock().calls.clear();
+    }
+
+    fn queue(&self, method: &str, response: Result<Value, String>) {
+        self.lock()
+            .queued
+            .entry(method.to_string())
+            .or_default()
+            .push_back(response);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Script> {
+        self.script.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Log a `method` call and take its answer.
+    fn answer<T: DeserializeOwned + Send + 'static>(
+        &self,
+        method: &'static str,
+    ) -> RelayerFuture<'static, T> {
+        let response = {
+            let mut script = self.lock();
+            script.calls.push(method.to_string());
+            match script.queued.get_mut(method).and_then(VecDeque::pop_front) {
+                Some(response) => response,
+                None => script.standing.get(method).cloned().unwrap_or_else(|| {
+                    Err(format!("MockRelayer: no response scripted for {}", method))
+                }),
+            }
+        };
+        Box::pin(async move {
+            let value = response.map_err(RpcError::Custom)?;
+            serde_json::from_value(value).map_err(|e| {
+                RpcError::Custom(format!("MockRelayer: invalid {} response: {}", method, e))
+            })
+        })
+    }
+}
+
+fn to_value(method: &str, response: impl Serialize) -> Value {
+    serde_json::to_value(response)
+        .unwrap_or_else(|e| panic!("MockRelayer: {} response does not serialize: {}", method, e))
+}
+
+/// `get_market_stats` of an open market with room on both sides.
+fn open_market() -> Value {
+    serde_json::json!({
+        "pool_equity_btc": 10.0,
+        "total_long_btc": 0.0,
+        "total_short_btc": 0.0,
+        "total_pending_long_btc": 0.0,
+        "total_pending_short_btc": 0.0,
+        "open_interest_btc": 0.0,
+        "net_exposure_btc": 0.0,
+        "long_pct": 0.0,
+        "short_pct": 0.0,
+        "utilization": 0.0,
+        "max_long_btc": 5.0,
+        "max_short_btc": 5.0,
+        "status": "HEALTHY",
+        "status_reason": null,
+        "params": {
+            "max_oi_mult": 4.0,
+            "max_net_mult": 0.8,
+            "max_position_pct": 0.2,
+            "min_position_btc": 0.0,
+            "max_leverage": 50.0,
+            "mm_ratio": 0.4,
+        },
+        "funding_rate": {
+            "funding_rate": 0.0,
+            "estimated_funding_rate": 0.0,
+            "funding_rate_timestamp": "2025-03-01T12:00:00Z",
+            "estimated_funding_rate_timestamp": "2025-03-01T12:00:00Z",
+        },
+    })
+}
+
+impl RelayerApi for MockRelayer {
+    fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    fn capabilities(&self) -> RelayerFuture<'_, RelayerCapabilities> {
+        let capabilities = self.capabilities.clone();
+        Box::pin(async move { Ok(capabilities) })
+    }
+
+    fn btc_usd_price(&self) -> RelayerFuture<'_, BtcUsdPrice> {
+        self.answer("btc_usd_price")
+    }
+
+    fn get_market_stats(&self) -> RelayerFuture<'_, MarketStats> {
+        self.answer("get_market_stats")
+    }
+
+    fn transaction_hashes(&self, _params: TransactionHashArgs) -> RelayerFuture<'_, Vec<TxHash>> {
+        self.answer("transaction_hashes")
+    }
+
+    fn trader_order_info(&self, _tx: QueryTraderOrderZkos) -> RelayerFuture<'_, TraderOrder> {
+        self.answer("trader_order_info")
+    }
+
+    fn trader_order_info_v1(&self, _tx: QueryTraderOrderZkos) -> RelayerFuture<'_, TraderOrderV1> {
+        self.answer("trader_order_info_v1")
+    }
+
+    fn lend_order_info(&self, _tx: QueryLendOrderZkos) -> RelayerFuture<'_, LendOrder> {
+        self.answer("lend_order_info")
+    }
+
+    fn lend_order_info_v1(&self, _tx: QueryLendOrderZkos) -> RelayerFuture<'_, LendOrderV1> {
+        self.answer("lend_order_info_v1")
+    }
+
+    fn submit_trade_order_with_key<'a>(
+        &'a self,
+        _tx: CreateTraderOrderClientZkos,
+        _client_order_id: Option<&'a str>,
+    ) -> RelayerFuture<'a, RequestResponse> {
+        self.answer("submit_trade_order")
+    }
+
+    fn submit_lend_order_with_key<'a>(
+        &'a self,
+        _tx: CreateLendOrderZkos,
+        _client_order_id: Option<&'a str>,
+    ) -> RelayerFuture<'a, RequestResponse> {
+        self.answer("submit_lend_order")
+    }
+
+    fn settle_trade_order(
+        &self,
+        _tx: ExecuteTraderOrderZkos,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        self.answer("settle_trade_order")
+    }
+
+    fn settle_trade_order_sltp(
+        &self,
+        _tx: ExecuteTraderOrderZkosSlTp,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        self.answer("settle_trade_order")
+    }
+
+    fn settle_trade_order_partial(
+        &self,
+        _tx: ExecuteTraderOrderZkos,
+        _settle_size: f64,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        self.answer("settle_trade_order_partial")
+    }
+
+    fn settle_lend_order(&self, _tx: ExecuteLendOrderZkos) -> RelayerFuture<'_, RequestResponse> {
+        self.answer("settle_lend_order")
+    }
+
+    fn cancel_trader_order(
+        &self,
+        _tx: CancelTraderOrderZkos,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        self.answer("cancel_trader_order")
+    }
+
+    fn cancel_trader_order_sltp(
+        &self,
+        _tx: CancelTraderOrderZkosSlTp,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        self.answer("cancel_trader_order")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::capabilities::Capability;
+
+    #[tokio::test]
+    async fn test_queued_answers_come_before_the_standing_one() {
+        let relayer = MockRelayer::new();
+        let api: &dyn RelayerApi = &relayer;
+        assert_eq!(api.get_market_stats().await.unwrap().status, "HEALTHY");
+
+        relayer.respond(
+            "btc_usd_price",
+            serde_json::json!({ "id": 1, "price": "65000.5", "timestamp": "2025-01-01T00:00:00Z" }),
+        );
+        relayer.fail_once("btc_usd_price", "relayer busy");
+        let err = api.btc_usd_price().await.unwrap_err();
+        assert!(err.to_string().contains("relayer busy"), "{}", err);
+        assert_eq!(api.btc_usd_price().await.unwrap().price, 65_000.5);
+        assert_eq!(api.btc_usd_price().await.unwrap().price, 65_000.5);
+
+        let clone = relayer.clone();
+        clone.accept("settle_trade_order", "REQ1");
+        assert_eq!(relayer.call_count("btc_usd_price"), 3);
+        assert_eq!(
+            relayer.calls(),
+            vec![
+                "get_market_stats",
+                "btc_usd_price",
+                "btc_usd_price",
+                "btc_usd_price"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unscripted_method_fails_and_capabilities_are_not_logged() {
+        let relayer = MockRelayer::new();
+        let api: &dyn RelayerApi = &relayer;
+        let err = api
+            .transaction_hashes(TransactionHashArgs::RequestId {
+                id: "REQ1".to_string(),
+                status: None,
+                limit: None,
+                offset: None,
+            })
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("no response scripted for transaction_hashes"),
+            "{}",
+            err
+        );
+        assert!(!api.supports(Capability::OrderInfoV1).await);
+
+        let mut capabilities = RelayerCapabilities::default();
+        capabilities.set(Capability::OrderInfoV1, true);
+        let relayer = relayer.with_capabilities(capabilities);
+        assert!(RelayerApi::supports(&relayer, Capability::OrderInfoV1).await);
+        assert_eq!(relayer.calls(), vec!["transaction_hashes"]);
+    }
+}
@@ -5,10 +5,47 @@
 //!
 //! ## Module Organization
 //!
+//! - [`account_pool`]: Pool of funded accounts handed out to concurrent positions, with rotation
+//! - [`address_book`]: Labelled Twilight, BTC and ZkOS addresses, checked on entry, for the send paths
+//! - [`account_sync`]: Repairing local account state that diverged from the chain's UTXOs
+//! - [`account_state`]: Lock-guarded per-account state shared by concurrent OrderWallet operations
+//! - [`capabilities`]: Relayer version/capabilities handshake used to gate optional features
+//! - [`chain_tx`]: Per-address queue that serializes signing and broadcast across wallets
+//! - [`clock`]: Injectable time source (`SystemClock` / `ManualClock`) for time-dependent logic
+//! - [`conditional_orders`]: Client-side stop-loss and take-profit triggers closing positions on price
+//! - [`endpoint_pool`]: Per-endpoint health and attempt order for relayer failover
+//! - [`events`]: Order and account events emitted by OrderWallet to webhooks and `subscribe_events` receivers
+//! - [`execution_report`]: Slippage and time to fill of trader orders against their requested entry price
+//! - [`fees`]: Relayer fee schedule in basis points and per-order fee estimates in sats
+//! - `health`: `/healthz` and `/readyz` probe endpoints (`health-endpoint` feature)
+//! - [`idempotency`]: Idempotency keys that stop a retried submit from opening a second order
+//! - [`lend_yield`]: Lend pool share valuation and yield estimates for new deposits
+//! - [`leverage`]: Fixed-precision `Leverage` type with market-derived bounds
+//! - [`market_snapshot`]: Price, funding, order book, recent trades and open position sizes in one call
+//! - `mock_relayer`: Scripted in-memory `RelayerApi` for offline OrderWallet tests (`test-utils` feature)
+//! - [`order_wait`]: Submit-and-wait helpers that open an order and wait for its fill
+//! - [`order_watcher`]: Background watcher reconciling local account state with order status changes
 //! - [`order_wallet`]: High-level trading interface that orchestrates the complete trading workflow
+//! - [`position_health`]: Liquidation price and margin health of a trader position
+//! - [`program_cache`]: In-memory cache of the parsed relayer program shared across orders
+//! - [`pending_operations`]: Resumable records for partially completed multi-account operations
+//! - [`rate_limiter`]: Token bucket shared by a relayer client and its clones
+//! - [`receiver_check`]: Address, ownership and confirmation checks for transfers to foreign addresses
 //! - [`relayer_api`]: Low-level JSON-RPC client for direct relayer endpoint access
 //! - [`relayer_order`]: Order creation and execution primitives for trader and lend operations
+//! - [`relayer_program`]: Validated relayer program (`relayerprogram.json`), loaded when an OrderWallet is built
 //! - [`relayer_types`]: Type definitions and data structures for relayer communication
+//! - [`request_metrics`]: Per-method call counts, errors and latency percentiles of a relayer client
+//! - [`response_cache`]: Shared short-TTL cache with request coalescing for hot read endpoints
+//! - `risk`: Wallet-level exposure, leverage and liquidation-distance report
+//! - [`simulation`]: Local simulated execution on a manually advanced clock
+//! - `status`: Human-readable wallet status report and one-line log summary
+//! - [`statement`]: Account statements of fund movements over a period, as CSV or JSON lines
+//! - [`trade_flow`]: Volume, VWAP, side ratio and time-binned aggregates over recent trades
+//! - [`trading_limits`]: Relayer leverage and position size bounds checked before an order is signed
+//! - `test_fixtures`: Builders and ready-made relayer values for unit tests (`test-utils` feature)
+//! - `webhooks`: Signed, retried delivery of lifecycle events to HTTP endpoints (`webhooks` feature)
+//! - [`wallet_lock`]: Lock/unlock lifecycle and idle auto-lock for the cached DB passphrase
 //! - [`utils`]: Utility functions for transaction building, retry logic, and chain communication
 //!
 //! ## Usage Patterns
@@ -19,7 +56,7 @@
 //!
 //! ```no_run
 //! use nyks_wallet::relayer_module::order_wallet::OrderWallet;
-//! use twilight_client_sdk::relayer_types::{OrderType, PositionType};
+//! use nyks_wallet::compat::relayer_types::{OrderType, PositionType};
 //!
 //! # async fn example() -> Result<(), String> {
 //! let mut order_wallet = OrderWallet::new(None).map_err(|e| e.to_string())?;
@@ -61,7 +98,7 @@
 //!
 //! ```no_run
 //! use nyks_wallet::relayer_module::relayer_order::create_trader_order;
-//! use twilight_client_sdk::relayer_types::{OrderType, PositionType};
+//! use nyks_wallet::compat::relayer_types::{OrderType, PositionType};
 //!
 //! # async fn example() -> Result<(), String> {
 //! # let secret_key = todo!();
@@ -71,7 +108,7 @@
 //! # let entry_price = 50000u64;
 //! # let position_value = 10000u64;
 //! # let position_size = 500000000u64;
-//! # let relayer_program_path = "path/to/relayer.json";
+//! # let relayer_program = nyks_wallet::relayer_module::relayer_program::RelayerProgram::load("path/to/relayer.json").map_err(|e| e.to_string())?;
 //! # let account_address = "account_address".to_string();
 //! # let relayer_client = todo!();
 //! let request_id = create_trader_order(
@@ -84,7 +121,7 @@
 //!     entry_price,
 //!     position_value,
 //!     position_size,
-//!     relayer_program_path,
+//!     &relayer_program,
 //!     account_address,
 //!     &relayer_client,
 //! ).await?;
@@ -113,12 +150,98 @@
 //!
 //! See [`utils`] for retry configuration and helper functions.
 
-pub mod nonce_manager;
+// Available with the `market-data` feature alone.
+pub mod activity;
+pub mod capabilities;
+pub mod clock;
+pub mod endpoint_pool;
+pub mod fees;
+pub mod leverage;
+pub mod market_snapshot;
+pub mod order_query;
+pub mod rate_limiter;
+pub mod relayer_api;
+pub mod relayer_types;
+pub mod request_metrics;
+#[cfg(feature = "ws")]
+pub mod relayer_ws;
+pub mod response_cache;
+pub mod trade_flow;
+pub mod trading_limits;
+
+// Trading stack; needs keys and ZkOS accounts.
+#[cfg(feature = "order-wallet")]
+pub mod account_pool;
+#[cfg(feature = "order-wallet")]
+pub mod account_state;
+#[cfg(feature = "order-wallet")]
+pub mod account_sync;
+#[cfg(feature = "order-wallet")]
+pub mod address_book;
+#[cfg(feature = "order-wallet")]
+pub mod chain_tx;
+#[cfg(feature = "order-wallet")]
+pub mod conditional_orders;
+#[cfg(feature = "order-wallet")]
+pub mod diagnostics;
+#[cfg(feature = "order-wallet")]
+pub mod events;
+#[cfg(feature = "order-wallet")]
+pub mod execution_report;
+#[cfg(feature = "health-endpoint")]
+pub mod health;
+#[cfg(feature = "order-wallet")]
+pub mod idempotency;
+#[cfg(feature = "order-wallet")]
+pub mod lend_yield;
+// Moved to `wallet::nonce_manager`; re-exported under the old path.
+#[cfg(feature = "order-wallet")]
+pub use crate::wallet::nonce_manager;
+#[cfg(feature = "order-wallet")]
+pub mod order_wait;
+#[cfg(feature = "order-wallet")]
 pub mod order_wallet;
+#[cfg(feature = "order-wallet")]
+pub mod order_watcher;
+#[cfg(feature = "order-wallet")]
+pub mod pending_operations;
+#[cfg(feature = "order-wallet")]
 pub mod portfolio;
-pub mod relayer_api;
+#[cfg(feature = "order-wallet")]
+pub mod position_health;
+#[cfg(feature = "order-wallet")]
+pub mod program_cache;
+#[cfg(feature = "order-wallet")]
+pub mod risk;
+#[cfg(feature = "order-wallet")]
+pub mod shutdown;
+#[cfg(feature = "order-wallet")]
+pub mod signing_audit;
+#[cfg(feature = "order-wallet")]
+pub mod simulation;
+#[cfg(feature = "order-wallet")]
+pub mod status;
+#[cfg(feature = "order-wallet")]
+pub mod statement;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod mock_relayer;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_fixtures;
+#[cfg(feature = "order-wallet")]
 pub mod transaction_history;
+#[cfg(feature = "order-wallet")]
+pub mod receiver_check;
+#[cfg(feature = "order-wallet")]
 pub mod relayer_order;
-pub mod relayer_types;
+#[cfg(feature = "order-wallet")]
+pub mod relayer_program;
+#[cfg(feature = "order-wallet")]
+pub mod utxo_cache;
+#[cfg(feature = "order-wallet")]
+pub mod wallet_lock;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
+#[cfg(feature = "order-wallet")]
 mod utils;
+#[cfg(feature = "order-wallet")]
 pub use utils::*;
@@ -0,0 +1,250 @@
+//! Authenticated order queries for callers holding individual account keys.
+//!
+//! The relayer only returns an order to a request signed with the secret key
+//! of the ZkOS account that placed it. `OrderWallet` derives those keys from
+//! its seed; services that hold exported per-account keys instead can use the
+//! builders here with
+//! [`RelayerJsonRpcClient::trader_order_info`] and
+//! [`RelayerJsonRpcClient::lend_order_info`], without a `Wallet`:
+//!
+//! ```no_run
+//! # use nyks_wallet::relayer_module::{order_query, relayer_api::RelayerJsonRpcClient};
+//! # use nyks_wallet::relayer_module::relayer_types::OrderStatus;
+//! # async fn run(
+//! #     secret_key: nyks_wallet::compat::quisquislib::RistrettoSecretKey,
+//! #     account_address: String,
+//! # ) -> Result<(), String> {
+//! let client = RelayerJsonRpcClient::new("http://0.0.0.0:8088/api").map_err(|e| e.to_string())?;
+//! let query = order_query::build_trader_order_query(&secret_key, &account_address, OrderStatus::PENDING)?;
+//! let order = client.trader_order_info(query).await.map_err(|e| e.to_string())?;
+//! order_query::verify_trader_order_owner(&order, &account_address)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The `verify_*` helpers let such callers check that what came back belongs
+//! to the account they asked about.
+
+use crate::compat::{
+    quisquislib::RistrettoSecretKey,
+    relayer::{query_lend_order_zkos, query_trader_order_zkos},
+    relayer_types::{
+        LendOrder, OrderStatus, QueryLendOrderZkos, QueryTraderOrderZkos, TraderOrder,
+    },
+    zkvm::Output,
+};
+
+use serde::Serialize;
+
+use super::relayer_api::RelayerJsonRpcClient;
+
+/// Open orders found across a wallet's accounts, e.g. by
+/// `OrderWallet::list_open_orders`.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenOrders<O> {
+    /// Each open order with the index of the account holding it, by index.
+    pub orders: Vec<(u64, O)>,
+    /// Accounts whose order could not be queried, with the reason.
+    pub failed: Vec<(u64, String)>,
+}
+
+impl<O> Default for OpenOrders<O> {
+    fn default() -> Self {
+        Self {
+            orders: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+/// Sign a trader order query for `account_address`, hex-encoded as sent to the relayer.
+pub fn encode_trader_order_query(
+    secret_key: &RistrettoSecretKey,
+    account_address: &str,
+    status: OrderStatus,
+) -> String {
+    query_trader_order_zkos(
+        account_address.to_string(),
+        secret_key,
+        account_address.to_string(),
+        status,
+    )
+}
+
+/// Sign a lend order query for `account_address`, hex-encoded as sent to the relayer.
+pub fn encode_lend_order_query(
+    secret_key: &RistrettoSecretKey,
+    account_address: &str,
+    status: OrderStatus,
+) -> String {
+    query_lend_order_zkos(
+        account_address.to_string(),
+        secret_key,
+        account_address.to_string(),
+        status,
+    )
+}
+
+/// Build a signed `QueryTraderOrderZkos` for `account_address`.
+pub fn build_trader_order_query(
+    secret_key: &RistrettoSecretKey,
+    account_address: &str,
+    status: OrderStatus,
+) -> Result<QueryTraderOrderZkos, String> {
+    QueryTraderOrderZkos::decode_from_hex_string(encode_trader_order_query(
+        secret_key,
+        account_address,
+        status,
+    ))
+}
+
+/// Build a signed `QueryLendOrderZkos` for `account_address`.
+pub fn build_lend_order_query(
+    secret_key: &RistrettoSecretKey,
+    account_address: &str,
+    status: OrderStatus,
+) -> Result<QueryLendOrderZkos, String> {
+    QueryLendOrderZkos::decode_from_hex_string(encode_lend_order_query(
+        secret_key,
+        account_address,
+        status,
+    ))
+}
+
+/// Check that a trader order returned by the relayer belongs to `account_address`.
+pub fn verify_trader_order_owner(order: &TraderOrder, account_address: &str) -> Result<(), String> {
+    if order.account_id != account_address {
+        return Err(format!(
+            "Trader order {} belongs to {}, not {}",
+            order.uuid, order.account_id, account_address
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a lend order returned by the relayer belongs to `account_address`.
+pub fn verify_lend_order_owner(order: &LendOrder, account_address: &str) -> Result<(), String> {
+    if order.account_id != account_address {
+        return Err(format!(
+            "Lend order {} belongs to {}, not {}",
+            order.uuid, order.account_id, account_address
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a coin `output` (e.g. from a UTXO lookup) is owned by `secret_key`.
+pub fn verify_output_owner(secret_key: &RistrettoSecretKey, output: &Output) -> Result<(), String> {
+    let account = output.to_quisquis_account()?;
+    account
+        .verify_account_keypair(secret_key)
+        .map_err(|_| "Output is not owned by the given key".to_string())
+}
+
+/// Query the trader order of `account_address` and verify the response belongs to it.
+pub async fn query_trader_order(
+    client: &RelayerJsonRpcClient,
+    secret_key: &RistrettoSecretKey,
+    account_address: &str,
+    status: OrderStatus,
+) -> Result<TraderOrder, String> {
+    let query = build_trader_order_query(secret_key, account_address, status)?;
+    let order = client
+        .trader_order_info(query)
+        .await
+        .map_err(|e| e.to_string())?;
+    verify_trader_order_owner(&order, account_address)?;
+    Ok(order)
+}
+
+/// Query the lend order of `account_address` and verify the response belongs to it.
+pub async fn query_lend_order(
+    client: &RelayerJsonRpcClient,
+    secret_key: &RistrettoSecretKey,
+    account_address: &str,
+    status: OrderStatus,
+) -> Result<LendOrder, String> {
+    let query = build_lend_order_query(secret_key, account_address, status)?;
+    let order = client
+        .lend_order_info(query)
+        .await
+        .map_err(|e| e.to_string())?;
+    verify_lend_order_owner(&order, account_address)?;
+    Ok(order)
+}
+
+#[cfg(all(test, feature = "zk-accounts"))]
+mod tests {
+    use super::*;
+    use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+    use crate::zkos_accounts::{
+        encrypted_account::{EncryptedAccount, KeyManager},
+        zkaccount::ZkAccount,
+    };
+    use jsonrpc_core::{IoHandler, Params, Value};
+    use jsonrpc_http_server::ServerBuilder;
+    use secrecy::{ExposeSecret, SecretString};
+
+    const FIXTURE_SEED: &str = "order-query-fixture-seed";
+
+    fn fixture_account(index: u64) -> (RistrettoSecretKey, ZkAccount) {
+        let seed = SecretString::new(FIXTURE_SEED.to_string());
+        let key = KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes())
+            .derive_child_key(index);
+        (key, ZkAccount::from_seed(index, &seed, 1_000).unwrap())
+    }
+
+    fn trader_order_json(account_id: &str) -> Value {
+        TraderOrderBuilder::new()
+            .account_id(account_id)
+            .order_status(OrderStatus::PENDING)
+            .to_json()
+    }
+
+    #[tokio::test]
+    async fn test_standalone_trader_query_against_mock_relayer() {
+        let (secret_key, account) = fixture_account(1);
+        let address = account.account.clone();
+
+        let mut io = IoHandler::new();
+        let response = trader_order_json(&address);
+        io.add_sync_method("trader_order_info", move |params: Params| {
+            let params: Value = params.parse()?;
+            let data = params["data"].as_str().unwrap_or_default();
+            let bytes =
+                hex::decode(data).map_err(|_| jsonrpc_core::Error::invalid_params("hex"))?;
+            bincode::deserialize::<QueryTraderOrderZkos>(&bytes)
+                .map_err(|_| jsonrpc_core::Error::invalid_params("query"))?;
+            Ok(response.clone())
+        });
+        let server = ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let client = RelayerJsonRpcClient::new(&format!("http://{}", server.address())).unwrap();
+
+        let order = query_trader_order(&client, &secret_key, &address, OrderStatus::PENDING)
+            .await
+            .unwrap();
+        assert_eq!(order.account_id, address);
+        assert_eq!(order.order_status, OrderStatus::PENDING);
+
+        // A response for some other account is rejected.
+        let (other_key, other) = fixture_account(2);
+        let err = query_trader_order(&client, &other_key, &other.account, OrderStatus::PENDING)
+            .await
+            .unwrap_err();
+        assert!(err.contains("belongs to"));
+        server.close();
+    }
+
+    #[test]
+    fn test_output_ownership() {
+        let (secret_key, account) = fixture_account(1);
+        let (other_key, _) = fixture_account(2);
+        let output: Output = EncryptedAccount::from_hex_str(account.qq_address.clone())
+            .unwrap()
+            .into();
+        assert!(verify_output_owner(&secret_key, &output).is_ok());
+        assert!(verify_output_owner(&other_key, &output).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Submit-and-wait helpers for opening orders.
+//!
+//! [`OrderWallet::open_trader_order_and_wait`](super::order_wallet::OrderWallet::open_trader_order_and_wait)
+//! and [`OrderWallet::open_lend_order_and_wait`](super::order_wallet::OrderWallet::open_lend_order_and_wait)
+//! submit an order, then poll its status every [`ORDER_WAIT_POLL_INTERVAL`]
+//! until it fills, is cancelled or rejected, or the wait runs out. The wait
+//! runs on the wallet's [`Clock`](super::clock::Clock), so in simulated mode
+//! it advances the simulated clock instead of sleeping.
+
+use std::time::Duration;
+
+use crate::compat::relayer_types::{OrderStatus, OrderType, PositionType, TraderOrder};
+
+use super::events::OrderKind;
+use super::fees::FeeEstimate;
+use super::leverage::Leverage;
+use super::order_wallet::AccountIndex;
+use super::simulation::SimulatedOrder;
+
+/// Time between status polls while waiting for a fill.
+pub const ORDER_WAIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Arguments of [`open_trader_order`](super::order_wallet::OrderWallet::open_trader_order).
+#[derive(Debug, Clone)]
+pub struct TraderOrderParams {
+    pub index: AccountIndex,
+    pub order_type: OrderType,
+    pub order_side: PositionType,
+    pub entry_price: u64,
+    pub leverage: Leverage,
+}
+
+impl TraderOrderParams {
+    pub fn new(
+        index: AccountIndex,
+        order_type: OrderType,
+        order_side: PositionType,
+        entry_price: u64,
+        leverage: impl Into<Leverage>,
+    ) -> Self {
+        Self {
+            index,
+            order_type,
+            order_side,
+            entry_price,
+            leverage: leverage.into(),
+        }
+    }
+}
+
+/// A trader order as seen once it filled.
+#[derive(Debug, Clone)]
+pub enum TraderOrderSnapshot {
+    Relayer(TraderOrder),
+    Simulated(SimulatedOrder),
+}
+
+impl TraderOrderSnapshot {
+    pub fn order_status(&self) -> &OrderStatus {
+        match self {
+            TraderOrderSnapshot::Relayer(order) => &order.order_status,
+            TraderOrderSnapshot::Simulated(order) => &order.order_status,
+        }
+    }
+}
+
+/// Result of a successful submit-and-wait.
+#[derive(Debug, Clone)]
+pub struct FilledOrderReceipt<O> {
+    pub account_index: AccountIndex,
+    pub request_id: String,
+    pub order: O,
+    /// Time from submission to the poll that saw the fill.
+    pub waited: Duration,
+    /// Fees estimated at submission, for trader orders of a wallet built
+    /// [`with_fee_estimates`](super::order_wallet::OrderWallet::with_fee_estimates).
+    pub fee_estimate: Option<FeeEstimate>,
+}
+
+/// Result of [`OrderWallet::replace_trader_order`](super::order_wallet::OrderWallet::replace_trader_order).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceOrderReceipt {
+    pub account_index: AccountIndex,
+    /// Request ID of the cancel.
+    pub cancel_request_id: String,
+    /// Request ID of the replacement order.
+    pub request_id: String,
+    /// Whether the replacement reused the UTXO cached before the original
+    /// order instead of fetching it again.
+    pub reused_utxo: bool,
+}
+
+/// What [`OrderWallet::close_or_cancel_trader_order`](super::order_wallet::OrderWallet::close_or_cancel_trader_order)
+/// did, by the status the order was in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloseOutcome {
+    /// The order was still pending and has been cancelled.
+    Cancelled(String),
+    /// The position was filled and a close has been submitted.
+    Settled(String),
+    /// The position had already settled; the account was unlocked.
+    AlreadySettled,
+    /// The position had been liquidated; the account was unlocked.
+    Liquidated,
+}
+
+impl CloseOutcome {
+    /// Request ID of the cancel or close, if one was submitted.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            CloseOutcome::Cancelled(request_id) | CloseOutcome::Settled(request_id) => {
+                Some(request_id)
+            }
+            CloseOutcome::AlreadySettled | CloseOutcome::Liquidated => None,
+        }
+    }
+}
+
+/// Why [`OrderWallet::amend_trader_order`](super::order_wallet::OrderWallet::amend_trader_order)
+/// did not move the order.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AmendError {
+    /// The order filled before it could be amended; it is now a position.
+    #[error("order {request_id} on account {account_index} already filled")]
+    AlreadyFilled {
+        account_index: AccountIndex,
+        request_id: String,
+    },
+    /// The order is neither pending nor filled (e.g. cancelled or rejected).
+    #[error("order {request_id} on account {account_index} is not pending, status: {status}")]
+    NotPending {
+        account_index: AccountIndex,
+        request_id: String,
+        status: String,
+    },
+    #[error("amend failed: {0}")]
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OpenWaitError {
+    /// The order was never accepted; nothing to track.
+    #[error("order submission failed: {0}")]
+    SubmissionFailed(String),
+    /// Still pending when the wait ran out. Keep tracking `request_id`.
+    #[error("order {request_id} on account {account_index} still pending after {waited:?}")]
+    TimedOutStillPending {
+        account_index: AccountIndex,
+        request_id: String,
+        waited: Duration,
+    },
+    /// The relayer or chain refused the order; the account has been unlocked.
+    #[error(
+        "order {request_id} on account {account_index} rejected with status {status}: {reason}"
+    )]
+    Rejected {
+        account_index: AccountIndex,
+        request_id: String,
+        status: String,
+        reason: String,
+    },
+    #[error("order {request_id} on account {account_index} was cancelled")]
+    Cancelled {
+        account_index: AccountIndex,
+        request_id: String,
+    },
+    /// The order filled but querying it afterwards failed.
+    #[error(
+        "order {request_id} on account {account_index} filled, but querying it failed: {error}"
+    )]
+    SnapshotUnavailable {
+        account_index: AccountIndex,
+        request_id: String,
+        error: String,
+    },
+}
+
+/// Where an opened order stands while waiting for its fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OpenProgress {
+    Pending,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl OpenProgress {
+    /// Classify the status of a newly opened `order`. An order that already
+    /// moved past its fill (settled, liquidated, lent) counts as filled.
+    pub(crate) fn of(status: &OrderStatus, order: OrderKind) -> Self {
+        match status {
+            OrderStatus::PENDING => OpenProgress::Pending,
+            OrderStatus::FILLED | OrderStatus::SETTLED => OpenProgress::Filled,
+            OrderStatus::LIQUIDATE if order == OrderKind::Trader => OpenProgress::Filled,
+            OrderStatus::LENDED if order == OrderKind::Lend => OpenProgress::Filled,
+            OrderStatus::CANCELLED => OpenProgress::Cancelled,
+            _ => OpenProgress::Rejected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_progress_by_order_kind() {
+        assert_eq!(
+            OpenProgress::of(&OrderStatus::PENDING, OrderKind::Trader),
+            OpenProgress::Pending
+        );
+        assert_eq!(
+            OpenProgress::of(&OrderStatus::LIQUIDATE, OrderKind::Trader),
+            OpenProgress::Filled
+        );
+        assert_eq!(
+            OpenProgress::of(&OrderStatus::LIQUIDATE, OrderKind::Lend),
+            OpenProgress::Rejected
+        );
+        assert_eq!(
+            OpenProgress::of(&OrderStatus::LENDED, OrderKind::Lend),
+            OpenProgress::Filled
+        );
+        assert_eq!(
+            OpenProgress::of(&OrderStatus::CANCELLED, OrderKind::Lend),
+            OpenProgress::Cancelled
+        );
+    }
+}
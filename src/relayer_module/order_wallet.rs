@@ -6,56 +6,139 @@
 //! - Open/close/cancel trader and lend orders via the relayer
 //! - Query order states with retry helpers
 //! - Optionally persist wallet, ZK accounts, UTXOs, and request IDs in a database
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use std::sync::Arc;
 
 use crate::{
-    config::{EndpointConfig, RelayerEndPointConfig},
-    error::{Result as WalletResult, WalletError},
+    compat::{
+        self, ChainBroadcaster, SdkChainBroadcaster, SdkTransferBuilder, TransferBuilder,
+    },
+    config::{
+        Config, ConfigDrift, ConfigFingerprint, EndpointConfig, ProgramLoading,
+        RelayerEndPointConfig, RiskLimits,
+    },
+    error::{OrderWalletError, OrderWalletResult, Result as WalletResult, WalletError},
+    log_privacy::{LogPrivacy, LoggedAmount},
     relayer_module::{
-        self, check_tx_status, fetch_removed_utxo_details_with_retry,
-        fetch_tx_hash_with_account_address_retry, fetch_tx_hash_with_once,
-        fetch_tx_hash_with_retry, fetch_utxo_details_with_once, fetch_utxo_details_with_retry,
-        nonce_manager::NonceManager,
-        relayer_api::RelayerJsonRpcClient,
+        self,
+        account_state::{AccountLocks, AccountMap, Guarded, ZkAccountStore},
+        account_sync::{
+            fetch_minted_accounts, memo_order, plan_fixes, probe_chain_utxo, restore_from_chain,
+            AccountFix, AccountSyncReport, ChainUtxo, ChainUtxoState, RecoveredAccount,
+            ResyncSummary, RESYNC_CONCURRENCY,
+        },
+        activity::{ActivityCategory, ActivityHistogram, ActivityTracker, DEFAULT_RETENTION},
+        address_book::{AddressBook, AddressBookEntry, AddressBookError, AddressKind},
+        capabilities::{Capability, RelayerCapabilities},
+        chain_tx::{ChainTxRegistry, ChainTxSerializer},
+        check_tx_status,
+        clock::{system_clock, Clock, ManualClock},
+        diagnostics::DiagnosticSnapshot,
+        events::{EventBus, OrderKind, WalletEvent},
+        execution_report::{ExecutionQuality, ExecutionReport},
+        fees::FeeEstimate,
+        fetch_removed_utxo_details_with_policy,
+        fetch_tx_hash_with_account_address_retry, fetch_tx_hash_with_clock,
+        fetch_tx_hash_with_once,
+        idempotency::{PendingSubmission, ReconciledSubmission, Reconciliation},
+        is_unreachable_error,
+        leverage::{Leverage, LeverageLimits},
+        nonce_manager::{NonceManager, SEQUENCE_MISMATCH_CODE},
+        order_query::{
+            encode_lend_order_query, encode_trader_order_query, verify_lend_order_owner,
+            verify_trader_order_owner, OpenOrders,
+        },
+        order_wait::{
+            AmendError, CloseOutcome, FilledOrderReceipt, OpenProgress, OpenWaitError,
+            ReplaceOrderReceipt, TraderOrderParams, TraderOrderSnapshot, ORDER_WAIT_POLL_INTERVAL,
+        },
+        order_watcher::{self, OrderWatcher, OrderWatcherHandle, Reconcile},
+        pending_operations::{
+            MultiTransferOutcome, OperationInputs, OperationStep, PendingOperation,
+            PendingOperationKind, PendingOperationStatus,
+        },
+        program_cache::ProgramCache,
+        receiver_check::{
+            network_byte, validate_address, verify_ownership_proof, ReceiverCheckError,
+            TransferOptions,
+        },
+        relayer_api::{RelayerApi, RelayerJsonRpcClient},
         relayer_order::{
-            cancel_trader_order, cancel_trader_order_sltp, close_lend_order,
-            close_trader_order_internal, close_trader_order_sltp_internal, create_lend_order,
-            create_trader_order,
+            build_lend_order_with_input, build_trader_order_with_input,
+            cancel_trader_order_audited, cancel_trader_order_sltp_audited,
+            close_lend_order_audited, close_trader_order_internal_audited,
+            close_trader_order_partial_audited, close_trader_order_sltp_internal_audited,
+        },
+        relayer_program::{RelayerProgram, RelayerProgramError},
+        relayer_types::{LendPoolInfo, MarketStats, TransactionHashArgs},
+        risk::{run_bounded, LendExposure, RiskReport, TraderExposure, RISK_QUERY_CONCURRENCY},
+        statement::{statement_rows, write_statement, StatementFormat, StatementSummary},
+        status::{EndpointStatus, StatusSnapshot},
+        shutdown::{
+            run_step, ShutdownOptions, ShutdownRegistry, ShutdownReport, StepStatus,
+            DEADLINE_EXCEEDED,
+        },
+        signing_audit::{SigningAudit, SigningAuditEntry, SigningPurpose},
+        simulation::{
+            dry_run_request_id, dry_run_tx_hash, is_dry_run_id, ExecutionMode, SimulatedExchange,
+            SimulatedOrder,
+        },
+        trading_limits::{TradingLimits, DEFAULT_LIMITS_REFRESH},
+        transaction_history::{
+            merge_trade_history, AmountDiscrepancy, OrderRecord, OrderRecordKind, TradeHistoryEntry,
+        },
+        utxo_cache::{
+            is_stale_input_error, AccountStateKey, ChainUtxoFetcher, UtxoCache, UtxoFetcher,
+            UtxoLookup, UtxoStamp,
         },
     },
-    wallet::Wallet,
+    security::seed_storage::{OsKeystore, SeedKeystore, SeedStorage, SeedVault},
+    wallet::{check_balance, Wallet},
     zkos_accounts::{
-        encrypted_account::{KeyManager, DERIVATION_MESSAGE},
-        zkaccount::{ZkAccount, ZkAccountDB},
+        encrypted_account::{EncryptedAccount, KeyManager, DERIVATION_MESSAGE},
+        zkaccount::{committed_amount, StoredError, ZkAccount, ZkAccountDB, ZkAccountError},
     },
 };
 
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use crate::database::{connection::run_migrations_once, DatabaseManager, WalletList};
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::relayer_module::transaction_history::OrderSnapshot;
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+use crate::relayer_module::wallet_lock::{PassphraseCache, WalletLocked};
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
 use crate::security::SecurePassword;
-use log::{debug, error, info};
-use relayer_module::utils::{build_and_sign_msg_mint_burn_trading_btc, send_tx_to_chain, TxResult};
+#[cfg(feature = "health-endpoint")]
+use crate::relayer_module::health::{self, HealthRegistry, HealthServerHandle};
+#[cfg(feature = "webhooks")]
+use crate::relayer_module::webhooks::{EventFilter, WebhookConfig, WebhookDispatcher, WebhookStats};
+use crate::MsgMintBurnTradingBtc;
+use chrono::{DateTime, Utc};
+use jsonrpsee::core::client::Error as RpcError;
+use log::{debug, error, info, warn};
+use crate::nyks_rpc::rpcclient::fee_bump::{
+    broadcast_signed_tx, query_tx_status, submit_with_fee_bump, FeeBumpPolicy,
+};
+use relayer_module::utils::{
+    build_and_sign_msg_mint_burn_trading_btc, estimate_mint_burn_fee, mint_burn_trading_btc_msg,
+    send_tx_to_chain, sign_msgs_mint_burn_trading_btc, sign_msgs_mint_burn_trading_btc_estimated,
+    TxResult,
+};
 #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-use secrecy::{ExposeSecret, SecretString};
+use secrecy::ExposeSecret;
+use secrecy::SecretString;
 use serde::Serialize;
-use twilight_client_sdk::{
-    quisquislib::RistrettoSecretKey,
-    relayer::{query_lend_order_zkos, query_trader_order_zkos},
+use tokio::sync::broadcast;
+use crate::compat::{
+    quisquislib::{Account, RistrettoSecretKey},
     relayer_rpcclient::method::UtxoDetailResponse,
     relayer_types::{
         LendOrder, OrderStatus, OrderType, PositionType, QueryLendOrderZkos, QueryTraderOrderZkos,
-        SlTpOrderCancel, TXType, TraderOrder,
-    },
-    transaction::{Receiver, Sender},
-    transfer::{
-        create_burn_message_transaction,
-        create_private_transfer_transaction_single_source_multiple_recievers,
-        create_private_transfer_tx_single,
+        SlTpOrderCancel, TXType, TraderOrder, TxHash,
     },
-    zkvm::IOType,
+    transaction::{Receiver, Sender, Transaction},
+    zkvm::{IOType, Input},
 };
 
 /// One-based index of a ZkOS account tracked by `ZkAccountDB`.
@@ -64,60 +147,224 @@ pub type Balance = u64;
 /// Relayer request ID string returned after submitting an order.
 pub type RequestId = String;
 pub type AccountBalance = (AccountIndex, Balance);
+
+/// Most mints [`OrderWallet::funding_to_trading_multiple`] puts in one
+/// transaction; more accounts are funded in several.
+pub const MAX_MINTS_PER_TX: usize = 8;
+/// UTXO cache stamp origin for an input carried over a cancel by `replace_trader_order`.
+const REPLACE_ORIGIN: &str = "replace_trader_order";
+/// Funding rates requested per `historical_funding_rate` page.
+const FUNDING_HISTORY_PAGE_SIZE: i64 = 500;
+/// Settled orders shown in the `recent_settlements` section of a status snapshot.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+const STATUS_RECENT_SETTLEMENTS: usize = 5;
+/// How often `serve_health` polls the relayer and DB in the background.
+#[cfg(feature = "health-endpoint")]
+const HEALTH_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
 #[derive(Debug, Clone, Serialize)]
 /// High-level wallet orchestrator for relayer trading/lending using ZkOS accounts.
 pub struct OrderWallet {
     pub wallet: Wallet,
-    pub zk_accounts: ZkAccountDB,
+    pub zk_accounts: ZkAccountStore,
     pub chain_id: String,
     #[serde(skip)]
-    seed: SecretString,
-    pub utxo_details: HashMap<AccountIndex, UtxoDetailResponse>,
-    pub request_ids: HashMap<AccountIndex, RequestId>,
+    seed: SeedVault,
+    pub utxo_details: AccountMap<UtxoDetailResponse>,
+    pub request_ids: AccountMap<RequestId>,
+    /// Idempotency key each tracked request id was submitted with.
+    #[serde(skip)]
+    idempotency_keys: AccountMap<String>,
+    /// Submits whose outcome is not known yet; see [`idempotency`](super::idempotency).
+    #[serde(skip)]
+    pending_submissions: AccountMap<PendingSubmission>,
     #[serde(skip)]
     pub relayer_api_client: RelayerJsonRpcClient,
+    /// What orders are submitted, settled, cancelled and queried through;
+    /// `relayer_api_client` unless replaced with [`with_relayer`](Self::with_relayer).
+    #[serde(skip)]
+    relayer: Arc<dyn RelayerApi>,
     pub relayer_endpoint_config: RelayerEndPointConfig,
     #[serde(skip)]
     pub nonce_manager: Arc<NonceManager>,
+    #[serde(skip)]
+    chain_tx: Option<Arc<ChainTxSerializer>>,
+    #[serde(skip)]
+    fee_bump: Option<FeeBumpPolicy>,
+    #[serde(skip)]
+    clock: Arc<dyn Clock>,
+    #[serde(skip)]
+    pending_ops: Guarded<HashMap<String, PendingOperation>>,
+    #[serde(skip)]
+    program_cache: ProgramCache,
+    #[serde(skip)]
+    signing_audit: SigningAudit,
+    #[serde(skip)]
+    shutdown: ShutdownRegistry,
+    #[serde(skip)]
+    utxo_fetcher: Arc<dyn UtxoFetcher>,
+    #[serde(skip)]
+    utxo_cache: Guarded<UtxoCache>,
+    /// Serializes order operations per account; see [`account_state`](super::account_state).
+    #[serde(skip)]
+    account_locks: AccountLocks,
+    #[serde(skip)]
+    transfer_builder: Arc<dyn TransferBuilder>,
+    #[serde(skip)]
+    chain_broadcaster: Arc<dyn ChainBroadcaster>,
+    #[serde(skip)]
+    activity: ActivityTracker,
+    #[serde(skip)]
+    simulation: Guarded<Option<SimulatedExchange>>,
+    /// [`ExecutionMode::DryRun`]: build orders and mints but broadcast nothing.
+    #[serde(skip)]
+    dry_run: bool,
+    /// Foreign ZkOS addresses that no longer need first-transfer confirmation.
+    #[serde(skip)]
+    known_receivers: HashSet<String>,
+    #[serde(skip)]
+    address_book: AddressBook,
+    /// Refuse sends to raw addresses not in `address_book`.
+    #[serde(skip)]
+    strict_address_book: bool,
+    /// Where `address_book` is kept when there is no database.
+    #[serde(skip)]
+    address_book_file: Option<std::path::PathBuf>,
+    #[serde(skip)]
+    amount_discrepancies: Guarded<Vec<AmountDiscrepancy>>,
+    /// Every order request made per account, oldest first.
+    #[serde(skip)]
+    order_records: AccountMap<Vec<OrderRecord>>,
+    /// Attach a [`FeeEstimate`] to each trader order opened.
+    #[serde(skip)]
+    fee_estimates_enabled: bool,
+    #[serde(skip)]
+    fee_estimates: AccountMap<FeeEstimate>,
+    /// Execution reports of filled trader opens, per account.
+    #[serde(skip)]
+    execution_reports: AccountMap<Vec<ExecutionReport>>,
+    /// Relayer trading parameters new trader orders are checked against.
+    #[serde(skip)]
+    trading_limits: Guarded<TradingLimits>,
+    #[serde(skip)]
+    trading_limits_refresh: std::time::Duration,
+    /// Local limits new trader orders are checked against.
+    #[serde(skip)]
+    risk_limits: RiskLimits,
+    /// Most recent [`OrderWallet::risk_report`], included in diagnostic snapshots.
+    #[serde(skip)]
+    last_risk_report: Option<RiskReport>,
+    /// Last recorded operation per account, used to pick accounts to archive.
+    #[serde(skip)]
+    account_activity: AccountMap<DateTime<Utc>>,
+    /// Differences from the configuration the wallet was saved under, found by
+    /// [`OrderWallet::load_from_db`]. Empty otherwise.
+    #[serde(skip)]
+    config_drift: ConfigDrift,
+    #[cfg(feature = "health-endpoint")]
+    #[serde(skip)]
+    health: Option<HealthRegistry>,
+    #[serde(skip)]
+    events: EventBus,
+    #[cfg(feature = "webhooks")]
+    #[serde(skip)]
+    webhooks: WebhookDispatcher,
     #[cfg(any(feature = "sqlite", feature = "postgresql"))]
     #[serde(skip)]
     db_manager: Option<DatabaseManager>,
     #[cfg(any(feature = "sqlite", feature = "postgresql"))]
     #[serde(skip)]
-    wallet_password: Option<SecretString>,
+    passphrase: PassphraseCache,
 }
 
+// Order operations on different accounts run concurrently through an
+// `Arc<OrderWallet>`; keep every field shareable across tasks.
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+    let _ = assert_send_sync::<OrderWallet>;
+};
+
 impl OrderWallet {
     /// Internal constructor helper that wires endpoint configs and the relayer client,
     /// derives the ZkOS seed from the wallet, and initializes runtime caches.
+    /// Fails on a config that does not pass [`EndpointConfig::validate`], and
+    /// loads the relayer program unless `endpoint_config.program_loading` is lazy.
     fn init(
         wallet: Wallet,
         zk_accounts: ZkAccountDB,
         endpoint_config: EndpointConfig,
     ) -> WalletResult<Self> {
+        endpoint_config.validate()?;
         let relayer_endpoint_config = endpoint_config.to_relayer_endpoint_config();
+        let activity = ActivityTracker::new(system_clock(), DEFAULT_RETENTION);
+        let retry_policy = relayer_endpoint_config.retry_policy;
         let relayer_api_client =
-            RelayerJsonRpcClient::new(&relayer_endpoint_config.relayer_api_endpoint)
-                .map_err(|e| WalletError::RelayerClient(e.to_string()))?;
+            RelayerJsonRpcClient::from_endpoint_config(&relayer_endpoint_config)
+                .map_err(|e| WalletError::RelayerClient(e.to_string()))?
+                .with_activity(activity.clone());
         let seed = wallet
             .get_zk_account_seed(&endpoint_config.chain_id, DERIVATION_MESSAGE)
             .map_err(|e| WalletError::ZkAccountSeedNotFound(e.to_string()))?;
+        // Shared with the wallet, so its own transactions and the mint/burns
+        // signed here count on the same sequence.
+        let nonce_manager = wallet.nonce_manager.clone();
 
-        Ok(Self {
+        let order_wallet = Self {
             wallet,
-            zk_accounts,
+            zk_accounts: ZkAccountStore::new(zk_accounts),
             chain_id: endpoint_config.chain_id,
-            seed: seed,
-            utxo_details: HashMap::new(),
-            request_ids: HashMap::new(),
+            seed: SeedVault::in_memory(seed),
+            utxo_details: AccountMap::new(),
+            request_ids: AccountMap::new(),
+            idempotency_keys: AccountMap::new(),
+            pending_submissions: AccountMap::new(),
+            relayer: Arc::new(relayer_api_client.clone()),
             relayer_api_client,
             relayer_endpoint_config,
-            nonce_manager: Arc::new(NonceManager::new()),
+            nonce_manager,
+            chain_tx: None,
+            fee_bump: None,
+            clock: system_clock(),
+            pending_ops: Guarded::default(),
+            program_cache: ProgramCache::new(),
+            signing_audit: SigningAudit::disabled(),
+            shutdown: ShutdownRegistry::default(),
+            utxo_fetcher: Arc::new(ChainUtxoFetcher::with_policy(retry_policy)),
+            utxo_cache: Guarded::default(),
+            account_locks: AccountLocks::default(),
+            transfer_builder: Arc::new(SdkTransferBuilder),
+            chain_broadcaster: Arc::new(SdkChainBroadcaster),
+            activity,
+            simulation: Guarded::default(),
+            dry_run: false,
+            known_receivers: HashSet::new(),
+            address_book: AddressBook::new(),
+            strict_address_book: false,
+            address_book_file: None,
+            amount_discrepancies: Guarded::default(),
+            order_records: AccountMap::new(),
+            fee_estimates_enabled: false,
+            trading_limits: Guarded::new(TradingLimits::fallback()),
+            trading_limits_refresh: DEFAULT_LIMITS_REFRESH,
+            risk_limits: RiskLimits::from_env(),
+            fee_estimates: AccountMap::new(),
+            execution_reports: AccountMap::new(),
+            last_risk_report: None,
+            account_activity: AccountMap::new(),
+            config_drift: ConfigDrift::default(),
+            #[cfg(feature = "health-endpoint")]
+            health: None,
+            events: EventBus::default(),
+            #[cfg(feature = "webhooks")]
+            webhooks: WebhookDispatcher::default(),
             #[cfg(any(feature = "sqlite", feature = "postgresql"))]
             db_manager: None,
             #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-            wallet_password: None,
-        })
+            passphrase: PassphraseCache::default(),
+        };
+        if endpoint_config.program_loading == ProgramLoading::Eager {
+            order_wallet.relayer_program()?;
+        }
+        Ok(order_wallet)
     }
 
     /// Create a new `OrderWallet` with a freshly generated base `Wallet`.
@@ -125,6 +372,8 @@ impl OrderWallet {
     /// The base wallet generates a new mnemonic and prints it once to the TTY.
     pub fn new(endpoint_config: Option<EndpointConfig>) -> WalletResult<Self> {
         let endpoint_config = endpoint_config.unwrap_or_default();
+        // Before the mnemonic is generated and shown.
+        endpoint_config.validate()?;
         let wallet_endpoint_config = endpoint_config.to_wallet_endpoint_config();
         let wallet = Wallet::new(Some(wallet_endpoint_config))
             .map_err(|e| WalletError::WalletCreation(e.to_string()))?;
@@ -145,6 +394,57 @@ impl OrderWallet {
         Self::init(wallet, zk_accounts, endpoint_config).map_err(|e| e.to_string())
     }
 
+    /// [`import_from_mnemonic`](OrderWallet::import_from_mnemonic) with a BIP-39
+    /// passphrase; see [`Wallet::from_mnemonic_with_passphrase`]. The
+    /// passphrase is not kept or persisted.
+    pub fn import_from_mnemonic_with_passphrase(
+        mnemonic: &str,
+        passphrase: SecretString,
+        endpoint_config: Option<EndpointConfig>,
+    ) -> Result<Self, String> {
+        let endpoint_config = endpoint_config.unwrap_or_default();
+        let wallet_endpoint_config = endpoint_config.to_wallet_endpoint_config();
+        let wallet = Wallet::from_mnemonic_with_passphrase(
+            mnemonic,
+            passphrase,
+            Some(wallet_endpoint_config),
+        )
+        .map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+        Self::init(wallet, zk_accounts, endpoint_config).map_err(|e| e.to_string())
+    }
+
+    /// [`import_from_mnemonic`](OrderWallet::import_from_mnemonic) with the ZkOS
+    /// seed kept in `storage`.
+    pub fn import_from_mnemonic_with_storage(
+        mnemonic: &str,
+        endpoint_config: Option<EndpointConfig>,
+        storage: SeedStorage,
+    ) -> Result<Self, String> {
+        Self::import_from_mnemonic(mnemonic, endpoint_config)?.with_seed_storage(storage)
+    }
+
+    /// Import an `OrderWallet` from a hex-encoded private key, for deployments
+    /// that inject the key instead of a mnemonic. Nothing is read from or
+    /// printed to the TTY. When `btc_address` is `None` the BTC SegWit address
+    /// is derived from the same key. The ZkOS seed is derived as in
+    /// [`import_from_mnemonic`](OrderWallet::import_from_mnemonic).
+    pub fn import_from_private_key(
+        private_key_hex: &str,
+        btc_address: Option<&str>,
+        endpoint_config: Option<EndpointConfig>,
+    ) -> WalletResult<Self> {
+        let endpoint_config = endpoint_config.unwrap_or_default();
+        let wallet_endpoint_config = endpoint_config.to_wallet_endpoint_config();
+        let wallet = Wallet::from_private_key_hex(
+            private_key_hex,
+            btc_address,
+            Some(wallet_endpoint_config),
+        )?;
+        let zk_accounts = ZkAccountDB::new();
+        Self::init(wallet, zk_accounts, endpoint_config)
+    }
+
     // deafault feature is sqlite, if postgresql is enabled, then use postgresql
     // mnemonic will be securely printed for the first time and then deleted from memory and will not be stored in the database or any other storage
     /// Enable database persistence. Returns a cloned instance with DB enabled.
@@ -173,12 +473,47 @@ impl OrderWallet {
 
     /// Load OrderWallet from DB by `wallet_id`. If `password` is None, it will
     /// resolve via env/prompt. Also loads Zk accounts, UTXO details, and request IDs.
+    ///
+    /// Returns the [`ConfigDrift`] since the wallet was saved alongside it. Drift
+    /// is logged and also kept in [`config_drift`](OrderWallet::config_drift);
+    /// use [`load_from_db_checked`](OrderWallet::load_from_db_checked) to
+    /// refuse loading on drift.
     #[cfg(any(feature = "sqlite", feature = "postgresql"))]
     pub fn load_from_db(
         wallet_id: String,
         password: Option<SecretString>,
         db_url: Option<String>,
-    ) -> Result<OrderWallet, String> {
+    ) -> Result<(OrderWallet, ConfigDrift), String> {
+        Self::load_from_db_checked(wallet_id, password, db_url, false)
+    }
+
+    /// [`load_from_db`](OrderWallet::load_from_db) with the ZkOS seed kept in
+    /// `storage`. The seed stays encrypted in the database either way.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn load_from_db_with_storage(
+        wallet_id: String,
+        password: Option<SecretString>,
+        db_url: Option<String>,
+        storage: SeedStorage,
+    ) -> Result<(OrderWallet, ConfigDrift), String> {
+        let (wallet, drift) = Self::load_from_db(wallet_id, password, db_url)?;
+        Ok((wallet.with_seed_storage(storage)?, drift))
+    }
+
+    /// [`load_from_db`](OrderWallet::load_from_db), checking the
+    /// [`ConfigDrift`] between the configuration stored with the wallet and the
+    /// current one. With `strict_config`, any drift other than the crate
+    /// version is an error and the wallet is not loaded.
+    ///
+    /// The stored fingerprint is replaced with the current one the next time
+    /// the wallet is saved.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn load_from_db_checked(
+        wallet_id: String,
+        password: Option<SecretString>,
+        db_url: Option<String>,
+        strict_config: bool,
+    ) -> Result<(OrderWallet, ConfigDrift), String> {
         let pool = crate::database::connection::init_pool(db_url)?;
         run_migrations_once(&pool)?;
 
@@ -199,11 +534,14 @@ impl OrderWallet {
             }
         };
         wallet.chain_config = EndpointConfig::default().to_wallet_endpoint_config();
-        // Load zk accounts
+        // Load zk accounts, encrypting rows saved before their secrets were
+        db_manager.unlock_zk_accounts(&secure_password)?;
         let zk_accounts = db_manager.load_all_zk_accounts()?;
+        let archived = db_manager.load_archived_zk_accounts()?;
         let max_account_index = db_manager.get_max_account_index()?;
         // index is the *next* account index to use, so it must be max + 1
-        let next_index = if zk_accounts.is_empty() {
+        // (archived accounts keep their rows, so they count too)
+        let next_index = if zk_accounts.is_empty() && archived.is_empty() {
             0
         } else {
             max_account_index + 1
@@ -211,16 +549,43 @@ impl OrderWallet {
         let zk_accounts_db = ZkAccountDB {
             accounts: zk_accounts,
             index: next_index,
+            archived,
         };
 
+        let saved_fingerprint = db_manager.load_config_fingerprint()?;
+
         let mut order_wallet = OrderWallet::init(wallet, zk_accounts_db, EndpointConfig::default())
             .map_err(|e| e.to_string())?;
-        order_wallet.wallet_password = Some(secure_password);
+        // Checked before the DB is attached so a refused load does not
+        // overwrite the stored fingerprint on drop.
+        let drift = check_config_drift(
+            db_manager.get_wallet_id(),
+            saved_fingerprint.as_ref(),
+            &order_wallet.config_fingerprint(),
+            strict_config,
+        )?;
+        order_wallet.config_drift = drift.clone();
+        order_wallet
+            .passphrase
+            .unlock(secure_password, order_wallet.clock.now());
         order_wallet.db_manager = Some(db_manager);
         order_wallet.load_all_utxo_details_from_db()?;
         order_wallet.load_all_request_ids_from_db()?;
+        order_wallet.load_order_records_from_db()?;
+        order_wallet.load_pending_operations_from_db()?;
+        order_wallet.load_pending_submissions_from_db()?;
+        if let Some(ref db_manager) = order_wallet.db_manager {
+            order_wallet
+                .account_activity
+                .replace(db_manager.load_zk_account_update_times()?);
+            order_wallet.address_book = AddressBook::from_entries(db_manager.load_address_book()?);
+            let since = order_wallet.activity.cutoff(order_wallet.clock.now());
+            order_wallet
+                .activity
+                .merge(db_manager.load_activity_counts(since)?);
+        }
 
-        Ok(order_wallet)
+        Ok((order_wallet, drift))
     }
 
     #[cfg(any(feature = "sqlite", feature = "postgresql"))]
@@ -231,6 +596,31 @@ impl OrderWallet {
         Ok(wallet_list)
     }
 
+    /// Delete a stored wallet and all its rows after checking that
+    /// `password` decrypts it. See [`DatabaseManager::delete_wallet`].
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn delete_wallet_from_db(
+        wallet_id: &str,
+        password: &SecretString,
+        db_url: Option<String>,
+    ) -> Result<(), String> {
+        let pool = crate::database::connection::init_pool(db_url)?;
+        run_migrations_once(&pool)?;
+        DatabaseManager::delete_wallet(&pool, wallet_id, password)
+    }
+
+    /// Rename a stored wallet; fails if `new_id` is taken.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn rename_wallet_in_db(
+        old_id: &str,
+        new_id: &str,
+        db_url: Option<String>,
+    ) -> Result<(), String> {
+        let pool = crate::database::connection::init_pool(db_url)?;
+        run_migrations_once(&pool)?;
+        DatabaseManager::rename_wallet(&pool, old_id, new_id)
+    }
+
     #[cfg(any(feature = "sqlite", feature = "postgresql"))]
     pub fn get_wallet_id_from_db(wallet_id: &str, db_url: Option<String>) -> Result<bool, String> {
         let pool = crate::database::connection::init_pool(db_url)?;
@@ -239,2570 +629,14278 @@ impl OrderWallet {
     }
 
     /// Derive a child secret key for the given account index from the ZkOS seed.
-    pub fn get_secret_key(&self, index: AccountIndex) -> RistrettoSecretKey {
-        let key_manager = KeyManager::from_cosmos_signature(self.seed.expose_secret().as_bytes());
-        key_manager.derive_child_key(index)
+    /// With [`SeedStorage::OsKeystore`] the seed is fetched for this call only.
+    pub fn get_secret_key(&self, index: AccountIndex) -> Result<RistrettoSecretKey, String> {
+        self.seed.with_seed(|seed| {
+            KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes())
+                .derive_child_key(index)
+        })
     }
     /// Get last stored request ID for the account; errors if none exists.
-    pub fn request_id(&self, index: AccountIndex) -> Result<&str, String> {
+    pub fn request_id(&self, index: AccountIndex) -> Result<RequestId, String> {
         self.request_ids
             .get(&index)
-            .map(|s| s.as_str())
             .ok_or(format!("Request ID not found for account index: {}", index))
     }
 
-    /// Get a reference to the database manager, if DB persistence is enabled.
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn get_db_manager(&self) -> Option<&DatabaseManager> {
-        self.db_manager.as_ref()
+    /// Every order request (open, close, cancel) made from the account,
+    /// oldest first. Empty if the account has none.
+    pub fn order_history(&self, index: AccountIndex) -> Vec<OrderRecord> {
+        self.order_records.get(&index).unwrap_or_default()
     }
 
-    /// Get a reference to the wallet password, if available.
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn get_wallet_password(&self) -> Option<&SecretString> {
-        self.wallet_password.as_ref()
+    /// Request ID of the account's latest `kind` request, for a settlement
+    /// the relayer reports without one.
+    fn latest_request_id(&self, index: AccountIndex, kind: OrderRecordKind) -> Option<RequestId> {
+        self.order_history(index)
+            .into_iter()
+            .rev()
+            .find(|record| record.kind == kind)
+            .map(|record| record.request_id)
     }
 
-    /// Ensure the account exists on-chain, has IOType::Coin, and a non-zero balance.
-    pub fn ensure_coin_onchain(&self, index: AccountIndex) -> Result<(), String> {
-        let a = self
-            .zk_accounts
-            .get_account(&index)
-            .map_err(|e| e.to_string())?;
-        if a.io_type != IOType::Coin {
-            return Err(format!("Account is locked, io type: {:?}", a.io_type));
-        }
-        if !a.on_chain || a.balance == 0 {
-            return Err(format!("Account does not exist on chain or has no balance"));
-        }
-        Ok(())
+    /// [`order_history`](Self::order_history) of every account.
+    pub fn all_order_history(&self) -> HashMap<AccountIndex, Vec<OrderRecord>> {
+        self.order_records.snapshot()
     }
-    /// Ensure the account exists on-chain, has IOType::Coin, and a non-zero balance.
-    pub fn ensure_zk_account_onchain(&self, a: &ZkAccount) -> Result<(), String> {
-        if a.io_type != IOType::Coin {
-            return Err(format!("Account is locked, io type: {:?}", a.io_type));
-        }
-        if !a.on_chain || a.balance == 0 {
-            return Err(format!("Account does not exist on chain or has no balance"));
-        }
-        Ok(())
+
+    /// Estimate the fees of each trader order opened from now on, read back
+    /// with [`fee_estimate`](Self::fee_estimate). Costs one `get_fee_rate`
+    /// call per order. Off by default; simulated orders get no estimate.
+    pub fn with_fee_estimates(mut self, enabled: bool) -> Self {
+        self.fee_estimates_enabled = enabled;
+        self
     }
 
-    /// Sync the nonce manager from the on-chain account state.
-    /// Call this before a batch of transactions, or periodically to
-    /// re-anchor the local sequence counter.
-    pub async fn sync_nonce(&self) -> Result<(), String> {
-        self.nonce_manager
-            .sync_from_chain(
-                &self.wallet.chain_config.lcd_endpoint,
-                &self.wallet.twilightaddress,
-            )
-            .await
+    /// How long fetched [`trading_limits`](Self::trading_limits) are used
+    /// before the next trader order fetches them again. Defaults to
+    /// [`DEFAULT_LIMITS_REFRESH`].
+    pub fn with_trading_limits_refresh(mut self, refresh: std::time::Duration) -> Self {
+        self.trading_limits_refresh = refresh;
+        self
     }
 
-    // -------------------------
-    // Internal helpers
-    // -------------------------
+    /// Local leverage, position value and open order limits checked before
+    /// each new trader order. Defaults to the `NYKS_RISK_*` variables.
+    pub fn with_risk_limits(mut self, risk_limits: RiskLimits) -> Self {
+        self.risk_limits = risk_limits;
+        self
+    }
 
-    /// Sync an account's on-chain UTXO state. Call this to complete a deferred
-    /// sync after a `--no-wait` open or close operation.
-    pub async fn sync_account_state(&mut self, index: AccountIndex) -> Result<(), String> {
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let io_type = self.zk_accounts.get_account(&index)?.io_type;
-        let utxo_detail = fetch_utxo_details_with_retry(account_address, io_type).await?;
-        self.utxo_details.insert(index, utxo_detail.clone());
+    /// Apply the wallet settings of a config file: its `[risk]` limits,
+    /// overridden by the environment. Pass
+    /// [`config.endpoint_config()`](Config::endpoint_config) to the
+    /// constructor for the rest.
+    pub fn with_config(self, config: &Config) -> Self {
+        self.with_risk_limits(config.risk_limits())
+    }
 
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        if let Err(e) = self.sync_utxo_detail_to_db(index, &utxo_detail) {
-            error!("Failed to sync UTXO detail to database: {}", e);
-        }
-        if io_type == IOType::Coin {
-            let account = utxo_detail.output.to_quisquis_account()?;
-            self.zk_accounts.update_qq_account(&index, account)?;
+    pub fn risk_limits(&self) -> &RiskLimits {
+        &self.risk_limits
+    }
 
-            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-            {
-                if let Ok(account) = self.zk_accounts.get_account(&index) {
-                    let _ = self.update_zk_account_in_db(&account);
-                }
+    /// Leverage and position size bounds new trader orders are checked
+    /// against, for sizing positions up front (see
+    /// [`trading_limits`](super::trading_limits)). Fetched from the relayer
+    /// by the first trader order and again once older than the refresh
+    /// interval, or on [`refresh_trading_limits`](Self::refresh_trading_limits);
+    /// the static fallback until then.
+    pub fn trading_limits(&self) -> TradingLimits {
+        self.trading_limits.get()
+    }
+
+    /// Fetch the relayer's risk parameters (`get_market_stats`) now. When the
+    /// relayer does not answer, the current limits (the fallback, before any
+    /// fetch) are kept until the next refresh.
+    pub async fn refresh_trading_limits(&self) -> TradingLimits {
+        let now = self.clock.now();
+        let limits = match self.relayer.get_market_stats().await {
+            Ok(stats) => TradingLimits::from_market_stats(&stats, now),
+            Err(e) => {
+                let mut limits = self.trading_limits.get();
+                warn!(
+                    "Could not fetch relayer market stats, keeping {:?} limits: {}",
+                    limits.source, e
+                );
+                limits.fetched_at = Some(now);
+                limits
             }
-        }
-        info!("Account {} synced with on-chain state", index);
-        Ok(())
+        };
+        self.trading_limits.replace(limits.clone());
+        limits
     }
 
-    /// Cache a UTXO detail in memory and sync to database if enabled.
-    fn cache_utxo(&mut self, index: AccountIndex, utxo_detail: UtxoDetailResponse) {
-        self.utxo_details.insert(index, utxo_detail.clone());
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        if let Err(e) = self.sync_utxo_detail_to_db(index, &utxo_detail) {
-            error!("Failed to sync UTXO detail to database: {}", e);
+    async fn ensure_trading_limits(&self) -> TradingLimits {
+        let limits = self.trading_limits.get();
+        if limits.is_stale(self.clock.now(), self.trading_limits_refresh) {
+            return self.refresh_trading_limits().await;
         }
+        limits
     }
 
-    /// Remove a UTXO detail from memory and database.
-    fn uncache_utxo(&mut self, index: AccountIndex) {
-        self.utxo_details.remove(&index);
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        if let Err(e) = self.remove_utxo_detail_from_db(index) {
-            error!("Failed to remove UTXO detail from database: {}", e);
+    /// Fees estimated when the account's current trader order was opened,
+    /// until the account is back in Coin state; see [`fees`](super::fees).
+    pub fn fee_estimate(&self, index: AccountIndex) -> Option<FeeEstimate> {
+        self.fee_estimates.get(&index)
+    }
+
+    /// Fees of an order worth `order_value` sats, or `None` without one or
+    /// when the lookup fails; a failed lookup does not fail the order.
+    async fn estimate_fees(
+        &self,
+        index: AccountIndex,
+        order_value: Option<u64>,
+        order_type: OrderType,
+    ) -> Option<FeeEstimate> {
+        match self
+            .relayer_api_client
+            .estimate_order_fees(order_value?, order_type)
+            .await
+        {
+            Ok(estimate) => Some(estimate),
+            Err(e) => {
+                warn!("Failed to estimate fees for account {}: {}", index, e);
+                None
+            }
         }
     }
 
-    /// Persist a newly created ZkAccount to the database.
-    fn try_save_new_account_to_db(&self, index: &AccountIndex) {
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        if let Ok(account) = self.zk_accounts.get_account(index) {
-            let _ = self.sync_zk_account_to_db(&account);
+    /// Requested versus filled entry of the account's latest trader order,
+    /// with its slippage and time to fill; see
+    /// [`execution_report`](super::execution_report). The order is queried
+    /// until it has filled; the report is kept from then on.
+    pub async fn execution_report(&self, index: AccountIndex) -> Result<ExecutionReport, String> {
+        let record = self
+            .order_history(index)
+            .into_iter()
+            .rev()
+            .find(|record| record.kind == OrderRecordKind::TraderOpen)
+            .ok_or_else(|| format!("No trader order opened on account {}", index))?;
+        if let Some(report) = self.cached_execution_report(index, &record.request_id) {
+            return Ok(report);
         }
+        let order = self.query_trader_order(index).await?;
+        let report = ExecutionReport::from_trader_order(index, &record, &order)?;
+        self.execution_reports
+            .update(index, |reports| reports.push(report.clone()));
+        Ok(report)
     }
 
-    /// Persist the current state of an existing ZkAccount to the database.
-    fn try_update_account_in_db(&self, index: &AccountIndex) {
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        if let Ok(account) = self.zk_accounts.get_account(index) {
-            let _ = self.update_zk_account_in_db(&account);
+    fn cached_execution_report(
+        &self,
+        index: AccountIndex,
+        request_id: &str,
+    ) -> Option<ExecutionReport> {
+        self.execution_reports
+            .get(&index)?
+            .into_iter()
+            .find(|report| report.request_id == request_id)
+    }
+
+    /// [`ExecutionQuality`] of the trader orders opened over the last
+    /// `window`, across accounts. An order counts when its report was kept
+    /// or, for the latest order of an account, can be made now; the others
+    /// count as unavailable.
+    pub async fn execution_quality(&self, window: std::time::Duration) -> ExecutionQuality {
+        let since = chrono::Duration::from_std(window)
+            .ok()
+            .and_then(|window| self.clock.now().checked_sub_signed(window))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let mut reports = Vec::new();
+        let mut unavailable = 0;
+        let mut history: Vec<_> = self.all_order_history().into_iter().collect();
+        history.sort_by_key(|(index, _)| *index);
+        for (index, records) in history {
+            let latest_open = records
+                .iter()
+                .rev()
+                .find(|record| record.kind == OrderRecordKind::TraderOpen)
+                .map(|record| record.request_id.clone());
+            for record in records {
+                if record.kind != OrderRecordKind::TraderOpen || record.recorded_at < since {
+                    continue;
+                }
+                let report = match self.cached_execution_report(index, &record.request_id) {
+                    Some(report) => Ok(report),
+                    None if latest_open.as_deref() == Some(record.request_id.as_str()) => {
+                        self.execution_report(index).await
+                    }
+                    None => Err(format!(
+                        "Order {} can no longer be queried",
+                        record.request_id
+                    )),
+                };
+                match report {
+                    Ok(report) => reports.push(report),
+                    Err(e) => {
+                        debug!("No execution report for account {}: {}", index, e);
+                        unavailable += 1;
+                    }
+                }
+            }
         }
+        ExecutionQuality::from_reports(&reports, unavailable)
     }
 
-    /// Store a request ID in memory and sync to database.
-    fn cache_request_id(&mut self, index: AccountIndex, request_id: &str) {
-        self.request_ids.insert(index, request_id.to_string());
+    /// [`all_order_history`](Self::all_order_history) oldest first, each
+    /// close and cancel with the prices, margins and realized P&L stored for
+    /// it. Snapshots are only stored with a database; without one every
+    /// entry has `snapshot: None`.
+    pub fn trade_history(&self) -> Result<Vec<TradeHistoryEntry>, String> {
         #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        if let Err(e) = self.sync_request_id_to_db(index, request_id) {
-            error!("Failed to sync request ID to database: {}", e);
-        }
+        let snapshots = match self.db_manager {
+            Some(ref db_manager) => db_manager.load_order_snapshots(db_manager.get_wallet_id())?,
+            None => Vec::new(),
+        };
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let snapshots = Vec::new();
+        Ok(merge_trade_history(self.all_order_history(), snapshots))
     }
 
-    /// Build an authenticated `QueryTraderOrderZkos` for the given account.
-    fn build_trader_query(&self, index: AccountIndex) -> Result<QueryTraderOrderZkos, String> {
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let query_order = query_trader_order_zkos(
-            account_address.clone(),
-            &secret_key,
-            account_address,
-            "PENDING".to_string(),
-        );
-        QueryTraderOrderZkos::decode_from_hex_string(query_order)
+    /// Replace the clock used for time-dependent logic (timestamps, waits).
+    /// Defaults to the system clock; pass a `ManualClock` in tests or backtests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.set_clock(clock);
+        self
     }
 
-    /// Build an authenticated `QueryLendOrderZkos` for the given account.
-    fn build_lend_query(&self, index: AccountIndex) -> Result<QueryLendOrderZkos, String> {
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let query_order = query_lend_order_zkos(
-            account_address.clone(),
-            &secret_key,
-            account_address,
-            OrderStatus::LENDED.to_str(),
-        );
-        QueryLendOrderZkos::decode_from_hex_string(query_order)
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.activity.set_clock(clock.clone());
+        self.clock = clock;
     }
 
-    /// Transition an account back to Coin state after an order settles.
-    /// Updates balance, IO type, QQ account, and UTXO cache.
-    fn settle_to_coin(
-        &mut self,
-        index: AccountIndex,
-        new_balance: u64,
-        utxo_detail: UtxoDetailResponse,
-    ) -> Result<(), String> {
-        self.zk_accounts.update_balance(&index, new_balance)?;
-        self.zk_accounts
-            .update_io_type(&index, IOType::Coin, None)?;
-        let account = utxo_detail.output.to_quisquis_account()?;
-        self.zk_accounts.update_qq_account(&index, account)?;
-        self.cache_utxo(index, utxo_detail);
-        self.try_update_account_in_db(&index);
-        Ok(())
+    /// Clock used by this wallet for time-dependent logic.
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
     }
 
-    // -------------------------
-    // Funding Operations
-    // -------------------------
-    pub async fn funding_to_trading(&mut self, amount: u64) -> Result<(TxResult, u64), String> {
-        let wallet_balance = self
-            .wallet
-            .update_balance()
-            .await
-            .map_err(|e| e.to_string())?;
-        // temporary disabled, will be re-enabled later if there is a need to check nyks balance
-        // if wallet_balance.nyks == 0 {
-        //     return Err("Insufficient balance".to_string());
-        // }
-        if wallet_balance.sats < amount {
-            return Err("Insufficient balance".to_string());
-        }
+    /// Replace the relayer program cache (e.g. one with a custom `ProgramLoader`).
+    pub fn with_program_cache(mut self, program_cache: ProgramCache) -> Self {
+        self.program_cache = program_cache;
+        self
+    }
 
-        let account_index = self.zk_accounts.generate_new_account(amount, &self.seed)?;
-        self.try_save_new_account_to_db(&account_index);
+    /// Replace the relayer orders go through (defaults to `relayer_api_client`),
+    /// e.g. with a scripted `mock_relayer::MockRelayer` in tests. Market
+    /// analytics, fee and pool queries still use `relayer_api_client`.
+    pub fn with_relayer(mut self, relayer: Arc<dyn RelayerApi>) -> Self {
+        self.relayer = relayer;
+        self
+    }
 
-        // self.wallet
-        //     .update_account_info()
-        //     .await
-        //     .map_err(|e| e.to_string())?;
+    /// Replace the source of account UTXOs (defaults to the chain, with retries).
+    pub fn with_utxo_fetcher(mut self, utxo_fetcher: Arc<dyn UtxoFetcher>) -> Self {
+        self.utxo_fetcher = utxo_fetcher;
+        self
+    }
 
-        // Sync nonce manager and acquire a sequence number
-        self.nonce_manager
-            .sync_from_chain(
-                &self.wallet.chain_config.lcd_endpoint,
-                &self.wallet.twilightaddress,
-            )
-            .await?;
-        let (sequence, account_number) = self.nonce_manager.acquire_next()?;
+    /// Replace how transfer transactions are built (defaults to the SDK).
+    pub fn with_transfer_builder(mut self, transfer_builder: Arc<dyn TransferBuilder>) -> Self {
+        self.transfer_builder = transfer_builder;
+        self
+    }
 
-        let signed_tx = build_and_sign_msg_mint_burn_trading_btc(
-            &self.wallet,
-            &self.zk_accounts,
-            account_index,
-            sequence,
-            account_number,
-            amount,
-            true,
-        )?;
-        let result = send_tx_to_chain(signed_tx, &self.wallet.chain_config.rpc_endpoint).await?;
-        if result.code != 0 {
-            self.nonce_manager.release(sequence);
-            return Err(format!("Failed to send tx to chain: {}", result.tx_hash));
-        }
-        let _ = check_tx_status(&result.tx_hash, &self.wallet.chain_config.lcd_endpoint).await?;
-        // self.sync_account_state(account_index).await?;
-        self.zk_accounts.update_on_chain(&account_index, true)?;
-        self.try_update_account_in_db(&account_index);
+    /// Replace how ZkOS transactions are broadcast (defaults to the SDK).
+    pub fn with_chain_broadcaster(mut self, chain_broadcaster: Arc<dyn ChainBroadcaster>) -> Self {
+        self.chain_broadcaster = chain_broadcaster;
+        self
+    }
 
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        self.log_transfer_history(
-            "fund_to_trade",
-            None,
-            Some(account_index),
-            amount,
-            Some(&result.tx_hash),
+    /// Let order opens reuse an account's UTXO fetched within `ttl` instead of
+    /// re-fetching it, as long as the account has not changed locally since.
+    /// `None` (the default) always re-fetches.
+    pub fn with_utxo_cache_ttl(mut self, ttl: Option<std::time::Duration>) -> Self {
+        self.utxo_cache.get_mut().set_ttl(ttl);
+        self
+    }
+
+    /// Funnel this wallet's mint/burn transactions through the `registry`'s
+    /// serializer for its twilight address, so wallets sharing the address
+    /// (same mnemonic) take turns instead of racing on the account sequence.
+    /// The wallet adopts the serializer's `NonceManager`, so its other
+    /// transactions count on the same sequence.
+    /// Use [`ChainTxRegistry::global`] to share across the whole process.
+    pub fn with_chain_tx_registry(mut self, registry: &ChainTxRegistry) -> Self {
+        let serializer = registry.serializer(
+            &self.wallet.twilightaddress,
+            &self.wallet.chain_config.lcd_endpoint,
+            &self.wallet.nonce_manager,
         );
+        self.wallet.nonce_manager = serializer.nonce_manager();
+        self.nonce_manager = self.wallet.nonce_manager.clone();
+        self.chain_tx = Some(serializer);
+        self
+    }
 
-        Ok((result, account_index))
+    /// Wait for funding and withdrawal mint/burn transactions to confirm and
+    /// re-submit them with a higher fee while they stay unconfirmed. Ignored
+    /// for wallets using a shared [`ChainTxSerializer`], which owns the
+    /// sequence of every transaction it sends.
+    pub fn with_fee_bump(mut self, policy: FeeBumpPolicy) -> Self {
+        self.fee_bump = Some(policy);
+        self
     }
-    //  -> Result<(TxResult, u64), String>
-    pub async fn trading_to_trading(
-        &mut self,
-        index: AccountIndex,
-    ) -> Result<AccountIndex, String> {
-        self.sync_account_state(index).await?;
-        let sender_account = self.zk_accounts.get_account(&index)?;
-        self.ensure_zk_account_onchain(&sender_account)?;
-        let amount = sender_account.balance;
-        let new_account_index = self.zk_accounts.generate_new_account(amount, &self.seed)?;
-        self.try_save_new_account_to_db(&new_account_index);
 
-        let receiver_input_string = self.zk_accounts.get_account(&new_account_index)?.account;
-        let utxo_detail = self
-            .utxo_details
-            .get(&index)
-            .ok_or("UTXO detail not found")?;
-        let input = utxo_detail.get_input()?;
-        let tx_wallet = create_private_transfer_tx_single(
-            self.get_secret_key(index),
-            input,
-            receiver_input_string,
-            amount,
-            false,
-            0,
-            1u64,
+    /// Set the process-wide log redaction policy (see [`crate::log_privacy`]).
+    /// The policy is global, so it also applies to other wallets in the process.
+    pub fn with_log_privacy(self, policy: LogPrivacy) -> Self {
+        LogPrivacy::set(policy);
+        self
+    }
+
+    /// Keep the ZkOS seed in `storage`. With [`SeedStorage::OsKeystore`] the
+    /// seed moves to the platform keystore and is fetched per derivation; if
+    /// the keystore is unavailable it stays in memory with a warning (see
+    /// [`crate::security::seed_storage`]). Check [`seed_storage`](Self::seed_storage)
+    /// for the mode in effect.
+    pub fn with_seed_storage(self, storage: SeedStorage) -> Result<Self, String> {
+        self.with_seed_keystore(storage, Arc::new(OsKeystore))
+    }
+
+    /// [`with_seed_storage`](Self::with_seed_storage) with an explicit keystore
+    /// backend.
+    pub fn with_seed_keystore(
+        mut self,
+        storage: SeedStorage,
+        backend: Arc<dyn SeedKeystore>,
+    ) -> Result<Self, String> {
+        let label = format!(
+            "zkos-seed/{}/{}",
+            self.chain_id, self.wallet.twilightaddress
         );
+        let seed = self.seed.secret()?;
+        self.seed = SeedVault::open(storage, backend, &label, seed);
+        Ok(self)
+    }
 
-        let encrypt_scalar = tx_wallet.get_encrypt_scalar_hex();
+    /// Where the ZkOS seed is actually kept, after any keystore fallback.
+    pub fn seed_storage(&self) -> SeedStorage {
+        self.seed.storage()
+    }
 
-        let response = tokio::task::spawn_blocking(move || {
-            twilight_client_sdk::chain::tx_commit_broadcast_transaction(
-                tx_wallet.get_tx().ok_or("Failed to get tx")?,
-            )
-        })
-        .await
-        .map_err(|e| format!("Failed to send RPC request: {}", e))?;
-        debug!("trading_to_trading response: {:?}", response);
+    /// Serializer set by [`with_chain_tx_registry`](Self::with_chain_tx_registry), if any.
+    pub fn chain_tx_serializer(&self) -> Option<Arc<ChainTxSerializer>> {
+        self.chain_tx.clone()
+    }
 
-        let utxo_detail = fetch_utxo_details_with_retry(
-            self.zk_accounts.get_account_address(&new_account_index)?,
-            IOType::Coin,
-        )
-        .await?;
+    /// When and why the cached UTXO for `index` was last fetched.
+    pub fn utxo_freshness(&self, index: AccountIndex) -> Option<UtxoStamp> {
+        self.utxo_cache.with(|cache| cache.stamp(index).cloned())
+    }
 
-        self.cache_utxo(new_account_index, utxo_detail.clone());
-        self.uncache_utxo(index);
+    /// UTXO fetches order opens skipped by reusing a cached or pre-warmed
+    /// UTXO (see [`with_utxo_cache_ttl`](Self::with_utxo_cache_ttl)).
+    pub fn utxo_fetches_avoided(&self) -> u64 {
+        self.utxo_cache.with(UtxoCache::avoided_fetches)
+    }
 
-        self.zk_accounts.update_on_chain(&new_account_index, true)?;
-        self.zk_accounts.update_on_chain(&index, false)?;
-        self.zk_accounts.update_balance(&index, 0u64)?;
-        let account = utxo_detail.output.to_quisquis_account()?;
-        self.zk_accounts
-            .update_qq_account(&new_account_index, account)?;
-        self.zk_accounts
-            .update_scalar(&new_account_index, &encrypt_scalar)?;
+    /// Validated relayer program for the configured `relayer_program_json_path`.
+    /// Parsed once and reused; reloaded when the path or the file changes.
+    pub fn relayer_program(&self) -> Result<Arc<RelayerProgram>, RelayerProgramError> {
+        self.program_cache
+            .get(&self.relayer_endpoint_config.relayer_program_json_path)
+    }
 
-        self.try_update_account_in_db(&new_account_index);
-        self.try_update_account_in_db(&index);
+    /// Get a reference to the database manager, if DB persistence is enabled.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn get_db_manager(&self) -> Option<&DatabaseManager> {
+        self.db_manager.as_ref()
+    }
 
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        {
-            let tx_hash = match &response {
-                Ok(hash) => Some(hash.as_str()),
-                Err(_) => None,
-            };
-            self.log_transfer_history(
-                "trade_to_trade",
-                Some(index),
-                Some(new_account_index),
-                sender_account.balance,
-                tx_hash,
-            );
+    /// A copy of the wallet password, unless the wallet is locked. The copy
+    /// outlives a later [`lock`](OrderWallet::lock); prefer
+    /// [`with_wallet_password`](OrderWallet::with_wallet_password).
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn get_wallet_password(&self) -> Option<SecretString> {
+        self.with_wallet_password(|password| password.clone()).ok()
+    }
+
+    /// Run `f`, an operation that encrypts, with the wallet password, or
+    /// fail with [`WalletLocked`]. Resets the auto-lock idle timer.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn with_wallet_password<T>(
+        &self,
+        f: impl FnOnce(&SecretString) -> T,
+    ) -> Result<T, WalletLocked> {
+        self.passphrase.with_secret(self.clock.now(), f)
+    }
+
+    /// Whether the cached database passphrase has been dropped, by
+    /// [`lock`](OrderWallet::lock) or by the auto-lock timer.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn is_locked(&self) -> bool {
+        self.passphrase.is_locked(self.clock.now())
+    }
+
+    /// Drop the cached database passphrase (zeroized on drop). Until
+    /// [`unlock`](OrderWallet::unlock), saving the encrypted seed or wallet
+    /// blob returns [`WalletLocked`]; writes to unencrypted tables keep
+    /// working, and drop skips the encrypted saves with a warning.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn lock(&mut self) {
+        if self.passphrase.lock() {
+            info!("Wallet locked");
         }
+    }
 
-        Ok(new_account_index)
+    /// Cache `password` again after checking it decrypts the stored wallet.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn unlock(&mut self, password: SecretString) -> Result<(), String> {
+        let db_manager = self
+            .db_manager
+            .as_ref()
+            .ok_or_else(|| "Database persistence is not enabled".to_string())?;
+        db_manager
+            .load_encrypted_wallet(&password)
+            .map_err(|e| format!("Failed to unlock wallet: {}", e))?;
+        self.passphrase.unlock(password, self.clock.now());
+        self.passphrase.arm_auto_lock(self.clock.clone());
+        info!("Wallet unlocked");
+        Ok(())
     }
 
-    pub async fn trading_to_funding(&mut self, old_index: AccountIndex) -> Result<(), String> {
-        self.ensure_coin_onchain(old_index)?;
-        let index = self.trading_to_trading(old_index).await?;
+    /// Lock the wallet after `idle` without an encrypting operation, measured
+    /// on the wallet's clock. Inside a tokio runtime a timer task locks it
+    /// when the timeout passes; otherwise it locks at the next passphrase
+    /// access. `None` (the default) keeps it unlocked until
+    /// [`lock`](OrderWallet::lock).
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn with_auto_lock(self, idle: Option<std::time::Duration>) -> Self {
+        self.passphrase.set_auto_lock(idle);
+        self.passphrase.arm_auto_lock(self.clock.clone());
+        self
+    }
 
-        self.sync_account_state(index).await?;
-        let input = self
-            .utxo_details
-            .get(&index)
-            .ok_or("UTXO detail not found")?
-            .get_input()?;
+    /// Re-encrypt the wallet blob, the saved configuration and the zk
+    /// account secrets under `new_password`, and cache it in place of the
+    /// old one. Fails with [`WalletLocked`] while the wallet is locked.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn change_wallet_password(&mut self, new_password: SecretString) -> Result<(), String> {
+        let db_manager = self
+            .db_manager
+            .as_ref()
+            .ok_or_else(|| "Database persistence is not enabled".to_string())?;
+        self.with_wallet_password(|_| ())?;
+        db_manager.rekey_zk_accounts(&new_password)?;
+        db_manager.save_encrypted_wallet(&self.wallet, &new_password)?;
+        self.passphrase.unlock(new_password, self.clock.now());
+        self.passphrase.arm_auto_lock(self.clock.clone());
+        self.save_order_wallet_to_db()
+    }
 
-        let sender_account = self.zk_accounts.get_account(&index)?;
-        let amount = sender_account.balance;
-        let encrypt_scalar = sender_account.scalar.clone();
-        let sk = self.get_secret_key(index);
-        let tx_hex = create_burn_message_transaction(
-            input,
-            amount,
-            encrypt_scalar,
-            sk,
-            sender_account.account.clone(),
-        );
-        let transaction = bincode::deserialize(&hex::decode(tx_hex).map_err(|e| e.to_string())?)
-            .map_err(|e| e.to_string())?;
-        let _tx_hash = tokio::task::spawn_blocking(move || {
-            twilight_client_sdk::chain::tx_commit_broadcast_transaction(transaction)
-        })
-        .await
-        .map_err(|e| format!("Failed to send RPC request: {}", e))?
-        .map_err(|e| format!("Failed to get tx hash: {}", e))?;
-        //waiting for the utxo to be removed
-        let _ = fetch_removed_utxo_details_with_retry(
-            self.zk_accounts.get_account_address(&index)?,
-            IOType::Coin,
-        )
-        .await?;
+    /// Write a backup of the wallet's database state to `path`; see
+    /// [`DatabaseManager::export_backup_to_file`]. Fails with
+    /// [`WalletLocked`] while the wallet is locked.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn export_backup_to_file(&self, path: &str) -> Result<(), String> {
+        let db_manager = self
+            .db_manager
+            .as_ref()
+            .ok_or_else(|| "Database persistence is not enabled".to_string())?;
+        self.with_wallet_password(|_| db_manager.export_backup_to_file(path))?
+    }
+
+    /// Ensure the account exists on-chain, has IOType::Coin, and a non-zero balance.
+    pub fn ensure_coin_onchain(&self, index: AccountIndex) -> OrderWalletResult<()> {
+        let a = self.zk_accounts.get_account(&index)?;
+        self.ensure_zk_account_onchain(&a)
+    }
+    /// Ensure the account exists on-chain, has IOType::Coin, and a non-zero balance.
+    pub fn ensure_zk_account_onchain(&self, a: &ZkAccount) -> OrderWalletResult<()> {
+        if a.io_type != IOType::Coin {
+            return Err(OrderWalletError::InvalidOrderState {
+                expected: format!("{:?}", IOType::Coin),
+                actual: format!("{:?}", a.io_type),
+            });
+        }
+        if !a.on_chain {
+            return Err(OrderWalletError::AccountNotOnChain(a.index));
+        }
+        if a.balance == 0 {
+            return Err(OrderWalletError::EmptyAccount(a.index));
+        }
+        Ok(())
+    }
+
+    /// Capabilities of the connected relayer (cached after the first handshake).
+    pub async fn relayer_capabilities(&self) -> Result<RelayerCapabilities, String> {
+        self.relayer.capabilities().await.map_err(|e| e.to_string())
+    }
+
+    /// Point the wallet at a different relayer. The new client re-runs the
+    /// capabilities handshake on first use, and replaces a relayer set with
+    /// [`with_relayer`](Self::with_relayer).
+    pub fn update_endpoints(
+        &mut self,
+        relayer_endpoint_config: RelayerEndPointConfig,
+    ) -> Result<(), String> {
+        self.relayer_api_client =
+            RelayerJsonRpcClient::from_endpoint_config(&relayer_endpoint_config)
+                .map_err(|e| e.to_string())?
+                .with_activity(self.activity.clone());
+        self.relayer = Arc::new(self.relayer_api_client.clone());
+        self.relayer_endpoint_config = relayer_endpoint_config;
+        Ok(())
+    }
 
-        // Sync nonce manager and acquire a sequence number
+    /// Sync the nonce manager from the on-chain account state.
+    /// Call this before a batch of transactions, or periodically to
+    /// re-anchor the local sequence counter.
+    pub async fn sync_nonce(&self) -> Result<(), String> {
         self.nonce_manager
             .sync_from_chain(
                 &self.wallet.chain_config.lcd_endpoint,
                 &self.wallet.twilightaddress,
             )
-            .await?;
-        let (sequence, account_number) = self.nonce_manager.acquire_next()?;
+            .await
+    }
 
-        let signed_tx = build_and_sign_msg_mint_burn_trading_btc(
+    // -------------------------
+    // Internal helpers
+    // -------------------------
+
+    /// Sign and broadcast `msgs`, `MsgMintBurnTradingBtc`s, in one transaction.
+    ///
+    /// Waits for the signer account to be indexed on chain before the first
+    /// signing, then signs through [`Wallet::sign_and_broadcast`], which
+    /// counts sequences locally and re-signs once at the sequence the chain
+    /// asks for on a mismatch. With a shared [`ChainTxSerializer`] the
+    /// serializer assigns the sequence and handles the re-sign instead.
+    async fn sign_and_send_mint_burn(
+        &self,
+        msgs: Vec<MsgMintBurnTradingBtc>,
+    ) -> OrderWalletResult<TxResult> {
+        if let Some(serializer) = &self.chain_tx {
+            let result = serializer
+                .submit(|sequence, account_number| {
+                    let msgs = msgs.clone();
+                    async move {
+                        let signed_tx = sign_msgs_mint_burn_trading_btc_estimated(
+                            &self.wallet,
+                            msgs,
+                            sequence,
+                            account_number,
+                        )
+                        .await?;
+                        send_tx_to_chain(signed_tx, &self.wallet.chain_config.rpc_endpoint).await
+                    }
+                })
+                .await?;
+            if result.code == 0 {
+                self.note_activity(ActivityCategory::ChainTxs);
+                return Ok(result);
+            }
+            return Err(mint_burn_rejected(&result));
+        }
+
+        if !self.nonce_manager.is_synced() {
+            self.nonce_manager
+                .sync_from_chain_with_retry(
+                    &self.wallet.chain_config.lcd_endpoint,
+                    &self.wallet.twilightaddress,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        // Simulated at the sequence the transaction is about to take.
+        let fee = estimate_mint_burn_fee(
+            &self.wallet,
+            &msgs,
+            self.nonce_manager.peek_next(),
+            self.nonce_manager.account_number(),
+        )
+        .await?;
+        let sent = self
+            .wallet
+            .sign_and_broadcast(|sequence, account_number| {
+                sign_msgs_mint_burn_trading_btc(
+                    &self.wallet,
+                    msgs.clone(),
+                    sequence,
+                    account_number,
+                    &fee,
+                )
+                .map_err(anyhow::Error::msg)
+            })
+            .await
+            .map_err(mint_burn_send_failed)?;
+        let result = TxResult {
+            tx_hash: sent.hash,
+            code: sent.code,
+            simulated: false,
+            attempts: Vec::new(),
+            log: sent.log,
+        };
+        if result.code == 0 {
+            self.note_activity(ActivityCategory::ChainTxs);
+            return Ok(result);
+        }
+        Err(mint_burn_rejected(&result))
+    }
+
+    /// Sign and broadcast a `MsgMintBurnTradingBtc` for `index` and wait for it
+    /// to confirm, fee bumping it when a [`FeeBumpPolicy`] is set.
+    async fn send_and_confirm_mint_burn(
+        &self,
+        index: AccountIndex,
+        amount: u64,
+        mint_or_burn: bool,
+    ) -> OrderWalletResult<TxResult> {
+        let msg = mint_burn_trading_btc_msg(
             &self.wallet,
-            &self.zk_accounts,
+            &self.zk_accounts.read(),
             index,
-            sequence,
-            account_number,
             amount,
-            false,
+            mint_or_burn,
         )?;
-        let result = send_tx_to_chain(signed_tx, &self.wallet.chain_config.rpc_endpoint).await?;
-        if result.code != 0 {
-            self.nonce_manager.release(sequence);
-            return Err(format!("Failed to send tx to chain: {}", result.tx_hash));
-        }
-        let _ = check_tx_status(&result.tx_hash, &self.wallet.chain_config.lcd_endpoint).await?;
-        self.zk_accounts.update_on_chain(&index, false)?;
-        self.zk_accounts.update_balance(&index, 0)?;
-        self.try_update_account_in_db(&index);
+        self.send_and_confirm_mint_burn_msgs(vec![msg]).await
+    }
 
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        self.log_transfer_history(
-            "trade_to_fund",
-            Some(old_index),
-            None,
-            amount,
-            Some(&result.tx_hash),
-        );
+    /// [`send_and_confirm_mint_burn`](Self::send_and_confirm_mint_burn) for
+    /// all of `msgs` in one transaction.
+    async fn send_and_confirm_mint_burn_msgs(
+        &self,
+        msgs: Vec<MsgMintBurnTradingBtc>,
+    ) -> OrderWalletResult<TxResult> {
+        let (Some(policy), None) = (&self.fee_bump, &self.chain_tx) else {
+            let result = self.sign_and_send_mint_burn(msgs).await?;
+            let _ =
+                check_tx_status(&result.tx_hash, &self.wallet.chain_config.lcd_endpoint).await?;
+            return Ok(result);
+        };
+
+        self.nonce_manager
+            .sync_from_chain_with_retry(
+                &self.wallet.chain_config.lcd_endpoint,
+                &self.wallet.twilightaddress,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        // Every attempt is signed with this sequence, so only one can land.
+        let (sequence, account_number) = self.nonce_manager.acquire_next()?;
+        let rpc_endpoint = self.wallet.chain_config.rpc_endpoint.clone();
+        let lcd_endpoint = self.wallet.chain_config.lcd_endpoint.clone();
+        let outcome = submit_with_fee_bump(
+            policy,
+            |fee| {
+                let signed_tx = sign_msgs_mint_burn_trading_btc(
+                    &self.wallet,
+                    msgs.clone(),
+                    sequence,
+                    account_number,
+                    &self.wallet.chain_config.tx_fee.clone().with_fee_amount(fee),
+                );
+                let rpc_endpoint = rpc_endpoint.clone();
+                async move { broadcast_signed_tx(signed_tx?, &rpc_endpoint).await }
+            },
+            |tx_hash| {
+                let lcd_endpoint = lcd_endpoint.clone();
+                async move { query_tx_status(&tx_hash, &lcd_endpoint).await }
+            },
+        )
+        .await;
+        match outcome {
+            Ok(receipt) => {
+                self.note_activity(ActivityCategory::ChainTxs);
+                if receipt.bumps() > 0 {
+                    info!(
+                        "mint/burn tx {} confirmed at fee {} after {} fee bumps",
+                        receipt.tx_hash,
+                        receipt.fee_nyks,
+                        receipt.bumps()
+                    );
+                }
+                Ok(TxResult {
+                    tx_hash: receipt.tx_hash,
+                    code: 0,
+                    simulated: false,
+                    attempts: receipt.attempts,
+                    log: None,
+                })
+            }
+            Err(e) => {
+                if !e.attempts().iter().any(|attempt| attempt.accepted()) {
+                    self.nonce_manager.release(sequence);
+                }
+                Err(e.to_string().into())
+            }
+        }
+    }
 
+    /// Sync an account's on-chain UTXO state. Call this to complete a deferred
+    /// sync after a `--no-wait` open or close operation.
+    pub async fn sync_account_state(&self, index: AccountIndex) -> OrderWalletResult<()> {
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let io_type = self.zk_accounts.get_account(&index)?.io_type;
+        let utxo_detail = self.utxo_fetcher.fetch(account_address, io_type).await?;
+        let fetched_at = self.clock.now();
+        self.apply_fetched_utxo(index, utxo_detail, "sync_account_state", fetched_at)?;
         Ok(())
     }
-    /// Split a single Coin account into multiple new Coin accounts as specified by `balances`.
-    /// Returns a vector of `(new_account_index, balance)` for each created account.
-    /// Requirements:
-    /// - Sender must be on-chain in Coin state and have at least sum(balances)
-    /// - `balances` must be non-empty
-    /// - Recommended to create at most 8 accounts per call due to tx size limits
-    pub async fn trading_to_trading_multiple_accounts(
-        &mut self,
-        sender_account_index: AccountIndex,
-        balances: Vec<Balance>,
-    ) -> Result<Vec<AccountBalance>, String> {
-        self.ensure_coin_onchain(sender_account_index)?;
-        let sk = self.get_secret_key(sender_account_index);
 
-        self.sync_account_state(sender_account_index).await?;
-        let input_sender = self
-            .utxo_details
-            .get(&sender_account_index)
-            .ok_or("UTXO detail not found")?
-            .get_input()?;
+    /// Adopt a fetched UTXO for `index`: cache it, refresh the QuisQuis account
+    /// for coin accounts, and stamp the UTXO cache with the resulting state.
+    fn apply_fetched_utxo(
+        &self,
+        index: AccountIndex,
+        utxo_detail: UtxoDetailResponse,
+        origin: &str,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let io_type = self.zk_accounts.get_account(&index)?.io_type;
+        self.utxo_details.insert(index, utxo_detail.clone());
 
-        let mut new_account_balances = Vec::new();
-        let mut commitment_scalar_vec = Vec::new();
-        let mut receiver_vec = Vec::new();
-        let mut updated_reciever_balance_vec = Vec::new();
-        let num_of_new_accounts = balances.len();
-        let sender_transfering_amt = balances.iter().sum::<Balance>();
-        let sender_account = self.zk_accounts.get_account(&sender_account_index)?;
-        if sender_account.balance < sender_transfering_amt {
-            return Err(format!("Insufficient balance"));
-        }
-        if num_of_new_accounts == 0 || num_of_new_accounts > 9 {
-            return Err(format!("No new accounts to create"));
-        }
-        let updated_sender_balance = sender_account.balance - sender_transfering_amt;
-        for balance in balances {
-            let new_account_index = self.zk_accounts.generate_new_account(0, &self.seed)?;
-            new_account_balances.push((new_account_index, balance));
-            commitment_scalar_vec.push(
-                self.zk_accounts
-                    .get_account(&new_account_index)?
-                    .get_scalar()?,
-            );
-            receiver_vec.push(Receiver::set_receiver(
-                balance as i64,
-                self.zk_accounts
-                    .get_account(&new_account_index)?
-                    .get_qq_account()?,
-            ));
-            updated_reciever_balance_vec.push(balance);
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Err(e) = self.sync_utxo_detail_to_db(index, &utxo_detail) {
+            error!("Failed to sync UTXO detail to database: {}", e);
+            self.emit_db_sync_failed(index, "sync_utxo_detail", &e);
         }
-        let sender_array = vec![Sender::set_sender(
-            (sender_transfering_amt as i64) * -1,
-            sender_account.get_qq_account()?,
-            receiver_vec,
-        )];
-        let tx_wallet = create_private_transfer_transaction_single_source_multiple_recievers(
-            sender_array,
-            input_sender,
-            sk,
-            vec![updated_sender_balance],
-            updated_reciever_balance_vec,
-            Some(&commitment_scalar_vec),
-            1u64,
-        )?;
-        let tx = tx_wallet.get_tx().ok_or("Failed to get tx")?;
-        let outputs = tx.get_tx_outputs();
-        let encrypt_scalar = tx_wallet.get_encrypt_scalar();
-
-        let response = tokio::task::spawn_blocking(move || {
-            twilight_client_sdk::chain::tx_commit_broadcast_transaction(
-                tx_wallet.get_tx().ok_or("Failed to get tx")?,
-            )
-        })
-        .await
-        .map_err(|e| format!("Failed to send RPC request: {}", e))?;
+        if io_type == IOType::Coin {
+            let account = output_account(index, &utxo_detail)?;
+            self.zk_accounts.update_qq_account(&index, account)?;
 
-        debug!(
-            "trading_to_trading_multiple_accounts response: {:?}",
-            response
-        );
-        if let Err(e) = response {
-            return Err(format!("Failed to send RPC request: {}", e));
-        }
-        for (i, (new_account_index, balance)) in new_account_balances.iter().enumerate() {
-            let utxo_detail = fetch_utxo_details_with_retry(
-                self.zk_accounts.get_account_address(new_account_index)?,
-                IOType::Coin,
-            )
-            .await?;
-            self.cache_utxo(*new_account_index, utxo_detail.clone());
-            self.zk_accounts.update_on_chain(new_account_index, true)?;
-            self.zk_accounts
-                .update_balance(new_account_index, *balance)?;
-            let account = utxo_detail.output.to_quisquis_account()?;
-            self.zk_accounts
-                .update_qq_account(new_account_index, account)?;
-            self.zk_accounts
-                .update_scalar(new_account_index, &encrypt_scalar[i])?;
-            self.zk_accounts.update_account_key(
-                new_account_index,
-                &outputs[i + 1]
-                    .as_output_data()
-                    .get_owner_address()
-                    .ok_or("Failed to get owner address")?,
-            )?;
-            self.try_update_account_in_db(new_account_index);
+            self.try_update_account_in_db(&index);
         }
+        let state = AccountStateKey::of(&self.zk_accounts.get_account(&index)?);
+        self.utxo_cache
+            .update(|cache| cache.record(index, state, origin, fetched_at));
+        info!("Account {} synced with on-chain state", index);
+        Ok(())
+    }
 
-        if updated_sender_balance > 0 {
-            self.zk_accounts
-                .update_balance(&sender_account_index, updated_sender_balance)?;
-            let utxo_detail = fetch_utxo_details_with_retry(
-                self.zk_accounts
-                    .get_account_address(&sender_account_index)?,
-                IOType::Coin,
-            )
-            .await?;
-            let account = utxo_detail.output.to_quisquis_account()?;
-            self.zk_accounts
-                .update_qq_account(&sender_account_index, account)?;
-            self.cache_utxo(sender_account_index, utxo_detail);
-            self.try_update_account_in_db(&sender_account_index);
-        } else {
-            self.zk_accounts.update_balance(&sender_account_index, 0)?;
-            self.zk_accounts
-                .update_on_chain(&sender_account_index, false)?;
-            self.try_update_account_in_db(&sender_account_index);
-            self.uncache_utxo(sender_account_index);
+    /// Sync `index` unless its cached UTXO is still fresh or was carried over
+    /// a cancel. A fresh pre-warmed UTXO is adopted instead of fetching.
+    async fn ensure_fresh_utxo(&self, index: AccountIndex) -> Result<(), String> {
+        let state = AccountStateKey::of(&self.zk_accounts.get_account(&index)?);
+        let now = self.clock.now();
+        let cached = self.utxo_details.contains_key(&index);
+        let (lookup, avoided) = self.utxo_cache.update(|cache| {
+            let lookup = cache.lookup(index, &state, now, cached);
+            (lookup, cache.avoided_fetches())
+        });
+        match lookup {
+            UtxoLookup::Carried => {
+                debug!(
+                    "Reusing UTXO carried over a cancel for account {} ({} fetches avoided)",
+                    index, avoided
+                );
+                Ok(())
+            }
+            UtxoLookup::Fresh => {
+                debug!(
+                    "Reusing cached UTXO for account {} ({} fetches avoided)",
+                    index, avoided
+                );
+                Ok(())
+            }
+            UtxoLookup::Prewarmed(utxo_detail, stamp) => {
+                debug!(
+                    "Using pre-warmed UTXO for account {} ({} fetches avoided)",
+                    index, avoided
+                );
+                self.apply_fetched_utxo(index, utxo_detail, &stamp.origin, stamp.fetched_at)
+            }
+            UtxoLookup::Miss => self.sync_account_state(index).await.map_err(String::from),
         }
+    }
+
+    /// Stamp the UTXO just cached for `index` as fetched now, for the
+    /// account's current state.
+    fn stamp_utxo(&self, index: AccountIndex, origin: &str) -> Result<(), String> {
+        let state = AccountStateKey::of(&self.zk_accounts.get_account(&index)?);
+        let now = self.clock.now();
+        self.utxo_cache
+            .update(|cache| cache.record(index, state, origin, now));
+        Ok(())
+    }
 
-        Ok(new_account_balances)
+    /// Whether [`ensure_fresh_utxo`](Self::ensure_fresh_utxo) would skip the fetch for `index`.
+    fn has_reusable_utxo(&self, index: AccountIndex) -> bool {
+        let Ok(account) = self.zk_accounts.get_account(&index) else {
+            return false;
+        };
+        let state = AccountStateKey::of(&account);
+        let now = self.clock.now();
+        let cached = self.utxo_details.contains_key(&index);
+        self.utxo_cache.with(|cache| {
+            (cached && (cache.is_carried(index, &state) || cache.is_fresh(index, &state, now)))
+                || cache.has_prewarmed(index, &state, now)
+        })
     }
-    // -------------------------
-    // Trader Order Operations
-    // -------------------------
 
-    /// Validate that the market is not halted.
+    /// Fetch UTXOs in the background for up to `next_n` accounts likely to open
+    /// an order next (on-chain coin accounts with a balance, largest first)
+    /// whose cached UTXO is not fresh. Results are adopted by the next
+    /// `open_trader_order`/`open_lend_order` on that account if the account is
+    /// unchanged by then. Does nothing unless a UTXO cache TTL is set.
     ///
-    /// Close, cancel, and lend operations are allowed during HEALTHY and CLOSE_ONLY
-    /// but rejected during HALT (per risk_engine.md).
-    pub async fn validate_market_not_halted(&self) -> Result<(), String> {
-        let stats = self
-            .relayer_api_client
-            .get_market_stats()
-            .await
-            .map_err(|e| format!("Failed to fetch market stats: {}", e))?;
+    /// The returned task can be registered with
+    /// [`shutdown_registry`](Self::shutdown_registry).
+    pub fn prewarm(&self, next_n: usize) -> tokio::task::JoinHandle<()> {
+        let now = self.clock.now();
+        let mut candidates: Vec<(AccountIndex, String, AccountStateKey)> = Vec::new();
+        let cache = self.utxo_cache.get();
+        if cache.ttl().is_some() {
+            for account in self.zk_accounts.get_all_accounts() {
+                let index = account.index;
+                let state = AccountStateKey::of(&account);
+                if account.io_type == IOType::Coin
+                    && account.on_chain
+                    && account.balance > 0
+                    && !(self.utxo_details.contains_key(&index)
+                        && cache.is_fresh(index, &state, now))
+                {
+                    candidates.push((index, account.account, state));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.2.balance.cmp(&a.2.balance).then(a.0.cmp(&b.0)));
+        candidates.truncate(next_n);
+
+        let fetcher = self.utxo_fetcher.clone();
+        let clock = self.clock.clone();
+        tokio::spawn(async move {
+            for (index, address, state) in candidates {
+                match fetcher.fetch(address, IOType::Coin).await {
+                    Ok(utxo_detail) => {
+                        let stamp = UtxoStamp {
+                            fetched_at: clock.now(),
+                            origin: "prewarm".to_string(),
+                            state,
+                        };
+                        cache.put_prewarmed(index, utxo_detail, stamp);
+                    }
+                    Err(e) => debug!("Pre-warming UTXO for account {} failed: {}", index, e),
+                }
+            }
+        })
+    }
 
-        if stats.status == "HALT" {
-            return Err(format!(
-                "Market is halted: {}. All operations are blocked.",
-                stats.status_reason.as_deref().unwrap_or("unknown")
-            ));
+    /// Cache a UTXO detail in memory and sync to database if enabled.
+    fn cache_utxo(&self, index: AccountIndex, utxo_detail: UtxoDetailResponse) {
+        self.utxo_details.insert(index, utxo_detail.clone());
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Err(e) = self.sync_utxo_detail_to_db(index, &utxo_detail) {
+            error!("Failed to sync UTXO detail to database: {}", e);
+            self.emit_db_sync_failed(index, "sync_utxo_detail", &e);
         }
-        Ok(())
     }
 
-    /// Pre-validate an open order against the relayer's risk engine via `get_market_stats`.
-    ///
-    /// This replicates the server-side validation pipeline locally so the user gets
-    /// an immediate, descriptive error instead of a generic rejection from the relayer.
-    /// Checks performed (matching `risk_engine.md`):
-    /// 1. Market status — reject if HALT or CLOSE_ONLY
-    /// 2. Max leverage — reject if leverage exceeds `params.max_leverage`
-    /// 3. Min position size — reject if entry_value < `params.min_position_btc`
-    /// 4. Per-position cap — reject if entry_value > `params.max_position_pct * pool_equity`
-    /// 5. Directional headroom — reject if entry_value > `max_long_btc` / `max_short_btc`
-    pub async fn validate_open_order(
-        &self,
-        order_side: &PositionType,
-        initial_margin: u64,
-        leverage: u64,
-    ) -> Result<(), String> {
-        let stats = self
-            .relayer_api_client
-            .get_market_stats()
-            .await
-            .map_err(|e| format!("Failed to fetch market stats: {}", e))?;
+    /// Remove a UTXO detail from memory and database.
+    fn uncache_utxo(&self, index: AccountIndex) {
+        self.utxo_details.remove(&index);
+        self.utxo_cache.update(|cache| cache.invalidate(index));
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Err(e) = self.remove_utxo_detail_from_db(index) {
+            error!("Failed to remove UTXO detail from database: {}", e);
+            self.emit_db_sync_failed(index, "remove_utxo_detail", &e);
+        }
+    }
 
-        // 1. Market status
-        match stats.status.as_str() {
-            "HALT" => {
-                return Err(format!(
-                    "Market is halted: {}",
-                    stats.status_reason.as_deref().unwrap_or("unknown")
-                ));
+    /// Persist a newly created ZkAccount to the database.
+    fn try_save_new_account_to_db(&self, index: &AccountIndex) {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        match self.zk_accounts.get_account(index) {
+            Ok(account) if account.simulated => {}
+            Ok(account) => {
+                if let Err(e) = self.sync_zk_account_to_db(&account) {
+                    error!("Failed to save account {} to database: {}", index, e);
+                    self.emit_db_sync_failed(*index, "save_account", &e);
+                }
             }
-            "CLOSE_ONLY" => {
-                return Err(format!(
-                    "Market is in close-only mode: {}",
-                    stats.status_reason.as_deref().unwrap_or("unknown")
-                ));
+            Err(e) => warn!("Not saving account to database: {}", e),
+        }
+    }
+
+    /// Persist the current state of an existing ZkAccount to the database.
+    fn try_update_account_in_db(&self, index: &AccountIndex) {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        match self.zk_accounts.get_account(index) {
+            Ok(account) if account.simulated => {}
+            Ok(account) => {
+                if let Err(e) = self.update_zk_account_in_db(&account) {
+                    error!("Failed to update account {} in database: {}", index, e);
+                    self.emit_db_sync_failed(*index, "update_account", &e);
+                }
             }
-            _ => {}
+            Err(e) => warn!("Not updating account in database: {}", e),
         }
+    }
 
-        // entry_value = initial_margin * leverage (in BTC / sats)
-        let entry_value = initial_margin as f64 * leverage as f64;
+    /// Record the outcome of a user-facing operation on an account: a failure
+    /// is stored as the account's `last_error`, a success clears it.
+    /// Sync `index` from chain and reconcile its balance against the output
+    /// (see [`reconcile_committed_balance`](Self::reconcile_committed_balance)).
+    /// If the UTXO cannot be fetched the `requested` amount is kept and the
+    /// account is flagged `balance_unverified`.
+    async fn sync_committed_balance(
+        &self,
+        index: AccountIndex,
+        requested: u64,
+        operation: &str,
+        tx_hash: Option<&str>,
+    ) -> Result<u64, String> {
+        let synced = self.sync_account_state(index).await.map_err(String::from);
+        self.settle_committed_balance(index, requested, operation, tx_hash, synced)
+    }
 
-        // 2. Max leverage
-        if stats.params.max_leverage > 0.0 && leverage as f64 > stats.params.max_leverage {
-            return Err(format!(
-                "Leverage {} exceeds maximum allowed {}",
-                leverage, stats.params.max_leverage
-            ));
+    /// [`sync_committed_balance`](Self::sync_committed_balance) once the sync
+    /// of `index` has `synced`.
+    fn settle_committed_balance(
+        &self,
+        index: AccountIndex,
+        requested: u64,
+        operation: &str,
+        tx_hash: Option<&str>,
+        synced: Result<(), String>,
+    ) -> Result<u64, String> {
+        if let Err(e) = synced {
+            warn!(
+                "Could not fetch the output of account {} after {} ({}); keeping the requested {} sats unverified",
+                index,
+                operation,
+                e,
+                LoggedAmount(requested)
+            );
+            self.set_account_balance(&index, requested)?;
+            self.zk_accounts.set_balance_unverified(&index, true)?;
+            self.try_update_account_in_db(&index);
+            return Ok(requested);
         }
+        self.reconcile_committed_balance(index, requested, operation, tx_hash)
+    }
 
-        // 3. Min position size
-        if stats.params.min_position_btc > 0.0 && entry_value < stats.params.min_position_btc {
-            return Err(format!(
-                "Position size {:.0} sats is below minimum {:.0} sats",
-                entry_value, stats.params.min_position_btc
-            ));
+    /// Store the amount the current QuisQuis account of `index` commits to as
+    /// its balance, instead of the `requested` amount. A difference is logged
+    /// and recorded as an [`AmountDiscrepancy`]. When the committed amount
+    /// cannot be derived, `requested` is stored and the account is flagged
+    /// `balance_unverified`. `tx_hash` is the mint that set the output, if
+    /// any. Returns the stored balance.
+    fn reconcile_committed_balance(
+        &self,
+        index: AccountIndex,
+        requested: u64,
+        operation: &str,
+        tx_hash: Option<&str>,
+    ) -> Result<u64, String> {
+        let secret_key = self.get_secret_key(index)?;
+        let committed = self
+            .zk_accounts
+            .get_account(&index)?
+            .get_qq_account()
+            .ok()
+            .and_then(|account| committed_amount(&account, &secret_key, requested));
+        let balance = match committed {
+            Some(committed) => {
+                self.zk_accounts.set_balance_unverified(&index, false)?;
+                committed
+            }
+            None => {
+                warn!(
+                    "Could not derive the committed amount of account {} after {}; keeping the requested {} sats unverified",
+                    index,
+                    operation,
+                    LoggedAmount(requested)
+                );
+                self.zk_accounts.set_balance_unverified(&index, true)?;
+                requested
+            }
+        };
+        self.set_account_balance(&index, balance)?;
+        self.try_update_account_in_db(&index);
+        if balance != requested {
+            self.record_amount_discrepancy(index, operation, requested, balance, tx_hash);
         }
+        Ok(balance)
+    }
 
-        // 4. Per-position cap
-        let pos_cap = stats.params.max_position_pct * stats.pool_equity_btc;
-        if pos_cap > 0.0 && entry_value > pos_cap {
-            return Err(format!(
-                "Position size {:.0} sats exceeds per-position cap {:.0} sats ({:.1}% of pool equity)",
-                entry_value, pos_cap, stats.params.max_position_pct * 100.0
-            ));
+    fn record_amount_discrepancy(
+        &self,
+        index: AccountIndex,
+        operation: &str,
+        requested: u64,
+        actual: u64,
+        tx_hash: Option<&str>,
+    ) {
+        let discrepancy = AmountDiscrepancy {
+            account_index: index,
+            operation: operation.to_string(),
+            requested,
+            actual,
+            tx_hash: tx_hash.map(str::to_string),
+            recorded_at: self.clock.now(),
+        };
+        warn!(
+            "Account {}: {} requested {} sats but {} moved on chain; recording a fee of {} sats",
+            index,
+            operation,
+            LoggedAmount(requested),
+            LoggedAmount(actual),
+            LoggedAmount(discrepancy.fee())
+        );
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if actual < requested {
+            self.log_transfer_history("fee", Some(index), None, requested - actual, tx_hash);
+        } else {
+            self.log_transfer_history("fee_refund", None, Some(index), actual - requested, tx_hash);
         }
+        self.amount_discrepancies
+            .update(|discrepancies| discrepancies.push(discrepancy));
+    }
+
+    /// Mints and burns this session whose on-chain amount differed from the
+    /// requested amount, oldest first.
+    pub fn amount_discrepancies(&self) -> Vec<AmountDiscrepancy> {
+        self.amount_discrepancies.get()
+    }
 
-        // 5. Directional headroom (max_long / max_short already incorporates OI + net limits)
-        let (headroom, direction) = match order_side {
-            PositionType::LONG => (stats.max_long_btc, "long"),
-            PositionType::SHORT => (stats.max_short_btc, "short"),
+    fn record_account_outcome<T, E: std::fmt::Display>(
+        &self,
+        index: AccountIndex,
+        operation: &str,
+        result: &Result<T, E>,
+    ) {
+        self.account_activity.insert(index, self.clock.now());
+        let changed = match result {
+            Ok(_) => {
+                if let Some(category) = ActivityCategory::for_operation(operation) {
+                    self.note_activity(category);
+                }
+                self.zk_accounts.clear_last_error(&index)
+            }
+            Err(e) => {
+                self.note_activity(ActivityCategory::Errors);
+                let stored = StoredError::new(operation, &e.to_string(), self.clock.now());
+                self.zk_accounts
+                    .set_last_error(&index, stored)
+                    .map(|()| true)
+            }
         };
-        if entry_value > headroom {
-            return Err(format!(
-                "Position size {:.0} sats exceeds max available {} capacity {:.0} sats (utilization: {:.1}%)",
-                entry_value, direction, headroom, stats.utilization * 100.0
-            ));
+        match changed {
+            Ok(true) => self.try_update_account_in_db(&index),
+            Ok(false) => {}
+            Err(e) => debug!("Outcome of {} not recorded: {}", operation, e),
         }
+    }
 
-        Ok(())
+    /// [`record_account_outcome`](Self::record_account_outcome) for order
+    /// operations, also emitting the matching [`WalletEvent`].
+    fn record_order_outcome<E: std::fmt::Display>(
+        &self,
+        index: AccountIndex,
+        operation: &str,
+        result: &Result<String, E>,
+    ) {
+        self.record_order_outcome_with(index, operation, result, None);
     }
 
-    pub async fn open_trader_order(
-        &mut self,
+    /// [`record_order_outcome`](Self::record_order_outcome) for a trader
+    /// open, keeping the requested order type and entry price on its record.
+    fn record_trader_open_outcome<E: std::fmt::Display>(
+        &self,
         index: AccountIndex,
-        order_type: OrderType,
-        order_side: PositionType,
+        order_type: &OrderType,
         entry_price: u64,
-        leverage: u64,
-    ) -> Result<String, String> {
-        self.ensure_coin_onchain(index)?;
-        if leverage == 0 {
-            return Err("Leverage must be greater than 0".to_string());
+        result: &Result<String, E>,
+    ) {
+        let requested = (order_type.to_str().to_string(), entry_price);
+        self.record_order_outcome_with(index, "open_trader_order", result, Some(requested));
+    }
+
+    fn record_order_outcome_with<E: std::fmt::Display>(
+        &self,
+        index: AccountIndex,
+        operation: &str,
+        result: &Result<String, E>,
+        requested: Option<(String, u64)>,
+    ) {
+        self.record_account_outcome(index, operation, result);
+        let result = result
+            .as_ref()
+            .map(Clone::clone)
+            .map_err(ToString::to_string);
+        let now = self.clock.now();
+        if let Some(event) = WalletEvent::from_outcome(index, operation, &result, now) {
+            if let Some((kind, request_id)) = OrderRecordKind::of_event(&event) {
+                // A partial close leaves the order open, unlike other closes.
+                let kind = match operation {
+                    "close_trader_order_partial" => OrderRecordKind::TraderPartialClose,
+                    _ => kind,
+                };
+                let mut record = OrderRecord::new(request_id.to_string(), kind, now);
+                if let (OrderRecordKind::TraderOpen, Some((order_type, price))) = (kind, requested)
+                {
+                    record = record.with_requested_entry(order_type, price);
+                }
+                self.push_order_record(index, record);
+            }
+            self.emit_event(event);
         }
+    }
 
-        let _ = self.sync_account_state(index).await?;
-        // Pre-validate against the risk engine before submitting
-        let initial_margin = self.zk_accounts.get_account(&index)?.balance;
-        self.validate_open_order(&order_side, initial_margin, leverage)
-            .await?;
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let r_scalar = self.zk_accounts.get_account(&index)?.get_scalar()?;
-        let initial_margin = self.zk_accounts.get_account(&index)?.balance;
-        let position_value = initial_margin
-            .checked_mul(leverage)
-            .ok_or_else(|| "position_value overflow".to_string())?;
-        let position_size = position_value
-            .checked_mul(entry_price)
-            .ok_or_else(|| "position_size overflow".to_string())?;
+    /// Append `record` to the account's order history and save it.
+    fn push_order_record(&self, index: AccountIndex, record: OrderRecord) {
         #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        let order_side_str = format!("{:?}", order_side);
-        let request_id = create_trader_order(
-            secret_key,
-            r_scalar,
-            initial_margin,
-            order_side,
-            order_type.clone(),
-            leverage,
-            entry_price,
-            position_value,
-            position_size,
-            &self.relayer_endpoint_config.relayer_program_json_path,
-            account_address.clone(),
-            &self.relayer_api_client,
-        )
-        .await?;
-        debug!(
-            "inserting request_id: {:?} for account index: {:?}",
-            request_id, index
-        );
-        self.cache_request_id(index, &request_id);
+        let saved = record.clone();
+        let _seq = self.order_records.update(index, |records| {
+            records.push(record);
+            records.len() - 1
+        });
+        // Written after the map's lock is released, which other accounts share.
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.save_order_record(index, _seq, &saved);
+    }
 
-        self.zk_accounts
-            .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
-        self.try_update_account_in_db(&index);
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn save_order_record(&self, index: AccountIndex, seq: usize, record: &OrderRecord) {
+        if let (Some(db_manager), false) = (&self.db_manager, self.dry_run) {
+            if let Err(e) = db_manager.save_order_record(index, seq, record, self.clock.now()) {
+                error!("Failed to save order record to database: {}", e);
+                self.emit_db_sync_failed(index, "save_order_record", &e);
+            }
+        }
+    }
 
+    /// Set the status of the account's latest `order` record to what a
+    /// query just returned, emitting [`WalletEvent::OrderStatusChanged`]
+    /// when it differs.
+    fn note_order_status(&self, index: AccountIndex, order: OrderKind, status: &OrderStatus) {
+        let status = status.to_str();
+        let mut changed = None;
+        self.order_records.update_existing(&index, |records| {
+            let Some(seq) = records.iter().rposition(|r| r.kind.order() == order) else {
+                return;
+            };
+            if records[seq].status == status {
+                return;
+            }
+            let from = std::mem::replace(&mut records[seq].status, status.to_string());
+            changed = Some((seq, records[seq].clone(), from));
+        });
+        if let Some((_seq, record, from)) = changed {
+            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+            self.save_order_record(index, _seq, &record);
+            let request_id = record.request_id;
+            self.emit_event_with(|occurred_at| WalletEvent::OrderStatusChanged {
+                account_index: index,
+                request_id,
+                order,
+                from,
+                to: status.to_string(),
+                occurred_at,
+            });
+        }
+    }
+
+    fn emit_event(&self, event: WalletEvent) {
+        debug!("wallet event: {:?}", event);
+        #[cfg(feature = "webhooks")]
+        self.webhooks.dispatch(&self.wallet.twilightaddress, &event);
+        self.events.send(event);
+    }
+
+    /// Build and emit an event only when a subscriber or webhook receives it.
+    fn emit_event_with(&self, event: impl FnOnce(DateTime<Utc>) -> WalletEvent) {
+        if self.has_event_receivers() {
+            self.emit_event(event(self.clock.now()));
+        }
+    }
+
+    fn has_event_receivers(&self) -> bool {
+        #[cfg(feature = "webhooks")]
+        if !self.webhooks.is_empty() {
+            return true;
+        }
+        self.events.has_subscribers()
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn emit_db_sync_failed(
+        &self,
+        index: AccountIndex,
+        operation: &str,
+        error: &impl std::fmt::Display,
+    ) {
+        self.emit_event_with(|occurred_at| WalletEvent::DbSyncFailed {
+            account_index: index,
+            operation: operation.to_string(),
+            error: error.to_string(),
+            occurred_at,
+        });
+    }
+
+    /// Set the tracked balance of `index`, emitting
+    /// [`WalletEvent::BalanceUpdated`] when it changes.
+    fn set_account_balance(
+        &self,
+        index: &AccountIndex,
+        balance: u64,
+    ) -> Result<(), ZkAccountError> {
+        let previous = if self.has_event_receivers() {
+            self.zk_accounts.get_balance(index).ok()
+        } else {
+            None
+        };
+        self.zk_accounts.update_balance(index, balance)?;
+        if let Some(previous) = previous.filter(|previous| *previous != balance) {
+            self.emit_event(WalletEvent::BalanceUpdated {
+                account_index: *index,
+                previous,
+                balance,
+                occurred_at: self.clock.now(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Count one operation in the hourly activity histogram, saving dirty
+    /// buckets to the database when they are due.
+    fn note_activity(&self, category: ActivityCategory) {
+        self.activity.record(category);
         #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        self.log_order_history(
-            index,
-            &request_id,
-            "open",
-            &format!("{:?}", order_type),
-            Some(&order_side_str),
-            initial_margin,
-            Some(entry_price as f64),
-            Some(leverage),
-            None,
-            "submitted",
-            None,
-        );
+        if self.activity.persist_due() {
+            if let Err(e) = self.persist_activity() {
+                error!("Failed to save activity counts to database: {}", e);
+            }
+        }
+    }
 
-        Ok(request_id)
+    /// Upsert activity buckets changed since the last save and prune rows
+    /// outside the retention window. No-op without a database.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn persist_activity(&self) -> Result<(), String> {
+        let Some(ref db_manager) = self.db_manager else {
+            return Ok(());
+        };
+        let counts = self.activity.take_dirty();
+        let cutoff = self.activity.cutoff(self.clock.now());
+        db_manager
+            .save_activity_counts(&counts, cutoff)
+            .inspect_err(|_| self.activity.mark_dirty(&counts))
     }
 
-    pub async fn close_trader_order(
-        &mut self,
+    /// Store a request ID and the idempotency key it was submitted with in
+    /// memory and sync to database.
+    fn cache_request_id(
+        &self,
         index: AccountIndex,
-        order_type: OrderType,
-        execution_price: f64,
-    ) -> Result<String, String> {
-        self.validate_market_not_halted().await?;
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let trader_order = self.query_trader_order(index).await?;
-        if trader_order.order_status != OrderStatus::FILLED {
-            if trader_order.order_status == OrderStatus::LIQUIDATE
-                || trader_order.order_status == OrderStatus::SETTLED
-            {
-                let (_, request_id) = self.unlock_trader_order(index).await?;
-                return Ok(request_id);
+        request_id: &str,
+        idempotency_key: Option<&str>,
+    ) {
+        self.request_ids.insert(index, request_id.to_string());
+        match idempotency_key {
+            Some(key) => {
+                self.idempotency_keys.insert(index, key.to_string());
+            }
+            None => {
+                self.idempotency_keys.remove(&index);
             }
-            return Err(format!(
-                "Order is not filled, status: {}",
-                trader_order.order_status.to_str()
-            ));
         }
-        let request_id = self.request_id(index)?;
-        let tx_hash = fetch_tx_hash_with_retry(request_id, &self.relayer_api_client).await?;
-        let output = tx_hash.get_output()?;
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if !self.dry_run {
+            if let Err(e) = self.sync_request_id_to_db(index, request_id, idempotency_key) {
+                error!("Failed to sync request ID to database: {}", e);
+                self.emit_db_sync_failed(index, "sync_request_id", &e);
+            }
+        }
+    }
 
-        let order_type_str = format!("{:?}", order_type);
-        self.sync_account_state(index).await?;
+    /// Build an authenticated `QueryTraderOrderZkos` for the given account.
+    fn build_trader_query(&self, index: AccountIndex) -> Result<QueryTraderOrderZkos, String> {
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let query_order =
+            encode_trader_order_query(&secret_key, &account_address, OrderStatus::PENDING);
+        self.signing_audit
+            .record(index, SigningPurpose::Query, query_order.as_bytes());
+        QueryTraderOrderZkos::decode_from_hex_string(query_order)
+    }
 
-        let request_id = close_trader_order_internal(
-            output,
-            &secret_key,
-            account_address.clone(),
-            tx_hash.order_id,
-            order_type.clone(),
-            execution_price,
-            &self.relayer_api_client,
-        )
-        .await?;
+    /// Build an authenticated `QueryLendOrderZkos` for the given account.
+    fn build_lend_query(&self, index: AccountIndex) -> Result<QueryLendOrderZkos, String> {
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let query_order =
+            encode_lend_order_query(&secret_key, &account_address, OrderStatus::LENDED);
+        self.signing_audit
+            .record(index, SigningPurpose::Query, query_order.as_bytes());
+        QueryLendOrderZkos::decode_from_hex_string(query_order)
+    }
 
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        self.log_order_history(
-            index,
-            &request_id,
-            "close",
-            &order_type_str,
-            Some(&format!("{:?}", trader_order.position_type)),
-            trader_order.available_margin as u64,
-            Some(execution_price),
-            Some(trader_order.leverage as u64),
-            Some(trader_order.unrealized_pnl),
-            "submitted",
-            None,
-        );
+    /// Put account `index` back in Coin state, dropping the fee estimate of
+    /// its order.
+    fn return_to_coin(&self, index: AccountIndex) -> Result<(), String> {
+        self.zk_accounts
+            .update_io_type(&index, IOType::Coin, None)?;
+        self.fee_estimates.remove(&index);
+        Ok(())
+    }
 
-        Ok(request_id)
+    /// Transition an account back to Coin state after an order settles.
+    /// Updates balance, IO type, QQ account, and UTXO cache.
+    fn settle_to_coin(
+        &self,
+        index: AccountIndex,
+        new_balance: u64,
+        utxo_detail: UtxoDetailResponse,
+    ) -> Result<(), String> {
+        self.set_account_balance(&index, new_balance)?;
+        self.return_to_coin(index)?;
+        let account = output_account(index, &utxo_detail)?;
+        self.zk_accounts.update_qq_account(&index, account)?;
+        self.cache_utxo(index, utxo_detail);
+        self.stamp_utxo(index, "settle_to_coin")?;
+        self.try_update_account_in_db(&index);
+        Ok(())
     }
 
-    pub async fn close_trader_order_sltp(
+    // -------------------------
+    // Chain resync
+    // -------------------------
+
+    /// Bring `index` back in line with the chain after its local state
+    /// diverged, e.g. through a crash between broadcast and save. Looks the
+    /// account up as a Coin and a Memo output and repairs `on_chain`,
+    /// `io_type`, the order kind, the balance (zero when spent, the committed
+    /// amount of a Coin when it can be derived), the cached UTXO detail and
+    /// the database rows. A Memo without an order kind gets the kind and
+    /// request ID of the relayer's latest order on the account. See
+    /// [`account_sync`](super::account_sync).
+    pub async fn resync_account(
         &mut self,
         index: AccountIndex,
-        order_type: OrderType,
-        execution_price: f64,
-        stop_loss_price: Option<f64>,
-        take_profit_price: Option<f64>,
-    ) -> Result<String, String> {
-        self.validate_market_not_halted().await?;
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let trader_order = self.query_trader_order(index).await?;
-        if trader_order.order_status != OrderStatus::FILLED {
-            if trader_order.order_status == OrderStatus::LIQUIDATE
-                || trader_order.order_status == OrderStatus::SETTLED
-            {
-                let (_, request_id) = self.unlock_trader_order(index).await?;
-                return Ok(request_id);
+    ) -> Result<AccountSyncReport, String> {
+        let address = self.zk_accounts.get_account_address(&index)?;
+        let chain = probe_chain_utxo(self.utxo_fetcher.clone(), address).await?;
+        let mut report = self.apply_chain_utxo(index, chain)?;
+        self.identify_memo_order(&mut report).await;
+        Ok(report)
+    }
+
+    /// [`resync_account`](Self::resync_account) for every active account
+    /// except simulated ones, probing up to [`RESYNC_CONCURRENCY`] at once.
+    /// Accounts whose chain state cannot be determined, including those
+    /// whose probe panicked, are listed in [`ResyncSummary::failed`] and
+    /// left unchanged.
+    pub async fn resync_all(&mut self) -> ResyncSummary {
+        let mut summary = ResyncSummary::default();
+        let mut probes = Vec::new();
+        for account in self.zk_accounts.get_all_accounts() {
+            if account.simulated {
+                continue;
+            }
+            let index = account.index;
+            let fetcher = self.utxo_fetcher.clone();
+            probes.push(async move {
+                let context = format!("account {}", index);
+                let probe = probe_chain_utxo(fetcher, account.account);
+                let chain = compat::guard_async("probe_chain_utxo", &context, probe)
+                    .await
+                    .unwrap_or_else(|panicked| Err(panicked.to_string()));
+                (index, chain)
+            });
+        }
+        let mut probed = run_bounded(probes, RESYNC_CONCURRENCY).await;
+        probed.sort_by_key(|(index, _)| *index);
+        for (index, chain) in probed {
+            match chain.and_then(|chain| self.apply_chain_utxo(index, chain)) {
+                Ok(mut report) => {
+                    self.identify_memo_order(&mut report).await;
+                    summary.accounts.push(report);
+                }
+                Err(e) => {
+                    warn!(
+                        "Resync: cannot determine the state of account {}: {}",
+                        index, e
+                    );
+                    summary.failed.push((index, e));
+                }
             }
-            return Err(format!(
-                "Invalid request, order status is {}",
-                trader_order.order_status.to_str()
-            ));
         }
+        info!(
+            "Resynced {} accounts with the chain: {} repaired, {} failed",
+            summary.accounts.len(),
+            summary.repaired().count(),
+            summary.failed.len()
+        );
+        summary
+    }
 
-        let request_id = self.request_id(index)?;
-        let tx_hash = fetch_tx_hash_with_retry(request_id, &self.relayer_api_client).await?;
-        let output = tx_hash.get_output()?;
-        // #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        let order_type_str = format!("{:?}", order_type);
-        let request_id = close_trader_order_sltp_internal(
-            output,
-            &secret_key,
-            account_address.clone(),
-            tx_hash.order_id,
-            order_type,
-            execution_price,
-            stop_loss_price,
-            take_profit_price,
-            &self.relayer_api_client,
+    /// Recover accounts missing from local state, e.g. after re-importing the
+    /// wallet from its mnemonic without the database. Derives the address of
+    /// every index in `0..=max_index` that is neither active nor archived,
+    /// probes it as a Coin and a Memo output and adds an account for each
+    /// output found, with the balance decrypted from a Coin where possible.
+    /// The next account index moves past the highest recovered one. Nothing
+    /// is added when any address cannot be probed, so a failed scan can be
+    /// run again.
+    ///
+    /// Only addresses derived from the seed are found. Accounts created
+    /// before addresses were derived deterministically are found by
+    /// [`scan_minted_accounts`](OrderWallet::scan_minted_accounts); those
+    /// whose address was replaced by a transfer have to be restored from a
+    /// backup.
+    pub async fn scan_derived_accounts(
+        &mut self,
+        max_index: u64,
+    ) -> Result<Vec<RecoveredAccount>, String> {
+        let key_manager = self
+            .seed
+            .with_seed(|seed| KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes()))?;
+        let mut candidates = Vec::new();
+        for index in 0..=max_index {
+            if self.zk_accounts.resolve_account(&index).is_err() {
+                let account = self
+                    .seed
+                    .with_seed(|seed| ZkAccount::from_seed(index, seed, 0))??;
+                candidates.push(account);
+            }
+        }
+        self.recover_from_chain(&key_manager, candidates, "scan_derived_accounts")
+            .await
+    }
+
+    /// [`scan_derived_accounts`](OrderWallet::scan_derived_accounts) for
+    /// accounts created before addresses were derived from the seed, whose
+    /// addresses were randomized. Searches the chain for the mints the
+    /// wallet signed and recovers each funded account owned by the key of
+    /// an index in `0..=max_index` that is neither active nor archived,
+    /// starting from the latest mint to that key. Accounts only ever funded
+    /// by a transfer are not found.
+    pub async fn scan_minted_accounts(
+        &mut self,
+        max_index: u64,
+    ) -> Result<Vec<RecoveredAccount>, String> {
+        let minted = fetch_minted_accounts(
+            &self.wallet.chain_config.lcd_endpoint,
+            &self.wallet.twilightaddress,
         )
         .await?;
-
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        {
-            if stop_loss_price.is_some() {
-                self.log_order_history(
+        let key_manager = self
+            .seed
+            .with_seed(|seed| KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes()))?;
+        let mut candidates = Vec::new();
+        for index in 0..=max_index {
+            if self.zk_accounts.resolve_account(&index).is_ok() {
+                continue;
+            }
+            let secret_key = key_manager.derive_child_key(index);
+            let owned = minted.iter().rev().find_map(|mint| {
+                let qq_account = EncryptedAccount::from_hex_str(mint.qq_account.clone()).ok()?;
+                qq_account
+                    .verify_keypair(&secret_key)
+                    .then(|| (qq_account.get_address(), mint))
+            });
+            if let Some((address, mint)) = owned {
+                candidates.push(ZkAccount::new(
+                    mint.qq_account.clone(),
+                    0,
+                    address,
+                    mint.encrypt_scalar.clone(),
                     index,
-                    &request_id,
-                    "close_sl",
-                    &order_type_str,
-                    Some(&format!("{:?}", trader_order.position_type)),
-                    trader_order.available_margin as u64,
-                    stop_loss_price,
-                    Some(trader_order.leverage as u64),
-                    Some(trader_order.unrealized_pnl),
-                    "submitted",
-                    None,
-                );
+                ));
             }
         }
-        if take_profit_price.is_some() {
-            self.log_order_history(
-                index,
-                &request_id,
-                "close_tp",
-                &order_type_str,
-                Some(&format!("{:?}", trader_order.position_type)),
-                trader_order.available_margin as u64,
-                take_profit_price,
-                Some(trader_order.leverage as u64),
-                Some(trader_order.unrealized_pnl),
-                "submitted",
-                None,
+        self.recover_from_chain(&key_manager, candidates, "scan_minted_accounts")
+            .await
+    }
+
+    /// Probe the address of every candidate account and add those with an
+    /// output on chain; nothing is added when any probe fails or panics.
+    async fn recover_from_chain(
+        &mut self,
+        key_manager: &KeyManager,
+        candidates: Vec<ZkAccount>,
+        origin: &str,
+    ) -> Result<Vec<RecoveredAccount>, String> {
+        let mut probes = Vec::new();
+        for account in candidates {
+            let fetcher = self.utxo_fetcher.clone();
+            probes.push(async move {
+                let context = format!("account {}", account.index);
+                let probe = probe_chain_utxo(fetcher, account.account.clone());
+                let chain = compat::guard_async("probe_chain_utxo", &context, probe)
+                    .await
+                    .unwrap_or_else(|panicked| Err(panicked.to_string()));
+                (account, chain)
+            });
+        }
+        let mut probed = run_bounded(probes, RESYNC_CONCURRENCY).await;
+        probed.sort_by_key(|(account, _)| account.index);
+        let mut found = Vec::new();
+        for (account, chain) in probed {
+            match chain {
+                Ok(ChainUtxo::Absent) => {}
+                Ok(chain) => found.push((account, chain)),
+                Err(e) => {
+                    return Err(format!("Failed to probe account {}: {}", account.index, e));
+                }
+            }
+        }
+
+        let mut recovered = Vec::new();
+        for (account, chain) in found {
+            let index = account.index;
+            let address = account.account.clone();
+            let chain_state = chain.state();
+            let Some(utxo_detail) = chain.into_detail() else {
+                continue;
+            };
+            let balance = if chain_state == ChainUtxoState::Coin {
+                let secret_key = key_manager.derive_child_key(index);
+                output_account(index, &utxo_detail)
+                    .ok()
+                    .and_then(|qq| committed_amount(&qq, &secret_key, 0))
+            } else {
+                None
+            };
+            self.zk_accounts
+                .restore_account(restore_from_chain(account, chain_state, balance))?;
+            self.try_save_new_account_to_db(&index);
+            let fetched_at = self.clock.now();
+            self.apply_fetched_utxo(index, utxo_detail, origin, fetched_at)?;
+            info!(
+                "Recovered account {} from the chain ({:?})",
+                index, chain_state
             );
+            recovered.push(RecoveredAccount {
+                account_index: index,
+                address,
+                chain_state,
+                balance,
+            });
         }
-        Ok(request_id)
+        Ok(recovered)
     }
 
-    pub async fn query_trader_order(&mut self, index: AccountIndex) -> Result<TraderOrder, String> {
-        debug!("query_trader_order for account index: {:?}", index);
-        let query = self.build_trader_query(index)?;
-        match self.relayer_api_client.trader_order_info(query).await {
-            Ok(order) => Ok(order),
+    /// Give a resynced Memo without an order kind the kind and request ID
+    /// read from the relayer's records of its address ([`memo_order`]). A
+    /// failed lookup is logged and leaves the account as it is; see
+    /// [`identify_memo_orders`](Self::identify_memo_orders).
+    async fn identify_memo_order(&self, report: &mut AccountSyncReport) {
+        let index = report.account_index;
+        let Ok(account) = self.zk_accounts.get_account(&index) else {
+            return;
+        };
+        if report.chain_state != ChainUtxoState::Memo || account.tx_type.is_some() {
+            return;
+        }
+        let (tx_type, request_id) = match self.lookup_memo_order(&account.account).await {
+            Ok(Some(order)) => order,
+            Ok(None) => {
+                warn!(
+                    "Resync: the relayer has no order for Memo account {}",
+                    index
+                );
+                return;
+            }
             Err(e) => {
-                let request_id = self.request_id(index)?;
-                let tx_hash = fetch_tx_hash_with_once(request_id, &self.relayer_api_client).await?;
-                if tx_hash.order_status != OrderStatus::PENDING
-                    && tx_hash.order_status != OrderStatus::FILLED
-                    && tx_hash.order_status != OrderStatus::LIQUIDATE
-                {
-                    self.unlock_failed_order(index).await?;
-                    return Err(format!(
-                        "Lend order failed, status: {}, reason: {}",
-                        tx_hash.order_status.to_str(),
-                        tx_hash.reason.unwrap_or_default()
-                    ));
-                } else {
-                    return Err(e.to_string());
-                }
+                warn!(
+                    "Resync: cannot look up the order of Memo account {}: {}",
+                    index, e
+                );
+                return;
             }
+        };
+        if let Err(e) = self
+            .zk_accounts
+            .update_io_type(&index, IOType::Memo, Some(tx_type.clone()))
+        {
+            warn!("Resync: cannot mark the order of account {}: {}", index, e);
+            return;
         }
+        self.try_update_account_in_db(&index);
+        // Any request ID still cached is of an order settled before.
+        self.cache_request_id(index, &request_id, None);
+        info!(
+            "Resync: Memo account {} holds order request {} ({:?})",
+            index, request_id, tx_type
+        );
+        report.fixes.push(AccountFix::OrderIdentified {
+            tx_type,
+            request_id,
+        });
     }
 
-    /// Query enhanced trader order info (v1) with settle_limit, take_profit, stop_loss, funding_applied.
-    pub async fn query_trader_order_v1(
+    fn apply_chain_utxo(
         &mut self,
         index: AccountIndex,
-    ) -> Result<super::relayer_types::TraderOrderV1, String> {
-        let query = self.build_trader_query(index)?;
-        match self.relayer_api_client.trader_order_info_v1(query).await {
-            Ok(order) => Ok(order),
-            Err(e) => {
-                let request_id = self.request_id(index)?;
-                let tx_hash = fetch_tx_hash_with_once(request_id, &self.relayer_api_client).await?;
-                if tx_hash.order_status != OrderStatus::PENDING
-                    && tx_hash.order_status != OrderStatus::FILLED
-                    && tx_hash.order_status != OrderStatus::LIQUIDATE
-                {
-                    self.unlock_failed_order(index).await?;
-                    return Err(format!(
-                        "Lend order failed, status: {}, reason: {}",
-                        tx_hash.order_status.to_str(),
-                        tx_hash.reason.unwrap_or_default()
-                    ));
-                } else {
-                    return Err(e.to_string());
+        chain: ChainUtxo,
+    ) -> Result<AccountSyncReport, String> {
+        let chain_state = chain.state();
+        let mut fixes = {
+            let mut accounts = self.zk_accounts.write();
+            let account = accounts.get_mut_account(&index)?;
+            let fixes = plan_fixes(account, chain_state);
+            for fix in &fixes {
+                fix.apply(account);
+            }
+            fixes
+        };
+
+        let cached = self.utxo_details.get(&index);
+        match chain.into_detail() {
+            Some(utxo_detail) => {
+                let changed = match &cached {
+                    Some(cached) => {
+                        serde_json::to_value(cached).ok() != serde_json::to_value(&utxo_detail).ok()
+                    }
+                    None => true,
+                };
+                let fetched_at = self.clock.now();
+                self.apply_fetched_utxo(index, utxo_detail, "resync_account", fetched_at)?;
+                if changed {
+                    fixes.push(AccountFix::UtxoDetailUpdated);
+                }
+                if chain_state == ChainUtxoState::Coin {
+                    let account = self.zk_accounts.get_account(&index)?;
+                    let secret_key = self.get_secret_key(index)?;
+                    let committed = account
+                        .get_qq_account()
+                        .ok()
+                        .and_then(|qq| committed_amount(&qq, &secret_key, account.balance));
+                    match committed {
+                        Some(balance) => {
+                            if balance != account.balance {
+                                fixes.push(AccountFix::Balance {
+                                    from: account.balance,
+                                    to: balance,
+                                });
+                                self.set_account_balance(&index, balance)?;
+                            }
+                            self.zk_accounts.set_balance_unverified(&index, false)?;
+                        }
+                        None => warn!(
+                            "Resync: could not derive the committed amount of account {}; keeping {} sats",
+                            index,
+                            LoggedAmount(account.balance)
+                        ),
+                    }
                 }
             }
+            None => {
+                if cached.is_some() {
+                    self.uncache_utxo(index);
+                    fixes.push(AccountFix::UtxoDetailRemoved);
+                }
+            }
+        }
+
+        if !fixes.is_empty() {
+            self.try_update_account_in_db(&index);
+            warn!(
+                "Account {} diverged from the chain ({:?}); applied {:?}",
+                index, chain_state, fixes
+            );
         }
+        Ok(AccountSyncReport {
+            account_index: index,
+            chain_state,
+            fixes,
+        })
     }
 
-    /// Query enhanced lend order info (v1) with unrealised profit and APR.
-    pub async fn query_lend_order_v1(
+    // -------------------------
+    // Funding Operations
+    // -------------------------
+
+    /// Top up the funding wallet from the testnet faucet with
+    /// [`get_test_tokens`](crate::wallet::get_test_tokens) and log the sats
+    /// received to transfer history as `faucet`, for
+    /// [`export_statement`](Self::export_statement). Returns the faucet's
+    /// report and the sats received; like the burn in `trading_to_funding`
+    /// this is the change in balance, so unrelated activity on the funding
+    /// address shows up in it too. Call this rather than `get_test_tokens`
+    /// on [`wallet`](Self::wallet) so the receipt is recorded.
+    pub async fn request_test_tokens(
         &mut self,
-        index: AccountIndex,
-    ) -> Result<super::relayer_types::LendOrderV1, String> {
-        let query = self.build_lend_query(index)?;
-        match self.relayer_api_client.lend_order_info_v1(query).await {
-            Ok(order) => Ok(order),
-            Err(e) => {
-                let request_id = self.request_id(index)?;
-                let tx_hash = fetch_tx_hash_with_once(request_id, &self.relayer_api_client).await?;
-                if tx_hash.order_status != OrderStatus::SETTLED
-                    && tx_hash.order_status != OrderStatus::FILLED
-                {
-                    self.unlock_failed_order(index).await?;
-                    return Err(format!(
-                        "Lend order failed, status: {}, reason: {}",
-                        tx_hash.order_status.to_str(),
-                        tx_hash.reason.unwrap_or_default()
-                    ));
-                } else {
-                    return Err(e.to_string());
-                }
-            }
+    ) -> Result<(crate::wallet::FaucetReport, u64), String> {
+        let before = self
+            .wallet
+            .update_balance()
+            .await
+            .map_err(|e| e.to_string())?;
+        let report = crate::wallet::get_test_tokens(&mut self.wallet)
+            .await
+            .map_err(|e| e.to_string())?;
+        let received = report.balance.sats.saturating_sub(before.sats);
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if received > 0 {
+            self.log_transfer_history("faucet", None, None, received, None);
         }
+        Ok((report, received))
     }
 
-    /// Query historical trader orders for an account.
-    pub async fn historical_trader_order(
+    pub async fn funding_to_trading(&mut self, amount: u64) -> OrderWalletResult<(TxResult, u64)> {
+        self.funding_to_new_account(amount, None).await
+    }
+
+    /// [`funding_to_trading`](Self::funding_to_trading) into an account named
+    /// `label`. The label is checked before anything is sent, so a duplicate
+    /// fails without creating an account.
+    pub async fn funding_to_trading_labeled(
         &mut self,
-        index: AccountIndex,
-    ) -> Result<Vec<TraderOrder>, String> {
-        let query = self.build_trader_query(index)?;
-        self.relayer_api_client
-            .historical_trader_order_info(query)
-            .await
-            .map_err(|e| e.to_string())
+        amount: u64,
+        label: &str,
+    ) -> OrderWalletResult<(TxResult, u64)> {
+        self.funding_to_new_account(amount, Some(label)).await
     }
 
-    /// Query historical lend orders for an account.
-    pub async fn historical_lend_order(
+    /// Fund one new trading account per entry of `amounts` from the wallet.
+    /// The mints of up to [`MAX_MINTS_PER_TX`] accounts go in each chain
+    /// transaction, and the UTXOs of all the accounts are fetched
+    /// concurrently once their transactions confirm, so a pool is funded in
+    /// about the time of one [`funding_to_trading`](Self::funding_to_trading).
+    /// Returns the transaction of each chunk and the funded accounts with
+    /// their balances, in the order of `amounts`.
+    ///
+    /// The total is checked against the wallet balance before any account is
+    /// created. When a chunk fails no further chunk is sent; the accounts of
+    /// the chunks before it are still synced, and the error is
+    /// [`PartialFunding`](OrderWalletError::PartialFunding), carrying the
+    /// transactions already on chain and the accounts they funded.
+    pub async fn funding_to_trading_multiple(
         &mut self,
-        index: AccountIndex,
-    ) -> Result<Vec<LendOrder>, String> {
-        let query = self.build_lend_query(index)?;
-        self.relayer_api_client
-            .historical_lend_order_info(query)
-            .await
-            .map_err(|e| e.to_string())
+        amounts: Vec<u64>,
+    ) -> OrderWalletResult<(Vec<TxResult>, Vec<AccountBalance>)> {
+        let requests = amounts.into_iter().map(|amount| (amount, None)).collect();
+        self.funding_to_new_accounts(requests).await
     }
 
-    /// Query funding payment history for a trader order on an account.
-    pub async fn order_funding_history(
+    async fn funding_to_new_account(
         &mut self,
-        index: AccountIndex,
-    ) -> Result<Vec<super::relayer_types::FundingHistoryEntry>, String> {
-        let query = self.build_trader_query(index)?;
-        self.relayer_api_client
-            .order_funding_history(query)
-            .await
-            .map_err(|e| e.to_string())
+        amount: u64,
+        label: Option<&str>,
+    ) -> OrderWalletResult<(TxResult, u64)> {
+        let (mut txs, funded) = self.funding_to_new_accounts(vec![(amount, label)]).await?;
+        match (txs.pop(), funded.first()) {
+            (Some(tx), Some(&(account_index, _))) => Ok((tx, account_index)),
+            _ => Err("Funding returned no account".into()),
+        }
     }
 
-    pub async fn cancel_trader_order(&mut self, index: AccountIndex) -> Result<String, String> {
-        self.validate_market_not_halted().await?;
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let trader_orderv1 = self.query_trader_order_v1(index).await?;
-        let trader_order = trader_orderv1.order;
-        let is_pending_limit = trader_order.order_status == OrderStatus::PENDING;
-        let is_close_limit = trader_orderv1.settle_limit.is_some();
-        if !is_pending_limit && !is_close_limit {
-            return Err(format!(
-                "Order is not pending or close limit, status: {}",
-                trader_order.order_status.to_str()
-            ));
+    async fn funding_to_new_accounts(
+        &mut self,
+        requests: Vec<(u64, Option<&str>)>,
+    ) -> OrderWalletResult<(Vec<TxResult>, Vec<AccountBalance>)> {
+        if requests.is_empty() {
+            return Err("No accounts to fund".into());
         }
-        let request_id = cancel_trader_order(
-            account_address.clone(),
-            &secret_key,
-            account_address.clone(),
-            trader_order.uuid,
-            &self.relayer_api_client,
-        )
-        .await?;
-        if is_pending_limit {
-            let tx_hash = fetch_tx_hash_with_retry(&request_id, &self.relayer_api_client).await?;
-            if tx_hash.order_status != OrderStatus::CANCELLED {
-                return Err(format!(
-                    "Order is not cancelled, status: {}",
-                    tx_hash.order_status.to_str()
-                ));
+        for (_, label) in &requests {
+            if let Some(label) = label {
+                if label.trim().is_empty() {
+                    return Err("Account label must not be empty".into());
+                }
+                if let Some(owner) = self.zk_accounts.get_by_label(label) {
+                    return Err(
+                        ZkAccountError::DuplicateLabel(label.to_string(), owner.index).into(),
+                    );
+                }
             }
+        }
+        let required = requests
+            .iter()
+            .fold(0u64, |total, (amount, _)| total.saturating_add(*amount));
+        let wallet_balance = self
+            .wallet
+            .update_balance()
+            .await
+            .map_err(|e| e.to_string())?;
+        // temporary disabled, will be re-enabled later if there is a need to check nyks balance
+        // if wallet_balance.nyks == 0 {
+        //     return Err("Insufficient balance".to_string());
+        // }
+        if wallet_balance.sats < required {
+            return Err(OrderWalletError::InsufficientBalance {
+                required,
+                available: wallet_balance.sats,
+            });
+        }
 
-            self.zk_accounts
-                .update_io_type(&index, IOType::Coin, None)?;
-            self.try_update_account_in_db(&index);
-
-            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-            {
-                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
-                self.log_order_history(
-                    index,
-                    &request_id,
-                    "cancel_pending",
-                    "LIMIT",
-                    None,
-                    balance,
-                    None,
-                    None,
-                    None,
-                    "cancelled",
-                    None,
-                );
+        let seed = self.seed.secret()?;
+        let mut accounts = Vec::with_capacity(requests.len());
+        for (amount, label) in requests {
+            let account_index = self.zk_accounts.generate_new_account(amount, &seed)?;
+            if let Some(label) = label {
+                self.zk_accounts
+                    .set_label(&account_index, Some(label.to_string()))?;
             }
+            accounts.push((account_index, amount));
         }
-        if is_close_limit {
-            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-            {
-                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
-                self.log_order_history(
-                    index,
-                    &request_id,
-                    "cancel_close_limit",
-                    "LIMIT",
-                    None,
-                    balance,
-                    None,
-                    None,
-                    None,
-                    "cancelled",
-                    None,
-                );
+        drop(seed);
+        if self.dry_run {
+            let mut txs = Vec::with_capacity(accounts.len());
+            for &(account_index, amount) in &accounts {
+                self.zk_accounts.mark_simulated(&account_index)?;
+                let result = self.dry_run_mint(account_index, amount).await;
+                self.record_account_outcome(account_index, "funding_to_trading", &result);
+                txs.push(result?);
             }
+            return Ok((txs, accounts));
         }
-        Ok(request_id)
-    }
 
-    pub async fn cancel_trader_order_sltp(
-        &mut self,
-        index: AccountIndex,
-        cancel_sl: bool,
-        cancel_tp: bool,
-    ) -> Result<String, String> {
-        self.validate_market_not_halted().await?;
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let trader_orderv1 = self.query_trader_order_v1(index).await?;
-        let trader_order = trader_orderv1.order;
-        let is_sl_cancellable = trader_orderv1.stop_loss.is_some();
-        let is_tp_cancellable = trader_orderv1.take_profit.is_some();
-        if !is_sl_cancellable && !is_tp_cancellable {
-            return Err("Order is not sl or tp cancellable".to_string());
+        // self.wallet
+        //     .update_account_info()
+        //     .await
+        //     .map_err(|e| e.to_string())?;
+
+        let mut intents = Vec::with_capacity(accounts.len());
+        for &(account_index, amount) in &accounts {
+            self.try_save_new_account_to_db(&account_index);
+            let intent = self.journal_intent(
+                PendingOperationKind::FundAccount,
+                OperationInputs::FundAccount {
+                    account_index,
+                    amount,
+                },
+                vec![OperationStep::ConfirmFunding {
+                    account_index,
+                    amount,
+                }],
+            );
+            intents.push((account_index, intent));
         }
-        if trader_order.order_status != OrderStatus::FILLED {
-            return Err(format!(
-                "Order is not filled, status: {}",
-                trader_order.order_status.to_str()
-            ));
+        let (txs, funded, failure) = self.fund_new_accounts(&accounts).await;
+        for (account_index, intent) in intents {
+            let result = if funded.iter().any(|(index, _)| *index == account_index) {
+                Ok(())
+            } else {
+                Err(failure
+                    .as_ref()
+                    .map_or_else(|| "Account was not funded".to_string(), |e| e.to_string()))
+            };
+            self.finish_intent(intent, &result);
         }
-        let sltp_cancel = SlTpOrderCancel::new(cancel_sl, cancel_tp);
-        let request_id = cancel_trader_order_sltp(
-            account_address.clone(),
-            &secret_key,
-            account_address.clone(),
-            trader_order.uuid,
-            sltp_cancel,
-            &self.relayer_api_client,
-        )
-        .await?;
-        if cancel_sl && is_sl_cancellable {
-            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-            {
-                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
-                self.log_order_history(
-                    index,
-                    &request_id,
-                    "cancel_sl",
-                    "SLTP",
-                    None,
-                    balance,
-                    None,
-                    None,
-                    None,
-                    "sl_cancelled",
-                    None,
-                );
+        funding_outcome(txs, funded, failure)
+    }
+
+    /// Mint `accounts`, `(index, amount)` pairs, in chunks of
+    /// [`MAX_MINTS_PER_TX`], then sync and reconcile every minted account.
+    /// Returns the chunk transactions, the funded accounts and the first
+    /// error, which stops the chunks; each account's outcome is recorded.
+    async fn fund_new_accounts(
+        &mut self,
+        accounts: &[(AccountIndex, u64)],
+    ) -> (Vec<TxResult>, Vec<AccountBalance>, Option<OrderWalletError>) {
+        let mut txs = Vec::new();
+        let mut minted = Vec::new();
+        let mut failure = None;
+        for chunk in accounts.chunks(MAX_MINTS_PER_TX) {
+            let msgs = chunk
+                .iter()
+                .map(|&(index, amount)| {
+                    mint_burn_trading_btc_msg(
+                        &self.wallet,
+                        &self.zk_accounts.read(),
+                        index,
+                        amount,
+                        true,
+                    )
+                })
+                .collect::<Result<Vec<_>, String>>();
+            let result = match msgs {
+                Ok(msgs) => self.send_and_confirm_mint_burn_msgs(msgs).await,
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Ok(result) => {
+                    for &(index, amount) in chunk {
+                        minted.push((index, amount, result.tx_hash.clone()));
+                    }
+                    txs.push(result);
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
             }
         }
-        if cancel_tp && is_tp_cancellable {
-            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-            {
-                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
-                self.log_order_history(
-                    index,
-                    &request_id,
-                    "cancel_tp",
-                    "SLTP",
-                    None,
-                    balance,
-                    None,
-                    None,
-                    None,
-                    "tp_cancelled",
-                    None,
-                );
+        if let Some(e) = &failure {
+            let e = e.to_string();
+            for &(index, _) in &accounts[minted.len()..] {
+                self.record_account_outcome(index, "funding_to_trading", &Err::<(), _>(&e));
             }
         }
-        Ok(request_id)
-    }
 
-    /// Check if a previously closed (e.g. SLTP) trader order has settled and, if so,
-    /// unlock the account by refreshing its UTXO, balance, and IO type back to `Coin`.
-    ///
-    /// Returns the current `OrderStatus` so the caller can decide what to do next.
-    pub async fn unlock_trader_order(
+        let mut fetches = Vec::new();
+        for &(index, _, _) in &minted {
+            let address = self.zk_accounts.get_account_address(&index);
+            let fetcher = self.utxo_fetcher.clone();
+            fetches.push(async move {
+                let utxo_detail = match address {
+                    Ok(address) => fetcher.fetch(address, IOType::Coin).await,
+                    Err(e) => Err(e),
+                };
+                (index, utxo_detail)
+            });
+        }
+        let concurrency = fetches.len();
+        let mut fetched: HashMap<AccountIndex, Result<UtxoDetailResponse, String>> =
+            run_bounded(fetches, concurrency)
+                .await
+                .into_iter()
+                .collect();
+
+        let mut funded = Vec::with_capacity(minted.len());
+        for (index, amount, tx_hash) in minted {
+            let fetched_at = self.clock.now();
+            let result = self
+                .zk_accounts
+                .update_on_chain(&index, true)
+                .map_err(String::from)
+                .and_then(|()| {
+                    self.try_update_account_in_db(&index);
+                    let synced = fetched
+                        .remove(&index)
+                        .unwrap_or_else(|| Err("UTXO fetch did not complete".to_string()))
+                        .and_then(|utxo_detail| {
+                            self.apply_fetched_utxo(
+                                index,
+                                utxo_detail,
+                                "funding_to_trading",
+                                fetched_at,
+                            )
+                        });
+                    self.settle_committed_balance(
+                        index,
+                        amount,
+                        "funding_to_trading",
+                        Some(tx_hash.as_str()),
+                        synced,
+                    )
+                });
+            match &result {
+                Ok(balance) => {
+                    self.note_account_funded(index, *balance, &tx_hash);
+                    funded.push((index, *balance));
+                }
+                Err(e) => {
+                    failure.get_or_insert_with(|| e.clone().into());
+                }
+            }
+            self.record_account_outcome(index, "funding_to_trading", &result);
+        }
+        (txs, funded, failure)
+    }
+
+    /// Emit [`WalletEvent::AccountFunded`] for `account_index`, funded with
+    /// `funded` sats by `tx_hash`, and log the transfer.
+    fn note_account_funded(&self, account_index: AccountIndex, funded: u64, tx_hash: &str) {
+        self.emit_event_with(|occurred_at| WalletEvent::AccountFunded {
+            account_index,
+            amount: funded,
+            tx_hash: tx_hash.to_string(),
+            occurred_at,
+        });
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.log_transfer_history(
+            "fund_to_trade",
+            None,
+            Some(account_index),
+            funded,
+            Some(tx_hash),
+        );
+    }
+
+    /// Spending input of the cached UTXO of `index`.
+    fn utxo_input(&self, index: AccountIndex) -> Result<Input, String> {
+        let utxo_detail = self
+            .utxo_details
+            .get(&index)
+            .ok_or("UTXO detail not found")?;
+        compat::guard_result("get_input", &format!("account {}", index), || {
+            utxo_detail.get_input()
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    /// Build a transfer of `amount` from `index` to `receiver`, leaving
+    /// `remaining` on `index`, returning the transaction and the receiver's
+    /// encryption scalar.
+    fn build_single_transfer(
+        &self,
+        index: AccountIndex,
+        input: Input,
+        receiver: String,
+        amount: u64,
+        remaining: u64,
+    ) -> WalletResult<(Transaction, String)> {
+        let context = format!(
+            "account {}, amount {}, receiver of {} chars",
+            index,
+            amount,
+            receiver.len()
+        );
+        let secret_key = self
+            .get_secret_key(index)
+            .map_err(WalletError::ZkAccountSeedNotFound)?;
+        let transfer = compat::guard("single_receiver", &context, || {
+            self.transfer_builder
+                .single_receiver(secret_key, input, receiver, amount, false, remaining, 1u64)
+        })?;
+        let tx = transfer.tx.ok_or_else(|| WalletError::ClientSdk {
+            operation: "single_receiver".to_string(),
+            detail: format!("{}: no transaction built", context),
+        })?;
+        Ok((tx, transfer.encrypt_scalar_hex))
+    }
+
+    /// Broadcast `tx`, spent from `index`, on a blocking thread. The outer
+    /// error is a failed or panicked task; the inner one the broadcaster's.
+    async fn broadcast_tx(
+        &self,
+        index: AccountIndex,
+        tx: Transaction,
+    ) -> WalletResult<Result<String, String>> {
+        let broadcaster = self.chain_broadcaster.clone();
+        tokio::task::spawn_blocking(move || broadcaster.broadcast(tx))
+            .await
+            .map_err(|e| compat::join_error("broadcast", &format!("account {}", index), e))
+    }
+
+    //  -> Result<(TxResult, u64), String>
+    pub async fn trading_to_trading(
         &mut self,
         index: AccountIndex,
-    ) -> Result<(OrderStatus, String), String> {
-        let trader_order = self.query_trader_order(index).await?;
+    ) -> OrderWalletResult<AccountIndex> {
+        self.ensure_not_dry_run("trading_to_trading")?;
+        let result = self.trading_to_trading_inner(index).await;
+        self.record_account_outcome(index, "trading_to_trading", &result);
+        result.map_err(Into::into)
+    }
 
-        if trader_order.order_status != OrderStatus::SETTLED
-            && trader_order.order_status != OrderStatus::LIQUIDATE
+    /// Send the whole balance of account `index` to a ZkOS `address` outside
+    /// this wallet, after the receiver checks selected by `options` (see
+    /// [`receiver_check`](super::receiver_check)). Check failures are returned
+    /// before anything is signed. `address` may be an address book label; see
+    /// [`resolve_recipient`](Self::resolve_recipient).
+    pub async fn transfer_to_address(
+        &mut self,
+        index: AccountIndex,
+        address: &str,
+        options: &TransferOptions,
+    ) -> Result<TxResult, ReceiverCheckError> {
+        let amount = self
+            .zk_accounts
+            .get_account(&index)
+            .map_err(|e| ReceiverCheckError::Transfer(e.into()))?
+            .balance;
+        self.transfer_amount_to_address(index, address, amount, options)
+            .await
+    }
+
+    /// Send `amount` sats of Coin account `index` to a ZkOS `address`, with
+    /// the checks of [`transfer_to_address`](Self::transfer_to_address). The
+    /// rest stays on `index`, which keeps its on-chain state with the fresh
+    /// UTXO of the transfer; sending the whole balance leaves it off chain.
+    /// Accounts holding an order (Memo) are refused. When `address` is one of
+    /// this wallet's own accounts, that account is resynced afterwards so the
+    /// funds show up on it.
+    pub async fn transfer_amount_to_address(
+        &mut self,
+        index: AccountIndex,
+        address: &str,
+        amount: u64,
+        options: &TransferOptions,
+    ) -> Result<TxResult, ReceiverCheckError> {
+        self.ensure_not_dry_run("transfer_to_address")
+            .map_err(ReceiverCheckError::Transfer)?;
+        let address = &self.resolve_recipient(address, AddressKind::ZkOs, options.allow_unknown)?;
+        self.check_receiver(index, address, amount, options)?;
+        let result = self.transfer_to_address_inner(index, address, amount).await;
+        self.record_account_outcome(index, "transfer_to_address", &result);
+        result.map_err(ReceiverCheckError::Transfer)
+    }
+
+    /// Run the receiver checks of [`transfer_to_address`](Self::transfer_to_address)
+    /// for sending `amount` sats from account `index`.
+    pub fn check_receiver(
+        &self,
+        index: AccountIndex,
+        address: &str,
+        amount: u64,
+        options: &TransferOptions,
+    ) -> Result<(), ReceiverCheckError> {
+        if options.check_format {
+            let sender = self
+                .zk_accounts
+                .get_account_address(&index)
+                .map_err(|e| ReceiverCheckError::Transfer(e.into()))?;
+            validate_address(address, network_byte(&sender))?;
+        }
+        match &options.ownership_proof {
+            Some(proof) => verify_ownership_proof(address, proof)?,
+            None if options.require_ownership_proof => {
+                return Err(ReceiverCheckError::ProofRequired(address.to_string()));
+            }
+            None => {}
+        }
+        if amount > options.confirmation_threshold
+            && !options.confirm_new_receiver
+            && !self.is_known_receiver(address)
         {
+            return Err(ReceiverCheckError::ConfirmationRequired {
+                address: address.to_string(),
+                amount,
+                threshold: options.confirmation_threshold,
+            });
+        }
+        Ok(())
+    }
+
+    /// Mark `address` as a known receiver, so transfers to it skip the
+    /// first-transfer confirmation. Addresses are also added after every
+    /// successful `transfer_to_address`.
+    pub fn add_known_receiver(&mut self, address: &str) {
+        self.known_receivers.insert(address.to_string());
+    }
+
+    /// Whether `address` is a known receiver, in the address book or one of
+    /// this wallet's own accounts.
+    pub fn is_known_receiver(&self, address: &str) -> bool {
+        self.known_receivers.contains(address)
+            || self.address_book.verify(address).is_some()
+            || self
+                .zk_accounts
+                .get_all_accounts()
+                .iter()
+                .any(|account| account.account == address)
+    }
+
+    async fn transfer_to_address_inner(
+        &mut self,
+        index: AccountIndex,
+        address: &str,
+        amount: u64,
+    ) -> Result<TxResult, String> {
+        if amount == 0 {
+            return Err("Transfer amount must be greater than zero".to_string());
+        }
+        let own_receiver = self
+            .zk_accounts
+            .get_all_accounts()
+            .into_iter()
+            .find(|account| account.account == address)
+            .map(|account| account.index);
+        if own_receiver == Some(index) {
+            return Err(format!("Account {} cannot transfer to itself", index));
+        }
+        if self.zk_accounts.get_account(&index)?.io_type == IOType::Memo {
             return Err(format!(
-                "Order is not settled or liquidated, status: {}",
-                trader_order.order_status.to_str()
+                "Account {} holds an order (Memo state); close or cancel it before transferring",
+                index
             ));
         }
-        info!(
-            "PnL: {:?}, Net PnL: {:?}, Available Margin: {:?}",
-            trader_order.unrealized_pnl,
-            trader_order.available_margin - trader_order.initial_margin,
-            trader_order.available_margin
+        self.sync_account_state(index).await?;
+        let sender_account = self.zk_accounts.get_account(&index)?;
+        self.ensure_zk_account_onchain(&sender_account)?;
+        if amount > sender_account.balance {
+            return Err(OrderWalletError::InsufficientBalance {
+                required: amount,
+                available: sender_account.balance,
+            }
+            .to_string());
+        }
+        let remaining = sender_account.balance - amount;
+        let input = self.utxo_input(index)?;
+        let (tx, _) = self
+            .build_single_transfer(index, input, address.to_string(), amount, remaining)
+            .map_err(|e| e.to_string())?;
+
+        let response = self
+            .broadcast_tx(index, tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let tx_hash = response.map_err(|e| format!("Failed to broadcast transfer: {:?}", e))?;
+        debug!("transfer_to_address tx hash: {}", tx_hash);
+        let finalize = OperationStep::FinalizeSender {
+            account_index: index,
+            remaining_balance: remaining,
+        };
+        let intent = self.journal_intent(
+            PendingOperationKind::TransferToAddress,
+            OperationInputs::TransferToAddress {
+                account_index: index,
+                address: address.to_string(),
+                amount,
+                tx_hash: tx_hash.clone(),
+            },
+            vec![finalize.clone()],
         );
-        let account_address = self.zk_accounts.get_account_address(&index)?.to_string();
-        let tx_hash = fetch_tx_hash_with_account_address_retry(
-            &account_address,
-            Some(trader_order.order_status.clone()),
-            &self.relayer_api_client,
-        )
-        .await?;
-        let request_id = tx_hash.request_id.unwrap_or_default();
-        let utxo_detail = fetch_utxo_details_with_retry(account_address, IOType::Coin).await?;
-        self.settle_to_coin(index, trader_order.available_margin as u64, utxo_detail)?;
 
+        self.known_receivers.insert(address.to_string());
         #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        self.log_order_history(
-            index,
-            &request_id.to_string(),
-            "close",
-            "MARKET",
-            Some(&format!("{:?}", trader_order.position_type)),
-            trader_order.available_margin as u64,
-            Some(0.0),
-            Some(trader_order.leverage as u64),
-            Some(trader_order.unrealized_pnl),
-            &format!("{}", trader_order.order_status.to_str()),
-            Some(&tx_hash.tx_hash.clone()),
+        self.log_transfer_history(
+            "trade_to_address",
+            Some(index),
+            own_receiver,
+            amount,
+            Some(tx_hash.as_str()),
         );
 
-        Ok((trader_order.order_status, request_id))
+        // Keeps the remainder on the sender's fresh UTXO, or takes it off chain.
+        let finalized = self.run_operation_step(&finalize).await;
+        self.finish_intent(intent.clone(), &finalized);
+        if let Err(e) = finalized {
+            return Err(format!(
+                "Transfer {} was broadcast, but account {} could not be finalized: {}. Retry with `resume_operation(\"{}\")`",
+                tx_hash,
+                index,
+                e,
+                intent.unwrap_or_default()
+            ));
+        }
+        if let Some(receiver) = own_receiver {
+            if let Err(e) = self.resync_account(receiver).await {
+                warn!(
+                    "Transfer from account {} reached own account {}, which could not be resynced yet: {}",
+                    index, receiver, e
+                );
+            }
+        }
+        Ok(TxResult {
+            tx_hash,
+            code: 0,
+            simulated: false,
+            attempts: Vec::new(),
+            log: None,
+        })
     }
-    /// Check if a previously closed lend order has settled and, if so,
-    /// unlock the account by refreshing its UTXO, balance, and IO type back to `Coin`.
-    ///
-    /// Returns the current `OrderStatus` so the caller can decide what to do next.
-    pub async fn unlock_lend_order(
+
+    async fn trading_to_trading_inner(
         &mut self,
         index: AccountIndex,
-    ) -> Result<(OrderStatus, String), String> {
-        let lend_order = self.query_lend_order(index).await?;
+    ) -> Result<AccountIndex, String> {
+        self.sync_account_state(index).await?;
+        let sender_account = self.zk_accounts.get_account(&index)?;
+        self.ensure_zk_account_onchain(&sender_account)?;
+        let amount = sender_account.balance;
+        let seed = self.seed.secret()?;
+        let new_account_index = self.zk_accounts.generate_new_account(amount, &seed)?;
+        drop(seed);
+        self.try_save_new_account_to_db(&new_account_index);
 
-        if lend_order.order_status != OrderStatus::SETTLED {
-            return Err(format!(
-                "Order is not settled, status: {}",
-                lend_order.order_status.to_str()
-            ));
-        }
-        info!(
-            "PnL: {:?}, Available Margin: {:?}",
-            lend_order.new_lend_state_amount - lend_order.deposit,
-            lend_order.new_lend_state_amount
+        let receiver_input_string = self.zk_accounts.get_account(&new_account_index)?.account;
+        let input = self.utxo_input(index)?;
+        let (tx, encrypt_scalar) = self
+            .build_single_transfer(index, input, receiver_input_string, amount, 0)
+            .map_err(|e| e.to_string())?;
+
+        let intent = self.journal_intent(
+            PendingOperationKind::RotateAccount,
+            OperationInputs::RotateAccount {
+                sender_account_index: index,
+                receiver_account_index: new_account_index,
+                balance: amount,
+            },
+            vec![
+                OperationStep::FinalizeRotation {
+                    account_index: new_account_index,
+                    balance: amount,
+                    encrypt_scalar: encrypt_scalar.clone(),
+                },
+                OperationStep::FinalizeSender {
+                    account_index: index,
+                    remaining_balance: 0,
+                },
+            ],
         );
-        let account_address = self.zk_accounts.get_account_address(&index)?.to_string();
-        let tx_hash = fetch_tx_hash_with_account_address_retry(
-            &account_address,
-            Some(lend_order.order_status.clone()),
-            &self.relayer_api_client,
-        )
+        let result = self
+            .send_rotation(index, new_account_index, amount, tx, encrypt_scalar)
+            .await;
+        self.finish_intent(intent, &result);
+        result
+    }
+
+    /// Broadcast `tx`, which moves the whole balance of `index` to
+    /// `new_account_index`, and finish both accounts.
+    async fn send_rotation(
+        &mut self,
+        index: AccountIndex,
+        new_account_index: AccountIndex,
+        amount: Balance,
+        tx: Transaction,
+        encrypt_scalar: String,
+    ) -> Result<AccountIndex, String> {
+        let response = self
+            .broadcast_tx(index, tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        debug!("trading_to_trading response: {:?}", response);
+
+        self.run_operation_step(&OperationStep::FinalizeRotation {
+            account_index: new_account_index,
+            balance: amount,
+            encrypt_scalar,
+        })
         .await?;
-        let request_id = tx_hash.request_id.unwrap_or_default();
-        let utxo_detail = fetch_utxo_details_with_retry(account_address, IOType::Coin).await?;
-        self.settle_to_coin(index, lend_order.new_lend_state_amount as u64, utxo_detail)?;
+        self.run_operation_step(&OperationStep::FinalizeSender {
+            account_index: index,
+            remaining_balance: 0,
+        })
+        .await?;
+        self.carry_label(index, new_account_index)?;
 
         #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        self.log_order_history(
-            index,
-            &request_id.to_string(),
-            "close",
-            "LEND",
-            None,
-            lend_order.new_lend_state_amount as u64,
-            None,
-            None,
-            Some(lend_order.new_lend_state_amount - lend_order.deposit),
-            &format!("{}", lend_order.order_status.to_str()),
-            Some(&tx_hash.tx_hash.clone()),
-        );
+        {
+            let tx_hash = match &response {
+                Ok(hash) => Some(hash.as_str()),
+                Err(_) => None,
+            };
+            self.log_transfer_history(
+                "trade_to_trade",
+                Some(index),
+                Some(new_account_index),
+                amount,
+                tx_hash,
+            );
+        }
+        self.emit_event_with(|occurred_at| WalletEvent::AccountRotated {
+            account_index: index,
+            new_account_index,
+            balance: amount,
+            occurred_at,
+        });
 
-        Ok((lend_order.order_status, request_id))
+        Ok(new_account_index)
     }
 
-    pub async fn unlock_failed_order(&mut self, index: AccountIndex) -> Result<(), String> {
-        let account_address = self.zk_accounts.get_account_address(&index)?.to_string();
-        let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
-        let utxo_detail = fetch_utxo_details_with_once(account_address, IOType::Coin).await?;
-        self.zk_accounts.update_balance(&index, balance)?;
-        self.zk_accounts
-            .update_io_type(&index, IOType::Coin, None)?;
-        let account = utxo_detail.output.to_quisquis_account()?;
-        self.zk_accounts.update_qq_account(&index, account)?;
-        self.cache_utxo(index, utxo_detail);
-        self.try_update_account_in_db(&index);
+    /// Move the label of `from` to `to`, which its whole balance moved to, so
+    /// the name stays with the funds. A `to` with a label of its own keeps it.
+    fn carry_label(&self, from: AccountIndex, to: AccountIndex) -> Result<(), String> {
+        let Some(label) = self.zk_accounts.get_account(&from)?.label else {
+            return Ok(());
+        };
+        if self.zk_accounts.get_account(&to)?.label.is_some() {
+            return Ok(());
+        }
+        self.zk_accounts.set_label(&from, None)?;
+        self.zk_accounts.set_label(&to, Some(label))?;
+        self.try_update_account_in_db(&from);
+        self.try_update_account_in_db(&to);
         Ok(())
     }
-    // -------------------------
-    // Lend Order Operations
-    // -------------------------
 
-    pub async fn open_lend_order(&mut self, index: AccountIndex) -> Result<String, String> {
-        self.validate_market_not_halted().await?;
-        self.ensure_coin_onchain(index)?;
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        // let _utxo_detail =
-        //     fetch_utxo_details_with_retry(account_address.clone(), IOType::Coin).await?;
+    /// Burn the whole balance of Coin account `old_index` back to the
+    /// on-chain wallet. The balance is first moved to a fresh account, whose
+    /// output the burn spends; that account ends off-chain with a zero
+    /// balance and no cached UTXO. Returns the mint/burn transaction result.
+    pub async fn trading_to_funding(
+        &mut self,
+        old_index: AccountIndex,
+    ) -> OrderWalletResult<TxResult> {
+        self.ensure_not_dry_run("trading_to_funding")?;
+        self.ensure_coin_onchain(old_index)?;
+        let index = self.trading_to_trading(old_index).await?;
+
+        let result = self.burn_to_funding(old_index, index).await;
+        self.record_account_outcome(index, "trading_to_funding", &result);
+        result.map_err(Into::into)
+    }
+
+    async fn burn_to_funding(
+        &mut self,
+        old_index: AccountIndex,
+        index: AccountIndex,
+    ) -> Result<TxResult, String> {
         self.sync_account_state(index).await?;
-        let secret_key = self.get_secret_key(index);
-        let scalar_hex: String = self.zk_accounts.get_account(&index)?.scalar.clone();
-        let amount = self.zk_accounts.get_account(&index)?.balance;
+        let input = self.utxo_input(index)?;
 
-        let request_id = create_lend_order(
-            account_address.clone(),
-            secret_key,
-            amount,
-            &self.relayer_endpoint_config.relayer_program_json_path,
-            scalar_hex,
-            &self.relayer_api_client,
+        let stored_balance = self.zk_accounts.get_account(&index)?.balance;
+        let amount =
+            self.reconcile_committed_balance(index, stored_balance, "trading_to_funding", None)?;
+        let sender_account = self.zk_accounts.get_account(&index)?;
+        let encrypt_scalar = sender_account.scalar.clone();
+        let sk = self.get_secret_key(index)?;
+        let context = format!("account {}, amount {}", index, amount);
+        let tx_hex = compat::guard("burn_message", &context, || {
+            self.transfer_builder.burn_message(
+                input,
+                amount,
+                encrypt_scalar,
+                sk,
+                sender_account.account.clone(),
+            )
+        })
+        .map_err(|e| e.to_string())?;
+        let transaction = bincode::deserialize(&hex::decode(tx_hex).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        let _tx_hash = self
+            .broadcast_tx(index, transaction)
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to get tx hash: {}", e))?;
+        //waiting for the utxo to be removed
+        let _ = fetch_removed_utxo_details_with_policy(
+            self.zk_accounts.get_account_address(&index)?,
+            IOType::Coin,
+            self.relayer_api_client.retry_policy(),
+            self.clock.as_ref(),
         )
         .await?;
-        self.cache_request_id(index, &request_id);
 
-        // let utxo_detail = fetch_utxo_details_with_retry(account_address, IOType::Memo).await?;
-        // self.cache_utxo(index, utxo_detail);
-        self.zk_accounts
-            .update_io_type(&index, IOType::Memo, Some(TXType::LENDTX))?;
+        let sats_before = self.wallet.update_balance().await.ok().map(|b| b.sats);
+        let result = self.send_and_confirm_mint_burn(index, amount, false).await?;
+        self.uncache_utxo(index);
+        self.zk_accounts.update_on_chain(&index, false)?;
+        self.set_account_balance(&index, 0)?;
         self.try_update_account_in_db(&index);
+        // Best effort: unrelated activity on the funding address between the two
+        // reads would show up here too.
+        let sats_after = self.wallet.update_balance().await.ok().map(|b| b.sats);
+        if let (Some(before), Some(after)) = (sats_before, sats_after) {
+            let credited = after.saturating_sub(before);
+            if credited != amount {
+                self.record_amount_discrepancy(
+                    index,
+                    "trading_to_funding",
+                    amount,
+                    credited,
+                    Some(result.tx_hash.as_str()),
+                );
+            }
+        }
 
         #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        self.log_order_history(
-            index,
-            &request_id,
-            "open",
-            "LEND",
+        self.log_transfer_history(
+            "trade_to_fund",
+            Some(old_index),
             None,
             amount,
-            None,
-            None,
-            None,
-            "submitted",
-            None,
+            Some(&result.tx_hash),
         );
 
-        Ok(request_id)
+        Ok(result)
     }
-
-    pub async fn query_lend_order(&mut self, index: AccountIndex) -> Result<LendOrder, String> {
-        let query = self.build_lend_query(index)?;
-        match self.relayer_api_client.lend_order_info(query).await {
-            Ok(order) => Ok(order),
-            Err(e) => {
-                let request_id = self.request_id(index)?;
-                let tx_hash = fetch_tx_hash_with_once(request_id, &self.relayer_api_client).await?;
-                if tx_hash.order_status != OrderStatus::SETTLED
-                    && tx_hash.order_status != OrderStatus::FILLED
-                {
-                    self.unlock_failed_order(index).await?;
-                    return Err(format!(
-                        "Lend order failed, status: {}, reason: {}",
-                        tx_hash.order_status.to_str(),
-                        tx_hash.reason.unwrap_or_default()
-                    ));
-                } else {
-                    return Err(e.to_string());
-                }
-            }
+    /// Split a single Coin account into multiple new Coin accounts as specified by `balances`.
+    /// Requirements:
+    /// - Sender must be on-chain in Coin state and have at least sum(balances)
+    /// - `balances` must be non-empty, without zero amounts
+    /// - Recommended to create at most 8 accounts per call due to tx size limits
+    ///
+    /// Fails only while nothing has been sent. Once the transaction is on
+    /// chain, every receiver and the sender are finalized independently and
+    /// the [`MultiTransferOutcome`] lists each new account as confirmed or
+    /// unconfirmed. The sender's balance is updated either way. Unconfirmed
+    /// accounts stay in the split's pending record, for
+    /// [`confirm_pending_receivers`](Self::confirm_pending_receivers) to retry.
+    pub async fn trading_to_trading_multiple_accounts(
+        &mut self,
+        sender_account_index: AccountIndex,
+        balances: Vec<Balance>,
+    ) -> OrderWalletResult<MultiTransferOutcome> {
+        self.ensure_not_dry_run("trading_to_trading_multiple_accounts")?;
+        let num_of_new_accounts = balances.len();
+        if num_of_new_accounts == 0 || num_of_new_accounts > 9 {
+            return Err("No new accounts to create".into());
         }
-    }
+        if let Some(position) = balances.iter().position(|b| *b == 0) {
+            return Err(format!("Receiver {} has a zero balance", position + 1).into());
+        }
+        self.ensure_coin_onchain(sender_account_index)?;
+        let sk = self.get_secret_key(sender_account_index)?;
 
-    pub async fn close_lend_order(&mut self, index: AccountIndex) -> Result<String, String> {
-        self.validate_market_not_halted().await?;
-        self.sync_account_state(index).await?;
-        let account_address = self.zk_accounts.get_account_address(&index)?;
-        let secret_key = self.get_secret_key(index);
-        let lend_order = self.query_lend_order(index).await?;
-        if lend_order.order_status == OrderStatus::SETTLED {
-            let (_, request_id) = self.unlock_lend_order(index).await?;
-            return Ok(request_id);
+        self.sync_account_state(sender_account_index).await?;
+        let input_sender = self.utxo_input(sender_account_index)?;
+
+        let mut new_account_balances = Vec::new();
+        let mut commitment_scalar_vec = Vec::new();
+        let mut receiver_vec = Vec::new();
+        let mut updated_reciever_balance_vec = Vec::new();
+        let sender_transfering_amt = balances.iter().sum::<Balance>();
+        let sender_account = self.zk_accounts.get_account(&sender_account_index)?;
+        if sender_account.balance < sender_transfering_amt {
+            return Err(OrderWalletError::InsufficientBalance {
+                required: sender_transfering_amt,
+                available: sender_account.balance,
+            });
         }
-        if lend_order.order_status != OrderStatus::FILLED {
-            return Err(format!(
-                "Order is not filled, status: {}",
-                lend_order.order_status.to_str()
+        let updated_sender_balance = sender_account.balance - sender_transfering_amt;
+        let seed = self.seed.secret()?;
+        for balance in balances {
+            let new_account_index = self.zk_accounts.generate_new_account(0, &seed)?;
+            new_account_balances.push((new_account_index, balance));
+            commitment_scalar_vec.push(
+                self.zk_accounts
+                    .get_account(&new_account_index)?
+                    .get_scalar()?,
+            );
+            receiver_vec.push(Receiver::set_receiver(
+                balance as i64,
+                self.zk_accounts
+                    .get_account(&new_account_index)?
+                    .get_qq_account()?,
             ));
+            updated_reciever_balance_vec.push(balance);
         }
-        let request_id = self.request_id(index)?;
-        let tx_hash = fetch_tx_hash_with_retry(request_id, &self.relayer_api_client).await?;
-        let output = tx_hash.get_output()?;
-        let request_id = close_lend_order(
-            output,
-            &secret_key,
-            account_address.clone(),
-            tx_hash.order_id,
-            OrderType::LEND,
-            &self.relayer_api_client,
-        )
-        .await?;
+        drop(seed);
+        let sender_array = vec![Sender::set_sender(
+            (sender_transfering_amt as i64) * -1,
+            sender_account.get_qq_account()?,
+            receiver_vec,
+        )];
+        let context = format!(
+            "account {}, {} receivers",
+            sender_account_index, num_of_new_accounts
+        );
+        let transfer = compat::guard_result("multiple_receivers", &context, || {
+            self.transfer_builder.multiple_receivers(
+                sender_array,
+                input_sender,
+                sk,
+                vec![updated_sender_balance],
+                updated_reciever_balance_vec,
+                Some(&commitment_scalar_vec),
+                1u64,
+            )
+        })
+        .map_err(|e| e.to_string())?;
+        let tx = transfer.tx.ok_or("Failed to get tx")?;
+        let outputs = tx.get_tx_outputs();
+        let encrypt_scalar = transfer.encrypt_scalars;
 
-        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-        {
-            let pnl = lend_order.new_lend_state_amount - lend_order.deposit;
-            self.log_order_history(
-                index,
-                &request_id,
-                "close",
-                "LEND",
-                None,
-                lend_order.new_lend_state_amount as u64,
-                None,
-                None,
-                Some(pnl),
-                "submitted",
-                None,
-            );
+        // Everything a step needs is read from the transaction before it is
+        // sent, so the bookkeeping after broadcast cannot fail on it.
+        let mut steps = Vec::with_capacity(new_account_balances.len() + 1);
+        for (i, (new_account_index, balance)) in new_account_balances.iter().enumerate() {
+            let account_key = outputs
+                .get(i + 1)
+                .and_then(|output| {
+                    output
+                        .as_output_data()
+                        .get_owner_address()
+                        .map(|address| address.to_string())
+                })
+                .ok_or("Failed to get owner address")?;
+            steps.push(OperationStep::FinalizeReceiver {
+                account_index: *new_account_index,
+                balance: *balance,
+                encrypt_scalar: encrypt_scalar
+                    .get(i)
+                    .ok_or("Failed to get receiver scalar")?
+                    .to_string(),
+                account_key,
+            });
         }
+        steps.push(OperationStep::FinalizeSender {
+            account_index: sender_account_index,
+            remaining_balance: updated_sender_balance,
+        });
 
-        Ok(request_id)
-    }
+        let response = self
+            .broadcast_tx(sender_account_index, tx.clone())
+            .await
+            .map_err(|e| e.to_string())?;
 
-    // -------------------------
-    // Database Operations
-    // -------------------------
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn sync_zk_account_to_db(
-        &self,
-        account: &crate::zkos_accounts::zkaccount::ZkAccount,
-    ) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            db_manager.save_zk_account(account)?;
+        debug!(
+            "trading_to_trading_multiple_accounts response: {:?}",
+            response
+        );
+        if let Err(e) = response {
+            return Err(format!("Failed to send RPC request: {}", e).into());
         }
-        Ok(())
+
+        // The transfer is on chain; the rest is per-account bookkeeping that can be
+        // resumed later for the accounts it fails on.
+        let op = PendingOperation::new(
+            PendingOperationKind::SplitAccount,
+            OperationInputs::SplitAccount {
+                sender_account_index,
+                balances: new_account_balances.iter().map(|(_, b)| *b).collect(),
+            },
+            steps,
+            self.clock.now(),
+        );
+        Ok(self.drive_split(op).await)
     }
 
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn update_zk_account_in_db(
-        &self,
-        account: &crate::zkos_accounts::zkaccount::ZkAccount,
-    ) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            db_manager.update_zk_account(account)?;
+    /// Move exactly `amount` sats from Coin account `sender` to a new Coin
+    /// account and return the new account's index. The rest stays on
+    /// `sender`, which remains on chain with the fresh UTXO of the transfer;
+    /// moving the whole balance leaves it off chain as
+    /// [`trading_to_trading`](Self::trading_to_trading) does. This is a split
+    /// into one receiver; when an account cannot be finalized after
+    /// broadcast, the error names it and
+    /// [`confirm_pending_receivers`](Self::confirm_pending_receivers) retries.
+    pub async fn trading_to_trading_partial(
+        &mut self,
+        sender: AccountIndex,
+        amount: Balance,
+    ) -> OrderWalletResult<AccountIndex> {
+        if amount == 0 {
+            return Err("Transfer amount must be greater than zero".into());
         }
-        Ok(())
+        let available = self.zk_accounts.get_account(&sender)?.balance;
+        if amount > available {
+            return Err(OrderWalletError::InsufficientBalance {
+                required: amount,
+                available,
+            });
+        }
+        let outcome = self
+            .trading_to_trading_multiple_accounts(sender, vec![amount])
+            .await?;
+        if let Some((index, e)) = outcome.unconfirmed.first() {
+            return Err(format!(
+                "Transfer from account {} is on chain but account {} is unconfirmed: {}",
+                sender, index, e
+            )
+            .into());
+        }
+        outcome
+            .confirmed
+            .first()
+            .map(|(index, _)| *index)
+            .ok_or_else(|| "Transfer created no receiver account".into())
     }
 
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn remove_zk_account_from_db(&self, account_index: u64) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            db_manager.remove_zk_account(account_index)?;
+    /// Retry the outstanding steps of every pending split, e.g. receivers
+    /// whose UTXO was not queryable yet when
+    /// [`trading_to_trading_multiple_accounts`](Self::trading_to_trading_multiple_accounts)
+    /// returned. Records flagged for resolution are skipped. The outcome lists
+    /// the accounts confirmed by this call and those still unconfirmed.
+    pub async fn confirm_pending_receivers(&mut self) -> Result<MultiTransferOutcome, String> {
+        self.ensure_not_dry_run("confirm_pending_receivers")?;
+        let mut outcome = MultiTransferOutcome::default();
+        for op in self.pending_operations() {
+            if op.kind != PendingOperationKind::SplitAccount || op.needs_resolution() {
+                continue;
+            }
+            outcome.extend(self.drive_split(op).await);
         }
-        Ok(())
+        Ok(outcome)
     }
 
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn get_all_zk_accounts_from_db(
-        &self,
-    ) -> Result<HashMap<u64, crate::zkos_accounts::zkaccount::ZkAccount>, String> {
-        if let Some(ref db_manager) = self.db_manager {
-            db_manager.load_all_zk_accounts()
-        } else {
-            Err("Database manager not initialized".to_string())
+    /// Run every remaining step of the split `op`, each regardless of how
+    /// the others went. Steps that fail stay in the record, which is stored
+    /// (memory + DB) while any remain.
+    async fn drive_split(&mut self, mut op: PendingOperation) -> MultiTransferOutcome {
+        let mut outcome = MultiTransferOutcome::default();
+        for step in op.remaining_steps.clone() {
+            let index = step.account_index();
+            match self.run_operation_step(&step).await {
+                Ok(()) => {
+                    if let OperationStep::FinalizeReceiver { balance, .. } = &step {
+                        outcome.confirmed.push((index, *balance));
+                    }
+                    op.complete_step(&step, self.clock.now());
+                }
+                Err(e) => {
+                    warn!(
+                        "{} operation {}: account {} unconfirmed: {}",
+                        op.kind, op.id, index, e
+                    );
+                    op.fail(e.clone(), self.clock.now());
+                    outcome.unconfirmed.push((index, e));
+                }
+            }
+        }
+        if !op.is_done() {
+            error!(
+                "{} operation {} left {} accounts unconfirmed. Retry with `confirm_pending_receivers()`",
+                op.kind,
+                op.id,
+                outcome.unconfirmed.len()
+            );
+            self.store_pending_operation(op);
+        } else if self.has_pending_operation(&op.id) {
+            self.store_pending_operation(op);
         }
+        outcome
     }
+    // -------------------------
+    // Pending (resumable) operations
+    // -------------------------
 
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    /// Enable database persistence with automatic password prompt
-    pub fn enable_database_persistence(
-        &mut self,
-        wallet_password: Option<SecretString>,
-        wallet_id: Option<String>,
-    ) -> Result<(), String> {
-        let wallet_password = match wallet_password {
-            Some(password) => password,
-            None => SecurePassword::get_passphrase_with_prompt(
-                "Could not find passphrase from environment, \nplease enter wallet encryption password: ",
-            )
-            .map_err(|e| format!("Failed to get password: {}", e))?,
-        };
-
-        // Generate wallet ID from wallet address
-        let wallet_id = match wallet_id {
-            Some(id) => id,
-            None => self.wallet.twilightaddress.clone(),
-        };
-
-        // Initialize database connection and run migrations
-        let pool = crate::database::connection::init_pool(None)?;
-        run_migrations_once(&pool)?;
+    /// Composite operations that ended partially completed and can be resumed.
+    pub fn pending_operations(&self) -> Vec<PendingOperation> {
+        let mut ops: Vec<PendingOperation> = self
+            .pending_ops
+            .with(|ops| ops.values().filter(|op| !op.is_done()).cloned().collect());
+        ops.sort_by_key(|op| op.created_at);
+        ops
+    }
 
-        // Create database manager
-        // let wallet_list = DatabaseManager::get_wallet_list(&pool)?;
-        // if wallet_list.iter().any(|w| w.wallet_id == wallet_id) {
-        //     return Err(format!("Wallet ID already exists: {}", wallet_id));
-        // }
-        if DatabaseManager::check_wallet_id_exists(&pool, &wallet_id)? {
-            return Err(format!("Wallet ID already exists: {}", wallet_id));
-        }
-        let db_manager = DatabaseManager::new(wallet_id, pool);
-        // Save encrypted wallet if password is provided
+    fn pending_operation(&self, id: &str) -> Option<PendingOperation> {
+        self.pending_ops.with(|ops| ops.get(id).cloned())
+    }
 
-        db_manager.save_encrypted_wallet(&self.wallet, &wallet_password)?;
+    fn has_pending_operation(&self, id: &str) -> bool {
+        self.pending_ops.with(|ops| ops.contains_key(id))
+    }
 
-        // Save existing zk accounts
-        for account in self.zk_accounts.get_all_accounts() {
-            db_manager.save_zk_account(account)?;
+    /// Re-execute the remaining steps of a pending operation using its recorded inputs.
+    /// Returns the updated record (status `Done` on success).
+    pub async fn resume_operation(&mut self, id: &str) -> Result<PendingOperation, String> {
+        self.ensure_not_dry_run("resume_operation")?;
+        let op = self
+            .pending_operation(id)
+            .ok_or(format!("Pending operation not found: {}", id))?;
+        if op.is_done() {
+            return Ok(op);
         }
-
-        self.db_manager = Some(db_manager);
-        self.wallet_password = Some(wallet_password);
-        Ok(())
+        info!(
+            "Resuming {} operation {} ({} steps remaining)",
+            op.kind,
+            op.id,
+            op.remaining_steps.len()
+        );
+        self.drive_operation(op).await
     }
 
-    /// Save the OrderWallet configuration to database
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn save_order_wallet_to_db(&self) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            if let Some(ref password) = self.wallet_password {
-                db_manager.save_order_wallet(
-                    &self.chain_id,
-                    self.seed.expose_secret(),
-                    &self.relayer_endpoint_config,
-                    password.expose_secret(),
-                )?;
-
-                Ok(())
-            } else {
-                Err("No password available for encryption".to_string())
+    /// Finish what a restart interrupted, oldest record first: resume every
+    /// pending record that is not flagged for resolution. A journaled
+    /// operation whose step fails because an endpoint is unreachable stays
+    /// pending for the next call. Any other failure flags it
+    /// [`NeedsResolution`](PendingOperationStatus::NeedsResolution) rather
+    /// than left for a retry: its transaction or request may never have been
+    /// sent, which only the operator can tell. Returns the updated records.
+    pub async fn resume_pending(&mut self) -> Result<Vec<PendingOperation>, String> {
+        self.ensure_not_dry_run("resume_pending")?;
+        let mut resumed = Vec::new();
+        for op in self.pending_operations() {
+            if op.needs_resolution() {
+                continue;
             }
-        } else {
-            Ok(()) // No database persistence enabled
+            let e = match self.resume_operation(&op.id).await {
+                Ok(done) => {
+                    resumed.push(done);
+                    continue;
+                }
+                Err(e) => e,
+            };
+            let Some(mut failed) = self.pending_operation(&op.id) else {
+                return Err(e);
+            };
+            let reason = failed.last_error.clone().unwrap_or(e);
+            if failed.kind.is_journaled() && is_unreachable_error(&reason) {
+                warn!(
+                    "{} operation {} could not reach an endpoint and stays pending: {}",
+                    failed.kind, failed.id, reason
+                );
+            } else if failed.kind.is_journaled() {
+                warn!(
+                    "{} operation {} needs manual resolution: {}",
+                    failed.kind, failed.id, reason
+                );
+                failed.flag_for_resolution(reason, self.clock.now());
+                self.store_pending_operation(failed.clone());
+            }
+            resumed.push(failed);
         }
+        Ok(resumed)
     }
 
-    /// Sync UTXO detail to database
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn sync_utxo_detail_to_db(
+    /// Close a pending record, e.g. one flagged for resolution, after the
+    /// account has been checked by hand. Its remaining steps are not run.
+    pub fn mark_operation_resolved(&mut self, id: &str) -> Result<PendingOperation, String> {
+        let mut op = self
+            .pending_operation(id)
+            .ok_or(format!("Pending operation not found: {}", id))?;
+        op.complete(self.clock.now());
+        info!("{} operation {} marked resolved", op.kind, op.id);
+        self.store_pending_operation(op.clone());
+        Ok(op)
+    }
+
+    /// Write-ahead record of an operation about to send a transaction or
+    /// relayer request; `None` in dry-run mode, which sends nothing.
+    fn journal_intent(
         &self,
-        account_index: u64,
-        utxo_detail: &UtxoDetailResponse,
-    ) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            db_manager.save_utxo_detail(account_index, utxo_detail)?;
+        kind: PendingOperationKind,
+        inputs: OperationInputs,
+        steps: Vec<OperationStep>,
+    ) -> Option<String> {
+        if self.dry_run {
+            return None;
         }
-        Ok(())
+        let op = PendingOperation::new(kind, inputs, steps, self.clock.now());
+        let id = op.id.clone();
+        self.store_pending_operation(op);
+        Some(id)
     }
 
-    /// Sync request ID to database
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn sync_request_id_to_db(
+    fn journal_order(
         &self,
-        account_index: u64,
-        request_id: &str,
-    ) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            db_manager.save_request_id(account_index, request_id)?;
+        kind: PendingOperationKind,
+        index: AccountIndex,
+        order: OrderKind,
+        params: String,
+    ) -> Option<String> {
+        self.journal_intent(
+            kind,
+            OperationInputs::Order {
+                account_index: index,
+                order,
+                params,
+            },
+            vec![OperationStep::ReconcileOrder {
+                account_index: index,
+                order,
+            }],
+        )
+    }
+
+    /// Settle the intent `id` once its call returned: done when the call
+    /// succeeded. A failed call may have failed after its transaction or
+    /// request went out, so its intent keeps the remaining steps, with the
+    /// error, for [`resume_pending`](Self::resume_pending).
+    fn finish_intent<T, E: std::fmt::Display>(&self, id: Option<String>, result: &Result<T, E>) {
+        let Some(mut op) = id.and_then(|id| self.pending_operation(&id)) else {
+            return;
+        };
+        match result {
+            Ok(_) => op.complete(self.clock.now()),
+            Err(e) => op.fail(e.to_string(), self.clock.now()),
         }
-        Ok(())
+        self.store_pending_operation(op);
     }
 
-    /// Load all UTXO details from database
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn load_all_utxo_details_from_db(&mut self) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            let utxo_details = db_manager.load_all_utxo_details()?;
-            self.utxo_details = utxo_details;
+    /// Bring `index` in line with its order on the relayer: adopt an order an
+    /// unconfirmed submit created, then apply what the
+    /// [order watcher](super::order_watcher) would for the order's status.
+    /// Without an order, the account's `Coin` UTXO is fetched again.
+    async fn reconcile_order(
+        &mut self,
+        index: AccountIndex,
+        order: OrderKind,
+    ) -> Result<(), String> {
+        if let Some((submission, request_id)) = self.resolve_pending_submission(index).await? {
+            self.adopt_submitted_order(&submission, &request_id).await?;
+        }
+        let io_type = self.zk_accounts.get_account(&index)?.io_type;
+        let status = match order {
+            OrderKind::Trader => self.query_trader_order(index).await.map(|o| o.order_status),
+            OrderKind::Lend => self.query_lend_order(index).await.map(|o| o.order_status),
+        };
+        match status {
+            Ok(status) => match Reconcile::for_status(order, &status, None) {
+                Some(step) => order_watcher::reconcile(self, index, order, step).await,
+                None => Ok(()),
+            },
+            Err(e) if io_type == IOType::Coin => {
+                debug!("No {} order on account {}: {}", order.as_str(), index, e);
+                self.sync_account_state(index)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
         }
-        Ok(())
     }
 
-    /// Load all request IDs from database
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn load_all_request_ids_from_db(&mut self) -> Result<(), String> {
-        if let Some(ref db_manager) = self.db_manager {
-            let request_ids = db_manager.load_all_request_ids()?;
-            self.request_ids = request_ids;
+    /// Run the remaining steps of `op` in order. On failure the record is stored
+    /// (memory + DB) with the failing step still outstanding.
+    async fn drive_operation(&mut self, mut op: PendingOperation) -> Result<PendingOperation, String> {
+        while let Some(step) = op.remaining_steps.first().cloned() {
+            if let Err(e) = self.run_operation_step(&step).await {
+                op.fail(e.clone(), self.clock.now());
+                let total = op.completed_steps.len() + op.remaining_steps.len();
+                let msg = format!(
+                    "{} operation {} partially completed ({}/{} steps): {}. Resume with `resume_operation(\"{}\")`",
+                    op.kind,
+                    op.id,
+                    op.completed_steps.len(),
+                    total,
+                    e,
+                    op.id
+                );
+                error!("{}", msg);
+                self.store_pending_operation(op);
+                return Err(msg);
+            }
+            op.complete_next_step(self.clock.now());
+        }
+        if let OperationInputs::RotateAccount {
+            sender_account_index,
+            receiver_account_index,
+            ..
+        } = op.inputs
+        {
+            self.carry_label(sender_account_index, receiver_account_index)?;
+        }
+        if self.has_pending_operation(&op.id) {
+            self.store_pending_operation(op.clone());
+        }
+        Ok(op)
+    }
+
+    async fn run_operation_step(&mut self, step: &OperationStep) -> Result<(), String> {
+        match step {
+            OperationStep::FinalizeReceiver {
+                account_index,
+                balance,
+                encrypt_scalar,
+                account_key,
+            } => {
+                let utxo_detail = self
+                    .utxo_fetcher
+                    .fetch(
+                        self.zk_accounts.get_account_address(account_index)?,
+                        IOType::Coin,
+                    )
+                    .await?;
+                self.cache_utxo(*account_index, utxo_detail.clone());
+                self.zk_accounts.update_on_chain(account_index, true)?;
+                self.set_account_balance(account_index, *balance)?;
+                let account = output_account(*account_index, &utxo_detail)?;
+                self.zk_accounts.update_qq_account(account_index, account)?;
+                self.zk_accounts.update_scalar(account_index, encrypt_scalar)?;
+                self.zk_accounts
+                    .update_account_key(account_index, account_key)?;
+                self.try_update_account_in_db(account_index);
+            }
+            OperationStep::FinalizeSender {
+                account_index,
+                remaining_balance,
+            } => {
+                if *remaining_balance > 0 {
+                    // The balance goes with the new output's commitment: an
+                    // unfetched UTXO leaves both as they were.
+                    let utxo_detail = self
+                        .utxo_fetcher
+                        .fetch(
+                            self.zk_accounts.get_account_address(account_index)?,
+                            IOType::Coin,
+                        )
+                        .await?;
+                    let account = output_account(*account_index, &utxo_detail)?;
+                    self.set_account_balance(account_index, *remaining_balance)?;
+                    self.zk_accounts.update_qq_account(account_index, account)?;
+                    self.cache_utxo(*account_index, utxo_detail);
+                    self.try_update_account_in_db(account_index);
+                } else {
+                    self.set_account_balance(account_index, 0)?;
+                    self.zk_accounts.update_on_chain(account_index, false)?;
+                    self.try_update_account_in_db(account_index);
+                    self.uncache_utxo(*account_index);
+                }
+            }
+            OperationStep::ConfirmFunding {
+                account_index,
+                amount,
+            } => {
+                // Fails while the mint's UTXO cannot be found.
+                self.sync_account_state(*account_index)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.zk_accounts.update_on_chain(account_index, true)?;
+                self.reconcile_committed_balance(
+                    *account_index,
+                    *amount,
+                    "funding_to_trading",
+                    None,
+                )?;
+                self.try_update_account_in_db(account_index);
+            }
+            OperationStep::FinalizeRotation {
+                account_index,
+                balance,
+                encrypt_scalar,
+            } => {
+                let utxo_detail = self
+                    .utxo_fetcher
+                    .fetch(
+                        self.zk_accounts.get_account_address(account_index)?,
+                        IOType::Coin,
+                    )
+                    .await?;
+                self.cache_utxo(*account_index, utxo_detail.clone());
+                self.zk_accounts.update_on_chain(account_index, true)?;
+                self.set_account_balance(account_index, *balance)?;
+                let account = output_account(*account_index, &utxo_detail)?;
+                self.zk_accounts.update_qq_account(account_index, account)?;
+                self.zk_accounts
+                    .update_scalar(account_index, encrypt_scalar)?;
+                // The next order on the account can reuse this fetch.
+                self.stamp_utxo(*account_index, "trading_to_trading")?;
+                self.try_update_account_in_db(account_index);
+            }
+            OperationStep::ReconcileOrder {
+                account_index,
+                order,
+            } => self.reconcile_order(*account_index, *order).await?,
         }
         Ok(())
     }
 
-    /// Remove UTXO detail from database
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn remove_utxo_detail_from_db(&self, account_index: u64) -> Result<(), String> {
+    fn store_pending_operation(&self, op: PendingOperation) {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
         if let Some(ref db_manager) = self.db_manager {
-            db_manager.remove_utxo_detail(account_index)?;
+            if let Err(e) = db_manager.save_pending_operation(&op) {
+                error!("Failed to persist pending operation {}: {}", op.id, e);
+            }
         }
-        Ok(())
+        if op.is_done() && op.kind.is_journaled() {
+            // Finished journal records are only kept in the database.
+            self.pending_ops.update(|ops| ops.remove(&op.id));
+        } else {
+            self.pending_ops.update(|ops| ops.insert(op.id.clone(), op));
+        }
+        #[cfg(feature = "health-endpoint")]
+        self.update_accounts_health();
     }
-    /// Remove request ID from database
+
+    /// Load pending-operation records, including those flagged for
+    /// resolution, from the database into memory.
     #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn remove_request_id_from_db(&self, account_index: u64) -> Result<(), String> {
+    pub fn load_pending_operations_from_db(&mut self) -> Result<(), String> {
         if let Some(ref db_manager) = self.db_manager {
-            db_manager.remove_request_id(account_index)?;
+            for status in [
+                PendingOperationStatus::Pending,
+                PendingOperationStatus::NeedsResolution,
+            ] {
+                for op in db_manager.load_pending_operations(Some(status.as_str()))? {
+                    self.pending_ops.get_mut().insert(op.id.clone(), op);
+                }
+            }
         }
         Ok(())
     }
 
     // -------------------------
-    // Transaction History
+    // Signing audit
     // -------------------------
 
-    /// Log an order action (open/close/cancel) to the history table.
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    fn log_order_history(
-        &self,
-        account_index: AccountIndex,
-        request_id: &str,
-        action: &str,
-        order_type: &str,
-        position_type: Option<&str>,
-        amount: u64,
-        price: Option<f64>,
-        leverage: Option<u64>,
-        pnl: Option<f64>,
-        status: &str,
-        tx_hash: Option<&str>,
-    ) {
-        if let Some(ref db_manager) = self.db_manager {
-            let entry = crate::database::models::NewDbOrderHistory {
-                wallet_id: db_manager.get_wallet_id().to_string(),
-                account_index: account_index as i64,
-                request_id: request_id.to_string(),
-                action: action.to_string(),
-                order_type: order_type.to_string(),
-                position_type: position_type.map(|s| s.to_string()),
-                amount: amount as i64,
-                price,
-                leverage: leverage.map(|l| l as i64),
-                pnl,
-                status: status.to_string(),
-                tx_hash: tx_hash.map(|s| s.to_string()),
-                created_at: chrono::Utc::now().naive_utc(),
-                network_type: crate::config::NETWORK_TYPE.to_string(),
-            };
-            if let Err(e) = db_manager.save_order_history(entry) {
-                error!("Failed to log order history: {}", e);
-            }
+    /// Start recording a hash of every signed relayer query, cancel and settle
+    /// request (see [`signing_audit`](super::signing_audit)). With DB persistence
+    /// the chain continues from, and is appended to, the `signing_audit` table.
+    /// No-op if already enabled.
+    pub fn enable_signing_audit(&mut self) -> Result<(), String> {
+        if self.signing_audit.is_enabled() {
+            return Ok(());
+        }
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(db_manager) = &self.db_manager {
+            let entries = db_manager.load_signing_audit_entries()?;
+            let audit = SigningAudit::resume(self.clock.clone(), entries)?;
+            audit.set_sink(Arc::new(db_manager.clone()));
+            self.signing_audit = audit;
+            return Ok(());
         }
+        self.signing_audit = SigningAudit::enabled(self.clock.clone());
+        Ok(())
     }
 
-    /// Log a transfer action (fund_to_trade/trade_to_fund/trade_to_trade) to the history table.
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    fn log_transfer_history(
-        &self,
-        direction: &str,
-        from_index: Option<AccountIndex>,
-        to_index: Option<AccountIndex>,
-        amount: u64,
-        tx_hash: Option<&str>,
-    ) {
-        if let Some(ref db_manager) = self.db_manager {
-            let entry = crate::database::models::NewDbTransferHistory {
-                wallet_id: db_manager.get_wallet_id().to_string(),
-                direction: direction.to_string(),
-                from_index: from_index.map(|i| i as i64),
-                to_index: to_index.map(|i| i as i64),
-                amount: amount as i64,
-                tx_hash: tx_hash.map(|s| s.to_string()),
-                created_at: chrono::Utc::now().naive_utc(),
-                network_type: crate::config::NETWORK_TYPE.to_string(),
-            };
-            if let Err(e) = db_manager.save_transfer_history(entry) {
-                error!("Failed to log transfer history: {}", e);
-            }
-        }
+    /// Stop recording signed requests. Already stored entries are kept.
+    pub fn disable_signing_audit(&mut self) {
+        self.signing_audit = SigningAudit::disabled();
     }
 
-    /// Query order history with optional filters.
-    /// Requires database persistence to be enabled.
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn get_order_history(
-        &self,
-        filter: super::transaction_history::OrderHistoryFilter,
-    ) -> Result<Vec<super::transaction_history::OrderHistoryEntry>, String> {
-        let db_manager = self.db_manager.as_ref().ok_or("Database not enabled")?;
+    /// Signed requests recorded at or after `since`, oldest first. Empty when
+    /// the audit is disabled.
+    pub fn signing_activity(&self, since: DateTime<Utc>) -> Vec<SigningAuditEntry> {
+        self.signing_audit.entries_since(since)
+    }
 
-        let limit = filter.limit.unwrap_or(100);
-        let offset = filter.offset.unwrap_or(0);
+    /// Check the recorded chain for missing or altered entries.
+    pub fn verify_signing_audit(&self) -> Result<(), String> {
+        self.signing_audit.verify()
+    }
 
-        let rows = if let Some(account_index) = filter.account_index {
-            db_manager.load_order_history_by_account(account_index, limit, offset)?
-        } else {
-            db_manager.load_order_history(limit, offset)?
-        };
+    // -------------------------
+    // Simulated execution
+    // -------------------------
 
-        Ok(rows
-            .iter()
-            .map(super::transaction_history::OrderHistoryEntry::from_db)
-            .collect())
+    /// Choose how orders execute. `ExecutionMode::Simulated` fills trader
+    /// orders against a local [`SimulatedExchange`] and replaces the wallet's
+    /// clock with the simulation's [`ManualClock`]. `DryRun` validates and
+    /// builds funding and order payloads but broadcasts nothing (see
+    /// [`simulation`](super::simulation)). `Live` (the default) drops any
+    /// simulation and restores the system clock.
+    pub fn with_execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.set_execution_mode(mode);
+        self
     }
 
-    /// Query transfer history with optional filters.
-    /// Requires database persistence to be enabled.
-    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
-    pub fn get_transfer_history(
-        &self,
-        filter: super::transaction_history::TransferHistoryFilter,
-    ) -> Result<Vec<super::transaction_history::TransferHistoryEntry>, String> {
-        let db_manager = self.db_manager.as_ref().ok_or("Database not enabled")?;
+    /// [`with_execution_mode`](Self::with_execution_mode) on a wallet in use,
+    /// e.g. to go live after paper trading. Accounts changed by a dry run
+    /// keep their in-memory state; reload the wallet to drop it.
+    pub fn set_execution_mode(&mut self, mode: ExecutionMode) {
+        self.dry_run = matches!(mode, ExecutionMode::DryRun);
+        match mode {
+            ExecutionMode::Live | ExecutionMode::DryRun => {
+                *self.simulation.get_mut() = None;
+                self.set_clock(system_clock());
+            }
+            ExecutionMode::Simulated(config) => {
+                let simulation = SimulatedExchange::new(config);
+                let clock = simulation.clock();
+                *self.simulation.get_mut() = Some(simulation);
+                self.set_clock(Arc::new(clock));
+            }
+        }
+    }
 
-        let limit = filter.limit.unwrap_or(100);
-        let offset = filter.offset.unwrap_or(0);
+    pub fn is_simulated(&self) -> bool {
+        self.simulation.with(Option::is_some)
+    }
 
-        let rows = db_manager.load_transfer_history(limit, offset)?;
+    /// Run `f` on the simulated exchange; `None` outside simulated mode.
+    fn simulate<R>(&self, f: impl FnOnce(&mut SimulatedExchange) -> R) -> Option<R> {
+        self.simulation
+            .update(|simulation| simulation.as_mut().map(f))
+    }
 
-        Ok(rows
-            .iter()
-            .map(super::transaction_history::TransferHistoryEntry::from_db)
-            .collect())
+    /// Whether the wallet is in [`ExecutionMode::DryRun`].
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
     }
 
-    // -------------------------
-    // Portfolio / Position Tracking
-    // -------------------------
+    /// Refuse `operation` in dry-run mode, which only covers
+    /// `funding_to_trading` and opening, closing and cancelling orders.
+    fn ensure_not_dry_run(&self, operation: &str) -> Result<(), String> {
+        if self.dry_run {
+            return Err(format!("{} is not available in dry-run mode", operation));
+        }
+        Ok(())
+    }
 
-    /// Get a snapshot of all ZkOS account balances and their states.
-    pub fn get_account_balances(&self) -> Vec<super::portfolio::AccountBalanceInfo> {
-        self.zk_accounts
-            .get_all_accounts()
-            .iter()
-            .map(|a| super::portfolio::AccountBalanceInfo {
-                account_index: a.index,
-                balance: a.balance,
-                io_type: a.io_type.clone(),
-                on_chain: a.on_chain,
-            })
-            .collect()
+    /// Dry-run counterpart of the `funding_to_trading` mint: sign it with the
+    /// next chain sequence, then release the sequence unused.
+    async fn dry_run_mint(&mut self, index: AccountIndex, amount: u64) -> Result<TxResult, String> {
+        self.nonce_manager
+            .sync_from_chain_with_retry(
+                &self.wallet.chain_config.lcd_endpoint,
+                &self.wallet.twilightaddress,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        let (sequence, account_number) = self.nonce_manager.acquire_next()?;
+        let signed_tx = build_and_sign_msg_mint_burn_trading_btc(
+            &self.wallet,
+            &self.zk_accounts.read(),
+            index,
+            sequence,
+            account_number,
+            amount,
+            true,
+        );
+        self.nonce_manager.release(sequence);
+        let signed_tx = signed_tx?;
+        self.zk_accounts.update_on_chain(&index, true)?;
+        Ok(TxResult {
+            tx_hash: dry_run_tx_hash(&signed_tx),
+            code: 0,
+            simulated: true,
+            attempts: Vec::new(),
+            log: None,
+        })
     }
 
-    /// Query a single trader position and return a structured summary with PnL.
-    /// The position must be in Memo state (i.e. an open order exists).
-    pub async fn get_position_pnl(
-        &mut self,
-        index: AccountIndex,
-    ) -> Result<super::portfolio::PositionSummary, String> {
+    /// Dry-run close or cancel: unlock an order opened by a dry run back to
+    /// Coin. Orders opened live are not closed or cancelled in dry-run mode.
+    fn dry_run_unlock(&self, index: AccountIndex) -> Result<String, String> {
         let account = self.zk_accounts.get_account(&index)?;
-        if account.io_type != IOType::Memo {
+        if !account.simulated {
             return Err(format!(
-                "Account {} is not in Memo state (no open position)",
+                "Account {} holds a live order; it is not closed or cancelled in dry-run mode",
                 index
             ));
         }
-        let order_v1 = self.query_trader_order_v1(index).await?;
-        let current_price = self
-            .relayer_api_client
-            .btc_usd_price()
-            .await
-            .map(|p| p.price)
-            .unwrap_or(order_v1.order.entryprice);
-        Ok(super::portfolio::PositionSummary::from_trader_order_v1(
-            index,
-            &order_v1,
-            current_price,
-        ))
-    }
-
-    /// Query a single lend position and return a structured summary.
-    /// The position must be in Memo state (i.e. an active lend order exists).
-    pub async fn get_lend_position_pnl(
-        &mut self,
-        index: AccountIndex,
-    ) -> Result<super::portfolio::LendPositionSummary, String> {
-        let account = self.zk_accounts.get_account(&index)?;
         if account.io_type != IOType::Memo {
             return Err(format!(
-                "Account {} is not in Memo state (no active lend position)",
-                index
+                "Account has no open order, io type: {:?}",
+                account.io_type
             ));
         }
-        let order_v1 = self.query_lend_order_v1(index).await?;
-        Ok(super::portfolio::LendPositionSummary::from_lend_order_v1(
-            index, &order_v1,
-        ))
+        self.return_to_coin(index)?;
+        Ok(dry_run_request_id())
     }
 
-    /// Build a full portfolio summary across all accounts.
-    ///
-    /// This queries the relayer for each open trader/lend position to get live PnL data.
-    /// Accounts in Coin state contribute to `total_trading_balance`.
-    /// Accounts in Memo state are queried as trader positions first; if that fails,
-    /// they are tried as lend positions.
-    pub async fn get_portfolio_summary(&mut self) -> Result<super::portfolio::Portfolio, String> {
-        let current_price = self
-            .relayer_api_client
-            .btc_usd_price()
-            .await
-            .map(|p| p.price)
-            .unwrap_or(0.0);
+    /// Clock driving simulated execution; advance it to move simulated time.
+    /// `None` in live mode.
+    pub fn simulated_clock(&self) -> Option<ManualClock> {
+        self.simulation.with(|s| s.as_ref().map(|s| s.clock()))
+    }
 
-        let mut total_trading_balance: u64 = 0;
-        let mut trader_positions = Vec::new();
-        let mut closed_trader_positions = Vec::new();
-        let mut liquidated_trader_positions = Vec::new();
-        let mut lend_positions = Vec::new();
-        let mut closed_lend_positions = Vec::new();
-        let mut on_chain_count = 0;
+    /// The local exchange used in simulated mode (e.g. to move the mark price).
+    pub fn simulation_mut(&mut self) -> Option<&mut SimulatedExchange> {
+        self.simulation.get_mut().as_mut()
+    }
 
-        let accounts: Vec<_> = self
-            .zk_accounts
-            .get_all_accounts()
-            .into_iter()
-            .cloned()
-            .collect();
-        let total_accounts = accounts.len();
+    /// Simulated order on `index`, brought up to date with the simulated clock.
+    pub fn simulated_trader_order(&self, index: AccountIndex) -> Option<SimulatedOrder> {
+        self.simulate(|simulation| simulation.trader_order(index))?
+    }
 
-        for account in &accounts {
-            if account.on_chain {
-                on_chain_count += 1;
-            }
+    // -------------------------
+    // Activity & diagnostics
+    // -------------------------
 
-            match account.io_type {
-                IOType::Coin => {
-                    if account.on_chain && account.balance > 0 {
-                        total_trading_balance += account.balance;
-                    }
-                }
-                IOType::Memo => {
-                    match account.tx_type {
-                        Some(TXType::LENDTX) => {
-                            // Lend order
-                            if let Ok(order_v1) = self.query_lend_order_v1(account.index).await {
-                                if order_v1.order.order_status == OrderStatus::SETTLED {
-                                    let summary =
-                                        super::portfolio::LendPositionSummary::from_lend_order_v1(
-                                            account.index,
-                                            &order_v1,
-                                        );
-
-                                    match self.unlock_lend_order(account.index).await {
-                                        Ok(_) => {
-                                            info!(
-                                                "Unlocked settled lend account {} during portfolio scan",
-                                                account.index
-                                            );
-                                        }
-                                        Err(e) => {
-                                            error!(
-                                                "Failed to unlock settled lend account {}: {}",
-                                                account.index, e
-                                            );
-                                        }
-                                    }
-
-                                    closed_lend_positions.push(summary);
-                                } else {
-                                    lend_positions.push(
-                                        super::portfolio::LendPositionSummary::from_lend_order_v1(
-                                            account.index,
-                                            &order_v1,
-                                        ),
-                                    );
-                                }
-                            }
-                        }
-                        Some(TXType::ORDERTX) | None => {
-                            // Trader order (None for backward compatibility)
-                            match self.query_trader_order_v1(account.index).await {
-                                Ok(order_v1) => {
-                                    if order_v1.order.order_status == OrderStatus::SETTLED {
-                                        let mut summary =
-                                            super::portfolio::PositionSummary::from_trader_order_v1(
-                                                account.index,
-                                                &order_v1,
-                                                current_price,
-                                            );
-                                        summary.unrealized_pnl = order_v1.order.unrealized_pnl;
-
-                                        match self.unlock_trader_order(account.index).await {
-                                            Ok(_) => {
-                                                info!(
-                                                    "Unlocked settled account {} during portfolio scan",
-                                                    account.index
-                                                );
-                                            }
-                                            Err(e) => {
-                                                error!(
-                                                    "Failed to unlock settled account {}: {}",
-                                                    account.index, e
-                                                );
-                                            }
-                                        }
-
-                                        closed_trader_positions.push(summary);
-                                    } else if order_v1.order.order_status == OrderStatus::LIQUIDATE
-                                    {
-                                        let summary =
-                                            super::portfolio::PositionSummary::from_trader_order_v1(
-                                                account.index,
-                                                &order_v1,
-                                                current_price,
-                                            );
-
-                                        match self.unlock_trader_order(account.index).await {
-                                            Ok(_) => {
-                                                info!(
-                                                    "Unlocked liquidated account {} during portfolio scan",
-                                                    account.index
-                                                );
-                                            }
-                                            Err(e) => {
-                                                error!(
-                                                    "Failed to unlock liquidated account {}: {}",
-                                                    account.index, e
-                                                );
-                                            }
-                                        }
-
-                                        liquidated_trader_positions.push(summary);
-                                    } else {
-                                        trader_positions.push(
-                                            super::portfolio::PositionSummary::from_trader_order_v1(
-                                                account.index,
-                                                &order_v1,
-                                                current_price,
-                                            ),
-                                        );
-                                    }
-                                }
-                                Err(_) => {}
-                            }
-                        }
-                    }
-                }
-                _ => {}
-            }
+    /// Operation counts per UTC hour for hours starting within `range`
+    /// (orders opened/settled, transfers, chain txs, relayer calls, errors).
+    /// Hours are kept for 14 days and, with DB persistence, survive restarts.
+    pub fn activity_histogram(&self, range: std::ops::Range<DateTime<Utc>>) -> ActivityHistogram {
+        self.activity.histogram(range)
+    }
+
+    /// Fingerprint of the configuration this wallet is running under; stored
+    /// with the wallet whenever it is saved to the database.
+    pub fn config_fingerprint(&self) -> ConfigFingerprint {
+        ConfigFingerprint::capture(
+            &self.chain_id,
+            &self.wallet.chain_config,
+            &self.relayer_endpoint_config,
+        )
+    }
+
+    /// Drift found when this wallet was loaded from the database.
+    pub fn config_drift(&self) -> &ConfigDrift {
+        &self.config_drift
+    }
+
+    /// Summary of wallet state and the last 24 hours of activity.
+    pub fn diagnostic_snapshot(&self) -> DiagnosticSnapshot {
+        let now = self.clock.now();
+        let accounts = self.zk_accounts.get_all_accounts();
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let database_enabled = self.db_manager.is_some();
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let database_enabled = false;
+        DiagnosticSnapshot {
+            generated_at: now,
+            chain_id: self.chain_id.clone(),
+            accounts: accounts.len(),
+            archived_accounts: self.zk_accounts.archived_len(),
+            accounts_with_errors: accounts.iter().filter(|a| a.last_error.is_some()).count(),
+            pending_operations: self.pending_ops.with(HashMap::len),
+            database_enabled,
+            signing_audit_enabled: self.signing_audit.is_enabled(),
+            shut_down: self.shutdown.is_shut_down(),
+            activity: self.activity.histogram(now - chrono::Duration::hours(24)..now),
+            risk: self.last_risk_report.clone(),
+            config_drift: self.config_drift.clone(),
         }
+    }
 
-        let wallet_balance_sats = self
-            .wallet
-            .update_balance()
-            .await
-            .map(|b| b.sats)
-            .unwrap_or(0);
+    // -------------------------
+    // Shutdown
+    // -------------------------
 
-        Ok(super::portfolio::Portfolio::build(
-            wallet_balance_sats,
-            total_trading_balance,
-            trader_positions,
-            closed_trader_positions,
-            liquidated_trader_positions,
-            lend_positions,
-            closed_lend_positions,
-            total_accounts,
-            on_chain_count,
-        ))
+    /// Registry of subsystems stopped by [`shutdown`](OrderWallet::shutdown).
+    /// Register background tasks and stop hooks (such as
+    /// `HealthServerHandle::shutdown()`) here as they are started.
+    pub fn shutdown_registry(&self) -> &ShutdownRegistry {
+        &self.shutdown
     }
 
-    /// Get liquidation risk info for all open trader positions, sorted by distance
-    /// to liquidation (most at-risk first).
-    pub async fn get_liquidation_risks(
-        &mut self,
-    ) -> Result<Vec<super::portfolio::LiquidationRisk>, String> {
-        let current_price = self
-            .relayer_api_client
-            .btc_usd_price()
-            .await
-            .map(|p| p.price)
-            .unwrap_or(0.0);
+    /// Stop the wallet cleanly, within `options.timeout`:
+    ///
+    /// 1. mark the wallet not-ready on the health endpoint, if served;
+    /// 2. abort registered background tasks, newest first;
+    /// 3. await registered stop hooks, newest first;
+    /// 4. cancel pending trader orders (`cancel_pending`);
+    /// 5. close filled trader orders at market and open lend orders (`close_positions`);
+    /// 6. persist wallet, account, UTXO and request-ID state (`flush`);
+    /// 7. release the database handle; later writes are no-ops.
+    ///
+    /// Steps reached after the deadline are skipped; flush and release always
+    /// run since they are local. Calling it again returns a report with
+    /// `already_shut_down` set and does nothing.
+    pub async fn shutdown(&mut self, options: ShutdownOptions) -> ShutdownReport {
+        let Some(registered) = self.shutdown.begin() else {
+            return ShutdownReport {
+                already_shut_down: true,
+                steps: Vec::new(),
+            };
+        };
+        let deadline = tokio::time::Instant::now() + options.timeout;
+        let mut report = ShutdownReport::default();
+
+        #[cfg(feature = "health-endpoint")]
+        if let Some(ref registry) = self.health {
+            registry.set_persistent(
+                health::CHECK_WALLET,
+                false,
+                Some("shutting down".to_string()),
+            );
+        }
 
-        if current_price == 0.0 {
-            return Err("Could not fetch current BTC/USD price".to_string());
+        for (name, task) in registered.tasks.into_iter().rev() {
+            task.abort();
+            let status = run_step(deadline, async move {
+                let _ = task.await;
+                Ok(None)
+            })
+            .await;
+            report.push(format!("task:{}", name), status);
+        }
+        for (name, hook) in registered.hooks.into_iter().rev() {
+            let status = run_step(deadline, async move {
+                hook.await;
+                Ok(None)
+            })
+            .await;
+            report.push(format!("hook:{}", name), status);
         }
 
-        let accounts: Vec<_> = self
-            .zk_accounts
-            .get_all_accounts()
-            .into_iter()
-            .cloned()
-            .collect();
-        let mut risks = Vec::new();
+        let status = if options.cancel_pending {
+            run_step(deadline, self.cancel_pending_orders()).await
+        } else {
+            StepStatus::Skipped("not requested".to_string())
+        };
+        report.push("cancel_pending", status);
 
-        for account in &accounts {
-            if account.io_type != IOType::Memo || !account.on_chain {
-                continue;
+        let status = if options.close_positions {
+            run_step(deadline, self.close_open_positions()).await
+        } else {
+            StepStatus::Skipped("not requested".to_string())
+        };
+        report.push("close_positions", status);
+
+        let status = if options.flush {
+            match self.flush_state() {
+                Ok(detail) => StepStatus::Done(detail),
+                Err(e) => StepStatus::Failed(e),
             }
+        } else {
+            StepStatus::Skipped("not requested".to_string())
+        };
+        report.push("flush", status);
 
-            if let Ok(order) = self.query_trader_order(account.index).await {
-                if order.liquidation_price > 0.0 {
-                    let distance_pct = match order.position_type {
-                        PositionType::LONG => {
-                            (current_price - order.liquidation_price) / current_price * 100.0
-                        }
-                        PositionType::SHORT => {
-                            (order.liquidation_price - current_price) / current_price * 100.0
-                        }
-                    };
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let status = match self.db_manager.take() {
+            Some(_) => {
+                self.signing_audit.clear_sink();
+                self.passphrase.lock();
+                StepStatus::Done(None)
+            }
+            None => StepStatus::Skipped("no database".to_string()),
+        };
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let status = StepStatus::Skipped("no database".to_string());
+        report.push("release_database", status);
+
+        if report.steps.iter().any(|s| {
+            s.status == StepStatus::TimedOut
+                || s.status == StepStatus::Skipped(DEADLINE_EXCEEDED.to_string())
+        }) {
+            warn!("Shutdown deadline exceeded: {:?}", report.timed_out());
+        }
+        info!("OrderWallet shut down");
+        report
+    }
 
-                    let margin_ratio = if order.initial_margin > 0.0 {
-                        order.available_margin / order.initial_margin
-                    } else {
-                        0.0
-                    };
+    /// Wait for `signal` (e.g. `tokio::signal::ctrl_c()`), then [`shutdown`](OrderWallet::shutdown).
+    pub async fn run_until_shutdown<S, T>(
+        &mut self,
+        signal: S,
+        options: ShutdownOptions,
+    ) -> ShutdownReport
+    where
+        S: std::future::Future<Output = T>,
+    {
+        let _ = signal.await;
+        info!("Shutdown signal received");
+        self.shutdown(options).await
+    }
 
-                    risks.push(super::portfolio::LiquidationRisk {
-                        account_index: account.index,
-                        position_type: order.position_type,
-                        liquidation_price: order.liquidation_price,
-                        current_price,
-                        distance_pct,
-                        margin_ratio,
-                    });
+    /// Accounts locked in a lend order (`lend`) or a trader order. Memo
+    /// accounts whose order kind is unknown are in neither.
+    fn open_accounts(&self, lend: bool) -> Vec<AccountIndex> {
+        let kind = if lend {
+            OrderKind::Lend
+        } else {
+            OrderKind::Trader
+        };
+        self.memo_accounts()
+            .into_iter()
+            .filter(|(_, account_kind)| *account_kind == Some(kind))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Open trader orders for the risk limits: Memo accounts not known to
+    /// hold a lend order, so an account of unknown kind counts.
+    fn open_trader_order_count(&self) -> usize {
+        self.memo_accounts()
+            .iter()
+            .filter(|(_, kind)| *kind != Some(OrderKind::Lend))
+            .count()
+    }
+
+    /// Errors for the Memo accounts whose order kind
+    /// [`identify_memo_orders`](Self::identify_memo_orders) cannot tell.
+    async fn unidentified_memo_errors(&self) -> Vec<String> {
+        self.identify_memo_orders()
+            .await
+            .into_iter()
+            .map(|index| format!("account {}: {}", index, unknown_order_kind(index)))
+            .collect()
+    }
+
+    async fn cancel_pending_orders(&mut self) -> Result<Option<String>, String> {
+        let mut cancelled = 0;
+        let mut errors = self.unidentified_memo_errors().await;
+        for index in self.open_accounts(false) {
+            match self.query_trader_order(index).await {
+                Ok(order) if order.order_status == OrderStatus::PENDING => {
+                    match self.cancel_trader_order(index).await {
+                        Ok(_) => cancelled += 1,
+                        Err(e) => errors.push(format!("account {}: {}", index, e)),
+                    }
                 }
+                Ok(_) => {}
+                Err(e) => errors.push(format!("account {}: {}", index, e)),
             }
         }
+        if errors.is_empty() {
+            Ok(Some(format!("{} order(s) cancelled", cancelled)))
+        } else {
+            Err(errors.join("; "))
+        }
+    }
 
-        // Sort by distance — most at-risk (lowest distance) first
-        risks.sort_by(|a, b| {
-            a.distance_pct
-                .partial_cmp(&b.distance_pct)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
-
-        Ok(risks)
+    async fn close_open_positions(&mut self) -> Result<Option<String>, String> {
+        let mut closed = 0;
+        let mut errors = self.unidentified_memo_errors().await;
+        for index in self.open_accounts(false) {
+            match self.query_trader_order(index).await {
+                Ok(order) if order.order_status == OrderStatus::FILLED => {
+                    match self.close_trader_order(index, OrderType::MARKET, 0.0).await {
+                        Ok(_) => closed += 1,
+                        Err(e) => errors.push(format!("account {}: {}", index, e)),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => errors.push(format!("account {}: {}", index, e)),
+            }
+        }
+        for index in self.open_accounts(true) {
+            match self.close_lend_order(index).await {
+                Ok(_) => closed += 1,
+                Err(e) => errors.push(format!("account {}: {}", index, e)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(Some(format!("{} position(s) closed", closed)))
+        } else {
+            Err(errors.join("; "))
+        }
     }
-}
 
-// -------------------------
-// Drop
-// -------------------------
-#[cfg(any(feature = "sqlite", feature = "postgresql"))]
-impl Drop for OrderWallet {
-    fn drop(&mut self) {
+    /// Write all in-memory state to the database, if persistence is enabled.
+    fn flush_state(&self) -> Result<Option<String>, String> {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
         if let Some(ref db_manager) = self.db_manager {
-            // Save all current zk accounts to database
-            for account in self.zk_accounts.get_all_accounts() {
-                if let Err(e) = db_manager.save_zk_account(account) {
-                    error!(
-                        "Failed to persist zk_account {} during drop: {}",
-                        account.index, e
-                    );
-                }
+            for account in self.persisted_accounts() {
+                db_manager.save_zk_account(&account)?;
+            }
+            for (index, utxo_detail) in self.utxo_details.snapshot() {
+                db_manager.save_utxo_detail(index, &utxo_detail)?;
+            }
+            for (index, request_id) in self.active_request_ids() {
+                db_manager.save_request_id(index, &request_id)?;
             }
+            for op in self.pending_ops.get().values() {
+                db_manager.save_pending_operation(op)?;
+            }
+            for submission in self.pending_submissions.snapshot().values() {
+                db_manager.save_pending_submission(submission)?;
+            }
+            self.persist_activity()?;
+            // Last, so a locked wallet still flushes the unencrypted tables.
+            self.save_order_wallet_to_db()?;
+            return Ok(None);
+        }
+        Ok(Some("no database".to_string()))
+    }
 
-            // Save encrypted wallet if password is available
-            if let Some(ref password) = self.wallet_password {
-                // if let Err(e) = db_manager.save_encrypted_wallet(&self.wallet, password) {
-                //     error!("Failed to persist wallet during drop: {}", e);
-                // }
+    // -------------------------
+    // Events
+    // -------------------------
 
-                // Save OrderWallet configuration
-                if let Err(e) = db_manager.save_order_wallet(
-                    &self.chain_id,
-                    self.seed.expose_secret(),
-                    &self.relayer_endpoint_config,
-                    password.expose_secret(),
-                ) {
-                    error!(
-                        "Failed to persist OrderWallet configuration during drop: {}",
-                        e
-                    );
+    /// Receive every [`WalletEvent`] emitted from now on. Each receiver
+    /// buffers [`EVENT_BUFFER`](super::events::EVENT_BUFFER) events; one that
+    /// falls further behind gets `RecvError::Lagged` and skips the oldest.
+    /// Events other than order outcomes are only built while a receiver (or
+    /// a webhook) exists.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<WalletEvent> {
+        self.events.subscribe()
+    }
+
+    /// Start an [`OrderWatcher`] over `wallet` that polls its open orders
+    /// every `poll_interval` and reconciles fills, settlements and
+    /// cancellations as they happen. Stop it with
+    /// [`OrderWatcherHandle::stop`].
+    pub fn spawn_watcher(
+        wallet: &Arc<Self>,
+        poll_interval: std::time::Duration,
+    ) -> OrderWatcherHandle {
+        OrderWatcher::new(wallet.clone())
+            .with_poll_interval(poll_interval)
+            .spawn()
+    }
+
+    // -------------------------
+    // Webhooks
+    // -------------------------
+
+    /// POST wallet events matching `event_filter` to `url`, signed
+    /// with `secret`, using the default [`WebhookConfig`]. Delivery runs in
+    /// the background and never delays the operation that emitted the event.
+    #[cfg(feature = "webhooks")]
+    pub fn add_webhook(
+        &mut self,
+        url: &str,
+        secret: &str,
+        event_filter: EventFilter,
+    ) -> Result<(), String> {
+        self.add_webhook_with_config(url, secret, event_filter, WebhookConfig::default())
+    }
+
+    #[cfg(feature = "webhooks")]
+    pub fn add_webhook_with_config(
+        &mut self,
+        url: &str,
+        secret: &str,
+        event_filter: EventFilter,
+        config: WebhookConfig,
+    ) -> Result<(), String> {
+        let task = self.webhooks.add(
+            url,
+            secrecy::SecretString::new(secret.to_string()),
+            event_filter,
+            config,
+        )?;
+        self.shutdown
+            .register_task(&format!("webhook:{}", url), task);
+        Ok(())
+    }
+
+    /// Delivery counters over all webhooks.
+    #[cfg(feature = "webhooks")]
+    pub fn webhook_stats(&self) -> WebhookStats {
+        self.webhooks.stats()
+    }
+
+    // -------------------------
+    // Health endpoint
+    // -------------------------
+
+    /// Serve `/healthz` and `/readyz` on `addr` (port 0 picks a free port).
+    ///
+    /// Readiness aggregates cached checks: wallet loaded, database reachable
+    /// (DB builds only), relayer reachable, chain LCD not degraded, and no
+    /// unfinished pending operations. The relayer and DB are polled and the
+    /// LCD state is read every `HEALTH_REFRESH_INTERVAL` by a background task;
+    /// probes only read the cached results. A significant
+    /// [`config_drift`](OrderWallet::config_drift) since the wallet was saved
+    /// fails the `config` check. Call `shutdown` on the returned handle to stop.
+    #[cfg(feature = "health-endpoint")]
+    pub async fn serve_health(
+        &mut self,
+        addr: std::net::SocketAddr,
+    ) -> Result<HealthServerHandle, String> {
+        let registry =
+            HealthRegistry::new(Some(HEALTH_REFRESH_INTERVAL * 3)).with_clock(self.clock.clone());
+        registry.set_persistent(health::CHECK_WALLET, true, None);
+        let drifted = self.config_drift.is_significant();
+        let detail = drifted.then(|| self.config_drift.to_string());
+        registry.set_persistent(health::CHECK_CONFIG, !drifted, detail);
+        self.health = Some(registry.clone());
+        self.update_accounts_health();
+
+        let mut handle = health::serve(addr, registry.clone()).await?;
+        let relayer_api_client = self.relayer_api_client.clone();
+        let lcd_endpoint = self.wallet.chain_config.lcd_endpoint.clone();
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let db_manager = self.db_manager.clone();
+        let refresher = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_REFRESH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let relayer = relayer_api_client.server_time().await.map(|_| ());
+                registry.set_result(health::CHECK_RELAYER, &relayer);
+
+                match crate::nyks_rpc::lcd::status_for(&lcd_endpoint) {
+                    Some(lcd) if lcd.degraded => {
+                        registry.set(health::CHECK_LCD, false, lcd.last_error)
+                    }
+                    _ => registry.set(health::CHECK_LCD, true, None),
                 }
-            }
 
-            // Save all UTXO details
-            for (account_index, utxo_detail) in &self.utxo_details {
-                if let Err(e) = db_manager.save_utxo_detail(*account_index, utxo_detail) {
-                    error!(
-                        "Failed to persist UTXO detail for account {} during drop: {}",
-                        account_index, e
-                    );
+                #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+                if let Some(db_manager) = db_manager.clone() {
+                    let database = tokio::task::spawn_blocking(move || {
+                        crate::database::connection::get_conn(db_manager.pool()).map(|_| ())
+                    })
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|r| r);
+                    registry.set_result(health::CHECK_DATABASE, &database);
                 }
             }
+        });
+        handle.attach_task(refresher);
+        info!("Health endpoint listening on {}", handle.local_addr());
+        Ok(handle)
+    }
 
-            // Save all request IDs
-            for (account_index, request_id) in &self.request_ids {
-                if let Err(e) = db_manager.save_request_id(*account_index, request_id) {
-                    error!(
-                        "Failed to persist request ID for account {} during drop: {}",
-                        account_index, e
-                    );
-                }
+    #[cfg(feature = "health-endpoint")]
+    fn update_accounts_health(&self) {
+        if let Some(ref registry) = self.health {
+            let open = self
+                .pending_ops
+                .with(|ops| ops.values().filter(|op| !op.is_done()).count());
+            if open == 0 {
+                registry.set_persistent(health::CHECK_ACCOUNTS, true, None);
+            } else {
+                registry.set_persistent(
+                    health::CHECK_ACCOUNTS,
+                    false,
+                    Some(format!("{} pending operation(s) need resume_operation", open)),
+                );
             }
+        }
+    }
+
+    // -------------------------
+    // Trader Order Operations
+    // -------------------------
+
+    /// Validate that the market is not halted.
+    ///
+    /// Close, cancel, and lend operations are allowed during HEALTHY and CLOSE_ONLY
+    /// but rejected during HALT (per risk_engine.md).
+    pub async fn validate_market_not_halted(&self) -> Result<(), String> {
+        let stats = self
+            .relayer
+            .get_market_stats()
+            .await
+            .map_err(|e| format!("Failed to fetch market stats: {}", e))?;
+
+        if stats.status == "HALT" {
+            return Err(format!(
+                "Market is halted: {}. All operations are blocked.",
+                stats.status_reason.as_deref().unwrap_or("unknown")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Pre-validate an open order against the relayer's risk engine via `get_market_stats`.
+    ///
+    /// This replicates the server-side validation pipeline locally so the user gets
+    /// an immediate, descriptive error instead of a generic rejection from the relayer.
+    /// Checks performed (matching `risk_engine.md`):
+    /// 1. Market status — reject if HALT or CLOSE_ONLY
+    /// 2. Max leverage — reject if leverage exceeds `params.max_leverage` (see `LeverageLimits`)
+    /// 3. Min position size — reject if entry_value < `params.min_position_btc`
+    /// 4. Per-position cap — reject if entry_value > `params.max_position_pct * pool_equity`
+    /// 5. Directional headroom — reject if entry_value > `max_long_btc` / `max_short_btc`
+    pub async fn validate_open_order(
+        &self,
+        order_side: &PositionType,
+        initial_margin: u64,
+        leverage: impl Into<Leverage>,
+    ) -> Result<(), String> {
+        let stats = self
+            .relayer
+            .get_market_stats()
+            .await
+            .map_err(|e| format!("Failed to fetch market stats: {}", e))?;
+        check_open_order(&stats, order_side, initial_margin, leverage.into())
+    }
+
+    /// Open a trader order on `index` using the account's whole balance as margin.
+    /// `leverage` accepts a whole `u64` or a fractional [`Leverage`] (e.g. `"2.5".parse()?`);
+    /// it is bounded by the market's `max_leverage`.
+    pub async fn open_trader_order(
+        &self,
+        index: AccountIndex,
+        order_type: OrderType,
+        order_side: PositionType,
+        entry_price: u64,
+        leverage: impl Into<Leverage>,
+    ) -> OrderWalletResult<String> {
+        let leverage = leverage.into();
+        let requested_type = order_type.clone();
+        let _account = self.account_locks.lock(index).await;
+        if self.is_simulated() {
+            let initial_margin = self.zk_accounts.get_account(&index)?.balance;
+            let result = self
+                .simulate(|simulation| {
+                    simulation.open_trader_order(
+                        index,
+                        order_type,
+                        order_side,
+                        entry_price,
+                        leverage,
+                        initial_margin,
+                    )
+                })
+                .unwrap_or_else(|| Err("The wallet left simulated mode".to_string()));
+            if let Ok(request_id) = &result {
+                self.request_ids.insert(index, request_id.clone());
+            }
+            self.record_trader_open_outcome(index, &requested_type, entry_price, &result);
+            return result.map_err(Into::into);
+        }
+        if !self.dry_run {
+            let result = match self.resolve_pending_submission(index).await {
+                Ok(None) => None,
+                Ok(Some((submission, request_id))) if submission.kind == OrderKind::Trader => {
+                    let params = TraderOrderParams::new(
+                        index,
+                        order_type.clone(),
+                        order_side.clone(),
+                        entry_price,
+                        leverage,
+                    );
+                    Some(
+                        self.record_trader_order_open(
+                            &params,
+                            &request_id,
+                            Some(&submission.idempotency_key),
+                        )
+                        .map(|()| request_id),
+                    )
+                }
+                Ok(Some((submission, request_id))) => {
+                    Some(self.adopt_other_kind(&submission, &request_id).await)
+                }
+                Err(e) => Some(Err(e)),
+            };
+            if let Some(result) = result {
+                self.record_trader_open_outcome(index, &requested_type, entry_price, &result);
+                return result.map_err(Into::into);
+            }
+        }
+        let reused_utxo = self.has_reusable_utxo(index);
+        let fee_order_value = if self.fee_estimates_enabled {
+            self.zk_accounts
+                .get_account(&index)
+                .ok()
+                .and_then(|account| leverage.apply(account.balance))
+        } else {
+            None
+        };
+        let intent = self.journal_order(
+            PendingOperationKind::OpenOrder,
+            index,
+            OrderKind::Trader,
+            format!(
+                "{:?} {:?} at {}, {}x",
+                order_type, order_side, entry_price, leverage
+            ),
+        );
+        // The fee rates are fetched while the order is built and sent.
+        let (mut result, fee_estimate) = tokio::join!(
+            self.open_trader_order_inner(
+                index,
+                order_type.clone(),
+                order_side.clone(),
+                entry_price,
+                leverage,
+            ),
+            self.estimate_fees(index, fee_order_value, requested_type.clone()),
+        );
+        if reused_utxo && matches!(&result, Err(e) if is_stale_input_error(&e.to_string())) {
+            warn!(
+                "Cached UTXO for account {} looks stale; retrying with a fresh fetch",
+                index
+            );
+            self.utxo_cache.update(|cache| cache.invalidate(index));
+            result = self
+                .open_trader_order_inner(index, order_type, order_side, entry_price, leverage)
+                .await;
+        }
+        self.finish_intent(intent, &result);
+        self.record_trader_open_outcome(index, &requested_type, entry_price, &result);
+        match (&result, fee_estimate) {
+            (Ok(_), Some(estimate)) => self.fee_estimates.insert(index, estimate),
+            _ => self.fee_estimates.remove(&index),
+        };
+        result
+    }
+
+    /// Open a trader order and wait up to `wait` for it to fill. An order
+    /// still pending afterwards stays open and tracked like any other; a
+    /// cancelled or rejected one leaves the account unlocked. See
+    /// [`order_wait`](super::order_wait).
+    pub async fn open_trader_order_and_wait(
+        &mut self,
+        params: TraderOrderParams,
+        wait: std::time::Duration,
+    ) -> Result<FilledOrderReceipt<TraderOrderSnapshot>, OpenWaitError> {
+        self.ensure_not_dry_run("open_trader_order_and_wait")
+            .map_err(OpenWaitError::SubmissionFailed)?;
+        let index = params.index;
+        let request_id = self
+            .open_trader_order(
+                index,
+                params.order_type,
+                params.order_side,
+                params.entry_price,
+                params.leverage,
+            )
+            .await
+            .map_err(|e| OpenWaitError::SubmissionFailed(e.to_string()))?;
+        let waited = self
+            .wait_for_open(index, &request_id, OrderKind::Trader, wait)
+            .await?;
+        let order = match self.simulated_trader_order(index) {
+            Some(order) => TraderOrderSnapshot::Simulated(order),
+            None => TraderOrderSnapshot::Relayer(self.query_trader_order(index).await.map_err(
+                |error| OpenWaitError::SnapshotUnavailable {
+                    account_index: index,
+                    request_id: request_id.clone(),
+                    error: error.to_string(),
+                },
+            )?),
+        };
+        Ok(FilledOrderReceipt {
+            account_index: index,
+            request_id,
+            order,
+            waited,
+            fee_estimate: self.fee_estimate(index),
+        })
+    }
+
+    /// Open trader orders on several accounts in one call.
+    ///
+    /// Every order is checked first: coin state, on-chain balance and leverage,
+    /// then the market limits against a single market stats fetch. UTXOs that
+    /// cannot be reused are fetched concurrently, and the valid orders are
+    /// built and submitted concurrently so they reach the relayer close
+    /// together.
+    ///
+    /// Returns one result per order, in input order. A failed order does not
+    /// stop the others, and a panic while its UTXO is fetched or it is built
+    /// or sent fails that order only, with the panic's message; an account
+    /// listed twice fails from its second entry.
+    /// The call itself fails only if the market stats cannot be fetched, in
+    /// which case no order is submitted.
+    pub async fn open_trader_orders_batch(
+        &mut self,
+        orders: Vec<TraderOrderParams>,
+    ) -> Result<Vec<(AccountIndex, Result<RequestId, String>)>, String> {
+        self.ensure_not_dry_run("open_trader_orders_batch")?;
+        let mut seen = HashSet::new();
+        let mut outcomes: Vec<Option<Result<RequestId, String>>> = orders
+            .iter()
+            .map(|order| {
+                (!seen.insert(order.index)).then(|| {
+                    Err(format!(
+                        "Account {} appears more than once in the batch",
+                        order.index
+                    ))
+                })
+            })
+            .collect();
+        if self.is_simulated() {
+            for (order, outcome) in orders.iter().zip(outcomes.iter_mut()) {
+                if outcome.is_none() {
+                    let result = self
+                        .open_trader_order(
+                            order.index,
+                            order.order_type.clone(),
+                            order.order_side.clone(),
+                            order.entry_price,
+                            order.leverage,
+                        )
+                        .await;
+                    *outcome = Some(result.map_err(String::from));
+                }
+            }
+        } else {
+            self.submit_trader_orders_batch(&orders, &mut outcomes)
+                .await?;
+        }
+        Ok(orders
+            .iter()
+            .zip(outcomes)
+            .map(|(order, outcome)| {
+                let result =
+                    outcome.unwrap_or_else(|| Err("Order submission did not complete".to_string()));
+                (order.index, result)
+            })
+            .collect())
+    }
+
+    /// Relayer path of [`open_trader_orders_batch`](Self::open_trader_orders_batch).
+    /// Orders whose outcome is still `None` are attempted; every attempt is
+    /// recorded on its account like a single `open_trader_order`, including
+    /// its pending submission, so an order an earlier batch got accepted is
+    /// adopted instead of sent again.
+    async fn submit_trader_orders_batch(
+        &mut self,
+        orders: &[TraderOrderParams],
+        outcomes: &mut [Option<Result<RequestId, String>>],
+    ) -> Result<(), String> {
+        let attempted: Vec<usize> = outcomes
+            .iter()
+            .enumerate()
+            .filter(|(_, outcome)| outcome.is_none())
+            .map(|(position, _)| position)
+            .collect();
+        for (order, outcome) in orders.iter().zip(outcomes.iter_mut()) {
+            if outcome.is_some() {
+                continue;
+            }
+            *outcome = match self.resolve_pending_submission(order.index).await {
+                Ok(None) => None,
+                Ok(Some((submission, request_id))) if submission.kind == OrderKind::Trader => Some(
+                    self.record_trader_order_open(
+                        order,
+                        &request_id,
+                        Some(&submission.idempotency_key),
+                    )
+                    .map(|()| request_id),
+                ),
+                Ok(Some((submission, request_id))) => {
+                    Some(self.adopt_other_kind(&submission, &request_id).await)
+                }
+                Err(e) => Some(Err(e)),
+            };
+        }
+        for (order, outcome) in orders.iter().zip(outcomes.iter_mut()) {
+            if outcome.is_some() {
+                continue;
+            }
+            let checked = self
+                .ensure_coin_onchain(order.index)
+                .map_err(String::from)
+                .and_then(|()| {
+                    if order.leverage.is_zero() {
+                        Err("Leverage must be greater than 0".to_string())
+                    } else {
+                        Ok(())
+                    }
+                });
+            if let Err(e) = checked {
+                *outcome = Some(Err(e));
+            }
+        }
+        if outcomes.iter().all(Option::is_some) {
+            self.record_batch_outcomes(orders, outcomes, &attempted);
+            return Ok(());
+        }
+        self.ensure_trading_limits().await;
+
+        // Adopt reusable UTXOs; fetch the rest concurrently with the market stats.
+        let mut reused = HashSet::new();
+        let mut fetches = Vec::new();
+        for (order, outcome) in orders.iter().zip(outcomes.iter_mut()) {
+            if outcome.is_some() {
+                continue;
+            }
+            let index = order.index;
+            if self.has_reusable_utxo(index) {
+                reused.insert(index);
+                if let Err(e) = self.ensure_fresh_utxo(index).await {
+                    *outcome = Some(Err(e));
+                }
+                continue;
+            }
+            match self.zk_accounts.get_account_address(&index) {
+                Ok(address) => {
+                    let fetcher = self.utxo_fetcher.clone();
+                    fetches.push(async move {
+                        let fetched = compat::guard_async(
+                            "get_utxo_details",
+                            &format!("account {}", index),
+                            async move { fetcher.fetch(address, IOType::Coin).await },
+                        )
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|result| result);
+                        (index, fetched)
+                    });
+                }
+                Err(e) => *outcome = Some(Err(e)),
+            }
+        }
+        let concurrency = fetches.len();
+        let (fetched, stats) = tokio::join!(
+            run_bounded(fetches, concurrency),
+            self.relayer.get_market_stats(),
+        );
+        let mut synced = reused.clone();
+        for (index, utxo_detail) in fetched {
+            let fetched_at = self.clock.now();
+            let applied = utxo_detail.and_then(|utxo_detail| {
+                self.apply_fetched_utxo(index, utxo_detail, "open_trader_orders_batch", fetched_at)
+            });
+            match applied {
+                Ok(()) => {
+                    synced.insert(index);
+                }
+                Err(e) => {
+                    let position = orders.iter().position(|order| order.index == index);
+                    if let Some(position) = position {
+                        outcomes[position] = Some(Err(e));
+                    }
+                }
+            }
+        }
+        let stats = stats.map_err(|e| format!("Failed to fetch market stats: {}", e))?;
+
+        // Build and submit every order that passed.
+        let program = self.relayer_program().map_err(|e| e.to_string())?;
+        let send_key = matches!(
+            self.relayer.capabilities().await,
+            Ok(capabilities) if capabilities.supports(Capability::ClientOrderId)
+        );
+        let mut pending = HashMap::new();
+        let mut submissions = Vec::new();
+        let mut open_orders = self.open_trader_order_count();
+        for (order, outcome) in orders.iter().zip(outcomes.iter_mut()) {
+            if outcome.is_some() {
+                continue;
+            }
+            if !synced.contains(&order.index) {
+                *outcome = Some(Err(format!(
+                    "Could not fetch the UTXO for account {}",
+                    order.index
+                )));
+                continue;
+            }
+            let prepared = match self.prepare_trader_order(order, &stats, open_orders) {
+                Ok(prepared) => {
+                    open_orders += 1;
+                    prepared
+                }
+                Err(e) => {
+                    *outcome = Some(Err(e));
+                    continue;
+                }
+            };
+            let submission =
+                self.begin_submission(order.index, OrderKind::Trader, &prepared.address);
+            let client_order_id = send_key.then(|| submission.idempotency_key.clone());
+            pending.insert(order.index, submission);
+            let order = order.clone();
+            let program = program.clone();
+            let client = self.relayer.clone();
+            submissions.push(async move {
+                let context = format!("account {}", order.index);
+                let order_call = async {
+                    let order_tx = compat::guard("create_trader_order", &context, || {
+                        build_trader_order_with_input(
+                            prepared.input_coin,
+                            prepared.secret_key,
+                            prepared.r_scalar,
+                            prepared.initial_margin,
+                            order.order_side,
+                            order.order_type,
+                            order.leverage,
+                            order.entry_price,
+                            prepared.position_value,
+                            prepared.position_size,
+                            program.contracts(),
+                        )
+                    })
+                    .map_err(|e| e.to_string())??;
+                    client
+                        .submit_trade_order_with_key(order_tx, client_order_id.as_deref())
+                        .await
+                        .map(|response| response.id_key.to_string())
+                        .map_err(|e| e.to_string())
+                };
+                let result = compat::guard_async("submit_trade_order", &context, order_call)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|result| result);
+                (order.index, result)
+            });
+        }
+        let concurrency = submissions.len();
+        info!("Submitting {} trader orders as a batch", concurrency);
+        let submitted = run_bounded(submissions, concurrency).await;
+
+        for (index, result) in submitted {
+            let Some(position) = orders.iter().position(|order| order.index == index) else {
+                continue;
+            };
+            let order = &orders[position];
+            let Some(submission) = pending.remove(&index) else {
+                continue;
+            };
+            let key = Some(submission.idempotency_key.as_str());
+            let result = match result {
+                Ok(request_id) => {
+                    self.end_submission(index);
+                    Ok(request_id)
+                }
+                Err(e) => match self.find_submitted_order(&submission).await {
+                    Ok(Some(request_id)) => {
+                        warn!(
+                            "Batch submit on account {} failed ({}) but the relayer accepted the order as {}",
+                            index, e, request_id
+                        );
+                        self.end_submission(index);
+                        Ok(request_id)
+                    }
+                    Ok(None) | Err(_) => Err(e),
+                },
+            };
+            let result = match result {
+                Ok(request_id) => self
+                    .record_trader_order_open(order, &request_id, key)
+                    .map(|()| request_id),
+                Err(e) if reused.contains(&index) && is_stale_input_error(&e) => {
+                    warn!(
+                        "Cached UTXO for account {} looks stale; retrying with a fresh fetch",
+                        index
+                    );
+                    self.utxo_cache.update(|cache| cache.invalidate(index));
+                    self.open_trader_order_inner(
+                        index,
+                        order.order_type.clone(),
+                        order.order_side.clone(),
+                        order.entry_price,
+                        order.leverage,
+                    )
+                    .await
+                    .map_err(String::from)
+                }
+                Err(e) => Err(e),
+            };
+            outcomes[position] = Some(result);
+        }
+        self.record_batch_outcomes(orders, outcomes, &attempted);
+        Ok(())
+    }
+
+    fn record_batch_outcomes(
+        &mut self,
+        orders: &[TraderOrderParams],
+        outcomes: &[Option<Result<RequestId, String>>],
+        attempted: &[usize],
+    ) {
+        for &position in attempted {
+            if let Some(result) = &outcomes[position] {
+                let order = &orders[position];
+                self.record_trader_open_outcome(
+                    order.index,
+                    &order.order_type,
+                    order.entry_price,
+                    result,
+                );
+            }
+        }
+    }
+
+    /// Check `order` against the trading limits, the market limits in
+    /// `stats` and the risk limits with `open_orders` trader orders open, and
+    /// gather what submitting it needs.
+    fn prepare_trader_order(
+        &self,
+        order: &TraderOrderParams,
+        stats: &MarketStats,
+        open_orders: usize,
+    ) -> Result<PreparedTraderOrder, String> {
+        let account = self.zk_accounts.get_account(&order.index)?;
+        let initial_margin = account.balance;
+        self.trading_limits
+            .with(|limits| limits.check(initial_margin, order.leverage))
+            .map_err(|e| e.to_string())?;
+        check_open_order(stats, &order.order_side, initial_margin, order.leverage)?;
+        let position_value = order
+            .leverage
+            .apply(initial_margin)
+            .ok_or_else(|| "position_value overflow".to_string())?;
+        self.risk_limits
+            .check(position_value, order.leverage.as_f64(), open_orders)?;
+        let position_size = position_value
+            .checked_mul(order.entry_price)
+            .ok_or_else(|| "position_size overflow".to_string())?;
+        Ok(PreparedTraderOrder {
+            secret_key: self.get_secret_key(order.index)?,
+            r_scalar: account.get_scalar()?,
+            initial_margin,
+            position_value,
+            position_size,
+            address: self.zk_accounts.get_account_address(&order.index)?,
+            input_coin: self.utxo_input(order.index)?,
+        })
+    }
+
+    /// Poll the order opened on `index` until it leaves `PENDING` or `wait`
+    /// runs out, and return how long that took if it filled. Cancelled and
+    /// rejected orders get the same account updates as the cancel and
+    /// failed-order paths, and are recorded on the account.
+    async fn wait_for_open(
+        &mut self,
+        index: AccountIndex,
+        request_id: &str,
+        order: OrderKind,
+        wait: std::time::Duration,
+    ) -> Result<std::time::Duration, OpenWaitError> {
+        let started = self.clock.now();
+        loop {
+            let waited = (self.clock.now() - started).to_std().unwrap_or_default();
+            match self.poll_open_status(index, request_id).await {
+                Ok((status, reason)) => match OpenProgress::of(&status, order) {
+                    OpenProgress::Filled => return Ok(waited),
+                    OpenProgress::Pending => {}
+                    OpenProgress::Cancelled => {
+                        if !self.is_simulated() {
+                            if let Err(e) = self.unlock_cancelled_order(index) {
+                                warn!("Failed to unlock account {}: {}", index, e);
+                            }
+                        }
+                        let error = OpenWaitError::Cancelled {
+                            account_index: index,
+                            request_id: request_id.to_string(),
+                        };
+                        self.record_wait_failure(index, order, &error);
+                        return Err(error);
+                    }
+                    OpenProgress::Rejected => {
+                        if !self.is_simulated() {
+                            if let Err(e) = self.unlock_failed_order(index).await {
+                                warn!("Failed to unlock account {}: {}", index, e);
+                            }
+                        }
+                        let error = OpenWaitError::Rejected {
+                            account_index: index,
+                            request_id: request_id.to_string(),
+                            status: status.to_str().to_string(),
+                            reason: reason.unwrap_or_default(),
+                        };
+                        self.record_wait_failure(index, order, &error);
+                        return Err(error);
+                    }
+                },
+                Err(e) => debug!("Status poll for order {} failed: {}", request_id, e),
+            }
+            if waited >= wait {
+                return Err(OpenWaitError::TimedOutStillPending {
+                    account_index: index,
+                    request_id: request_id.to_string(),
+                    waited,
+                });
+            }
+            self.clock
+                .sleep(ORDER_WAIT_POLL_INTERVAL.min(wait - waited))
+                .await;
+        }
+    }
+
+    /// Tx hash of `request_id` once the relayer has indexed it, polling on
+    /// the wallet's clock.
+    async fn poll_tx_hash(&self, request_id: &str) -> Result<TxHash, String> {
+        fetch_tx_hash_with_clock(request_id, self.relayer.as_ref(), self.clock.as_ref()).await
+    }
+
+    /// Current status of the order opened as `request_id`, with the
+    /// relayer's reason if it gave one.
+    async fn poll_open_status(
+        &mut self,
+        index: AccountIndex,
+        request_id: &str,
+    ) -> Result<(OrderStatus, Option<String>), String> {
+        if let Some(order) = self.simulate(|simulation| simulation.trader_order(index)) {
+            let order = order.ok_or_else(|| format!("No simulated order on account {}", index))?;
+            return Ok((order.order_status, None));
+        }
+        let tx_hash = fetch_tx_hash_with_once(request_id, self.relayer.as_ref()).await?;
+        Ok((tx_hash.order_status, tx_hash.reason))
+    }
+
+    fn record_wait_failure(
+        &mut self,
+        index: AccountIndex,
+        order: OrderKind,
+        error: &OpenWaitError,
+    ) {
+        let operation = match order {
+            OrderKind::Trader => "open_trader_order_and_wait",
+            OrderKind::Lend => "open_lend_order_and_wait",
+        };
+        self.record_account_outcome::<(), _>(index, operation, &Err(error.to_string()));
+    }
+
+    async fn open_trader_order_inner(
+        &self,
+        index: AccountIndex,
+        order_type: OrderType,
+        order_side: PositionType,
+        entry_price: u64,
+        leverage: impl Into<Leverage>,
+    ) -> OrderWalletResult<String> {
+        let leverage = leverage.into();
+        self.ensure_coin_onchain(index)?;
+        if leverage.is_zero() {
+            return Err("Leverage must be greater than 0".into());
+        }
+        // Funded by a dry run: no chain UTXO backs the account, so the order is
+        // built on the account's own input.
+        let unfunded = self.dry_run && self.zk_accounts.get_account(&index)?.simulated;
+
+        if !unfunded {
+            self.ensure_fresh_utxo(index).await?;
+        }
+        // Pre-validate against the relayer's limits and risk engine before submitting
+        let initial_margin = self.zk_accounts.get_account(&index)?.balance;
+        self.ensure_trading_limits()
+            .await
+            .check(initial_margin, leverage)
+            .map_err(|e| e.to_string())?;
+        self.risk_limits.check(
+            leverage.apply(initial_margin).unwrap_or(u64::MAX),
+            leverage.as_f64(),
+            self.open_trader_order_count(),
+        )?;
+        self.validate_open_order(&order_side, initial_margin, leverage)
+            .await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let r_scalar = self.zk_accounts.get_account(&index)?.get_scalar()?;
+        let initial_margin = self.zk_accounts.get_account(&index)?.balance;
+        let position_value = leverage
+            .apply(initial_margin)
+            .ok_or_else(|| "position_value overflow".to_string())?;
+        let position_size = position_value
+            .checked_mul(entry_price)
+            .ok_or_else(|| "position_size overflow".to_string())?;
+        let program = self.relayer_program().map_err(|e| e.to_string())?;
+        // The input of the UTXO just synced (or still fresh in the cache), so
+        // the order is not built on a second fetch.
+        let account = self.zk_accounts.get_account(&index)?;
+        let input_coin = if unfunded {
+            account.get_new_account_input()?
+        } else {
+            self.utxo_input(index)?
+        };
+        let order = compat::guard("create_trader_order", &format!("account {}", index), || {
+            build_trader_order_with_input(
+                input_coin,
+                secret_key,
+                r_scalar,
+                initial_margin,
+                order_side.clone(),
+                order_type.clone(),
+                leverage,
+                entry_price,
+                position_value,
+                position_size,
+                program.contracts(),
+            )
+        })
+        .map_err(|e| e.to_string())??;
+        let mut idempotency_key = None;
+        let request_id = if self.dry_run {
+            dry_run_request_id()
+        } else {
+            let submission = self.begin_submission(index, OrderKind::Trader, &account_address);
+            let client_order_id = self.client_order_id(&submission).await;
+            let sent = self
+                .relayer
+                .submit_trade_order_with_key(order, client_order_id)
+                .await
+                .map(|response| response.id_key.to_string());
+            let request_id = self.finish_submission(&submission, sent).await?;
+            idempotency_key = Some(submission.idempotency_key);
+            request_id
+        };
+        if self.dry_run {
+            self.zk_accounts.mark_simulated(&index)?;
+        }
+        let params = TraderOrderParams::new(index, order_type, order_side, entry_price, leverage);
+        self.record_trader_order_open(&params, &request_id, idempotency_key.as_deref())?;
+
+        Ok(request_id)
+    }
+
+    /// Bookkeeping once the relayer accepted a trader order: track the
+    /// request ID, mark the account as an order memo and log the order.
+    fn record_trader_order_open(
+        &self,
+        params: &TraderOrderParams,
+        request_id: &str,
+        idempotency_key: Option<&str>,
+    ) -> Result<(), String> {
+        let index = params.index;
+        debug!(
+            "inserting request_id: {:?} for account index: {:?}",
+            request_id, index
+        );
+        self.cache_request_id(index, request_id, idempotency_key);
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let initial_margin = self.zk_accounts.get_account(&index)?.balance;
+
+        self.zk_accounts
+            .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
+        self.try_update_account_in_db(&index);
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.log_order_history(
+            index,
+            request_id,
+            "open",
+            &format!("{:?}", params.order_type),
+            Some(&format!("{:?}", params.order_side)),
+            initial_margin,
+            Some(params.entry_price as f64),
+            Some(params.leverage.whole_part()),
+            None,
+            "submitted",
+            None,
+        );
+        Ok(())
+    }
+
+    // -------------------------
+    // Submission idempotency
+    // -------------------------
+
+    /// Idempotency key the account's current order was submitted with, if
+    /// it went through [`open_trader_order`](Self::open_trader_order) or
+    /// [`open_lend_order`](Self::open_lend_order).
+    pub fn idempotency_key(&self, index: AccountIndex) -> Option<String> {
+        self.idempotency_keys.get(&index)
+    }
+
+    /// Order submits whose outcome is not known yet, by account index. See
+    /// [`reconcile_pending_orders`](Self::reconcile_pending_orders).
+    pub fn pending_submissions(&self) -> Vec<PendingSubmission> {
+        let mut submissions: Vec<_> = self.pending_submissions.snapshot().into_values().collect();
+        submissions.sort_by_key(|submission| submission.account_index);
+        submissions
+    }
+
+    /// Record that an order is about to be sent from `index`. A submission
+    /// left over from an earlier attempt at the same order keeps its key.
+    fn begin_submission(
+        &self,
+        index: AccountIndex,
+        kind: OrderKind,
+        account_address: &str,
+    ) -> PendingSubmission {
+        let submission = match self.pending_submissions.get(&index) {
+            Some(existing)
+                if existing.kind == kind && existing.account_address == account_address =>
+            {
+                existing
+            }
+            _ => PendingSubmission::new(index, kind, account_address, self.clock.now()),
+        };
+        self.pending_submissions.insert(index, submission.clone());
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let (Some(db_manager), false) = (&self.db_manager, self.dry_run) {
+            if let Err(e) = db_manager.save_pending_submission(&submission) {
+                error!("Failed to save pending submission to database: {}", e);
+                self.emit_db_sync_failed(index, "save_pending_submission", &e);
+            }
+        }
+        submission
+    }
+
+    /// The key to send with the submit, when the relayer accepts one.
+    async fn client_order_id<'a>(&self, submission: &'a PendingSubmission) -> Option<&'a str> {
+        match self.relayer.capabilities().await {
+            Ok(capabilities) if capabilities.supports(Capability::ClientOrderId) => {
+                Some(&submission.idempotency_key)
+            }
+            _ => None,
+        }
+    }
+
+    fn end_submission(&self, index: AccountIndex) {
+        self.pending_submissions.remove(&index);
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let (Some(db_manager), false) = (&self.db_manager, self.dry_run) {
+            if let Err(e) = db_manager.remove_pending_submission(index) {
+                error!("Failed to remove pending submission from database: {}", e);
+                self.emit_db_sync_failed(index, "remove_pending_submission", &e);
+            }
+        }
+    }
+
+    /// Ask the relayer for an order matching `submission`.
+    async fn find_submitted_order(
+        &self,
+        submission: &PendingSubmission,
+    ) -> Result<Option<RequestId>, String> {
+        let txs = self
+            .relayer
+            .transaction_hashes(TransactionHashArgs::AccountId {
+                id: submission.account_address.clone(),
+                status: None,
+                limit: None,
+                offset: None,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(submission.find_request_id(&txs))
+    }
+
+    /// Settle a submit. An error response means the relayer rejected the
+    /// order; any other failure may have happened after it was accepted, so
+    /// the relayer is asked once and, failing that, the submission stays
+    /// pending for the next attempt.
+    async fn finish_submission(
+        &self,
+        submission: &PendingSubmission,
+        sent: Result<RequestId, RpcError>,
+    ) -> OrderWalletResult<RequestId> {
+        let index = submission.account_index;
+        let err = match sent {
+            Ok(request_id) => {
+                self.end_submission(index);
+                return Ok(request_id);
+            }
+            Err(e @ RpcError::Call(_)) => {
+                self.end_submission(index);
+                return Err(e.into());
+            }
+            Err(e) => e,
+        };
+        match self.find_submitted_order(submission).await {
+            Ok(Some(request_id)) => {
+                warn!(
+                    "Submit on account {} failed ({}) but the relayer accepted the order as {}",
+                    index, err, request_id
+                );
+                self.end_submission(index);
+                Ok(request_id)
+            }
+            Ok(None) | Err(_) => {
+                warn!(
+                    "Submit on account {} failed ({}); submission {} is unconfirmed and is checked before the next attempt",
+                    index, err, submission.idempotency_key
+                );
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Settle an earlier unconfirmed submit on `index` before sending a new
+    /// order. Returns the order the relayer accepted, if any; a submission
+    /// the relayer does not know is kept, so the retry reuses its key.
+    async fn resolve_pending_submission(
+        &self,
+        index: AccountIndex,
+    ) -> Result<Option<(PendingSubmission, RequestId)>, String> {
+        let Some(submission) = self.pending_submissions.get(&index) else {
+            return Ok(None);
+        };
+        match self.find_submitted_order(&submission).await? {
+            Some(request_id) => {
+                info!(
+                    "Account {} already submitted order {} (idempotency key {})",
+                    index, request_id, submission.idempotency_key
+                );
+                self.end_submission(index);
+                Ok(Some((submission, request_id)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Track an order found on the relayer for `submission` as the account's
+    /// open order, with the same bookkeeping as an open that returned. A
+    /// trader order's type, side, price and leverage are read back from the
+    /// relayer; when that query fails the order is tracked without them.
+    async fn adopt_submitted_order(
+        &self,
+        submission: &PendingSubmission,
+        request_id: &str,
+    ) -> Result<(), String> {
+        let index = submission.account_index;
+        let key = Some(submission.idempotency_key.as_str());
+        let (tx_type, operation) = match submission.kind {
+            OrderKind::Trader => (TXType::ORDERTX, "open_trader_order"),
+            OrderKind::Lend => (TXType::LENDTX, "open_lend_order"),
+        };
+        let recorded = match submission.kind {
+            OrderKind::Trader => match self.submitted_trader_order_params(index).await {
+                Ok(params) => Some(self.record_trader_order_open(&params, request_id, key)),
+                Err(e) => {
+                    warn!(
+                        "Adopting order {} on account {} without its details: {}",
+                        request_id, index, e
+                    );
+                    None
+                }
+            },
+            OrderKind::Lend => Some(self.record_lend_order_open(index, request_id, key)),
+        };
+        match recorded {
+            Some(result) => result?,
+            None => {
+                self.cache_request_id(index, request_id, key);
+                self.zk_accounts
+                    .update_io_type(&index, IOType::Memo, Some(tx_type))?;
+                self.try_update_account_in_db(&index);
+            }
+        }
+        self.record_order_outcome(index, operation, &Ok::<_, String>(request_id.to_string()));
+        Ok(())
+    }
+
+    /// The parameters of the trader order the relayer holds for `index`.
+    async fn submitted_trader_order_params(
+        &self,
+        index: AccountIndex,
+    ) -> Result<TraderOrderParams, String> {
+        let query = self.build_trader_query(index)?;
+        let order = self
+            .relayer
+            .trader_order_info(query)
+            .await
+            .map_err(|e| e.to_string())?;
+        let leverage = Leverage::try_from_f64(order.leverage).map_err(|e| e.to_string())?;
+        Ok(TraderOrderParams::new(
+            index,
+            order.order_type,
+            order.position_type,
+            order.entryprice.round() as u64,
+            leverage,
+        ))
+    }
+
+    /// An order of the other kind was found while opening one: adopt it and
+    /// refuse the new order, since the account now holds an order.
+    async fn adopt_other_kind(
+        &self,
+        submission: &PendingSubmission,
+        request_id: &str,
+    ) -> Result<String, String> {
+        self.adopt_submitted_order(submission, request_id).await?;
+        Err(format!(
+            "Account {} already holds {} order {} from an earlier submit",
+            submission.account_index,
+            submission.kind.as_str(),
+            request_id
+        ))
+    }
+
+    /// Settle every unconfirmed submit, e.g. after a restart: an order the
+    /// relayer accepted is tracked as the account's open order, any other
+    /// record is dropped so the account can submit again.
+    pub async fn reconcile_pending_orders(&mut self) -> Result<Vec<ReconciledSubmission>, String> {
+        let mut reconciled = Vec::new();
+        for submission in self.pending_submissions() {
+            let reconciliation = match self.find_submitted_order(&submission).await? {
+                Some(request_id) => {
+                    self.adopt_submitted_order(&submission, &request_id).await?;
+                    Reconciliation::Accepted { request_id }
+                }
+                None => Reconciliation::NotFound,
+            };
+            self.end_submission(submission.account_index);
+            reconciled.push(ReconciledSubmission {
+                submission,
+                reconciliation,
+            });
+        }
+        Ok(reconciled)
+    }
+
+    pub async fn close_trader_order(
+        &self,
+        index: AccountIndex,
+        order_type: OrderType,
+        execution_price: f64,
+    ) -> OrderWalletResult<String> {
+        let _account = self.account_locks.lock(index).await;
+        if let Some(result) = self.simulate(|simulation| {
+            simulation
+                .settle_trader_order(index)
+                .map(|order| order.request_id)
+        }) {
+            self.record_order_outcome(index, "close_trader_order", &result);
+            return result.map_err(Into::into);
+        }
+        if self.dry_run {
+            let result = self.dry_run_unlock(index);
+            self.record_order_outcome(index, "close_trader_order", &result);
+            return result.map_err(Into::into);
+        }
+        let intent = self.journal_order(
+            PendingOperationKind::CloseOrder,
+            index,
+            OrderKind::Trader,
+            format!("{:?} at {}", order_type, execution_price),
+        );
+        let result = self
+            .close_trader_order_inner(index, order_type, execution_price)
+            .await;
+        self.finish_intent(intent, &result);
+        self.record_order_outcome(index, "close_trader_order", &result);
+        result.map_err(Into::into)
+    }
+
+    async fn close_trader_order_inner(
+        &self,
+        index: AccountIndex,
+        order_type: OrderType,
+        execution_price: f64,
+    ) -> Result<String, String> {
+        self.validate_market_not_halted().await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let trader_order = self.query_trader_order(index).await?;
+        if trader_order.order_status != OrderStatus::FILLED {
+            if trader_order.order_status == OrderStatus::LIQUIDATE
+                || trader_order.order_status == OrderStatus::SETTLED
+            {
+                let (_, request_id) = self.unlock_trader_order_inner(index).await?;
+                return Ok(request_id);
+            }
+            return Err(format!(
+                "Order is not filled, status: {}",
+                trader_order.order_status.to_str()
+            ));
+        }
+        let request_id = self.request_id(index)?;
+        let tx_hash = self.poll_tx_hash(&request_id).await?;
+        let output = tx_hash.get_output()?;
+
+        let order_type_str = format!("{:?}", order_type);
+        self.sync_account_state(index).await?;
+
+        let order_call = close_trader_order_internal_audited(
+            output,
+            &secret_key,
+            account_address.clone(),
+            tx_hash.order_id,
+            order_type.clone(),
+            execution_price,
+            self.relayer.as_ref(),
+            &self.signing_audit,
+            index,
+        );
+        let request_id = compat::guard_async(
+            "close_trader_order",
+            &format!("account {}", index),
+            order_call,
+        )
+        .await
+        .map_err(|e| e.to_string())??;
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.log_order_history(
+            index,
+            &request_id,
+            "close",
+            &order_type_str,
+            Some(&format!("{:?}", trader_order.position_type)),
+            trader_order.available_margin as u64,
+            Some(execution_price),
+            Some(trader_order.leverage as u64),
+            Some(trader_order.unrealized_pnl),
+            "submitted",
+            None,
+        );
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.save_order_snapshot(OrderSnapshot::of_trader_order(
+            index,
+            request_id.clone(),
+            OrderRecordKind::TraderClose,
+            &trader_order,
+            Some(execution_price),
+            self.clock.now(),
+        ));
+
+        Ok(request_id)
+    }
+
+    /// Close `fraction` of the filled trader order on `index`, in `(0, 1]`.
+    /// A fraction of 1 is [`close_trader_order`](Self::close_trader_order).
+    ///
+    /// A smaller fraction settles that share of the position size; the rest
+    /// stays open and the account stays a memo. The relayer must support
+    /// [`Capability::PartialClose`], and the settled position value must meet
+    /// its minimum position size. The account's balance is not adjusted
+    /// locally: it is re-read from the order the relayer reports, and the
+    /// memo UTXO is fetched again on next use.
+    pub async fn close_trader_order_partial(
+        &self,
+        index: AccountIndex,
+        fraction: f64,
+        order_type: OrderType,
+        execution_price: f64,
+    ) -> OrderWalletResult<String> {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            let result: Result<String, String> = Err(format!(
+                "Close fraction must be in (0, 1], got {}",
+                fraction
+            ));
+            self.record_order_outcome(index, "close_trader_order_partial", &result);
+            return result.map_err(Into::into);
+        }
+        if fraction == 1.0 {
+            return self
+                .close_trader_order(index, order_type, execution_price)
+                .await;
+        }
+        self.ensure_not_dry_run("close_trader_order_partial")?;
+        let _account = self.account_locks.lock(index).await;
+        if self.is_simulated() {
+            return Err("Partial closes are not available in simulated mode".into());
+        }
+        let intent = self.journal_order(
+            PendingOperationKind::CloseOrder,
+            index,
+            OrderKind::Trader,
+            format!("{} of {:?} at {}", fraction, order_type, execution_price),
+        );
+        let result = self
+            .close_trader_order_partial_inner(index, fraction, order_type, execution_price)
+            .await;
+        self.finish_intent(intent, &result);
+        self.record_order_outcome(index, "close_trader_order_partial", &result);
+        result.map_err(Into::into)
+    }
+
+    async fn close_trader_order_partial_inner(
+        &self,
+        index: AccountIndex,
+        fraction: f64,
+        order_type: OrderType,
+        execution_price: f64,
+    ) -> Result<String, String> {
+        self.relayer
+            .capabilities()
+            .await
+            .map_err(|e| e.to_string())?
+            .require(Capability::PartialClose)
+            .map_err(|e| e.to_string())?;
+        self.validate_market_not_halted().await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let trader_order = self.query_trader_order(index).await?;
+        if trader_order.order_status != OrderStatus::FILLED {
+            return Err(format!(
+                "Order is not filled, status: {}",
+                trader_order.order_status.to_str()
+            ));
+        }
+
+        let settle_size = trader_order.positionsize * fraction;
+        // Position value in sats, margin times leverage, as the limits count it.
+        let settle_value =
+            (trader_order.initial_margin * trader_order.leverage * fraction).floor() as u64;
+        let min_value = self.ensure_trading_limits().await.min_position_value.max(1);
+        if settle_value < min_value {
+            return Err(format!(
+                "Partial close settles a position value of {} sats, below relayer minimum {} sats",
+                settle_value, min_value
+            ));
+        }
+
+        let request_id = self.request_id(index)?;
+        let tx_hash = self.poll_tx_hash(&request_id).await?;
+        let output = tx_hash.get_output()?;
+        let order_type_str = format!("{:?}", order_type);
+        self.sync_account_state(index).await?;
+
+        let order_call = close_trader_order_partial_audited(
+            output,
+            &secret_key,
+            account_address.clone(),
+            tx_hash.order_id,
+            order_type.clone(),
+            settle_size,
+            execution_price,
+            self.relayer.as_ref(),
+            &self.signing_audit,
+            index,
+        );
+        let request_id = compat::guard_async(
+            "close_trader_order_partial",
+            &format!("account {}", index),
+            order_call,
+        )
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| {
+            format!(
+                "Relayer rejected partial close of {} of {} position size: {}",
+                settle_size, trader_order.positionsize, e
+            )
+        })?;
+
+        // The relayer replaces the memo output when it settles the share.
+        self.utxo_cache.update(|cache| cache.invalidate(index));
+        match self.query_trader_order(index).await {
+            Ok(order) if order.initial_margin < trader_order.initial_margin => {
+                self.set_account_balance(&index, order.initial_margin.round() as u64)?;
+                self.try_update_account_in_db(&index);
+            }
+            Ok(_) => debug!(
+                "Partial close of account {} not settled yet; balance follows on the next sync",
+                index
+            ),
+            Err(e) => warn!(
+                "Could not re-read the order of account {} after a partial close: {}",
+                index, e
+            ),
+        }
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.log_order_history(
+            index,
+            &request_id,
+            "close_partial",
+            &order_type_str,
+            Some(&format!("{:?}", trader_order.position_type)),
+            settle_value,
+            Some(execution_price),
+            Some(trader_order.leverage as u64),
+            // Realized only once the relayer settles the share.
+            None,
+            "submitted",
+            None,
+        );
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.save_order_snapshot(OrderSnapshot::of_trader_order(
+            index,
+            request_id.clone(),
+            OrderRecordKind::TraderPartialClose,
+            &trader_order,
+            Some(execution_price),
+            self.clock.now(),
+        ));
+
+        Ok(request_id)
+    }
+
+    /// Exit the trader order on `index` whatever state it is in: cancel it
+    /// while pending, close it once filled, and unlock the account back to
+    /// `Coin` if it has already settled or been liquidated.
+    ///
+    /// After a `MARKET` close the account is unlocked straight away if the
+    /// relayer has settled the position; otherwise it stays a memo until
+    /// [`unlock_trader_order`](Self::unlock_trader_order) succeeds.
+    pub async fn close_or_cancel_trader_order(
+        &mut self,
+        index: AccountIndex,
+        order_type: OrderType,
+        execution_price: f64,
+    ) -> Result<CloseOutcome, String> {
+        self.ensure_not_dry_run("close_or_cancel_trader_order")?;
+        let status = match self.simulate(|simulation| simulation.trader_order(index)) {
+            Some(order) => order
+                .map(|order| order.order_status)
+                .ok_or_else(|| format!("No simulated order on account {}", index)),
+            None => self
+                .query_trader_order(index)
+                .await
+                .map(|order| order.order_status)
+                .map_err(String::from),
+        };
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                self.record_account_outcome(
+                    index,
+                    "close_or_cancel_trader_order",
+                    &Err::<(), _>(e.clone()),
+                );
+                return Err(e);
+            }
+        };
+        let outcome = match status {
+            OrderStatus::PENDING => self
+                .cancel_trader_order(index)
+                .await
+                .map(CloseOutcome::Cancelled)?,
+            OrderStatus::FILLED => {
+                let request_id = self
+                    .close_trader_order(index, order_type.clone(), execution_price)
+                    .await?;
+                if !self.is_simulated() && matches!(order_type, OrderType::MARKET) {
+                    if let Err(e) = self.unlock_trader_order(index).await {
+                        debug!("Account {} closed but not settled yet: {}", index, e);
+                    }
+                }
+                CloseOutcome::Settled(request_id)
+            }
+            OrderStatus::SETTLED | OrderStatus::LIQUIDATE => {
+                if !self.is_simulated() {
+                    let result = self.unlock_trader_order(index).await;
+                    self.record_account_outcome(index, "close_or_cancel_trader_order", &result);
+                    result?;
+                }
+                match status {
+                    OrderStatus::LIQUIDATE => CloseOutcome::Liquidated,
+                    _ => CloseOutcome::AlreadySettled,
+                }
+            }
+            status => {
+                let error = format!(
+                    "Order cannot be closed or cancelled, status: {}",
+                    status.to_str()
+                );
+                self.record_account_outcome(
+                    index,
+                    "close_or_cancel_trader_order",
+                    &Err::<(), _>(error.clone()),
+                );
+                return Err(error);
+            }
+        };
+        self.try_update_account_in_db(&index);
+        Ok(outcome)
+    }
+
+    pub async fn close_trader_order_sltp(
+        &self,
+        index: AccountIndex,
+        order_type: OrderType,
+        execution_price: f64,
+        stop_loss_price: Option<f64>,
+        take_profit_price: Option<f64>,
+    ) -> OrderWalletResult<String> {
+        self.ensure_not_dry_run("close_trader_order_sltp")?;
+        let _account = self.account_locks.lock(index).await;
+        let result = self
+            .close_trader_order_sltp_inner(
+                index,
+                order_type,
+                execution_price,
+                stop_loss_price,
+                take_profit_price,
+            )
+            .await;
+        self.record_order_outcome(index, "close_trader_order_sltp", &result);
+        result.map_err(Into::into)
+    }
+
+    async fn close_trader_order_sltp_inner(
+        &self,
+        index: AccountIndex,
+        order_type: OrderType,
+        execution_price: f64,
+        stop_loss_price: Option<f64>,
+        take_profit_price: Option<f64>,
+    ) -> Result<String, String> {
+        self.validate_market_not_halted().await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let trader_order = self.query_trader_order(index).await?;
+        if trader_order.order_status != OrderStatus::FILLED {
+            if trader_order.order_status == OrderStatus::LIQUIDATE
+                || trader_order.order_status == OrderStatus::SETTLED
+            {
+                let (_, request_id) = self.unlock_trader_order_inner(index).await?;
+                return Ok(request_id);
+            }
+            return Err(format!(
+                "Invalid request, order status is {}",
+                trader_order.order_status.to_str()
+            ));
+        }
+
+        let request_id = self.request_id(index)?;
+        let tx_hash = self.poll_tx_hash(&request_id).await?;
+        let output = tx_hash.get_output()?;
+        // #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let order_type_str = format!("{:?}", order_type);
+        let order_call = close_trader_order_sltp_internal_audited(
+            output,
+            &secret_key,
+            account_address.clone(),
+            tx_hash.order_id,
+            order_type,
+            execution_price,
+            stop_loss_price,
+            take_profit_price,
+            self.relayer.as_ref(),
+            &self.signing_audit,
+            index,
+        );
+        let request_id = compat::guard_async(
+            "close_trader_order_sltp",
+            &format!("account {}", index),
+            order_call,
+        )
+        .await
+        .map_err(|e| e.to_string())??;
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        {
+            if stop_loss_price.is_some() {
+                self.log_order_history(
+                    index,
+                    &request_id,
+                    "close_sl",
+                    &order_type_str,
+                    Some(&format!("{:?}", trader_order.position_type)),
+                    trader_order.available_margin as u64,
+                    stop_loss_price,
+                    Some(trader_order.leverage as u64),
+                    Some(trader_order.unrealized_pnl),
+                    "submitted",
+                    None,
+                );
+            }
+        }
+        if take_profit_price.is_some() {
+            self.log_order_history(
+                index,
+                &request_id,
+                "close_tp",
+                &order_type_str,
+                Some(&format!("{:?}", trader_order.position_type)),
+                trader_order.available_margin as u64,
+                take_profit_price,
+                Some(trader_order.leverage as u64),
+                Some(trader_order.unrealized_pnl),
+                "submitted",
+                None,
+            );
+        }
+        Ok(request_id)
+    }
+
+    pub async fn query_trader_order(&self, index: AccountIndex) -> OrderWalletResult<TraderOrder> {
+        debug!("query_trader_order for account index: {:?}", index);
+        let query = self.build_trader_query(index)?;
+        match self.relayer.trader_order_info(query).await {
+            Ok(order) => {
+                self.note_order_status(index, OrderKind::Trader, &order.order_status);
+                Ok(order)
+            }
+            Err(e) => {
+                let request_id = self.request_id(index)?;
+                let tx_hash = fetch_tx_hash_with_once(&request_id, self.relayer.as_ref()).await?;
+                if tx_hash.order_status != OrderStatus::PENDING
+                    && tx_hash.order_status != OrderStatus::FILLED
+                    && tx_hash.order_status != OrderStatus::LIQUIDATE
+                {
+                    self.unlock_failed_order(index).await?;
+                    return Err(format!(
+                        "Lend order failed, status: {}, reason: {}",
+                        tx_hash.order_status.to_str(),
+                        tx_hash.reason.unwrap_or_default()
+                    )
+                    .into());
+                } else {
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    /// Query enhanced trader order info (v1) with settle_limit, take_profit, stop_loss, funding_applied.
+    ///
+    /// On relayers without `trader_order_info_v1` this falls back to
+    /// [`query_trader_order`](Self::query_trader_order) with the v1-only fields unset.
+    pub async fn query_trader_order_v1(
+        &self,
+        index: AccountIndex,
+    ) -> Result<super::relayer_types::TraderOrderV1, String> {
+        if !self.relayer.supports(Capability::OrderInfoV1).await {
+            let order = self.query_trader_order(index).await?;
+            return Ok(order.into());
+        }
+        let query = self.build_trader_query(index)?;
+        match self.relayer.trader_order_info_v1(query).await {
+            Ok(order) => Ok(order),
+            Err(e) => {
+                let request_id = self.request_id(index)?;
+                let tx_hash = fetch_tx_hash_with_once(&request_id, self.relayer.as_ref()).await?;
+                if tx_hash.order_status != OrderStatus::PENDING
+                    && tx_hash.order_status != OrderStatus::FILLED
+                    && tx_hash.order_status != OrderStatus::LIQUIDATE
+                {
+                    self.unlock_failed_order(index).await?;
+                    return Err(format!(
+                        "Lend order failed, status: {}, reason: {}",
+                        tx_hash.order_status.to_str(),
+                        tx_hash.reason.unwrap_or_default()
+                    ));
+                } else {
+                    return Err(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Query enhanced lend order info (v1) with unrealised profit and APR.
+    ///
+    /// On relayers without `lend_order_info_v1` this falls back to
+    /// [`query_lend_order`](Self::query_lend_order) without the profit details.
+    pub async fn query_lend_order_v1(
+        &self,
+        index: AccountIndex,
+    ) -> Result<super::relayer_types::LendOrderV1, String> {
+        if !self.relayer.supports(Capability::OrderInfoV1).await {
+            let order = self.query_lend_order(index).await?;
+            return Ok(order.into());
+        }
+        let query = self.build_lend_query(index)?;
+        match self.relayer.lend_order_info_v1(query).await {
+            Ok(order) => Ok(order),
+            Err(e) => {
+                let request_id = self.request_id(index)?;
+                let tx_hash = fetch_tx_hash_with_once(&request_id, self.relayer.as_ref()).await?;
+                if tx_hash.order_status != OrderStatus::SETTLED
+                    && tx_hash.order_status != OrderStatus::FILLED
+                {
+                    self.unlock_failed_order(index).await?;
+                    return Err(format!(
+                        "Lend order failed, status: {}, reason: {}",
+                        tx_hash.order_status.to_str(),
+                        tx_hash.reason.unwrap_or_default()
+                    ));
+                } else {
+                    return Err(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Query historical trader orders for an account.
+    pub async fn historical_trader_order(
+        &self,
+        index: AccountIndex,
+    ) -> Result<Vec<TraderOrder>, String> {
+        let query = self.build_trader_query(index)?;
+        self.relayer_api_client
+            .historical_trader_order_info(query)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Query historical lend orders for an account.
+    pub async fn historical_lend_order(
+        &self,
+        index: AccountIndex,
+    ) -> Result<Vec<LendOrder>, String> {
+        let query = self.build_lend_query(index)?;
+        self.relayer_api_client
+            .historical_lend_order_info(query)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Query funding payment history for a trader order on an account.
+    /// Fails with the relayer version if the relayer has no funding history.
+    pub async fn order_funding_history(
+        &self,
+        index: AccountIndex,
+    ) -> Result<Vec<super::relayer_types::FundingHistoryEntry>, String> {
+        if let Ok(capabilities) = self.relayer.capabilities().await {
+            capabilities
+                .require(Capability::FundingHistory)
+                .map_err(|e| e.to_string())?;
+        }
+        let query = self.build_trader_query(index)?;
+        self.relayer_api_client
+            .order_funding_history(query)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Funding paid or received by the trader position on an account, one
+    /// entry per funding interval from the order's open time to now, or to
+    /// its settlement or liquidation once it is closed.
+    ///
+    /// Unlike [`order_funding_history`](Self::order_funding_history) this
+    /// only needs the relayer's public funding rate history; each payment is
+    /// computed from the position size with [`super::portfolio::funding_payments`].
+    pub async fn position_funding_history(
+        &self,
+        index: AccountIndex,
+    ) -> Result<Vec<super::portfolio::FundingPayment>, String> {
+        self.request_id(index)?;
+        let order = self.query_trader_order(index).await?;
+        if order.order_status == OrderStatus::PENDING {
+            return Err(format!(
+                "Order on account {} is not filled yet, status: {}",
+                index,
+                order.order_status.to_str()
+            ));
+        }
+        let opened_at = DateTime::parse_from_rfc3339(&order.timestamp)
+            .map(|at| at.with_timezone(&Utc))
+            .map_err(|e| format!("Invalid order timestamp {}: {}", order.timestamp, e))?;
+        let until = match order.order_status {
+            OrderStatus::SETTLED | OrderStatus::LIQUIDATE => {
+                let account_address = self.zk_accounts.get_account_address(&index)?;
+                let txs = self
+                    .relayer
+                    .transaction_hashes(TransactionHashArgs::AccountId {
+                        id: account_address,
+                        status: None,
+                        limit: None,
+                        offset: None,
+                    })
+                    .await
+                    .map_err(|e| e.to_string())?;
+                order_closed_at(&order.uuid.to_string(), &txs).ok_or_else(|| {
+                    format!("No settlement record for the order on account {}", index)
+                })?
+            }
+            _ => self.clock.now(),
+        };
+
+        // The relayer may cap pages below the size asked for, so paging
+        // stops at the first page that adds nothing new.
+        let mut rates = Vec::new();
+        let mut seen = HashSet::new();
+        loop {
+            let args = super::relayer_types::HistoricalFundingArgs {
+                from: opened_at,
+                to: until,
+                limit: FUNDING_HISTORY_PAGE_SIZE,
+                offset: rates.len() as i64,
+            };
+            let page = self
+                .relayer_api_client
+                .historical_funding_rate(args)
+                .await
+                .map_err(|e| e.to_string())?;
+            let fetched = rates.len();
+            rates.extend(page.into_iter().filter(|rate| seen.insert(rate.id)));
+            if rates.len() == fetched {
+                break;
+            }
+        }
+
+        Ok(super::portfolio::funding_payments(
+            &order.position_type,
+            order.positionsize,
+            opened_at,
+            &rates,
+        ))
+    }
+
+    pub async fn cancel_trader_order(&self, index: AccountIndex) -> OrderWalletResult<String> {
+        let _account = self.account_locks.lock(index).await;
+        if let Some(result) = self.simulate(|simulation| {
+            simulation
+                .cancel_trader_order(index)
+                .map(|order| order.request_id)
+        }) {
+            self.record_order_outcome(index, "cancel_trader_order", &result);
+            return result.map_err(Into::into);
+        }
+        if self.dry_run {
+            let result = self.dry_run_unlock(index);
+            self.record_order_outcome(index, "cancel_trader_order", &result);
+            return result.map_err(Into::into);
+        }
+        let result = self.cancel_trader_order_inner(index).await;
+        self.record_order_outcome(index, "cancel_trader_order", &result);
+        result.map_err(Into::into)
+    }
+
+    async fn cancel_trader_order_inner(&self, index: AccountIndex) -> Result<String, String> {
+        self.validate_market_not_halted().await?;
+        let trader_orderv1 = self.query_trader_order_v1(index).await?;
+        self.cancel_queried_trader_order(index, trader_orderv1)
+            .await
+            .map(|(request_id, _)| request_id)
+    }
+
+    /// Cancel `trader_orderv1`, the order just queried for `index`. For a
+    /// pending limit order the cancel's transaction record is returned too.
+    async fn cancel_queried_trader_order(
+        &self,
+        index: AccountIndex,
+        trader_orderv1: super::relayer_types::TraderOrderV1,
+    ) -> Result<(String, Option<TxHash>), String> {
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let trader_order = trader_orderv1.order;
+        let is_pending_limit = trader_order.order_status == OrderStatus::PENDING;
+        let is_close_limit = trader_orderv1.settle_limit.is_some();
+        if !is_pending_limit && !is_close_limit {
+            return Err(format!(
+                "Order is not pending or close limit, status: {}",
+                trader_order.order_status.to_str()
+            ));
+        }
+        let order_call = cancel_trader_order_audited(
+            account_address.clone(),
+            &secret_key,
+            account_address.clone(),
+            trader_order.uuid,
+            self.relayer.as_ref(),
+            &self.signing_audit,
+            index,
+        );
+        let request_id = compat::guard_async(
+            "cancel_trader_order",
+            &format!("account {}", index),
+            order_call,
+        )
+        .await
+        .map_err(|e| e.to_string())??;
+        let mut cancel_tx = None;
+        if is_pending_limit {
+            let tx_hash = self.poll_tx_hash(&request_id).await?;
+            if tx_hash.order_status != OrderStatus::CANCELLED {
+                return Err(format!(
+                    "Order is not cancelled, status: {}",
+                    tx_hash.order_status.to_str()
+                ));
+            }
+            cancel_tx = Some(tx_hash);
+
+            self.return_to_coin(index)?;
+            self.try_update_account_in_db(&index);
+
+            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+            {
+                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
+                self.log_order_history(
+                    index,
+                    &request_id,
+                    "cancel_pending",
+                    "LIMIT",
+                    None,
+                    balance,
+                    None,
+                    None,
+                    None,
+                    "cancelled",
+                    None,
+                );
+            }
+        }
+        if is_close_limit {
+            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+            {
+                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
+                self.log_order_history(
+                    index,
+                    &request_id,
+                    "cancel_close_limit",
+                    "LIMIT",
+                    None,
+                    balance,
+                    None,
+                    None,
+                    None,
+                    "cancelled",
+                    None,
+                );
+            }
+        }
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        {
+            let mut snapshot = OrderSnapshot::of_trader_order(
+                index,
+                request_id.clone(),
+                OrderRecordKind::TraderCancel,
+                &trader_order,
+                None,
+                self.clock.now(),
+            );
+            if let Some(ref tx) = cancel_tx {
+                snapshot.order_status = tx.order_status.to_str().to_string();
+            }
+            self.save_order_snapshot(snapshot);
+        }
+        Ok((request_id, cancel_tx))
+    }
+
+    /// Replace the pending limit order on `index` with a limit order at
+    /// `new_price`, keeping its side and, unless `new_leverage` is given, its
+    /// leverage. Request IDs, IO types, order history and events are recorded
+    /// exactly as for `cancel_trader_order` followed by `open_trader_order`.
+    ///
+    /// A pending limit order has not spent the account's input. When the
+    /// cancel record confirms that, the UTXO cached before the original open
+    /// is reused without a fetch, whatever the UTXO cache TTL. If the record
+    /// shows the input consumed, or the relayer rejects the reused input as
+    /// stale, the UTXO is fetched again.
+    pub async fn replace_trader_order(
+        &mut self,
+        index: AccountIndex,
+        new_price: u64,
+        new_leverage: Option<Leverage>,
+    ) -> Result<ReplaceOrderReceipt, String> {
+        self.ensure_not_dry_run("replace_trader_order")?;
+        if let Some(original) = self.simulate(|simulation| simulation.trader_order(index)) {
+            let original =
+                original.ok_or_else(|| format!("No simulated order on account {}", index))?;
+            let leverage = match new_leverage {
+                Some(leverage) => leverage,
+                None => Leverage::try_from_f64(original.leverage).map_err(|e| e.to_string())?,
+            };
+            let cancel_request_id = self.cancel_trader_order(index).await?;
+            let request_id = self
+                .open_trader_order(
+                    index,
+                    OrderType::LIMIT,
+                    original.position_type,
+                    new_price,
+                    leverage,
+                )
+                .await?;
+            return Ok(ReplaceOrderReceipt {
+                account_index: index,
+                cancel_request_id,
+                request_id,
+                reused_utxo: false,
+            });
+        }
+
+        let cancelled = self.cancel_for_replace(index).await;
+        let outcome = cancelled
+            .as_ref()
+            .map(|(request_id, ..)| request_id.clone())
+            .map_err(Clone::clone);
+        self.record_order_outcome(index, "cancel_trader_order", &outcome);
+        let (cancel_request_id, position_type, original_leverage, cancel_tx) = cancelled?;
+
+        let leverage = match new_leverage {
+            Some(leverage) => leverage,
+            None => Leverage::try_from_f64(original_leverage).map_err(|e| e.to_string())?,
+        };
+        let carried = self.carry_utxo_over_cancel(index, &cancel_tx)?;
+        let request_id = self
+            .open_trader_order(index, OrderType::LIMIT, position_type, new_price, leverage)
+            .await?;
+        let reused_utxo = carried
+            && self
+                .utxo_freshness(index)
+                .is_some_and(|stamp| stamp.origin == REPLACE_ORIGIN);
+        Ok(ReplaceOrderReceipt {
+            account_index: index,
+            cancel_request_id,
+            request_id,
+            reused_utxo,
+        })
+    }
+
+    /// Move the pending limit order on `index` to `new_entry_price`, keeping
+    /// its side and leverage, and return the new request ID.
+    ///
+    /// The order's status is checked from its request ID first. An order that
+    /// filled before or during the amend is reported as
+    /// [`AmendError::AlreadyFilled`], so the caller can switch to managing the
+    /// position instead of retrying.
+    ///
+    /// The relayer API this crate targets has no in-place amend message, so
+    /// the amend runs as [`replace_trader_order`](Self::replace_trader_order):
+    /// a cancel and a reopen that reuses the cached UTXO when it can.
+    pub async fn amend_trader_order(
+        &mut self,
+        index: AccountIndex,
+        new_entry_price: u64,
+    ) -> Result<RequestId, AmendError> {
+        self.ensure_not_dry_run("amend_trader_order")
+            .map_err(AmendError::Failed)?;
+        let request_id = self.request_id(index).map_err(AmendError::Failed)?;
+        self.check_amendable(index, &request_id).await?;
+        let replaced = self
+            .replace_trader_order(index, new_entry_price, None)
+            .await;
+        match replaced {
+            Ok(receipt) => Ok(receipt.request_id),
+            // The fill may have raced the cancel.
+            Err(e) => match self.check_amendable(index, &request_id).await {
+                Err(filled @ AmendError::AlreadyFilled { .. }) => Err(filled),
+                _ => Err(AmendError::Failed(e)),
+            },
+        }
+    }
+
+    /// Whether the order behind `request_id` is still pending.
+    async fn check_amendable(
+        &mut self,
+        index: AccountIndex,
+        request_id: &str,
+    ) -> Result<(), AmendError> {
+        let status = match self.simulate(|simulation| simulation.trader_order(index)) {
+            Some(order) => order
+                .map(|order| order.order_status)
+                .ok_or_else(|| format!("No simulated order on account {}", index)),
+            None => self
+                .poll_tx_hash(request_id)
+                .await
+                .map(|tx_hash| tx_hash.order_status),
+        }
+        .map_err(AmendError::Failed)?;
+        match status {
+            OrderStatus::PENDING => Ok(()),
+            OrderStatus::FILLED => Err(AmendError::AlreadyFilled {
+                account_index: index,
+                request_id: request_id.to_string(),
+            }),
+            status => Err(AmendError::NotPending {
+                account_index: index,
+                request_id: request_id.to_string(),
+                status: status.to_str().to_string(),
+            }),
+        }
+    }
+
+    /// Cancel half of [`replace_trader_order`](Self::replace_trader_order):
+    /// only pending limit orders qualify, since cancelling a close limit
+    /// leaves the position open.
+    async fn cancel_for_replace(
+        &mut self,
+        index: AccountIndex,
+    ) -> Result<(String, PositionType, f64, TxHash), String> {
+        self.validate_market_not_halted().await?;
+        let trader_orderv1 = self.query_trader_order_v1(index).await?;
+        let order = &trader_orderv1.order;
+        if order.order_status != OrderStatus::PENDING {
+            return Err(format!(
+                "Only pending limit orders can be replaced, status: {}",
+                order.order_status.to_str()
+            ));
+        }
+        let position_type = order.position_type.clone();
+        let leverage = order.leverage;
+        let (request_id, cancel_tx) = self
+            .cancel_queried_trader_order(index, trader_orderv1)
+            .await?;
+        let cancel_tx =
+            cancel_tx.ok_or_else(|| "Cancel returned no transaction record".to_string())?;
+        Ok((request_id, position_type, leverage, cancel_tx))
+    }
+
+    /// Keep the UTXO cached for the original open of `index` for the
+    /// replacement order if `cancel_tx` shows the input left unspent;
+    /// otherwise drop it so the replacement fetches. Returns whether it was
+    /// kept.
+    fn carry_utxo_over_cancel(
+        &self,
+        index: AccountIndex,
+        cancel_tx: &TxHash,
+    ) -> Result<bool, String> {
+        if cancel_left_input_unspent(cancel_tx) && self.utxo_details.contains_key(&index) {
+            let state = AccountStateKey::of(&self.zk_accounts.get_account(&index)?);
+            let now = self.clock.now();
+            self.utxo_cache.update(|cache| {
+                cache.record(index, state.clone(), REPLACE_ORIGIN, now);
+                cache.carry_over(index, state);
+            });
+            debug!("Carrying cached UTXO for account {} over the cancel", index);
+            return Ok(true);
+        }
+        debug!(
+            "Cancel on account {} did not leave a reusable input; the replacement fetches",
+            index
+        );
+        self.uncache_utxo(index);
+        Ok(false)
+    }
+
+    pub async fn cancel_trader_order_sltp(
+        &self,
+        index: AccountIndex,
+        cancel_sl: bool,
+        cancel_tp: bool,
+    ) -> OrderWalletResult<String> {
+        self.ensure_not_dry_run("cancel_trader_order_sltp")?;
+        let _account = self.account_locks.lock(index).await;
+        let result = self
+            .cancel_trader_order_sltp_inner(index, cancel_sl, cancel_tp)
+            .await;
+        self.record_order_outcome(index, "cancel_trader_order_sltp", &result);
+        result.map_err(Into::into)
+    }
+
+    async fn cancel_trader_order_sltp_inner(
+        &self,
+        index: AccountIndex,
+        cancel_sl: bool,
+        cancel_tp: bool,
+    ) -> Result<String, String> {
+        self.validate_market_not_halted().await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let trader_orderv1 = self.query_trader_order_v1(index).await?;
+        let trader_order = trader_orderv1.order;
+        let is_sl_cancellable = trader_orderv1.stop_loss.is_some();
+        let is_tp_cancellable = trader_orderv1.take_profit.is_some();
+        if !is_sl_cancellable && !is_tp_cancellable {
+            return Err("Order is not sl or tp cancellable".to_string());
+        }
+        if trader_order.order_status != OrderStatus::FILLED {
+            return Err(format!(
+                "Order is not filled, status: {}",
+                trader_order.order_status.to_str()
+            ));
+        }
+        let sltp_cancel = SlTpOrderCancel::new(cancel_sl, cancel_tp);
+        let order_call = cancel_trader_order_sltp_audited(
+            account_address.clone(),
+            &secret_key,
+            account_address.clone(),
+            trader_order.uuid,
+            sltp_cancel,
+            self.relayer.as_ref(),
+            &self.signing_audit,
+            index,
+        );
+        let request_id = compat::guard_async(
+            "cancel_trader_order_sltp",
+            &format!("account {}", index),
+            order_call,
+        )
+        .await
+        .map_err(|e| e.to_string())??;
+        if cancel_sl && is_sl_cancellable {
+            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+            {
+                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
+                self.log_order_history(
+                    index,
+                    &request_id,
+                    "cancel_sl",
+                    "SLTP",
+                    None,
+                    balance,
+                    None,
+                    None,
+                    None,
+                    "sl_cancelled",
+                    None,
+                );
+            }
+        }
+        if cancel_tp && is_tp_cancellable {
+            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+            {
+                let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
+                self.log_order_history(
+                    index,
+                    &request_id,
+                    "cancel_tp",
+                    "SLTP",
+                    None,
+                    balance,
+                    None,
+                    None,
+                    None,
+                    "tp_cancelled",
+                    None,
+                );
+            }
+        }
+        Ok(request_id)
+    }
+
+    /// Check if a previously closed (e.g. SLTP) trader order has settled and, if so,
+    /// unlock the account by refreshing its UTXO, balance, and IO type back to `Coin`.
+    ///
+    /// Returns the current `OrderStatus` so the caller can decide what to do next.
+    pub async fn unlock_trader_order(
+        &self,
+        index: AccountIndex,
+    ) -> OrderWalletResult<(OrderStatus, String)> {
+        let _account = self.account_locks.lock(index).await;
+        self.unlock_trader_order_inner(index).await
+    }
+
+    async fn unlock_trader_order_inner(
+        &self,
+        index: AccountIndex,
+    ) -> OrderWalletResult<(OrderStatus, String)> {
+        let trader_order = self.query_trader_order(index).await?;
+
+        if trader_order.order_status != OrderStatus::SETTLED
+            && trader_order.order_status != OrderStatus::LIQUIDATE
+        {
+            return Err(OrderWalletError::InvalidOrderState {
+                expected: "SETTLED or LIQUIDATE".to_string(),
+                actual: trader_order.order_status.to_str().to_string(),
+            });
+        }
+        info!(
+            "PnL: {}, Net PnL: {}, Available Margin: {}",
+            LoggedAmount(trader_order.unrealized_pnl),
+            LoggedAmount(trader_order.available_margin - trader_order.initial_margin),
+            LoggedAmount(trader_order.available_margin)
+        );
+        let account_address = self.zk_accounts.get_account_address(&index)?.to_string();
+        let tx_hash = fetch_tx_hash_with_account_address_retry(
+            &account_address,
+            Some(trader_order.order_status.clone()),
+            self.relayer.as_ref(),
+            self.clock.as_ref(),
+        )
+        .await?;
+        let settled_request_id = tx_hash
+            .request_id
+            .clone()
+            .or_else(|| self.latest_request_id(index, OrderRecordKind::TraderClose));
+        let request_id = settled_request_id.clone().unwrap_or_default();
+        let utxo_detail = self
+            .utxo_fetcher
+            .fetch(account_address, IOType::Coin)
+            .await?;
+        self.settle_to_coin(index, trader_order.available_margin as u64, utxo_detail)?;
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.log_order_history(
+            index,
+            &request_id.to_string(),
+            "close",
+            "MARKET",
+            Some(&format!("{:?}", trader_order.position_type)),
+            trader_order.available_margin as u64,
+            Some(0.0),
+            Some(trader_order.leverage as u64),
+            Some(trader_order.unrealized_pnl),
+            &format!("{}", trader_order.order_status.to_str()),
+            Some(&tx_hash.tx_hash.clone()),
+        );
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        match settled_request_id {
+            Some(settled_request_id) => self.save_order_snapshot(OrderSnapshot::of_trader_order(
+                index,
+                settled_request_id,
+                OrderRecordKind::TraderClose,
+                &trader_order,
+                None,
+                self.clock.now(),
+            )),
+            None => warn!(
+                "No request ID for the settlement of account {}; snapshot not saved",
+                index
+            ),
+        }
+
+        Ok((trader_order.order_status, request_id))
+    }
+    /// Check if a previously closed lend order has settled and, if so,
+    /// unlock the account by refreshing its UTXO, balance, and IO type back to `Coin`.
+    ///
+    /// Returns the current `OrderStatus` so the caller can decide what to do next.
+    pub async fn unlock_lend_order(
+        &self,
+        index: AccountIndex,
+    ) -> OrderWalletResult<(OrderStatus, String)> {
+        let _account = self.account_locks.lock(index).await;
+        self.unlock_lend_order_inner(index).await
+    }
+
+    async fn unlock_lend_order_inner(
+        &self,
+        index: AccountIndex,
+    ) -> OrderWalletResult<(OrderStatus, String)> {
+        let lend_order = self.query_lend_order(index).await?;
+
+        if lend_order.order_status != OrderStatus::SETTLED {
+            return Err(OrderWalletError::InvalidOrderState {
+                expected: OrderStatus::SETTLED.to_str().to_string(),
+                actual: lend_order.order_status.to_str().to_string(),
+            });
+        }
+        info!(
+            "PnL: {}, Available Margin: {}",
+            LoggedAmount(lend_order.new_lend_state_amount - lend_order.deposit),
+            LoggedAmount(lend_order.new_lend_state_amount)
+        );
+        let account_address = self.zk_accounts.get_account_address(&index)?.to_string();
+        let tx_hash = fetch_tx_hash_with_account_address_retry(
+            &account_address,
+            Some(lend_order.order_status.clone()),
+            self.relayer.as_ref(),
+            self.clock.as_ref(),
+        )
+        .await?;
+        let settled_request_id = tx_hash
+            .request_id
+            .clone()
+            .or_else(|| self.latest_request_id(index, OrderRecordKind::LendClose));
+        let request_id = settled_request_id.clone().unwrap_or_default();
+        let utxo_detail = self
+            .utxo_fetcher
+            .fetch(account_address, IOType::Coin)
+            .await?;
+        self.settle_to_coin(index, lend_order.new_lend_state_amount as u64, utxo_detail)?;
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.log_order_history(
+            index,
+            &request_id.to_string(),
+            "close",
+            "LEND",
+            None,
+            lend_order.new_lend_state_amount as u64,
+            None,
+            None,
+            Some(lend_order.new_lend_state_amount - lend_order.deposit),
+            &format!("{}", lend_order.order_status.to_str()),
+            Some(&tx_hash.tx_hash.clone()),
+        );
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        match settled_request_id {
+            Some(settled_request_id) => self.save_order_snapshot(OrderSnapshot::of_lend_order(
+                index,
+                settled_request_id,
+                &lend_order,
+                self.clock.now(),
+            )),
+            None => warn!(
+                "No request ID for the settlement of account {}; snapshot not saved",
+                index
+            ),
+        }
+
+        Ok((lend_order.order_status, request_id))
+    }
+
+    pub async fn unlock_failed_order(&self, index: AccountIndex) -> OrderWalletResult<()> {
+        let account_address = self.zk_accounts.get_account_address(&index)?.to_string();
+        let balance = self.zk_accounts.get_balance(&index).unwrap_or(0);
+        let utxo_detail = self
+            .utxo_fetcher
+            .fetch(account_address, IOType::Coin)
+            .await?;
+        self.set_account_balance(&index, balance)?;
+        self.return_to_coin(index)?;
+        let account = output_account(index, &utxo_detail)?;
+        self.zk_accounts.update_qq_account(&index, account)?;
+        self.cache_utxo(index, utxo_detail);
+        self.try_update_account_in_db(&index);
+        Ok(())
+    }
+
+    /// Return the account of an order cancelled before it filled to `Coin`.
+    /// The order never spent the account's UTXO, so only local state changes.
+    pub(crate) fn unlock_cancelled_order(&self, index: AccountIndex) -> Result<(), String> {
+        self.return_to_coin(index)?;
+        self.try_update_account_in_db(&index);
+        Ok(())
+    }
+    // -------------------------
+    // Lend Order Operations
+    // -------------------------
+
+    pub async fn open_lend_order(&self, index: AccountIndex) -> OrderWalletResult<String> {
+        let _account = self.account_locks.lock(index).await;
+        if !self.dry_run {
+            let result = match self.resolve_pending_submission(index).await {
+                Ok(None) => None,
+                Ok(Some((submission, request_id))) if submission.kind == OrderKind::Lend => Some(
+                    self.record_lend_order_open(
+                        index,
+                        &request_id,
+                        Some(&submission.idempotency_key),
+                    )
+                    .map(|()| request_id),
+                ),
+                Ok(Some((submission, request_id))) => {
+                    Some(self.adopt_other_kind(&submission, &request_id).await)
+                }
+                Err(e) => Some(Err(e)),
+            };
+            if let Some(result) = result {
+                self.record_order_outcome(index, "open_lend_order", &result);
+                return result.map_err(Into::into);
+            }
+        }
+        let reused_utxo = self.has_reusable_utxo(index);
+        let balance = self
+            .zk_accounts
+            .get_account(&index)
+            .map(|account| account.balance)
+            .unwrap_or_default();
+        let intent = self.journal_order(
+            PendingOperationKind::OpenOrder,
+            index,
+            OrderKind::Lend,
+            format!("lend {} sats", balance),
+        );
+        let mut result = self.open_lend_order_inner(index).await;
+        if reused_utxo && matches!(&result, Err(e) if is_stale_input_error(&e.to_string())) {
+            warn!(
+                "Cached UTXO for account {} looks stale; retrying with a fresh fetch",
+                index
+            );
+            self.utxo_cache.update(|cache| cache.invalidate(index));
+            result = self.open_lend_order_inner(index).await;
+        }
+        self.finish_intent(intent, &result);
+        self.record_order_outcome(index, "open_lend_order", &result);
+        result
+    }
+
+    /// Lend-order counterpart of
+    /// [`open_trader_order_and_wait`](Self::open_trader_order_and_wait).
+    /// Not available in simulated mode, which has no lend book.
+    pub async fn open_lend_order_and_wait(
+        &mut self,
+        index: AccountIndex,
+        wait: std::time::Duration,
+    ) -> Result<FilledOrderReceipt<LendOrder>, OpenWaitError> {
+        self.ensure_not_dry_run("open_lend_order_and_wait")
+            .map_err(OpenWaitError::SubmissionFailed)?;
+        if self.is_simulated() {
+            return Err(OpenWaitError::SubmissionFailed(
+                "lend orders are not simulated".to_string(),
+            ));
+        }
+        let request_id = self
+            .open_lend_order(index)
+            .await
+            .map_err(|e| OpenWaitError::SubmissionFailed(e.to_string()))?;
+        let waited = self
+            .wait_for_open(index, &request_id, OrderKind::Lend, wait)
+            .await?;
+        let order = self.query_lend_order(index).await.map_err(|error| {
+            OpenWaitError::SnapshotUnavailable {
+                account_index: index,
+                request_id: request_id.clone(),
+                error: error.to_string(),
+            }
+        })?;
+        Ok(FilledOrderReceipt {
+            account_index: index,
+            request_id,
+            order,
+            waited,
+            fee_estimate: None,
+        })
+    }
+
+    async fn open_lend_order_inner(&self, index: AccountIndex) -> OrderWalletResult<String> {
+        self.validate_market_not_halted().await?;
+        self.ensure_coin_onchain(index)?;
+        // Funded by a dry run: no chain UTXO backs the account, so the order is
+        // built on the account's own input.
+        let unfunded = self.dry_run && self.zk_accounts.get_account(&index)?.simulated;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        // let _utxo_detail =
+        //     fetch_utxo_details_with_retry(account_address.clone(), IOType::Coin).await?;
+        if !unfunded {
+            self.ensure_fresh_utxo(index).await?;
+        }
+        let secret_key = self.get_secret_key(index)?;
+        let scalar_hex: String = self.zk_accounts.get_account(&index)?.scalar.clone();
+        let amount = self.zk_accounts.get_account(&index)?.balance;
+
+        let program = self.relayer_program().map_err(|e| e.to_string())?;
+        let account = self.zk_accounts.get_account(&index)?;
+        let input_coin = if unfunded {
+            account.get_new_account_input()?
+        } else {
+            self.utxo_input(index)?
+        };
+        let order = compat::guard("create_lend_order", &format!("account {}", index), || {
+            build_lend_order_with_input(
+                input_coin,
+                account_address.clone(),
+                secret_key,
+                amount,
+                program.contracts(),
+                scalar_hex,
+            )
+        })
+        .map_err(|e| e.to_string())??;
+        let mut idempotency_key = None;
+        let request_id = if self.dry_run {
+            dry_run_request_id()
+        } else {
+            let submission = self.begin_submission(index, OrderKind::Lend, &account_address);
+            let client_order_id = self.client_order_id(&submission).await;
+            let sent = self
+                .relayer
+                .submit_lend_order_with_key(order, client_order_id)
+                .await
+                .map(|response| response.id_key.to_string());
+            let request_id = self.finish_submission(&submission, sent).await?;
+            idempotency_key = Some(submission.idempotency_key);
+            request_id
+        };
+        if self.dry_run {
+            self.zk_accounts.mark_simulated(&index)?;
+        }
+        self.record_lend_order_open(index, &request_id, idempotency_key.as_deref())?;
+
+        Ok(request_id)
+    }
+
+    /// Bookkeeping once the relayer accepted a lend order: track the request
+    /// ID, mark the account as a lend memo and log the order.
+    fn record_lend_order_open(
+        &self,
+        index: AccountIndex,
+        request_id: &str,
+        idempotency_key: Option<&str>,
+    ) -> Result<(), String> {
+        self.cache_request_id(index, request_id, idempotency_key);
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let amount = self.zk_accounts.get_account(&index)?.balance;
+
+        // let utxo_detail = fetch_utxo_details_with_retry(account_address, IOType::Memo).await?;
+        // self.cache_utxo(index, utxo_detail);
+        self.zk_accounts
+            .update_io_type(&index, IOType::Memo, Some(TXType::LENDTX))?;
+        self.try_update_account_in_db(&index);
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        self.log_order_history(
+            index,
+            request_id,
+            "open",
+            "LEND",
+            None,
+            amount,
+            None,
+            None,
+            None,
+            "submitted",
+            None,
+        );
+        Ok(())
+    }
+
+    pub async fn query_lend_order(&self, index: AccountIndex) -> OrderWalletResult<LendOrder> {
+        let query = self.build_lend_query(index)?;
+        match self.relayer.lend_order_info(query).await {
+            Ok(order) => {
+                self.note_order_status(index, OrderKind::Lend, &order.order_status);
+                Ok(order)
+            }
+            Err(e) => {
+                let request_id = self.request_id(index)?;
+                let tx_hash = fetch_tx_hash_with_once(&request_id, self.relayer.as_ref()).await?;
+                if tx_hash.order_status != OrderStatus::SETTLED
+                    && tx_hash.order_status != OrderStatus::FILLED
+                {
+                    self.unlock_failed_order(index).await?;
+                    return Err(format!(
+                        "Lend order failed, status: {}, reason: {}",
+                        tx_hash.order_status.to_str(),
+                        tx_hash.reason.unwrap_or_default()
+                    )
+                    .into());
+                } else {
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    // -------------------------
+    // Open order listing
+    // -------------------------
+
+    /// The open trader order of every Memo account holding one, queried one
+    /// account at a time. Unlike
+    /// [`query_trader_order`](Self::query_trader_order) this needs no request
+    /// ID, so it also finds orders whose request IDs were lost in a restart.
+    /// Settled, cancelled and liquidated orders are left out; an account
+    /// whose query fails, or whose order kind
+    /// [`identify_memo_orders`](Self::identify_memo_orders) cannot tell, is
+    /// reported in [`OpenOrders::failed`] instead of failing the listing.
+    pub async fn list_open_orders(&self) -> OpenOrders<TraderOrder> {
+        let mut listing = OpenOrders::default();
+        for index in self.identify_memo_orders().await {
+            listing.failed.push((index, unknown_order_kind(index)));
+        }
+        for (index, kind) in self.memo_accounts() {
+            if kind != Some(OrderKind::Trader) {
+                continue;
+            }
+            match self.query_open_trader_order(index).await {
+                Ok(Some(order)) => listing.orders.push((index, order)),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(
+                        "Open orders: trader order on account {} unavailable: {}",
+                        index, e
+                    );
+                    listing.failed.push((index, e));
+                }
+            }
+        }
+        listing
+    }
+
+    /// [`list_open_orders`](Self::list_open_orders) for lend orders.
+    pub async fn list_open_lend_orders(&self) -> OpenOrders<LendOrder> {
+        let mut listing = OpenOrders::default();
+        for index in self.identify_memo_orders().await {
+            listing.failed.push((index, unknown_order_kind(index)));
+        }
+        for (index, kind) in self.memo_accounts() {
+            if kind != Some(OrderKind::Lend) {
+                continue;
+            }
+            match self.query_open_lend_order(index).await {
+                Ok(Some(order)) => listing.orders.push((index, order)),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(
+                        "Open orders: lend order on account {} unavailable: {}",
+                        index, e
+                    );
+                    listing.failed.push((index, e));
+                }
+            }
+        }
+        listing
+    }
+
+    /// Query the trader order with ID (`uuid`) `order_id`, whatever the local
+    /// state of the account that placed it. The relayer has no unsigned
+    /// order-by-ID query: the account is found from the relayer's record of
+    /// the order and the query is signed with its key, so only orders placed
+    /// by this wallet's accounts can be queried.
+    pub async fn query_trader_order_by_id(&self, order_id: &str) -> OrderWalletResult<TraderOrder> {
+        let index = self.order_account(order_id).await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let query = self.build_trader_query(index)?;
+        let order = self.relayer.trader_order_info(query).await?;
+        verify_trader_order_owner(&order, &account_address)?;
+        if !order.uuid.to_string().eq_ignore_ascii_case(order_id) {
+            return Err(format!(
+                "Account {} now holds trader order {}, not {}",
+                index, order.uuid, order_id
+            )
+            .into());
+        }
+        self.note_order_status(index, OrderKind::Trader, &order.order_status);
+        Ok(order)
+    }
+
+    /// [`query_trader_order_by_id`](Self::query_trader_order_by_id) for lend
+    /// orders.
+    pub async fn query_lend_order_by_id(&self, order_id: &str) -> OrderWalletResult<LendOrder> {
+        let index = self.order_account(order_id).await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let query = self.build_lend_query(index)?;
+        let order = self.relayer.lend_order_info(query).await?;
+        verify_lend_order_owner(&order, &account_address)?;
+        if !order.uuid.to_string().eq_ignore_ascii_case(order_id) {
+            return Err(format!(
+                "Account {} now holds lend order {}, not {}",
+                index, order.uuid, order_id
+            )
+            .into());
+        }
+        self.note_order_status(index, OrderKind::Lend, &order.order_status);
+        Ok(order)
+    }
+
+    /// Memo accounts with the kind of order they hold
+    /// ([`memo_order_kind`](Self::memo_order_kind)), by index.
+    pub(crate) fn memo_accounts(&self) -> Vec<(AccountIndex, Option<OrderKind>)> {
+        let mut accounts: Vec<_> = self
+            .zk_accounts
+            .get_all_accounts()
+            .iter()
+            .filter(|a| a.io_type == IOType::Memo)
+            .map(|a| (a.index, self.memo_order_kind(a)))
+            .collect();
+        accounts.sort_by_key(|(index, _)| *index);
+        accounts
+    }
+
+    /// Kind of the order Memo account `account` holds: its `tx_type`, or for
+    /// an account saved before the kind was kept, the kind of its latest
+    /// order record. `None` when neither tells; such an account is not
+    /// queried, closed or unlocked as either kind.
+    fn memo_order_kind(&self, account: &ZkAccount) -> Option<OrderKind> {
+        match account.tx_type {
+            Some(TXType::ORDERTX) => Some(OrderKind::Trader),
+            Some(TXType::LENDTX) => Some(OrderKind::Lend),
+            None => self
+                .order_history(account.index)
+                .last()
+                .map(|record| record.kind.order()),
+        }
+    }
+
+    /// Set the order kind of every Memo account without one, from its order
+    /// records or else from the relayer's records of its address
+    /// ([`memo_order`]), caching the request ID found there. Accounts saved
+    /// before the kind was kept have none. Returns the accounts whose kind
+    /// is still unknown, which order paths leave alone rather than guess.
+    pub async fn identify_memo_orders(&self) -> Vec<AccountIndex> {
+        let untyped: Vec<ZkAccount> = self
+            .zk_accounts
+            .get_all_accounts()
+            .into_iter()
+            .filter(|a| a.io_type == IOType::Memo && a.tx_type.is_none())
+            .collect();
+        let mut unknown = Vec::new();
+        for account in untyped {
+            let index = account.index;
+            let tx_type = match self.memo_order_kind(&account) {
+                Some(OrderKind::Trader) => TXType::ORDERTX,
+                Some(OrderKind::Lend) => TXType::LENDTX,
+                None => match self.lookup_memo_order(&account.account).await {
+                    Ok(Some((tx_type, request_id))) => {
+                        self.cache_request_id(index, &request_id, None);
+                        tx_type
+                    }
+                    Ok(None) => {
+                        warn!("The relayer has no order for Memo account {}", index);
+                        unknown.push(index);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Cannot look up the order of Memo account {}: {}", index, e);
+                        unknown.push(index);
+                        continue;
+                    }
+                },
+            };
+            match self
+                .zk_accounts
+                .update_io_type(&index, IOType::Memo, Some(tx_type.clone()))
+            {
+                Ok(()) => {
+                    self.try_update_account_in_db(&index);
+                    info!("Memo account {} holds a {:?} order", index, tx_type);
+                }
+                Err(e) => {
+                    warn!("Cannot mark the order of account {}: {}", index, e);
+                    unknown.push(index);
+                }
+            }
+        }
+        unknown
+    }
+
+    /// Kind and request ID of the newest order in the relayer's records of
+    /// `account_address` ([`memo_order`]).
+    async fn lookup_memo_order(
+        &self,
+        account_address: &str,
+    ) -> Result<Option<(TXType, String)>, String> {
+        let txs = self
+            .relayer
+            .transaction_hashes(TransactionHashArgs::AccountId {
+                id: account_address.to_string(),
+                status: None,
+                limit: None,
+                offset: None,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(memo_order(&txs))
+    }
+
+    /// The trader order of `index` if it is still pending or filled.
+    async fn query_open_trader_order(
+        &self,
+        index: AccountIndex,
+    ) -> Result<Option<TraderOrder>, String> {
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let query = self.build_trader_query(index)?;
+        let order = self
+            .relayer
+            .trader_order_info(query)
+            .await
+            .map_err(|e| e.to_string())?;
+        verify_trader_order_owner(&order, &account_address)?;
+        self.note_order_status(index, OrderKind::Trader, &order.order_status);
+        let open = matches!(
+            order.order_status,
+            OrderStatus::PENDING | OrderStatus::FILLED
+        );
+        Ok(open.then_some(order))
+    }
+
+    /// The lend order of `index` if it has not settled or been cancelled.
+    async fn query_open_lend_order(
+        &self,
+        index: AccountIndex,
+    ) -> Result<Option<LendOrder>, String> {
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let query = self.build_lend_query(index)?;
+        let order = self
+            .relayer
+            .lend_order_info(query)
+            .await
+            .map_err(|e| e.to_string())?;
+        verify_lend_order_owner(&order, &account_address)?;
+        self.note_order_status(index, OrderKind::Lend, &order.order_status);
+        let open = matches!(
+            order.order_status,
+            OrderStatus::PENDING | OrderStatus::FILLED | OrderStatus::LENDED
+        );
+        Ok(open.then_some(order))
+    }
+
+    /// Index of the local account that placed `order_id`, from the relayer's
+    /// transaction records.
+    async fn order_account(&self, order_id: &str) -> OrderWalletResult<AccountIndex> {
+        let txs = self
+            .relayer
+            .transaction_hashes(TransactionHashArgs::TxId {
+                id: order_id.to_string(),
+                status: None,
+                limit: None,
+                offset: None,
+            })
+            .await?;
+        let account_address = txs
+            .iter()
+            .find(|tx| tx.order_id.eq_ignore_ascii_case(order_id))
+            .map(|tx| tx.account_id.clone())
+            .ok_or_else(|| format!("Relayer has no record of order {}", order_id))?;
+        self.zk_accounts
+            .get_all_accounts()
+            .iter()
+            .find(|a| a.account == account_address)
+            .map(|a| a.index)
+            .ok_or_else(|| {
+                format!(
+                    "Order {} was placed by account {}, which this wallet does not hold",
+                    order_id, account_address
+                )
+                .into()
+            })
+    }
+
+    pub async fn close_lend_order(&self, index: AccountIndex) -> OrderWalletResult<String> {
+        let _account = self.account_locks.lock(index).await;
+        let result = if self.dry_run {
+            self.dry_run_unlock(index)
+        } else {
+            let intent = self.journal_order(
+                PendingOperationKind::CloseOrder,
+                index,
+                OrderKind::Lend,
+                "lend".to_string(),
+            );
+            let result = self.close_lend_order_inner(index).await;
+            self.finish_intent(intent, &result);
+            result
+        };
+        self.record_order_outcome(index, "close_lend_order", &result);
+        result.map_err(Into::into)
+    }
+
+    async fn close_lend_order_inner(&self, index: AccountIndex) -> Result<String, String> {
+        self.validate_market_not_halted().await?;
+        self.sync_account_state(index).await?;
+        let account_address = self.zk_accounts.get_account_address(&index)?;
+        let secret_key = self.get_secret_key(index)?;
+        let lend_order = self.query_lend_order(index).await?;
+        if lend_order.order_status == OrderStatus::SETTLED {
+            let (_, request_id) = self.unlock_lend_order_inner(index).await?;
+            return Ok(request_id);
+        }
+        if lend_order.order_status != OrderStatus::FILLED {
+            return Err(format!(
+                "Order is not filled, status: {}",
+                lend_order.order_status.to_str()
+            ));
+        }
+        let request_id = self.request_id(index)?;
+        let tx_hash = self.poll_tx_hash(&request_id).await?;
+        let output = tx_hash.get_output()?;
+        let order_call = close_lend_order_audited(
+            output,
+            &secret_key,
+            account_address.clone(),
+            tx_hash.order_id,
+            OrderType::LEND,
+            self.relayer.as_ref(),
+            &self.signing_audit,
+            index,
+        );
+        let request_id = compat::guard_async(
+            "close_lend_order",
+            &format!("account {}", index),
+            order_call,
+        )
+        .await
+        .map_err(|e| e.to_string())??;
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        {
+            let pnl = lend_order.new_lend_state_amount - lend_order.deposit;
+            self.log_order_history(
+                index,
+                &request_id,
+                "close",
+                "LEND",
+                None,
+                lend_order.new_lend_state_amount as u64,
+                None,
+                None,
+                Some(pnl),
+                "submitted",
+                None,
+            );
+            self.save_order_snapshot(OrderSnapshot::of_lend_order(
+                index,
+                request_id.clone(),
+                &lend_order,
+                self.clock.now(),
+            ));
+        }
+
+        Ok(request_id)
+    }
+
+    // -------------------------
+    // Database Operations
+    // -------------------------
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn sync_zk_account_to_db(
+        &self,
+        account: &crate::zkos_accounts::zkaccount::ZkAccount,
+    ) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.save_zk_account(account)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn update_zk_account_in_db(
+        &self,
+        account: &crate::zkos_accounts::zkaccount::ZkAccount,
+    ) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.update_zk_account(account)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn remove_zk_account_from_db(&self, account_index: u64) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.remove_zk_account(account_index)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn get_all_zk_accounts_from_db(
+        &self,
+    ) -> Result<HashMap<u64, crate::zkos_accounts::zkaccount::ZkAccount>, String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.load_all_zk_accounts()
+        } else {
+            Err("Database manager not initialized".to_string())
+        }
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    /// Enable database persistence with automatic password prompt
+    pub fn enable_database_persistence(
+        &mut self,
+        wallet_password: Option<SecretString>,
+        wallet_id: Option<String>,
+    ) -> Result<(), String> {
+        let wallet_password = match wallet_password {
+            Some(password) => password,
+            None => SecurePassword::get_passphrase_with_prompt(
+                "Could not find passphrase from environment, \nplease enter wallet encryption password: ",
+            )
+            .map_err(|e| format!("Failed to get password: {}", e))?,
+        };
+
+        // Generate wallet ID from wallet address
+        let wallet_id = match wallet_id {
+            Some(id) => id,
+            None => self.wallet.twilightaddress.clone(),
+        };
+
+        // Initialize database connection and run migrations
+        let pool = crate::database::connection::init_pool(None)?;
+        run_migrations_once(&pool)?;
+        self.attach_database(wallet_password, wallet_id, pool)
+    }
+
+    /// Save the wallet to a new `wallet_id` in `pool` and keep persisting
+    /// to it.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub(crate) fn attach_database(
+        &mut self,
+        wallet_password: SecretString,
+        wallet_id: String,
+        pool: crate::database::connection::DbPool,
+    ) -> Result<(), String> {
+        // Create database manager
+        // let wallet_list = DatabaseManager::get_wallet_list(&pool)?;
+        // if wallet_list.iter().any(|w| w.wallet_id == wallet_id) {
+        //     return Err(format!("Wallet ID already exists: {}", wallet_id));
+        // }
+        if DatabaseManager::check_wallet_id_exists(&pool, &wallet_id)? {
+            return Err(format!("Wallet ID already exists: {}", wallet_id));
+        }
+        let db_manager = DatabaseManager::new(wallet_id, pool);
+        // Save encrypted wallet if password is provided
+
+        db_manager.save_encrypted_wallet(&self.wallet, &wallet_password)?;
+        db_manager.unlock_zk_accounts(&wallet_password)?;
+
+        // Save existing zk accounts
+        for account in self.persisted_accounts() {
+            db_manager.save_zk_account(&account)?;
+        }
+        let now = self.clock.now();
+        for account in self.zk_accounts.get_archived_accounts() {
+            db_manager.archive_zk_account(&account, now)?;
+        }
+        for entry in self.address_book.entries() {
+            db_manager.save_address_book_entry(entry)?;
+        }
+
+        // Carry over entries signed before persistence was enabled.
+        if self.signing_audit.is_enabled() {
+            for entry in self.signing_audit.entries_since(DateTime::<Utc>::MIN_UTC) {
+                db_manager.save_signing_audit_entry(&entry)?;
+            }
+            self.signing_audit.set_sink(Arc::new(db_manager.clone()));
+        }
+
+        self.db_manager = Some(db_manager);
+        self.passphrase.unlock(wallet_password, now);
+        self.passphrase.arm_auto_lock(self.clock.clone());
+        self.activity.mark_all_dirty();
+        self.persist_activity()?;
+        Ok(())
+    }
+
+    /// Save the OrderWallet configuration to database. Fails with
+    /// [`WalletLocked`] while the wallet is locked.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn save_order_wallet_to_db(&self) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            let seed = self.seed.secret()?;
+            self.with_wallet_password(|password| {
+                db_manager.save_order_wallet(
+                    &self.chain_id,
+                    seed.expose_secret(),
+                    &self.relayer_endpoint_config,
+                    &self.config_fingerprint(),
+                    password.expose_secret(),
+                )
+            })??;
+
+            Ok(())
+        } else {
+            Ok(()) // No database persistence enabled
+        }
+    }
+
+    /// Re-encrypt and save the wallet blob (keys, BTC address). Fails with
+    /// [`WalletLocked`] while the wallet is locked.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn save_encrypted_wallet_to_db(&self) -> Result<(), String> {
+        let db_manager = self
+            .db_manager
+            .as_ref()
+            .ok_or_else(|| "Database persistence is not enabled".to_string())?;
+        self.with_wallet_password(|password| {
+            db_manager.save_encrypted_wallet(&self.wallet, password)
+        })?
+    }
+
+    /// Sync UTXO detail to database
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn sync_utxo_detail_to_db(
+        &self,
+        account_index: u64,
+        utxo_detail: &UtxoDetailResponse,
+    ) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.save_utxo_detail(account_index, utxo_detail)?;
+        }
+        Ok(())
+    }
+
+    /// Sync request ID, and the idempotency key it was submitted with, to database
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn sync_request_id_to_db(
+        &self,
+        account_index: u64,
+        request_id: &str,
+        idempotency_key: Option<&str>,
+    ) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.save_request_id_with_key(account_index, request_id, idempotency_key)?;
+        }
+        Ok(())
+    }
+
+    /// Load all UTXO details from database
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn load_all_utxo_details_from_db(&mut self) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            let utxo_details = db_manager.load_all_utxo_details()?;
+            self.utxo_details.replace(utxo_details);
+        }
+        Ok(())
+    }
+
+    /// Load all request IDs from database
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn load_all_request_ids_from_db(&mut self) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            let request_ids = db_manager.load_all_request_ids()?;
+            self.request_ids.replace(request_ids);
+            self.idempotency_keys
+                .replace(db_manager.load_idempotency_keys()?);
+        }
+        Ok(())
+    }
+
+    /// Load unconfirmed order submits from database; see
+    /// [`reconcile_pending_orders`](Self::reconcile_pending_orders).
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn load_pending_submissions_from_db(&mut self) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            self.pending_submissions
+                .replace(db_manager.load_pending_submissions()?);
+        }
+        Ok(())
+    }
+
+    /// Load the order history of every account from database
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn load_order_records_from_db(&mut self) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            self.order_records.replace(db_manager.load_order_records()?);
+        }
+        Ok(())
+    }
+
+    /// Remove UTXO detail from database
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn remove_utxo_detail_from_db(&self, account_index: u64) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.remove_utxo_detail(account_index)?;
+        }
+        Ok(())
+    }
+    /// Remove request ID from database
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn remove_request_id_from_db(&self, account_index: u64) -> Result<(), String> {
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.remove_request_id(account_index)?;
+        }
+        Ok(())
+    }
+
+    // -------------------------
+    // Transaction History
+    // -------------------------
+
+    /// Log an order action (open/close/cancel) to the history table.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn log_order_history(
+        &self,
+        account_index: AccountIndex,
+        request_id: &str,
+        action: &str,
+        order_type: &str,
+        position_type: Option<&str>,
+        amount: u64,
+        price: Option<f64>,
+        leverage: Option<u64>,
+        pnl: Option<f64>,
+        status: &str,
+        tx_hash: Option<&str>,
+    ) {
+        if self.dry_run {
+            return;
+        }
+        if let Some(ref db_manager) = self.db_manager {
+            let entry = crate::database::models::NewDbOrderHistory {
+                wallet_id: db_manager.get_wallet_id().to_string(),
+                account_index: account_index as i64,
+                request_id: request_id.to_string(),
+                action: action.to_string(),
+                order_type: order_type.to_string(),
+                position_type: position_type.map(|s| s.to_string()),
+                amount: amount as i64,
+                price,
+                leverage: leverage.map(|l| l as i64),
+                pnl,
+                status: status.to_string(),
+                tx_hash: tx_hash.map(|s| s.to_string()),
+                created_at: self.clock.now().naive_utc(),
+                network_type: crate::config::NETWORK_TYPE.to_string(),
+            };
+            if let Err(e) = db_manager.save_order_history(entry) {
+                error!("Failed to log order history: {}", e);
+            }
+        }
+    }
+
+    /// Store `snapshot` for [`trade_history`](Self::trade_history), replacing
+    /// the one stored for the same request.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn save_order_snapshot(&self, snapshot: OrderSnapshot) {
+        if self.dry_run {
+            return;
+        }
+        if let Some(ref db_manager) = self.db_manager {
+            if let Err(e) = db_manager.save_order_snapshot(&snapshot) {
+                error!("Failed to save order snapshot: {}", e);
+            }
+        }
+    }
+
+    /// Log a transfer action (fund_to_trade/trade_to_fund/trade_to_trade) to the history table.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn log_transfer_history(
+        &self,
+        direction: &str,
+        from_index: Option<AccountIndex>,
+        to_index: Option<AccountIndex>,
+        amount: u64,
+        tx_hash: Option<&str>,
+    ) {
+        if self.dry_run {
+            return;
+        }
+        if let Some(ref db_manager) = self.db_manager {
+            let entry = crate::database::models::NewDbTransferHistory {
+                wallet_id: db_manager.get_wallet_id().to_string(),
+                direction: direction.to_string(),
+                from_index: from_index.map(|i| i as i64),
+                to_index: to_index.map(|i| i as i64),
+                amount: amount as i64,
+                tx_hash: tx_hash.map(|s| s.to_string()),
+                created_at: self.clock.now().naive_utc(),
+                network_type: crate::config::NETWORK_TYPE.to_string(),
+            };
+            if let Err(e) = db_manager.save_transfer_history(entry) {
+                error!("Failed to log transfer history: {}", e);
+            }
+        }
+    }
+
+    /// Query order history with optional filters.
+    /// Requires database persistence to be enabled.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn get_order_history(
+        &self,
+        filter: super::transaction_history::OrderHistoryFilter,
+    ) -> Result<Vec<super::transaction_history::OrderHistoryEntry>, String> {
+        let db_manager = self.db_manager.as_ref().ok_or("Database not enabled")?;
+
+        let limit = filter.limit.unwrap_or(100);
+        let offset = filter.offset.unwrap_or(0);
+
+        let rows = if let Some(account_index) = filter.account_index {
+            db_manager.load_order_history_by_account(account_index, limit, offset)?
+        } else {
+            db_manager.load_order_history(limit, offset)?
+        };
+
+        Ok(rows
+            .iter()
+            .map(super::transaction_history::OrderHistoryEntry::from_db)
+            .collect())
+    }
+
+    /// Query transfer history with optional filters.
+    /// Requires database persistence to be enabled.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn get_transfer_history(
+        &self,
+        filter: super::transaction_history::TransferHistoryFilter,
+    ) -> Result<Vec<super::transaction_history::TransferHistoryEntry>, String> {
+        let db_manager = self.db_manager.as_ref().ok_or("Database not enabled")?;
+
+        let limit = filter.limit.unwrap_or(100);
+        let offset = filter.offset.unwrap_or(0);
+
+        let rows = db_manager.load_transfer_history(limit, offset)?;
+
+        Ok(rows
+            .iter()
+            .map(super::transaction_history::TransferHistoryEntry::from_db)
+            .collect())
+    }
+
+    /// Write a statement of the wallet's fund movements in `[from, to)` to
+    /// `writer` and return its totals; see [`statement`](super::statement)
+    /// for the rows and columns. Transfers, fees, faucet receipts and the
+    /// margins of opens come from the database; without one the statement
+    /// lists order requests only.
+    pub fn export_statement(
+        &self,
+        format: StatementFormat,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        writer: impl std::io::Write,
+    ) -> Result<StatementSummary, String> {
+        if to < from {
+            return Err(format!(
+                "Statement period ends ({}) before it starts ({})",
+                to, from
+            ));
+        }
+        let trades = self.trade_history()?;
+        #[allow(unused_mut)]
+        let mut transfers = Vec::new();
+        #[allow(unused_mut)]
+        let mut logged: HashMap<String, super::statement::LoggedRequest> = HashMap::new();
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(ref db_manager) = self.db_manager {
+            let (start, end) = (from.naive_utc(), to.naive_utc());
+            transfers = db_manager
+                .load_transfer_history_between(start, end)?
+                .into_iter()
+                .filter_map(|row| {
+                    super::statement::StatementRow::of_transfer(
+                        row.id,
+                        &row.direction,
+                        row.from_index.map(|i| i as u64),
+                        row.to_index.map(|i| i as u64),
+                        row.amount as u64,
+                        row.tx_hash,
+                        row.created_at.and_utc(),
+                    )
+                })
+                .collect();
+            for row in db_manager.load_order_history_between(start, end)? {
+                let request = logged.entry(row.request_id).or_default();
+                request.amount.get_or_insert(row.amount as u64);
+                if row.tx_hash.is_some() {
+                    request.tx_hash = row.tx_hash;
+                }
+            }
+        }
+        let rows = statement_rows(transfers, &trades, &logged, from, to);
+        write_statement(&rows, format, writer)?;
+        Ok(StatementSummary::of_rows(&rows, from, to))
+    }
+
+    /// Save a submitted BTC withdrawal to `btc_withdrawals`, and to transfer
+    /// history as `btc_withdrawal` so history exports include it.
+    /// Requires database persistence to be enabled.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    pub fn record_btc_withdrawal(
+        &self,
+        submission: &crate::wallet::btc_withdrawal::BtcWithdrawalSubmission,
+    ) -> Result<(), String> {
+        let db_manager = self.db_manager.as_ref().ok_or("Database not enabled")?;
+        let now = self.clock.now().naive_utc();
+        db_manager.save_btc_withdrawal(crate::database::models::NewDbBtcWithdrawal {
+            wallet_id: db_manager.get_wallet_id().to_string(),
+            network_type: crate::config::NETWORK_TYPE.to_string(),
+            withdraw_address: submission.id.btc_address.clone(),
+            twilight_address: self.wallet.twilightaddress.clone(),
+            reserve_id: submission.id.reserve_id as i64,
+            amount: submission.id.amount_sats as i64,
+            tx_hash: Some(submission.tx.hash.clone()),
+            status: "submitted".to_string(),
+            created_at: now,
+            updated_at: now,
+        })?;
+        self.log_transfer_history(
+            "btc_withdrawal",
+            None,
+            None,
+            submission.id.amount_sats,
+            Some(&submission.tx.hash),
+        );
+        Ok(())
+    }
+
+    // -------------------------
+    // Address book
+    // -------------------------
+
+    /// Labelled addresses the send paths accept in place of an address; see
+    /// [`address_book`](super::address_book).
+    pub fn address_book(&self) -> &AddressBook {
+        &self.address_book
+    }
+
+    /// Check `address` for `kind` and store it under `label`, in the
+    /// database when persistence is enabled, otherwise in the
+    /// [address book file](Self::with_address_book_file) if there is one.
+    pub fn add_address_book_entry(
+        &mut self,
+        label: &str,
+        address: &str,
+        kind: AddressKind,
+    ) -> Result<AddressBookEntry, AddressBookError> {
+        let entry = self
+            .address_book
+            .add_entry(label, address, kind, self.clock.now())?;
+        if let Err(e) = self.store_address_book_change(label) {
+            self.address_book.remove_entry(label);
+            return Err(AddressBookError::Storage(e));
+        }
+        Ok(entry)
+    }
+
+    pub fn remove_address_book_entry(
+        &mut self,
+        label: &str,
+    ) -> Result<Option<AddressBookEntry>, AddressBookError> {
+        let before = self.address_book.clone();
+        let Some(entry) = self.address_book.remove_entry(label) else {
+            return Ok(None);
+        };
+        if let Err(e) = self.store_address_book_change(label) {
+            self.address_book = before;
+            return Err(AddressBookError::Storage(e));
+        }
+        Ok(Some(entry))
+    }
+
+    /// Refuse sends to raw addresses that are neither in the address book
+    /// nor the wallet's own unless the call passes `allow_unknown`. Off by
+    /// default.
+    pub fn set_strict_address_book(&mut self, strict: bool) {
+        self.strict_address_book = strict;
+    }
+
+    /// Keep the address book in the JSON file at `path` while the wallet has
+    /// no database. Entries already in the file are added to the book, which
+    /// is written back to the file now and on every change.
+    pub fn with_address_book_file(
+        mut self,
+        path: impl Into<std::path::PathBuf>,
+    ) -> Result<Self, AddressBookError> {
+        let path = path.into();
+        if path.exists() {
+            let json = std::fs::read_to_string(&path).map_err(|e| {
+                AddressBookError::Storage(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            self.address_book.merge(AddressBook::from_json(&json)?)?;
+        }
+        self.address_book_file = Some(path);
+        self.write_address_book_file()
+            .map_err(AddressBookError::Storage)?;
+        Ok(self)
+    }
+
+    /// The address book as JSON, for [`import_address_book`](Self::import_address_book)
+    /// on another deployment.
+    pub fn export_address_book(&self) -> Result<String, AddressBookError> {
+        self.address_book.to_json()
+    }
+
+    /// Add the entries of an exported address book and return those added.
+    /// Every entry is checked as when added one by one. Entries already in
+    /// the book are skipped; if a label or address is taken by a different
+    /// entry, nothing is added.
+    pub fn import_address_book(
+        &mut self,
+        json: &str,
+    ) -> Result<Vec<AddressBookEntry>, AddressBookError> {
+        let before = self.address_book.clone();
+        let added = self.address_book.merge(AddressBook::from_json(json)?)?;
+        for entry in &added {
+            if let Err(e) = self.store_address_book_change(&entry.label) {
+                self.address_book = before;
+                for stored in &added {
+                    let _ = self.store_address_book_change(&stored.label);
+                }
+                return Err(AddressBookError::Storage(e));
+            }
+        }
+        Ok(added)
+    }
+
+    /// Address to send `kind` funds to for `recipient`, an address book
+    /// label or a raw address (see [`AddressBook::resolve`]). In
+    /// [strict mode](Self::set_strict_address_book), a raw address must be in
+    /// the book or the wallet's own unless `allow_unknown` is set.
+    pub fn resolve_recipient(
+        &self,
+        recipient: &str,
+        kind: AddressKind,
+        allow_unknown: bool,
+    ) -> Result<String, AddressBookError> {
+        let strict =
+            self.strict_address_book && !allow_unknown && !self.is_own_address(recipient, kind);
+        self.address_book.resolve(recipient, kind, strict)
+    }
+
+    fn is_own_address(&self, address: &str, kind: AddressKind) -> bool {
+        match kind {
+            AddressKind::Twilight => self.wallet.twilightaddress == address,
+            AddressKind::Btc => self.wallet.btc_address == address,
+            AddressKind::ZkOs => self
+                .zk_accounts
+                .get_all_accounts()
+                .iter()
+                .any(|account| account.account == address),
+        }
+    }
+
+    /// Store the change to the `label` entry: its row in the database, or
+    /// the whole book in the address book file.
+    fn store_address_book_change(&self, label: &str) -> Result<(), String> {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(ref db_manager) = self.db_manager {
+            return match self.address_book.get(label) {
+                Some(entry) => db_manager.save_address_book_entry(entry),
+                None => db_manager.remove_address_book_entry(label),
+            };
+        }
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let _ = label;
+        self.write_address_book_file()
+    }
+
+    fn write_address_book_file(&self) -> Result<(), String> {
+        if let Some(ref path) = self.address_book_file {
+            let json = self.address_book.to_json().map_err(|e| e.to_string())?;
+            std::fs::write(path, json)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// [`Wallet::send_tokens`] to `recipient`, an address book label or a
+    /// Twilight address, checked by [`resolve_recipient`](Self::resolve_recipient).
+    pub async fn send_tokens(
+        &mut self,
+        recipient: &str,
+        amount: u64,
+        denom: &str,
+        allow_unknown: bool,
+    ) -> Result<String, String> {
+        self.ensure_not_dry_run("send_tokens")?;
+        let address = self
+            .resolve_recipient(recipient, AddressKind::Twilight, allow_unknown)
+            .map_err(|e| e.to_string())?;
+        self.wallet
+            .send_tokens(&address, amount, denom)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// [`Wallet::request_btc_withdrawal`] to `recipient`, an address book
+    /// label or a BTC address, checked by
+    /// [`resolve_recipient`](Self::resolve_recipient). With a database the
+    /// submission is recorded as by [`record_btc_withdrawal`](Self::record_btc_withdrawal).
+    pub async fn request_btc_withdrawal(
+        &mut self,
+        recipient: &str,
+        amount_sats: u64,
+        allow_unknown: bool,
+    ) -> Result<crate::wallet::btc_withdrawal::BtcWithdrawalSubmission, String> {
+        self.ensure_not_dry_run("request_btc_withdrawal")?;
+        let address = self
+            .resolve_recipient(recipient, AddressKind::Btc, allow_unknown)
+            .map_err(|e| e.to_string())?;
+        let submission = self
+            .wallet
+            .request_btc_withdrawal(&address, amount_sats)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.note_btc_withdrawal(&submission);
+        Ok(submission)
+    }
+
+    /// [`Wallet::submit_btc_withdrawal`] of a request built for a chosen
+    /// reserve, recorded like [`request_btc_withdrawal`](Self::request_btc_withdrawal).
+    pub async fn submit_btc_withdrawal(
+        &mut self,
+        msg: crate::MsgWithdrawBtcRequest,
+    ) -> Result<crate::wallet::btc_withdrawal::BtcWithdrawalSubmission, String> {
+        self.ensure_not_dry_run("submit_btc_withdrawal")?;
+        let submission = self
+            .wallet
+            .submit_btc_withdrawal(msg)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.note_btc_withdrawal(&submission);
+        Ok(submission)
+    }
+
+    /// Record a submitted withdrawal when a database is attached; the
+    /// withdrawal is on chain already, so a failure is only logged.
+    fn note_btc_withdrawal(
+        &self,
+        submission: &crate::wallet::btc_withdrawal::BtcWithdrawalSubmission,
+    ) {
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if self.db_manager.is_some() {
+            if let Err(e) = self.record_btc_withdrawal(submission) {
+                warn!(
+                    "Failed to record BTC withdrawal {}: {}",
+                    submission.tx.hash, e
+                );
+            }
+        }
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let _ = submission;
+    }
+
+    // -------------------------
+    // Account labels
+    // -------------------------
+
+    /// Name account `index`, or clear its name with `None`. Labels are unique
+    /// among active accounts; a label already given to another account is
+    /// refused. Saved to the database along with the account.
+    pub fn set_account_label(
+        &mut self,
+        index: AccountIndex,
+        label: Option<&str>,
+    ) -> Result<(), String> {
+        self.zk_accounts
+            .set_label(&index, label.map(str::to_string))?;
+        self.try_update_account_in_db(&index);
+        Ok(())
+    }
+
+    /// The active account named `label`.
+    pub fn account_by_label(&self, label: &str) -> Option<ZkAccount> {
+        self.zk_accounts.get_by_label(label)
+    }
+
+    /// Index of the active account named `label`.
+    pub fn account_index_by_label(&self, label: &str) -> Result<AccountIndex, String> {
+        self.account_by_label(label)
+            .map(|account| account.index)
+            .ok_or_else(|| format!("No account labelled {:?}", label))
+    }
+
+    /// Active accounts whose label starts with `prefix`, by index; e.g. with
+    /// labels `"mm/bid"` and `"mm/ask"`, `accounts_with_tag("mm/")` returns both.
+    pub fn accounts_with_tag(&self, prefix: &str) -> Vec<ZkAccount> {
+        self.zk_accounts.accounts_with_tag(prefix)
+    }
+
+    // -------------------------
+    // Account archival
+    // -------------------------
+
+    /// Move spent accounts out of `zk_accounts`, so summaries, flushes and
+    /// drop no longer iterate them. An account qualifies when it is off-chain
+    /// with a zero balance, has no open order, no UTXO detail and no
+    /// unfinished pending operation, and its last recorded operation is at
+    /// least `older_than` old. Accounts with no recorded operation are
+    /// stamped now and can qualify on a later call.
+    ///
+    /// With DB persistence the row is kept and marked in
+    /// `archived_zk_accounts`, so history referencing the index still
+    /// resolves (see [`ZkAccountDB::resolve_account`]). Returns the archived
+    /// indices in ascending order.
+    pub fn archive_inactive_accounts(
+        &mut self,
+        older_than: std::time::Duration,
+    ) -> Result<Vec<AccountIndex>, String> {
+        let now = self.clock.now();
+        let Some(cutoff) = chrono::Duration::from_std(older_than)
+            .ok()
+            .and_then(|age| now.checked_sub_signed(age))
+        else {
+            return Ok(Vec::new());
+        };
+        let mut candidates: Vec<AccountIndex> = self
+            .zk_accounts
+            .get_all_accounts()
+            .into_iter()
+            .filter(|account| self.is_archivable(account))
+            .map(|account| account.index)
+            .collect();
+        candidates.sort_unstable();
+
+        let mut archived = Vec::new();
+        for index in candidates {
+            match self.account_activity.get(&index) {
+                Some(last_active) if last_active <= cutoff => {}
+                Some(_) => continue,
+                None => {
+                    self.account_activity.insert(index, now);
+                    continue;
+                }
+            }
+            #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+            if let Some(ref db_manager) = self.db_manager {
+                db_manager.archive_zk_account(&self.zk_accounts.get_account(&index)?, now)?;
+            }
+            self.zk_accounts.archive_account(&index)?;
+            self.account_activity.remove(&index);
+            self.utxo_cache.update(|cache| cache.invalidate(index));
+            archived.push(index);
+        }
+        if !archived.is_empty() {
+            info!("Archived {} inactive account(s)", archived.len());
+        }
+        Ok(archived)
+    }
+
+    /// Restore an archived account, e.g. when it turns out to hold funds.
+    pub fn unarchive(&mut self, index: AccountIndex) -> Result<(), String> {
+        if !self.zk_accounts.is_archived(&index) {
+            return Err(format!("Account {} is not archived", index));
+        }
+        if let Some(label) = self.zk_accounts.resolve_account(&index)?.label {
+            self.zk_accounts.check_label_free(&index, &label)?;
+        }
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        if let Some(ref db_manager) = self.db_manager {
+            db_manager.unarchive_zk_account(index)?;
+        }
+        self.zk_accounts.unarchive_account(&index)?;
+        self.account_activity.insert(index, self.clock.now());
+        Ok(())
+    }
+
+    fn is_archivable(&self, account: &ZkAccount) -> bool {
+        // A Memo account has an open order, i.e. its request id is still pending.
+        !account.on_chain
+            && account.balance == 0
+            && account.io_type == IOType::Coin
+            && !self.utxo_details.contains_key(&account.index)
+            && !self.pending_ops.with(|ops| {
+                ops.values()
+                    .any(|op| !op.is_done() && op.involves(account.index))
+            })
+    }
+
+    /// Request ids of accounts that are not archived; archived ones were
+    /// persisted when they were archived. Dry-run request ids are left out.
+    fn active_request_ids(&self) -> impl Iterator<Item = (AccountIndex, RequestId)> {
+        self.request_ids
+            .snapshot()
+            .into_iter()
+            .filter(|(index, request_id)| {
+                !self.zk_accounts.is_archived(index) && !is_dry_run_id(request_id)
+            })
+    }
+
+    /// Accounts to write to the database: all but those changed by a dry run.
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn persisted_accounts(&self) -> impl Iterator<Item = ZkAccount> {
+        self.zk_accounts
+            .get_all_accounts()
+            .into_iter()
+            .filter(|account| !account.simulated)
+    }
+
+    // -------------------------
+    // Portfolio / Position Tracking
+    // -------------------------
+
+    /// Get a snapshot of all ZkOS account balances and their states.
+    /// Archived accounts are listed only with `include_archived`.
+    pub fn get_account_balances(
+        &self,
+        include_archived: bool,
+    ) -> Vec<super::portfolio::AccountBalanceInfo> {
+        let archived = if include_archived {
+            self.zk_accounts.get_archived_accounts()
+        } else {
+            Vec::new()
+        };
+        self.zk_accounts
+            .get_all_accounts()
+            .iter()
+            .chain(archived.iter())
+            .map(|a| super::portfolio::AccountBalanceInfo {
+                account_index: a.index,
+                balance: a.balance,
+                io_type: a.io_type.clone(),
+                on_chain: a.on_chain,
+                last_error: a.last_error.clone(),
+                balance_unverified: a.balance_unverified,
+                archived: self.zk_accounts.is_archived(&a.index),
+                label: a.label.clone(),
+            })
+            .collect()
+    }
+
+    /// Local view of the wallet for dashboards: the cached on-chain balance,
+    /// every active account with its pending request ID, Coin and Memo totals
+    /// and open order counts. Makes no network calls; every Memo account
+    /// counts as an open order of its kind, and one whose order kind is
+    /// unknown has no `order_kind` and is not counted. Simulated orders do
+    /// not lock their accounts and are not counted.
+    pub fn summary(&self) -> super::portfolio::OrderWalletSummary {
+        self.build_summary(&HashMap::new(), false)
+    }
+
+    /// [`summary`](Self::summary) after re-querying the order of every Memo
+    /// account, so settled and cancelled orders no longer count as open. A
+    /// failed query is logged and leaves that account counted as open. Like
+    /// [`query_trader_order`](Self::query_trader_order), a query that finds a
+    /// failed order unlocks the account.
+    pub async fn refresh_summary(&mut self) -> super::portfolio::OrderWalletSummary {
+        self.identify_memo_orders().await;
+        let mut statuses = HashMap::new();
+        for (index, kind) in self.memo_accounts() {
+            let status = match kind {
+                Some(OrderKind::Lend) => self
+                    .query_lend_order(index)
+                    .await
+                    .map(|order| order.order_status),
+                Some(OrderKind::Trader) => self
+                    .query_trader_order(index)
+                    .await
+                    .map(|order| order.order_status),
+                None => Err(unknown_order_kind(index).into()),
+            };
+            match status {
+                Ok(status) => {
+                    statuses.insert(index, status);
+                }
+                Err(e) => warn!("Summary: order on account {} unavailable: {}", index, e),
+            }
+        }
+        self.build_summary(&statuses, true)
+    }
+
+    fn build_summary(
+        &self,
+        statuses: &HashMap<AccountIndex, OrderStatus>,
+        statuses_refreshed: bool,
+    ) -> super::portfolio::OrderWalletSummary {
+        let mut accounts: Vec<_> = self
+            .zk_accounts
+            .get_all_accounts()
+            .iter()
+            .map(|a| {
+                let order_kind = match a.io_type {
+                    IOType::Memo => self.memo_order_kind(a),
+                    _ => None,
+                };
+                super::portfolio::AccountEntry {
+                    account_index: a.index,
+                    balance: a.balance,
+                    io_type: a.io_type.clone(),
+                    on_chain: a.on_chain,
+                    order_kind,
+                    pending_request_id: self.request_ids.get(&a.index),
+                    order_status: order_kind.and(statuses.get(&a.index).cloned()),
+                }
+            })
+            .collect();
+        accounts.sort_by_key(|a| a.account_index);
+        super::portfolio::OrderWalletSummary::build(
+            self.clock.now(),
+            self.wallet.balance_nyks,
+            self.wallet.balance_sats,
+            accounts,
+            statuses_refreshed,
+        )
+    }
+
+    /// Query a single trader position and return a structured summary with PnL.
+    /// The position must be in Memo state (i.e. an open order exists).
+    pub async fn get_position_pnl(
+        &self,
+        index: AccountIndex,
+    ) -> Result<super::portfolio::PositionSummary, String> {
+        let account = self.zk_accounts.get_account(&index)?;
+        if account.io_type != IOType::Memo {
+            return Err(format!(
+                "Account {} is not in Memo state (no open position)",
+                index
+            ));
+        }
+        let order_v1 = self.query_trader_order_v1(index).await?;
+        let current_price = self
+            .relayer
+            .btc_usd_price()
+            .await
+            .map(|p| p.price)
+            .unwrap_or(order_v1.order.entryprice);
+        Ok(super::portfolio::PositionSummary::from_trader_order_v1(
+            index,
+            &order_v1,
+            current_price,
+        ))
+    }
+
+    /// Liquidation price, maintenance margin and health ratio of the trader
+    /// position on `index`, at the current BTC/USD price and the relayer's
+    /// risk parameters and current fee and funding rates. See
+    /// [`position_health`](super::position_health).
+    pub async fn position_health(
+        &self,
+        index: AccountIndex,
+    ) -> Result<super::position_health::PositionHealth, String> {
+        let account = self.zk_accounts.get_account(&index)?;
+        if account.io_type != IOType::Memo {
+            return Err(format!(
+                "Account {} is not in Memo state (no open position)",
+                index
+            ));
+        }
+        let order = self.query_trader_order(index).await?;
+        let (price, stats, fee, funding) = tokio::join!(
+            self.relayer.btc_usd_price(),
+            self.relayer.get_market_stats(),
+            self.relayer_api_client.get_fee_rate(),
+            self.relayer_api_client.get_funding_rate()
+        );
+        let mark_price = price.map_err(|e| e.to_string())?.price;
+        let params = super::position_health::MarginParams::from_risk_params(
+            &stats.map_err(|e| e.to_string())?.params,
+            fee.map_err(|e| e.to_string())?.order_settled_on_market,
+            funding.map_err(|e| e.to_string())?.rate,
+        );
+        Ok(super::position_health::PositionHealth::from_trader_order(
+            index, &order, mark_price, &params,
+        ))
+    }
+
+    /// Query a single lend position and return a structured summary.
+    /// The position must be in Memo state (i.e. an active lend order exists).
+    pub async fn get_lend_position_pnl(
+        &self,
+        index: AccountIndex,
+    ) -> Result<super::portfolio::LendPositionSummary, String> {
+        let account = self.zk_accounts.get_account(&index)?;
+        if account.io_type != IOType::Memo {
+            return Err(format!(
+                "Account {} is not in Memo state (no active lend position)",
+                index
+            ));
+        }
+        let order_v1 = self.query_lend_order_v1(index).await?;
+        Ok(super::portfolio::LendPositionSummary::from_lend_order_v1(
+            index, &order_v1,
+        ))
+    }
+
+    /// The relayer's market data in one call; see
+    /// [`market_snapshot`](super::market_snapshot).
+    pub async fn market_snapshot(&self) -> super::market_snapshot::MarketSnapshot {
+        self.relayer_api_client.market_snapshot().await
+    }
+
+    /// Current state of the relayer's lend pool.
+    pub async fn lend_pool_info(&self) -> Result<LendPoolInfo, String> {
+        self.relayer_api_client
+            .lend_pool_info()
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Projected interest on lending `amount` sats for `duration_hours`, from
+    /// the pool's size, utilization and last-24h APY. See
+    /// [`lend_yield`](super::lend_yield).
+    pub async fn estimate_lend_yield(
+        &self,
+        amount: u64,
+        duration_hours: u64,
+    ) -> Result<super::lend_yield::LendYieldEstimate, String> {
+        let (pool, apy, stats) = tokio::join!(
+            self.relayer_api_client.lend_pool_info(),
+            self.relayer_api_client.last_day_apy(),
+            self.relayer.get_market_stats()
+        );
+        let pool = pool.map_err(|e| e.to_string())?;
+        let apy = apy
+            .map_err(|e| e.to_string())?
+            .ok_or("Relayer reports no lend pool APY yet")?;
+        let utilization = stats.map_err(|e| e.to_string())?.utilization;
+        Ok(super::lend_yield::LendYieldEstimate::new(
+            amount,
+            duration_hours,
+            &pool,
+            apy,
+            utilization,
+        ))
+    }
+
+    /// Value and accrued interest of the open lend order on `index`, priced
+    /// at the pool's current share value instead of waiting for settlement.
+    pub async fn lend_position_value(
+        &self,
+        index: AccountIndex,
+    ) -> Result<super::lend_yield::LendPositionValue, String> {
+        let account = self.zk_accounts.get_account(&index)?;
+        if account.io_type != IOType::Memo {
+            return Err(format!(
+                "Account {} is not in Memo state (no active lend position)",
+                index
+            ));
+        }
+        let (order, pool) = tokio::join!(self.query_lend_order(index), self.lend_pool_info());
+        let order = order?;
+        if order.order_status != OrderStatus::FILLED {
+            return Err(format!(
+                "Lend order on account {} is not open, status: {}",
+                index,
+                order.order_status.to_str()
+            ));
+        }
+        super::lend_yield::LendPositionValue::from_lend_order(
+            index,
+            &order,
+            &pool?,
+            self.clock.now(),
+        )
+    }
+
+    /// Build a full portfolio summary across all accounts.
+    ///
+    /// This queries the relayer for each open trader/lend position to get live PnL data.
+    /// Accounts in Coin state contribute to `total_trading_balance`.
+    /// Accounts in Memo state are queried as trader positions first; if that fails,
+    /// they are tried as lend positions. Accounts whose order settled or was
+    /// liquidated are unlocked, and the funding balance is refreshed.
+    pub async fn get_portfolio_summary(&mut self) -> Result<super::portfolio::Portfolio, String> {
+        let wallet_balance_sats = self
+            .wallet
+            .update_balance()
+            .await
+            .map(|b| b.sats)
+            .unwrap_or(0);
+        let (portfolio, closed) = self.scan_portfolio(wallet_balance_sats).await;
+        for (index, kind, outcome) in closed {
+            let unlocked = match kind {
+                OrderKind::Lend => self.unlock_lend_order(index).await,
+                OrderKind::Trader => self.unlock_trader_order(index).await,
+            };
+            match unlocked {
+                Ok(_) => info!(
+                    "Unlocked {} account {} during portfolio scan",
+                    outcome, index
+                ),
+                Err(e) => error!("Failed to unlock {} account {}: {}", outcome, index, e),
+            }
+        }
+        Ok(portfolio)
+    }
+
+    /// The portfolio at `wallet_balance_sats`, and the accounts whose order
+    /// settled or was liquidated, with how it closed. Changes nothing.
+    async fn scan_portfolio(
+        &self,
+        wallet_balance_sats: u64,
+    ) -> (
+        super::portfolio::Portfolio,
+        Vec<(AccountIndex, OrderKind, &'static str)>,
+    ) {
+        use super::portfolio::{LendPositionSummary, Portfolio, PositionSummary};
+
+        for index in self.identify_memo_orders().await {
+            warn!("Portfolio: {}", unknown_order_kind(index));
+        }
+        let current_price = self
+            .relayer
+            .btc_usd_price()
+            .await
+            .map(|p| p.price)
+            .unwrap_or(0.0);
+
+        let mut total_trading_balance: u64 = 0;
+        let mut trader_positions = Vec::new();
+        let mut closed_trader_positions = Vec::new();
+        let mut liquidated_trader_positions = Vec::new();
+        let mut lend_positions = Vec::new();
+        let mut closed_lend_positions = Vec::new();
+        let mut closed = Vec::new();
+        let mut on_chain_count = 0;
+
+        let accounts = self.zk_accounts.get_all_accounts();
+        let total_accounts = accounts.len();
+
+        for account in &accounts {
+            if account.on_chain {
+                on_chain_count += 1;
+            }
+
+            match account.io_type {
+                IOType::Coin => {
+                    if account.on_chain && account.balance > 0 {
+                        total_trading_balance += account.balance;
+                    }
+                }
+                IOType::Memo => match self.memo_order_kind(account) {
+                    Some(OrderKind::Lend) => {
+                        if let Ok(order_v1) = self.query_lend_order_v1(account.index).await {
+                            let summary =
+                                LendPositionSummary::from_lend_order_v1(account.index, &order_v1);
+                            if order_v1.order.order_status == OrderStatus::SETTLED {
+                                closed.push((account.index, OrderKind::Lend, "settled lend"));
+                                closed_lend_positions.push(summary);
+                            } else {
+                                lend_positions.push(summary);
+                            }
+                        }
+                    }
+                    Some(OrderKind::Trader) => {
+                        if let Ok(order_v1) = self.query_trader_order_v1(account.index).await {
+                            let mut summary = PositionSummary::from_trader_order_v1(
+                                account.index,
+                                &order_v1,
+                                current_price,
+                            );
+                            match order_v1.order.order_status {
+                                OrderStatus::SETTLED => {
+                                    summary.unrealized_pnl = order_v1.order.unrealized_pnl;
+                                    closed.push((account.index, OrderKind::Trader, "settled"));
+                                    closed_trader_positions.push(summary);
+                                }
+                                OrderStatus::LIQUIDATE => {
+                                    closed.push((account.index, OrderKind::Trader, "liquidated"));
+                                    liquidated_trader_positions.push(summary);
+                                }
+                                _ => trader_positions.push(summary),
+                            }
+                        }
+                    }
+                    // Reported by identify_memo_orders above.
+                    None => {}
+                },
+                _ => {}
+            }
+        }
+
+        let portfolio = Portfolio::build(
+            wallet_balance_sats,
+            total_trading_balance,
+            trader_positions,
+            closed_trader_positions,
+            liquidated_trader_positions,
+            lend_positions,
+            closed_lend_positions,
+            total_accounts,
+            on_chain_count,
+        );
+        (portfolio, closed)
+    }
+
+    /// Get liquidation risk info for all open trader positions, sorted by distance
+    /// to liquidation (most at-risk first).
+    pub async fn get_liquidation_risks(
+        &self,
+    ) -> Result<Vec<super::portfolio::LiquidationRisk>, String> {
+        let current_price = self
+            .relayer
+            .btc_usd_price()
+            .await
+            .map(|p| p.price)
+            .unwrap_or(0.0);
+
+        if current_price == 0.0 {
+            return Err("Could not fetch current BTC/USD price".to_string());
+        }
+
+        let accounts = self.zk_accounts.get_all_accounts();
+        let mut risks = Vec::new();
+
+        for account in &accounts {
+            if account.io_type != IOType::Memo || !account.on_chain {
+                continue;
+            }
+
+            if let Ok(order) = self.query_trader_order(account.index).await {
+                if order.liquidation_price > 0.0 {
+                    let distance_pct = match order.position_type {
+                        PositionType::LONG => {
+                            (current_price - order.liquidation_price) / current_price * 100.0
+                        }
+                        PositionType::SHORT => {
+                            (order.liquidation_price - current_price) / current_price * 100.0
+                        }
+                    };
+
+                    let margin_ratio = if order.initial_margin > 0.0 {
+                        order.available_margin / order.initial_margin
+                    } else {
+                        0.0
+                    };
+
+                    risks.push(super::portfolio::LiquidationRisk {
+                        account_index: account.index,
+                        position_type: order.position_type,
+                        liquidation_price: order.liquidation_price,
+                        current_price,
+                        distance_pct,
+                        margin_ratio,
+                    });
+                }
+            }
+        }
+
+        // Sort by distance — most at-risk (lowest distance) first
+        risks.sort_by(|a, b| {
+            a.distance_pct
+                .partial_cmp(&b.distance_pct)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        Ok(risks)
+    }
+
+    /// Wallet-level exposure, margin usage and liquidation distance.
+    ///
+    /// Relayer queries for all open positions run concurrently, at most
+    /// [`RISK_QUERY_CONCURRENCY`] at a time, alongside the price and funding
+    /// balance lookups. Formulas are documented in [`super::risk`]. Positions
+    /// that cannot be queried, or whose query panics, are listed in
+    /// `unavailable_accounts`; a missing
+    /// BTC/USD price fails the report. The result is kept for
+    /// [`diagnostic_snapshot`](OrderWallet::diagnostic_snapshot).
+    pub async fn risk_report(&mut self) -> Result<RiskReport, String> {
+        enum Query {
+            Trader(QueryTraderOrderZkos),
+            Lend(QueryLendOrderZkos),
+        }
+        enum Exposure {
+            Trader(TraderExposure),
+            Lend(LendExposure),
+            Closed,
+        }
+
+        self.identify_memo_orders().await;
+        let mut coin_account_sats: u64 = 0;
+        let mut unavailable_accounts = Vec::new();
+        let mut tasks = Vec::new();
+        for account in self.zk_accounts.get_all_accounts() {
+            let query = match (&account.io_type, self.memo_order_kind(&account)) {
+                (IOType::Coin, _) => {
+                    if account.on_chain {
+                        coin_account_sats = coin_account_sats.saturating_add(account.balance);
+                    }
+                    continue;
+                }
+                (IOType::Memo, Some(OrderKind::Lend)) => {
+                    self.build_lend_query(account.index).map(Query::Lend)
+                }
+                (IOType::Memo, Some(OrderKind::Trader)) => {
+                    self.build_trader_query(account.index).map(Query::Trader)
+                }
+                (IOType::Memo, None) => Err(unknown_order_kind(account.index)),
+                _ => continue,
+            };
+            let index = account.index;
+            let query = match query {
+                Ok(query) => query,
+                Err(e) => {
+                    warn!("Risk report: cannot query account {}: {}", index, e);
+                    unavailable_accounts.push(index);
+                    continue;
+                }
+            };
+            let client = self.relayer.clone();
+            let query = async move {
+                match query {
+                    Query::Trader(query) => client
+                        .trader_order_info(query)
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|order| match order.order_status {
+                            OrderStatus::PENDING | OrderStatus::FILLED => {
+                                TraderExposure::from_trader_order(index, &order)
+                                    .map(Exposure::Trader)
+                            }
+                            _ => Ok(Exposure::Closed),
+                        }),
+                    Query::Lend(query) => client
+                        .lend_order_info(query)
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|order| match order.order_status {
+                            OrderStatus::SETTLED => Ok(Exposure::Closed),
+                            _ => LendExposure::from_lend_order(index, &order).map(Exposure::Lend),
+                        }),
+                }
+            };
+            // A query that panics leaves its account unavailable, not missing.
+            tasks.push(async move {
+                let exposure =
+                    compat::guard_async("risk_report", &format!("account {}", index), query)
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|exposure| exposure);
+                (index, exposure)
+            });
+        }
+
+        let (results, price, on_chain) = tokio::join!(
+            run_bounded(tasks, RISK_QUERY_CONCURRENCY),
+            self.relayer.btc_usd_price(),
+            check_balance(
+                &self.wallet.twilightaddress,
+                &self.wallet.chain_config.lcd_endpoint
+            ),
+        );
+        let btc_usd_price = price
+            .map(|p| p.price)
+            .map_err(|e| format!("Could not fetch current BTC/USD price: {}", e))?;
+        let on_chain_sats = match on_chain {
+            Ok(balance) => balance.sats,
+            Err(e) => {
+                warn!("Risk report: funding balance unavailable: {}", e);
+                0
+            }
+        };
+
+        let mut traders = Vec::new();
+        let mut lends = Vec::new();
+        for (index, exposure) in results {
+            match exposure {
+                Ok(Exposure::Trader(t)) => traders.push(t),
+                Ok(Exposure::Lend(l)) => lends.push(l),
+                Ok(Exposure::Closed) => {}
+                Err(e) => {
+                    warn!("Risk report: account {} unavailable: {}", index, e);
+                    unavailable_accounts.push(index);
+                }
+            }
+        }
+        traders.sort_by_key(|t| t.account_index);
+        lends.sort_by_key(|l| l.account_index);
+
+        let report = RiskReport::compute(
+            self.clock.now(),
+            btc_usd_price,
+            on_chain_sats,
+            coin_account_sats,
+            &traders,
+            &lends,
+            unavailable_accounts,
+        );
+        self.last_risk_report = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Read-only snapshot for [`status::render`](super::status::render) and
+    /// [`status::render_compact`](super::status::render_compact).
+    ///
+    /// Probes the relayer (BTC/USD price) and the chain LCD (funding balance),
+    /// then builds the portfolio like
+    /// [`get_portfolio_summary`](OrderWallet::get_portfolio_summary), but
+    /// changes nothing: settled accounts stay locked and the wallet's cached
+    /// balance is left as it was. A failed probe is reported in `endpoints`
+    /// rather than failing the snapshot, and the cached balance is shown.
+    pub async fn status_snapshot(&self) -> Result<StatusSnapshot, String> {
+        let started = std::time::Instant::now();
+        let price = self.relayer.btc_usd_price().await;
+        let relayer = EndpointStatus::from_probe(
+            "relayer",
+            &self.relayer_endpoint_config.relayer_api_endpoint,
+            started.elapsed(),
+            price.as_ref().map(|_| ()),
+        );
+
+        let started = std::time::Instant::now();
+        let balance = check_balance(
+            &self.wallet.twilightaddress,
+            &self.wallet.chain_config.lcd_endpoint,
+        )
+        .await;
+        let lcd = EndpointStatus::from_probe(
+            "lcd",
+            &self.wallet.chain_config.lcd_endpoint,
+            started.elapsed(),
+            balance.as_ref().map(|_| ()),
+        );
+
+        let wallet_balance_sats = balance.map_or(self.wallet.balance_sats, |b| b.sats);
+        let (portfolio, _) = self.scan_portfolio(wallet_balance_sats).await;
+
+        #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+        let recent_settlements = if self.db_manager.is_some() {
+            self.get_order_history(super::transaction_history::OrderHistoryFilter {
+                limit: Some(50),
+                ..Default::default()
+            })
+            .unwrap_or_else(|e| {
+                warn!("Status snapshot: order history unavailable: {}", e);
+                Vec::new()
+            })
+            .into_iter()
+            .filter(|entry| entry.action.starts_with("close"))
+            .take(STATUS_RECENT_SETTLEMENTS)
+            .collect()
+        } else {
+            Vec::new()
+        };
+        #[cfg(not(any(feature = "sqlite", feature = "postgresql")))]
+        let recent_settlements = Vec::new();
+
+        Ok(StatusSnapshot {
+            generated_at: self.clock.now(),
+            chain_id: self.chain_id.clone(),
+            twilight_address: self.wallet.twilightaddress.clone(),
+            btc_usd_price: price.ok().map(|p| p.price),
+            portfolio,
+            accounts: self.get_account_balances(false),
+            last_activity: self.account_activity.snapshot().into_iter().collect(),
+            recent_settlements,
+            endpoints: vec![relayer, lcd],
+        })
+    }
+}
+
+/// Compare the fingerprint stored with `wallet_id` against `current`. Drift is
+/// logged; with `strict_config` any significant drift is returned as an error.
+/// Wallets saved before fingerprints existed report no drift.
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+fn check_config_drift(
+    wallet_id: &str,
+    saved: Option<&ConfigFingerprint>,
+    current: &ConfigFingerprint,
+    strict_config: bool,
+) -> Result<ConfigDrift, String> {
+    let Some(saved) = saved else {
+        return Ok(ConfigDrift::default());
+    };
+    let drift = ConfigDrift::between(saved, current);
+    if drift.is_empty() {
+        return Ok(drift);
+    }
+    let message = format!(
+        "Configuration changed since wallet {} was saved: {}",
+        crate::log_privacy::LoggedAddress(wallet_id),
+        drift
+    );
+    if strict_config && drift.is_significant() {
+        error!("{}", message);
+        return Err(message);
+    }
+    warn!("{}", message);
+    Ok(drift)
+}
+
+/// When the order `order_id` was settled or liquidated, by the earliest such
+/// record in `txs`.
+fn order_closed_at(order_id: &str, txs: &[TxHash]) -> Option<DateTime<Utc>> {
+    txs.iter()
+        .filter(|tx| tx.order_id == order_id)
+        .filter(|tx| {
+            matches!(
+                tx.order_status,
+                OrderStatus::SETTLED | OrderStatus::LIQUIDATE
+            )
+        })
+        .filter_map(|tx| super::relayer_types::parse_relayer_timestamp(&tx.datetime))
+        .min()
+}
+
+/// Whether a pending limit order's cancel record shows the order cancelled
+/// without settling its input into a new output.
+fn cancel_left_input_unspent(cancel_tx: &TxHash) -> bool {
+    cancel_tx.order_status == OrderStatus::CANCELLED
+        && cancel_tx.output.as_deref().is_none_or(str::is_empty)
+}
+
+/// Why Memo account `index` is left out of order queries, closes and unlocks.
+fn unknown_order_kind(index: AccountIndex) -> String {
+    format!(
+        "Memo account {} holds an order of unknown kind (trader or lend)",
+        index
+    )
+}
+
+/// Error for a mint/burn tx the chain answered with a non-zero code. Only
+/// a sequence mismatch is worth re-signing; a failed signature check
+/// (code 4) is a plain rejection.
+fn mint_burn_rejected(result: &TxResult) -> OrderWalletError {
+    if result.code == SEQUENCE_MISMATCH_CODE {
+        return OrderWalletError::SequenceMismatch {
+            tx_hash: result.tx_hash.clone(),
+            code: result.code,
+        };
+    }
+    OrderWalletError::ChainTx {
+        tx_hash: result.tx_hash.clone(),
+        code: result.code,
+        log: result.log.clone().unwrap_or_default(),
+    }
+}
+
+/// Error for a mint/burn tx [`Wallet::sign_and_broadcast`] did not get a
+/// CheckTx answer for, or got a second sequence mismatch for.
+fn mint_burn_send_failed(e: WalletError) -> OrderWalletError {
+    match e {
+        WalletError::SequenceMismatch { tx_hash, .. } => OrderWalletError::SequenceMismatch {
+            tx_hash,
+            code: SEQUENCE_MISMATCH_CODE,
+        },
+        e => e.to_string().into(),
+    }
+}
+
+/// Inputs of one order in [`OrderWallet::open_trader_orders_batch`], built
+/// before the concurrent submission.
+struct PreparedTraderOrder {
+    secret_key: RistrettoSecretKey,
+    r_scalar: curve25519_dalek::scalar::Scalar,
+    initial_margin: u64,
+    position_value: u64,
+    position_size: u64,
+    address: String,
+    /// The input of the UTXO the batch synced, so the order is not built on
+    /// a second fetch.
+    input_coin: Input,
+}
+
+/// The pre-trade checks of [`OrderWallet::validate_open_order`] against
+/// already fetched market stats.
+fn check_open_order(
+    stats: &MarketStats,
+    order_side: &PositionType,
+    initial_margin: u64,
+    leverage: Leverage,
+) -> Result<(), String> {
+    // 1. Market status
+    match stats.status.as_str() {
+        "HALT" => {
+            return Err(format!(
+                "Market is halted: {}",
+                stats.status_reason.as_deref().unwrap_or("unknown")
+            ));
+        }
+        "CLOSE_ONLY" => {
+            return Err(format!(
+                "Market is in close-only mode: {}",
+                stats.status_reason.as_deref().unwrap_or("unknown")
+            ));
+        }
+        _ => {}
+    }
+
+    // entry_value = initial_margin * leverage (in BTC / sats)
+    let entry_value = initial_margin as f64 * leverage.as_f64();
+
+    // 2. Max leverage
+    LeverageLimits::from_risk_params(&stats.params)
+        .check(leverage)
+        .map_err(|e| e.to_string())?;
+
+    // 3. Min position size
+    if stats.params.min_position_btc > 0.0 && entry_value < stats.params.min_position_btc {
+        return Err(format!(
+            "Position size {:.0} sats is below minimum {:.0} sats",
+            entry_value, stats.params.min_position_btc
+        ));
+    }
+
+    // 4. Per-position cap
+    let pos_cap = stats.params.max_position_pct * stats.pool_equity_btc;
+    if pos_cap > 0.0 && entry_value > pos_cap {
+        return Err(format!(
+            "Position size {:.0} sats exceeds per-position cap {:.0} sats ({:.1}% of pool equity)",
+            entry_value,
+            pos_cap,
+            stats.params.max_position_pct * 100.0
+        ));
+    }
+
+    // 5. Directional headroom (max_long / max_short already incorporates OI + net limits)
+    let (headroom, direction) = match order_side {
+        PositionType::LONG => (stats.max_long_btc, "long"),
+        PositionType::SHORT => (stats.max_short_btc, "short"),
+    };
+    if entry_value > headroom {
+        return Err(format!(
+            "Position size {:.0} sats exceeds max available {} capacity {:.0} sats (utilization: {:.1}%)",
+            entry_value, direction, headroom, stats.utilization * 100.0
+        ));
+    }
+
+    Ok(())
+}
+
+/// Result of a multi-account funding: the chunk transactions and funded
+/// accounts, or the failure with whatever already reached the chain.
+fn funding_outcome(
+    txs: Vec<TxResult>,
+    funded: Vec<AccountBalance>,
+    failure: Option<OrderWalletError>,
+) -> OrderWalletResult<(Vec<TxResult>, Vec<AccountBalance>)> {
+    match failure {
+        None => Ok((txs, funded)),
+        Some(e) if txs.is_empty() && funded.is_empty() => Err(e),
+        Some(e) => Err(OrderWalletError::PartialFunding {
+            txs,
+            funded,
+            source: Box::new(e),
+        }),
+    }
+}
+
+/// QuisQuis account of the output in `utxo_detail`, fetched for `index`.
+fn output_account(
+    index: AccountIndex,
+    utxo_detail: &UtxoDetailResponse,
+) -> Result<Account, String> {
+    compat::guard_result("to_quisquis_account", &format!("account {}", index), || {
+        utxo_detail.output.to_quisquis_account()
+    })
+    .map_err(|e| e.to_string())
+}
+
+// -------------------------
+// Drop
+// -------------------------
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl Drop for OrderWallet {
+    fn drop(&mut self) {
+        // Persisting serializes SDK types; don't let a panic there escape drop.
+        if let Err(e) = compat::guard("persist_on_drop", "drop", || self.persist_on_drop()) {
+            error!("OrderWallet state not fully persisted during drop: {}", e);
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl OrderWallet {
+    fn persist_on_drop(&self) {
+        if let Some(ref db_manager) = self.db_manager {
+            // Save all current zk accounts to database
+            for account in self.persisted_accounts() {
+                if let Err(e) = db_manager.save_zk_account(&account) {
+                    error!(
+                        "Failed to persist zk_account {} during drop: {}",
+                        account.index, e
+                    );
+                }
+            }
+
+            // Save encrypted wallet if the wallet is unlocked
+            let saved = self.with_wallet_password(|password| {
+                // if let Err(e) = db_manager.save_encrypted_wallet(&self.wallet, password) {
+                //     error!("Failed to persist wallet during drop: {}", e);
+                // }
+
+                // Save OrderWallet configuration
+                self.seed.secret().and_then(|seed| {
+                    db_manager.save_order_wallet(
+                        &self.chain_id,
+                        seed.expose_secret(),
+                        &self.relayer_endpoint_config,
+                        &self.config_fingerprint(),
+                        password.expose_secret(),
+                    )
+                })
+            });
+            match saved {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!(
+                    "Failed to persist OrderWallet configuration during drop: {}",
+                    e
+                ),
+                Err(WalletLocked) => {
+                    warn!("Wallet is locked; skipping encrypted OrderWallet save during drop")
+                }
+            }
+
+            // Save all UTXO details
+            for (account_index, utxo_detail) in self.utxo_details.snapshot() {
+                if let Err(e) = db_manager.save_utxo_detail(account_index, &utxo_detail) {
+                    error!(
+                        "Failed to persist UTXO detail for account {} during drop: {}",
+                        account_index, e
+                    );
+                }
+            }
+
+            // Save request IDs of active accounts
+            for (account_index, request_id) in self.active_request_ids() {
+                if let Err(e) = db_manager.save_request_id(account_index, &request_id) {
+                    error!(
+                        "Failed to persist request ID for account {} during drop: {}",
+                        account_index, e
+                    );
+                }
+            }
+
+            debug!("OrderWallet data persisted to database during drop");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::utxo_cache::UtxoFuture;
+    use crate::zkos_accounts::zkaccount::MAX_STORED_ERROR_LEN;
+    use crate::{get_test_tokens, relayer_module::fetch_tx_hash_with_retry};
+    use log::info;
+    use serial_test::serial;
+    use std::sync::Once;
+    use tokio::time::{sleep, Duration};
+    use crate::compat::relayer_types::PositionType;
+    static INIT: Once = Once::new();
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    // This function initializes the logger for the tests.
+    fn init_logger() {
+        INIT.call_once(|| {
+            // `env_logger` with `is_test(true)`, which keeps the default
+            // filter at `trace` and respects RUST_LOG if you set it.
+            crate::log_privacy::test_logger::init();
+        });
+    }
+    async fn setup_wallet() -> Result<Wallet, String> {
+        info!("Creating new wallet with random BTC address");
+        // let mut wallet = Wallet::new(None).map_err(|e| e.to_string())?;
+        // info!("importing wallet from json");
+        let mut wallet = Wallet::import_from_json("test.json").map_err(|e| e.to_string())?;
+
+        info!("Getting test tokens from faucet");
+        // Returns once the faucet's balance changes are visible on chain.
+        match get_test_tokens(&mut wallet).await {
+            Ok(report) => info!("Tokens received in {:?}", report.elapsed),
+            Err(e) => return Err(e.to_string()),
+        }
+
+        Ok(wallet)
+    }
+    // cargo test --no-default-features --features postgresql --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
+    // cargo test --no-default-features --features sqlite --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
+    // cargo test --all-features --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_create_order_complete_cycle() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        unsafe {
+            // std::env::set_var("DATABASE_URL", "./test.db");
+            std::env::set_var("NYKS_WALLET_PASSPHRASE", "test1_password");
+        }
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        // order_wallet.with_db(None, None)?;
+        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+
+        let btc_price = order_wallet
+            .relayer_api_client
+            .btc_usd_price()
+            .await
+            .map_err(|e| e.to_string())?;
+        info!("btc_price: {:?}", btc_price);
+        let entry_price = btc_price.price as u64;
+        let result = order_wallet
+            .open_trader_order(
+                account_index,
+                OrderType::MARKET,
+                PositionType::LONG,
+                entry_price,
+                10,
+            )
+            .await?;
+        info!("result: {:?}", result);
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        info!("tx_hash: {:?}", tx_hash);
+        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
+        let response = order_wallet.query_trader_order(account_index).await?;
+        info!("response: {:?}", response);
+        assert_eq!(response.order_status, OrderStatus::FILLED);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&account_index)?
+                .io_type,
+            IOType::Memo
+        );
+
+        let result = order_wallet
+            .close_trader_order(account_index, OrderType::MARKET, 0.0)
+            .await?;
+
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        assert_eq!(tx_hash.order_status, OrderStatus::SETTLED);
+        let response = order_wallet.query_trader_order(account_index).await?;
+        assert_eq!(response.order_status, OrderStatus::SETTLED);
+        order_wallet.unlock_trader_order(account_index).await?;
+        let zk_account = order_wallet.zk_accounts.get_account(&account_index)?;
+        assert_eq!(zk_account.io_type, IOType::Coin);
+        assert_eq!(zk_account.balance, response.available_margin as u64);
+        let receiver_account_index = order_wallet.trading_to_trading(account_index).await?;
+        assert_ne!(account_index, receiver_account_index);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&account_index)?
+                .on_chain,
+            false
+        );
+        info!("receiver_account_index: {:?}", receiver_account_index);
+        let result1 = order_wallet
+            .open_trader_order(
+                receiver_account_index,
+                OrderType::MARKET,
+                PositionType::LONG,
+                entry_price,
+                10,
+            )
+            .await?;
+        info!("result1: {:?}", result1);
+        let tx_hash1 = fetch_tx_hash_with_retry(&result1, &order_wallet.relayer_api_client).await?;
+        info!("tx_hash1: {:?}", tx_hash1);
+        assert_eq!(tx_hash1.order_status, OrderStatus::FILLED);
+        let response = order_wallet
+            .query_trader_order(receiver_account_index)
+            .await?;
+        info!("response: {:?}", response);
+        assert_eq!(response.order_status, OrderStatus::FILLED);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&receiver_account_index)?
+                .io_type,
+            IOType::Memo
+        );
+        let result2 = order_wallet
+            .close_trader_order(receiver_account_index, OrderType::MARKET, 0.0)
+            .await?;
+        let tx_hash2 = fetch_tx_hash_with_retry(&result2, &order_wallet.relayer_api_client).await?;
+        assert_eq!(tx_hash2.order_status, OrderStatus::SETTLED);
+        let response2 = order_wallet
+            .query_trader_order(receiver_account_index)
+            .await?;
+        assert_eq!(response2.order_status, OrderStatus::SETTLED);
+        order_wallet
+            .unlock_trader_order(receiver_account_index)
+            .await?;
+        order_wallet
+            .sync_account_state(receiver_account_index)
+            .await?;
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&receiver_account_index)?
+                .io_type,
+            IOType::Coin
+        );
+        let new_recever_index = order_wallet
+            .trading_to_trading(receiver_account_index)
+            .await?;
+        assert_ne!(receiver_account_index, new_recever_index);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&new_recever_index)?
+                .io_type,
+            IOType::Coin
+        );
+        let result3 = order_wallet
+            .open_trader_order(
+                new_recever_index,
+                OrderType::MARKET,
+                PositionType::LONG,
+                entry_price,
+                10,
+            )
+            .await?;
+        let tx_hash3 = fetch_tx_hash_with_retry(&result3, &order_wallet.relayer_api_client).await?;
+        info!("tx_hash3: {:?}", tx_hash3);
+        assert_eq!(tx_hash3.order_status, OrderStatus::FILLED);
+        let response3 = order_wallet.query_trader_order(new_recever_index).await?;
+        info!("response3: {:?}", response3);
+        assert_eq!(response3.order_status, OrderStatus::FILLED);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&new_recever_index)?
+                .io_type,
+            IOType::Memo
+        );
+        let result4 = order_wallet
+            .close_trader_order(new_recever_index, OrderType::MARKET, 0.0)
+            .await?;
+        debug!("result4: {:?}", result4);
+
+        Ok(())
+    }
+    // cargo test --no-default-features --features postgresql --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
+    // cargo test --no-default-features --features sqlite --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
+    // cargo test --all-features --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
+    // #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_create_order_complete_cycle_sltp() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        unsafe {
+            // std::env::set_var("DATABASE_URL", "./test.db");
+            std::env::set_var("NYKS_WALLET_PASSPHRASE", "test1_password");
+        }
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        // order_wallet.with_db(None, None)?;
+        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+
+        let btc_price = order_wallet
+            .relayer_api_client
+            .btc_usd_price()
+            .await
+            .map_err(|e| e.to_string())?;
+        info!("btc_price: {:?}", btc_price);
+        let entry_price = btc_price.price as u64;
+        let result = order_wallet
+            .open_trader_order(
+                account_index,
+                OrderType::MARKET,
+                PositionType::LONG,
+                entry_price,
+                10,
+            )
+            .await?;
+        info!("result: {:?}", result);
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        info!("tx_hash: {:?}", tx_hash);
+        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
+        let response = order_wallet.query_trader_order(account_index).await?;
+        info!("response: {:?}", response);
+        assert_eq!(response.order_status, OrderStatus::FILLED);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&account_index)?
+                .io_type,
+            IOType::Memo
+        );
+
+        let result = order_wallet
+            .close_trader_order_sltp(
+                account_index,
+                OrderType::SLTP,
+                0.0,
+                Some(entry_price as f64 - 1000.0),
+                Some(entry_price as f64 + 1000.0),
+            )
+            .await?;
+
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        println!("tx_hash: {:?}", tx_hash);
+        let response = order_wallet.query_trader_order(account_index).await?;
+        println!("response: {:?}", response);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_trading_to_trading() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        let (tx_result, sender_account_index) = order_wallet.funding_to_trading(6000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+        let receiver_account_index = order_wallet
+            .trading_to_trading(sender_account_index)
+            .await?;
+        assert_ne!(sender_account_index, receiver_account_index);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&sender_account_index)?
+                .on_chain,
+            false
+        );
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&receiver_account_index)?
+                .balance,
+            6000
+        );
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&receiver_account_index)?
+                .on_chain,
+            true
+        );
+        let btc_price = order_wallet
+            .relayer_api_client
+            .btc_usd_price()
+            .await
+            .map_err(|e| e.to_string())?;
+        info!("btc_price: {:?}", btc_price);
+        let entry_price = btc_price.price as u64;
+        let result = order_wallet
+            .open_trader_order(
+                receiver_account_index,
+                OrderType::MARKET,
+                PositionType::LONG,
+                entry_price,
+                10,
+            )
+            .await?;
+        info!("result: {:?}", result);
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        info!("tx_hash: {:?}", tx_hash);
+        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
+        let response = order_wallet
+            .query_trader_order(receiver_account_index)
+            .await?;
+        info!("response: {:?}", response);
+        assert_eq!(response.order_status, OrderStatus::FILLED);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&receiver_account_index)?
+                .io_type,
+            IOType::Memo
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_open_lend_order() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+        let result = order_wallet.open_lend_order(account_index).await?;
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
+        let response = order_wallet.query_lend_order(account_index).await?;
+        assert_eq!(response.order_status, OrderStatus::FILLED);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&account_index)?
+                .io_type,
+            IOType::Memo
+        );
+        let result = order_wallet.close_lend_order(account_index).await?;
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        assert_eq!(tx_hash.order_status, OrderStatus::SETTLED);
+        let response = order_wallet.query_lend_order(account_index).await?;
+        assert_eq!(response.order_status, OrderStatus::SETTLED);
+        order_wallet.unlock_lend_order(account_index).await?;
+        order_wallet.sync_account_state(account_index).await?;
+        let zk_account = order_wallet.zk_accounts.get_account(&account_index)?;
+        assert_eq!(zk_account.io_type, IOType::Coin);
+        assert_eq!(zk_account.balance, response.new_lend_state_amount as u64);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_create_order_with_cancel() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+
+        let btc_price = order_wallet
+            .relayer_api_client
+            .btc_usd_price()
+            .await
+            .map_err(|e| e.to_string())?;
+        info!("btc_price: {:?}", btc_price);
+        let entry_price = btc_price.price as u64;
+        let result = order_wallet
+            .open_trader_order(
+                account_index,
+                OrderType::LIMIT,
+                PositionType::LONG,
+                entry_price - 1000,
+                10,
+            )
+            .await?;
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        assert_eq!(tx_hash.order_status, OrderStatus::PENDING);
+        let response = order_wallet.query_trader_order(account_index).await?;
+        assert_eq!(response.order_status, OrderStatus::PENDING);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&account_index)?
+                .io_type,
+            IOType::Memo
+        );
+
+        let result = order_wallet.cancel_trader_order(account_index).await?;
+
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        assert_eq!(tx_hash.order_status, OrderStatus::CANCELLED);
+        let response = order_wallet.query_trader_order(account_index).await?;
+        assert_eq!(response.order_status, OrderStatus::CANCELLED);
+        let zk_account = order_wallet.zk_accounts.get_account(&account_index)?;
+        assert_eq!(zk_account.io_type, IOType::Coin);
+        assert_eq!(zk_account.balance, response.available_margin as u64);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_get_wallet_list_from_db() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet_list = OrderWallet::get_wallet_list_from_db(None).map_err(|e| e.to_string())?;
+        println!("wallet_list: {:?}", wallet_list);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_with_db() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let mut order_wallet = OrderWallet::new(None).map_err(|e| e.to_string())?;
+        order_wallet.with_db(None, None)?;
+        let wallet_list = OrderWallet::get_wallet_list_from_db(None).map_err(|e| e.to_string())?;
+        println!("wallet_list: {:?}", wallet_list);
+        Ok(())
+    }
+
+    // Interrupts a two-step operation halfway, reloads the wallet from the DB and resumes it.
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_pending_operation_resume_after_reload() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let password = SecretString::new("pending_ops_password".into());
+        let wallet_id = format!("pending-ops-{}", uuid::Uuid::new_v4());
+        let mut order_wallet = OrderWallet::new(None).map_err(|e| e.to_string())?;
+        order_wallet.with_db(Some(password.clone()), Some(wallet_id.clone()))?;
+
+        let first = order_wallet.zk_accounts.generate_new_account(0, &order_wallet.seed.secret()?)?;
+        order_wallet.try_save_new_account_to_db(&first);
+        // The second account does not exist yet, so the second step fails.
+        let second = first + 1;
+        let op = PendingOperation::new(
+            PendingOperationKind::SplitAccount,
+            OperationInputs::SplitAccount {
+                sender_account_index: first,
+                balances: vec![],
+            },
+            vec![
+                OperationStep::FinalizeSender {
+                    account_index: first,
+                    remaining_balance: 0,
+                },
+                OperationStep::FinalizeSender {
+                    account_index: second,
+                    remaining_balance: 0,
+                },
+            ],
+            order_wallet.clock.now(),
+        );
+        let op_id = op.id.clone();
+        assert!(order_wallet.drive_operation(op).await.is_err());
+        drop(order_wallet);
+
+        let (mut reloaded, _) = OrderWallet::load_from_db(wallet_id, Some(password), None)?;
+        let pending = reloaded.pending_operations();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, op_id);
+        assert_eq!(pending[0].completed_steps.len(), 1);
+        assert!(pending[0].last_error.is_some());
+
+        let created = reloaded.zk_accounts.generate_new_account(0, &reloaded.seed.secret()?)?;
+        assert_eq!(created, second);
+        let done = reloaded.resume_operation(&op_id).await?;
+        assert!(done.is_done());
+        assert!(reloaded.pending_operations().is_empty());
+        Ok(())
+    }
+
+    // A failing split step leaves the steps after it to run, and only it outstanding.
+    #[tokio::test]
+    async fn test_split_steps_run_independently() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let first = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        // The second account does not exist yet, so its step fails.
+        let second = first + 1;
+        let step = |account_index| OperationStep::FinalizeSender {
+            account_index,
+            remaining_balance: 0,
+        };
+        let op = PendingOperation::new(
+            PendingOperationKind::SplitAccount,
+            OperationInputs::SplitAccount {
+                sender_account_index: first,
+                balances: vec![],
+            },
+            vec![step(second), step(first)],
+            order_wallet.clock.now(),
+        );
+        let outcome = order_wallet.drive_split(op).await;
+        assert_eq!(outcome.unconfirmed.len(), 1);
+        assert_eq!(outcome.unconfirmed[0].0, second);
+        assert!(!order_wallet
+            .zk_accounts
+            .is_on_chain(&first)
+            .map_err(|e| e.to_string())?);
+        let pending = order_wallet.pending_operations();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].remaining_steps, vec![step(second)]);
+
+        let created = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        assert_eq!(created, second);
+        let retried = order_wallet.confirm_pending_receivers().await?;
+        assert!(retried.is_complete());
+        assert!(order_wallet.pending_operations().is_empty());
+        Ok(())
+    }
+
+    // A receiver whose UTXO is not queryable yet is confirmed by a later retry.
+    #[tokio::test]
+    async fn test_unfound_receiver_is_confirmed_by_retry() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::coin_utxo;
+
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&sender, true)?;
+        let receiver = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        let receiver_account = order_wallet.zk_accounts.get_account(&receiver)?;
+        let fetcher = Arc::new(ScriptedFetcher::new(vec![
+            Err("Failed to get utxo details: UTXO not found".to_string()),
+            Ok(coin_utxo(&receiver_account)),
+        ]));
+        let mut order_wallet = order_wallet.with_utxo_fetcher(fetcher);
+
+        let receiver_step = OperationStep::FinalizeReceiver {
+            account_index: receiver,
+            balance: 1_000,
+            encrypt_scalar: receiver_account.scalar.clone(),
+            account_key: receiver_account.account.clone(),
+        };
+        let op = PendingOperation::new(
+            PendingOperationKind::SplitAccount,
+            OperationInputs::SplitAccount {
+                sender_account_index: sender,
+                balances: vec![1_000],
+            },
+            vec![
+                receiver_step.clone(),
+                OperationStep::FinalizeSender {
+                    account_index: sender,
+                    remaining_balance: 0,
+                },
+            ],
+            order_wallet.clock.now(),
+        );
+        let outcome = order_wallet.drive_split(op).await;
+        assert!(outcome.confirmed.is_empty());
+        assert_eq!(outcome.unconfirmed.len(), 1);
+        assert_eq!(outcome.unconfirmed[0].0, receiver);
+        assert!(outcome.unconfirmed[0].1.contains("UTXO not found"));
+        // The sender was finalized regardless.
+        assert!(!order_wallet.zk_accounts.is_on_chain(&sender)?);
+        assert!(!order_wallet.zk_accounts.is_on_chain(&receiver)?);
+        let pending = order_wallet.pending_operations();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].remaining_steps, vec![receiver_step]);
+
+        let retried = order_wallet.confirm_pending_receivers().await?;
+        assert!(retried.is_complete());
+        assert_eq!(retried.confirmed, vec![(receiver, 1_000)]);
+        assert!(order_wallet.zk_accounts.is_on_chain(&receiver)?);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&receiver)?, 1_000);
+        assert!(order_wallet.pending_operations().is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_interrupted_intent_is_flagged_after_reload() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let password = SecretString::new("journal_password".into());
+        let wallet_id = format!("journal-{}", uuid::Uuid::new_v4());
+        let mut order_wallet = OrderWallet::new(None).map_err(|e| e.to_string())?;
+        order_wallet.with_db(Some(password.clone()), Some(wallet_id.clone()))?;
+
+        let funded = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.try_save_new_account_to_db(&funded);
+        let intent = |account_index| {
+            (
+                PendingOperationKind::FundAccount,
+                OperationInputs::FundAccount {
+                    account_index,
+                    amount: 1_000,
+                },
+                vec![OperationStep::ConfirmFunding {
+                    account_index,
+                    amount: 1_000,
+                }],
+            )
+        };
+        // A call that returned leaves nothing behind.
+        let (kind, inputs, steps) = intent(funded);
+        let finished = order_wallet.journal_intent(kind, inputs, steps);
+        order_wallet.finish_intent(finished, &Ok::<(), String>(()));
+        assert!(order_wallet.pending_operations().is_empty());
+
+        // An interrupted call whose account has no UTXO to confirm.
+        let (kind, inputs, steps) = intent(funded + 1);
+        let interrupted = order_wallet
+            .journal_intent(kind, inputs, steps)
+            .ok_or("intent not journaled")?;
+        drop(order_wallet);
+
+        let (mut reloaded, _) =
+            OrderWallet::load_from_db(wallet_id.clone(), Some(password.clone()), None)?;
+        assert_eq!(reloaded.pending_operations().len(), 1);
+        let resumed = reloaded.resume_pending().await?;
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].id, interrupted);
+        assert!(resumed[0].needs_resolution());
+        assert!(resumed[0].last_error.is_some());
+        // Flagged records wait for the operator.
+        assert!(reloaded.resume_pending().await?.is_empty());
+        drop(reloaded);
+
+        let (mut reloaded, _) = OrderWallet::load_from_db(wallet_id, Some(password), None)?;
+        let pending = reloaded.pending_operations();
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].needs_resolution());
+        assert!(reloaded.mark_operation_resolved(&interrupted)?.is_done());
+        assert!(reloaded.pending_operations().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resume_pending_finishes_failed_open_order() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{TraderOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+
+        // The submit timed out after it went out, so the intent stays open.
+        let intent = order_wallet.journal_order(
+            PendingOperationKind::OpenOrder,
+            index,
+            OrderKind::Trader,
+            "LIMIT SHORT at 52000, 5x".to_string(),
+        );
+        order_wallet.begin_submission(index, OrderKind::Trader, &address);
+        order_wallet.finish_intent(intent, &Err::<(), _>("Request timeout"));
+        let pending = order_wallet.pending_operations();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].last_error.as_deref(), Some("Request timeout"));
+
+        relayer.fail_once("transaction_hashes", "connection refused");
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .account_id(address.as_str())
+                .field(
+                    "datetime",
+                    order_wallet.clock.now().timestamp_millis().to_string(),
+                )
+                .request_id("REQ-OPEN")
+                .to_json()],
+        );
+        relayer.respond(
+            "trader_order_info",
+            TraderOrderBuilder::new()
+                .account_id(address.as_str())
+                .order_type(OrderType::LIMIT)
+                .order_status(OrderStatus::PENDING)
+                .position_type(PositionType::SHORT)
+                .position(1_000.0, 5.0, 52_000.0)
+                .to_json(),
+        );
+
+        // The relayer cannot be reached: still pending, not left to the operator.
+        let resumed = order_wallet.resume_pending().await?;
+        assert_eq!(resumed.len(), 1);
+        assert!(!resumed[0].is_done());
+        assert!(!resumed[0].needs_resolution());
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+
+        let resumed = order_wallet.resume_pending().await?;
+        assert!(resumed[0].is_done());
+        assert!(order_wallet.pending_operations().is_empty());
+        assert!(order_wallet.pending_submissions().is_empty());
+        assert_eq!(order_wallet.request_id(index)?, "REQ-OPEN");
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        Ok(())
+    }
+
+    /// Answers UTXO fetches from `replies` in turn, then as if the chain had
+    /// no output.
+    #[derive(Debug, Default)]
+    struct ScriptedFetcher {
+        replies: std::sync::Mutex<std::collections::VecDeque<Result<UtxoDetailResponse, String>>>,
+    }
+
+    impl ScriptedFetcher {
+        fn new(replies: Vec<Result<UtxoDetailResponse, String>>) -> Self {
+            Self {
+                replies: std::sync::Mutex::new(replies.into()),
+            }
+        }
+    }
+
+    impl UtxoFetcher for ScriptedFetcher {
+        fn fetch(&self, _account_address: String, _io_type: IOType) -> UtxoFuture {
+            let reply =
+                self.replies.lock().unwrap().pop_front().unwrap_or_else(|| {
+                    Err("Failed to get utxo details: UTXO not found".to_string())
+                });
+            Box::pin(async move { reply })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_pending_finishes_interrupted_rotation() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::coin_utxo;
+
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&sender, true)?;
+        let receiver = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let receiver_account = order_wallet.zk_accounts.get_account(&receiver)?;
+        order_wallet.set_account_label(sender, Some("main"))?;
+        let fetcher = Arc::new(ScriptedFetcher::new(vec![
+            Err(
+                "Failed to get utxo details after 3 attempts (transient): connection refused"
+                    .to_string(),
+            ),
+            Ok(coin_utxo(&receiver_account)),
+        ]));
+        let mut order_wallet = order_wallet.with_utxo_fetcher(fetcher);
+
+        // Interrupted after the transfer was broadcast.
+        let intent = order_wallet.journal_intent(
+            PendingOperationKind::RotateAccount,
+            OperationInputs::RotateAccount {
+                sender_account_index: sender,
+                receiver_account_index: receiver,
+                balance: 1_000,
+            },
+            vec![
+                OperationStep::FinalizeRotation {
+                    account_index: receiver,
+                    balance: 1_000,
+                    encrypt_scalar: receiver_account.scalar.clone(),
+                },
+                OperationStep::FinalizeSender {
+                    account_index: sender,
+                    remaining_balance: 0,
+                },
+            ],
+        );
+        let id = intent.ok_or("intent not journaled")?;
+
+        let resumed = order_wallet.resume_pending().await?;
+        assert_eq!(resumed[0].id, id);
+        assert!(!resumed[0].needs_resolution());
+        assert_eq!(resumed[0].remaining_steps.len(), 2);
+        assert_eq!(order_wallet.account_index_by_label("main")?, sender);
+
+        let resumed = order_wallet.resume_pending().await?;
+        assert!(resumed[0].is_done());
+        assert!(order_wallet.zk_accounts.is_on_chain(&receiver)?);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&receiver)?, 1_000);
+        assert!(!order_wallet.zk_accounts.is_on_chain(&sender)?);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&sender)?, 0);
+        assert!(order_wallet.pending_operations().is_empty());
+        // The label moved with the balance.
+        assert_eq!(order_wallet.account_index_by_label("main")?, receiver);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resume_pending_flags_missing_utxo() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let receiver = order_wallet
+            .zk_accounts
+            .generate_new_account(500, &order_wallet.seed.secret()?)?;
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(ScriptedFetcher::default()));
+        let scalar = order_wallet.zk_accounts.get_account(&receiver)?.scalar;
+        order_wallet.journal_intent(
+            PendingOperationKind::RotateAccount,
+            OperationInputs::RotateAccount {
+                sender_account_index: receiver,
+                receiver_account_index: receiver,
+                balance: 500,
+            },
+            vec![OperationStep::FinalizeRotation {
+                account_index: receiver,
+                balance: 500,
+                encrypt_scalar: scalar,
+            }],
+        );
+
+        // The transfer may never have been sent; only the operator can tell.
+        let resumed = order_wallet.resume_pending().await?;
+        assert!(resumed[0].needs_resolution());
+        assert!(order_wallet.resume_pending().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trading_limits_come_from_market_stats() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::coin_utxo;
+        use crate::relayer_module::trading_limits::LimitsSource;
+
+        let relayer = MockRelayer::new();
+        let mut stats = relayer
+            .get_market_stats()
+            .await
+            .map_err(|e| e.to_string())?;
+        stats.params.min_position_btc = 20_000.0;
+        stats.params.max_leverage = 20.0;
+        relayer.respond("get_market_stats", stats);
+
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        let utxo = coin_utxo(&order_wallet.zk_accounts.get_account(&index)?);
+        let order_wallet =
+            order_wallet.with_utxo_fetcher(Arc::new(ScriptedFetcher::new(vec![Ok(utxo)])));
+
+        let limits = order_wallet.refresh_trading_limits().await;
+        assert_eq!(limits.source, LimitsSource::Relayer);
+        assert_eq!(limits.min_position_value, 20_000);
+        assert_eq!(limits.leverage.max, Leverage::whole(20));
+
+        // 1_000 sats at 10x is below the minimum: rejected before anything is sent.
+        let err = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 10)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Position value 10000 sats below relayer minimum 20000 sats"
+        );
+        assert_eq!(relayer.call_count("submit_trade_order"), 0);
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+
+        // A relayer that stops answering keeps the limits it last published.
+        relayer.fail("get_market_stats", "connection refused");
+        let limits = order_wallet.refresh_trading_limits().await;
+        assert_eq!(limits.min_position_value, 20_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_risk_report_against_a_mock_relayer() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+
+        let chain = MockChain::spawn();
+        chain.route(
+            "/cosmos/bank/v1beta1/balances/",
+            vec![MockResponse::ok(
+                r#"{"balances":[{"denom":"sats","amount":"5000"}]}"#,
+            )],
+        );
+        let relayer = MockRelayer::new();
+        relayer.respond(
+            "btc_usd_price",
+            serde_json::json!({ "id": 1, "price": "50000", "timestamp": "2025-01-01T00:00:00Z" }),
+        );
+        relayer.respond("trader_order_info", TraderOrderBuilder::new().to_json());
+        relayer.fail("lend_order_info", "relayer busy");
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+
+        let seed = order_wallet.seed.secret()?;
+        let accounts = &order_wallet.zk_accounts;
+        let coin = accounts.generate_new_account(2_000, &seed)?;
+        let trader = accounts.generate_new_account(1_000, &seed)?;
+        let lend = accounts.generate_new_account(3_000, &seed)?;
+        for index in [coin, trader, lend] {
+            accounts.update_on_chain(&index, true)?;
+        }
+        accounts.update_io_type(&trader, IOType::Memo, Some(TXType::ORDERTX))?;
+        accounts.update_io_type(&lend, IOType::Memo, Some(TXType::LENDTX))?;
+
+        let report = order_wallet.risk_report().await?;
+        assert_eq!(report.on_chain_sats, 5_000);
+        assert_eq!(report.coin_account_sats, 2_000);
+        assert_eq!(report.long.positions, 1);
+        assert_eq!(report.margin_locked_sats, 1_000);
+        // The lend order could not be queried.
+        assert_eq!(report.unavailable_accounts, vec![lend]);
+        let riskiest = report.riskiest_position.clone().unwrap();
+        assert_eq!(riskiest.account_index, trader);
+        assert!((riskiest.distance_pct - 32.0).abs() < 1e-9);
+        assert_eq!(
+            order_wallet.diagnostic_snapshot().risk.as_ref(),
+            Some(&report)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_status_snapshot_leaves_settled_accounts_locked() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+
+        let chain = MockChain::spawn();
+        chain.route(
+            "/cosmos/bank/v1beta1/balances/",
+            vec![MockResponse::ok(
+                r#"{"balances":[{"denom":"sats","amount":"5000"}]}"#,
+            )],
+        );
+        let relayer = MockRelayer::new();
+        relayer.respond(
+            "btc_usd_price",
+            serde_json::json!({ "id": 1, "price": "50000", "timestamp": "2025-01-01T00:00:00Z" }),
+        );
+        let settled = TraderOrderBuilder::new().order_status(OrderStatus::SETTLED);
+        relayer.respond("trader_order_info_v1", settled.to_json());
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        order_wallet
+            .zk_accounts
+            .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
+
+        let snapshot = order_wallet.status_snapshot().await?;
+        assert_eq!(snapshot.portfolio.wallet_balance_sats, 5_000);
+        assert_eq!(snapshot.portfolio.closed_trader_positions.len(), 1);
+        assert!(snapshot.endpoints.iter().all(|e| e.healthy));
+        // Reported as settled, but neither unlocked nor cached.
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        assert_eq!(order_wallet.wallet.balance_sats, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_config_risk_limits_reject_before_submitting() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::coin_utxo;
+
+        let config =
+            Config::from_toml("[risk]\nmax_position_sats = 5000\n").map_err(|e| e.to_string())?;
+        let relayer = MockRelayer::new();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()))
+            .with_config(&config);
+        assert_eq!(order_wallet.risk_limits().max_position_sats, Some(5_000));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        let utxo = coin_utxo(&order_wallet.zk_accounts.get_account(&index)?);
+        let order_wallet =
+            order_wallet.with_utxo_fetcher(Arc::new(ScriptedFetcher::new(vec![Ok(utxo)])));
+
+        let err = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 10)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Position value 10000 sats above the configured maximum 5000 sats"
+        );
+        assert_eq!(relayer.call_count("submit_trade_order"), 0);
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    #[serial]
+    fn test_load_from_db_reports_config_drift() -> Result<(), String> {
+        init_logger();
+        let path = std::env::temp_dir().join(format!("nyks-drift-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = path.to_str().unwrap().to_string();
+        let pool = crate::database::connection::init_pool(Some(url.clone()))?;
+        let mut conn = crate::database::connection::get_conn(&pool)?;
+        crate::database::connection::run_migrations(&mut conn)?;
+        drop(conn);
+        let password = SecretString::new("config_drift_password".into());
+        let wallet_id = format!("config-drift-{}", uuid::Uuid::new_v4());
+        let mut saved_config = EndpointConfig::default();
+        saved_config.relayer_api_endpoint = "http://saved-relayer:8088/api".to_string();
+        let mut order_wallet =
+            OrderWallet::import_from_mnemonic(TEST_MNEMONIC, Some(saved_config))?;
+        order_wallet.attach_database(password.clone(), wallet_id.clone(), pool)?;
+        order_wallet.save_order_wallet_to_db()?;
+        drop(order_wallet);
+
+        let err = OrderWallet::load_from_db_checked(
+            wallet_id.clone(),
+            Some(password.clone()),
+            Some(url.clone()),
+            true,
+        )
+        .err()
+        .expect("strict load refuses drifted config");
+        assert!(err.contains("relayer_api_endpoint"));
+
+        let (reloaded, drift) = OrderWallet::load_from_db(wallet_id, Some(password), Some(url))?;
+        assert_eq!(drift.fields.len(), 1);
+        let field = drift.get("relayer_api_endpoint").unwrap();
+        assert_eq!(field.saved, "http://saved-relayer:8088/api");
+        assert_eq!(
+            field.current,
+            crate::config::RELAYER_API_RPC_SERVER_URL.as_str()
+        );
+        assert_eq!(reloaded.config_drift(), &drift);
+        assert_eq!(reloaded.diagnostic_snapshot().config_drift, drift);
+        drop(reloaded);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    fn drift_fixture() -> (ConfigFingerprint, ConfigFingerprint) {
+        let saved = ConfigFingerprint::capture(
+            "nyks",
+            &crate::config::WalletEndPointConfig::default(),
+            &RelayerEndPointConfig::default(),
+        );
+        let mut current = saved.clone();
+        current.relayer_api_endpoint = "http://other-relayer/api".to_string();
+        current.chain_id = "nyks-test".to_string();
+        (saved, current)
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    #[test]
+    fn test_config_drift_lenient_by_default() {
+        let (saved, current) = drift_fixture();
+        let drift = check_config_drift("w", Some(&saved), &current, false).unwrap();
+        assert_eq!(drift.fields.len(), 2);
+        assert_eq!(drift.get("chain_id").unwrap().current, "nyks-test");
+        // Rows saved before fingerprints were stored report nothing.
+        assert!(check_config_drift("w", None, &current, true)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    #[test]
+    fn test_config_drift_fatal_when_strict() {
+        let (saved, current) = drift_fixture();
+        let err = check_config_drift("w", Some(&saved), &current, true).unwrap_err();
+        assert!(err.contains("relayer_api_endpoint"));
+        assert!(err.contains("chain_id"));
+
+        // A crate upgrade alone does not block a strict load.
+        let mut upgraded = saved.clone();
+        upgraded.crate_version = "999.0.0".to_string();
+        let drift = check_config_drift("w", Some(&saved), &upgraded, true).unwrap();
+        assert_eq!(drift.fields.len(), 1);
+        assert!(check_config_drift("w", Some(&saved), &saved, true)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "health-endpoint")]
+    #[tokio::test]
+    async fn test_serve_health_fails_config_check_on_drift() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let saved = order_wallet.config_fingerprint();
+        let mut current = saved.clone();
+        current.relayer_api_endpoint = "http://other-relayer/api".to_string();
+        order_wallet.config_drift = ConfigDrift::between(&saved, &current);
+
+        let addr = ([127, 0, 0, 1], 0).into();
+        let handle = order_wallet.serve_health(addr).await?;
+        let report = order_wallet.health.as_ref().unwrap().report();
+        let config = &report.checks[health::CHECK_CONFIG];
+        assert!(!config.healthy);
+        let detail = config.detail.as_deref().unwrap_or_default();
+        assert!(detail.contains("relayer_api_endpoint"));
+        assert!(!report.ready);
+        handle.shutdown().await;
+        Ok(())
+    }
+
+    #[test]
+    fn test_relayer_program_loaded_at_construction() {
+        let missing = EndpointConfig {
+            relayer_program_json_path: "./no-such-relayerprogram.json".to_string(),
+            ..EndpointConfig::default()
+        };
+        let err =
+            OrderWallet::import_from_mnemonic(TEST_MNEMONIC, Some(missing.clone())).unwrap_err();
+        assert_eq!(
+            err,
+            "relayer program file not found at ./no-such-relayerprogram.json (set RELAYER_PROGRAM_JSON_PATH)"
+        );
+
+        let lazy = missing.with_program_loading(ProgramLoading::Lazy);
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, Some(lazy)).unwrap();
+        assert!(matches!(
+            order_wallet.relayer_program(),
+            Err(RelayerProgramError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_endpoint_config_fails_construction() {
+        let invalid = EndpointConfig {
+            nyks_lcd_endpoint: "0.0.0.0:1317".to_string(),
+            ..EndpointConfig::default()
+        };
+        let err =
+            OrderWallet::import_from_mnemonic(TEST_MNEMONIC, Some(invalid.clone())).unwrap_err();
+        assert!(
+            err.starts_with("invalid config value `nyks_lcd_endpoint`"),
+            "{}",
+            err
+        );
+        assert!(matches!(
+            OrderWallet::new(Some(invalid)),
+            Err(WalletError::InvalidConfig(crate::config::ConfigError::Invalid { ref field, .. }))
+                if field == "nyks_lcd_endpoint"
+        ));
+    }
+
+    #[test]
+    fn test_mnemonic_passphrase_derives_other_wallet_and_seed() -> Result<(), String> {
+        use secrecy::ExposeSecret;
+
+        let plain = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let protected = OrderWallet::import_from_mnemonic_with_passphrase(
+            TEST_MNEMONIC,
+            SecretString::new("TREZOR".to_string()),
+            None,
+        )?;
+        assert_ne!(
+            protected.wallet.twilightaddress,
+            plain.wallet.twilightaddress
+        );
+        assert_ne!(
+            protected.seed.secret()?.expose_secret(),
+            plain.seed.secret()?.expose_secret()
+        );
+        // The passphrase goes into neither serialized form.
+        let json = serde_json::to_string(&protected).map_err(|e| e.to_string())?;
+        assert!(!json.contains("TREZOR"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_funding_to_trading_multiple() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        // More accounts than fit in one transaction, so two chunks are sent.
+        let amounts: Vec<u64> = (1..=MAX_MINTS_PER_TX as u64 + 2)
+            .map(|i| 1_000 * i)
+            .collect();
+        let started = std::time::Instant::now();
+        let (txs, funded) = order_wallet
+            .funding_to_trading_multiple(amounts.clone())
+            .await?;
+        info!(
+            "Funded {} accounts in {} txs in {:?}",
+            funded.len(),
+            txs.len(),
+            started.elapsed()
+        );
+        assert_eq!(txs.len(), 2);
+        assert!(txs.iter().all(|tx| tx.code == 0));
+        assert_eq!(
+            funded
+                .iter()
+                .map(|(_, balance)| *balance)
+                .collect::<Vec<_>>(),
+            amounts
+        );
+        for (index, balance) in funded {
+            let account = order_wallet.zk_accounts.get_account(&index)?;
+            assert!(account.on_chain);
+            assert_eq!(account.io_type, IOType::Coin);
+            assert_eq!(account.balance, balance);
+            assert!(!account.balance_unverified);
+            assert!(order_wallet.utxo_details.contains_key(&index));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_funding_outcome_keeps_chunks_on_chain() {
+        let tx = |hash: &str| TxResult {
+            tx_hash: hash.to_string(),
+            code: 0,
+            simulated: false,
+            attempts: Vec::new(),
+            log: None,
+        };
+        let failed = || Some(OrderWalletError::Other("chunk 2 rejected".to_string()));
+
+        assert!(matches!(
+            funding_outcome(vec![], vec![], failed()),
+            Err(OrderWalletError::Other(e)) if e == "chunk 2 rejected"
+        ));
+        let completed = funding_outcome(vec![tx("A")], vec![(1, 1_000)], None).unwrap();
+        assert_eq!(completed, (vec![tx("A")], vec![(1, 1_000)]));
+
+        let err =
+            funding_outcome(vec![tx("A")], vec![(1, 1_000), (2, 2_000)], failed()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "funded accounts [1, 2], then failed: chunk 2 rejected"
+        );
+        assert!(!err.is_retryable());
+        let OrderWalletError::PartialFunding { txs, funded, .. } = err else {
+            panic!("expected PartialFunding, got {:?}", err);
+        };
+        assert_eq!(txs, vec![tx("A")]);
+        assert_eq!(funded, vec![(1, 1_000), (2, 2_000)]);
+
+        // Minted but not synced: the transaction is still reported.
+        let err = funding_outcome(vec![tx("B")], vec![], failed()).unwrap_err();
+        assert!(
+            matches!(err, OrderWalletError::PartialFunding { txs, .. } if txs == vec![tx("B")])
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_trading_to_trading_multiple_accounts() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        // order_wallet.with_db(None, Some("test_wallet_multiple_accounts10".to_string()))?;
+        let (tx_result, sender_account_index) = order_wallet.funding_to_trading(40000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+        let balances = vec![5000, 1000, 8000, 600];
+        let outcome = order_wallet
+            .trading_to_trading_multiple_accounts(sender_account_index, balances)
+            .await?;
+        println!("outcome: {:?}", outcome);
+        assert!(
+            outcome.is_complete(),
+            "unconfirmed: {:?}",
+            outcome.unconfirmed
+        );
+        let new_account_balances = outcome.confirmed;
+        println!("zk_accounts: {:?}", order_wallet.zk_accounts);
+
+        let btc_price = order_wallet
+            .relayer_api_client
+            .btc_usd_price()
+            .await
+            .map_err(|e| e.to_string())?;
+        info!("btc_price: {:?}", btc_price);
+        let entry_price = btc_price.price as u64;
+        info!(
+            "zk_accounts: {:?}",
+            order_wallet
+                .zk_accounts
+                .get_account(&new_account_balances[0].0)
+        );
+        info!("waiting 10 seconds");
+        sleep(Duration::from_secs(10)).await;
+        let result = order_wallet
+            .open_trader_order(
+                new_account_balances[0].0,
+                OrderType::MARKET,
+                PositionType::LONG,
+                entry_price,
+                10,
+            )
+            .await?;
+        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
+        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
+        let response = order_wallet
+            .query_trader_order(new_account_balances[0].0)
+            .await?;
+        assert_eq!(response.order_status, OrderStatus::FILLED);
+        assert_eq!(
+            order_wallet
+                .zk_accounts
+                .get_account(&new_account_balances[0].0)?
+                .io_type,
+            IOType::Memo
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_trading_to_trading_partial() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        let (tx_result, sender) = order_wallet.funding_to_trading(10000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+        let receiver = order_wallet
+            .trading_to_trading_partial(sender, 4000)
+            .await?;
+        let sender_account = order_wallet.zk_accounts.get_account(&sender)?;
+        let receiver_account = order_wallet.zk_accounts.get_account(&receiver)?;
+        assert!(sender_account.on_chain);
+        assert_eq!(sender_account.balance, 6000);
+        assert!(receiver_account.on_chain);
+        assert_eq!(receiver_account.balance, 4000);
+
+        let entry_price = order_wallet
+            .relayer_api_client
+            .btc_usd_price()
+            .await
+            .map_err(|e| e.to_string())?
+            .price as u64;
+        sleep(Duration::from_secs(10)).await;
+        for index in [sender, receiver] {
+            let request_id = order_wallet
+                .open_trader_order(index, OrderType::MARKET, PositionType::LONG, entry_price, 5)
+                .await?;
+            let tx_hash =
+                fetch_tx_hash_with_retry(&request_id, &order_wallet.relayer_api_client).await?;
+            assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
+            assert_eq!(
+                order_wallet.zk_accounts.get_account(&index)?.io_type,
+                IOType::Memo
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trading_to_trading_partial_rejects_bad_amounts() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+
+        let err = order_wallet
+            .trading_to_trading_partial(sender, 0)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("greater than zero"), "{}", err);
+        let err = order_wallet
+            .trading_to_trading_partial(sender, 101)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                OrderWalletError::InsufficientBalance {
+                    required: 101,
+                    available: 100
+                }
+            ),
+            "{}",
+            err
+        );
+        // Nothing was created for either.
+        assert_eq!(order_wallet.zk_accounts.get_all_accounts().len(), 1);
+        Ok(())
+    }
+
+    /// Stands in for the chain around a transfer: the outputs of broadcast
+    /// transactions become the UTXOs fetched for their owner addresses.
+    #[derive(Debug, Default)]
+    struct TransferLedger {
+        outputs: std::sync::Mutex<Vec<crate::compat::zkvm::Output>>,
+    }
+
+    impl ChainBroadcaster for TransferLedger {
+        fn broadcast(&self, tx: Transaction) -> Result<String, String> {
+            self.outputs.lock().unwrap().extend(tx.get_tx_outputs());
+            Ok("fixture-tx-hash".to_string())
+        }
+    }
+
+    impl UtxoFetcher for TransferLedger {
+        fn fetch(&self, account_address: String, _io_type: IOType) -> UtxoFuture {
+            use crate::relayer_module::test_fixtures::UtxoDetailResponseBuilder;
+
+            // The latest output wins, as the earlier one was spent.
+            let reply = self
+                .outputs
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .find(|output| {
+                    output
+                        .as_output_data()
+                        .get_owner_address()
+                        .is_some_and(|address| address.to_string() == account_address)
+                })
+                .map(|output| UtxoDetailResponseBuilder::from_output(output.clone()).build())
+                .ok_or_else(|| "Failed to get utxo details: UTXO not found".to_string());
+            Box::pin(async move { reply })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_both_accounts_of_a_partial_transfer_can_trade() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::coin_utxo;
+
+        let relayer = MockRelayer::new();
+        let mut stats = relayer
+            .get_market_stats()
+            .await
+            .map_err(|e| e.to_string())?;
+        stats.pool_equity_btc = 1_000_000.0;
+        stats.max_long_btc = 1_000_000.0;
+        stats.max_short_btc = 1_000_000.0;
+        relayer.respond("get_market_stats", stats);
+        relayer.respond("transaction_hashes", serde_json::json!([]));
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(10_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&sender, true)?;
+        let funded = coin_utxo(&order_wallet.zk_accounts.get_account(&sender)?).output;
+        let ledger = Arc::new(TransferLedger {
+            outputs: std::sync::Mutex::new(vec![funded]),
+        });
+        let mut order_wallet = order_wallet
+            .with_chain_broadcaster(ledger.clone())
+            .with_utxo_fetcher(ledger.clone());
+
+        let receiver = order_wallet
+            .trading_to_trading_partial(sender, 4_000)
+            .await?;
+        let sender_account = order_wallet.zk_accounts.get_account(&sender)?;
+        let receiver_account = order_wallet.zk_accounts.get_account(&receiver)?;
+        assert!(sender_account.on_chain);
+        assert_eq!(sender_account.balance, 6_000);
+        assert!(receiver_account.on_chain);
+        assert_eq!(receiver_account.balance, 4_000);
+        assert!(order_wallet.pending_operations().is_empty());
+
+        for (index, request_id) in [(sender, "REQ-SENDER"), (receiver, "REQ-RECEIVER")] {
+            relayer.accept("submit_trade_order", request_id);
+            let opened = order_wallet
+                .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+                .await?;
+            assert_eq!(opened, request_id);
+            assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        }
+        assert_eq!(relayer.call_count("submit_trade_order"), 2);
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_label_follows_a_rotation_and_survives_a_reload() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::coin_utxo;
+
+        let path = std::env::temp_dir().join(format!("nyks-labels-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = path.to_str().unwrap().to_string();
+        let pool = crate::database::connection::init_pool(Some(url.clone()))?;
+        let mut conn = crate::database::connection::get_conn(&pool)?;
+        crate::database::connection::run_migrations(&mut conn)?;
+        drop(conn);
+        let password = SecretString::new("labels_password".into());
+        let wallet_id = format!("labels-{}", uuid::Uuid::new_v4());
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        order_wallet.attach_database(password.clone(), wallet_id.clone(), pool)?;
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(5_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&sender, true)?;
+        order_wallet.try_save_new_account_to_db(&sender);
+        order_wallet.set_account_label(sender, Some("hedge"))?;
+        let funded = coin_utxo(&order_wallet.zk_accounts.get_account(&sender)?).output;
+        let ledger = Arc::new(TransferLedger {
+            outputs: std::sync::Mutex::new(vec![funded]),
+        });
+        let mut order_wallet = order_wallet
+            .with_chain_broadcaster(ledger.clone())
+            .with_utxo_fetcher(ledger);
+
+        let receiver = order_wallet.trading_to_trading(sender).await?;
+        assert_eq!(order_wallet.account_index_by_label("hedge")?, receiver);
+        assert_eq!(order_wallet.zk_accounts.get_account(&sender)?.label, None);
+        order_wallet.save_order_wallet_to_db()?;
+        drop(order_wallet);
+
+        let (reloaded, _) = OrderWallet::load_from_db(wallet_id, Some(password), Some(url))?;
+        assert_eq!(reloaded.account_index_by_label("hedge")?, receiver);
+        assert_eq!(reloaded.zk_accounts.get_account(&sender)?.label, None);
+        drop(reloaded);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_trading_to_funding() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
+        let zk_accounts = ZkAccountDB::new();
+        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+            .map_err(|e| e.to_string())?;
+        let sats_before = order_wallet
+            .wallet
+            .update_balance()
+            .await
+            .map_err(|e| e.to_string())?
+            .sats;
+        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
+        if tx_result.code != 0 {
+            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+        }
+        sleep(Duration::from_secs(10)).await;
+        order_wallet
+            .wallet
+            .update_balance()
+            .await
+            .map_err(|e| e.to_string())?;
+        println!("wallet balance: {:?}", order_wallet.wallet.balance_sats);
+        let result = order_wallet.trading_to_funding(account_index).await?;
+        println!("result: {:?}", result);
+        assert_eq!(result.code, 0);
+        sleep(Duration::from_secs(10)).await;
+        let sats_after = order_wallet
+            .wallet
+            .update_balance()
+            .await
+            .map_err(|e| e.to_string())?
+            .sats;
+        println!("wallet balance: {:?}", order_wallet.wallet.balance_sats);
+        assert_eq!(sats_after, sats_before);
+
+        // The burned account is off-chain, empty and has no cached UTXO.
+        let burned = order_wallet
+            .zk_accounts
+            .get_all_accounts()
+            .into_iter()
+            .filter(|account| account.index != account_index)
+            .max_by_key(|account| account.index)
+            .ok_or("burned account not found")?;
+        assert!(!burned.on_chain);
+        assert_eq!(burned.balance, 0);
+        assert!(!order_wallet.utxo_details.contains_key(&burned.index));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_last_error_recorded_and_cleared() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+
+        // The account was never funded, so the order is rejected locally.
+        let result = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 10)
+            .await;
+        assert!(result.is_err());
+        let stored = order_wallet
+            .zk_accounts
+            .get_account(&index)?
+            .last_error
+            .ok_or("last_error not recorded")?;
+        assert_eq!(stored.operation, "open_trader_order");
+        assert!(stored.message.contains("does not exist on chain"));
+        assert!(!stored.retriable);
+
+        order_wallet.record_account_outcome(index, "open_trader_order", &Ok::<(), String>(()));
+        assert!(order_wallet.zk_accounts.get_account(&index)?.last_error.is_none());
+
+        let long = StoredError::new("close_trader_order", &"é".repeat(400), Utc::now());
+        assert!(long.message.len() <= MAX_STORED_ERROR_LEN + 3);
+        assert!(long.message.ends_with("..."));
+        assert!(StoredError::new("open_lend_order", "request timed out", Utc::now()).retriable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_checks_return_typed_errors() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+
+        let err = order_wallet.ensure_coin_onchain(index).unwrap_err();
+        assert!(matches!(err, OrderWalletError::AccountNotOnChain(i) if i == index));
+        assert!(!err.is_retryable());
+
+        order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        order_wallet.zk_accounts.update_balance(&index, 0)?;
+        assert!(matches!(
+            order_wallet.ensure_coin_onchain(index),
+            Err(OrderWalletError::EmptyAccount(i)) if i == index
+        ));
+
+        order_wallet
+            .zk_accounts
+            .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
+        let memo = format!("{:?}", IOType::Memo);
+        assert!(matches!(
+            order_wallet.ensure_coin_onchain(index),
+            Err(OrderWalletError::InvalidOrderState { actual, .. }) if actual == memo
+        ));
+        assert!(matches!(
+            order_wallet.ensure_coin_onchain(index + 1),
+            Err(OrderWalletError::Account(_))
+        ));
+
+        // Errors from the layers below keep their message; a missing UTXO is
+        // recognised and worth retrying.
+        let missing = OrderWalletError::from("Failed to get utxo details: UTXO not found");
+        assert!(matches!(missing, OrderWalletError::UtxoNotFound));
+        assert_eq!(missing.retry_class(), crate::relayer_module::RetryClass::NotFound);
+        assert!(missing.is_retryable());
+        let other = OrderWalletError::from("position_value overflow");
+        assert_eq!(String::from(other), "position_value overflow");
+        let unreachable = OrderWalletError::from(
+            "Failed to get utxo details after 3 attempts (transient): connection refused",
+        );
+        assert!(matches!(unreachable, OrderWalletError::ChainUnreachable(_)));
+        assert!(unreachable.is_retryable());
+        let reused = OrderWalletError::from("RPC error: Value Witness Verification Failed");
+        assert!(matches!(reused, OrderWalletError::WitnessRejected(_)));
+        assert!(!reused.is_retryable());
+        let stale = OrderWalletError::ChainTx {
+            tx_hash: "ABC".to_string(),
+            code: 32,
+            log: "account sequence mismatch".to_string(),
+        };
+        assert!(stale.is_retryable());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_signing_audit_records_signed_requests() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+
+        // Disabled by default: signing leaves no trace.
+        order_wallet.build_trader_query(index)?;
+        assert!(order_wallet.signing_activity(DateTime::<Utc>::MIN_UTC).is_empty());
+
+        order_wallet.enable_signing_audit()?;
+        order_wallet.build_trader_query(index)?;
+        order_wallet.build_lend_query(index)?;
+        // The request is signed and recorded before the (unreachable) relayer is contacted.
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let unreachable =
+            RelayerJsonRpcClient::new("http://127.0.0.1:1").map_err(|e| e.to_string())?;
+        let cancel = cancel_trader_order_audited(
+            address.clone(),
+            &order_wallet.get_secret_key(index)?,
+            address,
+            uuid::Uuid::new_v4(),
+            &unreachable,
+            &order_wallet.signing_audit,
+            index,
+        )
+        .await;
+        assert!(cancel.is_err());
+
+        let activity = order_wallet.signing_activity(DateTime::<Utc>::MIN_UTC);
+        let purposes: Vec<SigningPurpose> = activity.iter().map(|e| e.purpose).collect();
+        assert_eq!(
+            purposes,
+            vec![SigningPurpose::Query, SigningPurpose::Query, SigningPurpose::Cancel]
+        );
+        assert!(activity.iter().all(|e| e.account_index == index));
+        assert!(activity.iter().all(|e| e.payload_hash.len() == 64));
+        order_wallet.verify_signing_audit()?;
+
+        let later = order_wallet.clock().now() + chrono::Duration::seconds(1);
+        assert!(order_wallet.signing_activity(later).is_empty());
+
+        order_wallet.disable_signing_audit();
+        order_wallet.build_trader_query(index)?;
+        assert!(order_wallet.signing_activity(DateTime::<Utc>::MIN_UTC).is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_subsystems_in_reverse_order() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let registry = order_wallet.shutdown_registry().clone();
+        registry.register_task("sync", tokio::spawn(std::future::pending()));
+        for name in ["bot", "health"] {
+            let events = events.clone();
+            registry.register_hook(name, async move {
+                events.lock().unwrap().push(name);
+            });
+        }
+
+        let report = order_wallet.shutdown(ShutdownOptions::default()).await;
+        let steps: Vec<&str> = report.steps.iter().map(|s| s.step.as_str()).collect();
+        assert_eq!(
+            steps,
+            vec![
+                "task:sync",
+                "hook:health",
+                "hook:bot",
+                "cancel_pending",
+                "close_positions",
+                "flush",
+                "release_database",
+            ]
+        );
+        assert_eq!(*events.lock().unwrap(), vec!["health", "bot"]);
+        assert!(report.is_clean());
+
+        let again = order_wallet.shutdown(ShutdownOptions::default()).await;
+        assert!(again.already_shut_down);
+        assert!(again.steps.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_timeout_is_reported() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        order_wallet
+            .shutdown_registry()
+            .register_hook("slow", async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            });
+
+        let report = order_wallet
+            .shutdown(ShutdownOptions {
+                cancel_pending: true,
+                timeout: Duration::from_millis(50),
+                ..ShutdownOptions::default()
+            })
+            .await;
+        assert_eq!(report.timed_out(), vec!["hook:slow"]);
+        assert_eq!(
+            report.status("cancel_pending"),
+            Some(&StepStatus::Skipped(DEADLINE_EXCEEDED.to_string()))
+        );
+        // Local steps still run after the deadline.
+        assert!(matches!(report.status("flush"), Some(StepStatus::Done(_))));
+        assert!(!report.is_clean());
+        Ok(())
+    }
+
+    // Entries signed after shutdown must not reach the released database.
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    #[serial]
+    #[ignore]
+    async fn test_shutdown_releases_database() -> Result<(), String> {
+        dotenv::dotenv().ok();
+        init_logger();
+        let password = SecretString::new("shutdown_password".into());
+        let wallet_id = format!("shutdown-{}", uuid::Uuid::new_v4());
+        let mut order_wallet = OrderWallet::new(None).map_err(|e| e.to_string())?;
+        order_wallet.with_db(Some(password.clone()), Some(wallet_id.clone()))?;
+        order_wallet.enable_signing_audit()?;
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        order_wallet.build_trader_query(index)?;
+
+        let report = order_wallet.shutdown(ShutdownOptions::default()).await;
+        assert_eq!(report.status("flush"), Some(&StepStatus::Done(None)));
+        assert_eq!(
+            report.status("release_database"),
+            Some(&StepStatus::Done(None))
+        );
+        assert!(order_wallet.get_db_manager().is_none());
+        order_wallet.build_trader_query(index)?;
+        assert_eq!(
+            order_wallet
+                .signing_activity(DateTime::<Utc>::MIN_UTC)
+                .len(),
+            2
+        );
+
+        let (mut reloaded, _) = OrderWallet::load_from_db(wallet_id, Some(password), None)?;
+        reloaded.enable_signing_audit()?;
+        assert_eq!(reloaded.signing_activity(DateTime::<Utc>::MIN_UTC).len(), 1);
+        assert!(reloaded.zk_accounts.get_account(&index).is_ok());
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_btc_withdrawal_against_a_mock_chain_is_recorded() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+        use crate::relayer_module::transaction_history::TransferHistoryFilter;
+
+        // Withdrawals are refused off mainnet before anything is sent.
+        if !crate::config::is_mainnet() {
+            return Ok(());
+        }
+        let path = std::env::temp_dir().join(format!("nyks-withdraw-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = path.to_str().unwrap().to_string();
+        let pool = crate::database::connection::init_pool(Some(url))?;
+        let mut conn = crate::database::connection::get_conn(&pool)?;
+        crate::database::connection::run_migrations(&mut conn)?;
+        drop(conn);
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let password = SecretString::new("withdraw_password".into());
+        order_wallet.attach_database(password, "withdraw".to_string(), pool)?;
+
+        let chain = MockChain::spawn();
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        order_wallet.wallet.chain_config.rpc_endpoint = chain.rpc_url();
+        chain.route(
+            "/twilight-project/nyks/volt/btc_reserve",
+            vec![MockResponse::ok(
+                r#"{"BtcReserves":[{"ReserveId":"1","TotalValue":"1000"},{"ReserveId":"3","TotalValue":"900000"}]}"#,
+            )],
+        );
+        chain.route(
+            "/cosmos/bank/v1beta1/balances/",
+            vec![MockResponse::ok(
+                r#"{"balances":[{"denom":"nyks","amount":"100000"},{"denom":"sats","amount":"80000"}]}"#,
+            )],
+        );
+        let account = serde_json::json!({"account": {
+            "@type": "/cosmos.auth.v1beta1.BaseAccount",
+            "address": order_wallet.wallet.twilightaddress,
+            "pub_key": null,
+            "account_number": "7",
+            "sequence": "3",
+        }});
+        chain.route(
+            "/cosmos/auth/v1beta1/accounts/",
+            vec![MockResponse::ok(account.to_string())],
+        );
+        chain.route(
+            "/rpc",
+            vec![MockResponse::ok(
+                r#"{"jsonrpc":"2.0","id":"1","result":{"code":0,"data":"","log":"","codespace":"","hash":"CCCC"}}"#,
+            )],
+        );
+
+        let btc_address = order_wallet.wallet.btc_address.clone();
+        let submission = order_wallet
+            .request_btc_withdrawal(&btc_address, 50_000, true)
+            .await?;
+        assert_eq!(submission.id.reserve_id, 3);
+        assert_eq!(submission.tx.hash, "CCCC");
+        assert_eq!(chain.count("/rpc"), 1);
+
+        let db_manager = order_wallet.get_db_manager().unwrap();
+        let withdrawals = db_manager.load_btc_withdrawals()?;
+        assert_eq!(withdrawals.len(), 1);
+        assert_eq!(withdrawals[0].amount, 50_000);
+        assert_eq!(withdrawals[0].tx_hash.as_deref(), Some("CCCC"));
+        let history = order_wallet.get_transfer_history(TransferHistoryFilter::default())?;
+        let withdrawal = history
+            .iter()
+            .find(|entry| entry.direction == "btc_withdrawal");
+        assert_eq!(withdrawal.map(|entry| entry.amount), Some(50_000));
+        drop(order_wallet);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    // Locking blocks encrypted saves, password changes and backups only;
+    // unlocking checks the passphrase.
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_locked_wallet_refuses_encrypted_saves() -> Result<(), String> {
+        let path = std::env::temp_dir().join(format!("nyks-lock-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = path.to_str().unwrap().to_string();
+        let pool = crate::database::connection::init_pool(Some(url.clone()))?;
+        let mut conn = crate::database::connection::get_conn(&pool)?;
+        crate::database::connection::run_migrations(&mut conn)?;
+        drop(conn);
+        let password = SecretString::new("lock_password".into());
+        let wallet_id = format!("lock-{}", uuid::Uuid::new_v4());
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        order_wallet.attach_database(password.clone(), wallet_id.clone(), pool)?;
+        assert!(!order_wallet.is_locked());
+        order_wallet.save_order_wallet_to_db()?;
+
+        order_wallet.lock();
+        assert!(order_wallet.is_locked());
+        assert!(order_wallet.get_wallet_password().is_none());
+        let locked = WalletLocked.to_string();
+        assert_eq!(order_wallet.save_order_wallet_to_db(), Err(locked.clone()));
+        assert_eq!(
+            order_wallet.save_encrypted_wallet_to_db(),
+            Err(locked.clone())
+        );
+        let backup = std::env::temp_dir().join(format!("nyks-lock-{}.json", std::process::id()));
+        let backup = backup.to_str().unwrap();
+        assert_eq!(
+            order_wallet.export_backup_to_file(backup),
+            Err(locked.clone())
+        );
+        let new_password = SecretString::new("new_lock_password".into());
+        assert_eq!(
+            order_wallet.change_wallet_password(new_password.clone()),
+            Err(locked.clone())
+        );
+        // Tables that need no password are still written before the flush
+        // reports the lock; zk accounts are sealed with the key kept from unlock.
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        assert_eq!(order_wallet.flush_state(), Err(locked));
+        let saved = order_wallet
+            .get_db_manager()
+            .unwrap()
+            .load_all_zk_accounts()?;
+        assert!(saved.contains_key(&index));
+
+        let wrong = order_wallet.unlock(SecretString::new("not_the_password".into()));
+        assert!(wrong.is_err());
+        assert!(order_wallet.is_locked());
+        order_wallet.unlock(password.clone())?;
+        assert!(!order_wallet.is_locked());
+        order_wallet.save_order_wallet_to_db()?;
+        order_wallet.save_encrypted_wallet_to_db()?;
+        order_wallet.export_backup_to_file(backup)?;
+        let _ = std::fs::remove_file(backup);
+
+        // After a password change only the new password opens the wallet.
+        order_wallet.change_wallet_password(new_password.clone())?;
+        drop(order_wallet);
+        let old = OrderWallet::load_from_db(wallet_id.clone(), Some(password), Some(url.clone()));
+        assert!(old.is_err());
+        let (reloaded, _) = OrderWallet::load_from_db(wallet_id, Some(new_password), Some(url))?;
+        assert!(reloaded.zk_accounts.get_account(&index).is_ok());
+        drop(reloaded);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_from_private_key_matches_mnemonic_import() -> Result<(), String> {
+        let from_mnemonic = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let key = hex::encode(from_mnemonic.wallet.private_key_bytes());
+        let from_key = OrderWallet::import_from_private_key(
+            &key,
+            Some(&from_mnemonic.wallet.btc_address),
+            None,
+        )
+        .map_err(|e| e.to_string())?;
+        assert_eq!(
+            from_key.wallet.twilightaddress,
+            from_mnemonic.wallet.twilightaddress
+        );
+        assert_eq!(
+            from_key.wallet.btc_address,
+            from_mnemonic.wallet.btc_address
+        );
+        assert_eq!(
+            from_key.seed.secret()?.expose_secret(),
+            from_mnemonic.seed.secret()?.expose_secret()
+        );
+
+        let derived =
+            OrderWallet::import_from_private_key(&key, None, None).map_err(|e| e.to_string())?;
+        assert!(derived.wallet.btc_wallet.is_some());
+        assert!(matches!(
+            OrderWallet::import_from_private_key(&key[..10], None, None),
+            Err(WalletError::InvalidPrivateKeyLength(5))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_keystore_seed_derives_same_keys() -> Result<(), String> {
+        use crate::security::seed_storage::testing::MockKeystore;
+        let mut in_memory = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let seed = in_memory.seed.secret()?;
+        let keystore = Arc::new(MockKeystore::default());
+        let mut keystore_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_seed_keystore(SeedStorage::OsKeystore, keystore.clone())?;
+        assert_eq!(keystore_wallet.seed_storage(), SeedStorage::OsKeystore);
+        assert_eq!(in_memory.seed_storage(), SeedStorage::InMemory);
+        let debug = format!("{:?}", keystore_wallet);
+        assert!(!debug.contains(seed.expose_secret().as_str()));
+
+        // Both wallets derive the same keys and accounts; the keystore one
+        // reads the seed back for every derivation.
+        let loads = keystore.loads();
+        for index in [0, 1, 7] {
+            assert_eq!(
+                keystore_wallet.get_secret_key(index)?.as_bytes(),
+                in_memory.get_secret_key(index)?.as_bytes(),
+                "secret key {}",
+                index
+            );
+        }
+        assert_eq!(keystore.loads(), loads + 3);
+        let expected = in_memory
+            .zk_accounts
+            .generate_new_account(0, &in_memory.seed.secret()?)?;
+        let index = keystore_wallet
+            .zk_accounts
+            .generate_new_account(0, &keystore_wallet.seed.secret()?)?;
+        assert_eq!(
+            keystore_wallet.zk_accounts.get_account_address(&index)?,
+            in_memory.zk_accounts.get_account_address(&expected)?
+        );
+        assert_eq!(keystore.loads(), loads + 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unavailable_keystore_keeps_seed_in_memory() -> Result<(), String> {
+        use crate::security::seed_storage::testing::MockKeystore;
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_seed_keystore(
+                SeedStorage::OsKeystore,
+                Arc::new(MockKeystore::unavailable()),
+            )?;
+        assert_eq!(order_wallet.seed_storage(), SeedStorage::InMemory);
+        order_wallet.get_secret_key(0)?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "sqlite", feature = "postgresql"))]
+    #[test]
+    fn test_wallet_auto_locks_on_manual_clock() -> Result<(), String> {
+        use crate::relayer_module::clock::ManualClock;
+        let start: DateTime<Utc> = "2025-03-01T09:15:00Z".parse().unwrap();
+        let clock = ManualClock::new(start);
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_clock(Arc::new(clock.clone()))
+            .with_auto_lock(Some(Duration::from_secs(15 * 60)));
+        assert!(order_wallet.is_locked());
+        order_wallet
+            .passphrase
+            .unlock(SecretString::new("pw".into()), clock.now());
+
+        clock.advance(Duration::from_secs(10 * 60));
+        assert_eq!(
+            order_wallet.with_wallet_password(|pw| pw.expose_secret().to_string()),
+            Ok("pw".to_string())
+        );
+        // Idle time counts from the last use, not from unlocking.
+        clock.advance(Duration::from_secs(10 * 60));
+        assert!(!order_wallet.is_locked());
+        clock.advance(Duration::from_secs(5 * 60));
+        assert!(order_wallet.is_locked());
+        assert_eq!(order_wallet.with_wallet_password(|_| ()), Err(WalletLocked));
+        // The cache no longer holds the passphrase, so nothing can hand it out.
+        assert!(order_wallet.get_wallet_password().is_none());
+        assert_eq!(
+            format!("{:?}", order_wallet.passphrase),
+            "PassphraseCache { unlocked: false, auto_lock: Some(900s) }"
+        );
+        Ok(())
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingFetcher {
+        calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl CountingFetcher {
+        fn calls(&self) -> usize {
+            self.calls.lock().unwrap().len()
+        }
+    }
+
+    impl UtxoFetcher for CountingFetcher {
+        fn fetch(&self, account_address: String, _io_type: IOType) -> UtxoFuture {
+            self.calls.lock().unwrap().push(account_address);
+            Box::pin(async { Err("utxo fetch unavailable".to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_utxo_fetches_and_prewarm_use_fetcher() -> Result<(), String> {
+        let fetcher = Arc::new(CountingFetcher::default());
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_utxo_fetcher(fetcher.clone());
+        let funded = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let empty = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        for index in [funded, empty] {
+            order_wallet
+                .zk_accounts
+                .update_on_chain(&index, true)
+                .unwrap();
+        }
+
+        // Without a TTL nothing is pre-warmed and every open fetches.
+        order_wallet.prewarm(5).await.unwrap();
+        assert_eq!(fetcher.calls(), 0);
+        let err = order_wallet
+            .open_trader_order(funded, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "utxo fetch unavailable");
+        assert_eq!(fetcher.calls(), 1);
+        assert!(order_wallet.utxo_freshness(funded).is_none());
+
+        // Only the funded on-chain coin account is a pre-warm candidate.
+        let order_wallet = order_wallet.with_utxo_cache_ttl(Some(Duration::from_secs(30)));
+        order_wallet.prewarm(5).await.unwrap();
+        assert_eq!(fetcher.calls(), 2);
+        assert_eq!(
+            fetcher.calls.lock().unwrap()[1],
+            order_wallet.zk_accounts.get_account_address(&funded)?
+        );
+        order_wallet.prewarm(0).await.unwrap();
+        assert_eq!(fetcher.calls(), 2);
+        Ok(())
+    }
+
+    /// A wallet trading against `relayer`, on a market with room for its
+    /// orders, with a 30 s UTXO cache and a funded on-chain coin account
+    /// whose UTXO `replies` fetches return.
+    async fn cached_utxo_wallet(
+        relayer: &crate::relayer_module::mock_relayer::MockRelayer,
+        replies: usize,
+    ) -> Result<(OrderWallet, AccountIndex, Arc<ScriptedFetcher>), String> {
+        use crate::relayer_module::test_fixtures::coin_utxo;
+
+        let mut stats = relayer
+            .get_market_stats()
+            .await
+            .map_err(|e| e.to_string())?;
+        stats.pool_equity_btc = 1_000_000.0;
+        stats.max_long_btc = 1_000_000.0;
+        stats.max_short_btc = 1_000_000.0;
+        relayer.respond("get_market_stats", stats);
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()))
+            .with_utxo_cache_ttl(Some(Duration::from_secs(30)));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        let utxo = coin_utxo(&order_wallet.zk_accounts.get_account(&index)?);
+        let fetcher = Arc::new(ScriptedFetcher::new(vec![Ok(utxo); replies]));
+        let order_wallet = order_wallet.with_utxo_fetcher(fetcher.clone());
+        Ok((order_wallet, index, fetcher))
+    }
+
+    #[tokio::test]
+    async fn test_second_open_reuses_the_cached_utxo() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+
+        let relayer = MockRelayer::new();
+        relayer.respond("transaction_hashes", serde_json::json!([]));
+        relayer.fail_once("submit_trade_order", "Order queue is full");
+        relayer.accept("submit_trade_order", "REQ-SECOND");
+        let (order_wallet, index, fetcher) = cached_utxo_wallet(&relayer, 2).await?;
+
+        let err = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Order queue is full"));
+        assert_eq!(fetcher.replies.lock().unwrap().len(), 1);
+        assert!(order_wallet.has_reusable_utxo(index));
+
+        // Within the TTL the second open builds on the cached UTXO.
+        let request_id = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+            .await?;
+        assert_eq!(request_id, "REQ-SECOND");
+        assert_eq!(fetcher.replies.lock().unwrap().len(), 1);
+        assert_eq!(relayer.call_count("submit_trade_order"), 2);
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fee_estimate_is_kept_while_the_order_is_open() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use jsonrpc_core::IoHandler;
+
+        let mut io = IoHandler::new();
+        io.add_sync_method("get_fee_rate", |_| {
+            Ok(serde_json::json!({
+                "order_filled_on_market": "0.05",
+                "order_filled_on_limit": "0.02",
+                "order_settled_on_market": "0.04",
+                "order_settled_on_limit": "0.025",
+                "timestamp": "2025-01-02T00:00:00Z"
+            }))
+        });
+        let server = jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let relayer = MockRelayer::new();
+        relayer.respond("transaction_hashes", serde_json::json!([]));
+        relayer.fail_once("submit_trade_order", "Order queue is full");
+        relayer.accept("submit_trade_order", "REQ-FEES");
+        let (order_wallet, index, _) = cached_utxo_wallet(&relayer, 2).await?;
+        let mut order_wallet = order_wallet.with_fee_estimates(true);
+        order_wallet.relayer_api_client =
+            RelayerJsonRpcClient::new(&format!("http://{}", server.address()))
+                .map_err(|e| e.to_string())?;
+
+        // A failed open keeps no estimate.
+        let err = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Order queue is full"));
+        assert_eq!(order_wallet.fee_estimate(index), None);
+
+        order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+            .await?;
+        let estimate = order_wallet.fee_estimate(index).ok_or("no fee estimate")?;
+        assert_eq!(estimate.order_value, 2_000);
+        assert_eq!((estimate.open_fee, estimate.close_fee), (1, 1));
+
+        order_wallet.unlock_cancelled_order(index)?;
+        assert_eq!(order_wallet.fee_estimate(index), None);
+        server.close();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_on_a_stale_cached_utxo_refetches_and_retries() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+
+        let relayer = MockRelayer::new();
+        relayer.respond("transaction_hashes", serde_json::json!([]));
+        relayer.fail_once("submit_trade_order", "Invalid input: UTXO not found");
+        relayer.accept("submit_trade_order", "REQ-RETRIED");
+        let (order_wallet, index, fetcher) = cached_utxo_wallet(&relayer, 2).await?;
+        order_wallet.sync_account_state(index).await?;
+        assert!(order_wallet.has_reusable_utxo(index));
+
+        // The relayer refuses the cached input: the cache entry is dropped,
+        // the UTXO fetched again and the order sent once more.
+        let request_id = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+            .await?;
+        assert_eq!(request_id, "REQ-RETRIED");
+        assert!(fetcher.replies.lock().unwrap().is_empty());
+        assert_eq!(relayer.call_count("submit_trade_order"), 2);
+        assert_eq!(order_wallet.request_id(index)?, "REQ-RETRIED");
+        Ok(())
+    }
+
+    /// The chain has no output anywhere; one address cannot be looked up.
+    #[derive(Debug)]
+    struct SpentFetcher {
+        unreachable: String,
+    }
+
+    impl UtxoFetcher for SpentFetcher {
+        fn fetch(&self, account_address: String, _io_type: IOType) -> UtxoFuture {
+            let reply = if account_address == self.unreachable {
+                "connection refused"
+            } else {
+                "Failed to get utxo details: UTXO not found"
+            };
+            Box::pin(async move { Err(reply.to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resync_repairs_spent_accounts() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let spent = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let unfunded = order_wallet
+            .zk_accounts
+            .generate_new_account(500, &order_wallet.seed.secret()?)?;
+        let unreachable = order_wallet
+            .zk_accounts
+            .generate_new_account(200, &order_wallet.seed.secret()?)?;
+        for index in [spent, unreachable] {
+            order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        }
+        order_wallet
+            .zk_accounts
+            .update_io_type(&spent, IOType::Memo, Some(TXType::ORDERTX))?;
+        let unreachable_address = order_wallet.zk_accounts.get_account_address(&unreachable)?;
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(SpentFetcher {
+            unreachable: unreachable_address,
+        }));
+
+        // Locally an open order; on chain nothing is left.
+        let report = order_wallet.resync_account(spent).await?;
+        assert_eq!(report.chain_state, ChainUtxoState::Absent);
+        assert_eq!(report.fixes.len(), 4, "{:?}", report.fixes);
+        let account = order_wallet.zk_accounts.get_account(&spent)?;
+        assert!(!account.on_chain);
+        assert_eq!(account.io_type, IOType::Coin);
+        assert!(account.tx_type.is_none());
+        assert_eq!(account.balance, 0);
+        assert!(order_wallet.resync_account(spent).await?.is_clean());
+
+        // Funding not confirmed yet is left alone; lookup failures change nothing.
+        let summary = order_wallet.resync_all().await;
+        let probed: Vec<AccountIndex> = summary.accounts.iter().map(|r| r.account_index).collect();
+        assert_eq!(probed, vec![spent, unfunded]);
+        assert_eq!(summary.repaired().count(), 0);
+        assert_eq!(
+            summary.failed,
+            vec![(unreachable, "connection refused".to_string())]
+        );
+        let account = order_wallet.zk_accounts.get_account(&unreachable)?;
+        assert!(account.on_chain);
+        assert_eq!(account.balance, 200);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scan_derived_accounts_is_all_or_nothing() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let known = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        let unreachable = order_wallet.seed.with_seed(|seed| {
+            KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes()).derive_address(2)
+        })?;
+        let mut order_wallet =
+            order_wallet.with_utxo_fetcher(Arc::new(SpentFetcher { unreachable }));
+
+        // Nothing on chain at the derived addresses: nothing to recover.
+        assert!(order_wallet.scan_derived_accounts(1).await?.is_empty());
+        let err = order_wallet.scan_derived_accounts(3).await.unwrap_err();
+        assert_eq!(err, "Failed to probe account 2: connection refused");
+        assert_eq!(order_wallet.zk_accounts.len(), 1);
+        assert_eq!(order_wallet.zk_accounts.snapshot().index, known + 1);
+        Ok(())
+    }
+
+    /// Outputs by address and IO type; anything else is not found, and the
+    /// `panicking` address panics the fetch.
+    #[derive(Default)]
+    struct OutputsFetcher {
+        outputs: Vec<(String, IOType, UtxoDetailResponse)>,
+        panicking: Option<String>,
+    }
+
+    impl OutputsFetcher {
+        /// `account`'s output, served as `io_type`.
+        fn output(account: &ZkAccount, io_type: IOType) -> (String, IOType, UtxoDetailResponse) {
+            let detail = test_fixtures::coin_utxo(account);
+            (account.account.clone(), io_type, detail)
+        }
+    }
+
+    impl UtxoFetcher for OutputsFetcher {
+        fn fetch(&self, account_address: String, io_type: IOType) -> UtxoFuture {
+            if self.panicking.as_ref() == Some(&account_address) {
+                panic!("indexer client bug");
+            }
+            let output = self
+                .outputs
+                .iter()
+                .find(|(address, io, _)| *address == account_address && *io == io_type)
+                .map(|(_, _, detail)| detail.clone());
+            Box::pin(async move {
+                output.ok_or_else(|| "Failed to get utxo details: UTXO not found".to_string())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_derived_accounts_recovers_coin_and_memo_outputs() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let seed = order_wallet.seed.secret()?;
+        let coin = ZkAccount::from_seed(1, &seed, 700)?;
+        let memo = ZkAccount::from_seed(3, &seed, 0)?;
+        let fetcher = OutputsFetcher {
+            outputs: vec![
+                OutputsFetcher::output(&coin, IOType::Coin),
+                OutputsFetcher::output(&memo, IOType::Memo),
+            ],
+            panicking: None,
+        };
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+
+        let recovered = order_wallet.scan_derived_accounts(4).await?;
+        let found: Vec<_> = recovered
+            .iter()
+            .map(|r| (r.account_index, r.chain_state, r.balance))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, ChainUtxoState::Coin, Some(700)),
+                (3, ChainUtxoState::Memo, None)
+            ]
+        );
+        let account = order_wallet.zk_accounts.get_account(&1)?;
+        assert_eq!((account.balance, account.io_type), (700, IOType::Coin));
+        assert!(account.on_chain && !account.balance_unverified);
+        assert_eq!(account.account, coin.account);
+        let account = order_wallet.zk_accounts.get_account(&3)?;
+        assert_eq!(account.io_type, IOType::Memo);
+        assert!(account.on_chain);
+        assert_eq!(order_wallet.zk_accounts.len(), 2);
+        assert_eq!(order_wallet.zk_accounts.snapshot().index, 4);
+
+        // Recovered accounts are local now and are not probed again.
+        assert!(order_wallet.scan_derived_accounts(4).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resync_repairs_coin_and_memo_accounts() -> Result<(), String> {
+        use crate::compat::{
+            address::AddressType,
+            quisquislib::{ristretto::RistrettoPublicKey, Account, ElGamalCommitment},
+            zkvm::Address,
+        };
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
+        use curve25519_dalek::scalar::Scalar;
+        use rand::rngs::OsRng;
+
+        let relayer = MockRelayer::new();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let funded = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let lending = order_wallet
+            .zk_accounts
+            .generate_new_account(500, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&lending, true)?;
+
+        // The funding confirmed with 10 sats less than requested; the other
+        // account's funds went into a lend order the wallet never recorded.
+        let address = order_wallet.zk_accounts.get_account_address(&funded)?;
+        let pk: RistrettoPublicKey = Address::from_hex(&address, AddressType::Standard)
+            .map_err(|e| e.to_string())?
+            .into();
+        let commitment =
+            ElGamalCommitment::generate_commitment(&pk, Scalar::random(&mut OsRng), 990u64.into());
+        let mut on_chain = order_wallet.zk_accounts.get_account(&funded)?;
+        on_chain.qq_address = EncryptedAccount::from(Account::set_account(pk, commitment))
+            .to_hex_str()
+            .map_err(|e| e.to_string())?;
+        let lend_account = order_wallet.zk_accounts.get_account(&lending)?;
+        let fetcher = OutputsFetcher {
+            outputs: vec![
+                OutputsFetcher::output(&on_chain, IOType::Coin),
+                OutputsFetcher::output(&lend_account, IOType::Memo),
+            ],
+            panicking: None,
+        };
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+        let lend_tx = TxHashBuilder::new()
+            .account_id(lend_account.account.clone())
+            .order_type(OrderType::LEND)
+            .request_id("REQLEND")
+            .build();
+        relayer.respond("transaction_hashes", vec![lend_tx]);
+
+        let report = order_wallet.resync_account(funded).await?;
+        assert_eq!(report.chain_state, ChainUtxoState::Coin);
+        let fixes = serde_json::to_value(&report.fixes).map_err(|e| e.to_string())?;
+        assert_eq!(
+            fixes,
+            serde_json::json!([
+                {"field": "on_chain", "from": false, "to": true},
+                {"field": "utxo_detail_updated"},
+                {"field": "balance", "from": 1_000, "to": 990},
+            ])
+        );
+        let account = order_wallet.zk_accounts.get_account(&funded)?;
+        assert!(account.on_chain && !account.balance_unverified);
+        assert_eq!(account.balance, 990);
+
+        let report = order_wallet.resync_account(lending).await?;
+        assert_eq!(report.chain_state, ChainUtxoState::Memo);
+        let identified = report.fixes.iter().any(|fix| {
+            matches!(fix, AccountFix::OrderIdentified { tx_type: TXType::LENDTX, request_id }
+                if request_id == "REQLEND")
+        });
+        assert!(identified, "{:?}", report.fixes);
+        let account = order_wallet.zk_accounts.get_account(&lending)?;
+        assert_eq!(account.io_type, IOType::Memo);
+        assert!(matches!(account.tx_type, Some(TXType::LENDTX)));
+        let request_id = order_wallet.request_ids.get(&lending);
+        assert_eq!(request_id.as_deref(), Some("REQLEND"));
+
+        // Both accounts now match the chain; the order is not looked up again.
+        let summary = order_wallet.resync_all().await;
+        assert_eq!(summary.accounts.len(), 2);
+        assert_eq!(summary.repaired().count(), 0);
+        assert_eq!(relayer.call_count("transaction_hashes"), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resync_all_reports_a_panicked_probe() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let healthy = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let broken = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let fetcher = OutputsFetcher {
+            outputs: Vec::new(),
+            panicking: Some(order_wallet.zk_accounts.get_account_address(&broken)?),
+        };
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+
+        let summary = order_wallet.resync_all().await;
+        let probed: Vec<AccountIndex> = summary.accounts.iter().map(|r| r.account_index).collect();
+        assert_eq!(probed, vec![healthy]);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, broken);
+        assert!(summary.failed[0].1.contains("panicked: indexer client bug"));
+        let account = order_wallet.zk_accounts.get_account(&broken)?;
+        assert_eq!(account.balance, 1_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scan_derived_accounts_reports_a_panicked_probe() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let seed = order_wallet.seed.secret()?;
+        let coin = ZkAccount::from_seed(0, &seed, 700)?;
+        let fetcher = OutputsFetcher {
+            outputs: vec![OutputsFetcher::output(&coin, IOType::Coin)],
+            panicking: Some(ZkAccount::from_seed(1, &seed, 0)?.account),
+        };
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+
+        let err = order_wallet.scan_derived_accounts(2).await.unwrap_err();
+        assert!(err.starts_with("Failed to probe account 1:"), "{}", err);
+        assert!(err.contains("panicked: indexer client bug"), "{}", err);
+        assert_eq!(order_wallet.zk_accounts.len(), 0);
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_scan_derived_accounts_saves_recovered_accounts() -> Result<(), String> {
+        let path = std::env::temp_dir().join(format!("nyks-scan-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pool = crate::database::connection::init_pool(Some(path.to_str().unwrap().into()))?;
+        let mut conn = crate::database::connection::get_conn(&pool)?;
+        crate::database::connection::run_migrations(&mut conn)?;
+        drop(conn);
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let wallet_id = format!("scan-{}", uuid::Uuid::new_v4());
+        let password = SecretString::new("scan_password".into());
+        order_wallet.attach_database(password, wallet_id, pool)?;
+        let coin = ZkAccount::from_seed(2, &order_wallet.seed.secret()?, 900)?;
+        let fetcher = OutputsFetcher {
+            outputs: vec![OutputsFetcher::output(&coin, IOType::Coin)],
+            panicking: None,
+        };
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+
+        order_wallet.scan_derived_accounts(2).await?;
+        let saved = order_wallet
+            .get_db_manager()
+            .unwrap()
+            .load_all_zk_accounts()?;
+        assert_eq!(saved[&2].balance, 900);
+        assert_eq!(saved[&2].account, coin.account);
+        assert!(saved[&2].on_chain);
+        drop(order_wallet);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scan_minted_accounts_recovers_randomized_addresses() -> Result<(), String> {
+        use crate::compat::quisquislib::{
+            keys::PublicKey, ristretto::RistrettoPublicKey, Account, ElGamalCommitment,
+        };
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+        use curve25519_dalek::scalar::Scalar;
+        use rand::rngs::OsRng;
+
+        let chain = MockChain::spawn();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let key_manager = order_wallet
+            .seed
+            .with_seed(|seed| KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes()))?;
+        // Accounts used to take a fresh random public key for the index's key.
+        let legacy = |secret_key, balance: u64| -> Result<ZkAccount, String> {
+            let pk = RistrettoPublicKey::from_secret_key(&secret_key, &mut OsRng);
+            let scalar = Scalar::random(&mut OsRng);
+            let commitment =
+                ElGamalCommitment::generate_commitment(&pk, scalar, Scalar::from(balance));
+            let qq_account = EncryptedAccount::from(Account::set_account(pk, commitment));
+            Ok(ZkAccount::new(
+                qq_account.to_hex_str().map_err(|e| e.to_string())?,
+                balance,
+                qq_account.get_address(),
+                hex::encode(scalar.to_bytes()),
+                0,
+            ))
+        };
+        let owned = legacy(key_manager.derive_child_key(1), 800)?;
+        let other_wallet = KeyManager::from_cosmos_signature(b"other");
+        let foreign = legacy(other_wallet.derive_child_key(1), 5)?;
+        let mint = |account: &ZkAccount| {
+            serde_json::json!({
+                "@type": "/twilightproject.nyks.zkos.MsgMintBurnTradingBtc",
+                "mint_or_burn": true,
+                "btc_value": account.balance.to_string(),
+                "qq_account": account.qq_address,
+                "encrypt_scalar": account.scalar,
+                "twilight_address": order_wallet.wallet.twilightaddress,
+            })
+        };
+        let page = serde_json::json!({
+            "txs": [
+                {"body": {"messages": [mint(&foreign)]}},
+                {"body": {"messages": [mint(&owned)]}},
+            ],
+            "pagination": {"next_key": null, "total": "2"},
+        });
+        chain.route(
+            "/cosmos/tx/v1beta1/txs?",
+            vec![MockResponse::ok(page.to_string())],
+        );
+        let fetcher = OutputsFetcher {
+            outputs: vec![OutputsFetcher::output(&owned, IOType::Coin)],
+            panicking: None,
+        };
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+
+        // The derived addresses were never used.
+        assert!(order_wallet.scan_derived_accounts(2).await?.is_empty());
+        let recovered = order_wallet.scan_minted_accounts(2).await?;
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].account_index, 1);
+        assert_eq!(recovered[0].address, owned.account);
+        assert_eq!(recovered[0].balance, Some(800));
+        let account = order_wallet.zk_accounts.get_account(&1)?;
+        assert_eq!((account.balance, account.scalar), (800, owned.scalar));
+        assert_eq!(chain.count("/cosmos/tx/v1beta1/txs?"), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_committed_amount_replaces_requested_balance() -> Result<(), String> {
+        use crate::compat::{
+            address::AddressType,
+            quisquislib::{ristretto::RistrettoPublicKey, Account, ElGamalCommitment},
+            zkvm::Address,
+        };
+        use curve25519_dalek::scalar::Scalar;
+        use rand::rngs::OsRng;
+
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_utxo_fetcher(Arc::new(CountingFetcher::default()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(10_000, &order_wallet.seed.secret()?)?;
+
+        // The chain output encodes 10 sats less than was requested.
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let pk: RistrettoPublicKey = Address::from_hex(&address, AddressType::Standard)
+            .map_err(|e| e.to_string())?
+            .into();
+        let commitment = ElGamalCommitment::generate_commitment(
+            &pk,
+            Scalar::random(&mut OsRng),
+            Scalar::from(9_990u64),
+        );
+        order_wallet
+            .zk_accounts
+            .update_qq_account(&index, Account::set_account(pk, commitment))?;
+
+        let balance = order_wallet.reconcile_committed_balance(
+            index,
+            10_000,
+            "funding_to_trading",
+            Some("MINT"),
+        )?;
+        assert_eq!(balance, 9_990);
+        let account = order_wallet.zk_accounts.get_account(&index)?;
+        assert_eq!(account.balance, 9_990);
+        assert!(!account.balance_unverified);
+        let discrepancies = order_wallet.amount_discrepancies();
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].account_index, index);
+        assert_eq!(discrepancies[0].operation, "funding_to_trading");
+        assert_eq!(discrepancies[0].actual, 9_990);
+        assert_eq!(discrepancies[0].fee(), 10);
+        assert_eq!(discrepancies[0].tx_hash.as_deref(), Some("MINT"));
+
+        // A matching output records nothing.
+        order_wallet.reconcile_committed_balance(index, 9_990, "trading_to_funding", None)?;
+        assert_eq!(order_wallet.amount_discrepancies().len(), 1);
+
+        // Without the output the requested amount is kept, flagged for verification.
+        let unfetched = order_wallet
+            .zk_accounts
+            .generate_new_account(5_000, &order_wallet.seed.secret()?)?;
+        let balance = order_wallet
+            .sync_committed_balance(unfetched, 5_000, "funding_to_trading", None)
+            .await?;
+        assert_eq!(balance, 5_000);
+        assert!(
+            order_wallet
+                .zk_accounts
+                .get_account(&unfetched)?
+                .balance_unverified
+        );
+        assert_eq!(order_wallet.amount_discrepancies().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_activity_histogram_and_diagnostics() -> Result<(), String> {
+        use crate::relayer_module::clock::ManualClock;
+        let start: DateTime<Utc> = "2025-03-01T09:15:00Z".parse().unwrap();
+        let clock = ManualClock::new(start);
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_clock(Arc::new(clock.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+
+        // Rejected locally: the account was never funded.
+        let _ = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 10)
+            .await;
+        clock.advance(Duration::from_secs(3600));
+        order_wallet.record_account_outcome(index, "open_trader_order", &Ok::<(), String>(()));
+        order_wallet.record_account_outcome(index, "trading_to_funding", &Ok::<(), String>(()));
+
+        let histogram = order_wallet.activity_histogram(
+            start - chrono::Duration::days(1)..start + chrono::Duration::days(1),
+        );
+        assert_eq!(histogram.buckets.len(), 2);
+        assert_eq!(
+            histogram.buckets[0].hour,
+            "2025-03-01T09:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(histogram.buckets[0].counts[&ActivityCategory::Errors], 1);
+        assert_eq!(
+            histogram.buckets[1].counts[&ActivityCategory::OrdersOpened],
+            1
+        );
+        assert_eq!(histogram.totals[&ActivityCategory::Transfers], 1);
+
+        let snapshot = order_wallet.diagnostic_snapshot();
+        assert_eq!(snapshot.generated_at, start + chrono::Duration::hours(1));
+        assert_eq!(snapshot.accounts, 1);
+        assert_eq!(snapshot.accounts_with_errors, 0);
+        assert_eq!(snapshot.activity.buckets.len(), 2);
+        let json = serde_json::to_value(&snapshot).map_err(|e| e.to_string())?;
+        assert_eq!(json["activity"]["totals"]["orders_opened"], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mint_burn_shares_the_wallet_sequence() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        assert!(Arc::ptr_eq(
+            &order_wallet.nonce_manager,
+            &order_wallet.wallet.nonce_manager
+        ));
+
+        let stale = mint_burn_rejected(&TxResult {
+            tx_hash: "ABC".to_string(),
+            code: 32,
+            simulated: false,
+            attempts: Vec::new(),
+            log: None,
+        });
+        assert!(matches!(
+            &stale,
+            OrderWalletError::SequenceMismatch { tx_hash, code: 32 } if tx_hash == "ABC"
+        ));
+        assert!(stale.is_retryable());
+        let rejected = mint_burn_rejected(&TxResult {
+            tx_hash: "DEF".to_string(),
+            code: 5,
+            simulated: false,
+            attempts: Vec::new(),
+            log: Some("insufficient funds".to_string()),
+        });
+        assert_eq!(
+            rejected.to_string(),
+            "chain rejected tx DEF with code 5: insufficient funds"
+        );
+        assert!(!rejected.is_retryable());
+        // A signature that does not verify is not re-signed.
+        let unauthorized = mint_burn_rejected(&TxResult {
+            tx_hash: "GHI".to_string(),
+            code: 4,
+            simulated: false,
+            attempts: Vec::new(),
+            log: Some("signature verification failed".to_string()),
+        });
+        assert!(matches!(
+            unauthorized,
+            OrderWalletError::ChainTx { code: 4, .. }
+        ));
+        assert!(!unauthorized.is_retryable());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mint_resigns_at_the_sequence_the_chain_expects() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+
+        let chain = MockChain::spawn();
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        order_wallet.wallet.chain_config.rpc_endpoint = chain.rpc_url();
+        // Sequences 3 to 8 went to transactions that never landed: the local
+        // counter is ahead of the chain's 3.
+        order_wallet.wallet.nonce_manager = Arc::new(NonceManager::with_initial(9, 7));
+        order_wallet.nonce_manager = order_wallet.wallet.nonce_manager.clone();
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+
+        let mismatch = MockResponse::ok(
+            r#"{"jsonrpc":"2.0","id":"1","result":{"code":32,"data":"","log":"account sequence mismatch, expected 3, got 9: incorrect account sequence","codespace":"sdk","hash":"AAAA"}}"#,
+        );
+        let accepted = MockResponse::ok(
+            r#"{"jsonrpc":"2.0","id":"1","result":{"code":0,"data":"","log":"[]","codespace":"","hash":"BBBB"}}"#,
+        );
+        chain.route("/rpc", vec![mismatch, accepted]);
+        chain.route(
+            "/cosmos/tx/v1beta1/txs/BBBB",
+            vec![MockResponse::ok(
+                r#"{"tx_response":{"code":0,"raw_log":"","txhash":"BBBB"}}"#,
+            )],
+        );
+
+        let minted = order_wallet
+            .send_and_confirm_mint_burn(index, 1_000, true)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(minted.tx_hash, "BBBB");
+        assert_eq!(chain.count("/rpc"), 2);
+        // Re-signed at 3, not at the rejected 9 a max with the chain keeps.
+        assert_eq!(order_wallet.nonce_manager.acquire_next()?, (4, 7));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fee_bumped_mint_reports_every_attempt() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse, NOT_FOUND};
+
+        let chain = MockChain::spawn();
+        let policy = FeeBumpPolicy {
+            confirm_polls: 2,
+            poll_interval: Duration::ZERO,
+            ..FeeBumpPolicy::default()
+        };
+        let mut order_wallet =
+            OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?.with_fee_bump(policy);
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        order_wallet.wallet.chain_config.rpc_endpoint = chain.rpc_url();
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+
+        let account = serde_json::json!({
+            "account": {
+                "@type": "/cosmos.auth.v1beta1.BaseAccount",
+                "address": order_wallet.wallet.twilightaddress,
+                "pub_key": null,
+                "account_number": "7",
+                "sequence": "3",
+            }
+        });
+        let broadcast = |hash: &str| {
+            MockResponse::ok(format!(
+                r#"{{"jsonrpc":"2.0","id":"1","result":{{"code":0,"data":"","log":"[]","codespace":"","hash":"{}"}}}}"#,
+                hash
+            ))
+        };
+        let committed = r#"{"tx_response":{"code":0,"raw_log":"","txhash":"BBBB"}}"#;
+        chain.route(
+            "/cosmos/auth/v1beta1/accounts/",
+            vec![MockResponse::ok(account.to_string())],
+        );
+        chain.route("/rpc", vec![broadcast("AAAA"), broadcast("BBBB")]);
+        // AAAA never shows up; the bumped BBBB confirms on its second poll.
+        chain.route(
+            "/cosmos/tx/v1beta1/txs/BBBB",
+            vec![MockResponse::ok(NOT_FOUND), MockResponse::ok(committed)],
+        );
+
+        let result = order_wallet
+            .send_and_confirm_mint_burn(index, 1_000, true)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(result.tx_hash, "BBBB");
+        let attempts: Vec<(&str, u64)> = result
+            .attempts
+            .iter()
+            .map(|attempt| (attempt.tx_hash.as_str(), attempt.fee_nyks))
+            .collect();
+        assert_eq!(attempts, [("AAAA", 1_000), ("BBBB", 1_500)]);
+        assert_eq!(chain.count("/rpc"), 2);
+        // Both attempts were signed with the one sequence taken.
+        assert_eq!(order_wallet.nonce_manager.acquire_next()?, (4, 7));
+        Ok(())
+    }
+
+    #[cfg(feature = "validator-wallet")]
+    #[tokio::test]
+    async fn test_delegate_then_mint_take_consecutive_sequences() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+
+        let chain = MockChain::spawn();
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        order_wallet.wallet.chain_config.rpc_endpoint = chain.rpc_url();
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+
+        // The LCD has not seen either transaction: it stays at sequence 3.
+        let account = serde_json::json!({
+            "account": {
+                "@type": "/cosmos.auth.v1beta1.BaseAccount",
+                "address": order_wallet.wallet.twilightaddress,
+                "pub_key": null,
+                "account_number": "7",
+                "sequence": "3",
+            }
+        });
+        let broadcast = |hash: &str| {
+            MockResponse::ok(format!(
+                r#"{{"jsonrpc":"2.0","id":"1","result":{{"code":0,"data":"","log":"[]","codespace":"","hash":"{}"}}}}"#,
+                hash
+            ))
+        };
+        chain.route(
+            "/cosmos/auth/v1beta1/accounts/",
+            vec![MockResponse::ok(account.to_string())],
+        );
+        chain.route("/rpc", vec![broadcast("AAAA"), broadcast("BBBB")]);
+        chain.route(
+            "/cosmos/tx/v1beta1/txs/BBBB",
+            vec![MockResponse::ok(
+                r#"{"tx_response":{"code":0,"raw_log":"","txhash":"BBBB"}}"#,
+            )],
+        );
+
+        let validator = cosmrs::AccountId::new("twilightvaloper", &[1; 20])
+            .unwrap()
+            .to_string();
+        let delegated = order_wallet
+            .wallet
+            .delegate(&validator, 10_000)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(delegated.hash, "AAAA");
+        assert_eq!(order_wallet.nonce_manager.peek_next(), 4);
+
+        let minted = order_wallet
+            .send_and_confirm_mint_burn(index, 1_000, true)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(minted.tx_hash, "BBBB");
+        // The mint took 4 although the LCD still reports 3.
+        assert_eq!(order_wallet.nonce_manager.acquire_next()?, (5, 7));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partial_close_rejects_fraction_out_of_range() -> Result<(), String> {
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        for fraction in [0.0, -0.25, 1.5, f64::NAN] {
+            let err = order_wallet
+                .close_trader_order_partial(0, fraction, OrderType::MARKET, 50_000.0)
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string()
+                    .starts_with("Close fraction must be in (0, 1]"),
+                "{}",
+                err
+            );
+        }
+        Ok(())
+    }
+
+    /// A wallet on `relayer` with a 2_000 sat account holding a filled 5x
+    /// long opened as `REQ-OPEN`, and the order as the relayer reports it.
+    async fn partially_closable(
+        relayer: &crate::relayer_module::mock_relayer::MockRelayer,
+    ) -> Result<(OrderWallet, AccountIndex, serde_json::Value), String> {
+        use crate::relayer_module::test_fixtures::{
+            coin_utxo, output_hex, TraderOrderBuilder, TxHashBuilder,
+        };
+
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(2_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&index, true)?;
+        let account = order_wallet.zk_accounts.get_account(&index)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let utxo = coin_utxo(&account);
+        let order_wallet =
+            order_wallet.with_utxo_fetcher(Arc::new(ScriptedFetcher::new(vec![Ok(utxo)])));
+        let params =
+            TraderOrderParams::new(index, OrderType::MARKET, PositionType::LONG, 50_000, 5);
+        order_wallet.record_trader_order_open(&params, "REQ-OPEN", None)?;
+
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .account_id(address.as_str())
+                .request_id("REQ-OPEN")
+                .order_status(OrderStatus::FILLED)
+                .output(output_hex(&account))
+                .to_json()],
+        );
+        let order = TraderOrderBuilder::new()
+            .account_id(address.as_str())
+            .order_status(OrderStatus::FILLED)
+            .position_type(PositionType::LONG)
+            .position(2_000.0, 5.0, 50_000.0)
+            .to_json();
+        Ok((order_wallet, index, order))
+    }
+
+    fn partial_close_relayer() -> crate::relayer_module::mock_relayer::MockRelayer {
+        crate::relayer_module::mock_relayer::MockRelayer::new().with_capabilities(
+            RelayerCapabilities {
+                supports_partial_close: true,
+                ..RelayerCapabilities::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_partial_close_resyncs_balance_from_relayer() -> Result<(), String> {
+        let relayer = partial_close_relayer();
+        let (mut order_wallet, index, order) = partially_closable(&relayer).await?;
+        let mut remaining = order.clone();
+        remaining["initial_margin"] = serde_json::json!(1_000.0);
+        relayer.respond_once("trader_order_info", order);
+        relayer.respond_once("trader_order_info", remaining);
+        relayer.accept("settle_trade_order_partial", "REQ-PART");
+
+        let request_id = order_wallet
+            .close_trader_order_partial(index, 0.5, OrderType::MARKET, 0.0)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(request_id, "REQ-PART");
+        assert_eq!(relayer.call_count("settle_trade_order_partial"), 1);
+        assert_eq!(relayer.call_count("settle_trade_order"), 0);
+        // Still open on the rest of the position, at the margin the relayer reports.
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, 1_000);
+        let last = order_wallet.order_history(index).pop().unwrap();
+        assert_eq!(last.kind, OrderRecordKind::TraderPartialClose);
+        assert_eq!(last.request_id, "REQ-PART");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partial_close_below_relayer_minimum_is_not_sent() -> Result<(), String> {
+        let relayer = partial_close_relayer();
+        let mut stats = relayer
+            .get_market_stats()
+            .await
+            .map_err(|e| e.to_string())?;
+        stats.params.min_position_btc = 2_000.0;
+        relayer.respond("get_market_stats", stats);
+        let (mut order_wallet, index, order) = partially_closable(&relayer).await?;
+        relayer.respond("trader_order_info", order);
+
+        // A tenth of 2_000 sats at 5x is a 1_000 sat position.
+        let err = order_wallet
+            .close_trader_order_partial(index, 0.1, OrderType::MARKET, 0.0)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Partial close settles a position value of 1000 sats, below relayer minimum 2000 sats"
+        );
+        assert_eq!(relayer.call_count("settle_trade_order_partial"), 0);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, 2_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partial_close_needs_the_capability() -> Result<(), String> {
+        let relayer = crate::relayer_module::mock_relayer::MockRelayer::new();
+        let (mut order_wallet, index, order) = partially_closable(&relayer).await?;
+        relayer.respond("trader_order_info", order);
+
+        let err = order_wallet
+            .close_trader_order_partial(index, 0.5, OrderType::MARKET, 0.0)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Relayer (version unknown) does not support partial_close"
+        );
+        assert!(relayer.calls().is_empty());
+        assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, 2_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stop_loss_retries_a_failed_close_while_crossed() -> Result<(), String> {
+        use crate::relayer_module::clock::ManualClock;
+        use crate::relayer_module::conditional_orders::{
+            ConditionalOrderManager, TriggerEvent, TriggerState,
+        };
+        use crate::relayer_module::mock_relayer::MockRelayer;
+
+        let relayer = MockRelayer::new();
+        let (order_wallet, index, order) = partially_closable(&relayer).await?;
+        let clock = ManualClock::new(DateTime::<Utc>::UNIX_EPOCH);
+        let order_wallet = order_wallet.with_clock(Arc::new(clock.clone()));
+        relayer.respond("trader_order_info", order);
+        let manager =
+            ConditionalOrderManager::new(Arc::new(tokio::sync::Mutex::new(order_wallet))).await?;
+        manager
+            .add_stop_loss(index, 48_000.0, OrderType::MARKET)
+            .await?;
+
+        relayer.fail_once("trader_order_info", "relayer down");
+        let retry_at = match &manager.on_price(47_500.0).await[..] {
+            [TriggerEvent::Failed {
+                retry_at: Some(retry_at),
+                ..
+            }] => *retry_at,
+            events => return Err(format!("unexpected events: {:?}", events)),
+        };
+        assert!(matches!(
+            manager.triggers()[0].state,
+            TriggerState::Retrying { failures: 1, .. }
+        ));
+        // Still crossed, but inside the backoff: nothing is sent.
+        relayer.clear_calls();
+        assert!(manager.on_price(47_000.0).await.is_empty());
+        assert!(relayer.calls().is_empty());
+
+        clock.set(retry_at);
+        relayer.accept("settle_trade_order", "REQ-CLOSE");
+        let fired = manager.on_price(47_000.0).await;
+        assert!(matches!(
+            &fired[..],
+            [TriggerEvent::Fired { request_id, .. }] if request_id == "REQ-CLOSE"
+        ));
+        assert_eq!(relayer.call_count("settle_trade_order"), 1);
+        assert!(manager.triggers().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_trigger_is_settled_against_the_order() -> Result<(), String> {
+        use crate::relayer_module::conditional_orders::{
+            ConditionalOrderManager, ConditionalTrigger, TriggerKind, TriggerState,
+        };
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+
+        let relayer = MockRelayer::new();
+        let (order_wallet, index, order) = partially_closable(&relayer).await?;
+        let wallet = Arc::new(tokio::sync::Mutex::new(order_wallet));
+        let trigger = |kind, price| {
+            ConditionalTrigger::new(
+                index,
+                kind,
+                price,
+                PositionType::LONG,
+                OrderType::MARKET,
+                DateTime::<Utc>::UNIX_EPOCH,
+            )
+        };
+        let interrupted = || {
+            let mut stop_loss = trigger(TriggerKind::StopLoss, 48_000.0);
+            stop_loss.state = TriggerState::Firing { failures: 0 };
+            vec![stop_loss, trigger(TriggerKind::TakeProfit, 55_000.0)]
+        };
+
+        // Still filled: the close did not go through and is retried.
+        relayer.respond_once("trader_order_info", order);
+        let manager = ConditionalOrderManager::from_triggers(wallet.clone(), interrupted()).await;
+        let triggers = manager.triggers();
+        assert!(matches!(
+            triggers[0].state,
+            TriggerState::Retrying { failures: 1, .. }
+        ));
+        assert_eq!(triggers[1].state, TriggerState::Armed);
+
+        // Settled: the close went through before the restart.
+        let settled = TraderOrderBuilder::new()
+            .order_status(OrderStatus::SETTLED)
+            .to_json();
+        relayer.respond_once("trader_order_info", settled);
+        let manager = ConditionalOrderManager::from_triggers(wallet, interrupted()).await;
+        assert!(manager.triggers().is_empty());
+        assert_eq!(relayer.call_count("settle_trade_order"), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watcher_syncs_fill_then_settles() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{coin_utxo, TraderOrderBuilder};
+
+        let relayer = MockRelayer::new();
+        let (order_wallet, index, order) = partially_closable(&relayer).await?;
+        let utxo = coin_utxo(&order_wallet.zk_accounts.get_account(&index)?);
+        let fetcher = ScriptedFetcher::new(vec![Ok(utxo.clone()), Ok(utxo)]);
+        let order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let watcher = OrderWatcher::new(Arc::new(order_wallet));
+        let mut events = watcher.subscribe();
+
+        // First fill: the Memo UTXO is fetched, once.
+        relayer.respond_once("trader_order_info", order.clone());
+        let transitions = watcher.poll_once().await;
+        assert_eq!(transitions.len(), 1);
+        let filled = &transitions[0];
+        assert_eq!(filled.account_index, index);
+        assert_eq!(filled.order, OrderKind::Trader);
+        assert_eq!(filled.request_id, "REQ-OPEN");
+        assert_eq!(filled.from, None);
+        assert_eq!(filled.to.as_deref(), Some("FILLED"));
+        assert_eq!(filled.reconciled, Some(Reconcile::SyncMemo));
+        assert_eq!(filled.error, None);
+        assert_eq!(events.try_recv().ok().as_ref(), Some(filled));
+        assert!(watcher.wallet().utxo_details.contains_key(&index));
+        relayer.respond_once("trader_order_info", order);
+        assert!(watcher.poll_once().await.is_empty());
+
+        // Settled: the account is back on its Coin UTXO with the settled margin.
+        let settled = TraderOrderBuilder::new()
+            .account_id(address.as_str())
+            .order_status(OrderStatus::SETTLED)
+            .position(2_000.0, 5.0, 50_000.0)
+            .field("available_margin", 2_300.0)
+            .to_json();
+        // Queried by the poll and again by the unlock.
+        relayer.respond("trader_order_info", settled);
+        let transitions = watcher.poll_once().await;
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(
+            (transitions[0].from.as_deref(), transitions[0].to.as_deref()),
+            (Some("FILLED"), Some("SETTLED"))
+        );
+        assert_eq!(transitions[0].reconciled, Some(Reconcile::Settle));
+        assert_eq!(transitions[0].error, None);
+        let account = watcher.wallet().zk_accounts.get_account(&index)?;
+        assert_eq!((account.io_type, account.balance), (IOType::Coin, 2_300));
+        assert!(watcher.wallet().memo_accounts().is_empty());
+        assert!(watcher.poll_once().await.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watcher_restores_cancelled_and_ignores_unknown_status() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+
+        let relayer = MockRelayer::new();
+        let (order_wallet, index, order) = partially_closable(&relayer).await?;
+        let watcher = OrderWatcher::new(Arc::new(order_wallet));
+
+        // A status that is not a trader order's is reported, not acted on.
+        let mut lended = order.clone();
+        lended["order_status"] = serde_json::json!("LENDED");
+        relayer.respond_once("trader_order_info", lended);
+        let transitions = watcher.poll_once().await;
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].to.as_deref(), Some("LENDED"));
+        assert_eq!(transitions[0].reconciled, None);
+        assert_eq!(
+            watcher.wallet().zk_accounts.get_account(&index)?.io_type,
+            IOType::Memo
+        );
+
+        let mut cancelled = order;
+        cancelled["order_status"] = serde_json::json!("CANCELLED");
+        relayer.respond_once("trader_order_info", cancelled);
+        let transitions = watcher.poll_once().await;
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(
+            (transitions[0].from.as_deref(), transitions[0].to.as_deref()),
+            (Some("LENDED"), Some("CANCELLED"))
+        );
+        assert_eq!(transitions[0].reconciled, Some(Reconcile::RestoreCancelled));
+        let account = watcher.wallet().zk_accounts.get_account(&index)?;
+        assert_eq!((account.io_type, account.balance), (IOType::Coin, 2_000));
+        assert!(watcher.wallet().memo_accounts().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_order_state_transitions_against_mock_relayer() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{TraderOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(2_000, &order_wallet.seed.secret()?)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let submitted_at = order_wallet.clock.now().timestamp_millis().to_string();
+        let tx = |request_id: &str, status: OrderStatus| {
+            TxHashBuilder::new()
+                .account_id(address.as_str())
+                .request_id(request_id)
+                .order_status(status)
+                .field("datetime", submitted_at.as_str())
+                .to_json()
+        };
+
+        // Open: the submit times out after the relayer accepted the order.
+        let submission = order_wallet.begin_submission(index, OrderKind::Trader, &address);
+        relayer.respond_once("transaction_hashes", vec![tx("REQ1", OrderStatus::PENDING)]);
+        let request_id = order_wallet
+            .finish_submission(&submission, Err(RpcError::RequestTimeout))
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(request_id, "REQ1");
+        assert!(order_wallet.pending_submissions().is_empty());
+        let params =
+            TraderOrderParams::new(index, OrderType::LIMIT, PositionType::SHORT, 52_000, 5);
+        order_wallet.record_trader_order_open(&params, &request_id, None)?;
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        assert_eq!(order_wallet.request_id(index)?, "REQ1");
+
+        // Close: the limit has not filled, so nothing is settled.
+        relayer.respond(
+            "trader_order_info",
+            TraderOrderBuilder::new()
+                .account_id(address.as_str())
+                .order_type(OrderType::LIMIT)
+                .order_status(OrderStatus::PENDING)
+                .to_json(),
+        );
+        let err = order_wallet
+            .close_trader_order(index, OrderType::MARKET, 0.0)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Order is not filled, status: PENDING");
+        assert_eq!(relayer.call_count("settle_trade_order"), 0);
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+
+        // Cancel: the relayer cancels the limit and the account is a coin again.
+        relayer.clear_calls();
+        relayer.accept("cancel_trader_order", "REQ2");
+        relayer.respond_once(
+            "transaction_hashes",
+            vec![tx("REQ2", OrderStatus::CANCELLED)],
+        );
+        let cancel_id = order_wallet
+            .cancel_trader_order(index)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(cancel_id, "REQ2");
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, 2_000);
+        assert_eq!(
+            relayer.calls(),
+            vec![
+                "get_market_stats",
+                "trader_order_info",
+                "cancel_trader_order",
+                "transaction_hashes",
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_market_close_settles_against_mock_relayer() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{coin_utxo, TraderOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        let (order_wallet, index, order) = partially_closable(&relayer).await?;
+        let utxo = coin_utxo(&order_wallet.zk_accounts.get_account(&index)?);
+        let fetcher = ScriptedFetcher::new(vec![Ok(utxo.clone()), Ok(utxo)]);
+        let order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        assert_eq!(order_wallet.request_id(index)?, "REQ-OPEN");
+
+        // Close: the filled order is settled at market.
+        relayer.respond_once("trader_order_info", order);
+        relayer.accept("settle_trade_order", "REQ-CLOSE");
+        let close_id = order_wallet
+            .close_trader_order(index, OrderType::MARKET, 0.0)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(close_id, "REQ-CLOSE");
+        assert_eq!(relayer.call_count("settle_trade_order"), 1);
+        // Until the relayer settles it, the account holds the open order.
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, 2_000);
+        assert_eq!(order_wallet.request_id(index)?, "REQ-OPEN");
+
+        // Settled: the account is a coin again, holding the settled margin.
+        relayer.respond(
+            "trader_order_info",
+            TraderOrderBuilder::new()
+                .account_id(address.as_str())
+                .order_status(OrderStatus::SETTLED)
+                .position(2_000.0, 5.0, 50_000.0)
+                .field("available_margin", 2_250.0)
+                .to_json(),
+        );
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .account_id(address.as_str())
+                .request_id("REQ-CLOSE")
+                .order_status(OrderStatus::SETTLED)
+                .to_json()],
+        );
+        let (status, settled_id) = order_wallet.unlock_trader_order(index).await?;
+        assert_eq!(status, OrderStatus::SETTLED);
+        assert_eq!(settled_id, "REQ-CLOSE");
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, 2_250);
+        assert!(order_wallet.memo_accounts().is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_close_snapshot_is_settled_under_the_close_request() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{coin_utxo, TraderOrderBuilder, TxHashBuilder};
+
+        let path = std::env::temp_dir().join(format!("nyks-snapshots-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pool = crate::database::connection::init_pool(Some(path.to_str().unwrap().into()))?;
+        let mut conn = crate::database::connection::get_conn(&pool)?;
+        crate::database::connection::run_migrations(&mut conn)?;
+        drop(conn);
+        let relayer = MockRelayer::new();
+        let (order_wallet, index, order) = partially_closable(&relayer).await?;
+        let utxo = coin_utxo(&order_wallet.zk_accounts.get_account(&index)?);
+        let fetcher = ScriptedFetcher::new(vec![Ok(utxo.clone()), Ok(utxo)]);
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
+        let wallet_id = format!("snapshots-{}", uuid::Uuid::new_v4());
+        let password = SecretString::new("snapshot_password".into());
+        order_wallet.attach_database(password, wallet_id.clone(), pool)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+
+        relayer.respond_once("trader_order_info", order);
+        relayer.accept("settle_trade_order", "REQ-CLOSE");
+        order_wallet
+            .close_trader_order(index, OrderType::MARKET, 0.0)
+            .await
+            .map_err(|e| e.to_string())?;
+        let db_manager = order_wallet.get_db_manager().unwrap();
+        let submitted = db_manager.load_order_snapshots(&wallet_id)?;
+        assert_eq!(submitted.len(), 1);
+        assert_eq!(submitted[0].request_id, "REQ-CLOSE");
+        assert_eq!(submitted[0].kind, OrderRecordKind::TraderClose);
+        assert_eq!(submitted[0].settled_margin, None);
+
+        // The settlement record carries no request ID: the snapshot of the
+        // close is updated rather than a second one stored under "".
+        relayer.respond(
+            "trader_order_info",
+            TraderOrderBuilder::new()
+                .account_id(address.as_str())
+                .order_status(OrderStatus::SETTLED)
+                .position(2_000.0, 5.0, 50_000.0)
+                .field("available_margin", 2_250.0)
+                .to_json(),
+        );
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .account_id(address.as_str())
+                .order_status(OrderStatus::SETTLED)
+                .field("request_id", serde_json::Value::Null)
+                .to_json()],
+        );
+        let (_, settled_id) = order_wallet.unlock_trader_order(index).await?;
+        assert_eq!(settled_id, "REQ-CLOSE");
+        let settled = db_manager.load_order_snapshots(&wallet_id)?;
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].request_id, "REQ-CLOSE");
+        assert_eq!(settled[0].order_status, "SETTLED");
+        assert_eq!(settled[0].settled_margin, Some(2_250.0));
+
+        let history = order_wallet.trade_history()?;
+        let close = history
+            .iter()
+            .find(|entry| entry.record.kind == OrderRecordKind::TraderClose)
+            .unwrap();
+        assert_eq!(close.record.request_id, "REQ-CLOSE");
+        assert_eq!(close.snapshot.as_ref(), Some(&settled[0]));
+        assert!(!history.iter().any(|entry| entry.record.request_id.is_empty()));
+        drop(order_wallet);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_chain_tx_serializer_orders_funding() -> Result<(), String> {
+        use crate::relayer_module::chain_tx::{SequenceFuture, SequenceSource};
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Mutex;
+
+        #[derive(Debug, Default)]
+        struct Chain {
+            sequence: AtomicU64,
+            sent: Mutex<Vec<(&'static str, u64)>>,
+        }
+        #[derive(Debug)]
+        struct Source(Arc<Chain>);
+        impl SequenceSource for Source {
+            fn fetch(&self, _address: &str) -> SequenceFuture {
+                let sequence = self.0.sequence.load(Ordering::SeqCst);
+                Box::pin(async move { Ok((sequence, 1)) })
+            }
+        }
+
+        let chain = Arc::new(Chain::default());
+        chain.sequence.store(40, Ordering::SeqCst);
+        let registry = ChainTxRegistry::default().with_source(Arc::new(Source(chain.clone())));
+        let first = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_chain_tx_registry(&registry);
+        let second = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_chain_tx_registry(&registry);
+        let a = first.chain_tx_serializer().unwrap();
+        let b = second.chain_tx_serializer().unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let fund = |serializer: Arc<ChainTxSerializer>, caller: &'static str| {
+            let chain = chain.clone();
+            async move {
+                serializer
+                    .submit(|sequence, _| {
+                        let chain = chain.clone();
+                        async move {
+                            sleep(Duration::from_millis(5)).await;
+                            // Accept only the sequence the chain expects.
+                            let expected = chain.sequence.load(Ordering::SeqCst);
+                            if sequence != expected {
+                                return Ok(TxResult {
+                                    tx_hash: String::new(),
+                                    code: 32,
+                                    simulated: false,
+                                    attempts: Vec::new(),
+                                    log: None,
+                                });
+                            }
+                            chain.sequence.store(expected + 1, Ordering::SeqCst);
+                            chain.sent.lock().unwrap().push((caller, sequence));
+                            Ok(TxResult {
+                                tx_hash: format!("tx-{}", sequence),
+                                code: 0,
+                                simulated: false,
+                                attempts: Vec::new(),
+                                log: None,
+                            })
+                        }
+                    })
+                    .await
+            }
+        };
+        let results = tokio::join!(
+            fund(a.clone(), "first-1"),
+            fund(b.clone(), "second-1"),
+            fund(a.clone(), "first-2"),
+            fund(b.clone(), "second-2"),
+            fund(a.clone(), "first-3"),
+        );
+        for result in [results.0, results.1, results.2, results.3, results.4] {
+            assert_eq!(result?.code, 0);
+        }
+
+        assert_eq!(
+            *chain.sent.lock().unwrap(),
+            vec![
+                ("first-1", 40),
+                ("second-1", 41),
+                ("first-2", 42),
+                ("second-2", 43),
+                ("first-3", 44),
+            ]
+        );
+        let stats = a.stats();
+        assert_eq!(stats.accepted, 5);
+        assert_eq!(stats.refreshes, 1);
+        assert_eq!(stats.mismatch_retries, 0);
+        // Both wallets' other transactions count on the serializer's sequence.
+        assert!(Arc::ptr_eq(&first.nonce_manager, &a.nonce_manager()));
+        assert!(Arc::ptr_eq(&second.nonce_manager, &a.nonce_manager()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wallet_sends_and_serialized_submits_share_the_sequence() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+        use std::sync::Mutex;
+
+        let chain = MockChain::spawn();
+        let account = serde_json::json!({"account": {
+            "@type": "/cosmos.auth.v1beta1.BaseAccount",
+            "address": "",
+            "pub_key": null,
+            "account_number": "7",
+            "sequence": "20",
+        }});
+        chain.route(
+            "/cosmos/auth/v1beta1/accounts/",
+            vec![MockResponse::ok(account.to_string())],
+        );
+        chain.route(
+            "/rpc",
+            vec![MockResponse::ok(
+                r#"{"jsonrpc":"2.0","id":"1","result":{"code":0,"data":"","log":"[]","codespace":"","hash":"AAAA"}}"#,
+            )],
+        );
+        let on_chain = |mut order_wallet: OrderWallet| {
+            order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+            order_wallet.wallet.chain_config.rpc_endpoint = chain.rpc_url();
+            order_wallet
+        };
+        let registry = ChainTxRegistry::default();
+        let funding = on_chain(OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?)
+            .with_chain_tx_registry(&registry);
+        let staking = on_chain(OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?)
+            .with_chain_tx_registry(&registry);
+        let serializer = funding.chain_tx_serializer().unwrap();
+
+        let signed = Mutex::new(Vec::new());
+        let sign = |caller: &'static str, sequence: u64, account_number: u64| {
+            signed
+                .lock()
+                .unwrap()
+                .push((caller, sequence, account_number));
+        };
+        let submit = |caller: &'static str| {
+            serializer.submit(move |sequence, account_number| {
+                sign(caller, sequence, account_number);
+                async move {
+                    Ok(TxResult {
+                        tx_hash: format!("tx-{}", sequence),
+                        code: 0,
+                        simulated: false,
+                        attempts: Vec::new(),
+                        log: None,
+                    })
+                }
+            })
+        };
+        let wallet = &staking.wallet;
+        let wallet_send = |caller: &'static str| async move {
+            let sent = wallet.sign_and_broadcast(move |sequence, account_number| {
+                sign(caller, sequence, account_number);
+                Ok(format!("signed-{}", sequence))
+            });
+            sent.await.map_err(|e| e.to_string())
+        };
+
+        assert_eq!(submit("fund-1").await?.code, 0);
+        assert_eq!(wallet_send("stake-1").await?.code, 0);
+        assert_eq!(submit("fund-2").await?.code, 0);
+        assert_eq!(wallet_send("stake-2").await?.code, 0);
+
+        assert_eq!(
+            *signed.lock().unwrap(),
+            vec![
+                ("fund-1", 20, 7),
+                ("stake-1", 21, 7),
+                ("fund-2", 22, 7),
+                ("stake-2", 23, 7),
+            ]
+        );
+        assert_eq!(chain.count("/rpc"), 2);
+        // One sync for the address, shared by both paths.
+        assert_eq!(serializer.stats().refreshes, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relayer_capabilities_gate_dependent_queries() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+        use jsonrpc_core::{IoHandler, Params, Value};
+        use jsonrpc_http_server::ServerBuilder;
+
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let order = TraderOrderBuilder::new()
+            .account_id(address.as_str())
+            .to_json();
+        let serve = |io: IoHandler| {
+            ServerBuilder::new(io)
+                .start_http(&"127.0.0.1:0".parse().unwrap())
+                .unwrap()
+        };
+        let base = order_wallet.relayer_endpoint_config.clone();
+        let endpoint = |server: &jsonrpc_http_server::Server| RelayerEndPointConfig {
+            relayer_api_endpoint: format!("http://{}", server.address()),
+            ..base.clone()
+        };
+
+        // New relayer: advertises v1 queries but no funding history.
+        let mut io = IoHandler::new();
+        io.add_sync_method("server_info", |_: Params| {
+            Ok(serde_json::json!({ "version": "0.9.0", "capabilities": ["order_info_v1"] }))
+        });
+        let mut v1 = order.clone();
+        v1["funding_applied"] = Value::from("1.5");
+        io.add_sync_method("trader_order_info_v1", move |_: Params| Ok(v1.clone()));
+        let new_relayer = serve(io);
+        order_wallet.update_endpoints(endpoint(&new_relayer))?;
+
+        let capabilities = order_wallet.relayer_capabilities().await?;
+        assert_eq!(capabilities.version.as_deref(), Some("0.9.0"));
+        let err = order_wallet.order_funding_history(index).await.unwrap_err();
+        assert_eq!(err, "Relayer 0.9.0 does not support funding_history");
+        let v1 = order_wallet.query_trader_order_v1(index).await?;
+        assert_eq!(v1.funding_applied, Some(1.5));
+
+        // Old relayer: no version endpoint and no v1 queries; probing finds funding history.
+        let mut io = IoHandler::new();
+        let plain = order.clone();
+        io.add_sync_method("trader_order_info", move |_: Params| Ok(plain.clone()));
+        io.add_sync_method("order_funding_history", |_: Params| Ok(Value::Array(vec![])));
+        let old_relayer = serve(io);
+        order_wallet.update_endpoints(endpoint(&old_relayer))?;
+
+        let capabilities = order_wallet.relayer_capabilities().await?;
+        assert!(!capabilities.advertised);
+        assert_eq!(capabilities.version, None);
+        assert!(capabilities.supports_funding_history);
+        assert!(!capabilities.supports_order_info_v1);
+        assert!(order_wallet.order_funding_history(index).await?.is_empty());
+        let v1 = order_wallet.query_trader_order_v1(index).await?;
+        assert_eq!(v1.order.account_id, address);
+        assert_eq!(v1.funding_applied, None);
+
+        new_relayer.close();
+        old_relayer.close();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_queries_on_different_accounts_run_concurrently() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+        use jsonrpc_core::{IoHandler, Params};
+        use jsonrpc_http_server::ServerBuilder;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let first = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+        let second = order_wallet
+            .zk_accounts
+            .generate_new_account(200, &order_wallet.seed.secret()?)?;
+        let order = TraderOrderBuilder::new().to_json();
+
+        // Each request waits (up to a bound) for the other to arrive, so both
+        // are only ever in flight together if the wallet issued them together.
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let mut io = IoHandler::new();
+        {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            io.add_sync_method("trader_order_info", move |_: Params| {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(now, Ordering::SeqCst);
+                let deadline = std::time::Instant::now() + Duration::from_secs(2);
+                while in_flight.load(Ordering::SeqCst) < 2
+                    && std::time::Instant::now() < deadline
+                {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                max_in_flight.fetch_max(in_flight.load(Ordering::SeqCst), Ordering::SeqCst);
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(order.clone())
+            });
+        }
+        let server = ServerBuilder::new(io)
+            .threads(2)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        order_wallet.update_endpoints(RelayerEndPointConfig {
+            relayer_api_endpoint: format!("http://{}", server.address()),
+            ..order_wallet.relayer_endpoint_config.clone()
+        })?;
+
+        let shared = &order_wallet;
+        let (a, b) = tokio::time::timeout(Duration::from_secs(10), async {
+            tokio::join!(
+                shared.query_trader_order(first),
+                shared.query_trader_order(second)
+            )
+        })
+        .await
+        .map_err(|_| "queries did not finish".to_string())?;
+        a?;
+        b?;
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+
+        server.close();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_order_calls_on_different_accounts_share_one_wallet() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{TraderOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let mut indices = Vec::new();
+        for (balance, request_id) in [(1_000, "REQ-A"), (2_000, "REQ-B")] {
+            let index = order_wallet
+                .zk_accounts
+                .generate_new_account(balance, &order_wallet.seed.secret()?)?;
+            order_wallet
+                .zk_accounts
+                .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
+            order_wallet.cache_request_id(index, request_id, None);
+            indices.push(index);
+        }
+        relayer.respond(
+            "trader_order_info",
+            TraderOrderBuilder::new()
+                .order_type(OrderType::LIMIT)
+                .order_status(OrderStatus::PENDING)
+                .to_json(),
+        );
+        relayer.accept("cancel_trader_order", "REQ-CANCEL");
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .request_id("REQ-CANCEL")
+                .order_status(OrderStatus::CANCELLED)
+                .to_json()],
+        );
+
+        // Each task cancels on its own account through the same `Arc`.
+        let order_wallet = Arc::new(order_wallet);
+        let tasks: Vec<_> = indices
+            .iter()
+            .map(|&index| {
+                let order_wallet = order_wallet.clone();
+                tokio::spawn(async move { order_wallet.cancel_trader_order(index).await })
+            })
+            .collect();
+        for task in tasks {
+            let request_id = task.await.map_err(|e| e.to_string())?;
+            assert_eq!(request_id.map_err(|e| e.to_string())?, "REQ-CANCEL");
+        }
+        for (index, balance) in indices.into_iter().zip([1_000, 2_000]) {
+            assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+            assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, balance);
+        }
+        assert_eq!(relayer.call_count("cancel_trader_order"), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memo_accounts_without_an_order_kind_are_identified() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{LendOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        // Memo accounts saved before the order kind was kept.
+        let memo = || -> Result<AccountIndex, String> {
+            let index = order_wallet
+                .zk_accounts
+                .generate_new_account(100, &order_wallet.seed.secret()?)?;
+            order_wallet
+                .zk_accounts
+                .update_io_type(&index, IOType::Memo, None)?;
+            Ok(index)
+        };
+        let (recorded, lending) = (memo()?, memo()?);
+        let record = OrderRecord::new("REQ-T".to_string(), OrderRecordKind::TraderOpen, Utc::now());
+        order_wallet.push_order_record(recorded, record);
+        let lending_address = order_wallet.zk_accounts.get_account_address(&lending)?;
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .account_id(lending_address.as_str())
+                .order_type(OrderType::LEND)
+                .request_id("REQ-LEND")
+                .to_json()],
+        );
+        assert!(order_wallet.identify_memo_orders().await.is_empty());
+        let account = |index| order_wallet.zk_accounts.get_account(&index);
+        assert!(matches!(account(recorded)?.tx_type, Some(TXType::ORDERTX)));
+        assert!(matches!(account(lending)?.tx_type, Some(TXType::LENDTX)));
+        assert_eq!(order_wallet.request_id(lending)?, "REQ-LEND");
+        // The recorded account's kind came from its order record.
+        assert_eq!(relayer.call_count("transaction_hashes"), 1);
+
+        // Nothing tells the kind of this one: it is neither order kind.
+        let unknown = memo()?;
+        relayer.respond("transaction_hashes", serde_json::json!([]));
+        assert_eq!(order_wallet.identify_memo_orders().await, vec![unknown]);
+        assert_eq!(order_wallet.open_accounts(false), vec![recorded]);
+        assert_eq!(order_wallet.open_accounts(true), vec![lending]);
+        assert_eq!(order_wallet.open_trader_order_count(), 2);
+        let summary = order_wallet.summary();
+        let entry = summary
+            .accounts
+            .iter()
+            .find(|a| a.account_index == unknown)
+            .unwrap();
+        assert_eq!(entry.order_kind, None);
+
+        relayer.respond(
+            "lend_order_info",
+            LendOrderBuilder::new()
+                .account_id(lending_address.as_str())
+                .order_status(OrderStatus::FILLED)
+                .to_json(),
+        );
+        let lend = order_wallet.list_open_lend_orders().await;
+        assert_eq!(lend.orders.len(), 1);
+        assert_eq!(lend.orders[0].0, lending);
+        assert_eq!(lend.failed.len(), 1);
+        assert_eq!(lend.failed[0].0, unknown);
+        assert_eq!(relayer.call_count("trader_order_info"), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_open_orders_and_query_by_id() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::{
+            LendOrderBuilder, TraderOrderBuilder, TxHashBuilder,
+        };
+        use jsonrpc_core::{IoHandler, Params, Value};
+        use jsonrpc_http_server::ServerBuilder;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let mut accounts = Vec::new();
+        for _ in 0..3 {
+            accounts.push(
+                order_wallet
+                    .zk_accounts
+                    .generate_new_account(100, &order_wallet.seed.secret()?)?,
+            );
+        }
+        let (trading, failing, lending) = (accounts[0], accounts[1], accounts[2]);
+        // A Coin account, never queried.
+        order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+        for index in [trading, failing] {
+            order_wallet
+                .zk_accounts
+                .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
+        }
+        order_wallet
+            .zk_accounts
+            .update_io_type(&lending, IOType::Memo, Some(TXType::LENDTX))?;
+        let trading_address = order_wallet.zk_accounts.get_account_address(&trading)?;
+        let lending_address = order_wallet.zk_accounts.get_account_address(&lending)?;
+        let order = TraderOrderBuilder::new()
+            .account_id(trading_address.as_str())
+            .to_json();
+        let order_id = order["uuid"].as_str().unwrap().to_string();
+
+        // The second trader query (the failing account's) errors.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut io = IoHandler::new();
+        {
+            let calls = calls.clone();
+            io.add_sync_method("trader_order_info", move |_: Params| {
+                if calls.fetch_add(1, Ordering::SeqCst) == 1 {
+                    return Err(jsonrpc_core::Error::invalid_params("order not found"));
+                }
+                Ok(order.clone())
+            });
+        }
+        let lend = LendOrderBuilder::new()
+            .account_id(lending_address.as_str())
+            .order_status(OrderStatus::FILLED)
+            .to_json();
+        io.add_sync_method("lend_order_info", move |_: Params| Ok(lend.clone()));
+        let tx = TxHashBuilder::new()
+            .order_id(order_id.as_str())
+            .account_id(trading_address.as_str())
+            .to_json();
+        io.add_sync_method("transaction_hashes", move |_: Params| {
+            Ok(Value::Array(vec![tx.clone()]))
+        });
+        let server = ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        order_wallet.update_endpoints(RelayerEndPointConfig {
+            relayer_api_endpoint: format!("http://{}", server.address()),
+            ..order_wallet.relayer_endpoint_config.clone()
+        })?;
+
+        let open = order_wallet.list_open_orders().await;
+        assert_eq!(open.orders.len(), 1);
+        assert_eq!(open.orders[0].0, trading);
+        assert_eq!(open.orders[0].1.account_id, trading_address);
+        assert_eq!(open.failed.len(), 1);
+        assert_eq!(open.failed[0].0, failing);
+        // Neither the lending account nor the Coin account got a trader query.
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let lends = order_wallet.list_open_lend_orders().await;
+        assert_eq!(lends.orders.len(), 1);
+        assert_eq!(lends.orders[0].0, lending);
+        assert!(lends.failed.is_empty());
+
+        let by_id = order_wallet.query_trader_order_by_id(&order_id).await?;
+        assert_eq!(by_id.uuid.to_string(), order_id);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let err = order_wallet
+            .query_trader_order_by_id("00000000-0000-0000-0000-000000000000")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no record"), "{}", err);
+
+        server.close();
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_labels() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let main = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+        let hedge = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+        order_wallet.set_account_label(main, Some("bot/main"))?;
+        order_wallet.set_account_label(hedge, Some("bot/hedge"))?;
+
+        assert_eq!(order_wallet.account_index_by_label("bot/hedge")?, hedge);
+        let tagged: Vec<_> = order_wallet
+            .accounts_with_tag("bot/")
+            .iter()
+            .map(|account| account.index)
+            .collect();
+        assert_eq!(tagged, vec![main, hedge]);
+        let err = order_wallet
+            .set_account_label(hedge, Some("bot/main"))
+            .unwrap_err();
+        assert!(err.contains("already used by account"), "{}", err);
+        assert_eq!(
+            order_wallet.account_by_label("bot/hedge").map(|a| a.index),
+            Some(hedge)
+        );
+        let balances = order_wallet.get_account_balances(false);
+        assert!(balances
+            .iter()
+            .any(|b| b.account_index == main && b.label.as_deref() == Some("bot/main")));
+        Ok(())
+    }
+
+    /// Mock relayer answering `transaction_hashes` with `txs` and counting
+    /// order submits.
+    fn spawn_submission_relayer(
+        txs: Vec<serde_json::Value>,
+    ) -> (
+        jsonrpc_http_server::Server,
+        Arc<std::sync::atomic::AtomicUsize>,
+    ) {
+        use jsonrpc_core::{IoHandler, Params, Value};
+        use jsonrpc_http_server::ServerBuilder;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let submits = Arc::new(AtomicUsize::new(0));
+        let mut io = IoHandler::new();
+        io.add_sync_method("transaction_hashes", move |_: Params| {
+            Ok(Value::Array(txs.clone()))
+        });
+        for method in ["submit_trade_order", "submit_lend_order"] {
+            let submits = submits.clone();
+            io.add_sync_method(method, move |_: Params| {
+                submits.fetch_add(1, Ordering::SeqCst);
+                Err(jsonrpc_core::Error::internal_error())
+            });
+        }
+        let server = ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        (server, submits)
+    }
+
+    #[tokio::test]
+    async fn test_open_trader_order_adopts_unconfirmed_submit() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
+        use std::sync::atomic::Ordering;
+
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let now = Utc::now();
+        // An earlier attempt timed out after the relayer accepted the order.
+        let submission = PendingSubmission::new(
+            index,
+            OrderKind::Trader,
+            address.clone(),
+            now - chrono::Duration::seconds(30),
+        );
+        order_wallet
+            .pending_submissions
+            .insert(index, submission.clone());
+        let tx = TxHashBuilder::new()
+            .account_id(address)
+            .field("datetime", now.timestamp_millis().to_string())
+            .request_id("REQ-ACCEPTED")
+            .to_json();
+        let (server, submits) = spawn_submission_relayer(vec![tx]);
+        order_wallet.update_endpoints(RelayerEndPointConfig {
+            relayer_api_endpoint: format!("http://{}", server.address()),
+            ..order_wallet.relayer_endpoint_config.clone()
+        })?;
+
+        let request_id = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 50_000, 5u64)
+            .await?;
+        assert_eq!(request_id, "REQ-ACCEPTED");
+        assert_eq!(submits.load(Ordering::SeqCst), 0);
+        assert_eq!(order_wallet.request_id(index)?, "REQ-ACCEPTED");
+        assert_eq!(
+            order_wallet.idempotency_key(index),
+            Some(submission.idempotency_key)
+        );
+        assert!(order_wallet.pending_submissions().is_empty());
+
+        server.close();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_pending_orders() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
 
-            debug!("OrderWallet data persisted to database during drop");
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let accepted = order_wallet
+            .zk_accounts
+            .generate_new_account(100, &order_wallet.seed.secret()?)?;
+        let lost = order_wallet
+            .zk_accounts
+            .generate_new_account(200, &order_wallet.seed.secret()?)?;
+        let now = Utc::now();
+        let accepted_address = order_wallet.zk_accounts.get_account_address(&accepted)?;
+        let lost_address = order_wallet.zk_accounts.get_account_address(&lost)?;
+        let before = now - chrono::Duration::seconds(30);
+        for submission in [
+            PendingSubmission::new(accepted, OrderKind::Lend, accepted_address.clone(), before),
+            PendingSubmission::new(lost, OrderKind::Trader, lost_address, before),
+        ] {
+            order_wallet
+                .pending_submissions
+                .insert(submission.account_index, submission);
         }
+        let tx = TxHashBuilder::new()
+            .account_id(accepted_address)
+            .field("datetime", now.timestamp_millis().to_string())
+            .request_id("REQ-LEND")
+            .to_json();
+        let (server, _) = spawn_submission_relayer(vec![tx]);
+        order_wallet.update_endpoints(RelayerEndPointConfig {
+            relayer_api_endpoint: format!("http://{}", server.address()),
+            ..order_wallet.relayer_endpoint_config.clone()
+        })?;
+
+        let reconciled = order_wallet.reconcile_pending_orders().await?;
+        let outcomes: Vec<_> = reconciled
+            .iter()
+            .map(|r| (r.submission.account_index, r.reconciliation.clone()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (
+                    accepted,
+                    Reconciliation::Accepted {
+                        request_id: "REQ-LEND".to_string()
+                    }
+                ),
+                (lost, Reconciliation::NotFound),
+            ]
+        );
+        assert_eq!(order_wallet.request_id(accepted)?, "REQ-LEND");
+        assert!(order_wallet.request_id(lost).is_err());
+        assert!(order_wallet.pending_submissions().is_empty());
+
+        server.close();
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{get_test_tokens, relayer_module::fetch_tx_hash_with_retry};
-    use log::info;
-    use serial_test::serial;
-    use std::sync::Once;
-    use tokio::time::{sleep, Duration};
-    use twilight_client_sdk::relayer_types::PositionType;
-    static INIT: Once = Once::new();
+    #[tokio::test]
+    async fn test_reconcile_reads_back_adopted_trader_order() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{TraderOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let now = order_wallet.clock.now();
+        let submission = PendingSubmission::new(
+            index,
+            OrderKind::Trader,
+            address.clone(),
+            now - chrono::Duration::seconds(30),
+        );
+        order_wallet.pending_submissions.insert(index, submission);
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .account_id(address.as_str())
+                .field("datetime", now.timestamp_millis().to_string())
+                .request_id("REQ-TRADE")
+                .to_json()],
+        );
+        relayer.respond(
+            "trader_order_info",
+            TraderOrderBuilder::new()
+                .account_id(address.as_str())
+                .order_type(OrderType::LIMIT)
+                .position_type(PositionType::SHORT)
+                .position(1_000.0, 5.0, 52_000.0)
+                .to_json(),
+        );
 
-    // This function initializes the logger for the tests.
-    fn init_logger() {
-        INIT.call_once(|| {
-            // `is_test(true)` keeps the default filter at `trace`
-            // and respects RUST_LOG if you set it.
-            env_logger::builder().is_test(true).try_init().ok();
-        });
+        let params = order_wallet.submitted_trader_order_params(index).await?;
+        assert!(matches!(params.order_type, OrderType::LIMIT));
+        assert!(matches!(params.order_side, PositionType::SHORT));
+        assert_eq!(params.entry_price, 52_000);
+        assert_eq!(params.leverage, Leverage::from(5u64));
+
+        let reconciled = order_wallet.reconcile_pending_orders().await?;
+        assert_eq!(
+            reconciled[0].reconciliation,
+            Reconciliation::Accepted {
+                request_id: "REQ-TRADE".to_string()
+            }
+        );
+        assert_eq!(order_wallet.request_id(index)?, "REQ-TRADE");
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        assert!(matches!(
+            order_wallet.zk_accounts.get_account(&index)?.tx_type,
+            Some(TXType::ORDERTX)
+        ));
+        assert_eq!(relayer.call_count("trader_order_info"), 2);
+        Ok(())
     }
-    async fn setup_wallet() -> Result<Wallet, String> {
-        info!("Creating new wallet with random BTC address");
-        // let mut wallet = Wallet::new(None).map_err(|e| e.to_string())?;
-        // info!("importing wallet from json");
-        let mut wallet = Wallet::import_from_json("test.json").map_err(|e| e.to_string())?;
 
-        info!("Getting test tokens from faucet");
-        match get_test_tokens(&mut wallet).await {
-            Ok(_) => info!("Tokens received successfully"),
-            Err(e) => return Err(e.to_string()),
-        }
+    #[tokio::test]
+    async fn test_batch_open_adopts_unconfirmed_submit() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
+
+        let relayer = MockRelayer::new();
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let address = order_wallet.zk_accounts.get_account_address(&index)?;
+        let now = order_wallet.clock.now();
+        let submission = PendingSubmission::new(
+            index,
+            OrderKind::Trader,
+            address.clone(),
+            now - chrono::Duration::seconds(30),
+        );
+        order_wallet
+            .pending_submissions
+            .insert(index, submission.clone());
+        // The newer record has no usable timestamp, so it may predate the
+        // submission and is not adopted.
+        let tx = |id: i64, at: String, request_id: &str| {
+            TxHashBuilder::new()
+                .field("id", id)
+                .account_id(address.as_str())
+                .field("datetime", at)
+                .request_id(request_id)
+                .to_json()
+        };
+        relayer.respond(
+            "transaction_hashes",
+            vec![
+                tx(2, now.timestamp_millis().to_string(), "REQ-BATCH"),
+                tx(9, String::new(), "REQ-OLD"),
+            ],
+        );
 
-        // Give the faucet some time to finalize and the indexer to catch up.
-        sleep(Duration::from_secs(5)).await;
+        let params =
+            TraderOrderParams::new(index, OrderType::MARKET, PositionType::LONG, 50_000, 5);
+        let outcomes = order_wallet.open_trader_orders_batch(vec![params]).await?;
+        assert_eq!(outcomes, vec![(index, Ok("REQ-BATCH".to_string()))]);
+        assert_eq!(relayer.call_count("submit_trade_order"), 0);
+        assert_eq!(order_wallet.request_id(index)?, "REQ-BATCH");
+        assert_eq!(
+            order_wallet.idempotency_key(index),
+            Some(submission.idempotency_key)
+        );
+        assert!(order_wallet.pending_submissions().is_empty());
+        Ok(())
+    }
 
-        Ok(wallet)
+    /// Answers UTXO fetches by account address; fetches for `panics` panic.
+    #[derive(Debug, Default)]
+    struct AddressFetcher {
+        replies: HashMap<String, UtxoDetailResponse>,
+        panics: String,
     }
-    // cargo test --no-default-features --features postgresql --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
-    // cargo test --no-default-features --features sqlite --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
-    // cargo test --all-features --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
-    #[cfg(feature = "sqlite")]
-    #[tokio::test]
-    #[serial]
-    #[ignore]
-    async fn test_create_order_complete_cycle() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        unsafe {
-            // std::env::set_var("DATABASE_URL", "./test.db");
-            std::env::set_var("NYKS_WALLET_PASSPHRASE", "test1_password");
+
+    impl UtxoFetcher for AddressFetcher {
+        fn fetch(&self, account_address: String, _io_type: IOType) -> UtxoFuture {
+            if account_address == self.panics {
+                panic!("malformed UTXO for {}", account_address);
+            }
+            let reply = self
+                .replies
+                .get(&account_address)
+                .cloned()
+                .ok_or_else(|| "Failed to get utxo details: UTXO not found".to_string());
+            Box::pin(async move { reply })
         }
-        init_logger();
-        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
-        let zk_accounts = ZkAccountDB::new();
+    }
 
-        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
-            .map_err(|e| e.to_string())?;
-        // order_wallet.with_db(None, None)?;
-        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
-        if tx_result.code != 0 {
-            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+    #[tokio::test]
+    async fn test_batch_open_reports_each_failure_on_its_account() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::coin_utxo;
+
+        let relayer = MockRelayer::new();
+        relayer.accept("submit_trade_order", "REQ-FILLED");
+        let (order_wallet, filled, _) = cached_utxo_wallet(&relayer, 0).await?;
+        let seed = order_wallet.seed.secret()?;
+        let accounts = &order_wallet.zk_accounts;
+        let off_chain = accounts.generate_new_account(1_000, &seed)?;
+        let panicking = accounts.generate_new_account(1_000, &seed)?;
+        let over_leveraged = accounts.generate_new_account(1_000, &seed)?;
+        let mut fetcher = AddressFetcher::default();
+        for index in [filled, panicking, over_leveraged] {
+            accounts.update_on_chain(&index, true)?;
+            let account = accounts.get_account(&index)?;
+            fetcher
+                .replies
+                .insert(accounts.get_account_address(&index)?, coin_utxo(&account));
         }
+        fetcher.panics = accounts.get_account_address(&panicking)?;
+        let mut order_wallet = order_wallet.with_utxo_fetcher(Arc::new(fetcher));
 
-        let btc_price = order_wallet
-            .relayer_api_client
-            .btc_usd_price()
-            .await
-            .map_err(|e| e.to_string())?;
-        info!("btc_price: {:?}", btc_price);
-        let entry_price = btc_price.price as u64;
-        let result = order_wallet
-            .open_trader_order(
-                account_index,
+        let long = |index, leverage| {
+            TraderOrderParams::new(
+                index,
                 OrderType::MARKET,
                 PositionType::LONG,
-                entry_price,
-                10,
+                50_000,
+                leverage,
             )
+        };
+        let outcomes = order_wallet
+            .open_trader_orders_batch(vec![
+                long(filled, 2),
+                long(off_chain, 2),
+                long(panicking, 2),
+                long(over_leveraged, 60),
+            ])
             .await?;
-        info!("result: {:?}", result);
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        info!("tx_hash: {:?}", tx_hash);
-        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
-        let response = order_wallet.query_trader_order(account_index).await?;
-        info!("response: {:?}", response);
-        assert_eq!(response.order_status, OrderStatus::FILLED);
-        assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&account_index)?
-                .io_type,
-            IOType::Memo
-        );
+        let indices: Vec<_> = outcomes.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, vec![filled, off_chain, panicking, over_leveraged]);
+        assert_eq!(outcomes[0].1, Ok("REQ-FILLED".to_string()));
+        let error = |position: usize| outcomes[position].1.clone().unwrap_err();
+        assert!(error(1).contains("does not exist on chain"), "{}", error(1));
+        assert!(error(2).contains("panicked"), "{}", error(2));
+        assert!(error(2).contains("malformed UTXO"), "{}", error(2));
+        assert!(error(3).contains("exceeds maximum"), "{}", error(3));
+
+        // Only the valid order reached the relayer.
+        assert_eq!(relayer.call_count("submit_trade_order"), 1);
+        assert_eq!(order_wallet.request_id(filled)?, "REQ-FILLED");
+        let accounts = &order_wallet.zk_accounts;
+        assert_eq!(accounts.get_io_type(&filled)?, IOType::Memo);
+        assert_eq!(accounts.get_io_type(&panicking)?, IOType::Coin);
+        assert!(order_wallet.pending_submissions().is_empty());
+        Ok(())
+    }
 
-        let result = order_wallet
-            .close_trader_order(account_index, OrderType::MARKET, 0.0)
+    #[tokio::test]
+    async fn test_simulated_funding_accrues_per_epoch() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        let start: DateTime<Utc> = "2025-03-01T00:30:00Z".parse().unwrap();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig {
+                start,
+                funding_rate: 0.0005,
+                ..SimulationConfig::default()
+            }));
+        let clock = order_wallet.simulated_clock().unwrap();
+        assert_eq!(order_wallet.clock().now(), start);
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+
+        order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 0, 10)
             .await?;
+        // Within the first epoch: nothing charged yet.
+        clock.advance(Duration::from_secs(20 * 60));
+        let order = order_wallet.simulated_trader_order(index).unwrap();
+        assert_eq!(order.funding_applied, 0.0);
 
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        assert_eq!(tx_hash.order_status, OrderStatus::SETTLED);
-        let response = order_wallet.query_trader_order(account_index).await?;
-        assert_eq!(response.order_status, OrderStatus::SETTLED);
-        order_wallet.unlock_trader_order(account_index).await?;
-        let zk_account = order_wallet.zk_accounts.get_account(&account_index)?;
-        assert_eq!(zk_account.io_type, IOType::Coin);
-        assert_eq!(zk_account.balance, response.available_margin as u64);
-        let receiver_account_index = order_wallet.trading_to_trading(account_index).await?;
-        assert_ne!(account_index, receiver_account_index);
-        assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&account_index)?
-                .on_chain,
-            false
-        );
-        info!("receiver_account_index: {:?}", receiver_account_index);
-        let result1 = order_wallet
-            .open_trader_order(
-                receiver_account_index,
-                OrderType::MARKET,
-                PositionType::LONG,
-                entry_price,
-                10,
-            )
+        // 00:50 -> 03:50 crosses 01:00, 02:00 and 03:00.
+        clock.advance(Duration::from_secs(3 * 3600));
+        let order = order_wallet.simulated_trader_order(index).unwrap();
+        let expected = -3.0 * 0.0005 * 10_000.0;
+        assert!((order.funding_applied - expected).abs() < 1e-9);
+
+        order_wallet
+            .close_trader_order(index, OrderType::MARKET, 0.0)
             .await?;
-        info!("result1: {:?}", result1);
-        let tx_hash1 = fetch_tx_hash_with_retry(&result1, &order_wallet.relayer_api_client).await?;
-        info!("tx_hash1: {:?}", tx_hash1);
-        assert_eq!(tx_hash1.order_status, OrderStatus::FILLED);
-        let response = order_wallet
-            .query_trader_order(receiver_account_index)
+        let order = order_wallet.simulated_trader_order(index).unwrap();
+        assert_eq!(order.order_status, OrderStatus::SETTLED);
+        assert_eq!(order.closed_at, Some(start + chrono::Duration::minutes(200)));
+        Ok(())
+    }
+
+    #[cfg(feature = "webhooks")]
+    #[tokio::test]
+    async fn test_slow_webhook_does_not_delay_orders() -> Result<(), String> {
+        use crate::relayer_module::events::WalletEventKind;
+        use crate::relayer_module::simulation::SimulationConfig;
+        // Accepts connections and answers nothing for two seconds.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let _held = stream;
+                std::thread::sleep(Duration::from_secs(2));
+            }
+        });
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        order_wallet.add_webhook(&url, "shh", EventFilter::all())?;
+        order_wallet.add_webhook(
+            &url,
+            "shh",
+            EventFilter::only([WalletEventKind::OrderFailed]),
+        )?;
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+
+        let started = std::time::Instant::now();
+        order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 0, 2)
             .await?;
-        info!("response: {:?}", response);
-        assert_eq!(response.order_status, OrderStatus::FILLED);
-        assert_eq!(
+        order_wallet
+            .close_trader_order(index, OrderType::MARKET, 0.0)
+            .await?;
+        assert!(order_wallet.cancel_trader_order(index).await.is_err());
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(order_wallet.webhook_stats().dropped, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_events() -> Result<(), String> {
+        use crate::relayer_module::events::WalletEventKind;
+        use crate::relayer_module::simulation::SimulationConfig;
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        // Nobody listens yet: nothing is built or buffered.
+        order_wallet.set_account_balance(&index, 900)?;
+
+        let mut events = order_wallet.subscribe_events();
+        let request_id = order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 0, 2)
+            .await?;
+        order_wallet.note_order_status(index, OrderKind::Trader, &OrderStatus::FILLED);
+        order_wallet.note_order_status(index, OrderKind::Trader, &OrderStatus::FILLED);
+        order_wallet.set_account_balance(&index, 900)?;
+        order_wallet.set_account_balance(&index, 1_200)?;
+
+        let opened = events.try_recv().unwrap();
+        assert_eq!(opened.kind(), WalletEventKind::OrderOpened);
+        assert_eq!(opened.request_id(), Some(request_id.as_str()));
+        match events.try_recv().unwrap() {
+            WalletEvent::OrderStatusChanged {
+                account_index,
+                request_id: changed,
+                from,
+                to,
+                ..
+            } => {
+                assert_eq!((account_index, changed), (index, request_id));
+                assert_eq!((from.as_str(), to.as_str()), ("SUBMITTED", "FILLED"));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        match events.try_recv().unwrap() {
+            WalletEvent::BalanceUpdated {
+                previous, balance, ..
+            } => assert_eq!((previous, balance), (900, 1_200)),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_unlocks_only_dry_run_orders() -> Result<(), String> {
+        use crate::relayer_module::simulation::is_dry_run_id;
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        order_wallet.set_execution_mode(ExecutionMode::DryRun);
+        assert!(order_wallet.is_dry_run() && !order_wallet.is_simulated());
+        let seed = order_wallet.seed.secret()?;
+        let paper = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &seed)?;
+        let live = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &seed)?;
+        for index in [paper, live] {
             order_wallet
                 .zk_accounts
-                .get_account(&receiver_account_index)?
-                .io_type,
+                .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
+        }
+        order_wallet.zk_accounts.mark_simulated(&paper)?;
+
+        let request_id = order_wallet
+            .close_trader_order(paper, OrderType::MARKET, 0.0)
+            .await?;
+        assert!(is_dry_run_id(&request_id));
+        assert_eq!(
+            order_wallet.zk_accounts.get_account(&paper)?.io_type,
+            IOType::Coin
+        );
+        assert!(order_wallet.cancel_trader_order(live).await.is_err());
+        assert_eq!(
+            order_wallet.zk_accounts.get_account(&live)?.io_type,
             IOType::Memo
         );
-        let result2 = order_wallet
-            .close_trader_order(receiver_account_index, OrderType::MARKET, 0.0)
+        let refused = order_wallet.trading_to_funding(paper).await.unwrap_err();
+        assert!(refused.to_string().contains("dry-run mode"), "{}", refused);
+
+        order_wallet.set_execution_mode(ExecutionMode::Live);
+        assert!(!order_wallet.is_dry_run());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_funds_accounts_and_builds_orders_on_them() -> Result<(), String> {
+        use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::simulation::is_dry_run_id;
+
+        let chain = MockChain::spawn();
+        let relayer = MockRelayer::new();
+        let mut stats = relayer
+            .get_market_stats()
+            .await
+            .map_err(|e| e.to_string())?;
+        stats.pool_equity_btc = 1_000_000.0;
+        stats.max_long_btc = 1_000_000.0;
+        relayer.respond("get_market_stats", stats);
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()))
+            .with_execution_mode(ExecutionMode::DryRun);
+        order_wallet.wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        chain.route(
+            "/cosmos/bank/v1beta1/balances/",
+            vec![MockResponse::ok(
+                r#"{"balances":[{"denom":"sats","amount":"5000"}]}"#,
+            )],
+        );
+        let account = serde_json::json!({
+            "account": {
+                "@type": "/cosmos.auth.v1beta1.BaseAccount",
+                "address": order_wallet.wallet.twilightaddress,
+                "pub_key": null,
+                "account_number": "7",
+                "sequence": "3",
+            }
+        });
+        chain.route(
+            "/cosmos/auth/v1beta1/accounts/",
+            vec![MockResponse::ok(account.to_string())],
+        );
+
+        // The mints are signed at the chain's sequence, which is released unused.
+        let (txs, funded) = order_wallet
+            .funding_to_trading_multiple(vec![1_000, 1_000])
             .await?;
-        let tx_hash2 = fetch_tx_hash_with_retry(&result2, &order_wallet.relayer_api_client).await?;
-        assert_eq!(tx_hash2.order_status, OrderStatus::SETTLED);
-        let response2 = order_wallet
-            .query_trader_order(receiver_account_index)
+        assert!(txs.iter().all(|tx| tx.simulated && !tx.tx_hash.is_empty()));
+        assert_eq!(order_wallet.nonce_manager.released_count(), 1);
+        assert_eq!(chain.count("/rpc"), 0);
+        let (trader, lend) = (funded[0].0, funded[1].0);
+        for &(index, _) in &funded {
+            let account = order_wallet.zk_accounts.get_account(&index)?;
+            assert!(account.simulated && account.on_chain);
+            assert_eq!(account.io_type, IOType::Coin);
+        }
+
+        // No chain UTXO backs the accounts: the orders are built, proof
+        // included, on the accounts' own inputs and never submitted.
+        let request_id = order_wallet
+            .open_trader_order(trader, OrderType::MARKET, PositionType::LONG, 50_000, 2)
             .await?;
-        assert_eq!(response2.order_status, OrderStatus::SETTLED);
+        assert!(is_dry_run_id(&request_id));
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&trader)?, IOType::Memo);
+        let request_id = order_wallet.open_lend_order(lend).await?;
+        assert!(is_dry_run_id(&request_id));
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&lend)?, IOType::Memo);
+        assert_eq!(relayer.call_count("submit_trade_order"), 0);
+        assert_eq!(relayer.call_count("submit_lend_order"), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_order_history_records_each_request() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        use crate::relayer_module::transaction_history::OrderRecordKind;
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        assert!(order_wallet.order_history(index).is_empty());
+
         order_wallet
-            .unlock_trader_order(receiver_account_index)
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 0, 2)
             .await?;
         order_wallet
-            .sync_account_state(receiver_account_index)
-            .await?;
-        assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&receiver_account_index)?
-                .io_type,
-            IOType::Coin
-        );
-        let new_recever_index = order_wallet
-            .trading_to_trading(receiver_account_index)
+            .close_trader_order(index, OrderType::MARKET, 0.0)
             .await?;
-        assert_ne!(receiver_account_index, new_recever_index);
+        // Failed requests are not recorded.
+        assert!(order_wallet.cancel_trader_order(index).await.is_err());
+
+        let history = order_wallet.order_history(index);
+        let kinds: Vec<_> = history.iter().map(|r| r.kind).collect();
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&new_recever_index)?
-                .io_type,
-            IOType::Coin
-        );
-        let result3 = order_wallet
-            .open_trader_order(
-                new_recever_index,
-                OrderType::MARKET,
-                PositionType::LONG,
-                entry_price,
-                10,
-            )
-            .await?;
-        let tx_hash3 = fetch_tx_hash_with_retry(&result3, &order_wallet.relayer_api_client).await?;
-        info!("tx_hash3: {:?}", tx_hash3);
-        assert_eq!(tx_hash3.order_status, OrderStatus::FILLED);
-        let response3 = order_wallet.query_trader_order(new_recever_index).await?;
-        info!("response3: {:?}", response3);
-        assert_eq!(response3.order_status, OrderStatus::FILLED);
+            kinds,
+            [OrderRecordKind::TraderOpen, OrderRecordKind::TraderClose]
+        );
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&new_recever_index)?
-                .io_type,
-            IOType::Memo
+            order_wallet.request_id(index)?,
+            history.last().unwrap().request_id
         );
-        let result4 = order_wallet
-            .close_trader_order(new_recever_index, OrderType::MARKET, 0.0)
+        assert_eq!(order_wallet.all_order_history().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_statement_lists_order_requests() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        use crate::relayer_module::statement::{StatementCategory, CSV_COLUMNS};
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let from = order_wallet.clock().now();
+        order_wallet
+            .open_trader_order(index, OrderType::MARKET, PositionType::LONG, 0, 2)
             .await?;
-        debug!("result4: {:?}", result4);
+        order_wallet
+            .close_trader_order(index, OrderType::MARKET, 0.0)
+            .await?;
+        let to = order_wallet.clock().now() + chrono::Duration::seconds(1);
+
+        let mut csv = Vec::new();
+        let summary = order_wallet.export_statement(StatementFormat::Csv, from, to, &mut csv)?;
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_COLUMNS.join(","));
+        let history = order_wallet.order_history(index);
+        assert!(lines[1].ends_with(&history[0].request_id));
+        assert_eq!(summary.rows, 2);
+        assert_eq!(summary.by_category[&StatementCategory::TradeOpen].rows, 1);
+        assert_eq!(summary.by_category[&StatementCategory::TradeClose].rows, 1);
+
+        // Requests outside the period are left out.
+        let summary =
+            order_wallet.export_statement(StatementFormat::JsonLines, to, to, Vec::new())?;
+        assert_eq!(summary.rows, 0);
+        assert!(order_wallet
+            .export_statement(StatementFormat::Csv, to, from, Vec::new())
+            .is_err());
+        Ok(())
+    }
 
+    #[tokio::test]
+    async fn test_simulated_limit_order_expires_on_manual_clock() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        let real_start = std::time::Instant::now();
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig {
+                limit_order_ttl: Some(chrono::Duration::hours(6)),
+                ..SimulationConfig::default()
+            }));
+        let clock = order_wallet.simulated_clock().unwrap();
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet
+            .open_trader_order(index, OrderType::LIMIT, PositionType::LONG, 40_000, 2)
+            .await?;
+        let deadline = order_wallet
+            .simulated_trader_order(index)
+            .unwrap()
+            .expires_at
+            .unwrap();
+
+        clock.advance(Duration::from_secs(6 * 3600 - 1));
+        let order = order_wallet.simulated_trader_order(index).unwrap();
+        assert_eq!(order.order_status, OrderStatus::PENDING);
+
+        // The wallet's own waits run on the simulated clock too.
+        order_wallet.clock().sleep(Duration::from_secs(1)).await;
+        let order = order_wallet.simulated_trader_order(index).unwrap();
+        assert_eq!(order.order_status, OrderStatus::CANCELLED);
+        assert_eq!(order.closed_at, Some(deadline));
+        assert!(order_wallet.cancel_trader_order(index).await.is_err());
+        assert!(real_start.elapsed() < Duration::from_secs(5));
         Ok(())
     }
-    // cargo test --no-default-features --features postgresql --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
-    // cargo test --no-default-features --features sqlite --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
-    // cargo test --all-features --lib -- relayer_module::order_wallet::tests::test_create_order --exact --show-output
-    // #[cfg(feature = "sqlite")]
+
     #[tokio::test]
-    #[serial]
-    #[ignore]
-    async fn test_create_order_complete_cycle_sltp() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        unsafe {
-            // std::env::set_var("DATABASE_URL", "./test.db");
-            std::env::set_var("NYKS_WALLET_PASSPHRASE", "test1_password");
-        }
-        init_logger();
-        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
-        let zk_accounts = ZkAccountDB::new();
+    async fn test_replace_simulated_limit_order() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let original = order_wallet
+            .open_trader_order(index, OrderType::LIMIT, PositionType::SHORT, 60_000, 2)
+            .await?;
 
-        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
-            .map_err(|e| e.to_string())?;
-        // order_wallet.with_db(None, None)?;
-        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
-        if tx_result.code != 0 {
-            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
-        }
+        let receipt = order_wallet
+            .replace_trader_order(index, 58_000, None)
+            .await?;
+        assert_eq!(receipt.account_index, index);
+        assert_eq!(receipt.cancel_request_id, original);
+        assert_ne!(receipt.request_id, original);
+        assert!(!receipt.reused_utxo);
+        assert_eq!(order_wallet.request_id(index)?, receipt.request_id.as_str());
+        let order = order_wallet.simulated_trader_order(index).unwrap();
+        assert_eq!(order.order_status, OrderStatus::PENDING);
+        assert_eq!(order.position_type, PositionType::SHORT);
+        assert_eq!(order.entry_price, 58_000.0);
+        assert_eq!(order.leverage, 2.0);
 
-        let btc_price = order_wallet
-            .relayer_api_client
-            .btc_usd_price()
-            .await
-            .map_err(|e| e.to_string())?;
-        info!("btc_price: {:?}", btc_price);
-        let entry_price = btc_price.price as u64;
-        let result = order_wallet
-            .open_trader_order(
-                account_index,
-                OrderType::MARKET,
-                PositionType::LONG,
-                entry_price,
-                10,
-            )
+        order_wallet
+            .replace_trader_order(index, 57_000, Some(Leverage::from(3)))
             .await?;
-        info!("result: {:?}", result);
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        info!("tx_hash: {:?}", tx_hash);
-        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
-        let response = order_wallet.query_trader_order(account_index).await?;
-        info!("response: {:?}", response);
-        assert_eq!(response.order_status, OrderStatus::FILLED);
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&account_index)?
-                .io_type,
-            IOType::Memo
+            order_wallet.simulated_trader_order(index).unwrap().leverage,
+            3.0
         );
 
-        let result = order_wallet
-            .close_trader_order_sltp(
-                account_index,
-                OrderType::SLTP,
-                0.0,
-                Some(entry_price as f64 - 1000.0),
-                Some(entry_price as f64 + 1000.0),
-            )
+        // A filled order has nothing to replace.
+        let filled = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet
+            .open_trader_order(filled, OrderType::MARKET, PositionType::LONG, 50_000, 2)
             .await?;
-
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        println!("tx_hash: {:?}", tx_hash);
-        let response = order_wallet.query_trader_order(account_index).await?;
-        println!("response: {:?}", response);
+        let replaced = order_wallet
+            .replace_trader_order(filled, 49_000, None)
+            .await;
+        assert!(replaced.is_err());
         Ok(())
     }
 
     #[tokio::test]
-    #[serial]
-    async fn test_trading_to_trading() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        init_logger();
-        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
-        let zk_accounts = ZkAccountDB::new();
-        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+    async fn test_amend_simulated_limit_order() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let original = order_wallet
+            .open_trader_order(index, OrderType::LIMIT, PositionType::LONG, 45_000, 3)
+            .await?;
+        let amended = order_wallet
+            .amend_trader_order(index, 46_000)
+            .await
             .map_err(|e| e.to_string())?;
-        let (tx_result, sender_account_index) = order_wallet.funding_to_trading(6000).await?;
-        if tx_result.code != 0 {
-            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
-        }
-        let receiver_account_index = order_wallet
-            .trading_to_trading(sender_account_index)
+        assert_ne!(amended, original);
+        assert_eq!(order_wallet.request_id(index)?, amended.as_str());
+        let order = order_wallet.simulated_trader_order(index).unwrap();
+        assert_eq!(order.entry_price, 46_000.0);
+        assert_eq!(order.leverage, 3.0);
+
+        // A filled order is reported as such, not as a generic failure.
+        let filled = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let market = order_wallet
+            .open_trader_order(filled, OrderType::MARKET, PositionType::LONG, 50_000, 2)
             .await?;
-        assert_ne!(sender_account_index, receiver_account_index);
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&sender_account_index)?
-                .on_chain,
-            false
+            order_wallet.amend_trader_order(filled, 49_000).await,
+            Err(AmendError::AlreadyFilled {
+                account_index: filled,
+                request_id: market,
+            })
         );
-        assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&receiver_account_index)?
-                .balance,
-            6000
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_amend_against_mock_relayer() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{TxHashBuilder, pending_limit};
+
+        let relayer = MockRelayer::new();
+        relayer.accept("submit_trade_order", "REQ-ORIGINAL");
+        let (mut order_wallet, index, _) = cached_utxo_wallet(&relayer, 1).await?;
+        order_wallet
+            .open_trader_order(index, OrderType::LIMIT, PositionType::SHORT, 52_000, 5)
+            .await?;
+
+        // The status check finds the limit pending, so it is cancelled and
+        // reopened at the new price.
+        let status = |request_id: &str, order_status: OrderStatus| {
+            TxHashBuilder::new()
+                .request_id(request_id)
+                .order_type(OrderType::LIMIT)
+                .order_status(order_status)
+                .to_json()
+        };
+        relayer.respond_once(
+            "transaction_hashes",
+            serde_json::json!([status("REQ-ORIGINAL", OrderStatus::PENDING)]),
         );
-        assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&receiver_account_index)?
-                .on_chain,
-            true
+        relayer.respond("trader_order_info", pending_limit());
+        relayer.accept("cancel_trader_order", "REQ-CANCEL");
+        relayer.respond(
+            "transaction_hashes",
+            serde_json::json!([status("REQ-CANCEL", OrderStatus::CANCELLED)]),
         );
-        let btc_price = order_wallet
-            .relayer_api_client
-            .btc_usd_price()
+        relayer.accept("submit_trade_order", "REQ-AMENDED");
+        let amended = order_wallet
+            .amend_trader_order(index, 51_000)
             .await
             .map_err(|e| e.to_string())?;
-        info!("btc_price: {:?}", btc_price);
-        let entry_price = btc_price.price as u64;
-        let result = order_wallet
-            .open_trader_order(
-                receiver_account_index,
-                OrderType::MARKET,
-                PositionType::LONG,
-                entry_price,
-                10,
-            )
+        assert_eq!(amended, "REQ-AMENDED");
+        assert_eq!(order_wallet.request_id(index)?, "REQ-AMENDED");
+        assert_eq!(relayer.call_count("cancel_trader_order"), 1);
+        assert_eq!(relayer.call_count("submit_trade_order"), 2);
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+
+        // Once the relayer reports a fill, nothing is cancelled.
+        relayer.respond_once(
+            "transaction_hashes",
+            serde_json::json!([status("REQ-AMENDED", OrderStatus::FILLED)]),
+        );
+        assert_eq!(
+            order_wallet.amend_trader_order(index, 50_000).await,
+            Err(AmendError::AlreadyFilled {
+                account_index: index,
+                request_id: amended,
+            })
+        );
+        assert_eq!(relayer.call_count("cancel_trader_order"), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_or_cancel_follows_order_status() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        let pending = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let limit = order_wallet
+            .open_trader_order(pending, OrderType::LIMIT, PositionType::LONG, 45_000, 2)
             .await?;
-        info!("result: {:?}", result);
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        info!("tx_hash: {:?}", tx_hash);
-        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
-        let response = order_wallet
-            .query_trader_order(receiver_account_index)
+        let outcome = order_wallet
+            .close_or_cancel_trader_order(pending, OrderType::MARKET, 0.0)
             .await?;
-        info!("response: {:?}", response);
-        assert_eq!(response.order_status, OrderStatus::FILLED);
+        assert_eq!(outcome, CloseOutcome::Cancelled(limit));
+        let order = order_wallet.simulated_trader_order(pending).unwrap();
+        assert_eq!(order.order_status, OrderStatus::CANCELLED);
+
+        let filled = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let market = order_wallet
+            .open_trader_order(filled, OrderType::MARKET, PositionType::LONG, 50_000, 2)
+            .await?;
+        let outcome = order_wallet
+            .close_or_cancel_trader_order(filled, OrderType::MARKET, 0.0)
+            .await?;
+        assert_eq!(outcome, CloseOutcome::Settled(market));
+        // Closing again finds the position already settled.
+        let outcome = order_wallet
+            .close_or_cancel_trader_order(filled, OrderType::MARKET, 0.0)
+            .await?;
+        assert_eq!(outcome, CloseOutcome::AlreadySettled);
+        assert_eq!(outcome.request_id(), None);
+
+        // Cancelled orders are neither pending nor open.
+        assert!(order_wallet
+            .close_or_cancel_trader_order(pending, OrderType::MARKET, 0.0)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_or_cancel_against_mock_relayer() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{TraderOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_relayer(Arc::new(relayer.clone()));
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet
+            .zk_accounts
+            .update_io_type(&index, IOType::Memo, Some(TXType::ORDERTX))?;
+        order_wallet.cache_request_id(index, "REQ-OPEN", None);
+
+        // The relayer reports the limit pending, so it is cancelled.
+        let order = |order_status: OrderStatus| {
+            TraderOrderBuilder::new()
+                .order_type(OrderType::LIMIT)
+                .order_status(order_status)
+                .to_json()
+        };
+        relayer.respond("trader_order_info", order(OrderStatus::PENDING));
+        relayer.accept("cancel_trader_order", "REQ-CANCEL");
+        relayer.respond(
+            "transaction_hashes",
+            vec![TxHashBuilder::new()
+                .request_id("REQ-CANCEL")
+                .order_status(OrderStatus::CANCELLED)
+                .to_json()],
+        );
+        let outcome = order_wallet
+            .close_or_cancel_trader_order(index, OrderType::MARKET, 0.0)
+            .await?;
+        assert_eq!(outcome, CloseOutcome::Cancelled("REQ-CANCEL".to_string()));
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+        assert_eq!(order_wallet.zk_accounts.get_balance(&index)?, 1_000);
+        assert_eq!(relayer.call_count("settle_trade_order"), 0);
+
+        // A cancelled order is neither cancelled again nor closed.
+        relayer.respond("trader_order_info", order(OrderStatus::CANCELLED));
+        let err = order_wallet
+            .close_or_cancel_trader_order(index, OrderType::MARKET, 0.0)
+            .await
+            .unwrap_err();
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&receiver_account_index)?
-                .io_type,
-            IOType::Memo
+            err,
+            "Order cannot be closed or cancelled, status: CANCELLED"
         );
+        assert_eq!(relayer.call_count("cancel_trader_order"), 1);
         Ok(())
     }
 
     #[tokio::test]
-    #[serial]
-    async fn test_open_lend_order() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        init_logger();
-        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
-        let zk_accounts = ZkAccountDB::new();
-        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
-            .map_err(|e| e.to_string())?;
-        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
-        if tx_result.code != 0 {
-            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+    async fn test_simulated_batch_reports_each_order() -> Result<(), String> {
+        use crate::relayer_module::simulation::SimulationConfig;
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig::default()));
+        let buy = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let sell = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+
+        let results = order_wallet
+            .open_trader_orders_batch(vec![
+                TraderOrderParams::new(buy, OrderType::LIMIT, PositionType::LONG, 49_000, 2),
+                TraderOrderParams::new(sell, OrderType::LIMIT, PositionType::SHORT, 51_000, 2),
+                TraderOrderParams::new(buy, OrderType::LIMIT, PositionType::SHORT, 52_000, 2),
+                TraderOrderParams::new(99, OrderType::LIMIT, PositionType::LONG, 48_000, 2),
+            ])
+            .await?;
+        let indices: Vec<AccountIndex> = results.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, vec![buy, sell, buy, 99]);
+        assert_eq!(
+            order_wallet.request_id(buy)?,
+            results[0].1.as_deref().unwrap()
+        );
+        assert_eq!(
+            order_wallet.request_id(sell)?,
+            results[1].1.as_deref().unwrap()
+        );
+        assert!(results[2]
+            .1
+            .as_ref()
+            .is_err_and(|e| e.contains("more than once")));
+        assert!(results[3].1.is_err());
+        let sell_order = order_wallet.simulated_trader_order(sell).unwrap();
+        assert_eq!(sell_order.position_type, PositionType::SHORT);
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_reflects_local_state() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        order_wallet.wallet.balance_sats = 50_000;
+        let idle = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let trading = order_wallet
+            .zk_accounts
+            .generate_new_account(2_000, &order_wallet.seed.secret()?)?;
+        let lending = order_wallet
+            .zk_accounts
+            .generate_new_account(3_000, &order_wallet.seed.secret()?)?;
+        for index in [idle, trading, lending] {
+            order_wallet.zk_accounts.update_on_chain(&index, true)?;
         }
-        let result = order_wallet.open_lend_order(account_index).await?;
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
-        let response = order_wallet.query_lend_order(account_index).await?;
-        assert_eq!(response.order_status, OrderStatus::FILLED);
+        order_wallet
+            .zk_accounts
+            .update_io_type(&trading, IOType::Memo, Some(TXType::ORDERTX))?;
+        order_wallet
+            .zk_accounts
+            .update_io_type(&lending, IOType::Memo, Some(TXType::LENDTX))?;
+        order_wallet.cache_request_id(trading, "req-trading", None);
+
+        let summary = order_wallet.summary();
+        assert_eq!(summary.wallet_balance_sats, 50_000);
+        assert_eq!(summary.coin_balance, 1_000);
+        assert_eq!(summary.locked_margin, 5_000);
+        assert_eq!(summary.active_trader_orders, 1);
+        assert_eq!(summary.active_lend_orders, 1);
+        assert!(!summary.statuses_refreshed);
+        let indices: Vec<AccountIndex> = summary.accounts.iter().map(|a| a.account_index).collect();
+        assert_eq!(indices, vec![idle, trading, lending]);
+        let trading_entry = &summary.accounts[1];
+        assert_eq!(trading_entry.order_kind, Some(OrderKind::Trader));
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&account_index)?
-                .io_type,
-            IOType::Memo
+            trading_entry.pending_request_id.as_deref(),
+            Some("req-trading")
         );
-        let result = order_wallet.close_lend_order(account_index).await?;
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        assert_eq!(tx_hash.order_status, OrderStatus::SETTLED);
-        let response = order_wallet.query_lend_order(account_index).await?;
-        assert_eq!(response.order_status, OrderStatus::SETTLED);
-        order_wallet.unlock_lend_order(account_index).await?;
-        order_wallet.sync_account_state(account_index).await?;
-        let zk_account = order_wallet.zk_accounts.get_account(&account_index)?;
-        assert_eq!(zk_account.io_type, IOType::Coin);
-        assert_eq!(zk_account.balance, response.new_lend_state_amount as u64);
+        assert!(summary.accounts[0].order_kind.is_none());
+        assert!(serde_json::to_string(&summary).is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_rejects_invalid_accounts_before_submitting() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let off_chain = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        // Nothing passes the account checks, so neither the UTXO fetcher nor
+        // the relayer is contacted.
+        let results = order_wallet
+            .open_trader_orders_batch(vec![
+                TraderOrderParams::new(off_chain, OrderType::MARKET, PositionType::LONG, 50_000, 2),
+                TraderOrderParams::new(off_chain, OrderType::MARKET, PositionType::LONG, 50_000, 2),
+            ])
+            .await?;
+        assert_eq!(results.len(), 2);
+        assert!(results[0]
+            .1
+            .as_ref()
+            .is_err_and(|e| e.contains("does not exist on chain")));
+        assert!(results[1]
+            .1
+            .as_ref()
+            .is_err_and(|e| e.contains("more than once")));
+        assert!(order_wallet.request_ids.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_closed_at_takes_the_earliest_settlement() {
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
+
+        let record = |order_id: &str, status: OrderStatus, millis: i64| {
+            TxHashBuilder::new()
+                .order_id(order_id)
+                .order_status(status)
+                .field("datetime", millis.to_string())
+                .build()
+        };
+        let txs = vec![
+            record("ORDER", OrderStatus::FILLED, 1_000),
+            record("ORDER", OrderStatus::SETTLED, 7_200_000),
+            record("OTHER", OrderStatus::SETTLED, 3_600_000),
+            record("ORDER", OrderStatus::SETTLED, 10_800_000),
+        ];
+        assert_eq!(
+            order_closed_at("ORDER", &txs),
+            Some(DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::hours(2))
+        );
+        assert_eq!(order_closed_at("ORDER", &txs[..1]), None);
+    }
+
+    #[tokio::test]
+    async fn test_replace_refetches_when_cancel_consumed_input() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
+        let fetcher = Arc::new(CountingFetcher::default());
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_utxo_fetcher(fetcher.clone());
+        let index = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        order_wallet
+            .zk_accounts
+            .update_on_chain(&index, true)
+            .unwrap();
+
+        let unspent = TxHashBuilder::new()
+            .order_type(OrderType::LIMIT)
+            .order_status(OrderStatus::CANCELLED)
+            .build();
+        let consumed = TxHashBuilder::new()
+            .order_type(OrderType::LIMIT)
+            .order_status(OrderStatus::CANCELLED)
+            .output("0a0b0c")
+            .build();
+        assert!(cancel_left_input_unspent(&unspent));
+        assert!(!cancel_left_input_unspent(&consumed));
+        assert!(!cancel_left_input_unspent(&TxHashBuilder::new().build()));
+
+        // Nothing to carry over: the relayer reported the input consumed, and
+        // an unspent input is only reused if its UTXO is cached.
+        assert!(!order_wallet.carry_utxo_over_cancel(index, &consumed)?);
+        assert!(!order_wallet.carry_utxo_over_cancel(index, &unspent)?);
+        assert!(!order_wallet.has_reusable_utxo(index));
+        assert!(order_wallet.utxo_freshness(index).is_none());
+
+        // So the replacement open falls back to a fetch.
+        let err = order_wallet
+            .open_trader_order(index, OrderType::LIMIT, PositionType::LONG, 48_000, 2)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "utxo fetch unavailable");
+        assert_eq!(fetcher.calls(), 1);
         Ok(())
     }
 
     #[tokio::test]
-    #[serial]
-    #[ignore]
-    async fn test_create_order_with_cancel() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        init_logger();
-        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
-        let zk_accounts = ZkAccountDB::new();
+    async fn test_replace_reuses_the_input_the_cancel_left_unspent() -> Result<(), String> {
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{TxHashBuilder, pending_limit};
+
+        let relayer = MockRelayer::new();
+        relayer.accept("submit_trade_order", "REQ-ORIGINAL");
+        // One fetch, for the original open; a second would find no UTXO.
+        let (mut order_wallet, index, fetcher) = cached_utxo_wallet(&relayer, 1).await?;
+        order_wallet
+            .open_trader_order(index, OrderType::LIMIT, PositionType::SHORT, 52_000, 5)
+            .await?;
+        assert!(fetcher.replies.lock().unwrap().is_empty());
+
+        relayer.respond("trader_order_info", pending_limit());
+        relayer.accept("cancel_trader_order", "REQ-CANCEL");
+        let cancelled = TxHashBuilder::new()
+            .request_id("REQ-CANCEL")
+            .order_type(OrderType::LIMIT)
+            .order_status(OrderStatus::CANCELLED)
+            .to_json();
+        relayer.respond("transaction_hashes", serde_json::json!([cancelled]));
+        relayer.accept("submit_trade_order", "REQ-REPLACED");
+
+        let receipt = order_wallet
+            .replace_trader_order(index, 51_000, None)
+            .await?;
+        assert_eq!(receipt.cancel_request_id, "REQ-CANCEL");
+        assert_eq!(receipt.request_id, "REQ-REPLACED");
+        assert!(receipt.reused_utxo);
+        assert_eq!(relayer.call_count("submit_trade_order"), 2);
+        assert_eq!(order_wallet.request_id(index)?, "REQ-REPLACED");
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+        Ok(())
+    }
 
-        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
+    #[tokio::test]
+    async fn test_address_book_labels_and_strict_mode() -> Result<(), String> {
+        let path =
+            std::env::temp_dir().join(format!("nyks-address-book-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_address_book_file(&path)
             .map_err(|e| e.to_string())?;
-        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
-        if tx_result.code != 0 {
-            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
-        }
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(5_000, &order_wallet.seed.secret()?)?;
+        let receiver_wallet = OrderWallet::import_from_mnemonic(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            None,
+        )?;
+        let mut receiver_accounts = ZkAccountDB::new();
+        let seed = receiver_wallet.seed.secret()?;
+        let receiver = receiver_accounts.generate_new_account(0, &seed)?;
+        let stranger = receiver_accounts.generate_new_account(0, &seed)?;
+        let receiver_address = receiver_accounts.get_account_address(&receiver)?;
+        let stranger_address = receiver_accounts.get_account_address(&stranger)?;
 
-        let btc_price = order_wallet
-            .relayer_api_client
-            .btc_usd_price()
+        order_wallet
+            .add_address_book_entry("exchange", &receiver_address, AddressKind::ZkOs)
+            .map_err(|e| e.to_string())?;
+        assert!(order_wallet
+            .add_address_book_entry("typo", &receiver_address[..100], AddressKind::ZkOs)
+            .is_err());
+        assert_eq!(
+            order_wallet.resolve_recipient("exchange", AddressKind::ZkOs, false),
+            Ok(receiver_address.clone())
+        );
+        assert!(order_wallet.is_known_receiver(&receiver_address));
+
+        // Strict mode refuses raw addresses outside the book before anything
+        // else is checked; the label, own accounts and `allow_unknown` pass.
+        order_wallet.set_strict_address_book(true);
+        let err = order_wallet
+            .transfer_amount_to_address(sender, &stranger_address, 0, &TransferOptions::default())
             .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ReceiverCheckError::AddressBook(AddressBookError::UnknownAddress(_))
+        ));
+        let unchecked = TransferOptions::unchecked();
+        for address in ["exchange", stranger_address.as_str()] {
+            let err = order_wallet
+                .transfer_amount_to_address(sender, address, 0, &unchecked)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("greater than zero"), "{}", err);
+        }
+        let own = order_wallet.zk_accounts.get_account_address(&sender)?;
+        assert!(order_wallet
+            .resolve_recipient(&own, AddressKind::ZkOs, false)
+            .is_ok());
+        let twilight = order_wallet.wallet.twilightaddress.clone();
+        assert!(order_wallet
+            .resolve_recipient(&twilight, AddressKind::Twilight, false)
+            .is_ok());
+        assert!(order_wallet
+            .resolve_recipient("exchange", AddressKind::Twilight, true)
+            .is_err());
+
+        // The file holds the book for the next wallet, and an export of it
+        // adds nothing new.
+        let mut reloaded = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_address_book_file(&path)
             .map_err(|e| e.to_string())?;
-        info!("btc_price: {:?}", btc_price);
-        let entry_price = btc_price.price as u64;
-        let result = order_wallet
-            .open_trader_order(
-                account_index,
-                OrderType::LIMIT,
-                PositionType::LONG,
-                entry_price - 1000,
-                10,
-            )
-            .await?;
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        assert_eq!(tx_hash.order_status, OrderStatus::PENDING);
-        let response = order_wallet.query_trader_order(account_index).await?;
-        assert_eq!(response.order_status, OrderStatus::PENDING);
+        assert_eq!(reloaded.address_book(), order_wallet.address_book());
+        let export = order_wallet
+            .export_address_book()
+            .map_err(|e| e.to_string())?;
+        assert!(reloaded
+            .import_address_book(&export)
+            .map_err(|e| e.to_string())?
+            .is_empty());
+        reloaded
+            .remove_address_book_entry("exchange")
+            .map_err(|e| e.to_string())?;
+        let json = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        assert!(AddressBook::from_json(&json)
+            .map_err(|e| e.to_string())?
+            .is_empty());
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_receiver_checks_before_foreign_transfer() -> Result<(), String> {
+        use crate::relayer_module::receiver_check::{
+            create_ownership_proof, new_ownership_challenge, OwnershipProof,
+        };
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(5_000, &order_wallet.seed.secret()?)?;
+        // The receiver is another wallet; its accounts are derived from a different seed.
+        let receiver_wallet = OrderWallet::import_from_mnemonic(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            None,
+        )?;
+        let mut receiver_accounts = ZkAccountDB::new();
+        let receiver =
+            receiver_accounts.generate_new_account(0, &receiver_wallet.seed.secret()?)?;
+        let other = receiver_accounts.generate_new_account(0, &receiver_wallet.seed.secret()?)?;
+        let receiver_address = receiver_accounts.get_account_address(&receiver)?;
+        let receiver_key = receiver_wallet.get_secret_key(receiver)?;
+        let other_key = receiver_wallet.get_secret_key(other)?;
+
+        // Malformed input is rejected before anything else.
+        let options = TransferOptions::default();
+        let truncated = &receiver_address[..100];
+        assert!(matches!(
+            order_wallet.check_receiver(sender, truncated, 5_000, &options),
+            Err(ReceiverCheckError::Malformed(_))
+        ));
+
+        // Ownership proof round trip.
+        let challenge = new_ownership_challenge();
+        let proof = OwnershipProof {
+            proof: create_ownership_proof(&receiver_key, &receiver_address, &challenge)
+                .map_err(|e| e.to_string())?,
+            challenge: challenge.clone(),
+        };
+        let with_proof = TransferOptions {
+            require_ownership_proof: true,
+            ownership_proof: Some(proof),
+            confirm_new_receiver: true,
+            ..TransferOptions::default()
+        };
+        assert!(order_wallet
+            .check_receiver(sender, &receiver_address, 5_000, &with_proof)
+            .is_ok());
+        let forged = TransferOptions {
+            ownership_proof: Some(OwnershipProof {
+                proof: create_ownership_proof(&other_key, &receiver_address, &challenge)
+                    .map_err(|e| e.to_string())?,
+                challenge,
+            }),
+            ..with_proof.clone()
+        };
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&account_index)?
-                .io_type,
-            IOType::Memo
+            order_wallet.check_receiver(sender, &receiver_address, 5_000, &forged),
+            Err(ReceiverCheckError::InvalidProof(receiver_address.clone()))
         );
+        let missing = TransferOptions {
+            ownership_proof: None,
+            ..with_proof
+        };
+        assert!(matches!(
+            order_wallet.check_receiver(sender, &receiver_address, 5_000, &missing),
+            Err(ReceiverCheckError::ProofRequired(_))
+        ));
+
+        // Confirmation gate for first-time receivers above the threshold.
+        let gated = TransferOptions {
+            confirmation_threshold: 1_000,
+            ..TransferOptions::default()
+        };
+        assert!(matches!(
+            order_wallet.check_receiver(sender, &receiver_address, 5_000, &gated),
+            Err(ReceiverCheckError::ConfirmationRequired { amount: 5_000, threshold: 1_000, .. })
+        ));
+        assert!(order_wallet
+            .check_receiver(sender, &receiver_address, 500, &gated)
+            .is_ok());
+        let own = order_wallet.zk_accounts.get_account_address(&sender)?;
+        assert!(order_wallet.check_receiver(sender, &own, 5_000, &gated).is_ok());
+        order_wallet.add_known_receiver(&receiver_address);
+        assert!(order_wallet
+            .check_receiver(sender, &receiver_address, 5_000, &gated)
+            .is_ok());
+        assert!(order_wallet
+            .check_receiver(sender, truncated, 5_000, &TransferOptions::unchecked())
+            .is_ok());
+
+        // Checks fail before anything is signed or recorded on the account.
+        let err = order_wallet
+            .transfer_to_address(sender, truncated, &TransferOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ReceiverCheckError::Malformed(_)));
+        assert!(order_wallet.zk_accounts.get_account(&sender)?.last_error.is_none());
+
+        // Refused before anything is fetched or signed.
+        let unchecked = TransferOptions::unchecked();
+        for (amount, address, reason) in [
+            (0, receiver_address.as_str(), "greater than zero"),
+            (1_000, own.as_str(), "cannot transfer to itself"),
+        ] {
+            let err = order_wallet
+                .transfer_amount_to_address(sender, address, amount, &unchecked)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains(reason), "{}", err);
+        }
+        order_wallet
+            .zk_accounts
+            .update_io_type(&sender, IOType::Memo, None)?;
+        let err = order_wallet
+            .transfer_amount_to_address(sender, &receiver_address, 1_000, &unchecked)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Memo state"), "{}", err);
+        Ok(())
+    }
 
-        let result = order_wallet.cancel_trader_order(account_index).await?;
+    #[derive(Debug, Default)]
+    struct RecordingBroadcaster {
+        sent: std::sync::Mutex<usize>,
+    }
 
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        assert_eq!(tx_hash.order_status, OrderStatus::CANCELLED);
-        let response = order_wallet.query_trader_order(account_index).await?;
-        assert_eq!(response.order_status, OrderStatus::CANCELLED);
-        let zk_account = order_wallet.zk_accounts.get_account(&account_index)?;
-        assert_eq!(zk_account.io_type, IOType::Coin);
-        assert_eq!(zk_account.balance, response.available_margin as u64);
+    impl ChainBroadcaster for RecordingBroadcaster {
+        fn broadcast(&self, _tx: crate::compat::transaction::Transaction) -> Result<String, String> {
+            *self.sent.lock().unwrap() += 1;
+            Ok("fixture-tx-hash".to_string())
+        }
+    }
+
+    #[test]
+    fn test_sdk_transfer_adapter_feeds_broadcaster() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let receiver = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        let input = order_wallet
+            .zk_accounts
+            .get_account(&sender)?
+            .get_new_account_input()?;
+        let receiver_account = order_wallet.zk_accounts.get_account(&receiver)?.account;
+
+        // Same arguments trading_to_trading passes.
+        let transfer = SdkTransferBuilder.single_receiver(
+            order_wallet.get_secret_key(sender)?,
+            input,
+            receiver_account,
+            1_000,
+            false,
+            0,
+            1u64,
+        );
+        assert!(hex::decode(&transfer.encrypt_scalar_hex).is_ok_and(|bytes| bytes.len() == 32));
+        let tx = transfer.tx.ok_or("SDK built no transaction")?;
 
+        let broadcaster = Arc::new(RecordingBroadcaster::default());
+        let order_wallet = order_wallet.with_chain_broadcaster(broadcaster.clone());
+        assert_eq!(order_wallet.chain_broadcaster.broadcast(tx)?, "fixture-tx-hash");
+        assert_eq!(*broadcaster.sent.lock().unwrap(), 1);
         Ok(())
     }
 
     #[tokio::test]
-    #[serial]
-    #[ignore]
-    async fn test_get_wallet_list_from_db() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        init_logger();
-        let wallet_list = OrderWallet::get_wallet_list_from_db(None).map_err(|e| e.to_string())?;
-        println!("wallet_list: {:?}", wallet_list);
+    async fn test_transfer_keeps_its_hash_when_the_sender_is_not_finalized() -> Result<(), String> {
+        use crate::relayer_module::test_fixtures::coin_utxo;
 
+        let order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?;
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(5_000, &order_wallet.seed.secret()?)?;
+        order_wallet.zk_accounts.update_on_chain(&sender, true)?;
+        let foreign = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        let address = order_wallet.zk_accounts.get_account_address(&foreign)?;
+        order_wallet.zk_accounts.remove_account(&foreign)?;
+        let before = order_wallet.zk_accounts.get_account(&sender)?;
+        // The sync before signing finds the UTXO; the change UTXO is not found.
+        let broadcaster = Arc::new(RecordingBroadcaster::default());
+        let mut order_wallet = order_wallet
+            .with_chain_broadcaster(broadcaster.clone())
+            .with_utxo_fetcher(Arc::new(ScriptedFetcher::new(vec![Ok(coin_utxo(&before))])));
+
+        let err = order_wallet
+            .transfer_amount_to_address(sender, &address, 2_000, &TransferOptions::unchecked())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(*broadcaster.sent.lock().unwrap(), 1);
+        assert!(err.contains("fixture-tx-hash was broadcast"), "{}", err);
+        // Balance and commitment stay together until the change UTXO is found.
+        let account = order_wallet.zk_accounts.get_account(&sender)?;
+        assert_eq!(account.balance, 5_000);
+        assert_eq!(account.qq_address, before.qq_address);
+
+        let pending = order_wallet.pending_operations();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].kind, PendingOperationKind::TransferToAddress);
+        match &pending[0].inputs {
+            OperationInputs::TransferToAddress {
+                tx_hash, amount, ..
+            } => assert_eq!((tx_hash.as_str(), *amount), ("fixture-tx-hash", 2_000)),
+            other => panic!("unexpected inputs {:?}", other),
+        }
+        assert!(err.contains(&pending[0].id), "{}", err);
+
+        let mut order_wallet = order_wallet
+            .with_utxo_fetcher(Arc::new(ScriptedFetcher::new(vec![Ok(coin_utxo(&before))])));
+        let resumed = order_wallet.resume_operation(&pending[0].id).await?;
+        assert!(resumed.is_done());
+        assert_eq!(order_wallet.zk_accounts.get_balance(&sender)?, 3_000);
+        assert!(order_wallet.utxo_details.contains_key(&sender));
+        assert!(order_wallet.pending_operations().is_empty());
         Ok(())
     }
 
+    #[derive(Debug)]
+    struct PanickingBuilder;
+
+    impl TransferBuilder for PanickingBuilder {
+        fn single_receiver(
+            &self,
+            _sender_key: RistrettoSecretKey,
+            _input: Input,
+            _receiver: String,
+            _amount: u64,
+            _address_input: bool,
+            _updated_sender_balance: u64,
+            _fee: u64,
+        ) -> crate::compat::SingleTransfer {
+            panic!("transfer proof failed")
+        }
+
+        fn multiple_receivers(
+            &self,
+            _senders: Vec<Sender>,
+            _input: Input,
+            _sender_key: RistrettoSecretKey,
+            _updated_sender_balances: Vec<u64>,
+            _updated_receiver_balances: Vec<u64>,
+            _receiver_scalars: Option<&[curve25519_dalek::scalar::Scalar]>,
+            _fee: u64,
+        ) -> Result<crate::compat::MultiTransfer, String> {
+            panic!("transfer proof failed")
+        }
+
+        fn burn_message(
+            &self,
+            _input: Input,
+            _amount: u64,
+            _encrypt_scalar_hex: String,
+            _sender_key: RistrettoSecretKey,
+            _account: String,
+        ) -> String {
+            panic!("transfer proof failed")
+        }
+    }
+
+    #[derive(Debug)]
+    struct PanickingBroadcaster;
+
+    impl ChainBroadcaster for PanickingBroadcaster {
+        fn broadcast(&self, _tx: Transaction) -> Result<String, String> {
+            panic!("broadcast exploded")
+        }
+    }
+
     #[tokio::test]
-    #[serial]
-    #[ignore]
-    async fn test_with_db() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        init_logger();
-        let mut order_wallet = OrderWallet::new(None).map_err(|e| e.to_string())?;
-        order_wallet.with_db(None, None)?;
-        let wallet_list = OrderWallet::get_wallet_list_from_db(None).map_err(|e| e.to_string())?;
-        println!("wallet_list: {:?}", wallet_list);
+    async fn test_client_sdk_panics_become_typed_errors() -> Result<(), String> {
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_transfer_builder(Arc::new(PanickingBuilder))
+            .with_chain_broadcaster(Arc::new(PanickingBroadcaster));
+        let sender = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let receiver = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        let receiver_account = order_wallet.zk_accounts.get_account(&receiver)?.account;
+        let input = order_wallet
+            .zk_accounts
+            .get_account(&sender)?
+            .get_new_account_input()?;
+
+        match order_wallet.build_single_transfer(sender, input, receiver_account.clone(), 1_000, 0)
+        {
+            Err(WalletError::ClientSdk { operation, detail }) => {
+                assert_eq!(operation, "single_receiver");
+                assert!(detail.starts_with(&format!("account {}, amount 1000", sender)));
+                assert!(detail.ends_with("panicked: transfer proof failed"));
+            }
+            other => panic!("expected a ClientSdk error, got {:?}", other.map(|_| ())),
+        }
+
+        // The wallet is still usable, and a panicking broadcast is contained too.
+        let order_wallet = order_wallet.with_transfer_builder(Arc::new(SdkTransferBuilder));
+        let input = order_wallet
+            .zk_accounts
+            .get_account(&sender)?
+            .get_new_account_input()?;
+        let (tx, _) = order_wallet
+            .build_single_transfer(sender, input, receiver_account, 1_000, 0)
+            .map_err(|e| e.to_string())?;
+        let err = order_wallet
+            .broadcast_tx(sender, tx.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            WalletError::ClientSdk { operation, .. } if operation == "broadcast"
+        ));
+        assert!(err.to_string().contains("panicked: broadcast exploded"));
+
+        let order_wallet =
+            order_wallet.with_chain_broadcaster(Arc::new(RecordingBroadcaster::default()));
+        let tx_hash = order_wallet
+            .broadcast_tx(sender, tx)
+            .await
+            .map_err(|e| e.to_string())??;
+        assert_eq!(tx_hash, "fixture-tx-hash");
         Ok(())
     }
 
     #[tokio::test]
-    #[serial]
-    #[ignore]
-    async fn test_trading_to_trading_multiple_accounts() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        init_logger();
-        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
-        let zk_accounts = ZkAccountDB::new();
-        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
-            .map_err(|e| e.to_string())?;
-        // order_wallet.with_db(None, Some("test_wallet_multiple_accounts10".to_string()))?;
-        let (tx_result, sender_account_index) = order_wallet.funding_to_trading(40000).await?;
-        if tx_result.code != 0 {
-            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
-        }
-        let balances = vec![5000, 1000, 8000, 600];
-        let new_account_balances = order_wallet
-            .trading_to_trading_multiple_accounts(sender_account_index, balances)
-            .await?;
-        println!("new_account_balances: {:?}", new_account_balances);
-        println!("zk_accounts: {:?}", order_wallet.zk_accounts);
+    async fn test_open_and_wait_on_simulated_clock() -> Result<(), String> {
+        use crate::relayer_module::order_wait::{
+            OpenWaitError, TraderOrderParams, TraderOrderSnapshot,
+        };
+        use crate::relayer_module::simulation::SimulationConfig;
+        let real_start = std::time::Instant::now();
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_execution_mode(ExecutionMode::Simulated(SimulationConfig {
+                limit_order_ttl: Some(chrono::Duration::seconds(2)),
+                ..SimulationConfig::default()
+            }));
+        let clock = order_wallet.simulated_clock().unwrap();
+        let market = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let limit = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
 
-        let btc_price = order_wallet
-            .relayer_api_client
-            .btc_usd_price()
+        // A market order fills on the first poll.
+        let params =
+            TraderOrderParams::new(market, OrderType::MARKET, PositionType::LONG, 50_000, 2);
+        let receipt = order_wallet
+            .open_trader_order_and_wait(params.clone(), Duration::from_secs(5))
             .await
             .map_err(|e| e.to_string())?;
-        info!("btc_price: {:?}", btc_price);
-        let entry_price = btc_price.price as u64;
-        info!(
-            "zk_accounts: {:?}",
-            order_wallet
-                .zk_accounts
-                .get_account(&new_account_balances[0].0)
+        assert_eq!(receipt.request_id, order_wallet.request_id(market)?);
+        assert_eq!(receipt.waited, Duration::ZERO);
+        assert!(matches!(receipt.order, TraderOrderSnapshot::Simulated(_)));
+        assert_eq!(receipt.order.order_status(), &OrderStatus::FILLED);
+        assert!(order_wallet
+            .zk_accounts
+            .get_account(&market)?
+            .last_error
+            .is_none());
+
+        // A second open on the same account is refused before any waiting.
+        let err = order_wallet
+            .open_trader_order_and_wait(params, Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OpenWaitError::SubmissionFailed(_)));
+
+        // A limit below the mark outlives a short wait and stays open.
+        let started = clock.now();
+        let params = TraderOrderParams::new(limit, OrderType::LIMIT, PositionType::LONG, 40_000, 2);
+        let err = order_wallet
+            .open_trader_order_and_wait(params, Duration::from_millis(1_500))
+            .await
+            .unwrap_err();
+        let request_id = order_wallet.request_id(limit)?;
+        assert_eq!(
+            err,
+            OpenWaitError::TimedOutStillPending {
+                account_index: limit,
+                request_id: request_id.clone(),
+                waited: Duration::from_millis(1_500),
+            }
         );
-        info!("waiting 10 seconds");
-        sleep(Duration::from_secs(10)).await;
-        let result = order_wallet
-            .open_trader_order(
-                new_account_balances[0].0,
-                OrderType::MARKET,
-                PositionType::LONG,
-                entry_price,
-                10,
+        assert_eq!(clock.now() - started, chrono::Duration::milliseconds(1_500));
+        let order = order_wallet.simulated_trader_order(limit).unwrap();
+        assert_eq!(order.order_status, OrderStatus::PENDING);
+        assert!(order_wallet
+            .zk_accounts
+            .get_account(&limit)?
+            .last_error
+            .is_none());
+
+        // Once the TTL cancels it, waiting again reports the cancellation.
+        clock.advance(Duration::from_secs(1));
+        let err = order_wallet
+            .wait_for_open(
+                limit,
+                &request_id,
+                OrderKind::Trader,
+                Duration::from_secs(10),
             )
-            .await?;
-        let tx_hash = fetch_tx_hash_with_retry(&result, &order_wallet.relayer_api_client).await?;
-        assert_eq!(tx_hash.order_status, OrderStatus::FILLED);
-        let response = order_wallet
-            .query_trader_order(new_account_balances[0].0)
-            .await?;
-        assert_eq!(response.order_status, OrderStatus::FILLED);
+            .await
+            .unwrap_err();
         assert_eq!(
-            order_wallet
-                .zk_accounts
-                .get_account(&new_account_balances[0].0)?
-                .io_type,
-            IOType::Memo
+            err,
+            OpenWaitError::Cancelled {
+                account_index: limit,
+                request_id,
+            }
         );
+        let stored = order_wallet
+            .zk_accounts
+            .get_account(&limit)?
+            .last_error
+            .ok_or("last_error not recorded")?;
+        assert_eq!(stored.operation, "open_trader_order_and_wait");
+        assert!(real_start.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
 
+    #[tokio::test]
+    async fn test_open_and_wait_unlocks_a_rejected_order() -> Result<(), String> {
+        use crate::relayer_module::clock::ManualClock;
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::TxHashBuilder;
+
+        let relayer = MockRelayer::new();
+        relayer.accept("submit_trade_order", "REQ-REJECTED");
+        let pending = TxHashBuilder::new()
+            .request_id("REQ-REJECTED")
+            .order_status(OrderStatus::PENDING)
+            .to_json();
+        let rejected = TxHashBuilder::new()
+            .request_id("REQ-REJECTED")
+            .field("order_status", "RejectedFromChain")
+            .reason("insufficient margin")
+            .to_json();
+        relayer.respond_once("transaction_hashes", serde_json::json!([pending]));
+        relayer.respond("transaction_hashes", serde_json::json!([rejected]));
+        // One fetch for the open, one for the unlock.
+        let (order_wallet, index, fetcher) = cached_utxo_wallet(&relayer, 2).await?;
+        let clock = ManualClock::new("2025-03-01T09:15:00Z".parse().unwrap());
+        let mut order_wallet = order_wallet.with_clock(Arc::new(clock.clone()));
+
+        let params = TraderOrderParams::new(index, OrderType::LIMIT, PositionType::LONG, 49_000, 2);
+        let err = order_wallet
+            .open_trader_order_and_wait(params, Duration::from_secs(10))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            OpenWaitError::Rejected { request_id, reason, .. }
+                if request_id == "REQ-REJECTED" && reason == "insufficient margin"
+        ));
+        // The second poll, a second after the first, saw the rejection.
+        assert_eq!(relayer.call_count("transaction_hashes"), 2);
+        assert_eq!(
+            clock.now(),
+            "2025-03-01T09:15:01Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert!(fetcher.replies.lock().unwrap().is_empty());
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Coin);
+        let stored = order_wallet
+            .zk_accounts
+            .get_account(&index)?
+            .last_error
+            .ok_or("last_error not recorded")?;
+        assert_eq!(stored.operation, "open_trader_order_and_wait");
         Ok(())
     }
 
     #[tokio::test]
-    #[serial]
-    async fn test_trading_to_funding() -> Result<(), String> {
-        dotenv::dotenv().ok();
-        init_logger();
-        let wallet = setup_wallet().await.map_err(|e| e.to_string())?;
-        let zk_accounts = ZkAccountDB::new();
-        let mut order_wallet = OrderWallet::init(wallet, zk_accounts, EndpointConfig::default())
-            .map_err(|e| e.to_string())?;
-        let (tx_result, account_index) = order_wallet.funding_to_trading(6000).await?;
-        if tx_result.code != 0 {
-            return Err(format!("Failed to send tx to chain: {}", tx_result.tx_hash));
+    async fn test_open_lend_order_and_wait_polls_until_lent() -> Result<(), String> {
+        use crate::relayer_module::clock::ManualClock;
+        use crate::relayer_module::mock_relayer::MockRelayer;
+        use crate::relayer_module::test_fixtures::{LendOrderBuilder, TxHashBuilder};
+
+        let relayer = MockRelayer::new();
+        relayer.accept("submit_lend_order", "REQ-LEND");
+        for status in [OrderStatus::PENDING, OrderStatus::PENDING] {
+            let tx = TxHashBuilder::new()
+                .request_id("REQ-LEND")
+                .order_status(status)
+                .to_json();
+            relayer.respond_once("transaction_hashes", serde_json::json!([tx]));
         }
-        sleep(Duration::from_secs(10)).await;
-        order_wallet
-            .wallet
-            .update_balance()
+        let lent = TxHashBuilder::new()
+            .request_id("REQ-LEND")
+            .order_status(OrderStatus::LENDED)
+            .to_json();
+        relayer.respond("transaction_hashes", serde_json::json!([lent]));
+        let order = LendOrderBuilder::new()
+            .order_status(OrderStatus::LENDED)
+            .amounts(1_000.0, 1_000.0)
+            .to_json();
+        relayer.respond("lend_order_info", order);
+        let (order_wallet, index, _) = cached_utxo_wallet(&relayer, 1).await?;
+        let clock = ManualClock::new("2025-03-01T09:15:00Z".parse().unwrap());
+        let mut order_wallet = order_wallet.with_clock(Arc::new(clock.clone()));
+
+        let receipt = order_wallet
+            .open_lend_order_and_wait(index, Duration::from_secs(10))
             .await
             .map_err(|e| e.to_string())?;
-        println!("wallet balance: {:?}", order_wallet.wallet.balance_sats);
-        let result = order_wallet.trading_to_funding(account_index).await?;
-        println!("result: {:?}", result);
-        sleep(Duration::from_secs(10)).await;
-        order_wallet
-            .wallet
-            .update_balance()
+        assert_eq!(receipt.request_id, "REQ-LEND");
+        assert_eq!(receipt.waited, Duration::from_secs(2));
+        assert_eq!(receipt.order.order_status, OrderStatus::LENDED);
+        assert_eq!(relayer.call_count("transaction_hashes"), 3);
+        assert_eq!(order_wallet.zk_accounts.get_io_type(&index)?, IOType::Memo);
+
+        // A lend still pending when the wait runs out stays open.
+        let relayer = MockRelayer::new();
+        relayer.accept("submit_lend_order", "REQ-SLOW");
+        let pending = TxHashBuilder::new()
+            .request_id("REQ-SLOW")
+            .order_status(OrderStatus::PENDING)
+            .to_json();
+        relayer.respond("transaction_hashes", serde_json::json!([pending]));
+        let (order_wallet, index, _) = cached_utxo_wallet(&relayer, 1).await?;
+        let mut order_wallet = order_wallet.with_clock(Arc::new(clock.clone()));
+        let err = order_wallet
+            .open_lend_order_and_wait(index, Duration::from_millis(1_500))
             .await
-            .map_err(|e| e.to_string())?;
-        println!("wallet balance: {:?}", order_wallet.wallet.balance_sats);
+            .unwrap_err();
+        assert_eq!(
+            err,
+            OpenWaitError::TimedOutStillPending {
+                account_index: index,
+                request_id: "REQ-SLOW".to_string(),
+                waited: Duration::from_millis(1_500),
+            }
+        );
+        assert_eq!(order_wallet.request_id(index)?, "REQ-SLOW");
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_inactive_accounts_round_trip() -> Result<(), String> {
+        use crate::relayer_module::clock::ManualClock;
+        let start: DateTime<Utc> = "2025-03-01T09:15:00Z".parse().unwrap();
+        let clock = ManualClock::new(start);
+        let mut order_wallet = OrderWallet::import_from_mnemonic(TEST_MNEMONIC, None)?
+            .with_clock(Arc::new(clock.clone()));
+        let mut spent = Vec::new();
+        for _ in 0..5 {
+            spent.push(
+                order_wallet
+                    .zk_accounts
+                    .generate_new_account(0, &order_wallet.seed.secret()?)?,
+            );
+        }
+        let funded = order_wallet
+            .zk_accounts
+            .generate_new_account(1_000, &order_wallet.seed.secret()?)?;
+        let locked = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        order_wallet
+            .zk_accounts
+            .update_io_type(&locked, IOType::Memo, Some(TXType::ORDERTX))?;
+        order_wallet
+            .request_ids
+            .insert(spent[0], "settled-request".to_string());
+
+        // Nothing has been seen yet, so the first pass only stamps the accounts.
+        let one_hour = Duration::from_secs(3600);
+        assert!(order_wallet.archive_inactive_accounts(one_hour)?.is_empty());
+        clock.advance(Duration::from_secs(2 * 3600));
+        assert_eq!(order_wallet.archive_inactive_accounts(one_hour)?, spent);
+
+        assert_eq!(order_wallet.zk_accounts.get_all_accounts().len(), 2);
+        assert_eq!(order_wallet.get_account_balances(false).len(), 2);
+        let all = order_wallet.get_account_balances(true);
+        assert_eq!(all.len(), 7);
+        assert_eq!(all.iter().filter(|b| b.archived).count(), 5);
+        assert_eq!(order_wallet.diagnostic_snapshot().archived_accounts, 5);
+        assert_eq!(
+            order_wallet.active_request_ids().count(),
+            0,
+            "archived accounts are skipped on flush"
+        );
+
+        // References by index still resolve, but the account is out of use.
+        assert!(order_wallet.zk_accounts.get_account(&spent[0]).is_err());
+        assert_eq!(
+            order_wallet.zk_accounts.resolve_account(&spent[0])?.index,
+            spent[0]
+        );
+        assert_eq!(order_wallet.request_id(spent[0])?, "settled-request");
+        let next = order_wallet
+            .zk_accounts
+            .generate_new_account(0, &order_wallet.seed.secret()?)?;
+        assert_eq!(next, locked + 1);
+
+        order_wallet.unarchive(spent[0])?;
+        assert_eq!(
+            order_wallet.zk_accounts.get_account(&spent[0])?.index,
+            spent[0]
+        );
+        assert!(!order_wallet.zk_accounts.is_archived(&spent[0]));
+        assert_eq!(order_wallet.active_request_ids().count(), 1);
+        assert!(order_wallet.unarchive(spent[0]).is_err());
+        // Unarchiving counts as activity, so it is not archived straight back.
+        assert!(!order_wallet
+            .archive_inactive_accounts(one_hour)?
+            .contains(&spent[0]));
         Ok(())
     }
 }
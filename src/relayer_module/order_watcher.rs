@@ -0,0 +1,397 @@
+//! Background order status watcher.
+//!
+//! An [`OrderWatcher`] polls the order on every `Memo` account of a shared
+//! [`OrderWallet`] and brings local state in line with what the relayer
+//! reports, through the same paths a caller would use by hand:
+//!
+//! - an order seen `FILLED` (or `LENDED`) for the first time has its Memo
+//!   UTXO fetched with `sync_account_state`;
+//! - a settled or liquidated trader order, and a settled lend order, is
+//!   unlocked with `unlock_trader_order` / `unlock_lend_order`, which
+//!   restore the `Coin` UTXO and the settled balance;
+//! - a cancelled order is returned to `Coin` like a cancel inside
+//!   `open_*_and_wait`;
+//! - an order the relayer no longer reports, once the query has unlocked it
+//!   as rejected, is reported as [`Reconcile::UnlockFailed`].
+//!
+//! Any other status, including one the wallet does not know or one that
+//! does not belong to the order's kind, is reported but left alone.
+//!
+//! Accounts, UTXO details and the database are updated by those paths, and
+//! the order queries emit `order_status_changed` on the wallet's event bus
+//! ([`OrderWallet::subscribe_events`]) as usual. The watcher also
+//! broadcasts an [`OrderTransition`] for every status it sees change and
+//! every reconciliation it runs. A step that fails is reported and retried
+//! on the next poll.
+//!
+//! The wallet is shared as an `Arc<OrderWallet>`. Its operations take the
+//! lock of the account they touch, so the watcher never holds a wallet-wide
+//! lock across a relayer or chain call, and other tasks can use the other
+//! accounts while it polls. A simulated wallet has nothing to reconcile; the
+//! watcher leaves it alone.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::Serialize;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+
+use crate::compat::relayer_types::OrderStatus;
+
+use super::events::OrderKind;
+use super::order_wallet::{AccountIndex, OrderWallet, RequestId};
+
+/// Default interval between polls of the tracked orders.
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Transitions buffered for each [`OrderWatcherHandle::subscribe`] receiver.
+const EVENT_BUFFER: usize = 64;
+
+/// Local state change the watcher makes for an order status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reconcile {
+    /// Fetch the Memo UTXO of a filled order.
+    SyncMemo,
+    /// Restore the `Coin` UTXO and settled balance.
+    Settle,
+    /// Return a cancelled order's account to `Coin`.
+    RestoreCancelled,
+    /// The account of a rejected order was unlocked by the failed query.
+    UnlockFailed,
+}
+
+impl Reconcile {
+    /// What to do for an order of `kind` now in `status`, last seen in
+    /// `last_seen`. Pending orders, fills already synced, and statuses that
+    /// are unknown or do not belong to `kind` need nothing.
+    pub fn for_status(
+        kind: OrderKind,
+        status: &OrderStatus,
+        last_seen: Option<&str>,
+    ) -> Option<Self> {
+        match status {
+            OrderStatus::PENDING => None,
+            OrderStatus::FILLED => Self::sync_once(status, last_seen),
+            OrderStatus::LENDED if kind == OrderKind::Lend => Self::sync_once(status, last_seen),
+            OrderStatus::SETTLED => Some(Reconcile::Settle),
+            OrderStatus::LIQUIDATE if kind == OrderKind::Trader => Some(Reconcile::Settle),
+            OrderStatus::CANCELLED => Some(Reconcile::RestoreCancelled),
+            _ => None,
+        }
+    }
+
+    fn sync_once(status: &OrderStatus, last_seen: Option<&str>) -> Option<Self> {
+        (last_seen != Some(&*status.to_str())).then_some(Reconcile::SyncMemo)
+    }
+}
+
+/// A status change or reconciliation step seen by the watcher.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderTransition {
+    pub account_index: AccountIndex,
+    pub order: OrderKind,
+    pub request_id: RequestId,
+    /// Status at the previous poll; `None` the first time the order is seen.
+    pub from: Option<String>,
+    /// Status now; `None` when the relayer no longer reports the order and
+    /// the wallet unlocked the account as failed.
+    pub to: Option<String>,
+    pub reconciled: Option<Reconcile>,
+    /// Why the reconciliation step failed; it is retried on the next poll.
+    pub error: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Polls the orders of a shared [`OrderWallet`]; see the [module docs](self).
+///
+/// Clones share the tracked statuses and the event channel.
+#[derive(Clone)]
+pub struct OrderWatcher {
+    wallet: Arc<OrderWallet>,
+    /// Last status seen per account, with the request it belongs to.
+    last_seen: Arc<Mutex<HashMap<AccountIndex, (RequestId, String)>>>,
+    poll_interval: Duration,
+    events: broadcast::Sender<OrderTransition>,
+}
+
+impl OrderWatcher {
+    pub fn new(wallet: Arc<OrderWallet>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            wallet,
+            last_seen: Arc::new(Mutex::new(HashMap::new())),
+            poll_interval: DEFAULT_WATCH_INTERVAL,
+            events,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn wallet(&self) -> &Arc<OrderWallet> {
+        &self.wallet
+    }
+
+    /// Transitions from now on. A receiver that falls more than 64 behind
+    /// skips the oldest.
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderTransition> {
+        self.events.subscribe()
+    }
+
+    /// Poll every tracked order once and reconcile the ones that moved.
+    /// Returns the transitions, which are also broadcast.
+    pub async fn poll_once(&self) -> Vec<OrderTransition> {
+        if self.wallet.is_simulated() {
+            return Vec::new();
+        }
+        for index in self.wallet.identify_memo_orders().await {
+            debug!("Order watcher: account {} has no known order kind", index);
+        }
+        let accounts = self.wallet.memo_accounts();
+        self.last_seen
+            .lock()
+            .unwrap()
+            .retain(|index, _| accounts.iter().any(|(i, _)| i == index));
+
+        let mut transitions = Vec::new();
+        for (index, order) in accounts {
+            let Some(order) = order else {
+                continue;
+            };
+            if let Some(transition) = self.poll_account(index, order).await {
+                let _ = self.events.send(transition.clone());
+                transitions.push(transition);
+            }
+        }
+        transitions
+    }
+
+    async fn poll_account(&self, index: AccountIndex, order: OrderKind) -> Option<OrderTransition> {
+        let wallet = self.wallet.as_ref();
+        // The account may have been settled or rotated since it was listed.
+        if !wallet.memo_accounts().iter().any(|(i, _)| *i == index) {
+            return None;
+        }
+        let request_id = match wallet.request_id(index) {
+            Ok(request_id) => request_id,
+            Err(e) => {
+                debug!("Order watcher: skipping account {}: {}", index, e);
+                return None;
+            }
+        };
+        let from = {
+            let last_seen = self.last_seen.lock().unwrap();
+            match last_seen.get(&index) {
+                Some((seen_id, status)) if *seen_id == request_id => Some(status.clone()),
+                _ => None,
+            }
+        };
+        let status = match order {
+            OrderKind::Trader => wallet
+                .query_trader_order(index)
+                .await
+                .map(|o| o.order_status),
+            OrderKind::Lend => wallet.query_lend_order(index).await.map(|o| o.order_status),
+        };
+        let status = match status {
+            Ok(status) => status,
+            // A failed query unlocks an order the relayer rejected.
+            Err(e) if !wallet.memo_accounts().iter().any(|(i, _)| *i == index) => {
+                info!("Order watcher: account {} unlocked: {}", index, e);
+                self.last_seen.lock().unwrap().remove(&index);
+                return Some(OrderTransition {
+                    account_index: index,
+                    order,
+                    request_id,
+                    from,
+                    to: None,
+                    reconciled: Some(Reconcile::UnlockFailed),
+                    error: None,
+                    occurred_at: wallet.clock().now(),
+                });
+            }
+            Err(e) => {
+                debug!("Order watcher: query of account {} failed: {}", index, e);
+                return None;
+            }
+        };
+
+        let to = status.to_str().to_string();
+        let reconciled = Reconcile::for_status(order, &status, from.as_deref());
+        if reconciled.is_none() && from.as_deref() == Some(to.as_str()) {
+            return None;
+        }
+        let result = match reconciled {
+            None => Ok(()),
+            Some(step) => reconcile(wallet, index, order, step).await,
+        };
+        let error = match result {
+            Ok(()) => {
+                self.last_seen
+                    .lock()
+                    .unwrap()
+                    .insert(index, (request_id.clone(), to.clone()));
+                if let Some(step) = reconciled {
+                    info!("Order watcher: account {} {} -> {:?}", index, to, step);
+                }
+                None
+            }
+            Err(e) => {
+                warn!(
+                    "Order watcher: reconciling account {} ({}) failed: {}",
+                    index, to, e
+                );
+                Some(e)
+            }
+        };
+        Some(OrderTransition {
+            account_index: index,
+            order,
+            request_id,
+            from,
+            to: Some(to),
+            reconciled,
+            error,
+            occurred_at: wallet.clock().now(),
+        })
+    }
+
+    /// Poll every poll interval until the returned handle is stopped.
+    pub fn spawn(&self) -> OrderWatcherHandle {
+        let watcher = self.clone();
+        let (stop, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(watcher.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        watcher.poll_once().await;
+                    }
+                    _ = stopped.changed() => break,
+                }
+            }
+        });
+        OrderWatcherHandle {
+            watcher: self.clone(),
+            stop,
+            task,
+        }
+    }
+}
+
+/// Apply `step` to the account through the wallet's own sync paths.
+pub(super) async fn reconcile(
+    wallet: &OrderWallet,
+    index: AccountIndex,
+    order: OrderKind,
+    step: Reconcile,
+) -> Result<(), String> {
+    match (step, order) {
+        (Reconcile::SyncMemo, _) => wallet
+            .sync_account_state(index)
+            .await
+            .map_err(|e| e.to_string()),
+        (Reconcile::Settle, OrderKind::Trader) => wallet
+            .unlock_trader_order(index)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        (Reconcile::Settle, OrderKind::Lend) => wallet
+            .unlock_lend_order(index)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        (Reconcile::RestoreCancelled, _) => wallet.unlock_cancelled_order(index),
+        (Reconcile::UnlockFailed, _) => wallet
+            .unlock_failed_order(index)
+            .await
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// A running [`OrderWatcher`] task.
+pub struct OrderWatcherHandle {
+    watcher: OrderWatcher,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl OrderWatcherHandle {
+    pub fn watcher(&self) -> &OrderWatcher {
+        &self.watcher
+    }
+
+    /// See [`OrderWatcher::subscribe`].
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderTransition> {
+        self.watcher.subscribe()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stop polling. A poll in progress finishes first, so no account is
+    /// left half reconciled.
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        if let Err(e) = self.task.await {
+            warn!("Order watcher task ended abnormally: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconcile_by_status_and_order_kind() {
+        let trader = OrderKind::Trader;
+        let lend = OrderKind::Lend;
+        assert_eq!(
+            Reconcile::for_status(trader, &OrderStatus::PENDING, None),
+            None
+        );
+        assert_eq!(
+            Reconcile::for_status(trader, &OrderStatus::FILLED, Some("PENDING")),
+            Some(Reconcile::SyncMemo)
+        );
+        // A fill is synced once.
+        assert_eq!(
+            Reconcile::for_status(trader, &OrderStatus::FILLED, Some("FILLED")),
+            None
+        );
+        assert_eq!(
+            Reconcile::for_status(lend, &OrderStatus::LENDED, None),
+            Some(Reconcile::SyncMemo)
+        );
+        assert_eq!(
+            Reconcile::for_status(trader, &OrderStatus::LIQUIDATE, Some("FILLED")),
+            Some(Reconcile::Settle)
+        );
+        assert_eq!(
+            Reconcile::for_status(lend, &OrderStatus::SETTLED, Some("LENDED")),
+            Some(Reconcile::Settle)
+        );
+        assert_eq!(
+            Reconcile::for_status(lend, &OrderStatus::CANCELLED, None),
+            Some(Reconcile::RestoreCancelled)
+        );
+        // Statuses of the other order kind are left alone.
+        assert_eq!(
+            Reconcile::for_status(lend, &OrderStatus::LIQUIDATE, None),
+            None
+        );
+        assert_eq!(
+            Reconcile::for_status(trader, &OrderStatus::LENDED, None),
+            None
+        );
+    }
+}
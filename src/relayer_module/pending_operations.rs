@@ -0,0 +1,460 @@
+//! Resumable records for composite (multi-step) wallet operations.
+//!
+//! A composite operation such as splitting one ZkOS account into several is a
+//! single on-chain transaction followed by per-account bookkeeping steps. If
+//! the transaction lands but some of the follow-up steps fail (e.g. the UTXO
+//! for a receiver was not yet queryable), the wallet records a
+//! [`PendingOperation`] with the steps that are still outstanding. The record
+//! is kept in memory on the `OrderWallet` and, when DB persistence is enabled,
+//! in the `pending_operations` table so it survives restarts.
+//!
+//! [`OrderWallet::pending_operations`](super::order_wallet::OrderWallet::pending_operations)
+//! lists open records and
+//! [`OrderWallet::resume_operation`](super::order_wallet::OrderWallet::resume_operation)
+//! re-executes only the remaining steps. The steps of a split do not depend
+//! on each other: each account is finalized on its own and the result is a
+//! [`MultiTransferOutcome`].
+//!
+//! Funding, account rotation and order submits and closes are journaled
+//! ahead of time instead: their record is written just before the
+//! transaction or request is sent and marked done when the call returns. A
+//! record still pending after a restart belongs to a call that was
+//! interrupted, and its steps only re-check and finish local state (fetch
+//! the UTXO, re-query the order). When that state is not what the operation
+//! would have left, e.g. the funding UTXO never appeared, the record is
+//! flagged [`PendingOperationStatus::NeedsResolution`] for the operator
+//! instead of being retried; see
+//! [`OrderWallet::resume_pending`](super::order_wallet::OrderWallet::resume_pending).
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::events::OrderKind;
+use super::order_wallet::{AccountBalance, AccountIndex, Balance};
+
+/// Kind of composite operation a record belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingOperationKind {
+    /// `trading_to_trading_multiple_accounts`: one sender split into several receivers.
+    SplitAccount,
+    /// `funding_to_trading`: mint from the on-chain wallet into a new account.
+    FundAccount,
+    /// `trading_to_trading`: whole balance moved to a fresh account.
+    RotateAccount,
+    /// `transfer_to_address`: recorded once the transfer is broadcast, so its
+    /// hash survives a sender that could not be finalized.
+    TransferToAddress,
+    /// Trader or lend order submit.
+    OpenOrder,
+    /// Trader or lend order close.
+    CloseOrder,
+}
+
+impl PendingOperationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingOperationKind::SplitAccount => "split_account",
+            PendingOperationKind::FundAccount => "fund_account",
+            PendingOperationKind::RotateAccount => "rotate_account",
+            PendingOperationKind::TransferToAddress => "transfer_to_address",
+            PendingOperationKind::OpenOrder => "open_order",
+            PendingOperationKind::CloseOrder => "close_order",
+        }
+    }
+
+    /// Whether the record is written before the operation starts, rather
+    /// than after a partial failure.
+    pub fn is_journaled(&self) -> bool {
+        !matches!(self, PendingOperationKind::SplitAccount)
+    }
+}
+
+impl std::fmt::Display for PendingOperationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Lifecycle of a pending operation record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingOperationStatus {
+    /// Some steps are still outstanding.
+    Pending,
+    /// All steps have completed.
+    Done,
+    /// Resuming found state the operation cannot finish from; left for the
+    /// operator, see `last_error`.
+    NeedsResolution,
+}
+
+impl PendingOperationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingOperationStatus::Pending => "pending",
+            PendingOperationStatus::Done => "done",
+            PendingOperationStatus::NeedsResolution => "needs_resolution",
+        }
+    }
+}
+
+/// One resumable unit of work. Each step carries everything needed to run it
+/// again without the original in-memory transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum OperationStep {
+    /// Fetch the receiver's new UTXO and record its balance, scalar and account key.
+    FinalizeReceiver {
+        account_index: AccountIndex,
+        balance: Balance,
+        encrypt_scalar: String,
+        account_key: String,
+    },
+    /// Refresh the sender after the transfer (fetch its change UTXO, or mark it emptied).
+    FinalizeSender {
+        account_index: AccountIndex,
+        remaining_balance: Balance,
+    },
+    /// Fetch the UTXO a mint created for a new account and store its
+    /// committed balance.
+    ConfirmFunding {
+        account_index: AccountIndex,
+        amount: Balance,
+    },
+    /// Fetch the UTXO of the account a whole balance was rotated into and
+    /// record its balance and scalar.
+    FinalizeRotation {
+        account_index: AccountIndex,
+        balance: Balance,
+        encrypt_scalar: String,
+    },
+    /// Query the account's order and bring the account in line with its
+    /// status, as the order watcher does.
+    ReconcileOrder {
+        account_index: AccountIndex,
+        order: OrderKind,
+    },
+}
+
+impl OperationStep {
+    /// ZkOS account the step operates on.
+    pub fn account_index(&self) -> AccountIndex {
+        match self {
+            OperationStep::FinalizeReceiver { account_index, .. }
+            | OperationStep::FinalizeSender { account_index, .. }
+            | OperationStep::ConfirmFunding { account_index, .. }
+            | OperationStep::FinalizeRotation { account_index, .. }
+            | OperationStep::ReconcileOrder { account_index, .. } => *account_index,
+        }
+    }
+}
+
+/// Inputs a composite operation was started with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OperationInputs {
+    SplitAccount {
+        sender_account_index: AccountIndex,
+        balances: Vec<Balance>,
+    },
+    FundAccount {
+        account_index: AccountIndex,
+        amount: Balance,
+    },
+    RotateAccount {
+        sender_account_index: AccountIndex,
+        receiver_account_index: AccountIndex,
+        balance: Balance,
+    },
+    TransferToAddress {
+        account_index: AccountIndex,
+        address: String,
+        amount: Balance,
+        tx_hash: String,
+    },
+    /// An order submit or close; `params` describes the order for the operator.
+    Order {
+        account_index: AccountIndex,
+        order: OrderKind,
+        params: String,
+    },
+}
+
+impl OperationInputs {
+    /// Account the operation starts from.
+    pub fn account_index(&self) -> AccountIndex {
+        match self {
+            OperationInputs::SplitAccount {
+                sender_account_index,
+                ..
+            }
+            | OperationInputs::RotateAccount {
+                sender_account_index,
+                ..
+            } => *sender_account_index,
+            OperationInputs::FundAccount { account_index, .. }
+            | OperationInputs::TransferToAddress { account_index, .. }
+            | OperationInputs::Order { account_index, .. } => *account_index,
+        }
+    }
+}
+
+/// A partially completed composite operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingOperation {
+    pub id: String,
+    pub kind: PendingOperationKind,
+    pub status: PendingOperationStatus,
+    pub inputs: OperationInputs,
+    pub completed_steps: Vec<OperationStep>,
+    pub remaining_steps: Vec<OperationStep>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PendingOperation {
+    /// Start a new record with all `steps` outstanding.
+    pub fn new(
+        kind: PendingOperationKind,
+        inputs: OperationInputs,
+        steps: Vec<OperationStep>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            status: PendingOperationStatus::Pending,
+            inputs,
+            completed_steps: Vec::new(),
+            remaining_steps: steps,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Move the first remaining step to the completed list.
+    pub fn complete_next_step(&mut self, now: DateTime<Utc>) {
+        if !self.remaining_steps.is_empty() {
+            let step = self.remaining_steps.remove(0);
+            self.completed_steps.push(step);
+        }
+        if self.remaining_steps.is_empty() {
+            self.status = PendingOperationStatus::Done;
+            self.last_error = None;
+        }
+        self.updated_at = now;
+    }
+
+    /// Move `step` from the remaining to the completed list wherever it is,
+    /// for operations whose steps do not depend on each other.
+    pub fn complete_step(&mut self, step: &OperationStep, now: DateTime<Utc>) {
+        if let Some(position) = self.remaining_steps.iter().position(|s| s == step) {
+            let step = self.remaining_steps.remove(position);
+            self.completed_steps.push(step);
+        }
+        if self.remaining_steps.is_empty() {
+            self.status = PendingOperationStatus::Done;
+            self.last_error = None;
+        }
+        self.updated_at = now;
+    }
+
+    /// Mark every remaining step completed, e.g. when the journaled call
+    /// returned.
+    pub fn complete(&mut self, now: DateTime<Utc>) {
+        self.completed_steps.append(&mut self.remaining_steps);
+        self.status = PendingOperationStatus::Done;
+        self.last_error = None;
+        self.updated_at = now;
+    }
+
+    /// Record a failure on the current step; the step stays outstanding.
+    pub fn fail(&mut self, error: impl Into<String>, now: DateTime<Utc>) {
+        self.last_error = Some(error.into());
+        self.status = PendingOperationStatus::Pending;
+        self.updated_at = now;
+    }
+
+    /// Leave the operation to the operator; `reason` is kept as the last error.
+    pub fn flag_for_resolution(&mut self, reason: impl Into<String>, now: DateTime<Utc>) {
+        self.last_error = Some(reason.into());
+        self.status = PendingOperationStatus::NeedsResolution;
+        self.updated_at = now;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.status == PendingOperationStatus::Done
+    }
+
+    pub fn needs_resolution(&self) -> bool {
+        self.status == PendingOperationStatus::NeedsResolution
+    }
+
+    /// Whether a step holds zk secrets (an encrypt scalar or account key),
+    /// which the database only stores sealed.
+    pub fn carries_secrets(&self) -> bool {
+        self.completed_steps
+            .iter()
+            .chain(&self.remaining_steps)
+            .any(|step| {
+                matches!(
+                    step,
+                    OperationStep::FinalizeReceiver { .. } | OperationStep::FinalizeRotation { .. }
+                )
+            })
+    }
+
+    /// Whether an outstanding step, or the account the operation starts
+    /// from, is `index`.
+    pub fn involves(&self, index: AccountIndex) -> bool {
+        self.inputs.account_index() == index
+            || self
+                .remaining_steps
+                .iter()
+                .any(|step| step.account_index() == index)
+    }
+}
+
+/// What the bookkeeping after a split's transaction achieved. The
+/// transaction is on chain either way; an account in `unconfirmed` keeps its
+/// step in the split's pending record for
+/// [`OrderWallet::confirm_pending_receivers`](super::order_wallet::OrderWallet::confirm_pending_receivers).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MultiTransferOutcome {
+    /// Receivers whose UTXO was found, with their balance.
+    pub confirmed: Vec<AccountBalance>,
+    /// Accounts with an outstanding step and its error: receivers whose UTXO
+    /// was not found, and the sender when its change UTXO was not.
+    pub unconfirmed: Vec<(AccountIndex, String)>,
+}
+
+impl MultiTransferOutcome {
+    /// Whether every account was finalized.
+    pub fn is_complete(&self) -> bool {
+        self.unconfirmed.is_empty()
+    }
+
+    pub fn extend(&mut self, other: MultiTransferOutcome) {
+        self.confirmed.extend(other.confirmed);
+        self.unconfirmed.extend(other.unconfirmed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PendingOperation {
+        PendingOperation::new(
+            PendingOperationKind::SplitAccount,
+            OperationInputs::SplitAccount {
+                sender_account_index: 1,
+                balances: vec![100, 200],
+            },
+            vec![
+                OperationStep::FinalizeReceiver {
+                    account_index: 2,
+                    balance: 100,
+                    encrypt_scalar: "aa".to_string(),
+                    account_key: "bb".to_string(),
+                },
+                OperationStep::FinalizeReceiver {
+                    account_index: 3,
+                    balance: 200,
+                    encrypt_scalar: "cc".to_string(),
+                    account_key: "dd".to_string(),
+                },
+                OperationStep::FinalizeSender {
+                    account_index: 1,
+                    remaining_balance: 0,
+                },
+            ],
+            DateTime::<Utc>::UNIX_EPOCH,
+        )
+    }
+
+    #[test]
+    fn test_steps_progress_to_done() {
+        let now = DateTime::<Utc>::UNIX_EPOCH;
+        let mut op = sample();
+        op.complete_next_step(now);
+        op.fail("utxo not found", now);
+        assert_eq!(op.completed_steps.len(), 1);
+        assert_eq!(op.remaining_steps[0].account_index(), 3);
+        assert_eq!(op.status, PendingOperationStatus::Pending);
+        assert_eq!(op.last_error.as_deref(), Some("utxo not found"));
+
+        op.complete_next_step(now);
+        op.complete_next_step(now);
+        assert!(op.is_done());
+        assert!(op.remaining_steps.is_empty());
+        assert!(op.last_error.is_none());
+    }
+
+    #[test]
+    fn test_independent_steps_complete_out_of_order() {
+        let now = DateTime::<Utc>::UNIX_EPOCH;
+        let mut op = sample();
+        let steps = op.remaining_steps.clone();
+        op.complete_step(&steps[1], now);
+        op.complete_step(&steps[2], now);
+        op.fail("utxo not found", now);
+        assert_eq!(op.remaining_steps, vec![steps[0].clone()]);
+        assert_eq!(op.completed_steps.len(), 2);
+        assert_eq!(op.status, PendingOperationStatus::Pending);
+
+        // A step no longer outstanding is ignored.
+        op.complete_step(&steps[1], now);
+        assert_eq!(op.completed_steps.len(), 2);
+        op.complete_step(&steps[0], now);
+        assert!(op.is_done());
+        assert!(op.last_error.is_none());
+    }
+
+    #[test]
+    fn test_journaled_intent_completes_or_is_flagged() {
+        let now = DateTime::<Utc>::UNIX_EPOCH;
+        let intent = || {
+            PendingOperation::new(
+                PendingOperationKind::OpenOrder,
+                OperationInputs::Order {
+                    account_index: 4,
+                    order: OrderKind::Trader,
+                    params: "MARKET LONG".to_string(),
+                },
+                vec![OperationStep::ReconcileOrder {
+                    account_index: 4,
+                    order: OrderKind::Trader,
+                }],
+                now,
+            )
+        };
+        assert!(intent().kind.is_journaled());
+        assert!(!sample().kind.is_journaled());
+        assert!(intent().involves(4));
+
+        let mut done = intent();
+        done.complete(now);
+        assert!(done.is_done());
+        assert_eq!(done.completed_steps.len(), 1);
+
+        let mut flagged = intent();
+        flagged.flag_for_resolution("order not found", now);
+        assert!(flagged.needs_resolution() && !flagged.is_done());
+        assert_eq!(flagged.status.as_str(), "needs_resolution");
+        let json = serde_json::to_string(&flagged).unwrap();
+        let back: PendingOperation = serde_json::from_str(&json).unwrap();
+        assert_eq!(flagged, back);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let op = sample();
+        let json = serde_json::to_string(&op).unwrap();
+        let back: PendingOperation = serde_json::from_str(&json).unwrap();
+        assert_eq!(op, back);
+    }
+}
@@ -3,14 +3,17 @@
 //! Provides aggregate views of portfolio state, per-position PnL calculations,
 //! liquidation price monitoring, and risk metrics for both trader and lend positions.
 
+use chrono::{DateTime, Utc};
 use serde::Serialize;
-use twilight_client_sdk::{
+use crate::compat::{
     relayer_types::{LendOrder, OrderStatus, PositionType, TraderOrder},
     zkvm::IOType,
 };
 
+use super::events::OrderKind;
 use super::order_wallet::AccountIndex;
-use super::relayer_types::{LendOrderV1, OrderTrigger, TraderOrderV1};
+use super::relayer_types::{FundingRate, LendOrderV1, OrderTrigger, TraderOrderV1};
+use crate::zkos_accounts::zkaccount::StoredError;
 
 /// Compute unrealized PnL for an inverse perpetual BTC/USD position.
 ///
@@ -307,6 +310,59 @@ pub struct LiquidationRisk {
     pub margin_ratio: f64,
 }
 
+/// Funding settled on a position at one funding interval.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FundingPayment {
+    pub timestamp: DateTime<Utc>,
+    /// Funding rate for the interval, in percent.
+    pub funding_rate: f64,
+    /// BTC price the relayer used for the interval.
+    pub btc_price: f64,
+    /// Sats received (positive) or paid (negative) at this interval.
+    pub payment: f64,
+    /// Sum of `payment` up to and including this interval.
+    pub cumulative: f64,
+}
+
+/// Funding payments for a position of `position_size` opened at `opened_at`,
+/// one per rate in `rates` published after the open, oldest first.
+///
+/// Each interval moves `position_size * rate / (btc_price * 100)` sats of
+/// margin, the formula the relayer applies: with a positive rate longs pay
+/// and shorts receive, with a negative rate the reverse.
+pub fn funding_payments(
+    position_type: &PositionType,
+    position_size: f64,
+    opened_at: DateTime<Utc>,
+    rates: &[FundingRate],
+) -> Vec<FundingPayment> {
+    let mut rates: Vec<&FundingRate> = rates
+        .iter()
+        .filter(|rate| rate.timestamp > opened_at && rate.btc_price > 0.0)
+        .collect();
+    rates.sort_by_key(|rate| rate.timestamp);
+
+    let sign = match position_type {
+        PositionType::LONG => -1.0,
+        PositionType::SHORT => 1.0,
+    };
+    let mut cumulative = 0.0;
+    rates
+        .into_iter()
+        .map(|rate| {
+            let payment = sign * position_size * rate.rate / (rate.btc_price * 100.0);
+            cumulative += payment;
+            FundingPayment {
+                timestamp: rate.timestamp,
+                funding_rate: rate.rate,
+                btc_price: rate.btc_price,
+                payment,
+                cumulative,
+            }
+        })
+        .collect()
+}
+
 /// Per-account balance snapshot for quick overview.
 #[derive(Debug, Clone, Serialize)]
 pub struct AccountBalanceInfo {
@@ -314,6 +370,106 @@ pub struct AccountBalanceInfo {
     pub balance: u64,
     pub io_type: IOType,
     pub on_chain: bool,
+    /// Most recent failed operation on the account, cleared by the next success.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<StoredError>,
+    /// The balance could not be checked against the on-chain output.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub balance_unverified: bool,
+    /// Archived as spent; see `OrderWallet::archive_inactive_accounts`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// One active account in an [`OrderWalletSummary`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountEntry {
+    pub account_index: AccountIndex,
+    pub balance: u64,
+    pub io_type: IOType,
+    pub on_chain: bool,
+    /// Book of the order holding a Memo account; `None` for Coin accounts.
+    pub order_kind: Option<OrderKind>,
+    pub pending_request_id: Option<String>,
+    /// Status re-queried by `OrderWallet::refresh_summary`; `None` otherwise
+    /// or if the query failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_status: Option<OrderStatus>,
+}
+
+impl AccountEntry {
+    /// Whether the account holds an order that is still open. Without a
+    /// re-queried status every Memo account of known order kind counts as
+    /// open.
+    pub fn has_active_order(&self) -> bool {
+        match (&self.order_status, self.order_kind) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(status), Some(OrderKind::Trader)) => {
+                matches!(status, OrderStatus::PENDING | OrderStatus::FILLED)
+            }
+            (Some(status), Some(OrderKind::Lend)) => {
+                !matches!(status, OrderStatus::SETTLED | OrderStatus::CANCELLED)
+            }
+        }
+    }
+}
+
+/// Local view of an `OrderWallet`, built from cached state only.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderWalletSummary {
+    pub generated_at: DateTime<Utc>,
+    /// On-chain balances as last fetched by `Wallet::update_balance`.
+    pub wallet_balance_nyks: u64,
+    pub wallet_balance_sats: u64,
+    pub accounts: Vec<AccountEntry>,
+    /// Sum of on-chain Coin account balances (idle trading capital).
+    pub coin_balance: u64,
+    /// Sum of Memo account balances (margin and deposits locked in orders).
+    pub locked_margin: u64,
+    pub active_trader_orders: usize,
+    pub active_lend_orders: usize,
+    /// Order statuses were re-queried before building the summary.
+    pub statuses_refreshed: bool,
+}
+
+impl OrderWalletSummary {
+    /// Compute the totals and order counts over `accounts`.
+    pub fn build(
+        generated_at: DateTime<Utc>,
+        wallet_balance_nyks: u64,
+        wallet_balance_sats: u64,
+        accounts: Vec<AccountEntry>,
+        statuses_refreshed: bool,
+    ) -> Self {
+        let coin_balance = accounts
+            .iter()
+            .filter(|a| a.io_type == IOType::Coin && a.on_chain)
+            .fold(0u64, |sum, a| sum.saturating_add(a.balance));
+        let locked_margin = accounts
+            .iter()
+            .filter(|a| a.io_type == IOType::Memo)
+            .fold(0u64, |sum, a| sum.saturating_add(a.balance));
+        let active = |kind: OrderKind| {
+            accounts
+                .iter()
+                .filter(|a| a.order_kind == Some(kind) && a.has_active_order())
+                .count()
+        };
+        Self {
+            generated_at,
+            wallet_balance_nyks,
+            wallet_balance_sats,
+            coin_balance,
+            locked_margin,
+            active_trader_orders: active(OrderKind::Trader),
+            active_lend_orders: active(OrderKind::Lend),
+            accounts,
+            statuses_refreshed,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -379,4 +535,88 @@ mod tests {
         // They should sum to zero (LONG gain = SHORT loss for same params)
         assert!(approx_eq(long_pnl + short_pnl, 0.0, 1e-10));
     }
+
+    fn funding_rate(hour: i64, rate: f64) -> FundingRate {
+        FundingRate {
+            id: hour,
+            rate,
+            btc_price: 50_000.0,
+            timestamp: DateTime::<Utc>::UNIX_EPOCH + chrono::TimeDelta::hours(hour),
+        }
+    }
+
+    #[test]
+    fn test_funding_payments_since_open() {
+        let opened_at = DateTime::<Utc>::UNIX_EPOCH + chrono::TimeDelta::minutes(90);
+        // Out of order, and one rate from before the position opened.
+        let rates = vec![
+            funding_rate(3, -0.05),
+            funding_rate(1, 0.1),
+            funding_rate(2, 0.1),
+        ];
+        // 1_000 sats at 10x and 50_000: 0.1% moves 10 sats per interval.
+        let position_size = 1_000.0 * 10.0 * 50_000.0;
+
+        let long = funding_payments(&PositionType::LONG, position_size, opened_at, &rates);
+        assert_eq!(long.len(), 2);
+        assert_eq!(long[0].timestamp, rates[2].timestamp);
+        assert!(approx_eq(long[0].payment, -10.0, 1e-9));
+        assert!(approx_eq(long[1].payment, 5.0, 1e-9));
+        assert!(approx_eq(long[1].cumulative, -5.0, 1e-9));
+
+        let short = funding_payments(&PositionType::SHORT, position_size, opened_at, &rates);
+        assert!(approx_eq(short[1].cumulative, 5.0, 1e-9));
+        assert!(funding_payments(&PositionType::LONG, position_size, opened_at, &[]).is_empty());
+    }
+
+    fn entry(
+        account_index: AccountIndex,
+        balance: u64,
+        order_kind: Option<OrderKind>,
+        order_status: Option<OrderStatus>,
+    ) -> AccountEntry {
+        AccountEntry {
+            account_index,
+            balance,
+            io_type: if order_kind.is_some() {
+                IOType::Memo
+            } else {
+                IOType::Coin
+            },
+            on_chain: true,
+            order_kind,
+            pending_request_id: order_kind.map(|_| format!("req-{}", account_index)),
+            order_status,
+        }
+    }
+
+    #[test]
+    fn test_summary_totals_by_state() {
+        let mut off_chain = entry(1, 500, None, None);
+        off_chain.on_chain = false;
+        let summary = OrderWalletSummary::build(
+            DateTime::<Utc>::UNIX_EPOCH,
+            10,
+            20_000,
+            vec![
+                off_chain,
+                entry(2, 1_000, None, None),
+                entry(3, 2_000, Some(OrderKind::Trader), None),
+                entry(
+                    4,
+                    3_000,
+                    Some(OrderKind::Trader),
+                    Some(OrderStatus::SETTLED),
+                ),
+                entry(5, 4_000, Some(OrderKind::Lend), Some(OrderStatus::LENDED)),
+            ],
+            true,
+        );
+        assert_eq!(summary.coin_balance, 1_000);
+        assert_eq!(summary.locked_margin, 9_000);
+        // A settled trader order is no longer active.
+        assert_eq!(summary.active_trader_orders, 1);
+        assert_eq!(summary.active_lend_orders, 1);
+        assert_eq!(summary.accounts.len(), 5);
+    }
 }
@@ -0,0 +1,421 @@
+//! Liquidation price and margin health of a trader position.
+//!
+//! [`OrderWallet::position_health`](super::order_wallet::OrderWallet::position_health)
+//! queries the open order, the BTC/USD mark price, the relayer's risk
+//! parameters and its current fee and funding rates, and
+//! [`PositionHealth::from_trader_order`] measures the mark price against the
+//! bankruptcy price, maintenance margin and liquidation price the relayer
+//! reports on the order. An order that does not report them yet, e.g. a
+//! pending limit order, gets estimates from the relayer's settlement
+//! arithmetic below, and [`PositionHealth::estimated`] is set.
+//!
+//! ## Formulas
+//!
+//! With `E` the entry price, `L` the leverage, `m` the initial margin,
+//! `a` the available margin (initial margin after funding) and `S` the
+//! `positionsize` (`m * L * E`), as the relayer computes them:
+//!
+//! - **entry value** = `m * L`.
+//! - **bankruptcy price** = `E * L / (L + 1)` for a long and
+//!   `E * L / (L - 1)` for a short (`0` for an unleveraged short).
+//! - **bankruptcy value** = `S / bankruptcy price` (`0` without one).
+//! - **maintenance margin** =
+//!   `(0.4 * entry value + fee * bankruptcy value + funding * bankruptcy value) / 100`,
+//!   with the settlement fee and funding rate in percent as the relayer
+//!   reports them.
+//! - **liquidation price** = `E * S / (side * E * (mm - a) + S)` with
+//!   `side = -1` for a long and `1` for a short: the price at which
+//!   `a + unrealized PnL` falls to the maintenance margin. `0` when the
+//!   denominator is not positive: a long whose margin is already below
+//!   maintenance, or a short no price rise can liquidate.
+//! - **health** = `(a + unrealized PnL - mm) / (a - mm)`, clamped to `[0, 1]`:
+//!   `1` at or beyond break-even, `0` at the liquidation price.
+//!
+//! The relayer program file only carries the ZkOS programs, not these
+//! parameters; the maintenance ratio is the `mm_ratio` of the relayer's
+//! market stats and the rates come from its `get_fee_rate` and
+//! `get_funding_rate` endpoints.
+
+use serde::Serialize;
+
+use crate::compat::relayer_types::{PositionType, TraderOrder};
+
+use super::order_wallet::AccountIndex;
+use super::portfolio::unrealized_pnl;
+use super::relayer_types::RiskParams;
+
+/// Rates entering the maintenance margin, in percent.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MarginParams {
+    /// Share of the entry value held as maintenance margin before fees and
+    /// funding; the relayer's `mm_ratio`.
+    pub maintenance_margin_pct: f64,
+    /// Fee charged when the position settles on market.
+    pub fee_pct: f64,
+    pub funding_rate_pct: f64,
+}
+
+impl MarginParams {
+    pub fn from_risk_params(params: &RiskParams, fee_pct: f64, funding_rate_pct: f64) -> Self {
+        Self {
+            maintenance_margin_pct: params.mm_ratio,
+            fee_pct,
+            funding_rate_pct,
+        }
+    }
+}
+
+pub fn bankruptcy_price(position_type: &PositionType, entry_price: f64, leverage: f64) -> f64 {
+    match position_type {
+        PositionType::LONG => entry_price * leverage / (leverage + 1.0),
+        PositionType::SHORT if leverage > 1.0 => entry_price * leverage / (leverage - 1.0),
+        PositionType::SHORT => 0.0,
+    }
+}
+
+pub fn bankruptcy_value(position_size: f64, bankruptcy_price: f64) -> f64 {
+    if bankruptcy_price > 0.0 {
+        position_size / bankruptcy_price
+    } else {
+        0.0
+    }
+}
+
+pub fn maintenance_margin(entry_value: f64, bankruptcy_value: f64, params: &MarginParams) -> f64 {
+    (params.maintenance_margin_pct * entry_value
+        + params.fee_pct * bankruptcy_value
+        + params.funding_rate_pct * bankruptcy_value)
+        / 100.0
+}
+
+/// Price at which `margin` plus unrealized PnL equals `maintenance_margin`,
+/// or `0` without one; see the [module docs](self).
+pub fn liquidation_price(
+    position_type: &PositionType,
+    entry_price: f64,
+    position_size: f64,
+    maintenance_margin: f64,
+    margin: f64,
+) -> f64 {
+    let side = match position_type {
+        PositionType::LONG => -1.0,
+        PositionType::SHORT => 1.0,
+    };
+    let denominator = side * entry_price * (maintenance_margin - margin) + position_size;
+    if denominator > 0.0 {
+        entry_price * position_size / denominator
+    } else {
+        0.0
+    }
+}
+
+/// The value the relayer reported, or `estimate` if it reported none.
+fn reported_or(reported: f64, estimate: impl FnOnce() -> f64, estimated: &mut bool) -> f64 {
+    if reported.is_finite() && reported > 0.0 {
+        return reported;
+    }
+    *estimated = true;
+    estimate()
+}
+
+/// How close an open trader position is to liquidation.
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionHealth {
+    pub account_index: AccountIndex,
+    pub position_type: PositionType,
+    pub entry_price: f64,
+    pub mark_price: f64,
+    pub leverage: f64,
+    pub position_size: f64,
+    pub bankruptcy_price: f64,
+    pub liquidation_price: f64,
+    pub maintenance_margin: f64,
+    /// Margin backing the position after funding, as the relayer reports it.
+    pub available_margin: f64,
+    /// Unrealized PnL at the mark price, in sats.
+    pub unrealized_pnl: f64,
+    /// `available_margin + unrealized_pnl`.
+    pub margin_at_mark: f64,
+    /// `1` at or beyond break-even, `0` at or past the liquidation price.
+    pub health: f64,
+    pub params: MarginParams,
+    /// Whether the order lacked a bankruptcy price, maintenance margin or
+    /// liquidation price and it was computed from `params` instead.
+    pub estimated: bool,
+}
+
+impl PositionHealth {
+    pub fn from_trader_order(
+        account_index: AccountIndex,
+        order: &TraderOrder,
+        mark_price: f64,
+        params: &MarginParams,
+    ) -> Self {
+        let mut estimated = false;
+        let bankruptcy_price = reported_or(
+            order.bankruptcy_price,
+            || bankruptcy_price(&order.position_type, order.entryprice, order.leverage),
+            &mut estimated,
+        );
+        let maintenance_margin = reported_or(
+            order.maintenance_margin,
+            || {
+                maintenance_margin(
+                    order.initial_margin * order.leverage,
+                    bankruptcy_value(order.positionsize, bankruptcy_price),
+                    params,
+                )
+            },
+            &mut estimated,
+        );
+        let liquidation_price = reported_or(
+            order.liquidation_price,
+            || {
+                liquidation_price(
+                    &order.position_type,
+                    order.entryprice,
+                    order.positionsize,
+                    maintenance_margin,
+                    order.available_margin,
+                )
+            },
+            &mut estimated,
+        );
+        let upnl = unrealized_pnl(
+            &order.position_type,
+            order.positionsize,
+            order.entryprice,
+            mark_price,
+        );
+        let margin_at_mark = order.available_margin + upnl;
+        let cushion = order.available_margin - maintenance_margin;
+        let health = if cushion > 0.0 {
+            ((margin_at_mark - maintenance_margin) / cushion).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        Self {
+            account_index,
+            position_type: order.position_type.clone(),
+            entry_price: order.entryprice,
+            mark_price,
+            leverage: order.leverage,
+            position_size: order.positionsize,
+            bankruptcy_price,
+            liquidation_price,
+            maintenance_margin,
+            available_margin: order.available_margin,
+            unrealized_pnl: upnl,
+            margin_at_mark,
+            health,
+            params: *params,
+            estimated,
+        }
+    }
+
+    /// Whether the mark price has reached the liquidation price.
+    pub fn is_liquidatable(&self) -> bool {
+        self.health <= 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::test_fixtures::TraderOrderBuilder;
+
+    const PARAMS: MarginParams = MarginParams {
+        maintenance_margin_pct: 0.4,
+        fee_pct: 0.05,
+        funding_rate_pct: 0.01,
+    };
+
+    /// An order without the relayer's own prices, so they are estimated.
+    fn unpriced(
+        position_type: PositionType,
+        margin: f64,
+        leverage: f64,
+        entry: f64,
+    ) -> TraderOrderBuilder {
+        TraderOrderBuilder::new()
+            .position_type(position_type)
+            .position(margin, leverage, entry)
+            .field("bankruptcy_price", 0.0)
+            .field("bankruptcy_value", 0.0)
+            .field("maintenance_margin", 0.0)
+            .liquidation_price(0.0)
+    }
+
+    fn order(position_type: PositionType, margin: f64, leverage: f64, entry: f64) -> TraderOrder {
+        unpriced(position_type, margin, leverage, entry).build()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_long_fixtures() {
+        // (margin, leverage, entry, bankruptcy price, maintenance margin, liquidation price)
+        let fixtures = [
+            (
+                1000.0,
+                2.0,
+                50_000.0,
+                33_333.333333333333,
+                9.8,
+                33_442.5790916996,
+            ),
+            (
+                1000.0,
+                10.0,
+                50_000.0,
+                45_454.545454545455,
+                46.6,
+                45_647.9266711706,
+            ),
+            (
+                5000.0,
+                50.0,
+                60_000.0,
+                58_823.529411764706,
+                1153.0,
+                59_090.7121218687,
+            ),
+        ];
+        for (margin, leverage, entry, bankruptcy, mm, liq) in fixtures {
+            let order = order(PositionType::LONG, margin, leverage, entry);
+            let health = PositionHealth::from_trader_order(0, &order, entry, &PARAMS);
+            assert_close(health.bankruptcy_price, bankruptcy);
+            assert_close(health.maintenance_margin, mm);
+            assert_close(health.liquidation_price, liq);
+            assert_eq!(health.health, 1.0);
+            assert!(bankruptcy < liq && liq < entry);
+        }
+    }
+
+    #[test]
+    fn test_short_fixtures() {
+        let fixtures = [
+            (1000.0, 2.0, 50_000.0, 100_000.0, 8.6, 99_147.332936744),
+            (
+                1000.0,
+                10.0,
+                50_000.0,
+                55_555.555555555556,
+                45.4,
+                55_276.7152364738,
+            ),
+            // Unleveraged: no bankruptcy price, only the base maintenance margin.
+            (1000.0, 1.0, 50_000.0, 0.0, 4.0, 12_500_000.0),
+        ];
+        for (margin, leverage, entry, bankruptcy, mm, liq) in fixtures {
+            let order = order(PositionType::SHORT, margin, leverage, entry);
+            let health = PositionHealth::from_trader_order(0, &order, entry, &PARAMS);
+            assert_close(health.bankruptcy_price, bankruptcy);
+            assert_close(health.maintenance_margin, mm);
+            assert_close(health.liquidation_price, liq);
+            assert_eq!(health.health, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_health_at_liquidation_boundary() {
+        for position_type in [PositionType::LONG, PositionType::SHORT] {
+            let order = order(position_type.clone(), 1000.0, 10.0, 50_000.0);
+            let at_entry = PositionHealth::from_trader_order(0, &order, 50_000.0, &PARAMS);
+            let liq = at_entry.liquidation_price;
+
+            let at_liq = PositionHealth::from_trader_order(0, &order, liq, &PARAMS);
+            assert_close(at_liq.margin_at_mark, at_liq.maintenance_margin);
+            assert!(at_liq.health.abs() < 1e-9, "{:?}", at_liq);
+
+            let past = match position_type {
+                PositionType::LONG => liq - 50.0,
+                PositionType::SHORT => liq + 50.0,
+            };
+            let past = PositionHealth::from_trader_order(0, &order, past, &PARAMS);
+            assert_eq!(past.health, 0.0);
+            assert!(past.is_liquidatable());
+
+            // Between entry and liquidation the ratio is strictly inside (0, 1).
+            let midway =
+                PositionHealth::from_trader_order(0, &order, (liq + 50_000.0) / 2.0, &PARAMS);
+            assert!(midway.health > 0.0 && midway.health < 1.0);
+            assert!(!midway.is_liquidatable());
+        }
+    }
+
+    #[test]
+    fn test_funding_shifts_liquidation_price() {
+        let order = unpriced(PositionType::LONG, 1000.0, 10.0, 50_000.0)
+            .field("available_margin", 900.0)
+            .build();
+        let health = PositionHealth::from_trader_order(0, &order, 50_000.0, &PARAMS);
+        // Less margin left: liquidated sooner, at a higher price for a long.
+        assert!(health.liquidation_price > 45_647.9266711706);
+        assert_close(health.margin_at_mark, 900.0);
+    }
+
+    #[test]
+    fn test_relayer_reported_prices_are_used() {
+        // The fixture is a filled 2x long at 50_000 the relayer priced itself.
+        let priced = TraderOrderBuilder::new().build();
+        let health = PositionHealth::from_trader_order(0, &priced, 50_000.0, &PARAMS);
+        assert!(!health.estimated);
+        assert_eq!(health.bankruptcy_price, 33_333.0);
+        assert_eq!(health.maintenance_margin, 10.0);
+        assert_eq!(health.liquidation_price, 34_000.0);
+        assert_eq!(health.health, 1.0);
+
+        let at_liq = PositionHealth::from_trader_order(0, &priced, 34_000.0, &PARAMS);
+        assert!(at_liq.health < 0.1, "{:?}", at_liq);
+
+        let built = order(PositionType::LONG, 1000.0, 2.0, 50_000.0);
+        assert!(PositionHealth::from_trader_order(0, &built, 50_000.0, &PARAMS).estimated);
+    }
+
+    #[test]
+    fn test_liquidation_price_without_positive_denominator() {
+        // A long whose margin is already below maintenance.
+        assert_eq!(
+            liquidation_price(&PositionType::LONG, 50_000.0, 1e8, 3_000.0, 500.0),
+            0.0
+        );
+        // A short the margin covers beyond any price rise.
+        assert_eq!(
+            liquidation_price(&PositionType::SHORT, 50_000.0, 1e8, 10.0, 2_010.0),
+            0.0
+        );
+        let order = unpriced(PositionType::LONG, 1000.0, 10.0, 50_000.0)
+            .field("available_margin", -20_000.0)
+            .build();
+        let health = PositionHealth::from_trader_order(0, &order, 50_000.0, &PARAMS);
+        assert_eq!(health.liquidation_price, 0.0);
+        assert!(health.is_liquidatable());
+    }
+
+    #[test]
+    fn test_margin_params_from_relayer_risk_params() {
+        let risk = RiskParams {
+            max_oi_mult: 4.0,
+            max_net_mult: 0.8,
+            max_position_pct: 0.2,
+            min_position_btc: 0.0,
+            max_leverage: 50.0,
+            mm_ratio: 0.5,
+        };
+        let params = MarginParams::from_risk_params(&risk, 0.05, 0.01);
+        assert_eq!(params.maintenance_margin_pct, 0.5);
+        let order = order(PositionType::LONG, 1000.0, 10.0, 50_000.0);
+        let health = PositionHealth::from_trader_order(0, &order, 50_000.0, &params);
+        // 0.1 percentage points more of the 10_000 entry value than PARAMS.
+        assert_close(health.maintenance_margin, 46.6 + 10.0);
+    }
+}
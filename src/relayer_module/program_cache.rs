@@ -0,0 +1,201 @@
+//! In-memory cache for the parsed relayer program (`relayerprogram.json`).
+//!
+//! Every trader/lend order needs the relayer [`RelayerProgram`]. Parsing it
+//! from disk on each order costs a file read plus JSON decoding, so
+//! [`ProgramCache`] keeps one parsed copy behind an `Arc` and reuses it until
+//! either the configured path changes or the file on disk changes. Changes are
+//! detected from file metadata (size and modification time), which needs a
+//! `stat` but no read.
+//!
+//! A missing or invalid file is a [`RelayerProgramError`]. Errors are not
+//! cached, so the next lookup reads the file again.
+
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::relayer_program::{RelayerProgram, RelayerProgramError};
+
+/// Cheap identity of a program file, used to detect on-disk changes.
+/// `None` means the file does not exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramFingerprint {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Where relayer programs come from. The default [`FileProgramLoader`] reads
+/// from disk; tests wrap it to count reads.
+pub trait ProgramLoader: std::fmt::Debug + Send + Sync {
+    /// Fingerprint of the program at `path`, or `None` if it does not exist.
+    fn fingerprint(&self, path: &str) -> Option<ProgramFingerprint>;
+
+    /// Read and validate the program at `path`.
+    fn load(&self, path: &str) -> Result<RelayerProgram, RelayerProgramError>;
+}
+
+/// Loads programs from the filesystem with [`RelayerProgram::load`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileProgramLoader;
+
+impl ProgramLoader for FileProgramLoader {
+    fn fingerprint(&self, path: &str) -> Option<ProgramFingerprint> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(ProgramFingerprint {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn load(&self, path: &str) -> Result<RelayerProgram, RelayerProgramError> {
+        RelayerProgram::load(path)
+    }
+}
+
+struct CachedProgram {
+    path: String,
+    fingerprint: Option<ProgramFingerprint>,
+    program: Arc<RelayerProgram>,
+}
+
+/// Shared cache of the parsed relayer program. Clones share the same entry.
+#[derive(Clone)]
+pub struct ProgramCache {
+    loader: Arc<dyn ProgramLoader>,
+    entry: Arc<Mutex<Option<CachedProgram>>>,
+}
+
+impl std::fmt::Debug for ProgramCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cached_path = self.lock().as_ref().map(|c| c.path.clone());
+        f.debug_struct("ProgramCache")
+            .field("loader", &self.loader)
+            .field("cached_path", &cached_path)
+            .finish()
+    }
+}
+
+impl Default for ProgramCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgramCache {
+    /// Cache backed by [`FileProgramLoader`].
+    pub fn new() -> Self {
+        Self::with_loader(Arc::new(FileProgramLoader))
+    }
+
+    /// Cache backed by a custom loader.
+    pub fn with_loader(loader: Arc<dyn ProgramLoader>) -> Self {
+        Self {
+            loader,
+            entry: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Parsed program for `path`. Reloads only if `path` differs from the cached
+    /// one or the file's fingerprint has changed since it was loaded.
+    pub fn get(&self, path: &str) -> Result<Arc<RelayerProgram>, RelayerProgramError> {
+        let fingerprint = self.loader.fingerprint(path);
+        let mut entry = self.lock();
+        if let Some(cached) = entry.as_ref() {
+            if cached.path == path && cached.fingerprint == fingerprint {
+                return Ok(cached.program.clone());
+            }
+        }
+        let program = Arc::new(self.loader.load(path)?);
+        *entry = Some(CachedProgram {
+            path: path.to_string(),
+            fingerprint,
+            program: program.clone(),
+        });
+        Ok(program)
+    }
+
+    /// Drop the cached program; the next [`get`](ProgramCache::get) reloads it.
+    pub fn invalidate(&self) {
+        *self.lock() = None;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<CachedProgram>> {
+        self.entry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::relayer_program::DEFAULT_RELAYER_PROGRAM_JSON;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Wraps [`FileProgramLoader`] and counts how often the file is parsed.
+    #[derive(Debug, Default)]
+    struct CountingLoader {
+        loads: AtomicUsize,
+    }
+
+    impl ProgramLoader for CountingLoader {
+        fn fingerprint(&self, path: &str) -> Option<ProgramFingerprint> {
+            FileProgramLoader.fingerprint(path)
+        }
+
+        fn load(&self, path: &str) -> Result<RelayerProgram, RelayerProgramError> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            FileProgramLoader.load(path)
+        }
+    }
+
+    fn temp_program_file() -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("relayerprogram-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, DEFAULT_RELAYER_PROGRAM_JSON).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_second_lookup_does_not_reload() {
+        let path = temp_program_file();
+        let path_str = path.to_str().unwrap();
+        let loader = Arc::new(CountingLoader::default());
+        let cache = ProgramCache::with_loader(loader.clone());
+
+        let first = cache.get(path_str).unwrap();
+        let second = cache.get(path_str).unwrap();
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Clones share the entry.
+        let _ = cache.clone().get(path_str).unwrap();
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_reloads_on_file_change_and_path_swap() {
+        let path = temp_program_file();
+        let path_str = path.to_str().unwrap();
+        let loader = Arc::new(CountingLoader::default());
+        let cache = ProgramCache::with_loader(loader.clone());
+
+        cache.get(path_str).unwrap();
+        // A different size always changes the fingerprint, regardless of mtime resolution.
+        std::fs::write(&path, format!("{}\n", DEFAULT_RELAYER_PROGRAM_JSON)).unwrap();
+        cache.get(path_str).unwrap();
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 2);
+
+        cache.invalidate();
+        cache.get(path_str).unwrap();
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 3);
+
+        // A missing file is an error, tried again on every lookup.
+        let missing = std::env::temp_dir().join(format!("missing-{}.json", uuid::Uuid::new_v4()));
+        let err = cache.get(missing.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, RelayerProgramError::NotFound { .. }));
+        assert!(cache.get(missing.to_str().unwrap()).is_err());
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 5);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,131 @@
+//! Token bucket spacing out a relayer client's requests.
+//!
+//! A [`RelayerJsonRpcClient`](super::relayer_api::RelayerJsonRpcClient) and
+//! its clones share one `RateLimiter` sized by a [`RateLimitPolicy`]. Each
+//! request takes a token before it is sent. The bucket holds `burst` tokens
+//! and refills at `requests_per_sec`; a request that finds it empty reserves
+//! the next token and sleeps until it is due, so concurrent callers go out
+//! in the order they asked, and none of them fails for being over the limit.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::RateLimitPolicy;
+
+/// Longest a request waits for a token, however low the rate.
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    policy: RateLimitPolicy,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Below zero when callers are waiting for tokens not yet refilled.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(policy: RateLimitPolicy) -> Self {
+        Self {
+            policy,
+            bucket: Mutex::new(Bucket {
+                tokens: Self::capacity(&policy),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> &RateLimitPolicy {
+        &self.policy
+    }
+
+    fn capacity(policy: &RateLimitPolicy) -> f64 {
+        policy.burst.max(1) as f64
+    }
+
+    /// Take a token, waiting until it is due. Returns the time waited.
+    pub(crate) async fn acquire(&self) -> Duration {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+
+    /// Take the next token at `now` and return how long until it is due,
+    /// at most [`MAX_WAIT`].
+    fn reserve(&self, now: Instant) -> Duration {
+        if self.policy.is_unlimited() {
+            return Duration::ZERO;
+        }
+        let rate = self.policy.requests_per_sec;
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        if now > bucket.refilled_at {
+            let refill = (now - bucket.refilled_at).as_secs_f64() * rate;
+            bucket.tokens = (bucket.tokens + refill).min(Self::capacity(&self.policy));
+            bucket.refilled_at = now;
+        }
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::try_from_secs_f64(-bucket.tokens / rate)
+                .map_or(MAX_WAIT, |wait| wait.min(MAX_WAIT))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_spaced_at_the_rate() {
+        let limiter = RateLimiter::new(RateLimitPolicy::new(10.0, 3));
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(start), Duration::ZERO);
+        }
+        // The fourth and fifth requests queue 100 ms apart.
+        let fourth = limiter.reserve(start);
+        let fifth = limiter.reserve(start);
+        assert!((fourth.as_secs_f64() - 0.1).abs() < 1e-6, "{:?}", fourth);
+        assert!((fifth.as_secs_f64() - 0.2).abs() < 1e-6, "{:?}", fifth);
+
+        // Later the debt is paid and the bucket refilled, but never
+        // above the burst.
+        let later = start + Duration::from_secs(10);
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(later), Duration::ZERO);
+        }
+        assert!(limiter.reserve(later) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_tiny_rate_waits_at_most_max_wait() {
+        let limiter = RateLimiter::new(RateLimitPolicy::new(1e-300, 1));
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), Duration::ZERO);
+        // 1 / 1e-300 seconds does not fit in a Duration.
+        assert_eq!(limiter.reserve(now), MAX_WAIT);
+        assert_eq!(limiter.reserve(now + Duration::from_secs(60)), MAX_WAIT);
+    }
+
+    #[test]
+    fn test_nan_rate_never_waits() {
+        let limiter = RateLimiter::new(RateLimitPolicy::new(f64::NAN, 1));
+        let now = Instant::now();
+        assert!((0..10).all(|_| limiter.reserve(now).is_zero()));
+    }
+
+    #[test]
+    fn test_unlimited_never_waits() {
+        let limiter = RateLimiter::new(RateLimitPolicy::unlimited());
+        let now = Instant::now();
+        assert!((0..1000).all(|_| limiter.reserve(now).is_zero()));
+    }
+}
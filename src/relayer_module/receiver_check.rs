@@ -0,0 +1,232 @@
+//! Safety checks for transfers to ZkOS addresses outside this wallet.
+//!
+//! Funds sent to an address nobody holds the key for cannot be recovered, so
+//! [`OrderWallet::transfer_to_address`](super::order_wallet::OrderWallet::transfer_to_address)
+//! runs three checks before building the transaction. Each can be switched
+//! off in [`TransferOptions`]:
+//!
+//! 1. Format: [`validate_address`] checks that the address is hex of the
+//!    right length, is for the sender's network, and parses as a standard
+//!    address.
+//! 2. Ownership (opt-in): the receiver signs a challenge string with their
+//!    account key using [`create_ownership_proof`], and the sender checks the
+//!    result with [`verify_ownership_proof`].
+//! 3. Confirmation: a first transfer to an unknown address above
+//!    `confirmation_threshold` fails with
+//!    [`ReceiverCheckError::ConfirmationRequired`] until the caller sets
+//!    `confirm_new_receiver`.
+
+use rand::RngCore;
+use crate::compat::{
+    address::AddressType,
+    quisquislib::{RistrettoPublicKey, RistrettoSecretKey, keys::PublicKey},
+    zkvm::Address,
+};
+
+use super::address_book::AddressBookError;
+
+/// Hex length of a standard ZkOS address (69 bytes).
+pub const ADDRESS_HEX_LEN: usize = 138;
+
+/// Hex length of an encrypted account (address plus ElGamal commitment);
+/// a common paste mistake.
+const ENCRYPTED_ACCOUNT_HEX_LEN: usize = 266;
+
+/// Signing label for ownership proofs.
+const OWNERSHIP_PROOF_LABEL: &[u8] = b"nyks-wallet/receiver-ownership/v1";
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReceiverCheckError {
+    #[error("malformed receiver address: {0}")]
+    Malformed(String),
+    #[error("receiver address is for network byte {found:#04x}, expected {expected:#04x}")]
+    WrongNetwork { expected: u8, found: u8 },
+    #[error("an ownership proof is required for {0}")]
+    ProofRequired(String),
+    #[error("ownership proof for {0} is invalid")]
+    InvalidProof(String),
+    #[error(
+        "first transfer of {amount} sats to {address} exceeds {threshold} sats; \
+         set confirm_new_receiver to proceed"
+    )]
+    ConfirmationRequired {
+        address: String,
+        amount: u64,
+        threshold: u64,
+    },
+    /// The receiver is not a usable address book label or address.
+    #[error(transparent)]
+    AddressBook(#[from] AddressBookError),
+    /// The checks passed but the transfer itself failed.
+    #[error("{0}")]
+    Transfer(String),
+}
+
+/// A receiver's answer to an ownership challenge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipProof {
+    pub challenge: String,
+    /// Output of [`create_ownership_proof`].
+    pub proof: String,
+}
+
+/// Which receiver checks [`transfer_to_address`](super::order_wallet::OrderWallet::transfer_to_address) runs.
+/// The default runs the format check and requires confirmation for any
+/// first-time receiver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferOptions {
+    pub check_format: bool,
+    /// Reject the transfer unless `ownership_proof` is present and valid.
+    pub require_ownership_proof: bool,
+    /// Verified whenever present.
+    pub ownership_proof: Option<OwnershipProof>,
+    /// First transfers to unknown receivers above this many sats need confirmation.
+    pub confirmation_threshold: u64,
+    pub confirm_new_receiver: bool,
+    /// Send to an address outside a strict address book.
+    pub allow_unknown: bool,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            check_format: true,
+            require_ownership_proof: false,
+            ownership_proof: None,
+            confirmation_threshold: 0,
+            confirm_new_receiver: false,
+            allow_unknown: false,
+        }
+    }
+}
+
+impl TransferOptions {
+    /// Skip every check, for automation that has already verified the receiver.
+    pub fn unchecked() -> Self {
+        Self {
+            check_format: false,
+            confirm_new_receiver: true,
+            allow_unknown: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// Structural check of a standard ZkOS address. With `expected_network`
+/// set, the leading network byte must match it.
+pub fn validate_address(address: &str, expected_network: Option<u8>) -> Result<(), ReceiverCheckError> {
+    if !address.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ReceiverCheckError::Malformed(
+            "address must be hex-encoded".to_string(),
+        ));
+    }
+    if address.len() == ENCRYPTED_ACCOUNT_HEX_LEN {
+        return Err(ReceiverCheckError::Malformed(
+            "this looks like an encrypted account, not an address".to_string(),
+        ));
+    }
+    if address.len() != ADDRESS_HEX_LEN {
+        return Err(ReceiverCheckError::Malformed(format!(
+            "expected {} hex characters, got {}",
+            ADDRESS_HEX_LEN,
+            address.len()
+        )));
+    }
+    let network = u8::from_str_radix(&address[..2], 16)
+        .map_err(|e| ReceiverCheckError::Malformed(e.to_string()))?;
+    if let Some(expected) = expected_network.filter(|expected| *expected != network) {
+        return Err(ReceiverCheckError::WrongNetwork {
+            expected,
+            found: network,
+        });
+    }
+    address_public_key(address).map(|_| ())
+}
+
+/// Network byte of a hex address.
+pub fn network_byte(address: &str) -> Option<u8> {
+    address.get(..2).and_then(|b| u8::from_str_radix(b, 16).ok())
+}
+
+/// Random challenge to send to a receiver.
+pub fn new_ownership_challenge() -> String {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Prove ownership of `address` by signing `challenge` with its account key.
+/// Receivers run this and send the result back to the payer.
+pub fn create_ownership_proof(
+    secret_key: &RistrettoSecretKey,
+    address: &str,
+    challenge: &str,
+) -> Result<String, ReceiverCheckError> {
+    let public_key = address_public_key(address)?;
+    let signature = public_key.sign_msg(
+        &proof_message(address, challenge),
+        secret_key,
+        OWNERSHIP_PROOF_LABEL,
+    );
+    let bytes = bincode::serialize(&signature)
+        .map_err(|e| ReceiverCheckError::Malformed(e.to_string()))?;
+    Ok(hex::encode(bytes))
+}
+
+/// Check a proof made by [`create_ownership_proof`].
+pub fn verify_ownership_proof(address: &str, proof: &OwnershipProof) -> Result<(), ReceiverCheckError> {
+    let invalid = || ReceiverCheckError::InvalidProof(address.to_string());
+    let public_key = address_public_key(address)?;
+    let bytes = hex::decode(&proof.proof).map_err(|_| invalid())?;
+    let signature = bincode::deserialize(&bytes).map_err(|_| invalid())?;
+    public_key
+        .verify_msg(
+            &proof_message(address, &proof.challenge),
+            &signature,
+            OWNERSHIP_PROOF_LABEL,
+        )
+        .map_err(|_| invalid())
+}
+
+fn address_public_key(address: &str) -> Result<RistrettoPublicKey, ReceiverCheckError> {
+    let address = Address::from_hex(address, AddressType::Standard)
+        .map_err(|e| ReceiverCheckError::Malformed(e.to_string()))?;
+    Ok(address.into())
+}
+
+fn proof_message(address: &str, challenge: &str) -> Vec<u8> {
+    format!("{}:{}", address, challenge).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_malformed_addresses_rejected() {
+        let too_short = "0c".repeat(10);
+        assert!(matches!(
+            validate_address(&too_short, None),
+            Err(ReceiverCheckError::Malformed(_))
+        ));
+        let not_hex = "zz".repeat(ADDRESS_HEX_LEN / 2);
+        assert!(matches!(
+            validate_address(&not_hex, None),
+            Err(ReceiverCheckError::Malformed(_))
+        ));
+        let encrypted = "0c".repeat(ENCRYPTED_ACCOUNT_HEX_LEN / 2);
+        let err = validate_address(&encrypted, None).unwrap_err();
+        assert!(err.to_string().contains("encrypted account"));
+        let wrong_network = format!("0d{}", "00".repeat(ADDRESS_HEX_LEN / 2 - 1));
+        assert_eq!(
+            validate_address(&wrong_network, Some(0x0c)),
+            Err(ReceiverCheckError::WrongNetwork {
+                expected: 0x0c,
+                found: 0x0d
+            })
+        );
+        // Right shape, but not a valid address (checksum / curve point).
+        let garbage = format!("0c{}", "00".repeat(ADDRESS_HEX_LEN / 2 - 1));
+        assert!(validate_address(&garbage, Some(0x0c)).is_err());
+    }
+}
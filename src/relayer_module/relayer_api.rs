@@ -12,10 +12,28 @@
 
 use super::relayer_types::{
     AccountSummary, AccountSummaryArgs, AllAccountSummariesArgs, AllAccountSummariesResponse,
-    ApyChartArgs, ApyChartPoint, BtcUsdPrice, Candle, Candles, FeeHistory, FundingHistoryEntry,
-    FundingRate, HistoricalFeeArgs, HistoricalFundingArgs, HistoricalPriceArgs, LendOrder,
-    LendOrderV1, LendPoolInfo, MarketStats, OpenInterest, OrderBook, PositionSize, RecentOrders,
-    RequestResponse, TraderOrder, TraderOrderV1, TransactionHashArgs, TxHash,
+    ApyChartArgs, ApyChartPoint, BtcUsdPrice, Candle, CandleInterval, Candles, FeeHistory,
+    FundingHistoryEntry, FundingRate, HistoricalFeeArgs, HistoricalFundingArgs,
+    HistoricalPriceArgs, LendOrder, LendOrderV1, LendPoolInfo, MarketStats, OpenInterest,
+    OrderBook, PositionSize, RecentOrders, RequestResponse, TraderOrder, TraderOrderV1,
+    TransactionHashArgs, TxHash,
+};
+use super::activity::{ActivityCategory, ActivityTracker};
+use super::capabilities::{
+    Capability, RelayerCapabilities, ServerInfo, METHOD_NOT_FOUND, PARTIAL_SETTLE_METHOD,
+    SERVER_INFO_METHOD,
+};
+use super::endpoint_pool::{
+    is_failover_error, is_submit_failover_error, Endpoint, EndpointHealth, EndpointPool,
+};
+use super::fees::{FeeEstimate, FeeSchedule, FEE_HISTORY_PAGE_SIZE};
+use super::market_snapshot::{MarketSnapshot, PositionSizeInfo};
+use super::rate_limiter::RateLimiter;
+use super::request_metrics::{MetricsRecorder, RelayerMetrics};
+use super::response_cache::{EndpointClass, ResponseCache};
+use crate::config::failover::split_endpoints;
+use crate::config::{
+    FailoverPolicy, FailoverStrategy, RateLimitPolicy, RelayerEndPointConfig, RetryPolicy,
 };
 use chrono::{DateTime, Utc};
 use jsonrpsee::core::client::ClientT;
@@ -23,21 +41,64 @@ use jsonrpsee::core::traits::ToRpcParams;
 use serde_json::value::RawValue;
 
 use jsonrpsee::core::client::Error as RpcError;
-use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::http_client::{HeaderMap, HeaderValue, HttpClient, HttpClientBuilder};
+use log::debug;
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use jsonrpsee::rpc_params;
-use serde::{Deserialize, Serialize};
-use twilight_client_sdk::relayer_types::{
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::compat::relayer_types::{
     CancelTraderOrderZkos, CancelTraderOrderZkosSlTp, CreateLendOrderZkos,
     CreateTraderOrderClientZkos, ExecuteLendOrderZkos, ExecuteTraderOrderZkos,
-    ExecuteTraderOrderZkosSlTp, QueryLendOrderZkos, QueryTraderOrderZkos,
+    ExecuteTraderOrderZkosSlTp, OrderType, QueryLendOrderZkos, QueryTraderOrderZkos,
 };
 
+#[cfg(feature = "ws")]
+pub use super::relayer_ws::{
+    DEFAULT_RECONNECT_BACKOFF, FeedState, FeedStatus, FeedStream, RelayerWsClient,
+};
+
+/// Params of [`PARTIAL_SETTLE_METHOD`]: a hex-encoded execute request and the
+/// share of the position size it settles.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartialSettleData {
+    pub data: String,
+    pub settle_size: f64,
+}
+
+/// Candles requested per `candle_data` page by [`RelayerJsonRpcClient::candles`].
+pub const CANDLE_PAGE_SIZE: usize = 500;
+
 /// Wrapper for hex-encoded binary data sent to relayer endpoints.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HexEncodedData {
     pub data: String,
 }
 
+impl HexEncodedData {
+    /// Hex of the bincode encoding of `tx`.
+    fn bincode<T: Serialize>(tx: &T) -> Result<Self, RpcError> {
+        let data = bincode::serialize(tx)
+            .map_err(|e| RpcError::Custom(format!("failed to encode request: {}", e)))?;
+        Ok(Self {
+            data: hex::encode(data),
+        })
+    }
+}
+
+/// Order submit payload: [`HexEncodedData`] plus the optional idempotency key
+/// of relayers with [`Capability::ClientOrderId`]. Without a key it encodes
+/// exactly like [`HexEncodedData`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubmitOrderData {
+    pub data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+}
+
 /// JSON-RPC HTTP client for the Twilight relayer API.
 ///
 /// Provides async methods for all relayer endpoints including market data,
@@ -65,19 +126,373 @@ pub struct HexEncodedData {
 /// ```
 #[derive(Debug, Clone)]
 pub struct RelayerJsonRpcClient {
-    client: HttpClient,
+    /// Shared by clones, so they see the same endpoint health.
+    endpoints: Arc<EndpointPool>,
+    activity: Option<ActivityTracker>,
+    /// Shared by clones; filled by the first capabilities handshake.
+    capabilities: Arc<Mutex<Option<RelayerCapabilities>>>,
+    cache: Option<ResponseCache>,
+    retry_policy: RetryPolicy,
+    /// Shared by clones, so together they stay within one rate limit.
+    limiter: Arc<RateLimiter>,
+    /// Shared by clones.
+    metrics: Arc<MetricsRecorder>,
 }
 
 impl RelayerJsonRpcClient {
     /// Create a new relayer client with the specified endpoint URL.
     ///
+    /// Uses [`RetryPolicy::from_env`]; see [`new_with_policy`](Self::new_with_policy).
+    ///
     /// # Arguments
     /// * `url` - The base URL of the relayer API (e.g., "http://0.0.0.0:8088/api")
     pub fn new(url: &str) -> Result<Self, RpcError> {
-        let client = HttpClientBuilder::default()
-            .request_timeout(std::time::Duration::from_secs(30))
-            .build(url)?;
-        Ok(Self { client })
+        Self::new_with_policy(url, RetryPolicy::from_env())
+    }
+
+    /// Create a relayer client whose requests time out after
+    /// `policy.request_timeout`. The tx hash polling helpers that take this
+    /// client retry with the policy's attempts and delays.
+    ///
+    /// `url` may be a comma-separated list of endpoints, which are failed over
+    /// with [`FailoverPolicy::from_env`]; see [`new_with_failover`](Self::new_with_failover).
+    pub fn new_with_policy(url: &str, policy: RetryPolicy) -> Result<Self, RpcError> {
+        Self::new_with_failover_policy(split_endpoints(url), policy, FailoverPolicy::from_env())
+    }
+
+    /// Create a relayer client that sends each request to one of `endpoints`,
+    /// in `strategy` order, and moves on to the next one when an endpoint
+    /// cannot be reached or times out. Errors returned by a relayer are not
+    /// retried elsewhere; see [`endpoint_pool`](super::endpoint_pool).
+    pub fn new_with_failover(
+        endpoints: Vec<String>,
+        strategy: FailoverStrategy,
+    ) -> Result<Self, RpcError> {
+        Self::new_with_failover_policy(
+            endpoints,
+            RetryPolicy::from_env(),
+            FailoverPolicy::new(strategy),
+        )
+    }
+
+    /// [`new_with_failover`](Self::new_with_failover) with explicit retry and failover policies.
+    pub fn new_with_failover_policy(
+        endpoints: Vec<String>,
+        policy: RetryPolicy,
+        failover: FailoverPolicy,
+    ) -> Result<Self, RpcError> {
+        if endpoints.is_empty() {
+            return Err(RpcError::Custom(
+                "No relayer endpoint configured".to_string(),
+            ));
+        }
+        let endpoints = endpoints
+            .into_iter()
+            .map(|url| {
+                let client = HttpClientBuilder::default()
+                    .request_timeout(policy.request_timeout)
+                    .build(&url)?;
+                Ok(Endpoint { url, client })
+            })
+            .collect::<Result<Vec<_>, RpcError>>()?;
+        Ok(Self {
+            endpoints: Arc::new(EndpointPool::new(endpoints, failover)),
+            activity: None,
+            capabilities: Arc::new(Mutex::new(None)),
+            cache: None,
+            retry_policy: policy,
+            limiter: Arc::new(RateLimiter::new(RateLimitPolicy::from_env())),
+            metrics: Arc::new(MetricsRecorder::default()),
+        })
+    }
+
+    /// Client for the relayer endpoints and policies of `config`.
+    pub fn from_endpoint_config(config: &RelayerEndPointConfig) -> Result<Self, RpcError> {
+        let client = Self::new_with_failover_policy(
+            config.relayer_api_endpoints(),
+            config.retry_policy,
+            config.failover,
+        )?
+        .with_rate_limit(config.rate_limit);
+        Ok(match config.metrics_log_interval {
+            Some(interval) => client.with_metrics_log_interval(interval),
+            None => client,
+        })
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn failover_policy(&self) -> &FailoverPolicy {
+        self.endpoints.policy()
+    }
+
+    pub fn rate_limit(&self) -> &RateLimitPolicy {
+        self.limiter.policy()
+    }
+
+    /// Limit requests to `policy`, replacing the limit read from the
+    /// environment. Clones made after this share the new limit; see
+    /// [`rate_limiter`](super::rate_limiter).
+    pub fn with_rate_limit(mut self, policy: RateLimitPolicy) -> Self {
+        self.limiter = Arc::new(RateLimiter::new(policy));
+        self
+    }
+
+    /// Call counts, errors and latencies by method, over this client and its
+    /// clones; see [`request_metrics`](super::request_metrics).
+    pub fn metrics(&self) -> RelayerMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Log [`metrics`](Self::metrics) every `interval` until the client and
+    /// all its clones are dropped. Needs a Tokio runtime; without one the
+    /// metrics are only available through [`metrics`](Self::metrics).
+    pub fn with_metrics_log_interval(self, interval: Duration) -> Self {
+        let metrics = Arc::downgrade(&self.metrics);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    let mut ticker = tokio::time::interval(interval);
+                    ticker.tick().await;
+                    loop {
+                        ticker.tick().await;
+                        let Some(metrics) = metrics.upgrade() else {
+                            break;
+                        };
+                        metrics.snapshot().log();
+                    }
+                });
+            }
+            Err(_) => debug!("No Tokio runtime, relayer metrics will not be logged"),
+        }
+        self
+    }
+
+    /// Health of each endpoint, in configured order.
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.endpoints.health()
+    }
+
+    /// Count every request made through this client as a relayer call in `tracker`.
+    pub fn with_activity(mut self, tracker: ActivityTracker) -> Self {
+        self.activity = Some(tracker);
+        self
+    }
+
+    /// Serve price, order book and pool info reads from `cache`. Pass clones of
+    /// one cache to every client on the host to share entries between them;
+    /// see [`response_cache`](super::response_cache).
+    pub fn with_response_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.cache.as_ref()
+    }
+
+    /// Parameterless read of `method`, through the response cache when one is
+    /// set. Hits and misses are counted in the metrics.
+    async fn cached<T>(&self, method: &str, class: EndpointClass) -> Result<T, RpcError>
+    where
+        T: DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        let fetch = || async { self.request(method, rpc_params![]).await };
+        match &self.cache {
+            Some(cache) => {
+                let key = format!("{} {}", self.endpoints.primary().url, method);
+                let observe = |hit| self.metrics.record_cache(method, hit);
+                cache
+                    .get_or_fetch_observed(&key, class, observe, fetch)
+                    .await
+            }
+            None => fetch().await,
+        }
+    }
+
+    /// Send `method` to the endpoints in failover order until one answers.
+    async fn request<R>(&self, method: &str, params: impl ToRpcParams + Send) -> Result<R, RpcError>
+    where
+        R: DeserializeOwned,
+    {
+        self.request_with(method, params, is_failover_error).await
+    }
+
+    /// [`request`](Self::request), moving on to the next endpoint only when
+    /// `fails_over` accepts the error. Waits for the rate limiter first and
+    /// records the call in the metrics.
+    async fn request_with<R>(
+        &self,
+        method: &str,
+        params: impl ToRpcParams + Send,
+        fails_over: fn(&RpcError) -> bool,
+    ) -> Result<R, RpcError>
+    where
+        R: DeserializeOwned,
+    {
+        let throttled = !self.limiter.acquire().await.is_zero();
+        let started = Instant::now();
+        let result = self.send_with(method, params, fails_over).await;
+        self.metrics
+            .record(method, started.elapsed(), result.is_ok(), throttled);
+        result
+    }
+
+    async fn send_with<R>(
+        &self,
+        method: &str,
+        params: impl ToRpcParams + Send,
+        fails_over: fn(&RpcError) -> bool,
+    ) -> Result<R, RpcError>
+    where
+        R: DeserializeOwned,
+    {
+        // Encoded once so every endpoint gets the same payload.
+        let params = RawParams(
+            params
+                .to_rpc_params()
+                .map_err(|e| RpcError::Custom(format!("Failed to encode params: {}", e)))?,
+        );
+        let mut last_error = None;
+        for index in self.endpoints.attempt_order() {
+            let endpoint = self.endpoints.endpoint(index);
+            match self.rpc(endpoint).request(method, params.clone()).await {
+                Err(e) if fails_over(&e) => {
+                    debug!("Relayer endpoint {} failed {}: {}", endpoint.url, method, e);
+                    self.endpoints.record_failure(index, &e);
+                    last_error = Some(e);
+                }
+                result => {
+                    self.endpoints.record_success(index);
+                    return result;
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| RpcError::Custom("No relayer endpoint configured".to_string())))
+    }
+
+    /// HTTP client to use for the next request to `endpoint`.
+    ///
+    /// jsonrpsee only supports headers fixed at build time, so when a trace context is
+    /// active (`otel` feature) a short-lived client carrying `traceparent`/`tracestate`
+    /// is built for the call. Otherwise the endpoint's shared client is reused.
+    fn rpc<'a>(&self, endpoint: &'a Endpoint) -> Cow<'a, HttpClient> {
+        if let Some(activity) = &self.activity {
+            activity.record(ActivityCategory::RelayerCalls);
+        }
+        let trace_headers = crate::telemetry::trace_headers();
+        if trace_headers.is_empty() {
+            return Cow::Borrowed(&endpoint.client);
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in trace_headers {
+            let name: &'static str = match name.as_str() {
+                "traceparent" => "traceparent",
+                "tracestate" => "tracestate",
+                _ => continue,
+            };
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+        match HttpClientBuilder::default()
+            .request_timeout(self.retry_policy.request_timeout)
+            .set_headers(headers)
+            .build(&endpoint.url)
+        {
+            Ok(client) => Cow::Owned(client),
+            Err(e) => {
+                debug!("Failed to build traced relayer client, falling back: {}", e);
+                Cow::Borrowed(&endpoint.client)
+            }
+        }
+    }
+
+    /// Submit an order-mutating request and record the returned request id on the active span.
+    ///
+    /// Only fails over when the endpoint could not be reached: a timed out
+    /// submit may have been accepted, and sending it again could double it.
+    async fn submit<P: Serialize + Send>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<RequestResponse, RpcError> {
+        let response: RequestResponse = self
+            .request_with(method, AsRpcParams(params), is_submit_failover_error)
+            .await?;
+        crate::telemetry::record_request_id(&response.id_key);
+        Ok(response)
+    }
+
+    // -------------------------
+    // Capabilities
+    // -------------------------
+
+    /// Capabilities of the connected relayer. The handshake runs on first use
+    /// and its result is cached; see [`capabilities`](super::capabilities).
+    pub async fn capabilities(&self) -> Result<RelayerCapabilities, RpcError> {
+        if let Some(capabilities) = self.lock_capabilities().clone() {
+            return Ok(capabilities);
+        }
+        self.refresh_capabilities().await
+    }
+
+    /// Re-run the capabilities handshake and replace the cached result.
+    pub async fn refresh_capabilities(&self) -> Result<RelayerCapabilities, RpcError> {
+        let info: Result<ServerInfo, RpcError> =
+            self.request(SERVER_INFO_METHOD, rpc_params![]).await;
+        let capabilities = match info {
+            Ok(info) => RelayerCapabilities::from_server_info(&info),
+            Err(e) if is_method_not_found(&e) => self.probe_capabilities().await?,
+            Err(e) => return Err(e),
+        };
+        debug!("Relayer capabilities: {:?}", capabilities);
+        *self.lock_capabilities() = Some(capabilities.clone());
+        Ok(capabilities)
+    }
+
+    /// Whether the relayer supports `capability`. If the handshake itself
+    /// fails this returns `true`, so the dependent call reports the real error.
+    pub async fn supports(&self, capability: Capability) -> bool {
+        match self.capabilities().await {
+            Ok(capabilities) => capabilities.supports(capability),
+            Err(e) => {
+                debug!("Relayer capabilities unavailable, assuming {}: {}", capability, e);
+                true
+            }
+        }
+    }
+
+    /// Infer capabilities on a relayer without a version endpoint by calling
+    /// each probeable method with an empty payload.
+    async fn probe_capabilities(&self) -> Result<RelayerCapabilities, RpcError> {
+        let mut capabilities = RelayerCapabilities::default();
+        for capability in Capability::ALL {
+            let Some(method) = capability.probe_method() else {
+                continue;
+            };
+            let params = HexEncodedData {
+                data: String::new(),
+            };
+            let probe: Result<serde_json::Value, RpcError> =
+                self.request(method, AsRpcParams(params)).await;
+            let supported = match probe {
+                Ok(_) => true,
+                Err(e) if is_method_not_found(&e) => false,
+                // The method exists and rejected the empty payload.
+                Err(RpcError::Call(_)) => true,
+                Err(e) => return Err(e),
+            };
+            capabilities.set(capability, supported);
+        }
+        Ok(capabilities)
+    }
+
+    fn lock_capabilities(&self) -> std::sync::MutexGuard<'_, Option<RelayerCapabilities>> {
+        self.capabilities.lock().unwrap_or_else(|e| e.into_inner())
     }
 
     // -------------------------
@@ -85,8 +500,15 @@ impl RelayerJsonRpcClient {
     // -------------------------
 
     /// Get the current BTC/USD price from the relayer.
+    ///
+    /// Served from the response cache when one is configured.
     pub async fn btc_usd_price(&self) -> Result<BtcUsdPrice, RpcError> {
-        self.client.request("btc_usd_price", rpc_params![]).await
+        self.cached("btc_usd_price", EndpointClass::Price).await
+    }
+
+    /// [`btc_usd_price`](Self::btc_usd_price), always asking the relayer.
+    pub async fn btc_usd_price_uncached(&self) -> Result<BtcUsdPrice, RpcError> {
+        self.request("btc_usd_price", rpc_params![]).await
     }
 
     /// Get historical BTC/USD price data for a given time range.
@@ -94,72 +516,177 @@ impl RelayerJsonRpcClient {
         &self,
         params: HistoricalPriceArgs,
     ) -> Result<Vec<BtcUsdPrice>, RpcError> {
-        self.client
-            .request("historical_price", AsRpcParams(params))
-            .await
+        self.request("historical_price", AsRpcParams(params)).await
     }
 
     /// Get candlestick/OHLCV data for price charting.
     pub async fn candle_data(&self, params: Candles) -> Result<Vec<Candle>, RpcError> {
-        self.client
-            .request("candle_data", AsRpcParams(params))
-            .await
+        self.request("candle_data", AsRpcParams(params)).await
+    }
+
+    /// Up to `limit` candles of `interval` starting at `since`, oldest first.
+    ///
+    /// Fetches [`CANDLE_PAGE_SIZE`] candles per `candle_data` request until
+    /// `limit` is reached or the relayer has no more. Intervals without trades
+    /// are left out rather than filled in.
+    pub async fn candles(
+        &self,
+        interval: CandleInterval,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<Candle>, RpcError> {
+        let mut candles: Vec<Candle> = Vec::new();
+        while candles.len() < limit {
+            let requested = (limit - candles.len()).min(CANDLE_PAGE_SIZE);
+            let page = self
+                .candle_data(Candles {
+                    interval: interval.into(),
+                    since,
+                    limit: requested as i64,
+                    offset: candles.len() as i64,
+                })
+                .await?;
+            let last_page = page.len() < requested;
+            candles.extend(page);
+            if last_page {
+                break;
+            }
+        }
+        candles.sort_by_key(|candle| candle.started_at);
+        candles.dedup_by_key(|candle| candle.started_at);
+        candles.truncate(limit);
+        Ok(candles)
     }
 
     pub async fn historical_funding_rate(
         &self,
         params: HistoricalFundingArgs,
     ) -> Result<Vec<FundingRate>, RpcError> {
-        self.client
-            .request("historical_funding_rate", AsRpcParams(params))
+        self.request("historical_funding_rate", AsRpcParams(params))
             .await
     }
 
     pub async fn get_funding_rate(&self) -> Result<FundingRate, RpcError> {
-        self.client.request("get_funding_rate", rpc_params![]).await
+        self.request("get_funding_rate", rpc_params![]).await
     }
 
     pub async fn historical_fee_rate(
         &self,
         params: HistoricalFeeArgs,
     ) -> Result<Vec<FeeHistory>, RpcError> {
-        self.client
-            .request("historical_fee_rate", AsRpcParams(params))
+        self.request("historical_fee_rate", AsRpcParams(params))
             .await
     }
 
     pub async fn get_fee_rate(&self) -> Result<FeeHistory, RpcError> {
-        self.client.request("get_fee_rate", rpc_params![]).await
+        self.request("get_fee_rate", rpc_params![]).await
+    }
+
+    /// The relayer's current fee rates in basis points.
+    pub async fn current_fees(&self) -> Result<FeeSchedule, RpcError> {
+        Ok(FeeSchedule::from_fee_history(&self.get_fee_rate().await?))
+    }
+
+    /// Fees, in sats, of opening and closing an order worth `order_value`
+    /// sats (`initial margin * leverage`) at the current rates; see
+    /// [`fees`](super::fees).
+    pub async fn estimate_order_fees(
+        &self,
+        order_value: u64,
+        order_type: OrderType,
+    ) -> Result<FeeEstimate, RpcError> {
+        let schedule = self.current_fees().await?;
+        Ok(FeeEstimate::new(&schedule, order_value, order_type))
     }
 
+    /// Every fee rate change in `range`, oldest first.
+    ///
+    /// Fetches [`FEE_HISTORY_PAGE_SIZE`] entries per `historical_fee_rate`
+    /// request until the relayer has no more.
+    pub async fn fee_history(
+        &self,
+        range: std::ops::Range<DateTime<Utc>>,
+    ) -> Result<Vec<FeeHistory>, RpcError> {
+        let mut history: Vec<FeeHistory> = Vec::new();
+        if range.start >= range.end {
+            return Ok(history);
+        }
+        loop {
+            let page = self
+                .historical_fee_rate(HistoricalFeeArgs {
+                    from: range.start,
+                    to: range.end,
+                    limit: FEE_HISTORY_PAGE_SIZE as i64,
+                    offset: history.len() as i64,
+                })
+                .await?;
+            let last_page = page.len() < FEE_HISTORY_PAGE_SIZE;
+            history.extend(page);
+            if last_page {
+                break;
+            }
+        }
+        history.sort_by_key(|fee| fee.timestamp);
+        Ok(history)
+    }
+
+    /// Served from the response cache when one is configured.
     pub async fn open_limit_orders(&self) -> Result<OrderBook, RpcError> {
-        self.client
-            .request("open_limit_orders", rpc_params![])
+        self.cached("open_limit_orders", EndpointClass::OrderBook)
             .await
     }
 
+    pub async fn open_limit_orders_uncached(&self) -> Result<OrderBook, RpcError> {
+        self.request("open_limit_orders", rpc_params![]).await
+    }
+
     pub async fn recent_trade_orders(&self) -> Result<RecentOrders, RpcError> {
-        self.client
-            .request("recent_trade_orders", rpc_params![])
-            .await
+        self.request("recent_trade_orders", rpc_params![]).await
     }
 
     pub async fn position_size(&self) -> Result<PositionSize, RpcError> {
-        self.client.request("position_size", rpc_params![]).await
+        self.request("position_size", rpc_params![]).await
+    }
+
+    /// Open long and short position sizes with their shares; see
+    /// [`PositionSizeInfo`].
+    pub async fn position_size_info(&self) -> Result<PositionSizeInfo, RpcError> {
+        self.position_size().await.map(PositionSizeInfo::from)
+    }
+
+    /// Price, funding rate, order book, recent trades and position sizes,
+    /// fetched concurrently. A part that fails is `None` in the snapshot; see
+    /// [`market_snapshot`](super::market_snapshot).
+    pub async fn market_snapshot(&self) -> MarketSnapshot {
+        let captured_at = Utc::now();
+        let (price, funding_rate, order_book, recent_trades, position_size) = tokio::join!(
+            self.btc_usd_price(),
+            self.get_funding_rate(),
+            self.open_limit_orders(),
+            self.recent_trade_orders(),
+            self.position_size_info()
+        );
+        MarketSnapshot::from_results(
+            captured_at,
+            price,
+            funding_rate,
+            order_book,
+            recent_trades,
+            position_size,
+        )
     }
 
     pub async fn transaction_hashes(
         &self,
         params: TransactionHashArgs,
     ) -> Result<Vec<TxHash>, RpcError> {
-        self.client
-            .request("transaction_hashes", AsRpcParams(params))
+        self.request("transaction_hashes", AsRpcParams(params))
             .await
     }
 
     /// Get the current server time in UTC.
     pub async fn server_time(&self) -> Result<DateTime<Utc>, RpcError> {
-        self.client.request("server_time", rpc_params![]).await
+        self.request("server_time", rpc_params![]).await
     }
 
     // -------------------------
@@ -167,27 +694,25 @@ impl RelayerJsonRpcClient {
     // -------------------------
 
     /// Query trader order information using ZkOS parameters.
+    ///
+    /// `tx` must be signed with the account's secret key; callers holding
+    /// per-account keys can build it with
+    /// [`order_query::build_trader_order_query`](super::order_query::build_trader_order_query).
     pub async fn trader_order_info(
         &self,
         tx: QueryTraderOrderZkos,
     ) -> Result<TraderOrder, RpcError> {
-        let data = bincode::serialize(&tx).unwrap();
-        let params = HexEncodedData {
-            data: hex::encode(data),
-        };
-        self.client
-            .request("trader_order_info", AsRpcParams(params))
-            .await
+        let params = HexEncodedData::bincode(&tx)?;
+        self.request("trader_order_info", AsRpcParams(params)).await
     }
 
+    /// Query lend order information using ZkOS parameters.
+    ///
+    /// `tx` must be signed with the account's secret key; see
+    /// [`order_query::build_lend_order_query`](super::order_query::build_lend_order_query).
     pub async fn lend_order_info(&self, tx: QueryLendOrderZkos) -> Result<LendOrder, RpcError> {
-        let data = bincode::serialize(&tx).unwrap();
-        let params = HexEncodedData {
-            data: hex::encode(data),
-        };
-        self.client
-            .request("lend_order_info", AsRpcParams(params))
-            .await
+        let params = HexEncodedData::bincode(&tx)?;
+        self.request("lend_order_info", AsRpcParams(params)).await
     }
 
     /// Query enhanced lend order info (v1) with unrealised profit and APR.
@@ -195,12 +720,8 @@ impl RelayerJsonRpcClient {
         &self,
         tx: QueryLendOrderZkos,
     ) -> Result<LendOrderV1, RpcError> {
-        let data = bincode::serialize(&tx).unwrap();
-        let params = HexEncodedData {
-            data: hex::encode(data),
-        };
-        self.client
-            .request("lend_order_info_v1", AsRpcParams(params))
+        let params = HexEncodedData::bincode(&tx)?;
+        self.request("lend_order_info_v1", AsRpcParams(params))
             .await
     }
 
@@ -208,11 +729,8 @@ impl RelayerJsonRpcClient {
         &self,
         tx: QueryTraderOrderZkos,
     ) -> Result<Vec<TraderOrder>, RpcError> {
-        let data = bincode::serialize(&tx).unwrap();
-        let data = hex::encode(data);
-        let params = HexEncodedData { data };
-        self.client
-            .request("historical_trader_order_info", AsRpcParams(params))
+        let params = HexEncodedData::bincode(&tx)?;
+        self.request("historical_trader_order_info", AsRpcParams(params))
             .await
     }
 
@@ -220,12 +738,8 @@ impl RelayerJsonRpcClient {
         &self,
         tx: QueryLendOrderZkos,
     ) -> Result<Vec<LendOrder>, RpcError> {
-        let data = bincode::serialize(&tx).unwrap();
-        let params = HexEncodedData {
-            data: hex::encode(data),
-        };
-        self.client
-            .request("historical_lend_order_info", AsRpcParams(params))
+        let params = HexEncodedData::bincode(&tx)?;
+        self.request("historical_lend_order_info", AsRpcParams(params))
             .await
     }
 
@@ -238,24 +752,44 @@ impl RelayerJsonRpcClient {
         &self,
         tx: CreateTraderOrderClientZkos,
     ) -> Result<RequestResponse, RpcError> {
-        let params = HexEncodedData {
+        self.submit_trade_order_with_key(tx, None).await
+    }
+
+    /// [`submit_trade_order`](Self::submit_trade_order) tagged with
+    /// `client_order_id`. Only send a key to a relayer with
+    /// [`Capability::ClientOrderId`]; it answers a repeated key with the
+    /// request id of the order it already accepted.
+    pub async fn submit_trade_order_with_key(
+        &self,
+        tx: CreateTraderOrderClientZkos,
+        client_order_id: Option<&str>,
+    ) -> Result<RequestResponse, RpcError> {
+        let params = SubmitOrderData {
             data: tx.encode_as_hex_string().map_err(|e| RpcError::Custom(e))?,
+            client_order_id: client_order_id.map(str::to_string),
         };
-        self.client
-            .request("submit_trade_order", AsRpcParams(params))
-            .await
+        self.submit("submit_trade_order", params).await
     }
 
     pub async fn submit_lend_order(
         &self,
         tx: CreateLendOrderZkos,
     ) -> Result<RequestResponse, RpcError> {
-        let params = HexEncodedData {
+        self.submit_lend_order_with_key(tx, None).await
+    }
+
+    /// Lend-order counterpart of
+    /// [`submit_trade_order_with_key`](Self::submit_trade_order_with_key).
+    pub async fn submit_lend_order_with_key(
+        &self,
+        tx: CreateLendOrderZkos,
+        client_order_id: Option<&str>,
+    ) -> Result<RequestResponse, RpcError> {
+        let params = SubmitOrderData {
             data: tx.encode_as_hex_string(),
+            client_order_id: client_order_id.map(str::to_string),
         };
-        self.client
-            .request("submit_lend_order", AsRpcParams(params))
-            .await
+        self.submit("submit_lend_order", params).await
     }
 
     pub async fn settle_trade_order(
@@ -265,9 +799,7 @@ impl RelayerJsonRpcClient {
         let params = HexEncodedData {
             data: tx.encode_as_hex_string(),
         };
-        self.client
-            .request("settle_trade_order", AsRpcParams(params))
-            .await
+        self.submit("settle_trade_order", params).await
     }
 
     pub async fn settle_trade_order_sltp(
@@ -277,9 +809,21 @@ impl RelayerJsonRpcClient {
         let params = HexEncodedData {
             data: tx.encode_as_hex_string(),
         };
-        self.client
-            .request("settle_trade_order", AsRpcParams(params))
-            .await
+        self.submit("settle_trade_order", params).await
+    }
+
+    /// Settle `settle_size` of the position of `tx`'s order; needs
+    /// [`Capability::PartialClose`].
+    pub async fn settle_trade_order_partial(
+        &self,
+        tx: ExecuteTraderOrderZkos,
+        settle_size: f64,
+    ) -> Result<RequestResponse, RpcError> {
+        let params = PartialSettleData {
+            data: tx.encode_as_hex_string(),
+            settle_size,
+        };
+        self.submit(PARTIAL_SETTLE_METHOD, params).await
     }
 
     pub async fn settle_lend_order(
@@ -289,9 +833,7 @@ impl RelayerJsonRpcClient {
         let params = HexEncodedData {
             data: tx.encode_as_hex_string(),
         };
-        self.client
-            .request("settle_lend_order", AsRpcParams(params))
-            .await
+        self.submit("settle_lend_order", params).await
     }
 
     pub async fn cancel_trader_order(
@@ -301,9 +843,7 @@ impl RelayerJsonRpcClient {
         let params = HexEncodedData {
             data: tx.encode_as_hex_string(),
         };
-        self.client
-            .request("cancel_trader_order", AsRpcParams(params))
-            .await
+        self.submit("cancel_trader_order", params).await
     }
 
     pub async fn cancel_trader_order_sltp(
@@ -313,17 +853,26 @@ impl RelayerJsonRpcClient {
         let params = HexEncodedData {
             data: tx.encode_as_hex_string(),
         };
-        self.client
-            .request("cancel_trader_order", AsRpcParams(params))
-            .await
+        self.submit("cancel_trader_order", params).await
     }
 
+    /// Served from the response cache when one is configured.
     pub async fn pool_share_value(&self) -> Result<f64, RpcError> {
-        self.client.request("pool_share_value", rpc_params![]).await
+        self.cached("pool_share_value", EndpointClass::PoolInfo)
+            .await
+    }
+
+    pub async fn pool_share_value_uncached(&self) -> Result<f64, RpcError> {
+        self.request("pool_share_value", rpc_params![]).await
     }
 
+    /// Served from the response cache when one is configured.
     pub async fn lend_pool_info(&self) -> Result<LendPoolInfo, RpcError> {
-        self.client.request("lend_pool_info", rpc_params![]).await
+        self.cached("lend_pool_info", EndpointClass::PoolInfo).await
+    }
+
+    pub async fn lend_pool_info_uncached(&self) -> Result<LendPoolInfo, RpcError> {
+        self.request("lend_pool_info", rpc_params![]).await
     }
 
     // -------------------------
@@ -332,12 +881,12 @@ impl RelayerJsonRpcClient {
 
     /// Get the annualized percentage yield for the last 24 hours.
     pub async fn last_day_apy(&self) -> Result<Option<f64>, RpcError> {
-        self.client.request("last_day_apy", rpc_params![]).await
+        self.request("last_day_apy", rpc_params![]).await
     }
 
     /// Get APY chart data points for visualization.
     pub async fn apy_chart(&self, params: ApyChartArgs) -> Result<Vec<ApyChartPoint>, RpcError> {
-        self.client.request("apy_chart", AsRpcParams(params)).await
+        self.request("apy_chart", AsRpcParams(params)).await
     }
 
     // -------------------------
@@ -346,12 +895,12 @@ impl RelayerJsonRpcClient {
 
     /// Get current open interest (long/short exposure).
     pub async fn open_interest(&self) -> Result<OpenInterest, RpcError> {
-        self.client.request("open_interest", rpc_params![]).await
+        self.request("open_interest", rpc_params![]).await
     }
 
     /// Get comprehensive market risk statistics.
     pub async fn get_market_stats(&self) -> Result<MarketStats, RpcError> {
-        self.client.request("get_market_stats", rpc_params![]).await
+        self.request("get_market_stats", rpc_params![]).await
     }
 
     // -------------------------
@@ -363,8 +912,7 @@ impl RelayerJsonRpcClient {
         &self,
         params: AccountSummaryArgs,
     ) -> Result<AccountSummary, RpcError> {
-        self.client
-            .request("account_summary_by_twilight_address", AsRpcParams(params))
+        self.request("account_summary_by_twilight_address", AsRpcParams(params))
             .await
     }
 
@@ -373,8 +921,7 @@ impl RelayerJsonRpcClient {
         &self,
         params: AllAccountSummariesArgs,
     ) -> Result<AllAccountSummariesResponse, RpcError> {
-        self.client
-            .request("all_account_summaries", AsRpcParams(params))
+        self.request("all_account_summaries", AsRpcParams(params))
             .await
     }
 
@@ -387,12 +934,8 @@ impl RelayerJsonRpcClient {
         &self,
         tx: QueryTraderOrderZkos,
     ) -> Result<TraderOrderV1, RpcError> {
-        let data = bincode::serialize(&tx).unwrap();
-        let params = HexEncodedData {
-            data: hex::encode(data),
-        };
-        self.client
-            .request("trader_order_info_v1", AsRpcParams(params))
+        let params = HexEncodedData::bincode(&tx)?;
+        self.request("trader_order_info_v1", AsRpcParams(params))
             .await
     }
 
@@ -401,16 +944,219 @@ impl RelayerJsonRpcClient {
         &self,
         tx: QueryTraderOrderZkos,
     ) -> Result<Vec<FundingHistoryEntry>, RpcError> {
-        let data = bincode::serialize(&tx).unwrap();
-        let params = HexEncodedData {
-            data: hex::encode(data),
-        };
-        self.client
-            .request("order_funding_history", AsRpcParams(params))
+        let params = HexEncodedData::bincode(&tx)?;
+        self.request("order_funding_history", AsRpcParams(params))
             .await
     }
 }
 
+// -------------------------
+// RelayerApi
+// -------------------------
+
+/// Boxed future returned by [`RelayerApi`] methods.
+pub type RelayerFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, RpcError>> + Send + 'a>>;
+
+/// The relayer calls an `OrderWallet` trades through: order submission,
+/// settlement and cancellation, order queries, the price, market status and
+/// transaction lookups. [`RelayerJsonRpcClient`] implements it over JSON-RPC;
+/// tests substitute a scripted mock (`mock_relayer::MockRelayer`, with the
+/// `test-utils` feature) via `OrderWallet::with_relayer`.
+pub trait RelayerApi: std::fmt::Debug + Send + Sync {
+    /// Policy of the polling helpers waiting on this relayer, e.g.
+    /// `fetch_tx_hash_with_retry`.
+    fn retry_policy(&self) -> &RetryPolicy;
+
+    fn capabilities(&self) -> RelayerFuture<'_, RelayerCapabilities>;
+
+    /// Whether the relayer supports `capability`; `true` when the handshake
+    /// fails, as in [`RelayerJsonRpcClient::supports`].
+    fn supports(&self, capability: Capability) -> Pin<Box<dyn Future<Output = bool> + Send + '_>> {
+        Box::pin(async move {
+            self.capabilities()
+                .await
+                .map_or(true, |capabilities| capabilities.supports(capability))
+        })
+    }
+
+    fn btc_usd_price(&self) -> RelayerFuture<'_, BtcUsdPrice>;
+
+    fn get_market_stats(&self) -> RelayerFuture<'_, MarketStats>;
+
+    fn transaction_hashes(&self, params: TransactionHashArgs) -> RelayerFuture<'_, Vec<TxHash>>;
+
+    fn trader_order_info(&self, tx: QueryTraderOrderZkos) -> RelayerFuture<'_, TraderOrder>;
+
+    fn trader_order_info_v1(&self, tx: QueryTraderOrderZkos) -> RelayerFuture<'_, TraderOrderV1>;
+
+    fn lend_order_info(&self, tx: QueryLendOrderZkos) -> RelayerFuture<'_, LendOrder>;
+
+    fn lend_order_info_v1(&self, tx: QueryLendOrderZkos) -> RelayerFuture<'_, LendOrderV1>;
+
+    fn submit_trade_order_with_key<'a>(
+        &'a self,
+        tx: CreateTraderOrderClientZkos,
+        client_order_id: Option<&'a str>,
+    ) -> RelayerFuture<'a, RequestResponse>;
+
+    fn submit_trade_order(
+        &self,
+        tx: CreateTraderOrderClientZkos,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        self.submit_trade_order_with_key(tx, None)
+    }
+
+    fn submit_lend_order_with_key<'a>(
+        &'a self,
+        tx: CreateLendOrderZkos,
+        client_order_id: Option<&'a str>,
+    ) -> RelayerFuture<'a, RequestResponse>;
+
+    fn submit_lend_order(&self, tx: CreateLendOrderZkos) -> RelayerFuture<'_, RequestResponse> {
+        self.submit_lend_order_with_key(tx, None)
+    }
+
+    fn settle_trade_order(&self, tx: ExecuteTraderOrderZkos) -> RelayerFuture<'_, RequestResponse>;
+
+    fn settle_trade_order_sltp(
+        &self,
+        tx: ExecuteTraderOrderZkosSlTp,
+    ) -> RelayerFuture<'_, RequestResponse>;
+
+    fn settle_trade_order_partial(
+        &self,
+        tx: ExecuteTraderOrderZkos,
+        settle_size: f64,
+    ) -> RelayerFuture<'_, RequestResponse>;
+
+    fn settle_lend_order(&self, tx: ExecuteLendOrderZkos) -> RelayerFuture<'_, RequestResponse>;
+
+    fn cancel_trader_order(&self, tx: CancelTraderOrderZkos) -> RelayerFuture<'_, RequestResponse>;
+
+    fn cancel_trader_order_sltp(
+        &self,
+        tx: CancelTraderOrderZkosSlTp,
+    ) -> RelayerFuture<'_, RequestResponse>;
+}
+
+impl RelayerApi for RelayerJsonRpcClient {
+    fn retry_policy(&self) -> &RetryPolicy {
+        RelayerJsonRpcClient::retry_policy(self)
+    }
+
+    fn capabilities(&self) -> RelayerFuture<'_, RelayerCapabilities> {
+        Box::pin(RelayerJsonRpcClient::capabilities(self))
+    }
+
+    fn supports(&self, capability: Capability) -> Pin<Box<dyn Future<Output = bool> + Send + '_>> {
+        Box::pin(RelayerJsonRpcClient::supports(self, capability))
+    }
+
+    fn btc_usd_price(&self) -> RelayerFuture<'_, BtcUsdPrice> {
+        Box::pin(RelayerJsonRpcClient::btc_usd_price(self))
+    }
+
+    fn get_market_stats(&self) -> RelayerFuture<'_, MarketStats> {
+        Box::pin(RelayerJsonRpcClient::get_market_stats(self))
+    }
+
+    fn transaction_hashes(&self, params: TransactionHashArgs) -> RelayerFuture<'_, Vec<TxHash>> {
+        Box::pin(RelayerJsonRpcClient::transaction_hashes(self, params))
+    }
+
+    fn trader_order_info(&self, tx: QueryTraderOrderZkos) -> RelayerFuture<'_, TraderOrder> {
+        Box::pin(RelayerJsonRpcClient::trader_order_info(self, tx))
+    }
+
+    fn trader_order_info_v1(&self, tx: QueryTraderOrderZkos) -> RelayerFuture<'_, TraderOrderV1> {
+        Box::pin(RelayerJsonRpcClient::trader_order_info_v1(self, tx))
+    }
+
+    fn lend_order_info(&self, tx: QueryLendOrderZkos) -> RelayerFuture<'_, LendOrder> {
+        Box::pin(RelayerJsonRpcClient::lend_order_info(self, tx))
+    }
+
+    fn lend_order_info_v1(&self, tx: QueryLendOrderZkos) -> RelayerFuture<'_, LendOrderV1> {
+        Box::pin(RelayerJsonRpcClient::lend_order_info_v1(self, tx))
+    }
+
+    fn submit_trade_order_with_key<'a>(
+        &'a self,
+        tx: CreateTraderOrderClientZkos,
+        client_order_id: Option<&'a str>,
+    ) -> RelayerFuture<'a, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::submit_trade_order_with_key(
+            self,
+            tx,
+            client_order_id,
+        ))
+    }
+
+    fn submit_lend_order_with_key<'a>(
+        &'a self,
+        tx: CreateLendOrderZkos,
+        client_order_id: Option<&'a str>,
+    ) -> RelayerFuture<'a, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::submit_lend_order_with_key(
+            self,
+            tx,
+            client_order_id,
+        ))
+    }
+
+    fn settle_trade_order(&self, tx: ExecuteTraderOrderZkos) -> RelayerFuture<'_, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::settle_trade_order(self, tx))
+    }
+
+    fn settle_trade_order_sltp(
+        &self,
+        tx: ExecuteTraderOrderZkosSlTp,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::settle_trade_order_sltp(self, tx))
+    }
+
+    fn settle_trade_order_partial(
+        &self,
+        tx: ExecuteTraderOrderZkos,
+        settle_size: f64,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::settle_trade_order_partial(
+            self,
+            tx,
+            settle_size,
+        ))
+    }
+
+    fn settle_lend_order(&self, tx: ExecuteLendOrderZkos) -> RelayerFuture<'_, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::settle_lend_order(self, tx))
+    }
+
+    fn cancel_trader_order(&self, tx: CancelTraderOrderZkos) -> RelayerFuture<'_, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::cancel_trader_order(self, tx))
+    }
+
+    fn cancel_trader_order_sltp(
+        &self,
+        tx: CancelTraderOrderZkosSlTp,
+    ) -> RelayerFuture<'_, RequestResponse> {
+        Box::pin(RelayerJsonRpcClient::cancel_trader_order_sltp(self, tx))
+    }
+}
+
+fn is_method_not_found(e: &RpcError) -> bool {
+    matches!(e, RpcError::Call(err) if err.code() == METHOD_NOT_FOUND)
+}
+
+/// Params already encoded by [`ToRpcParams`], so they can be sent more than once.
+#[derive(Clone)]
+struct RawParams(Option<Box<RawValue>>);
+
+impl ToRpcParams for RawParams {
+    fn to_rpc_params(self) -> Result<Option<Box<RawValue>>, serde_json::Error> {
+        Ok(self.0)
+    }
+}
+
 pub struct AsRpcParams<T>(pub T);
 
 impl<T: Serialize> ToRpcParams for AsRpcParams<T> {
@@ -743,4 +1489,531 @@ mod tests {
             }
         }
     }
+
+    /// Serve a single JSON-RPC response on a local port, echoing the request id.
+    /// The request headers (lower-cased names) are sent back on the returned channel.
+    fn spawn_mock_relayer(
+        result: serde_json::Value,
+    ) -> (String, std::sync::mpsc::Receiver<Vec<(String, String)>>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (headers_tx, headers_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0usize;
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    let name = name.trim().to_ascii_lowercase();
+                    if name == "content-length" {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                    headers.push((name, value.trim().to_string()));
+                }
+            }
+            let _ = headers_tx.send(headers);
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": result,
+            })
+            .to_string();
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        });
+        (format!("http://{}", addr), headers_rx)
+    }
+
+    #[tokio::test]
+    async fn test_btc_usd_price_mock_relayer() {
+        let (url, _headers) = spawn_mock_relayer(mock_price());
+        let relayer = RelayerJsonRpcClient::new(&url).unwrap();
+        let price = relayer.btc_usd_price().await.unwrap();
+        assert_eq!(price.id, 1);
+        assert_eq!(price.price, 65000.5);
+    }
+
+    #[tokio::test]
+    async fn test_candles_paginates_and_sorts() {
+        use jsonrpc_core::{IoHandler, Params};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let since: DateTime<Utc> = "2025-01-01T00:00:00Z".parse().unwrap();
+        // 1100 one-minute candles with no candle at minute 10.
+        let stored: Vec<serde_json::Value> = (0..1101)
+            .filter(|minute| *minute != 10)
+            .map(|minute| {
+                let start = since + chrono::Duration::minutes(minute);
+                let end = start + chrono::Duration::minutes(1);
+                serde_json::json!({
+                    "resolution": "ONE_MINUTE",
+                    "start": start.to_rfc3339(),
+                    "end": end.to_rfc3339(),
+                    "updated_at": end.to_rfc3339(),
+                    "low": "1", "high": "2", "open": "1", "close": "2",
+                    "btc_volume": "0.5", "trades": 3, "usd_volume": "1"
+                })
+            })
+            .collect();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut io = IoHandler::new();
+        io.add_sync_method("candle_data", move |params: Params| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let args: serde_json::Value = params.parse()?;
+            assert_eq!(args["interval"], "ONE_MINUTE");
+            let offset = args["offset"].as_u64().unwrap() as usize;
+            let limit = args["limit"].as_u64().unwrap() as usize;
+            // Newest first within a page, to check the result is re-sorted.
+            let mut page: Vec<_> = stored.iter().skip(offset).take(limit).cloned().collect();
+            page.reverse();
+            Ok(serde_json::Value::Array(page))
+        });
+        let server = jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let relayer = RelayerJsonRpcClient::new(&format!("http://{}", server.address())).unwrap();
+
+        let candles = relayer
+            .candles(CandleInterval::OneMinute, since, 1050)
+            .await
+            .unwrap();
+        assert_eq!(candles.len(), 1050);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(candles
+            .windows(2)
+            .all(|w| w[0].started_at < w[1].started_at));
+        // The gap is kept, not filled.
+        assert_eq!(candles[9].started_at, since + chrono::Duration::minutes(9));
+        assert_eq!(
+            candles[10].started_at,
+            since + chrono::Duration::minutes(11)
+        );
+
+        // Stops once the relayer runs out.
+        let all = relayer
+            .candles(CandleInterval::OneMinute, since, 5000)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 1100);
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn test_fee_estimate_and_history_from_mock_relayer() {
+        use jsonrpc_core::{IoHandler, Params};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let from: DateTime<Utc> = "2025-01-01T00:00:00Z".parse().unwrap();
+        // Recorded `get_fee_rate` response; the relayer sends rates as strings.
+        let current = serde_json::json!({
+            "order_filled_on_market": "0.05",
+            "order_filled_on_limit": "0.02",
+            "order_settled_on_market": "0.04",
+            "order_settled_on_limit": "0.025",
+            "timestamp": "2025-01-02T00:00:00Z"
+        });
+        let stored: Vec<serde_json::Value> = (0..(FEE_HISTORY_PAGE_SIZE + 20))
+            .map(|hour| {
+                let mut entry = current.clone();
+                entry["timestamp"] = (from + chrono::Duration::hours(hour as i64))
+                    .to_rfc3339()
+                    .into();
+                entry
+            })
+            .collect();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut io = IoHandler::new();
+        io.add_sync_method("get_fee_rate", move |_| Ok(current.clone()));
+        io.add_sync_method("historical_fee_rate", move |params: Params| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let args: serde_json::Value = params.parse()?;
+            let offset = args["offset"].as_u64().unwrap() as usize;
+            let limit = args["limit"].as_u64().unwrap() as usize;
+            let page: Vec<_> = stored.iter().skip(offset).take(limit).cloned().collect();
+            Ok(serde_json::Value::Array(page))
+        });
+        let server = jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let relayer = RelayerJsonRpcClient::new(&format!("http://{}", server.address())).unwrap();
+
+        let fees = relayer.current_fees().await.unwrap();
+        assert!((fees.taker_bps - 5.0).abs() < 1e-9);
+        // A 10x market position on 50_000 sats of margin.
+        let estimate = relayer
+            .estimate_order_fees(500_000, OrderType::MARKET)
+            .await
+            .unwrap();
+        assert_eq!((estimate.open_fee, estimate.close_fee), (250, 200));
+        assert_eq!(estimate.total_fee, 450);
+
+        let history = relayer
+            .fee_history(from..from + chrono::Duration::days(30))
+            .await
+            .unwrap();
+        assert_eq!(history.len(), FEE_HISTORY_PAGE_SIZE + 20);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(history[0].timestamp, from);
+        assert!(relayer.fee_history(from..from).await.unwrap().is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_handshake_is_cached() {
+        use jsonrpc_core::{IoHandler, Params};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut io = IoHandler::new();
+        io.add_sync_method("server_info", move |_: Params| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(serde_json::json!({ "version": "1.2.0", "capabilities": ["bulk_query", "ws"] }))
+        });
+        let server = jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let relayer = RelayerJsonRpcClient::new(&format!("http://{}", server.address())).unwrap();
+
+        let capabilities = relayer.capabilities().await.unwrap();
+        assert_eq!(capabilities.version.as_deref(), Some("1.2.0"));
+        assert!(capabilities.supports_bulk_query && capabilities.supports_ws);
+        assert!(!capabilities.supports_post_only);
+        // Clones share the cache.
+        assert_eq!(relayer.clone().capabilities().await.unwrap(), capabilities);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        relayer.refresh_capabilities().await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_probed_without_server_info() {
+        use jsonrpc_core::{Error, IoHandler, Params};
+
+        let mut io = IoHandler::new();
+        io.add_sync_method("all_account_summaries", |_: Params| {
+            Err::<serde_json::Value, _>(Error::invalid_params("bad payload"))
+        });
+        let server = jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let relayer = RelayerJsonRpcClient::new(&format!("http://{}", server.address())).unwrap();
+
+        let capabilities = relayer.capabilities().await.unwrap();
+        assert!(!capabilities.advertised);
+        assert_eq!(capabilities.version, None);
+        assert!(capabilities.supports_bulk_query);
+        assert!(!capabilities.supports_funding_history);
+        assert!(!relayer.supports(Capability::OrderInfoV1).await);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn test_market_snapshot_survives_failing_endpoints() {
+        use jsonrpc_core::{IoHandler, Params};
+
+        let mut io = IoHandler::new();
+        io.add_sync_method("btc_usd_price", |_: Params| Ok(mock_price()));
+        io.add_sync_method("position_size", |_: Params| {
+            Ok(serde_json::json!({ "total_short": "100", "total_long": "300", "total": "400" }))
+        });
+        io.add_sync_method("recent_trade_orders", |_: Params| Ok(serde_json::json!([])));
+        let server = jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let relayer = RelayerJsonRpcClient::new(&format!("http://{}", server.address())).unwrap();
+
+        let snapshot = relayer.market_snapshot().await;
+        assert_eq!(snapshot.price.as_ref().map(|p| p.price), Some(65000.5));
+        let sizes = snapshot.position_size.as_ref().unwrap();
+        assert_eq!(sizes.long_share(), Some(0.75));
+        assert!(snapshot.recent_trades.is_some());
+        // Not served by the mock.
+        assert!(snapshot.funding_rate.is_none() && snapshot.order_book.is_none());
+        assert_eq!(
+            snapshot.errors.keys().copied().collect::<Vec<_>>(),
+            vec!["funding_rate", "order_book"]
+        );
+        server.close();
+    }
+
+    /// Serve `btc_usd_price` with a delay, counting upstream calls.
+    fn counting_price_server() -> (
+        jsonrpc_http_server::Server,
+        Arc<std::sync::atomic::AtomicUsize>,
+    ) {
+        use jsonrpc_core::{IoHandler, Params};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut io = IoHandler::new();
+        io.add_sync_method("btc_usd_price", move |_: Params| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            Ok(mock_price())
+        });
+        let server = jsonrpc_http_server::ServerBuilder::new(io)
+            .threads(4)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        (server, calls)
+    }
+
+    #[tokio::test]
+    async fn test_policy_request_timeout_applies() {
+        let (server, _calls) = counting_price_server();
+        let policy = RetryPolicy {
+            request_timeout: std::time::Duration::from_millis(10),
+            ..Default::default()
+        };
+        let url = format!("http://{}", server.address());
+        let relayer = RelayerJsonRpcClient::new_with_policy(&url, policy).unwrap();
+        assert_eq!(
+            relayer.retry_policy().request_timeout,
+            policy.request_timeout
+        );
+        let err = relayer.btc_usd_price_uncached().await.unwrap_err();
+        assert!(matches!(err, RpcError::RequestTimeout), "{:?}", err);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn test_failover_skips_unreachable_endpoint() {
+        use std::sync::atomic::Ordering;
+
+        let (server, calls) = counting_price_server();
+        let endpoints = vec![
+            "http://127.0.0.1:1".to_string(),
+            format!("http://{}", server.address()),
+        ];
+        let relayer =
+            RelayerJsonRpcClient::new_with_failover(endpoints, FailoverStrategy::Priority).unwrap();
+        for _ in 0..3 {
+            let price = relayer.btc_usd_price_uncached().await.unwrap();
+            assert_eq!(price.price, 65000.5);
+        }
+        let health = relayer.endpoint_health();
+        assert!(!health[0].healthy);
+        assert_eq!(health[0].total_failures, 3);
+        assert!(health[0].last_error.is_some());
+        assert!(health[1].healthy);
+        assert_eq!(health[1].total_requests, 3);
+
+        // While it cools down the unreachable endpoint is no longer tried first.
+        relayer.btc_usd_price_uncached().await.unwrap();
+        assert_eq!(relayer.endpoint_health()[0].total_requests, 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_spaces_calls_and_metrics_are_shared() {
+        let (server, _calls) = counting_price_server();
+        let relayer = RelayerJsonRpcClient::new(&format!("http://{}", server.address()))
+            .unwrap()
+            .with_rate_limit(RateLimitPolicy::new(5.0, 1));
+        let clone = relayer.clone();
+        let started = Instant::now();
+        for client in [&relayer, &clone, &relayer] {
+            client.btc_usd_price_uncached().await.unwrap();
+        }
+        // One burst token, then a call every 200 ms.
+        assert!(started.elapsed() >= Duration::from_millis(350));
+
+        let metrics = clone.metrics();
+        assert_eq!(metrics, relayer.metrics());
+        let price = metrics.method("btc_usd_price").unwrap();
+        assert_eq!((price.calls, price.errors, price.throttled), (3, 0, 2));
+        assert!(price.p50 >= Duration::from_millis(50));
+        server.close();
+
+        let unreachable = RelayerJsonRpcClient::new("http://127.0.0.1:1").unwrap();
+        assert!(unreachable.btc_usd_price_uncached().await.is_err());
+        assert_eq!(unreachable.metrics().total_errors(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_relayer_error_does_not_fail_over() {
+        use jsonrpc_core::{IoHandler, Params};
+        use std::sync::atomic::Ordering;
+
+        let mut io = IoHandler::new();
+        io.add_sync_method("btc_usd_price", |_: Params| {
+            Err(jsonrpc_core::Error::invalid_params("no price"))
+        });
+        let failing = jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        let (server, calls) = counting_price_server();
+        let endpoints = vec![
+            format!("http://{}", failing.address()),
+            format!("http://{}", server.address()),
+        ];
+        let relayer =
+            RelayerJsonRpcClient::new_with_failover(endpoints, FailoverStrategy::Priority).unwrap();
+        let err = relayer.btc_usd_price_uncached().await.unwrap_err();
+        assert!(matches!(err, RpcError::Call(_)), "{:?}", err);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert!(relayer.endpoint_health()[0].healthy);
+        failing.close();
+        server.close();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_cached_price_calls_hit_upstream_once() {
+        use crate::relayer_module::response_cache::CacheStats;
+        use std::sync::atomic::Ordering;
+
+        let (server, calls) = counting_price_server();
+        let cache = ResponseCache::default();
+        let url = format!("http://{}", server.address());
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..10 {
+            // Separate clients sharing one cache, as several bots on a host would.
+            let relayer = RelayerJsonRpcClient::new(&url)
+                .unwrap()
+                .with_response_cache(cache.clone());
+            tasks.spawn(async move {
+                let price = relayer.btc_usd_price().await.map(|p| p.price);
+                (price, relayer.metrics())
+            });
+        }
+        let mut hits = 0;
+        while let Some(joined) = tasks.join_next().await {
+            let (price, metrics) = joined.unwrap();
+            assert_eq!(price.unwrap(), 65000.5);
+            hits += metrics.total_cache_hits();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.stats(), CacheStats { hits: 9, misses: 1 });
+        assert_eq!(hits, 9);
+        server.close();
+    }
+
+    #[tokio::test]
+    async fn test_uncached_price_always_goes_upstream() {
+        use std::sync::atomic::Ordering;
+
+        let (server, calls) = counting_price_server();
+        let relayer = RelayerJsonRpcClient::new(&format!("http://{}", server.address()))
+            .unwrap()
+            .with_response_cache(ResponseCache::default());
+        relayer.btc_usd_price().await.unwrap();
+        for _ in 0..3 {
+            relayer.btc_usd_price_uncached().await.unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        let m = relayer.metrics().method("btc_usd_price").cloned().unwrap();
+        assert_eq!((m.calls, m.cache_hits, m.cache_misses), (4, 0, 1));
+        // Without a cache every call goes through as before.
+        let plain = RelayerJsonRpcClient::new(&format!("http://{}", server.address())).unwrap();
+        plain.btc_usd_price().await.unwrap();
+        plain.btc_usd_price().await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+        server.close();
+    }
+
+    fn mock_price() -> serde_json::Value {
+        serde_json::json!({
+            "id": 1,
+            "price": "65000.5",
+            "timestamp": "2025-01-01T00:00:00Z"
+        })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    #[tokio::test]
+    async fn test_no_traceparent_without_otel() {
+        let (url, headers) = spawn_mock_relayer(mock_price());
+        let relayer = RelayerJsonRpcClient::new(&url).unwrap();
+        relayer.btc_usd_price().await.unwrap();
+        let headers = headers.recv().unwrap();
+        assert!(!headers.iter().any(|(name, _)| name == "traceparent"));
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test]
+    async fn test_traceparent_propagated_with_otel() {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+        };
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let cx = opentelemetry::Context::current().with_remote_span_context(span_context);
+
+        let (url, headers) = spawn_mock_relayer(mock_price());
+        let relayer = RelayerJsonRpcClient::new(&url).unwrap();
+        {
+            // Only the request construction needs the context attached.
+            let _guard = cx.clone().attach();
+            let client = relayer.rpc(relayer.endpoints.primary()).into_owned();
+            drop(_guard);
+            let _: BtcUsdPrice = client.request("btc_usd_price", rpc_params![]).await.unwrap();
+        }
+        let headers = headers.recv().unwrap();
+        let traceparent = headers
+            .iter()
+            .find(|(name, _)| name == "traceparent")
+            .map(|(_, value)| value.as_str());
+        assert_eq!(
+            traceparent,
+            Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+        );
+    }
+
+    #[test]
+    fn test_submit_order_data_sends_key_only_when_given() {
+        let plain = SubmitOrderData {
+            data: "00ff".to_string(),
+            client_order_id: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&plain).unwrap(),
+            serde_json::to_value(HexEncodedData {
+                data: "00ff".to_string()
+            })
+            .unwrap()
+        );
+
+        let keyed = SubmitOrderData {
+            data: "00ff".to_string(),
+            client_order_id: Some("key-1".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(&keyed).unwrap(),
+            serde_json::json!({ "data": "00ff", "client_order_id": "key-1" })
+        );
+    }
 }
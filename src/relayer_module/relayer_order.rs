@@ -1,5 +1,5 @@
 use curve25519_dalek::scalar::Scalar;
-use twilight_client_sdk::{
+use crate::compat::{
     chain::get_transaction_coin_input_from_address_fast,
     programcontroller::ContractManager,
     quisquislib::RistrettoSecretKey,
@@ -10,46 +10,20 @@ use twilight_client_sdk::{
     relayer_types::{
         CancelTraderOrderZkos, CancelTraderOrderZkosSlTp, CreateLendOrderZkos,
         CreateTraderOrderClientZkos, ExecuteLendOrderZkos, ExecuteTraderOrderZkos,
-        ExecuteTraderOrderZkosSlTp, OrderStatus, OrderType, PositionType, SlTpOrder,
-        SlTpOrderCancel, TXType,
+        ExecuteTraderOrderZkosSlTp, OrderType, PositionType, SlTpOrder, SlTpOrderCancel, TXType,
     },
     util::create_output_memo_for_lender,
-    zkvm::Output,
+    zkvm::{Input, Output},
 };
 use uuid::Uuid;
 
-use crate::relayer_module::relayer_api::RelayerJsonRpcClient;
-
-const DEFAULT_RELAYER_PROGRAM_JSON: &str = r#"{
-  "program_index": {
-    "LiquidateOrder": 5,
-    "SettleTraderOrderNegativeMarginDifference": 6,
-    "SettleTraderOrder": 2,
-    "SettleLendOrder": 4,
-    "CreateLendOrder": 3,
-    "RelayerInitializer": 0,
-    "CreateTraderOrder": 1
-  },
-  "program": [
-    "060a0402000000060a0e0401000000060a0402000000060a0e1013",
-    "060a0403000000060a0405000000060a0d0e13020202",
-    "040300000002040300000002040a0000000603000000000a0b04070000000603000000000a04020000000c04020000000a0b04020000000a0c0404000000060a0b0c0302000000050d0307000000050d0407000000050403000000050b0c0406000000050d0407000000050d0403000000050c0e04010000000b0403000000060a0c0402000000060a0e101302",
-    "0401000000060a0302000000060a0306000000060a0c0e0403000000060a0304000000060a0307000000060a0c0e100401000000050402000000060a0405000000060a0d0c0402000000060a0403000000060a0d0e1013",
-    "050304000000060a0307000000060a0d0c0302000000060a0306000000060a0d0e0406000000060a0b0403000000060a0c0402000000060a0e100401000000060a0402000000060a0403000000060a0b0c0e101302",
-    "0202020202060a0401000000060a0407000000060a0c0e130202020202",
-    "040300000002040300000002040a0000000603000000000a0b04070000000603000000000a04020000000c04020000000a0b04020000000a0c0404000000060a0c0302000000050d0307000000050d0407000000050403000000050b0c0406000000050d0407000000050d0403000000050c0e04010000000b0403000000060a0c0402000000060a0e101302"
-  ]
-}"#;
-
-fn load_programs(contract_path: &str) -> ContractManager {
-    if std::path::Path::new(contract_path).exists() {
-        ContractManager::import_program(contract_path)
-    } else {
-        serde_json::from_str(DEFAULT_RELAYER_PROGRAM_JSON)
-            .expect("hardcoded relayer program JSON is valid")
-    }
-}
+use crate::relayer_module::leverage::Leverage;
+use crate::relayer_module::relayer_api::RelayerApi;
+use crate::relayer_module::relayer_program::RelayerProgram;
+use crate::relayer_module::signing_audit::{SigningAudit, SigningPurpose};
 
+/// [`create_trader_order_with_programs`] with the programs of a loaded
+/// [`RelayerProgram`].
 pub async fn create_trader_order(
     sk: RistrettoSecretKey,
     rscalar: Scalar,
@@ -60,38 +34,132 @@ pub async fn create_trader_order(
     entry_price: u64,
     position_value: u64,
     position_size: u64,
-    contract_path: &str,
+    relayer_program: &RelayerProgram,
+    address: String,
+    relayer_api_client: &dyn RelayerApi,
+) -> Result<String, String> {
+    create_trader_order_with_programs(
+        sk,
+        rscalar,
+        value,
+        order_side,
+        order_type,
+        Leverage::from(leverage),
+        entry_price,
+        position_value,
+        position_size,
+        relayer_program.contracts(),
+        address,
+        relayer_api_client,
+    )
+    .await
+}
+
+/// Build, verify and submit a trader order using an already parsed relayer program.
+pub async fn create_trader_order_with_programs(
+    sk: RistrettoSecretKey,
+    rscalar: Scalar,
+    value: u64,
+    order_side: PositionType,
+    order_type: OrderType,
+    leverage: Leverage,
+    entry_price: u64,
+    position_value: u64,
+    position_size: u64,
+    programs: &ContractManager,
     address: String,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
 ) -> Result<String, String> {
-    let programs = load_programs(&contract_path);
+    let order_data = build_trader_order_with_programs(
+        sk,
+        rscalar,
+        value,
+        order_side,
+        order_type,
+        leverage,
+        entry_price,
+        position_value,
+        position_size,
+        programs,
+        address,
+    )
+    .await?;
+    submit_trader_order(order_data, relayer_api_client).await
+}
+
+/// Build and verify a trader order, proof included, without submitting it.
+pub async fn build_trader_order_with_programs(
+    sk: RistrettoSecretKey,
+    rscalar: Scalar,
+    value: u64,
+    order_side: PositionType,
+    order_type: OrderType,
+    leverage: Leverage,
+    entry_price: u64,
+    position_value: u64,
+    position_size: u64,
+    programs: &ContractManager,
+    address: String,
+) -> Result<CreateTraderOrderClientZkos, String> {
     let input_coin =
         tokio::task::spawn_blocking(move || get_transaction_coin_input_from_address_fast(address))
             .await
             .map_err(|e| e.to_string())?;
     let input_coin = input_coin.map_err(|e| e.to_string())?;
-    let order_tx_message = twilight_client_sdk::relayer::create_trader_order_zkos(
+    build_trader_order_with_input(
         input_coin,
         sk,
         rscalar,
         value,
-        order_side.to_str(),
-        order_type.to_str(),
-        leverage as f64,
-        value as f64,
-        value as f64,
-        "PENDING".to_string(),
-        entry_price as f64,
-        entry_price as f64,
+        order_side,
+        order_type,
+        leverage,
+        entry_price,
         position_value,
         position_size,
-        order_side.clone(),
-        &programs,
-        0u32,
+        programs,
     )
-    .map_err(|e| e.to_string())?;
+}
+
+/// [`build_trader_order_with_programs`] on a given coin input instead of the
+/// account's input fetched from chain.
+pub fn build_trader_order_with_input(
+    input_coin: Input,
+    sk: RistrettoSecretKey,
+    rscalar: Scalar,
+    value: u64,
+    order_side: PositionType,
+    order_type: OrderType,
+    leverage: Leverage,
+    entry_price: u64,
+    position_value: u64,
+    position_size: u64,
+    programs: &ContractManager,
+) -> Result<CreateTraderOrderClientZkos, String> {
+    let order_tx_message = crate::compat::relayer::create_trader_order_zkos(
+        input_coin,
+        sk,
+        rscalar,
+        value,
+        order_side,
+        order_type,
+        leverage.as_f64(),
+        entry_price,
+        position_value,
+        position_size,
+        programs,
+    )?;
     let order_data = CreateTraderOrderClientZkos::decode_from_hex_string(order_tx_message.clone())?;
     let _verified = order_data.tx.verify()?;
+    Ok(order_data)
+}
+
+/// Submit a trader order built by [`build_trader_order_with_programs`],
+/// returning its request ID.
+pub async fn submit_trader_order(
+    order_data: CreateTraderOrderClientZkos,
+    relayer_api_client: &dyn RelayerApi,
+) -> Result<String, String> {
     let response = relayer_api_client
         .submit_trade_order(order_data)
         .await
@@ -99,6 +167,7 @@ pub async fn create_trader_order(
     Ok(response.id_key.to_string())
 }
 
+/// Unaudited wrapper around [`close_trader_order_internal_audited`].
 pub async fn close_trader_order_internal(
     output_memo: Output, // Provides the Prover Memo Output used to create the order. Input memo will be created by Exchange on behalf of the user
     secret_key: &RistrettoSecretKey,
@@ -106,27 +175,114 @@ pub async fn close_trader_order_internal(
     uuid: Uuid,
     order_type: OrderType,
     execution_price: f64,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
 ) -> Result<String, String> {
-    let request_msg = execute_order_zkos(
+    close_trader_order_internal_audited(
         output_memo,
         secret_key,
         account_id,
         uuid,
-        order_type.to_str(),
-        0.0,
-        OrderStatus::FILLED.to_str(),
+        order_type,
         execution_price,
-        TXType::ORDERTX,
-    );
+        relayer_api_client,
+        &SigningAudit::disabled(),
+        0,
+    )
+    .await
+}
+
+/// Same as [`close_trader_order_internal`], also recording the signed request in
+/// `signing_audit` against `account_index`.
+pub async fn close_trader_order_internal_audited(
+    output_memo: Output, // Provides the Prover Memo Output used to create the order. Input memo will be created by Exchange on behalf of the user
+    secret_key: &RistrettoSecretKey,
+    account_id: String,
+    uuid: Uuid,
+    order_type: OrderType,
+    execution_price: f64,
+    relayer_api_client: &dyn RelayerApi,
+    signing_audit: &SigningAudit,
+    account_index: u64,
+) -> Result<String, String> {
+    let request = execute_trader_request(
+        output_memo,
+        secret_key,
+        account_id,
+        uuid,
+        order_type,
+        execution_price,
+        signing_audit,
+        account_index,
+    )?;
     let response = relayer_api_client
-        .settle_trade_order(ExecuteTraderOrderZkos::decode_from_hex_string(
-            request_msg.clone(),
-        )?)
+        .settle_trade_order(request)
         .await
         .map_err(|e| e.to_string())?;
     Ok(response.id_key.to_string())
 }
+
+/// [`close_trader_order_internal_audited`] settling `settle_size` of the
+/// position. The execute request is the one of a full close; the size goes
+/// alongside it to
+/// [`PARTIAL_SETTLE_METHOD`](super::capabilities::PARTIAL_SETTLE_METHOD), so
+/// only relayers with
+/// [`Capability::PartialClose`](super::capabilities::Capability::PartialClose)
+/// accept it.
+pub async fn close_trader_order_partial_audited(
+    output_memo: Output,
+    secret_key: &RistrettoSecretKey,
+    account_id: String,
+    uuid: Uuid,
+    order_type: OrderType,
+    settle_size: f64,
+    execution_price: f64,
+    relayer_api_client: &dyn RelayerApi,
+    signing_audit: &SigningAudit,
+    account_index: u64,
+) -> Result<String, String> {
+    let request = execute_trader_request(
+        output_memo,
+        secret_key,
+        account_id,
+        uuid,
+        order_type,
+        execution_price,
+        signing_audit,
+        account_index,
+    )?;
+    let response = relayer_api_client
+        .settle_trade_order_partial(request, settle_size)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(response.id_key.to_string())
+}
+
+/// Signed execute request settling the trader order of `account_id`,
+/// recorded in `signing_audit`.
+#[allow(clippy::too_many_arguments)]
+fn execute_trader_request(
+    output_memo: Output,
+    secret_key: &RistrettoSecretKey,
+    account_id: String,
+    uuid: Uuid,
+    order_type: OrderType,
+    execution_price: f64,
+    signing_audit: &SigningAudit,
+    account_index: u64,
+) -> Result<ExecuteTraderOrderZkos, String> {
+    let request_msg = execute_order_zkos(
+        output_memo,
+        secret_key,
+        account_id,
+        uuid,
+        order_type,
+        execution_price,
+        TXType::ORDERTX,
+    );
+    signing_audit.record(account_index, SigningPurpose::Settle, request_msg.as_bytes());
+    ExecuteTraderOrderZkos::decode_from_hex_string(request_msg)
+}
+/// Unaudited wrapper around [`close_trader_order_sltp_internal_audited`].
 pub async fn close_trader_order_sltp_internal(
     output_memo: Output, // Provides the Prover Memo Output used to create the order. Input memo will be created by Exchange on behalf of the user
     secret_key: &RistrettoSecretKey,
@@ -136,20 +292,49 @@ pub async fn close_trader_order_sltp_internal(
     execution_price: f64,
     stop_loss_price: Option<f64>,
     take_profit_price: Option<f64>,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
+) -> Result<String, String> {
+    close_trader_order_sltp_internal_audited(
+        output_memo,
+        secret_key,
+        account_id,
+        uuid,
+        order_type,
+        execution_price,
+        stop_loss_price,
+        take_profit_price,
+        relayer_api_client,
+        &SigningAudit::disabled(),
+        0,
+    )
+    .await
+}
+
+/// Same as [`close_trader_order_sltp_internal`], also recording the signed request in
+/// `signing_audit` against `account_index`.
+pub async fn close_trader_order_sltp_internal_audited(
+    output_memo: Output, // Provides the Prover Memo Output used to create the order. Input memo will be created by Exchange on behalf of the user
+    secret_key: &RistrettoSecretKey,
+    account_id: String,
+    uuid: Uuid,
+    order_type: OrderType,
+    execution_price: f64,
+    stop_loss_price: Option<f64>,
+    take_profit_price: Option<f64>,
+    relayer_api_client: &dyn RelayerApi,
+    signing_audit: &SigningAudit,
+    account_index: u64,
 ) -> Result<String, String> {
     let request_msg = execute_order_zkos_sltp(
         output_memo,
         secret_key,
         account_id,
         uuid,
-        order_type.to_str(),
-        0.0,
-        OrderStatus::FILLED.to_str(),
+        order_type,
         execution_price,
-        TXType::ORDERTX,
-        Some(SlTpOrder::new(stop_loss_price, take_profit_price)),
+        SlTpOrder::new(stop_loss_price, take_profit_price),
     );
+    signing_audit.record(account_index, SigningPurpose::Settle, request_msg.as_bytes());
     let response = relayer_api_client
         .settle_trade_order_sltp(ExecuteTraderOrderZkosSlTp::decode_from_hex_string(
             request_msg.clone(),
@@ -161,25 +346,50 @@ pub async fn close_trader_order_sltp_internal(
     Ok(response.id_key.to_string())
 }
 
+/// Unaudited wrapper around [`close_lend_order_audited`].
 pub async fn close_lend_order(
     output_memo: Output, // Provides the Prover Memo Output used to create the order. Input memo will be created by Exchange on behalf of the user
     secret_key: &RistrettoSecretKey,
     account_id: String,
     uuid: Uuid,
     order_type: OrderType,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
+) -> Result<String, String> {
+    close_lend_order_audited(
+        output_memo,
+        secret_key,
+        account_id,
+        uuid,
+        order_type,
+        relayer_api_client,
+        &SigningAudit::disabled(),
+        0,
+    )
+    .await
+}
+
+/// Same as [`close_lend_order`], also recording the signed request in
+/// `signing_audit` against `account_index`.
+pub async fn close_lend_order_audited(
+    output_memo: Output, // Provides the Prover Memo Output used to create the order. Input memo will be created by Exchange on behalf of the user
+    secret_key: &RistrettoSecretKey,
+    account_id: String,
+    uuid: Uuid,
+    order_type: OrderType,
+    relayer_api_client: &dyn RelayerApi,
+    signing_audit: &SigningAudit,
+    account_index: u64,
 ) -> Result<String, String> {
     let request_msg = execute_order_zkos(
         output_memo,
         secret_key,
         account_id,
         uuid,
-        order_type.to_str(),
-        0.0,
-        OrderStatus::FILLED.to_str(),
+        order_type,
         0.0,
         TXType::LENDTX,
     );
+    signing_audit.record(account_index, SigningPurpose::Settle, request_msg.as_bytes());
     let response = relayer_api_client
         .settle_lend_order(ExecuteLendOrderZkos::decode_from_hex_string(
             request_msg.clone(),
@@ -189,14 +399,50 @@ pub async fn close_lend_order(
     Ok(response.id_key.to_string())
 }
 
+/// [`create_lend_order_with_programs`] with the programs of a loaded
+/// [`RelayerProgram`].
 pub async fn create_lend_order(
     account_address: String,
     secret_key: RistrettoSecretKey,
     amount: u64,
-    contract_path: &str,
+    relayer_program: &RelayerProgram,
+    scalar_hex: String,
+    relayer_api_client: &dyn RelayerApi,
+) -> Result<String, String> {
+    create_lend_order_with_programs(
+        account_address,
+        secret_key,
+        amount,
+        relayer_program.contracts(),
+        scalar_hex,
+        relayer_api_client,
+    )
+    .await
+}
+
+/// Build and submit a lend order using an already parsed relayer program.
+pub async fn create_lend_order_with_programs(
+    account_address: String,
+    secret_key: RistrettoSecretKey,
+    amount: u64,
+    programs: &ContractManager,
     scalar_hex: String,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
 ) -> Result<String, String> {
+    let order_data =
+        build_lend_order_with_programs(account_address, secret_key, amount, programs, scalar_hex)
+            .await?;
+    submit_lend_order(order_data, relayer_api_client).await
+}
+
+/// Build a lend order without submitting it.
+pub async fn build_lend_order_with_programs(
+    account_address: String,
+    secret_key: RistrettoSecretKey,
+    amount: u64,
+    programs: &ContractManager,
+    scalar_hex: String,
+) -> Result<CreateLendOrderZkos, String> {
     let account_address_clone = account_address.clone();
     let input_coin = tokio::task::spawn_blocking(move || {
         get_transaction_coin_input_from_address_fast(account_address.clone())
@@ -204,53 +450,97 @@ pub async fn create_lend_order(
     .await
     .map_err(|e| e.to_string())?;
     let input_coin = input_coin.map_err(|e| e.to_string())?;
-    let programs = load_programs(&contract_path);
-    let script_address =
-        programs.create_contract_address(twilight_client_sdk::address::Network::default())?;
-    let output_memo_scalar = twilight_client_sdk::util::hex_to_scalar(scalar_hex.clone())
+    build_lend_order_with_input(
+        input_coin,
+        account_address_clone,
+        secret_key,
+        amount,
+        programs,
+        scalar_hex,
+    )
+}
+
+/// [`build_lend_order_with_programs`] on a given coin input instead of the
+/// account's input fetched from chain.
+pub fn build_lend_order_with_input(
+    input_coin: Input,
+    account_address: String,
+    secret_key: RistrettoSecretKey,
+    amount: u64,
+    programs: &ContractManager,
+    scalar_hex: String,
+) -> Result<CreateLendOrderZkos, String> {
+    let output_memo_scalar = crate::compat::util::hex_to_scalar(scalar_hex.clone())
         .ok_or("Failed to convert scalar hex to scalar")?;
     let output_memo = create_output_memo_for_lender(
-        script_address,
-        account_address_clone.clone(),
+        programs,
+        account_address.clone(),
         amount,
-        0,
         output_memo_scalar,
-        0,
-    );
+    )?;
     let request_msg = create_lend_order_zkos(
         input_coin,
         output_memo,
         secret_key,
         scalar_hex,
         amount,
-        account_address_clone,
-        amount as f64,
-        OrderType::LEND.to_str(),
-        OrderStatus::PENDING.to_str(),
-        amount as f64,
-    );
+        account_address,
+    )?;
+    CreateLendOrderZkos::decode_from_hex_string(request_msg)
+}
+
+/// Submit a lend order built by [`build_lend_order_with_programs`],
+/// returning its request ID.
+pub async fn submit_lend_order(
+    order_data: CreateLendOrderZkos,
+    relayer_api_client: &dyn RelayerApi,
+) -> Result<String, String> {
     let response = relayer_api_client
-        .submit_lend_order(CreateLendOrderZkos::decode_from_hex_string(request_msg?)?)
+        .submit_lend_order(order_data)
         .await
         .map_err(|e| e.to_string())?;
     Ok(response.id_key.to_string())
 }
 
+/// Unaudited wrapper around [`cancel_trader_order_audited`].
 pub async fn cancel_trader_order(
     account_address: String,
     secret_key: &RistrettoSecretKey,
     account_id: String,
     uuid: Uuid,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
+) -> Result<String, String> {
+    cancel_trader_order_audited(
+        account_address,
+        secret_key,
+        account_id,
+        uuid,
+        relayer_api_client,
+        &SigningAudit::disabled(),
+        0,
+    )
+    .await
+}
+
+/// Same as [`cancel_trader_order`], also recording the signed request in
+/// `signing_audit` against `account_index`.
+pub async fn cancel_trader_order_audited(
+    account_address: String,
+    secret_key: &RistrettoSecretKey,
+    account_id: String,
+    uuid: Uuid,
+    relayer_api_client: &dyn RelayerApi,
+    signing_audit: &SigningAudit,
+    account_index: u64,
 ) -> Result<String, String> {
     let request_msg = cancel_trader_order_zkos(
         account_address,
         secret_key,
         account_id,
         uuid,
-        OrderType::LIMIT.to_str(),
-        OrderStatus::CANCELLED.to_str(),
+        OrderType::LIMIT,
     );
+    signing_audit.record(account_index, SigningPurpose::Cancel, request_msg.as_bytes());
     let response = relayer_api_client
         .cancel_trader_order(CancelTraderOrderZkos::decode_from_hex_string(
             request_msg.clone(),
@@ -260,23 +550,43 @@ pub async fn cancel_trader_order(
     Ok(response.id_key.to_string())
 }
 
+/// Unaudited wrapper around [`cancel_trader_order_sltp_audited`].
 pub async fn cancel_trader_order_sltp(
     account_address: String,
     secret_key: &RistrettoSecretKey,
     account_id: String,
     uuid: Uuid,
     sltp_cancel: SlTpOrderCancel,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
 ) -> Result<String, String> {
-    let request_msg = cancel_trader_order_zkos_sltp(
+    cancel_trader_order_sltp_audited(
         account_address,
         secret_key,
         account_id,
         uuid,
-        OrderType::SLTP.to_str(),
-        OrderStatus::CANCELLED.to_str(),
         sltp_cancel,
-    );
+        relayer_api_client,
+        &SigningAudit::disabled(),
+        0,
+    )
+    .await
+}
+
+/// Same as [`cancel_trader_order_sltp`], also recording the signed request in
+/// `signing_audit` against `account_index`.
+pub async fn cancel_trader_order_sltp_audited(
+    account_address: String,
+    secret_key: &RistrettoSecretKey,
+    account_id: String,
+    uuid: Uuid,
+    sltp_cancel: SlTpOrderCancel,
+    relayer_api_client: &dyn RelayerApi,
+    signing_audit: &SigningAudit,
+    account_index: u64,
+) -> Result<String, String> {
+    let request_msg =
+        cancel_trader_order_zkos_sltp(account_address, secret_key, account_id, uuid, sltp_cancel);
+    signing_audit.record(account_index, SigningPurpose::Cancel, request_msg.as_bytes());
     let response = relayer_api_client
         .cancel_trader_order_sltp(CancelTraderOrderZkosSlTp::decode_from_hex_string(
             request_msg.clone(),
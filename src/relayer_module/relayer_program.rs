@@ -0,0 +1,288 @@
+//! The relayer program (`relayerprogram.json`), parsed and validated.
+//!
+//! Every trader and lend order is built against the relayer's programs. A
+//! [`RelayerProgram`] is that file checked up front: it must be JSON with a
+//! `program_index` naming each program the wallet's orders run
+//! ([`REQUIRED_PROGRAMS`]) and a `program` list of hex bytecode those indices
+//! point into, and an optional `version` that must be
+//! [`RELAYER_PROGRAM_VERSION`]. Problems come back as a
+//! [`RelayerProgramError`] instead of a panic in the SDK's parser.
+//!
+//! `OrderWallet` loads the configured file when it is constructed unless
+//! [`ProgramLoading::Lazy`](crate::config::ProgramLoading::Lazy) is set, so
+//! a missing or malformed file is reported before any funds move. The path
+//! [`BUILTIN_RELAYER_PROGRAM`] selects the program compiled into the wallet.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::compat::programcontroller::ContractManager;
+
+/// Program file format this wallet understands. Files without a `version`
+/// are taken to be in it.
+pub const RELAYER_PROGRAM_VERSION: u32 = 1;
+
+/// Programs the wallet's orders run, by their `program_index` name.
+pub const REQUIRED_PROGRAMS: [&str; 4] = [
+    "CreateTraderOrder",
+    "SettleTraderOrder",
+    "CreateLendOrder",
+    "SettleLendOrder",
+];
+
+/// `relayer_program_json_path` value that selects [`RelayerProgram::builtin`]
+/// instead of a file.
+pub const BUILTIN_RELAYER_PROGRAM: &str = "builtin";
+
+pub(crate) const DEFAULT_RELAYER_PROGRAM_JSON: &str = r#"{
+  "program_index": {
+    "LiquidateOrder": 5,
+    "SettleTraderOrderNegativeMarginDifference": 6,
+    "SettleTraderOrder": 2,
+    "SettleLendOrder": 4,
+    "CreateLendOrder": 3,
+    "RelayerInitializer": 0,
+    "CreateTraderOrder": 1
+  },
+  "program": [
+    "060a0402000000060a0e0401000000060a0402000000060a0e1013",
+    "060a0403000000060a0405000000060a0d0e13020202",
+    "040300000002040300000002040a0000000603000000000a0b04070000000603000000000a04020000000c04020000000a0b04020000000a0c0404000000060a0b0c0302000000050d0307000000050d0407000000050403000000050b0c0406000000050d0407000000050d0403000000050c0e04010000000b0403000000060a0c0402000000060a0e101302",
+    "0401000000060a0302000000060a0306000000060a0c0e0403000000060a0304000000060a0307000000060a0c0e100401000000050402000000060a0405000000060a0d0c0402000000060a0403000000060a0d0e1013",
+    "050304000000060a0307000000060a0d0c0302000000060a0306000000060a0d0e0406000000060a0b0403000000060a0c0402000000060a0e100401000000060a0402000000060a0403000000060a0b0c0e101302",
+    "0202020202060a0401000000060a0407000000060a0c0e130202020202",
+    "040300000002040300000002040a0000000603000000000a0b04070000000603000000000a04020000000c04020000000a0b04020000000a0c0404000000060a0c0302000000050d0307000000050d0407000000050403000000050b0c0406000000050d0407000000050d0403000000050c0e04010000000b0403000000060a0c0402000000060a0e101302"
+  ]
+}"#;
+
+/// Why a relayer program could not be loaded. `path` is the configured path.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RelayerProgramError {
+    #[error("relayer program file not found at {path} (set RELAYER_PROGRAM_JSON_PATH)")]
+    NotFound { path: String },
+    #[error("failed to read relayer program {path}: {reason}")]
+    Read { path: String, reason: String },
+    #[error("relayer program {path} is malformed: {reason}")]
+    Malformed { path: String, reason: String },
+    #[error("relayer program {path} has version {version}, this wallet supports {supported}")]
+    UnsupportedVersion {
+        path: String,
+        version: u32,
+        supported: u32,
+    },
+    #[error("relayer program {path} has no {name} program")]
+    MissingProgram { path: String, name: String },
+    #[error("relayer program {path}: {name} is program {index}, but only {count} are defined")]
+    IndexOutOfRange {
+        path: String,
+        name: String,
+        index: usize,
+        count: usize,
+    },
+    #[error("relayer program {path}: program {index} is not hex bytecode")]
+    InvalidBytecode { path: String, index: usize },
+}
+
+/// Wire layout of the program file, checked before the SDK parses it.
+#[derive(Deserialize)]
+struct ProgramFile {
+    #[serde(default)]
+    version: Option<u32>,
+    program_index: BTreeMap<String, usize>,
+    program: Vec<String>,
+}
+
+/// A validated relayer program. Clones share the parsed contracts.
+#[derive(Clone)]
+pub struct RelayerProgram {
+    path: String,
+    version: u32,
+    program_index: BTreeMap<String, usize>,
+    contracts: Arc<ContractManager>,
+}
+
+impl std::fmt::Debug for RelayerProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelayerProgram")
+            .field("path", &self.path)
+            .field("version", &self.version)
+            .field("program_index", &self.program_index)
+            .finish()
+    }
+}
+
+impl RelayerProgram {
+    /// Read and validate the program at `path`, or the built-in program for
+    /// [`BUILTIN_RELAYER_PROGRAM`].
+    pub fn load(path: &str) -> Result<Self, RelayerProgramError> {
+        if path == BUILTIN_RELAYER_PROGRAM {
+            return Ok(Self::builtin());
+        }
+        let json = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => RelayerProgramError::NotFound {
+                path: path.to_string(),
+            },
+            _ => RelayerProgramError::Read {
+                path: path.to_string(),
+                reason: e.to_string(),
+            },
+        })?;
+        Self::parse(path, &json)
+    }
+
+    /// The program compiled into the wallet.
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_RELAYER_PROGRAM, DEFAULT_RELAYER_PROGRAM_JSON)
+            .expect("built-in relayer program is valid")
+    }
+
+    /// Validate and parse program `json`; `path` names it in errors.
+    pub fn parse(path: &str, json: &str) -> Result<Self, RelayerProgramError> {
+        let malformed = |e: serde_json::Error| RelayerProgramError::Malformed {
+            path: path.to_string(),
+            reason: e.to_string(),
+        };
+        let file: ProgramFile = serde_json::from_str(json).map_err(malformed)?;
+        let version = file.version.unwrap_or(RELAYER_PROGRAM_VERSION);
+        if version != RELAYER_PROGRAM_VERSION {
+            return Err(RelayerProgramError::UnsupportedVersion {
+                path: path.to_string(),
+                version,
+                supported: RELAYER_PROGRAM_VERSION,
+            });
+        }
+        if let Some(name) = REQUIRED_PROGRAMS
+            .iter()
+            .find(|name| !file.program_index.contains_key(**name))
+        {
+            return Err(RelayerProgramError::MissingProgram {
+                path: path.to_string(),
+                name: name.to_string(),
+            });
+        }
+        let count = file.program.len();
+        if let Some((name, &index)) = file.program_index.iter().find(|(_, i)| **i >= count) {
+            return Err(RelayerProgramError::IndexOutOfRange {
+                path: path.to_string(),
+                name: name.clone(),
+                index,
+                count,
+            });
+        }
+        if let Some(index) = file
+            .program
+            .iter()
+            .position(|code| code.is_empty() || hex::decode(code).is_err())
+        {
+            return Err(RelayerProgramError::InvalidBytecode {
+                path: path.to_string(),
+                index,
+            });
+        }
+        let contracts: ContractManager = serde_json::from_str(json).map_err(malformed)?;
+        Ok(Self {
+            path: path.to_string(),
+            version,
+            program_index: file.program_index,
+            contracts: Arc::new(contracts),
+        })
+    }
+
+    /// Path the program was loaded from, or [`BUILTIN_RELAYER_PROGRAM`].
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Index of the program called `name`, e.g. `CreateTraderOrder`.
+    pub fn program_index(&self, name: &str) -> Option<usize> {
+        self.program_index.get(name).copied()
+    }
+
+    /// The parsed programs, as the SDK's order builders take them.
+    pub fn contracts(&self) -> &ContractManager {
+        &self.contracts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn default_json() -> Value {
+        serde_json::from_str(DEFAULT_RELAYER_PROGRAM_JSON).unwrap()
+    }
+
+    fn parse(json: &Value) -> Result<RelayerProgram, RelayerProgramError> {
+        RelayerProgram::parse("test.json", &json.to_string())
+    }
+
+    #[test]
+    fn test_builtin_and_repo_programs_are_valid() {
+        let builtin = RelayerProgram::builtin();
+        assert_eq!(builtin.version(), RELAYER_PROGRAM_VERSION);
+        assert_eq!(builtin.program_index("CreateTraderOrder"), Some(1));
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/relayerprogram.json");
+        assert_eq!(RelayerProgram::load(path).unwrap().path(), path);
+    }
+
+    #[test]
+    fn test_invalid_programs_are_rejected() {
+        let err = RelayerProgram::load("./no-such-relayerprogram.json").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "relayer program file not found at ./no-such-relayerprogram.json (set RELAYER_PROGRAM_JSON_PATH)"
+        );
+
+        let mut json = default_json();
+        json.as_object_mut().unwrap().remove("program");
+        assert!(matches!(
+            parse(&json),
+            Err(RelayerProgramError::Malformed { .. })
+        ));
+
+        let mut json = default_json();
+        json["version"] = Value::from(2);
+        assert!(matches!(
+            parse(&json),
+            Err(RelayerProgramError::UnsupportedVersion { version: 2, .. })
+        ));
+
+        let mut json = default_json();
+        json["program_index"]
+            .as_object_mut()
+            .unwrap()
+            .remove("CreateLendOrder");
+        assert_eq!(
+            parse(&json).unwrap_err(),
+            RelayerProgramError::MissingProgram {
+                path: "test.json".to_string(),
+                name: "CreateLendOrder".to_string(),
+            }
+        );
+
+        let mut json = default_json();
+        json["program_index"]["LiquidateOrder"] = Value::from(9);
+        assert!(matches!(
+            parse(&json),
+            Err(RelayerProgramError::IndexOutOfRange {
+                index: 9,
+                count: 7,
+                ..
+            })
+        ));
+
+        let mut json = default_json();
+        json["program"][3] = Value::from("not hex");
+        assert!(matches!(
+            parse(&json),
+            Err(RelayerProgramError::InvalidBytecode { index: 3, .. })
+        ));
+    }
+}
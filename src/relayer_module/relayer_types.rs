@@ -1,8 +1,8 @@
 #![allow(non_camel_case_types)]
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, de};
-pub use twilight_client_sdk::relayer_types::*;
-pub use twilight_client_sdk::zkvm::IOType;
+pub use crate::compat::relayer_types::*;
+pub use crate::compat::zkvm::IOType;
 use uuid::Uuid;
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -48,6 +48,72 @@ pub enum Interval {
     ONE_DAY_CHANGE,
 }
 
+/// Candle resolutions accepted by
+/// [`RelayerJsonRpcClient::candles`](super::relayer_api::RelayerJsonRpcClient::candles).
+/// Serializes to the same names as the matching [`Interval`].
+#[derive(Copy, Eq, Hash, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum CandleInterval {
+    #[serde(rename = "ONE_MINUTE")]
+    OneMinute,
+    #[serde(rename = "FIVE_MINUTE")]
+    FiveMinute,
+    #[serde(rename = "FIFTEEN_MINUTE")]
+    FifteenMinute,
+    #[serde(rename = "ONE_HOUR")]
+    OneHour,
+    #[serde(rename = "FOUR_HOUR")]
+    FourHour,
+    #[serde(rename = "ONE_DAY")]
+    OneDay,
+}
+
+impl CandleInterval {
+    /// Length of one candle.
+    pub fn duration(&self) -> chrono::Duration {
+        match self {
+            CandleInterval::OneMinute => chrono::Duration::minutes(1),
+            CandleInterval::FiveMinute => chrono::Duration::minutes(5),
+            CandleInterval::FifteenMinute => chrono::Duration::minutes(15),
+            CandleInterval::OneHour => chrono::Duration::hours(1),
+            CandleInterval::FourHour => chrono::Duration::hours(4),
+            CandleInterval::OneDay => chrono::Duration::days(1),
+        }
+    }
+}
+
+impl From<CandleInterval> for Interval {
+    fn from(interval: CandleInterval) -> Self {
+        match interval {
+            CandleInterval::OneMinute => Interval::ONE_MINUTE,
+            CandleInterval::FiveMinute => Interval::FIVE_MINUTE,
+            CandleInterval::FifteenMinute => Interval::FIFTEEN_MINUTE,
+            CandleInterval::OneHour => Interval::ONE_HOUR,
+            CandleInterval::FourHour => Interval::FOUR_HOUR,
+            CandleInterval::OneDay => Interval::ONE_DAY,
+        }
+    }
+}
+
+impl std::str::FromStr for CandleInterval {
+    type Err = String;
+
+    /// Parse `1m`, `5m`, `15m`, `1h`, `4h` or `1d`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "1m" => Ok(CandleInterval::OneMinute),
+            "5m" => Ok(CandleInterval::FiveMinute),
+            "15m" => Ok(CandleInterval::FifteenMinute),
+            "1h" => Ok(CandleInterval::OneHour),
+            "4h" => Ok(CandleInterval::FourHour),
+            "1d" => Ok(CandleInterval::OneDay),
+            other => Err(format!(
+                "Unknown candle interval: {}. Use: 1m, 5m, 15m, 1h, 4h, 1d",
+                other
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HistoricalFundingArgs {
     #[serde(with = "rfc3339_date")]
@@ -182,6 +248,129 @@ pub struct Ask {
     pub price: f64,
 }
 
+/// Cumulative size on each side of an [`OrderBook`] within a price band; see
+/// [`OrderBook::depth_at`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BookDepth {
+    pub bid: f64,
+    pub ask: f64,
+}
+
+/// The relayer does not guarantee level order, so the helpers below never
+/// rely on it, and skip levels with a non-finite or non-positive price or
+/// size. A side without such a level counts as empty.
+impl OrderBook {
+    pub fn new(bid: Vec<Bid>, ask: Vec<Ask>) -> Self {
+        Self { bid, ask }
+    }
+
+    /// The book with invalid levels dropped, bids by descending and asks by
+    /// ascending price.
+    pub fn sorted(&self) -> OrderBook {
+        let mut bid: Vec<Bid> = self.valid_bids().cloned().collect();
+        let mut ask: Vec<Ask> = self.valid_asks().cloned().collect();
+        bid.sort_by(|a, b| b.price.total_cmp(&a.price));
+        ask.sort_by(|a, b| a.price.total_cmp(&b.price));
+        OrderBook { bid, ask }
+    }
+
+    /// Highest priced bid.
+    pub fn best_bid(&self) -> Option<&Bid> {
+        self.valid_bids().max_by(|a, b| a.price.total_cmp(&b.price))
+    }
+
+    /// Lowest priced ask.
+    pub fn best_ask(&self) -> Option<&Ask> {
+        self.valid_asks().min_by(|a, b| a.price.total_cmp(&b.price))
+    }
+
+    /// Midpoint of the best bid and ask; `None` unless both sides have a level.
+    pub fn mid_price(&self) -> Option<f64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        Some((bid.price + ask.price) / 2.0)
+    }
+
+    /// Best ask minus best bid. Negative when the book is crossed.
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// Mid price weighted by the size at the top of the book: it leans towards
+    /// the side with less size, where the next trade is more likely to move it.
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        let size = bid.positionsize + ask.positionsize;
+        Some((bid.price * ask.positionsize + ask.price * bid.positionsize) / size)
+    }
+
+    /// Size resting within `price_distance_pct` percent of the mid price on
+    /// each side, e.g. `0.5` counts bids down to 0.5% below the mid and asks
+    /// up to 0.5% above it. `None` without a mid price or for a negative or
+    /// non-finite band.
+    pub fn depth_at(&self, price_distance_pct: f64) -> Option<BookDepth> {
+        if !price_distance_pct.is_finite() || price_distance_pct < 0.0 {
+            return None;
+        }
+        let mid = self.mid_price()?;
+        let band = mid * price_distance_pct / 100.0;
+        Some(BookDepth {
+            bid: self
+                .valid_bids()
+                .filter(|level| level.price >= mid - band)
+                .map(|level| level.positionsize)
+                .sum(),
+            ask: self
+                .valid_asks()
+                .filter(|level| level.price <= mid + band)
+                .map(|level| level.positionsize)
+                .sum(),
+        })
+    }
+
+    /// `(bid_volume - ask_volume) / (bid_volume + ask_volume)` over the whole
+    /// book, from -1 (only asks) to 1 (only bids). `None` for an empty book.
+    pub fn imbalance(&self) -> Option<f64> {
+        let bid: f64 = self.valid_bids().map(|level| level.positionsize).sum();
+        let ask: f64 = self.valid_asks().map(|level| level.positionsize).sum();
+        let total = bid + ask;
+        (total > 0.0).then(|| (bid - ask) / total)
+    }
+
+    fn valid_bids(&self) -> impl Iterator<Item = &Bid> {
+        self.bid
+            .iter()
+            .filter(|level| is_valid_level(level.price, level.positionsize))
+    }
+
+    fn valid_asks(&self) -> impl Iterator<Item = &Ask> {
+        self.ask
+            .iter()
+            .filter(|level| is_valid_level(level.price, level.positionsize))
+    }
+}
+
+fn is_valid_level(price: f64, size: f64) -> bool {
+    price.is_finite() && price > 0.0 && size.is_finite() && size > 0.0
+}
+
+impl Bid {
+    pub fn new(positionsize: f64, price: f64) -> Self {
+        Self {
+            positionsize,
+            price,
+        }
+    }
+}
+
+impl Ask {
+    pub fn new(positionsize: f64, price: f64) -> Self {
+        Self {
+            positionsize,
+            price,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(transparent)]
 pub struct RecentOrders {
@@ -201,7 +390,9 @@ pub struct CloseTrade {
     pub positionsize: f64,
     #[serde(deserialize_with = "from_str_to_f64")]
     pub price: f64,
-    #[serde(with = "rfc3339_date")]
+    /// Sent as RFC 3339, a zone-less date-time in UTC, or Unix seconds or
+    /// milliseconds, depending on the relayer version.
+    #[serde(with = "trade_timestamp")]
     pub timestamp: DateTime<Utc>,
 }
 
@@ -423,6 +614,19 @@ pub struct TraderOrderV1 {
     pub funding_applied: Option<f64>,
 }
 
+/// The v1 view of a plain order, with the v1-only fields unset.
+impl From<TraderOrder> for TraderOrderV1 {
+    fn from(order: TraderOrder) -> Self {
+        Self {
+            order,
+            settle_limit: None,
+            take_profit: None,
+            stop_loss: None,
+            funding_applied: None,
+        }
+    }
+}
+
 /// A single funding history entry from `order_funding_history`.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FundingHistoryEntry {
@@ -452,6 +656,15 @@ pub struct LendOrderV1 {
     pub unrealised_profit: Option<UnrealisedProfit>,
 }
 
+impl From<LendOrder> for LendOrderV1 {
+    fn from(order: LendOrder) -> Self {
+        Self {
+            order,
+            unrealised_profit: None,
+        }
+    }
+}
+
 /// Deserialize an optional string-or-number to `Option<f64>`.
 pub fn option_from_str_to_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
 where
@@ -524,6 +737,101 @@ mod rfc3339_date {
     }
 }
 
+/// Trade timestamps in any of the formats relayers have sent, normalized to
+/// UTC. Serialized as RFC 3339.
+mod trade_timestamp {
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use serde::{Deserializer, Serializer, de};
+
+    /// Unix timestamps above this are in milliseconds (year 5138 in seconds).
+    const MILLIS_THRESHOLD: i64 = 100_000_000_000;
+    /// And above this in microseconds (year 5138 in milliseconds).
+    const MICROS_THRESHOLD: i64 = 100_000_000_000_000;
+
+    pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&date.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(Visitor)
+    }
+
+    pub(super) fn parse(s: &str) -> Option<DateTime<Utc>> {
+        let s = s.trim();
+        if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            return Some(dt.with_timezone(&Utc));
+        }
+        for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+            if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+                return Some(naive.and_utc());
+            }
+        }
+        s.parse::<i64>().ok().and_then(from_unix)
+    }
+
+    fn from_unix(ts: i64) -> Option<DateTime<Utc>> {
+        if ts.abs() >= MICROS_THRESHOLD {
+            DateTime::from_timestamp_micros(ts)
+        } else if ts.abs() >= MILLIS_THRESHOLD {
+            DateTime::from_timestamp_millis(ts)
+        } else {
+            DateTime::from_timestamp(ts, 0)
+        }
+    }
+
+    struct Visitor;
+
+    impl<'de> de::Visitor<'de> for Visitor {
+        type Value = DateTime<Utc>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "an RFC 3339 date-time or a Unix timestamp")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            parse(v).ok_or_else(|| E::custom(format!("unrecognized timestamp `{}`", v)))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            from_unix(v).ok_or_else(|| E::custom(format!("timestamp {} out of range", v)))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let v = i64::try_from(v).map_err(E::custom)?;
+            self.visit_i64(v)
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.visit_i64(v as i64)
+        }
+    }
+}
+
+/// A relayer timestamp such as `TxHash.datetime`, in any format trade
+/// timestamps are accepted in: RFC 3339, a naive UTC date-time, or Unix
+/// seconds, milliseconds or microseconds. `None` when it does not parse.
+pub fn parse_relayer_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    trade_timestamp::parse(s)
+}
+
 pub fn from_str_to_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
 where
     D: Deserializer<'de>,
@@ -569,3 +877,186 @@ where
 
     deserializer.deserialize_any(Visitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response_mutations::{fixture_value, mutated_value};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_relayer_fixtures_deserialize() {
+        let price: BtcUsdPrice =
+            serde_json::from_value(fixture_value("relayer_types/btc_usd_price.json")).unwrap();
+        assert_eq!(price.price, 65000.5);
+        let order: TraderOrder =
+            serde_json::from_value(fixture_value("relayer_types/trader_order.json")).unwrap();
+        assert_eq!(order.order_status, OrderStatus::FILLED);
+    }
+
+    #[test]
+    fn test_constructors_match_deserialized_values() {
+        use crate::relayer_module::test_fixtures::{filled_long, order_book, settled_lend};
+
+        let book: OrderBook =
+            serde_json::from_value(fixture_value("relayer_types/order_book.json")).unwrap();
+        assert_eq!(
+            book,
+            OrderBook::new(
+                vec![
+                    Bid::new(250_000_000.0, 49_950.0),
+                    Bid::new(1_000_000_000.0, 49_900.0),
+                ],
+                vec![
+                    Ask::new(400_000_000.0, 50_050.0),
+                    Ask::new(750_000_000.0, 50_100.0),
+                ],
+            )
+        );
+        assert_eq!(order_book(), book);
+
+        let v1 = TraderOrderV1::from(filled_long());
+        let json = serde_json::to_value(&v1).unwrap();
+        assert_eq!(serde_json::from_value::<TraderOrderV1>(json).unwrap(), v1);
+        assert!(v1.settle_limit.is_none() && v1.funding_applied.is_none());
+        assert_eq!(LendOrderV1::from(settled_lend()).order, settled_lend());
+    }
+
+    #[test]
+    fn test_trade_timestamps_normalize_to_utc() {
+        let expected: DateTime<Utc> = "2025-03-01T12:30:00Z".parse().unwrap();
+        for raw in [
+            serde_json::json!("2025-03-01T12:30:00Z"),
+            serde_json::json!("2025-03-01T13:30:00+01:00"),
+            serde_json::json!("2025-03-01 12:30:00"),
+            serde_json::json!("2025-03-01T12:30:00.000"),
+            serde_json::json!(1_740_832_200),
+            serde_json::json!(1_740_832_200_000u64),
+            serde_json::json!("1740832200"),
+        ] {
+            let trade: CloseTrade = serde_json::from_value(serde_json::json!({
+                "order_id": "00000000-0000-0000-0000-000000000001",
+                "side": "LONG",
+                "positionsize": "1000",
+                "price": 50000,
+                "timestamp": raw.clone(),
+            }))
+            .unwrap_or_else(|e| panic!("{}: {}", raw, e));
+            assert_eq!(trade.timestamp, expected, "{}", raw);
+        }
+        assert!(trade_timestamp::parse("yesterday").is_none());
+        assert_eq!(parse_relayer_timestamp("1740832200000000"), Some(expected));
+        assert_eq!(parse_relayer_timestamp(""), None);
+    }
+
+    #[test]
+    fn test_order_book_helpers_sort_defensively() {
+        // Levels out of order, plus ones the helpers must skip.
+        let book = OrderBook::new(
+            vec![
+                Bid::new(100.0, 49_900.0),
+                Bid::new(0.0, 49_990.0),
+                Bid::new(300.0, 49_950.0),
+                Bid::new(50.0, f64::NAN),
+            ],
+            vec![Ask::new(200.0, 50_100.0), Ask::new(100.0, 50_050.0)],
+        );
+        assert_eq!(book.best_bid(), Some(&Bid::new(300.0, 49_950.0)));
+        assert_eq!(book.best_ask(), Some(&Ask::new(100.0, 50_050.0)));
+        assert_eq!(book.mid_price(), Some(50_000.0));
+        assert_eq!(book.spread(), Some(100.0));
+        // 3x the size on the bid pulls the price towards the ask.
+        assert_eq!(book.microprice(), Some(50_025.0));
+        assert_eq!(
+            book.depth_at(0.1),
+            Some(BookDepth {
+                bid: 300.0,
+                ask: 100.0
+            })
+        );
+        assert_eq!(
+            book.depth_at(1.0),
+            Some(BookDepth {
+                bid: 400.0,
+                ask: 300.0
+            })
+        );
+        assert_eq!(book.depth_at(-1.0), None);
+        assert_eq!(book.imbalance(), Some(100.0 / 700.0));
+        let sorted = book.sorted();
+        assert_eq!(sorted.bid.len(), 2);
+        assert_eq!(sorted.bid[0].price, 49_950.0);
+        assert_eq!(sorted.ask[0].price, 50_050.0);
+
+        let one_sided = OrderBook::new(book.bid.clone(), vec![]);
+        assert!(one_sided.best_ask().is_none());
+        assert_eq!(one_sided.mid_price(), None);
+        assert_eq!(one_sided.spread(), None);
+        assert_eq!(one_sided.depth_at(1.0), None);
+        assert_eq!(one_sided.imbalance(), Some(1.0));
+        assert_eq!(OrderBook::default().imbalance(), None);
+    }
+
+    fn levels() -> impl Strategy<Value = Vec<(f64, f64)>> {
+        prop::collection::vec((0.0f64..1e9, 1.0f64..100_000.0), 0..12)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_order_book_helpers_are_consistent(bids in levels(), asks in levels()) {
+            let bid: Vec<Bid> = bids.iter().map(|(size, price)| Bid::new(*size, *price)).collect();
+            let ask: Vec<Ask> = asks.iter().map(|(size, price)| Ask::new(*size, *price)).collect();
+            let book = OrderBook::new(bid.clone(), ask.clone());
+            let mut reversed = OrderBook::new(bid, ask);
+            reversed.bid.reverse();
+            reversed.ask.reverse();
+
+            // Level order never matters.
+            prop_assert_eq!(book.mid_price(), reversed.mid_price());
+            prop_assert_eq!(book.imbalance(), reversed.imbalance());
+            let prices = |book: OrderBook| -> (Vec<f64>, Vec<f64>) {
+                let sorted = book.sorted();
+                (
+                    sorted.bid.iter().map(|level| level.price).collect(),
+                    sorted.ask.iter().map(|level| level.price).collect(),
+                )
+            };
+            prop_assert_eq!(prices(book.clone()), prices(reversed));
+
+            if let Some(best) = book.best_bid() {
+                prop_assert!(book.valid_bids().all(|level| level.price <= best.price));
+            }
+            if let Some(best) = book.best_ask() {
+                prop_assert!(book.valid_asks().all(|level| level.price >= best.price));
+            }
+            match (book.best_bid(), book.best_ask(), book.mid_price()) {
+                (Some(bid), Some(ask), Some(mid)) => {
+                    prop_assert!(mid >= bid.price.min(ask.price) && mid <= bid.price.max(ask.price));
+                    let micro = book.microprice().unwrap();
+                    let tolerance = bid.price.max(ask.price) * 1e-12;
+                    prop_assert!(micro >= bid.price.min(ask.price) - tolerance);
+                    prop_assert!(micro <= bid.price.max(ask.price) + tolerance);
+                    let narrow = book.depth_at(0.5).unwrap();
+                    let wide = book.depth_at(5.0).unwrap();
+                    prop_assert!(narrow.bid <= wide.bid && narrow.ask <= wide.ask);
+                }
+                (_, _, mid) => {
+                    prop_assert!(mid.is_none());
+                    prop_assert!(book.spread().is_none() && book.depth_at(1.0).is_none());
+                }
+            }
+            if let Some(imbalance) = book.imbalance() {
+                prop_assert!((-1.0..=1.0).contains(&imbalance));
+            }
+        }
+
+        #[test]
+        fn prop_relayer_types_never_panic(
+            price in mutated_value("relayer_types/btc_usd_price.json"),
+            order in mutated_value("relayer_types/trader_order.json"),
+        ) {
+            let _ = serde_json::from_value::<BtcUsdPrice>(price);
+            let _ = serde_json::from_value::<TraderOrder>(order);
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! WebSocket subscriptions to the relayer's live feeds.
+//!
+//! [`RelayerWsClient`] opens one WebSocket connection per subscription and
+//! forwards every notification into a [`FeedStream`]. A background task owns
+//! the connection:
+//!
+//! - When the socket drops or the server closes the subscription, the task
+//!   reconnects after an exponential [`Backoff`] and
+//!   subscribes again. Messages sent while disconnected are lost.
+//! - Every state change is published on the stream's [`FeedStatus`], together
+//!   with the time of the last message, so a bot can stop quoting while
+//!   [`FeedStatus::is_stale`] holds.
+//! - Dropping the [`FeedStream`] stops the task and closes the connection.
+//!
+//! The subscription method names below follow the relayer's
+//! `subscribe_*` / `unsubscribe_*` convention. Check
+//! [`Capability::Ws`](super::capabilities::Capability::Ws) in `server_info`
+//! before relying on them.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use jsonrpsee::core::client::{Subscription, SubscriptionClientT};
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use super::relayer_types::{BtcUsdPrice, OrderBook, RecentOrders};
+use crate::config::Backoff;
+
+/// Notifications buffered per stream before the feed task waits for the reader.
+const DEFAULT_BUFFER: usize = 256;
+
+/// A relayer subscription: method to subscribe with and to unsubscribe with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Topic {
+    name: &'static str,
+    subscribe: &'static str,
+    unsubscribe: &'static str,
+}
+
+const LIVE_PRICE: Topic = Topic {
+    name: "live price",
+    subscribe: "subscribe_live_price_data",
+    unsubscribe: "unsubscribe_live_price_data",
+};
+
+const ORDER_BOOK: Topic = Topic {
+    name: "order book",
+    subscribe: "subscribe_order_book",
+    unsubscribe: "unsubscribe_order_book",
+};
+
+const RECENT_TRADES: Topic = Topic {
+    name: "recent trades",
+    subscribe: "subscribe_recent_trades",
+    unsubscribe: "unsubscribe_recent_trades",
+};
+
+/// How a feed reconnects after its connection drops: half a second,
+/// doubling up to 30 seconds, plus up to 20% jitter.
+pub const DEFAULT_RECONNECT_BACKOFF: Backoff = Backoff {
+    initial_delay: Duration::from_millis(500),
+    max_delay: Duration::from_secs(30),
+    factor: 2.0,
+    jitter: 0.2,
+};
+
+/// Connection state of one feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedState {
+    /// First connection in progress.
+    Connecting,
+    /// Subscribed; notifications are flowing.
+    Connected,
+    /// The connection failed or dropped; waiting to retry.
+    Reconnecting { attempt: u32, last_error: String },
+}
+
+/// Latest state of a feed and when it last delivered a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedStatus {
+    pub state: FeedState,
+    pub last_message_at: Option<Instant>,
+}
+
+impl FeedStatus {
+    fn connecting() -> Self {
+        Self {
+            state: FeedState::Connecting,
+            last_message_at: None,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == FeedState::Connected
+    }
+
+    /// Whether the feed should not be trusted at `now`: it is not connected,
+    /// has delivered nothing yet, or its last message is older than `max_age`.
+    pub fn is_stale_at(&self, max_age: Duration, now: Instant) -> bool {
+        match self.last_message_at {
+            Some(at) if self.is_connected() => now.saturating_duration_since(at) > max_age,
+            _ => true,
+        }
+    }
+
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.is_stale_at(max_age, Instant::now())
+    }
+}
+
+/// Notifications of one subscription, also usable as a `futures::Stream`.
+/// Dropping it closes the connection.
+#[derive(Debug)]
+pub struct FeedStream<T> {
+    items: mpsc::Receiver<T>,
+    status: watch::Receiver<FeedStatus>,
+    task: JoinHandle<()>,
+}
+
+impl<T> FeedStream<T> {
+    /// Next notification. Waits across reconnects; returns `None` only once
+    /// the feed task has stopped.
+    pub async fn next(&mut self) -> Option<T> {
+        self.items.recv().await
+    }
+
+    /// Current connection state.
+    pub fn status(&self) -> FeedStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Receiver that is notified on every state change, for a supervisor
+    /// task that pauses trading independently of the reader.
+    pub fn status_watch(&self) -> watch::Receiver<FeedStatus> {
+        self.status.clone()
+    }
+}
+
+impl<T> futures_core::Stream for FeedStream<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().items.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for FeedStream<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// WebSocket client for the relayer's live price, order book and trade feeds.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use nyks_wallet::relayer_module::relayer_api::RelayerWsClient;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = RelayerWsClient::new("https://relayer.twilight.rest/api");
+///     let mut prices = client.subscribe_btc_price();
+///     while let Some(price) = prices.next().await {
+///         if prices.status().is_stale(Duration::from_secs(5)) {
+///             continue;
+///         }
+///         println!("BTC/USD: ${}", price.price);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RelayerWsClient {
+    endpoint: String,
+    policy: Backoff,
+    buffer: usize,
+}
+
+impl RelayerWsClient {
+    /// Client for `endpoint`. An `http://` or `https://` relayer URL is
+    /// turned into its `ws://` or `wss://` form.
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: ws_endpoint(endpoint),
+            policy: DEFAULT_RECONNECT_BACKOFF,
+            buffer: DEFAULT_BUFFER,
+        }
+    }
+
+    pub fn with_reconnect_policy(mut self, policy: Backoff) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Notifications each stream buffers before the feed waits for the reader.
+    pub fn with_buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer.max(1);
+        self
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Live BTC/USD price updates.
+    pub fn subscribe_btc_price(&self) -> FeedStream<BtcUsdPrice> {
+        self.subscribe(LIVE_PRICE)
+    }
+
+    /// Order book snapshots.
+    pub fn subscribe_order_book(&self) -> FeedStream<OrderBook> {
+        self.subscribe(ORDER_BOOK)
+    }
+
+    /// Recently closed trades.
+    pub fn subscribe_recent_trades(&self) -> FeedStream<RecentOrders> {
+        self.subscribe(RECENT_TRADES)
+    }
+
+    /// Spawns the feed task; must be called inside a Tokio runtime.
+    fn subscribe<T>(&self, topic: Topic) -> FeedStream<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let (item_tx, items) = mpsc::channel(self.buffer);
+        let (status_tx, status) = watch::channel(FeedStatus::connecting());
+        let task = tokio::spawn(run_feed(
+            self.endpoint.clone(),
+            topic,
+            self.policy,
+            item_tx,
+            status_tx,
+        ));
+        FeedStream {
+            items,
+            status,
+            task,
+        }
+    }
+}
+
+/// `ws(s)://` form of a relayer URL; other schemes are kept as given.
+fn ws_endpoint(endpoint: &str) -> String {
+    if let Some(rest) = endpoint.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = endpoint.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        endpoint.to_string()
+    }
+}
+
+/// Keeps `topic` subscribed until the stream is dropped.
+async fn run_feed<T>(
+    endpoint: String,
+    topic: Topic,
+    policy: Backoff,
+    items: mpsc::Sender<T>,
+    status: watch::Sender<FeedStatus>,
+) where
+    T: DeserializeOwned + Send + 'static,
+{
+    let mut attempt = 0u32;
+    loop {
+        let error = match stream_once(&endpoint, topic, &items, &status, &mut attempt).await {
+            Ok(()) => return,
+            Err(e) => e,
+        };
+        if items.is_closed() {
+            return;
+        }
+        attempt += 1;
+        let delay = policy.delay(attempt);
+        warn!(
+            "Relayer {} feed disconnected ({}); reconnecting in {:?} (attempt {})",
+            topic.name, error, delay, attempt
+        );
+        status.send_modify(|s| {
+            s.state = FeedState::Reconnecting {
+                attempt,
+                last_error: error,
+            }
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// One connection: subscribe and forward notifications until it fails.
+/// `Ok` means the reader went away and the feed should stop.
+async fn stream_once<T>(
+    endpoint: &str,
+    topic: Topic,
+    items: &mpsc::Sender<T>,
+    status: &watch::Sender<FeedStatus>,
+    attempt: &mut u32,
+) -> Result<(), String>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let client = WsClientBuilder::default()
+        .build(endpoint)
+        .await
+        .map_err(|e| format!("connect to {} failed: {}", endpoint, e))?;
+    let mut subscription: Subscription<T> = client
+        .subscribe(topic.subscribe, rpc_params![], topic.unsubscribe)
+        .await
+        .map_err(|e| format!("{} failed: {}", topic.subscribe, e))?;
+    *attempt = 0;
+    status.send_modify(|s| s.state = FeedState::Connected);
+    debug!("Subscribed to relayer {} feed at {}", topic.name, endpoint);
+
+    loop {
+        tokio::select! {
+            _ = items.closed() => return Ok(()),
+            next = subscription.next() => match next {
+                Some(Ok(item)) => {
+                    status.send_modify(|s| s.last_message_at = Some(Instant::now()));
+                    if items.send(item).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                Some(Err(e)) => warn!("Skipping undecodable {} message: {}", topic.name, e),
+                None => return Err("subscription closed by the relayer".to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_endpoints_become_ws() {
+        assert_eq!(
+            ws_endpoint("https://relayer.twilight.rest/api"),
+            "wss://relayer.twilight.rest/api"
+        );
+        assert_eq!(
+            ws_endpoint("http://0.0.0.0:8088/api"),
+            "ws://0.0.0.0:8088/api"
+        );
+        assert_eq!(ws_endpoint("ws://0.0.0.0:8990"), "ws://0.0.0.0:8990");
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = DEFAULT_RECONNECT_BACKOFF;
+        let first = policy.delay(1);
+        assert!(first >= Duration::from_millis(500) && first <= Duration::from_millis(600));
+        let third = policy.delay(3);
+        assert!(third >= Duration::from_secs(2) && third <= Duration::from_millis(2400));
+        let late = policy.delay(40);
+        assert!(late >= Duration::from_secs(30) && late <= Duration::from_secs(36));
+    }
+
+    #[test]
+    fn test_feed_is_stale_until_connected_and_fresh() {
+        let now = Instant::now();
+        let max_age = Duration::from_secs(5);
+        let mut status = FeedStatus::connecting();
+        assert!(status.is_stale_at(max_age, now));
+
+        status.state = FeedState::Connected;
+        assert!(status.is_stale_at(max_age, now));
+        status.last_message_at = Some(now);
+        assert!(!status.is_stale_at(max_age, now + Duration::from_secs(5)));
+        assert!(status.is_stale_at(max_age, now + Duration::from_secs(6)));
+
+        status.state = FeedState::Reconnecting {
+            attempt: 1,
+            last_error: "reset".to_string(),
+        };
+        assert!(status.is_stale_at(max_age, now));
+    }
+
+    /// A price feed server on `addr` that sends one price per subscription.
+    async fn serve_prices(addr: std::net::SocketAddr) -> jsonrpsee::server::ServerHandle {
+        use jsonrpsee::core::SubscriptionResult;
+        use jsonrpsee::server::{RpcModule, Server};
+
+        let server = Server::builder().build(addr).await.unwrap();
+        let mut module = RpcModule::new(());
+        module
+            .register_subscription(
+                LIVE_PRICE.subscribe,
+                "live_price",
+                LIVE_PRICE.unsubscribe,
+                |_, pending, _, _| async move {
+                    let sink = pending.accept().await?;
+                    let price = serde_json::json!({
+                        "id": 1,
+                        "price": "50000",
+                        "timestamp": "2025-03-01T12:00:00Z",
+                    });
+                    sink.send(serde_json::value::to_raw_value(&price)?.into())
+                        .await?;
+                    SubscriptionResult::Ok(())
+                },
+            )
+            .unwrap();
+        server.start(module)
+    }
+
+    #[tokio::test]
+    async fn test_feed_reconnects_once_the_relayer_is_up() {
+        use futures_core::Stream;
+
+        // Nothing listens on the port until the server starts below.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let backoff = Backoff {
+            initial_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(50),
+            factor: 2.0,
+            jitter: 0.0,
+        };
+        let client =
+            RelayerWsClient::new(&format!("http://{}", addr)).with_reconnect_policy(backoff);
+        let mut prices = client.subscribe_btc_price();
+        let mut status = prices.status_watch();
+        let reconnecting = status.wait_for(|s| matches!(s.state, FeedState::Reconnecting { .. }));
+        tokio::time::timeout(Duration::from_secs(5), reconnecting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(prices.status().is_stale(Duration::from_secs(5)));
+
+        let server = serve_prices(addr).await;
+        let next = std::future::poll_fn(|cx| Pin::new(&mut prices).poll_next(cx));
+        let price = tokio::time::timeout(Duration::from_secs(5), next)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(price.price, 50_000.0);
+        let status = prices.status();
+        assert!(status.is_connected());
+        assert!(!status.is_stale(Duration::from_secs(5)));
+        server.stop().unwrap();
+    }
+}
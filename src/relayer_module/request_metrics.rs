@@ -0,0 +1,221 @@
+//! Per-method request metrics of a relayer client.
+//!
+//! A [`RelayerJsonRpcClient`](super::relayer_api::RelayerJsonRpcClient) and
+//! its clones count every relayer call by JSON-RPC method: calls, errors,
+//! calls that waited for the rate limiter, and the latency of the call from
+//! the first attempt to the answer, failover included. Percentiles are over
+//! the last [`LATENCY_SAMPLES`] calls of each method. Reads that go through a
+//! [response cache](super::response_cache) are also counted as cache hits or
+//! misses; a hit is not a call.
+//!
+//! [`RelayerJsonRpcClient::metrics`](super::relayer_api::RelayerJsonRpcClient::metrics)
+//! returns a [`RelayerMetrics`] snapshot;
+//! [`RelayerMetrics::log`] writes one line per method.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::info;
+use serde::Serialize;
+
+/// Latencies kept per method for the percentiles.
+pub const LATENCY_SAMPLES: usize = 1024;
+
+/// Metrics of one method.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodMetrics {
+    pub calls: u64,
+    pub errors: u64,
+    /// Calls that waited for the rate limiter before they were sent.
+    pub throttled: u64,
+    /// Reads answered by the response cache without a call.
+    pub cache_hits: u64,
+    /// Reads the response cache passed on as a call.
+    pub cache_misses: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Snapshot of a client's metrics, by method name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RelayerMetrics {
+    pub methods: BTreeMap<String, MethodMetrics>,
+}
+
+impl RelayerMetrics {
+    pub fn method(&self, method: &str) -> Option<&MethodMetrics> {
+        self.methods.get(method)
+    }
+
+    pub fn total_calls(&self) -> u64 {
+        self.methods.values().map(|m| m.calls).sum()
+    }
+
+    pub fn total_errors(&self) -> u64 {
+        self.methods.values().map(|m| m.errors).sum()
+    }
+
+    pub fn total_cache_hits(&self) -> u64 {
+        self.methods.values().map(|m| m.cache_hits).sum()
+    }
+
+    /// Log one line per method.
+    pub fn log(&self) {
+        for (method, m) in &self.methods {
+            info!(
+                "relayer {}: {} calls, {} errors, {} throttled, {} cache hits, {} cache misses, p50 {:?}, p90 {:?}, p99 {:?}",
+                method,
+                m.calls,
+                m.errors,
+                m.throttled,
+                m.cache_hits,
+                m.cache_misses,
+                m.p50,
+                m.p90,
+                m.p99
+            );
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MethodState {
+    calls: u64,
+    errors: u64,
+    throttled: u64,
+    cache_hits: u64,
+    cache_misses: u64,
+    latencies: VecDeque<Duration>,
+}
+
+impl MethodState {
+    fn snapshot(&self) -> MethodMetrics {
+        let mut sorted: Vec<Duration> = self.latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        MethodMetrics {
+            calls: self.calls,
+            errors: self.errors,
+            throttled: self.throttled,
+            cache_hits: self.cache_hits,
+            cache_misses: self.cache_misses,
+            p50: percentile(&sorted, 0.50),
+            p90: percentile(&sorted, 0.90),
+            p99: percentile(&sorted, 0.99),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Nearest-rank percentile of `sorted`; zero when empty.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Shared by a client and its clones.
+#[derive(Debug, Default)]
+pub(crate) struct MetricsRecorder {
+    methods: Mutex<HashMap<String, MethodState>>,
+}
+
+impl MetricsRecorder {
+    pub(crate) fn record(&self, method: &str, latency: Duration, ok: bool, throttled: bool) {
+        let mut methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        let state = methods.entry(method.to_string()).or_default();
+        state.calls += 1;
+        if !ok {
+            state.errors += 1;
+        }
+        if throttled {
+            state.throttled += 1;
+        }
+        if state.latencies.len() == LATENCY_SAMPLES {
+            state.latencies.pop_front();
+        }
+        state.latencies.push_back(latency);
+    }
+
+    /// Count a read of `method` that the response cache answered (`hit`) or
+    /// passed on.
+    pub(crate) fn record_cache(&self, method: &str, hit: bool) {
+        let mut methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        let state = methods.entry(method.to_string()).or_default();
+        if hit {
+            state.cache_hits += 1;
+        } else {
+            state.cache_misses += 1;
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> RelayerMetrics {
+        let methods = self.methods.lock().unwrap_or_else(|e| e.into_inner());
+        RelayerMetrics {
+            methods: methods
+                .iter()
+                .map(|(method, state)| (method.clone(), state.snapshot()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_and_percentiles_per_method() {
+        let recorder = MetricsRecorder::default();
+        for ms in 1..=100 {
+            recorder.record("btc_usd_price", Duration::from_millis(ms), ms != 7, ms > 95);
+        }
+        recorder.record("get_market_stats", Duration::from_millis(40), true, false);
+
+        let metrics = recorder.snapshot();
+        let price = metrics.method("btc_usd_price").unwrap();
+        assert_eq!((price.calls, price.errors, price.throttled), (100, 1, 5));
+        assert_eq!(price.p50, Duration::from_millis(50));
+        assert_eq!(price.p90, Duration::from_millis(90));
+        assert_eq!(price.p99, Duration::from_millis(99));
+        assert_eq!(price.max, Duration::from_millis(100));
+        assert_eq!(
+            metrics.method("get_market_stats").unwrap().p99,
+            Duration::from_millis(40)
+        );
+        assert_eq!(metrics.total_calls(), 101);
+        assert_eq!(metrics.total_errors(), 1);
+    }
+
+    #[test]
+    fn test_cache_hits_are_not_calls() {
+        let recorder = MetricsRecorder::default();
+        recorder.record_cache("open_limit_orders", false);
+        recorder.record("open_limit_orders", Duration::from_millis(3), true, false);
+        recorder.record_cache("open_limit_orders", true);
+        recorder.record_cache("open_limit_orders", true);
+
+        let metrics = recorder.snapshot();
+        let book = metrics.method("open_limit_orders").unwrap();
+        assert_eq!((book.calls, book.cache_hits, book.cache_misses), (1, 2, 1));
+        assert_eq!(metrics.total_cache_hits(), 2);
+    }
+
+    #[test]
+    fn test_percentiles_use_recent_samples_only() {
+        let recorder = MetricsRecorder::default();
+        for _ in 0..LATENCY_SAMPLES {
+            recorder.record("m", Duration::from_secs(5), true, false);
+        }
+        for _ in 0..LATENCY_SAMPLES {
+            recorder.record("m", Duration::from_millis(1), true, false);
+        }
+        let m = recorder.snapshot().methods["m"].clone();
+        assert_eq!(m.calls, 2 * LATENCY_SAMPLES as u64);
+        assert_eq!(m.max, Duration::from_millis(1));
+    }
+}
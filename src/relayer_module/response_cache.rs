@@ -0,0 +1,367 @@
+//! Short-lived response cache for hot relayer read endpoints.
+//!
+//! Bots that poll `btc_usd_price` or `open_limit_orders` several times a
+//! second can share one [`ResponseCache`] (cheap to clone) between every
+//! [`RelayerJsonRpcClient`](super::relayer_api::RelayerJsonRpcClient) on the
+//! host with
+//! [`with_response_cache`](super::relayer_api::RelayerJsonRpcClient::with_response_cache).
+//!
+//! - A response is reused for the TTL of its [`EndpointClass`]; a zero TTL
+//!   turns caching off for that class.
+//! - Concurrent identical requests are coalesced (single flight): while one
+//!   caller is fetching, the others wait for its result instead of issuing a
+//!   duplicate request.
+//! - Errors are never cached, so the next waiter fetches again.
+//! - Hits and misses are counted per class and reported by
+//!   [`stats`](ResponseCache::stats); each client also counts its own per
+//!   method in its [request metrics](super::request_metrics).
+//!
+//! Callers that need absolute freshness use the `*_uncached()` client methods.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Which TTL applies to an endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointClass {
+    /// `btc_usd_price`.
+    Price,
+    /// `open_limit_orders`.
+    OrderBook,
+    /// `lend_pool_info`, `pool_share_value`.
+    PoolInfo,
+}
+
+impl EndpointClass {
+    pub const ALL: [EndpointClass; 3] = [
+        EndpointClass::Price,
+        EndpointClass::OrderBook,
+        EndpointClass::PoolInfo,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndpointClass::Price => "price",
+            EndpointClass::OrderBook => "order_book",
+            EndpointClass::PoolInfo => "pool_info",
+        }
+    }
+}
+
+/// TTL per endpoint class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseCacheConfig {
+    pub price_ttl: Duration,
+    pub order_book_ttl: Duration,
+    pub pool_info_ttl: Duration,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            price_ttl: Duration::from_millis(250),
+            order_book_ttl: Duration::from_millis(500),
+            pool_info_ttl: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ResponseCacheConfig {
+    pub fn ttl(&self, class: EndpointClass) -> Duration {
+        match class {
+            EndpointClass::Price => self.price_ttl,
+            EndpointClass::OrderBook => self.order_book_ttl,
+            EndpointClass::PoolInfo => self.pool_info_ttl,
+        }
+    }
+}
+
+/// Hit/miss counters. A caller that waited on another caller's in-flight
+/// request counts as a hit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Entry {
+    fetched_at: Instant,
+    value: Box<dyn Any + Send + Sync>,
+}
+
+/// Held across the upstream request so concurrent callers queue behind it.
+type Slot = Arc<tokio::sync::Mutex<Option<Entry>>>;
+
+#[derive(Default)]
+struct Inner {
+    config: ResponseCacheConfig,
+    slots: Mutex<HashMap<String, Slot>>,
+    stats: Mutex<HashMap<EndpointClass, CacheStats>>,
+}
+
+/// Shared response cache; clones share entries and counters.
+#[derive(Clone, Default)]
+pub struct ResponseCache {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("config", &self.inner.config)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn config(&self) -> ResponseCacheConfig {
+        self.inner.config
+    }
+
+    /// Counters summed over all classes.
+    pub fn stats(&self) -> CacheStats {
+        let stats = self.lock_stats();
+        stats
+            .values()
+            .fold(CacheStats::default(), |acc, s| CacheStats {
+                hits: acc.hits + s.hits,
+                misses: acc.misses + s.misses,
+            })
+    }
+
+    pub fn stats_for(&self, class: EndpointClass) -> CacheStats {
+        self.lock_stats().get(&class).copied().unwrap_or_default()
+    }
+
+    /// Drop every cached response. Counters are kept.
+    pub fn clear(&self) {
+        self.lock_slots().clear();
+    }
+
+    /// Return the cached value for `key` if it is younger than the class TTL,
+    /// otherwise run `fetch` and cache its result. Concurrent callers for the
+    /// same key wait for the one that is fetching.
+    pub async fn get_or_fetch<T, E, F, Fut>(
+        &self,
+        key: &str,
+        class: EndpointClass,
+        fetch: F,
+    ) -> Result<T, E>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.get_or_fetch_observed(key, class, |_| {}, fetch).await
+    }
+
+    /// [`get_or_fetch`](Self::get_or_fetch), also passing whether the lookup
+    /// was a hit to `observe`. Not called when the class TTL is zero.
+    pub(crate) async fn get_or_fetch_observed<T, E, F, Fut>(
+        &self,
+        key: &str,
+        class: EndpointClass,
+        observe: impl FnOnce(bool),
+        fetch: F,
+    ) -> Result<T, E>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let ttl = self.inner.config.ttl(class);
+        if ttl.is_zero() {
+            return fetch().await;
+        }
+
+        let slot = self
+            .lock_slots()
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        let mut entry = slot.lock().await;
+        let fresh = entry
+            .as_ref()
+            .filter(|cached| cached.fetched_at.elapsed() < ttl)
+            .and_then(|cached| cached.value.downcast_ref::<T>());
+        if let Some(value) = fresh {
+            self.record(class, true);
+            observe(true);
+            return Ok(value.clone());
+        }
+
+        self.record(class, false);
+        observe(false);
+        let value = fetch().await?;
+        *entry = Some(Entry {
+            fetched_at: Instant::now(),
+            value: Box::new(value.clone()),
+        });
+        Ok(value)
+    }
+
+    fn record(&self, class: EndpointClass, hit: bool) {
+        let mut stats = self.lock_stats();
+        let counters = stats.entry(class).or_default();
+        if hit {
+            counters.hits += 1;
+        } else {
+            counters.misses += 1;
+        }
+    }
+
+    fn lock_slots(&self) -> std::sync::MutexGuard<'_, HashMap<String, Slot>> {
+        self.inner.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, HashMap<EndpointClass, CacheStats>> {
+        self.inner.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting_fetch(calls: Arc<AtomicUsize>) -> impl Future<Output = Result<usize, String>> {
+        async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(calls.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_are_coalesced() {
+        let cache = ResponseCache::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..8 {
+            let cache = cache.clone();
+            let calls = calls.clone();
+            tasks.spawn(async move {
+                cache
+                    .get_or_fetch("price", EndpointClass::Price, || {
+                        counting_fetch(calls.clone())
+                    })
+                    .await
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            assert_eq!(result.unwrap(), Ok(1));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.stats(), CacheStats { hits: 7, misses: 1 });
+        assert_eq!(
+            cache.stats_for(EndpointClass::OrderBook),
+            CacheStats::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entries_expire_after_ttl() {
+        let cache = ResponseCache::new(ResponseCacheConfig {
+            price_ttl: Duration::from_millis(30),
+            ..Default::default()
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetch = || counting_fetch(calls.clone());
+        assert_eq!(
+            cache.get_or_fetch("p", EndpointClass::Price, fetch).await,
+            Ok(1)
+        );
+        assert_eq!(
+            cache.get_or_fetch("p", EndpointClass::Price, fetch).await,
+            Ok(1)
+        );
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(
+            cache.get_or_fetch("p", EndpointClass::Price, fetch).await,
+            Ok(2)
+        );
+        // Other keys are independent.
+        assert_eq!(
+            cache.get_or_fetch("q", EndpointClass::Price, fetch).await,
+            Ok(3)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_not_cached() {
+        let cache = ResponseCache::default();
+        let first: Result<u32, String> = cache
+            .get_or_fetch("k", EndpointClass::PoolInfo, || async {
+                Err("down".to_string())
+            })
+            .await;
+        assert_eq!(first, Err("down".to_string()));
+        let second: Result<u32, String> = cache
+            .get_or_fetch("k", EndpointClass::PoolInfo, || async { Ok(5) })
+            .await;
+        assert_eq!(second, Ok(5));
+        assert_eq!(cache.stats_for(EndpointClass::PoolInfo).misses, 2);
+    }
+
+    #[tokio::test]
+    async fn test_zero_ttl_disables_class() {
+        let cache = ResponseCache::new(ResponseCacheConfig {
+            order_book_ttl: Duration::ZERO,
+            ..Default::default()
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetch = || counting_fetch(calls.clone());
+        cache
+            .get_or_fetch("o", EndpointClass::OrderBook, fetch)
+            .await
+            .unwrap();
+        cache
+            .get_or_fetch("o", EndpointClass::OrderBook, fetch)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_clear_drops_entries() {
+        let cache = ResponseCache::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetch = || counting_fetch(calls.clone());
+        cache
+            .get_or_fetch("p", EndpointClass::Price, fetch)
+            .await
+            .unwrap();
+        cache.clear();
+        assert_eq!(
+            cache.get_or_fetch("p", EndpointClass::Price, fetch).await,
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_default_ttls() {
+        let config = ResponseCacheConfig::default();
+        assert_eq!(config.ttl(EndpointClass::Price), Duration::from_millis(250));
+        assert_eq!(
+            config.ttl(EndpointClass::OrderBook),
+            Duration::from_millis(500)
+        );
+        assert_eq!(config.ttl(EndpointClass::PoolInfo), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,500 @@
+//! Wallet-level risk metrics for dashboards.
+//!
+//! [`OrderWallet::risk_report`](super::order_wallet::OrderWallet::risk_report)
+//! queries every open position in one bounded concurrent pass and
+//! [`RiskReport::compute`] reduces them to a single report. Amounts the
+//! relayer returns as `f64` are converted to whole sats with
+//! [`sats_from_f64`]; a position with an amount that does not convert is
+//! listed in [`RiskReport::unavailable_accounts`] instead of being guessed.
+//!
+//! ## Formulas
+//!
+//! With `P` the current BTC/USD price and, per trader position, `m` its
+//! initial margin (sats), `L` its leverage and `S` its `positionsize`
+//! (`m * L * entry_price`, in sats·USD):
+//!
+//! - **notional (USD)** = `S / 100_000_000`, the contract value fixed at entry.
+//! - **BTC exposure (sats)** = `S / P`, what that contract is worth in sats now.
+//! - **margin locked** = `Σ m` over open positions, pending limit orders
+//!   included since their margin is already committed.
+//! - **gross notional** = `notional(long) + notional(short)`;
+//!   **net notional** = `notional(long) - notional(short)`.
+//! - **weighted average leverage** = `Σ (L * m) / Σ m`, i.e. total position
+//!   value over total margin.
+//! - **margin utilization** = `margin locked / (coin account sats + margin locked)`,
+//!   as in [`Portfolio`](super::portfolio::Portfolio).
+//! - **distance to liquidation (%)** = `(P - liq) / P * 100` for a long and
+//!   `(liq - P) / P * 100` for a short; the riskiest position has the smallest.
+//!
+//! Notional, BTC exposure, leverage and liquidation distance only count filled
+//! positions; a pending order has no exposure yet.
+
+use std::future::Future;
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use crate::compat::relayer_types::{LendOrder, OrderStatus, PositionType, TraderOrder};
+
+use super::leverage::Leverage;
+use super::order_wallet::AccountIndex;
+
+pub const SATS_PER_BTC: f64 = 100_000_000.0;
+
+/// Relayer queries in flight at once while building a report.
+pub const RISK_QUERY_CONCURRENCY: usize = 8;
+
+/// A relayer amount in whole sats, rounded to nearest. `None` for negative,
+/// non-finite or out-of-range values.
+pub fn sats_from_f64(value: f64) -> Option<u64> {
+    if !value.is_finite() || value < 0.0 {
+        return None;
+    }
+    let rounded = value.round();
+    if rounded >= u64::MAX as f64 {
+        return None;
+    }
+    Some(rounded as u64)
+}
+
+/// The parts of an open trader order the report needs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraderExposure {
+    pub account_index: AccountIndex,
+    pub position_type: PositionType,
+    pub order_status: OrderStatus,
+    pub initial_margin_sats: u64,
+    pub leverage: Leverage,
+    /// `initial_margin * leverage * entry_price`, in sats·USD.
+    pub position_size: f64,
+    pub entry_price: f64,
+    pub liquidation_price: f64,
+}
+
+impl TraderExposure {
+    pub fn from_trader_order(index: AccountIndex, order: &TraderOrder) -> Result<Self, String> {
+        let initial_margin_sats = sats_from_f64(order.initial_margin)
+            .ok_or_else(|| format!("invalid initial_margin {}", order.initial_margin))?;
+        let leverage = Leverage::try_from_f64(order.leverage).map_err(|e| e.to_string())?;
+        if !order.positionsize.is_finite() || order.positionsize < 0.0 {
+            return Err(format!("invalid positionsize {}", order.positionsize));
+        }
+        Ok(Self {
+            account_index: index,
+            position_type: order.position_type.clone(),
+            order_status: order.order_status.clone(),
+            initial_margin_sats,
+            leverage,
+            position_size: order.positionsize,
+            entry_price: order.entryprice,
+            liquidation_price: order.liquidation_price,
+        })
+    }
+
+    pub fn is_filled(&self) -> bool {
+        self.order_status != OrderStatus::PENDING
+    }
+
+    pub fn notional_usd(&self) -> f64 {
+        self.position_size / SATS_PER_BTC
+    }
+
+    /// Current value of the contract in sats at `price`.
+    pub fn btc_exposure_sats(&self, price: f64) -> Option<u64> {
+        if price > 0.0 {
+            sats_from_f64(self.position_size / price)
+        } else {
+            None
+        }
+    }
+
+    /// Percent the price can move against the position before liquidation;
+    /// negative once past it. `None` without a price or liquidation price.
+    pub fn distance_to_liquidation_pct(&self, price: f64) -> Option<f64> {
+        if price <= 0.0 || self.liquidation_price <= 0.0 {
+            return None;
+        }
+        Some(match self.position_type {
+            PositionType::LONG => (price - self.liquidation_price) / price * 100.0,
+            PositionType::SHORT => (self.liquidation_price - price) / price * 100.0,
+        })
+    }
+}
+
+/// The parts of an active lend order the report needs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LendExposure {
+    pub account_index: AccountIndex,
+    pub deposit_sats: u64,
+    /// Current value of the pool share.
+    pub value_sats: u64,
+}
+
+impl LendExposure {
+    pub fn from_lend_order(index: AccountIndex, order: &LendOrder) -> Result<Self, String> {
+        Ok(Self {
+            account_index: index,
+            deposit_sats: sats_from_f64(order.deposit)
+                .ok_or_else(|| format!("invalid deposit {}", order.deposit))?,
+            value_sats: sats_from_f64(order.new_lend_state_amount).ok_or_else(|| {
+                format!(
+                    "invalid new_lend_state_amount {}",
+                    order.new_lend_state_amount
+                )
+            })?,
+        })
+    }
+}
+
+/// Totals for one side of the book.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SideExposure {
+    /// Open positions, pending limit orders included.
+    pub positions: usize,
+    pub margin_sats: u64,
+    pub notional_usd: f64,
+    pub btc_exposure_sats: u64,
+}
+
+/// The position closest to liquidation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RiskiestPosition {
+    pub account_index: AccountIndex,
+    pub position_type: PositionType,
+    pub liquidation_price: f64,
+    pub distance_pct: f64,
+}
+
+/// Exposure and margin usage across a wallet's accounts.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RiskReport {
+    pub generated_at: DateTime<Utc>,
+    pub btc_usd_price: f64,
+    /// Balance of the funding (twilight) address.
+    pub on_chain_sats: u64,
+    /// Sum of on-chain Coin account balances.
+    pub coin_account_sats: u64,
+    pub long: SideExposure,
+    pub short: SideExposure,
+    pub margin_locked_sats: u64,
+    pub lend_deposit_sats: u64,
+    pub lend_value_sats: u64,
+    pub gross_notional_usd: f64,
+    pub net_notional_usd: f64,
+    /// `None` without filled positions.
+    pub weighted_average_leverage: Option<Leverage>,
+    pub margin_utilization: f64,
+    pub riskiest_position: Option<RiskiestPosition>,
+    /// Accounts whose order could not be queried or converted.
+    pub unavailable_accounts: Vec<AccountIndex>,
+}
+
+impl RiskReport {
+    /// Apply the formulas in the [module docs](self) to already-fetched positions.
+    pub fn compute(
+        generated_at: DateTime<Utc>,
+        btc_usd_price: f64,
+        on_chain_sats: u64,
+        coin_account_sats: u64,
+        traders: &[TraderExposure],
+        lends: &[LendExposure],
+        mut unavailable_accounts: Vec<AccountIndex>,
+    ) -> Self {
+        let mut long = SideExposure::default();
+        let mut short = SideExposure::default();
+        let mut leverage_units_x_margin: u128 = 0;
+        let mut filled_margin: u128 = 0;
+        let mut riskiest_position: Option<RiskiestPosition> = None;
+
+        for position in traders {
+            let side = match position.position_type {
+                PositionType::LONG => &mut long,
+                PositionType::SHORT => &mut short,
+            };
+            side.positions += 1;
+            side.margin_sats = side
+                .margin_sats
+                .saturating_add(position.initial_margin_sats);
+            if !position.is_filled() {
+                continue;
+            }
+            side.notional_usd += position.notional_usd();
+            side.btc_exposure_sats = side
+                .btc_exposure_sats
+                .saturating_add(position.btc_exposure_sats(btc_usd_price).unwrap_or(0));
+            leverage_units_x_margin +=
+                position.leverage.units() as u128 * position.initial_margin_sats as u128;
+            filled_margin += position.initial_margin_sats as u128;
+
+            let closer = position
+                .distance_to_liquidation_pct(btc_usd_price)
+                .filter(|distance| {
+                    riskiest_position
+                        .as_ref()
+                        .is_none_or(|riskiest| *distance < riskiest.distance_pct)
+                });
+            if let Some(distance_pct) = closer {
+                riskiest_position = Some(RiskiestPosition {
+                    account_index: position.account_index,
+                    position_type: position.position_type.clone(),
+                    liquidation_price: position.liquidation_price,
+                    distance_pct,
+                });
+            }
+        }
+
+        let weighted_average_leverage = leverage_units_x_margin
+            .checked_div(filled_margin)
+            .and_then(|units| u64::try_from(units).ok())
+            .map(Leverage::from_units);
+        let margin_locked_sats = long.margin_sats.saturating_add(short.margin_sats);
+        let capital = coin_account_sats as f64 + margin_locked_sats as f64;
+        let margin_utilization = if capital > 0.0 {
+            margin_locked_sats as f64 / capital
+        } else {
+            0.0
+        };
+        unavailable_accounts.sort_unstable();
+
+        Self {
+            generated_at,
+            btc_usd_price,
+            on_chain_sats,
+            coin_account_sats,
+            margin_locked_sats,
+            lend_deposit_sats: lends
+                .iter()
+                .fold(0u64, |sum, l| sum.saturating_add(l.deposit_sats)),
+            lend_value_sats: lends
+                .iter()
+                .fold(0u64, |sum, l| sum.saturating_add(l.value_sats)),
+            gross_notional_usd: long.notional_usd + short.notional_usd,
+            net_notional_usd: long.notional_usd - short.notional_usd,
+            long,
+            short,
+            weighted_average_leverage,
+            margin_utilization,
+            riskiest_position,
+            unavailable_accounts,
+        }
+    }
+
+    /// Weighted-average leverage as a plain multiplier, for display.
+    pub fn weighted_average_leverage_f64(&self) -> Option<f64> {
+        self.weighted_average_leverage.map(|l| l.as_f64())
+    }
+}
+
+/// Run `tasks` with at most `limit` in flight; results come back in
+/// completion order. Tasks that panic are logged and dropped; run a task
+/// under [`compat::guard_async`](crate::compat::guard_async) to get its
+/// panic back as an error instead.
+pub(crate) async fn run_bounded<T, F>(tasks: Vec<F>, limit: usize) -> Vec<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut set = JoinSet::new();
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        if set.len() >= limit.max(1) {
+            if let Some(joined) = set.join_next().await {
+                results.extend(joined_result(joined));
+            }
+        }
+        set.spawn(task);
+    }
+    while let Some(joined) = set.join_next().await {
+        results.extend(joined_result(joined));
+    }
+    results
+}
+
+fn joined_result<T>(joined: Result<T, tokio::task::JoinError>) -> Option<T> {
+    joined.map_err(|e| warn!("Bounded task failed: {}", e)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn position(
+        index: AccountIndex,
+        position_type: PositionType,
+        margin: u64,
+        leverage: u64,
+        entry_price: f64,
+        liquidation_price: f64,
+    ) -> TraderExposure {
+        TraderExposure {
+            account_index: index,
+            position_type,
+            order_status: OrderStatus::FILLED,
+            initial_margin_sats: margin,
+            leverage: Leverage::from(leverage),
+            position_size: (margin * leverage) as f64 * entry_price,
+            entry_price,
+            liquidation_price,
+        }
+    }
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_two_longs_one_short_one_lend() {
+        let at = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let traders = [
+            // 10_000 sats at 5x from 40k: size 2e9, liquidated at 33k.
+            position(1, PositionType::LONG, 10_000, 5, 40_000.0, 33_000.0),
+            // 20_000 sats at 2x from 50k: size 2e9, liquidated at 26k.
+            position(2, PositionType::LONG, 20_000, 2, 50_000.0, 26_000.0),
+            // 10_000 sats at 10x from 52k: size 5.2e9, liquidated at 56k.
+            position(3, PositionType::SHORT, 10_000, 10, 52_000.0, 56_000.0),
+        ];
+        let lends = [LendExposure {
+            account_index: 4,
+            deposit_sats: 30_000,
+            value_sats: 31_000,
+        }];
+
+        let report = RiskReport::compute(at, 50_000.0, 100_000, 15_000, &traders, &lends, vec![]);
+
+        assert_eq!(report.generated_at, at);
+        assert_eq!(report.btc_usd_price, 50_000.0);
+        assert_eq!(report.on_chain_sats, 100_000);
+        assert_eq!(report.coin_account_sats, 15_000);
+        // Notional: (2e9 + 2e9) / 1e8 = 40 USD; exposure 4e9 / 50k = 80_000 sats.
+        assert_eq!(
+            report.long,
+            SideExposure {
+                positions: 2,
+                margin_sats: 30_000,
+                notional_usd: 40.0,
+                btc_exposure_sats: 80_000,
+            }
+        );
+        // 5.2e9 / 1e8 = 52 USD; 5.2e9 / 50k = 104_000 sats.
+        assert_eq!(
+            report.short,
+            SideExposure {
+                positions: 1,
+                margin_sats: 10_000,
+                notional_usd: 52.0,
+                btc_exposure_sats: 104_000,
+            }
+        );
+        assert_eq!(report.margin_locked_sats, 40_000);
+        assert_eq!(report.lend_deposit_sats, 30_000);
+        assert_eq!(report.lend_value_sats, 31_000);
+        assert_eq!(report.gross_notional_usd, 92.0);
+        assert_eq!(report.net_notional_usd, -12.0);
+        // (5 * 10_000 + 2 * 20_000 + 10 * 10_000) / 40_000 = 4.75x.
+        assert_eq!(
+            report.weighted_average_leverage,
+            Some(Leverage::from_units(47_500))
+        );
+        assert_eq!(report.weighted_average_leverage_f64(), Some(4.75));
+        // 40_000 / (15_000 + 40_000).
+        assert!(approx_eq(report.margin_utilization, 40_000.0 / 55_000.0));
+        // Long 1: 34%, long 2: 48%, short: (56k - 50k) / 50k = 12%.
+        let riskiest = report.riskiest_position.as_ref().unwrap();
+        assert_eq!(riskiest.account_index, 3);
+        assert_eq!(riskiest.position_type, PositionType::SHORT);
+        assert_eq!(riskiest.liquidation_price, 56_000.0);
+        assert!(approx_eq(riskiest.distance_pct, 12.0));
+        assert!(report.unavailable_accounts.is_empty());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["weighted_average_leverage"], serde_json::json!(4.75));
+        assert_eq!(json["long"]["margin_sats"], serde_json::json!(30_000));
+    }
+
+    #[test]
+    fn test_pending_orders_lock_margin_without_exposure() {
+        let mut pending = position(1, PositionType::LONG, 10_000, 5, 40_000.0, 33_000.0);
+        pending.order_status = OrderStatus::PENDING;
+        let report = RiskReport::compute(Utc::now(), 50_000.0, 0, 0, &[pending], &[], vec![7, 2]);
+
+        assert_eq!(report.long.positions, 1);
+        assert_eq!(report.long.margin_sats, 10_000);
+        assert_eq!(report.long.notional_usd, 0.0);
+        assert_eq!(report.weighted_average_leverage, None);
+        assert!(report.riskiest_position.is_none());
+        assert_eq!(report.margin_utilization, 1.0);
+        assert_eq!(report.unavailable_accounts, vec![2, 7]);
+    }
+
+    #[test]
+    fn test_sats_from_f64() {
+        assert_eq!(sats_from_f64(1_000.4), Some(1_000));
+        assert_eq!(sats_from_f64(0.0), Some(0));
+        assert_eq!(sats_from_f64(-1.0), None);
+        assert_eq!(sats_from_f64(f64::NAN), None);
+        assert_eq!(sats_from_f64(f64::INFINITY), None);
+        assert_eq!(sats_from_f64(1e30), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_caps_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let in_flight = in_flight.clone();
+                let peak = peak.clone();
+                async move {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    i
+                }
+            })
+            .collect();
+        let mut results = run_bounded(tasks, 3).await;
+        results.sort_unstable();
+        assert_eq!(results, (0..20).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_guarded_task_reports_its_panic() {
+        let tasks: Vec<_> = (0..3u64)
+            .map(|i| async move {
+                let query = async move {
+                    if i == 1 {
+                        panic!("bad response");
+                    }
+                    i
+                };
+                let result = crate::compat::guard_async("risk_report", "test", query).await;
+                (i, result.map_err(|e| e.to_string()))
+            })
+            .collect();
+        let mut results = run_bounded(tasks, 2).await;
+        results.sort_by_key(|(i, _)| *i);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].1, Ok(0));
+        let err = results[1].1.clone().unwrap_err();
+        assert!(err.contains("panicked: bad response"), "{}", err);
+
+        // Unguarded, the panicking task is dropped.
+        let tasks: Vec<_> = (0..3u64)
+            .map(|i| async move {
+                assert_ne!(i, 1, "bad response");
+                i
+            })
+            .collect();
+        let mut results = run_bounded(tasks, 2).await;
+        results.sort_unstable();
+        assert_eq!(results, vec![0, 2]);
+    }
+}
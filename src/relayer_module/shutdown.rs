@@ -0,0 +1,226 @@
+//! Coordinated shutdown for an [`OrderWallet`](super::order_wallet::OrderWallet).
+//!
+//! Long-running bots start several things next to the wallet (background
+//! tasks, the health endpoint, their own loops). Registering them with the
+//! wallet's [`ShutdownRegistry`] lets
+//! [`OrderWallet::shutdown`](super::order_wallet::OrderWallet::shutdown) stop
+//! them in reverse start order, optionally cancel pending orders and close
+//! open positions, persist wallet state, and release the database, all under
+//! one deadline. The returned [`ShutdownReport`] lists every step and whether
+//! it completed, failed, timed out or was skipped.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Boxed future awaited by a stop hook.
+pub type StopHook = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// What [`OrderWallet::shutdown`](super::order_wallet::OrderWallet::shutdown) should do.
+#[derive(Debug, Clone)]
+pub struct ShutdownOptions {
+    /// Close filled trader orders (market) and open lend orders.
+    pub close_positions: bool,
+    /// Cancel trader orders that are still pending.
+    pub cancel_pending: bool,
+    /// Persist wallet, account, UTXO and request-ID state to the database.
+    pub flush: bool,
+    /// Deadline for the whole shutdown.
+    pub timeout: Duration,
+}
+
+impl Default for ShutdownOptions {
+    fn default() -> Self {
+        Self {
+            close_positions: false,
+            cancel_pending: false,
+            flush: true,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Outcome of one shutdown step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum StepStatus {
+    Done(Option<String>),
+    Skipped(String),
+    Failed(String),
+    TimedOut,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepReport {
+    pub step: String,
+    #[serde(flatten)]
+    pub status: StepStatus,
+}
+
+/// What a shutdown did, in execution order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ShutdownReport {
+    /// Set when shutdown had already run; no steps were executed.
+    pub already_shut_down: bool,
+    pub steps: Vec<StepReport>,
+}
+
+impl ShutdownReport {
+    pub(crate) fn push(&mut self, step: impl Into<String>, status: StepStatus) {
+        self.steps.push(StepReport {
+            step: step.into(),
+            status,
+        });
+    }
+
+    /// Status of the step named `step`, if it ran.
+    pub fn status(&self, step: &str) -> Option<&StepStatus> {
+        self.steps
+            .iter()
+            .find(|s| s.step == step)
+            .map(|s| &s.status)
+    }
+
+    /// Names of steps that hit the deadline.
+    pub fn timed_out(&self) -> Vec<&str> {
+        self.steps
+            .iter()
+            .filter(|s| s.status == StepStatus::TimedOut)
+            .map(|s| s.step.as_str())
+            .collect()
+    }
+
+    /// True when no step failed, timed out or was skipped for lack of time.
+    pub fn is_clean(&self) -> bool {
+        self.steps.iter().all(|s| match &s.status {
+            StepStatus::Done(_) => true,
+            StepStatus::Skipped(reason) => reason != DEADLINE_EXCEEDED,
+            StepStatus::Failed(_) | StepStatus::TimedOut => false,
+        })
+    }
+}
+
+pub(crate) const DEADLINE_EXCEEDED: &str = "deadline exceeded";
+
+#[derive(Default)]
+struct RegistryState {
+    tasks: Vec<(String, JoinHandle<()>)>,
+    hooks: Vec<(String, StopHook)>,
+    shut_down: bool,
+}
+
+/// Subsystems to stop on shutdown. Clones share the same registry.
+#[derive(Clone, Default)]
+pub struct ShutdownRegistry {
+    state: Arc<Mutex<RegistryState>>,
+}
+
+impl std::fmt::Debug for ShutdownRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock();
+        f.debug_struct("ShutdownRegistry")
+            .field(
+                "tasks",
+                &state.tasks.iter().map(|t| &t.0).collect::<Vec<_>>(),
+            )
+            .field(
+                "hooks",
+                &state.hooks.iter().map(|h| &h.0).collect::<Vec<_>>(),
+            )
+            .field("shut_down", &state.shut_down)
+            .finish()
+    }
+}
+
+/// Registered subsystems, in registration order, handed over to shutdown.
+pub(crate) struct Registered {
+    pub tasks: Vec<(String, JoinHandle<()>)>,
+    pub hooks: Vec<(String, StopHook)>,
+}
+
+impl ShutdownRegistry {
+    /// Abort `task` on shutdown.
+    pub fn register_task(&self, name: &str, task: JoinHandle<()>) {
+        self.lock().tasks.push((name.to_string(), task));
+    }
+
+    /// Await `hook` on shutdown (e.g. `HealthServerHandle::shutdown()`).
+    pub fn register_hook(&self, name: &str, hook: impl Future<Output = ()> + Send + 'static) {
+        self.lock().hooks.push((name.to_string(), Box::pin(hook)));
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.lock().shut_down
+    }
+
+    /// Mark the registry as shut down and take everything registered.
+    /// Returns `None` if shutdown already started.
+    pub(crate) fn begin(&self) -> Option<Registered> {
+        let mut state = self.lock();
+        if state.shut_down {
+            return None;
+        }
+        state.shut_down = true;
+        Some(Registered {
+            tasks: std::mem::take(&mut state.tasks),
+            hooks: std::mem::take(&mut state.hooks),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RegistryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Run one step against `deadline`. Steps reached after the deadline are skipped.
+pub(crate) async fn run_step<F>(deadline: Instant, step: F) -> StepStatus
+where
+    F: Future<Output = Result<Option<String>, String>>,
+{
+    if Instant::now() >= deadline {
+        return StepStatus::Skipped(DEADLINE_EXCEEDED.to_string());
+    }
+    match tokio::time::timeout_at(deadline, step).await {
+        Ok(Ok(detail)) => StepStatus::Done(detail),
+        Ok(Err(e)) => StepStatus::Failed(e),
+        Err(_) => StepStatus::TimedOut,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_begin_is_one_shot() {
+        let registry = ShutdownRegistry::default();
+        registry.register_task("sync", tokio::spawn(std::future::pending()));
+        registry.register_hook("bot", async {});
+
+        let registered = registry.begin().unwrap();
+        assert_eq!(registered.tasks.len(), 1);
+        assert_eq!(registered.hooks.len(), 1);
+        assert!(registry.is_shut_down());
+        assert!(registry.begin().is_none());
+        registered.tasks[0].1.abort();
+    }
+
+    #[tokio::test]
+    async fn test_run_step_deadline() {
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let status = run_step(deadline, async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(None)
+        })
+        .await;
+        assert_eq!(status, StepStatus::TimedOut);
+
+        let status = run_step(deadline, async { Ok(None) }).await;
+        assert_eq!(status, StepStatus::Skipped(DEADLINE_EXCEEDED.to_string()));
+    }
+}
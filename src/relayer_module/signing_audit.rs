@@ -0,0 +1,282 @@
+//! Opt-in audit trail of relayer messages signed with account secret keys.
+//!
+//! Every query, cancel and settle request sent to the relayer is signed with
+//! the ZkOS account's secret key. When the audit is enabled, each signed
+//! payload is recorded as a [`SigningAuditEntry`] holding only the SHA-256 of
+//! the payload, the account index, the [`SigningPurpose`] and the time of
+//! signing; neither payloads nor keys are stored.
+//!
+//! Entries form a hash chain: each entry's hash covers its own fields and the
+//! previous entry's hash, so deleting, reordering or editing a stored entry is
+//! detected by [`verify_chain`].
+//!
+//! [`SigningAudit`] is a cheap, cloneable handle. A disabled handle (the
+//! default) records nothing. [`OrderWallet::enable_signing_audit`](super::order_wallet::OrderWallet::enable_signing_audit)
+//! turns it on and, with DB persistence, appends entries to the
+//! `signing_audit` table.
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, SubsecRound, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::clock::Clock;
+use super::order_wallet::AccountIndex;
+
+/// `prev_hash` of the first entry in a chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Why a payload was signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningPurpose {
+    /// Authenticated order lookup (`QueryTraderOrderZkos` / `QueryLendOrderZkos`).
+    Query,
+    /// Order or SL/TP cancellation.
+    Cancel,
+    /// Close/settle request for a trader or lend order.
+    Settle,
+}
+
+impl SigningPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningPurpose::Query => "query",
+            SigningPurpose::Cancel => "cancel",
+            SigningPurpose::Settle => "settle",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "query" => Some(SigningPurpose::Query),
+            "cancel" => Some(SigningPurpose::Cancel),
+            "settle" => Some(SigningPurpose::Settle),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SigningPurpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One signed payload. Hashes are lowercase hex SHA-256.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningAuditEntry {
+    pub seq: u64,
+    pub account_index: AccountIndex,
+    pub purpose: SigningPurpose,
+    pub payload_hash: String,
+    pub signed_at: DateTime<Utc>,
+    pub prev_hash: String,
+    pub entry_hash: String,
+}
+
+impl SigningAuditEntry {
+    fn compute_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(self.seq.to_be_bytes());
+        hasher.update(self.account_index.to_be_bytes());
+        hasher.update(self.purpose.as_str().as_bytes());
+        hasher.update(self.payload_hash.as_bytes());
+        hasher.update(self.signed_at.timestamp_micros().to_be_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Check that `entries` (oldest first) form an unbroken chain from the genesis hash.
+pub fn verify_chain(entries: &[SigningAuditEntry]) -> Result<(), String> {
+    let mut prev_hash = GENESIS_HASH;
+    for (expected_seq, entry) in entries.iter().enumerate() {
+        if entry.seq != expected_seq as u64 {
+            return Err(format!(
+                "Signing audit entry {} out of sequence (expected {})",
+                entry.seq, expected_seq
+            ));
+        }
+        if entry.prev_hash != prev_hash {
+            return Err(format!(
+                "Signing audit entry {} does not link to the previous entry",
+                entry.seq
+            ));
+        }
+        if entry.compute_hash() != entry.entry_hash {
+            return Err(format!("Signing audit entry {} hash mismatch", entry.seq));
+        }
+        prev_hash = &entry.entry_hash;
+    }
+    Ok(())
+}
+
+/// Durable destination for new entries (e.g. the wallet database).
+pub trait SigningAuditSink: std::fmt::Debug + Send + Sync {
+    fn append(&self, entry: &SigningAuditEntry) -> Result<(), String>;
+}
+
+#[derive(Debug)]
+struct AuditState {
+    clock: Arc<dyn Clock>,
+    entries: Vec<SigningAuditEntry>,
+    sink: Option<Arc<dyn SigningAuditSink>>,
+}
+
+/// Handle to the signing audit log. Clones share the same log.
+#[derive(Debug, Clone, Default)]
+pub struct SigningAudit {
+    state: Option<Arc<Mutex<AuditState>>>,
+}
+
+impl SigningAudit {
+    /// A handle that records nothing.
+    pub fn disabled() -> Self {
+        Self { state: None }
+    }
+
+    /// An empty, enabled log timestamped by `clock`.
+    pub fn enabled(clock: Arc<dyn Clock>) -> Self {
+        Self::resume(clock, Vec::new()).expect("an empty chain is valid")
+    }
+
+    /// An enabled log continuing from previously stored `entries` (oldest
+    /// first). Fails if they do not form a valid chain.
+    pub fn resume(clock: Arc<dyn Clock>, entries: Vec<SigningAuditEntry>) -> Result<Self, String> {
+        verify_chain(&entries)?;
+        Ok(Self {
+            state: Some(Arc::new(Mutex::new(AuditState {
+                clock,
+                entries,
+                sink: None,
+            }))),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Persist future entries to `sink` as well as keeping them in memory.
+    pub fn set_sink(&self, sink: Arc<dyn SigningAuditSink>) {
+        if let Some(state) = &self.state {
+            lock(state).sink = Some(sink);
+        }
+    }
+
+    /// Stop persisting entries; they are still kept in memory.
+    pub fn clear_sink(&self) {
+        if let Some(state) = &self.state {
+            lock(state).sink = None;
+        }
+    }
+
+    /// Record that `payload` was signed for `account_index`. No-op when disabled.
+    /// Sink failures are logged; the in-memory chain is still extended.
+    pub fn record(&self, account_index: AccountIndex, purpose: SigningPurpose, payload: &[u8]) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let mut state = lock(state);
+        let prev_hash = state
+            .entries
+            .last()
+            .map(|e| e.entry_hash.clone())
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+        let mut entry = SigningAuditEntry {
+            seq: state.entries.len() as u64,
+            account_index,
+            purpose,
+            payload_hash: hex::encode(Sha256::digest(payload)),
+            // Stored timestamps keep microsecond precision; hash what survives a round-trip.
+            signed_at: state.clock.now().trunc_subsecs(6),
+            prev_hash,
+            entry_hash: String::new(),
+        };
+        entry.entry_hash = entry.compute_hash();
+        if let Some(sink) = &state.sink {
+            if let Err(e) = sink.append(&entry) {
+                error!("Failed to persist signing audit entry {}: {}", entry.seq, e);
+            }
+        }
+        state.entries.push(entry);
+    }
+
+    /// Entries signed at or after `since`, oldest first. Empty when disabled.
+    pub fn entries_since(&self, since: DateTime<Utc>) -> Vec<SigningAuditEntry> {
+        match &self.state {
+            Some(state) => lock(state)
+                .entries
+                .iter()
+                .filter(|e| e.signed_at >= since)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Verify the whole in-memory chain.
+    pub fn verify(&self) -> Result<(), String> {
+        match &self.state {
+            Some(state) => verify_chain(&lock(state).entries),
+            None => Ok(()),
+        }
+    }
+}
+
+fn lock(state: &Mutex<AuditState>) -> std::sync::MutexGuard<'_, AuditState> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::clock::ManualClock;
+
+    fn clock() -> Arc<dyn Clock> {
+        Arc::new(ManualClock::new(DateTime::<Utc>::UNIX_EPOCH))
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let audit = SigningAudit::enabled(clock());
+        audit.record(1, SigningPurpose::Query, b"query-1");
+        audit.record(1, SigningPurpose::Settle, b"settle-1");
+        audit.record(2, SigningPurpose::Cancel, b"cancel-2");
+        audit.verify().unwrap();
+
+        let entries = audit.entries_since(DateTime::<Utc>::UNIX_EPOCH);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].entry_hash);
+        assert_eq!(
+            entries[0].payload_hash,
+            hex::encode(Sha256::digest(b"query-1"))
+        );
+
+        let mut edited = entries.clone();
+        edited[1].account_index = 7;
+        assert!(verify_chain(&edited).is_err());
+
+        let mut dropped = entries.clone();
+        dropped.remove(1);
+        assert!(verify_chain(&dropped).is_err());
+
+        // A restored chain keeps linking new entries to the stored tail.
+        let resumed = SigningAudit::resume(clock(), entries.clone()).unwrap();
+        resumed.record(3, SigningPurpose::Query, b"query-3");
+        resumed.verify().unwrap();
+        assert!(SigningAudit::resume(clock(), edited).is_err());
+    }
+
+    #[test]
+    fn test_disabled_records_nothing() {
+        let audit = SigningAudit::disabled();
+        audit.record(1, SigningPurpose::Query, b"query");
+        assert!(!audit.is_enabled());
+        assert!(audit.entries_since(DateTime::<Utc>::UNIX_EPOCH).is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+//! Simulated execution for strategy tests and backtests.
+//!
+//! With [`ExecutionMode::Simulated`], an
+//! [`OrderWallet`](super::order_wallet::OrderWallet) fills trader orders
+//! against a local [`SimulatedExchange`] and never talks to the relayer or
+//! the chain. Simulated time is a [`ManualClock`] that also becomes the
+//! wallet's clock, so tests move it with
+//! [`OrderWallet::simulated_clock`](super::order_wallet::OrderWallet::simulated_clock)
+//! and nothing sleeps for real. Whenever the exchange is used it first
+//! catches up to the clock:
+//!
+//! - Filled positions are charged funding once for each epoch boundary
+//!   crossed since the last charge. The charge is `funding_rate` times the
+//!   position value. Longs pay a positive rate and shorts receive it.
+//! - Pending limit orders fill once the mark price reaches their entry price.
+//!   A limit order still pending at its deadline is cancelled.
+//! - Fill, settle and cancel times come from the clock.
+//!
+//! [`ExecutionMode::DryRun`] is for paper trading against the real relayer
+//! and chain instead. `funding_to_trading`, `open_trader_order` and
+//! `open_lend_order` run the same checks and build the same signed payloads
+//! as in live mode, proofs included, but broadcast nothing. They return a
+//! [`TxResult`](super::utils::TxResult) with `simulated` set or a request ID
+//! starting with [`DRY_RUN_PREFIX`], and apply the usual in-memory account
+//! transitions, marking every account touched as `simulated`. An account
+//! funded by a dry run has no chain UTXO, so orders on it are checked but no
+//! payload is built. `close_trader_order`, `cancel_trader_order` and
+//! `close_lend_order` unlock orders opened by a dry run; other operations
+//! that would broadcast fail. Simulated accounts, and the history of dry-run
+//! operations, are not written to the database.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use crate::compat::relayer_types::{OrderStatus, OrderType, PositionType};
+
+use super::clock::{Clock, ManualClock};
+use super::leverage::Leverage;
+use super::order_wallet::AccountIndex;
+
+/// How an `OrderWallet` executes orders.
+#[derive(Debug, Clone, Default)]
+pub enum ExecutionMode {
+    /// Orders go to the relayer.
+    #[default]
+    Live,
+    /// Orders fill against a local [`SimulatedExchange`].
+    Simulated(SimulationConfig),
+    /// Orders and mints are validated and built, but not broadcast.
+    DryRun,
+}
+
+/// Prefix of the request IDs returned in [`ExecutionMode::DryRun`].
+pub const DRY_RUN_PREFIX: &str = "dry-run-";
+
+/// Whether `request_id` was made up by a dry run.
+pub fn is_dry_run_id(request_id: &str) -> bool {
+    request_id.starts_with(DRY_RUN_PREFIX)
+}
+
+/// A fresh dry-run request ID.
+pub(crate) fn dry_run_request_id() -> String {
+    format!("{}{}", DRY_RUN_PREFIX, uuid::Uuid::new_v4())
+}
+
+/// Dry-run stand-in for the hash of the signed transaction `signed_tx`.
+pub(crate) fn dry_run_tx_hash(signed_tx: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!(
+        "{}{}",
+        DRY_RUN_PREFIX,
+        hex::encode_upper(Sha256::digest(signed_tx.as_bytes()))
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// Initial time for the simulated clock.
+    pub start: DateTime<Utc>,
+    /// Mark price (USD/BTC) until changed with [`SimulatedExchange::set_mark_price`].
+    pub mark_price: f64,
+    /// Funding rate charged per epoch, as a fraction of position value.
+    pub funding_rate: f64,
+    /// Funding epoch length; epochs are aligned to the Unix epoch.
+    pub funding_interval: TimeDelta,
+    /// How long a limit order may stay pending. `None`: until cancelled.
+    pub limit_order_ttl: Option<TimeDelta>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            start: Utc::now(),
+            mark_price: 50_000.0,
+            funding_rate: 0.0,
+            funding_interval: TimeDelta::hours(1),
+            limit_order_ttl: None,
+        }
+    }
+}
+
+/// A simulated trader order on one account.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatedOrder {
+    pub request_id: String,
+    pub order_type: OrderType,
+    pub position_type: PositionType,
+    pub order_status: OrderStatus,
+    pub entry_price: f64,
+    pub leverage: f64,
+    pub initial_margin: u64,
+    /// Initial margin times leverage.
+    pub position_value: f64,
+    pub created_at: DateTime<Utc>,
+    pub filled_at: Option<DateTime<Utc>>,
+    /// Deadline after which a pending limit order is cancelled.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Funding received (positive) or paid (negative), summed over epochs.
+    pub funding_applied: f64,
+    pub settlement_price: Option<f64>,
+    /// When the order was settled or cancelled.
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Index of the last funding epoch charged.
+    funding_epoch: i64,
+}
+
+/// Local stand-in for the relayer used by [`ExecutionMode::Simulated`].
+#[derive(Debug, Clone)]
+pub struct SimulatedExchange {
+    clock: ManualClock,
+    config: SimulationConfig,
+    mark_price: f64,
+    orders: HashMap<AccountIndex, SimulatedOrder>,
+    next_request: u64,
+}
+
+impl SimulatedExchange {
+    pub fn new(config: SimulationConfig) -> Self {
+        Self {
+            clock: ManualClock::new(config.start),
+            mark_price: config.mark_price,
+            config,
+            orders: HashMap::new(),
+            next_request: 1,
+        }
+    }
+
+    /// Clock driving the simulation. Clones share its time.
+    pub fn clock(&self) -> ManualClock {
+        self.clock.clone()
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    pub fn mark_price(&self) -> f64 {
+        self.mark_price
+    }
+
+    /// Change the mark price. Pending limits are checked against it on the next catch-up.
+    pub fn set_mark_price(&mut self, price: f64) {
+        self.catch_up();
+        self.mark_price = price;
+    }
+
+    /// Place a trader order for `index` with `initial_margin` sats.
+    pub fn open_trader_order(
+        &mut self,
+        index: AccountIndex,
+        order_type: OrderType,
+        position_type: PositionType,
+        entry_price: u64,
+        leverage: Leverage,
+        initial_margin: u64,
+    ) -> Result<String, String> {
+        self.catch_up();
+        let has_open = self.orders.get(&index).is_some_and(|order| {
+            matches!(
+                order.order_status,
+                OrderStatus::PENDING | OrderStatus::FILLED
+            )
+        });
+        if has_open {
+            return Err(format!(
+                "Account {} already has an open simulated order",
+                index
+            ));
+        }
+        if !matches!(order_type, OrderType::MARKET | OrderType::LIMIT) {
+            return Err(format!(
+                "Unsupported simulated order type: {:?}",
+                order_type
+            ));
+        }
+        let now = self.clock.now();
+        let request_id = format!("sim-{}", self.next_request);
+        self.next_request += 1;
+        let leverage = leverage.as_f64();
+        let mut order = SimulatedOrder {
+            request_id: request_id.clone(),
+            order_type: order_type.clone(),
+            position_type,
+            order_status: OrderStatus::PENDING,
+            entry_price: entry_price as f64,
+            leverage,
+            initial_margin,
+            position_value: initial_margin as f64 * leverage,
+            created_at: now,
+            filled_at: None,
+            expires_at: None,
+            funding_applied: 0.0,
+            settlement_price: None,
+            closed_at: None,
+            funding_epoch: 0,
+        };
+        if matches!(order_type, OrderType::MARKET) {
+            order.entry_price = self.mark_price;
+            self.fill(&mut order, now);
+        } else {
+            order.expires_at = self.config.limit_order_ttl.map(|ttl| now + ttl);
+        }
+        self.orders.insert(index, order);
+        Ok(request_id)
+    }
+
+    /// Settle the filled order on `index` at the mark price.
+    pub fn settle_trader_order(&mut self, index: AccountIndex) -> Result<SimulatedOrder, String> {
+        self.catch_up();
+        let now = self.clock.now();
+        let mark_price = self.mark_price;
+        let order = self.open_order_mut(index)?;
+        if order.order_status != OrderStatus::FILLED {
+            return Err(format!(
+                "Simulated order on account {} is not filled",
+                index
+            ));
+        }
+        order.order_status = OrderStatus::SETTLED;
+        order.settlement_price = Some(mark_price);
+        order.closed_at = Some(now);
+        Ok(order.clone())
+    }
+
+    /// Cancel the pending order on `index`.
+    pub fn cancel_trader_order(&mut self, index: AccountIndex) -> Result<SimulatedOrder, String> {
+        self.catch_up();
+        let now = self.clock.now();
+        let order = self.open_order_mut(index)?;
+        if order.order_status != OrderStatus::PENDING {
+            return Err(format!(
+                "Simulated order on account {} is not pending",
+                index
+            ));
+        }
+        order.order_status = OrderStatus::CANCELLED;
+        order.closed_at = Some(now);
+        Ok(order.clone())
+    }
+
+    /// The order on `index`, brought up to date with the clock.
+    pub fn trader_order(&mut self, index: AccountIndex) -> Option<SimulatedOrder> {
+        self.catch_up();
+        self.orders.get(&index).cloned()
+    }
+
+    /// Apply funding, limit fills and expiries up to the current clock time.
+    pub fn catch_up(&mut self) {
+        let now = self.clock.now();
+        let epoch = self.epoch_of(now);
+        let mark_price = self.mark_price;
+        let funding_rate = self.config.funding_rate;
+        for order in self.orders.values_mut() {
+            match order.order_status {
+                OrderStatus::FILLED => {
+                    let crossed = epoch - order.funding_epoch;
+                    if crossed > 0 {
+                        let per_epoch = funding_rate * order.position_value;
+                        let sign = match order.position_type {
+                            PositionType::LONG => -1.0,
+                            PositionType::SHORT => 1.0,
+                        };
+                        order.funding_applied += sign * per_epoch * crossed as f64;
+                        order.funding_epoch = epoch;
+                    }
+                }
+                OrderStatus::PENDING => {
+                    let reached = match order.position_type {
+                        PositionType::LONG => mark_price <= order.entry_price,
+                        PositionType::SHORT => mark_price >= order.entry_price,
+                    };
+                    if reached {
+                        order.order_status = OrderStatus::FILLED;
+                        order.filled_at = Some(now);
+                        order.funding_epoch = epoch;
+                    } else if order.expires_at.is_some_and(|deadline| now >= deadline) {
+                        order.order_status = OrderStatus::CANCELLED;
+                        order.closed_at = order.expires_at;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn fill(&self, order: &mut SimulatedOrder, now: DateTime<Utc>) {
+        order.order_status = OrderStatus::FILLED;
+        order.filled_at = Some(now);
+        order.funding_epoch = self.epoch_of(now);
+    }
+
+    fn epoch_of(&self, time: DateTime<Utc>) -> i64 {
+        let interval = self.config.funding_interval.num_seconds().max(1);
+        time.timestamp().div_euclid(interval)
+    }
+
+    fn open_order_mut(&mut self, index: AccountIndex) -> Result<&mut SimulatedOrder, String> {
+        self.orders
+            .get_mut(&index)
+            .ok_or_else(|| format!("No simulated order on account {}", index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SimulationConfig {
+        SimulationConfig {
+            start: "2025-01-01T00:30:00Z".parse().unwrap(),
+            funding_rate: 0.001,
+            ..SimulationConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_limit_order_fills_when_mark_reaches_entry() {
+        let mut exchange = SimulatedExchange::new(config());
+        exchange
+            .open_trader_order(
+                1,
+                OrderType::LIMIT,
+                PositionType::LONG,
+                49_000,
+                Leverage::from(2),
+                1_000,
+            )
+            .unwrap();
+        exchange
+            .clock()
+            .advance(std::time::Duration::from_secs(600));
+        exchange.set_mark_price(48_900.0);
+        let order = exchange.trader_order(1).unwrap();
+        assert_eq!(order.order_status, OrderStatus::FILLED);
+        assert_eq!(order.filled_at, Some(exchange.clock().now()));
+
+        let settled = exchange.settle_trader_order(1).unwrap();
+        assert_eq!(settled.settlement_price, Some(48_900.0));
+        assert!(exchange.cancel_trader_order(1).is_err());
+    }
+
+    #[test]
+    fn test_short_receives_positive_funding() {
+        let mut exchange = SimulatedExchange::new(config());
+        exchange
+            .open_trader_order(
+                1,
+                OrderType::MARKET,
+                PositionType::SHORT,
+                0,
+                Leverage::from(5),
+                2_000,
+            )
+            .unwrap();
+        // 00:30 -> 01:59 crosses one boundary.
+        exchange
+            .clock()
+            .advance(std::time::Duration::from_secs(89 * 60));
+        let order = exchange.trader_order(1).unwrap();
+        assert!((order.funding_applied - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,529 @@
+//! Account statements: every movement of a wallet's funds over a period.
+//!
+//! `OrderWallet::export_statement` gathers the wallet's transfer history,
+//! order history and [`TradeHistoryEntry`]s into [`StatementRow`]s, oldest
+//! first, and writes them with [`write_statement`] as CSV ([`CSV_COLUMNS`],
+//! in that order) or as one JSON object per line. Every row carries a
+//! `reference` an auditor can look up: the tx hash when there is one,
+//! otherwise the relayer request ID, otherwise the transfer history row
+//! (`transfer_history:<id>`) for movements the chain reports no hash for,
+//! such as faucet receipts. Fee and fee refund rows carry the hash of the
+//! mint or burn they were charged on, and order rows the hash order history
+//! logged for the request, e.g. of a settled close.
+//!
+//! All amounts are in sats. [`StatementSummary`] totals each category and
+//! nets realized trading P&L and lend interest against chain fees.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::str::FromStr;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::transaction_history::{OrderRecordKind, TradeHistoryEntry};
+
+/// Output format of `OrderWallet::export_statement`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementFormat {
+    /// Header line of [`CSV_COLUMNS`], then one line per row.
+    Csv,
+    /// One [`StatementRow`] JSON object per line, no header.
+    JsonLines,
+}
+
+impl FromStr for StatementFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(StatementFormat::Csv),
+            "jsonl" | "json_lines" | "json-lines" => Ok(StatementFormat::JsonLines),
+            other => Err(format!(
+                "unknown statement format '{}' (expected csv or jsonl)",
+                other
+            )),
+        }
+    }
+}
+
+/// What a statement row records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementCategory {
+    /// Sats received from the testnet faucet.
+    Faucet,
+    /// Mint from the funding wallet into a trading account.
+    Funding,
+    /// Burn from a trading account back to the funding wallet.
+    Withdrawal,
+    /// Move between trading accounts, or to another wallet's address.
+    Transfer,
+    /// Sats the chain kept from a mint or burn.
+    Fee,
+    /// Sats the chain credited beyond a mint or burn.
+    FeeRefund,
+    /// BTC withdrawal request from the funding wallet.
+    BtcWithdrawal,
+    TradeOpen,
+    /// Trader close, with the realized P&L once settled.
+    TradeClose,
+    /// Settlement of part of a trader position.
+    TradePartialClose,
+    TradeCancel,
+    LendOpen,
+    /// Lend withdrawal, with the interest earned once settled.
+    LendClose,
+}
+
+impl StatementCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatementCategory::Faucet => "faucet",
+            StatementCategory::Funding => "funding",
+            StatementCategory::Withdrawal => "withdrawal",
+            StatementCategory::Transfer => "transfer",
+            StatementCategory::Fee => "fee",
+            StatementCategory::FeeRefund => "fee_refund",
+            StatementCategory::BtcWithdrawal => "btc_withdrawal",
+            StatementCategory::TradeOpen => "trade_open",
+            StatementCategory::TradeClose => "trade_close",
+            StatementCategory::TradePartialClose => "trade_partial_close",
+            StatementCategory::TradeCancel => "trade_cancel",
+            StatementCategory::LendOpen => "lend_open",
+            StatementCategory::LendClose => "lend_close",
+        }
+    }
+
+    /// Category of a transfer history `direction`; `None` for one this
+    /// wallet does not write.
+    pub fn of_transfer(direction: &str) -> Option<Self> {
+        match direction {
+            "faucet" => Some(StatementCategory::Faucet),
+            "fund_to_trade" => Some(StatementCategory::Funding),
+            "trade_to_fund" => Some(StatementCategory::Withdrawal),
+            "trade_to_trade" | "trade_to_address" => Some(StatementCategory::Transfer),
+            "fee" => Some(StatementCategory::Fee),
+            "fee_refund" => Some(StatementCategory::FeeRefund),
+            "btc_withdrawal" => Some(StatementCategory::BtcWithdrawal),
+            _ => None,
+        }
+    }
+
+    pub fn of_order(kind: OrderRecordKind) -> Self {
+        match kind {
+            OrderRecordKind::TraderOpen => StatementCategory::TradeOpen,
+            OrderRecordKind::TraderClose => StatementCategory::TradeClose,
+            OrderRecordKind::TraderPartialClose => StatementCategory::TradePartialClose,
+            OrderRecordKind::TraderCancel => StatementCategory::TradeCancel,
+            OrderRecordKind::LendOpen => StatementCategory::LendOpen,
+            OrderRecordKind::LendClose => StatementCategory::LendClose,
+        }
+    }
+}
+
+/// Columns of a CSV statement, in order. New columns are only ever appended.
+pub const CSV_COLUMNS: [&str; 12] = [
+    "timestamp",
+    "category",
+    "detail",
+    "account_index",
+    "counterparty_index",
+    "amount",
+    "realized_pnl",
+    "fees",
+    "status",
+    "tx_hash",
+    "request_id",
+    "reference",
+];
+
+/// What order history logged for one request ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoggedRequest {
+    /// Amount of the first row logged for the request.
+    pub amount: Option<u64>,
+    /// Tx hash of the latest row that has one.
+    pub tx_hash: Option<String>,
+}
+
+/// One movement of funds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatementRow {
+    pub timestamp: DateTime<Utc>,
+    pub category: StatementCategory,
+    /// Transfer history direction or order record kind the row came from.
+    pub detail: String,
+    /// Account the funds left, or the account of the order.
+    pub account_index: Option<u64>,
+    /// Account the funds went to, for moves between accounts.
+    pub counterparty_index: Option<u64>,
+    /// Sats moved; the margin or deposit of an order. `None` when unknown.
+    pub amount: Option<u64>,
+    /// Settled margin minus initial margin of a settled close.
+    pub realized_pnl: Option<f64>,
+    /// Fill and settlement fees reported by the relayer.
+    pub fees: Option<f64>,
+    /// Last order status seen, for order rows.
+    pub status: Option<String>,
+    pub tx_hash: Option<String>,
+    pub request_id: Option<String>,
+    /// `tx_hash`, else `request_id`, else `transfer_history:<id>`.
+    pub reference: String,
+}
+
+impl StatementRow {
+    /// Row of a transfer history entry; `None` for a direction outside
+    /// [`StatementCategory::of_transfer`]. `id` is the history row's ID.
+    pub fn of_transfer(
+        id: Option<i32>,
+        direction: &str,
+        from_index: Option<u64>,
+        to_index: Option<u64>,
+        amount: u64,
+        tx_hash: Option<String>,
+        timestamp: DateTime<Utc>,
+    ) -> Option<Self> {
+        let category = StatementCategory::of_transfer(direction)?;
+        // Mints and refunds only have a receiving account.
+        let (account_index, counterparty_index) = match from_index {
+            Some(from) => (Some(from), to_index),
+            None => (to_index, None),
+        };
+        let reference = match (&tx_hash, id) {
+            (Some(hash), _) => hash.clone(),
+            (None, Some(id)) => format!("transfer_history:{}", id),
+            (None, None) => format!("transfer_history:{}", direction),
+        };
+        Some(Self {
+            timestamp,
+            category,
+            detail: direction.to_string(),
+            account_index,
+            counterparty_index,
+            amount: Some(amount),
+            realized_pnl: None,
+            fees: None,
+            status: None,
+            tx_hash,
+            request_id: None,
+            reference,
+        })
+    }
+
+    /// Row of an order request, with what order history `logged` for it; a
+    /// snapshot's margins take precedence over the logged amount.
+    pub fn of_trade(entry: &TradeHistoryEntry, logged: Option<&LoggedRequest>) -> Self {
+        let snapshot = entry.snapshot.as_ref();
+        let margin = snapshot.map(|s| s.settled_margin.unwrap_or(s.initial_margin) as u64);
+        let amount = logged.and_then(|logged| logged.amount);
+        let tx_hash = logged.and_then(|logged| logged.tx_hash.clone());
+        Self {
+            timestamp: entry.record.recorded_at,
+            category: StatementCategory::of_order(entry.record.kind),
+            detail: entry.record.kind.as_str().to_string(),
+            account_index: Some(entry.account_index),
+            counterparty_index: None,
+            amount: margin.or(amount),
+            realized_pnl: snapshot.and_then(|s| s.realized_pnl),
+            fees: snapshot.and_then(|s| s.fees),
+            status: Some(entry.record.status.clone()),
+            reference: tx_hash
+                .clone()
+                .unwrap_or_else(|| entry.record.request_id.clone()),
+            tx_hash,
+            request_id: Some(entry.record.request_id.clone()),
+        }
+    }
+
+    fn csv_fields(&self) -> [String; 12] {
+        fn opt<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(T::to_string).unwrap_or_default()
+        }
+        [
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.category.as_str().to_string(),
+            self.detail.clone(),
+            opt(&self.account_index),
+            opt(&self.counterparty_index),
+            opt(&self.amount),
+            opt(&self.realized_pnl),
+            opt(&self.fees),
+            opt(&self.status),
+            opt(&self.tx_hash),
+            opt(&self.request_id),
+            self.reference.clone(),
+        ]
+    }
+}
+
+/// Count and summed `amount` of the rows of one category.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryTotal {
+    pub rows: usize,
+    pub amount: u64,
+}
+
+/// Totals of a statement.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatementSummary {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub rows: usize,
+    pub by_category: BTreeMap<StatementCategory, CategoryTotal>,
+    /// Realized P&L of settled trader closes; already net of relayer fees.
+    pub trading_pnl: f64,
+    /// Realized P&L of settled lend closes.
+    pub lend_interest: f64,
+    /// Relayer fill and settlement fees, for reference.
+    pub relayer_fees: f64,
+    /// `fee` minus `fee_refund` sats.
+    pub chain_fees: i64,
+    /// `trading_pnl + lend_interest - chain_fees`.
+    pub net_pnl: f64,
+}
+
+impl StatementSummary {
+    pub fn of_rows(rows: &[StatementRow], from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        let mut by_category: BTreeMap<StatementCategory, CategoryTotal> = BTreeMap::new();
+        let (mut trading_pnl, mut lend_interest, mut relayer_fees) = (0.0, 0.0, 0.0);
+        for row in rows {
+            let total = by_category.entry(row.category).or_default();
+            total.rows += 1;
+            total.amount += row.amount.unwrap_or(0);
+            match row.category {
+                StatementCategory::TradeClose
+                | StatementCategory::TradePartialClose
+                | StatementCategory::TradeCancel => trading_pnl += row.realized_pnl.unwrap_or(0.0),
+                StatementCategory::LendClose => lend_interest += row.realized_pnl.unwrap_or(0.0),
+                _ => {}
+            }
+            relayer_fees += row.fees.unwrap_or(0.0);
+        }
+        let amount_of = |category| by_category.get(&category).map_or(0, |t| t.amount) as i64;
+        let chain_fees =
+            amount_of(StatementCategory::Fee) - amount_of(StatementCategory::FeeRefund);
+        Self {
+            from,
+            to,
+            rows: rows.len(),
+            by_category,
+            trading_pnl,
+            lend_interest,
+            relayer_fees,
+            chain_fees,
+            net_pnl: trading_pnl + lend_interest - chain_fees as f64,
+        }
+    }
+}
+
+/// Rows of `transfers` and `trades` timestamped in `[from, to)`, oldest
+/// first. `logged` maps request IDs to what order history logged for them.
+pub fn statement_rows(
+    transfers: Vec<StatementRow>,
+    trades: &[TradeHistoryEntry],
+    logged: &HashMap<String, LoggedRequest>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<StatementRow> {
+    let mut rows: Vec<StatementRow> = transfers
+        .into_iter()
+        .chain(
+            trades
+                .iter()
+                .map(|entry| StatementRow::of_trade(entry, logged.get(&entry.record.request_id))),
+        )
+        .filter(|row| row.timestamp >= from && row.timestamp < to)
+        .collect();
+    rows.sort_by(|a, b| (a.timestamp, a.account_index).cmp(&(b.timestamp, b.account_index)));
+    rows
+}
+
+/// Write `rows` to `writer` in `format`.
+pub fn write_statement(
+    rows: &[StatementRow],
+    format: StatementFormat,
+    mut writer: impl Write,
+) -> Result<(), String> {
+    let io_err = |e: std::io::Error| format!("Failed to write statement: {}", e);
+    match format {
+        StatementFormat::Csv => {
+            writeln!(writer, "{}", CSV_COLUMNS.join(",")).map_err(io_err)?;
+            for row in rows {
+                let line: Vec<String> = row.csv_fields().iter().map(|f| csv_field(f)).collect();
+                writeln!(writer, "{}", line.join(",")).map_err(io_err)?;
+            }
+        }
+        StatementFormat::JsonLines => {
+            for row in rows {
+                let line = serde_json::to_string(row)
+                    .map_err(|e| format!("Failed to serialize statement row: {}", e))?;
+                writeln!(writer, "{}", line).map_err(io_err)?;
+            }
+        }
+    }
+    writer.flush().map_err(io_err)
+}
+
+/// `field` quoted if it holds a comma, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::transaction_history::{OrderRecord, OrderSnapshot};
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn transfer(
+        id: i32,
+        direction: &str,
+        amount: u64,
+        tx_hash: Option<&str>,
+        secs: i64,
+    ) -> StatementRow {
+        StatementRow::of_transfer(
+            Some(id),
+            direction,
+            None,
+            Some(1),
+            amount,
+            tx_hash.map(str::to_string),
+            at(secs),
+        )
+        .unwrap()
+    }
+
+    fn trade(
+        kind: OrderRecordKind,
+        request_id: &str,
+        secs: i64,
+        pnl: Option<f64>,
+    ) -> TradeHistoryEntry {
+        let record = OrderRecord::new(request_id.to_string(), kind, at(secs));
+        let snapshot = pnl.map(|pnl| OrderSnapshot {
+            account_index: 1,
+            request_id: request_id.to_string(),
+            kind,
+            order_status: "SETTLED".to_string(),
+            position_type: None,
+            entry_price: None,
+            exit_price: None,
+            leverage: None,
+            initial_margin: 1_000.0,
+            settled_margin: Some(1_000.0 + pnl),
+            realized_pnl: Some(pnl),
+            fees: Some(2.0),
+            opened_at: None,
+            recorded_at: at(secs),
+        });
+        TradeHistoryEntry {
+            account_index: 1,
+            record,
+            snapshot,
+        }
+    }
+
+    #[test]
+    fn test_rows_and_summary() {
+        let transfers = vec![
+            transfer(1, "faucet", 50_000, None, 0),
+            transfer(2, "fee", 10, Some("HASH"), 1),
+            transfer(3, "fund_to_trade", 9_990, Some("HASH"), 1),
+            transfer(4, "trade_to_fund", 9_000, Some("BURN"), 500),
+        ];
+        let trades = vec![
+            trade(OrderRecordKind::TraderOpen, "REQ1", 10, None),
+            trade(OrderRecordKind::TraderClose, "REQ2", 20, Some(150.0)),
+            trade(OrderRecordKind::LendClose, "REQ3", 30, Some(7.5)),
+        ];
+        let logged = HashMap::from([
+            (
+                "REQ1".to_string(),
+                LoggedRequest {
+                    amount: Some(1_000),
+                    tx_hash: None,
+                },
+            ),
+            (
+                "REQ2".to_string(),
+                LoggedRequest {
+                    amount: Some(1_000),
+                    tx_hash: Some("SETTLE".to_string()),
+                },
+            ),
+        ]);
+        let rows = statement_rows(transfers, &trades, &logged, at(0), at(100));
+        assert!(StatementRow::of_transfer(None, "unknown", None, None, 1, None, at(0)).is_none());
+
+        // The burn at 500s falls outside the period.
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0].category, StatementCategory::Faucet);
+        assert_eq!(rows[0].reference, "transfer_history:1");
+        // The fee was charged on the mint of the same tx.
+        assert_eq!(rows[1].reference, "HASH");
+        assert_eq!(rows[2].reference, "HASH");
+        assert_eq!(rows[3].amount, Some(1_000));
+        assert_eq!(rows[3].reference, "REQ1");
+        assert_eq!(rows[4].tx_hash.as_deref(), Some("SETTLE"));
+        assert_eq!(rows[4].reference, "SETTLE");
+        assert_eq!(rows[4].request_id.as_deref(), Some("REQ2"));
+        assert!(rows.iter().all(|r| r.tx_hash.is_some()
+            || r.request_id.is_some()
+            || r.reference.starts_with("transfer_history:")));
+
+        let summary = StatementSummary::of_rows(&rows, at(0), at(100));
+        assert_eq!(summary.rows, 6);
+        assert_eq!(
+            summary.by_category[&StatementCategory::Funding],
+            CategoryTotal {
+                rows: 1,
+                amount: 9_990
+            }
+        );
+        assert_eq!(summary.trading_pnl, 150.0);
+        assert_eq!(summary.lend_interest, 7.5);
+        assert_eq!(summary.chain_fees, 10);
+        assert_eq!(summary.relayer_fees, 4.0);
+        assert_eq!(summary.net_pnl, 147.5);
+    }
+
+    #[test]
+    fn test_write_csv_and_json_lines() {
+        let mut row = transfer(1, "trade_to_trade", 500, Some("a,\"b\""), 0);
+        row.counterparty_index = Some(2);
+        let rows = vec![row];
+
+        let mut csv = Vec::new();
+        write_statement(&rows, StatementFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_COLUMNS.join(","));
+        assert_eq!(
+            lines[1],
+            "2023-11-14T22:13:20.000Z,transfer,trade_to_trade,1,2,500,,,,\"a,\"\"b\"\"\",,\"a,\"\"b\"\"\""
+        );
+
+        let mut jsonl = Vec::new();
+        write_statement(&rows, StatementFormat::JsonLines, &mut jsonl).unwrap();
+        let parsed: StatementRow =
+            serde_json::from_str(String::from_utf8(jsonl).unwrap().trim_end()).unwrap();
+        assert_eq!(parsed, rows[0]);
+
+        assert_eq!(
+            "jsonl".parse::<StatementFormat>(),
+            Ok(StatementFormat::JsonLines)
+        );
+        assert!("xml".parse::<StatementFormat>().is_err());
+    }
+}
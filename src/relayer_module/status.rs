@@ -0,0 +1,608 @@
+//! Human-readable wallet status for terminals and logs.
+//!
+//! [`render`] turns a [`StatusSnapshot`] into a multi-section report
+//! (balances, accounts, open orders, recent settlements, endpoints) and
+//! [`render_compact`] into a single line for periodic logging. Both only read
+//! the snapshot; build a live one with
+//! [`OrderWallet::status_snapshot`](super::order_wallet::OrderWallet::status_snapshot).
+//!
+//! Output uses fixed column widths and no terminal control codes, so it reads
+//! the same in a terminal, a log file or a test assertion. Account glyphs:
+//!
+//! | Glyph | Account |
+//! |-------|---------|
+//! | `●` | Coin, on chain |
+//! | `○` | Coin, not on chain |
+//! | `◆` | Memo (open trader or lend order) |
+//! | `◇` | State |
+//! | `✗` | last operation failed |
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::compat::zkvm::IOType;
+
+use super::order_wallet::AccountIndex;
+use super::portfolio::{AccountBalanceInfo, Portfolio};
+use super::relayer_types::OrderStatus;
+use super::transaction_history::OrderHistoryEntry;
+
+const RULE_WIDTH: usize = 72;
+/// Longest error or endpoint detail shown before it is cut with `…`.
+const MAX_DETAIL_LEN: usize = 40;
+
+/// Everything [`render`] and [`render_compact`] show, captured at one point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusSnapshot {
+    pub generated_at: DateTime<Utc>,
+    pub chain_id: String,
+    pub twilight_address: String,
+    pub btc_usd_price: Option<f64>,
+    pub portfolio: Portfolio,
+    pub accounts: Vec<AccountBalanceInfo>,
+    /// Last recorded operation per account; open-order age is measured from it.
+    pub last_activity: BTreeMap<AccountIndex, DateTime<Utc>>,
+    /// Most recent closes, newest first. Empty without DB persistence.
+    pub recent_settlements: Vec<OrderHistoryEntry>,
+    pub endpoints: Vec<EndpointStatus>,
+}
+
+/// Outcome of one request to a remote endpoint while taking the snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EndpointStatus {
+    pub name: String,
+    pub url: String,
+    pub healthy: bool,
+    pub latency_ms: Option<u64>,
+    pub detail: Option<String>,
+}
+
+impl EndpointStatus {
+    pub fn from_probe<E: std::fmt::Display>(
+        name: &str,
+        url: &str,
+        elapsed: std::time::Duration,
+        result: Result<(), E>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            healthy: result.is_ok(),
+            latency_ms: Some(elapsed.as_millis() as u64),
+            detail: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Multi-line report, one section per heading, ending with a newline.
+pub fn render(snapshot: &StatusSnapshot) -> String {
+    let portfolio = &snapshot.portfolio;
+    let mut lines = vec![
+        format!(
+            "Wallet status  {}  {}",
+            snapshot.generated_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            snapshot.chain_id
+        ),
+        "=".repeat(RULE_WIDTH),
+        "Balances".to_string(),
+    ];
+    let mut field = |label: &str, value: String| lines.push(format!("  {:<16}{}", label, value));
+    field("Address", snapshot.twilight_address.clone());
+    field(
+        "On-chain",
+        format!("{} sats", portfolio.wallet_balance_sats),
+    );
+    field(
+        "Trading",
+        format!("{} sats", portfolio.total_trading_balance),
+    );
+    field(
+        "Margin used",
+        format!("{:.0} sats", portfolio.total_margin_used),
+    );
+    field(
+        "Unrealized PnL",
+        format!("{:+.2} sats", portfolio.unrealized_pnl),
+    );
+    field(
+        "Lend value",
+        format!("{:.0} sats", portfolio.total_lend_value),
+    );
+    field(
+        "BTC/USD",
+        snapshot
+            .btc_usd_price
+            .map(|p| format!("{:.2}", p))
+            .unwrap_or_else(|| "-".to_string()),
+    );
+
+    lines.push(String::new());
+    lines.push(format!("Accounts ({})", snapshot.accounts.len()));
+    if snapshot.accounts.is_empty() {
+        lines.push("  (none)".to_string());
+    } else {
+        lines.push(format!(
+            "    {:>4}  {:<6} {:>12}",
+            "IDX", "STATE", "BALANCE"
+        ));
+        for account in &snapshot.accounts {
+            let mut line = format!(
+                "  {} {:>4}  {:<6} {:>12}",
+                account_glyph(account),
+                account.account_index,
+                account_state(&account.io_type),
+                account.balance
+            );
+            if account.balance_unverified {
+                line.push_str("  unverified");
+            }
+            if let Some(error) = &account.last_error {
+                line.push_str(&format!(
+                    "  {}: {}",
+                    error.operation,
+                    truncate(&error.message)
+                ));
+            }
+            lines.push(line);
+        }
+    }
+
+    let open = portfolio.trader_positions.len() + portfolio.lend_positions.len();
+    lines.push(String::new());
+    lines.push(format!("Open orders ({})", open));
+    if open == 0 {
+        lines.push("  (none)".to_string());
+    } else {
+        lines.push(format!(
+            "  {:>4}  {:<5}  {:<9} {:>5} {:>10} {:>10} {:>10} {:>8}",
+            "IDX", "SIDE", "STATUS", "LEV", "ENTRY", "MARGIN", "PnL", "AGE"
+        ));
+        for p in &portfolio.trader_positions {
+            let pnl = if p.order_status == OrderStatus::PENDING {
+                "-".to_string()
+            } else {
+                format!("{:+.2}", p.unrealized_pnl)
+            };
+            lines.push(format!(
+                "  {:>4}  {:<5}  {:<9} {:>5} {:>10.2} {:>10.0} {:>10} {:>8}",
+                p.account_index,
+                format!("{:?}", p.position_type),
+                p.order_status.to_str(),
+                format!("{}x", p.leverage),
+                p.entry_price,
+                p.initial_margin,
+                pnl,
+                age(snapshot, p.account_index)
+            ));
+        }
+        for l in &portfolio.lend_positions {
+            lines.push(format!(
+                "  {:>4}  {:<5}  {:<9} {:>5} {:>10} {:>10.0} {:>10} {:>8}",
+                l.account_index,
+                "LEND",
+                l.order_status.to_str(),
+                "-",
+                "-",
+                l.deposit,
+                format!("{:+.2}", l.pnl),
+                age(snapshot, l.account_index)
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(format!(
+        "Recent settlements ({})",
+        snapshot.recent_settlements.len()
+    ));
+    if snapshot.recent_settlements.is_empty() {
+        lines.push("  (none)".to_string());
+    }
+    for s in &snapshot.recent_settlements {
+        lines.push(format!(
+            "  {:<19}  {:>4}  {:<8} {:<6} {:<5} {:>10}",
+            s.created_at.get(..19).unwrap_or(&s.created_at),
+            s.account_index,
+            s.action,
+            s.order_type,
+            s.position_type.as_deref().unwrap_or("-"),
+            s.pnl
+                .map(|pnl| format!("{:+.2}", pnl))
+                .unwrap_or_else(|| "-".to_string())
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Endpoints".to_string());
+    if snapshot.endpoints.is_empty() {
+        lines.push("  (none)".to_string());
+    }
+    for e in &snapshot.endpoints {
+        let mut line = format!(
+            "  {} {:<8} {:>8}  {}",
+            if e.healthy { "✓" } else { "✗" },
+            e.name,
+            e.latency_ms
+                .map(|ms| format!("{} ms", ms))
+                .unwrap_or_else(|| "-".to_string()),
+            e.url
+        );
+        if let Some(detail) = &e.detail {
+            line.push_str(&format!("  ({})", truncate(detail)));
+        }
+        lines.push(line);
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// One line for periodic logging, without a trailing newline.
+pub fn render_compact(snapshot: &StatusSnapshot) -> String {
+    let portfolio = &snapshot.portfolio;
+    let errors = snapshot
+        .accounts
+        .iter()
+        .filter(|a| a.last_error.is_some())
+        .count();
+    let endpoints = if snapshot.endpoints.is_empty() {
+        "no endpoints".to_string()
+    } else {
+        snapshot
+            .endpoints
+            .iter()
+            .map(|e| format!("{} {}", e.name, if e.healthy { "ok" } else { "down" }))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "{} | on-chain {} sats | trading {} sats | margin {:.0} sats | uPnL {:+.2} | orders {} | accounts {} ({} err) | BTC/USD {} | {}",
+        snapshot.chain_id,
+        portfolio.wallet_balance_sats,
+        portfolio.total_trading_balance,
+        portfolio.total_margin_used,
+        portfolio.unrealized_pnl,
+        portfolio.trader_positions.len() + portfolio.lend_positions.len(),
+        snapshot.accounts.len(),
+        errors,
+        snapshot
+            .btc_usd_price
+            .map(|p| format!("{:.2}", p))
+            .unwrap_or_else(|| "-".to_string()),
+        endpoints
+    )
+}
+
+fn account_glyph(account: &AccountBalanceInfo) -> char {
+    if account.last_error.is_some() {
+        return '✗';
+    }
+    match account.io_type {
+        IOType::Coin if account.on_chain => '●',
+        IOType::Coin => '○',
+        IOType::Memo => '◆',
+        _ => '◇',
+    }
+}
+
+fn account_state(io_type: &IOType) -> &'static str {
+    match io_type {
+        IOType::Coin => "coin",
+        IOType::Memo => "order",
+        _ => "state",
+    }
+}
+
+fn age(snapshot: &StatusSnapshot, index: AccountIndex) -> String {
+    snapshot
+        .last_activity
+        .get(&index)
+        .map(|since| format_age(snapshot.generated_at - *since))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// `45s`, `12m`, `3h 05m` or `2d 04h`.
+fn format_age(age: chrono::Duration) -> String {
+    let secs = age.num_seconds().max(0);
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {:02}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_DETAIL_LEN {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(MAX_DETAIL_LEN - 1).collect();
+        format!("{}…", cut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::portfolio::{LendPositionSummary, PositionSummary};
+    use crate::relayer_module::relayer_types::PositionType;
+    use crate::relayer_module::test_fixtures::{LendOrderBuilder, TraderOrderBuilder};
+    use crate::zkos_accounts::zkaccount::StoredError;
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn account(
+        index: AccountIndex,
+        io_type: IOType,
+        on_chain: bool,
+        balance: u64,
+    ) -> AccountBalanceInfo {
+        AccountBalanceInfo {
+            account_index: index,
+            balance,
+            io_type,
+            on_chain,
+            last_error: None,
+            balance_unverified: false,
+            archived: false,
+            label: None,
+        }
+    }
+
+    fn settlement(
+        index: AccountIndex,
+        order_type: &str,
+        position_type: Option<&str>,
+        pnl: f64,
+        created_at: &str,
+    ) -> OrderHistoryEntry {
+        OrderHistoryEntry {
+            account_index: index,
+            request_id: format!("REQ{}", index),
+            action: "close".to_string(),
+            order_type: order_type.to_string(),
+            position_type: position_type.map(str::to_string),
+            amount: 8_000,
+            price: Some(51_000.0),
+            leverage: None,
+            pnl: Some(pnl),
+            status: "SETTLED".to_string(),
+            tx_hash: None,
+            created_at: created_at.to_string(),
+        }
+    }
+
+    fn empty_snapshot() -> StatusSnapshot {
+        StatusSnapshot {
+            generated_at: at("2025-01-01T12:00:00Z"),
+            chain_id: "nyks".to_string(),
+            twilight_address: "twilight1statusfixture".to_string(),
+            btc_usd_price: None,
+            portfolio: Portfolio::build(0, 0, vec![], vec![], vec![], vec![], vec![], 0, 0),
+            accounts: vec![],
+            last_activity: BTreeMap::new(),
+            recent_settlements: vec![],
+            endpoints: vec![],
+        }
+    }
+
+    fn populated_snapshot() -> StatusSnapshot {
+        let now = at("2025-01-01T12:00:00Z");
+        let mut long = PositionSummary::from_trader_order(
+            3,
+            &TraderOrderBuilder::new()
+                .position(10_000.0, 5.0, 40_000.0)
+                .build(),
+            50_000.0,
+        );
+        long.unrealized_pnl = 123.45;
+        let pending = PositionSummary::from_trader_order(
+            4,
+            &TraderOrderBuilder::new()
+                .order_status(OrderStatus::PENDING)
+                .position_type(PositionType::SHORT)
+                .position(2_000.0, 2.5, 52_000.0)
+                .build(),
+            50_000.0,
+        );
+        let lend = LendPositionSummary::from_lend_order(
+            5,
+            &LendOrderBuilder::new()
+                .order_status(OrderStatus::LENDED)
+                .build(),
+        );
+
+        let mut failed = account(6, IOType::Coin, true, 5_000);
+        failed.balance_unverified = true;
+        failed.last_error = Some(StoredError::new(
+            "funding_to_trading",
+            "relayer request timed out after 30s while waiting for utxo",
+            now,
+        ));
+
+        StatusSnapshot {
+            btc_usd_price: Some(50_000.0),
+            portfolio: Portfolio::build(
+                100_000,
+                15_000,
+                vec![long, pending],
+                vec![],
+                vec![],
+                vec![lend],
+                vec![],
+                6,
+                5,
+            ),
+            accounts: vec![
+                account(1, IOType::Coin, true, 15_000),
+                account(2, IOType::Coin, false, 0),
+                account(3, IOType::Memo, true, 10_000),
+                account(4, IOType::Memo, true, 2_000),
+                account(5, IOType::Memo, true, 30_000),
+                failed,
+            ],
+            last_activity: BTreeMap::from([
+                (3, now - chrono::Duration::minutes(125)),
+                (4, now - chrono::Duration::seconds(45)),
+                (5, now - chrono::Duration::minutes(26 * 60 + 30)),
+            ]),
+            recent_settlements: vec![
+                settlement(
+                    2,
+                    "MARKET",
+                    Some("LONG"),
+                    250.0,
+                    "2025-01-01 10:15:00.123456",
+                ),
+                settlement(7, "LEND", None, -12.5, "2024-12-31 22:00:00"),
+            ],
+            endpoints: vec![
+                EndpointStatus::from_probe::<String>(
+                    "relayer",
+                    "https://relayer.twilight.rest/api",
+                    std::time::Duration::from_millis(42),
+                    Ok(()),
+                ),
+                EndpointStatus::from_probe(
+                    "lcd",
+                    "https://lcd.twilight.rest",
+                    std::time::Duration::from_millis(3_000),
+                    Err("error sending request for url (https://lcd.twilight.rest/cosmos/bank)"),
+                ),
+            ],
+            ..empty_snapshot()
+        }
+    }
+
+    fn golden(lines: &[&str]) -> String {
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    #[test]
+    fn test_render_populated_snapshot() {
+        assert_eq!(
+            render(&populated_snapshot()),
+            golden(&[
+            "Wallet status  2025-01-01 12:00:00 UTC  nyks",
+            "========================================================================",
+            "Balances",
+            "  Address         twilight1statusfixture",
+            "  On-chain        100000 sats",
+            "  Trading         15000 sats",
+            "  Margin used     12000 sats",
+            "  Unrealized PnL  +123.45 sats",
+            "  Lend value      31000 sats",
+            "  BTC/USD         50000.00",
+            "",
+            "Accounts (6)",
+            "     IDX  STATE       BALANCE",
+            "  ●    1  coin          15000",
+            "  ○    2  coin              0",
+            "  ◆    3  order         10000",
+            "  ◆    4  order          2000",
+            "  ◆    5  order         30000",
+            "  ✗    6  coin           5000  unverified  funding_to_trading: relayer request timed out after 30s whi…",
+            "",
+            "Open orders (3)",
+            "   IDX  SIDE   STATUS      LEV      ENTRY     MARGIN        PnL      AGE",
+            "     3  LONG   FILLED       5x   40000.00      10000    +123.45   2h 05m",
+            "     4  SHORT  PENDING    2.5x   52000.00       2000          -      45s",
+            "     5  LEND   LENDED        -          -      30000   +1000.00   1d 02h",
+            "",
+            "Recent settlements (2)",
+            "  2025-01-01 10:15:00     2  close    MARKET LONG     +250.00",
+            "  2024-12-31 22:00:00     7  close    LEND   -         -12.50",
+            "",
+            "Endpoints",
+            "  ✓ relayer     42 ms  https://relayer.twilight.rest/api",
+            "  ✗ lcd       3000 ms  https://lcd.twilight.rest  (error sending request for url (https://…)",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_render_empty_wallet() {
+        assert_eq!(
+            render(&empty_snapshot()),
+            golden(&[
+                "Wallet status  2025-01-01 12:00:00 UTC  nyks",
+                "========================================================================",
+                "Balances",
+                "  Address         twilight1statusfixture",
+                "  On-chain        0 sats",
+                "  Trading         0 sats",
+                "  Margin used     0 sats",
+                "  Unrealized PnL  +0.00 sats",
+                "  Lend value      0 sats",
+                "  BTC/USD         -",
+                "",
+                "Accounts (0)",
+                "  (none)",
+                "",
+                "Open orders (0)",
+                "  (none)",
+                "",
+                "Recent settlements (0)",
+                "  (none)",
+                "",
+                "Endpoints",
+                "  (none)",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_render_compact() {
+        assert_eq!(
+            render_compact(&populated_snapshot()),
+            "nyks | on-chain 100000 sats | trading 15000 sats | margin 12000 sats | uPnL +123.45 | orders 3 | accounts 6 (1 err) | BTC/USD 50000.00 | relayer ok, lcd down"
+        );
+        assert_eq!(
+            render_compact(&empty_snapshot()),
+            "nyks | on-chain 0 sats | trading 0 sats | margin 0 sats | uPnL +0.00 | orders 0 | accounts 0 (0 err) | BTC/USD - | no endpoints"
+        );
+    }
+
+    #[test]
+    fn test_render_many_accounts_stays_aligned() {
+        let snapshot = StatusSnapshot {
+            accounts: (1..=250)
+                .map(|i| account(i, IOType::Coin, i % 3 != 0, i * 1_000))
+                .collect(),
+            ..empty_snapshot()
+        };
+        let text = render(&snapshot);
+        let rows: Vec<&str> = text
+            .lines()
+            .skip_while(|l| !l.starts_with("Accounts (250)"))
+            .skip(2)
+            .take_while(|l| !l.is_empty())
+            .collect();
+        assert_eq!(rows.len(), 250);
+        assert_eq!(rows[0], "  ●    1  coin           1000");
+        assert_eq!(rows[2], "  ○    3  coin           3000");
+        assert_eq!(rows[249], "  ●  250  coin         250000");
+        let width = rows[0].chars().count();
+        assert!(rows.iter().all(|r| r.chars().count() == width));
+        assert!(!render_compact(&snapshot).contains('\n'));
+        assert!(render_compact(&snapshot).contains("accounts 250 (0 err)"));
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(chrono::Duration::seconds(-5)), "0s");
+        assert_eq!(format_age(chrono::Duration::seconds(59)), "59s");
+        assert_eq!(format_age(chrono::Duration::seconds(61)), "1m");
+        assert_eq!(format_age(chrono::Duration::seconds(3_600)), "1h 00m");
+        assert_eq!(format_age(chrono::Duration::seconds(90_000)), "1d 01h");
+    }
+}
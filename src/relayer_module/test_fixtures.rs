@@ -0,0 +1,564 @@
+//! Ready-made relayer values for unit tests (`test-utils` feature).
+//!
+//! `TraderOrder`, `LendOrder` and `TxHash` come from the client SDK and are
+//! normally only produced by deserializing relayer responses. The builders
+//! here start from the real responses checked in under
+//! `fuzz/corpus/relayer_types/`, override individual fields and deserialize
+//! the result, so a built value always has the relayer's wire format:
+//!
+//! ```ignore
+//! use nyks_wallet::relayer_module::test_fixtures::TraderOrderBuilder;
+//! use nyks_wallet::relayer_module::relayer_types::{OrderStatus, PositionType};
+//!
+//! let order = TraderOrderBuilder::new()
+//!     .position_type(PositionType::SHORT)
+//!     .position(10_000.0, 5.0, 52_000.0)
+//!     .liquidation_price(62_400.0)
+//!     .build();
+//! assert_eq!(order.order_status, OrderStatus::FILLED);
+//! ```
+//!
+//! [`TraderOrderBuilder::to_json`] and friends return the same value as a
+//! relayer response body, for mock servers.
+//!
+//! `field` only accepts the wire names of the checked-in response, so a typo
+//! is caught instead of being ignored by deserialization. `try_build` reports
+//! an unknown name or a value the type does not accept as a
+//! [`FixtureError`]; `build` panics with it.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::relayer_types::{
+    LendOrder, OrderBook, OrderStatus, OrderType, PositionType, TraderOrder, TxHash,
+};
+use crate::compat::relayer_rpcclient::method::UtxoDetailResponse;
+use crate::compat::zkvm::{Output, Utxo};
+use crate::zkos_accounts::zkaccount::ZkAccount;
+
+/// Filled 2x long on 1_000 sats at 50_000.
+pub const TRADER_ORDER_JSON: &str =
+    include_str!("../../fuzz/corpus/relayer_types/trader_order.json");
+/// Settled lend of 30_000 sats now worth 31_000.
+pub const LEND_ORDER_JSON: &str = include_str!("../../fuzz/corpus/relayer_types/lend_order.json");
+/// Filled market order's transaction record.
+pub const TX_HASH_JSON: &str = include_str!("../../fuzz/corpus/relayer_types/tx_hash.json");
+/// Two bid and two ask levels around 50_000.
+pub const ORDER_BOOK_JSON: &str = include_str!("../../fuzz/corpus/relayer_types/order_book.json");
+
+/// Why a builder could not produce its value.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FixtureError {
+    #[error("{type_name} has no field `{field}`")]
+    UnknownField {
+        type_name: &'static str,
+        field: String,
+    },
+    #[error("invalid {type_name}: {message}")]
+    Invalid {
+        type_name: &'static str,
+        message: String,
+    },
+}
+
+fn parse(name: &str, json: &str) -> Value {
+    serde_json::from_str(json).unwrap_or_else(|e| panic!("{} fixture: {}", name, e))
+}
+
+/// A response body being overridden field by field; keeps the first error.
+#[derive(Debug, Clone)]
+struct Fields {
+    type_name: &'static str,
+    json: Value,
+    error: Option<FixtureError>,
+}
+
+impl Fields {
+    fn new(type_name: &'static str, json: Value) -> Self {
+        Self {
+            type_name,
+            json,
+            error: None,
+        }
+    }
+
+    fn set(&mut self, field: &str, value: impl Serialize) {
+        if self.error.is_some() {
+            return;
+        }
+        let Some(slot) = self.json.get_mut(field) else {
+            self.error = Some(FixtureError::UnknownField {
+                type_name: self.type_name,
+                field: field.to_string(),
+            });
+            return;
+        };
+        match serde_json::to_value(value) {
+            Ok(value) => *slot = value,
+            Err(e) => {
+                self.error = Some(FixtureError::Invalid {
+                    type_name: self.type_name,
+                    message: format!("field `{}`: {}", field, e),
+                })
+            }
+        }
+    }
+
+    fn build<T: serde::de::DeserializeOwned>(self) -> Result<T, FixtureError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        serde_json::from_value(self.json).map_err(|e| FixtureError::Invalid {
+            type_name: self.type_name,
+            message: e.to_string(),
+        })
+    }
+}
+
+fn build<T: serde::de::DeserializeOwned>(fields: Fields) -> T {
+    fields.build().unwrap_or_else(|e| panic!("{}", e))
+}
+
+// -------------------------
+// TraderOrder
+// -------------------------
+
+/// Builds a [`TraderOrder`], starting from [`TRADER_ORDER_JSON`].
+#[derive(Debug, Clone)]
+pub struct TraderOrderBuilder {
+    fields: Fields,
+}
+
+impl Default for TraderOrderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TraderOrderBuilder {
+    pub fn new() -> Self {
+        Self {
+            fields: Fields::new("TraderOrder", parse("trader_order", TRADER_ORDER_JSON)),
+        }
+    }
+
+    pub fn account_id(self, account_id: impl Into<String>) -> Self {
+        self.field("account_id", account_id.into())
+    }
+
+    pub fn position_type(self, position_type: PositionType) -> Self {
+        self.field("position_type", position_type)
+    }
+
+    pub fn order_status(self, order_status: OrderStatus) -> Self {
+        self.field("order_status", order_status)
+    }
+
+    pub fn order_type(self, order_type: OrderType) -> Self {
+        self.field("order_type", order_type)
+    }
+
+    /// Margin, leverage and entry price, with the fields the relayer derives
+    /// from them (`available_margin`, `execution_price`, `positionsize`) kept
+    /// consistent.
+    pub fn position(self, initial_margin: f64, leverage: f64, entry_price: f64) -> Self {
+        self.field("initial_margin", initial_margin)
+            .field("available_margin", initial_margin)
+            .field("leverage", leverage)
+            .field("entryprice", entry_price)
+            .field("execution_price", entry_price)
+            .field("positionsize", initial_margin * leverage * entry_price)
+    }
+
+    pub fn liquidation_price(self, price: f64) -> Self {
+        self.field("liquidation_price", price)
+    }
+
+    pub fn unrealized_pnl(self, pnl: f64) -> Self {
+        self.field("unrealized_pnl", pnl)
+    }
+
+    pub fn settlement_price(self, price: f64) -> Self {
+        self.field("settlement_price", price)
+    }
+
+    /// Set any response field by its wire name.
+    pub fn field(mut self, name: &str, value: impl Serialize) -> Self {
+        self.fields.set(name, value);
+        self
+    }
+
+    /// The order as a `trader_order_info` response body.
+    pub fn to_json(&self) -> Value {
+        self.fields.json.clone()
+    }
+
+    pub fn try_build(self) -> Result<TraderOrder, FixtureError> {
+        self.fields.build()
+    }
+
+    /// Panics where [`try_build`](Self::try_build) fails.
+    pub fn build(self) -> TraderOrder {
+        build(self.fields)
+    }
+}
+
+// -------------------------
+// LendOrder
+// -------------------------
+
+/// Builds a [`LendOrder`], starting from [`LEND_ORDER_JSON`].
+#[derive(Debug, Clone)]
+pub struct LendOrderBuilder {
+    fields: Fields,
+}
+
+impl Default for LendOrderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LendOrderBuilder {
+    pub fn new() -> Self {
+        Self {
+            fields: Fields::new("LendOrder", parse("lend_order", LEND_ORDER_JSON)),
+        }
+    }
+
+    pub fn account_id(self, account_id: impl Into<String>) -> Self {
+        self.field("account_id", account_id.into())
+    }
+
+    pub fn order_status(self, order_status: OrderStatus) -> Self {
+        self.field("order_status", order_status)
+    }
+
+    /// Deposit and its current value (`new_lend_state_amount`, `balance`).
+    pub fn amounts(self, deposit: f64, value: f64) -> Self {
+        self.field("deposit", deposit)
+            .field("new_lend_state_amount", value)
+            .field("balance", value)
+            .field("payment", value - deposit)
+    }
+
+    pub fn npoolshare(self, share: f64) -> Self {
+        self.field("npoolshare", share)
+    }
+
+    /// Set any response field by its wire name.
+    pub fn field(mut self, name: &str, value: impl Serialize) -> Self {
+        self.fields.set(name, value);
+        self
+    }
+
+    /// The order as a `lend_order_info` response body.
+    pub fn to_json(&self) -> Value {
+        self.fields.json.clone()
+    }
+
+    pub fn try_build(self) -> Result<LendOrder, FixtureError> {
+        self.fields.build()
+    }
+
+    /// Panics where [`try_build`](Self::try_build) fails.
+    pub fn build(self) -> LendOrder {
+        build(self.fields)
+    }
+}
+
+// -------------------------
+// TxHash
+// -------------------------
+
+/// Builds a [`TxHash`], starting from [`TX_HASH_JSON`].
+#[derive(Debug, Clone)]
+pub struct TxHashBuilder {
+    fields: Fields,
+}
+
+impl Default for TxHashBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TxHashBuilder {
+    pub fn new() -> Self {
+        Self {
+            fields: Fields::new("TxHash", parse("tx_hash", TX_HASH_JSON)),
+        }
+    }
+
+    pub fn order_id(self, order_id: impl Into<String>) -> Self {
+        self.field("order_id", order_id.into())
+    }
+
+    pub fn account_id(self, account_id: impl Into<String>) -> Self {
+        self.field("account_id", account_id.into())
+    }
+
+    pub fn request_id(self, request_id: impl Into<String>) -> Self {
+        self.field("request_id", Some(request_id.into()))
+    }
+
+    pub fn order_type(self, order_type: OrderType) -> Self {
+        self.field("order_type", order_type)
+    }
+
+    pub fn order_status(self, order_status: OrderStatus) -> Self {
+        self.field("order_status", order_status)
+    }
+
+    pub fn reason(self, reason: impl Into<String>) -> Self {
+        self.field("reason", Some(reason.into()))
+    }
+
+    /// Hex-encoded output of the settled order.
+    pub fn output(self, output: impl Into<String>) -> Self {
+        self.field("output", Some(output.into()))
+    }
+
+    /// Set any response field by its wire name.
+    pub fn field(mut self, name: &str, value: impl Serialize) -> Self {
+        self.fields.set(name, value);
+        self
+    }
+
+    /// The record as one element of a `transaction_hashes` response.
+    pub fn to_json(&self) -> Value {
+        self.fields.json.clone()
+    }
+
+    pub fn try_build(self) -> Result<TxHash, FixtureError> {
+        self.fields.build()
+    }
+
+    /// Panics where [`try_build`](Self::try_build) fails.
+    pub fn build(self) -> TxHash {
+        build(self.fields)
+    }
+}
+
+// -------------------------
+// UtxoDetailResponse
+// -------------------------
+
+/// Builds a [`UtxoDetailResponse`], starting from `account`'s `Coin` output
+/// under the default UTXO id.
+#[derive(Debug, Clone)]
+pub struct UtxoDetailResponseBuilder {
+    fields: Fields,
+}
+
+impl UtxoDetailResponseBuilder {
+    /// Panics if the account's stored address does not decode.
+    pub fn new(account: &ZkAccount) -> Self {
+        Self::from_output(account_output(account))
+    }
+
+    pub fn from_output(output: Output) -> Self {
+        let json = serde_json::json!({ "id": Utxo::default(), "output": output });
+        Self {
+            fields: Fields::new("UtxoDetailResponse", json),
+        }
+    }
+
+    pub fn id(self, id: Utxo) -> Self {
+        self.field("id", id)
+    }
+
+    pub fn output(self, output: Output) -> Self {
+        self.field("output", output)
+    }
+
+    /// Set any response field by its wire name.
+    pub fn field(mut self, name: &str, value: impl Serialize) -> Self {
+        self.fields.set(name, value);
+        self
+    }
+
+    /// The UTXO as a `get_utxos_detail` response body.
+    pub fn to_json(&self) -> Value {
+        self.fields.json.clone()
+    }
+
+    pub fn try_build(self) -> Result<UtxoDetailResponse, FixtureError> {
+        self.fields.build()
+    }
+
+    /// Panics where [`try_build`](Self::try_build) fails.
+    pub fn build(self) -> UtxoDetailResponse {
+        build(self.fields)
+    }
+}
+
+// -------------------------
+// Ready-made values
+// -------------------------
+
+/// Filled 2x market long, 1_000 sats margin at 50_000, liquidation at 34_000.
+pub fn filled_long() -> TraderOrder {
+    TraderOrderBuilder::new().build()
+}
+
+/// Pending 5x limit short, 2_000 sats margin at 52_000, liquidation at 62_400.
+pub fn pending_limit() -> TraderOrder {
+    TraderOrderBuilder::new()
+        .order_type(OrderType::LIMIT)
+        .order_status(OrderStatus::PENDING)
+        .position_type(PositionType::SHORT)
+        .position(2_000.0, 5.0, 52_000.0)
+        .liquidation_price(62_400.0)
+        .build()
+}
+
+/// Settled lend of 30_000 sats returning 31_000.
+pub fn settled_lend() -> LendOrder {
+    LendOrderBuilder::new().build()
+}
+
+/// Transaction record of a filled market order.
+pub fn filled_tx_hash() -> TxHash {
+    TxHashBuilder::new().build()
+}
+
+/// Two bid levels below and two ask levels above 50_000.
+pub fn order_book() -> OrderBook {
+    let json = parse("order_book", ORDER_BOOK_JSON);
+    build(Fields::new("OrderBook", json))
+}
+
+/// UTXO lookup result for `account`: a `Coin` output holding its current
+/// commitment. Panics if the account's stored address does not decode.
+pub fn coin_utxo(account: &ZkAccount) -> UtxoDetailResponse {
+    UtxoDetailResponseBuilder::new(account).build()
+}
+
+/// `account`'s output hex-encoded, as [`TxHashBuilder::output`] takes it.
+pub fn output_hex(account: &ZkAccount) -> String {
+    let output = bincode::serialize(&account_output(account))
+        .unwrap_or_else(|e| panic!("output of account {}: {}", account.index, e));
+    hex::encode(output)
+}
+
+fn account_output(account: &ZkAccount) -> Output {
+    account
+        .get_qq_address()
+        .unwrap_or_else(|e| panic!("invalid account {}: {}", account.index, e))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response_mutations::fixture_value;
+
+    /// Serializing a built value and parsing it again gives the same value, and
+    /// the serialized form has the same fields as the checked-in response.
+    fn assert_round_trip<T>(fixture: &str, value: &T)
+    where
+        T: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let json = serde_json::to_value(value).unwrap();
+        assert_eq!(&serde_json::from_value::<T>(json.clone()).unwrap(), value);
+        let wire = fixture_value(fixture);
+        let mut expected: Vec<_> = wire.as_object().unwrap().keys().collect();
+        let mut actual: Vec<_> = json.as_object().unwrap().keys().collect();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected, "{} fields drifted", fixture);
+    }
+
+    #[test]
+    fn test_fixtures_round_trip_against_wire_format() {
+        assert_round_trip("relayer_types/trader_order.json", &filled_long());
+        assert_round_trip("relayer_types/trader_order.json", &pending_limit());
+        assert_round_trip("relayer_types/lend_order.json", &settled_lend());
+        assert_round_trip("relayer_types/order_book.json", &order_book());
+    }
+
+    #[test]
+    fn test_trader_order_builder_keeps_position_consistent() {
+        let order = pending_limit();
+        assert_eq!(order.order_status, OrderStatus::PENDING);
+        assert_eq!(order.order_type, OrderType::LIMIT);
+        assert_eq!(order.position_type, PositionType::SHORT);
+        assert_eq!(order.initial_margin, 2_000.0);
+        assert_eq!(order.available_margin, 2_000.0);
+        assert_eq!(order.leverage, 5.0);
+        assert_eq!(order.positionsize, 2_000.0 * 5.0 * 52_000.0);
+
+        let long = filled_long();
+        assert_eq!(long.order_status, OrderStatus::FILLED);
+        assert_eq!(long.position_type, PositionType::LONG);
+        assert_eq!(long.liquidation_price, 34_000.0);
+    }
+
+    #[test]
+    fn test_lend_and_tx_hash_builders() {
+        let lend = LendOrderBuilder::new()
+            .order_status(OrderStatus::FILLED)
+            .amounts(10_000.0, 10_250.0)
+            .build();
+        assert_eq!(lend.order_status, OrderStatus::FILLED);
+        assert_eq!(lend.deposit, 10_000.0);
+        assert_eq!(lend.new_lend_state_amount, 10_250.0);
+        assert_eq!(lend.payment, 250.0);
+        assert_eq!(settled_lend().order_status, OrderStatus::SETTLED);
+
+        let filled = filled_tx_hash();
+        assert_eq!(filled.order_status, OrderStatus::FILLED);
+        assert_eq!(filled.order_id, "3fa85f64-5717-4562-b3fc-2c963f66afa6");
+
+        let tx = TxHashBuilder::new()
+            .order_status(OrderStatus::CANCELLED)
+            .reason("expired")
+            .build();
+        assert_eq!(tx.order_status, OrderStatus::CANCELLED);
+        assert_eq!(tx.reason.as_deref(), Some("expired"));
+    }
+
+    #[test]
+    fn test_field_rejects_unknown_names_and_bad_values() {
+        assert_eq!(
+            TraderOrderBuilder::new()
+                .field("entry_price", 1.0)
+                .try_build()
+                .unwrap_err(),
+            FixtureError::UnknownField {
+                type_name: "TraderOrder",
+                field: "entry_price".to_string(),
+            }
+        );
+        let err = LendOrderBuilder::new()
+            .field("deposit", "lots")
+            .try_build()
+            .unwrap_err();
+        assert!(matches!(err, FixtureError::Invalid { .. }), "{}", err);
+        assert!(TxHashBuilder::new().reason("expired").try_build().is_ok());
+
+        let seed = secrecy::SecretString::new("test-fixtures-seed".to_string());
+        let account = ZkAccount::from_seed(0, &seed, 1_000).unwrap();
+        let builder = UtxoDetailResponseBuilder::new(&account);
+        assert_eq!(
+            serde_json::to_value(coin_utxo(&account)).unwrap(),
+            builder.to_json()
+        );
+        assert!(matches!(
+            UtxoDetailResponseBuilder::new(&account)
+                .field("amount", 1)
+                .try_build(),
+            Err(FixtureError::UnknownField { .. })
+        ));
+    }
+
+    #[test]
+    fn test_order_book_levels() {
+        let book = order_book();
+        assert_eq!(book.bid.len(), 2);
+        assert_eq!(book.ask.len(), 2);
+        assert!(book.bid.iter().all(|b| b.price < 50_000.0));
+        assert!(book.ask.iter().all(|a| a.price > 50_000.0));
+    }
+}
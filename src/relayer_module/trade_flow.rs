@@ -0,0 +1,313 @@
+//! Aggregates over the relayer's recent trades, for volume analysis.
+//!
+//! `recent_trade_orders` returns the latest closed trades as the relayer has
+//! them: not necessarily in time order, and a trade can appear twice. A
+//! [`TradeFlowAnalyzer`] keeps each order id once, sorts by timestamp, and
+//! answers volume, VWAP and side questions over a time window, or bins the
+//! trades into fixed intervals for charting ([`TradeFlowAnalyzer::trades_binned`]).
+//!
+//! Volumes are sums of `positionsize`, in the unit the relayer reports it.
+//! A trade on the LONG side counts as a buy, SHORT as a sell. Windows given
+//! as a duration end at the analyzer's `as_of` time, `Utc::now()` unless set
+//! with [`TradeFlowAnalyzer::with_as_of`].
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::Serialize;
+
+use super::relayer_types::{CloseTrade, PositionType, RecentOrders};
+
+/// Most buckets [`TradeFlowAnalyzer::trades_binned`] returns; a finer
+/// interval over the trades' time span is an error.
+pub const MAX_TRADE_BINS: usize = 10_000;
+
+/// Trade counts and volumes per side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct SideFlow {
+    pub buys: usize,
+    pub sells: usize,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+}
+
+impl SideFlow {
+    fn add(&mut self, trade: &CloseTrade) {
+        match trade.side {
+            PositionType::LONG => {
+                self.buys += 1;
+                self.buy_volume += trade.positionsize;
+            }
+            PositionType::SHORT => {
+                self.sells += 1;
+                self.sell_volume += trade.positionsize;
+            }
+        }
+    }
+
+    /// Buy volume over sell volume; `None` without sell volume.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.sell_volume > 0.0).then(|| self.buy_volume / self.sell_volume)
+    }
+}
+
+/// Trades in `[start, start + interval)`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TradeBin {
+    pub start: DateTime<Utc>,
+    pub trades: usize,
+    pub volume: f64,
+    pub flow: SideFlow,
+    /// `None` for a bin without trades.
+    pub vwap: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+}
+
+impl TradeBin {
+    fn empty(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            trades: 0,
+            volume: 0.0,
+            flow: SideFlow::default(),
+            vwap: None,
+            high: None,
+            low: None,
+        }
+    }
+}
+
+/// Deduplicated, time-ordered recent trades; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct TradeFlowAnalyzer {
+    trades: Vec<CloseTrade>,
+    as_of: DateTime<Utc>,
+}
+
+impl TradeFlowAnalyzer {
+    /// Trades of `recent`, one per order id (the first seen), oldest first.
+    pub fn new(recent: &RecentOrders) -> Self {
+        Self::from_trades(recent.orders.iter().cloned())
+    }
+
+    pub fn from_trades(trades: impl IntoIterator<Item = CloseTrade>) -> Self {
+        let mut seen = HashSet::new();
+        let mut trades: Vec<CloseTrade> = trades
+            .into_iter()
+            .filter(|trade| seen.insert(trade.order_id))
+            .collect();
+        trades.sort_by_key(|trade| trade.timestamp);
+        Self {
+            trades,
+            as_of: Utc::now(),
+        }
+    }
+
+    /// End of the windows given as a duration, e.g. when replaying data.
+    pub fn with_as_of(mut self, as_of: DateTime<Utc>) -> Self {
+        self.as_of = as_of;
+        self
+    }
+
+    /// All trades, oldest first.
+    pub fn trades(&self) -> &[CloseTrade] {
+        &self.trades
+    }
+
+    /// Trades at or after `ts`, oldest first.
+    pub fn since(&self, ts: DateTime<Utc>) -> &[CloseTrade] {
+        let start = self.trades.partition_point(|trade| trade.timestamp < ts);
+        &self.trades[start..]
+    }
+
+    pub fn volume_since(&self, ts: DateTime<Utc>) -> f64 {
+        self.since(ts).iter().map(|trade| trade.positionsize).sum()
+    }
+
+    /// Volume-weighted average price of trades since `ts`; `None` without
+    /// volume.
+    pub fn vwap_since(&self, ts: DateTime<Utc>) -> Option<f64> {
+        vwap(self.since(ts))
+    }
+
+    /// Trade counts and volumes per side since `ts`.
+    pub fn flow_since(&self, ts: DateTime<Utc>) -> SideFlow {
+        let mut flow = SideFlow::default();
+        for trade in self.since(ts) {
+            flow.add(trade);
+        }
+        flow
+    }
+
+    /// Buy over sell volume in the last `window`; `None` without sells.
+    pub fn buy_sell_ratio(&self, window: TimeDelta) -> Option<f64> {
+        self.flow_since(self.as_of - window).ratio()
+    }
+
+    /// The trade with the largest position size since `ts`.
+    pub fn largest_trade_since(&self, ts: DateTime<Utc>) -> Option<&CloseTrade> {
+        self.since(ts)
+            .iter()
+            .max_by(|a, b| a.positionsize.total_cmp(&b.positionsize))
+    }
+
+    /// Trades in consecutive `interval` buckets aligned to multiples of
+    /// `interval` since the Unix epoch, from the oldest trade's bucket to the
+    /// newest's. Buckets without trades are included, so the result plots
+    /// as an even time axis. Empty for a non-positive `interval`; an error
+    /// when that would take more than [`MAX_TRADE_BINS`] buckets.
+    pub fn trades_binned(&self, interval: TimeDelta) -> Result<Vec<TradeBin>, String> {
+        let (Some(first), Some(last)) = (self.trades.first(), self.trades.last()) else {
+            return Ok(Vec::new());
+        };
+        if interval <= TimeDelta::zero() {
+            return Ok(Vec::new());
+        }
+        let (Ok(first_start), Ok(last_start)) = (
+            first.timestamp.duration_trunc(interval),
+            last.timestamp.duration_trunc(interval),
+        ) else {
+            return Ok(Vec::new());
+        };
+        let bin_count = nanos(last_start - first_start) / nanos(interval) + 1;
+        if bin_count > MAX_TRADE_BINS as i128 {
+            return Err(format!(
+                "Binning {} trades by {} takes {} buckets, more than {}",
+                self.trades.len(),
+                interval,
+                bin_count,
+                MAX_TRADE_BINS
+            ));
+        }
+        let mut bins = Vec::with_capacity(bin_count as usize);
+        let mut start = first_start;
+        let mut trades = self.trades.as_slice();
+        while start <= last_start {
+            let end = start + interval;
+            let split = trades.partition_point(|trade| trade.timestamp < end);
+            let (in_bin, rest) = trades.split_at(split);
+            trades = rest;
+            let mut bin = TradeBin::empty(start);
+            for trade in in_bin {
+                bin.trades += 1;
+                bin.volume += trade.positionsize;
+                bin.flow.add(trade);
+                bin.high = Some(bin.high.map_or(trade.price, |high| high.max(trade.price)));
+                bin.low = Some(bin.low.map_or(trade.price, |low| low.min(trade.price)));
+            }
+            bin.vwap = vwap(in_bin);
+            bins.push(bin);
+            start = end;
+        }
+        Ok(bins)
+    }
+}
+
+impl RecentOrders {
+    /// A [`TradeFlowAnalyzer`] over these trades.
+    pub fn flow(&self) -> TradeFlowAnalyzer {
+        TradeFlowAnalyzer::new(self)
+    }
+}
+
+fn nanos(delta: TimeDelta) -> i128 {
+    i128::from(delta.num_seconds()) * 1_000_000_000 + i128::from(delta.subsec_nanos())
+}
+
+fn vwap(trades: &[CloseTrade]) -> Option<f64> {
+    let volume: f64 = trades.iter().map(|trade| trade.positionsize).sum();
+    let notional: f64 = trades
+        .iter()
+        .map(|trade| trade.price * trade.positionsize)
+        .sum();
+    (volume > 0.0).then(|| notional / volume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn t0() -> DateTime<Utc> {
+        "2025-03-01T12:00:00Z".parse().unwrap()
+    }
+
+    fn trade(id: u128, side: PositionType, size: f64, price: f64, secs: i64) -> CloseTrade {
+        CloseTrade {
+            order_id: Uuid::from_u128(id),
+            side,
+            positionsize: size,
+            price,
+            timestamp: t0() + TimeDelta::seconds(secs),
+        }
+    }
+
+    fn recent() -> RecentOrders {
+        RecentOrders {
+            orders: vec![
+                trade(3, PositionType::SHORT, 300.0, 50_100.0, 150),
+                trade(1, PositionType::LONG, 100.0, 50_000.0, 10),
+                trade(2, PositionType::LONG, 200.0, 50_200.0, 70),
+                // Duplicate of order 1.
+                trade(1, PositionType::LONG, 100.0, 50_000.0, 10),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_window_aggregates_dedupe_and_sort() {
+        let flow = recent().flow().with_as_of(t0() + TimeDelta::seconds(180));
+        let ids: Vec<u128> = flow.trades().iter().map(|t| t.order_id.as_u128()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        assert_eq!(flow.volume_since(t0()), 600.0);
+        assert_eq!(flow.volume_since(t0() + TimeDelta::seconds(70)), 500.0);
+        // (100 * 50_000 + 200 * 50_200 + 300 * 50_100) / 600
+        let vwap = flow.vwap_since(t0()).unwrap();
+        assert!((vwap - 50_116.666_666).abs() < 1e-3, "{}", vwap);
+        assert!(flow.vwap_since(t0() + TimeDelta::hours(1)).is_none());
+
+        let sides = flow.flow_since(t0());
+        assert_eq!((sides.buys, sides.sells), (2, 1));
+        assert_eq!(flow.buy_sell_ratio(TimeDelta::minutes(5)), Some(1.0));
+        // The last two minutes hold the 200 buy and the 300 sell.
+        let ratio = flow.buy_sell_ratio(TimeDelta::minutes(2)).unwrap();
+        assert!((ratio - 200.0 / 300.0).abs() < 1e-9);
+        assert_eq!(
+            flow.largest_trade_since(t0()).map(|t| t.order_id.as_u128()),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_trades_binned_fills_gaps() {
+        let flow = recent().flow();
+        let bins = flow.trades_binned(TimeDelta::minutes(1)).unwrap();
+        let starts: Vec<i64> = bins
+            .iter()
+            .map(|b| (b.start - t0()).num_seconds())
+            .collect();
+        assert_eq!(starts, vec![0, 60, 120]);
+        assert_eq!(
+            bins.iter().map(|b| b.trades).collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+        assert_eq!(bins[1].vwap, Some(50_200.0));
+
+        let bins = flow.trades_binned(TimeDelta::seconds(50)).unwrap();
+        assert_eq!(bins.iter().map(|b| b.trades).sum::<usize>(), 3);
+        assert!(bins.iter().any(|b| b.trades == 0 && b.vwap.is_none()));
+        assert_eq!(flow.trades_binned(TimeDelta::zero()), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_trades_binned_refuses_too_many_buckets() {
+        let flow = recent().flow();
+        // 140 seconds from the first trade to the last.
+        let bins = flow.trades_binned(TimeDelta::milliseconds(20)).unwrap();
+        assert_eq!(bins.len(), 7_001);
+        let err = flow.trades_binned(TimeDelta::milliseconds(10)).unwrap_err();
+        assert!(err.contains("14001 buckets, more than 10000"), "{}", err);
+    }
+}
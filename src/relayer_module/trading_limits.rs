@@ -0,0 +1,178 @@
+//! Leverage and position size bounds of the connected relayer.
+//!
+//! The relayer rejects an order outside its risk parameters with a terse
+//! error after the order has been built and sent. The parameters come with
+//! `get_market_stats` ([`MarketStats::params`]);
+//! [`TradingLimits::from_market_stats`] keeps them, and
+//! [`TradingLimits::check`] applies them before anything is signed, with an
+//! error naming the bound that failed. Until the stats have been fetched, or
+//! when they cannot be, [`TradingLimits::fallback`] only requires leverage
+//! above zero.
+//!
+//! Sizes are in sats. A position's value is its initial margin times its
+//! leverage, as the relayer's risk engine computes it. The per-position cap
+//! moves with the pool equity, so it is left to the risk-engine checks made
+//! against current stats.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::leverage::{Leverage, LeverageError, LeverageLimits};
+use super::relayer_types::MarketStats;
+
+/// How long an `OrderWallet` uses fetched limits before fetching them again.
+pub const DEFAULT_LIMITS_REFRESH: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Where a [`TradingLimits`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitsSource {
+    Relayer,
+    /// The relayer's market stats could not be fetched.
+    Fallback,
+}
+
+/// An order outside the relayer's trading limits.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TradingLimitError {
+    #[error(transparent)]
+    Leverage(#[from] LeverageError),
+    #[error("Position value {value} sats below relayer minimum {min} sats")]
+    PositionBelowMin { value: u64, min: u64 },
+}
+
+/// Trading parameters in force for new orders; see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TradingLimits {
+    pub leverage: LeverageLimits,
+    /// `params.min_position_btc`; 0 without a minimum.
+    pub min_position_value: u64,
+    pub source: LimitsSource,
+    /// When the limits were fetched, or the fetch failed; `None` until the
+    /// first attempt.
+    pub fetched_at: Option<DateTime<Utc>>,
+}
+
+impl Default for TradingLimits {
+    fn default() -> Self {
+        Self::fallback()
+    }
+}
+
+impl TradingLimits {
+    /// Bounds checked without the relayer's parameters.
+    pub fn fallback() -> Self {
+        Self {
+            leverage: LeverageLimits::default(),
+            min_position_value: 0,
+            source: LimitsSource::Fallback,
+            fetched_at: None,
+        }
+    }
+
+    pub fn from_market_stats(stats: &MarketStats, fetched_at: DateTime<Utc>) -> Self {
+        let params = &stats.params;
+        Self {
+            leverage: LeverageLimits::from_risk_params(params),
+            min_position_value: params.min_position_btc.max(0.0).ceil() as u64,
+            source: LimitsSource::Relayer,
+            fetched_at: Some(fetched_at),
+        }
+    }
+
+    /// Whether the limits are older than `max_age` at `now`, or were never
+    /// fetched.
+    pub fn is_stale(&self, now: DateTime<Utc>, max_age: std::time::Duration) -> bool {
+        match (self.fetched_at, chrono::Duration::from_std(max_age)) {
+            (Some(fetched_at), Ok(max_age)) => now - fetched_at >= max_age,
+            _ => true,
+        }
+    }
+
+    /// Check a new trader order of `initial_margin` sats at `leverage`.
+    pub fn check(&self, initial_margin: u64, leverage: Leverage) -> Result<(), TradingLimitError> {
+        self.leverage.check(leverage)?;
+        let value = leverage.apply(initial_margin).unwrap_or(u64::MAX);
+        if value < self.min_position_value {
+            return Err(TradingLimitError::PositionBelowMin {
+                value,
+                min: self.min_position_value,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> TradingLimits {
+        let stats: MarketStats = serde_json::from_value(serde_json::json!({
+            "pool_equity_btc": 5_000_000.0,
+            "total_long_btc": 0.0,
+            "total_short_btc": 0.0,
+            "total_pending_long_btc": 0.0,
+            "total_pending_short_btc": 0.0,
+            "open_interest_btc": 0.0,
+            "net_exposure_btc": 0.0,
+            "long_pct": 0.0,
+            "short_pct": 0.0,
+            "utilization": 0.0,
+            "max_long_btc": 1_000_000.0,
+            "max_short_btc": 1_000_000.0,
+            "status": "HEALTHY",
+            "status_reason": null,
+            "params": {
+                "max_oi_mult": 4.0,
+                "max_net_mult": 0.8,
+                "max_position_pct": 0.2,
+                "min_position_btc": 2000.0,
+                "max_leverage": 20.0,
+                "mm_ratio": 0.4,
+            },
+            "funding_rate": {
+                "funding_rate": 0.0,
+                "estimated_funding_rate": 0.0,
+                "funding_rate_timestamp": "2025-03-01T12:00:00Z",
+                "estimated_funding_rate_timestamp": "2025-03-01T12:00:00Z",
+            },
+        }))
+        .unwrap();
+        TradingLimits::from_market_stats(&stats, DateTime::<Utc>::UNIX_EPOCH)
+    }
+
+    #[test]
+    fn test_check_names_the_failed_bound() {
+        let limits = limits();
+        assert_eq!(limits.min_position_value, 2_000);
+        let ten = Leverage::whole(10).unwrap();
+        assert!(limits.check(5_000, ten).is_ok());
+
+        let err = limits.check(150, ten).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Position value 1500 sats below relayer minimum 2000 sats"
+        );
+        assert!(matches!(
+            limits.check(5_000, Leverage::whole(25).unwrap()),
+            Err(TradingLimitError::Leverage(LeverageError::AboveMax { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_fallback_only_rejects_zero_leverage_and_goes_stale() {
+        let fallback = TradingLimits::fallback();
+        let now = DateTime::<Utc>::UNIX_EPOCH;
+        assert!(fallback.check(1, Leverage::whole(100).unwrap()).is_ok());
+        assert!(matches!(
+            fallback.check(1, Leverage::from_units(0)),
+            Err(TradingLimitError::Leverage(LeverageError::Zero))
+        ));
+        assert!(fallback.is_stale(now, DEFAULT_LIMITS_REFRESH));
+
+        let fetched = limits();
+        assert!(!fetched.is_stale(now + chrono::Duration::seconds(60), DEFAULT_LIMITS_REFRESH));
+        assert!(fetched.is_stale(now + chrono::Duration::seconds(300), DEFAULT_LIMITS_REFRESH));
+    }
+}
@@ -3,8 +3,14 @@
 //! Provides structured types for order and transfer history entries,
 //! plus filter structs for querying historical data.
 
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::events::{OrderKind, WalletEvent};
+use super::relayer_types::{LendOrder, OrderStatus, TraderOrder};
+
 /// An entry in the order history audit log.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderHistoryEntry {
@@ -33,6 +39,307 @@ pub struct TransferHistoryEntry {
     pub created_at: String,
 }
 
+/// A mint or burn whose on-chain amount differed from the amount requested.
+/// The difference is also written to transfer history, as `fee` when the
+/// chain credited less and `fee_refund` when it credited more.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AmountDiscrepancy {
+    pub account_index: u64,
+    /// `OrderWallet` method that moved the funds.
+    pub operation: String,
+    pub requested: u64,
+    /// Amount committed to by the account output, or credited to the wallet for a burn.
+    pub actual: u64,
+    /// Mint or burn that moved the funds, when known.
+    #[serde(default)]
+    pub tx_hash: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl AmountDiscrepancy {
+    /// Sats kept by the chain; negative when it credited more than requested.
+    pub fn fee(&self) -> i64 {
+        self.requested as i64 - self.actual as i64
+    }
+}
+
+/// What an order request recorded in [`OrderRecord`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderRecordKind {
+    TraderOpen,
+    TraderClose,
+    /// Settlement of part of a trader position; the order stays open.
+    TraderPartialClose,
+    TraderCancel,
+    LendOpen,
+    LendClose,
+}
+
+impl OrderRecordKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderRecordKind::TraderOpen => "trader_open",
+            OrderRecordKind::TraderClose => "trader_close",
+            OrderRecordKind::TraderPartialClose => "trader_partial_close",
+            OrderRecordKind::TraderCancel => "trader_cancel",
+            OrderRecordKind::LendOpen => "lend_open",
+            OrderRecordKind::LendClose => "lend_close",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "trader_open" => Some(OrderRecordKind::TraderOpen),
+            "trader_close" => Some(OrderRecordKind::TraderClose),
+            "trader_partial_close" => Some(OrderRecordKind::TraderPartialClose),
+            "trader_cancel" => Some(OrderRecordKind::TraderCancel),
+            "lend_open" => Some(OrderRecordKind::LendOpen),
+            "lend_close" => Some(OrderRecordKind::LendClose),
+            _ => None,
+        }
+    }
+
+    /// Which book the request went to.
+    pub fn order(&self) -> OrderKind {
+        match self {
+            OrderRecordKind::TraderOpen
+            | OrderRecordKind::TraderClose
+            | OrderRecordKind::TraderPartialClose
+            | OrderRecordKind::TraderCancel => OrderKind::Trader,
+            OrderRecordKind::LendOpen | OrderRecordKind::LendClose => OrderKind::Lend,
+        }
+    }
+
+    /// Kind and request ID of the request behind an open, close or cancel
+    /// `event`. `None` for every other event, including
+    /// [`WalletEvent::OrderFailed`], which has no request ID.
+    pub fn of_event(event: &WalletEvent) -> Option<(Self, &str)> {
+        match event {
+            WalletEvent::OrderOpened {
+                request_id, order, ..
+            } => Some((
+                match order {
+                    OrderKind::Trader => OrderRecordKind::TraderOpen,
+                    OrderKind::Lend => OrderRecordKind::LendOpen,
+                },
+                request_id,
+            )),
+            WalletEvent::OrderClosed {
+                request_id, order, ..
+            } => Some((
+                match order {
+                    OrderKind::Trader => OrderRecordKind::TraderClose,
+                    OrderKind::Lend => OrderRecordKind::LendClose,
+                },
+                request_id,
+            )),
+            WalletEvent::OrderCancelled { request_id, .. } => {
+                Some((OrderRecordKind::TraderCancel, request_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Status of an [`OrderRecord`] before any query has seen the order.
+pub const ORDER_RECORD_SUBMITTED: &str = "SUBMITTED";
+
+/// One order request made from an account. `OrderWallet::order_history`
+/// keeps these oldest first; `OrderWallet::request_id` is the newest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderRecord {
+    pub request_id: String,
+    pub kind: OrderRecordKind,
+    /// When the request was submitted.
+    pub recorded_at: DateTime<Utc>,
+    /// Last order status seen by `query_trader_order` / `query_lend_order`,
+    /// or [`ORDER_RECORD_SUBMITTED`] if none has been seen yet.
+    pub status: String,
+    /// Order type a trader open was requested with; `None` for other kinds
+    /// and for opens recorded before it was kept.
+    #[serde(default)]
+    pub order_type: Option<String>,
+    /// Entry price a trader open was requested at, in USD.
+    #[serde(default)]
+    pub requested_price: Option<u64>,
+}
+
+impl OrderRecord {
+    pub fn new(request_id: String, kind: OrderRecordKind, recorded_at: DateTime<Utc>) -> Self {
+        Self {
+            request_id,
+            kind,
+            recorded_at,
+            status: ORDER_RECORD_SUBMITTED.to_string(),
+            order_type: None,
+            requested_price: None,
+        }
+    }
+
+    /// Record the order type and entry price a trader open was requested
+    /// with, for [`ExecutionReport`](super::execution_report::ExecutionReport).
+    pub fn with_requested_entry(mut self, order_type: String, requested_price: u64) -> Self {
+        self.order_type = Some(order_type);
+        self.requested_price = Some(requested_price);
+        self
+    }
+}
+
+/// Prices and margins of an order when it was closed or cancelled, for
+/// accounting. `OrderWallet::close_trader_order`, `cancel_trader_order` and
+/// `close_lend_order` store one per request; a close is stored again once
+/// `unlock_trader_order` / `unlock_lend_order` sees it settled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderSnapshot {
+    pub account_index: u64,
+    pub request_id: String,
+    pub kind: OrderRecordKind,
+    /// Relayer status of the order when the snapshot was taken.
+    pub order_status: String,
+    /// `LONG` or `SHORT`; `None` for lend orders.
+    pub position_type: Option<String>,
+    pub entry_price: Option<f64>,
+    /// Settlement price once settled, otherwise the price the close was requested at.
+    pub exit_price: Option<f64>,
+    pub leverage: Option<f64>,
+    /// Margin the order was opened with; the deposit of a lend order.
+    pub initial_margin: f64,
+    /// Margin returned to the account; `None` until the order has settled.
+    pub settled_margin: Option<f64>,
+    /// `settled_margin - initial_margin`.
+    pub realized_pnl: Option<f64>,
+    /// Fill and settlement fees reported by the relayer.
+    pub fees: Option<f64>,
+    /// When the relayer opened the order, if its timestamp parses.
+    pub opened_at: Option<DateTime<Utc>>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl OrderSnapshot {
+    /// Snapshot of a queried trader order. `exit_price` is the requested close
+    /// price; a settled or liquidated order reports its settlement price instead.
+    pub fn of_trader_order(
+        account_index: u64,
+        request_id: String,
+        kind: OrderRecordKind,
+        order: &TraderOrder,
+        exit_price: Option<f64>,
+        recorded_at: DateTime<Utc>,
+    ) -> Self {
+        let settled = matches!(
+            order.order_status,
+            OrderStatus::SETTLED | OrderStatus::LIQUIDATE
+        );
+        let settled_margin = settled.then_some(order.available_margin);
+        Self {
+            account_index,
+            request_id,
+            kind,
+            order_status: order.order_status.to_str().to_string(),
+            position_type: Some(format!("{:?}", order.position_type)),
+            entry_price: Some(order.entryprice),
+            exit_price: if settled {
+                Some(order.settlement_price)
+            } else {
+                exit_price
+            },
+            leverage: Some(order.leverage),
+            initial_margin: order.initial_margin,
+            settled_margin,
+            realized_pnl: settled_margin.map(|margin| margin - order.initial_margin),
+            fees: Some(order.fee_filled + order.fee_settled),
+            opened_at: parse_order_timestamp(&order.timestamp),
+            recorded_at,
+        }
+    }
+
+    /// Snapshot of a queried lend order, settled once its status is `SETTLED`.
+    pub fn of_lend_order(
+        account_index: u64,
+        request_id: String,
+        order: &LendOrder,
+        recorded_at: DateTime<Utc>,
+    ) -> Self {
+        let settled_margin =
+            (order.order_status == OrderStatus::SETTLED).then_some(order.new_lend_state_amount);
+        Self {
+            account_index,
+            request_id,
+            kind: OrderRecordKind::LendClose,
+            order_status: order.order_status.to_str().to_string(),
+            position_type: None,
+            entry_price: None,
+            exit_price: None,
+            leverage: None,
+            initial_margin: order.deposit,
+            settled_margin,
+            realized_pnl: settled_margin.map(|margin| margin - order.deposit),
+            fees: None,
+            opened_at: parse_order_timestamp(&order.timestamp),
+            recorded_at,
+        }
+    }
+}
+
+fn parse_order_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// An order request from `OrderWallet::trade_history`, with the snapshot
+/// stored for it if any.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeHistoryEntry {
+    pub account_index: u64,
+    pub record: OrderRecord,
+    /// `None` for opens, and for closes made without a database.
+    pub snapshot: Option<OrderSnapshot>,
+}
+
+/// Join the order records of every account with the snapshots of the same
+/// requests, oldest first. A snapshot without a record, such as a settlement
+/// found by `unlock_trader_order`, is listed with a record built from it.
+pub fn merge_trade_history(
+    records: HashMap<u64, Vec<OrderRecord>>,
+    snapshots: Vec<OrderSnapshot>,
+) -> Vec<TradeHistoryEntry> {
+    let mut by_request: HashMap<(u64, String), OrderSnapshot> = snapshots
+        .into_iter()
+        .map(|s| ((s.account_index, s.request_id.clone()), s))
+        .collect();
+    let mut entries: Vec<TradeHistoryEntry> = records
+        .into_iter()
+        .flat_map(|(account_index, records)| {
+            records
+                .into_iter()
+                .map(move |record| (account_index, record))
+        })
+        .map(|(account_index, record)| TradeHistoryEntry {
+            snapshot: by_request.remove(&(account_index, record.request_id.clone())),
+            account_index,
+            record,
+        })
+        .collect();
+    entries.extend(by_request.into_values().map(|snapshot| TradeHistoryEntry {
+        account_index: snapshot.account_index,
+        record: OrderRecord {
+            request_id: snapshot.request_id.clone(),
+            kind: snapshot.kind,
+            recorded_at: snapshot.recorded_at,
+            status: snapshot.order_status.clone(),
+            order_type: None,
+            requested_price: None,
+        },
+        snapshot: Some(snapshot),
+    }));
+    entries.sort_by(|a, b| {
+        (a.record.recorded_at, a.account_index).cmp(&(b.record.recorded_at, b.account_index))
+    });
+    entries
+}
+
 /// Filter for querying order history.
 #[derive(Debug, Clone, Default)]
 pub struct OrderHistoryFilter {
@@ -86,3 +393,179 @@ impl TransferHistoryEntry {
         }
     }
 }
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl OrderRecord {
+    pub fn from_db(row: &crate::database::models::DbOrderRecord) -> Result<Self, String> {
+        let kind = OrderRecordKind::parse(&row.kind)
+            .ok_or_else(|| format!("Unknown order record kind: {}", row.kind))?;
+        Ok(Self {
+            request_id: row.request_id.clone(),
+            kind,
+            recorded_at: row.recorded_at.and_utc(),
+            status: row.status.clone(),
+            order_type: row.order_type.clone(),
+            requested_price: row.requested_price.map(|p| p as u64),
+        })
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgresql"))]
+impl OrderSnapshot {
+    pub fn from_db(row: &crate::database::models::DbOrderSnapshot) -> Result<Self, String> {
+        let kind = OrderRecordKind::parse(&row.kind)
+            .ok_or_else(|| format!("Unknown order snapshot kind: {}", row.kind))?;
+        Ok(Self {
+            account_index: row.account_index as u64,
+            request_id: row.request_id.clone(),
+            kind,
+            order_status: row.order_status.clone(),
+            position_type: row.position_type.clone(),
+            entry_price: row.entry_price,
+            exit_price: row.exit_price,
+            leverage: row.leverage,
+            initial_margin: row.initial_margin,
+            settled_margin: row.settled_margin,
+            realized_pnl: row.realized_pnl,
+            fees: row.fees,
+            opened_at: row.opened_at.map(|t| t.and_utc()),
+            recorded_at: row.recorded_at.and_utc(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relayer_module::relayer_types::PositionType;
+    use crate::relayer_module::test_fixtures::{LendOrderBuilder, TraderOrderBuilder};
+
+    #[test]
+    fn test_order_record_kind_from_events() {
+        let closed = WalletEvent::OrderClosed {
+            account_index: 1,
+            request_id: "req-close".to_string(),
+            order: OrderKind::Lend,
+            occurred_at: Utc::now(),
+        };
+        assert_eq!(
+            OrderRecordKind::of_event(&closed),
+            Some((OrderRecordKind::LendClose, "req-close"))
+        );
+        let failed = WalletEvent::OrderFailed {
+            account_index: 1,
+            operation: "open_trader_order".to_string(),
+            error: "boom".to_string(),
+            occurred_at: Utc::now(),
+        };
+        assert_eq!(OrderRecordKind::of_event(&failed), None);
+        for kind in [
+            OrderRecordKind::TraderOpen,
+            OrderRecordKind::TraderClose,
+            OrderRecordKind::TraderCancel,
+            OrderRecordKind::LendOpen,
+            OrderRecordKind::LendClose,
+        ] {
+            assert_eq!(OrderRecordKind::parse(kind.as_str()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn test_trader_snapshot_realizes_pnl_once_settled() {
+        let now = Utc::now();
+        let order = TraderOrderBuilder::new()
+            .position_type(PositionType::SHORT)
+            .position(1_000.0, 2.0, 50_000.0)
+            .build();
+        let submitted = OrderSnapshot::of_trader_order(
+            3,
+            "req-close".to_string(),
+            OrderRecordKind::TraderClose,
+            &order,
+            Some(48_000.0),
+            now,
+        );
+        assert_eq!(submitted.position_type.as_deref(), Some("SHORT"));
+        assert_eq!(submitted.entry_price, Some(50_000.0));
+        assert_eq!(submitted.exit_price, Some(48_000.0));
+        assert_eq!(submitted.settled_margin, None);
+        assert_eq!(submitted.realized_pnl, None);
+        assert!(submitted.opened_at.is_some());
+
+        let settled = TraderOrderBuilder::new()
+            .position_type(PositionType::SHORT)
+            .position(1_000.0, 2.0, 50_000.0)
+            .order_status(OrderStatus::SETTLED)
+            .settlement_price(47_500.0)
+            .field("available_margin", 1_090.0)
+            .field("fee_filled", 4.0)
+            .field("fee_settled", 6.0)
+            .build();
+        let snapshot = OrderSnapshot::of_trader_order(
+            3,
+            "req-close".to_string(),
+            OrderRecordKind::TraderClose,
+            &settled,
+            Some(48_000.0),
+            now,
+        );
+        assert_eq!(snapshot.exit_price, Some(47_500.0));
+        assert_eq!(snapshot.settled_margin, Some(1_090.0));
+        assert_eq!(snapshot.realized_pnl, Some(90.0));
+        assert_eq!(snapshot.fees, Some(10.0));
+
+        let lend = OrderSnapshot::of_lend_order(
+            4,
+            "req-lend".to_string(),
+            &LendOrderBuilder::new().build(),
+            now,
+        );
+        assert_eq!(lend.initial_margin, 30_000.0);
+        assert_eq!(lend.realized_pnl, Some(1_000.0));
+    }
+
+    #[test]
+    fn test_merge_trade_history_joins_by_request() {
+        let t0 = Utc::now();
+        let t1 = t0 + chrono::Duration::seconds(1);
+        let t2 = t0 + chrono::Duration::seconds(2);
+        let snapshot = |request_id: &str, recorded_at| OrderSnapshot {
+            account_index: 1,
+            request_id: request_id.to_string(),
+            kind: OrderRecordKind::TraderClose,
+            order_status: "SETTLED".to_string(),
+            position_type: Some("LONG".to_string()),
+            entry_price: Some(50_000.0),
+            exit_price: Some(51_000.0),
+            leverage: Some(2.0),
+            initial_margin: 1_000.0,
+            settled_margin: Some(1_040.0),
+            realized_pnl: Some(40.0),
+            fees: Some(0.0),
+            opened_at: None,
+            recorded_at,
+        };
+        let records = HashMap::from([(
+            1,
+            vec![
+                OrderRecord::new("req-open".to_string(), OrderRecordKind::TraderOpen, t0),
+                OrderRecord::new("req-close".to_string(), OrderRecordKind::TraderClose, t1),
+            ],
+        )]);
+        let history = merge_trade_history(
+            records,
+            vec![snapshot("req-settle", t2), snapshot("req-close", t1)],
+        );
+        let ids: Vec<_> = history
+            .iter()
+            .map(|e| e.record.request_id.as_str())
+            .collect();
+        assert_eq!(ids, ["req-open", "req-close", "req-settle"]);
+        assert!(history[0].snapshot.is_none());
+        assert_eq!(
+            history[1].snapshot.as_ref().unwrap().realized_pnl,
+            Some(40.0)
+        );
+        assert_eq!(history[2].record.status, "SETTLED");
+    }
+}
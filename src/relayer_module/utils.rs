@@ -1,42 +1,156 @@
 use crate::{
-    nyks_rpc::rpcclient::{
-        method::{Method, MethodTypeURL},
-        txrequest::{RpcBody, RpcRequest, TxParams},
-        txresult::parse_tx_response,
+    nyks_rpc::{
+        lcd,
+        rpcclient::{
+            fee_bump::TxAttempt,
+            gas::estimate_tx_fee,
+            method::{sign_msgs_with_fee_config, Method, MethodTypeURL},
+            txrequest::{RpcBody, RpcRequest, TxParams},
+            txresult::parse_tx_response,
+        },
+    },
+    config::TxFeeConfig,
+    error::{Result as WalletResult, WalletError},
+    log_privacy::{LoggedAddress, LoggedDebug},
+    relayer_module::{
+        clock::{Clock, SystemClock},
+        relayer_api::RelayerApi,
+        relayer_types::TransactionHashArgs,
     },
-    relayer_module::{relayer_api::RelayerJsonRpcClient, relayer_types::TransactionHashArgs},
+    wallet::faucet::{try_fetch_account_details, Account},
     zkos_accounts::ZkAccountDB,
     *,
 };
+use jsonrpsee::core::client::Error as RpcError;
 use log::{debug, error, info};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::future::Future;
+use std::sync::Arc;
 use tokio::time::{sleep, Duration};
-use twilight_client_sdk::{
+use crate::compat::{
     relayer_rpcclient::method::UtxoDetailResponse,
     relayer_types::{OrderStatus, TxHash},
     zkvm::IOType,
 };
 
-// Retry configuration constants
-const DEFAULT_UTXO_ATTEMPTS: u32 = 30;
-const TXHASH_ATTEMPTS: u32 = 60;
-const INITIAL_RETRY_DELAY_MS: u64 = 200;
-const MAX_RETRY_DELAY_MS: u64 = 1_000;
-const BACKOFF_FACTOR: f64 = 1.5;
+pub use crate::config::RetryPolicy;
 
-/// Calculate retry delay with exponential backoff and jitter.
+/// Retry delay for the chain polling helpers, from the delays of
+/// [`RetryPolicy::from_env`] (so the `RELAYER_RETRY_*` overrides and the
+/// config file's `[retry]` section apply to them too).
 fn retry_delay(attempt: u32) -> Duration {
-    let base = INITIAL_RETRY_DELAY_MS as f64 * BACKOFF_FACTOR.powi(attempt as i32);
-    let capped = base.min(MAX_RETRY_DELAY_MS as f64);
-    // Add ~30% jitter to avoid thundering herd
-    let jitter = fastrand::f64() * capped * 0.1;
-    Duration::from_millis((capped + jitter) as u64)
+    RetryPolicy::from_env().delay(attempt)
+}
+
+/// Whether a failed call may succeed if made again.
+///
+/// Every retry decision goes through this one classification: the lookup
+/// helpers, [`OrderWalletError::is_retryable`] and the `retriable` flag of
+/// an account's stored error. [`Transient`](Self::Transient) is an
+/// unreachable, slow or rate-limiting endpoint; [`NotFound`](Self::NotFound)
+/// a lookup that worked but found no output yet, which is worth polling for
+/// while the index catches up; [`Permanent`](Self::Permanent) a malformed
+/// request or one the server rejects, returned on the first attempt, tagged
+/// with its class in the error message.
+///
+/// [`OrderWalletError::is_retryable`]: crate::error::OrderWalletError::is_retryable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryClass {
+    Transient,
+    NotFound,
+    Permanent,
+}
+
+/// Substrings, besides those of [`is_unreachable_error`], of errors that
+/// clear up by waiting, matched case-insensitively.
+const TRANSIENT_MARKERS: &[&str] = &[
+    "try again",
+    "rate limit",
+    "too many requests",
+    "not found after",
+];
+
+/// Substrings of errors about the request itself, matched
+/// case-insensitively. A bare "invalid" or "decode" is not enough: a
+/// truncated or proxied response fails to decode as well.
+const PERMANENT_MARKERS: &[&str] = &[
+    "invalid address",
+    "invalid account",
+    "invalid hex",
+    "hex decode",
+    "malformed",
+    "bad request",
+    "unauthorized",
+    "forbidden",
+    "method not found",
+];
+
+impl RetryClass {
+    /// Classifies an error message; see the [type docs](Self). Messages
+    /// nothing is known about are permanent.
+    pub fn of_message(error: &str) -> Self {
+        Self::classify(error).unwrap_or(RetryClass::Permanent)
+    }
+
+    /// Classifies an error from a ZkOS UTXO lookup like
+    /// [`of_message`](Self::of_message), except that unrecognised errors are
+    /// transient, which keeps the helpers polling as before.
+    pub fn of_lookup_error(error: &str) -> Self {
+        Self::classify(error).unwrap_or(RetryClass::Transient)
+    }
+
+    fn classify(error: &str) -> Option<Self> {
+        let lower = error.to_lowercase();
+        let has = |markers: &[&str]| markers.iter().any(|marker| lower.contains(marker));
+        // A failed connection can come wrapped in any other error.
+        if is_unreachable_error(error) || has(TRANSIENT_MARKERS) {
+            Some(RetryClass::Transient)
+        } else if is_utxo_not_found(error) {
+            Some(RetryClass::NotFound)
+        } else if has(PERMANENT_MARKERS) {
+            Some(RetryClass::Permanent)
+        } else {
+            None
+        }
+    }
+
+    /// Classifies a relayer RPC error: transport failures and timeouts are
+    /// transient, everything the relayer answered or that could not be
+    /// encoded is permanent.
+    pub fn of_rpc_error(error: &RpcError) -> Self {
+        match error {
+            RpcError::Transport(_) | RpcError::RestartNeeded(_) | RpcError::RequestTimeout => {
+                RetryClass::Transient
+            }
+            _ => RetryClass::Permanent,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        *self != RetryClass::Permanent
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetryClass::Transient => "transient",
+            RetryClass::NotFound => "not found",
+            RetryClass::Permanent => "permanent",
+        }
+    }
+}
+
+impl std::fmt::Display for RetryClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Constructs a `MsgMintBurnTradingBtc` for the given wallet/zk account, then signs it and
 /// returns the base64-encoded transaction ready for broadcast.
+///
+/// The fee is the wallet's [`TxFeeConfig`] as configured; see
+/// [`sign_msg_mint_burn_trading_btc_estimated`] to simulate first.
 pub fn build_and_sign_msg_mint_burn_trading_btc(
     wallet: &Wallet,
     zk_accounts: &ZkAccountDB,
@@ -46,24 +160,108 @@ pub fn build_and_sign_msg_mint_burn_trading_btc(
     amount: u64,
     mint_or_burn: bool,
 ) -> Result<String, String> {
+    build_and_sign_msg_mint_burn_trading_btc_with_fee_config(
+        wallet,
+        zk_accounts,
+        index,
+        sequence,
+        account_number,
+        amount,
+        mint_or_burn,
+        &wallet.chain_config.tx_fee,
+    )
+}
+
+/// [`build_and_sign_msg_mint_burn_trading_btc`] paying `fee_nyks` instead of
+/// the configured fee amount.
+#[allow(clippy::too_many_arguments)]
+pub fn build_and_sign_msg_mint_burn_trading_btc_with_fee(
+    wallet: &Wallet,
+    zk_accounts: &ZkAccountDB,
+    index: u64,
+    sequence: u64,
+    account_number: u64,
+    amount: u64,
+    mint_or_burn: bool,
+    fee_nyks: u64,
+) -> Result<String, String> {
+    build_and_sign_msg_mint_burn_trading_btc_with_fee_config(
+        wallet,
+        zk_accounts,
+        index,
+        sequence,
+        account_number,
+        amount,
+        mint_or_burn,
+        &wallet.chain_config.tx_fee.clone().with_fee_amount(fee_nyks),
+    )
+}
+
+/// [`build_and_sign_msg_mint_burn_trading_btc`] with the fee and gas limit of `fee`.
+#[allow(clippy::too_many_arguments)]
+pub fn build_and_sign_msg_mint_burn_trading_btc_with_fee_config(
+    wallet: &Wallet,
+    zk_accounts: &ZkAccountDB,
+    index: u64,
+    sequence: u64,
+    account_number: u64,
+    amount: u64,
+    mint_or_burn: bool,
+    fee: &TxFeeConfig,
+) -> Result<String, String> {
+    let msg = mint_burn_trading_btc_msg(wallet, zk_accounts, index, amount, mint_or_burn)?;
+    sign_msg_mint_burn_trading_btc(wallet, msg, sequence, account_number, fee)
+}
+
+/// The `MsgMintBurnTradingBtc` moving `amount` between the wallet and zk account `index`.
+pub fn mint_burn_trading_btc_msg(
+    wallet: &Wallet,
+    zk_accounts: &ZkAccountDB,
+    index: u64,
+    amount: u64,
+    mint_or_burn: bool,
+) -> Result<MsgMintBurnTradingBtc, String> {
     // Retrieve zk account (index is 1-based from setup)
     let account_idx = index;
     let zk_account = zk_accounts
         .get_account(&account_idx)
         .map_err(|e| e.to_string())?;
 
-    // Build message
-    let msg = MsgMintBurnTradingBtc {
+    Ok(MsgMintBurnTradingBtc {
         mint_or_burn,
         btc_value: amount,
         qq_account: zk_account.qq_address.clone(),
         encrypt_scalar: zk_account.scalar.clone(),
         twilight_address: wallet.twilightaddress.clone(),
-    };
+    })
+}
 
-    // Serialize into Any and sign
+/// Signs `msg` with the wallet key and `fee`, returning the base64-encoded transaction.
+pub fn sign_msg_mint_burn_trading_btc(
+    wallet: &Wallet,
+    msg: MsgMintBurnTradingBtc,
+    sequence: u64,
+    account_number: u64,
+    fee: &TxFeeConfig,
+) -> Result<String, String> {
+    sign_msgs_mint_burn_trading_btc(wallet, vec![msg], sequence, account_number, fee)
+}
+
+/// [`sign_msg_mint_burn_trading_btc`] for a transaction carrying all of
+/// `msgs`, in order.
+pub fn sign_msgs_mint_burn_trading_btc(
+    wallet: &Wallet,
+    msgs: Vec<MsgMintBurnTradingBtc>,
+    sequence: u64,
+    account_number: u64,
+    fee: &TxFeeConfig,
+) -> Result<String, String> {
+    // Serialize each into Any and sign them together
     let method_type = MethodTypeURL::MsgMintBurnTradingBtc;
-    let any_msg = method_type.type_url(msg);
+    let any_msgs = msgs
+        .into_iter()
+        .map(|msg| method_type.type_url(msg))
+        .collect();
 
     let sk = wallet
         .signing_key()
@@ -72,11 +270,55 @@ pub fn build_and_sign_msg_mint_burn_trading_btc(
         .public_key()
         .map_err(|e| format!("Failed to get public key: {}", e))?;
 
-    let signed_tx = method_type
-        .sign_msg::<MsgMintBurnTradingBtc>(any_msg, pk, sequence, account_number, sk)
-        .map_err(|e| e.to_string())?;
+    sign_msgs_with_fee_config(any_msgs, pk, sequence, account_number, sk, fee)
+        .map_err(|e| e.to_string())
+}
 
-    Ok(signed_tx)
+/// [`sign_msg_mint_burn_trading_btc`] with the wallet's [`TxFeeConfig`], after
+/// simulating a draft against the LCD when the config sets a gas adjustment.
+pub async fn sign_msg_mint_burn_trading_btc_estimated(
+    wallet: &Wallet,
+    msg: MsgMintBurnTradingBtc,
+    sequence: u64,
+    account_number: u64,
+) -> Result<String, String> {
+    sign_msgs_mint_burn_trading_btc_estimated(wallet, vec![msg], sequence, account_number).await
+}
+
+/// [`sign_msg_mint_burn_trading_btc_estimated`] for a transaction carrying
+/// all of `msgs`, simulated as a whole.
+pub async fn sign_msgs_mint_burn_trading_btc_estimated(
+    wallet: &Wallet,
+    msgs: Vec<MsgMintBurnTradingBtc>,
+    sequence: u64,
+    account_number: u64,
+) -> Result<String, String> {
+    let fee = estimate_mint_burn_fee(wallet, &msgs, sequence, account_number).await?;
+    sign_msgs_mint_burn_trading_btc(wallet, msgs, sequence, account_number, &fee)
+}
+
+/// The fee to sign `msgs` with, from a draft signed at `sequence` when the
+/// wallet's fee config simulates.
+pub async fn estimate_mint_burn_fee(
+    wallet: &Wallet,
+    msgs: &[MsgMintBurnTradingBtc],
+    sequence: u64,
+    account_number: u64,
+) -> Result<TxFeeConfig, String> {
+    estimate_tx_fee(
+        &wallet.chain_config.tx_fee,
+        &wallet.chain_config.lcd_endpoint,
+        |draft_fee| {
+            sign_msgs_mint_burn_trading_btc(
+                wallet,
+                msgs.to_vec(),
+                sequence,
+                account_number,
+                draft_fee,
+            )
+        },
+    )
+    .await
 }
 
 /// Broadcasts the signed transaction to the NYKS RPC endpoint and logs the response.
@@ -89,7 +331,9 @@ pub async fn send_tx_to_chain(signed_tx: String, rpc_endpoint: &str) -> Result<T
     let rpc_endpoint = rpc_endpoint.to_string();
 
     // Execute the blocking HTTP request on a separate thread
-    let response = tokio::task::spawn_blocking(move || tx_send.send(rpc_endpoint))
+    let response = tokio::task::spawn_blocking(crate::telemetry::in_current_context(move || {
+        tx_send.send(rpc_endpoint)
+    }))
         .await
         .map_err(|e| format!("Failed to send RPC request: {}", e))?;
 
@@ -107,7 +351,13 @@ pub async fn send_tx_to_chain(signed_tx: String, rpc_endpoint: &str) -> Result<T
                 "transaction sent successfully, tx hash: {} with code: {}",
                 tx_hash, code
             );
-            Ok(TxResult { tx_hash, code })
+            Ok(TxResult {
+                tx_hash,
+                code,
+                simulated: false,
+                attempts: Vec::new(),
+                log: result.get_log(),
+            })
         }
         Err(e) => Err(format!("Failed to get tx result: {}", e)),
     }
@@ -119,62 +369,137 @@ pub async fn fetch_utxo_details_with_retry(
     account_id: String,
     io_type: IOType,
 ) -> Result<UtxoDetailResponse, String> {
+    fetch_utxo_details_with_policy(account_id, io_type, &RetryPolicy::from_env()).await
+}
+
+/// [`fetch_utxo_details_with_retry`] with the attempts and delays of `policy`.
+pub async fn fetch_utxo_details_with_policy(
+    account_id: String,
+    io_type: IOType,
+    policy: &RetryPolicy,
+) -> Result<UtxoDetailResponse, String> {
+    fetch_utxo_details_with_lookup(account_id, io_type, policy, |account_id, io_type| {
+        crate::compat::chain::get_utxo_details_by_address(account_id, io_type)
+    })
+    .await
+}
+
+/// [`fetch_utxo_details_with_policy`] querying through the blocking `lookup`
+/// instead of the chain. Transient errors are retried per `policy`; a
+/// [`RetryClass::Permanent`] one is returned after the first call.
+pub async fn fetch_utxo_details_with_lookup<L>(
+    account_id: String,
+    io_type: IOType,
+    policy: &RetryPolicy,
+    lookup: L,
+) -> Result<UtxoDetailResponse, String>
+where
+    L: Fn(String, IOType) -> Result<UtxoDetailResponse, String> + Send + Sync + 'static,
+{
+    let max_attempts = policy.utxo_max_attempts;
+    let lookup = Arc::new(lookup);
     let mut attempts = 0;
-    debug!("fetch_utxo_details_with_retry: account_id: {}", account_id);
+    debug!(
+        "fetch_utxo_details_with_retry: account_id: {}",
+        LoggedAddress(&account_id)
+    );
     loop {
         let account_id_clone = account_id.clone();
-        match tokio::task::spawn_blocking(move || {
-            twilight_client_sdk::chain::get_utxo_details_by_address(account_id_clone, io_type)
-        })
-        .await
-        {
+        let lookup = lookup.clone();
+        match tokio::task::spawn_blocking(move || lookup(account_id_clone, io_type)).await {
             Ok(response) => match response {
                 Ok(utxo_detail) => {
-                    debug!("utxo_detail: {:?}, account_id: {}", utxo_detail, account_id);
+                    debug!(
+                        "utxo_detail: {}, account_id: {}",
+                        LoggedDebug(&utxo_detail),
+                        LoggedAddress(&account_id)
+                    );
                     return Ok(utxo_detail);
                 }
                 Err(err) => {
                     attempts += 1;
-                    if attempts >= DEFAULT_UTXO_ATTEMPTS {
+                    let class = RetryClass::of_lookup_error(&err);
+                    if class == RetryClass::Permanent {
+                        error!(
+                            "Failed to get utxo details ({}): {} for account_id: {}",
+                            class,
+                            err,
+                            LoggedAddress(&account_id)
+                        );
+                        return Err(format!("Failed to get utxo details ({}): {}", class, err));
+                    }
+                    if attempts >= max_attempts {
                         error!(
-                            "Failed to get utxo details after {} attempts: {} for account_id: {}",
-                            DEFAULT_UTXO_ATTEMPTS, err, account_id
+                            "Failed to get utxo details after {} attempts ({}): {} for account_id: {}",
+                            max_attempts,
+                            class,
+                            err,
+                            LoggedAddress(&account_id)
                         );
                         return Err(format!(
-                            "Failed to get utxo details after {} attempts: {}",
-                            DEFAULT_UTXO_ATTEMPTS, err
+                            "Failed to get utxo details after {} attempts ({}): {}",
+                            max_attempts, class, err
                         ));
                     }
                 }
             },
             Err(e) => {
                 attempts += 1;
-                if attempts >= DEFAULT_UTXO_ATTEMPTS {
+                if attempts >= max_attempts {
                     error!(
                         "Failed to spawn blocking task after {} attempts: {}",
-                        DEFAULT_UTXO_ATTEMPTS, e
+                        max_attempts, e
                     );
                     return Err(format!("Failed to spawn blocking task: {}", e));
                 }
             }
         }
-        sleep(retry_delay(attempts)).await;
+        sleep(policy.delay(attempts)).await;
     }
 }
 
+/// Whether an error only says that an endpoint could not be reached or did
+/// not answer in time, matched case-insensitively. Such a call can succeed
+/// later without anything else changing.
+pub fn is_unreachable_error(error: &str) -> bool {
+    let lower = error.to_lowercase();
+    [
+        "connection",
+        "timed out",
+        "timeout",
+        "error sending request",
+        "failed to send rpc request",
+        "temporarily unavailable",
+        "service unavailable",
+        "dns error",
+    ]
+    .iter()
+    .any(|marker| lower.contains(marker))
+}
+
+/// Whether a UTXO lookup error means the chain has no output at the address,
+/// as opposed to the lookup itself failing.
+pub fn is_utxo_not_found(error: &str) -> bool {
+    error.contains("UTXO not found")
+}
+
 pub async fn fetch_utxo_details_with_once(
     account_id: String,
     io_type: IOType,
 ) -> Result<UtxoDetailResponse, String> {
     let account_id_clone = account_id.clone();
     match tokio::task::spawn_blocking(move || {
-        twilight_client_sdk::chain::get_utxo_details_by_address(account_id_clone, io_type)
+        crate::compat::chain::get_utxo_details_by_address(account_id_clone, io_type)
     })
     .await
     {
         Ok(response) => match response {
             Ok(utxo_detail) => {
-                debug!("utxo_detail: {:?}, account_id: {}", utxo_detail, account_id);
+                debug!(
+                    "utxo_detail: {}, account_id: {}",
+                    LoggedDebug(&utxo_detail),
+                    LoggedAddress(&account_id)
+                );
                 return Ok(utxo_detail);
             }
             Err(err) => {
@@ -187,30 +512,72 @@ pub async fn fetch_utxo_details_with_once(
     }
 }
 
+/// Polls the relayer for the tx hash of `request_id` until it is indexed. Transport
+/// errors and timeouts are retried like an empty answer; an error the relayer
+/// returns is [`RetryClass::Permanent`] and ends the poll.
 pub async fn fetch_tx_hash_with_retry(
     request_id: &str,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
 ) -> Result<TxHash, String> {
+    fetch_tx_hash_with_clock(request_id, relayer_api_client, &SystemClock).await
+}
+
+/// [`fetch_tx_hash_with_retry`] waiting between polls on `clock`.
+pub async fn fetch_tx_hash_with_clock(
+    request_id: &str,
+    relayer_api_client: &dyn RelayerApi,
+    clock: &dyn Clock,
+) -> Result<TxHash, String> {
+    fetch_tx_hash_with_lookup(relayer_api_client.retry_policy(), clock, || {
+        relayer_api_client.transaction_hashes(TransactionHashArgs::RequestId {
+            id: request_id.to_string(),
+            status: None,
+            limit: None,
+            offset: None,
+        })
+    })
+    .await
+}
+
+/// [`fetch_tx_hash_with_clock`] querying through `lookup` instead of the relayer.
+pub async fn fetch_tx_hash_with_lookup<L, F>(
+    policy: &RetryPolicy,
+    clock: &dyn Clock,
+    mut lookup: L,
+) -> Result<TxHash, String>
+where
+    L: FnMut() -> F,
+    F: Future<Output = Result<Vec<TxHash>, RpcError>>,
+{
     let mut attempts = 0;
     loop {
-        let response = relayer_api_client
-            .transaction_hashes(TransactionHashArgs::RequestId {
-                id: request_id.to_string(),
-                status: None,
-                limit: None,
-                offset: None,
-            })
-            .await
-            .map_err(|e| e.to_string())?;
+        let response = match lookup().await {
+            Ok(response) => response,
+            Err(e) => {
+                attempts += 1;
+                let class = RetryClass::of_rpc_error(&e);
+                if class == RetryClass::Permanent {
+                    return Err(format!("Failed to get tx hash ({}): {}", class, e));
+                }
+                if attempts >= policy.max_attempts {
+                    return Err(format!(
+                        "Failed to get tx hash after {} attempts ({}): {}",
+                        policy.max_attempts, class, e
+                    ));
+                }
+                clock.sleep(policy.delay(attempts)).await;
+                continue;
+            }
+        };
         if response.is_empty() {
             attempts += 1;
-            if attempts >= TXHASH_ATTEMPTS {
+            if attempts >= policy.max_attempts {
                 return Err(format!(
                     "Failed to get tx hash after {} attempts",
-                    TXHASH_ATTEMPTS
+                    policy.max_attempts
                 ));
             }
-            sleep(retry_delay(attempts)).await;
+            clock.sleep(policy.delay(attempts)).await;
         } else {
             let latest_tx = response
                 .iter()
@@ -224,7 +591,7 @@ pub async fn fetch_tx_hash_with_retry(
 
 pub async fn fetch_tx_hash_with_once(
     request_id: &str,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
 ) -> Result<TxHash, String> {
     let response = relayer_api_client
         .transaction_hashes(TransactionHashArgs::RequestId {
@@ -251,9 +618,10 @@ pub async fn fetch_tx_hash_with_once(
 
 pub async fn fetch_tx_hash_with_retry_with_close_order(
     request_id: &str,
-    relayer_api_client: &RelayerJsonRpcClient,
-    _order_type: twilight_client_sdk::relayer_types::OrderType,
+    relayer_api_client: &dyn RelayerApi,
+    _order_type: crate::compat::relayer_types::OrderType,
 ) -> Result<TxHash, String> {
+    let policy = relayer_api_client.retry_policy();
     let mut attempts = 0;
     loop {
         let response = relayer_api_client
@@ -267,13 +635,13 @@ pub async fn fetch_tx_hash_with_retry_with_close_order(
             .map_err(|e| e.to_string())?;
         if response.is_empty() {
             attempts += 1;
-            if attempts >= TXHASH_ATTEMPTS {
+            if attempts >= policy.max_attempts {
                 return Err(format!(
                     "Failed to get tx hash after {} attempts",
-                    TXHASH_ATTEMPTS
+                    policy.max_attempts
                 ));
             }
-            sleep(retry_delay(attempts)).await;
+            sleep(policy.delay(attempts)).await;
         } else {
             // Filter out specific cancelled order statuses
             let filtered_response: Vec<_> = response
@@ -289,13 +657,13 @@ pub async fn fetch_tx_hash_with_retry_with_close_order(
             if filtered_response.is_empty() {
                 // Do not return; continue to retry in the outer loop
                 attempts += 1;
-                if attempts >= TXHASH_ATTEMPTS {
+                if attempts >= policy.max_attempts {
                     return Err(format!(
                         "Failed to get tx hash after {} attempts",
-                        TXHASH_ATTEMPTS
+                        policy.max_attempts
                     ));
                 }
-                sleep(retry_delay(attempts)).await;
+                sleep(policy.delay(attempts)).await;
                 continue;
             }
 
@@ -322,8 +690,10 @@ pub async fn fetch_tx_hash_with_retry_with_close_order(
 pub async fn fetch_tx_hash_with_account_address_retry(
     account_address: &str,
     order_status: Option<OrderStatus>,
-    relayer_api_client: &RelayerJsonRpcClient,
+    relayer_api_client: &dyn RelayerApi,
+    clock: &dyn Clock,
 ) -> Result<TxHash, String> {
+    let policy = relayer_api_client.retry_policy();
     let mut attempts = 0;
     loop {
         let response = relayer_api_client
@@ -337,13 +707,13 @@ pub async fn fetch_tx_hash_with_account_address_retry(
             .map_err(|e| e.to_string())?;
         if response.is_empty() {
             attempts += 1;
-            if attempts >= TXHASH_ATTEMPTS {
+            if attempts >= policy.max_attempts {
                 return Err(format!(
                     "Failed to get tx hash after {} attempts",
-                    TXHASH_ATTEMPTS
+                    policy.max_attempts
                 ));
             }
-            sleep(retry_delay(attempts)).await;
+            clock.sleep(policy.delay(attempts)).await;
         } else {
             let latest_tx = response
                 .iter()
@@ -360,6 +730,16 @@ pub async fn fetch_tx_hash_with_account_address_retry(
 pub struct TxResult {
     pub tx_hash: String,
     pub code: u32,
+    /// Built and signed but not broadcast: the result of a dry run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub simulated: bool,
+    /// Every broadcast of a fee-bumped transaction, the confirmed one
+    /// included; empty when the transaction was not fee bumped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<TxAttempt>,
+    /// The chain's log for the broadcast, e.g. why CheckTx rejected it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<String>,
 }
 
 /// Repeatedly queries the chain for UTXO details until the UTXO is removed (not found)
@@ -368,21 +748,39 @@ pub async fn fetch_removed_utxo_details_with_retry(
     account_id: String,
     io_type: IOType,
 ) -> Result<(), String> {
+    fetch_removed_utxo_details_with_policy(
+        account_id,
+        io_type,
+        &RetryPolicy::from_env(),
+        &SystemClock,
+    )
+    .await
+}
+
+/// [`fetch_removed_utxo_details_with_retry`] with the attempts and delays of
+/// `policy`, waiting on `clock`.
+pub async fn fetch_removed_utxo_details_with_policy(
+    account_id: String,
+    io_type: IOType,
+    policy: &RetryPolicy,
+    clock: &dyn Clock,
+) -> Result<(), String> {
+    let max_attempts = policy.utxo_max_attempts;
     let mut attempts = 0;
     debug!(
         "fetch_removed_utxo_details_with_retry: account_id: {}",
-        account_id
+        LoggedAddress(&account_id)
     );
     loop {
         let account_id_clone = account_id.clone();
         match tokio::task::spawn_blocking(move || {
-            twilight_client_sdk::chain::get_utxo_details_by_address(account_id_clone, io_type)
+            crate::compat::chain::get_utxo_details_by_address(account_id_clone, io_type)
         })
         .await
         {
             Ok(response) => match response {
                 Err(err) => {
-                    if err.contains("UTXO not found") {
+                    if is_utxo_not_found(&err) {
                         return Ok(());
                     } else {
                         return Err(format!("Failed to remove utxo details: {}", err));
@@ -390,29 +788,29 @@ pub async fn fetch_removed_utxo_details_with_retry(
                 }
                 Ok(_) => {
                     if attempts == 0 {
-                        sleep(Duration::from_secs(2)).await;
+                        clock.sleep(Duration::from_secs(2)).await;
                     }
                     attempts += 1;
-                    if attempts >= DEFAULT_UTXO_ATTEMPTS {
+                    if attempts >= max_attempts {
                         return Err(format!(
                             "Failed to remove utxo details after {} attempts: {}",
-                            DEFAULT_UTXO_ATTEMPTS, account_id
+                            max_attempts, account_id
                         ));
                     }
                 }
             },
             Err(e) => {
                 attempts += 1;
-                if attempts >= DEFAULT_UTXO_ATTEMPTS {
+                if attempts >= max_attempts {
                     error!(
                         "Failed to spawn blocking task after {} attempts: {}",
-                        DEFAULT_UTXO_ATTEMPTS, e
+                        max_attempts, e
                     );
                     return Err(format!("Failed to spawn blocking task: {}", e));
                 }
             }
         }
-        sleep(retry_delay(attempts)).await;
+        clock.sleep(policy.delay(attempts)).await;
     }
 }
 
@@ -423,14 +821,10 @@ const TX_STATUS_ATTEMPTS: u32 = 10;
 /// Returns `Ok(())` if `tx_response.code == 0`, otherwise returns an error with the raw_log.
 pub async fn check_tx_status(tx_hash: &str, lcd_endpoint: &str) -> Result<(), String> {
     let url = format!("{}/cosmos/tx/v1beta1/txs/{}", lcd_endpoint, tx_hash);
-    let client = Client::new();
     let mut attempts = 0;
     info!("Checking tx status on chain: {}", tx_hash);
     loop {
-        let response = client
-            .get(&url)
-            .header("accept", "application/json")
-            .send()
+        let response = lcd::get(&url)
             .await
             .map_err(|e| format!("Failed to query tx status: {}", e))?;
 
@@ -489,9 +883,91 @@ pub async fn check_tx_status(tx_hash: &str, lcd_endpoint: &str) -> Result<(), St
     }
 }
 
+const ACCOUNT_INFO_ATTEMPTS: u32 = 20;
+const ACCOUNT_PUBKEY_ATTEMPTS: u32 = 5;
+
+/// Fetches the on-chain account for `address`, retrying while the LCD has not indexed it yet.
+///
+/// Retries on 404/not-found for up to [`ACCOUNT_INFO_ATTEMPTS`] and returns
+/// [`WalletError::AccountNotOnChain`] if the account never appears. An account without a
+/// `pub_key` is retried a few more times (the LCD may still be catching up) and then
+/// returned as-is, since accounts that have never signed a tx legitimately have none.
+/// Any other LCD error is returned immediately.
+pub async fn fetch_account_details_with_retry(
+    address: &str,
+    lcd_endpoint: &str,
+) -> WalletResult<Account> {
+    fetch_account_details_with_attempts(address, lcd_endpoint, ACCOUNT_INFO_ATTEMPTS).await
+}
+
+/// [`fetch_account_details_with_retry`] with an explicit attempt budget.
+pub async fn fetch_account_details_with_attempts(
+    address: &str,
+    lcd_endpoint: &str,
+    max_attempts: u32,
+) -> WalletResult<Account> {
+    let mut attempts = 0;
+    let mut pubkey_attempts = 0;
+    loop {
+        attempts += 1;
+        match try_fetch_account_details(address, lcd_endpoint).await {
+            Ok(response) => {
+                let account = response.account;
+                let has_pub_key = account
+                    .pub_key
+                    .as_ref()
+                    .map(|k| !k.is_null())
+                    .unwrap_or(false);
+                pubkey_attempts += 1;
+                if has_pub_key
+                    || pubkey_attempts >= ACCOUNT_PUBKEY_ATTEMPTS
+                    || attempts >= max_attempts
+                {
+                    return Ok(account);
+                }
+                debug!(
+                    "Account {} has no pub_key yet (attempt {}/{}), retrying",
+                    LoggedAddress(address),
+                    pubkey_attempts,
+                    ACCOUNT_PUBKEY_ATTEMPTS
+                );
+            }
+            Err(e) if e.is_not_found() => {
+                if attempts >= max_attempts {
+                    error!(
+                        "Account {} not found on chain after {} attempts",
+                        LoggedAddress(address),
+                        attempts
+                    );
+                    return Err(WalletError::AccountNotOnChain {
+                        address: address.to_string(),
+                        attempts,
+                    });
+                }
+                debug!(
+                    "Account {} not indexed yet (attempt {}/{})",
+                    LoggedAddress(address),
+                    attempts,
+                    max_attempts
+                );
+            }
+            Err(e) => return Err(WalletError::WalletAccountInfo(e.to_string())),
+        }
+        sleep(retry_delay(attempts)).await;
+    }
+}
+
+/// Whether a CheckTx code means the tx was signed with a stale sequence
+/// and should be re-signed. A failed signature check (code 4,
+/// ErrUnauthorized) is not: the same key signs the retry.
+pub fn is_stale_signer_code(code: u32) -> bool {
+    code == crate::wallet::nonce_manager::SEQUENCE_MISMATCH_CODE
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
 
     #[tokio::test]
     async fn test_check_tx_status_success() {
@@ -531,4 +1007,295 @@ mod tests {
             err
         );
     }
+
+    fn not_found_body(address: &str) -> String {
+        format!(
+            r#"{{"code":5,"message":"rpc error: code = NotFound desc = account {} not found: key not found","details":[]}}"#,
+            address
+        )
+    }
+
+    fn account_body(address: &str, account_number: u64, sequence: u64) -> String {
+        format!(
+            r#"{{"account":{{"@type":"/cosmos.auth.v1beta1.BaseAccount","address":"{}","pub_key":{{"@type":"/cosmos.crypto.secp256k1.PubKey","key":"AA=="}},"account_number":"{}","sequence":"{}"}}}}"#,
+            address, account_number, sequence
+        )
+    }
+
+    const ACCOUNTS: &str = "/cosmos/auth/v1beta1/accounts/";
+
+    #[tokio::test]
+    async fn test_fetch_account_details_retries_until_indexed() {
+        let address = "twilight1mockaccount";
+        let chain = MockChain::spawn();
+        chain.route(
+            ACCOUNTS,
+            vec![
+                MockResponse::status(404, not_found_body(address)),
+                MockResponse::status(404, not_found_body(address)),
+                MockResponse::ok(account_body(address, 7, 3)),
+            ],
+        );
+        let account = fetch_account_details_with_attempts(address, chain.url(), 5)
+            .await
+            .unwrap();
+        assert_eq!(account.account_number, 7);
+        assert_eq!(account.sequence, 3);
+        assert_eq!(chain.count(ACCOUNTS), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_account_details_permanent_absence() {
+        let address = "twilight1neverindexed";
+        let chain = MockChain::spawn();
+        chain.route(
+            ACCOUNTS,
+            vec![MockResponse::status(404, not_found_body(address))],
+        );
+        let err = fetch_account_details_with_attempts(address, chain.url(), 3)
+            .await
+            .unwrap_err();
+        match err {
+            WalletError::AccountNotOnChain { address: a, attempts } => {
+                assert_eq!(a, address);
+                assert_eq!(attempts, 3);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(chain.count(ACCOUNTS), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_account_details_other_error_not_retried() {
+        // 5xx is retried inside `nyks_rpc::lcd`; a 4xx other than 404 is final.
+        let address = "twilight1broken";
+        let chain = MockChain::spawn();
+        chain.route(ACCOUNTS, vec![MockResponse::status(400, "bad request")]);
+        let err = fetch_account_details_with_attempts(address, chain.url(), 10)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WalletError::WalletAccountInfo(_)));
+        assert_eq!(chain.count(ACCOUNTS), 1);
+    }
+
+    #[test]
+    fn test_is_stale_signer_code() {
+        assert!(is_stale_signer_code(32));
+        assert!(!is_stale_signer_code(4));
+        assert!(!is_stale_signer_code(0));
+        assert!(!is_stale_signer_code(5));
+    }
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            utxo_max_attempts: max_attempts,
+            initial_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_retry_class_of_lookup_error() {
+        for transient in [
+            "connection refused",
+            "error sending request: operation timed out",
+            // Neither is about the request, whatever the wording.
+            "error decoding response body: connection closed before message completed",
+            "invalid response: 503 Service Unavailable",
+            "429 Too Many Requests",
+        ] {
+            assert_eq!(RetryClass::of_message(transient), RetryClass::Transient);
+        }
+        for not_found in [
+            "UTXO not found",
+            "Failed to get utxo details: UTXO not found",
+        ] {
+            assert_eq!(RetryClass::of_message(not_found), RetryClass::NotFound);
+            assert!(RetryClass::of_message(not_found).is_retryable());
+        }
+        for permanent in [
+            "Invalid address",
+            "hex decode error: odd length",
+            "400 Bad Request",
+        ] {
+            assert_eq!(
+                RetryClass::of_lookup_error(permanent),
+                RetryClass::Permanent
+            );
+        }
+        // Unknown errors keep a lookup polling but are not retried elsewhere.
+        let unknown = "position_value overflow";
+        assert_eq!(RetryClass::of_lookup_error(unknown), RetryClass::Transient);
+        assert_eq!(RetryClass::of_message(unknown), RetryClass::Permanent);
+        assert_eq!(
+            RetryClass::of_rpc_error(&RpcError::RequestTimeout),
+            RetryClass::Transient
+        );
+        assert_eq!(
+            RetryClass::of_rpc_error(&RpcError::Custom("invalid params".to_string())),
+            RetryClass::Permanent
+        );
+    }
+
+    #[tokio::test]
+    async fn test_utxo_lookup_permanent_error_short_circuits() {
+        let calls = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let counter = calls.clone();
+        let err = fetch_utxo_details_with_lookup(
+            "not-an-address".to_string(),
+            IOType::Coin,
+            &fast_policy(10),
+            move |_, _| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Err("Invalid address: not-an-address".to_string())
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(err.contains("(permanent)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_utxo_lookup_transient_error_uses_budget() {
+        let calls = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let counter = calls.clone();
+        let err = fetch_utxo_details_with_lookup(
+            "acct".to_string(),
+            IOType::Coin,
+            &fast_policy(3),
+            move |_, _| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Err("UTXO not found".to_string())
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert!(err.contains("after 3 attempts (transient)"), "{}", err);
+        assert!(is_utxo_not_found(&err));
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_utxo_lookup_logs_are_redacted_by_the_installed_logger() {
+        use crate::log_privacy::{fingerprint, test_logger, LogPrivacy};
+        use crate::relayer_module::test_fixtures::coin_utxo;
+        use crate::zkos_accounts::zkaccount::ZkAccount;
+
+        let seed = secrecy::SecretString::new("log-privacy-test-seed".to_string());
+        let account = ZkAccount::from_seed(0, &seed, 1_000).unwrap();
+        let address = account.qq_address.clone();
+        let utxo = coin_utxo(&account);
+
+        test_logger::start();
+        LogPrivacy::set(LogPrivacy::RedactAddresses);
+        let fetched = fetch_utxo_details_with_lookup(
+            address.clone(),
+            IOType::Coin,
+            &fast_policy(1),
+            move |_, _| Ok(utxo.clone()),
+        )
+        .await;
+        LogPrivacy::set(LogPrivacy::Full);
+        let lines = test_logger::finish();
+        assert!(fetched.is_ok());
+
+        let lookups: Vec<_> = lines
+            .iter()
+            .filter(|line| line.contains(&fingerprint(&address)))
+            .collect();
+        assert_eq!(lookups.len(), 2, "{:?}", lines);
+        assert!(
+            lookups
+                .iter()
+                .any(|line| line.starts_with("utxo_detail: [redacted],"))
+        );
+        assert!(!lines.iter().any(|line| line.contains(&address)));
+    }
+
+    #[tokio::test]
+    async fn test_tx_hash_lookup_permanent_error_short_circuits() {
+        let mut calls = 0;
+        let err = fetch_tx_hash_with_lookup(&fast_policy(10), &SystemClock, || {
+            calls += 1;
+            async { Err(RpcError::Custom("invalid request id".to_string())) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(calls, 1);
+        assert!(err.contains("(permanent)"), "{}", err);
+
+        let mut calls = 0;
+        let err = fetch_tx_hash_with_lookup(&fast_policy(4), &SystemClock, || {
+            calls += 1;
+            async { Err(RpcError::RequestTimeout) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(calls, 4);
+        assert!(err.contains("(transient)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_tx_hash_polls_wait_on_the_given_clock() {
+        use crate::relayer_module::clock::ManualClock;
+
+        let clock = ManualClock::default();
+        let start = clock.now();
+        let wall = std::time::Instant::now();
+        let policy = RetryPolicy::default();
+        let err = fetch_tx_hash_with_lookup(&policy, &clock, || async { Ok(Vec::new()) })
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Failed to get tx hash after 60 attempts");
+        // 59 waits of 200ms growing to 1s: close to a minute on the clock.
+        assert!(clock.now() - start >= chrono::Duration::seconds(50));
+        assert!(wall.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_mint_msgs_are_signed_into_one_tx() {
+        use base64::{engine::general_purpose, Engine as _};
+        use prost::Message;
+
+        let wallet = Wallet::from_mnemonic(
+            "test test test test test test test test test test test junk",
+            None,
+        )
+        .unwrap();
+        let seed = secrecy::SecretString::new("mint-batch-test-seed".to_string());
+        let mut zk_accounts = ZkAccountDB::new();
+        let amounts = [1_000, 2_500, 4_000];
+        let msgs: Vec<_> = amounts
+            .iter()
+            .map(|&amount| {
+                let index = zk_accounts.generate_new_account(amount, &seed).unwrap();
+                mint_burn_trading_btc_msg(&wallet, &zk_accounts, index, amount, true).unwrap()
+            })
+            .collect();
+        let signed =
+            sign_msgs_mint_burn_trading_btc(&wallet, msgs.clone(), 5, 9, &TxFeeConfig::default())
+                .unwrap();
+
+        let tx =
+            cosmrs::tx::Tx::from_bytes(&general_purpose::STANDARD.decode(signed).unwrap()).unwrap();
+        assert_eq!(tx.auth_info.signer_infos.len(), 1);
+        assert_eq!(tx.auth_info.signer_infos[0].sequence, 5);
+        let signed_msgs: Vec<MsgMintBurnTradingBtc> = tx
+            .body
+            .messages
+            .iter()
+            .map(|any| {
+                assert_eq!(
+                    any.type_url,
+                    "/twilightproject.nyks.zkos.MsgMintBurnTradingBtc"
+                );
+                MsgMintBurnTradingBtc::decode(any.value.as_slice()).unwrap()
+            })
+            .collect();
+        assert_eq!(signed_msgs, msgs);
+    }
 }
@@ -0,0 +1,440 @@
+//! Freshness tracking for cached account UTXOs.
+//!
+//! Opening an order first re-fetches the account's UTXO from the chain, even
+//! when nothing has happened to the account since the last fetch. A
+//! [`UtxoCache`] stamps each fetch with its time, what triggered it, and the
+//! local account state at that moment ([`AccountStateKey`]). The order paths
+//! reuse the cached UTXO while the stamp is younger than the configured TTL
+//! and the account state still matches; any local change (balance, IO type,
+//! on-chain flag, QuisQuis account) makes the entry stale automatically.
+//!
+//! [`OrderWallet::prewarm`](super::order_wallet::OrderWallet::prewarm) fetches
+//! UTXOs for likely-next accounts in the background; results land in the
+//! cache's shared pre-warm slot and are adopted by the next order on that
+//! account if they still match its state.
+//!
+//! [`OrderWallet::replace_trader_order`](super::order_wallet::OrderWallet::replace_trader_order)
+//! carries an entry over a cancel whose record shows the input untouched, so
+//! the replacement order reuses it once regardless of the TTL.
+//!
+//! Every reuse is counted ([`UtxoCache::avoided_fetches`]) and logged at
+//! debug level with the running total, so the saving is measurable.
+//!
+//! The cache is disabled (TTL `None`) by default, so every order fetches.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use crate::compat::relayer_rpcclient::method::UtxoDetailResponse;
+use crate::compat::zkvm::IOType;
+
+use super::order_wallet::AccountIndex;
+use super::{fetch_utxo_details_with_once, fetch_utxo_details_with_policy, RetryPolicy};
+use crate::zkos_accounts::zkaccount::ZkAccount;
+
+/// Boxed future returned by [`UtxoFetcher::fetch`].
+pub type UtxoFuture = Pin<Box<dyn Future<Output = Result<UtxoDetailResponse, String>> + Send>>;
+
+/// Source of account UTXOs. The default [`ChainUtxoFetcher`] queries the
+/// chain with retries; tests substitute a counting mock.
+pub trait UtxoFetcher: std::fmt::Debug + Send + Sync {
+    fn fetch(&self, account_address: String, io_type: IOType) -> UtxoFuture;
+
+    /// A single lookup without retries, for callers that treat "not found"
+    /// as an answer rather than a reason to wait. Defaults to [`fetch`](Self::fetch).
+    fn fetch_once(&self, account_address: String, io_type: IOType) -> UtxoFuture {
+        self.fetch(account_address, io_type)
+    }
+}
+
+/// Fetches via [`fetch_utxo_details_with_policy`], with the default
+/// [`RetryPolicy`] unless built [`with_policy`](Self::with_policy).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChainUtxoFetcher {
+    policy: RetryPolicy,
+}
+
+impl ChainUtxoFetcher {
+    pub fn with_policy(policy: RetryPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+impl UtxoFetcher for ChainUtxoFetcher {
+    fn fetch(&self, account_address: String, io_type: IOType) -> UtxoFuture {
+        let policy = self.policy;
+        Box::pin(
+            async move { fetch_utxo_details_with_policy(account_address, io_type, &policy).await },
+        )
+    }
+
+    fn fetch_once(&self, account_address: String, io_type: IOType) -> UtxoFuture {
+        Box::pin(fetch_utxo_details_with_once(account_address, io_type))
+    }
+}
+
+/// Local account state a cached UTXO was fetched for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountStateKey {
+    pub io_type: IOType,
+    pub balance: u64,
+    pub on_chain: bool,
+    pub account: String,
+}
+
+impl AccountStateKey {
+    pub fn of(account: &ZkAccount) -> Self {
+        Self {
+            io_type: account.io_type,
+            balance: account.balance,
+            on_chain: account.on_chain,
+            account: account.account.clone(),
+        }
+    }
+}
+
+/// When and why an account's cached UTXO was fetched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoStamp {
+    pub fetched_at: DateTime<Utc>,
+    /// Operation that triggered the fetch (e.g. `sync_account_state`, `prewarm`).
+    pub origin: String,
+    pub state: AccountStateKey,
+}
+
+impl UtxoStamp {
+    fn is_fresh(&self, state: &AccountStateKey, now: DateTime<Utc>, ttl: Duration) -> bool {
+        let age = (now - self.fetched_at).to_std().unwrap_or(Duration::ZERO);
+        age < ttl && self.state == *state
+    }
+}
+
+/// What [`UtxoCache::lookup`] found for an account about to open an order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UtxoLookup<T> {
+    /// The cached UTXO was carried over a cancel.
+    Carried,
+    /// The cached UTXO is younger than the TTL.
+    Fresh,
+    /// A pre-warmed UTXO to adopt.
+    Prewarmed(T, UtxoStamp),
+    /// Nothing reusable; fetch from the chain.
+    Miss,
+}
+
+#[derive(Debug, Clone)]
+struct Prewarmed<T> {
+    detail: T,
+    stamp: UtxoStamp,
+}
+
+/// Freshness stamps plus the shared pre-warm slot. Clones share the slot.
+#[derive(Debug, Clone)]
+pub struct UtxoCache<T = UtxoDetailResponse> {
+    ttl: Option<Duration>,
+    stamps: HashMap<AccountIndex, UtxoStamp>,
+    /// Entries that survived a cancel, keyed to the state they are valid for.
+    carried: HashMap<AccountIndex, AccountStateKey>,
+    prewarmed: Arc<Mutex<HashMap<AccountIndex, Prewarmed<T>>>>,
+    avoided_fetches: u64,
+}
+
+impl<T> Default for UtxoCache<T> {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<T> UtxoCache<T> {
+    /// `ttl` of `None` disables reuse: nothing is ever fresh.
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            stamps: HashMap::new(),
+            carried: HashMap::new(),
+            prewarmed: Arc::new(Mutex::new(HashMap::new())),
+            avoided_fetches: 0,
+        }
+    }
+
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    pub fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Stamp for `index`, if one is recorded.
+    pub fn stamp(&self, index: AccountIndex) -> Option<&UtxoStamp> {
+        self.stamps.get(&index)
+    }
+
+    /// Record that the UTXO for `index` was just fetched for `state`.
+    pub fn record(
+        &mut self,
+        index: AccountIndex,
+        state: AccountStateKey,
+        origin: &str,
+        now: DateTime<Utc>,
+    ) {
+        self.stamps.insert(
+            index,
+            UtxoStamp {
+                fetched_at: now,
+                origin: origin.to_string(),
+                state,
+            },
+        );
+    }
+
+    /// Whether the cached UTXO for `index` can be reused for `state`.
+    pub fn is_fresh(
+        &self,
+        index: AccountIndex,
+        state: &AccountStateKey,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match (self.ttl, self.stamps.get(&index)) {
+            (Some(ttl), Some(stamp)) => stamp.is_fresh(state, now, ttl),
+            _ => false,
+        }
+    }
+
+    /// Find a reusable UTXO for `index` in `state`: a carried-over or fresh
+    /// cached entry (`cached` says whether the caller holds one), else a
+    /// fresh pre-warmed one. Consumes carried and pre-warmed entries, and
+    /// counts every hit as an avoided fetch.
+    pub fn lookup(
+        &mut self,
+        index: AccountIndex,
+        state: &AccountStateKey,
+        now: DateTime<Utc>,
+        cached: bool,
+    ) -> UtxoLookup<T> {
+        let found = if cached && self.take_carried(index, state) {
+            UtxoLookup::Carried
+        } else if cached && self.is_fresh(index, state, now) {
+            UtxoLookup::Fresh
+        } else if let Some((detail, stamp)) = self.take_prewarmed(index, state, now) {
+            UtxoLookup::Prewarmed(detail, stamp)
+        } else {
+            UtxoLookup::Miss
+        };
+        if !matches!(found, UtxoLookup::Miss) {
+            self.avoided_fetches += 1;
+        }
+        found
+    }
+
+    /// Fetches [`lookup`](Self::lookup) has saved so far.
+    pub fn avoided_fetches(&self) -> u64 {
+        self.avoided_fetches
+    }
+
+    /// Forget the stamp, any carried-over entry and any pre-warmed UTXO for
+    /// `index`.
+    pub fn invalidate(&mut self, index: AccountIndex) {
+        self.stamps.remove(&index);
+        self.carried.remove(&index);
+        self.lock_prewarmed().remove(&index);
+    }
+
+    /// Keep the cached UTXO for `index` usable once more for `state`, even with
+    /// the cache disabled or the stamp expired. Used when a cancel is known to
+    /// have left the input unspent.
+    pub fn carry_over(&mut self, index: AccountIndex, state: AccountStateKey) {
+        self.carried.insert(index, state);
+    }
+
+    /// Whether a carried-over entry for `index` matches `state`.
+    pub fn is_carried(&self, index: AccountIndex, state: &AccountStateKey) -> bool {
+        self.carried.get(&index) == Some(state)
+    }
+
+    /// Consume the carried-over entry for `index`; `true` if it matches
+    /// `state`. A mismatching entry is discarded.
+    pub fn take_carried(&mut self, index: AccountIndex, state: &AccountStateKey) -> bool {
+        self.carried
+            .remove(&index)
+            .is_some_and(|carried| carried == *state)
+    }
+
+    /// Store a UTXO fetched in the background for `index`.
+    pub fn put_prewarmed(&self, index: AccountIndex, detail: T, stamp: UtxoStamp) {
+        self.lock_prewarmed()
+            .insert(index, Prewarmed { detail, stamp });
+    }
+
+    /// Whether a pre-warmed UTXO fresh for `state` is waiting for `index`.
+    pub fn has_prewarmed(
+        &self,
+        index: AccountIndex,
+        state: &AccountStateKey,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match (self.ttl, self.lock_prewarmed().get(&index)) {
+            (Some(ttl), Some(entry)) => entry.stamp.is_fresh(state, now, ttl),
+            _ => false,
+        }
+    }
+
+    /// Take the pre-warmed UTXO for `index` if it is fresh for `state`.
+    /// A stale entry is discarded.
+    pub fn take_prewarmed(
+        &self,
+        index: AccountIndex,
+        state: &AccountStateKey,
+        now: DateTime<Utc>,
+    ) -> Option<(T, UtxoStamp)> {
+        let ttl = self.ttl?;
+        let entry = self.lock_prewarmed().remove(&index)?;
+        if entry.stamp.is_fresh(state, now, ttl) {
+            Some((entry.detail, entry.stamp))
+        } else {
+            None
+        }
+    }
+
+    fn lock_prewarmed(&self) -> std::sync::MutexGuard<'_, HashMap<AccountIndex, Prewarmed<T>>> {
+        self.prewarmed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Heuristic: relayer/chain rejections that mean the submitted input was
+/// already spent or otherwise out of date.
+pub fn is_stale_input_error(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    [
+        "utxo not found",
+        "input not found",
+        "invalid input",
+        "input does not exist",
+        "already spent",
+        "double spend",
+        "stale",
+    ]
+    .iter()
+    .any(|needle| lower.contains(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(balance: u64) -> AccountStateKey {
+        AccountStateKey {
+            io_type: IOType::Coin,
+            balance,
+            on_chain: true,
+            account: "qq".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_freshness_follows_ttl_and_state() {
+        let t0 = DateTime::<Utc>::UNIX_EPOCH;
+        let mut cache: UtxoCache<u32> = UtxoCache::new(Some(Duration::from_secs(10)));
+        assert!(!cache.is_fresh(1, &state(100), t0));
+
+        cache.record(1, state(100), "sync_account_state", t0);
+        assert!(cache.is_fresh(1, &state(100), t0 + chrono::Duration::seconds(5)));
+        assert!(!cache.is_fresh(1, &state(100), t0 + chrono::Duration::seconds(10)));
+        // A local balance change invalidates the entry without an explicit call.
+        assert!(!cache.is_fresh(1, &state(90), t0));
+
+        cache.invalidate(1);
+        assert!(!cache.is_fresh(1, &state(100), t0));
+
+        let mut disabled: UtxoCache<u32> = UtxoCache::default();
+        disabled.record(1, state(100), "sync_account_state", t0);
+        assert!(!disabled.is_fresh(1, &state(100), t0));
+    }
+
+    #[test]
+    fn test_prewarmed_entries_are_shared_and_checked() {
+        let t0 = DateTime::<Utc>::UNIX_EPOCH;
+        let cache: UtxoCache<u32> = UtxoCache::new(Some(Duration::from_secs(10)));
+        let background = cache.clone();
+        let stamp = UtxoStamp {
+            fetched_at: t0,
+            origin: "prewarm".to_string(),
+            state: state(100),
+        };
+        background.put_prewarmed(1, 7, stamp.clone());
+        background.put_prewarmed(2, 8, stamp);
+
+        assert_eq!(
+            cache.take_prewarmed(1, &state(100), t0).map(|p| p.0),
+            Some(7)
+        );
+        assert!(cache.take_prewarmed(1, &state(100), t0).is_none());
+        assert!(cache.take_prewarmed(2, &state(50), t0).is_none());
+    }
+
+    #[test]
+    fn test_carried_entry_is_used_once_for_matching_state() {
+        let t0 = DateTime::<Utc>::UNIX_EPOCH;
+        let mut cache: UtxoCache<u32> = UtxoCache::default();
+        cache.carry_over(1, state(100));
+        // Carried entries do not depend on the (disabled) TTL.
+        assert!(!cache.is_fresh(1, &state(100), t0));
+        assert!(cache.take_carried(1, &state(100)));
+        assert!(!cache.take_carried(1, &state(100)));
+
+        cache.carry_over(1, state(100));
+        assert!(!cache.take_carried(1, &state(90)));
+        assert!(!cache.take_carried(1, &state(100)));
+
+        cache.carry_over(2, state(100));
+        cache.invalidate(2);
+        assert!(!cache.take_carried(2, &state(100)));
+    }
+
+    #[test]
+    fn test_lookup_skips_the_fetch_while_warm() {
+        let t0 = DateTime::<Utc>::UNIX_EPOCH;
+        let mut cache: UtxoCache<u32> = UtxoCache::new(Some(Duration::from_secs(10)));
+        let mut fetches = 0;
+        let mut open = |cache: &mut UtxoCache<u32>, at| {
+            if cache.lookup(1, &state(100), at, true) == UtxoLookup::Miss {
+                fetches += 1;
+                cache.record(1, state(100), "sync_account_state", at);
+            }
+        };
+        open(&mut cache, t0);
+        for secs in 1..10 {
+            open(&mut cache, t0 + chrono::Duration::seconds(secs));
+        }
+        open(&mut cache, t0 + chrono::Duration::seconds(12));
+        assert_eq!(fetches, 2);
+        assert_eq!(cache.avoided_fetches(), 9);
+
+        // Without a cached entry only a pre-warmed UTXO avoids the fetch.
+        assert_eq!(cache.lookup(2, &state(100), t0, false), UtxoLookup::Miss);
+        let stamp = UtxoStamp {
+            fetched_at: t0,
+            origin: "prewarm".to_string(),
+            state: state(100),
+        };
+        cache.put_prewarmed(2, 7, stamp.clone());
+        assert_eq!(
+            cache.lookup(2, &state(100), t0, false),
+            UtxoLookup::Prewarmed(7, stamp)
+        );
+        assert_eq!(cache.avoided_fetches(), 10);
+    }
+
+    #[test]
+    fn test_stale_input_classification() {
+        assert!(is_stale_input_error(
+            "Invalid input: UTXO not found on chain"
+        ));
+        assert!(is_stale_input_error("input already spent"));
+        assert!(!is_stale_input_error("Market is halted"));
+    }
+}
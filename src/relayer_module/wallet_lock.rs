@@ -0,0 +1,241 @@
+//! Lock/unlock lifecycle for the cached database passphrase.
+//!
+//! An [`OrderWallet`](super::order_wallet::OrderWallet) with database
+//! persistence needs its passphrase to re-encrypt the seed and the wallet
+//! blob. A [`PassphraseCache`] holds it only while the wallet is unlocked:
+//!
+//! - `lock()` drops the passphrase; `SecretString` zeroizes it on drop. The
+//!   cache lends the passphrase to one operation at a time rather than
+//!   handing out copies, so no copy outlives the lock.
+//! - With an auto-lock timeout the passphrase is dropped once it has gone
+//!   unused that long, measured on the wallet's
+//!   [`Clock`](super::clock::Clock): by a timer task when
+//!   [`arm_auto_lock`](PassphraseCache::arm_auto_lock) was called inside a
+//!   tokio runtime, and in any case on the next access.
+//! - While locked, operations that encrypt return [`WalletLocked`] until
+//!   `unlock(password)` is called. Writes to unencrypted tables (accounts,
+//!   UTXOs, request ids, history) keep working.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use secrecy::SecretString;
+use tokio::task::AbortHandle;
+
+use super::clock::Clock;
+
+/// The passphrase is not cached; unlock the wallet first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Wallet is locked: unlock it with the database passphrase first")]
+pub struct WalletLocked;
+
+impl From<WalletLocked> for String {
+    fn from(e: WalletLocked) -> Self {
+        e.to_string()
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    secret: Option<SecretString>,
+    auto_lock: Option<Duration>,
+    last_used: Option<DateTime<Utc>>,
+    /// Auto-lock timer task, if armed.
+    timer: Option<AbortHandle>,
+}
+
+impl Inner {
+    /// Drop the secret if it has been idle for the auto-lock timeout.
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let (Some(idle), Some(last_used)) = (self.auto_lock, self.last_used) else {
+            return;
+        };
+        let elapsed = (now - last_used).to_std().unwrap_or(Duration::ZERO);
+        if self.secret.is_some() && elapsed >= idle {
+            log::info!("Wallet auto-locked after {:?} idle", elapsed);
+            self.secret = None;
+        }
+    }
+
+    /// How long until the auto-lock drops the secret, or `None` when it
+    /// will not.
+    fn until_expiry(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let (Some(idle), Some(last_used)) = (self.auto_lock, self.last_used) else {
+            return None;
+        };
+        self.secret.as_ref()?;
+        let elapsed = (now - last_used).to_std().unwrap_or(Duration::ZERO);
+        Some(idle.saturating_sub(elapsed))
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+    }
+}
+
+/// Cached passphrase with optional idle auto-lock. Clones share the cache,
+/// so locking one handle locks every clone of the wallet.
+#[derive(Clone, Default)]
+pub struct PassphraseCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl std::fmt::Debug for PassphraseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.lock_inner();
+        f.debug_struct("PassphraseCache")
+            .field("unlocked", &inner.secret.is_some())
+            .field("auto_lock", &inner.auto_lock)
+            .finish()
+    }
+}
+
+impl PassphraseCache {
+    /// Cache `secret`, starting the idle timer at `now`.
+    pub fn unlock(&self, secret: SecretString, now: DateTime<Utc>) {
+        let mut inner = self.lock_inner();
+        inner.secret = Some(secret);
+        inner.last_used = Some(now);
+    }
+
+    /// Drop the cached passphrase. Returns whether one was cached.
+    pub fn lock(&self) -> bool {
+        let mut inner = self.lock_inner();
+        if let Some(timer) = inner.timer.take() {
+            timer.abort();
+        }
+        inner.secret.take().is_some()
+    }
+
+    /// Whether no passphrase is cached at `now`, applying the auto-lock.
+    pub fn is_locked(&self, now: DateTime<Utc>) -> bool {
+        let mut inner = self.lock_inner();
+        inner.expire(now);
+        inner.secret.is_none()
+    }
+
+    /// Run `f` with the passphrase. Resets the idle timer.
+    pub fn with_secret<T>(
+        &self,
+        now: DateTime<Utc>,
+        f: impl FnOnce(&SecretString) -> T,
+    ) -> Result<T, WalletLocked> {
+        let mut inner = self.lock_inner();
+        inner.expire(now);
+        let secret = inner.secret.as_ref().ok_or(WalletLocked)?;
+        let result = f(secret);
+        inner.last_used = Some(now);
+        Ok(result)
+    }
+
+    /// Drop the passphrase as soon as the auto-lock timeout passes on
+    /// `clock`, from a task on the current tokio runtime, instead of at the
+    /// next access. Replaces an earlier timer; does nothing outside a
+    /// runtime or without a passphrase and timeout.
+    ///
+    /// The task sleeps on the tokio timer and checks the timeout against
+    /// `clock`, so a [`ManualClock`](super::clock::ManualClock) only locks
+    /// once it has been advanced past it.
+    pub fn arm_auto_lock(&self, clock: Arc<dyn Clock>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let mut inner = self.lock_inner();
+        if let Some(timer) = inner.timer.take() {
+            timer.abort();
+        }
+        if inner.until_expiry(clock.now()).is_none() {
+            return;
+        }
+        let cache = Arc::downgrade(&self.inner);
+        let task = runtime.spawn(async move {
+            loop {
+                let wait = {
+                    let Some(cache) = cache.upgrade() else { return };
+                    let mut inner = cache.lock().unwrap_or_else(|e| e.into_inner());
+                    let now = clock.now();
+                    inner.expire(now);
+                    match inner.until_expiry(now) {
+                        Some(wait) => wait,
+                        None => return,
+                    }
+                };
+                tokio::time::sleep(wait).await;
+            }
+        });
+        inner.timer = Some(task.abort_handle());
+    }
+
+    pub fn auto_lock(&self) -> Option<Duration> {
+        self.lock_inner().auto_lock
+    }
+
+    /// Lock after `idle` without use; `None` keeps the passphrase until
+    /// [`lock`](Self::lock). Re-arm a running timer with
+    /// [`arm_auto_lock`](Self::arm_auto_lock).
+    pub fn set_auto_lock(&self, idle: Option<Duration>) {
+        self.lock_inner().auto_lock = idle;
+    }
+
+    fn lock_inner(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    fn t(secs: i64) -> DateTime<Utc> {
+        DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::seconds(secs)
+    }
+
+    #[test]
+    fn test_locked_cache_refuses_access() {
+        let cache = PassphraseCache::default();
+        assert!(cache.is_locked(t(0)));
+        assert_eq!(cache.with_secret(t(0), |_| ()), Err(WalletLocked));
+
+        cache.unlock(SecretString::new("pw".into()), t(0));
+        let length = cache.with_secret(t(1), |pw| pw.expose_secret().len());
+        assert_eq!(length, Ok(2));
+        assert!(cache.clone().lock());
+        assert!(cache.is_locked(t(1)));
+        assert!(!cache.lock());
+    }
+
+    #[test]
+    fn test_auto_lock_follows_last_use() {
+        let cache = PassphraseCache::default();
+        cache.set_auto_lock(Some(Duration::from_secs(60)));
+        cache.unlock(SecretString::new("pw".into()), t(0));
+        // Each use restarts the idle timer.
+        assert!(cache.with_secret(t(59), |_| ()).is_ok());
+        assert!(!cache.is_locked(t(118)));
+        assert!(cache.is_locked(t(119)));
+        assert_eq!(cache.with_secret(t(119), |_| ()), Err(WalletLocked));
+    }
+
+    #[tokio::test]
+    async fn test_armed_timer_drops_the_passphrase_without_an_access() {
+        use crate::relayer_module::clock::SystemClock;
+        let cache = PassphraseCache::default();
+        cache.set_auto_lock(Some(Duration::from_millis(100)));
+        cache.unlock(SecretString::new("pw".into()), Utc::now());
+        cache.arm_auto_lock(Arc::new(SystemClock));
+        assert!(cache.lock_inner().secret.is_some());
+
+        // Nothing reads the cache: the timer alone drops the only copy,
+        // which `SecretString` zeroizes.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let inner = cache.lock_inner();
+        assert!(inner.secret.is_none());
+        assert!(inner.timer.as_ref().is_some_and(AbortHandle::is_finished));
+    }
+}
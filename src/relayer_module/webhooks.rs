@@ -0,0 +1,510 @@
+//! Best-effort delivery of [`WalletEvent`]s to HTTP webhooks.
+//!
+//! Each endpoint registered with
+//! [`OrderWallet::add_webhook`](super::order_wallet::OrderWallet::add_webhook)
+//! gets a bounded queue and a background task. Emitting an event only
+//! serializes it and pushes it onto the queue of each endpoint whose
+//! [`EventFilter`] matches; a full queue drops the event. The task POSTs
+//! each event as a [`WebhookPayload`] signed with HMAC-SHA256 over the raw
+//! body ([`SIGNATURE_HEADER`], `sha256=<hex>`). Transport errors, 5xx and 429
+//! responses are retried with exponential backoff. Events older than
+//! [`WebhookConfig::event_ttl`] are dropped instead of retried. Failures are
+//! only logged and counted in [`WebhookStats`].
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::{debug, warn};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use super::events::{WalletEvent, WalletEventKind};
+
+/// Version of the [`WebhookPayload`] layout. Bumped on breaking changes.
+pub const WEBHOOK_SCHEMA_VERSION: u32 = 1;
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>`.
+pub const SIGNATURE_HEADER: &str = "X-Nyks-Signature";
+
+/// Which events an endpoint receives.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    /// `None`: every event.
+    kinds: Option<HashSet<WalletEventKind>>,
+}
+
+impl EventFilter {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn only(kinds: impl IntoIterator<Item = WalletEventKind>) -> Self {
+        Self {
+            kinds: Some(kinds.into_iter().collect()),
+        }
+    }
+
+    pub fn matches(&self, event: &WalletEvent) -> bool {
+        self.kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(&event.kind()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Delivery attempts per event, including the first.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Events not delivered within this long after being emitted are dropped.
+    pub event_ttl: Duration,
+    pub request_timeout: Duration,
+    /// Events queued per endpoint before new ones are dropped.
+    pub queue_capacity: usize,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            event_ttl: Duration::from_secs(300),
+            request_timeout: Duration::from_secs(10),
+            queue_capacity: 256,
+        }
+    }
+}
+
+/// JSON body POSTed to webhooks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub schema_version: u32,
+    /// Unique per event; receivers can use it to drop duplicate deliveries.
+    pub event_id: String,
+    /// Twilight address of the emitting wallet.
+    pub wallet: String,
+    pub occurred_at: DateTime<Utc>,
+    pub event: WalletEvent,
+}
+
+/// Delivery counters, summed over endpoints.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct WebhookStats {
+    pub delivered: u64,
+    /// Attempts that failed and were retried.
+    pub retried: u64,
+    /// Events given up on after the last attempt or a non-retryable response.
+    pub failed: u64,
+    /// Events dropped because they outlived the TTL.
+    pub expired: u64,
+    /// Events dropped because the endpoint's queue was full.
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    delivered: AtomicU64,
+    retried: AtomicU64,
+    failed: AtomicU64,
+    expired: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Counters {
+    fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> WebhookStats {
+        WebhookStats {
+            delivered: self.delivered.load(Ordering::Relaxed),
+            retried: self.retried.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Queued {
+    body: Vec<u8>,
+    emitted: Instant,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    filter: EventFilter,
+    queue: mpsc::Sender<Queued>,
+    counters: Arc<Counters>,
+}
+
+/// Fans events out to registered endpoints. Clones share endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookDispatcher {
+    endpoints: Vec<Arc<Endpoint>>,
+}
+
+impl WebhookDispatcher {
+    /// Register `url` and start its delivery task, which ends once every
+    /// dispatcher clone holding the endpoint is dropped. Must be called
+    /// inside a Tokio runtime.
+    pub fn add(
+        &mut self,
+        url: &str,
+        secret: SecretString,
+        filter: EventFilter,
+        config: WebhookConfig,
+    ) -> Result<JoinHandle<()>, String> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| "Webhooks need a Tokio runtime".to_string())?;
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let (queue, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let counters = Arc::new(Counters::default());
+        let task = runtime.spawn(deliver(
+            client,
+            url.to_string(),
+            secret,
+            config,
+            receiver,
+            counters.clone(),
+        ));
+        self.endpoints.push(Arc::new(Endpoint {
+            url: url.to_string(),
+            filter,
+            queue,
+            counters,
+        }));
+        Ok(task)
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    /// Queue `event` for every matching endpoint. Never blocks.
+    pub fn dispatch(&self, wallet: &str, event: &WalletEvent) {
+        let mut matching = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.filter.matches(event))
+            .peekable();
+        if matching.peek().is_none() {
+            return;
+        }
+        let payload = WebhookPayload {
+            schema_version: WEBHOOK_SCHEMA_VERSION,
+            event_id: uuid::Uuid::new_v4().to_string(),
+            wallet: wallet.to_string(),
+            occurred_at: event.occurred_at(),
+            event: event.clone(),
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize webhook payload: {}", e);
+                return;
+            }
+        };
+        let emitted = Instant::now();
+        for endpoint in matching {
+            let queued = Queued {
+                body: body.clone(),
+                emitted,
+            };
+            if endpoint.queue.try_send(queued).is_err() {
+                Counters::bump(&endpoint.counters.dropped);
+                warn!(
+                    "Webhook queue for {} is full or closed; dropping event",
+                    endpoint.url
+                );
+            }
+        }
+    }
+
+    pub fn stats(&self) -> WebhookStats {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.counters.snapshot())
+            .fold(WebhookStats::default(), |total, s| WebhookStats {
+                delivered: total.delivered + s.delivered,
+                retried: total.retried + s.retried,
+                failed: total.failed + s.failed,
+                expired: total.expired + s.expired,
+                dropped: total.dropped + s.dropped,
+            })
+    }
+}
+
+/// `sha256=<hex>` HMAC of `body` under `secret`, as sent in [`SIGNATURE_HEADER`].
+pub fn sign_payload(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Check a [`SIGNATURE_HEADER`] value in constant time. For receivers.
+pub fn verify_signature(secret: &[u8], body: &[u8], header: &str) -> bool {
+    let Some(signature) = header
+        .strip_prefix("sha256=")
+        .and_then(|hex_sig| hex::decode(hex_sig).ok())
+    else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    url: String,
+    secret: SecretString,
+    config: WebhookConfig,
+    mut receiver: mpsc::Receiver<Queued>,
+    counters: Arc<Counters>,
+) {
+    while let Some(queued) = receiver.recv().await {
+        let signature = sign_payload(secret.expose_secret().as_bytes(), &queued.body);
+        let mut backoff = config.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            if queued.emitted.elapsed() > config.event_ttl {
+                Counters::bump(&counters.expired);
+                warn!("Dropping webhook event for {}: older than TTL", url);
+                break;
+            }
+            let result = client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .body(queued.body.clone())
+                .send()
+                .await;
+            let retryable = match result {
+                Ok(response) if response.status().is_success() => {
+                    Counters::bump(&counters.delivered);
+                    break;
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    debug!("Webhook {} answered {}", url, status);
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => {
+                    debug!("Webhook {} unreachable: {}", url, e);
+                    true
+                }
+            };
+            if !retryable || attempt >= config.max_attempts {
+                Counters::bump(&counters.failed);
+                warn!(
+                    "Giving up on webhook event for {} after {} attempt(s)",
+                    url, attempt
+                );
+                break;
+            }
+            Counters::bump(&counters.retried);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(config.max_backoff);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::relayer_module::events::OrderKind;
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    /// A request received by [`MockReceiver`].
+    #[derive(Debug, Clone)]
+    struct Received {
+        signature: String,
+        body: Vec<u8>,
+    }
+
+    /// Minimal blocking HTTP server answering each request with the next
+    /// status from `statuses` (the last one repeats) after `delay`.
+    struct MockReceiver {
+        url: String,
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl MockReceiver {
+        fn start(statuses: Vec<u16>, delay: Duration) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let log = received.clone();
+            std::thread::spawn(move || {
+                for (n, stream) in listener.incoming().enumerate() {
+                    let Ok(mut stream) = stream else { return };
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut signature = String::new();
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                            break;
+                        }
+                        let lower = line.to_ascii_lowercase();
+                        if let Some(value) = lower.strip_prefix("content-length:") {
+                            length = value.trim().parse().unwrap_or(0);
+                        }
+                        if lower.starts_with(&SIGNATURE_HEADER.to_ascii_lowercase()) {
+                            signature = line.split_once(':').unwrap().1.trim().to_string();
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    log.lock().unwrap().push(Received { signature, body });
+                    std::thread::sleep(delay);
+                    let status = statuses[n.min(statuses.len() - 1)];
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                        status
+                    );
+                }
+            });
+            Self { url, received }
+        }
+
+        fn received(&self) -> Vec<Received> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    fn fast_config() -> WebhookConfig {
+        WebhookConfig {
+            initial_backoff: Duration::from_millis(10),
+            ..WebhookConfig::default()
+        }
+    }
+
+    fn opened(account_index: u64) -> WalletEvent {
+        WalletEvent::OrderOpened {
+            account_index,
+            request_id: format!("req-{}", account_index),
+            order: OrderKind::Trader,
+            occurred_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    async fn wait_for(dispatcher: &WebhookDispatcher, done: impl Fn(WebhookStats) -> bool) {
+        for _ in 0..200 {
+            if done(dispatcher.stats()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("webhook stats never settled: {:?}", dispatcher.stats());
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery_after_retry_on_500() {
+        let receiver = MockReceiver::start(vec![500, 200], Duration::ZERO);
+        let mut dispatcher = WebhookDispatcher::default();
+        dispatcher
+            .add(
+                &receiver.url,
+                SecretString::new("shh".to_string()),
+                EventFilter::all(),
+                fast_config(),
+            )
+            .unwrap();
+        dispatcher.dispatch("twilight1wallet", &opened(1));
+        wait_for(&dispatcher, |s| s.delivered == 1).await;
+
+        let stats = dispatcher.stats();
+        assert_eq!((stats.retried, stats.failed), (1, 0));
+        let received = receiver.received();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].body, received[1].body);
+        assert!(verify_signature(
+            b"shh",
+            &received[1].body,
+            &received[1].signature
+        ));
+        assert!(!verify_signature(
+            b"wrong",
+            &received[1].body,
+            &received[1].signature
+        ));
+
+        let payload: WebhookPayload = serde_json::from_slice(&received[1].body).unwrap();
+        assert_eq!(payload.schema_version, WEBHOOK_SCHEMA_VERSION);
+        assert_eq!(payload.wallet, "twilight1wallet");
+        assert_eq!(payload.event, opened(1));
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_client_errors() {
+        let failures = MockReceiver::start(vec![400], Duration::ZERO);
+        let mut dispatcher = WebhookDispatcher::default();
+        dispatcher
+            .add(
+                &failures.url,
+                SecretString::new("shh".to_string()),
+                EventFilter::only([WalletEventKind::OrderFailed]),
+                fast_config(),
+            )
+            .unwrap();
+        dispatcher.dispatch("w", &opened(1));
+        let failed = WalletEvent::OrderFailed {
+            account_index: 2,
+            operation: "open_trader_order".to_string(),
+            error: "boom".to_string(),
+            occurred_at: Utc::now(),
+        };
+        dispatcher.dispatch("w", &failed);
+        wait_for(&dispatcher, |s| s.failed == 1).await;
+
+        // Only the failure matched, and a 4xx is not retried.
+        assert_eq!(failures.received().len(), 1);
+        assert_eq!(dispatcher.stats().retried, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stale_events_expire_instead_of_retrying() {
+        let receiver = MockReceiver::start(vec![503], Duration::ZERO);
+        let mut dispatcher = WebhookDispatcher::default();
+        dispatcher
+            .add(
+                &receiver.url,
+                SecretString::new("shh".to_string()),
+                EventFilter::all(),
+                WebhookConfig {
+                    initial_backoff: Duration::from_millis(50),
+                    event_ttl: Duration::from_millis(20),
+                    ..WebhookConfig::default()
+                },
+            )
+            .unwrap();
+        dispatcher.dispatch("w", &opened(1));
+        wait_for(&dispatcher, |s| s.expired == 1).await;
+        assert_eq!(receiver.received().len(), 1);
+        assert_eq!(dispatcher.stats().failed, 0);
+    }
+}
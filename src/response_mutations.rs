@@ -0,0 +1,185 @@
+//! Mutated response fixtures for property tests of the response parsers.
+//!
+//! Fixtures are real relayer, RPC and LCD responses kept under
+//! `fuzz/corpus/<parser>/`, the same directories the `cargo fuzz` targets in
+//! `fuzz/` start from. [`mutated_fixture`] damages a fixture the way a broken
+//! or hostile endpoint would: fields removed or given the wrong type, numbers
+//! past `u64::MAX`, invalid UTF-8 inside strings, deeply nested arrays and
+//! truncated bodies. Proptest writes failing cases to `proptest-regressions/`
+//! next to the test's source file; commit those so failures replay.
+
+use proptest::prelude::*;
+use serde_json::{Map, Number, Value};
+
+/// Contents of `fuzz/corpus/<name>`.
+pub(crate) fn fixture(name: &str) -> String {
+    let path = format!("{}/fuzz/corpus/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("fixture {}: {}", path, e))
+}
+
+/// Parsed `fuzz/corpus/<name>`.
+pub(crate) fn fixture_value(name: &str) -> Value {
+    serde_json::from_str(&fixture(name)).unwrap_or_else(|e| panic!("fixture {}: {}", name, e))
+}
+
+#[derive(Debug, Clone)]
+enum Edit {
+    Remove,
+    Replace(Value),
+}
+
+#[derive(Debug, Clone)]
+enum ByteDamage {
+    None,
+    /// Invalid UTF-8 right after the `n`-th quote.
+    InvalidUtf8(usize),
+    /// Keep only the first `n` bytes (modulo the length).
+    Truncate(usize),
+}
+
+fn nested_arrays(depth: usize) -> Value {
+    (0..depth).fold(Value::Null, |inner, _| Value::Array(vec![inner]))
+}
+
+fn replacement() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        Just(Value::from(u64::MAX)),
+        any::<f64>().prop_map(|f| Number::from_f64(f).map_or(Value::Null, Value::Number)),
+        ".{0,16}".prop_map(Value::String),
+        "[1-9][0-9]{19,39}".prop_map(Value::String),
+        (1usize..200).prop_map(nested_arrays),
+    ]
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+    prop_oneof![
+        1 => Just(Edit::Remove),
+        4 => replacement().prop_map(Edit::Replace),
+    ]
+}
+
+/// Paths of every node below the root, as object keys or array indices.
+fn node_paths(value: &Value, prefix: &mut Vec<Value>, out: &mut Vec<Vec<Value>>) {
+    let children: Vec<(Value, &Value)> = match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| (Value::from(k.as_str()), v))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Value::from(i), v))
+            .collect(),
+        _ => return,
+    };
+    for (key, child) in children {
+        prefix.push(key);
+        out.push(prefix.clone());
+        node_paths(child, prefix, out);
+        prefix.pop();
+    }
+}
+
+fn apply(value: &mut Value, path: &[Value], edit: Edit) {
+    let Some((last, parents)) = path.split_last() else {
+        if let Edit::Replace(v) = edit {
+            *value = v;
+        }
+        return;
+    };
+    let mut node = value;
+    for key in parents {
+        node = match (node, key) {
+            (Value::Object(map), Value::String(k)) => map.get_mut(k).expect("path from node_paths"),
+            (Value::Array(items), Value::Number(i)) => &mut items[i.as_u64().unwrap() as usize],
+            _ => unreachable!("path from node_paths"),
+        };
+    }
+    match (node, last, edit) {
+        (Value::Object(map), Value::String(k), Edit::Remove) => {
+            map.remove(k);
+        }
+        (Value::Object(map), Value::String(k), Edit::Replace(v)) => {
+            map.insert(k.clone(), v);
+        }
+        (Value::Array(items), Value::Number(i), Edit::Remove) => {
+            items.remove(i.as_u64().unwrap() as usize);
+        }
+        (Value::Array(items), Value::Number(i), Edit::Replace(v)) => {
+            items[i.as_u64().unwrap() as usize] = v;
+        }
+        _ => unreachable!("path from node_paths"),
+    }
+}
+
+/// `value` with one to three of its nodes removed or replaced.
+pub(crate) fn mutated(value: Value) -> impl Strategy<Value = Value> {
+    prop::collection::vec((any::<usize>(), edit()), 1..=3).prop_map(move |edits| {
+        let mut value = value.clone();
+        for (selector, edit) in edits {
+            let mut paths = vec![Vec::new()];
+            node_paths(&value, &mut Vec::new(), &mut paths);
+            // Skip the root unless it is the only node, so most edits keep
+            // the overall shape of the response.
+            let path = if paths.len() == 1 {
+                paths[0].clone()
+            } else {
+                paths[1 + selector % (paths.len() - 1)].clone()
+            };
+            apply(&mut value, &path, edit);
+        }
+        value
+    })
+}
+
+/// `fuzz/corpus/<name>` with one to three of its nodes removed or replaced.
+pub(crate) fn mutated_value(name: &str) -> impl Strategy<Value = Value> {
+    mutated(fixture_value(name))
+}
+
+/// Serialized [`mutated_value`], sometimes with invalid UTF-8 inserted into a
+/// string or the body truncated.
+pub(crate) fn mutated_fixture(name: &str) -> impl Strategy<Value = Vec<u8>> {
+    let damage = prop_oneof![
+        2 => Just(ByteDamage::None),
+        1 => any::<usize>().prop_map(ByteDamage::InvalidUtf8),
+        1 => any::<usize>().prop_map(ByteDamage::Truncate),
+    ];
+    (mutated_value(name), damage).prop_map(|(value, damage)| {
+        let mut bytes = serde_json::to_vec(&value).expect("Value always serializes");
+        match damage {
+            ByteDamage::None => {}
+            ByteDamage::InvalidUtf8(n) => {
+                let quotes: Vec<usize> = (0..bytes.len()).filter(|&i| bytes[i] == b'"').collect();
+                if !quotes.is_empty() {
+                    let at = quotes[n % quotes.len()] + 1;
+                    bytes.insert(at, 0xFE);
+                    bytes.insert(at, 0xFF);
+                }
+            }
+            ByteDamage::Truncate(n) => bytes.truncate(n % (bytes.len() + 1)),
+        }
+        bytes
+    })
+}
+
+/// Any JSON value, up to a few levels deep.
+pub(crate) fn arbitrary_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        any::<u64>().prop_map(Value::from),
+        ".{0,16}".prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+            prop::collection::vec(("[a-z_]{1,12}", inner), 0..8)
+                .prop_map(|fields| Value::Object(fields.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
@@ -0,0 +1,187 @@
+//! Pluggable entropy for key material and encryption parameters.
+//!
+//! Every random byte that ends up in a wallet secret is drawn from an
+//! [`EntropySource`]. The process-wide default is [`OsEntropy`], so nothing
+//! changes unless a build installs its own source (for example an HSM DRBG)
+//! with [`set_default_source`] or passes one to [`Wallet::new_with_entropy`].
+//!
+//! Outputs influenced by the source:
+//! - the 32 bytes of BIP-39 entropy behind the 24-word mnemonic created by
+//!   `Wallet::new`, `Wallet::new_with_entropy` and
+//!   `Wallet::create_new_with_random_btc_address`, and therefore the Cosmos
+//!   signing key, the twilight address and the BTC key derived from it;
+//! - the throwaway mnemonic behind `generate_random_btc_address`;
+//! - the PBKDF2 salt (32 bytes) and AES-GCM nonce (12 bytes) of the encrypted
+//!   wallet blob and of the encrypted order-wallet seed in the database;
+//! - the PBKDF2 salt of a wallet's ZkOS account secrets and the per-row
+//!   AES-GCM nonce of each account's sealed secrets.
+//!
+//! Not influenced: ZkOS commitment scalars, receiver ownership challenges,
+//! JSON-RPC request ids and retry jitter. None of those are key material.
+//!
+//! [`Wallet::new_with_entropy`]: crate::wallet::Wallet::new_with_entropy
+
+use bip39::{Language, Mnemonic};
+use std::sync::{Arc, RwLock};
+use zeroize::Zeroize;
+
+/// Bytes of BIP-39 entropy for a 24-word mnemonic.
+pub const MNEMONIC_ENTROPY_BYTES: usize = 32;
+
+/// A source of cryptographically secure random bytes.
+///
+/// Implementations must be safe to share between threads; use interior
+/// mutability if the underlying generator keeps state.
+pub trait EntropySource: Send + Sync {
+    /// Fill `dest` entirely with random bytes.
+    fn fill_bytes(&self, dest: &mut [u8]);
+}
+
+/// The operating system RNG. This is the default source.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        use rand::RngCore;
+        rand::rngs::OsRng.fill_bytes(dest);
+    }
+}
+
+impl<T: EntropySource + ?Sized> EntropySource for Arc<T> {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        (**self).fill_bytes(dest)
+    }
+}
+
+static DEFAULT_SOURCE: RwLock<Option<Arc<dyn EntropySource>>> = RwLock::new(None);
+
+/// The process-wide source used when a caller does not pass one explicitly.
+pub fn default_source() -> Arc<dyn EntropySource> {
+    DEFAULT_SOURCE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(OsEntropy))
+}
+
+/// Replace the process-wide source. Affects every generation site listed in
+/// the module docs from this point on.
+pub fn set_default_source(source: Arc<dyn EntropySource>) {
+    *DEFAULT_SOURCE.write().unwrap_or_else(|e| e.into_inner()) = Some(source);
+}
+
+/// Go back to [`OsEntropy`].
+pub fn reset_default_source() {
+    *DEFAULT_SOURCE.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Generate a 24-word English mnemonic from `source`.
+pub fn generate_mnemonic(source: &dyn EntropySource) -> anyhow::Result<Mnemonic> {
+    let mut entropy = [0u8; MNEMONIC_ENTROPY_BYTES];
+    source.fill_bytes(&mut entropy);
+    let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy);
+    entropy.zeroize();
+    Ok(mnemonic?)
+}
+
+/// Draw a fresh PBKDF2 salt and AES-GCM nonce from `source`.
+pub fn salt_and_nonce(source: &dyn EntropySource) -> ([u8; 32], [u8; 12]) {
+    let mut salt = [0u8; 32];
+    let mut nonce = [0u8; 12];
+    source.fill_bytes(&mut salt);
+    source.fill_bytes(&mut nonce);
+    (salt, nonce)
+}
+
+// -------------------------
+// Test helpers
+// -------------------------
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::EntropySource;
+    use std::sync::Mutex;
+
+    /// Deterministic counter stream that records how many bytes and calls
+    /// were served.
+    #[derive(Default)]
+    pub struct CountingEntropy {
+        state: Mutex<(u8, usize, usize)>,
+    }
+
+    impl CountingEntropy {
+        pub fn starting_at(seed: u8) -> Self {
+            Self {
+                state: Mutex::new((seed, 0, 0)),
+            }
+        }
+
+        pub fn calls(&self) -> usize {
+            self.state.lock().unwrap().1
+        }
+
+        pub fn bytes(&self) -> usize {
+            self.state.lock().unwrap().2
+        }
+    }
+
+    impl EntropySource for CountingEntropy {
+        fn fill_bytes(&self, dest: &mut [u8]) {
+            let mut state = self.state.lock().unwrap();
+            for b in dest.iter_mut() {
+                *b = state.0;
+                state.0 = state.0.wrapping_add(1);
+            }
+            state.1 += 1;
+            state.2 += dest.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::CountingEntropy;
+    use super::*;
+
+    #[test]
+    fn test_mnemonic_uses_source_entropy() {
+        let source = CountingEntropy::starting_at(0);
+        let mnemonic = generate_mnemonic(&source).unwrap();
+        assert_eq!(source.calls(), 1);
+        assert_eq!(source.bytes(), MNEMONIC_ENTROPY_BYTES);
+        assert_eq!(mnemonic.word_count(), 24);
+        let expected: Vec<u8> = (0..32).collect();
+        assert_eq!(mnemonic.to_entropy(), expected);
+    }
+
+    #[test]
+    fn test_identical_streams_give_identical_mnemonics() {
+        let a = generate_mnemonic(&CountingEntropy::starting_at(7)).unwrap();
+        let b = generate_mnemonic(&CountingEntropy::starting_at(7)).unwrap();
+        let c = generate_mnemonic(&CountingEntropy::starting_at(8)).unwrap();
+        assert_eq!(a.to_string(), b.to_string());
+        assert_ne!(a.to_string(), c.to_string());
+    }
+
+    #[test]
+    fn test_salt_and_nonce_come_from_source() {
+        let source = CountingEntropy::starting_at(0);
+        let (salt, nonce) = salt_and_nonce(&source);
+        assert_eq!(source.calls(), 2);
+        assert_eq!(source.bytes(), 44);
+        assert_eq!(salt[0], 0);
+        assert_eq!(nonce[0], 32);
+    }
+
+    #[test]
+    fn test_shared_source_serves_one_stream() {
+        let source = Arc::new(CountingEntropy::starting_at(0));
+        let shared: Arc<dyn EntropySource> = source.clone();
+        let (salt, _) = salt_and_nonce(&*shared);
+        let (next_salt, _) = salt_and_nonce(&source);
+        assert_eq!(source.calls(), 4);
+        assert_eq!(salt[0], 0);
+        assert_eq!(next_salt[0], 44);
+    }
+}
@@ -1,12 +1,13 @@
+pub mod entropy;
 #[cfg(feature = "order-wallet")]
 pub mod keyring_store;
-#[cfg(feature = "order-wallet")]
 pub mod password;
+#[cfg(feature = "order-wallet")]
+pub mod seed_storage;
 pub mod secure_tty;
 // pub mod wallet_security;
 #[cfg(feature = "order-wallet")]
 pub use keyring_store::*;
-#[cfg(feature = "order-wallet")]
 pub use password::*;
 pub use secure_tty::*;
 // pub use wallet_security::*;
@@ -5,7 +5,7 @@ use zeroize::Zeroize;
 
 /// Number of PBKDF2 iterations for key derivation.
 /// 600_000 is the OWASP recommendation for PBKDF2-HMAC-SHA256 (2023+).
-const PBKDF2_ITERATIONS: u32 = 600_000;
+pub const PBKDF2_ITERATIONS: u32 = 600_000;
 
 /// Secure password management for wallet operations
 pub struct SecurePassword;
@@ -57,6 +57,16 @@ impl SecurePassword {
     ///
     /// Uses 600,000 iterations per OWASP recommendations.
     pub fn derive_key_from_passphrase(passphrase: &SecretString, salt: &[u8]) -> Result<[u8; 32]> {
+        Self::derive_key_with_iterations(passphrase, salt, PBKDF2_ITERATIONS)
+    }
+
+    /// [`derive_key_from_passphrase`](Self::derive_key_from_passphrase) with
+    /// `iterations` rounds, e.g. the count recorded with an encrypted file.
+    pub fn derive_key_with_iterations(
+        passphrase: &SecretString,
+        salt: &[u8],
+        iterations: u32,
+    ) -> Result<[u8; 32]> {
         use hmac::Hmac;
         use sha2::Sha256;
 
@@ -64,7 +74,7 @@ impl SecurePassword {
         pbkdf2::pbkdf2::<Hmac<Sha256>>(
             passphrase.expose_secret().as_bytes(),
             salt,
-            PBKDF2_ITERATIONS,
+            iterations,
             &mut key,
         )
         .map_err(|e| anyhow::anyhow!("PBKDF2 key derivation failed: {}", e))?;
@@ -0,0 +1,298 @@
+//! Where an `OrderWallet` keeps its ZkOS seed.
+//!
+//! With [`SeedStorage::InMemory`] (the default) the seed is held for the
+//! wallet's lifetime, as before. With [`SeedStorage::OsKeystore`] it is
+//! written to the OS keystore when the wallet is built and read back only for
+//! one key derivation or encryption at a time; no wallet field holds it
+//! in between, and each copy is zeroized when dropped.
+//!
+//! [`OsKeystore`] goes through the `keyring` crate: macOS Keychain, Windows
+//! Credential Manager (DPAPI) or the Linux Secret Service, depending on the
+//! platform features `keyring` is built with.
+//!
+//! Fallback: if the keystore cannot store and read back the seed, the wallet
+//! keeps the seed in memory and logs a warning. This is the usual case on
+//! headless Linux without a running Secret Service, or when `keyring` is
+//! built without a platform backend. [`SeedVault::storage`] reports which
+//! mode is actually in effect.
+//!
+//! The seed is derived from the wallet's signing key, which the `Wallet`
+//! still holds. The keystore shortens how long the seed itself stays in
+//! memory; it does not remove the key it can be derived from.
+
+use std::sync::Arc;
+
+use keyring::Entry;
+use log::warn;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+
+/// Keyring service name for ZkOS seeds.
+const SEED_SERVICE: &str = "com.nyks.wallet.zkos-seed";
+
+/// Requested location of the ZkOS seed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedStorage {
+    #[default]
+    InMemory,
+    OsKeystore,
+}
+
+/// Backend that stores seeds by label. [`OsKeystore`] in production; tests
+/// substitute an in-process map.
+pub trait SeedKeystore: std::fmt::Debug + Send + Sync {
+    fn store(&self, label: &str, seed: &SecretString) -> Result<(), String>;
+    fn load(&self, label: &str) -> Result<SecretString, String>;
+    fn delete(&self, label: &str) -> Result<(), String>;
+}
+
+/// The platform keystore via `keyring`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsKeystore;
+
+impl SeedKeystore for OsKeystore {
+    fn store(&self, label: &str, seed: &SecretString) -> Result<(), String> {
+        let entry = Entry::new(SEED_SERVICE, label).map_err(|e| e.to_string())?;
+        entry
+            .set_password(seed.expose_secret())
+            .map_err(|e| e.to_string())
+    }
+
+    fn load(&self, label: &str) -> Result<SecretString, String> {
+        let entry = Entry::new(SEED_SERVICE, label).map_err(|e| e.to_string())?;
+        entry
+            .get_password()
+            .map(SecretString::new)
+            .map_err(|e| e.to_string())
+    }
+
+    fn delete(&self, label: &str) -> Result<(), String> {
+        let entry = Entry::new(SEED_SERVICE, label).map_err(|e| e.to_string())?;
+        entry.delete_credential().map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Clone)]
+enum Held {
+    InMemory(SecretString),
+    Keystore {
+        backend: Arc<dyn SeedKeystore>,
+        label: String,
+    },
+}
+
+/// The wallet's handle on its seed: the seed itself, or where to fetch it.
+#[derive(Clone)]
+pub struct SeedVault {
+    held: Held,
+}
+
+impl std::fmt::Debug for SeedVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.held {
+            Held::InMemory(_) => f.write_str("SeedVault::InMemory([REDACTED])"),
+            Held::Keystore { backend, label } => f
+                .debug_struct("SeedVault::Keystore")
+                .field("backend", backend)
+                .field("label", label)
+                .finish(),
+        }
+    }
+}
+
+impl SeedVault {
+    pub fn in_memory(seed: SecretString) -> Self {
+        Self {
+            held: Held::InMemory(seed),
+        }
+    }
+
+    /// Place `seed` according to `storage`. For the keystore the seed is
+    /// stored under `label` and read back once to confirm the keystore
+    /// works; on any failure it stays in memory (see the module docs).
+    pub fn open(
+        storage: SeedStorage,
+        backend: Arc<dyn SeedKeystore>,
+        label: &str,
+        seed: SecretString,
+    ) -> Self {
+        if storage == SeedStorage::InMemory {
+            return Self::in_memory(seed);
+        }
+        match store_and_verify(backend.as_ref(), label, &seed) {
+            Ok(()) => Self {
+                held: Held::Keystore {
+                    backend,
+                    label: label.to_string(),
+                },
+            },
+            Err(e) => {
+                warn!(
+                    "OS keystore unavailable ({}); keeping the ZkOS seed in memory",
+                    e
+                );
+                Self::in_memory(seed)
+            }
+        }
+    }
+
+    /// Storage in effect, after any fallback.
+    pub fn storage(&self) -> SeedStorage {
+        match self.held {
+            Held::InMemory(_) => SeedStorage::InMemory,
+            Held::Keystore { .. } => SeedStorage::OsKeystore,
+        }
+    }
+
+    /// A transient copy of the seed; drop it as soon as possible.
+    pub fn secret(&self) -> Result<SecretString, String> {
+        match &self.held {
+            Held::InMemory(seed) => Ok(seed.clone()),
+            Held::Keystore { backend, label } => backend
+                .load(label)
+                .map_err(|e| format!("Failed to read ZkOS seed from OS keystore: {}", e)),
+        }
+    }
+
+    /// Run `f` with the seed, zeroizing the fetched copy afterwards.
+    pub fn with_seed<T>(&self, f: impl FnOnce(&SecretString) -> T) -> Result<T, String> {
+        let seed = self.secret()?;
+        Ok(f(&seed))
+    }
+
+    /// Remove the seed from the keystore. Does nothing for an in-memory seed.
+    pub fn forget(&self) -> Result<(), String> {
+        match &self.held {
+            Held::InMemory(_) => Ok(()),
+            Held::Keystore { backend, label } => backend.delete(label),
+        }
+    }
+}
+
+fn store_and_verify(
+    backend: &dyn SeedKeystore,
+    label: &str,
+    seed: &SecretString,
+) -> Result<(), String> {
+    backend.store(label, seed)?;
+    let read_back = backend.load(label)?;
+    if read_back.expose_secret() != seed.expose_secret() {
+        return Err("seed read back from keystore does not match".to_string());
+    }
+    Ok(())
+}
+
+// -------------------------
+// Test helpers
+// -------------------------
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::SeedKeystore;
+    use secrecy::{ExposeSecret, SecretString};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// In-process keystore; `unavailable()` fails every call like a missing
+    /// Secret Service.
+    #[derive(Default)]
+    pub struct MockKeystore {
+        entries: Mutex<HashMap<String, String>>,
+        unavailable: bool,
+        loads: Mutex<usize>,
+    }
+
+    impl std::fmt::Debug for MockKeystore {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let labels: Vec<String> = self.entries.lock().unwrap().keys().cloned().collect();
+            f.debug_struct("MockKeystore")
+                .field("labels", &labels)
+                .finish()
+        }
+    }
+
+    impl MockKeystore {
+        pub fn unavailable() -> Self {
+            Self {
+                unavailable: true,
+                ..Default::default()
+            }
+        }
+
+        pub fn contains(&self, label: &str) -> bool {
+            self.entries.lock().unwrap().contains_key(label)
+        }
+
+        pub fn loads(&self) -> usize {
+            *self.loads.lock().unwrap()
+        }
+    }
+
+    impl SeedKeystore for MockKeystore {
+        fn store(&self, label: &str, seed: &SecretString) -> Result<(), String> {
+            if self.unavailable {
+                return Err("no secret service".to_string());
+            }
+            self.entries
+                .lock()
+                .unwrap()
+                .insert(label.to_string(), seed.expose_secret().clone());
+            Ok(())
+        }
+
+        fn load(&self, label: &str) -> Result<SecretString, String> {
+            *self.loads.lock().unwrap() += 1;
+            self.entries
+                .lock()
+                .unwrap()
+                .get(label)
+                .map(|seed| SecretString::new(seed.clone()))
+                .ok_or_else(|| "no entry".to_string())
+        }
+
+        fn delete(&self, label: &str) -> Result<(), String> {
+            self.entries.lock().unwrap().remove(label);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::MockKeystore;
+    use super::*;
+
+    #[test]
+    fn test_keystore_vault_fetches_on_demand() {
+        let keystore = Arc::new(MockKeystore::default());
+        let vault = SeedVault::open(
+            SeedStorage::OsKeystore,
+            keystore.clone(),
+            "wallet",
+            SecretString::new("seed".into()),
+        );
+        assert_eq!(vault.storage(), SeedStorage::OsKeystore);
+        let len = vault.with_seed(|seed| seed.expose_secret().len()).unwrap();
+        assert_eq!(len, 4);
+        // One read to verify the store, one for `with_seed`.
+        assert_eq!(keystore.loads(), 2);
+
+        vault.forget().unwrap();
+        assert!(!keystore.contains("wallet"));
+        assert!(vault.secret().is_err());
+    }
+
+    #[test]
+    fn test_unavailable_keystore_falls_back_to_memory() {
+        let vault = SeedVault::open(
+            SeedStorage::OsKeystore,
+            Arc::new(MockKeystore::unavailable()),
+            "wallet",
+            SecretString::new("seed".into()),
+        );
+        assert_eq!(vault.storage(), SeedStorage::InMemory);
+        assert_eq!(vault.secret().unwrap().expose_secret(), "seed");
+        assert_eq!(format!("{:?}", vault), "SeedVault::InMemory([REDACTED])");
+    }
+}
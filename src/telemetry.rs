@@ -0,0 +1,149 @@
+//! W3C trace-context propagation for outgoing HTTP calls.
+//!
+//! With the `otel` feature enabled, [`trace_headers`] serializes the active
+//! OpenTelemetry context (from the current `tracing` span, or the attached
+//! OpenTelemetry context) through the globally installed text-map propagator,
+//! producing `traceparent` / `tracestate` headers. Relayer JSON-RPC calls and
+//! LCD/RPC `reqwest` calls attach these headers on every request.
+//!
+//! Install a propagator once at startup, e.g.
+//! `opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new())`.
+//!
+//! Without the feature every helper here is a no-op.
+
+/// Header name/value pairs describing the active trace context.
+///
+/// Empty when no valid span context is active, no propagator is installed,
+/// or the `otel` feature is disabled.
+#[cfg(feature = "otel")]
+pub fn trace_headers() -> Vec<(String, String)> {
+    use opentelemetry::trace::TraceContextExt;
+    use std::collections::HashMap;
+
+    let cx = current_context();
+    if !cx.span().span_context().is_valid() {
+        return Vec::new();
+    }
+    let mut carrier: HashMap<String, String> = HashMap::new();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut carrier)
+    });
+    carrier.into_iter().collect()
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn trace_headers() -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// Prefer the `tracing` span's OpenTelemetry context; fall back to the attached one.
+#[cfg(feature = "otel")]
+fn current_context() -> opentelemetry::Context {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let from_span = tracing::Span::current().context();
+    if from_span.span().span_context().is_valid() {
+        from_span
+    } else {
+        opentelemetry::Context::current()
+    }
+}
+
+/// Record the relayer's request/correlation id on the active span.
+#[cfg(feature = "otel")]
+pub fn record_request_id(request_id: &str) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    tracing::Span::current().set_attribute("relayer.request_id", request_id.to_string());
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn record_request_id(_request_id: &str) {}
+
+/// Wrap `f` so that it runs inside the caller's trace context, e.g. when
+/// handing work to `tokio::task::spawn_blocking`.
+#[cfg(feature = "otel")]
+pub fn in_current_context<F, R>(f: F) -> impl FnOnce() -> R + Send
+where
+    F: FnOnce() -> R + Send,
+{
+    let cx = current_context();
+    move || {
+        let _guard = cx.attach();
+        f()
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn in_current_context<F, R>(f: F) -> impl FnOnce() -> R + Send
+where
+    F: FnOnce() -> R + Send,
+{
+    f
+}
+
+/// Attach the active trace context to an outgoing `reqwest` request.
+pub trait WithTraceContext: Sized {
+    fn with_trace_context(self) -> Self;
+}
+
+impl WithTraceContext for reqwest::RequestBuilder {
+    fn with_trace_context(self) -> Self {
+        trace_headers()
+            .into_iter()
+            .fold(self, |rb, (name, value)| rb.header(name, value))
+    }
+}
+
+impl WithTraceContext for reqwest::blocking::RequestBuilder {
+    fn with_trace_context(self) -> Self {
+        trace_headers()
+            .into_iter()
+            .fold(self, |rb, (name, value)| rb.header(name, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "otel"))]
+    #[test]
+    fn test_trace_headers_empty_without_feature() {
+        assert!(trace_headers().is_empty());
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_trace_headers_from_attached_context() {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+        };
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        assert!(trace_headers().is_empty());
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let _guard = opentelemetry::Context::current()
+            .with_remote_span_context(span_context)
+            .attach();
+
+        let headers = trace_headers();
+        let traceparent = headers
+            .iter()
+            .find(|(name, _)| name == "traceparent")
+            .map(|(_, value)| value.as_str());
+        assert_eq!(
+            traceparent,
+            Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+        );
+    }
+}
@@ -23,9 +23,9 @@ mod tests {
     // This function initializes the logger for the tests.
     fn init_logger() {
         INIT.call_once(|| {
-            // `is_test(true)` keeps the default filter at `trace`
-            // and respects RUST_LOG if you set it.
-            env_logger::builder().is_test(true).try_init().ok();
+            // `env_logger` with `is_test(true)`, which keeps the default
+            // filter at `trace` and respects RUST_LOG if you set it.
+            crate::log_privacy::test_logger::init();
         });
     }
     async fn global_setup() {
@@ -47,7 +47,7 @@ mod tests {
                                 return;
                             }
                         };
-                        if let Err(e) = wallet.export_to_json("test.json") {
+                        if let Err(e) = wallet.export_to_json("test.json", true) {
                             warn!("Failed to export wallet: {}", e);
                         } else {
                             println!("wallet exported successfully");
@@ -104,7 +104,7 @@ mod tests {
         get_test_tokens(&mut wallet).await?;
         wallet.update_balance().await?;
         wallet.update_account_info().await?;
-        wallet.export_to_json("test.json")?;
+        wallet.export_to_json("test.json", true)?;
 
         println!("wallet: {:?}", wallet);
         Ok(())
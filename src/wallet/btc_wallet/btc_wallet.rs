@@ -73,6 +73,17 @@ impl BtcWallet {
         })
     }
 
+    /// [`from_mnemonic`](Self::from_mnemonic) with a BIP-39 passphrase.
+    pub fn from_mnemonic_with_passphrase(mnemonic: &str, passphrase: &str) -> anyhow::Result<Self> {
+        let (wif, address) =
+            super::keys::segwit_from_mnemonic_with_passphrase(mnemonic, passphrase)?;
+        Ok(BtcWallet {
+            wif,
+            address,
+            network: BtcNetwork::from_config(),
+        })
+    }
+
     /// Create from a WIF private key.
     pub fn from_wif(wif: &str) -> anyhow::Result<Self> {
         let (wif, address) = super::keys::segwit_from_private_key(wif)?;
@@ -83,6 +94,16 @@ impl BtcWallet {
         })
     }
 
+    /// Create from a raw 32-byte secp256k1 secret key.
+    pub fn from_secret_key(secret: &[u8]) -> anyhow::Result<Self> {
+        let (wif, address) = super::keys::segwit_from_secret_key(secret)?;
+        Ok(BtcWallet {
+            wif,
+            address,
+            network: BtcNetwork::from_config(),
+        })
+    }
+
     /// Get the WIF private key (for building BDK descriptor).
     pub fn wif(&self) -> &str {
         &self.wif
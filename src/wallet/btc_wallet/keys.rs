@@ -1,10 +1,12 @@
+use crate::security::entropy::{self, EntropySource};
 use bip39::{Language, Mnemonic};
 use bitcoin::{
     Address, CompressedPublicKey, Network, NetworkKind, PrivateKey, PublicKey,
     bip32::{DerivationPath, Xpriv},
-    secp256k1::Secp256k1,
+    secp256k1::{Secp256k1, SecretKey},
 };
 use std::str::FromStr;
+use zeroize::Zeroizing;
 
 fn btc_network() -> (Network, NetworkKind) {
     if crate::config::is_btc_mainnet() {
@@ -16,12 +18,20 @@ fn btc_network() -> (Network, NetworkKind) {
 
 /// Returns (WIF, bc1q/tb1q address)
 pub fn segwit_from_mnemonic(mnemonic: &str) -> anyhow::Result<(String, String)> {
+    segwit_from_mnemonic_with_passphrase(mnemonic, "")
+}
+
+/// [`segwit_from_mnemonic`] with a BIP-39 passphrase; empty for none.
+pub fn segwit_from_mnemonic_with_passphrase(
+    mnemonic: &str,
+    passphrase: &str,
+) -> anyhow::Result<(String, String)> {
     let mnemonic = Mnemonic::parse_in(Language::English, mnemonic)?;
-    let seed = mnemonic.to_seed("");
+    let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
 
     let (network, network_kind) = btc_network();
 
-    let master = Xpriv::new_master(network, &seed)?;
+    let master = Xpriv::new_master(network, &*seed)?;
     let path = DerivationPath::from_str("m/84'/0'/0'/0/0")?;
     let secp = Secp256k1::signing_only();
     let child = master.derive_priv(&secp, &path)?;
@@ -41,7 +51,14 @@ pub fn segwit_from_mnemonic(mnemonic: &str) -> anyhow::Result<(String, String)>
 /// Generate a random valid BTC segwit address using a fresh mnemonic.
 /// Returns (WIF, bc1q/tb1q address).
 pub fn generate_random_btc_address() -> anyhow::Result<(String, String)> {
-    let mnemonic = Mnemonic::generate_in(Language::English, 24)?;
+    generate_random_btc_address_with(&*entropy::default_source())
+}
+
+/// [`generate_random_btc_address`] with the mnemonic entropy drawn from `source`.
+pub fn generate_random_btc_address_with(
+    source: &dyn EntropySource,
+) -> anyhow::Result<(String, String)> {
+    let mnemonic = entropy::generate_mnemonic(source)?;
     segwit_from_mnemonic(&mnemonic.to_string())
 }
 
@@ -55,6 +72,17 @@ pub fn segwit_from_private_key(private_key: &str) -> anyhow::Result<(String, Str
     Ok((privkey.to_wif(), addr.to_string()))
 }
 
+/// Native SegWit key for a raw 32-byte secp256k1 secret on the configured
+/// network. Returns (WIF, bc1q/tb1q address).
+pub fn segwit_from_secret_key(secret: &[u8]) -> anyhow::Result<(String, String)> {
+    let (network, network_kind) = btc_network();
+    let privkey = PrivateKey::new(SecretKey::from_slice(secret)?, network_kind);
+    let secp = Secp256k1::signing_only();
+    let compressed_pubkey = CompressedPublicKey::from_private_key(&secp, &privkey)?;
+    let addr = Address::p2wpkh(&compressed_pubkey, network);
+    Ok((privkey.to_wif(), addr.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,6 +95,19 @@ mod tests {
         println!("Address: {}", address);
     }
 
+    #[test]
+    fn test_random_btc_address_uses_entropy_source() {
+        use crate::security::entropy::testing::CountingEntropy;
+
+        let source = CountingEntropy::starting_at(9);
+        let (wif_a, addr_a) = generate_random_btc_address_with(&source).unwrap();
+        assert_eq!(source.calls(), 1);
+        let (wif_b, addr_b) =
+            generate_random_btc_address_with(&CountingEntropy::starting_at(9)).unwrap();
+        assert_eq!(wif_a, wif_b);
+        assert_eq!(addr_a, addr_b);
+    }
+
     #[test]
     fn test_segwit_from_private_key() {
         let private_key = "Ky3HTdELEKGJaHBXn3sstmxWbiJVNinKUnZoDanPpBR6czAPMMVg";
@@ -74,4 +115,13 @@ mod tests {
         println!("WIF: {}", wif);
         println!("Address: {}", address);
     }
+
+    #[test]
+    fn test_segwit_from_secret_key_matches_wif() {
+        let secret = [7u8; 32];
+        let (wif, address) = segwit_from_secret_key(&secret).unwrap();
+        let (_, from_wif) = segwit_from_private_key(&wif).unwrap();
+        assert_eq!(address, from_wif);
+        assert!(segwit_from_secret_key(&[0u8; 32]).is_err());
+    }
 }
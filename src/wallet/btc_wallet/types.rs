@@ -21,6 +21,46 @@ pub struct BtcDepositInfo {
     pub is_confirmed: bool,
 }
 
+/// Registration of a wallet's BTC deposit address as the chain records it;
+/// see `Wallet::btc_registration_status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BtcRegistrationStatus {
+    /// No deposit address is registered for the twilight address.
+    Unregistered,
+    /// `address` is registered, but the bridge has not confirmed it yet.
+    Pending { address: String },
+    /// `address` is registered and confirmed by the bridge.
+    Registered { address: String },
+}
+
+impl BtcRegistrationStatus {
+    /// Status of `registered`, the address the chain holds for a twilight
+    /// address, given the `details` of that twilight address's deposits.
+    pub fn from_chain(registered: Option<&str>, details: &[BtcDepositDetail]) -> Self {
+        let Some(address) = registered else {
+            return Self::Unregistered;
+        };
+        let confirmed = details
+            .iter()
+            .any(|detail| detail.btc_deposit_address == address && detail.is_confirmed);
+        let address = address.to_string();
+        if confirmed {
+            Self::Registered { address }
+        } else {
+            Self::Pending { address }
+        }
+    }
+
+    /// The registered address, confirmed or not.
+    pub fn address(&self) -> Option<&str> {
+        match self {
+            Self::Unregistered => None,
+            Self::Pending { address } | Self::Registered { address } => Some(address),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BtcDepositDetail {
     pub btc_deposit_address: String,
@@ -42,7 +82,7 @@ pub struct BtcProposedReserve {
 }
 
 /// On-chain BTC withdrawal request status from LCD.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BtcWithdrawStatus {
     pub withdraw_identifier: u64,
     pub withdraw_address: String,
@@ -1,4 +1,4 @@
-use bitcoin::{Address, Network};
+use bitcoin::{Address, AddressType, Network};
 use std::str::FromStr;
 
 /// Validate that a BTC address is a native SegWit address for the configured network.
@@ -33,3 +33,45 @@ pub fn validate_btc_segwit_address(addr: &str) -> Result<(), String> {
     }
     Ok(())
 }
+
+/// Validate a BTC deposit address for the configured network: native SegWit
+/// (P2WPKH) or Taproot (P2TR).
+pub fn validate_btc_deposit_address(addr: &str) -> Result<(), String> {
+    let network = if crate::config::is_btc_mainnet() {
+        Network::Bitcoin
+    } else {
+        Network::Testnet
+    };
+    check_deposit_address(addr, network)
+}
+
+fn check_deposit_address(addr: &str, network: Network) -> Result<(), String> {
+    let parsed = Address::from_str(addr)
+        .map_err(|e| format!("Invalid BTC address: {}", e))?
+        .require_network(network)
+        .map_err(|e| format!("Address network mismatch: {}", e))?;
+    match parsed.address_type() {
+        Some(AddressType::P2wpkh) | Some(AddressType::P2tr) => Ok(()),
+        _ => Err("Deposit address must be a P2WPKH or P2TR address".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_address_accepts_p2wpkh_and_p2tr_only() {
+        let mainnet = |addr| check_deposit_address(addr, Network::Bitcoin);
+        assert!(mainnet("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").is_ok());
+        assert!(mainnet("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr").is_ok());
+        // P2WSH and legacy P2PKH.
+        assert!(mainnet("bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3").is_err());
+        assert!(mainnet("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").is_err());
+
+        let testnet_addr = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+        let err = mainnet(testnet_addr).unwrap_err();
+        assert!(err.starts_with("Address network mismatch"), "{}", err);
+        assert!(check_deposit_address(testnet_addr, Network::Testnet).is_ok());
+    }
+}
@@ -0,0 +1,484 @@
+//! BTC withdrawal requests: burn sats held on Twilight and have the bridge
+//! pay them out in BTC from a reserve.
+//!
+//! The user side of the bridge is a single `MsgWithdrawBtcRequest`; the
+//! signing, broadcast and confirmation messages that follow it are sent by
+//! validators and judges. A request moves through these states:
+//!
+//! 1. [`WithdrawalLifecycle::NotFound`] until the request transaction is included.
+//! 2. [`WithdrawalLifecycle::Requested`] once the chain has recorded it.
+//! 3. [`WithdrawalLifecycle::Queued`] while it waits in the reserve's withdraw pool.
+//! 4. [`WithdrawalLifecycle::Processing`] while a sweep round is paying it out.
+//! 5. [`WithdrawalLifecycle::Confirmed`] when the BTC payout is confirmed.
+//!
+//! The chain keys requests by requester, reserve, destination and amount, so
+//! a [`BtcWithdrawalId`] carries those instead of the numeric identifier,
+//! which the broadcast response does not include.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::btc_wallet::types::{BtcReserve, BtcWithdrawStatus};
+use super::btc_wallet::validation::validate_btc_segwit_address;
+use super::wallet::Balance;
+use crate::nyks_rpc::rpcclient::method::TX_FEE_NYKS;
+use crate::nyks_rpc::rpcclient::txresult::TxResult;
+use crate::MsgWithdrawBtcRequest;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BtcWithdrawalError {
+    #[error("invalid BTC withdrawal address: {0}")]
+    InvalidAddress(String),
+    #[error("withdrawal amount must be greater than zero")]
+    ZeroAmount,
+    #[error("withdrawal request is missing {0}")]
+    Missing(&'static str),
+    #[error("insufficient sats: {available} available, {requested} requested")]
+    InsufficientSats { available: u64, requested: u64 },
+    #[error(
+        "insufficient nyks for the transaction fee: {available} available, {required} required"
+    )]
+    InsufficientFee { available: u64, required: u64 },
+    #[error("no BTC reserve holds enough to pay out {0} sats")]
+    NoReserve(u64),
+}
+
+/// Builder for `MsgWithdrawBtcRequest`; [`build`](Self::build) validates it.
+#[derive(Debug, Clone, Default)]
+pub struct WithdrawBtcRequestBuilder {
+    twilight_address: String,
+    withdraw_address: Option<String>,
+    reserve_id: Option<u64>,
+    amount_sats: Option<u64>,
+}
+
+impl WithdrawBtcRequestBuilder {
+    /// Request sent by `twilight_address`, whose sats are burned.
+    pub fn new(twilight_address: impl Into<String>) -> Self {
+        Self {
+            twilight_address: twilight_address.into(),
+            ..Self::default()
+        }
+    }
+
+    /// BTC address that receives the payout.
+    pub fn to(mut self, btc_address: impl Into<String>) -> Self {
+        self.withdraw_address = Some(btc_address.into());
+        self
+    }
+
+    /// Reserve that pays out (see `Wallet::fetch_btc_reserves`).
+    pub fn reserve(mut self, reserve_id: u64) -> Self {
+        self.reserve_id = Some(reserve_id);
+        self
+    }
+
+    pub fn amount(mut self, amount_sats: u64) -> Self {
+        self.amount_sats = Some(amount_sats);
+        self
+    }
+
+    pub fn build(self) -> Result<MsgWithdrawBtcRequest, BtcWithdrawalError> {
+        let withdraw_address = self
+            .withdraw_address
+            .ok_or(BtcWithdrawalError::Missing("a destination address"))?;
+        validate_btc_segwit_address(&withdraw_address)
+            .map_err(BtcWithdrawalError::InvalidAddress)?;
+        let reserve_id = self
+            .reserve_id
+            .ok_or(BtcWithdrawalError::Missing("a reserve"))?;
+        let withdraw_amount = match self.amount_sats {
+            None => return Err(BtcWithdrawalError::Missing("an amount")),
+            Some(0) => return Err(BtcWithdrawalError::ZeroAmount),
+            Some(amount) => amount,
+        };
+        Ok(MsgWithdrawBtcRequest {
+            withdraw_address,
+            reserve_id,
+            withdraw_amount,
+            twilight_address: self.twilight_address,
+        })
+    }
+}
+
+/// Check that `balance` covers `amount_sats` plus the transaction fee.
+pub fn check_withdrawal_funds(
+    balance: &Balance,
+    amount_sats: u64,
+) -> Result<(), BtcWithdrawalError> {
+    if balance.sats < amount_sats {
+        return Err(BtcWithdrawalError::InsufficientSats {
+            available: balance.sats,
+            requested: amount_sats,
+        });
+    }
+    if balance.nyks < TX_FEE_NYKS {
+        return Err(BtcWithdrawalError::InsufficientFee {
+            available: balance.nyks,
+            required: TX_FEE_NYKS,
+        });
+    }
+    Ok(())
+}
+
+/// The reserve with the most BTC, if it can pay out `amount_sats`.
+pub fn select_reserve(reserves: &[BtcReserve], amount_sats: u64) -> Option<&BtcReserve> {
+    reserves
+        .iter()
+        .filter(|r| r.total_value >= amount_sats)
+        .max_by_key(|r| r.total_value)
+}
+
+/// A withdrawal request as the chain looks it up.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BtcWithdrawalId {
+    pub reserve_id: u64,
+    pub btc_address: String,
+    pub amount_sats: u64,
+}
+
+impl From<&MsgWithdrawBtcRequest> for BtcWithdrawalId {
+    fn from(msg: &MsgWithdrawBtcRequest) -> Self {
+        Self {
+            reserve_id: msg.reserve_id,
+            btc_address: msg.withdraw_address.clone(),
+            amount_sats: msg.withdraw_amount,
+        }
+    }
+}
+
+/// A broadcast withdrawal request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BtcWithdrawalSubmission {
+    pub id: BtcWithdrawalId,
+    pub tx: TxResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum WithdrawalLifecycle {
+    /// Not on chain: the request is not included yet, or was rejected.
+    NotFound,
+    /// Recorded on chain, not yet in the reserve's withdraw pool.
+    Requested { withdraw_identifier: u64 },
+    /// Waiting in the reserve's withdraw pool for a sweep round.
+    Queued { withdraw_identifier: u64 },
+    /// Being paid out by sweep round `round_id`.
+    Processing {
+        withdraw_identifier: u64,
+        round_id: u64,
+    },
+    /// BTC payout confirmed.
+    Confirmed { withdraw_identifier: u64 },
+}
+
+impl WithdrawalLifecycle {
+    /// Derive the state from the LCD withdraw request and, for unconfirmed
+    /// requests, the reserve's withdraw pool.
+    pub fn from_chain(
+        request: Option<&BtcWithdrawStatus>,
+        pool: Option<&ReserveWithdrawPool>,
+    ) -> Self {
+        let Some(request) = request else {
+            return WithdrawalLifecycle::NotFound;
+        };
+        let withdraw_identifier = request.withdraw_identifier;
+        if request.is_confirmed {
+            return WithdrawalLifecycle::Confirmed {
+                withdraw_identifier,
+            };
+        }
+        match pool {
+            Some(pool) if pool.processing.contains(&withdraw_identifier) => {
+                WithdrawalLifecycle::Processing {
+                    withdraw_identifier,
+                    round_id: pool.round_id,
+                }
+            }
+            Some(pool) if pool.queued.contains(&withdraw_identifier) => {
+                WithdrawalLifecycle::Queued {
+                    withdraw_identifier,
+                }
+            }
+            _ => WithdrawalLifecycle::Requested {
+                withdraw_identifier,
+            },
+        }
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self, WithdrawalLifecycle::Confirmed { .. })
+    }
+
+    /// Value of the `status` column in `btc_withdrawals`.
+    pub fn db_status(&self) -> &'static str {
+        if self.is_confirmed() {
+            "confirmed"
+        } else {
+            "submitted"
+        }
+    }
+}
+
+impl std::fmt::Display for WithdrawalLifecycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WithdrawalLifecycle::NotFound => write!(f, "NOT FOUND"),
+            WithdrawalLifecycle::Requested { .. } => write!(f, "REQUESTED"),
+            WithdrawalLifecycle::Queued { .. } => write!(f, "QUEUED"),
+            WithdrawalLifecycle::Processing { round_id, .. } => {
+                write!(f, "PROCESSING (round {})", round_id)
+            }
+            WithdrawalLifecycle::Confirmed { .. } => write!(f, "CONFIRMED"),
+        }
+    }
+}
+
+/// Withdraw requests a reserve is paying out or has queued.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveWithdrawPool {
+    pub reserve_id: u64,
+    pub round_id: u64,
+    pub processing: Vec<u64>,
+    pub queued: Vec<u64>,
+    pub current_processing_index: u64,
+}
+
+/// LCD JSON encodes 64-bit integers as strings and narrower ones as numbers.
+fn lcd_u64(value: Option<&Value>) -> Option<u64> {
+    match value? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn lcd_text(value: Option<&Value>, default: &str) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        _ => default.to_string(),
+    }
+}
+
+fn lcd_ids(value: Option<&Value>) -> Vec<u64> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|ids| ids.iter().filter_map(|id| lcd_u64(Some(id))).collect())
+        .unwrap_or_default()
+}
+
+/// Parse a `/twilight-project/nyks/volt/btc_withdraw_request/{address}`
+/// response; `None` when it holds no request.
+pub fn parse_withdraw_request_response(body: &str) -> anyhow::Result<Option<BtcWithdrawStatus>> {
+    let json: Value = serde_json::from_str(body)?;
+    let Some(req) = json.get("BtcWithdrawRequest") else {
+        return Ok(None);
+    };
+    Ok(Some(BtcWithdrawStatus {
+        withdraw_identifier: lcd_u64(req.get("withdrawIdentifier")).unwrap_or(0),
+        withdraw_address: lcd_text(req.get("withdrawAddress"), ""),
+        withdraw_reserve_id: lcd_text(req.get("withdrawReserveId"), "0"),
+        withdraw_amount: lcd_text(req.get("withdrawAmount"), "0"),
+        twilight_address: lcd_text(req.get("twilightAddress"), ""),
+        is_confirmed: req
+            .get("isConfirmed")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        creation_twilight_block_height: lcd_text(req.get("CreationTwilightBlockHeight"), "0"),
+    }))
+}
+
+/// Parse a `/twilight-project/nyks/volt/reserve_withdraw_pool/{reserveId}`
+/// response.
+pub fn parse_reserve_withdraw_pool(body: &str) -> anyhow::Result<ReserveWithdrawPool> {
+    let json: Value = serde_json::from_str(body)?;
+    let pool = json
+        .get("ReserveWithdrawPool")
+        .ok_or_else(|| anyhow::anyhow!("Missing ReserveWithdrawPool field in response"))?;
+    Ok(ReserveWithdrawPool {
+        reserve_id: lcd_u64(pool.get("ReserveID")).unwrap_or(0),
+        round_id: lcd_u64(pool.get("RoundID")).unwrap_or(0),
+        processing: lcd_ids(pool.get("processingWithdrawIdentifiers")),
+        queued: lcd_ids(pool.get("queuedWithdrawIdentifiers")),
+        current_processing_index: lcd_u64(pool.get("currentProcessingIndex")).unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nyks_rpc::mock_chain::{MockChain, MockResponse, NOT_FOUND};
+    use crate::wallet::Wallet;
+    use prost::Message;
+
+    const TWILIGHT_ADDRESS: &str = "twilight1x7vqcnmk0qcs5w4jnhwhlqpx5exe6d7yzv0dhg";
+
+    /// BIP-173 P2WPKH test vector for the configured BTC network.
+    fn btc_address() -> &'static str {
+        if crate::config::is_btc_mainnet() {
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        } else {
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+        }
+    }
+
+    fn request_body(confirmed: bool) -> String {
+        format!(
+            r#"{{"BtcWithdrawRequest":{{"withdrawIdentifier":7,"withdrawAddress":"{}","withdrawReserveId":"3","withdrawAmount":"50000","twilightAddress":"{}","isConfirmed":{},"CreationTwilightBlockHeight":"1200"}}}}"#,
+            btc_address(),
+            TWILIGHT_ADDRESS,
+            confirmed
+        )
+    }
+
+    fn pool_body(processing: &str, queued: &str) -> String {
+        format!(
+            r#"{{"ReserveWithdrawPool":{{"ReserveID":"3","RoundID":"12","processingWithdrawIdentifiers":[{}],"queuedWithdrawIdentifiers":[{}],"currentProcessingIndex":0}}}}"#,
+            processing, queued
+        )
+    }
+
+    #[test]
+    fn test_withdraw_request_golden_encoding() {
+        let msg = WithdrawBtcRequestBuilder::new(TWILIGHT_ADDRESS)
+            .to(btc_address())
+            .reserve(3)
+            .amount(50000)
+            .build()
+            .unwrap();
+        // Field 1 (withdrawAddress), 2 (reserveId), 3 (withdrawAmount), 4 (twilightAddress).
+        let mut expected = vec![0x0a, 0x2a];
+        expected.extend_from_slice(btc_address().as_bytes());
+        expected.extend_from_slice(&[0x10, 0x03, 0x18, 0xd0, 0x86, 0x03, 0x22, 0x2f]);
+        expected.extend_from_slice(TWILIGHT_ADDRESS.as_bytes());
+        assert_eq!(msg.encode_to_vec(), expected);
+
+        let any =
+            crate::nyks_rpc::rpcclient::method::MethodTypeURL::MsgWithdrawBtcRequest.type_url(msg);
+        assert_eq!(
+            any.type_url,
+            "/twilightproject.nyks.bridge.MsgWithdrawBtcRequest"
+        );
+        assert_eq!(any.value, expected);
+    }
+
+    #[test]
+    fn test_withdraw_request_validation() {
+        let base = WithdrawBtcRequestBuilder::new(TWILIGHT_ADDRESS).reserve(3);
+        assert!(matches!(
+            base.clone().to("not-an-address").amount(1).build(),
+            Err(BtcWithdrawalError::InvalidAddress(_))
+        ));
+        assert_eq!(
+            base.clone().to(btc_address()).amount(0).build(),
+            Err(BtcWithdrawalError::ZeroAmount)
+        );
+        assert_eq!(
+            WithdrawBtcRequestBuilder::new(TWILIGHT_ADDRESS)
+                .to(btc_address())
+                .amount(1)
+                .build(),
+            Err(BtcWithdrawalError::Missing("a reserve"))
+        );
+
+        let balance = Balance {
+            nyks: TX_FEE_NYKS,
+            sats: 100,
+        };
+        assert_eq!(check_withdrawal_funds(&balance, 100), Ok(()));
+        assert_eq!(
+            check_withdrawal_funds(&balance, 101),
+            Err(BtcWithdrawalError::InsufficientSats {
+                available: 100,
+                requested: 101
+            })
+        );
+        let no_fee = Balance { nyks: 0, sats: 100 };
+        assert!(matches!(
+            check_withdrawal_funds(&no_fee, 1),
+            Err(BtcWithdrawalError::InsufficientFee { .. })
+        ));
+    }
+
+    #[test]
+    fn test_lifecycle_from_lcd_responses() {
+        let pending = parse_withdraw_request_response(&request_body(false))
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending.withdraw_identifier, 7);
+        assert_eq!(pending.withdraw_reserve_id, "3");
+
+        let queued = parse_reserve_withdraw_pool(&pool_body("", "5,7")).unwrap();
+        let processing = parse_reserve_withdraw_pool(&pool_body("\"7\"", "")).unwrap();
+        let elsewhere = parse_reserve_withdraw_pool(&pool_body("1", "2")).unwrap();
+        assert_eq!(
+            WithdrawalLifecycle::from_chain(Some(&pending), Some(&queued)),
+            WithdrawalLifecycle::Queued {
+                withdraw_identifier: 7
+            }
+        );
+        assert_eq!(
+            WithdrawalLifecycle::from_chain(Some(&pending), Some(&processing)),
+            WithdrawalLifecycle::Processing {
+                withdraw_identifier: 7,
+                round_id: 12
+            }
+        );
+        assert_eq!(
+            WithdrawalLifecycle::from_chain(Some(&pending), Some(&elsewhere)),
+            WithdrawalLifecycle::Requested {
+                withdraw_identifier: 7
+            }
+        );
+
+        let confirmed = parse_withdraw_request_response(&request_body(true))
+            .unwrap()
+            .unwrap();
+        let lifecycle = WithdrawalLifecycle::from_chain(Some(&confirmed), Some(&queued));
+        assert!(lifecycle.is_confirmed());
+        assert_eq!(lifecycle.db_status(), "confirmed");
+        assert_eq!(
+            WithdrawalLifecycle::from_chain(None, None),
+            WithdrawalLifecycle::NotFound
+        );
+        assert_eq!(parse_withdraw_request_response("{}").unwrap(), None);
+        assert!(parse_reserve_withdraw_pool("{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_status_against_mock_lcd() {
+        let mut wallet = Wallet::from_mnemonic(
+            "test test test test test test test test test test test junk",
+            None,
+        )
+        .unwrap();
+        let id = BtcWithdrawalId {
+            reserve_id: 3,
+            btc_address: btc_address().to_string(),
+            amount_sats: 50000,
+        };
+
+        let chain = MockChain::spawn();
+        wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        let request = "/twilight-project/nyks/volt/btc_withdraw_request/";
+        chain.route(request, vec![MockResponse::ok(request_body(false))]);
+        chain.route(
+            "/twilight-project/nyks/volt/reserve_withdraw_pool/3",
+            vec![MockResponse::ok(pool_body("7", ""))],
+        );
+        assert_eq!(
+            wallet.withdrawal_status(&id).await.unwrap(),
+            WithdrawalLifecycle::Processing {
+                withdraw_identifier: 7,
+                round_id: 12
+            }
+        );
+
+        chain.route(request, vec![MockResponse::status(404, NOT_FOUND)]);
+        assert_eq!(
+            wallet.withdrawal_status(&id).await.unwrap(),
+            WithdrawalLifecycle::NotFound
+        );
+    }
+}
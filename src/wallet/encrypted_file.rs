@@ -0,0 +1,164 @@
+//! Passphrase-encrypted wallet files.
+//!
+//! [`Wallet::export_to_encrypted_json`](super::Wallet::export_to_encrypted_json)
+//! seals the document [`Wallet::export_to_json`](super::Wallet::export_to_json)
+//! would write with AES-256-GCM, under a key derived from the passphrase with
+//! PBKDF2-HMAC-SHA256: the scheme the database uses for stored wallets. The
+//! file is JSON with a versioned header:
+//!
+//! ```json
+//! {
+//!   "format": "nyks-wallet-encrypted",
+//!   "version": 1,
+//!   "kdf": "pbkdf2-hmac-sha256",
+//!   "iterations": 600000,
+//!   "salt": "<32 bytes hex>",
+//!   "nonce": "<12 bytes hex>",
+//!   "ciphertext": "<hex>"
+//! }
+//! ```
+//!
+//! The format name and version are authenticated along with the ciphertext,
+//! so a file whose header was edited fails to decrypt just like one whose
+//! ciphertext was. The key is derived with the file's own `iterations`, so
+//! files written before [`PBKDF2_ITERATIONS`] is raised still open; counts
+//! outside [`MIN_ITERATIONS`]..=[`MAX_ITERATIONS`] are refused.
+
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, KeyInit, Payload},
+};
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::error::WalletError;
+use crate::security::entropy::{self, EntropySource};
+use crate::security::{PBKDF2_ITERATIONS, SecurePassword};
+
+/// Value of the `format` field.
+pub const ENCRYPTED_WALLET_FORMAT: &str = "nyks-wallet-encrypted";
+/// Format version written by this build.
+pub const ENCRYPTED_WALLET_VERSION: u32 = 1;
+const KDF: &str = "pbkdf2-hmac-sha256";
+/// Fewest PBKDF2 iterations a file may name.
+pub const MIN_ITERATIONS: u32 = 100_000;
+/// Most PBKDF2 iterations a file may name, bounding the work an import does.
+pub const MAX_ITERATIONS: u32 = 10_000_000;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedWalletFile {
+    format: String,
+    version: u32,
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Associated data binding the header to the ciphertext.
+fn associated_data(format: &str, version: u32) -> Vec<u8> {
+    format!("{}:{}", format, version).into_bytes()
+}
+
+fn cipher(password: &SecretString, salt: &[u8], iterations: u32) -> Result<Aes256Gcm, WalletError> {
+    let key_bytes = Zeroizing::new(
+        SecurePassword::derive_key_with_iterations(password, salt, iterations)
+            .map_err(|e| WalletError::KeyDerivation(e.to_string()))?,
+    );
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&*key_bytes)))
+}
+
+/// Encrypt `plaintext` into the file contents, drawing the salt and nonce from
+/// the default entropy source.
+pub(crate) fn seal(plaintext: &[u8], password: &SecretString) -> Result<String, WalletError> {
+    seal_with(
+        plaintext,
+        password,
+        &*entropy::default_source(),
+        PBKDF2_ITERATIONS,
+    )
+}
+
+pub(crate) fn seal_with(
+    plaintext: &[u8],
+    password: &SecretString,
+    source: &dyn EntropySource,
+    iterations: u32,
+) -> Result<String, WalletError> {
+    let (salt, nonce) = entropy::salt_and_nonce(source);
+    let aad = associated_data(ENCRYPTED_WALLET_FORMAT, ENCRYPTED_WALLET_VERSION);
+    let ciphertext = cipher(password, &salt, iterations)?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &aad,
+            },
+        )
+        .map_err(|e| WalletError::Encryption(e.to_string()))?;
+    let file = EncryptedWalletFile {
+        format: ENCRYPTED_WALLET_FORMAT.to_string(),
+        version: ENCRYPTED_WALLET_VERSION,
+        kdf: KDF.to_string(),
+        iterations,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&file).map_err(|e| WalletError::Serialization(e.to_string()))
+}
+
+/// Decrypt file contents written by [`seal`]. A wrong password and a modified
+/// file fail the same way, with [`WalletError::Decryption`].
+pub(crate) fn open(
+    contents: &str,
+    password: &SecretString,
+) -> Result<Zeroizing<Vec<u8>>, WalletError> {
+    let file: EncryptedWalletFile = serde_json::from_str(contents)
+        .map_err(|e| WalletError::Import(format!("not an encrypted wallet file: {}", e)))?;
+    if file.format != ENCRYPTED_WALLET_FORMAT {
+        return Err(WalletError::Import(format!(
+            "unknown file format {:?}",
+            file.format
+        )));
+    }
+    if file.version != ENCRYPTED_WALLET_VERSION {
+        return Err(WalletError::Import(format!(
+            "unsupported encrypted wallet version {} (this build reads {})",
+            file.version, ENCRYPTED_WALLET_VERSION
+        )));
+    }
+    if file.kdf != KDF || !(MIN_ITERATIONS..=MAX_ITERATIONS).contains(&file.iterations) {
+        return Err(WalletError::Import(format!(
+            "unsupported key derivation {} with {} iterations",
+            file.kdf, file.iterations
+        )));
+    }
+    let field = |name: &str, value: &str| {
+        hex::decode(value).map_err(|e| WalletError::Import(format!("{} is not hex: {}", name, e)))
+    };
+    let salt = field("salt", &file.salt)?;
+    let nonce = field("nonce", &file.nonce)?;
+    let ciphertext = field("ciphertext", &file.ciphertext)?;
+    if nonce.len() != 12 {
+        return Err(WalletError::Import(format!(
+            "nonce must be 12 bytes, got {}",
+            nonce.len()
+        )));
+    }
+    let aad = associated_data(&file.format, file.version);
+    let plaintext = cipher(password, &salt, file.iterations)?
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| {
+            WalletError::Decryption("wrong password or the file has been modified".to_string())
+        })?;
+    Ok(Zeroizing::new(plaintext))
+}
@@ -5,17 +5,21 @@ use anyhow::anyhow;
 use base64::{engine::general_purpose, Engine as _};
 use cosmrs::crypto::{secp256k1::SigningKey, PublicKey};
 use cosmrs::tendermint::chain::Id;
-use cosmrs::{
-    tx::{Body, Fee, SignDoc, SignerInfo},
-    Coin,
-};
+use cosmrs::tx::{Body, SignDoc, SignerInfo};
+use crate::config::TxFeeConfig;
+use crate::nyks_rpc::lcd;
+use crate::nyks_rpc::rpcclient::{gas::estimate_tx_fee, method::cosmos_fee};
+use crate::telemetry::WithTraceContext;
 use log::debug;
 use prost::Message;
 use reqwest::Client;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
 
-use std::{error::Error, str::FromStr};
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
 
 pub fn create_register_btc_deposit_message(
     btc_address: String,
@@ -76,33 +80,167 @@ fn from_str_to_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s: &str = Deserialize::deserialize(deserializer)?;
+    // Owned, so escaped strings are rejected by the parse rather than by serde.
+    let s: String = Deserialize::deserialize(deserializer)?;
     s.parse::<u64>().map_err(serde::de::Error::custom)
 }
 
-pub async fn fetch_account_details(
+/// Failure modes of an LCD account lookup.
+///
+/// `NotFound` means the LCD has no record of the address yet, which right after a
+/// faucet transfer usually just means the transfer has not been indexed.
+#[derive(Debug, thiserror::Error)]
+pub enum AccountFetchError {
+    #[error("account {0} does not exist on chain yet")]
+    NotFound(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl AccountFetchError {
+    /// True when retrying later may succeed (the account is not indexed yet).
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, AccountFetchError::NotFound(_))
+    }
+}
+
+/// Parse an LCD `/cosmos/auth/v1beta1/accounts/{address}` response body.
+pub fn parse_account_response(body: &str) -> anyhow::Result<AccountResponse> {
+    serde_json::from_str(body).map_err(|e| anyhow!("invalid LCD account response: {}", e))
+}
+
+/// Like [`fetch_account_details`], but reports a missing account as
+/// [`AccountFetchError::NotFound`] instead of a generic error.
+pub async fn try_fetch_account_details(
     address: &str,
     lcd_endpoint: &str,
-) -> anyhow::Result<AccountResponse> {
+) -> Result<AccountResponse, AccountFetchError> {
     let url = format!("{}/cosmos/auth/v1beta1/accounts/{}", lcd_endpoint, address);
-    let client = Client::new();
-    let response = client.get(&url).send().await?;
+    let response = lcd::get(&url).await.map_err(anyhow::Error::from)?;
 
     if response.status().is_success() {
-        let text = response.text().await?;
-        let account_response: AccountResponse = serde_json::from_str(&text)?;
-        Ok(account_response)
+        let text = response.text().await.map_err(anyhow::Error::from)?;
+        Ok(parse_account_response(&text)?)
     } else {
         let status = response.status();
         let error_body = response
             .text()
             .await
             .unwrap_or_else(|_| "No response body".to_string());
-        Err(anyhow!(
+        if status == reqwest::StatusCode::NOT_FOUND
+            || error_body.to_lowercase().contains("not found")
+        {
+            return Err(AccountFetchError::NotFound(address.to_string()));
+        }
+        Err(AccountFetchError::Other(anyhow!(
             "Failed to fetch account details. Status: {}, Error: {}",
             status,
             error_body
-        ))
+        )))
+    }
+}
+
+pub async fn fetch_account_details(
+    address: &str,
+    lcd_endpoint: &str,
+) -> anyhow::Result<AccountResponse> {
+    try_fetch_account_details(address, lcd_endpoint)
+        .await
+        .map_err(|e| match e {
+            AccountFetchError::NotFound(addr) => anyhow!(
+                "Failed to fetch account details. Status: 404 Not Found, Error: account {} not found",
+                addr
+            ),
+            AccountFetchError::Other(e) => e,
+        })
+}
+
+/// How [`get_test_tokens_with_options`](crate::wallet::get_test_tokens_with_options)
+/// waits for each faucet step to land on chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaucetOptions {
+    /// Time between balance and tx checks.
+    pub poll_interval: Duration,
+    /// Budget shared by all the waits of one call.
+    pub timeout: Duration,
+}
+
+impl Default for FaucetOptions {
+    /// Poll every 2 s, for at most the 30 s the faucet used to sleep.
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(2),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A step of a faucet request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaucetStepKind {
+    /// NYKS from the `/faucet` endpoint.
+    Nyks,
+    /// Registration of the wallet's BTC deposit address.
+    RegisterBtcAddress,
+    /// Test sats from the `/mint` endpoint.
+    MintSats,
+}
+
+/// How a faucet step ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaucetStepStatus {
+    /// The balance changed, or the tx was committed.
+    Confirmed,
+    /// Not needed: the balance was already high enough, or the address
+    /// already registered.
+    Skipped,
+    /// The request or tx failed.
+    Failed(String),
+    /// Sent, but not seen on chain within the timeout.
+    TimedOut,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaucetStep {
+    pub kind: FaucetStepKind,
+    pub status: FaucetStepStatus,
+    /// Time spent on the request and the wait for it.
+    pub elapsed: Duration,
+}
+
+/// What a faucet request did, step by step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaucetReport {
+    pub steps: Vec<FaucetStep>,
+    /// Balance of the wallet afterwards.
+    pub balance: crate::wallet::Balance,
+    pub elapsed: Duration,
+}
+
+impl FaucetReport {
+    pub fn step(&self, kind: FaucetStepKind) -> Option<&FaucetStep> {
+        self.steps.iter().find(|step| step.kind == kind)
+    }
+}
+
+/// Await `check` every `interval` until it yields a value, or return `None`
+/// once `deadline` has passed. `check` always runs at least once.
+pub async fn poll_until<T, F, Fut>(interval: Duration, deadline: Instant, mut check: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    loop {
+        if let Some(value) = check().await {
+            return Some(value);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        sleep(interval.min(deadline - now)).await;
     }
 }
 
@@ -185,13 +323,27 @@ pub async fn mint_sats_5btc(
     }
 }
 
+/// Gas limit the deposit-address registration has always been signed with.
+/// It needs far less gas than the mints [`TxFeeConfig::gas_limit`] is sized
+/// for, so the configured fee buys this limit instead.
+pub const REGISTER_DEPOSIT_GAS_LIMIT: u64 = 200_000;
+
+/// `fee` with the registration's [`REGISTER_DEPOSIT_GAS_LIMIT`].
+fn registration_fee(fee: &TxFeeConfig) -> TxFeeConfig {
+    fee.clone().with_gas_limit(REGISTER_DEPOSIT_GAS_LIMIT)
+}
+
+/// Register `btc_address` for `sender_account`, paying the fee amount of
+/// `fee` for [`REGISTER_DEPOSIT_GAS_LIMIT`] gas; with a gas adjustment set
+/// the transaction is simulated first to size the gas limit.
 pub async fn sign_and_send_reg_deposit_tx(
     signing_key: SigningKey,
     public_key: PublicKey,
     sender_account: String,
     btc_address: String,
     lcd_endpoint: &str,
-) -> anyhow::Result<()> {
+    fee: &TxFeeConfig,
+) -> anyhow::Result<String> {
     // --- Msg & body
     let msg_any =
         create_register_btc_deposit_message(btc_address, 50_000, 10_000, sender_account.clone());
@@ -200,32 +352,142 @@ pub async fn sign_and_send_reg_deposit_tx(
     let account_details = fetch_account_details(&sender_account, lcd_endpoint).await?;
     let sequence = account_details.account.sequence;
     let account_number = account_details.account.account_number;
+    let fee = &registration_fee(fee);
 
-    // --- Fee & auth‑info
-    let gas_limit = 200_000u64;
-    let fee_amount = Coin {
-        denom: cosmrs::Denom::from_str("nyks").map_err(|e| anyhow!("{}", e))?,
-        amount: 1_000u64.into(),
+    // --- Fee, auth‑info & sign
+    let sign = |fee: &TxFeeConfig| -> anyhow::Result<String> {
+        let signer_info = SignerInfo::single_direct(Some(public_key), sequence);
+        let auth_info = signer_info.auth_info(cosmos_fee(fee)?);
+        let chain_id = Id::try_from("nyks").map_err(|e| anyhow!("{}", e))?;
+        let sign_doc = SignDoc::new(&body, &auth_info, &chain_id, account_number)
+            .map_err(|e| anyhow!("{}", e))?;
+        let raw_tx = sign_doc.sign(&signing_key).map_err(|e| anyhow!("{}", e))?;
+        let tx_bytes = raw_tx.to_bytes().map_err(|e| anyhow!("{}", e))?;
+        Ok(general_purpose::STANDARD.encode(&tx_bytes))
     };
-    let signer_info = SignerInfo::single_direct(Some(public_key), sequence);
-    let auth_info = signer_info.auth_info(Fee::from_amount_and_gas(fee_amount, gas_limit));
-
-    // --- Sign
-    let chain_id = Id::try_from("nyks").map_err(|e| anyhow!("{}", e))?;
-    let sign_doc =
-        SignDoc::new(&body, &auth_info, &chain_id, account_number).map_err(|e| anyhow!("{}", e))?;
-    let raw_tx = sign_doc.sign(&signing_key).map_err(|e| anyhow!("{}", e))?;
-
-    // --- Encode & broadcast
-    let tx_bytes = raw_tx.to_bytes().map_err(|e| anyhow!("{}", e))?;
-    let tx_base64 = general_purpose::STANDARD.encode(&tx_bytes);
+    let fee = estimate_tx_fee(fee, lcd_endpoint, |draft_fee| {
+        sign(draft_fee).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| anyhow!(e))?;
+    let tx_base64 = sign(&fee)?;
+
+    // --- Broadcast
     let client = Client::new();
     let res = client
         .post(format!("{}/cosmos/tx/v1beta1/txs", lcd_endpoint))
+        .with_trace_context()
         .json(&json!({ "tx_bytes": tx_base64, "mode": "BROADCAST_MODE_SYNC" }))
         .send()
         .await?;
 
-    debug!("Broadcast response: {}", res.text().await?);
-    Ok(())
+    let text = res.text().await?;
+    debug!("Broadcast response: {}", text);
+    parse_broadcast_response(&text)
+}
+
+/// Hash of the tx in an LCD `/cosmos/tx/v1beta1/txs` broadcast response,
+/// or the CheckTx error when it was rejected.
+fn parse_broadcast_response(body: &str) -> anyhow::Result<String> {
+    let body: Value =
+        serde_json::from_str(body).map_err(|e| anyhow!("invalid broadcast response: {}", e))?;
+    let tx_response = &body["tx_response"];
+    if let Some(code) = tx_response["code"].as_u64().filter(|code| *code != 0) {
+        return Err(anyhow!(
+            "BTC deposit address registration rejected with code {}: {}",
+            code,
+            tx_response["raw_log"].as_str().unwrap_or_default()
+        ));
+    }
+    tx_response["txhash"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("broadcast response has no tx hash: {}", body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response_mutations::{fixture, mutated_fixture};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_registration_keeps_its_gas_limit() {
+        let fee = registration_fee(&TxFeeConfig::default());
+        assert_eq!(fee.gas_limit, 200_000);
+        assert_eq!(fee.fee_amount, 1_000);
+
+        // A simulated registration pays the configured price per 200,000 gas.
+        let simulated = TxFeeConfig::default().with_gas_adjustment(Some(1.5));
+        let adjusted = registration_fee(&simulated).adjusted(200_000);
+        assert_eq!((adjusted.gas_limit, adjusted.fee_amount), (300_000, 1_500));
+    }
+
+    #[test]
+    fn test_parse_account_response() {
+        let account = parse_account_response(&fixture("lcd_account/base_account.json"))
+            .unwrap()
+            .account;
+        assert_eq!(account.account_number, 12);
+        assert_eq!(account.sequence, 3);
+
+        let huge = json!({"account": {
+            "@type": "/cosmos.auth.v1beta1.BaseAccount",
+            "address": account.address,
+            "pub_key": null,
+            "account_number": "18446744073709551616",
+            "sequence": "0",
+        }});
+        assert!(parse_account_response(&huge.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_broadcast_response() {
+        let accepted = r#"{"tx_response":{"code":0,"txhash":"ABC","raw_log":""}}"#;
+        assert_eq!(parse_broadcast_response(accepted).unwrap(), "ABC");
+        let rejected =
+            r#"{"tx_response":{"code":32,"txhash":"ABC","raw_log":"account sequence mismatch"}}"#;
+        assert!(parse_broadcast_response(rejected)
+            .unwrap_err()
+            .to_string()
+            .contains("code 32: account sequence mismatch"));
+        assert!(parse_broadcast_response(r#"{"code":3,"message":"bad"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_poll_until_stops_on_value_or_deadline() {
+        let mut calls = 0;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let found = poll_until(Duration::ZERO, deadline, || {
+            calls += 1;
+            let n = calls;
+            async move { (n == 3).then_some(n) }
+        })
+        .await;
+        assert_eq!(found, Some(3));
+
+        let mut calls = 0;
+        let started = Instant::now();
+        let found: Option<()> = poll_until(
+            Duration::from_millis(10),
+            started + Duration::from_millis(50),
+            || {
+                calls += 1;
+                async { None }
+            },
+        )
+        .await;
+        assert_eq!(found, None);
+        assert!(calls >= 2);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_account_response_never_panics(
+            body in mutated_fixture("lcd_account/base_account.json"),
+        ) {
+            let _ = parse_account_response(&String::from_utf8_lossy(&body));
+        }
+    }
 }
@@ -7,6 +7,11 @@ pub use faucet::*;
 pub mod seed_signer;
 pub use seed_signer::*;
 pub mod btc_wallet;
+pub mod btc_withdrawal;
+pub mod encrypted_file;
+pub mod nonce_manager;
+#[cfg(feature = "validator-wallet")]
+pub mod staking;
 
 // Backward-compat: old import path `crate::wallet::generate_btc_key::*` still works
 pub mod generate_btc_key {
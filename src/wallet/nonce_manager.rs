@@ -3,13 +3,35 @@
 //! Prevents sequence collisions when multiple transactions are signed
 //! concurrently by handing out monotonically increasing sequence numbers
 //! and allowing failed sequences to be released back into the pool.
+//!
+//! Every [`Wallet`](crate::wallet::Wallet) carries one, shared by its clones
+//! and by an `OrderWallet` built on it, so transactions signed in the same
+//! process count sequences on one counter instead of each asking the LCD,
+//! which lags behind transactions still in the mempool.
 
 use log::{debug, warn};
 use std::collections::BTreeSet;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex;
 
-use crate::wallet::faucet::fetch_account_details;
+#[cfg(feature = "order-wallet")]
+use crate::error::{Result as WalletResult, WalletError};
+#[cfg(feature = "order-wallet")]
+use crate::relayer_module::fetch_account_details_with_retry;
+use crate::wallet::faucet::{fetch_account_details, Account};
+
+/// CheckTx code for a tx signed with another sequence than the account's
+/// next one (ErrWrongSequence).
+pub const SEQUENCE_MISMATCH_CODE: u32 = 32;
+
+/// The sequence the chain expected, from the log of a
+/// [`SEQUENCE_MISMATCH_CODE`] rejection such as
+/// `account sequence mismatch, expected 7, got 5: incorrect account sequence`.
+pub fn expected_sequence(log: &str) -> Option<u64> {
+    let (_, rest) = log.split_once("expected ")?;
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
 
 /// Manages on-chain transaction sequence numbers for a single account.
 ///
@@ -115,12 +137,64 @@ impl NonceManager {
             fetch_account_details(address, lcd_endpoint)
                 .await
                 .map_err(|e| format!("Failed to fetch account details: {}", e))?;
+        self.apply_account(&account_response.account)
+    }
+
+    /// Like [`sync_from_chain`], but waits for the account to be indexed by the LCD
+    /// (e.g. right after a faucet transfer) instead of failing on the first 404.
+    ///
+    /// Returns [`WalletError::AccountNotOnChain`] if the account never shows up.
+    #[cfg(feature = "order-wallet")]
+    pub async fn sync_from_chain_with_retry(
+        &self,
+        lcd_endpoint: &str,
+        address: &str,
+    ) -> WalletResult<()> {
+        let account = fetch_account_details_with_retry(address, lcd_endpoint).await?;
+        self.apply_account(&account)
+            .map_err(WalletError::WalletAccountInfo)
+    }
+
+    /// Continue from `sequence`, e.g. the one a sequence mismatch rejection
+    /// says the chain expects, even if the local counter is ahead of it.
+    /// Released sequences are dropped: they are either consumed or handed out
+    /// again from `sequence` on.
+    pub fn reset_to(&self, sequence: u64) {
+        let prev = self.next.swap(sequence, Ordering::SeqCst);
+        if let Ok(mut released) = self.released.lock() {
+            released.clear();
+        }
+        self.synced.store(true, Ordering::Release);
+        debug!("NonceManager: reset from {} to {}", prev, sequence);
+    }
+
+    /// Like [`sync_from_chain`], but takes the chain's sequence even when the
+    /// local counter is ahead of it.
+    pub async fn reset_from_chain(&self, lcd_endpoint: &str, address: &str) -> Result<(), String> {
+        let account_response = fetch_account_details(address, lcd_endpoint)
+            .await
+            .map_err(|e| format!("Failed to fetch account details: {}", e))?;
+        self.apply_account(&account_response.account)?;
+        self.reset_to(account_response.account.sequence);
+        Ok(())
+    }
 
-        let chain_seq = account_response.account.sequence;
-        let chain_acc_num = account_response.account.account_number;
+    /// Re-anchor local state to an already-fetched on-chain account.
+    fn apply_account(&self, account: &Account) -> Result<(), String> {
+        self.sync_to(account.sequence, account.account_number)
+    }
 
+    /// Like [`sync_from_chain`], with a `chain_seq` and `chain_acc_num`
+    /// the caller fetched.
+    pub fn sync_to(&self, chain_seq: u64, chain_acc_num: u64) -> Result<(), String> {
         // Update account number
-        self.account_number.store(chain_acc_num, Ordering::Release);
+        let prev_acc_num = self.account_number.swap(chain_acc_num, Ordering::AcqRel);
+        if self.synced.load(Ordering::Acquire) && prev_acc_num != chain_acc_num {
+            warn!(
+                "NonceManager: account_number changed {} -> {}",
+                prev_acc_num, chain_acc_num
+            );
+        }
 
         // Update sequence: always advance to at least the chain value.
         // If our local counter is already ahead (pending txs in mempool),
@@ -230,4 +304,37 @@ mod tests {
         assert_eq!(s, 7);
         assert_eq!(nm.peek_next(), 8);
     }
+
+    #[test]
+    fn test_apply_account_picks_up_new_account_number() {
+        let nm = NonceManager::with_initial(3, 1);
+        let account = Account {
+            account_number: 9,
+            sequence: 5,
+            ..Default::default()
+        };
+        nm.apply_account(&account).unwrap();
+        let (seq, acc) = nm.acquire_next().unwrap();
+        assert_eq!(seq, 5);
+        assert_eq!(acc, 9);
+    }
+
+    #[test]
+    fn test_reset_to_moves_back_and_drops_released() {
+        let nm = NonceManager::with_initial(8, 1);
+        nm.acquire_next().unwrap(); // 8
+        nm.acquire_next().unwrap(); // 9
+        nm.release(9);
+        nm.reset_to(6);
+        assert_eq!(nm.released_count(), 0);
+        assert_eq!(nm.acquire_next().unwrap().0, 6);
+        assert_eq!(nm.acquire_next().unwrap().0, 7);
+    }
+
+    #[test]
+    fn test_expected_sequence() {
+        let log = "account sequence mismatch, expected 7, got 5: incorrect account sequence";
+        assert_eq!(expected_sequence(log), Some(7));
+        assert_eq!(expected_sequence("signature verification failed"), None);
+    }
 }
@@ -192,7 +192,7 @@ mod tests {
             Err(e) => return Err(anyhow::anyhow!(e)),
         };
 
-        println!("{:?}", seed.signature);
+        assert!(!seed.signature.is_empty());
         Ok(())
     }
 }
@@ -0,0 +1,481 @@
+//! Delegating the wallet's nyks to validators (`validator-wallet` feature).
+//!
+//! [`Wallet::delegate`], [`Wallet::undelegate`], [`Wallet::redelegate`] and
+//! [`Wallet::withdraw_rewards`] build the standard `cosmos.staking.v1beta1`
+//! and `cosmos.distribution.v1beta1` messages, sign them through the same
+//! `SignDoc` path and [`TxFeeConfig`](crate::config::TxFeeConfig) as every
+//! other chain transaction, and broadcast them to the wallet's RPC endpoint.
+//! They are sent with [`Wallet::sign_and_broadcast`], so they take their
+//! sequence from the wallet's nonce manager, in turn with the bridge and
+//! mint/burn transactions sent from the same wallet.
+//!
+//! The messages are the `cosmrs::proto` types. [`Wallet::query_delegations`]
+//! reads the delegations back from the LCD, page by page.
+
+use anyhow::anyhow;
+use cosmrs::AccountId;
+use cosmrs::proto::cosmos::base::v1beta1::Coin;
+use cosmrs::proto::cosmos::distribution::v1beta1::MsgWithdrawDelegatorReward;
+use cosmrs::proto::cosmos::staking::v1beta1::{MsgBeginRedelegate, MsgDelegate, MsgUndelegate};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::wallet::{BECH_PREFIX, Wallet};
+use crate::config::TxFeeConfig;
+use crate::log_privacy::LoggedAddress;
+use crate::nyks_rpc::lcd;
+use crate::nyks_rpc::rpcclient::gas::estimate_tx_fee;
+use crate::nyks_rpc::rpcclient::method::{MethodTypeURL, sign_msgs_with_fee_config};
+use crate::nyks_rpc::rpcclient::txresult::TxResult;
+
+/// Denom that is bonded to validators.
+pub const STAKING_DENOM: &str = "nyks";
+
+// -------------------------------------------------------------------------
+// Messages
+// -------------------------------------------------------------------------
+
+fn staking_coin(amount: u64) -> anyhow::Result<Option<Coin>> {
+    if amount == 0 {
+        return Err(anyhow!("staking amount must be greater than 0"));
+    }
+    Ok(Some(Coin {
+        denom: STAKING_DENOM.to_string(),
+        amount: amount.to_string(),
+    }))
+}
+
+/// Fails unless `address` is a bech32 validator operator address of this chain.
+pub fn check_validator_address(address: &str) -> anyhow::Result<()> {
+    let account_id: AccountId = address
+        .parse()
+        .map_err(|e| anyhow!("invalid validator address {}: {}", address, e))?;
+    let prefix = format!("{}valoper", BECH_PREFIX);
+    if account_id.prefix() != prefix {
+        return Err(anyhow!(
+            "validator address {} must start with {}",
+            address,
+            prefix
+        ));
+    }
+    Ok(())
+}
+
+/// `MsgDelegate` of `amount` nyks from `delegator` to `validator`.
+pub fn delegate_msg(delegator: &str, validator: &str, amount: u64) -> anyhow::Result<cosmrs::Any> {
+    check_validator_address(validator)?;
+    Ok(MethodTypeURL::MsgDelegate.type_url(MsgDelegate {
+        delegator_address: delegator.to_string(),
+        validator_address: validator.to_string(),
+        amount: staking_coin(amount)?,
+    }))
+}
+
+/// `MsgUndelegate` of `amount` nyks that `delegator` bonded to `validator`.
+pub fn undelegate_msg(
+    delegator: &str,
+    validator: &str,
+    amount: u64,
+) -> anyhow::Result<cosmrs::Any> {
+    check_validator_address(validator)?;
+    Ok(MethodTypeURL::MsgUndelegate.type_url(MsgUndelegate {
+        delegator_address: delegator.to_string(),
+        validator_address: validator.to_string(),
+        amount: staking_coin(amount)?,
+    }))
+}
+
+/// `MsgBeginRedelegate` of `amount` nyks from `src_validator` to `dst_validator`.
+pub fn redelegate_msg(
+    delegator: &str,
+    src_validator: &str,
+    dst_validator: &str,
+    amount: u64,
+) -> anyhow::Result<cosmrs::Any> {
+    check_validator_address(src_validator)?;
+    check_validator_address(dst_validator)?;
+    if src_validator == dst_validator {
+        return Err(anyhow!("cannot redelegate to the same validator"));
+    }
+    Ok(
+        MethodTypeURL::MsgBeginRedelegate.type_url(MsgBeginRedelegate {
+            delegator_address: delegator.to_string(),
+            validator_src_address: src_validator.to_string(),
+            validator_dst_address: dst_validator.to_string(),
+            amount: staking_coin(amount)?,
+        }),
+    )
+}
+
+/// `MsgWithdrawDelegatorReward` for the rewards `delegator` earned at `validator`.
+pub fn withdraw_reward_msg(delegator: &str, validator: &str) -> anyhow::Result<cosmrs::Any> {
+    check_validator_address(validator)?;
+    Ok(
+        MethodTypeURL::MsgWithdrawDelegatorReward.type_url(MsgWithdrawDelegatorReward {
+            delegator_address: delegator.to_string(),
+            validator_address: validator.to_string(),
+        }),
+    )
+}
+
+// -------------------------------------------------------------------------
+// Delegation queries
+// -------------------------------------------------------------------------
+
+/// One delegation of the wallet, as reported by the LCD.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delegation {
+    pub validator_address: String,
+    /// Validator shares, a decimal string.
+    pub shares: String,
+    /// Tokens the shares are worth, in `denom`.
+    pub balance: u64,
+    pub denom: String,
+}
+
+/// The delegations in one `/cosmos/staking/v1beta1/delegations/{address}`
+/// response page.
+pub fn parse_delegations_response(body: &Value) -> Result<Vec<Delegation>, String> {
+    let responses = body
+        .get("delegation_responses")
+        .and_then(|r| r.as_array())
+        .ok_or_else(|| format!("Missing delegation_responses in response: {}", body))?;
+    responses
+        .iter()
+        .map(|response| -> Result<Delegation, String> {
+            let delegation = &response["delegation"];
+            let balance = &response["balance"];
+            let validator_address = delegation["validator_address"]
+                .as_str()
+                .ok_or("Delegation without validator_address")?
+                .to_string();
+            let amount = balance["amount"].as_str().unwrap_or("0");
+            Ok(Delegation {
+                validator_address,
+                shares: delegation["shares"].as_str().unwrap_or("0").to_string(),
+                balance: amount
+                    .parse()
+                    .map_err(|e| format!("Invalid delegation amount {}: {}", amount, e))?,
+                denom: balance["denom"]
+                    .as_str()
+                    .unwrap_or(STAKING_DENOM)
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `pagination.next_key` of a response page; `None` on the last page.
+pub fn next_page_key(body: &Value) -> Option<String> {
+    body.pointer("/pagination/next_key")
+        .and_then(Value::as_str)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+}
+
+// -------------------------------------------------------------------------
+// Wallet operations
+// -------------------------------------------------------------------------
+
+impl Wallet {
+    /// Delegate `amount` nyks to `validator_addr`.
+    pub async fn delegate(
+        &mut self,
+        validator_addr: &str,
+        amount: u64,
+    ) -> anyhow::Result<TxResult> {
+        let msg = delegate_msg(&self.twilightaddress, validator_addr, amount)?;
+        info!(
+            "Delegating {} {} to {}",
+            amount,
+            STAKING_DENOM,
+            LoggedAddress(validator_addr)
+        );
+        self.send_staking_msgs(vec![msg], "Delegate").await
+    }
+
+    /// Start unbonding `amount` nyks from `validator_addr`. The tokens return
+    /// to the wallet after the chain's unbonding period.
+    pub async fn undelegate(
+        &mut self,
+        validator_addr: &str,
+        amount: u64,
+    ) -> anyhow::Result<TxResult> {
+        let msg = undelegate_msg(&self.twilightaddress, validator_addr, amount)?;
+        info!(
+            "Undelegating {} {} from {}",
+            amount,
+            STAKING_DENOM,
+            LoggedAddress(validator_addr)
+        );
+        self.send_staking_msgs(vec![msg], "Undelegate").await
+    }
+
+    /// Move `amount` bonded nyks from `src_validator` to `dst_validator`
+    /// without unbonding.
+    pub async fn redelegate(
+        &mut self,
+        src_validator: &str,
+        dst_validator: &str,
+        amount: u64,
+    ) -> anyhow::Result<TxResult> {
+        let msg = redelegate_msg(&self.twilightaddress, src_validator, dst_validator, amount)?;
+        info!(
+            "Redelegating {} {} from {} to {}",
+            amount,
+            STAKING_DENOM,
+            LoggedAddress(src_validator),
+            LoggedAddress(dst_validator)
+        );
+        self.send_staking_msgs(vec![msg], "Redelegate").await
+    }
+
+    /// The wallet's current delegations, following `pagination.next_key`
+    /// until the LCD's last page.
+    pub async fn query_delegations(&self) -> anyhow::Result<Vec<Delegation>> {
+        let base = format!(
+            "{}/cosmos/staking/v1beta1/delegations/{}",
+            self.chain_config.lcd_endpoint, self.twilightaddress
+        );
+        let mut delegations = Vec::new();
+        let mut next_key: Option<String> = None;
+        loop {
+            let mut url = reqwest::Url::parse(&base)
+                .map_err(|e| anyhow!("Invalid delegations URL {}: {}", base, e))?;
+            if let Some(key) = &next_key {
+                url.query_pairs_mut().append_pair("pagination.key", key);
+            }
+            let body: Value = lcd::get(url.as_str())
+                .await
+                .map_err(|e| anyhow!("Failed to query delegations: {}", e))?
+                .json()
+                .await
+                .map_err(|e| anyhow!("Failed to parse delegations response: {}", e))?;
+            delegations.extend(parse_delegations_response(&body).map_err(|e| anyhow!(e))?);
+            match next_page_key(&body) {
+                Some(key) if next_key.as_ref() != Some(&key) => next_key = Some(key),
+                _ => return Ok(delegations),
+            }
+        }
+    }
+
+    /// Withdraw the rewards of every delegation in one transaction.
+    pub async fn withdraw_rewards(&mut self) -> anyhow::Result<TxResult> {
+        let delegations = self.query_delegations().await?;
+        if delegations.is_empty() {
+            return Err(anyhow!("No delegations to withdraw rewards from"));
+        }
+        let msgs = delegations
+            .iter()
+            .map(|d| withdraw_reward_msg(&self.twilightaddress, &d.validator_address))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        info!("Withdrawing rewards from {} validators", msgs.len());
+        self.send_staking_msgs(msgs, "Withdraw rewards").await
+    }
+
+    /// Sign `msgs` at the account's current sequence with the wallet's fee
+    /// config and broadcast them with `broadcast_tx_sync`.
+    async fn send_staking_msgs(
+        &self,
+        msgs: Vec<cosmrs::Any>,
+        action: &str,
+    ) -> anyhow::Result<TxResult> {
+        if !self.nonce_manager.is_synced() {
+            self.sync_nonce().await?;
+        }
+        let sign =
+            |fee: &TxFeeConfig, sequence: u64, account_number: u64| -> anyhow::Result<String> {
+                sign_msgs_with_fee_config(
+                    msgs.clone(),
+                    self.public_key()?,
+                    sequence,
+                    account_number,
+                    self.signing_key()?,
+                    fee,
+                )
+            };
+        // Simulated at the sequence the transaction is about to take.
+        let (sequence, account_number) = (
+            self.nonce_manager.peek_next(),
+            self.nonce_manager.account_number(),
+        );
+        let fee = estimate_tx_fee(
+            &self.chain_config.tx_fee,
+            &self.chain_config.lcd_endpoint,
+            |draft_fee| sign(draft_fee, sequence, account_number).map_err(|e| e.to_string()),
+        )
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+        let result = self
+            .sign_and_broadcast(|sequence, account_number| sign(&fee, sequence, account_number))
+            .await?;
+        if result.code != 0 {
+            return Err(anyhow!(
+                "{} failed (code {}), TX Hash: {}: {}",
+                action,
+                result.code,
+                result.hash,
+                result.log.as_deref().unwrap_or_default()
+            ));
+        }
+        info!("{} sent, tx hash: {}", action, result.hash);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nyks_rpc::mock_chain::{MockChain, MockResponse};
+    use prost::Message;
+    use serde_json::json;
+
+    const MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    fn valoper(byte: u8) -> String {
+        AccountId::new("twilightvaloper", &[byte; 20])
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_delegate_msg_encodes_cosmos_staking_msg() {
+        let validator = valoper(1);
+        let any = delegate_msg("twilight1delegator", &validator, 25_000).unwrap();
+        assert_eq!(any.type_url, "/cosmos.staking.v1beta1.MsgDelegate");
+
+        let decoded = MsgDelegate::decode(any.value.as_slice()).unwrap();
+        assert_eq!(decoded.delegator_address, "twilight1delegator");
+        assert_eq!(decoded.validator_address, validator);
+        let amount = decoded.amount.unwrap();
+        assert_eq!(amount.denom, "nyks");
+        assert_eq!(amount.amount, "25000");
+
+        let any = redelegate_msg("twilight1delegator", &validator, &valoper(2), 10).unwrap();
+        let decoded = MsgBeginRedelegate::decode(any.value.as_slice()).unwrap();
+        assert_eq!(decoded.validator_dst_address, valoper(2));
+    }
+
+    #[test]
+    fn test_staking_msgs_reject_bad_input() {
+        let account = AccountId::new("twilight", &[1; 20]).unwrap().to_string();
+        assert!(delegate_msg("twilight1delegator", &account, 10).is_err());
+        assert!(delegate_msg("twilight1delegator", "not-bech32", 10).is_err());
+        assert!(undelegate_msg("twilight1delegator", &valoper(1), 0).is_err());
+        assert!(redelegate_msg("twilight1delegator", &valoper(1), &valoper(1), 10).is_err());
+    }
+
+    #[test]
+    fn test_parse_delegations_response() {
+        let body = json!({
+            "delegation_responses": [{
+                "delegation": {
+                    "delegator_address": "twilight1delegator",
+                    "validator_address": "twilightvaloper1abc",
+                    "shares": "50000.000000000000000000"
+                },
+                "balance": {"denom": "nyks", "amount": "50000"}
+            }],
+            "pagination": {"next_key": null, "total": "1"}
+        });
+        let delegations = parse_delegations_response(&body).unwrap();
+        assert_eq!(
+            delegations,
+            vec![Delegation {
+                validator_address: "twilightvaloper1abc".to_string(),
+                shares: "50000.000000000000000000".to_string(),
+                balance: 50_000,
+                denom: "nyks".to_string(),
+            }]
+        );
+        assert!(parse_delegations_response(&json!({"code": 5})).is_err());
+    }
+
+    fn delegations_page(validators: &[String], next_key: Option<&str>) -> MockResponse {
+        let responses: Vec<Value> = validators
+            .iter()
+            .map(|validator| {
+                json!({
+                    "delegation": {"validator_address": validator, "shares": "10.0"},
+                    "balance": {"denom": "nyks", "amount": "10"},
+                })
+            })
+            .collect();
+        let body = json!({
+            "delegation_responses": responses,
+            "pagination": {"next_key": next_key, "total": "0"},
+        });
+        MockResponse::ok(body.to_string())
+    }
+
+    /// A wallet on `chain` at account number 7, sequence 3.
+    fn wallet_on(chain: &MockChain) -> Wallet {
+        let mut wallet = Wallet::from_mnemonic(MNEMONIC, None).unwrap();
+        wallet.chain_config.lcd_endpoint = chain.url().to_string();
+        wallet.chain_config.rpc_endpoint = chain.rpc_url();
+        let account = json!({"account": {
+            "@type": "/cosmos.auth.v1beta1.BaseAccount",
+            "address": wallet.twilightaddress,
+            "pub_key": null,
+            "account_number": "7",
+            "sequence": "3",
+        }});
+        chain.route(
+            "/cosmos/auth/v1beta1/accounts/",
+            vec![MockResponse::ok(account.to_string())],
+        );
+        wallet
+    }
+
+    fn broadcast(code: u32, hash: &str) -> MockResponse {
+        MockResponse::ok(format!(
+            r#"{{"jsonrpc":"2.0","id":"1","result":{{"code":{},"data":"","log":"out of stake","codespace":"","hash":"{}"}}}}"#,
+            code, hash
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_query_delegations_follows_next_key() {
+        let chain = MockChain::spawn();
+        let wallet = wallet_on(&chain);
+        let address = &wallet.twilightaddress;
+        let path = format!("/cosmos/staking/v1beta1/delegations/{}", address);
+        // The key is base64 and reaches the LCD percent-encoded.
+        let first = delegations_page(&[valoper(1), valoper(2)], Some("a2V5+w=="));
+        chain.route(&path, vec![first]);
+        chain.route(
+            &format!("{}?pagination.key=a2V5%2Bw%3D%3D", path),
+            vec![delegations_page(&[valoper(3)], None)],
+        );
+
+        let delegations = wallet.query_delegations().await.unwrap();
+        let validators: Vec<&str> = delegations
+            .iter()
+            .map(|d| d.validator_address.as_str())
+            .collect();
+        assert_eq!(validators, vec![valoper(1), valoper(2), valoper(3)]);
+        assert_eq!(chain.count(&path), 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_staking_msgs_signs_at_the_next_sequence() {
+        let chain = MockChain::spawn();
+        let mut wallet = wallet_on(&chain);
+        chain.route("/rpc", vec![broadcast(0, "AAAA"), broadcast(5, "BBBB")]);
+
+        let result = wallet.delegate(&valoper(1), 25_000).await.unwrap();
+        assert_eq!(result.hash, "AAAA");
+        assert_eq!(wallet.nonce_manager.peek_next(), 4);
+
+        // A rejection names the action and the chain's log.
+        let err = wallet.undelegate(&valoper(1), 5_000).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Undelegate failed (code 5), TX Hash: BBBB: out of stake"
+        );
+        assert_eq!(chain.count("/rpc"), 2);
+        assert_eq!(chain.count("/cosmos/tx/v1beta1/simulate"), 0);
+    }
+}
@@ -1,21 +1,42 @@
 use crate::config::WalletEndPointConfig;
+use crate::error::WalletError;
+use crate::log_privacy::{LoggedAddress, LoggedAmount};
+use crate::nyks_rpc::lcd;
+use crate::nyks_rpc::rpcclient::fee_bump::{
+    broadcast_signed_tx, broadcast_tx_sync, query_tx_status, submit_with_fee_bump, FeeBumpError,
+    FeeBumpPolicy, FeeBumpReceipt, TxStatus,
+};
+use crate::nyks_rpc::rpcclient::txresult::TxResult;
+use crate::security::entropy::{self, EntropySource};
 use crate::security::print_secret_to_tty;
 use crate::{faucet::*, generate_seed};
+use crate::wallet::btc_withdrawal::{
+    check_withdrawal_funds, parse_reserve_withdraw_pool, parse_withdraw_request_response,
+    select_reserve, BtcWithdrawalError, BtcWithdrawalId, BtcWithdrawalSubmission,
+    ReserveWithdrawPool, WithdrawBtcRequestBuilder, WithdrawalLifecycle,
+};
+use crate::wallet::nonce_manager::{expected_sequence, NonceManager, SEQUENCE_MISMATCH_CODE};
 use anyhow::anyhow;
 use bip32::{DerivationPath, XPrv};
 use bip39::{Language as B39Lang, Mnemonic};
 use cosmrs::crypto::{secp256k1::SigningKey, PublicKey};
 use cosmrs::AccountId;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use reqwest::Client;
-use secrecy::SecretString;
+use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use tokio::time::{sleep, Duration};
-use zeroize::ZeroizeOnDrop;
+use std::sync::Arc;
+use tokio::time::Instant;
+use zeroize::{ZeroizeOnDrop, Zeroizing};
 pub const BECH_PREFIX: &str = "twilight";
 
+/// `twilight_staking_amount` of a deposit address registered with
+/// [`Wallet::register_btc_deposit_address`].
+pub const DEFAULT_TWILIGHT_STAKING_AMOUNT: u64 = 10_000;
+
 pub type NYKS = u64;
 pub type SATS = u64;
 
@@ -46,11 +67,12 @@ struct DerivedKeys {
 
 /// Shared key derivation pipeline: mnemonic -> seed -> XPrv -> SigningKey -> PublicKey -> AccountId.
 /// All wallet creation methods use this to avoid duplicating the derivation logic.
-fn derive_keys(mnemonic: &Mnemonic) -> anyhow::Result<DerivedKeys> {
-    let seed = mnemonic.to_seed("");
+/// `passphrase` is the BIP-39 passphrase ("25th word"); empty for none.
+fn derive_keys(mnemonic: &Mnemonic, passphrase: &str) -> anyhow::Result<DerivedKeys> {
+    let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
     let path = derivation_path();
 
-    let xprv = XPrv::derive_from_path(&seed, &path)
+    let xprv = XPrv::derive_from_path(&*seed, &path)
         .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
 
     let private_key_bytes = xprv.private_key().to_bytes();
@@ -69,6 +91,54 @@ fn derive_keys(mnemonic: &Mnemonic) -> anyhow::Result<DerivedKeys> {
     })
 }
 
+fn invalid_file(field: &str, reason: impl std::fmt::Display) -> WalletError {
+    WalletError::InvalidWalletFile {
+        field: field.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn required_str<'a>(file: &'a Value, field: &str) -> Result<&'a str, WalletError> {
+    optional_str(file, field)?.ok_or_else(|| invalid_file(field, "missing"))
+}
+
+fn optional_str<'a>(file: &'a Value, field: &str) -> Result<Option<&'a str>, WalletError> {
+    match file.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(invalid_file(field, "expected a string")),
+    }
+}
+
+fn optional_u64(file: &Value, field: &str) -> Result<Option<u64>, WalletError> {
+    match file.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| invalid_file(field, "expected an unsigned integer")),
+    }
+}
+
+fn optional_bool(file: &Value, field: &str) -> Result<Option<bool>, WalletError> {
+    match file.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| invalid_file(field, "expected a boolean")),
+    }
+}
+
+/// Bech32 prefixes of BTC addresses on the configured network.
+fn btc_address_prefixes() -> &'static [&'static str] {
+    if crate::config::is_btc_mainnet() {
+        &["bc1"]
+    } else {
+        &["tb1", "bcrt1"]
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Balance {
     pub nyks: NYKS,
@@ -130,9 +200,16 @@ pub use crate::wallet::btc_wallet::balance::*;
 /// Fetch on-chain balance for the given address via LCD endpoint.
 pub async fn check_balance(address: &str, lcd_endpoint: &str) -> anyhow::Result<Balance> {
     let url = format!("{}/cosmos/bank/v1beta1/balances/{}", lcd_endpoint, address);
-    let client = Client::new();
-    let response = client.get(url).send().await?;
-    let balance: Value = response.json().await?;
+    let response = lcd::get(&url).await?;
+    let body = response.text().await?;
+    parse_balance_response(&body)
+}
+
+/// Parse an LCD `/cosmos/bank/v1beta1/balances` response body. Denoms other
+/// than `nyks` and `sats` are ignored; an amount of either that is not a
+/// valid number is an error rather than a zero balance.
+pub fn parse_balance_response(body: &str) -> anyhow::Result<Balance> {
+    let balance: Value = serde_json::from_str(body)?;
     let mut balance_nyks = 0;
     let mut balance_sats = 0;
     if let Some(balances) = balance.get("balances").and_then(|b| b.as_array()) {
@@ -141,11 +218,15 @@ pub async fn check_balance(address: &str, lcd_endpoint: &str) -> anyhow::Result<
                 balance.get("amount").and_then(|a| a.as_str()),
                 balance.get("denom").and_then(|d| d.as_str()),
             ) {
-                debug!("Balance: {} {}", amount, denom);
+                debug!("Balance: {} {}", LoggedAmount(amount), denom);
                 if denom == "nyks" {
-                    balance_nyks = amount.parse::<NYKS>().unwrap_or(0);
+                    balance_nyks = amount
+                        .parse::<NYKS>()
+                        .map_err(|e| anyhow!("invalid nyks amount {:?}: {}", amount, e))?;
                 } else if denom == "sats" {
-                    balance_sats = amount.parse::<SATS>().unwrap_or(0);
+                    balance_sats = amount
+                        .parse::<SATS>()
+                        .map_err(|e| anyhow!("invalid sats amount {:?}: {}", amount, e))?;
                 }
             }
         }
@@ -156,7 +237,11 @@ pub async fn check_balance(address: &str, lcd_endpoint: &str) -> anyhow::Result<
     })
 }
 
-#[derive(Clone, Serialize, Deserialize, ZeroizeOnDrop)]
+/// `Serialize` writes a redacted view: the private key is replaced by its
+/// [`key_fingerprint`] and the BTC key is left out. The encrypted database
+/// blob uses [`Wallet::dangerous_serialize_with_secrets`], which `Deserialize`
+/// reads back.
+#[derive(Clone, Deserialize, ZeroizeOnDrop)]
 pub struct Wallet {
     pub(crate) private_key: Vec<u8>,
     pub public_key: Vec<u8>,
@@ -171,6 +256,11 @@ pub struct Wallet {
     pub btc_wallet: Option<crate::wallet::btc_wallet::BtcWallet>,
     #[zeroize(skip)]
     pub account_info: Option<Account>,
+    /// Sequence counter used by [`sign_and_broadcast`](Self::sign_and_broadcast),
+    /// shared by clones of the wallet.
+    #[serde(skip)]
+    #[zeroize(skip)]
+    pub nonce_manager: Arc<NonceManager>,
     #[zeroize(skip)]
     pub chain_config: WalletEndPointConfig,
 }
@@ -188,7 +278,7 @@ impl std::fmt::Display for Wallet {
 impl std::fmt::Debug for Wallet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Wallet")
-            .field("private_key", &"[REDACTED]")
+            .field("private_key_fingerprint", &self.key_fingerprint())
             .field("public_key", &hex::encode(&self.public_key))
             .field("twilightaddress", &self.twilightaddress)
             .field("balance_nyks", &self.balance_nyks)
@@ -198,43 +288,139 @@ impl std::fmt::Debug for Wallet {
             .field("btc_address_registered", &self.btc_address_registered)
             .field("btc_wallet", &self.btc_wallet)
             .field("account_info", &self.account_info)
+            .field("nonce_manager", &self.nonce_manager)
             .field("chain_config", &self.chain_config)
             .finish()
     }
 }
 
+/// Hex of the first 4 bytes of SHA-256 over `private_key`. Tells keys apart
+/// in logs without revealing them.
+pub fn key_fingerprint(private_key: &[u8]) -> String {
+    hex::encode(&Sha256::digest(private_key)[..4])
+}
+
+#[derive(Serialize)]
+struct RedactedWallet<'a> {
+    private_key_fingerprint: String,
+    public_key: &'a [u8],
+    twilightaddress: &'a str,
+    balance_nyks: NYKS,
+    balance_sats: SATS,
+    sequence: u64,
+    btc_address: &'a str,
+    btc_address_registered: bool,
+    btc_wallet: Option<RedactedBtcWallet<'a>>,
+    account_info: &'a Option<Account>,
+    chain_config: &'a WalletEndPointConfig,
+}
+
+#[derive(Serialize)]
+struct RedactedBtcWallet<'a> {
+    address: &'a str,
+    network: crate::wallet::btc_wallet::BtcNetwork,
+}
+
+/// Field for field the layout `Deserialize` reads, key material included.
+#[derive(Serialize)]
+struct WalletWithSecrets<'a> {
+    private_key: &'a [u8],
+    public_key: &'a [u8],
+    twilightaddress: &'a str,
+    balance_nyks: NYKS,
+    balance_sats: SATS,
+    sequence: u64,
+    btc_address: &'a str,
+    btc_address_registered: bool,
+    btc_wallet: &'a Option<crate::wallet::btc_wallet::BtcWallet>,
+    account_info: &'a Option<Account>,
+    chain_config: &'a WalletEndPointConfig,
+}
+
+impl Serialize for Wallet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RedactedWallet {
+            private_key_fingerprint: key_fingerprint(&self.private_key),
+            public_key: &self.public_key,
+            twilightaddress: &self.twilightaddress,
+            balance_nyks: self.balance_nyks,
+            balance_sats: self.balance_sats,
+            sequence: self.sequence,
+            btc_address: &self.btc_address,
+            btc_address_registered: self.btc_address_registered,
+            btc_wallet: self.btc_wallet.as_ref().map(|btc| RedactedBtcWallet {
+                address: &btc.address,
+                network: btc.network,
+            }),
+            account_info: &self.account_info,
+            chain_config: &self.chain_config,
+        }
+        .serialize(serializer)
+    }
+}
+
 impl Wallet {
+    /// JSON of the wallet with its private key and BTC key, in the layout
+    /// `Deserialize` reads. Only for the encrypted database blob; never log
+    /// or store the result unencrypted.
+    pub fn dangerous_serialize_with_secrets(&self) -> serde_json::Result<Zeroizing<Vec<u8>>> {
+        serde_json::to_vec(&WalletWithSecrets {
+            private_key: &self.private_key,
+            public_key: &self.public_key,
+            twilightaddress: &self.twilightaddress,
+            balance_nyks: self.balance_nyks,
+            balance_sats: self.balance_sats,
+            sequence: self.sequence,
+            btc_address: &self.btc_address,
+            btc_address_registered: self.btc_address_registered,
+            btc_wallet: &self.btc_wallet,
+            account_info: &self.account_info,
+            chain_config: &self.chain_config,
+        })
+        .map(Zeroizing::new)
+    }
+
+    /// [`key_fingerprint`] of this wallet's private key.
+    pub fn key_fingerprint(&self) -> String {
+        key_fingerprint(&self.private_key)
+    }
+
     /// Controlled access to private key bytes. Prefer `signing_key()` when possible.
     pub fn private_key_bytes(&self) -> &[u8] {
         &self.private_key
     }
 
     pub fn new(chain_config: Option<WalletEndPointConfig>) -> anyhow::Result<Self> {
-        let chain_config = chain_config.unwrap_or_default();
-        let mnemonic = Mnemonic::generate_in(B39Lang::English, 24)?;
-        let keys = derive_keys(&mnemonic)?;
+        Self::new_with_entropy(&*entropy::default_source(), chain_config)
+    }
+
+    /// Same as [`Wallet::new`], but the mnemonic entropy (and with it every
+    /// derived key) is drawn from `provider` instead of the default source.
+    /// See [`crate::security::entropy`] for the full list of influenced outputs.
+    pub fn new_with_entropy(
+        provider: &dyn EntropySource,
+        chain_config: Option<WalletEndPointConfig>,
+    ) -> anyhow::Result<Self> {
+        let (wallet, mut mnemonic_str) = Self::generate_with_entropy(provider, chain_config)?;
+        print_secret_to_tty(&mut mnemonic_str)?;
+        Ok(wallet)
+    }
+
+    /// Generate a fresh mnemonic from `provider` and build the wallet from it.
+    /// Returns the phrase alongside so the caller decides how to surface it.
+    fn generate_with_entropy(
+        provider: &dyn EntropySource,
+        chain_config: Option<WalletEndPointConfig>,
+    ) -> anyhow::Result<(Self, String)> {
+        let mnemonic = entropy::generate_mnemonic(provider)?;
         let mnemonic_str = mnemonic.to_string();
-        let btc_wallet = crate::wallet::btc_wallet::BtcWallet::from_mnemonic(&mnemonic_str)?;
-        let btc_address = btc_wallet.address.clone();
-        print_secret_to_tty(&mut mnemonic_str.clone())?;
-        Ok(Wallet {
-            private_key: keys.private_key,
-            public_key: keys.public_key,
-            twilightaddress: keys.account_id.to_string(),
-            balance_nyks: 0,
-            balance_sats: 0,
-            sequence: 0,
-            btc_address,
-            btc_address_registered: false,
-            btc_wallet: Some(btc_wallet),
-            account_info: None,
-            chain_config,
-        })
+        let wallet = Self::from_mnemonic(&mnemonic_str, chain_config)?;
+        Ok((wallet, mnemonic_str))
     }
 
     pub async fn create_new_with_random_btc_address() -> anyhow::Result<Wallet> {
-        let mnemonic = Mnemonic::generate_in(B39Lang::English, 24)?;
-        let keys = derive_keys(&mnemonic)?;
+        let mnemonic = entropy::generate_mnemonic(&*entropy::default_source())?;
+        let keys = derive_keys(&mnemonic, "")?;
         let btc_wallet =
             crate::wallet::btc_wallet::BtcWallet::from_mnemonic(&mnemonic.to_string())?;
         let btc_address = btc_wallet.address.clone();
@@ -250,6 +436,7 @@ impl Wallet {
             btc_address_registered: false,
             btc_wallet: Some(btc_wallet),
             account_info: None,
+            nonce_manager: Arc::default(),
             chain_config: WalletEndPointConfig::from_env(),
         })
     }
@@ -257,12 +444,35 @@ impl Wallet {
     pub fn from_mnemonic(
         mnemonic: &str,
         chain_config: Option<WalletEndPointConfig>,
+    ) -> anyhow::Result<Wallet> {
+        Self::from_mnemonic_and_passphrase(mnemonic, "", chain_config)
+    }
+
+    /// [`from_mnemonic`](Wallet::from_mnemonic) with a BIP-39 passphrase (the
+    /// "25th word"), for wallets created elsewhere with one. The twilight and
+    /// BTC keys, and the ZkOS seed signed with them, all follow the
+    /// passphrase. It is dropped, and zeroized, once the keys are derived.
+    pub fn from_mnemonic_with_passphrase(
+        mnemonic: &str,
+        passphrase: SecretString,
+        chain_config: Option<WalletEndPointConfig>,
+    ) -> anyhow::Result<Wallet> {
+        Self::from_mnemonic_and_passphrase(mnemonic, passphrase.expose_secret(), chain_config)
+    }
+
+    fn from_mnemonic_and_passphrase(
+        mnemonic: &str,
+        passphrase: &str,
+        chain_config: Option<WalletEndPointConfig>,
     ) -> anyhow::Result<Wallet> {
         let chain_config = chain_config.unwrap_or_default();
         let mnemonic = Mnemonic::parse_in(B39Lang::English, mnemonic)?;
-        let keys = derive_keys(&mnemonic)?;
+        let keys = derive_keys(&mnemonic, passphrase)?;
         let mnemonic_str = mnemonic.to_string();
-        let btc_wallet = crate::wallet::btc_wallet::BtcWallet::from_mnemonic(&mnemonic_str)?;
+        let btc_wallet = crate::wallet::btc_wallet::BtcWallet::from_mnemonic_with_passphrase(
+            &mnemonic_str,
+            passphrase,
+        )?;
         let btc_address = btc_wallet.address.clone();
         Ok(Wallet {
             private_key: keys.private_key,
@@ -275,6 +485,7 @@ impl Wallet {
             btc_address_registered: false,
             btc_wallet: Some(btc_wallet),
             account_info: None,
+            nonce_manager: Arc::default(),
             chain_config,
         })
     }
@@ -303,64 +514,202 @@ impl Wallet {
             btc_address_registered: false,
             btc_wallet: None,
             account_info: None,
+            nonce_manager: Arc::default(),
             chain_config,
         })
     }
 
+    /// Build a wallet from a hex-encoded 32-byte private key (an optional `0x`
+    /// prefix is accepted) without any TTY interaction.
+    ///
+    /// With `btc_address` unset, the BTC wallet is derived from the same key
+    /// as a native SegWit address on the configured network; a supplied
+    /// address is validated and stored without a BTC signing key.
+    pub fn from_private_key_hex(
+        private_key_hex: &str,
+        btc_address: Option<&str>,
+        chain_config: Option<WalletEndPointConfig>,
+    ) -> Result<Wallet, WalletError> {
+        let chain_config = chain_config.unwrap_or_default();
+        let private_key_hex = private_key_hex.trim();
+        let private_key_hex = private_key_hex
+            .strip_prefix("0x")
+            .unwrap_or(private_key_hex);
+        let private_key = Zeroizing::new(
+            hex::decode(private_key_hex)
+                .map_err(|e| WalletError::InvalidPrivateKeyHex(e.to_string()))?,
+        );
+        if private_key.len() != 32 {
+            return Err(WalletError::InvalidPrivateKeyLength(private_key.len()));
+        }
+        let signing_key = SigningKey::from_slice(&private_key)
+            .map_err(|e| WalletError::InvalidPrivateKey(e.to_string()))?;
+        let public_key = signing_key.public_key();
+        let account_id = public_key
+            .account_id(BECH_PREFIX)
+            .map_err(|e| WalletError::SigningKey(e.to_string()))?;
+
+        let (btc_address, btc_wallet) = match btc_address {
+            Some(address) => {
+                crate::wallet::btc_wallet::validate_btc_segwit_address(address)
+                    .map_err(WalletError::InvalidBtcAddress)?;
+                (address.to_string(), None)
+            }
+            None => {
+                let btc_wallet =
+                    crate::wallet::btc_wallet::BtcWallet::from_secret_key(&private_key)
+                        .map_err(|e| WalletError::InvalidPrivateKey(e.to_string()))?;
+                (btc_wallet.address.clone(), Some(btc_wallet))
+            }
+        };
+
+        Ok(Wallet {
+            private_key: private_key.to_vec(),
+            public_key: public_key.to_bytes().to_vec(),
+            twilightaddress: account_id.to_string(),
+            balance_nyks: 0,
+            balance_sats: 0,
+            sequence: 0,
+            btc_address,
+            btc_address_registered: false,
+            btc_wallet,
+            account_info: None,
+            nonce_manager: Arc::default(),
+            chain_config,
+        })
+    }
+
+    /// Import a wallet written by [`export_to_json`](Self::export_to_json),
+    /// checked against the endpoint configuration in the environment. See
+    /// [`import_from_json_checked`](Self::import_from_json_checked); the
+    /// error is a [`WalletError`] wrapped in `anyhow`.
     pub fn import_from_json(path: &str) -> anyhow::Result<Wallet> {
-        let json_string: String = std::fs::read_to_string(path)?;
-        let account_info: Value = serde_json::from_str(&json_string)?;
-        let wallet_config = WalletEndPointConfig::from_env();
-        let wallet = Wallet {
-            private_key: hex::decode(
-                account_info["private_key"]
-                    .as_str()
-                    .ok_or_else(|| anyhow!("private_key not found"))?
-                    .to_string(),
-            )
-            .unwrap_or_default(),
-            public_key: hex::decode(
-                account_info["public_key"]
-                    .as_str()
-                    .ok_or_else(|| anyhow!("public_key not found"))?
-                    .to_string(),
-            )
-            .unwrap_or_default(),
-            twilightaddress: account_info["twilightaddress"]
-                .as_str()
-                .unwrap()
-                .to_string(),
-            balance_nyks: account_info["balance_nyks"].as_u64().unwrap_or_default(),
-            balance_sats: account_info["balance_sats"].as_u64().unwrap_or_default(),
-            sequence: account_info["sequence"].as_u64().unwrap_or_default(),
-            btc_address: account_info["btc_address"].as_str().unwrap().to_string(),
-            btc_address_registered: account_info["btc_address_registered"]
-                .as_bool()
+        Ok(Self::import_from_json_checked(path, None, false)?)
+    }
+
+    /// Import a wallet file, validating every field:
+    ///
+    /// - `private_key` must be 32 bytes of hex and derive the stored
+    ///   `public_key` and `twilightaddress`;
+    /// - `btc_address` must carry a prefix of the configured BTC network;
+    /// - `chain_id` must match `chain_config` (the environment when `None`)
+    ///   unless `allow_chain_mismatch` is set;
+    /// - optional fields, when present, must have the right type.
+    ///
+    /// Endpoints missing from the file are taken from `chain_config`.
+    pub fn import_from_json_checked(
+        path: &str,
+        chain_config: Option<WalletEndPointConfig>,
+        allow_chain_mismatch: bool,
+    ) -> Result<Wallet, WalletError> {
+        let json_string = std::fs::read_to_string(path)
+            .map_err(|e| WalletError::Import(format!("failed to read {}: {}", path, e)))?;
+        Self::from_wallet_json(&json_string, chain_config, allow_chain_mismatch)
+    }
+
+    fn from_wallet_json(
+        json: &str,
+        chain_config: Option<WalletEndPointConfig>,
+        allow_chain_mismatch: bool,
+    ) -> Result<Wallet, WalletError> {
+        let file: Value = serde_json::from_str(json)
+            .map_err(|e| WalletError::Import(format!("wallet file is not valid JSON: {}", e)))?;
+        let expected = chain_config.unwrap_or_else(WalletEndPointConfig::from_env);
+
+        let private_key = Zeroizing::new(
+            hex::decode(required_str(&file, "private_key")?)
+                .map_err(|e| invalid_file("private_key", e))?,
+        );
+        if private_key.len() != 32 {
+            return Err(invalid_file(
+                "private_key",
+                format!("expected 32 bytes, got {}", private_key.len()),
+            ));
+        }
+        let signing_key =
+            SigningKey::from_slice(&private_key).map_err(|e| invalid_file("private_key", e))?;
+        let public_key = signing_key.public_key();
+        let derived_address = public_key
+            .account_id(BECH_PREFIX)
+            .map_err(|e| invalid_file("private_key", e))?
+            .to_string();
+        let public_key = public_key.to_bytes().to_vec();
+
+        let stored_public_key = hex::decode(required_str(&file, "public_key")?)
+            .map_err(|e| invalid_file("public_key", e))?;
+        if stored_public_key != public_key {
+            return Err(invalid_file("public_key", "does not match the private key"));
+        }
+        let twilightaddress = required_str(&file, "twilightaddress")?;
+        if twilightaddress != derived_address {
+            return Err(invalid_file(
+                "twilightaddress",
+                format!(
+                    "does not match the private key, which derives {}",
+                    derived_address
+                ),
+            ));
+        }
+
+        let btc_address = required_str(&file, "btc_address")?;
+        let prefixes = btc_address_prefixes();
+        if !prefixes
+            .iter()
+            .any(|prefix| btc_address.starts_with(prefix))
+        {
+            return Err(invalid_file(
+                "btc_address",
+                format!(
+                    "expected a {} address for the configured network",
+                    prefixes.join("/")
+                ),
+            ));
+        }
+
+        let chain_id = optional_str(&file, "chain_id")?.unwrap_or(&expected.chain_id);
+        if chain_id != expected.chain_id && !allow_chain_mismatch {
+            return Err(invalid_file(
+                "chain_id",
+                format!(
+                    "{:?} does not match the configured chain {:?}",
+                    chain_id, expected.chain_id
+                ),
+            ));
+        }
+
+        let btc_wallet = match file.get("btc_wallet") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(
+                serde_json::from_value(value.clone()).map_err(|e| invalid_file("btc_wallet", e))?,
+            ),
+        };
+
+        Ok(Wallet {
+            private_key: private_key.to_vec(),
+            public_key,
+            twilightaddress: twilightaddress.to_string(),
+            balance_nyks: optional_u64(&file, "balance_nyks")?.unwrap_or_default(),
+            balance_sats: optional_u64(&file, "balance_sats")?.unwrap_or_default(),
+            sequence: optional_u64(&file, "sequence")?.unwrap_or_default(),
+            btc_address: btc_address.to_string(),
+            btc_address_registered: optional_bool(&file, "btc_address_registered")?
                 .unwrap_or_default(),
-            btc_wallet: account_info
-                .get("btc_wallet")
-                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            btc_wallet,
             account_info: None,
+            nonce_manager: Arc::default(),
             chain_config: WalletEndPointConfig::new(
-                account_info["lcd_endpoint"]
-                    .as_str()
-                    .unwrap_or(&wallet_config.lcd_endpoint)
+                optional_str(&file, "lcd_endpoint")?
+                    .unwrap_or(&expected.lcd_endpoint)
                     .to_string(),
-                account_info["faucet_endpoint"]
-                    .as_str()
-                    .unwrap_or(&wallet_config.faucet_endpoint)
+                optional_str(&file, "faucet_endpoint")?
+                    .unwrap_or(&expected.faucet_endpoint)
                     .to_string(),
-                account_info["rpc_endpoint"]
-                    .as_str()
-                    .unwrap_or(&wallet_config.rpc_endpoint)
-                    .to_string(),
-                account_info["chain_id"]
-                    .as_str()
-                    .unwrap_or(wallet_config.chain_id.as_str())
+                optional_str(&file, "rpc_endpoint")?
+                    .unwrap_or(&expected.rpc_endpoint)
                     .to_string(),
+                chain_id.to_string(),
             ),
-        };
-        Ok(wallet)
+        })
     }
 
     pub fn signing_key(&self) -> anyhow::Result<SigningKey> {
@@ -391,8 +740,71 @@ impl Wallet {
         self.account_info = Some(account_details.account);
         Ok(())
     }
-    pub fn export_to_json(&self, path: &str) -> anyhow::Result<()> {
-        let account_info = serde_json::json!({
+    /// Write the wallet, private key included, to `path` as plain JSON.
+    ///
+    /// Refuses unless `allow_plaintext` is set, and logs a warning when it is;
+    /// prefer [`export_to_encrypted_json`](Self::export_to_encrypted_json).
+    pub fn export_to_json(&self, path: &str, allow_plaintext: bool) -> anyhow::Result<()> {
+        if !allow_plaintext {
+            return Err(anyhow!(
+                "refusing to write an unencrypted private key to {}; use export_to_encrypted_json",
+                path
+            ));
+        }
+        warn!(
+            "Writing wallet {} with its private key UNENCRYPTED to {}",
+            LoggedAddress(&self.twilightaddress),
+            path
+        );
+        let json = Zeroizing::new(self.export_json().to_string());
+        std::fs::write(path, json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Write the wallet to `path` encrypted with `password`. See
+    /// [`encrypted_file`](super::encrypted_file) for the file format.
+    pub fn export_to_encrypted_json(
+        &self,
+        path: &str,
+        password: &SecretString,
+    ) -> Result<(), WalletError> {
+        let json = Zeroizing::new(self.export_json().to_string());
+        let contents = super::encrypted_file::seal(json.as_bytes(), password)?;
+        std::fs::write(path, contents)
+            .map_err(|e| WalletError::Export(format!("failed to write {}: {}", path, e)))
+    }
+
+    /// Import a wallet written by
+    /// [`export_to_encrypted_json`](Self::export_to_encrypted_json), checked
+    /// against the endpoint configuration in the environment like
+    /// [`import_from_json`](Self::import_from_json). A wrong password and a
+    /// modified file both fail with [`WalletError::Decryption`].
+    pub fn import_from_encrypted_json(
+        path: &str,
+        password: &SecretString,
+    ) -> Result<Wallet, WalletError> {
+        Self::import_from_encrypted_json_checked(path, password, None, false)
+    }
+
+    /// [`import_from_encrypted_json`](Self::import_from_encrypted_json) with the
+    /// checks of [`import_from_json_checked`](Self::import_from_json_checked).
+    pub fn import_from_encrypted_json_checked(
+        path: &str,
+        password: &SecretString,
+        chain_config: Option<WalletEndPointConfig>,
+        allow_chain_mismatch: bool,
+    ) -> Result<Wallet, WalletError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| WalletError::Import(format!("failed to read {}: {}", path, e)))?;
+        let json = super::encrypted_file::open(&contents, password)?;
+        let json = std::str::from_utf8(&json)
+            .map_err(|e| WalletError::Import(format!("decrypted wallet is not UTF-8: {}", e)))?;
+        Self::from_wallet_json(json, chain_config, allow_chain_mismatch)
+    }
+
+    /// The document written by the JSON exports.
+    fn export_json(&self) -> Value {
+        serde_json::json!({
             "private_key": hex::encode(self.private_key.clone()),
             "public_key": hex::encode(self.public_key.clone()),
             "twilightaddress": self.twilightaddress,
@@ -407,9 +819,7 @@ impl Wallet {
             "faucet_endpoint": self.chain_config.faucet_endpoint,
             "rpc_endpoint": self.chain_config.rpc_endpoint,
             "chain_id": self.chain_config.chain_id,
-        });
-        std::fs::write(path, account_info.to_string())?;
-        Ok(())
+        })
     }
 
     pub fn from_mnemonic_file(path: &str) -> anyhow::Result<Wallet> {
@@ -426,9 +836,7 @@ impl Wallet {
         amount: u64,
         denom: &str,
     ) -> anyhow::Result<String> {
-        use crate::nyks_rpc::rpcclient::method::{Method, MethodTypeURL};
-        use crate::nyks_rpc::rpcclient::txrequest::{RpcBody, RpcRequest, TxParams};
-        use crate::nyks_rpc::rpcclient::txresult::parse_tx_response;
+        use crate::nyks_rpc::rpcclient::method::MethodTypeURL;
 
         if denom != "nyks" && denom != "sats" {
             return Err(anyhow!("denom must be 'nyks' or 'sats'"));
@@ -464,43 +872,24 @@ impl Wallet {
         let method_type = MethodTypeURL::MsgSend;
         let any_msg = method_type.type_url(msg);
 
-        let sk = self.signing_key()?;
-        let pk = self.public_key()?;
-
-        let account_details = crate::faucet::fetch_account_details(
-            &self.twilightaddress,
-            &self.chain_config.lcd_endpoint,
-        )
-        .await?;
-        let account_number = account_details.account.account_number;
-        let sequence = account_details.account.sequence;
-
-        let signed_tx =
-            method_type.sign_msg::<CosmosMsgSend>(any_msg, pk, sequence, account_number, sk)?;
-
-        let method = Method::broadcast_tx_sync;
-        let (tx_send, _): (RpcBody<TxParams>, String) =
-            RpcRequest::new_with_data(TxParams::new(signed_tx.clone()), method, signed_tx);
-
-        let rpc_endpoint = self.chain_config.rpc_endpoint.clone();
-        let response = tokio::task::spawn_blocking(move || tx_send.send(rpc_endpoint))
-            .await
-            .map_err(|e| anyhow!("RPC send failed: {e}"))?;
-
-        match response {
-            Ok(rpc_response) => {
-                let result = parse_tx_response(&method, rpc_response)?;
-                let tx_hash = result.get_tx_hash();
-                let code = result.get_code();
-                if code == 0 {
-                    Ok(tx_hash)
-                } else {
-                    Err(anyhow!(
-                        "Transaction failed (code {code}), TX Hash: {tx_hash}"
-                    ))
-                }
-            }
-            Err(e) => Err(anyhow!("RPC error: {e}")),
+        let result = self
+            .sign_and_broadcast(|sequence, account_number| {
+                method_type.sign_msg::<CosmosMsgSend>(
+                    any_msg.clone(),
+                    self.public_key()?,
+                    sequence,
+                    account_number,
+                    self.signing_key()?,
+                )
+            })
+            .await?;
+        let (tx_hash, code) = (result.hash, result.code);
+        if code == 0 {
+            Ok(tx_hash)
+        } else {
+            Err(anyhow!(
+                "Transaction failed (code {code}), TX Hash: {tx_hash}"
+            ))
         }
     }
 
@@ -517,9 +906,7 @@ impl Wallet {
             return Err(anyhow!("register_btc_deposit is only available on mainnet. Use get_test_tokens for testnet."));
         }
 
-        use crate::nyks_rpc::rpcclient::method::{Method, MethodTypeURL};
-        use crate::nyks_rpc::rpcclient::txrequest::{RpcBody, RpcRequest, TxParams};
-        use crate::nyks_rpc::rpcclient::txresult::parse_tx_response;
+        use crate::nyks_rpc::rpcclient::method::MethodTypeURL;
 
         let msg = crate::MsgRegisterBtcDepositAddress {
             btc_deposit_address: self.btc_address.clone(),
@@ -531,51 +918,199 @@ impl Wallet {
         let method_type = MethodTypeURL::MsgRegisterBtcDepositAddress;
         let any_msg = method_type.type_url(msg);
 
-        let sk = self.signing_key()?;
-        let pk = self.public_key()?;
+        let result = self
+            .sign_and_broadcast(|sequence, account_number| {
+                method_type.sign_msg::<crate::MsgRegisterBtcDepositAddress>(
+                    any_msg.clone(),
+                    self.public_key()?,
+                    sequence,
+                    account_number,
+                    self.signing_key()?,
+                )
+            })
+            .await?;
+        let (tx_hash, code) = (result.hash, result.code);
+        if code == 0 {
+            self.btc_address_registered = true;
+            info!(
+                "Registered BTC deposit address: {}",
+                LoggedAddress(&self.btc_address)
+            );
+            Ok(tx_hash)
+        } else {
+            Err(anyhow!(
+                "Register BTC deposit failed (code {code}), TX Hash: {tx_hash}"
+            ))
+        }
+    }
+
+    /// [`register_btc_deposit`](Self::register_btc_deposit), waiting for the
+    /// registration to confirm and re-submitting it with a higher fee while
+    /// it stays unconfirmed (see [`crate::nyks_rpc::rpcclient::fee_bump`]).
+    pub async fn register_btc_deposit_with_fee_bump(
+        &mut self,
+        btc_satoshi_amount: u64,
+        twilight_staking_amount: u64,
+        policy: &FeeBumpPolicy,
+    ) -> Result<FeeBumpReceipt, FeeBumpError> {
+        use crate::nyks_rpc::rpcclient::method::MethodTypeURL;
 
+        let not_sent = |error: String| FeeBumpError::Broadcast {
+            error,
+            attempts: Vec::new(),
+        };
+        if crate::config::NETWORK_TYPE.as_str() != "mainnet" {
+            return Err(not_sent(
+                "register_btc_deposit is only available on mainnet".to_string(),
+            ));
+        }
+        let msg = crate::MsgRegisterBtcDepositAddress {
+            btc_deposit_address: self.btc_address.clone(),
+            btc_satoshi_test_amount: btc_satoshi_amount,
+            twilight_staking_amount,
+            twilight_address: self.twilightaddress.clone(),
+        };
         let account_details = crate::faucet::fetch_account_details(
             &self.twilightaddress,
             &self.chain_config.lcd_endpoint,
         )
-        .await?;
+        .await
+        .map_err(|e| not_sent(e.to_string()))?;
+        // Every attempt reuses this sequence, so only one can be included.
         let account_number = account_details.account.account_number;
         let sequence = account_details.account.sequence;
+        let rpc_endpoint = self.chain_config.rpc_endpoint.clone();
+        let lcd_endpoint = self.chain_config.lcd_endpoint.clone();
+
+        let receipt = submit_with_fee_bump(
+            policy,
+            |fee| {
+                let method_type = MethodTypeURL::MsgRegisterBtcDepositAddress;
+                let signed_tx = self.signing_key().and_then(|sk| {
+                    method_type.sign_msg_with_fee::<crate::MsgRegisterBtcDepositAddress>(
+                        method_type.type_url(msg.clone()),
+                        self.public_key()?,
+                        sequence,
+                        account_number,
+                        sk,
+                        fee,
+                    )
+                });
+                let rpc_endpoint = rpc_endpoint.clone();
+                async move {
+                    broadcast_signed_tx(signed_tx.map_err(|e| e.to_string())?, &rpc_endpoint).await
+                }
+            },
+            |tx_hash| {
+                let lcd_endpoint = lcd_endpoint.clone();
+                async move { query_tx_status(&tx_hash, &lcd_endpoint).await }
+            },
+        )
+        .await?;
+        self.btc_address_registered = true;
+        info!(
+            "Registered BTC deposit address: {}",
+            LoggedAddress(&self.btc_address)
+        );
+        Ok(receipt)
+    }
 
-        let signed_tx = method_type.sign_msg::<crate::MsgRegisterBtcDepositAddress>(
-            any_msg,
-            pk,
-            sequence,
-            account_number,
-            sk,
-        )?;
+    /// Register `btc_address`, an address the user controls, as the wallet's
+    /// BTC deposit address for a deposit of `satoshi_amount` sats, and wait
+    /// for the registration to be committed. The address must be P2WPKH or
+    /// P2TR on the configured network and not registered to another twilight
+    /// address. Can be run again, e.g. after changing addresses; the wallet's
+    /// `btc_address` becomes `btc_address`, and `btc_address_registered` is
+    /// read back from the chain with
+    /// [`btc_registration_status`](Self::btc_registration_status).
+    pub async fn register_btc_deposit_address(
+        &mut self,
+        btc_address: &str,
+        satoshi_amount: u64,
+    ) -> anyhow::Result<TxResult> {
+        use crate::nyks_rpc::rpcclient::method::MethodTypeURL;
+
+        crate::wallet::btc_wallet::validate_btc_deposit_address(btc_address)
+            .map_err(|e| anyhow!(e))?;
+        if let Some(existing) = self.fetch_registered_btc_by_address(btc_address).await? {
+            if existing.twilight_address != self.twilightaddress {
+                return Err(anyhow!(
+                    "BTC address {} is already registered to a different twilight address: {}",
+                    btc_address,
+                    existing.twilight_address
+                ));
+            }
+        }
 
-        let method = Method::broadcast_tx_sync;
-        let (tx_send, _): (RpcBody<TxParams>, String) =
-            RpcRequest::new_with_data(TxParams::new(signed_tx.clone()), method, signed_tx);
+        let msg = crate::MsgRegisterBtcDepositAddress {
+            btc_deposit_address: btc_address.to_string(),
+            btc_satoshi_test_amount: satoshi_amount,
+            twilight_staking_amount: DEFAULT_TWILIGHT_STAKING_AMOUNT,
+            twilight_address: self.twilightaddress.clone(),
+        };
+        let method_type = MethodTypeURL::MsgRegisterBtcDepositAddress;
+        let any_msg = method_type.type_url(msg);
 
-        let rpc_endpoint = self.chain_config.rpc_endpoint.clone();
-        let response = tokio::task::spawn_blocking(move || tx_send.send(rpc_endpoint))
-            .await
-            .map_err(|e| anyhow!("RPC send failed: {e}"))?;
-
-        match response {
-            Ok(rpc_response) => {
-                let result = parse_tx_response(&method, rpc_response)?;
-                let tx_hash = result.get_tx_hash();
-                let code = result.get_code();
-                if code == 0 {
-                    self.btc_address_registered = true;
-                    info!("Registered BTC deposit address: {}", self.btc_address);
-                    Ok(tx_hash)
-                } else {
-                    Err(anyhow!(
-                        "Register BTC deposit failed (code {code}), TX Hash: {tx_hash}"
-                    ))
-                }
+        let tx = self
+            .sign_and_broadcast(|sequence, account_number| {
+                method_type.sign_msg::<crate::MsgRegisterBtcDepositAddress>(
+                    any_msg.clone(),
+                    self.public_key()?,
+                    sequence,
+                    account_number,
+                    self.signing_key()?,
+                )
+            })
+            .await?;
+        if tx.code != 0 {
+            return Err(anyhow!(
+                "Register BTC deposit address failed (code {}), TX Hash: {}",
+                tx.code,
+                tx.hash
+            ));
+        }
+
+        let options = FaucetOptions::default();
+        let deadline = Instant::now() + options.timeout;
+        let lcd_endpoint = &self.chain_config.lcd_endpoint;
+        match wait_for_tx(&tx.hash, lcd_endpoint, &options, deadline).await {
+            FaucetStepStatus::Confirmed => {}
+            FaucetStepStatus::Failed(e) => {
+                return Err(anyhow!("Register BTC deposit address failed: {}", e));
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Registration tx {} was not committed within {:?}",
+                    tx.hash,
+                    options.timeout
+                ));
             }
-            Err(e) => Err(anyhow!("RPC error: {e}")),
         }
+
+        self.btc_address = btc_address.to_string();
+        let status = self.btc_registration_status().await?;
+        info!(
+            "Registered BTC deposit address {}: {:?}",
+            LoggedAddress(&self.btc_address),
+            status
+        );
+        Ok(tx)
+    }
+
+    /// The BTC deposit address the chain holds for this wallet's twilight
+    /// address, and whether the bridge has confirmed it. Sets
+    /// `btc_address_registered` to whether that address is the wallet's
+    /// `btc_address`.
+    pub async fn btc_registration_status(&mut self) -> anyhow::Result<BtcRegistrationStatus> {
+        let status = match self.fetch_deposit_status().await? {
+            None => BtcRegistrationStatus::Unregistered,
+            Some(info) => {
+                let details = self.fetch_deposit_details().await?;
+                BtcRegistrationStatus::from_chain(Some(&info.btc_deposit_address), &details)
+            }
+        };
+        self.btc_address_registered = status.address() == Some(self.btc_address.as_str());
+        Ok(status)
     }
 
     /// Submit a BTC withdrawal request on-chain.
@@ -588,70 +1123,155 @@ impl Wallet {
         reserve_id: u64,
         withdraw_amount: u64,
     ) -> anyhow::Result<String> {
+        let msg = WithdrawBtcRequestBuilder::new(&self.twilightaddress)
+            .to(withdraw_address)
+            .reserve(reserve_id)
+            .amount(withdraw_amount)
+            .build()?;
+        let submission = self.submit_btc_withdrawal(msg).await?;
+        Ok(submission.tx.hash)
+    }
+
+    /// Request a withdrawal of `amount_sats` to `btc_address`, paid out by
+    /// the reserve holding the most BTC. Validates the address and checks the
+    /// sats balance and transaction fee before broadcasting.
+    ///
+    /// The wallet keeps no history; `OrderWallet::request_btc_withdrawal`
+    /// also records the withdrawal in the database and its history exports.
+    pub async fn request_btc_withdrawal(
+        &mut self,
+        btc_address: &str,
+        amount_sats: u64,
+    ) -> anyhow::Result<BtcWithdrawalSubmission> {
+        let reserves = self.fetch_btc_reserves().await?;
+        let reserve = select_reserve(&reserves, amount_sats)
+            .ok_or(BtcWithdrawalError::NoReserve(amount_sats))?;
+        let msg = WithdrawBtcRequestBuilder::new(&self.twilightaddress)
+            .to(btc_address)
+            .reserve(reserve.reserve_id)
+            .amount(amount_sats)
+            .build()?;
+        self.submit_btc_withdrawal(msg).await
+    }
+
+    /// Broadcast a withdrawal request built with [`WithdrawBtcRequestBuilder`],
+    /// after checking the sats balance and transaction fee. Not recorded; see
+    /// `OrderWallet::submit_btc_withdrawal`.
+    pub async fn submit_btc_withdrawal(
+        &mut self,
+        msg: crate::MsgWithdrawBtcRequest,
+    ) -> anyhow::Result<BtcWithdrawalSubmission> {
         if crate::config::NETWORK_TYPE.as_str() != "mainnet" {
             return Err(anyhow!("withdraw_btc is only available on mainnet."));
         }
 
-        use crate::nyks_rpc::rpcclient::method::{Method, MethodTypeURL};
-        use crate::nyks_rpc::rpcclient::txrequest::{RpcBody, RpcRequest, TxParams};
-        use crate::nyks_rpc::rpcclient::txresult::parse_tx_response;
+        use crate::nyks_rpc::rpcclient::method::MethodTypeURL;
 
-        let msg = crate::MsgWithdrawBtcRequest {
-            withdraw_address: withdraw_address.to_string(),
-            reserve_id,
-            withdraw_amount,
-            twilight_address: self.twilightaddress.clone(),
-        };
+        let balance = self.update_balance().await?;
+        check_withdrawal_funds(&balance, msg.withdraw_amount)?;
 
+        let id = BtcWithdrawalId::from(&msg);
         let method_type = MethodTypeURL::MsgWithdrawBtcRequest;
         let any_msg = method_type.type_url(msg);
 
-        let sk = self.signing_key()?;
-        let pk = self.public_key()?;
-
-        let account_details = crate::faucet::fetch_account_details(
-            &self.twilightaddress,
-            &self.chain_config.lcd_endpoint,
-        )
-        .await?;
-        let account_number = account_details.account.account_number;
-        let sequence = account_details.account.sequence;
-
-        let signed_tx = method_type.sign_msg::<crate::MsgWithdrawBtcRequest>(
-            any_msg,
-            pk,
-            sequence,
-            account_number,
-            sk,
-        )?;
-
-        let method = Method::broadcast_tx_sync;
-        let (tx_send, _): (RpcBody<TxParams>, String) =
-            RpcRequest::new_with_data(TxParams::new(signed_tx.clone()), method, signed_tx);
+        let tx = self
+            .sign_and_broadcast(|sequence, account_number| {
+                method_type.sign_msg::<crate::MsgWithdrawBtcRequest>(
+                    any_msg.clone(),
+                    self.public_key()?,
+                    sequence,
+                    account_number,
+                    self.signing_key()?,
+                )
+            })
+            .await?;
+        if tx.code == 0 {
+            info!(
+                "Withdrawal request submitted: {} sats to {}",
+                LoggedAmount(id.amount_sats),
+                LoggedAddress(&id.btc_address)
+            );
+            Ok(BtcWithdrawalSubmission { id, tx })
+        } else {
+            Err(anyhow!(
+                "Withdraw BTC request failed (code {}), TX Hash: {}",
+                tx.code,
+                tx.hash
+            ))
+        }
+    }
 
-        let rpc_endpoint = self.chain_config.rpc_endpoint.clone();
-        let response = tokio::task::spawn_blocking(move || tx_send.send(rpc_endpoint))
+    /// Sync [`nonce_manager`](Self::nonce_manager) from the chain. Local
+    /// sequences already ahead of the chain's are kept.
+    pub async fn sync_nonce(&self) -> Result<(), WalletError> {
+        self.nonce_manager
+            .sync_from_chain(&self.chain_config.lcd_endpoint, &self.twilightaddress)
             .await
-            .map_err(|e| anyhow!("RPC send failed: {e}"))?;
-
-        match response {
-            Ok(rpc_response) => {
-                let result = parse_tx_response(&method, rpc_response)?;
-                let tx_hash = result.get_tx_hash();
-                let code = result.get_code();
-                if code == 0 {
-                    info!(
-                        "Withdrawal request submitted: {} sats to {}",
-                        withdraw_amount, withdraw_address
-                    );
-                    Ok(tx_hash)
-                } else {
-                    Err(anyhow!(
-                        "Withdraw BTC request failed (code {code}), TX Hash: {tx_hash}"
-                    ))
+            .map_err(WalletError::WalletAccountInfo)
+    }
+
+    /// Sign with the next sequence of [`nonce_manager`](Self::nonce_manager)
+    /// and broadcast with `broadcast_tx_sync`, returning the CheckTx result.
+    ///
+    /// `sign` gets `(sequence, account_number)` and returns the signed tx.
+    /// The manager syncs from the LCD on first use and counts locally after
+    /// that, so back-to-back transactions do not depend on the LCD having
+    /// seen the previous one. On a sequence mismatch the manager is reset to
+    /// the sequence the chain expects and the tx is signed and sent once
+    /// more; a second mismatch is [`WalletError::SequenceMismatch`]. Other
+    /// rejections are returned as is, with their code.
+    pub async fn sign_and_broadcast<F>(&self, sign: F) -> Result<TxResult, WalletError>
+    where
+        F: Fn(u64, u64) -> anyhow::Result<String>,
+    {
+        if !self.nonce_manager.is_synced() {
+            self.sync_nonce().await?;
+        }
+        let mut resigned = false;
+        loop {
+            let (sequence, account_number) = self
+                .nonce_manager
+                .acquire_next()
+                .map_err(WalletError::WalletAccountInfo)?;
+            let sent = match sign(sequence, account_number) {
+                Ok(signed_tx) => broadcast_tx_sync(signed_tx, &self.chain_config.rpc_endpoint)
+                    .await
+                    .map_err(WalletError::RpcRequest),
+                Err(e) => Err(WalletError::TxBuild(e.to_string())),
+            };
+            let result = match sent {
+                Ok(result) if result.code == 0 => return Ok(result),
+                Ok(result) => result,
+                Err(e) => {
+                    self.nonce_manager.release(sequence);
+                    return Err(e);
                 }
+            };
+            self.nonce_manager.release(sequence);
+            if result.code != SEQUENCE_MISMATCH_CODE {
+                return Ok(result);
+            }
+            let expected = result.log.as_deref().and_then(expected_sequence);
+            if resigned {
+                return Err(WalletError::SequenceMismatch {
+                    tx_hash: result.hash,
+                    sequence,
+                    expected,
+                });
+            }
+            resigned = true;
+            warn!(
+                "tx with sequence {} rejected for a sequence mismatch (chain expects {:?}), re-signing",
+                sequence, expected
+            );
+            match expected {
+                Some(expected) => self.nonce_manager.reset_to(expected),
+                None => self
+                    .nonce_manager
+                    .reset_from_chain(&self.chain_config.lcd_endpoint, &self.twilightaddress)
+                    .await
+                    .map_err(WalletError::WalletAccountInfo)?,
             }
-            Err(e) => Err(anyhow!("RPC error: {e}")),
         }
     }
 
@@ -664,8 +1284,7 @@ impl Wallet {
             "{}/twilight-project/nyks/volt/btc_reserve",
             self.chain_config.lcd_endpoint
         );
-        let client = Client::new();
-        let response = client.get(&url).send().await?;
+        let response = lcd::get(&url).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -760,8 +1379,7 @@ impl Wallet {
             "{}/twilight-project/nyks/bridge/registered_btc_deposit_address/{}",
             self.chain_config.lcd_endpoint, btc_address
         );
-        let client = Client::new();
-        let response = client.get(&url).send().await?;
+        let response = lcd::get(&url).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -806,8 +1424,7 @@ impl Wallet {
             "{}/twilight-project/nyks/bridge/registered_btc_deposit_address_by_twilight_address/{}",
             self.chain_config.lcd_endpoint, self.twilightaddress
         );
-        let client = Client::new();
-        let response = client.get(&url).send().await?;
+        let response = lcd::get(&url).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -852,8 +1469,7 @@ impl Wallet {
             "{}/twilight-project/nyks/bridge/registered_btc_deposit_addresses",
             self.chain_config.lcd_endpoint
         );
-        let client = Client::new();
-        let response = client.get(&url).send().await?;
+        let response = lcd::get(&url).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -926,8 +1542,7 @@ impl Wallet {
             "{}/twilight-project/nyks/volt/btc_withdraw_request/{}?reserveId={}&btcAddress={}&withdrawAmount={}",
             self.chain_config.lcd_endpoint, self.twilightaddress, reserve_id, btc_address, withdraw_amount
         );
-        let client = Client::new();
-        let response = client.get(&url).send().await?;
+        let response = lcd::get(&url).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -942,47 +1557,57 @@ impl Wallet {
             ));
         }
 
-        let json: Value = response.json().await?;
-        let req = match json.get("BtcWithdrawRequest") {
-            Some(r) => r,
-            None => return Ok(None),
-        };
+        let body = response.text().await?;
+        parse_withdraw_request_response(&body)
+    }
 
-        Ok(Some(BtcWithdrawStatus {
-            withdraw_identifier: req
-                .get("withdrawIdentifier")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            withdraw_address: req
-                .get("withdrawAddress")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string(),
-            withdraw_reserve_id: req
-                .get("withdrawReserveId")
-                .and_then(|v| v.as_str())
-                .unwrap_or("0")
-                .to_string(),
-            withdraw_amount: req
-                .get("withdrawAmount")
-                .and_then(|v| v.as_str())
-                .unwrap_or("0")
-                .to_string(),
-            twilight_address: req
-                .get("twilightAddress")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string(),
-            is_confirmed: req
-                .get("isConfirmed")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false),
-            creation_twilight_block_height: req
-                .get("CreationTwilightBlockHeight")
-                .and_then(|v| v.as_str())
-                .unwrap_or("0")
-                .to_string(),
-        }))
+    /// Query the withdraw requests a reserve is paying out or has queued.
+    /// Returns `None` if the reserve has no withdraw pool.
+    pub async fn fetch_reserve_withdraw_pool(
+        &self,
+        reserve_id: u64,
+    ) -> anyhow::Result<Option<ReserveWithdrawPool>> {
+        let url = format!(
+            "{}/twilight-project/nyks/volt/reserve_withdraw_pool/{}",
+            self.chain_config.lcd_endpoint, reserve_id
+        );
+        let response = lcd::get(&url).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            if status.as_u16() == 404 || status.as_u16() == 400 {
+                return Ok(None);
+            }
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "Failed to query reserve withdraw pool ({}): {}",
+                status,
+                body
+            ));
+        }
+
+        let body = response.text().await?;
+        Ok(Some(parse_reserve_withdraw_pool(&body)?))
+    }
+
+    /// Where the withdrawal request `id` stands on the bridge.
+    pub async fn withdrawal_status(
+        &self,
+        id: &BtcWithdrawalId,
+    ) -> anyhow::Result<WithdrawalLifecycle> {
+        let request = self
+            .fetch_withdrawal_status(id.reserve_id, &id.btc_address, id.amount_sats)
+            .await?;
+        let pool = match &request {
+            Some(request) if !request.is_confirmed => {
+                self.fetch_reserve_withdraw_pool(id.reserve_id).await?
+            }
+            _ => None,
+        };
+        Ok(WithdrawalLifecycle::from_chain(
+            request.as_ref(),
+            pool.as_ref(),
+        ))
     }
 
     /// Fetch account details from the Twilight indexer, including deposits and withdrawals.
@@ -1184,8 +1809,7 @@ impl Wallet {
             "{}/twilight-project/nyks/bridge/propose_sweep_addresses_all/{}",
             self.chain_config.lcd_endpoint, limit
         );
-        let client = Client::new();
-        let response = client.get(&url).send().await?;
+        let response = lcd::get(&url).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -1303,95 +1927,259 @@ fn parse_cltv_from_script(hex_script: &str) -> anyhow::Result<u64> {
     ))
 }
 
-pub async fn get_test_tokens(wallet: &mut Wallet) -> anyhow::Result<()> {
+/// [`get_test_tokens_with_options`] with the default [`FaucetOptions`].
+pub async fn get_test_tokens(wallet: &mut Wallet) -> anyhow::Result<FaucetReport> {
+    get_test_tokens_with_options(wallet, &FaucetOptions::default()).await
+}
+
+/// Top up a testnet wallet from the faucet: NYKS while it holds fewer than
+/// 50,000, then, while it holds fewer than 50,000 sats, test BTC (registering
+/// a BTC deposit address first if it has none). After each request the chain
+/// is polled every `options.poll_interval` until the balance changes or the
+/// registration tx is committed, within `options.timeout` for the whole call.
+/// A failed or timed out step is logged and reported, not returned as an
+/// error.
+pub async fn get_test_tokens_with_options(
+    wallet: &mut Wallet,
+    options: &FaucetOptions,
+) -> anyhow::Result<FaucetReport> {
     if crate::config::NETWORK_TYPE.as_str() == "mainnet" {
         return Err(anyhow!(
             "get_test_tokens is only available on testnet. Use register-btc for mainnet deposits."
         ));
     }
 
+    let started = Instant::now();
+    let deadline = started + options.timeout;
+    let mut steps = Vec::new();
     let balance = wallet.update_balance().await?;
     debug!("Checking balance values if nyks is less than 50000");
-    debug!("nyks: {}", balance.nyks);
-    if balance.nyks < 50000 {
+    debug!("nyks: {}", LoggedAmount(balance.nyks));
+    let step_started = Instant::now();
+    let status = if balance.nyks < 50000 {
         debug!("Getting tokens from faucet");
-        get_nyks(
+        let sent = get_nyks(
             &wallet.twilightaddress,
             &wallet.chain_config.faucet_endpoint,
         )
         .await
-        .unwrap_or_else(|e| {
-            error!("Failed to get tokens from faucet: {}", e);
-            info!("You may need to wait or try again later");
-        });
+        .map_err(|e| e.to_string());
+        match sent {
+            Ok(()) => {
+                info!("waiting for updated nyks balance to appear on-chain");
+                wait_for_balance(wallet, options, deadline, balance, |b| b.nyks).await
+            }
+            Err(e) => {
+                error!("Failed to get tokens from faucet: {}", e);
+                info!("You may need to wait or try again later");
+                FaucetStepStatus::Failed(e)
+            }
+        }
     } else {
         info!("Skipping get tokens from faucet because nyks is greater than 50000");
-    }
-    info!("waiting for updated nyks balance to appear on-chain");
-    sleep(Duration::from_secs(10)).await;
+        FaucetStepStatus::Skipped
+    };
+    steps.push(FaucetStep {
+        kind: FaucetStepKind::Nyks,
+        status,
+        elapsed: step_started.elapsed(),
+    });
+
     debug!("Checking balance values if sats is 0 or less than 50000");
-    debug!("sats: {}", balance.sats);
-    if balance.sats == 0 && !wallet.btc_address_registered {
+    debug!("sats: {}", LoggedAmount(balance.sats));
+    let mut mint = balance.sats < 50000;
+    let step_started = Instant::now();
+    let status = if balance.sats == 0 && !wallet.btc_address_registered {
         info!("Registering random BTC deposit address");
-        match sign_and_send_reg_deposit_tx(
+        let sent = sign_and_send_reg_deposit_tx(
             wallet.signing_key()?,
             wallet.public_key()?,
             wallet.twilightaddress.to_string(),
             wallet.btc_address.to_string(),
             &wallet.chain_config.lcd_endpoint,
+            &wallet.chain_config.tx_fee,
         )
         .await
-        {
-            Ok(_) => {
-                info!("Successfully registered BTC deposit address!");
-                debug!("BTC Address: {}", &wallet.btc_address);
+        .map_err(|e| e.to_string());
+        let status = match sent {
+            Ok(tx_hash) => {
                 info!("waiting for registered BTC deposit address to appear on-chain");
-                sleep(Duration::from_secs(10)).await;
-                info!("Minting test BTC...");
-                mint_sats(
-                    &wallet.twilightaddress,
-                    &wallet.chain_config.faucet_endpoint,
-                )
-                .await
-                .unwrap_or_else(|e| {
-                    error!("Failed to mint satoshis: {}", e);
-                    info!("You may need to restart the process again or try again later");
-                });
-                wallet.btc_address_registered = true;
+                let lcd_endpoint = &wallet.chain_config.lcd_endpoint;
+                wait_for_tx(&tx_hash, lcd_endpoint, options, deadline).await
             }
-            Err(e) => {
+            Err(e) => FaucetStepStatus::Failed(e),
+        };
+        match status {
+            FaucetStepStatus::Failed(ref e) => {
                 error!("Failed to register BTC deposit address: {}", e);
-                debug!("BTC Address: {}", &wallet.btc_address);
+                debug!("BTC Address: {}", LoggedAddress(&wallet.btc_address));
                 info!("You may need to restart the process again or try again later");
+                mint = false;
             }
-        };
-    } else if balance.sats < 50000 {
-        debug!("Skipping register BTC deposit address because sats is less than 50000");
+            _ => {
+                info!("Successfully registered BTC deposit address!");
+                debug!("BTC Address: {}", LoggedAddress(&wallet.btc_address));
+                if let Err(e) = wallet.btc_registration_status().await {
+                    warn!("Could not read the BTC registration status: {}", e);
+                }
+            }
+        }
+        status
+    } else {
+        FaucetStepStatus::Skipped
+    };
+    steps.push(FaucetStep {
+        kind: FaucetStepKind::RegisterBtcAddress,
+        status,
+        elapsed: step_started.elapsed(),
+    });
+
+    let step_started = Instant::now();
+    let status = if mint {
         info!("Minting test BTC...");
-        mint_sats(
+        let sent = mint_sats(
             &wallet.twilightaddress,
             &wallet.chain_config.faucet_endpoint,
         )
         .await
-        .unwrap_or_else(|e| {
-            error!("Failed to mint satoshis: {}", e);
-            info!("You may need to restart the process again or try again later");
-        });
+        .map_err(|e| e.to_string());
+        match sent {
+            Ok(()) => {
+                info!("waiting for updated sats balance to appear on-chain");
+                wait_for_balance(wallet, options, deadline, balance, |b| b.sats).await
+            }
+            Err(e) => {
+                error!("Failed to mint satoshis: {}", e);
+                info!("You may need to restart the process again or try again later");
+                FaucetStepStatus::Failed(e)
+            }
+        }
     } else {
-        info!("Skipping minting test BTC because sats is greater than 50000");
-    }
-    info!("waiting for updated sats balance to appear on-chain");
-    sleep(Duration::from_secs(10)).await;
+        if balance.sats >= 50000 {
+            info!("Skipping minting test BTC because sats is greater than 50000");
+        }
+        FaucetStepStatus::Skipped
+    };
+    steps.push(FaucetStep {
+        kind: FaucetStepKind::MintSats,
+        status,
+        elapsed: step_started.elapsed(),
+    });
+
     let balance = wallet.update_balance().await?;
-    debug!("new balance: {:?}", balance);
+    debug!(
+        "new balance: {} nyks, {} sats",
+        LoggedAmount(balance.nyks),
+        LoggedAmount(balance.sats)
+    );
+    for step in &steps {
+        debug!(
+            "Faucet step {:?}: {:?} in {:?}",
+            step.kind, step.status, step.elapsed
+        );
+    }
+
+    Ok(FaucetReport {
+        steps,
+        balance,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Poll the balance of `wallet` until the `amount` it selects exceeds the
+/// one in `before`.
+async fn wait_for_balance(
+    wallet: &Wallet,
+    options: &FaucetOptions,
+    deadline: Instant,
+    before: Balance,
+    amount: fn(&Balance) -> u64,
+) -> FaucetStepStatus {
+    let address = wallet.twilightaddress.clone();
+    let lcd_endpoint = wallet.chain_config.lcd_endpoint.clone();
+    let landed = poll_until(options.poll_interval, deadline, || {
+        let (address, lcd_endpoint) = (address.clone(), lcd_endpoint.clone());
+        async move {
+            check_balance(&address, &lcd_endpoint)
+                .await
+                .ok()
+                .filter(|balance| amount(balance) > amount(&before))
+        }
+    })
+    .await;
+    match landed {
+        Some(_) => FaucetStepStatus::Confirmed,
+        None => {
+            warn!("Faucet balance did not update within the timeout");
+            FaucetStepStatus::TimedOut
+        }
+    }
+}
 
-    Ok(())
+/// Poll the LCD until `tx_hash` is committed.
+async fn wait_for_tx(
+    tx_hash: &str,
+    lcd_endpoint: &str,
+    options: &FaucetOptions,
+    deadline: Instant,
+) -> FaucetStepStatus {
+    let committed = poll_until(options.poll_interval, deadline, || async move {
+        match query_tx_status(tx_hash, lcd_endpoint).await {
+            Ok(TxStatus::Committed { code: 0, .. }) => Some(FaucetStepStatus::Confirmed),
+            Ok(TxStatus::Committed { code, raw_log }) => Some(FaucetStepStatus::Failed(format!(
+                "tx {} failed with code {}: {}",
+                tx_hash, code, raw_log
+            ))),
+            Ok(TxStatus::Pending) | Err(_) => None,
+        }
+    })
+    .await;
+    committed.unwrap_or_else(|| {
+        warn!("Tx {} was not committed within the timeout", tx_hash);
+        FaucetStepStatus::TimedOut
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_serialize_redacts_key_material() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(mnemonic, None).unwrap();
+        let json = serde_json::to_string(&wallet).unwrap();
+        assert!(!json.contains(&hex::encode(wallet.private_key_bytes())));
+        assert!(!json.contains("\"private_key\""));
+        let btc_wallet = wallet.btc_wallet.as_ref().unwrap();
+        assert!(!json.contains(btc_wallet.wif()));
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["private_key_fingerprint"], wallet.key_fingerprint());
+        assert_eq!(value["btc_wallet"]["address"], btc_wallet.address);
+        // Redacted JSON cannot be mistaken for a loadable wallet.
+        assert!(serde_json::from_str::<Wallet>(&json).is_err());
+
+        let secrets = wallet.dangerous_serialize_with_secrets().unwrap();
+        let restored: Wallet = serde_json::from_slice(&secrets).unwrap();
+        assert_eq!(restored.private_key_bytes(), wallet.private_key_bytes());
+        assert_eq!(
+            restored.btc_wallet.as_ref().unwrap().wif(),
+            btc_wallet.wif()
+        );
+        assert_eq!(restored.twilightaddress, wallet.twilightaddress);
+    }
+
+    #[test]
+    fn test_debug_shows_key_fingerprints_only() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(mnemonic, None).unwrap();
+        let debug = format!("{:?}", wallet);
+        assert!(!debug.contains(&hex::encode(wallet.private_key_bytes())));
+        assert!(!debug.contains(wallet.btc_wallet.as_ref().unwrap().wif()));
+        assert!(debug.contains(&wallet.key_fingerprint()));
+        assert!(!format!("{:#?}", wallet).contains(&hex::encode(wallet.private_key_bytes())));
+    }
+
     #[test]
     fn test_import_wallet_from_mnemonic() {
         let mnemonic = "test test test test test test test test test test test junk";
@@ -1408,6 +2196,325 @@ mod tests {
         println!("Public key hex:     {}", hex::encode(&wallet.public_key));
     }
 
+    #[test]
+    fn test_mnemonic_passphrase_changes_derived_addresses() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let plain = Wallet::from_mnemonic(mnemonic, None).unwrap();
+        let empty =
+            Wallet::from_mnemonic_with_passphrase(mnemonic, SecretString::new(String::new()), None)
+                .unwrap();
+        assert_eq!(empty.twilightaddress, plain.twilightaddress);
+
+        let protected = Wallet::from_mnemonic_with_passphrase(
+            mnemonic,
+            SecretString::new("correct horse".to_string()),
+            None,
+        )
+        .unwrap();
+        assert_ne!(protected.twilightaddress, plain.twilightaddress);
+        assert_ne!(protected.btc_address, plain.btc_address);
+        assert_ne!(protected.private_key, plain.private_key);
+    }
+
+    #[test]
+    fn test_identical_entropy_streams_yield_identical_wallets() {
+        use crate::security::entropy::testing::CountingEntropy;
+
+        let source_a = CountingEntropy::starting_at(42);
+        let source_b = CountingEntropy::starting_at(42);
+        let (a, phrase_a) = Wallet::generate_with_entropy(&source_a, None).unwrap();
+        let (b, phrase_b) = Wallet::generate_with_entropy(&source_b, None).unwrap();
+        assert_eq!(source_a.bytes(), entropy::MNEMONIC_ENTROPY_BYTES);
+        assert_eq!(phrase_a, phrase_b);
+        assert_eq!(a.twilightaddress, b.twilightaddress);
+        assert_eq!(a.private_key_bytes(), b.private_key_bytes());
+        assert_eq!(a.public_key, b.public_key);
+        assert_eq!(a.btc_address, b.btc_address);
+
+        let (c, _) =
+            Wallet::generate_with_entropy(&CountingEntropy::starting_at(43), None).unwrap();
+        assert_ne!(a.twilightaddress, c.twilightaddress);
+        assert_ne!(a.btc_address, c.btc_address);
+    }
+
+    #[test]
+    fn test_generated_wallet_matches_mnemonic_import() {
+        use crate::security::entropy::testing::CountingEntropy;
+
+        let (generated, phrase) =
+            Wallet::generate_with_entropy(&CountingEntropy::starting_at(0), None).unwrap();
+        let imported = Wallet::from_mnemonic(&phrase, None).unwrap();
+        assert_eq!(generated.twilightaddress, imported.twilightaddress);
+        assert_eq!(generated.btc_address, imported.btc_address);
+    }
+
+    fn chain_config(chain_id: &str) -> WalletEndPointConfig {
+        WalletEndPointConfig::new(
+            "http://lcd".to_string(),
+            "http://faucet".to_string(),
+            "http://rpc".to_string(),
+            chain_id.to_string(),
+        )
+    }
+
+    /// `wallet` exported to a temporary file and read back as JSON.
+    fn exported_json(wallet: &Wallet, name: &str) -> Value {
+        let path = std::env::temp_dir().join(format!("nyks-{}-{}.json", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        wallet.export_to_json(&path, true).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn invalid_field(result: Result<Wallet, WalletError>) -> String {
+        match result {
+            Err(WalletError::InvalidWalletFile { field, .. }) => field,
+            other => panic!(
+                "expected InvalidWalletFile, got {:?}",
+                other.map(|w| w.to_string())
+            ),
+        }
+    }
+
+    #[test]
+    fn test_export_import_json_round_trip() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(mnemonic, Some(chain_config("nyks"))).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("nyks-round-trip-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        wallet.export_to_json(&path, true).unwrap();
+        let imported =
+            Wallet::import_from_json_checked(&path, Some(chain_config("nyks")), false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(imported.private_key_bytes(), wallet.private_key_bytes());
+        assert_eq!(imported.public_key, wallet.public_key);
+        assert_eq!(imported.twilightaddress, wallet.twilightaddress);
+        assert_eq!(imported.btc_address, wallet.btc_address);
+        assert_eq!(
+            imported.btc_wallet.as_ref().map(|btc| btc.address.clone()),
+            wallet.btc_wallet.as_ref().map(|btc| btc.address.clone())
+        );
+        assert_eq!(imported.chain_config.chain_id, "nyks");
+        assert_eq!(imported.chain_config.lcd_endpoint, "http://lcd");
+    }
+
+    #[test]
+    fn test_import_json_rejects_malformed_fields() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(mnemonic, Some(chain_config("nyks"))).unwrap();
+        let good = exported_json(&wallet, "malformed");
+        let import = |file: &Value| {
+            Wallet::from_wallet_json(&file.to_string(), Some(chain_config("nyks")), false)
+        };
+
+        let mut file = good.clone();
+        file.as_object_mut().unwrap().remove("private_key");
+        assert_eq!(invalid_field(import(&file)), "private_key");
+
+        let mut file = good.clone();
+        file["private_key"] = Value::from("zz");
+        assert_eq!(invalid_field(import(&file)), "private_key");
+        file["private_key"] = Value::from("abcd");
+        assert_eq!(invalid_field(import(&file)), "private_key");
+
+        // A valid key that belongs to another wallet.
+        let other = Wallet::from_mnemonic(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            None,
+        )
+        .unwrap();
+        let mut file = good.clone();
+        file["private_key"] = Value::from(hex::encode(other.private_key_bytes()));
+        file["public_key"] = Value::from(hex::encode(&other.public_key));
+        assert_eq!(invalid_field(import(&file)), "twilightaddress");
+
+        let mut file = good.clone();
+        file["btc_address"] = Value::from("1BoatSLRHtKNngkdXEeobR76b53LETtpyT");
+        assert_eq!(invalid_field(import(&file)), "btc_address");
+
+        let mut file = good.clone();
+        file["balance_sats"] = Value::from("lots");
+        assert_eq!(invalid_field(import(&file)), "balance_sats");
+    }
+
+    #[test]
+    fn test_import_json_checks_chain_id() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(mnemonic, Some(chain_config("nyks"))).unwrap();
+        let json = exported_json(&wallet, "chain-id").to_string();
+
+        let mismatch = Wallet::from_wallet_json(&json, Some(chain_config("nyks-2")), false);
+        assert_eq!(invalid_field(mismatch), "chain_id");
+        let allowed = Wallet::from_wallet_json(&json, Some(chain_config("nyks-2")), true).unwrap();
+        assert_eq!(allowed.chain_config.chain_id, "nyks");
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("nyks-{}-{}.json", name, std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_encrypted_export_import_round_trip() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let mut wallet = Wallet::from_mnemonic(mnemonic, Some(chain_config("nyks"))).unwrap();
+        wallet.balance_nyks = 7;
+        wallet.balance_sats = 12_345;
+        wallet.sequence = 3;
+        wallet.btc_address_registered = true;
+        let password = SecretString::new("correct horse".into());
+        let path = temp_path("encrypted-round-trip");
+        wallet.export_to_encrypted_json(&path, &password).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&hex::encode(wallet.private_key_bytes())));
+        assert!(!contents.contains(&wallet.twilightaddress));
+        let header: Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(header["format"], "nyks-wallet-encrypted");
+        assert_eq!(header["version"], 1);
+
+        let imported = Wallet::import_from_encrypted_json_checked(
+            &path,
+            &password,
+            Some(chain_config("nyks")),
+            false,
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(imported.private_key_bytes(), wallet.private_key_bytes());
+        assert_eq!(imported.public_key, wallet.public_key);
+        assert_eq!(imported.twilightaddress, wallet.twilightaddress);
+        assert_eq!(imported.balance_nyks, 7);
+        assert_eq!(imported.balance_sats, 12_345);
+        assert_eq!(imported.sequence, 3);
+        assert_eq!(imported.btc_address, wallet.btc_address);
+        assert!(imported.btc_address_registered);
+        assert_eq!(
+            imported.btc_wallet.as_ref().map(|btc| btc.address.clone()),
+            wallet.btc_wallet.as_ref().map(|btc| btc.address.clone())
+        );
+        assert_eq!(imported.chain_config.chain_id, "nyks");
+        assert_eq!(imported.chain_config.lcd_endpoint, "http://lcd");
+    }
+
+    #[test]
+    fn test_encrypted_import_rejects_wrong_password_and_tampering() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(mnemonic, Some(chain_config("nyks"))).unwrap();
+        let password = SecretString::new("correct horse".into());
+        let path = temp_path("encrypted-tamper");
+        wallet.export_to_encrypted_json(&path, &password).unwrap();
+        let good: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let import = |file: &Value| {
+            std::fs::write(&path, file.to_string()).unwrap();
+            Wallet::import_from_encrypted_json_checked(
+                &path,
+                &password,
+                Some(chain_config("nyks")),
+                false,
+            )
+        };
+
+        let wrong = Wallet::import_from_encrypted_json_checked(
+            &path,
+            &SecretString::new("battery staple".into()),
+            Some(chain_config("nyks")),
+            false,
+        );
+        assert!(matches!(wrong, Err(WalletError::Decryption(_))));
+
+        let mut file = good.clone();
+        let mut ciphertext = file["ciphertext"].as_str().unwrap().to_string();
+        let flipped = if ciphertext.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        ciphertext.replace_range(0..1, flipped);
+        file["ciphertext"] = Value::from(ciphertext);
+        assert!(matches!(import(&file), Err(WalletError::Decryption(_))));
+
+        let mut file = good.clone();
+        file["version"] = Value::from(2);
+        assert!(matches!(import(&file), Err(WalletError::Import(_))));
+
+        let mut file = good.clone();
+        file["format"] = Value::from("something-else");
+        assert!(matches!(import(&file), Err(WalletError::Import(_))));
+
+        assert!(import(&good).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_import_uses_the_iterations_in_the_header() {
+        use crate::security::entropy::testing::CountingEntropy;
+        use crate::wallet::encrypted_file::{MAX_ITERATIONS, MIN_ITERATIONS, open, seal_with};
+
+        let password = SecretString::new("correct horse".into());
+        let source = CountingEntropy::starting_at(0);
+        let sealed = seal_with(b"wallet", &password, &source, MIN_ITERATIONS).unwrap();
+        let header: Value = serde_json::from_str(&sealed).unwrap();
+        assert_eq!(header["iterations"], MIN_ITERATIONS);
+        assert_eq!(open(&sealed, &password).unwrap().as_slice(), b"wallet");
+
+        for iterations in [MIN_ITERATIONS - 1, MAX_ITERATIONS + 1] {
+            let mut file = header.clone();
+            file["iterations"] = Value::from(iterations);
+            assert!(matches!(
+                open(&file.to_string(), &password),
+                Err(WalletError::Import(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_plaintext_export_requires_opt_in() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let wallet = Wallet::from_mnemonic(mnemonic, Some(chain_config("nyks"))).unwrap();
+        let path = temp_path("plaintext-refused");
+        assert!(wallet.export_to_json(&path, false).is_err());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_from_private_key_hex() {
+        let key = hex::encode([7u8; 32]);
+        let wallet = Wallet::from_private_key_hex(&key, None, None).unwrap();
+        let prefixed = Wallet::from_private_key_hex(&format!("0x{}", key), None, None).unwrap();
+        assert_eq!(wallet.twilightaddress, prefixed.twilightaddress);
+        assert_eq!(wallet.btc_address, prefixed.btc_address);
+        assert!(wallet.btc_wallet.is_some());
+        assert!(btc_address_prefixes()
+            .iter()
+            .any(|prefix| wallet.btc_address.starts_with(prefix)));
+
+        let supplied = Wallet::from_private_key_hex(&key, Some(&wallet.btc_address), None).unwrap();
+        assert_eq!(supplied.twilightaddress, wallet.twilightaddress);
+        assert!(supplied.btc_wallet.is_none());
+
+        assert!(matches!(
+            Wallet::from_private_key_hex("zz", None, None),
+            Err(WalletError::InvalidPrivateKeyHex(_))
+        ));
+        assert!(matches!(
+            Wallet::from_private_key_hex(&key[..62], None, None),
+            Err(WalletError::InvalidPrivateKeyLength(31))
+        ));
+        assert!(matches!(
+            Wallet::from_private_key_hex(&hex::encode([0u8; 32]), None, None),
+            Err(WalletError::InvalidPrivateKey(_))
+        ));
+        assert!(matches!(
+            Wallet::from_private_key_hex(&key, Some("not-an-address"), None),
+            Err(WalletError::InvalidBtcAddress(_))
+        ));
+    }
+
     #[test]
     fn test_parse_cltv_from_script() {
         // Real script from propose_sweep_addresses_all response
@@ -1431,6 +2538,36 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_registration_status_from_chain() {
+        let detail = |address: &str, is_confirmed| BtcDepositDetail {
+            btc_deposit_address: address.to_string(),
+            btc_satoshi_amount: 50_000,
+            twilight_staking_amount: DEFAULT_TWILIGHT_STAKING_AMOUNT,
+            twilight_address: "twilight1abc".to_string(),
+            is_confirmed,
+            creation_block_height: 10,
+        };
+        assert_eq!(
+            BtcRegistrationStatus::from_chain(None, &[detail("bc1qa", true)]),
+            BtcRegistrationStatus::Unregistered
+        );
+        let pending = BtcRegistrationStatus::from_chain(Some("bc1qb"), &[detail("bc1qa", true)]);
+        assert_eq!(
+            pending,
+            BtcRegistrationStatus::Pending {
+                address: "bc1qb".to_string()
+            }
+        );
+        assert_eq!(pending.address(), Some("bc1qb"));
+        assert_eq!(
+            BtcRegistrationStatus::from_chain(Some("bc1qa"), &[detail("bc1qa", true)]),
+            BtcRegistrationStatus::Registered {
+                address: "bc1qa".to_string()
+            }
+        );
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_fetch_btc_proposed_reserve() {
@@ -1460,4 +2597,98 @@ mod tests {
             "Should have at least one proposed reserve"
         );
     }
+
+    #[test]
+    fn test_parse_balance_response() {
+        let body = crate::response_mutations::fixture("lcd_balance/balances.json");
+        assert_eq!(
+            parse_balance_response(&body).unwrap(),
+            Balance {
+                nyks: 200000,
+                sats: 50000
+            }
+        );
+        let too_large = r#"{"balances":[{"denom":"sats","amount":"184467440737095516160"}]}"#;
+        assert!(parse_balance_response(too_large).is_err());
+        assert!(parse_balance_response("not json").is_err());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_parse_balance_response_never_panics(
+            body in crate::response_mutations::mutated_fixture("lcd_balance/balances.json"),
+        ) {
+            let _ = parse_balance_response(&String::from_utf8_lossy(&body));
+        }
+    }
+
+    /// Tendermint stand-in accepting only the sequence in `next`. The test's
+    /// "signed tx" is the sequence it was signed with.
+    fn sequence_checking_rpc(
+        next: Arc<std::sync::atomic::AtomicU64>,
+    ) -> jsonrpc_http_server::Server {
+        use jsonrpc_core::{IoHandler, Params};
+        use std::sync::atomic::Ordering;
+
+        let mut io = IoHandler::new();
+        io.add_sync_method("broadcast_tx_sync", move |params: Params| {
+            let args: Value = params.parse()?;
+            let sequence: u64 = args["tx"].as_str().unwrap().parse().unwrap();
+            let expected = next.load(Ordering::SeqCst);
+            if sequence != expected {
+                return Ok(serde_json::json!({
+                    "code": 32,
+                    "hash": format!("REJECTED{}", sequence),
+                    "log": format!(
+                        "account sequence mismatch, expected {}, got {}: incorrect account sequence",
+                        expected, sequence
+                    ),
+                }));
+            }
+            next.store(expected + 1, Ordering::SeqCst);
+            Ok(serde_json::json!({ "code": 0, "hash": format!("TX{}", sequence), "log": "[]" }))
+        });
+        jsonrpc_http_server::ServerBuilder::new(io)
+            .start_http(&"127.0.0.1:0".parse().unwrap())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sign_and_broadcast_counts_locally_and_resigns_once() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let chain_next = Arc::new(AtomicU64::new(5));
+        let server = sequence_checking_rpc(chain_next.clone());
+        let mnemonic = "test test test test test test test test test test test junk";
+        let mut config = chain_config("nyks");
+        config.rpc_endpoint = format!("http://{}", server.address());
+        let mut wallet = Wallet::from_mnemonic(mnemonic, Some(config)).unwrap();
+        // Another process already used sequences 3 and 4.
+        wallet.nonce_manager = Arc::new(NonceManager::with_initial(3, 1));
+        let sign = |sequence: u64, _: u64| Ok(sequence.to_string());
+
+        // Rejected at 3, re-signed at the 5 the chain asked for.
+        let first = wallet.sign_and_broadcast(sign).await.unwrap();
+        assert_eq!((first.code, first.hash.as_str()), (0, "TX5"));
+        // Queued straight after: counted locally, no refetch in between.
+        let second = wallet.clone().sign_and_broadcast(sign).await.unwrap();
+        assert_eq!((second.code, second.hash.as_str()), (0, "TX6"));
+
+        // The chain keeps moving on behind our back: one re-sign, then a typed error.
+        chain_next.store(20, Ordering::SeqCst);
+        let keep_moving = |sequence: u64, _: u64| {
+            chain_next.fetch_add(1, Ordering::SeqCst);
+            Ok(sequence.to_string())
+        };
+        match wallet.sign_and_broadcast(keep_moving).await {
+            Err(WalletError::SequenceMismatch {
+                sequence, expected, ..
+            }) => {
+                assert_eq!(sequence, 21);
+                assert_eq!(expected, Some(22));
+            }
+            other => panic!("expected a sequence mismatch, got {:?}", other),
+        }
+        server.close();
+    }
 }
@@ -1,11 +1,12 @@
 use core::convert::TryInto;
 use curve25519_dalek::scalar::Scalar;
-use rand::rngs::OsRng;
+use rand::rngs::{OsRng, StdRng};
+use rand::SeedableRng;
 use serde::Deserialize;
 use serde::Serialize;
 use sha2::{Digest, Sha512};
-use twilight_client_sdk::address::{AddressType, Network};
-use twilight_client_sdk::{
+use crate::compat::address::{AddressType, Network};
+use crate::compat::{
     quisquislib::{
         self, Account, ElGamalCommitment, RistrettoPublicKey, RistrettoSecretKey, keys::SecretKey,
     },
@@ -43,6 +44,22 @@ impl KeyManager {
     pub fn derive_child_key(&self, account_index: u64) -> RistrettoSecretKey {
         derive_child_key(&self.master_key, account_index)
     }
+
+    /// Derives the public key of the account at `account_index`. The key is
+    /// randomized from a seed derived from the master key and index instead of
+    /// the OS RNG, so the account address can be recomputed from the seed alone.
+    pub fn derive_public_key(&self, account_index: u64) -> RistrettoPublicKey {
+        let secret_key = self.derive_child_key(account_index);
+        let mut rng = StdRng::from_seed(address_rng_seed(&self.master_key, account_index));
+        <RistrettoPublicKey as quisquislib::keys::PublicKey>::from_secret_key(&secret_key, &mut rng)
+    }
+
+    /// Hex ZkOS address of the account at `account_index`; see
+    /// [`derive_public_key`](Self::derive_public_key).
+    pub fn derive_address(&self, account_index: u64) -> String {
+        Address::standard_address(Network::default(), self.derive_public_key(account_index))
+            .as_hex()
+    }
 }
 
 /// Derives the single, master Ristretto secret key from a user's Cosmos signature.
@@ -68,6 +85,17 @@ fn derive_child_key(master_key: &RistrettoSecretKey, account_index: u64) -> Rist
     SecretKey::from_bytes(&hasher.finalize())
 }
 
+/// Seed of the RNG that randomizes the public key of an account.
+fn address_rng_seed(master_key: &RistrettoSecretKey, account_index: u64) -> [u8; 32] {
+    let mut hasher = Sha512::new();
+    hasher.update(master_key.as_bytes());
+    hasher.update(b"twilight_account_address"); // Domain separation constant
+    hasher.update(&account_index.to_le_bytes());
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&hasher.finalize()[..32]);
+    seed
+}
+
 // convert the hex string into a RistrettoPublicKey
 pub fn public_key_from_hex(hex_str: String) -> Result<RistrettoPublicKey, &'static str> {
     let bytes = match hex::decode(hex_str) {
@@ -333,7 +361,8 @@ pub fn create_output_for_coin_from_encrypted_account(
 /// Returns EncryptedAccount as hex string.
 ///
 pub fn extract_encrypted_account_from_output_coin(output: String) -> Result<String, &'static str> {
-    let out: Output = serde_json::from_str(&output).unwrap();
+    let out: Output =
+        serde_json::from_str(&output).map_err(|_| "Error parsing Output Json string")?;
 
     let account: EncryptedAccount = EncryptedAccount::from(out.clone());
     account.to_hex_str()
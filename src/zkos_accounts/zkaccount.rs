@@ -1,20 +1,53 @@
 use super::encrypted_account::{EncryptedAccount, KeyManager};
+use chrono::{DateTime, Utc};
 use curve25519_dalek::scalar::Scalar;
 use rand::rngs::OsRng;
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use twilight_client_sdk::{
+use crate::compat::{
     address::Network,
-    quisquislib::{
-        keys::PublicKey, ristretto::RistrettoPublicKey, Account, ElGamalCommitment,
-        RistrettoSecretKey,
-    },
+    quisquislib::{Account, ElGamalCommitment, RistrettoSecretKey},
     relayer_types::TXType,
     zkvm::{IOType, Input, Utxo},
 };
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Maximum length (in bytes) of a stored error message.
+pub const MAX_STORED_ERROR_LEN: usize = 512;
+
+/// Most recent failure recorded against an account, kept for support/diagnostics.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct StoredError {
+    pub when: DateTime<Utc>,
+    pub operation: String,
+    pub message: String,
+    /// Whether retrying the same operation may succeed, by
+    /// [`RetryClass::of_message`](crate::relayer_module::RetryClass::of_message).
+    pub retriable: bool,
+}
+
+impl StoredError {
+    /// Build a record, truncating `message` to `MAX_STORED_ERROR_LEN` bytes.
+    pub fn new(operation: &str, message: &str, when: DateTime<Utc>) -> Self {
+        let mut end = message.len().min(MAX_STORED_ERROR_LEN);
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut truncated = message[..end].to_string();
+        if end < message.len() {
+            truncated.push_str("...");
+        }
+        Self {
+            when,
+            operation: operation.to_string(),
+            retriable: crate::relayer_module::RetryClass::of_message(message).is_retryable(),
+            message: truncated,
+        }
+    }
+}
+
+/// `Debug` shows the `scalar` as its [`key_fingerprint`](crate::wallet::key_fingerprint).
+#[derive(Deserialize, Serialize, Clone)]
 pub struct ZkAccount {
     pub qq_address: String,
     pub balance: u64,
@@ -24,7 +57,45 @@ pub struct ZkAccount {
     pub io_type: IOType,
     pub on_chain: bool,
     pub tx_type: Option<TXType>,
+    /// Most recent failed operation on this account; cleared on the next success.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<StoredError>,
+    /// `balance` is the requested funding amount because the amount committed
+    /// on chain could not be derived; check it before trading.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub balance_unverified: bool,
+    /// State changed by a dry run rather than by the chain or relayer; never
+    /// saved to the database.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub simulated: bool,
+    /// Caller-chosen name, unique among the active accounts; see
+    /// [`ZkAccountDB::set_label`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl std::fmt::Debug for ZkAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZkAccount")
+            .field("qq_address", &self.qq_address)
+            .field("balance", &self.balance)
+            .field("account", &self.account)
+            .field(
+                "scalar_fingerprint",
+                &crate::wallet::key_fingerprint(self.scalar.as_bytes()),
+            )
+            .field("index", &self.index)
+            .field("io_type", &self.io_type)
+            .field("on_chain", &self.on_chain)
+            .field("tx_type", &self.tx_type)
+            .field("last_error", &self.last_error)
+            .field("balance_unverified", &self.balance_unverified)
+            .field("simulated", &self.simulated)
+            .field("label", &self.label)
+            .finish()
+    }
 }
+
 impl ZkAccount {
     pub fn new(
         qq_address: String,
@@ -42,14 +113,20 @@ impl ZkAccount {
             io_type: IOType::Coin,
             on_chain: false,
             tx_type: None,
+            last_error: None,
+            balance_unverified: false,
+            simulated: false,
+            label: None,
         }
     }
 
+    /// Account `index` of `seed` committing to `balance`. Its address only
+    /// depends on the seed and index ([`KeyManager::derive_address`]), so it
+    /// can be found again from the seed; the commitment is freshly randomized.
     pub fn from_seed(index: u64, seed: &SecretString, balance: u64) -> Result<Self, String> {
         let key_manager = KeyManager::from_cosmos_signature(seed.expose_secret().as_bytes());
 
-        let secret_key = key_manager.derive_child_key(index);
-        let pk_in = RistrettoPublicKey::from_secret_key(&secret_key, &mut OsRng);
+        let pk_in = key_manager.derive_public_key(index);
 
         let rscalar = Scalar::random(&mut OsRng);
         let rscalar_str = hex::encode(rscalar.to_bytes());
@@ -91,7 +168,7 @@ impl ZkAccount {
         serde_json::to_string(&input).map_err(|e| e.to_string())
     }
     pub fn get_scalar(&self) -> Result<Scalar, String> {
-        let scalar = twilight_client_sdk::util::hex_to_scalar(self.scalar.clone())
+        let scalar = crate::compat::util::hex_to_scalar(self.scalar.clone())
             .ok_or("Failed to convert scalar_hex to scalar")?;
         Ok(scalar)
     }
@@ -106,10 +183,57 @@ impl ZkAccount {
     }
 }
 
+/// Amount the ElGamal commitment of `account` encrypts, or `None` if it
+/// cannot be recovered with `secret_key`. `expected` is checked first, so the
+/// usual case avoids decrypting.
+pub fn committed_amount(
+    account: &Account,
+    secret_key: &RistrettoSecretKey,
+    expected: u64,
+) -> Option<u64> {
+    if account
+        .verify_account(secret_key, Scalar::from(expected))
+        .is_ok()
+    {
+        return Some(expected);
+    }
+    let value = account.decrypt_account_balance_value(secret_key).ok()?;
+    let bytes = value.to_bytes();
+    if bytes[8..].iter().any(|b| *b != 0) {
+        return None;
+    }
+    let low: [u8; 8] = bytes[..8].try_into().ok()?;
+    Some(u64::from_le_bytes(low))
+}
+
+/// Errors from looking up or updating an account in a [`ZkAccountDB`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ZkAccountError {
+    #[error("Account with index {0} does not exist")]
+    NotFound(u64),
+    #[error("Account with index {0} already exists")]
+    AlreadyExists(u64),
+    #[error("Account with index {0} is in an invalid state: {1}")]
+    InvalidState(u64, String),
+    #[error("Label {0:?} is already used by account {1}")]
+    DuplicateLabel(String, u64),
+}
+
+/// Lets `?` propagate a [`ZkAccountError`] from functions returning `Result<_, String>`.
+impl From<ZkAccountError> for String {
+    fn from(error: ZkAccountError) -> Self {
+        error.to_string()
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ZkAccountDB {
     pub accounts: HashMap<u64, ZkAccount>,
     pub index: u64,
+    /// Spent accounts moved out of `accounts`. Still resolvable by index, but
+    /// skipped by iteration and persistence.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub archived: HashMap<u64, ZkAccount>,
 }
 
 impl ZkAccountDB {
@@ -117,54 +241,65 @@ impl ZkAccountDB {
         Self {
             accounts: HashMap::new(),
             index: 0,
+            archived: HashMap::new(),
         }
     }
-    pub fn add_account(&mut self, account: ZkAccount) -> Option<ZkAccount> {
-        let result = self.accounts.insert(self.index, account);
+    /// Store `account` under the next free index and return that index.
+    pub fn add_account(&mut self, account: ZkAccount) -> Result<u64, ZkAccountError> {
+        let index = self.index;
+        if self.accounts.contains_key(&index) || self.archived.contains_key(&index) {
+            return Err(ZkAccountError::AlreadyExists(index));
+        }
+        self.accounts.insert(index, account);
         self.index += 1;
-        result
+        Ok(index)
     }
     pub fn generate_new_account(
         &mut self,
         balance: u64,
         seed: &SecretString,
-    ) -> Result<u64, String> {
-        let zk_account = ZkAccount::from_seed(self.index, seed, balance)?;
-        self.add_account(zk_account);
-        Ok(self.index - 1)
+    ) -> Result<u64, ZkAccountError> {
+        let zk_account = ZkAccount::from_seed(self.index, seed, balance)
+            .map_err(|e| ZkAccountError::InvalidState(self.index, e))?;
+        self.add_account(zk_account)
     }
-    pub fn try_add_account(&mut self, account: ZkAccount) -> Result<u64, String> {
+    pub fn try_add_account(&mut self, account: ZkAccount) -> Result<u64, ZkAccountError> {
         if self.accounts.contains_key(&account.index) {
-            return Err(format!(
-                "Account with index {} already exists",
-                account.index
-            ));
+            return Err(ZkAccountError::AlreadyExists(account.index));
         }
         self.accounts.insert(self.index, account);
         self.index += 1;
         Ok(self.index)
     }
-    pub fn get_account_address(&self, index: &u64) -> Result<String, String> {
-        let account = self
-            .accounts
-            .get(index)
-            .map(|account| account.account.clone());
-        match account {
-            Some(account) => Ok(account),
-            None => Err(format!("Account with index {} does not exist", index)),
+    /// Store `account` under its own index, e.g. one recovered from the chain,
+    /// and move the next free index past it.
+    pub fn restore_account(&mut self, account: ZkAccount) -> Result<(), ZkAccountError> {
+        let index = account.index;
+        if self.accounts.contains_key(&index) || self.archived.contains_key(&index) {
+            return Err(ZkAccountError::AlreadyExists(index));
         }
+        self.accounts.insert(index, account);
+        self.index = self.index.max(index + 1);
+        Ok(())
     }
-    pub fn get_account(&self, index: &u64) -> Result<ZkAccount, String> {
-        match self.accounts.get(index).cloned() {
-            Some(account) => Ok(account),
-            None => Err(format!("Account with index {} does not exist", index)),
-        }
+    /// Same as [`get_address`](Self::get_address).
+    pub fn get_account_address(&self, index: &u64) -> Result<String, ZkAccountError> {
+        self.get_address(index)
     }
-    pub fn get_mut_account(&mut self, index: &u64) -> Option<&mut ZkAccount> {
-        self.accounts.get_mut(index)
+    pub fn get_account(&self, index: &u64) -> Result<ZkAccount, ZkAccountError> {
+        self.get_ref(index).cloned()
     }
-    pub fn remove_account(&mut self, index: &u64) {
-        self.accounts.remove(index);
+    pub fn get_mut_account(&mut self, index: &u64) -> Result<&mut ZkAccount, ZkAccountError> {
+        self.accounts
+            .get_mut(index)
+            .ok_or(ZkAccountError::NotFound(*index))
+    }
+    /// Remove an active account, returning it. Prefer
+    /// [`archive_account`](Self::archive_account) for spent accounts.
+    pub fn remove_account(&mut self, index: &u64) -> Result<ZkAccount, ZkAccountError> {
+        self.accounts
+            .remove(index)
+            .ok_or(ZkAccountError::NotFound(*index))
     }
     pub fn get_all_accounts(&self) -> Vec<&ZkAccount> {
         self.accounts.values().collect()
@@ -183,17 +318,21 @@ impl ZkAccountDB {
         };
         Ok(zk_accounts_db)
     }
-    pub fn get_balance(&self, index: &u64) -> Option<u64> {
-        self.accounts.get(index).map(|account| account.balance)
+    /// ZkOS address of an active account.
+    pub fn get_address(&self, index: &u64) -> Result<String, ZkAccountError> {
+        self.get_ref(index).map(|account| account.account.clone())
     }
-    pub fn update_balance(&mut self, index: &u64, balance: u64) -> Result<(), String> {
-        if !self.accounts.contains_key(index) {
-            return Err(format!("Account with index {} does not exist", index));
-        }
-        self.accounts
-            .get_mut(index)
-            .ok_or(format!("Account with index {} does not exist", index))?
-            .balance = balance;
+    pub fn get_balance(&self, index: &u64) -> Result<u64, ZkAccountError> {
+        self.get_ref(index).map(|account| account.balance)
+    }
+    pub fn get_io_type(&self, index: &u64) -> Result<IOType, ZkAccountError> {
+        self.get_ref(index).map(|account| account.io_type.clone())
+    }
+    pub fn is_on_chain(&self, index: &u64) -> Result<bool, ZkAccountError> {
+        self.get_ref(index).map(|account| account.on_chain)
+    }
+    pub fn update_balance(&mut self, index: &u64, balance: u64) -> Result<(), ZkAccountError> {
+        self.get_mut_account(index)?.balance = balance;
         Ok(())
     }
     pub fn export_to_json(&self, path: &str) -> Result<(), String> {
@@ -229,58 +368,389 @@ impl ZkAccountDB {
         index: &u64,
         io_type: IOType,
         tx_type: Option<TXType>,
-    ) -> Result<(), String> {
-        let account = self
-            .accounts
-            .get_mut(index)
-            .ok_or(format!("Account with index {} does not exist", index))?;
+    ) -> Result<(), ZkAccountError> {
+        let account = self.get_mut_account(index)?;
         account.io_type = io_type;
         if tx_type.is_some() {
             account.tx_type = tx_type;
         }
         Ok(())
     }
-    pub fn update_scalar(&mut self, index: &u64, scalar: &str) -> Result<(), String> {
-        self.accounts
-            .get_mut(index)
-            .ok_or(format!("Account with index {} does not exist", index))?
-            .scalar = scalar.to_string();
+    pub fn update_scalar(&mut self, index: &u64, scalar: &str) -> Result<(), ZkAccountError> {
+        self.get_mut_account(index)?.scalar = scalar.to_string();
         Ok(())
     }
-    pub fn update_account_key(&mut self, index: &u64, account_key: &str) -> Result<(), String> {
-        self.accounts
-            .get_mut(index)
-            .ok_or(format!("Account with index {} does not exist", index))?
-            .account = account_key.to_string();
+    pub fn update_account_key(
+        &mut self,
+        index: &u64,
+        account_key: &str,
+    ) -> Result<(), ZkAccountError> {
+        self.get_mut_account(index)?.account = account_key.to_string();
         Ok(())
     }
-    pub fn update_on_chain(&mut self, index: &u64, on_chain: bool) -> Result<(), String> {
-        // if !self.accounts.contains_key(&index) {
-        //     return Err(format!("Account with index {} does not exist", index));
-        // }
-        self.accounts
-            .get_mut(index)
-            .ok_or(format!("Account with index {} does not exist", index))?
-            .on_chain = on_chain;
+    pub fn update_on_chain(&mut self, index: &u64, on_chain: bool) -> Result<(), ZkAccountError> {
+        self.get_mut_account(index)?.on_chain = on_chain;
         Ok(())
     }
-    pub fn update_qq_account(&mut self, index: &u64, account: Account) -> Result<(), String> {
-        // if !self.accounts.contains_key(&index) {
-        //     return Err(format!("Account with index {} does not exist", index));
-        // }
+    pub fn update_qq_account(
+        &mut self,
+        index: &u64,
+        account: Account,
+    ) -> Result<(), ZkAccountError> {
+        let target = self.get_mut_account(index)?;
         let qq_address: EncryptedAccount = EncryptedAccount::from(account);
-        let qq_str = qq_address.to_hex_str().map_err(|e| e.to_string())?;
-        self.accounts
-            .get_mut(index)
-            .ok_or(format!("Account with index {} does not exist", index))?
-            .qq_address = qq_str;
+        target.qq_address = qq_address
+            .to_hex_str()
+            .map_err(|e| ZkAccountError::InvalidState(*index, e.to_string()))?;
         Ok(())
     }
-    pub fn remove_account_by_index(&mut self, index: &u64) -> Result<(), String> {
-        if !self.accounts.contains_key(&index) {
-            return Err(format!("Account with index {} does not exist", index));
+    /// Record `error` as the account's most recent failure.
+    pub fn set_last_error(
+        &mut self,
+        index: &u64,
+        error: StoredError,
+    ) -> Result<(), ZkAccountError> {
+        self.get_mut_account(index)?.last_error = Some(error);
+        Ok(())
+    }
+    /// Set or clear the account's `balance_unverified` flag.
+    pub fn set_balance_unverified(
+        &mut self,
+        index: &u64,
+        unverified: bool,
+    ) -> Result<(), ZkAccountError> {
+        self.get_mut_account(index)?.balance_unverified = unverified;
+        Ok(())
+    }
+    /// Mark the account as changed by a dry run.
+    pub fn mark_simulated(&mut self, index: &u64) -> Result<(), ZkAccountError> {
+        self.get_mut_account(index)?.simulated = true;
+        Ok(())
+    }
+    /// Clear the account's recorded failure. Returns `true` if one was present.
+    pub fn clear_last_error(&mut self, index: &u64) -> Result<bool, ZkAccountError> {
+        Ok(self.get_mut_account(index)?.last_error.take().is_some())
+    }
+    /// Move an account out of the active map. Its index is not reused.
+    pub fn archive_account(&mut self, index: &u64) -> Result<(), ZkAccountError> {
+        if self.archived.contains_key(index) {
+            return Err(ZkAccountError::InvalidState(
+                *index,
+                "already archived".to_string(),
+            ));
         }
-        self.accounts.remove(index);
+        let account = self.remove_account(index)?;
+        self.archived.insert(*index, account);
         Ok(())
     }
+    /// Move an archived account back into the active map. Fails if its label
+    /// has since been given to an active account.
+    pub fn unarchive_account(&mut self, index: &u64) -> Result<(), ZkAccountError> {
+        let Some(account) = self.archived.get(index) else {
+            if self.accounts.contains_key(index) {
+                return Err(ZkAccountError::InvalidState(
+                    *index,
+                    "not archived".to_string(),
+                ));
+            }
+            return Err(ZkAccountError::NotFound(*index));
+        };
+        if let Some(label) = &account.label {
+            self.check_label_free(index, label)?;
+        }
+        if let Some(account) = self.archived.remove(index) {
+            self.accounts.insert(*index, account);
+        }
+        Ok(())
+    }
+    pub fn is_archived(&self, index: &u64) -> bool {
+        self.archived.contains_key(index)
+    }
+    pub fn get_archived_accounts(&self) -> Vec<&ZkAccount> {
+        self.archived.values().collect()
+    }
+    /// Look up an account whether active or archived, e.g. to resolve the
+    /// account behind a historical order or transfer.
+    pub fn resolve_account(&self, index: &u64) -> Result<ZkAccount, ZkAccountError> {
+        self.accounts
+            .get(index)
+            .or_else(|| self.archived.get(index))
+            .cloned()
+            .ok_or(ZkAccountError::NotFound(*index))
+    }
+    pub fn remove_account_by_index(&mut self, index: &u64) -> Result<(), ZkAccountError> {
+        self.remove_account(index).map(|_| ())
+    }
+    /// Name an active account, or clear its name with `None`. Labels are
+    /// unique among active accounts: giving one already in use to another
+    /// account fails with [`ZkAccountError::DuplicateLabel`]. Archived
+    /// accounts keep their label, so a spent account's name can move on.
+    pub fn set_label(&mut self, index: &u64, label: Option<String>) -> Result<(), ZkAccountError> {
+        self.get_ref(index)?;
+        if let Some(label) = &label {
+            if label.trim().is_empty() {
+                return Err(ZkAccountError::InvalidState(
+                    *index,
+                    "label must not be empty".to_string(),
+                ));
+            }
+            self.check_label_free(index, label)?;
+        }
+        self.get_mut_account(index)?.label = label;
+        Ok(())
+    }
+    /// The active account named `label`.
+    pub fn get_by_label(&self, label: &str) -> Option<&ZkAccount> {
+        self.accounts
+            .values()
+            .find(|account| account.label.as_deref() == Some(label))
+    }
+    /// Active accounts whose label starts with `prefix` (e.g. `"mm/"` for
+    /// `"mm/bid"` and `"mm/ask"`), by index.
+    pub fn accounts_with_tag(&self, prefix: &str) -> Vec<&ZkAccount> {
+        let mut accounts: Vec<&ZkAccount> = self
+            .accounts
+            .values()
+            .filter(|account| {
+                account
+                    .label
+                    .as_deref()
+                    .is_some_and(|label| label.starts_with(prefix))
+            })
+            .collect();
+        accounts.sort_by_key(|account| account.index);
+        accounts
+    }
+    /// Fails if an active account other than `index` is named `label`.
+    pub fn check_label_free(&self, index: &u64, label: &str) -> Result<(), ZkAccountError> {
+        let owner = self
+            .accounts
+            .iter()
+            .find(|(_, account)| account.label.as_deref() == Some(label));
+        match owner {
+            Some((owner, _)) if owner != index => {
+                Err(ZkAccountError::DuplicateLabel(label.to_string(), *owner))
+            }
+            _ => Ok(()),
+        }
+    }
+    fn get_ref(&self, index: &u64) -> Result<&ZkAccount, ZkAccountError> {
+        self.accounts
+            .get(index)
+            .ok_or(ZkAccountError::NotFound(*index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed() -> SecretString {
+        SecretString::new("zkaccount-db-test-seed".to_string())
+    }
+
+    #[test]
+    fn test_empty_db_returns_not_found_from_every_index_method() {
+        let qq_account = ZkAccount::from_seed(0, &seed(), 0)
+            .unwrap()
+            .get_qq_account()
+            .unwrap();
+        let error = StoredError::new("test", "boom", Utc::now());
+        let missing = 7u64;
+        let not_found = ZkAccountError::NotFound(missing);
+        let mut db = ZkAccountDB::new();
+
+        assert_eq!(db.get_account(&missing).unwrap_err(), not_found);
+        assert_eq!(db.get_account_address(&missing).unwrap_err(), not_found);
+        assert_eq!(db.get_address(&missing).unwrap_err(), not_found);
+        assert_eq!(db.get_balance(&missing).unwrap_err(), not_found);
+        assert_eq!(db.get_io_type(&missing).unwrap_err(), not_found);
+        assert_eq!(db.is_on_chain(&missing).unwrap_err(), not_found);
+        assert_eq!(db.resolve_account(&missing).unwrap_err(), not_found);
+        assert_eq!(db.get_mut_account(&missing).unwrap_err(), not_found);
+        assert_eq!(db.remove_account(&missing).unwrap_err(), not_found);
+        assert_eq!(db.remove_account_by_index(&missing), Err(not_found.clone()));
+        assert_eq!(db.update_balance(&missing, 1), Err(not_found.clone()));
+        assert_eq!(
+            db.update_io_type(&missing, IOType::Memo, Some(TXType::ORDERTX)),
+            Err(not_found.clone())
+        );
+        assert_eq!(db.update_scalar(&missing, "00"), Err(not_found.clone()));
+        assert_eq!(
+            db.update_account_key(&missing, "key"),
+            Err(not_found.clone())
+        );
+        assert_eq!(db.update_on_chain(&missing, true), Err(not_found.clone()));
+        assert_eq!(
+            db.update_qq_account(&missing, qq_account),
+            Err(not_found.clone())
+        );
+        assert_eq!(db.set_last_error(&missing, error), Err(not_found.clone()));
+        assert_eq!(
+            db.set_balance_unverified(&missing, true),
+            Err(not_found.clone())
+        );
+        assert_eq!(db.clear_last_error(&missing), Err(not_found.clone()));
+        assert_eq!(db.archive_account(&missing), Err(not_found.clone()));
+        assert_eq!(db.unarchive_account(&missing), Err(not_found.clone()));
+        assert!(!db.is_archived(&missing));
+        assert!(db.get_all_accounts().is_empty());
+        assert!(db.get_archived_accounts().is_empty());
+
+        // Nothing was created along the way.
+        assert!(db.accounts.is_empty());
+        assert!(db.archived.is_empty());
+        assert_eq!(db.index, 0);
+    }
+
+    #[test]
+    fn test_debug_shows_the_scalar_fingerprint_only() {
+        let account = ZkAccount::from_seed(3, &seed(), 500).unwrap();
+        let debug = format!("{:?}", account);
+        assert!(!debug.contains(&account.scalar));
+        assert!(debug.contains(&crate::wallet::key_fingerprint(account.scalar.as_bytes())));
+        assert!(debug.contains(&account.qq_address));
+    }
+
+    #[test]
+    fn test_accessors_read_the_stored_account() {
+        let mut db = ZkAccountDB::new();
+        let index = db.generate_new_account(2_500, &seed()).unwrap();
+        let address = db.get_account(&index).unwrap().account;
+
+        assert_eq!(db.get_balance(&index), Ok(2_500));
+        assert_eq!(db.get_io_type(&index), Ok(IOType::Coin));
+        assert_eq!(db.is_on_chain(&index), Ok(false));
+        assert_eq!(db.get_address(&index), Ok(address.clone()));
+        assert_eq!(db.get_account_address(&index), Ok(address));
+
+        db.update_on_chain(&index, true).unwrap();
+        db.update_io_type(&index, IOType::Memo, None).unwrap();
+        assert_eq!(db.is_on_chain(&index), Ok(true));
+        assert_eq!(db.get_io_type(&index), Ok(IOType::Memo));
+
+        assert_eq!(db.clear_last_error(&index), Ok(false));
+        db.set_last_error(&index, StoredError::new("op", "failed", Utc::now()))
+            .unwrap();
+        assert_eq!(db.clear_last_error(&index), Ok(true));
+    }
+
+    #[test]
+    fn test_archive_state_errors_are_typed() {
+        let mut db = ZkAccountDB::new();
+        let index = db.generate_new_account(0, &seed()).unwrap();
+
+        assert_eq!(
+            db.unarchive_account(&index),
+            Err(ZkAccountError::InvalidState(
+                index,
+                "not archived".to_string()
+            ))
+        );
+        db.archive_account(&index).unwrap();
+        assert_eq!(db.get_balance(&index), Err(ZkAccountError::NotFound(index)));
+        assert_eq!(
+            db.archive_account(&index),
+            Err(ZkAccountError::InvalidState(
+                index,
+                "already archived".to_string()
+            ))
+        );
+        assert_eq!(db.resolve_account(&index).unwrap().index, index);
+        db.unarchive_account(&index).unwrap();
+        assert_eq!(db.get_balance(&index), Ok(0));
+    }
+
+    #[test]
+    fn test_add_account_refuses_an_occupied_slot() {
+        let mut db = ZkAccountDB::new();
+        let account = ZkAccount::from_seed(0, &seed(), 0).unwrap();
+        assert_eq!(db.add_account(account.clone()), Ok(0));
+        // A DB whose counter lags behind its map must not overwrite an account.
+        db.index = 0;
+        assert_eq!(
+            db.add_account(account),
+            Err(ZkAccountError::AlreadyExists(0))
+        );
+        assert_eq!(db.index, 0);
+    }
+
+    #[test]
+    fn test_derived_address_is_reproducible_from_the_seed() {
+        let first = ZkAccount::from_seed(3, &seed(), 100).unwrap();
+        let again = ZkAccount::from_seed(3, &seed(), 0).unwrap();
+        assert_eq!(first.account, again.account);
+
+        let key_manager = KeyManager::from_cosmos_signature(seed().expose_secret().as_bytes());
+        assert_eq!(key_manager.derive_address(3), first.account);
+        assert_ne!(key_manager.derive_address(4), first.account);
+        // The child key still owns the derived address.
+        let qq_address = first.get_qq_address().unwrap();
+        assert!(qq_address.verify_keypair(&key_manager.derive_child_key(3)));
+    }
+
+    #[test]
+    fn test_restore_account_advances_the_next_index() {
+        let mut db = ZkAccountDB::new();
+        let recovered = ZkAccount::from_seed(5, &seed(), 0).unwrap();
+        db.restore_account(recovered.clone()).unwrap();
+        assert_eq!(db.index, 6);
+        assert_eq!(
+            db.restore_account(recovered),
+            Err(ZkAccountError::AlreadyExists(5))
+        );
+        // Restoring below the counter leaves it alone.
+        db.restore_account(ZkAccount::from_seed(2, &seed(), 0).unwrap())
+            .unwrap();
+        assert_eq!(db.index, 6);
+        assert_eq!(db.generate_new_account(0, &seed()), Ok(6));
+    }
+
+    #[test]
+    fn test_labels_are_unique_and_looked_up_by_name_or_prefix() {
+        let mut db = ZkAccountDB::new();
+        let bid = db.generate_new_account(0, &seed()).unwrap();
+        let ask = db.generate_new_account(0, &seed()).unwrap();
+        let hedge = db.generate_new_account(0, &seed()).unwrap();
+        db.set_label(&bid, Some("mm/bid".to_string())).unwrap();
+        db.set_label(&ask, Some("mm/ask".to_string())).unwrap();
+        db.set_label(&hedge, Some("hedge".to_string())).unwrap();
+
+        assert_eq!(db.get_by_label("hedge").map(|a| a.index), Some(hedge));
+        assert!(db.get_by_label("main").is_none());
+        let tagged: Vec<u64> = db
+            .accounts_with_tag("mm/")
+            .iter()
+            .map(|a| a.index)
+            .collect();
+        assert_eq!(tagged, vec![bid, ask]);
+
+        assert_eq!(
+            db.set_label(&ask, Some("hedge".to_string())),
+            Err(ZkAccountError::DuplicateLabel("hedge".to_string(), hedge))
+        );
+        assert_eq!(db.get_by_label("mm/ask").map(|a| a.index), Some(ask));
+        // Re-labelling an account with its own label is not a conflict.
+        db.set_label(&hedge, Some("hedge".to_string())).unwrap();
+        assert!(db.set_label(&hedge, Some(" ".to_string())).is_err());
+
+        // An archived account frees its label, and cannot come back while
+        // another account holds it.
+        db.archive_account(&hedge).unwrap();
+        assert!(db.get_by_label("hedge").is_none());
+        db.set_label(&ask, Some("hedge".to_string())).unwrap();
+        assert_eq!(
+            db.unarchive_account(&hedge),
+            Err(ZkAccountError::DuplicateLabel("hedge".to_string(), ask))
+        );
+        db.set_label(&ask, None).unwrap();
+        db.unarchive_account(&hedge).unwrap();
+        assert_eq!(db.get_by_label("hedge").map(|a| a.index), Some(hedge));
+    }
+
+    #[test]
+    fn test_errors_convert_to_the_legacy_message() {
+        let message: String = ZkAccountError::NotFound(3).into();
+        assert_eq!(message, "Account with index 3 does not exist");
+    }
 }